@@ -0,0 +1,510 @@
+//! Content-defined chunking (FastCDC) for sub-file deduplication
+//!
+//! A chunked blob is stored as leaf blobs (ordinary CAS blobs, one per
+//! chunk) plus a chunk list under `chunks/ab/cd/<hash>`, where `<hash>` is
+//! the BLAKE3 of the whole content — the same hash an unchunked blob of that
+//! content would have, so manifests don't care how a blob is stored. Two
+//! builds of a large artifact that differ in a few places then share every
+//! chunk outside those places.
+//!
+//! Cut points follow FastCDC (Xia et al., USENIX ATC '16): a gear rolling
+//! hash with normalized chunking, i.e. a stricter mask before the average
+//! size and a looser one after it, which keeps chunk sizes close to
+//! [`CHUNK_AVG`].
+//!
+//! ## Chunk list format
+//!
+//! ```text
+//! offset  field    size
+//! ------  -------  ----
+//!  0      magic    8    "VRCHUNK1"
+//!  8      size     8    whole-content size (LE)
+//! 16      count    4    number of chunks (LE)
+//! 20      _pad     4
+//! 24      chunks   40 × count: BLAKE3 (32) + length (8, LE)
+//! ```
+//!
+//! New leaves are written as `hash_len.bin`, so readers without a directory
+//! scan (the inception layer) can compute their paths.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use crate::{Blake3Hash, CasError, CasStore, Result, STREAM_TEMP_SEQ};
+
+/// Smallest chunk, except for the last one
+pub const CHUNK_MIN: usize = 16 * 1024;
+/// Target chunk size
+pub const CHUNK_AVG: usize = 64 * 1024;
+/// Largest chunk; a cut is forced here
+pub const CHUNK_MAX: usize = 256 * 1024;
+
+/// Chunk list magic and format version
+pub const CHUNK_LIST_MAGIC: [u8; 8] = *b"VRCHUNK1";
+/// Chunk list header size
+pub const CHUNK_LIST_HEADER: usize = 24;
+/// Size of one chunk list record
+pub const CHUNK_RECORD: usize = 40;
+
+/// Before `CHUNK_AVG`: 18 bits must be zero (cuts are rarer)
+const MASK_S: u64 = ((1u64 << 18) - 1) << (64 - 18);
+/// After `CHUNK_AVG`: 14 bits must be zero (cuts are likelier)
+const MASK_L: u64 = ((1u64 << 14) - 1) << (64 - 14);
+
+/// Gear table. Cut points (and so every stored chunk list) depend on it:
+/// it must never change.
+static GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64 with a fixed seed
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x7672_6966_745f_6364; // "vrift_cd"
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Length of the first chunk of `data`.
+///
+/// `data` is either at least `CHUNK_MAX` bytes or the rest of the content.
+pub fn cut_point(data: &[u8]) -> usize {
+    let len = data.len().min(CHUNK_MAX);
+    if len <= CHUNK_MIN {
+        return len;
+    }
+    let normal = len.min(CHUNK_AVG);
+    let mut fp: u64 = 0;
+    let mut i = CHUNK_MIN;
+    while i < normal {
+        fp = (fp << 1).wrapping_add(GEAR[data[i] as usize]);
+        if fp & MASK_S == 0 {
+            return i;
+        }
+        i += 1;
+    }
+    while i < len {
+        fp = (fp << 1).wrapping_add(GEAR[data[i] as usize]);
+        if fp & MASK_L == 0 {
+            return i;
+        }
+        i += 1;
+    }
+    len
+}
+
+/// Splits a reader into content-defined chunks
+pub struct Chunker<R> {
+    reader: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(CHUNK_MAX * 2),
+            eof: false,
+        }
+    }
+
+    /// Read until a full `CHUNK_MAX` window is buffered (or the input ends)
+    fn fill(&mut self) -> io::Result<()> {
+        while !self.eof && self.buf.len() < CHUNK_MAX {
+            let start = self.buf.len();
+            self.buf.resize(CHUNK_MAX * 2, 0);
+            match self.reader.read(&mut self.buf[start..]) {
+                Ok(0) => {
+                    self.buf.truncate(start);
+                    self.eof = true;
+                }
+                Ok(n) => self.buf.truncate(start + n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => self.buf.truncate(start),
+                Err(e) => {
+                    self.buf.truncate(start);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Chunker<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.fill() {
+            return Some(Err(e));
+        }
+        if self.buf.is_empty() {
+            return None;
+        }
+        let cut = cut_point(&self.buf);
+        let rest = self.buf.split_off(cut);
+        Some(Ok(std::mem::replace(&mut self.buf, rest)))
+    }
+}
+
+/// One leaf of a chunked blob
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef {
+    pub hash: Blake3Hash,
+    pub len: u64,
+}
+
+/// The leaves of a chunked blob, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkList {
+    /// Whole-content size
+    pub size: u64,
+    pub chunks: Vec<ChunkRef>,
+}
+
+impl ChunkList {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(CHUNK_LIST_HEADER + self.chunks.len() * CHUNK_RECORD);
+        out.extend_from_slice(&CHUNK_LIST_MAGIC);
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&(self.chunks.len() as u32).to_le_bytes());
+        out.extend_from_slice(&[0u8; 4]);
+        for chunk in &self.chunks {
+            out.extend_from_slice(&chunk.hash);
+            out.extend_from_slice(&chunk.len.to_le_bytes());
+        }
+        out
+    }
+
+    /// Parse a chunk list; `None` if it's malformed or its lengths don't add
+    /// up to its size
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < CHUNK_LIST_HEADER || data[..8] != CHUNK_LIST_MAGIC {
+            return None;
+        }
+        let size = u64::from_le_bytes(data[8..16].try_into().ok()?);
+        let count = u32::from_le_bytes(data[16..20].try_into().ok()?) as usize;
+        let records = &data[CHUNK_LIST_HEADER..];
+        if records.len() != count.checked_mul(CHUNK_RECORD)? {
+            return None;
+        }
+        let chunks: Vec<ChunkRef> = records
+            .chunks_exact(CHUNK_RECORD)
+            .map(|r| ChunkRef {
+                hash: r[..32].try_into().unwrap(),
+                len: u64::from_le_bytes(r[32..].try_into().unwrap()),
+            })
+            .collect();
+        let total = chunks
+            .iter()
+            .try_fold(0u64, |acc, c| acc.checked_add(c.len))?;
+        (total == size).then_some(Self { size, chunks })
+    }
+}
+
+impl CasStore {
+    /// Where the chunk list of `hash` lives: `chunks/ab/cd/<hash>`
+    pub fn chunk_list_path(&self, hash: &Blake3Hash) -> PathBuf {
+        let hex = Self::hash_to_hex(hash);
+        self.root
+            .join("chunks")
+            .join(&hex[..2])
+            .join(&hex[2..4])
+            .join(hex)
+    }
+
+    /// The chunk list of `hash`, if it is stored chunked
+    pub fn chunk_list(&self, hash: &Blake3Hash) -> Result<Option<ChunkList>> {
+        let data = match fs::read(self.chunk_list_path(hash)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        ChunkList::decode(&data).map(Some).ok_or_else(|| {
+            CasError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed chunk list for {}", Self::hash_to_hex(hash)),
+            ))
+        })
+    }
+
+    /// Whole-content hashes of every chunked blob
+    pub fn chunk_lists(&self) -> Result<Vec<Blake3Hash>> {
+        let dir = self.root.join("chunks");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut hashes = Vec::new();
+        for entry in walkdir::WalkDir::new(&dir).min_depth(3).max_depth(3) {
+            let entry = entry.map_err(io::Error::from)?;
+            if let Some(hash) = entry.file_name().to_str().and_then(Self::hex_to_hash) {
+                hashes.push(hash);
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Store everything `reader` yields as a chunked blob.
+    ///
+    /// Only the leaves and the chunk list are written; a whole blob of the
+    /// same content already in the store is left alone.
+    #[tracing::instrument(skip(self, reader), level = "debug")]
+    pub fn store_chunked<R: Read>(&self, reader: R) -> Result<(Blake3Hash, ChunkList)> {
+        let mut hasher = blake3::Hasher::new();
+        let mut list = ChunkList::default();
+        for chunk in Chunker::new(reader) {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            let hash = self.store_leaf(&chunk)?;
+            list.size += chunk.len() as u64;
+            list.chunks.push(ChunkRef {
+                hash,
+                len: chunk.len() as u64,
+            });
+        }
+        let hash = *hasher.finalize().as_bytes();
+        self.write_chunk_list(&hash, &list)?;
+        Ok((hash, list))
+    }
+
+    /// Re-store an existing whole blob as chunks, then drop the whole blob.
+    pub fn split_blob(&self, hash: &Blake3Hash) -> Result<ChunkList> {
+        if let Some(list) = self.chunk_list(hash)? {
+            if self.find_blob_path(hash).is_some() {
+                self.delete(hash)?;
+            }
+            return Ok(list);
+        }
+        let reader = self.get_reader(hash)?;
+        let (actual, list) = self.store_chunked(reader)?;
+        if actual != *hash {
+            let _ = fs::remove_file(self.chunk_list_path(&actual));
+            return Err(CasError::HashMismatch {
+                expected: Self::hash_to_hex(hash),
+                actual: Self::hash_to_hex(&actual),
+            });
+        }
+        self.delete(hash)?;
+        Ok(list)
+    }
+
+    /// Store one leaf as `hash_len.bin` (see the module docs)
+    fn store_leaf(&self, data: &[u8]) -> Result<Blake3Hash> {
+        let hash = Self::compute_hash(data);
+        if self.find_blob_path(&hash).is_some() {
+            return Ok(hash);
+        }
+        let path = self.blob_path_with_metadata(&hash, data.len() as u64, "bin");
+        self.write_atomic(&path, data)?;
+        Ok(hash)
+    }
+
+    fn write_chunk_list(&self, hash: &Blake3Hash, list: &ChunkList) -> Result<()> {
+        let path = self.chunk_list_path(hash);
+        if path.exists() {
+            return Ok(());
+        }
+        self.write_atomic(&path, &list.encode())
+    }
+
+    /// Temp file + rename, read-only afterwards (RFC-0039)
+    fn write_atomic(&self, path: &std::path::Path, data: &[u8]) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_file_name(format!(
+            ".{}.{}.{}.tmp",
+            path.file_name().unwrap().to_string_lossy(),
+            std::process::id(),
+            STREAM_TEMP_SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        let written = (|| -> io::Result<()> {
+            let mut file = File::create(&temp_path)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+            fs::rename(&temp_path, path)
+        })();
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            if !path.exists() {
+                return Err(e.into());
+            }
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o444));
+        }
+        Ok(())
+    }
+
+    /// Read `len` bytes at `offset` of a chunked blob
+    pub(crate) fn read_range_chunked(
+        &self,
+        list: &ChunkList,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(len.min(list.size.saturating_sub(offset) as usize));
+        let mut start = 0u64;
+        for chunk in &list.chunks {
+            let end = start + chunk.len;
+            let want = offset + out.len() as u64;
+            if out.len() == len {
+                break;
+            }
+            if want < end {
+                let data = self.read_range(&chunk.hash, want - start, len - out.len())?;
+                out.extend_from_slice(&data);
+            }
+            start = end;
+        }
+        Ok(out)
+    }
+
+    /// Sequential reader over a chunked blob's leaves
+    pub(crate) fn chunked_reader(&self, list: ChunkList) -> ChunkedReader {
+        ChunkedReader {
+            cas: self.clone(),
+            chunks: list.chunks.into_iter(),
+            current: None,
+        }
+    }
+}
+
+/// Concatenation of a chunked blob's leaves (unverified; see `BlobReader`)
+pub(crate) struct ChunkedReader {
+    cas: CasStore,
+    chunks: std::vec::IntoIter<ChunkRef>,
+    current: Option<File>,
+}
+
+impl Read for ChunkedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(file) = &mut self.current {
+                let n = file.read(buf)?;
+                if n > 0 || buf.is_empty() {
+                    return Ok(n);
+                }
+                self.current = None;
+            }
+            let Some(chunk) = self.chunks.next() else {
+                return Ok(0);
+            };
+            let path = self.cas.find_blob_path(&chunk.hash).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("missing chunk {}", CasStore::hash_to_hex(&chunk.hash)),
+                )
+            })?;
+            self.current = Some(File::open(path)?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Deterministic pseudo-random bytes
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_sizes_within_bounds() {
+        let data = noise(4 * 1024 * 1024, 1);
+        let chunks: Vec<Vec<u8>> = Chunker::new(&data[..]).map(|c| c.unwrap()).collect();
+        assert_eq!(chunks.concat(), data);
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(rest
+            .iter()
+            .all(|c| (CHUNK_MIN..=CHUNK_MAX).contains(&c.len())));
+        assert!(last.len() <= CHUNK_MAX);
+    }
+
+    #[test]
+    fn test_insertion_only_changes_nearby_chunks() {
+        let base = noise(2 * 1024 * 1024, 7);
+        let mut edited = base.clone();
+        edited.splice(1_000_000..1_000_000, *b"inserted");
+
+        let hashes = |data: &[u8]| -> std::collections::HashSet<Blake3Hash> {
+            Chunker::new(data)
+                .map(|c| CasStore::compute_hash(&c.unwrap()))
+                .collect()
+        };
+        let (a, b) = (hashes(&base), hashes(&edited));
+        let shared = a.intersection(&b).count();
+        assert!(shared + 3 >= a.len(), "{} of {} shared", shared, a.len());
+    }
+
+    #[test]
+    fn test_chunked_blob_reads_back() {
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+        let data = noise(1024 * 1024, 3);
+
+        let (hash, list) = cas.store_chunked(&data[..]).unwrap();
+        assert_eq!(hash, CasStore::compute_hash(&data));
+        assert!(list.chunks.len() > 1);
+        assert_eq!(cas.chunk_list(&hash).unwrap(), Some(list.clone()));
+        assert_eq!(ChunkList::decode(&list.encode()), Some(list));
+
+        // Readers reassemble transparently
+        assert!(cas.exists(&hash));
+        assert_eq!(cas.get(&hash).unwrap(), data);
+        let mut streamed = Vec::new();
+        cas.get_reader(&hash)
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, data);
+        assert_eq!(
+            cas.read_range(&hash, 100_000, 200_000).unwrap(),
+            &data[100_000..300_000]
+        );
+        cas.verify(&hash).unwrap();
+    }
+
+    #[test]
+    fn test_split_blob_and_sweep_keeps_leaves() {
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+        let data = noise(512 * 1024, 5);
+        let hash = cas.store(&data).unwrap();
+
+        let list = cas.split_blob(&hash).unwrap();
+        assert!(cas.find_blob_path(&hash).is_none());
+        assert_eq!(cas.get(&hash).unwrap(), data);
+
+        // Only the whole hash is live: its leaves must survive the sweep
+        let mut bloom = crate::BloomFilter::new(1024);
+        bloom.add(&CasStore::hash_to_hex(&hash));
+        let (deleted, _) = cas.sweep(&bloom.bits).unwrap();
+        assert_eq!(deleted, 0);
+        assert_eq!(cas.get(&hash).unwrap(), data);
+
+        // Nothing live: chunk list and leaves go
+        let (deleted, _) = cas.sweep(&vec![0u8; 1024]).unwrap();
+        assert_eq!(deleted as usize, list.chunks.len());
+        assert!(!cas.exists(&hash));
+    }
+}
@@ -0,0 +1,489 @@
+//! Transparent zstd compression of blobs
+//!
+//! A compressed blob lives under `zstd/ab/cd/<hash>` instead of
+//! `blake3/ab/cd/<hash>_<size>`, where `<hash>` is still the BLAKE3 of the
+//! uncompressed content, so manifests don't care how a blob is stored.
+//! Readers that find no whole blob fall back to it, as they do for chunked
+//! blobs; the inception layer inflates it into a temp file on open.
+//!
+//! ## Format
+//!
+//! ```text
+//! offset  field    size
+//! ------  -------  ----
+//!  0      magic    8    "VRZSTD01"
+//!  8      size     8    uncompressed size (LE)
+//! 16      frame    ..   one zstd frame
+//! ```
+//!
+//! Compression is opt-in ([`CasStore::with_compression`], or
+//! `vrift ingest --compress`, which re-stores blobs after ingest like
+//! chunking does). Small blobs, data that samples as high-entropy (already
+//! compressed media, archives) and anything that doesn't shrink by at least
+//! an eighth stay whole. Compressed blobs never leave the primary root.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::{last_used_age, Blake3Hash, CasError, CasStore, Result, STREAM_TEMP_SEQ};
+
+/// Compressed blob magic and format version
+pub const ZSTD_MAGIC: [u8; 8] = *b"VRZSTD01";
+/// Compressed blob header size
+pub const ZSTD_HEADER: usize = 16;
+/// Blobs smaller than this are never compressed
+pub const MIN_COMPRESS_SIZE: u64 = 4096;
+
+/// Readers' temp files under `cache/` older than this belong to a reader
+/// that died mid-expansion
+const STALE_TEMP_AGE: Duration = Duration::from_secs(3600);
+
+/// zstd level: fast enough for ingest, most of the ratio of higher levels
+const LEVEL: i32 = 3;
+/// Bytes looked at by the entropy check
+const SAMPLE: usize = 64 * 1024;
+/// Samples above this many bits per byte are taken as incompressible
+const MAX_ENTROPY: f64 = 7.5;
+
+/// Quick guess whether zstd will shrink `sample`: its order-0 byte entropy
+pub fn looks_compressible(sample: &[u8]) -> bool {
+    if sample.is_empty() {
+        return false;
+    }
+    let mut counts = [0u32; 256];
+    for &b in sample {
+        counts[b as usize] += 1;
+    }
+    let n = sample.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / n;
+            -p * p.log2()
+        })
+        .sum();
+    entropy <= MAX_ENTROPY
+}
+
+/// Whether a compressed size is worth keeping over `size` bytes whole
+fn worth_it(size: u64, compressed: u64) -> bool {
+    compressed + ZSTD_HEADER as u64 <= size - size / 8
+}
+
+impl CasStore {
+    /// Where the compressed form of `hash` lives: `zstd/ab/cd/<hash>`
+    pub fn compressed_path(&self, hash: &Blake3Hash) -> PathBuf {
+        let hex = Self::hash_to_hex(hash);
+        self.root
+            .join("zstd")
+            .join(&hex[..2])
+            .join(&hex[2..4])
+            .join(hex)
+    }
+
+    /// Reader over the uncompressed content of `hash` and its size, if it
+    /// is stored compressed (unverified; see `BlobReader`)
+    pub(crate) fn compressed_reader(
+        &self,
+        hash: &Blake3Hash,
+    ) -> Result<Option<(Box<dyn Read + Send>, u64)>> {
+        let mut file = match File::open(self.compressed_path(hash)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let size = read_header(&mut file).ok_or_else(|| {
+            CasError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed compressed blob {}", Self::hash_to_hex(hash)),
+            ))
+        })?;
+        let decoder = zstd::stream::read::Decoder::new(file)?;
+        Ok(Some((Box::new(decoder), size)))
+    }
+
+    /// Whole-content hashes of every compressed blob
+    pub fn compressed_blobs(&self) -> Result<Vec<Blake3Hash>> {
+        let dir = self.root.join("zstd");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut hashes = Vec::new();
+        for entry in walkdir::WalkDir::new(&dir).min_depth(3).max_depth(3) {
+            let entry = entry.map_err(io::Error::from)?;
+            if let Some(hash) = entry.file_name().to_str().and_then(Self::hex_to_hash) {
+                hashes.push(hash);
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Where readers cache the expanded content of a chunked or compressed
+    /// blob: `cache/ab/cd/<hash>`. A copy is plain content and may be
+    /// removed at any time; the next reader expands the blob again.
+    pub fn expanded_path(&self, hash: &Blake3Hash) -> PathBuf {
+        let hex = Self::hash_to_hex(hash);
+        self.root
+            .join("cache")
+            .join(&hex[..2])
+            .join(&hex[2..4])
+            .join(hex)
+    }
+
+    /// Hashes of every cached expanded copy
+    pub fn expanded_copies(&self) -> Result<Vec<Blake3Hash>> {
+        let dir = self.root.join("cache");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut hashes = Vec::new();
+        for entry in walkdir::WalkDir::new(&dir).min_depth(3).max_depth(3) {
+            let entry = entry.map_err(io::Error::from)?;
+            if let Some(hash) = entry.file_name().to_str().and_then(Self::hex_to_hash) {
+                hashes.push(hash);
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Evict expanded copies, least recently used first (the later of atime
+    /// and mtime), until the rest take at most `max_bytes`. Temp files of
+    /// readers that died mid-expansion go too, once an hour old. Returns
+    /// the copies evicted and the bytes reclaimed.
+    pub fn trim_expanded(&self, max_bytes: u64) -> Result<(u32, u64)> {
+        let dir = self.root.join("cache");
+        if !dir.exists() {
+            return Ok((0, 0));
+        }
+        let now = SystemTime::now();
+        let mut reclaimed = 0;
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".reassemble.")
+            {
+                continue;
+            }
+            if let Ok(meta) = entry.metadata() {
+                if last_used_age(&meta, now) > STALE_TEMP_AGE
+                    && fs::remove_file(entry.path()).is_ok()
+                {
+                    reclaimed += meta.len();
+                }
+            }
+        }
+
+        let mut copies = Vec::new();
+        let mut total = 0u64;
+        for hash in self.expanded_copies()? {
+            let path = self.expanded_path(&hash);
+            if let Ok(meta) = fs::metadata(&path) {
+                total += meta.len();
+                copies.push((last_used_age(&meta, now), meta.len(), path));
+            }
+        }
+        // Longest unused first
+        copies.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+        let mut evicted = 0;
+        for (_, size, path) in copies {
+            if total <= max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= size;
+                evicted += 1;
+                reclaimed += size;
+            }
+        }
+        Ok((evicted, reclaimed))
+    }
+
+    /// Store `data` of hash `hash` compressed, if that pays off. Returns
+    /// whether it was stored.
+    pub(crate) fn store_compressed(&self, hash: &Blake3Hash, data: &[u8]) -> Result<bool> {
+        let size = data.len() as u64;
+        if size < MIN_COMPRESS_SIZE || !looks_compressible(&data[..data.len().min(SAMPLE)]) {
+            return Ok(false);
+        }
+        let frame = zstd::bulk::compress(data, LEVEL)?;
+        if !worth_it(size, frame.len() as u64) {
+            return Ok(false);
+        }
+        self.write_compressed(hash, size, |out| out.write_all(&frame))?;
+        Ok(true)
+    }
+
+    /// Re-store an existing whole blob compressed, then drop the whole blob.
+    /// Returns false (and keeps the whole blob) if compression doesn't pay.
+    pub fn compress_blob(&self, hash: &Blake3Hash) -> Result<bool> {
+        let whole = self.find_blob_path(hash);
+        if self.compressed_path(hash).exists() {
+            if whole.is_some() {
+                self.delete(hash)?;
+            }
+            return Ok(true);
+        }
+        let path = whole.ok_or_else(|| CasError::NotFound {
+            hash: Self::hash_to_hex(hash),
+        })?;
+        let mut file = File::open(&path)?;
+        let size = file.metadata()?.len();
+        if size < MIN_COMPRESS_SIZE {
+            return Ok(false);
+        }
+        let mut sample = vec![0u8; SAMPLE.min(size as usize)];
+        file.read_exact(&mut sample)?;
+        if !looks_compressible(&sample) {
+            return Ok(false);
+        }
+
+        let mut reader = io::Read::chain(io::Cursor::new(sample), file);
+        let mut hasher = blake3::Hasher::new();
+        let mut compressed = 0u64;
+        let kept = self.write_compressed(hash, size, |out| {
+            let mut encoder =
+                zstd::stream::write::Encoder::new(Counted(out, &mut compressed), LEVEL)?;
+            let mut buf = vec![0u8; 256 * 1024];
+            loop {
+                let n = reader.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+                encoder.write_all(&buf[..n])?;
+            }
+            encoder.finish()?;
+            if *hasher.finalize().as_bytes() != *hash {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "blob does not match its hash",
+                ));
+            }
+            if !worth_it(size, compressed) {
+                return Err(io::Error::other(NotWorthIt));
+            }
+            Ok(())
+        });
+        match kept {
+            Ok(()) => {
+                self.delete(hash)?;
+                Ok(true)
+            }
+            Err(CasError::Io(e)) if e.get_ref().is_some_and(|e| e.is::<NotWorthIt>()) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write header + `frame` to the compressed path via a temp file,
+    /// read-only afterwards
+    fn write_compressed<F>(&self, hash: &Blake3Hash, size: u64, frame: F) -> Result<()>
+    where
+        F: FnOnce(&mut File) -> io::Result<()>,
+    {
+        let path = self.compressed_path(hash);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_file_name(format!(
+            ".{}.{}.{}.tmp",
+            path.file_name().unwrap().to_string_lossy(),
+            std::process::id(),
+            STREAM_TEMP_SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        let written = (|| -> io::Result<()> {
+            let mut file = File::create(&temp_path)?;
+            file.write_all(&ZSTD_MAGIC)?;
+            file.write_all(&size.to_le_bytes())?;
+            frame(&mut file)?;
+            file.sync_all()?;
+            fs::rename(&temp_path, &path)
+        })();
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o444));
+        }
+        Ok(())
+    }
+}
+
+/// Uncompressed size from a compressed blob's header
+pub(crate) fn read_header(file: &mut File) -> Option<u64> {
+    let mut header = [0u8; ZSTD_HEADER];
+    file.read_exact(&mut header).ok()?;
+    (header[..8] == ZSTD_MAGIC).then(|| u64::from_le_bytes(header[8..].try_into().unwrap()))
+}
+
+/// Marker error: the compressed form is too large to keep
+#[derive(Debug)]
+struct NotWorthIt;
+
+impl std::fmt::Display for NotWorthIt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("compression does not pay off")
+    }
+}
+
+impl std::error::Error for NotWorthIt {}
+
+/// Writer that counts the bytes passed through
+struct Counted<'a, W>(W, &'a mut u64);
+
+impl<W: Write> Write for Counted<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.0.write(buf)?;
+        *self.1 += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn text(len: usize) -> Vec<u8> {
+        b"fn main() { println!(\"hello\"); }\n"
+            .iter()
+            .copied()
+            .cycle()
+            .take(len)
+            .collect()
+    }
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_entropy_check() {
+        assert!(looks_compressible(&text(SAMPLE)));
+        assert!(!looks_compressible(&noise(SAMPLE)));
+        assert!(!looks_compressible(&[]));
+    }
+
+    #[test]
+    fn test_compressed_store_reads_back() {
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap().with_compression(true);
+        let data = text(100_000);
+
+        let hash = cas.store(&data).unwrap();
+        assert!(cas.blob_path_for_hash(&hash).is_none());
+        assert!(cas.compressed_path(&hash).exists());
+        assert!(cas.exists(&hash));
+        assert_eq!(cas.get(&hash).unwrap(), data);
+        assert_eq!(cas.read_range(&hash, 99_990, 100).unwrap(), &data[99_990..]);
+        cas.verify(&hash).unwrap();
+
+        let mut streamed = Vec::new();
+        cas.get_reader(&hash)
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, data);
+
+        // Incompressible and tiny data stay whole
+        let random = cas.store(&noise(100_000)).unwrap();
+        assert!(cas.blob_path_for_hash(&random).is_some());
+        let small = cas.store(b"tiny").unwrap();
+        assert!(cas.blob_path_for_hash(&small).is_some());
+
+        let stats = cas.stats().unwrap();
+        assert_eq!(stats.blob_count, 3);
+        assert_eq!(stats.compressed_blobs, 1);
+        assert_eq!(stats.logical_bytes, 200_004);
+        assert!(stats.total_bytes < stats.logical_bytes);
+    }
+
+    #[test]
+    fn test_compress_blob_replaces_whole_copy() {
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+        let data = text(50_000);
+        let hash = cas.store(&data).unwrap();
+        let random = cas.store(&noise(50_000)).unwrap();
+
+        assert!(cas.compress_blob(&hash).unwrap());
+        assert!(cas.blob_path_for_hash(&hash).is_none());
+        assert_eq!(cas.get(&hash).unwrap(), data);
+
+        assert!(!cas.compress_blob(&random).unwrap());
+        assert!(cas.blob_path_for_hash(&random).is_some());
+        assert!(!cas.compressed_path(&random).exists());
+
+        // A reader's expanded copy goes with its blob
+        let expanded = cas.expanded_path(&hash);
+        fs::create_dir_all(expanded.parent().unwrap()).unwrap();
+        fs::write(&expanded, &data).unwrap();
+        assert_eq!(cas.expanded_copies().unwrap(), vec![hash]);
+
+        // Unreferenced compressed blobs are swept like whole ones
+        let bloom = crate::BloomFilter::new(1024);
+        let (deleted, _) = cas.sweep(&bloom.bits).unwrap();
+        assert_eq!(deleted, 2);
+        assert!(!cas.exists(&hash));
+        assert!(!expanded.exists());
+    }
+
+    #[test]
+    fn test_trim_expanded_evicts_least_recently_used() {
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+        let now = SystemTime::now();
+        let copy = |seed: u8, idle_secs: u64| {
+            let hash = [seed; 32];
+            let path = cas.expanded_path(&hash);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, vec![seed; 1000]).unwrap();
+            let used = now - Duration::from_secs(idle_secs);
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_times(fs::FileTimes::new().set_accessed(used).set_modified(used))
+                .unwrap();
+            path
+        };
+        let (hot, warm, cold) = (copy(1, 10), copy(2, 100), copy(3, 1000));
+        let stale = temp.path().join("cache").join(".reassemble.1.0");
+        fs::write(&stale, b"partial").unwrap();
+        File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_times(
+                fs::FileTimes::new()
+                    .set_accessed(now - 2 * STALE_TEMP_AGE)
+                    .set_modified(now - 2 * STALE_TEMP_AGE),
+            )
+            .unwrap();
+
+        assert_eq!(cas.trim_expanded(3000).unwrap(), (0, 7));
+        assert!(!stale.exists());
+        assert_eq!(cas.trim_expanded(1500).unwrap(), (2, 2000));
+        assert!(hot.exists() && !warm.exists() && !cold.exists());
+        assert_eq!(cas.trim_expanded(0).unwrap(), (1, 1000));
+    }
+}
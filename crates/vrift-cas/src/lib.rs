@@ -22,15 +22,22 @@
 //! - macOS: GCD-style dispatch
 //! - Fallback: Rayon thread pool
 
+pub mod chunking;
+pub mod compression;
 mod io_backend;
 pub mod link_strategy;
 pub mod parallel_ingest;
+pub mod pins;
 pub mod protection;
 pub mod reflink;
+pub mod scrub;
 pub mod streaming_ingest;
 pub mod streaming_pipeline;
+pub mod tiers;
 pub mod zero_copy_ingest;
 
+pub use chunking::{ChunkList, ChunkRef, Chunker};
+pub use compression::looks_compressible;
 pub use io_backend::{create_backend, rayon_backend, IngestBackend};
 #[cfg(target_os = "macos")]
 pub use link_strategy::is_binary_sensitive;
@@ -40,13 +47,17 @@ pub use parallel_ingest::{
     parallel_ingest_with_progress, parallel_ingest_with_threads, IngestMode, ParallelIngestStats,
     MAX_INGEST_THREADS,
 };
+pub use pins::PinStore;
 pub use protection::{
     enforce_cas_invariant, is_immutable, set_immutable, CAS_FORBIDDEN_PERM_MASK, CAS_READ_ONLY_PERM,
 };
+pub use scrub::{ScrubFinding, ScrubOptions, ScrubProblem, ScrubReport};
 pub use streaming_ingest::{
-    streaming_ingest, streaming_ingest_cached, streaming_ingest_with_progress,
+    ingest_symlinks, streaming_ingest, streaming_ingest_cached, streaming_ingest_with_progress,
+    IgnoreFn, IngestProgress, SymlinkResult,
 };
 pub use streaming_pipeline::{IngestPipeline, IngestStats, PipelineConfig};
+pub use tiers::{CasTier, TierSet};
 pub use zero_copy_ingest::{
     ingest_phantom, ingest_solid_tier1, ingest_solid_tier1_dedup, ingest_solid_tier2,
     ingest_solid_tier2_cached, ingest_solid_tier2_dedup, mtime_nsec_from_metadata, CacheHint,
@@ -57,6 +68,7 @@ use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use tracing::instrument;
 
@@ -80,22 +92,56 @@ pub enum CasError {
 
 pub type Result<T> = std::result::Result<T, CasError>;
 
+/// Buffer size for streaming store/get (heap-allocated once per call)
+const STREAM_CHUNK: usize = 1 << 20;
+
+/// Distinguishes concurrent streaming stores from one thread's temp files
+static STREAM_TEMP_SEQ: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
 /// Content-Addressable Storage store
 ///
 /// Stores blobs indexed by their BLAKE3 hash with a 2-char prefix fan-out.
+/// Blobs may also live in the storage tiers configured for the root
+/// (see [`tiers`]); lookups search every root.
 #[derive(Debug, Clone)]
 pub struct CasStore {
     root: PathBuf,
+    tiers: Vec<CasTier>,
+    /// Store new blobs zstd-compressed when that pays off
+    compress: bool,
 }
 
 impl CasStore {
     /// Create a new CAS store at the given root directory.
     ///
-    /// The directory will be created if it doesn't exist.
+    /// The directory will be created if it doesn't exist. Tiers listed in
+    /// its `tiers.json` are attached.
     pub fn new<P: AsRef<Path>>(root: P) -> Result<Self> {
         let root = root.as_ref().to_path_buf();
         fs::create_dir_all(&root)?;
-        Ok(Self { root })
+        let tiers = TierSet::load(&root)?.tiers().to_vec();
+        Ok(Self {
+            root,
+            tiers,
+            compress: false,
+        })
+    }
+
+    /// Create a CAS store with an explicit tier list, ignoring `tiers.json`
+    pub fn with_tiers<P: AsRef<Path>>(root: P, tiers: Vec<CasTier>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        Ok(Self {
+            root,
+            tiers,
+            compress: false,
+        })
+    }
+
+    /// Compress blobs written by [`store`](Self::store) (see [`compression`])
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
     }
 
     /// Create a CAS store at the default location (`~/.vrift/the_source/`).
@@ -135,36 +181,41 @@ impl CasStore {
     ///
     /// Uses RFC-0039 §6 layout: `blake3/ab/cd/`
     /// Returns the directory where the blob would be stored (not the full file path).
-    fn blob_dir(&self, hash: &Blake3Hash) -> PathBuf {
+    fn blob_dir(root: &Path, hash: &Blake3Hash) -> PathBuf {
         let hex = Self::hash_to_hex(hash);
         let l1 = &hex[..2]; // First 2 chars
         let l2 = &hex[2..4]; // Next 2 chars
-        self.root.join("blake3").join(l1).join(l2)
+        root.join("blake3").join(l1).join(l2)
     }
 
     /// Find the actual blob file path using RFC-0039 format.
     ///
+    /// Searches the primary root, then each tier in order.
     /// Returns the path if found, None otherwise.
     /// Only supports new format: `hash_size.ext` (with size and optional extension)
     fn find_blob_path(&self, hash: &Blake3Hash) -> Option<PathBuf> {
-        let dir = self.blob_dir(hash);
-        if !dir.exists() {
-            return None;
-        }
-
-        let hex = Self::hash_to_hex(hash);
-        if let Ok(entries) = fs::read_dir(&dir) {
-            for entry in entries.flatten() {
-                let filename = entry.file_name();
-                let filename_str = filename.to_string_lossy();
+        let prefix = format!("{}_", Self::hash_to_hex(hash));
+        self.roots().find_map(|root| {
+            let entries = fs::read_dir(Self::blob_dir(root, hash)).ok()?;
+            entries
+                .flatten()
                 // Match pattern: <hash>_* (RFC-0039 format only)
-                if filename_str.starts_with(&format!("{}_", hex)) {
-                    return Some(entry.path());
-                }
-            }
-        }
+                .find(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+                .map(|entry| entry.path())
+        })
+    }
+
+    /// The primary root followed by every tier root
+    fn roots(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.root.as_path()).chain(self.tiers.iter().map(|t| t.root.as_path()))
+    }
 
-        None
+    /// Root a blob of `size` bytes, last used `age` ago, belongs in
+    fn placement(&self, size: u64, age: Duration) -> &Path {
+        self.tiers
+            .iter()
+            .find(|t| t.accepts(size, age))
+            .map_or(&self.root, |t| &t.root)
     }
 
     /// Get the path for a self-describing blob (RFC-0039 format).
@@ -173,6 +224,11 @@ impl CasStore {
     /// - O(1) integrity check via filename size
     /// - Extension enables direct file type inspection
     pub fn blob_path_with_metadata(&self, hash: &Blake3Hash, size: u64, ext: &str) -> PathBuf {
+        Self::blob_path_in(&self.root, hash, size, ext)
+    }
+
+    /// [`blob_path_with_metadata`](Self::blob_path_with_metadata) under `root`
+    fn blob_path_in(root: &Path, hash: &Blake3Hash, size: u64, ext: &str) -> PathBuf {
         let hex = Self::hash_to_hex(hash);
         let l1 = &hex[..2];
         let l2 = &hex[2..4];
@@ -181,7 +237,7 @@ impl CasStore {
         } else {
             format!("{}_{}.{}", hex, size, ext)
         };
-        self.root.join("blake3").join(l1).join(l2).join(filename)
+        root.join("blake3").join(l1).join(l2).join(filename)
     }
 
     /// Store bytes in the CAS, returning the content hash.
@@ -194,13 +250,16 @@ impl CasStore {
         let hash = Self::compute_hash(data);
         let size = data.len() as u64;
 
-        // Deduplication: skip if already exists (check via find_blob_path)
-        if self.find_blob_path(&hash).is_some() {
+        // Deduplication: skip if already exists, whole or compressed
+        if self.find_blob_path(&hash).is_some() || self.compressed_path(&hash).exists() {
+            return Ok(hash);
+        }
+        if self.compress && self.store_compressed(&hash, data)? {
             return Ok(hash);
         }
 
         // RFC-0039 format: hash_size (no extension for raw bytes)
-        let path = self.blob_path_with_metadata(&hash, size, "");
+        let path = Self::blob_path_in(self.placement(size, Duration::ZERO), &hash, size, "");
 
         // Create prefix directory
         if let Some(parent) = path.parent() {
@@ -241,6 +300,76 @@ impl CasStore {
         Ok(hash)
     }
 
+    /// Store everything `reader` yields, returning the content hash.
+    ///
+    /// Like [`store`](Self::store), but hashes and writes in fixed-size
+    /// chunks, so memory use doesn't grow with the blob. The data goes to a
+    /// temp file under the CAS root first (the final name needs the hash) and
+    /// is renamed into place once complete (copied, if its tier is on
+    /// another filesystem).
+    #[instrument(skip(self, reader), level = "debug")]
+    pub fn store_reader<R: Read>(&self, mut reader: R) -> Result<Blake3Hash> {
+        let temp_path = self.root.join(format!(
+            ".store.{}.{:?}.{}.tmp",
+            std::process::id(),
+            std::thread::current().id(),
+            STREAM_TEMP_SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+
+        let written = (|| -> io::Result<(Blake3Hash, u64)> {
+            let mut file = File::create(&temp_path)?;
+            let mut hasher = blake3::Hasher::new();
+            let mut buf = vec![0u8; STREAM_CHUNK];
+            let mut size = 0u64;
+            loop {
+                let n = match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                hasher.update(&buf[..n]);
+                file.write_all(&buf[..n])?;
+                size += n as u64;
+            }
+            file.sync_all()?;
+            Ok((*hasher.finalize().as_bytes(), size))
+        })();
+        let (hash, size) = match written {
+            Ok(v) => v,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e.into());
+            }
+        };
+
+        if self.find_blob_path(&hash).is_some() {
+            let _ = fs::remove_file(&temp_path);
+            return Ok(hash);
+        }
+
+        let path = Self::blob_path_in(self.placement(size, Duration::ZERO), &hash, size, "");
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Err(e) = move_blob_file(&temp_path, &path, &hash) {
+            let _ = fs::remove_file(&temp_path);
+            if self.find_blob_path(&hash).is_some() {
+                return Ok(hash);
+            }
+            return Err(CasError::Io(e));
+        }
+
+        // RFC-0039: Ensure CAS blobs are read-only by default (0o444)
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o444));
+        }
+
+        Ok(hash)
+    }
+
     /// Compute the BLAKE3 hash of the given reader.
     pub fn compute_hash_reader<R: io::Read>(mut reader: R) -> io::Result<Blake3Hash> {
         let mut hasher = blake3::Hasher::new();
@@ -260,7 +389,8 @@ impl CasStore {
     /// This is a zero-copy operation if the source and CAS are on the same filesystem.
     /// If the content already exists, the source file is deleted (deduplication).
     /// This is the preferred method for reingesting CoW temp files.
-    /// Uses RFC-0039 format: `blake3/ab/cd/hash_size`
+    /// Uses RFC-0039 format: `blake3/ab/cd/hash_size.bin`, the name ingest
+    /// gives blobs and the shim opens
     #[instrument(skip(self, src_path), level = "info")]
     pub fn store_by_move<P: AsRef<Path>>(&self, src_path: P) -> Result<Blake3Hash> {
         let src = src_path.as_ref();
@@ -274,8 +404,7 @@ impl CasStore {
             return Ok(hash);
         }
 
-        // RFC-0039 format: hash_size (no extension)
-        let path = self.blob_path_with_metadata(&hash, size, "");
+        let path = Self::blob_path_in(self.placement(size, Duration::ZERO), &hash, size, "bin");
 
         // Create prefix directory
         if let Some(parent) = path.parent() {
@@ -287,10 +416,16 @@ impl CasStore {
             // Check for cross-device link error (EXDEV)
             if e.raw_os_error() == Some(libc::EXDEV) {
                 tracing::debug!("CAS: Cross-device move detected, falling back to copy");
-                let mut src_file = File::open(src)?;
-                let mut dst_file = File::create(&path)?;
-                io::copy(&mut src_file, &mut dst_file)?;
+                let copied = self.store_reader(File::open(src)?)?;
+                if copied != hash {
+                    // The source changed between hashing and copying
+                    return Err(CasError::HashMismatch {
+                        expected: Self::hash_to_hex(&hash),
+                        actual: Self::hash_to_hex(&copied),
+                    });
+                }
                 let _ = fs::remove_file(src);
+                return Ok(hash);
             } else {
                 return Err(CasError::Io(e));
             }
@@ -311,25 +446,31 @@ impl CasStore {
     /// NOTE: For high-performance zero-copy ingest, use `ingest_solid_tier2`
     /// from `zero_copy_ingest` module instead. This method is a simple fallback.
     pub fn store_file<P: AsRef<Path>>(&self, path: P) -> Result<Blake3Hash> {
-        let data = fs::read(path)?;
-        self.store(&data)
+        self.store_reader(File::open(path)?)
     }
 
     /// Retrieve bytes from the CAS by hash.
     #[instrument(skip(self), level = "debug")]
     pub fn get(&self, hash: &Blake3Hash) -> Result<Vec<u8>> {
-        let path = match self.find_blob_path(hash) {
-            Some(p) => p,
-            None => {
-                return Err(CasError::NotFound {
-                    hash: Self::hash_to_hex(hash),
-                })
-            }
-        };
-
-        let mut file = File::open(&path)?;
         let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+        match self.find_blob_path(hash) {
+            Some(path) => {
+                File::open(&path)?.read_to_end(&mut data)?;
+            }
+            None => match self.chunk_list(hash)? {
+                Some(list) => {
+                    self.chunked_reader(list).read_to_end(&mut data)?;
+                }
+                None => {
+                    let (mut reader, _) =
+                        self.compressed_reader(hash)?
+                            .ok_or_else(|| CasError::NotFound {
+                                hash: Self::hash_to_hex(hash),
+                            })?;
+                    reader.read_to_end(&mut data)?;
+                }
+            },
+        }
 
         // Verify hash on read (integrity check)
         let actual_hash = Self::compute_hash(&data);
@@ -343,16 +484,125 @@ impl CasStore {
         Ok(data)
     }
 
-    /// Check if a blob exists in the CAS.
+    /// Open a blob for streaming reads.
+    ///
+    /// The content hash is checked incrementally and a mismatch surfaces as an
+    /// `InvalidData` error from the read that reaches end of blob, so callers
+    /// must read to EOF before trusting (e.g. renaming into place) the data.
+    #[instrument(skip(self), level = "debug")]
+    pub fn get_reader(&self, hash: &Blake3Hash) -> Result<BlobReader> {
+        let (inner, len): (Box<dyn Read + Send>, u64) = match self.find_blob_path(hash) {
+            Some(path) => {
+                let file = File::open(&path)?;
+                let len = file.metadata()?.len();
+                (Box::new(file), len)
+            }
+            None => match self.chunk_list(hash)? {
+                Some(list) => {
+                    let len = list.size;
+                    (Box::new(self.chunked_reader(list)), len)
+                }
+                None => self
+                    .compressed_reader(hash)?
+                    .ok_or_else(|| CasError::NotFound {
+                        hash: Self::hash_to_hex(hash),
+                    })?,
+            },
+        };
+        Ok(BlobReader {
+            inner: io::BufReader::with_capacity(STREAM_CHUNK, inner),
+            hasher: blake3::Hasher::new(),
+            expected: *hash,
+            len,
+            checked: false,
+        })
+    }
+
+    /// Read `len` bytes at `offset` of a blob without loading the whole file.
+    ///
+    /// Returns fewer bytes at end of blob. Unlike `get()` this does not check
+    /// the content hash (a range can't be verified against a whole-blob hash);
+    /// pair it with `verify()` once per blob.
+    #[instrument(skip(self), level = "trace")]
+    pub fn read_range(&self, hash: &Blake3Hash, offset: u64, len: usize) -> Result<Vec<u8>> {
+        use std::os::unix::fs::FileExt;
+
+        let Some(path) = self.find_blob_path(hash) else {
+            return match self.chunk_list(hash)? {
+                Some(list) => self.read_range_chunked(&list, offset, len),
+                None => match self.compressed_reader(hash)? {
+                    // A zstd frame has no random access: decode up to the range
+                    Some((mut reader, _)) => {
+                        io::copy(&mut (&mut reader).take(offset), &mut io::sink())?;
+                        let mut buf = Vec::with_capacity(len);
+                        reader.take(len as u64).read_to_end(&mut buf)?;
+                        Ok(buf)
+                    }
+                    None => Err(CasError::NotFound {
+                        hash: Self::hash_to_hex(hash),
+                    }),
+                },
+            };
+        };
+        let file = File::open(&path)?;
+        let mut buf = vec![0u8; len];
+        let mut filled = 0;
+        while filled < len {
+            match file.read_at(&mut buf[filled..], offset + filled as u64) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        buf.truncate(filled);
+        Ok(buf)
+    }
+
+    /// Stream a blob through BLAKE3 and check it matches `hash`.
+    #[instrument(skip(self), level = "debug")]
+    pub fn verify(&self, hash: &Blake3Hash) -> Result<()> {
+        let actual = match self.find_blob_path(hash) {
+            Some(path) => Self::compute_hash_reader(File::open(&path)?)?,
+            None => match self.chunk_list(hash)? {
+                Some(list) => Self::compute_hash_reader(self.chunked_reader(list))?,
+                None => {
+                    let (reader, _) =
+                        self.compressed_reader(hash)?
+                            .ok_or_else(|| CasError::NotFound {
+                                hash: Self::hash_to_hex(hash),
+                            })?;
+                    Self::compute_hash_reader(reader)?
+                }
+            },
+        };
+        if actual != *hash {
+            return Err(CasError::HashMismatch {
+                expected: Self::hash_to_hex(hash),
+                actual: Self::hash_to_hex(&actual),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check if a blob exists in the CAS, whole, chunked or compressed.
     pub fn exists(&self, hash: &Blake3Hash) -> bool {
         self.find_blob_path(hash).is_some()
+            || self.chunk_list_path(hash).exists()
+            || self.compressed_path(hash).exists()
     }
 
     /// Delete a blob from the CAS.
     ///
-    /// Handles both old format (hash) and new format (hash_size.ext).
+    /// Removes the whole copy if there is one, else the compressed one,
+    /// and any expanded copy readers cached.
     pub fn delete(&self, hash: &Blake3Hash) -> Result<()> {
-        match self.find_blob_path(hash) {
+        let _ = fs::remove_file(self.expanded_path(hash));
+        let compressed = self.compressed_path(hash);
+        match self
+            .find_blob_path(hash)
+            .or_else(|| compressed.exists().then_some(compressed))
+        {
             Some(path) => {
                 // RFC-0039: Best effort to unset immutable flag before deletion
                 // This allows GC to clean up protected blobs.
@@ -372,71 +622,41 @@ impl CasStore {
         &self.root
     }
 
-    /// Get statistics about the CAS.
+    /// Get statistics about the CAS, across the primary root and all tiers.
     ///
-    /// Traverses the 3-level structure: blake3/ab/cd/hash
+    /// Traverses the 3-level structure: blake3/ab/cd/hash, then the
+    /// compressed blobs under zstd/.
     pub fn stats(&self) -> Result<CasStats> {
-        let mut blob_count = 0u64;
-        let mut total_bytes = 0u64;
-        let mut size_histogram: std::collections::HashMap<&str, u64> =
-            std::collections::HashMap::new();
-
-        // Level 0: blake3/ directory
-        let blake3_dir = self.root.join("blake3");
-        if !blake3_dir.exists() {
-            return Ok(CasStats::default());
-        }
-
-        // Level 1: ab/ directories
-        for l1_entry in fs::read_dir(&blake3_dir)? {
-            let l1_entry = l1_entry?;
-            if !l1_entry.file_type()?.is_dir() {
+        let mut stats = Self::collect_stats(BlobFiles::new(
+            self.roots().map(Path::to_path_buf).collect(),
+        ))?;
+        for hash in self.compressed_blobs()? {
+            let path = self.compressed_path(&hash);
+            let Ok(mut file) = File::open(&path) else {
                 continue;
-            }
+            };
+            let stored = file.metadata()?.len();
+            let Some(size) = compression::read_header(&mut file) else {
+                continue;
+            };
+            stats.add(size, stored);
+            stats.compressed_blobs += 1;
+        }
+        Ok(stats)
+    }
 
-            // Level 2: cd/ directories
-            for l2_entry in fs::read_dir(l1_entry.path())? {
-                let l2_entry = l2_entry?;
-                if !l2_entry.file_type()?.is_dir() {
-                    continue;
-                }
+    /// Statistics of the blobs under one CAS root, ignoring its tiers
+    pub fn root_stats(root: &Path) -> Result<CasStats> {
+        Self::collect_stats(BlobFiles::new(vec![root.to_path_buf()]))
+    }
 
-                // Level 3: hash files
-                for blob in fs::read_dir(l2_entry.path())? {
-                    let blob = blob?;
-                    if blob.file_type()?.is_file() {
-                        // Skip temp files
-                        if blob.path().extension().is_some_and(|ext| ext == "tmp") {
-                            continue;
-                        }
-                        let size = blob.metadata()?.len();
-                        blob_count += 1;
-                        total_bytes += size;
-
-                        // Categorize by size
-                        let category = if size < 1024 {
-                            "<1KB"
-                        } else if size < 1024 * 1024 {
-                            "1KB-1MB"
-                        } else if size < 100 * 1024 * 1024 {
-                            "1MB-100MB"
-                        } else {
-                            ">100MB"
-                        };
-                        *size_histogram.entry(category).or_insert(0) += 1;
-                    }
-                }
-            }
+    fn collect_stats(files: BlobFiles) -> Result<CasStats> {
+        let mut stats = CasStats::default();
+        for blob in files {
+            let size = blob?.metadata()?.len();
+            stats.add(size, size);
         }
-
-        Ok(CasStats {
-            blob_count,
-            total_bytes,
-            small_blobs: *size_histogram.get("<1KB").unwrap_or(&0),
-            medium_blobs: *size_histogram.get("1KB-1MB").unwrap_or(&0),
-            large_blobs: *size_histogram.get("1MB-100MB").unwrap_or(&0),
-            huge_blobs: *size_histogram.get(">100MB").unwrap_or(&0),
-        })
+        Ok(stats)
     }
 
     /// Get a memory-mapped view of a blob (D6: zero-copy optimization).
@@ -462,46 +682,77 @@ impl CasStore {
         Ok(mmap)
     }
 
-    /// Get an iterator over all blob hashes in the CAS.
+    /// Get an iterator over all blob hashes in the CAS, tiers included.
     ///
     /// Traverses the 3-level structure: blake3/ab/cd/hash
     pub fn iter(&self) -> Result<CasIterator> {
-        let blake3_dir = self.root.join("blake3");
-        if !blake3_dir.exists() {
-            // Return empty iterator if blake3 dir doesn't exist
-            return Ok(CasIterator {
-                l1_iter: fs::read_dir(&self.root)?, // Will be empty or invalid
-                l2_iter: None,
-                l3_iter: None,
-                blake3_exists: false,
-            });
-        }
         Ok(CasIterator {
-            l1_iter: fs::read_dir(&blake3_dir)?,
-            l2_iter: None,
-            l3_iter: None,
-            blake3_exists: true,
+            files: BlobFiles::new(self.roots().map(Path::to_path_buf).collect()),
         })
     }
 
     /// Perform a Garbage Collection sweep using a Bloom Filter of active hashes.
     ///
+    /// Blobs listed in the pin store are kept regardless of the filter, and
+    /// so are the leaves of live chunked blobs.
     /// Returns (deleted_count, reclaimed_bytes).
     pub fn sweep(&self, bloom_bits: &[u8]) -> Result<(u32, u64)> {
         let bloom = BloomFilter {
             bits: bloom_bits.to_vec(),
         };
+        let pinned = PinStore::load(&self.root)?.pinned_hashes();
 
         let mut deleted_count = 0;
         let mut reclaimed_bytes = 0;
 
+        // Live chunked blobs keep their leaves; dead chunk lists go first
+        let mut live_leaves = std::collections::HashSet::new();
+        for hash in self.chunk_lists()? {
+            if bloom.contains(&Self::hash_to_hex(&hash)) || pinned.contains(&hash) {
+                if let Some(list) = self.chunk_list(&hash)? {
+                    live_leaves.extend(list.chunks.iter().map(|c| c.hash));
+                }
+            } else {
+                let path = self.chunk_list_path(&hash);
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                if fs::remove_file(&path).is_ok() {
+                    reclaimed_bytes += size;
+                }
+            }
+        }
+
+        for hash in self.compressed_blobs()? {
+            if bloom.contains(&Self::hash_to_hex(&hash)) || pinned.contains(&hash) {
+                continue;
+            }
+            let path = self.compressed_path(&hash);
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            let _ = crate::protection::set_immutable(&path, false);
+            if fs::remove_file(&path).is_ok() {
+                deleted_count += 1;
+                reclaimed_bytes += size;
+            }
+        }
+
+        // Expanded copies only serve their blob; they are not counted as blobs
+        for hash in self.expanded_copies()? {
+            if bloom.contains(&Self::hash_to_hex(&hash)) || pinned.contains(&hash) {
+                continue;
+            }
+            let path = self.expanded_path(&hash);
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            if fs::remove_file(&path).is_ok() {
+                reclaimed_bytes += size;
+            }
+        }
+
         for hash_res in self.iter()? {
             let hash = hash_res?;
 
             // Convert Blake3Hash ([u8; 32]) to hex string for bloom lookup
             let hex = Self::hash_to_hex(&hash);
 
-            if !bloom.contains(&hex) {
+            if !bloom.contains(&hex) && !pinned.contains(&hash) && !live_leaves.contains(&hash) {
                 // Potential orphan (not in Bloom Filter)
                 if let Some(path) = self.find_blob_path(&hash) {
                     if let Ok(meta) = fs::metadata(&path) {
@@ -519,6 +770,68 @@ impl CasStore {
         Ok((deleted_count, reclaimed_bytes))
     }
 
+    /// Move every blob whose size and age now route it to a different root.
+    ///
+    /// Age is the time since the blob was last read or written (the later
+    /// of atime and mtime), so a cold blob that is read again moves back on
+    /// the next run. With `dry_run` nothing moves; the returned counts are
+    /// what would.
+    pub fn rebalance(&self, dry_run: bool) -> Result<RebalanceStats> {
+        let mut stats = RebalanceStats::default();
+        for root in self.roots() {
+            self.relocate(root, false, dry_run, &mut stats)?;
+        }
+        Ok(stats)
+    }
+
+    /// Move every blob under `root`, a tier being retired that is no longer
+    /// one of this store's, to wherever this store would place it.
+    pub fn drain(&self, root: &Path, dry_run: bool) -> Result<RebalanceStats> {
+        let mut stats = RebalanceStats::default();
+        self.relocate(root, true, dry_run, &mut stats)?;
+        Ok(stats)
+    }
+
+    /// Move the blobs under `from` that belong elsewhere (all of them with `all`)
+    fn relocate(
+        &self,
+        from: &Path,
+        all: bool,
+        dry_run: bool,
+        stats: &mut RebalanceStats,
+    ) -> Result<()> {
+        let now = SystemTime::now();
+        for entry in BlobFiles::new(vec![from.to_path_buf()]) {
+            let entry = entry?;
+            let Ok(meta) = entry.metadata() else {
+                continue; // Deleted under us (GC)
+            };
+            let to = self.placement(meta.len(), last_used_age(&meta, now));
+            if !all && to == from {
+                continue;
+            }
+            let name = entry.file_name();
+            let Some(hash) = name
+                .to_str()
+                .and_then(|n| n.split('_').next())
+                .and_then(Self::hex_to_hash)
+            else {
+                continue;
+            };
+            if !dry_run {
+                let dest = Self::blob_dir(to, &hash).join(&name);
+                if let Err(e) = move_blob_file(&entry.path(), &dest, &hash) {
+                    tracing::warn!(blob = %entry.path().display(), error = %e, "CAS: failed to move blob");
+                    stats.failed += 1;
+                    continue;
+                }
+            }
+            stats.moved += 1;
+            stats.bytes += meta.len();
+        }
+        Ok(())
+    }
+
     pub fn blob_path_for_hash(&self, hash: &Blake3Hash) -> Option<PathBuf> {
         self.find_blob_path(hash)
     }
@@ -564,6 +877,104 @@ impl CasStore {
     }
 }
 
+/// Move a blob file to `dest`, copying when it is on another filesystem.
+/// A copy keeps the source's timestamps and is checked against `hash`
+/// before the source is removed.
+fn move_blob_file(src: &Path, dest: &Path, hash: &Blake3Hash) -> io::Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Immutable files can't be renamed or unlinked
+    let _ = crate::protection::set_immutable(src, false);
+    match fs::rename(src, dest) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {}
+        other => return other,
+    }
+
+    let meta = fs::metadata(src)?;
+    let temp = dest.with_file_name(format!(
+        "{}.{}.{}.tmp",
+        dest.file_name().unwrap_or_default().to_string_lossy(),
+        std::process::id(),
+        STREAM_TEMP_SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    ));
+    let copied = (|| -> io::Result<()> {
+        fs::copy(src, &temp)?;
+        let file = File::open(&temp)?;
+        file.set_times(
+            fs::FileTimes::new()
+                .set_accessed(meta.accessed()?)
+                .set_modified(meta.modified()?),
+        )?;
+        file.sync_all()?;
+        if CasStore::compute_hash_reader(&file)? != *hash {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "copied blob does not match its hash",
+            ));
+        }
+        fs::rename(&temp, dest)
+    })();
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    fs::remove_file(src)
+}
+
+/// Time since a blob was last read or written
+pub(crate) fn last_used_age(meta: &fs::Metadata, now: SystemTime) -> Duration {
+    let used = meta
+        .accessed()
+        .into_iter()
+        .chain(meta.modified())
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    now.duration_since(used).unwrap_or_default()
+}
+
+/// Streaming blob reader returned by [`CasStore::get_reader`]
+pub struct BlobReader {
+    inner: io::BufReader<Box<dyn Read + Send>>,
+    hasher: blake3::Hasher,
+    expected: Blake3Hash,
+    len: u64,
+    checked: bool,
+}
+
+impl BlobReader {
+    /// Blob size in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Read for BlobReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.hasher.update(&buf[..n]);
+        } else if !buf.is_empty() && !self.checked {
+            self.checked = true;
+            let actual = *self.hasher.finalize().as_bytes();
+            if actual != self.expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    CasError::HashMismatch {
+                        expected: CasStore::hash_to_hex(&self.expected),
+                        actual: CasStore::hash_to_hex(&actual),
+                    },
+                ));
+            }
+        }
+        Ok(n)
+    }
+}
+
 // ============================================================================
 // Bloom Filter (RFC-0041 / RFC-0044)
 // ============================================================================
@@ -792,8 +1203,12 @@ impl CasStore {
 pub struct CasStats {
     /// Number of unique blobs stored
     pub blob_count: u64,
-    /// Total bytes stored (deduplicated)
+    /// Total bytes stored (deduplicated, as on disk)
     pub total_bytes: u64,
+    /// Total uncompressed size of the blobs
+    pub logical_bytes: u64,
+    /// Blobs stored zstd-compressed
+    pub compressed_blobs: u64,
     /// Blobs < 1KB
     pub small_blobs: u64,
     /// Blobs 1KB - 1MB
@@ -804,6 +1219,17 @@ pub struct CasStats {
     pub huge_blobs: u64,
 }
 
+/// Outcome of [`CasStore::rebalance`] and [`CasStore::drain`]
+#[derive(Debug, Clone, Default)]
+pub struct RebalanceStats {
+    /// Blobs moved (for a dry run: that would be)
+    pub moved: u64,
+    /// Their total size
+    pub bytes: u64,
+    /// Blobs left where they were because moving them failed
+    pub failed: u64,
+}
+
 impl CasStats {
     /// Calculate average blob size
     pub fn avg_blob_size(&self) -> u64 {
@@ -813,48 +1239,89 @@ impl CasStats {
             self.total_bytes / self.blob_count
         }
     }
+
+    /// Count a blob of `size` bytes taking `stored` bytes on disk
+    fn add(&mut self, size: u64, stored: u64) {
+        self.blob_count += 1;
+        self.total_bytes += stored;
+        self.logical_bytes += size;
+
+        // Categorize by size
+        if size < 1024 {
+            self.small_blobs += 1;
+        } else if size < 1024 * 1024 {
+            self.medium_blobs += 1;
+        } else if size < 100 * 1024 * 1024 {
+            self.large_blobs += 1;
+        } else {
+            self.huge_blobs += 1;
+        }
+    }
 }
 
 /// Iterator over CAS hashes (3-level: blake3/ab/cd/hash)
 pub struct CasIterator {
-    l1_iter: fs::ReadDir,         // Level 1: ab/ directories
-    l2_iter: Option<fs::ReadDir>, // Level 2: cd/ directories
-    l3_iter: Option<fs::ReadDir>, // Level 3: hash files
-    blake3_exists: bool,
+    files: BlobFiles,
 }
 
 impl Iterator for CasIterator {
     type Item = Result<Blake3Hash>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if !self.blake3_exists {
-            return None;
+        loop {
+            let entry = match self.files.next()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(CasError::Io(e))),
+            };
+            // Parse filename as hash (may include _size suffix)
+            if let Some(filename) = entry.file_name().to_str() {
+                // Handle both "hash" and "hash_size.ext" formats
+                let hash_part = filename.split('_').next().unwrap_or(filename);
+                if let Some(hash) = CasStore::hex_to_hash(hash_part) {
+                    return Some(Ok(hash));
+                }
+            }
         }
+    }
+}
+
+/// Blob files under a list of CAS roots, one root after another
+struct BlobFiles {
+    roots: std::vec::IntoIter<PathBuf>,
+    l1_iter: Option<fs::ReadDir>, // Level 1: ab/ directories
+    l2_iter: Option<fs::ReadDir>, // Level 2: cd/ directories
+    l3_iter: Option<fs::ReadDir>, // Level 3: hash files
+}
 
+impl BlobFiles {
+    fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots: roots.into_iter(),
+            l1_iter: None,
+            l2_iter: None,
+            l3_iter: None,
+        }
+    }
+}
+
+impl Iterator for BlobFiles {
+    type Item = io::Result<fs::DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let is_dir = |entry: &fs::DirEntry| entry.file_type().is_ok_and(|t| t.is_dir());
         loop {
             // Try to get next file from L3 (hash files)
             if let Some(ref mut l3) = self.l3_iter {
                 match l3.next() {
                     Some(Ok(entry)) => {
-                        let path = entry.path();
-                        if path.is_file() {
-                            // Skip temp files
-                            if path.extension().is_some_and(|ext| ext == "tmp") {
-                                continue;
-                            }
-
-                            // Parse filename as hash (may include _size suffix)
-                            if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                                // Handle both "hash" and "hash_size.ext" formats
-                                let hash_part = filename.split('_').next().unwrap_or(filename);
-                                if let Some(hash) = CasStore::hex_to_hash(hash_part) {
-                                    return Some(Ok(hash));
-                                }
-                            }
+                        // Skip temp files
+                        let is_temp = entry.path().extension().is_some_and(|ext| ext == "tmp");
+                        if entry.file_type().is_ok_and(|t| t.is_file()) && !is_temp {
+                            return Some(Ok(entry));
                         }
                         continue;
                     }
-                    Some(Err(e)) => return Some(Err(CasError::Io(e))),
+                    Some(Err(e)) => return Some(Err(e)),
                     None => self.l3_iter = None,
                 }
             }
@@ -863,31 +1330,41 @@ impl Iterator for CasIterator {
             if let Some(ref mut l2) = self.l2_iter {
                 match l2.next() {
                     Some(Ok(entry)) => {
-                        if entry.file_type().ok()?.is_dir() {
+                        if is_dir(&entry) {
                             match fs::read_dir(entry.path()) {
                                 Ok(iter) => self.l3_iter = Some(iter),
-                                Err(e) => return Some(Err(CasError::Io(e))),
+                                Err(e) => return Some(Err(e)),
                             }
                         }
                         continue;
                     }
-                    Some(Err(e)) => return Some(Err(CasError::Io(e))),
+                    Some(Err(e)) => return Some(Err(e)),
                     None => self.l2_iter = None,
                 }
             }
 
             // L2 exhausted, try to get next L1 directory
-            match self.l1_iter.next() {
-                Some(Ok(entry)) => {
-                    if entry.file_type().ok()?.is_dir() {
-                        match fs::read_dir(entry.path()) {
-                            Ok(iter) => self.l2_iter = Some(iter),
-                            Err(e) => return Some(Err(CasError::Io(e))),
+            if let Some(ref mut l1) = self.l1_iter {
+                match l1.next() {
+                    Some(Ok(entry)) => {
+                        if is_dir(&entry) {
+                            match fs::read_dir(entry.path()) {
+                                Ok(iter) => self.l2_iter = Some(iter),
+                                Err(e) => return Some(Err(e)),
+                            }
                         }
+                        continue;
                     }
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => self.l1_iter = None,
                 }
-                Some(Err(e)) => return Some(Err(CasError::Io(e))),
-                None => return None, // All levels exhausted
+            }
+
+            // Root exhausted, move on to the next one
+            match fs::read_dir(self.roots.next()?.join("blake3")) {
+                Ok(iter) => self.l1_iter = Some(iter),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Some(Err(e)),
             }
         }
     }
@@ -910,6 +1387,138 @@ mod tests {
         assert_eq!(retrieved, data);
     }
 
+    #[test]
+    fn test_read_range_and_verify() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+        let hash = cas.store(b"0123456789").unwrap();
+
+        assert_eq!(cas.read_range(&hash, 2, 3).unwrap(), b"234");
+        assert_eq!(cas.read_range(&hash, 8, 100).unwrap(), b"89");
+        assert!(cas.read_range(&hash, 20, 4).unwrap().is_empty());
+        cas.verify(&hash).unwrap();
+
+        let path = cas.blob_path_for_hash(&hash).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        fs::write(&path, b"0123456780").unwrap();
+        assert!(matches!(
+            cas.verify(&hash),
+            Err(CasError::HashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_streaming_store_and_get() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+
+        // Spans several chunks, with a short final one
+        let data: Vec<u8> = (0..STREAM_CHUNK * 2 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let hash = cas.store_reader(&data[..]).unwrap();
+        assert_eq!(hash, CasStore::compute_hash(&data));
+        assert_eq!(cas.store(&data).unwrap(), hash);
+
+        let mut reader = cas.get_reader(&hash).unwrap();
+        assert_eq!(reader.len(), data.len() as u64);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+
+        // No temp files left behind in the CAS root
+        let leftovers = fs::read_dir(temp.path())
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .count();
+        assert_eq!(leftovers, 0);
+
+        let path = cas.blob_path_for_hash(&hash).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let mut corrupt = data.clone();
+        corrupt[7] ^= 1;
+        fs::write(&path, &corrupt).unwrap();
+        let err = io::copy(&mut cas.get_reader(&hash).unwrap(), &mut io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_sweep_keeps_pinned_blobs() {
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+        let pinned = cas.store(b"toolchain").unwrap();
+        let orphan = cas.store(b"scratch").unwrap();
+
+        let mut pins = PinStore::load(temp.path()).unwrap();
+        pins.pin("release", [&pinned]);
+        pins.save().unwrap();
+
+        // Empty filter: nothing is referenced by a manifest
+        let (deleted, _) = cas.sweep(&vec![0u8; 1024]).unwrap();
+        assert_eq!(deleted, 1);
+        assert!(cas.exists(&pinned));
+        assert!(!cas.exists(&orphan));
+    }
+
+    #[test]
+    fn test_tiers_route_lookup_and_rebalance() {
+        let primary = TempDir::new().unwrap();
+        let nas = TempDir::new().unwrap();
+        let tier = |min_size| CasTier {
+            name: "nas".to_string(),
+            root: nas.path().to_path_buf(),
+            min_size: Some(min_size),
+            min_age_days: None,
+        };
+        let mut set = TierSet::load(primary.path()).unwrap();
+        set.add(tier(16));
+        set.save().unwrap();
+
+        // New blobs are routed by size and found in either root
+        let cas = CasStore::new(primary.path()).unwrap();
+        let small = cas.store(b"tiny").unwrap();
+        let big = cas.store(&[7u8; 64]).unwrap();
+        assert!(cas
+            .blob_path_for_hash(&small)
+            .unwrap()
+            .starts_with(primary.path()));
+        assert!(cas
+            .blob_path_for_hash(&big)
+            .unwrap()
+            .starts_with(nas.path()));
+        assert_eq!(cas.get(&big).unwrap(), vec![7u8; 64]);
+        assert_eq!(cas.iter().unwrap().count(), 2);
+        assert_eq!(cas.stats().unwrap().blob_count, 2);
+        assert_eq!(CasStore::root_stats(nas.path()).unwrap().blob_count, 1);
+
+        // A lower threshold moves the small blob over too
+        let cas = CasStore::with_tiers(primary.path(), vec![tier(1)]).unwrap();
+        assert_eq!(cas.rebalance(true).unwrap().moved, 1);
+        assert!(cas
+            .blob_path_for_hash(&small)
+            .unwrap()
+            .starts_with(primary.path()));
+        let stats = cas.rebalance(false).unwrap();
+        assert_eq!((stats.moved, stats.bytes, stats.failed), (1, 4, 0));
+        assert!(cas
+            .blob_path_for_hash(&small)
+            .unwrap()
+            .starts_with(nas.path()));
+        assert_eq!(cas.rebalance(false).unwrap().moved, 0);
+
+        // Retiring the tier brings everything home
+        let cas = CasStore::with_tiers(primary.path(), Vec::new()).unwrap();
+        assert_eq!(cas.drain(nas.path(), false).unwrap().moved, 2);
+        assert_eq!(CasStore::root_stats(nas.path()).unwrap().blob_count, 0);
+        cas.verify(&small).unwrap();
+        cas.verify(&big).unwrap();
+    }
+
     #[test]
     fn test_deduplication() {
         let temp = TempDir::new().unwrap();
@@ -288,31 +288,27 @@ pub fn parallel_ingest_with_fallback(
                 Err(crate::CasError::Io(ref io_err))
                     if io_err.raw_os_error() == Some(libc::EXDEV) =>
                 {
-                    // Cross-device fallback: read + store
-                    match std::fs::read(path) {
-                        Ok(content) => {
-                            let hash = crate::CasStore::compute_hash(&content);
-                            let size = content.len() as u64;
-                            match cas.store(&content) {
-                                Ok(_) => {
-                                    success_count.fetch_add(1, Ordering::Relaxed);
-                                    total_bytes.fetch_add(size, Ordering::Relaxed);
-                                    fallback_count.fetch_add(1, Ordering::Relaxed);
-                                    Some(IngestResult {
-                                        source_path: path.clone(),
-                                        hash,
-                                        size,
-                                        was_new: true,
-                                        skipped_by_cache: false,
-                                        mtime: 0, // fallback path: no metadata available
-                                        mode: 0o644,
-                                    })
-                                }
-                                Err(_) => {
-                                    error_count.fetch_add(1, Ordering::Relaxed);
-                                    None
-                                }
-                            }
+                    // Cross-device fallback: stream a copy into the CAS
+                    let stored = std::fs::File::open(path).and_then(|file| {
+                        let size = file.metadata()?.len();
+                        cas.store_reader(file)
+                            .map(|hash| (hash, size))
+                            .map_err(std::io::Error::other)
+                    });
+                    match stored {
+                        Ok((hash, size)) => {
+                            success_count.fetch_add(1, Ordering::Relaxed);
+                            total_bytes.fetch_add(size, Ordering::Relaxed);
+                            fallback_count.fetch_add(1, Ordering::Relaxed);
+                            Some(IngestResult {
+                                source_path: path.clone(),
+                                hash,
+                                size,
+                                was_new: true,
+                                skipped_by_cache: false,
+                                mtime: 0, // fallback path: no metadata available
+                                mode: 0o644,
+                            })
                         }
                         Err(_) => {
                             error_count.fetch_add(1, Ordering::Relaxed);
@@ -0,0 +1,187 @@
+//! # Blob Pins
+//!
+//! Labelled sets of blob hashes that garbage collection must never delete,
+//! even when no registered manifest references them (e.g. a release
+//! toolchain that is only mounted occasionally).
+//!
+//! Pins live next to the blob tree as `<cas_root>/pins.json`:
+//!
+//! ```json
+//! { "version": 1, "pins": { "toolchain-1.82": ["ab12…", "cd34…"] } }
+//! ```
+//!
+//! A hash stays protected while any label still lists it.
+//!
+//! Writers go through [`PinStore::load_locked`], which holds an exclusive
+//! flock on `<cas_root>/pins.lock` until the store is dropped, so two
+//! `vrift pin` runs can't each save over the other's label. Readers need no
+//! lock: `save` replaces the file with a rename.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use nix::fcntl::{Flock, FlockArg};
+use serde::{Deserialize, Serialize};
+
+use crate::{Blake3Hash, CasStore};
+
+/// File name of the pin store inside the CAS root
+pub const PINS_FILE: &str = "pins.json";
+
+/// Lock file serializing writers of `PINS_FILE` (which is itself replaced
+/// on every save, so can't be locked)
+const PINS_LOCK: &str = "pins.lock";
+
+const PINS_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PinFile {
+    version: u32,
+    /// label → hex hashes
+    pins: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Pin set for one CAS root
+#[derive(Debug)]
+pub struct PinStore {
+    path: PathBuf,
+    file: PinFile,
+    /// Held from load to drop by `load_locked`
+    _lock: Option<Flock<fs::File>>,
+}
+
+impl PinStore {
+    /// Load the pins for a load → change → save cycle, holding the writer
+    /// lock until the store is dropped. Blocks while another writer holds it.
+    pub fn load_locked(cas_root: &Path) -> io::Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(cas_root.join(PINS_LOCK))?;
+        let lock =
+            Flock::lock(file, FlockArg::LockExclusive).map_err(|(_, e)| io::Error::from(e))?;
+        Ok(Self {
+            _lock: Some(lock),
+            ..Self::load(cas_root)?
+        })
+    }
+
+    /// Load the pins of the CAS at `cas_root` (empty if none were ever saved)
+    pub fn load(cas_root: &Path) -> io::Result<Self> {
+        let path = cas_root.join(PINS_FILE);
+        let file = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => PinFile {
+                version: PINS_VERSION,
+                pins: BTreeMap::new(),
+            },
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            path,
+            file,
+            _lock: None,
+        })
+    }
+
+    /// Write the pin set (temp file + rename, so readers never see half a file)
+    pub fn save(&self) -> io::Result<()> {
+        let data = serde_json::to_vec_pretty(&self.file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// Add `hashes` under `label`. Returns how many were not already in it.
+    pub fn pin<'a>(
+        &mut self,
+        label: &str,
+        hashes: impl IntoIterator<Item = &'a Blake3Hash>,
+    ) -> usize {
+        let set = self.file.pins.entry(label.to_string()).or_default();
+        hashes
+            .into_iter()
+            .filter(|h| set.insert(CasStore::hash_to_hex(h)))
+            .count()
+    }
+
+    /// Drop a whole label. Returns the number of hashes it held.
+    pub fn unpin(&mut self, label: &str) -> Option<usize> {
+        self.file.pins.remove(label).map(|set| set.len())
+    }
+
+    /// Labels with their hash counts, sorted by label
+    pub fn labels(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.file
+            .pins
+            .iter()
+            .map(|(l, set)| (l.as_str(), set.len()))
+    }
+
+    /// Union of every label's hashes
+    pub fn pinned_hashes(&self) -> HashSet<Blake3Hash> {
+        self.file
+            .pins
+            .values()
+            .flatten()
+            .filter_map(|hex| CasStore::hex_to_hash(hex))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_store_roundtrip_and_unpin() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = ([1u8; 32], [2u8; 32]);
+
+        let mut pins = PinStore::load(dir.path()).unwrap();
+        assert_eq!(pins.pin("tools", [&a, &b]), 2);
+        assert_eq!(pins.pin("tools", [&a]), 0);
+        assert_eq!(pins.pin("release", [&a]), 1);
+        pins.save().unwrap();
+
+        let mut pins = PinStore::load(dir.path()).unwrap();
+        let labels: Vec<_> = pins.labels().collect();
+        assert_eq!(labels, vec![("release", 1), ("tools", 2)]);
+
+        // `a` stays pinned through the other label
+        assert_eq!(pins.unpin("tools"), Some(2));
+        assert_eq!(pins.unpin("tools"), None);
+        let hashes = pins.pinned_hashes();
+        assert!(hashes.contains(&a));
+        assert!(!hashes.contains(&b));
+    }
+
+    #[test]
+    fn test_locked_writers_keep_each_others_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let threads: Vec<_> = (0..8u8)
+            .map(|i| {
+                let root = dir.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let mut pins = PinStore::load_locked(&root).unwrap();
+                    pins.pin(&format!("label-{}", i), [&[i; 32]]);
+                    // Widen the window a lost update would need
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                    pins.save().unwrap();
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        let pins = PinStore::load(dir.path()).unwrap();
+        assert_eq!(pins.labels().count(), 8);
+        assert_eq!(pins.pinned_hashes().len(), 8);
+    }
+}
@@ -9,10 +9,12 @@
 //! - Linux: ioctl(FICLONE) for btrfs/xfs/ext4 (reflink-capable)
 //! - macOS: clonefile() for APFS
 
-use std::fs::{self};
+use std::fs::{self, File};
 use std::io;
 use std::path::Path;
 
+use crate::{Blake3Hash, CasStore};
+
 /// Result of an ingestion operation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IngestMethod {
@@ -203,6 +205,65 @@ pub fn ingest_and_remove(src: &Path, dst: &Path) -> io::Result<IngestMethod> {
     Ok(method)
 }
 
+impl CasStore {
+    /// Materialize blob `hash` at `dest` as cheaply as the filesystem
+    /// allows: a reflink of the blob, else a hard link to it, else a copy.
+    /// Chunked and compressed blobs are always copied (hash-checked).
+    ///
+    /// A hard-linked `dest` *is* the blob: read-only, with the blob's mode
+    /// and mtime, and changing either changes the CAS. Use
+    /// [`clone_out`](Self::clone_out) when the file needs its own metadata.
+    /// `dest` must not exist yet.
+    pub fn link_out(&self, hash: &Blake3Hash, dest: &Path) -> crate::Result<IngestMethod> {
+        self.place_out(hash, dest, true)
+    }
+
+    /// Like [`link_out`](Self::link_out), but never hard-links: `dest` is
+    /// always its own inode, free to chmod or edit
+    pub fn clone_out(&self, hash: &Blake3Hash, dest: &Path) -> crate::Result<IngestMethod> {
+        self.place_out(hash, dest, false)
+    }
+
+    fn place_out(
+        &self,
+        hash: &Blake3Hash,
+        dest: &Path,
+        hardlink: bool,
+    ) -> crate::Result<IngestMethod> {
+        // try_reflink creates `dest` with truncation; never clobber a file
+        if fs::symlink_metadata(dest).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", dest.display()),
+            )
+            .into());
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if let Some(blob) = self.find_blob_path(hash) {
+            if try_reflink(&blob, dest).is_ok() {
+                return Ok(IngestMethod::Reflink);
+            }
+            if hardlink && fs::hard_link(&blob, dest).is_ok() {
+                return Ok(IngestMethod::Hardlink);
+            }
+        }
+
+        let mut reader = self.get_reader(hash)?;
+        let copied = File::create_new(dest).and_then(|mut file| {
+            io::copy(&mut reader, &mut file)?;
+            file.sync_all()
+        });
+        if let Err(e) = copied {
+            let _ = fs::remove_file(dest);
+            return Err(e.into());
+        }
+        Ok(IngestMethod::Copy)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -421,4 +482,35 @@ mod tests {
         assert!(!format!("{}", e2).is_empty());
         assert!(!format!("{}", e3).is_empty());
     }
+
+    #[test]
+    fn test_link_out_and_clone_out() {
+        let temp = tempdir().unwrap();
+        let cas = CasStore::new(temp.path().join("cas")).unwrap();
+        let hash = cas.store(b"checked out").unwrap();
+
+        let linked = temp.path().join("tree/a.txt");
+        let method = cas.link_out(&hash, &linked).unwrap();
+        assert_ne!(method, IngestMethod::Copy, "same filesystem: no copy");
+        assert_eq!(fs::read(&linked).unwrap(), b"checked out");
+        assert!(cas.link_out(&hash, &linked).is_err(), "never clobbers");
+
+        let cloned = temp.path().join("tree/b.txt");
+        assert_ne!(
+            cas.clone_out(&hash, &cloned).unwrap(),
+            IngestMethod::Hardlink
+        );
+        assert_eq!(fs::read(&cloned).unwrap(), b"checked out");
+
+        // Chunked blobs have no single file to link
+        let big: Vec<u8> = (0..300_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let big_hash = cas.store(&big).unwrap();
+        cas.split_blob(&big_hash).unwrap();
+        let copied = temp.path().join("tree/big.bin");
+        assert_eq!(
+            cas.link_out(&big_hash, &copied).unwrap(),
+            IngestMethod::Copy
+        );
+        assert_eq!(fs::read(&copied).unwrap(), big);
+    }
 }
@@ -0,0 +1,319 @@
+//! # Integrity Scrub
+//!
+//! Re-hashes every blob under the CAS roots and checks it against its
+//! name (`<hash>_<size>[.ext]` in `blake3/ab/cd/`, or `<hash>` in `zstd/`
+//! for compressed blobs, which are decoded first). Blobs that fail are
+//! moved to `<cas_root>/quarantine/` so nothing hands out their content
+//! again; the report lists their hashes, and the hashes of chunked blobs
+//! built from them, so callers can find the manifest paths to re-ingest.
+//!
+//! Hashing is throttled to [`ScrubOptions::max_bytes_per_sec`], which lets
+//! the daemon scrub in the background without starving builds of I/O.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::{move_blob_file, Blake3Hash, BlobFiles, CasStore, Result};
+
+/// Directory inside the primary root that bad blobs are moved to
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// How a scrub runs
+#[derive(Debug, Clone, Default)]
+pub struct ScrubOptions {
+    /// Hashing budget (None: as fast as the disk allows)
+    pub max_bytes_per_sec: Option<u64>,
+    /// Move bad blobs out of the CAS (otherwise only report them)
+    pub quarantine: bool,
+}
+
+/// What is wrong with a blob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScrubProblem {
+    /// Content hashes to something other than its name
+    Corrupt { actual: Blake3Hash },
+    /// Length differs from the size in its name
+    SizeMismatch { recorded: u64, actual: u64 },
+    /// Compressed blob whose header or zstd frame is damaged
+    Undecodable,
+    /// Stored under another hash's directory, where lookups never look
+    Misplaced,
+    /// File name is not a blob name (reported, never quarantined)
+    Unrecognized,
+}
+
+/// One blob that failed the scrub
+#[derive(Debug, Clone)]
+pub struct ScrubFinding {
+    pub path: PathBuf,
+    /// Hash its name claims, if it names one
+    pub hash: Option<Blake3Hash>,
+    pub problem: ScrubProblem,
+    /// Where it was moved, if it was quarantined
+    pub quarantined: Option<PathBuf>,
+}
+
+/// Result of [`CasStore::scrub`]
+#[derive(Debug, Clone, Default)]
+pub struct ScrubReport {
+    /// Blobs hashed
+    pub checked: u64,
+    /// Bytes hashed
+    pub bytes: u64,
+    pub findings: Vec<ScrubFinding>,
+    /// Chunked blobs with a bad chunk
+    pub damaged_chunked: Vec<Blake3Hash>,
+}
+
+impl ScrubReport {
+    /// Whether every blob checked out
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Content hashes whose files can no longer be served as stored:
+    /// the bad blobs and the chunked blobs that include one
+    pub fn damaged_hashes(&self) -> HashSet<Blake3Hash> {
+        self.findings
+            .iter()
+            .filter(|f| f.problem != ScrubProblem::Unrecognized)
+            .filter_map(|f| f.hash)
+            .chain(self.damaged_chunked.iter().copied())
+            .collect()
+    }
+}
+
+impl CasStore {
+    /// Quarantine directory of this store
+    pub fn quarantine_dir(&self) -> PathBuf {
+        self.root.join(QUARANTINE_DIR)
+    }
+
+    /// Verify every blob in the primary root and all tiers.
+    ///
+    /// A blob that disappears mid-scan (GC, rebalance) is skipped. I/O
+    /// errors on single blobs are treated the same way; only a failure to
+    /// list the roots ends the scrub.
+    pub fn scrub(&self, options: &ScrubOptions) -> Result<ScrubReport> {
+        let mut report = ScrubReport::default();
+        let started = Instant::now();
+
+        for entry in BlobFiles::new(self.roots().map(Path::to_path_buf).collect()) {
+            let entry = entry?;
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let (hash_part, size_part) = match name.split_once('_') {
+                Some((hash, rest)) => (hash, Some(rest.split('.').next().unwrap_or(rest))),
+                None => (name.as_ref(), None),
+            };
+            let Some(hash) = Self::hex_to_hash(hash_part) else {
+                report.findings.push(ScrubFinding {
+                    path,
+                    hash: None,
+                    problem: ScrubProblem::Unrecognized,
+                    quarantined: None,
+                });
+                continue;
+            };
+
+            let problem = if path.parent() != Some(&Self::blob_dir(root_of(&path), &hash)) {
+                Some(ScrubProblem::Misplaced)
+            } else if let Some(recorded) = size_part
+                .and_then(|s| s.parse::<u64>().ok())
+                .filter(|&recorded| recorded != meta.len())
+            {
+                Some(ScrubProblem::SizeMismatch {
+                    recorded,
+                    actual: meta.len(),
+                })
+            } else {
+                match File::open(&path).and_then(Self::compute_hash_reader) {
+                    Ok(actual) if actual != hash => Some(ScrubProblem::Corrupt { actual }),
+                    Ok(_) => None,
+                    Err(_) => continue,
+                }
+            };
+            report.checked += 1;
+            report.bytes += meta.len();
+
+            if let Some(problem) = problem {
+                let quarantined = options
+                    .quarantine
+                    .then(|| self.quarantine(&path, &hash))
+                    .flatten();
+                report.findings.push(ScrubFinding {
+                    path,
+                    hash: Some(hash),
+                    problem,
+                    quarantined,
+                });
+            }
+
+            self.throttle(options, &report, started);
+        }
+
+        // Compressed blobs are named by their uncompressed hash: decode them
+        for hash in self.compressed_blobs()? {
+            let path = self.compressed_path(&hash);
+            let Ok(stored) = fs::metadata(&path).map(|m| m.len()) else {
+                continue;
+            };
+            let problem = match self.compressed_reader(&hash) {
+                Ok(Some((reader, _))) => match Self::compute_hash_reader(reader) {
+                    Ok(actual) if actual != hash => Some(ScrubProblem::Corrupt { actual }),
+                    Ok(_) => None,
+                    Err(_) => Some(ScrubProblem::Undecodable),
+                },
+                Ok(None) => continue,
+                Err(_) => Some(ScrubProblem::Undecodable),
+            };
+            report.checked += 1;
+            report.bytes += stored;
+            if let Some(problem) = problem {
+                let quarantined = options
+                    .quarantine
+                    .then(|| self.quarantine(&path, &hash))
+                    .flatten();
+                report.findings.push(ScrubFinding {
+                    path,
+                    hash: Some(hash),
+                    problem,
+                    quarantined,
+                });
+            }
+            self.throttle(options, &report, started);
+        }
+
+        let bad: HashSet<Blake3Hash> = report.findings.iter().filter_map(|f| f.hash).collect();
+        if !bad.is_empty() {
+            for whole in self.chunk_lists()? {
+                if let Some(list) = self.chunk_list(&whole)? {
+                    if list.chunks.iter().any(|c| bad.contains(&c.hash)) {
+                        report.damaged_chunked.push(whole);
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Sleep until the bytes hashed so far fit the rate budget
+    fn throttle(&self, options: &ScrubOptions, report: &ScrubReport, started: Instant) {
+        if let Some(rate) = options.max_bytes_per_sec.filter(|&r| r > 0) {
+            let due = Duration::from_secs_f64(report.bytes as f64 / rate as f64);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+    }
+
+    /// Move a bad blob into the quarantine directory, returning its new path
+    fn quarantine(&self, path: &Path, hash: &Blake3Hash) -> Option<PathBuf> {
+        let dest = self.quarantine_dir().join(path.file_name()?);
+        // Not fs::rename alone: the blob may live on another tier's device.
+        // move_blob_file re-checks copies against the hash, which a corrupt
+        // blob fails, so fall back to a plain copy for those.
+        match move_blob_file(path, &dest, hash) {
+            Ok(()) => Some(dest),
+            Err(_) => {
+                fs::create_dir_all(dest.parent()?).ok()?;
+                fs::copy(path, &dest).ok()?;
+                fs::remove_file(path).ok()?;
+                Some(dest)
+            }
+        }
+    }
+}
+
+/// CAS root of a blob at `<root>/blake3/ab/cd/<name>`
+fn root_of(path: &Path) -> &Path {
+    path.ancestors().nth(4).unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scrub_finds_and_quarantines_bad_blobs() {
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+        let good = cas.store(b"good content").unwrap();
+        let bad = cas.store(b"will be flipped").unwrap();
+
+        let bad_path = cas.blob_path_for_hash(&bad).unwrap();
+        fs::set_permissions(&bad_path, fs::Permissions::from_mode(0o644)).unwrap();
+        fs::write(&bad_path, b"will be fli ped").unwrap();
+
+        let report = cas.scrub(&ScrubOptions::default()).unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.findings.len(), 1);
+        assert!(matches!(
+            report.findings[0].problem,
+            ScrubProblem::Corrupt { .. }
+        ));
+        assert_eq!(report.damaged_hashes(), HashSet::from([bad]));
+        assert!(bad_path.exists(), "report-only scrub must not move blobs");
+
+        let report = cas
+            .scrub(&ScrubOptions {
+                quarantine: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let moved = report.findings[0].quarantined.clone().unwrap();
+        assert!(moved.starts_with(cas.quarantine_dir()));
+        assert!(!cas.exists(&bad));
+        assert!(cas.exists(&good));
+        assert!(cas.scrub(&ScrubOptions::default()).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_scrub_flags_misplaced_and_resized_blobs() {
+        let temp = TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path()).unwrap();
+        let hash = cas.store(b"intact").unwrap();
+        let path = cas.blob_path_for_hash(&hash).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+
+        // Intact content, but under a directory lookups never search
+        let l1 = if name.starts_with("00") { "ff" } else { "00" };
+        let stray_dir = temp.path().join("blake3").join(l1).join("00");
+        fs::create_dir_all(&stray_dir).unwrap();
+        fs::copy(&path, stray_dir.join(&name)).unwrap();
+
+        // Right place, wrong size in the name
+        let resized = path.with_file_name(name.replace("_6", "_7"));
+        fs::copy(&path, &resized).unwrap();
+
+        let report = cas.scrub(&ScrubOptions::default()).unwrap();
+        let problem = |p: &Path| {
+            report
+                .findings
+                .iter()
+                .find(|f| f.path == p)
+                .map(|f| &f.problem)
+        };
+        assert_eq!(
+            problem(&stray_dir.join(&name)),
+            Some(&ScrubProblem::Misplaced)
+        );
+        assert_eq!(
+            problem(&resized),
+            Some(&ScrubProblem::SizeMismatch {
+                recorded: 7,
+                actual: 6
+            })
+        );
+        assert_eq!(problem(&path), None);
+    }
+}
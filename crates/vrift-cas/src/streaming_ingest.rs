@@ -7,6 +7,7 @@
 //! Zero-copy: uses DirEntry::into_path() to transfer PathBuf ownership.
 
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use crossbeam::channel::{self, Receiver, Sender};
@@ -17,12 +18,59 @@ use crate::{CasError, IngestMode, IngestResult};
 /// Channel capacity (bounded ring buffer)
 const CHANNEL_CAP: usize = 1024;
 
+/// Caller's ignore rules: true for a path (and whether it is a directory)
+/// that ingest should skip. An ignored directory is not descended into.
+pub type IgnoreFn = Arc<dyn Fn(&Path, bool) -> bool + Send + Sync>;
+
+/// Live counters of a running ingest, for progress reporting. Updated by
+/// the scanner and workers of [`streaming_ingest`] and
+/// [`streaming_ingest_cached`].
+#[derive(Debug, Default)]
+pub struct IngestProgress {
+    /// Files the scanner has queued so far
+    pub found: AtomicU64,
+    /// Files processed (ingested, cache hits and failures)
+    pub done: AtomicU64,
+    /// Bytes of the files processed successfully
+    pub bytes: AtomicU64,
+    /// Set once the scanner has walked the whole tree (`found` is final)
+    pub scanned: AtomicBool,
+}
+
+impl IngestProgress {
+    fn record(&self, result: &Result<IngestResult, CasError>) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+        if let Ok(r) = result {
+            self.bytes.fetch_add(r.size, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Walk `source`, leaving out `.vrift`, `.git` and whatever `ignore` skips
+fn walk(source: &Path, ignore: Option<IgnoreFn>) -> WalkDir {
+    WalkDir::new(source).process_read_dir(move |_depth, _path, _state, children| {
+        children.retain(|entry| {
+            entry.as_ref().map_or(true, |e| {
+                let name = e.file_name.to_str().unwrap_or("");
+                if name == ".vrift" || name == ".git" {
+                    return false;
+                }
+                ignore
+                    .as_ref()
+                    .is_none_or(|ignore| !ignore(&e.path(), e.file_type.is_dir()))
+            })
+        });
+    })
+}
+
 /// Streaming ingest with producer-consumer pipeline
 pub fn streaming_ingest(
     source: &Path,
     cas_root: &Path,
     mode: IngestMode,
     threads: Option<usize>,
+    ignore: Option<IgnoreFn>,
+    progress: Option<Arc<IngestProgress>>,
 ) -> Vec<Result<IngestResult, CasError>> {
     use crate::zero_copy_ingest::{ingest_phantom, ingest_solid_tier1, ingest_solid_tier2};
 
@@ -40,28 +88,27 @@ pub fn streaming_ingest(
     // Scanner thread - sends paths, then drops tx to signal completion
     let source_path = source.to_path_buf();
     tracing::info!("[INGEST] Starting scanner thread for: {:?}", source_path);
+    let scanner_progress = progress.clone();
     let scanner = std::thread::spawn(move || {
         let mut file_count = 0;
-        for entry in WalkDir::new(&source_path)
-            .process_read_dir(|_depth, _path, _state, children| {
-                children.retain(|entry| {
-                    entry.as_ref().map_or(true, |e| {
-                        let name = e.file_name.to_str().unwrap_or("");
-                        name != ".vrift" && name != ".git"
-                    })
-                });
-            })
+        for entry in walk(&source_path, ignore)
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.file_type().is_file())
         {
             let path = entry.path();
             file_count += 1;
+            if let Some(p) = &scanner_progress {
+                p.found.fetch_add(1, Ordering::Relaxed);
+            }
             if tx.send(path).is_err() {
                 tracing::warn!("[INGEST] Scanner: receivers dropped, stopping");
                 break;
             }
         }
+        if let Some(p) = &scanner_progress {
+            p.scanned.store(true, Ordering::Relaxed);
+        }
         tracing::info!("[INGEST] Scanner complete: {} files found", file_count);
     });
 
@@ -73,6 +120,7 @@ pub fn streaming_ingest(
         .map(|i| {
             let rx = rx.clone();
             let cas = cas.clone();
+            let progress = progress.clone();
             std::thread::spawn(move || -> Vec<Result<IngestResult, CasError>> {
                 let mut local_results = Vec::new();
                 let mut processed = 0;
@@ -84,6 +132,9 @@ pub fn streaming_ingest(
                         IngestMode::SolidTier2 => ingest_solid_tier2(&path, &cas),
                     };
                     tracing::trace!("[INGEST] Worker {} done: {:?}", i, path);
+                    if let Some(p) = &progress {
+                        p.record(&result);
+                    }
                     local_results.push(result);
                     processed += 1;
                 }
@@ -117,6 +168,66 @@ pub fn streaming_ingest(
     all_results
 }
 
+/// A symlink found by [`ingest_symlinks`]
+#[derive(Debug, Clone)]
+pub struct SymlinkResult {
+    pub source_path: PathBuf,
+    /// Raw link target; stored in the CAS under `blake3(target)`
+    pub target: Vec<u8>,
+    /// Link mtime in nanoseconds (see `mtime_nsec_from_metadata`)
+    pub mtime: u64,
+}
+
+/// Store the targets of the symlinks under `source` as CAS blobs.
+///
+/// The file pipelines only pick up regular files, so links are collected
+/// here in a separate pass. Each target goes into the CAS so readers that
+/// only see the vnode (hash + length) can answer readlink() without the
+/// link existing on disk. Links into the CAS are skipped: those are files
+/// a Tier-1 ingest replaced, and are recorded as files.
+pub fn ingest_symlinks(
+    source: &Path,
+    cas_root: &Path,
+    ignore: Option<IgnoreFn>,
+) -> Vec<Result<SymlinkResult, CasError>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let cas = match crate::CasStore::new(cas_root) {
+        Ok(cas) => cas,
+        Err(e) => return vec![Err(e)],
+    };
+    let cas_canon = cas_root
+        .canonicalize()
+        .unwrap_or_else(|_| cas_root.to_path_buf());
+    walk(source, ignore)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_symlink())
+        .filter_map(|entry| {
+            let source_path = entry.path();
+            let target = match std::fs::read_link(&source_path) {
+                Ok(t) => t,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if target.starts_with(cas_root) || target.starts_with(&cas_canon) {
+                return None;
+            }
+            let stored = std::fs::symlink_metadata(&source_path)
+                .map_err(CasError::from)
+                .and_then(|metadata| {
+                    let target = target.as_os_str().as_bytes().to_vec();
+                    cas.store(&target)?;
+                    Ok(SymlinkResult {
+                        source_path,
+                        target,
+                        mtime: crate::zero_copy_ingest::mtime_nsec_from_metadata(&metadata),
+                    })
+                });
+            Some(stored)
+        })
+        .collect()
+}
+
 /// Streaming ingest with mtime+size cache skip (P0 Optimization)
 ///
 /// Same producer-consumer pipeline as `streaming_ingest`, but workers check
@@ -133,12 +244,16 @@ pub fn streaming_ingest(
 /// * `cas_root` - CAS storage root
 /// * `mode` - Ingest mode
 /// * `threads` - Worker thread count
+/// * `ignore` - Paths to leave out, on top of `.vrift` and `.git`
+/// * `progress` - Counters to update as files are found and processed
 /// * `cache_lookup` - Closure: manifest_key → Option<CacheHint>
 pub fn streaming_ingest_cached<F>(
     source: &Path,
     cas_root: &Path,
     mode: IngestMode,
     threads: Option<usize>,
+    ignore: Option<IgnoreFn>,
+    progress: Option<Arc<IngestProgress>>,
     cache_lookup: F,
 ) -> Vec<Result<IngestResult, CasError>>
 where
@@ -167,18 +282,11 @@ where
     // Scanner thread — stat's each file and sends metadata
     let source_path = source.to_path_buf();
     let scanner_source = source_path.clone();
+    let scanner_progress = progress.clone();
     let scanner = std::thread::spawn(move || {
         use std::os::unix::fs::MetadataExt;
         let mut file_count = 0;
-        for entry in WalkDir::new(&scanner_source)
-            .process_read_dir(|_depth, _path, _state, children| {
-                children.retain(|entry| {
-                    entry.as_ref().map_or(true, |e| {
-                        let name = e.file_name.to_str().unwrap_or("");
-                        name != ".vrift" && name != ".git"
-                    })
-                });
-            })
+        for entry in walk(&scanner_source, ignore)
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.file_type().is_file())
@@ -193,10 +301,16 @@ where
                 Err(_) => continue, // skip unreadable files
             };
             file_count += 1;
+            if let Some(p) = &scanner_progress {
+                p.found.fetch_add(1, Ordering::Relaxed);
+            }
             if tx.send((path, size, mtime, mode)).is_err() {
                 break;
             }
         }
+        if let Some(p) = &scanner_progress {
+            p.scanned.store(true, Ordering::Relaxed);
+        }
         tracing::info!("[INGEST] Scanner complete: {} files found", file_count);
     });
 
@@ -210,6 +324,7 @@ where
             let cas = cas.clone();
             let source_root = source_path.clone();
             let cache = Arc::clone(&cache_lookup);
+            let progress = progress.clone();
             std::thread::spawn(move || -> Vec<Result<IngestResult, CasError>> {
                 let mut local_results = Vec::new();
                 let mut processed = 0u64;
@@ -247,6 +362,9 @@ where
                         IngestMode::Phantom => ingest_phantom(&path, &cas),
                         IngestMode::SolidTier1 => ingest_solid_tier1(&path, &cas),
                     };
+                    if let Some(p) = &progress {
+                        p.record(&result);
+                    }
                     local_results.push(result);
                     processed += 1;
                 }
@@ -282,13 +400,14 @@ pub fn streaming_ingest_with_progress<F>(
     cas_root: &Path,
     mode: IngestMode,
     threads: Option<usize>,
+    ignore: Option<IgnoreFn>,
     on_progress: F,
 ) -> Vec<Result<IngestResult, CasError>>
 where
     F: Fn(&Result<IngestResult, CasError>, usize) + Send + Sync,
 {
     use crate::zero_copy_ingest::{ingest_phantom, ingest_solid_tier1, ingest_solid_tier2};
-    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::atomic::AtomicUsize;
 
     let (tx, rx): (Sender<PathBuf>, Receiver<PathBuf>) = channel::bounded(CHANNEL_CAP);
 
@@ -306,7 +425,7 @@ where
     // Scanner
     let source_path = source.to_path_buf();
     let scanner = std::thread::spawn(move || {
-        for entry in WalkDir::new(&source_path)
+        for entry in walk(&source_path, ignore)
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.file_type().is_file())
@@ -376,9 +495,82 @@ mod tests {
             .unwrap();
         }
 
-        let results = streaming_ingest(&source, &cas, IngestMode::SolidTier2, Some(4));
+        let progress = Arc::new(IngestProgress::default());
+        let results = streaming_ingest(
+            &source,
+            &cas,
+            IngestMode::SolidTier2,
+            Some(4),
+            None,
+            Some(progress.clone()),
+        );
 
         assert_eq!(results.len(), 100);
         assert!(results.iter().all(|r| r.is_ok()));
+        assert!(progress.scanned.load(Ordering::Relaxed));
+        assert_eq!(progress.found.load(Ordering::Relaxed), 100);
+        assert_eq!(progress.done.load(Ordering::Relaxed), 100);
+        let bytes: u64 = (0..100)
+            .map(|i| format!("content {}", i).len() as u64)
+            .sum();
+        assert_eq!(progress.bytes.load(Ordering::Relaxed), bytes);
+    }
+
+    #[test]
+    fn test_streaming_ingest_skips_ignored_paths() {
+        let temp = tempdir().unwrap();
+        let source = temp.path().join("source");
+        let cas = temp.path().join("cas");
+        fs::create_dir_all(source.join("target/debug")).unwrap();
+        fs::create_dir_all(&cas).unwrap();
+        fs::write(source.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(source.join("notes.log"), "log").unwrap();
+        fs::write(source.join("target/debug/app"), "elf").unwrap();
+
+        let ignore: IgnoreFn = Arc::new(|path: &Path, is_dir: bool| {
+            (is_dir && path.ends_with("target")) || path.extension().is_some_and(|ext| ext == "log")
+        });
+        let results = streaming_ingest(
+            &source,
+            &cas,
+            IngestMode::SolidTier2,
+            Some(1),
+            Some(ignore),
+            None,
+        );
+
+        let ingested: Vec<_> = results
+            .into_iter()
+            .map(|r| r.unwrap().source_path)
+            .collect();
+        assert_eq!(ingested, [source.join("main.rs")]);
+    }
+
+    #[test]
+    fn test_ingest_symlinks() {
+        let temp = tempdir().unwrap();
+        let source = temp.path().join("source");
+        let cas = temp.path().join("cas");
+        fs::create_dir_all(source.join("lib")).unwrap();
+        fs::create_dir_all(source.join(".git")).unwrap();
+        fs::write(source.join("lib/libz.so.1"), b"elf").unwrap();
+        std::os::unix::fs::symlink("libz.so.1", source.join("lib/libz.so")).unwrap();
+        std::os::unix::fs::symlink("/nowhere", source.join("dangling")).unwrap();
+        std::os::unix::fs::symlink("x", source.join(".git/HEAD.lnk")).unwrap();
+        // As left behind by a Tier-1 ingest
+        std::os::unix::fs::symlink(cas.join("blob"), source.join("ingested.txt")).unwrap();
+
+        let mut links: Vec<_> = ingest_symlinks(&source, &cas, None)
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect();
+        links.sort_by(|a, b| a.source_path.cmp(&b.source_path));
+        let targets: Vec<_> = links.iter().map(|l| l.target.as_slice()).collect();
+        assert_eq!(targets, [&b"/nowhere"[..], &b"libz.so.1"[..]]);
+
+        // Targets are readable from the CAS by hash
+        let store = crate::CasStore::new(&cas).unwrap();
+        let hash = *blake3::hash(b"libz.so.1").as_bytes();
+        assert_eq!(store.get(&hash).unwrap(), b"libz.so.1");
     }
 }
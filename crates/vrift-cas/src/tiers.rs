@@ -0,0 +1,179 @@
+//! # Storage Tiers
+//!
+//! Extra CAS roots that blobs are routed to by size or age, e.g. small,
+//! hot blobs on local NVMe and large or cold ones on a network share.
+//!
+//! Tiers are configured in the primary root as `<cas_root>/tiers.json`:
+//!
+//! ```json
+//! { "version": 1, "tiers": [
+//!     { "name": "nas", "root": "/mnt/nas/vrift", "min_size": 16777216 },
+//!     { "name": "archive", "root": "/mnt/archive/vrift", "min_age_days": 90 }
+//! ] }
+//! ```
+//!
+//! A blob belongs to the first tier whose rules it meets (all of a tier's
+//! rules must hold) and to the primary root otherwise. A tier without rules
+//! never receives blobs but is still searched, which is how a read-only
+//! shared CAS is attached. Lookups try the primary root first, then each
+//! tier in order; [`CasStore::rebalance`](crate::CasStore::rebalance) moves
+//! blobs whose placement has changed.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// File name of the tier list inside the primary CAS root
+pub const TIERS_FILE: &str = "tiers.json";
+
+/// Name the primary root goes by in listings
+pub const PRIMARY_TIER: &str = "primary";
+
+const TIERS_VERSION: u32 = 1;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// One extra CAS root and the blobs it should hold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CasTier {
+    pub name: String,
+    pub root: PathBuf,
+    /// Only blobs of at least this many bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    /// Only blobs not read or written for at least this many days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_age_days: Option<u64>,
+}
+
+impl CasTier {
+    /// Whether the tier has any routing rule (else it is lookup-only)
+    pub fn routes(&self) -> bool {
+        self.min_size.is_some() || self.min_age_days.is_some()
+    }
+
+    /// Whether a blob of `size` bytes, last used `age` ago, belongs here
+    pub fn accepts(&self, size: u64, age: Duration) -> bool {
+        self.routes()
+            && self.min_size.is_none_or(|min| size >= min)
+            && self
+                .min_age_days
+                .is_none_or(|days| age >= Duration::from_secs(days * SECS_PER_DAY))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TierFile {
+    version: u32,
+    tiers: Vec<CasTier>,
+}
+
+/// Tier list of one primary CAS root
+#[derive(Debug)]
+pub struct TierSet {
+    path: PathBuf,
+    file: TierFile,
+}
+
+impl TierSet {
+    /// Load the tiers of the CAS at `cas_root` (none if never configured)
+    pub fn load(cas_root: &Path) -> io::Result<Self> {
+        let path = cas_root.join(TIERS_FILE);
+        let file = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => TierFile {
+                version: TIERS_VERSION,
+                tiers: Vec::new(),
+            },
+            Err(e) => return Err(e),
+        };
+        Ok(Self { path, file })
+    }
+
+    /// Write the tier list (temp file + rename)
+    pub fn save(&self) -> io::Result<()> {
+        let data = serde_json::to_vec_pretty(&self.file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// Append a tier. Returns false if the name is already taken.
+    pub fn add(&mut self, tier: CasTier) -> bool {
+        if tier.name == PRIMARY_TIER || self.get(&tier.name).is_some() {
+            return false;
+        }
+        self.file.tiers.push(tier);
+        true
+    }
+
+    /// Drop a tier from the list
+    pub fn remove(&mut self, name: &str) -> Option<CasTier> {
+        let at = self.file.tiers.iter().position(|t| t.name == name)?;
+        Some(self.file.tiers.remove(at))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CasTier> {
+        self.file.tiers.iter().find(|t| t.name == name)
+    }
+
+    /// Tiers in routing order
+    pub fn tiers(&self) -> &[CasTier] {
+        &self.file.tiers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tier(name: &str, min_size: Option<u64>, min_age_days: Option<u64>) -> CasTier {
+        CasTier {
+            name: name.to_string(),
+            root: PathBuf::from(format!("/mnt/{}", name)),
+            min_size,
+            min_age_days,
+        }
+    }
+
+    #[test]
+    fn test_tier_rules() {
+        let day = Duration::from_secs(SECS_PER_DAY);
+
+        let big = tier("big", Some(1024), None);
+        assert!(big.accepts(1024, Duration::ZERO));
+        assert!(!big.accepts(1023, day * 365));
+
+        let cold_big = tier("cold", Some(1024), Some(30));
+        assert!(cold_big.accepts(4096, day * 30));
+        assert!(!cold_big.accepts(4096, day * 29));
+        assert!(!cold_big.accepts(10, day * 60));
+
+        // No rules: searched, never routed to
+        assert!(!tier("shared", None, None).accepts(u64::MAX, day * 1000));
+    }
+
+    #[test]
+    fn test_tier_set_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut set = TierSet::load(dir.path()).unwrap();
+        assert!(set.tiers().is_empty());
+        assert!(set.add(tier("nas", Some(1 << 20), None)));
+        assert!(set.add(tier("archive", None, Some(90))));
+        assert!(!set.add(tier("nas", None, None)));
+        assert!(!set.add(tier(PRIMARY_TIER, Some(1), None)));
+        set.save().unwrap();
+
+        let mut set = TierSet::load(dir.path()).unwrap();
+        let names: Vec<_> = set.tiers().iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["nas", "archive"]);
+        assert_eq!(set.remove("nas").unwrap().min_size, Some(1 << 20));
+        assert!(set.remove("nas").is_none());
+    }
+}
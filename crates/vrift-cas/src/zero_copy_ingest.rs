@@ -641,7 +641,10 @@ fn lock_with_retry(mut file: File, lock_type: FlockArg) -> Result<Flock<File>> {
 /// Tiered hashing strategy for optimal performance:
 /// - Small files (< 16KB): Direct read() avoids mmap setup overhead (~10µs)
 /// - Medium/Large files (>= 16KB): mmap for zero-copy access
+/// - Huge files (>= 1GB): streamed, so multi-GB artifacts don't need the
+///   address space or pin the page cache all at once
 const SMALL_FILE_THRESHOLD: u64 = 16 * 1024; // 16KB
+const HUGE_FILE_THRESHOLD: u64 = 1024 * 1024 * 1024; // 1GB
 
 fn tiered_hash(file: &File, size: u64) -> Result<Blake3Hash> {
     if size < SMALL_FILE_THRESHOLD {
@@ -650,6 +653,10 @@ fn tiered_hash(file: &File, size: u64) -> Result<Blake3Hash> {
         use std::io::Read;
         (&*file).read_exact(&mut buf)?;
         Ok(*blake3::hash(&buf).as_bytes())
+    } else if size >= HUGE_FILE_THRESHOLD {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_reader(file)?;
+        Ok(*hasher.finalize().as_bytes())
     } else {
         // Medium/Large file: mmap for zero-copy
         // SAFETY: mmap requires a valid file descriptor
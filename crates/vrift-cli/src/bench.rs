@@ -0,0 +1,385 @@
+//! # Overhead Benchmark
+//!
+//! `vrift bench` runs standardized workloads against the same tree twice — once
+//! natively and once with the inception layer preloaded — and reports the
+//! relative overhead. Each run happens in a child process (`vrift bench --worker`)
+//! because the shim only interposes the process it is loaded into.
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Project directory to benchmark (default: current directory)
+    #[arg(value_name = "DIR")]
+    directory: Option<PathBuf>,
+
+    /// Workloads to run, comma separated (default: stat,read,readdir)
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    workloads: Vec<Workload>,
+
+    /// Rounds per workload; the fastest round is reported
+    #[arg(short = 'n', long, default_value = "3")]
+    iterations: u32,
+
+    /// Maximum number of files touched by the stat and read workloads
+    #[arg(long, default_value = "10000")]
+    max_files: usize,
+
+    /// Exit non-zero if any workload exceeds this overhead percentage (for CI)
+    #[arg(long)]
+    max_overhead: Option<f64>,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Internal: run one workload in-process and print the result
+    #[arg(long, hide = true)]
+    worker: Option<Workload>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Workload {
+    /// stat() every file in the tree
+    Stat,
+    /// open() and read every file in the tree
+    Read,
+    /// Walk every directory with readdir()
+    Readdir,
+    /// Incremental `cargo build` after touching one source file
+    Cargo,
+}
+
+impl Workload {
+    fn name(self) -> &'static str {
+        match self {
+            Workload::Stat => "stat",
+            Workload::Read => "read",
+            Workload::Readdir => "readdir",
+            Workload::Cargo => "cargo",
+        }
+    }
+}
+
+/// Fastest round of one workload in one mode
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    ops: u64,
+    elapsed: Duration,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct BenchResult {
+    workload: &'static str,
+    ops: u64,
+    baseline_ms: f64,
+    shim_ms: f64,
+    overhead_pct: f64,
+}
+
+/// Relative overhead of `shim` over `baseline` in percent
+fn overhead_pct(baseline: Duration, shim: Duration) -> f64 {
+    let base = baseline.as_secs_f64();
+    if base == 0.0 {
+        return 0.0;
+    }
+    (shim.as_secs_f64() - base) / base * 100.0
+}
+
+pub async fn run(args: BenchArgs) -> Result<()> {
+    let dir = args
+        .directory
+        .clone()
+        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Directory not found: {}", dir.display()))?;
+
+    if let Some(workload) = args.worker {
+        let sample = run_worker(workload, &dir, args.iterations, args.max_files)?;
+        println!("{} {}", sample.ops, sample.elapsed.as_nanos());
+        return Ok(());
+    }
+
+    let workloads = if args.workloads.is_empty() {
+        vec![Workload::Stat, Workload::Read, Workload::Readdir]
+    } else {
+        args.workloads.clone()
+    };
+
+    let shim_env = shim_environment(&dir).await?;
+
+    if !args.json {
+        println!();
+        println!("⏱️  VRift Overhead Benchmark");
+        println!("   Tree:       {}", dir.display());
+        println!("   Iterations: {}", args.iterations);
+        println!();
+    }
+
+    let mut results = Vec::new();
+    for workload in workloads {
+        let (baseline, shim) = if workload == Workload::Cargo {
+            (
+                run_cargo(&dir, args.iterations, None)?,
+                run_cargo(&dir, args.iterations, Some(&shim_env))?,
+            )
+        } else {
+            (
+                spawn_worker(workload, &dir, &args, None)?,
+                spawn_worker(workload, &dir, &args, Some(&shim_env))?,
+            )
+        };
+
+        results.push(BenchResult {
+            workload: workload.name(),
+            ops: baseline.ops,
+            baseline_ms: baseline.elapsed.as_secs_f64() * 1000.0,
+            shim_ms: shim.elapsed.as_secs_f64() * 1000.0,
+            overhead_pct: overhead_pct(baseline.elapsed, shim.elapsed),
+        });
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        println!(
+            "   {:<10} {:>10} {:>12} {:>12} {:>10}",
+            "Workload", "Ops", "Native", "Shim", "Overhead"
+        );
+        for r in &results {
+            println!(
+                "   {:<10} {:>10} {:>10.1}ms {:>10.1}ms {:>9.1}%",
+                r.workload, r.ops, r.baseline_ms, r.shim_ms, r.overhead_pct
+            );
+        }
+        println!();
+    }
+
+    if let Some(limit) = args.max_overhead {
+        let over: Vec<_> = results.iter().filter(|r| r.overhead_pct > limit).collect();
+        if !over.is_empty() {
+            let names: Vec<_> = over.iter().map(|r| r.workload).collect();
+            anyhow::bail!("Overhead above {:.1}% for: {}", limit, names.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Environment that activates the inception layer for a child process
+async fn shim_environment(dir: &Path) -> Result<Vec<(String, String)>> {
+    let inception_path = crate::inception::find_inception_library(dir)?;
+    let cfg = vrift_config::Config::load_for_project(dir).unwrap_or_else(|e| {
+        eprintln!("Warning: Config load failed: {}. Using defaults.", e);
+        vrift_config::Config::default()
+    });
+
+    let mut env = cfg.shim_env();
+    env.push(("VRIFT_INCEPTION".to_string(), "1".to_string()));
+
+    // Same zero-RPC setup as `vrift inception`
+    if let Ok(conn) = crate::daemon::connect_to_daemon(dir).await {
+        if !conn.vdird_socket.is_empty() {
+            env.push(("VRIFT_VDIRD_SOCKET".to_string(), conn.vdird_socket));
+        }
+        if !conn.vdir_mmap_path.is_empty() {
+            env.push(("VRIFT_VDIR_MMAP".to_string(), conn.vdir_mmap_path));
+        }
+    }
+
+    let lib = inception_path.to_string_lossy().to_string();
+    #[cfg(target_os = "macos")]
+    {
+        env.push(("DYLD_INSERT_LIBRARIES".to_string(), lib));
+        env.push(("DYLD_FORCE_FLAT_NAMESPACE".to_string(), "1".to_string()));
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        env.push(("LD_PRELOAD".to_string(), lib));
+    }
+
+    Ok(env)
+}
+
+fn spawn_worker(
+    workload: Workload,
+    dir: &Path,
+    args: &BenchArgs,
+    env: Option<&[(String, String)]>,
+) -> Result<Sample> {
+    let exe = std::env::current_exe().context("Failed to locate vrift binary")?;
+    let mut cmd = Command::new(exe);
+    cmd.arg("bench")
+        .arg(dir)
+        .arg("--worker")
+        .arg(workload.name())
+        .arg("--iterations")
+        .arg(args.iterations.to_string())
+        .arg("--max-files")
+        .arg(args.max_files.to_string());
+    for (key, value) in env.unwrap_or_default() {
+        cmd.env(key, value);
+    }
+
+    let output = cmd.output().context("Failed to spawn bench worker")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Bench worker '{}' failed: {}",
+            workload.name(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_worker_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the `<ops> <elapsed_ns>` line printed by a worker
+fn parse_worker_output(stdout: &str) -> Result<Sample> {
+    let line = stdout
+        .lines()
+        .last()
+        .ok_or_else(|| anyhow::anyhow!("Bench worker produced no output"))?;
+    let mut parts = line.split_whitespace();
+    let ops = parts.next().and_then(|s| s.parse::<u64>().ok());
+    let nanos = parts.next().and_then(|s| s.parse::<u64>().ok());
+    match (ops, nanos) {
+        (Some(ops), Some(nanos)) => Ok(Sample {
+            ops,
+            elapsed: Duration::from_nanos(nanos),
+        }),
+        _ => anyhow::bail!("Malformed bench worker output: {}", line),
+    }
+}
+
+/// Run one workload in this process and return the fastest round
+fn run_worker(workload: Workload, dir: &Path, iterations: u32, max_files: usize) -> Result<Sample> {
+    // Collected up front so the readdir cost isn't charged to stat/read
+    let files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| !e.path().components().any(|c| c.as_os_str() == ".vrift"))
+        .take(max_files)
+        .map(|e| e.into_path())
+        .collect();
+
+    let mut best: Option<Sample> = None;
+    for _ in 0..iterations.max(1) {
+        let start = Instant::now();
+        let ops = match workload {
+            Workload::Stat => {
+                for f in &files {
+                    let _ = std::fs::symlink_metadata(f);
+                }
+                files.len() as u64
+            }
+            Workload::Read => {
+                let mut buf = Vec::new();
+                for f in &files {
+                    buf.clear();
+                    if let Ok(mut file) = std::fs::File::open(f) {
+                        let _ = std::io::Read::read_to_end(&mut file, &mut buf);
+                    }
+                }
+                files.len() as u64
+            }
+            Workload::Readdir => {
+                WalkDir::new(dir).into_iter().filter_map(|e| e.ok()).count() as u64
+            }
+            Workload::Cargo => anyhow::bail!("cargo workload runs in the parent process"),
+        };
+        let sample = Sample {
+            ops,
+            elapsed: start.elapsed(),
+        };
+        if best.is_none_or(|b| sample.elapsed < b.elapsed) {
+            best = Some(sample);
+        }
+    }
+
+    best.ok_or_else(|| anyhow::anyhow!("No iterations ran"))
+}
+
+/// Incremental `cargo build`: touch one source file, rebuild, keep the fastest round
+fn run_cargo(dir: &Path, iterations: u32, env: Option<&[(String, String)]>) -> Result<Sample> {
+    if !dir.join("Cargo.toml").exists() {
+        anyhow::bail!("cargo workload needs a Cargo.toml in {}", dir.display());
+    }
+    let touch_target = WalkDir::new(dir.join("src"))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .find(|e| e.path().extension().is_some_and(|ext| ext == "rs"))
+        .map(|e| e.into_path())
+        .ok_or_else(|| anyhow::anyhow!("No .rs file under {}/src", dir.display()))?;
+
+    let build = |env: Option<&[(String, String)]>| -> Result<Duration> {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("build").arg("--quiet").current_dir(dir);
+        for (key, value) in env.unwrap_or_default() {
+            cmd.env(key, value);
+        }
+        let start = Instant::now();
+        let status = cmd.status().context("Failed to run cargo")?;
+        if !status.success() {
+            anyhow::bail!("cargo build failed in {}", dir.display());
+        }
+        Ok(start.elapsed())
+    };
+
+    // Warm build so every measured round is incremental
+    build(None)?;
+
+    let mut best: Option<Duration> = None;
+    for _ in 0..iterations.max(1) {
+        std::fs::File::options()
+            .append(true)
+            .open(&touch_target)?
+            .set_modified(std::time::SystemTime::now())?;
+        let elapsed = build(env)?;
+        best = Some(best.map_or(elapsed, |b| b.min(elapsed)));
+    }
+
+    Ok(Sample {
+        ops: iterations.max(1) as u64,
+        elapsed: best.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overhead_pct() {
+        let base = Duration::from_millis(100);
+        assert!((overhead_pct(base, Duration::from_millis(150)) - 50.0).abs() < 1e-9);
+        assert!((overhead_pct(base, Duration::from_millis(80)) + 20.0).abs() < 1e-9);
+        assert_eq!(overhead_pct(Duration::ZERO, base), 0.0);
+    }
+
+    #[test]
+    fn test_parse_worker_output() {
+        let sample = parse_worker_output("1200 3500000\n").unwrap();
+        assert_eq!(sample.ops, 1200);
+        assert_eq!(sample.elapsed, Duration::from_nanos(3_500_000));
+        assert!(parse_worker_output("garbage").is_err());
+        assert!(parse_worker_output("").is_err());
+    }
+
+    #[test]
+    fn test_worker_stat_counts_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..5 {
+            std::fs::write(dir.path().join(format!("f{}.txt", i)), b"x").unwrap();
+        }
+        let sample = run_worker(Workload::Stat, dir.path(), 2, 3).unwrap();
+        assert_eq!(sample.ops, 3);
+        let sample = run_worker(Workload::Read, dir.path(), 1, 100).unwrap();
+        assert_eq!(sample.ops, 5);
+    }
+}
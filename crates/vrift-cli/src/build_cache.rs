@@ -0,0 +1,503 @@
+//! # Build Cache
+//!
+//! `vrift cache run -- <command>` skips a build step whose inputs were
+//! built before. vdir_d hashes the manifest entries the step reads
+//! (`--input`, default everything) minus what it writes (`--output`,
+//! default `target`), together with the command line. On a hit, the
+//! outputs recorded under that key are checked out from the CAS and the
+//! command doesn't run. On a miss, the command runs, and if it succeeds its
+//! outputs are stored under the key.
+//!
+//! The key uses content, not mtimes, so a fresh checkout of the same
+//! sources hits. It is computed from the manifest as shims currently see
+//! it, VDir overlay included, so writes and deletes made through the
+//! inception layer count right away; edits made outside it only count
+//! once they've been ingested.
+//!
+//! An entry is a flat manifest of the outputs, named by key:
+//!
+//! - locally in `~/.vrift/build-cache/`, with the blobs in the CAS and the
+//!   entry registered for GC;
+//! - remotely in a directory shared between machines (an NFS or SMB mount,
+//!   a synced bucket), given by `--remote` or `VRIFT_BUILD_CACHE_REMOTE`,
+//!   as `entries/<key>.manifest` and `blobs/<aa>/<hash>`. Blobs fetched
+//!   from it are re-hashed before use, and an entry is only written once
+//!   all its blobs are there.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use serde::{Deserialize, Serialize};
+use vrift_cas::{Blake3Hash, CasStore};
+use vrift_config::path::normalize_for_ipc;
+use vrift_manifest::Manifest;
+
+use crate::checkout::{checkout_tree, CheckoutOptions, Placement};
+use crate::daemon::DaemonContext;
+
+/// Environment fallback for `--remote`
+const REMOTE_ENV: &str = "VRIFT_BUILD_CACHE_REMOTE";
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommands {
+    /// Run a build step, or restore its outputs if these inputs were built before
+    Run(CacheRunArgs),
+    /// Show cached entries, hit rate and the remote backend
+    Stats {
+        /// Shared cache directory (default: $VRIFT_BUILD_CACHE_REMOTE)
+        #[arg(long, value_name = "DIR")]
+        remote: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct CacheRunArgs {
+    /// Project path the step reads (repeatable; default: the whole manifest)
+    #[arg(short, long = "input", value_name = "PATH")]
+    inputs: Vec<String>,
+
+    /// Project path the step writes (repeatable); restored on a hit, never part of the key
+    #[arg(short, long = "output", value_name = "PATH", default_values_t = ["target".to_string()])]
+    outputs: Vec<String>,
+
+    /// Shared cache directory (default: $VRIFT_BUILD_CACHE_REMOTE)
+    #[arg(long, value_name = "DIR")]
+    remote: Option<PathBuf>,
+
+    /// Project directory (default: current directory)
+    #[arg(short, long, value_name = "DIR")]
+    directory: Option<PathBuf>,
+
+    /// The build step, run from the project directory
+    #[arg(
+        value_name = "COMMAND",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    command: Vec<String>,
+}
+
+/// Where cached outputs are kept
+pub trait CacheBackend {
+    /// The outputs stored under `key`, with all their blobs now in `cas`
+    fn fetch(&self, key: &str, cas: &CasStore) -> Result<Option<Manifest>>;
+    /// Store `outputs` (whose blobs are in `cas`) under `key`
+    fn store(&self, key: &str, outputs: &Manifest, cas: &CasStore) -> Result<()>;
+    /// Number of entries held
+    fn entries(&self) -> Result<usize>;
+}
+
+/// Blobs an entry needs: file contents and symlink targets
+fn blobs(outputs: &Manifest) -> impl Iterator<Item = &Blake3Hash> {
+    outputs
+        .iter()
+        .filter(|(_, e)| !e.is_dir() && !e.is_whiteout())
+        .map(|(_, e)| &e.content_hash)
+}
+
+/// Write `path` through a temporary file in the same directory, so readers
+/// (possibly on other machines) never see it half-written
+fn write_atomic(path: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let dir = path.parent().context("Cache path has no parent")?;
+    fs::create_dir_all(dir)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    write(tmp.as_file_mut())?;
+    tmp.persist(path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+fn write_manifest(path: &Path, manifest: &Manifest) -> Result<()> {
+    let data = manifest.to_bytes()?;
+    write_atomic(path, |file| Ok(file.write_all(&data)?))
+}
+
+fn count_manifests(dir: &Path) -> Result<usize> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|x| x == "manifest"))
+            .count()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// `~/.vrift/build-cache/`, next to the local CAS
+pub struct LocalCache {
+    dir: PathBuf,
+    /// Project the entries are registered under for GC
+    project_root: PathBuf,
+}
+
+impl LocalCache {
+    pub fn new(dir: PathBuf, project_root: &Path) -> Self {
+        Self {
+            dir,
+            project_root: project_root.to_path_buf(),
+        }
+    }
+
+    pub fn default_dir() -> Result<PathBuf> {
+        let home = dirs::home_dir().context("Failed to get home directory")?;
+        Ok(home.join(".vrift").join("build-cache"))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.manifest", key))
+    }
+}
+
+impl CacheBackend for LocalCache {
+    fn fetch(&self, key: &str, cas: &CasStore) -> Result<Option<Manifest>> {
+        let path = self.entry_path(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let outputs = Manifest::load(&path)?;
+        // An entry whose blobs were garbage-collected counts as a miss
+        let complete = blobs(&outputs).all(|hash| cas.exists(hash));
+        Ok(complete.then_some(outputs))
+    }
+
+    fn store(&self, key: &str, outputs: &Manifest, _cas: &CasStore) -> Result<()> {
+        let path = self.entry_path(key);
+        write_manifest(&path, outputs)?;
+        crate::registry::ManifestRegistry::register_or_warn(&path, &self.project_root);
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<usize> {
+        count_manifests(&self.dir)
+    }
+}
+
+/// A cache directory shared between machines
+pub struct RemoteDir {
+    root: PathBuf,
+}
+
+impl RemoteDir {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// `--remote`, else `$VRIFT_BUILD_CACHE_REMOTE`
+    pub fn from_args(remote: Option<PathBuf>) -> Option<Self> {
+        remote
+            .or_else(|| std::env::var_os(REMOTE_ENV).map(PathBuf::from))
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(Self::new)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.root.join("entries").join(format!("{}.manifest", key))
+    }
+
+    fn blob_path(&self, hash: &Blake3Hash) -> PathBuf {
+        let hex = CasStore::hash_to_hex(hash);
+        self.root.join("blobs").join(&hex[..2]).join(hex)
+    }
+}
+
+impl CacheBackend for RemoteDir {
+    fn fetch(&self, key: &str, cas: &CasStore) -> Result<Option<Manifest>> {
+        let path = self.entry_path(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let outputs = Manifest::load(&path)?;
+        for hash in blobs(&outputs) {
+            if cas.exists(hash) {
+                continue;
+            }
+            let blob = self.blob_path(hash);
+            let file = match File::open(&blob) {
+                Ok(f) => f,
+                // Pruned from the share: treat the entry as gone
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to read {}", blob.display()))
+                }
+            };
+            let stored = cas.store_reader(file)?;
+            if &stored != hash {
+                // Leave the bad copy in the local CAS unreferenced; GC drops it
+                anyhow::bail!("Corrupt blob in remote cache: {}", blob.display());
+            }
+        }
+        Ok(Some(outputs))
+    }
+
+    fn store(&self, key: &str, outputs: &Manifest, cas: &CasStore) -> Result<()> {
+        for hash in blobs(outputs) {
+            let blob = self.blob_path(hash);
+            if blob.exists() {
+                continue;
+            }
+            write_atomic(&blob, |file| {
+                io::copy(&mut cas.get_reader(hash)?, file)?;
+                Ok(())
+            })?;
+        }
+        // Last, so no machine sees an entry before its blobs
+        write_manifest(&self.entry_path(key), outputs)
+    }
+
+    fn entries(&self) -> Result<usize> {
+        count_manifests(&self.root.join("entries"))
+    }
+}
+
+/// Lookup counters kept beside the local entries
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CacheCounters {
+    pub hits: u64,
+    /// Hits served by the remote backend (included in `hits`)
+    pub remote_hits: u64,
+    pub misses: u64,
+    /// Outputs stored after a miss
+    pub stored: u64,
+}
+
+impl CacheCounters {
+    fn path(dir: &Path) -> PathBuf {
+        dir.join("stats.json")
+    }
+
+    pub fn load(dir: &Path) -> Self {
+        fs::read(Self::path(dir))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        write_atomic(&Self::path(dir), |file| Ok(file.write_all(&data)?))
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.hits as f64 * 100.0 / total as f64
+    }
+}
+
+pub async fn run(command: CacheCommands, cas_root: &Path) -> Result<()> {
+    match command {
+        CacheCommands::Run(args) => cache_run(args, cas_root).await,
+        CacheCommands::Stats { remote } => stats(RemoteDir::from_args(remote)),
+    }
+}
+
+async fn cache_run(args: CacheRunArgs, cas_root: &Path) -> Result<()> {
+    let directory = match args.directory {
+        Some(dir) => dir,
+        None => std::env::current_dir().context("Failed to get current directory")?,
+    };
+    let root = normalize_for_ipc(&directory)?;
+    let normalize = |paths: &[String]| -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| crate::manifest_edit::normalize_key(p))
+            .collect()
+    };
+    let (inputs, outputs) = (normalize(&args.inputs), normalize(&args.outputs));
+
+    let (_conn, mut vdird) = crate::daemon::connect_to_vdird(&root).await?;
+    let (key, covered) = vdird
+        .build_cache_key(&inputs, &outputs, &args.command)
+        .await
+        .daemon_context("Build cache key failed")?;
+    let key = CasStore::hash_to_hex(&key);
+    let short = &key[..16];
+
+    let cas = CasStore::new(cas_root)
+        .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+    let local = LocalCache::new(LocalCache::default_dir()?, &root);
+    let remote = RemoteDir::from_args(args.remote);
+    let mut counters = CacheCounters::load(&local.dir);
+
+    let mut source = "local";
+    let mut found = local.fetch(&key, &cas)?;
+    if found.is_none() {
+        if let Some(remote) = &remote {
+            found = remote.fetch(&key, &cas)?;
+            if let Some(outputs) = &found {
+                source = "remote";
+                local.store(&key, outputs, &cas)?;
+            }
+        }
+    }
+
+    if let Some(cached) = found {
+        let options = CheckoutOptions {
+            placement: Placement::Clone,
+            overwrite: true,
+            clean: false,
+        };
+        let summary = checkout_tree(&cached, &cas, &root, &options)?;
+        counters.hits += 1;
+        if source == "remote" {
+            counters.remote_hits += 1;
+        }
+        counters.save(&local.dir)?;
+        println!(
+            "✅ Build cache hit ({}) {}: restored {} files into {}, skipped `{}`",
+            source,
+            short,
+            summary.files + summary.symlinks,
+            outputs.join(", "),
+            args.command.join(" ")
+        );
+        return Ok(());
+    }
+
+    println!(
+        "🔍 Build cache miss {} ({} inputs); running `{}`",
+        short,
+        covered,
+        args.command.join(" ")
+    );
+    let mut cmd = std::process::Command::new(&args.command[0]);
+    cmd.args(&args.command[1..]).current_dir(&root);
+    let status = tokio::task::block_in_place(|| crate::child::run(&mut cmd))
+        .with_context(|| format!("Failed to execute: {}", args.command[0]))?;
+    counters.misses += 1;
+    if !status.success() {
+        counters.save(&local.dir)?;
+        crate::child::exit_like(status);
+    }
+
+    let recorded = record_outputs(&cas, &root, &outputs)?;
+    local.store(&key, &recorded, &cas)?;
+    counters.stored += 1;
+    counters.save(&local.dir)?;
+    if let Some(remote) = &remote {
+        // The build succeeded; a share that's down shouldn't fail it
+        if let Err(e) = remote.store(&key, &recorded, &cas) {
+            eprintln!("⚠️  Could not push to remote cache: {:#}", e);
+        }
+    }
+    println!("💾 Stored {} outputs under {}", recorded.len(), short);
+    Ok(())
+}
+
+/// Every entry under the output paths, with contents stored in `cas`
+pub fn record_outputs(cas: &CasStore, root: &Path, outputs: &[String]) -> Result<Manifest> {
+    let mut manifest = Manifest::new();
+    for key in outputs {
+        let dir = root.join(crate::hydrate::key_to_rel_path(key)?);
+        for (path, entry) in crate::cargo::capture_dir(cas, root, &dir, &Manifest::new())? {
+            manifest.insert(&path, entry);
+        }
+    }
+    Ok(manifest)
+}
+
+fn stats(remote: Option<RemoteDir>) -> Result<()> {
+    let dir = LocalCache::default_dir()?;
+    let local = LocalCache::new(dir.clone(), Path::new("."));
+    let counters = CacheCounters::load(&dir);
+
+    println!("Build Cache");
+    println!("===========");
+    println!(
+        "  Local:      {} ({} entries)",
+        dir.display(),
+        local.entries()?
+    );
+    println!(
+        "  Lookups:    {} hits ({} remote), {} misses ({:.1}% hit rate)",
+        counters.hits,
+        counters.remote_hits,
+        counters.misses,
+        counters.hit_rate()
+    );
+    println!("  Stored:     {}", counters.stored);
+    match remote {
+        Some(remote) => println!(
+            "  Remote:     {} ({} entries)",
+            remote.root.display(),
+            remote.entries()?
+        ),
+        None => println!("  Remote:     (none; set --remote or {})", REMOTE_ENV),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_outputs(root: &Path) {
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("target/debug/app"), b"binary").unwrap();
+        fs::write(root.join("target/debug/app.d"), b"deps").unwrap();
+    }
+
+    #[test]
+    fn test_remote_round_trip_between_machines() {
+        let tmp = tempfile::tempdir().unwrap();
+        let remote = RemoteDir::new(tmp.path().join("share"));
+
+        // Machine A builds and pushes
+        let root_a = tmp.path().join("a");
+        build_outputs(&root_a);
+        let cas_a = CasStore::new(tmp.path().join("cas-a")).unwrap();
+        let outputs = record_outputs(&cas_a, &root_a, &["/target".to_string()]).unwrap();
+        assert_eq!(outputs.len(), 4);
+        remote.store("k1", &outputs, &cas_a).unwrap();
+        assert_eq!(remote.entries().unwrap(), 1);
+
+        // Machine B, with an empty CAS, pulls and checks out
+        let cas_b = CasStore::new(tmp.path().join("cas-b")).unwrap();
+        assert!(remote.fetch("k2", &cas_b).unwrap().is_none());
+        let fetched = remote.fetch("k1", &cas_b).unwrap().unwrap();
+        let root_b = tmp.path().join("b");
+        let options = CheckoutOptions {
+            placement: Placement::Clone,
+            overwrite: true,
+            clean: false,
+        };
+        checkout_tree(&fetched, &cas_b, &root_b, &options).unwrap();
+        assert_eq!(
+            fs::read(root_b.join("target/debug/app")).unwrap(),
+            b"binary"
+        );
+
+        // A tampered blob on the share is refused
+        let cas_c = CasStore::new(tmp.path().join("cas-c")).unwrap();
+        let app = outputs.get("/target/debug/app").unwrap();
+        fs::write(remote.blob_path(&app.content_hash), b"evil").unwrap();
+        assert!(remote.fetch("k1", &cas_c).is_err());
+    }
+
+    #[test]
+    fn test_local_entry_needs_its_blobs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("ws");
+        build_outputs(&root);
+        let cas = CasStore::new(tmp.path().join("cas")).unwrap();
+        let local = LocalCache::new(tmp.path().join("cache"), &root);
+        let outputs = record_outputs(&cas, &root, &["/target".to_string()]).unwrap();
+        local.store("k1", &outputs, &cas).unwrap();
+        assert!(local.fetch("k1", &cas).unwrap().is_some());
+
+        let other = CasStore::new(tmp.path().join("other")).unwrap();
+        assert!(local.fetch("k1", &other).unwrap().is_none());
+
+        let mut counters = CacheCounters::load(&local.dir);
+        counters.hits = 3;
+        counters.misses = 1;
+        counters.save(&local.dir).unwrap();
+        assert_eq!(CacheCounters::load(&local.dir).hit_rate(), 75.0);
+    }
+}
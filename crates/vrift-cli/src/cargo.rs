@@ -0,0 +1,420 @@
+//! # Cargo Build Cache
+//!
+//! `vrift cargo <args>` runs cargo under the inception layer with the
+//! workspace's target directory served from VDir:
+//!
+//! 1. Artifacts recorded by earlier builds are upserted into the project
+//!    manifest with their original mtimes. Nothing is copied; reads come
+//!    from the CAS.
+//! 2. cargo runs under the shim. Restored artifacts it rewrites are CoW
+//!    copies that vdir_d reingests on close; new ones land on disk.
+//! 3. The target directory is recorded again (virtual entries as vdir_d
+//!    now has them, real files hashed into the CAS) and saved as the cache
+//!    for the next build.
+//!
+//! cargo decides freshness by comparing an artifact's mtime with its
+//! sources', so a restored build is only a no-op when the sources are older
+//! than the cached artifacts. Sources served from the manifest keep their
+//! ingest-time mtimes, and `vrift ingest-git` gives them the commit time.
+//!
+//! Caches are named (the workspace directory's name by default) and live
+//! in `~/.vrift/cargo-cache/`. Each one is registered for GC like any other
+//! manifest, so its blobs survive `vrift gc`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vrift_cas::CasStore;
+use vrift_config::path::{normalize_for_ipc, normalize_nonexistent};
+use vrift_ipc::client::DaemonClient;
+use vrift_manifest::{Manifest, VnodeEntry};
+
+use crate::daemon::DaemonContext;
+
+#[derive(Args, Debug)]
+pub struct CargoArgs {
+    /// Build cache to restore from and save to (default: the workspace directory's name)
+    #[arg(long, value_name = "NAME")]
+    cache: Option<String>,
+
+    /// Build from scratch, but still save this build's artifacts
+    #[arg(long)]
+    no_restore: bool,
+
+    /// Arguments for cargo (e.g. `build --release`)
+    #[arg(
+        value_name = "CARGO_ARGS",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    args: Vec<String>,
+}
+
+/// How this build's target directory compares with the cache it started from
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Cached artifacts the build left as they were
+    pub reused: usize,
+    /// Cached artifacts the build wrote again
+    pub rebuilt: usize,
+    /// Artifacts the cache didn't have
+    pub new: usize,
+}
+
+impl CacheStats {
+    /// Compare files and symlinks (directories say nothing about the build)
+    pub fn compare(cached: &Manifest, after: &BTreeMap<String, VnodeEntry>) -> Self {
+        let mut stats = Self::default();
+        for (key, entry) in after.iter().filter(|(_, e)| !e.is_dir()) {
+            match cached.get(key) {
+                Some(old) if old == entry => stats.reused += 1,
+                Some(_) => stats.rebuilt += 1,
+                None => stats.new += 1,
+            }
+        }
+        stats
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.reused + self.rebuilt + self.new;
+        if total == 0 {
+            return 0.0;
+        }
+        self.reused as f64 * 100.0 / total as f64
+    }
+}
+
+pub async fn run(args: CargoArgs, cas_root: &Path) -> Result<()> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let (root, target) = locate_workspace(&cargo)?;
+    let rel = target.strip_prefix(&root).map_err(|_| {
+        anyhow::anyhow!(
+            "Target directory {} is outside the workspace {}; it can't be served from VDir",
+            target.display(),
+            root.display()
+        )
+    })?;
+    let target_key = vrift_ipc::path_key_for(rel);
+
+    let cfg = vrift_config::Config::load_for_project(&root).unwrap_or_else(|e| {
+        eprintln!("Warning: Config load failed: {}. Using defaults.", e);
+        vrift_config::Config::default()
+    });
+    let passthrough = cfg.project.passthrough.iter().map(String::as_str);
+    if vrift_config::passthrough_matches(passthrough, &target_key) {
+        anyhow::bail!(
+            "{} is in [project] passthrough, so the shim would never serve it",
+            target_key
+        );
+    }
+
+    let name = match args.cache {
+        Some(name) => name,
+        None => root
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "workspace".to_string()),
+    };
+    let cache_file = cache_path(&name)?;
+    let cas = CasStore::new(cas_root)
+        .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+
+    let (conn, mut vdird) = crate::daemon::connect_to_vdird(&root).await?;
+
+    let cached = if !args.no_restore && cache_file.exists() {
+        Manifest::load(&cache_file)
+            .with_context(|| format!("Failed to read cache: {}", cache_file.display()))?
+    } else {
+        Manifest::new()
+    };
+    let restored = restore(&mut vdird, &cas, &root, &cached).await?;
+    if restored > 0 {
+        println!(
+            "📦 Restored {} entries under {} from cache '{}'",
+            restored, target_key, name
+        );
+    }
+
+    let started = std::time::Instant::now();
+    let status = tokio::task::block_in_place(|| {
+        let mut cmd = crate::shim::shimmed_command(&cargo, &root, &cfg, &conn)?;
+        cmd.args(&args.args);
+        crate::child::run(&mut cmd).with_context(|| format!("Failed to execute: {}", cargo))
+    })?;
+    let elapsed = started.elapsed();
+
+    let after = record(&mut vdird, &cas, &root, &target, &cached).await?;
+    let stats = CacheStats::compare(&cached, &after);
+    println!(
+        "⚡ Build cache '{}': {} reused, {} rebuilt, {} new ({:.0}% hit rate, {:.1}s)",
+        name,
+        stats.reused,
+        stats.rebuilt,
+        stats.new,
+        stats.hit_rate(),
+        elapsed.as_secs_f64()
+    );
+
+    if !status.success() {
+        // A failed build's target directory isn't worth restoring next time
+        crate::child::exit_like(status);
+    }
+    save(&cache_file, &root, after)?;
+    Ok(())
+}
+
+/// Workspace root and target directory, as cargo itself resolves them
+fn locate_workspace(cargo: &str) -> Result<(PathBuf, PathBuf)> {
+    let output = std::process::Command::new(cargo)
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to execute: {} metadata", cargo))?;
+    if !output.status.success() {
+        anyhow::bail!("`cargo metadata` failed; run vrift cargo inside a cargo workspace");
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .context("Failed to parse `cargo metadata` output")?;
+    let field = |name: &str| -> Result<PathBuf> {
+        metadata[name]
+            .as_str()
+            .map(PathBuf::from)
+            .with_context(|| format!("`cargo metadata` has no {}", name))
+    };
+    // The target directory doesn't exist before the first build
+    Ok((
+        normalize_for_ipc(field("workspace_root")?)?,
+        normalize_nonexistent(field("target_directory")?)?,
+    ))
+}
+
+/// `~/.vrift/cargo-cache/<name>.manifest`
+fn cache_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        anyhow::bail!("Invalid cache name: {:?}", name);
+    }
+    let home = dirs::home_dir().context("Failed to get home directory")?;
+    Ok(home
+        .join(".vrift")
+        .join("cargo-cache")
+        .join(format!("{}.manifest", name)))
+}
+
+/// Upsert the cached entries into the project manifest. Paths that exist
+/// on disk are left alone: a real file is newer than anything cached, and
+/// the shim would otherwise hide it. Directories are created on disk too,
+/// since the shim opens the real directory behind a directory entry and
+/// new artifacts are written into it.
+async fn restore(
+    vdird: &mut DaemonClient,
+    cas: &CasStore,
+    root: &Path,
+    cached: &Manifest,
+) -> Result<usize> {
+    let mut entries: Vec<(&str, &VnodeEntry)> = cached.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let mut restored = 0;
+    for (key, entry) in entries {
+        let path = root.join(crate::hydrate::key_to_rel_path(key)?);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+        } else if path.symlink_metadata().is_ok() || !cas.exists(&entry.content_hash) {
+            // Blobs can be gone if the cache was unregistered and collected
+            continue;
+        }
+        let current = vdird
+            .manifest_get(key)
+            .await
+            .daemon_context("Manifest lookup failed")?;
+        if current.as_ref() != Some(entry) {
+            vdird
+                .manifest_upsert(key, entry.clone())
+                .await
+                .daemon_context("Manifest upsert failed")?;
+        }
+        restored += 1;
+    }
+    Ok(restored)
+}
+
+/// The target directory after the build: restored entries as vdir_d has
+/// them now (reingested, or gone if cargo deleted them), overlaid with
+/// what's on disk. Disk files matching their cached size and mtime reuse
+/// the cached entry instead of being hashed again.
+async fn record(
+    vdird: &mut DaemonClient,
+    cas: &CasStore,
+    root: &Path,
+    target: &Path,
+    cached: &Manifest,
+) -> Result<BTreeMap<String, VnodeEntry>> {
+    let mut after = BTreeMap::new();
+    for (key, _) in cached.iter() {
+        if let Some(entry) = vdird
+            .manifest_get(key)
+            .await
+            .daemon_context("Manifest lookup failed")?
+            .filter(|e| !e.is_whiteout())
+        {
+            after.insert(key.to_string(), entry);
+        }
+    }
+    after.extend(capture_dir(cas, root, target, cached)?);
+    Ok(after)
+}
+
+/// Manifest entries for everything under `dir`, keyed relative to `root`,
+/// with file contents and symlink targets stored in `cas`
+pub fn capture_dir(
+    cas: &CasStore,
+    root: &Path,
+    dir: &Path,
+    cached: &Manifest,
+) -> Result<HashMap<String, VnodeEntry>> {
+    let mut entries = HashMap::new();
+    if !dir.exists() {
+        return Ok(entries);
+    }
+    for item in walkdir::WalkDir::new(dir) {
+        let item = item?;
+        let path = item.path();
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        let key = vrift_ipc::path_key_for(rel);
+        let meta = item.metadata()?;
+        let mtime = meta.mtime().max(0) as u64;
+
+        let entry = if meta.is_dir() {
+            VnodeEntry::new_directory(mtime, meta.mode() & 0o7777)
+        } else if meta.file_type().is_symlink() {
+            let target = std::fs::read_link(path)?;
+            let bytes = target.as_os_str().as_encoded_bytes();
+            VnodeEntry::new_symlink(cas.store(bytes)?, bytes.len() as u64, mtime)
+        } else if meta.is_file() {
+            let mode = meta.mode() & 0o7777;
+            match cached.get(&key) {
+                Some(old)
+                    if old.is_file()
+                        && old.size == meta.len()
+                        && old.mtime == mtime
+                        && old.mode == mode =>
+                {
+                    old.clone()
+                }
+                _ => {
+                    let hash = cas
+                        .store_file(path)
+                        .with_context(|| format!("Failed to store {}", path.display()))?;
+                    VnodeEntry::new_file(hash, meta.len(), mtime, mode)
+                }
+            }
+        } else {
+            // Sockets and fifos aren't build artifacts
+            continue;
+        };
+        entries.insert(key, entry);
+    }
+    Ok(entries)
+}
+
+/// Write the cache and register it so GC keeps its blobs
+fn save(cache_file: &Path, root: &Path, entries: BTreeMap<String, VnodeEntry>) -> Result<()> {
+    let mut manifest = Manifest::new();
+    for (key, entry) in entries {
+        manifest.insert(&key, entry);
+    }
+    if let Some(parent) = cache_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Written aside and renamed, so an interrupted save keeps the old cache
+    let tmp = cache_file.with_extension("manifest.tmp");
+    manifest.save(&tmp)?;
+    std::fs::rename(&tmp, cache_file)
+        .with_context(|| format!("Failed to save cache: {}", cache_file.display()))?;
+
+    crate::registry::ManifestRegistry::register_or_warn(cache_file, root);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_stats() {
+        let mut cached = Manifest::new();
+        cached.insert("/target", VnodeEntry::new_directory(1, 0o755));
+        cached.insert(
+            "/target/a.rlib",
+            VnodeEntry::new_file([1; 32], 10, 100, 0o644),
+        );
+        cached.insert(
+            "/target/b.rlib",
+            VnodeEntry::new_file([2; 32], 10, 100, 0o644),
+        );
+
+        let mut after = BTreeMap::new();
+        after.insert("/target".to_string(), VnodeEntry::new_directory(2, 0o755));
+        after.insert(
+            "/target/a.rlib".to_string(),
+            VnodeEntry::new_file([1; 32], 10, 100, 0o644),
+        );
+        after.insert(
+            "/target/b.rlib".to_string(),
+            VnodeEntry::new_file([3; 32], 12, 200, 0o644),
+        );
+        after.insert(
+            "/target/c.rlib".to_string(),
+            VnodeEntry::new_file([4; 32], 5, 200, 0o644),
+        );
+
+        let stats = CacheStats::compare(&cached, &after);
+        assert_eq!(
+            stats,
+            CacheStats {
+                reused: 1,
+                rebuilt: 1,
+                new: 1,
+            }
+        );
+        assert!((stats.hit_rate() - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(CacheStats::default().hit_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_capture_dir_reuses_unchanged_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let root = dir.path().join("ws");
+        let target = root.join("target");
+        std::fs::create_dir_all(target.join("debug")).unwrap();
+        std::fs::write(target.join("debug/app"), b"binary").unwrap();
+        std::os::unix::fs::symlink("debug/app", target.join("app")).unwrap();
+
+        let first = capture_dir(&cas, &root, &target, &Manifest::new()).unwrap();
+        assert!(first["/target"].is_dir());
+        assert!(first["/target/debug"].is_dir());
+        let app = &first["/target/debug/app"];
+        assert_eq!(cas.get(&app.content_hash).unwrap(), b"binary");
+        let link = &first["/target/app"];
+        assert!(link.is_symlink());
+        assert_eq!(cas.get(&link.content_hash).unwrap(), b"debug/app");
+
+        // Matching size and mtime: the cached entry is trusted as-is
+        let mut cached = Manifest::new();
+        let mut stale = app.clone();
+        stale.content_hash = [9; 32];
+        cached.insert("/target/debug/app", stale.clone());
+        let second = capture_dir(&cas, &root, &target, &cached).unwrap();
+        assert_eq!(second["/target/debug/app"], stale);
+
+        assert!(capture_dir(&cas, &root, &root.join("missing"), &cached)
+            .unwrap()
+            .is_empty());
+    }
+}
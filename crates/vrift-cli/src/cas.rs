@@ -0,0 +1,227 @@
+//! # CAS Storage Tiers
+//!
+//! `vrift cas tier add|rm|list` manages the extra CAS roots of
+//! `vrift_cas::tiers` (e.g. a network share for large or cold blobs), and
+//! `vrift cas rebalance` moves blobs to the root their size and age now
+//! route them to. Every reader (CLI, daemon, shim) looks blobs up in all
+//! roots, so blobs can be moved while projects are in use.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use vrift_cas::tiers::PRIMARY_TIER;
+use vrift_cas::{CasStore, CasTier, RebalanceStats, TierSet};
+
+use crate::format_bytes;
+
+#[derive(Subcommand, Debug)]
+pub enum CasCommands {
+    /// Manage the extra CAS roots blobs are routed to
+    Tier {
+        #[command(subcommand)]
+        command: TierCommands,
+    },
+
+    /// Move blobs to the tier their size and age route them to
+    Rebalance {
+        /// Only report what would move
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TierCommands {
+    /// Add a tier. Rules are checked in the order tiers were added; a blob
+    /// matching none stays in the primary root.
+    Add {
+        /// Tier name (e.g. "nas")
+        name: String,
+
+        /// Directory holding the tier's blobs (created if missing)
+        root: PathBuf,
+
+        /// Route blobs of at least this size (bytes, or with a K/M/G suffix)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        min_size: Option<u64>,
+
+        /// Route blobs not read or written for this many days
+        #[arg(long, value_name = "DAYS")]
+        min_age_days: Option<u64>,
+    },
+
+    /// Move a tier's blobs back to the other roots and forget it
+    Rm {
+        /// Tier name
+        name: String,
+    },
+
+    /// List the primary root and tiers with their rules and contents
+    List,
+}
+
+pub fn run(command: CasCommands, cas_root: &Path) -> Result<()> {
+    match command {
+        CasCommands::Tier { command } => run_tier(command, cas_root),
+        CasCommands::Rebalance { dry_run } => {
+            let cas = CasStore::new(cas_root)?;
+            let stats = cas.rebalance(dry_run).context("Rebalance failed")?;
+            report(&stats, dry_run);
+            Ok(())
+        }
+    }
+}
+
+fn run_tier(command: TierCommands, cas_root: &Path) -> Result<()> {
+    std::fs::create_dir_all(cas_root)
+        .with_context(|| format!("Failed to create {}", cas_root.display()))?;
+    let mut tiers = TierSet::load(cas_root).context("Failed to read tier list")?;
+
+    match command {
+        TierCommands::Add {
+            name,
+            root,
+            min_size,
+            min_age_days,
+        } => {
+            let root = vrift_manifest::normalize_path(&root.to_string_lossy());
+            std::fs::create_dir_all(&root)
+                .with_context(|| format!("Failed to create {}", root.display()))?;
+            let root = root.canonicalize()?;
+            let taken = std::iter::once(cas_root.canonicalize()?)
+                .chain(tiers.tiers().iter().map(|t| t.root.clone()))
+                .any(|r| r.starts_with(&root) || root.starts_with(&r));
+            if taken {
+                anyhow::bail!(
+                    "{} overlaps the primary root or another tier",
+                    root.display()
+                );
+            }
+
+            let tier = CasTier {
+                name: name.clone(),
+                root,
+                min_size,
+                min_age_days,
+            };
+            let rules = describe_rules(&tier);
+            if !tiers.add(tier) {
+                anyhow::bail!("Tier name already in use: {}", name);
+            }
+            tiers.save().context("Failed to save tier list")?;
+            println!("🗄️  Added tier '{}' ({})", name, rules);
+            println!("   Run 'vrift cas rebalance' to move existing blobs.");
+        }
+        TierCommands::Rm { name } => {
+            let tier = tiers
+                .remove(&name)
+                .with_context(|| format!("No such tier: {}", name))?;
+            // Readers still see the old list until it is saved, and they
+            // search every root, so blobs stay reachable while they move
+            let cas = CasStore::with_tiers(cas_root, tiers.tiers().to_vec())?;
+            let stats = cas
+                .drain(&tier.root, false)
+                .with_context(|| format!("Failed to drain {}", tier.root.display()))?;
+            report(&stats, false);
+            if stats.failed > 0 {
+                anyhow::bail!(
+                    "Tier '{}' still holds {} blobs; it was not removed",
+                    name,
+                    stats.failed
+                );
+            }
+            tiers.save().context("Failed to save tier list")?;
+            println!("🗑️  Removed tier '{}'", name);
+        }
+        TierCommands::List => {
+            print_root(PRIMARY_TIER, cas_root, "everything else");
+            for tier in tiers.tiers() {
+                print_root(&tier.name, &tier.root, &describe_rules(tier));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `VRIFT_CAS_TIERS` for shimmed processes: the tier roots of the CAS at
+/// `cas_root`, which the shim tries when a blob isn't in the primary root
+pub fn tiers_env(cas_root: &Path) -> Option<(String, String)> {
+    let cas_root = vrift_manifest::normalize_path(&cas_root.to_string_lossy());
+    let tiers = TierSet::load(&cas_root).ok()?;
+    if tiers.tiers().is_empty() {
+        return None;
+    }
+    let roots: Vec<String> = tiers
+        .tiers()
+        .iter()
+        .map(|t| t.root.display().to_string())
+        .collect();
+    Some(("VRIFT_CAS_TIERS".to_string(), roots.join(":")))
+}
+
+fn print_root(name: &str, root: &Path, rules: &str) {
+    let contents = match CasStore::root_stats(root) {
+        Ok(s) => format!("{} blobs, {}", s.blob_count, format_bytes(s.total_bytes)),
+        Err(e) => format!("unreadable: {}", e),
+    };
+    println!("🗄️  {:<12} {}", name, root.display());
+    println!("   {:<12} {} ({})", "", rules, contents);
+}
+
+fn describe_rules(tier: &CasTier) -> String {
+    let mut rules = Vec::new();
+    if let Some(size) = tier.min_size {
+        rules.push(format!(">= {}", format_bytes(size)));
+    }
+    if let Some(days) = tier.min_age_days {
+        rules.push(format!("unused >= {} days", days));
+    }
+    if rules.is_empty() {
+        "lookup only".to_string()
+    } else {
+        rules.join(", ")
+    }
+}
+
+fn report(stats: &RebalanceStats, dry_run: bool) {
+    let verb = if dry_run { "Would move" } else { "Moved" };
+    println!(
+        "📦 {} {} blobs ({})",
+        verb,
+        stats.moved,
+        format_bytes(stats.bytes)
+    );
+    if stats.failed > 0 {
+        println!("⚠️  {} blobs could not be moved", stats.failed);
+    }
+}
+
+/// Parse `4096`, `64K`, `16M` or `2G` (binary units)
+pub(crate) fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    let s = s.trim();
+    let (digits, shift) = match s.char_indices().last() {
+        Some((i, c)) if c.eq_ignore_ascii_case(&'k') => (&s[..i], 10),
+        Some((i, c)) if c.eq_ignore_ascii_case(&'m') => (&s[..i], 20),
+        Some((i, c)) if c.eq_ignore_ascii_case(&'g') => (&s[..i], 30),
+        _ => (s, 0),
+    };
+    let n: u64 = digits.parse().map_err(|_| format!("invalid size: {}", s))?;
+    n.checked_mul(1 << shift)
+        .ok_or_else(|| format!("size too large: {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("64k"), Ok(64 << 10));
+        assert_eq!(parse_size("16M"), Ok(16 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("1.5G").is_err());
+    }
+}
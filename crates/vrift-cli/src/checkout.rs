@@ -0,0 +1,518 @@
+//! # Checkout
+//!
+//! `vrift checkout <manifest> <dir>` (alias `vrift export`) is the inverse
+//! of ingest: it writes every manifest entry into a plain directory with
+//! its mode, mtime and symlink target, for tools that can't run under the
+//! inception layer or a mount. Files are reflinks of their CAS blobs where
+//! the filesystem supports it, else hard links, else copies, so on the
+//! CAS's own filesystem a checkout costs little more than creating the
+//! entries.
+//!
+//! A hard-linked file is the blob itself: read-only, with the blob's mode
+//! and mtime. Executables, and every file with `--no-hardlink` or `--copy`,
+//! get their own inode instead, carrying the manifest's mode and mtime.
+//!
+//! The target must be empty unless `--overwrite` (replace what the
+//! manifest also has, leave the rest) or `--clean` (also delete what it
+//! doesn't) is given. Files that already match by size, mtime and mode are
+//! left alone, so re-running a checkout only rewrites what changed.
+//!
+//! Manifests can come from anywhere, so nothing is ever written through a
+//! symlink: keys with `..` are refused, symlinks are created only after
+//! every file is in place, and an existing symlink in a target's parent
+//! chain is an error rather than a path to follow.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use nix::sys::stat::{utimensat, UtimensatFlags};
+use nix::sys::time::TimeSpec;
+use rayon::prelude::*;
+use vrift_cas::reflink::IngestMethod;
+use vrift_cas::CasStore;
+use vrift_manifest::{Manifest, VnodeEntry};
+
+use crate::hydrate::{ensure_no_symlink_parents, key_to_rel_path};
+
+#[derive(Args, Debug)]
+pub struct CheckoutArgs {
+    /// Manifest to check out (manifest file or LMDB manifest directory)
+    #[arg(value_name = "MANIFEST")]
+    manifest: PathBuf,
+
+    /// Directory to create the tree in (empty or absent, unless
+    /// --overwrite or --clean)
+    #[arg(value_name = "DIR")]
+    directory: PathBuf,
+
+    /// Write into a non-empty directory, replacing entries the manifest has
+    #[arg(long)]
+    overwrite: bool,
+
+    /// Like --overwrite, and delete everything the manifest doesn't have
+    #[arg(long)]
+    clean: bool,
+
+    /// Give every file its own inode (reflink or copy, never a hard link)
+    #[arg(long)]
+    no_hardlink: bool,
+
+    /// Copy every file (no reflinks or hard links)
+    #[arg(long, conflicts_with = "no_hardlink")]
+    copy: bool,
+}
+
+/// How file content gets from the CAS to the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Placement {
+    /// Reflink, else hard link, else copy
+    #[default]
+    Link,
+    /// Reflink, else copy
+    Clone,
+    /// Always copy
+    Copy,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheckoutOptions {
+    pub placement: Placement,
+    /// Replace existing entries the manifest also has
+    pub overwrite: bool,
+    /// Delete entries the manifest doesn't have (implies `overwrite`)
+    pub clean: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CheckoutSummary {
+    pub files: usize,
+    pub dirs: usize,
+    pub symlinks: usize,
+    pub reflinked: usize,
+    pub hardlinked: usize,
+    pub copied: usize,
+    pub bytes: u64,
+    /// Files already matching the manifest
+    pub unchanged: usize,
+    /// Entries deleted by `clean`
+    pub removed: usize,
+}
+
+/// What happened to one non-directory entry
+enum Outcome {
+    Placed(IngestMethod),
+    Symlink,
+    Unchanged,
+}
+
+pub fn run(args: CheckoutArgs, cas_root: &Path) -> Result<()> {
+    let manifest = crate::pack::load_manifest(&args.manifest)?;
+    let cas = CasStore::new(cas_root)
+        .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+
+    let options = CheckoutOptions {
+        placement: if args.copy {
+            Placement::Copy
+        } else if args.no_hardlink {
+            Placement::Clone
+        } else {
+            Placement::Link
+        },
+        overwrite: args.overwrite || args.clean,
+        clean: args.clean,
+    };
+    if !options.overwrite && fs::read_dir(&args.directory).is_ok_and(|mut d| d.next().is_some()) {
+        anyhow::bail!(
+            "{} is not empty (use --overwrite or --clean)",
+            args.directory.display()
+        );
+    }
+
+    let started = std::time::Instant::now();
+    let summary = checkout_tree(&manifest, &cas, &args.directory, &options)?;
+
+    println!(
+        "✅ Checked out {} files, {} directories, {} symlinks ({:.1} MB) into {} in {:.2}s",
+        summary.files,
+        summary.dirs,
+        summary.symlinks,
+        summary.bytes as f64 / 1_048_576.0,
+        args.directory.display(),
+        started.elapsed().as_secs_f64()
+    );
+    println!(
+        "   reflinked: {}, hard-linked: {}, copied: {}",
+        summary.reflinked, summary.hardlinked, summary.copied
+    );
+    if summary.unchanged > 0 {
+        println!("   Already up to date: {}", summary.unchanged);
+    }
+    if summary.removed > 0 {
+        println!(
+            "   Removed: {} entries not in the manifest",
+            summary.removed
+        );
+    }
+    Ok(())
+}
+
+/// Create every entry of `manifest` under `root`
+pub fn checkout_tree(
+    manifest: &Manifest,
+    cas: &CasStore,
+    root: &Path,
+    options: &CheckoutOptions,
+) -> Result<CheckoutSummary> {
+    let mut summary = CheckoutSummary::default();
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    let mut links = Vec::new();
+    for (key, vnode) in manifest.iter() {
+        if vnode.is_whiteout() {
+            continue;
+        }
+        let target = root.join(key_to_rel_path(key)?);
+        if vnode.is_dir() {
+            dirs.push((target, vnode));
+        } else if vnode.is_symlink() {
+            links.push((target, vnode));
+        } else {
+            files.push((target, vnode));
+        }
+    }
+    // An entry under one of the manifest's own symlinks would be written
+    // wherever that link points
+    let link_paths: HashSet<&Path> = links.iter().map(|(t, _)| t.as_path()).collect();
+    for (target, _) in dirs.iter().chain(&files).chain(&links) {
+        if let Some(link) = target.ancestors().skip(1).find(|a| link_paths.contains(a)) {
+            anyhow::bail!(
+                "Manifest entry {} lies under symlink {}",
+                target.display(),
+                link.display()
+            );
+        }
+    }
+
+    fs::create_dir_all(root)?;
+    if options.clean {
+        summary.removed = remove_extraneous(
+            root,
+            dirs.iter().chain(&files).chain(&links).map(|(t, _)| t),
+        )?;
+    }
+    for (dir, _) in &dirs {
+        ensure_no_symlink_parents(root, dir)?;
+        if options.overwrite && fs::symlink_metadata(dir).is_ok_and(|m| !m.is_dir()) {
+            fs::remove_file(dir)?;
+        }
+        fs::create_dir_all(dir)?;
+    }
+
+    let outcomes = files
+        .par_iter()
+        .map(|(target, vnode)| {
+            checkout_entry(cas, root, target, vnode, options)
+                .with_context(|| format!("Failed to check out {}", target.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for ((_, vnode), outcome) in files.iter().zip(outcomes) {
+        match outcome {
+            Outcome::Symlink => summary.symlinks += 1,
+            Outcome::Unchanged => {
+                summary.files += 1;
+                summary.unchanged += 1;
+            }
+            Outcome::Placed(method) => {
+                summary.files += 1;
+                summary.bytes += vnode.size;
+                match method {
+                    IngestMethod::Reflink => summary.reflinked += 1,
+                    IngestMethod::Hardlink => summary.hardlinked += 1,
+                    IngestMethod::Copy => summary.copied += 1,
+                }
+            }
+        }
+    }
+
+    // Symlinks only once every file is in place, so no write above can
+    // go through one of them
+    links.sort_by(|a, b| b.0.cmp(&a.0));
+    for (target, vnode) in &links {
+        checkout_entry(cas, root, target, vnode, options)
+            .with_context(|| format!("Failed to check out {}", target.display()))?;
+        summary.symlinks += 1;
+    }
+
+    // Directories last, deepest first: creating children bumps a parent's
+    // mtime, and a read-only directory would block them
+    dirs.sort_by(|a, b| b.0.cmp(&a.0));
+    for (dir, vnode) in &dirs {
+        if vnode.mode & 0o7777 != 0 {
+            fs::set_permissions(dir, fs::Permissions::from_mode(vnode.mode & 0o7777))?;
+        }
+        set_mtime(dir, vnode.mtime)?;
+    }
+    summary.dirs = dirs.len();
+    Ok(summary)
+}
+
+/// Create one file or symlink at `target`
+fn checkout_entry(
+    cas: &CasStore,
+    root: &Path,
+    target: &Path,
+    vnode: &VnodeEntry,
+    options: &CheckoutOptions,
+) -> Result<Outcome> {
+    ensure_no_symlink_parents(root, target)?;
+    if let Ok(meta) = fs::symlink_metadata(target) {
+        if !options.overwrite {
+            anyhow::bail!("{} already exists", target.display());
+        }
+        if !vnode.is_symlink()
+            && meta.is_file()
+            && meta.len() == vnode.size
+            && meta.mtime() == vnode.mtime as i64
+            && meta.mode() & 0o7777 == vnode.mode & 0o7777
+        {
+            return Ok(Outcome::Unchanged);
+        }
+        if meta.is_dir() {
+            fs::remove_dir_all(target)?;
+        } else {
+            fs::remove_file(target)?;
+        }
+    }
+
+    if vnode.is_symlink() {
+        // Symlink targets are stored in the CAS like file content
+        use std::os::unix::ffi::OsStrExt;
+        let link = cas.get(&vnode.content_hash)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(&link), target)?;
+        set_mtime(target, vnode.mtime)?;
+        return Ok(Outcome::Symlink);
+    }
+
+    // A hard link can't carry the executable bit the blob lacks
+    let placement = match options.placement {
+        Placement::Link if vnode.mode & 0o111 != 0 => Placement::Clone,
+        placement => placement,
+    };
+    let method = match placement {
+        Placement::Link => cas.link_out(&vnode.content_hash, target)?,
+        Placement::Clone => cas.clone_out(&vnode.content_hash, target)?,
+        Placement::Copy => copy_out(cas, vnode, target)?,
+    };
+    if method != IngestMethod::Hardlink {
+        fs::set_permissions(target, fs::Permissions::from_mode(vnode.mode & 0o7777))?;
+        set_mtime(target, vnode.mtime)?;
+    }
+    Ok(Outcome::Placed(method))
+}
+
+/// Plain streamed copy of the blob (hash-checked by the reader)
+fn copy_out(cas: &CasStore, vnode: &VnodeEntry, target: &Path) -> Result<IngestMethod> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut reader = cas.get_reader(&vnode.content_hash)?;
+    let copied = File::create_new(target).and_then(|mut file| io::copy(&mut reader, &mut file));
+    if let Err(e) = copied {
+        let _ = fs::remove_file(target);
+        return Err(e.into());
+    }
+    Ok(IngestMethod::Copy)
+}
+
+/// Set atime and mtime of `path` itself (not a symlink's target)
+fn set_mtime(path: &Path, mtime: u64) -> Result<()> {
+    let time = TimeSpec::new(mtime as i64, 0);
+    utimensat(None, path, &time, &time, UtimensatFlags::NoFollowSymlink)
+        .with_context(|| format!("Failed to set mtime of {}", path.display()))?;
+    Ok(())
+}
+
+/// Delete everything under `root` that is neither in `wanted` nor a parent
+/// directory of something that is; returns the entries deleted (a deleted
+/// directory counts once)
+fn remove_extraneous<'a>(root: &Path, wanted: impl Iterator<Item = &'a PathBuf>) -> Result<usize> {
+    let mut keep: HashSet<&Path> = HashSet::new();
+    for path in wanted {
+        for ancestor in path.ancestors() {
+            if ancestor == root || !keep.insert(ancestor) {
+                break;
+            }
+        }
+    }
+
+    let mut removed = 0;
+    let mut walk = walkdir::WalkDir::new(root).min_depth(1).into_iter();
+    while let Some(entry) = walk.next() {
+        let entry = entry?;
+        if keep.contains(entry.path()) {
+            continue;
+        }
+        if entry.file_type().is_dir() {
+            fs::remove_dir_all(entry.path())?;
+            walk.skip_current_dir();
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, Manifest, CasStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let mut manifest = Manifest::new();
+        let file = |body: &str, mode| {
+            let hash = cas.store(body.as_bytes()).unwrap();
+            VnodeEntry::new_file(hash, body.len() as u64, 1_700_000_000, mode)
+        };
+        manifest.insert("/src", VnodeEntry::new_directory(1_600_000_000, 0o755));
+        manifest.insert("/src/lib.rs", file("pub fn a() {}", 0o644));
+        manifest.insert("/bin/run.sh", file("#!/bin/sh\n", 0o755));
+        let target = cas.store(b"src/lib.rs").unwrap();
+        manifest.insert(
+            "/lib.rs",
+            VnodeEntry::new_symlink(target, 10, 1_500_000_000),
+        );
+        (dir, manifest, cas)
+    }
+
+    #[test]
+    fn test_checkout_materializes_tree() {
+        let (dir, manifest, cas) = setup();
+        let root = dir.path().join("tree");
+
+        let options = CheckoutOptions::default();
+        let summary = checkout_tree(&manifest, &cas, &root, &options).unwrap();
+        assert_eq!(summary.files, 2);
+        assert_eq!(summary.symlinks, 1);
+        // Without reflinks the executable is copied, never lib.rs
+        assert!(summary.copied <= 1);
+        assert_eq!(summary.reflinked + summary.hardlinked + summary.copied, 2);
+
+        assert_eq!(
+            fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+            "pub fn a() {}"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("lib.rs")).unwrap(),
+            "pub fn a() {}"
+        );
+        let script = fs::metadata(root.join("bin/run.sh")).unwrap();
+        assert_eq!(script.mode() & 0o777, 0o755);
+        assert_eq!(script.mtime(), 1_700_000_000);
+        let link = fs::symlink_metadata(root.join("lib.rs")).unwrap();
+        assert_eq!(link.mtime(), 1_500_000_000);
+        let src = fs::metadata(root.join("src")).unwrap();
+        assert_eq!(src.mtime(), 1_600_000_000);
+
+        // Existing entries are an error without overwrite
+        assert!(checkout_tree(&manifest, &cas, &root, &options).is_err());
+    }
+
+    #[test]
+    fn test_copy_gives_private_inodes() {
+        let (dir, manifest, cas) = setup();
+        let root = dir.path().join("tree");
+
+        let options = CheckoutOptions {
+            placement: Placement::Copy,
+            ..Default::default()
+        };
+        let summary = checkout_tree(&manifest, &cas, &root, &options).unwrap();
+        assert_eq!(summary.copied, 2);
+        let lib = fs::metadata(root.join("src/lib.rs")).unwrap();
+        assert_eq!(lib.nlink(), 1);
+        assert_eq!(lib.mode() & 0o777, 0o644);
+    }
+
+    #[test]
+    fn test_clean_replaces_and_prunes() {
+        let (dir, manifest, cas) = setup();
+        let root = dir.path().join("tree");
+        let copy = CheckoutOptions {
+            placement: Placement::Copy,
+            ..Default::default()
+        };
+        checkout_tree(&manifest, &cas, &root, &copy).unwrap();
+
+        fs::remove_file(root.join("bin/run.sh")).unwrap();
+        fs::write(root.join("bin/run.sh"), "edited").unwrap();
+        fs::write(root.join("src/stray.o"), "junk").unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+
+        let clean = CheckoutOptions {
+            overwrite: true,
+            clean: true,
+            ..copy
+        };
+        let summary = checkout_tree(&manifest, &cas, &root, &clean).unwrap();
+        assert_eq!(summary.removed, 2);
+        assert_eq!(summary.unchanged, 1, "src/lib.rs already matched");
+        assert_eq!(
+            fs::read_to_string(root.join("bin/run.sh")).unwrap(),
+            "#!/bin/sh\n"
+        );
+        assert!(!root.join("src/stray.o").exists());
+        assert!(!root.join("target").exists());
+        assert!(root.join("lib.rs").is_symlink());
+    }
+
+    #[test]
+    fn test_never_writes_through_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        let root = dir.path().join("tree");
+        let body = cas.store(b"root::0:0").unwrap();
+        let passwd = VnodeEntry::new_file(body, 9, 1_700_000_000, 0o644);
+
+        // A link in the manifest itself, with an entry beneath it
+        let mut manifest = Manifest::new();
+        let link = cas.store(outside.to_str().unwrap().as_bytes()).unwrap();
+        manifest.insert(
+            "/a",
+            VnodeEntry::new_symlink(link, outside.as_os_str().len() as u64, 0),
+        );
+        manifest.insert("/a/passwd", passwd.clone());
+        let options = CheckoutOptions::default();
+        assert!(checkout_tree(&manifest, &cas, &root, &options).is_err());
+        assert!(!outside.join("passwd").exists());
+
+        // A link already on disk, overwritten in place
+        let mut manifest = Manifest::new();
+        manifest.insert("/a/passwd", passwd.clone());
+        fs::create_dir_all(&root).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("a")).unwrap();
+        let overwrite = CheckoutOptions {
+            overwrite: true,
+            ..options
+        };
+        let err = checkout_tree(&manifest, &cas, &root, &overwrite).unwrap_err();
+        assert!(format!("{:#}", err).contains("symlink"), "{:#}", err);
+        assert!(!outside.join("passwd").exists());
+
+        let mut manifest = Manifest::new();
+        manifest.insert("/../passwd", passwd);
+        assert!(checkout_tree(&manifest, &cas, &root, &overwrite).is_err());
+        assert!(!dir.path().join("passwd").exists());
+    }
+}
@@ -0,0 +1,106 @@
+//! Foreground child processes for `vrift run`
+//!
+//! vrift should be invisible between the shell and the command it runs:
+//!
+//! - Ctrl-C and Ctrl-\ already reach the child through the terminal's
+//!   process group, so vrift ignores them instead of dying first and
+//!   leaving the child orphaned.
+//! - SIGTERM and SIGHUP sent to vrift alone (a supervisor, a closed ssh
+//!   session) are passed on to the child.
+//! - vrift exits the way the child did. A child killed by SIGINT or SIGTERM
+//!   takes vrift down with the same signal, so a shell loop around
+//!   `vrift run` stops on Ctrl-C. Any other signal becomes 128 + signal.
+
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Pid of the child being waited on (0 = none)
+static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+
+const IGNORED: [libc::c_int; 2] = [libc::SIGINT, libc::SIGQUIT];
+const FORWARDED: [libc::c_int; 2] = [libc::SIGTERM, libc::SIGHUP];
+
+extern "C" fn forward(sig: libc::c_int) {
+    let pid = CHILD_PID.load(Ordering::Relaxed);
+    if pid > 0 {
+        unsafe { libc::kill(pid, sig) };
+    }
+}
+
+/// Spawn `cmd` and wait for it with the signal handling above
+pub fn run(cmd: &mut Command) -> io::Result<ExitStatus> {
+    // Dispositions change after the spawn: ignored signals stay ignored
+    // across exec, and the child must keep the defaults
+    let mut child = cmd.spawn()?;
+    CHILD_PID.store(child.id() as i32, Ordering::Relaxed);
+
+    let mut saved = Vec::with_capacity(IGNORED.len() + FORWARDED.len());
+    for sig in IGNORED {
+        saved.push((sig, set_handler(sig, libc::SIG_IGN)));
+    }
+    for sig in FORWARDED {
+        saved.push((sig, set_handler(sig, forward as libc::sighandler_t)));
+    }
+
+    let status = child.wait();
+
+    for (sig, old) in saved {
+        unsafe { libc::sigaction(sig, &old, std::ptr::null_mut()) };
+    }
+    CHILD_PID.store(0, Ordering::Relaxed);
+    status
+}
+
+/// Exit with the child's status (see the module docs)
+pub fn exit_like(status: ExitStatus) -> ! {
+    if let Some(sig) = status.signal() {
+        if sig == libc::SIGINT || sig == libc::SIGTERM {
+            unsafe {
+                libc::signal(sig, libc::SIG_DFL);
+                libc::raise(sig);
+            }
+        }
+        std::process::exit(128 + sig);
+    }
+    std::process::exit(status.code().unwrap_or(1))
+}
+
+fn set_handler(sig: libc::c_int, handler: libc::sighandler_t) -> libc::sigaction {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut old: libc::sigaction = std::mem::zeroed();
+        libc::sigaction(sig, &action, &mut old);
+        old
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One test: run() swaps process-wide signal dispositions
+    #[test]
+    fn test_run_reports_status_and_forwards_sigterm() {
+        let status = run(Command::new("sh").args(["-c", "exit 7"])).unwrap();
+        assert_eq!(status.code(), Some(7));
+
+        let status = run(Command::new("sh").args(["-c", "kill -TERM $$"])).unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+
+        // SIGTERM sent to this process reaches the child instead
+        let waiter =
+            std::thread::spawn(|| run(Command::new("sh").args(["-c", "exec sleep 5"])).unwrap());
+        while CHILD_PID.load(Ordering::Relaxed) == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        unsafe { libc::kill(libc::getpid(), libc::SIGTERM) };
+        let status = waiter.join().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+}
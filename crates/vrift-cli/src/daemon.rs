@@ -1,16 +1,64 @@
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
-use tokio::net::UnixStream;
 use vrift_config::path::{normalize_nonexistent, normalize_or_original};
-use vrift_ipc::{VeloRequest, VeloResponse, PROTOCOL_VERSION};
+use vrift_ipc::client::{ClientError, ClientResult, DaemonClient, IngestOptions};
+use vrift_ipc::VeloErrorKind;
+
+pub use vrift_ipc::client::IngestSummary as IngestResult;
+
+/// `.context()` for daemon calls that also says what the daemon's error kind
+/// means for the user. The `VeloError` stays in the chain, so `main` can
+/// still pick its exit code.
+pub trait DaemonContext<T> {
+    fn daemon_context(self, what: &str) -> Result<T>;
+}
+
+impl<T> DaemonContext<T> for ClientResult<T> {
+    fn daemon_context(self, what: &str) -> Result<T> {
+        self.map_err(|e| {
+            let hint = match &e {
+                ClientError::Daemon(err) => match err.kind {
+                    VeloErrorKind::NotFound => Some("not found"),
+                    VeloErrorKind::PermissionDenied => Some("permission denied"),
+                    VeloErrorKind::Conflict => Some("conflicts with an existing entry"),
+                    VeloErrorKind::Busy | VeloErrorKind::LockFailed => {
+                        Some("daemon busy, try again")
+                    }
+                    VeloErrorKind::Corrupt => Some("stored data is corrupt, run 'vrift doctor'"),
+                    VeloErrorKind::WorkspaceNotRegistered => Some("workspace not registered"),
+                    VeloErrorKind::SymlinkLoop => Some("too many levels of symbolic links"),
+                    VeloErrorKind::VersionMismatch => {
+                        Some("vrift and the daemon are different versions, restart the daemon")
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            let context = match hint {
+                Some(hint) => format!("{} ({})", what, hint),
+                None => what.to_string(),
+            };
+            anyhow::Error::new(e).context(context)
+        })
+    }
+}
+
+/// Exit code for an error whose chain carries a daemon error, if any
+pub fn exit_code(err: &anyhow::Error) -> Option<i32> {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<vrift_ipc::VeloError>())
+        .map(|e| e.exit_code())
+}
 
 /// Phase 1.2: Connection state returned by connect_to_daemon.
-/// Contains the vriftd stream plus vDird connection info from RegisterAck.
+/// Contains the vriftd client plus vDird connection info from RegisterAck.
 #[allow(dead_code)]
 pub struct DaemonConnection {
-    pub stream: UnixStream,
+    pub client: DaemonClient,
     pub vdird_socket: String,
     pub vdir_mmap_path: String,
+    /// Daemon-assigned session id (empty from daemons that predate sessions)
+    pub session_id: String,
 }
 
 fn get_socket_path() -> PathBuf {
@@ -18,76 +66,112 @@ fn get_socket_path() -> PathBuf {
 }
 
 pub async fn check_status(_project_root: &Path) -> Result<()> {
-    let mut stream = tokio::time::timeout(std::time::Duration::from_secs(10), connect_simple())
+    let mut client = tokio::time::timeout(std::time::Duration::from_secs(10), connect_simple())
         .await
         .map_err(|_| anyhow::anyhow!("Timed out connecting to daemon (10s)"))??;
 
-    // Status request
-    let req = VeloRequest::Status;
-    send_request(&mut stream, req).await?;
-    let resp = tokio::time::timeout(
-        std::time::Duration::from_secs(5),
-        read_response(&mut stream),
-    )
-    .await
-    .map_err(|_| anyhow::anyhow!("Timed out waiting for daemon status (5s)"))??;
-
-    match resp {
-        VeloResponse::StatusAck { status } => {
-            println!("Daemon Status: {}", status);
-        }
-        VeloResponse::Error(e) => anyhow::bail!("Status failed: {}", e),
-        _ => anyhow::bail!("Unexpected status response: {:?}", resp),
-    }
+    let status = tokio::time::timeout(std::time::Duration::from_secs(5), client.status())
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out waiting for daemon status (5s)"))?
+        .daemon_context("Status failed")?;
+    println!("Daemon Status: {}", status);
 
     Ok(())
 }
 
-pub async fn spawn_command(command: &[String], cwd: PathBuf, project_root: &Path) -> Result<()> {
-    let conn = connect_to_daemon(project_root).await?;
-    let mut stream = conn.stream;
+/// Query structured counters from vriftd (`None`) or a vdir_d socket.
+/// Never starts a daemon: a dashboard should show it as down instead.
+pub async fn fetch_metrics(socket: Option<&Path>) -> Result<vrift_ipc::DaemonMetrics> {
+    let socket_path = socket
+        .map(Path::to_path_buf)
+        .unwrap_or_else(get_socket_path);
+    let timeout = std::time::Duration::from_secs(2);
+    let mut client = tokio::time::timeout(timeout, DaemonClient::connect_path(&socket_path))
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out connecting to {}", socket_path.display()))??;
+
+    let metrics = tokio::time::timeout(timeout, client.metrics())
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out waiting for metrics"))?
+        .daemon_context("Metrics failed")?;
+    Ok(metrics)
+}
+
+/// Run `command` under vriftd's supervision, relaying its output here until
+/// it exits. Ctrl-C is forwarded to the child's process group. Returns the
+/// child's exit code (128 + signal if it was killed).
+pub async fn spawn_command(
+    command: &[String],
+    cwd: PathBuf,
+    project_root: &Path,
+    log_path: Option<&Path>,
+) -> Result<i32> {
+    use std::io::Write;
+
+    let mut conn = connect_to_daemon(project_root).await?;
 
     // Construct environment with explicit Strings
     let env: Vec<(String, String)> = std::env::vars().collect();
-
-    let req = VeloRequest::Spawn {
-        command: command.to_vec(),
-        env,
-        cwd: cwd.to_string_lossy().to_string(),
-    };
+    // The daemon opens the log, and its cwd differs from ours
+    let log_path = log_path.map(|p| {
+        normalize_nonexistent(p)
+            .unwrap_or_else(|_| p.to_path_buf())
+            .to_string_lossy()
+            .to_string()
+    });
 
     tracing::info!("Requesting daemon to spawn: {:?}", command);
-    send_request(&mut stream, req).await?;
-
-    let resp = read_response(&mut stream).await?;
-    match resp {
-        VeloResponse::SpawnAck { pid } => {
-            tracing::info!("Daemon successfully spawned process. PID: {}", pid);
-            println!("Daemon successfully spawned process. PID: {}", pid);
-            println!("(Output will be in daemon logs for now)");
-        }
-        VeloResponse::Error(msg) => {
-            anyhow::bail!("Daemon refused to spawn: {}", msg);
+    let pid = conn
+        .client
+        .spawn(
+            command.to_vec(),
+            env,
+            cwd.to_string_lossy().to_string(),
+            log_path,
+        )
+        .await
+        .daemon_context("Daemon refused to spawn")?;
+    tracing::info!("Daemon spawned process. PID: {}", pid);
+
+    let (mut stdout_offset, mut stderr_offset) = (0, 0);
+    loop {
+        let poll = conn
+            .client
+            .spawn_poll(pid, stdout_offset, stderr_offset, 1000);
+        let output = tokio::select! {
+            output = poll => output.daemon_context("Lost track of spawned process")?,
+            _ = tokio::signal::ctrl_c() => {
+                // The poll was abandoned mid-reply; signal over a fresh connection
+                let mut client = connect_simple().await?;
+                client
+                    .kill(pid, libc::SIGINT)
+                    .await
+                    .daemon_context("Failed to interrupt spawned process")?;
+                conn.client = connect_simple().await?;
+                continue;
+            }
+        };
+        std::io::stdout().write_all(&output.stdout)?;
+        std::io::stdout().flush()?;
+        std::io::stderr().write_all(&output.stderr)?;
+        stdout_offset = output.stdout_offset;
+        stderr_offset = output.stderr_offset;
+        if let Some(exit) = output.exit {
+            return Ok(exit.shell_code());
         }
-        _ => anyhow::bail!("Unexpected response from daemon: {:?}", resp),
     }
-
-    Ok(())
 }
 
 #[allow(dead_code)]
 pub async fn check_blob(hash: [u8; 32], project_root: &Path) -> Result<bool> {
     match connect_to_daemon(project_root).await {
-        Ok(conn) => {
-            let mut stream = conn.stream;
-            let req = VeloRequest::CasGet { hash };
-            send_request(&mut stream, req).await?;
-            match read_response(&mut stream).await? {
-                VeloResponse::CasFound { .. } => Ok(true),
-                VeloResponse::CasNotFound => Ok(false),
-                VeloResponse::Error(e) => anyhow::bail!("Check failed: {}", e),
-                _ => anyhow::bail!("Unexpected response"),
-            }
+        Ok(mut conn) => {
+            let size = conn
+                .client
+                .cas_get(hash)
+                .await
+                .daemon_context("Check failed")?;
+            Ok(size.is_some())
         }
         Err(_) => Ok(false),
     }
@@ -95,93 +179,90 @@ pub async fn check_blob(hash: [u8; 32], project_root: &Path) -> Result<bool> {
 
 #[allow(dead_code)]
 pub async fn notify_blob(hash: [u8; 32], size: u64, project_root: &Path) -> Result<()> {
-    if let Ok(conn) = connect_to_daemon(project_root).await {
-        let mut stream = conn.stream;
-        let req = VeloRequest::CasInsert { hash, size };
-        let _ = send_request(&mut stream, req).await;
+    if let Ok(mut conn) = connect_to_daemon(project_root).await {
+        let _ = conn.client.cas_insert(hash, size).await;
     }
     Ok(())
 }
 
 pub async fn connect_to_daemon(project_root: &Path) -> Result<DaemonConnection> {
     let socket_path = get_socket_path();
-    let connect_fut = UnixStream::connect(&socket_path);
-    let mut stream =
+    let connect_fut = DaemonClient::connect_path(&socket_path);
+    let mut client =
         match tokio::time::timeout(std::time::Duration::from_secs(5), connect_fut).await {
-            Ok(Ok(s)) => s,
+            Ok(Ok(c)) => c,
             _ => {
                 tracing::info!("Daemon not running. Attempting to start...");
                 spawn_daemon()?;
-                let mut s = None;
+                let mut c = None;
                 for _ in 0..10 {
                     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    if let Ok(conn) = UnixStream::connect(&socket_path).await {
-                        s = Some(conn);
+                    if let Ok(conn) = DaemonClient::connect_path(&socket_path).await {
+                        c = Some(conn);
                         break;
                     }
                 }
-                s.context("Failed to connect to daemon after starting it")?
+                c.context("Failed to connect to daemon after starting it")?
             }
         };
 
     // 1. Handshake
-    let handshake = VeloRequest::Handshake {
-        client_version: env!("CARGO_PKG_VERSION").to_string(),
-        protocol_version: PROTOCOL_VERSION,
-    };
-    send_request(&mut stream, handshake).await?;
-    let _ = read_response(&mut stream).await?;
+    client
+        .handshake()
+        .await
+        .daemon_context("Handshake failed")?;
 
     // 2. Register Workspace (normalize to absolute path for daemon)
     let abs_project_root = normalize_or_original(project_root);
-    let register = VeloRequest::RegisterWorkspace {
-        project_root: abs_project_root.to_string_lossy().to_string(),
-    };
-    send_request(&mut stream, register).await?;
-    let resp = read_response(&mut stream).await?;
-
-    match resp {
-        VeloResponse::RegisterAck {
-            workspace_id: _,
-            vdird_socket,
-            vdir_mmap_path,
-        } => Ok(DaemonConnection {
-            stream,
-            vdird_socket,
-            vdir_mmap_path,
-        }),
-        VeloResponse::Error(e) => anyhow::bail!("Workspace registration failed: {}", e),
-        _ => anyhow::bail!("Unexpected registration response"),
+    let registration = client
+        .register_workspace(&abs_project_root.to_string_lossy())
+        .await
+        .daemon_context("Workspace registration failed")?;
+
+    Ok(DaemonConnection {
+        client,
+        vdird_socket: registration.vdird_socket,
+        vdir_mmap_path: registration.vdir_mmap_path,
+        session_id: registration.session_id,
+    })
+}
+
+/// Register `project_root` with vriftd (starting it if needed) and connect
+/// to the project's vdir_d, which owns the manifest
+pub async fn connect_to_vdird(project_root: &Path) -> Result<(DaemonConnection, DaemonClient)> {
+    let conn = connect_to_daemon(project_root)
+        .await
+        .context("Failed to reach vriftd")?;
+    if conn.vdird_socket.is_empty() {
+        anyhow::bail!(
+            "vriftd did not start a vdir_d for {}",
+            project_root.display()
+        );
     }
+    let vdird = DaemonClient::connect_path(Path::new(&conn.vdird_socket))
+        .await
+        .with_context(|| format!("Failed to connect to vdir_d at {}", conn.vdird_socket))?;
+    Ok((conn, vdird))
 }
 
 /// Simple connection to daemon - only handshake, no workspace registration
 /// Used for standalone operations like IngestFullScan
-async fn connect_simple() -> Result<UnixStream> {
+async fn connect_simple() -> Result<DaemonClient> {
     let socket_path = get_socket_path();
 
     // Try connecting + handshake directly first
-    if let Ok(mut stream) = tokio::time::timeout(
+    if let Ok(Ok(mut client)) = tokio::time::timeout(
         std::time::Duration::from_secs(5),
-        UnixStream::connect(&socket_path),
+        DaemonClient::connect_path(&socket_path),
     )
     .await
-    .unwrap_or(Err(std::io::Error::new(
-        std::io::ErrorKind::TimedOut,
-        "timeout",
-    ))) {
-        let handshake = VeloRequest::Handshake {
-            client_version: env!("CARGO_PKG_VERSION").to_string(),
-            protocol_version: PROTOCOL_VERSION,
-        };
-        if send_request(&mut stream, handshake).await.is_ok() {
-            if let Ok(resp) = read_response(&mut stream).await {
-                match resp {
-                    VeloResponse::HandshakeAck { .. } => return Ok(stream),
-                    VeloResponse::Error(e) => anyhow::bail!("Handshake failed: {}", e),
-                    _ => {}
-                }
+    {
+        match client.handshake().await {
+            Ok(_) => return Ok(client),
+            Err(e @ ClientError::Daemon(_)) => {
+                return Err(e).daemon_context("Handshake failed");
             }
+            Err(_) => {}
         }
     }
 
@@ -192,22 +273,16 @@ async fn connect_simple() -> Result<UnixStream> {
     for attempt in 0..20 {
         tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
 
-        if let Ok(mut stream) = UnixStream::connect(&socket_path).await {
-            let handshake = VeloRequest::Handshake {
-                client_version: env!("CARGO_PKG_VERSION").to_string(),
-                protocol_version: PROTOCOL_VERSION,
-            };
-            if send_request(&mut stream, handshake).await.is_ok() {
-                if let Ok(resp) = read_response(&mut stream).await {
-                    match resp {
-                        VeloResponse::HandshakeAck { .. } => {
-                            tracing::info!("Connected to daemon after {} attempts", attempt + 1);
-                            return Ok(stream);
-                        }
-                        VeloResponse::Error(e) => anyhow::bail!("Handshake failed: {}", e),
-                        _ => tracing::debug!("Unexpected handshake response, retrying..."),
-                    }
+        if let Ok(mut client) = DaemonClient::connect_path(&socket_path).await {
+            match client.handshake().await {
+                Ok(_) => {
+                    tracing::info!("Connected to daemon after {} attempts", attempt + 1);
+                    return Ok(client);
+                }
+                Err(e @ ClientError::Daemon(_)) => {
+                    return Err(e).daemon_context("Handshake failed");
                 }
+                Err(e) => tracing::debug!("Handshake attempt failed ({}), retrying...", e),
             }
         }
     }
@@ -245,17 +320,19 @@ fn spawn_daemon() -> Result<()> {
     Ok(())
 }
 
-pub async fn send_request(stream: &mut UnixStream, req: VeloRequest) -> Result<u32> {
-    tracing::debug!("Sending request: {:?}", req);
-    let seq_id = vrift_ipc::frame_async::send_request(stream, &req).await?;
-    Ok(seq_id)
-}
+/// How long ingest may go without a sign of life before the CLI gives up.
+const INGEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
 
-pub async fn read_response(stream: &mut UnixStream) -> Result<VeloResponse> {
-    tracing::debug!("[CLI] Waiting for response...");
-    let (header, resp) = vrift_ipc::frame_async::read_response(stream).await?;
-    tracing::debug!("[CLI] Response received, seq_id={}", header.seq_id);
-    Ok(resp)
+fn ingest_progress_bar() -> indicatif::ProgressBar {
+    let bar = indicatif::ProgressBar::new(0);
+    bar.set_style(
+        indicatif::ProgressStyle::default_bar()
+            .template("{spinner:.cyan} [{bar:30.cyan/blue}] {pos}/{len} files  {msg}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    bar.enable_steady_tick(std::time::Duration::from_millis(80));
+    bar
 }
 
 /// Ingest files via daemon (unified architecture)
@@ -271,6 +348,8 @@ pub async fn ingest_via_daemon(
     prefix: Option<String>,
     cas_root: Option<&Path>,
     force_hash: bool,
+    compress: bool,
+    ignore: Vec<String>,
 ) -> Result<IngestResult> {
     // Normalize paths before sending to daemon (daemon's cwd may differ)
     let abs_path = normalize_or_original(path);
@@ -280,10 +359,10 @@ pub async fn ingest_via_daemon(
 
     // Use simple connection - IngestFullScan doesn't need workspace context
     tracing::info!("[CLI] Connecting to daemon for ingest...");
-    let mut stream = connect_simple().await?;
+    let mut client = connect_simple().await?;
     tracing::info!("[CLI] Connected to daemon successfully");
 
-    let req = VeloRequest::IngestFullScan {
+    let opts = IngestOptions {
         path: abs_path.to_string_lossy().to_string(),
         manifest_path: abs_manifest.to_string_lossy().to_string(),
         threads,
@@ -292,6 +371,8 @@ pub async fn ingest_via_daemon(
         prefix,
         cas_root: cas_root.map(|p| p.to_string_lossy().to_string()),
         force_hash,
+        compress,
+        ignore,
     };
 
     tracing::info!(
@@ -299,46 +380,67 @@ pub async fn ingest_via_daemon(
         abs_path,
         abs_manifest
     );
-    send_request(&mut stream, req).await?;
-    tracing::info!("[CLI] IngestFullScan request sent, waiting for response...");
 
-    // Ingest can take minutes for large datasets, use generous timeout
-    let resp = tokio::time::timeout(
-        std::time::Duration::from_secs(120),
-        read_response(&mut stream),
-    )
-    .await
-    .map_err(|_| anyhow::anyhow!("Timed out waiting for ingest response (120s)"))??;
+    // Ingest can take minutes for large datasets. Poll the daemon's ingest
+    // counters on a second connection to drive a progress bar; without one,
+    // fall back to a fixed deadline.
+    let ingest = client.ingest_full_scan(opts);
+    tokio::pin!(ingest);
+    let Ok(mut monitor) = connect_simple().await else {
+        return tokio::time::timeout(INGEST_TIMEOUT, ingest)
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out waiting for ingest response (120s)"))?
+            .daemon_context("Daemon ingest failed");
+    };
+
+    let bar = ingest_progress_bar();
+    let started = std::time::Instant::now();
+    let mut last_seen_running = started;
+    let mut tick = tokio::time::interval(std::time::Duration::from_millis(200));
+    let result = loop {
+        tokio::select! {
+            result = &mut ingest => break result,
+            _ = tick.tick() => {
+                let Ok(metrics) = monitor.metrics().await else { continue };
+                if metrics.ingests_running > 0 {
+                    last_seen_running = std::time::Instant::now();
+                } else if last_seen_running.elapsed() > INGEST_TIMEOUT {
+                    // The request is still pending but the daemon reports no
+                    // ingest in flight: it will not answer.
+                    bar.finish_and_clear();
+                    anyhow::bail!("Timed out waiting for ingest response (no ingest running for 120s)");
+                }
+                bar.set_length(metrics.ingest_files_found);
+                bar.set_position(metrics.ingest_files_done);
+                let secs = started.elapsed().as_secs_f64().max(0.001);
+                bar.set_message(format!(
+                    "{:.1} MB, {:.1} MB/s{}",
+                    metrics.ingest_bytes_done as f64 / 1_048_576.0,
+                    metrics.ingest_bytes_done as f64 / 1_048_576.0 / secs,
+                    if metrics.ingest_scan_complete { "" } else { " (scanning)" }
+                ));
+            }
+        }
+    };
+    bar.finish_and_clear();
+    let result = result.daemon_context("Daemon ingest failed")?;
     tracing::info!("[CLI] Received ingest response");
-    match resp {
-        VeloResponse::IngestAck {
-            files,
-            blobs,
-            new_bytes,
-            total_bytes,
-            duration_ms,
-            manifest_path,
-        } => Ok(IngestResult {
-            files,
-            blobs,
-            new_bytes,
-            total_bytes,
-            duration_ms,
-            manifest_path,
-        }),
-        VeloResponse::Error(e) => anyhow::bail!("Daemon ingest failed: {}", e),
-        _ => anyhow::bail!("Unexpected response from daemon: {:?}", resp),
-    }
+    Ok(result)
 }
 
-/// Result from daemon ingest
-#[derive(Debug)]
-#[allow(dead_code)]
-pub struct IngestResult {
-    pub files: u64,
-    pub blobs: u64,
-    pub new_bytes: u64,
-    pub total_bytes: u64,
-    pub duration_ms: u64,
-    pub manifest_path: String,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_ipc::VeloError;
+
+    #[test]
+    fn test_daemon_context_keeps_kind_for_exit_code() {
+        let result: ClientResult<()> = Err(ClientError::Daemon(VeloError::busy("draining")));
+        let err = result.daemon_context("Sweep failed").unwrap_err();
+        assert_eq!(err.to_string(), "Sweep failed (daemon busy, try again)");
+        assert_eq!(exit_code(&err), Some(75));
+
+        let plain = anyhow::anyhow!("no daemon involved");
+        assert_eq!(exit_code(&plain), None);
+    }
 }
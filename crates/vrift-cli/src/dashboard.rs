@@ -0,0 +1,202 @@
+//! # Status Dashboard
+//!
+//! `vrift status --watch` redraws a small terminal dashboard every interval.
+//! All numbers come from the daemons' `Metrics` replies (vriftd for the CAS,
+//! the project's vdir_d for manifest, journal and reingest activity), so a
+//! tick costs two socket round-trips instead of a CAS or manifest scan.
+
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use vrift_ipc::DaemonMetrics;
+
+use crate::{daemon, format_bytes, format_number};
+
+/// Recent reingests shown on screen
+const RECENT_ROWS: usize = 8;
+
+/// One refresh worth of daemon replies; `None` means the daemon didn't answer
+pub struct Snapshot {
+    pub vriftd: Option<DaemonMetrics>,
+    pub vdird: Option<DaemonMetrics>,
+}
+
+pub async fn run(project_dir: &Path, interval: Duration) -> Result<()> {
+    let project_id = vrift_config::path::compute_project_id(project_dir);
+    let vdird_socket = vrift_config::path::get_vdird_socket_path(&project_id);
+
+    // CAS growth is measured against the first successful reading
+    let mut baseline: Option<(u64, u64)> = None;
+
+    loop {
+        let snapshot = Snapshot {
+            vriftd: daemon::fetch_metrics(None).await.ok(),
+            vdird: match vdird_socket.as_deref() {
+                Some(socket) => daemon::fetch_metrics(Some(socket)).await.ok(),
+                None => None,
+            },
+        };
+        if baseline.is_none() {
+            baseline = snapshot.vriftd.as_ref().map(|m| (m.cas_blobs, m.cas_bytes));
+        }
+
+        // Clear screen and home the cursor before each redraw
+        print!("\x1B[2J\x1B[H");
+        print!("{}", render(project_dir, &snapshot, baseline, unix_now()));
+        println!();
+        println!("Refreshing every {}s. Ctrl-C to exit.", interval.as_secs());
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Render one frame. `baseline` is (blobs, bytes) at the first tick.
+pub fn render(
+    project_dir: &Path,
+    snapshot: &Snapshot,
+    baseline: Option<(u64, u64)>,
+    now: u64,
+) -> String {
+    use std::fmt::Write;
+    let mut out = String::new();
+
+    let _ = writeln!(out, "Velo Rift Status (watch)");
+    let _ = writeln!(out, "========================");
+    let _ = writeln!(out, "Project: {}", project_dir.display());
+    let _ = writeln!(out);
+
+    match &snapshot.vriftd {
+        Some(m) => {
+            let (base_blobs, base_bytes) = baseline.unwrap_or((m.cas_blobs, m.cas_bytes));
+            let _ = writeln!(out, "vriftd: up {}", format_uptime(m.uptime_secs));
+            let _ = writeln!(
+                out,
+                "  CAS blobs:   {} (+{})",
+                format_number(m.cas_blobs),
+                m.cas_blobs.saturating_sub(base_blobs)
+            );
+            let _ = writeln!(
+                out,
+                "  CAS size:    {} (+{})",
+                format_bytes(m.cas_bytes),
+                format_bytes(m.cas_bytes.saturating_sub(base_bytes))
+            );
+            let _ = writeln!(out, "  Connections: {}", m.connections);
+            let _ = writeln!(out, "  vDird procs: {}", m.vdird_processes);
+        }
+        None => {
+            let _ = writeln!(out, "vriftd: not running");
+        }
+    }
+    let _ = writeln!(out);
+
+    match &snapshot.vdird {
+        Some(m) => {
+            let _ = writeln!(out, "vdir_d: up {}", format_uptime(m.uptime_secs));
+            let _ = writeln!(
+                out,
+                "  Manifest entries: {}",
+                format_number(m.manifest_entries)
+            );
+            let _ = writeln!(out, "  Connections:      {}", m.connections);
+            let _ = writeln!(out, "  Pending journal:  {}", m.pending_journal);
+            let _ = writeln!(out, "  Reingests:        {}", m.reingests);
+            if m.vdir_admissions > 0 || m.vdir_misses_reported > 0 {
+                let _ = writeln!(
+                    out,
+                    "  Hot cache:        {} admitted, {} evicted, {} misses reported",
+                    format_number(m.vdir_admissions),
+                    format_number(m.vdir_evictions),
+                    format_number(m.vdir_misses_reported)
+                );
+            }
+            if !m.recent_reingests.is_empty() {
+                let _ = writeln!(out);
+                let _ = writeln!(out, "  Recent reingests:");
+                for ev in m.recent_reingests.iter().rev().take(RECENT_ROWS) {
+                    let _ = writeln!(
+                        out,
+                        "    {:>6} ago  {:>10}  {}",
+                        format_uptime(now.saturating_sub(ev.at_secs)),
+                        format_bytes(ev.size),
+                        ev.vpath
+                    );
+                }
+            }
+        }
+        None => {
+            let _ = writeln!(out, "vdir_d: not running for this project");
+        }
+    }
+    out
+}
+
+fn format_uptime(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}h{}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_ipc::ReingestEvent;
+
+    #[test]
+    fn test_render_growth_and_recent_reingests() {
+        let snapshot = Snapshot {
+            vriftd: Some(DaemonMetrics {
+                uptime_secs: 90,
+                cas_blobs: 12,
+                cas_bytes: 4096,
+                connections: 3,
+                ..Default::default()
+            }),
+            vdird: Some(DaemonMetrics {
+                manifest_entries: 1500,
+                pending_journal: 2,
+                reingests: 1,
+                recent_reingests: vec![ReingestEvent {
+                    vpath: "/src/lib.rs".to_string(),
+                    size: 10,
+                    at_secs: 995,
+                }],
+                ..Default::default()
+            }),
+        };
+        let out = render(Path::new("/p"), &snapshot, Some((10, 2048)), 1000);
+        assert!(out.contains("vriftd: up 1m30s"));
+        assert!(out.contains("CAS blobs:   12 (+2)"));
+        assert!(out.contains("(+2.00 KB)"));
+        assert!(out.contains("Manifest entries: 1,500"));
+        assert!(out.contains("Pending journal:  2"));
+        assert!(out.contains("5s ago"));
+        assert!(out.contains("/src/lib.rs"));
+    }
+
+    #[test]
+    fn test_render_daemons_down() {
+        let snapshot = Snapshot {
+            vriftd: None,
+            vdird: None,
+        };
+        let out = render(Path::new("/p"), &snapshot, None, 0);
+        assert!(out.contains("vriftd: not running"));
+        assert!(out.contains("vdir_d: not running"));
+    }
+}
@@ -0,0 +1,156 @@
+//! # Manifest Diff
+//!
+//! `vrift diff A B` compares two manifests (files or LMDB directories) and
+//! prints what changed going from A to B, one path per line in the style of
+//! `git diff --name-status`, followed by a summary. Used to audit what an
+//! ingest actually changed between two snapshots.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::Args;
+use vrift_manifest::{ManifestDiff, VnodeEntry};
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Older manifest (manifest file or LMDB manifest directory)
+    #[arg(value_name = "MANIFEST_A")]
+    old: PathBuf,
+
+    /// Newer manifest
+    #[arg(value_name = "MANIFEST_B")]
+    new: PathBuf,
+
+    /// Only print the summary line
+    #[arg(long)]
+    stat: bool,
+
+    /// Exit with status 1 if the manifests differ
+    #[arg(long)]
+    exit_code: bool,
+}
+
+pub fn run(args: DiffArgs) -> Result<()> {
+    let old = crate::pack::load_manifest(&args.old)?;
+    let new = crate::pack::load_manifest(&args.new)?;
+    let diff = old.diff(&new);
+
+    if !args.stat {
+        for line in name_status(&diff) {
+            println!("{}", line);
+        }
+    }
+    println!("{}", summary(&diff, &args.old, &args.new));
+
+    if args.exit_code && !diff.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `A`/`M`/`D` lines, merged into one path-ordered listing
+fn name_status(diff: &ManifestDiff) -> Vec<String> {
+    let mut lines: Vec<(&str, String)> = Vec::with_capacity(diff.len());
+    for (path, _) in &diff.added {
+        lines.push((path, format!("A  {}", path)));
+    }
+    for (path, _) in &diff.removed {
+        lines.push((path, format!("D  {}", path)));
+    }
+    for (path, old, new) in &diff.modified {
+        lines.push((
+            path,
+            format!("M  {}  ({})", path, describe_change(old, new)),
+        ));
+    }
+    lines.sort_by(|a, b| a.0.cmp(b.0));
+    lines.into_iter().map(|(_, line)| line).collect()
+}
+
+fn describe_change(old: &VnodeEntry, new: &VnodeEntry) -> String {
+    let mut what = Vec::new();
+    if kind(old) != kind(new) {
+        what.push(format!("{} → {}", kind(old), kind(new)));
+    } else if old.content_hash != new.content_hash || old.size != new.size {
+        what.push(format!("content, {} → {} bytes", old.size, new.size));
+    }
+    if old.mode != new.mode {
+        what.push(format!("mode {:o} → {:o}", old.mode, new.mode));
+    }
+    if what.is_empty() {
+        // Same type, content and mode: only representation flags differ
+        what.push(format!("flags {:#x} → {:#x}", old.flags, new.flags));
+    }
+    what.join(", ")
+}
+
+fn kind(entry: &VnodeEntry) -> &'static str {
+    if entry.is_dir() {
+        "dir"
+    } else if entry.is_symlink() {
+        "symlink"
+    } else {
+        "file"
+    }
+}
+
+fn summary(diff: &ManifestDiff, old: &Path, new: &Path) -> String {
+    if diff.is_empty() {
+        return format!("No changes between {} and {}", old.display(), new.display());
+    }
+    let file_bytes = |e: &VnodeEntry| if e.is_dir() { 0 } else { e.size };
+    let mut grown: u64 = diff.added.iter().map(|(_, e)| file_bytes(e)).sum();
+    let mut shrunk: u64 = diff.removed.iter().map(|(_, e)| file_bytes(e)).sum();
+    for (_, old, new) in &diff.modified {
+        let (a, b) = (file_bytes(old), file_bytes(new));
+        if b > a {
+            grown += b - a;
+        } else {
+            shrunk += a - b;
+        }
+    }
+    format!(
+        " {} path{} changed: {} added, {} modified, {} removed (+{}, -{})",
+        diff.len(),
+        if diff.len() == 1 { "" } else { "s" },
+        diff.added.len(),
+        diff.modified.len(),
+        diff.removed.len(),
+        crate::format_bytes(grown),
+        crate::format_bytes(shrunk)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_manifest::Manifest;
+
+    #[test]
+    fn test_name_status_and_summary() {
+        let mut old = Manifest::new();
+        old.insert("/b.txt", VnodeEntry::new_file([1u8; 32], 100, 0, 0o644));
+        old.insert("/run.sh", VnodeEntry::new_file([2u8; 32], 10, 0, 0o644));
+        old.insert("/z.txt", VnodeEntry::new_file([3u8; 32], 50, 0, 0o644));
+        let mut new = Manifest::new();
+        new.insert("/a.txt", VnodeEntry::new_file([4u8; 32], 2048, 0, 0o644));
+        new.insert("/b.txt", VnodeEntry::new_file([5u8; 32], 40, 0, 0o644));
+        new.insert("/run.sh", VnodeEntry::new_file([2u8; 32], 10, 0, 0o755));
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            name_status(&diff),
+            [
+                "A  /a.txt",
+                "M  /b.txt  (content, 100 → 40 bytes)",
+                "M  /run.sh  (mode 644 → 755)",
+                "D  /z.txt",
+            ]
+        );
+        assert_eq!(
+            summary(&diff, Path::new("a"), Path::new("b")),
+            " 4 paths changed: 1 added, 2 modified, 1 removed (+2.00 KB, -110 B)"
+        );
+        assert!(summary(&new.diff(&new), Path::new("a"), Path::new("b")).starts_with("No changes"));
+    }
+}
@@ -143,6 +143,14 @@ fn check_config(d: &mut DiagResult) {
                 "Config loads successfully (version {})",
                 cfg.config_version
             ));
+            if cfg.project.passthrough.is_empty() {
+                d.info("Passthrough: none (everything under the VFS prefix is virtualized)");
+            } else {
+                d.info(&format!(
+                    "Passthrough (real files): {}",
+                    cfg.project.passthrough.join(", ")
+                ));
+            }
         }
         Err(e) => {
             d.fail(&format!("Config load failed: {}", e));
@@ -208,10 +216,12 @@ fn check_daemon(d: &mut DiagResult) {
     if socket.exists() {
         d.pass(&format!("Socket exists: {}", socket.display()));
 
-        // Try connecting
-        match std::os::unix::net::UnixStream::connect(socket) {
-            Ok(_) => d.pass("Daemon is responsive"),
-            Err(e) => d.warn(&format!("Socket exists but can't connect: {}", e)),
+        // Handshake probe: a crashed daemon leaves the socket file behind
+        if vrift_ipc::probe_socket(socket, vrift_ipc::PROBE_TIMEOUT) {
+            d.pass("Daemon is responsive");
+        } else {
+            d.warn("Socket exists but daemon did not answer (stale socket)");
+            d.info("It will be reclaimed on next daemon start");
         }
     } else {
         d.warn(&format!("Daemon socket not found: {}", socket.display()));
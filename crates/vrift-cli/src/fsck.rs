@@ -0,0 +1,101 @@
+//! # CAS Integrity Check
+//!
+//! `vrift fsck` re-hashes every blob in the CAS (all tiers) and reports
+//! corrupt, resized and misplaced ones. With `--quarantine` they are moved
+//! out of the store; either way the registered manifest paths that refer
+//! to them are listed, since those files must be re-ingested from a good
+//! copy. The daemon runs the same check in the background (see
+//! `[daemon] scrub_interval_hours`).
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vrift_cas::{CasStore, ScrubOptions, ScrubProblem, ScrubReport};
+
+use crate::format_bytes;
+use crate::registry::ManifestRegistry;
+
+#[derive(Args, Debug)]
+pub struct FsckArgs {
+    /// Move bad blobs to the quarantine directory (default: report only)
+    #[arg(long)]
+    quarantine: bool,
+
+    /// Hash at most this many bytes per second (K/M/G suffix allowed)
+    #[arg(long, value_name = "RATE", value_parser = crate::cas::parse_size)]
+    max_rate: Option<u64>,
+}
+
+pub fn run(cas_root: &Path, args: FsckArgs) -> Result<()> {
+    println!("🔍 Checking CAS at {}", cas_root.display());
+    let cas = CasStore::new(cas_root)?;
+    let report = cas
+        .scrub(&ScrubOptions {
+            max_bytes_per_sec: args.max_rate,
+            quarantine: args.quarantine,
+        })
+        .context("CAS scrub failed")?;
+
+    println!(
+        "   {} blobs checked ({})",
+        report.checked,
+        format_bytes(report.bytes)
+    );
+    if report.is_clean() {
+        println!("✅ No problems found");
+        return Ok(());
+    }
+
+    print_findings(&report);
+    if args.quarantine {
+        println!(
+            "   Quarantined blobs are in {}",
+            cas.quarantine_dir().display()
+        );
+    }
+
+    let registry = ManifestRegistry::load_or_create()?;
+    let affected = registry.paths_with_hashes(&report.damaged_hashes())?;
+    if affected.is_empty() {
+        println!("   No registered manifest refers to them.");
+    } else {
+        println!();
+        println!("Affected files (re-ingest them from a good copy):");
+        for (project_root, paths) in &affected {
+            println!("  {}", project_root.display());
+            for path in paths {
+                println!("    {}", path);
+            }
+        }
+    }
+
+    anyhow::bail!("{} bad blobs found", report.findings.len())
+}
+
+fn print_findings(report: &ScrubReport) {
+    println!();
+    for finding in &report.findings {
+        let what = match &finding.problem {
+            ScrubProblem::Corrupt { .. } => "corrupt".to_string(),
+            ScrubProblem::Undecodable => "cannot be decompressed".to_string(),
+            ScrubProblem::SizeMismatch { recorded, actual } => {
+                format!("size {} but named {}", actual, recorded)
+            }
+            ScrubProblem::Misplaced => "in the wrong directory".to_string(),
+            ScrubProblem::Unrecognized => "not a blob".to_string(),
+        };
+        let moved = if finding.quarantined.is_some() {
+            " (quarantined)"
+        } else {
+            ""
+        };
+        println!("❌ {}: {}{}", finding.path.display(), what, moved);
+    }
+    if !report.damaged_chunked.is_empty() {
+        println!(
+            "❌ {} chunked blobs include a bad chunk",
+            report.damaged_chunked.len()
+        );
+    }
+}
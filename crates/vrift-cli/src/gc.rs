@@ -1,6 +1,8 @@
 //! # Garbage Collection (RFC-0041)
 //!
 //! Multi-manifest garbage collection with registry integration and Bloom-assisted daemon sweep.
+//! Blobs of the registered projects' snapshot generations are kept, so a
+//! snapshot can still be restored after GC.
 
 use anyhow::{Context, Result};
 use clap::Args;
@@ -11,6 +13,7 @@ use std::time::Instant;
 use vrift_cas::CasStore;
 use vrift_manifest::Manifest;
 
+use crate::daemon::DaemonContext;
 use crate::registry::ManifestRegistry;
 
 #[derive(Args, Debug)]
@@ -47,7 +50,7 @@ pub async fn run(cas_root: &Path, args: GcArgs) -> Result<()> {
     let (active_count, stale_count) = registry.verify_all();
 
     // Collect all referenced blob hashes
-    let keep_set: HashSet<_> = if let Some(ref manifest_path) = args.manifest {
+    let mut keep_set: HashSet<_> = if let Some(ref manifest_path) = args.manifest {
         println!();
         println!("  [Legacy Mode] Using single manifest: {:?}", manifest_path);
         let manifest = Manifest::load(manifest_path).context("Failed to parse manifest")?;
@@ -82,6 +85,20 @@ pub async fn run(cas_root: &Path, args: GcArgs) -> Result<()> {
         format_number(keep_set.len() as u64)
     );
 
+    // Pinned blobs are kept even when no manifest references them. The
+    // daemon's sweep also consults the pin store; adding them here keeps
+    // the dry run honest.
+    let pinned = vrift_cas::PinStore::load(cas_root)
+        .context("Failed to read pin store")?
+        .pinned_hashes();
+    if !pinned.is_empty() {
+        println!(
+            "  📌 Pinned blobs:     {}",
+            format_number(pinned.len() as u64)
+        );
+    }
+    keep_set.extend(pinned);
+
     // Build Bloom Filter from keep_set
     use vrift_ipc::{BloomFilter, BLOOM_SIZE};
     let mut bloom = BloomFilter::new(BLOOM_SIZE);
@@ -106,46 +123,41 @@ pub async fn run(cas_root: &Path, args: GcArgs) -> Result<()> {
         println!("  🧼 Triggering CAS sweep via daemon...");
 
         // Connect to daemon and send sweep request
-        use vrift_ipc::{VeloRequest, VeloResponse};
         let project_root = std::env::current_dir().context("Failed to get current directory")?;
-        let conn = crate::daemon::connect_to_daemon(&project_root)
+        let mut conn = crate::daemon::connect_to_daemon(&project_root)
             .await
             .context("Daemon not running or unreachable")?;
-        let mut stream = conn.stream;
-        crate::daemon::send_request(
-            &mut stream,
-            VeloRequest::CasSweep {
-                bloom_filter: bloom.bits.clone(),
-            },
-        )
-        .await?;
-
-        match crate::daemon::read_response(&mut stream).await? {
-            VeloResponse::CasSweepAck {
-                deleted_count,
-                reclaimed_bytes,
-            } => {
-                let gc_elapsed = gc_start.elapsed().as_secs_f64();
-                println!();
-                println!("╔════════════════════════════════════════╗");
-                println!("║  ✅ GC Complete in {:.2}s              ║", gc_elapsed);
-                println!("╚════════════════════════════════════════╝");
-                println!();
-                println!(
-                    "   🗑️  {} orphaned blobs deleted",
-                    format_number(deleted_count as u64)
-                );
-                println!("   💾 {} reclaimed", format_bytes(reclaimed_bytes));
-            }
-            VeloResponse::Error(e) => return Err(anyhow::anyhow!("Sweep failed: {}", e)),
-            _ => return Err(anyhow::anyhow!("Unexpected response from daemon")),
-        }
+        let (deleted_count, reclaimed_bytes) = conn
+            .client
+            .cas_sweep(bloom.bits.clone())
+            .await
+            .daemon_context("Sweep failed")?;
+
+        let gc_elapsed = gc_start.elapsed().as_secs_f64();
+        println!();
+        println!("╔════════════════════════════════════════╗");
+        println!("║  ✅ GC Complete in {:.2}s              ║", gc_elapsed);
+        println!("╚════════════════════════════════════════╝");
+        println!();
+        println!(
+            "   🗑️  {} orphaned blobs deleted",
+            format_number(deleted_count as u64)
+        );
+        println!("   💾 {} reclaimed", format_bytes(reclaimed_bytes));
     } else {
         println!("\n  📋 Dry Run: Scanning CAS for orphaned blobs...");
         let cas = CasStore::new(cas_root)?;
         let mut orphan_count = 0u64;
         let mut orphan_bytes = 0u64;
 
+        // Chunks of a kept chunked blob are kept with it (as the sweep does)
+        let chunk_leaves: Vec<_> = keep_set
+            .iter()
+            .filter_map(|hash| cas.chunk_list(hash).ok().flatten())
+            .flat_map(|list| list.chunks.into_iter().map(|c| c.hash))
+            .collect();
+        keep_set.extend(chunk_leaves);
+
         if let Ok(iter) = cas.iter() {
             for hash in iter.flatten() {
                 if !keep_set.contains(&hash) {
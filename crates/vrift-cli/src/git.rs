@@ -0,0 +1,283 @@
+//! # Git Ingest
+//!
+//! `vrift ingest-git <repo> --rev <rev>` snapshots a commit (or any tree-ish)
+//! straight from the repository's object database, with no working copy:
+//! blobs go to the CAS and trees become manifest directories. CI can
+//! snapshot a revision from a bare or shallow clone without checking it out.
+//!
+//! Git only records the executable bit, so files get mode 0644 or 0755 and
+//! directories 0755. Every entry takes the commit's time as its mtime, which
+//! keeps snapshots of the same commit identical. Submodules become empty
+//! directories, as in a checkout without `--recurse-submodules`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use gix::bstr::ByteSlice;
+use rayon::prelude::*;
+use vrift_cas::{Blake3Hash, CasStore};
+use vrift_manifest::lmdb::AssetTier;
+use vrift_manifest::{LmdbManifest, Provenance, VnodeEntry};
+
+#[derive(Args, Debug)]
+pub struct IngestGitArgs {
+    /// Repository (work tree, `.git` directory or bare repository)
+    #[arg(value_name = "REPO", default_value = ".")]
+    repo: PathBuf,
+
+    /// Revision to snapshot (commit, branch, tag or tree)
+    #[arg(long, default_value = "HEAD")]
+    rev: String,
+
+    /// LMDB manifest to create
+    #[arg(short, long, default_value = "vrift.lmdb")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GitIngestSummary {
+    pub files: usize,
+    pub dirs: usize,
+    pub symlinks: usize,
+    /// Submodules, recorded as empty directories
+    pub submodules: usize,
+    pub bytes: u64,
+    /// Distinct blobs read from the object database
+    pub blobs: usize,
+}
+
+/// A revision's tree, ready to be written as a manifest
+pub struct GitSnapshot {
+    pub entries: Vec<(String, VnodeEntry)>,
+    pub summary: GitIngestSummary,
+    /// Commit id, when the revision names a commit rather than a bare tree
+    pub commit: Option<String>,
+}
+
+pub fn run(args: IngestGitArgs, cas_root: &Path) -> Result<()> {
+    if args.output.exists() {
+        anyhow::bail!("{} already exists", args.output.display());
+    }
+    let repo = gix::open(&args.repo)
+        .with_context(|| format!("Failed to open git repository: {}", args.repo.display()))?;
+    let cas = CasStore::new(cas_root)
+        .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+
+    let started = std::time::Instant::now();
+    let GitSnapshot {
+        entries,
+        summary,
+        commit,
+    } = read_tree(&repo, &args.rev, &cas)?;
+
+    let manifest = LmdbManifest::open(&args.output)
+        .with_context(|| format!("Failed to create manifest: {}", args.output.display()))?;
+    for (key, vnode) in entries {
+        manifest.insert(&key, vnode, AssetTier::Tier2Mutable);
+    }
+    let mut provenance = Provenance::capture(std::env::args().collect::<Vec<_>>().join(" "), None);
+    provenance.vcs_commit = commit.clone();
+    manifest.commit_with_provenance(&provenance)?;
+    crate::registry::ManifestRegistry::register_or_warn(&args.output, &args.repo);
+
+    println!(
+        "✅ Ingested {} at {} in {:.2}s",
+        args.repo.display(),
+        commit.as_deref().unwrap_or(&args.rev),
+        started.elapsed().as_secs_f64()
+    );
+    println!(
+        "   {} files, {} directories, {} symlinks ({:.1} MB, {} distinct blobs)",
+        summary.files,
+        summary.dirs,
+        summary.symlinks,
+        summary.bytes as f64 / 1_048_576.0,
+        summary.blobs
+    );
+    if summary.submodules > 0 {
+        println!("   Submodules: {} (empty directories)", summary.submodules);
+    }
+    println!("   📄 Manifest: {}", args.output.display());
+    Ok(())
+}
+
+/// Manifest entries for `rev`'s tree, with every blob stored in `cas`
+pub fn read_tree(repo: &gix::Repository, rev: &str, cas: &CasStore) -> Result<GitSnapshot> {
+    let commit = repo
+        .rev_parse_single(format!("{}^{{commit}}", rev).as_str())
+        .ok()
+        .and_then(|id| id.object().ok())
+        .map(|object| object.into_commit());
+    let mtime = match &commit {
+        Some(commit) => commit.time()?.seconds.max(0) as u64,
+        None => 0,
+    };
+    let tree = repo
+        .rev_parse_single(format!("{}^{{tree}}", rev).as_str())
+        .with_context(|| format!("No tree for revision {:?}", rev))?
+        .object()?
+        .into_tree();
+
+    let mut recorder = gix::traverse::tree::Recorder::default();
+    tree.traverse()
+        .breadthfirst(&mut recorder)
+        .context("Failed to walk the tree")?;
+
+    // Each distinct blob is read and stored once, on all cores
+    let mut oids: Vec<gix::ObjectId> = recorder
+        .records
+        .iter()
+        .filter(|r| r.mode.is_blob() || r.mode.is_link())
+        .map(|r| r.oid)
+        .collect();
+    oids.sort_unstable();
+    oids.dedup();
+    let shared = repo.clone().into_sync();
+    let stored: HashMap<gix::ObjectId, (Blake3Hash, u64)> = oids
+        .par_iter()
+        .map_init(
+            || shared.to_thread_local(),
+            |repo, oid| -> Result<_> {
+                let blob = repo
+                    .find_object(*oid)
+                    .with_context(|| format!("Failed to read blob {}", oid))?;
+                let hash = cas.store(&blob.data)?;
+                Ok((*oid, (hash, blob.data.len() as u64)))
+            },
+        )
+        .collect::<Result<_>>()?;
+
+    let mut summary = GitIngestSummary {
+        blobs: stored.len(),
+        ..Default::default()
+    };
+    let mut entries = Vec::with_capacity(recorder.records.len());
+    for record in &recorder.records {
+        let key = format!(
+            "/{}",
+            vrift_ipc::encode_path_key(record.filepath.as_bytes())
+        );
+        let vnode = if record.mode.is_tree() {
+            summary.dirs += 1;
+            VnodeEntry::new_directory(mtime, 0o755)
+        } else if record.mode.is_commit() {
+            summary.submodules += 1;
+            VnodeEntry::new_directory(mtime, 0o755)
+        } else if record.mode.is_link() {
+            summary.symlinks += 1;
+            let (hash, len) = stored[&record.oid];
+            VnodeEntry::new_symlink(hash, len, mtime)
+        } else {
+            summary.files += 1;
+            let (hash, size) = stored[&record.oid];
+            summary.bytes += size;
+            let mode = if record.mode.is_executable() {
+                0o755
+            } else {
+                0o644
+            };
+            VnodeEntry::new_file(hash, size, mtime, mode)
+        };
+        entries.push((key, vnode));
+    }
+    Ok(GitSnapshot {
+        entries,
+        summary,
+        commit: commit.map(|c| c.id.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gix::objs::tree::{Entry, EntryKind};
+
+    fn write_tree(repo: &gix::Repository, mut entries: Vec<Entry>) -> gix::ObjectId {
+        entries.sort();
+        repo.write_object(gix::objs::Tree { entries })
+            .unwrap()
+            .detach()
+    }
+
+    fn entry(kind: EntryKind, name: &str, oid: gix::ObjectId) -> Entry {
+        Entry {
+            mode: kind.into(),
+            filename: name.into(),
+            oid,
+        }
+    }
+
+    #[test]
+    fn test_read_tree_without_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = gix::init_bare(dir.path().join("repo.git")).unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+
+        let lib = repo.write_blob(b"pub fn a() {}").unwrap().detach();
+        let script = repo.write_blob(b"#!/bin/sh\n").unwrap().detach();
+        let link = repo.write_blob(b"src/lib.rs").unwrap().detach();
+        let src = write_tree(
+            &repo,
+            vec![
+                entry(EntryKind::Blob, "lib.rs", lib),
+                entry(EntryKind::Blob, "copy.rs", lib),
+            ],
+        );
+        let root = write_tree(
+            &repo,
+            vec![
+                entry(EntryKind::Tree, "src", src),
+                entry(EntryKind::BlobExecutable, "run.sh", script),
+                entry(EntryKind::Link, "lib.rs", link),
+            ],
+        );
+        let signature = gix::actor::SignatureRef {
+            name: "CI".into(),
+            email: "ci@example.com".into(),
+            time: gix::date::Time::new(1_700_000_000, 0),
+        };
+        let commit = repo
+            .commit_as(
+                signature,
+                signature,
+                "HEAD",
+                "snapshot",
+                root,
+                gix::commit::NO_PARENT_IDS,
+            )
+            .unwrap();
+
+        let snapshot = read_tree(&repo, "HEAD", &cas).unwrap();
+        assert_eq!(snapshot.commit, Some(commit.to_string()));
+        assert_eq!(
+            snapshot.summary,
+            GitIngestSummary {
+                files: 3,
+                dirs: 1,
+                symlinks: 1,
+                submodules: 0,
+                bytes: 36,
+                blobs: 3,
+            }
+        );
+        let entries: HashMap<String, VnodeEntry> = snapshot.entries.into_iter().collect();
+        let lib_entry = &entries["/src/lib.rs"];
+        assert_eq!(cas.get(&lib_entry.content_hash).unwrap(), b"pub fn a() {}");
+        assert_eq!(lib_entry.mode, 0o644);
+        assert_eq!(lib_entry.mtime, 1_700_000_000);
+        assert_eq!(entries["/src/copy.rs"].content_hash, lib_entry.content_hash);
+        assert_eq!(entries["/run.sh"].mode, 0o755);
+        assert!(entries["/src"].is_dir());
+        let link_entry = &entries["/lib.rs"];
+        assert!(link_entry.is_symlink());
+        assert_eq!(cas.get(&link_entry.content_hash).unwrap(), b"src/lib.rs");
+
+        // A bare tree id works too, without a commit time
+        let snapshot = read_tree(&repo, &src.to_string(), &cas).unwrap();
+        assert_eq!(snapshot.commit, None);
+        assert_eq!(snapshot.entries.len(), 2);
+        assert!(snapshot.entries.iter().all(|(_, v)| v.mtime == 0));
+    }
+}
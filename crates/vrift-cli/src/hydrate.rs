@@ -0,0 +1,295 @@
+//! # Bulk Hydration
+//!
+//! `vrift hydrate` materializes stubbed files of a sparse working tree from
+//! the CAS, the same way the inception layer does on first open when
+//! `[hydrate] enabled = true`. A file counts as a stub when it is absent or
+//! zero-length while its manifest entry is not; anything else is left alone
+//! so local edits are never overwritten.
+
+use std::fs::{self, File, FileTimes};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vrift_cas::CasStore;
+use vrift_ipc::VnodeEntry;
+use vrift_manifest::lmdb::LmdbManifest;
+
+#[derive(Args, Debug)]
+pub struct HydrateArgs {
+    /// Paths or directories to hydrate, relative to the project root
+    /// (default: the project's [hydrate] policy, or everything)
+    #[arg(value_name = "PATH")]
+    paths: Vec<String>,
+
+    /// Project directory (default: current directory)
+    #[arg(short, long, value_name = "DIR")]
+    directory: Option<PathBuf>,
+
+    /// Only report what would be materialized
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HydrateSummary {
+    pub hydrated: usize,
+    pub bytes: u64,
+    pub skipped: usize,
+    pub missing_blobs: usize,
+}
+
+pub fn run(args: HydrateArgs, cas_root: &Path) -> Result<()> {
+    let dir = args
+        .directory
+        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    let project_id = vrift_config::path::compute_project_id(&dir);
+    let manifest_path = vrift_config::path::get_manifest_db_path(&project_id)
+        .ok_or_else(|| anyhow::anyhow!("Could not determine manifest path"))?;
+
+    if !manifest_path.exists() {
+        anyhow::bail!(
+            "Manifest not found at {}. Run 'vrift init' first.",
+            manifest_path.display()
+        );
+    }
+
+    let manifest = LmdbManifest::open(&manifest_path)?;
+    let cas = CasStore::new(cas_root)?;
+
+    // Explicit paths replace the configured policy
+    let rules = if args.paths.is_empty() {
+        vrift_config::Config::load_for_project(&dir)
+            .map(|cfg| cfg.hydrate.paths)
+            .unwrap_or_default()
+    } else {
+        args.paths
+    };
+
+    println!();
+    println!("💧 VRift Hydrate");
+    println!("   Project: {}", dir.display());
+    if !rules.is_empty() {
+        println!("   Paths:   {}", rules.join(", "));
+    }
+
+    let summary = hydrate_tree(&manifest, &cas, &dir, &rules, args.dry_run)?;
+
+    println!();
+    if args.dry_run {
+        println!(
+            "  Would hydrate {} files ({:.1} MB)",
+            summary.hydrated,
+            summary.bytes as f64 / 1_048_576.0
+        );
+    } else {
+        println!(
+            "✅ Hydrated {} files ({:.1} MB)",
+            summary.hydrated,
+            summary.bytes as f64 / 1_048_576.0
+        );
+    }
+    println!("   Already materialized: {}", summary.skipped);
+    if summary.missing_blobs > 0 {
+        println!(
+            "⚠️  {} entries have no blob in CAS (run 'vrift ingest' again)",
+            summary.missing_blobs
+        );
+    }
+    Ok(())
+}
+
+/// Materialize every stub under `root` selected by `rules`
+pub fn hydrate_tree(
+    manifest: &LmdbManifest,
+    cas: &CasStore,
+    root: &Path,
+    rules: &[String],
+    dry_run: bool,
+) -> Result<HydrateSummary> {
+    let mut summary = HydrateSummary::default();
+
+    for (key, entry) in manifest.iter()? {
+        let vnode = &entry.vnode;
+        if vnode.is_dir() || vnode.is_symlink() || vnode.is_whiteout() {
+            continue;
+        }
+        if !vrift_config::hydrate_policy_matches(rules.iter().map(String::as_str), &key) {
+            continue;
+        }
+
+        let target = root.join(key_to_rel_path(&key)?);
+        if !is_stub(&target, vnode) {
+            summary.skipped += 1;
+            continue;
+        }
+        // Whole or chunked: the reader reassembles chunked blobs
+        if !cas.exists(&vnode.content_hash) {
+            summary.missing_blobs += 1;
+            continue;
+        }
+
+        if !dry_run {
+            ensure_no_symlink_parents(root, &target)?;
+            materialize(cas, &target, vnode)
+                .with_context(|| format!("Failed to hydrate {}", target.display()))?;
+        }
+        summary.hydrated += 1;
+        summary.bytes += vnode.size;
+    }
+    Ok(summary)
+}
+
+/// Relative on-disk path for a manifest key (undoes non-UTF-8 escaping).
+/// Manifests and bundles can come from anywhere, so a key with anything
+/// but plain names in it (`..`, `.`) is refused rather than let it name a
+/// path outside the directory it is joined onto.
+pub fn key_to_rel_path(key: &str) -> Result<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    let bytes = vrift_ipc::decode_path_key(key.trim_start_matches('/'));
+    let path = PathBuf::from(std::ffi::OsStr::from_bytes(&bytes));
+    if let Some(bad) = path
+        .components()
+        .find(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        anyhow::bail!(
+            "Refusing manifest key {:?}: {:?} is not a plain name",
+            key,
+            bad.as_os_str()
+        );
+    }
+    Ok(path)
+}
+
+/// Refuse `target` when a directory between `root` and it is a symlink:
+/// writing there would follow the link out of `root`. Directories that
+/// don't exist yet are fine, they'll be created as real ones.
+pub fn ensure_no_symlink_parents(root: &Path, target: &Path) -> Result<()> {
+    let Some(parent) = target.strip_prefix(root).ok().and_then(Path::parent) else {
+        return Ok(());
+    };
+    let mut dir = root.to_path_buf();
+    for component in parent.components() {
+        dir.push(component);
+        match fs::symlink_metadata(&dir) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                anyhow::bail!("Refusing to write through symlink {}", dir.display())
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Absent, or an empty placeholder for non-empty content
+fn is_stub(target: &Path, vnode: &VnodeEntry) -> bool {
+    match fs::symlink_metadata(target) {
+        Ok(meta) => meta.is_file() && meta.len() == 0 && vnode.size > 0,
+        Err(_) => true,
+    }
+}
+
+/// Copy via a sibling temp file so readers never see a partial file
+fn materialize(cas: &CasStore, target: &Path, vnode: &VnodeEntry) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = target.with_file_name(format!(
+        ".{}.vrift-hydrate",
+        target
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default()
+    ));
+    let mut file = File::create(&tmp)?;
+    std::io::copy(&mut cas.get_reader(&vnode.content_hash)?, &mut file)?;
+    let mtime = UNIX_EPOCH + Duration::from_secs(vnode.mtime);
+    file.set_times(FileTimes::new().set_modified(mtime).set_accessed(mtime))?;
+    // Mode last: the manifest mode may be read-only
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(vnode.mode & 0o7777))?;
+    fs::rename(&tmp, target)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+    use vrift_manifest::lmdb::AssetTier;
+
+    fn setup() -> (tempfile::TempDir, LmdbManifest, CasStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = LmdbManifest::open(dir.path().join("manifest.lmdb")).unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        for (key, body) in [("/src/a.rs", "fn a() {}"), ("/vendor/b.rs", "fn b() {}")] {
+            let hash = cas.store(body.as_bytes()).unwrap();
+            let vnode = VnodeEntry {
+                content_hash: hash,
+                size: body.len() as u64,
+                mtime: 1_700_000_000,
+                mode: 0o640,
+                flags: 0,
+                _pad: 0,
+            };
+            manifest.insert(key, vnode, AssetTier::Tier2Mutable);
+        }
+        manifest.commit().unwrap();
+        (dir, manifest, cas)
+    }
+
+    #[test]
+    fn test_hydrates_stubs_per_policy() {
+        let (dir, manifest, cas) = setup();
+        let root = dir.path().join("tree");
+        fs::create_dir_all(root.join("src")).unwrap();
+        File::create(root.join("src/a.rs")).unwrap(); // empty placeholder
+
+        let rules = vec!["!vendor".to_string()];
+        let summary = hydrate_tree(&manifest, &cas, &root, &rules, false).unwrap();
+        assert_eq!(summary.hydrated, 1);
+        assert_eq!(
+            fs::read_to_string(root.join("src/a.rs")).unwrap(),
+            "fn a() {}"
+        );
+        let meta = fs::metadata(root.join("src/a.rs")).unwrap();
+        assert_eq!(meta.mode() & 0o777, 0o640);
+        assert_eq!(meta.mtime(), 1_700_000_000);
+        assert!(!root.join("vendor/b.rs").exists());
+    }
+
+    #[test]
+    fn test_never_overwrites_local_content() {
+        let (dir, manifest, cas) = setup();
+        let root = dir.path().join("tree");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/a.rs"), "local edit").unwrap();
+
+        let summary = hydrate_tree(&manifest, &cas, &root, &[], true).unwrap();
+        assert_eq!(summary.hydrated, 1); // vendor/b.rs (dry run)
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(
+            fs::read_to_string(root.join("src/a.rs")).unwrap(),
+            "local edit"
+        );
+        assert!(!root.join("vendor/b.rs").exists());
+    }
+
+    #[test]
+    fn test_refuses_to_hydrate_through_symlinks() {
+        let (dir, manifest, cas) = setup();
+        let root = dir.path().join("tree");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("src")).unwrap();
+
+        let rules = vec!["src".to_string()];
+        let err = hydrate_tree(&manifest, &cas, &root, &rules, false).unwrap_err();
+        assert!(format!("{:#}", err).contains("symlink"), "{:#}", err);
+        assert!(!outside.join("a.rs").exists());
+    }
+}
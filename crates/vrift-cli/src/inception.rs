@@ -31,6 +31,14 @@ const BOX_BR: &str = "╯";
 const BOX_H: &str = "─";
 const BOX_V: &str = "│";
 
+/// Session id for a new inception: the daemon's when connected, else local
+fn session_id_for(daemon_conn: Option<&crate::daemon::DaemonConnection>) -> String {
+    daemon_conn
+        .map(|c| c.session_id.clone())
+        .filter(|id| vrift_config::path::is_valid_session_id(id))
+        .unwrap_or_else(vrift_config::path::new_session_id)
+}
+
 // ============================================================================
 // Main Entry Point: vrift shell (or just `vrift` with no args)
 // ============================================================================
@@ -120,12 +128,17 @@ pub async fn cmd_shell(project_dir: &Path) -> Result<()> {
         eprintln!("Warning: Config load failed: {}. Using defaults.", e);
         vrift_config::Config::default()
     });
-    let shim_env = cfg.shim_env();
+    let mut shim_env = cfg.shim_env();
+    shim_env.extend(crate::cas::tiers_env(&cfg.storage.the_source));
+
+    // Staging files of this session live under .vrift/staging/<session_id>
+    let session_id = session_id_for(daemon_conn.as_ref());
 
     // Spawn subshell with VFS environment
     let mut cmd = Command::new(&shell);
     cmd.current_dir(&project_root)
         .env("VRIFT_INCEPTION", "1")
+        .env(vrift_config::path::SESSION_ID_ENV, &session_id)
         .env("PATH", new_path);
 
     // Phase 1.2: Inject vDird socket + mmap paths for zero-RPC inception init
@@ -160,6 +173,11 @@ pub async fn cmd_shell(project_dir: &Path) -> Result<()> {
         .env("PS1", format!("(vrift {}) $PS1", TOTEM_SPIN))
         .status()?;
 
+    // Session over: nothing left in its staging dir is still referenced
+    if let Err(e) = vrift_config::path::remove_session_staging(&project_root, &session_id) {
+        eprintln!("{} Failed to clean session staging: {}", WARN, e);
+    }
+
     // Wake up message
     eprintln!();
     eprintln!("{}{}{}", BOX_TL, BOX_H.repeat(35), BOX_TR);
@@ -234,7 +252,8 @@ pub async fn cmd_inception(project_dir: &Path) -> Result<()> {
         eprintln!("Warning: Config load failed: {}. Using defaults.", e);
         vrift_config::Config::default()
     });
-    let shim_env = cfg.shim_env();
+    let mut shim_env = cfg.shim_env();
+    shim_env.extend(crate::cas::tiers_env(&cfg.storage.the_source));
 
     // Output shell script to stdout (for eval)
     println!("# Velo Rift Inception Mode - Enter the Dream");
@@ -242,6 +261,11 @@ pub async fn cmd_inception(project_dir: &Path) -> Result<()> {
         println!("export {}=\"{}\"", key, value);
     }
     println!("export VRIFT_INCEPTION=1");
+    println!(
+        "export {}=\"{}\"",
+        vrift_config::path::SESSION_ID_ENV,
+        session_id_for(daemon_conn.as_ref())
+    );
     // Phase 1.2: Export vDird socket + mmap paths for zero-RPC inception init
     if let Some(ref conn) = daemon_conn {
         if !conn.vdird_socket.is_empty() {
@@ -322,6 +346,9 @@ pub fn cmd_wake() -> Result<()> {
     // shell-level value that was exported, not a fresh TOML load.
     let project_root = env::var("VRIFT_PROJECT_ROOT").unwrap_or_else(|_| ".".to_string());
     let _ = crate::active::deactivate(Path::new(&project_root));
+    if let Ok(session_id) = env::var(vrift_config::path::SESSION_ID_ENV) {
+        let _ = vrift_config::path::remove_session_staging(&project_root, &session_id);
+    }
 
     // Theatrical wake animation (to stderr)
     show_wake_animation();
@@ -333,6 +360,8 @@ pub fn cmd_wake() -> Result<()> {
     println!("unset VRIFT_MANIFEST");
     println!("unset VRIFT_VDIRD_SOCKET");
     println!("unset VRIFT_VDIR_MMAP");
+    println!("unset VRIFT_CAS_TIERS");
+    println!("unset {}", vrift_config::path::SESSION_ID_ENV);
     #[cfg(target_os = "macos")]
     {
         println!("unset DYLD_INSERT_LIBRARIES");
@@ -517,7 +546,7 @@ fn get_vfs_stats(vrift_dir: &Path) -> (String, String) {
     (file_count, cas_size)
 }
 
-fn find_inception_library(project_root: &Path) -> Result<std::path::PathBuf> {
+pub(crate) fn find_inception_library(project_root: &Path) -> Result<std::path::PathBuf> {
     let inception_name = if cfg!(target_os = "macos") {
         "libvrift_inception_layer.dylib"
     } else {
@@ -549,15 +578,21 @@ fn find_inception_library(project_root: &Path) -> Result<std::path::PathBuf> {
         }
     }
 
-    // Check cargo target directory (development mode)
-    let target_debug = Path::new("target/debug").join(inception_name);
-    if target_debug.exists() {
-        return normalize_for_ipc(target_debug).context("resolve target path");
-    }
-
-    let target_release = Path::new("target/release").join(inception_name);
-    if target_release.exists() {
-        return normalize_for_ipc(target_release).context("resolve target path");
+    // Check cargo target directory (development mode), preferring the
+    // profile this vrift was built with so the two don't drift apart
+    let target_dir = env::var_os("CARGO_TARGET_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| "target".into());
+    let profiles = if cfg!(debug_assertions) {
+        ["debug", "release"]
+    } else {
+        ["release", "debug"]
+    };
+    for profile in profiles {
+        let candidate = target_dir.join(profile).join(inception_name);
+        if candidate.exists() {
+            return normalize_for_ipc(candidate).context("resolve target path");
+        }
     }
 
     anyhow::bail!(
@@ -7,6 +7,8 @@
 //! - `vrift ingest <dir>` - Import files to CAS and generate manifest
 //! - `vrift run <cmd>` - Execute command with VeloVFS virtualization
 //! - `vrift status` - Display CAS statistics
+//! - `vrift diff <a> <b>` - Compare two manifests
+//! - `vrift bench` - Measure inception layer overhead
 
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -16,16 +18,37 @@ use clap::{Parser, Subcommand};
 use vrift_config::path::{normalize_for_ipc, normalize_or_original};
 
 mod active;
+mod bench;
+mod build_cache;
+mod cargo;
+mod cas;
+mod checkout;
+mod child;
 mod daemon;
+mod dashboard;
+mod diff;
 mod doctor;
+mod fsck;
 pub mod gc;
+mod git;
+mod hydrate;
 mod inception;
 mod isolation;
+mod manifest_edit;
 mod mount;
+mod node;
+mod oci;
+mod pack;
+mod pin;
 mod preflight;
+mod profile;
 pub mod registry;
 #[allow(dead_code)]
 mod security_filter;
+mod shim;
+mod snapshot;
+mod trace;
+mod venv;
 
 use vrift_cas::CasStore;
 use vrift_manifest::lmdb::LmdbManifest;
@@ -91,6 +114,14 @@ enum Commands {
         /// Useful for audit/verification when you suspect data corruption
         #[arg(long)]
         force_hash: bool,
+
+        /// Store compressible blobs zstd-compressed (text, sources, object files)
+        #[arg(long)]
+        compress: bool,
+
+        /// Apply tree-specific rules on top of the plain ingest
+        #[arg(long, value_enum)]
+        profile: Option<IngestProfile>,
     },
 
     /// Execute a command with VeloVFS virtualization
@@ -114,6 +145,47 @@ enum Commands {
         /// Run via daemon (delegated execution)
         #[arg(long)]
         daemon: bool,
+
+        /// With --daemon, also have the daemon append the command's output to FILE
+        #[arg(long, value_name = "FILE", requires = "daemon")]
+        log: Option<PathBuf>,
+
+        /// Run straight from a `vrift pack` bundle (no CAS or daemon needed)
+        #[arg(long, value_name = "BUNDLE", conflicts_with_all = ["isolate", "base", "daemon"])]
+        bundle: Option<PathBuf>,
+
+        /// Record an access heatmap to .vrift/heatmap.tsv (see `vrift profile heatmap`)
+        #[arg(long, conflicts_with_all = ["isolate", "daemon", "bundle"])]
+        heatmap: bool,
+
+        /// Inject this inception layer instead of searching for one
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["isolate", "daemon", "bundle"])]
+        shim_path: Option<PathBuf>,
+
+        /// Start vriftd if it isn't running and hand its vDird to the command
+        #[arg(long, conflicts_with_all = ["isolate", "daemon", "bundle"])]
+        start_daemon: bool,
+
+        /// How file access is intercepted; `trace` also reaches static binaries
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = RunBackend::Preload,
+            conflicts_with_all = ["isolate", "daemon", "bundle", "heatmap", "shim_path", "start_daemon"]
+        )]
+        backend: RunBackend,
+    },
+
+    /// Run cargo under the inception layer with target/ restored from a build cache
+    Cargo(cargo::CargoArgs),
+
+    /// Run a node script under the inception layer (after `ingest --profile node-modules`)
+    Node(node::NodeArgs),
+
+    /// Skip build steps whose inputs were built before, locally or on a shared cache
+    Cache {
+        #[command(subcommand)]
+        command: build_cache::CacheCommands,
     },
 
     /// Display CAS statistics and session status
@@ -133,14 +205,62 @@ enum Commands {
         /// Show Inception Layer internal diagnostics
         #[arg(long)]
         inception: bool,
+
+        /// Keep refreshing a live dashboard from daemon metrics
+        #[arg(short, long)]
+        watch: bool,
+
+        /// Refresh interval in seconds for --watch
+        #[arg(long, default_value_t = 2, value_name = "SECS")]
+        interval: u64,
     },
 
+    /// Snapshot a git revision into the CAS without checking it out
+    IngestGit(git::IngestGitArgs),
+
     /// Mount the manifest as a FUSE filesystem
     Mount(mount::MountArgs),
 
+    /// Write a manifest and all its blobs into one distributable bundle
+    Pack(pack::PackArgs),
+
+    /// Import a bundle into the CAS and write its manifest
+    Unpack(pack::UnpackArgs),
+
+    /// Show what changed between two manifests
+    Diff(diff::DiffArgs),
+
+    /// Materialize stubbed files of a sparse working tree from CAS
+    Hydrate(hydrate::HydrateArgs),
+
+    /// Write a manifest's whole tree into a directory (reflinks or hard links, not copies)
+    #[command(visible_alias = "export")]
+    Checkout(checkout::CheckoutArgs),
+
+    /// Write a manifest as an OCI image layout for container registries
+    ExportOci(oci::ExportOciArgs),
+
+    /// Flatten a Docker/OCI image into the CAS and a manifest
+    ImportOci(oci::ImportOciArgs),
+
     /// Garbage Collect unreferenced blobs
     Gc(gc::GcArgs),
 
+    /// Re-hash CAS blobs and report (or quarantine) damaged ones
+    Fsck(fsck::FsckArgs),
+
+    /// Protect blobs from garbage collection under a label
+    Pin {
+        #[command(subcommand)]
+        command: pin::PinCommands,
+    },
+
+    /// Manage CAS storage tiers and move blobs between them
+    Cas {
+        #[command(subcommand)]
+        command: cas::CasCommands,
+    },
+
     /// Resolve dependencies from a velo.lock file
     Resolve {
         /// Lockfile path
@@ -247,6 +367,33 @@ enum Commands {
         directory: Option<PathBuf>,
     },
 
+    /// Measure inception layer overhead against native filesystem access
+    Bench(bench::BenchArgs),
+
+    /// Build and inspect per-architecture inception layer libraries
+    Shim {
+        #[command(subcommand)]
+        command: shim::ShimCommands,
+    },
+
+    /// Snapshot the project manifest and roll back to earlier snapshots
+    Snapshot {
+        #[command(subcommand)]
+        command: snapshot::SnapshotCommands,
+    },
+
+    /// Access profiling reports
+    Profile {
+        #[command(subcommand)]
+        command: profile::ProfileCommands,
+    },
+
+    /// Syscall-level diagnostics (Linux, eBPF)
+    Trace {
+        #[command(subcommand)]
+        command: trace::TraceCommands,
+    },
+
     /// Debugging and observability tools (internal use)
     Debug {
         #[command(subcommand)]
@@ -254,6 +401,26 @@ enum Commands {
     },
 }
 
+/// Interception mechanism for `vrift run`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum RunBackend {
+    /// Inject the inception layer (LD_PRELOAD / DYLD_INSERT_LIBRARIES)
+    Preload,
+    /// Rewrite path syscalls from a supervisor: seccomp, or ptrace on older kernels (Linux)
+    Trace,
+    /// Like `trace`, but always ptrace (Linux x86_64)
+    Ptrace,
+}
+
+/// Tree-specific rules applied around a plain ingest
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum IngestProfile {
+    /// A Python virtualenv (pyvenv.cfg at the root): relocatable scripts, no __pycache__
+    PythonVenv,
+    /// A project's node_modules: relative links, native addon inventory, no caches
+    NodeModules,
+}
+
 #[derive(Subcommand)]
 enum DaemonCommands {
     /// Check daemon status (ping)
@@ -330,6 +497,80 @@ enum ManifestCommands {
         #[arg(value_name = "DIR")]
         directory: Option<PathBuf>,
     },
+
+    /// Remove an entry (and everything beneath it, for directories)
+    Rm {
+        /// Path to remove (relative to project root)
+        path: String,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+
+        /// Edit even while vdir_d is running for this project
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Rename an entry (and everything beneath it, for directories)
+    Mv {
+        /// Existing path
+        from: String,
+
+        /// New path (must not exist)
+        to: String,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+
+        /// Edit even while vdir_d is running for this project
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Add or replace an entry with the contents of a local file
+    Add {
+        /// Manifest path to write
+        path: String,
+
+        /// File whose contents are stored in CAS for this entry
+        #[arg(long, value_name = "FILE")]
+        from_file: PathBuf,
+
+        /// Octal mode (default: existing entry's mode, else the file's)
+        #[arg(long)]
+        mode: Option<String>,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+
+        /// Edit even while vdir_d is running for this project
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Re-hash an existing entry from the file currently in the project tree
+    Rehash {
+        /// Path to re-hash (relative to project root)
+        path: String,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+
+        /// Edit even while vdir_d is running for this project
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Make the running vdir_d republish the manifest on disk (after a --force edit)
+    Reload {
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -382,9 +623,18 @@ fn main() -> Result<()> {
         isolate,
         base,
         daemon: _,
+        log: _,
+        bundle: _,
+        heatmap: _,
+        shim_path: _,
+        start_daemon: _,
+        backend: _,
     }) = &cli.command
     {
         if *isolate {
+            if vrift_pack::is_bundle(manifest) {
+                anyhow::bail!("--isolate needs a CAS-backed manifest; run bundles without it");
+            }
             return isolation::run_isolated(command, manifest, &cas_root, base.as_deref());
         }
     }
@@ -394,7 +644,17 @@ fn main() -> Result<()> {
         .enable_all()
         .build()?;
 
-    rt.block_on(async_main(cli, cas_root, cli_cas_root_override))
+    // Daemon errors carry a kind; exit with its code instead of a blanket 1
+    if let Err(e) = rt.block_on(async_main(cli, cas_root, cli_cas_root_override)) {
+        match daemon::exit_code(&e) {
+            Some(code) => {
+                eprintln!("Error: {:?}", e);
+                std::process::exit(code);
+            }
+            None => return Err(e),
+        }
+    }
+    Ok(())
 }
 
 async fn async_main(
@@ -423,7 +683,24 @@ async fn async_main(
             no_security_filter: _,
             show_excluded: _,
             force_hash,
+            compress,
+            profile,
         } => {
+            let venv = match profile {
+                Some(IngestProfile::PythonVenv) => Some(venv::VenvLayout::detect(&directory)?),
+                _ => None,
+            };
+            if profile == Some(IngestProfile::NodeModules) {
+                node::detect(&directory)?;
+            }
+            let ignore: Vec<String> = match profile {
+                Some(IngestProfile::PythonVenv) => venv::IGNORE_PATTERNS,
+                Some(IngestProfile::NodeModules) => node::IGNORE_PATTERNS,
+                None => &[],
+            }
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
             let (mode, tier) = {
                 let config = vrift_config::config();
                 (
@@ -436,6 +713,7 @@ async fn async_main(
             let is_phantom = mode.to_lowercase() == "phantom";
             let is_tier1 = tier.to_lowercase() == "tier1";
             let prefix_val = prefix.unwrap_or_else(|| "".to_string());
+            let profile_prefix = prefix_val.clone();
 
             // RFC-0039: Always use LMDB manifest in target project directory
             // Must match daemon workspace path at get_or_create_workspace()
@@ -459,15 +737,20 @@ async fn async_main(
                 Some(prefix_val),
                 cli_cas_root_override.as_deref(),
                 force_hash,
+                compress,
+                ignore,
             )
             .await
             {
                 Ok(result) => {
                     let elapsed_secs = result.duration_ms as f64 / 1000.0;
-                    let files_per_sec = if elapsed_secs > 0.0 {
-                        result.files as f64 / elapsed_secs
+                    let (files_per_sec, mb_per_sec) = if elapsed_secs > 0.0 {
+                        (
+                            result.files as f64 / elapsed_secs,
+                            result.total_bytes as f64 / 1_048_576.0 / elapsed_secs,
+                        )
                     } else {
-                        0.0
+                        (0.0, 0.0)
                     };
                     let dedup_ratio = if result.files > 0 {
                         100.0 * (1.0 - (result.blobs as f64 / result.files as f64))
@@ -482,9 +765,62 @@ async fn async_main(
                     println!();
                     println!("   📁 {} files → {} blobs", result.files, result.blobs);
                     println!("   📊 {:.1}% dedup", dedup_ratio);
-                    println!("   ⚡ {:.0} files/sec", files_per_sec);
+                    println!(
+                        "   ⚡ {:.0} files/sec, {:.1} MB/s ({:.1}s)",
+                        files_per_sec, mb_per_sec, elapsed_secs
+                    );
                     println!("   📄 Manifest: {}", result.manifest_path);
 
+                    if let Some(layout) = &venv {
+                        let cas = CasStore::new(&cas_root)?;
+                        let applied = venv::apply(
+                            layout,
+                            &directory,
+                            Path::new(&result.manifest_path),
+                            &cas,
+                            &profile_prefix,
+                        )?;
+                        println!("   🐍 {}", layout.describe());
+                        println!(
+                            "   🔁 {} scripts made relocatable; activate with bin/{}",
+                            applied.scripts_relocated,
+                            venv::ACTIVATE_FILE
+                        );
+                    }
+                    if profile == Some(IngestProfile::NodeModules) {
+                        let cas = CasStore::new(&cas_root)?;
+                        let layout = node::apply(
+                            &directory,
+                            Path::new(&result.manifest_path),
+                            &cas,
+                            &profile_prefix,
+                        )?;
+                        println!(
+                            "   📦 {} packages ({} duplicate installs), {:.1} MB stored as {:.1} MB",
+                            layout.packages.len(),
+                            layout.duplicate_installs(),
+                            layout.bytes as f64 / 1_048_576.0,
+                            layout.unique_bytes as f64 / 1_048_576.0
+                        );
+                        println!(
+                            "   🔗 {} .bin links, {} made relative; {} native addons",
+                            layout.bin_links,
+                            layout.links_relativized,
+                            layout.native.len()
+                        );
+                    }
+
+                    // Stamp who/when/what produced this snapshot
+                    let provenance = vrift_manifest::Provenance::capture(
+                        std::env::args().collect::<Vec<_>>().join(" "),
+                        Some(&directory),
+                    );
+                    if let Err(e) = LmdbManifest::open(&result.manifest_path)
+                        .and_then(|m| m.set_provenance(&provenance))
+                    {
+                        tracing::warn!("Failed to record manifest provenance: {}", e);
+                    }
+
                     // RFC-0041: Explicitly register the manifest after ingest for GC tracking
                     // We attempt to acquire lock but don't block indefinitely on failures
                     match crate::registry::ManifestRegistry::load_or_create() {
@@ -514,12 +850,23 @@ async fn async_main(
                 Err(e) => Err(e),
             }
         }
+        Commands::Run {
+            bundle: Some(bundle),
+            command,
+            ..
+        } => pack::run_from_bundle(&bundle, &command),
         Commands::Run {
             manifest,
             command,
             isolate,
             base,
             daemon,
+            log,
+            bundle: None,
+            heatmap,
+            shim_path,
+            start_daemon,
+            backend,
         } => cmd_run(
             &cas_root,
             &manifest,
@@ -527,18 +874,47 @@ async fn async_main(
             isolate,
             base.as_deref(),
             daemon,
+            log.as_deref(),
+            heatmap,
+            shim_path.as_deref(),
+            start_daemon,
+            backend,
         ),
         Commands::Status {
             manifest,
             session,
             directory,
             inception,
+            watch,
+            interval,
         } => {
             let dir = directory.unwrap_or_else(|| std::env::current_dir().unwrap());
-            cmd_status(&cas_root, manifest.as_deref(), session, inception, &dir)
+            if watch {
+                dashboard::run(&dir, std::time::Duration::from_secs(interval.max(1))).await
+            } else {
+                cmd_status(&cas_root, manifest.as_deref(), session, inception, &dir)
+            }
         }
+        Commands::Cargo(args) => cargo::run(args, &cas_root).await,
+        Commands::Node(args) => node::run(args, &cas_root).await,
+        Commands::Cache { command } => build_cache::run(command, &cas_root).await,
+        Commands::IngestGit(args) => git::run(args, &cas_root),
         Commands::Mount(args) => mount::run(args, &cas_root),
+        Commands::Pack(args) => pack::run(args, &source_cas_root(&cli_cas_root_override)),
+        Commands::Unpack(args) => pack::unpack(args, &source_cas_root(&cli_cas_root_override)),
+        Commands::Diff(args) => diff::run(args),
+        Commands::Hydrate(args) => hydrate::run(args, &cas_root),
+        Commands::Checkout(args) => checkout::run(args, &cas_root),
+        Commands::ExportOci(args) => oci::run(args, &cas_root),
+        Commands::ImportOci(args) => oci::import(args, &cas_root),
         Commands::Gc(args) => gc::run(&cas_root, args).await,
+        Commands::Fsck(args) => fsck::run(&cas_root, args),
+        Commands::Pin { command } => pin::run(command, &cas_root),
+        Commands::Cas { command } => cas::run(command, &cas_root),
+        Commands::Profile { command } => profile::run(command).await,
+        Commands::Trace { command } => trace::run(command, &cas_root),
+        Commands::Shim { command } => shim::run(command),
+        Commands::Snapshot { command } => snapshot::run(command).await,
         Commands::Resolve { lockfile } => cmd_resolve(&cas_root, &lockfile),
         Commands::Daemon { command } => match command {
             DaemonCommands::Status { directory } => {
@@ -577,7 +953,7 @@ async fn async_main(
             ServiceCommands::Restart => cmd_service_restart(),
         },
         Commands::Config { command } => cmd_config(command),
-        Commands::Manifest { command } => cmd_manifest(command),
+        Commands::Manifest { command } => cmd_manifest(command, &cas_root).await,
         Commands::Sync { directory } => {
             let dir = directory.unwrap_or_else(|| std::env::current_dir().unwrap());
             cmd_sync(&dir).await
@@ -586,6 +962,7 @@ async fn async_main(
             let dir = directory.unwrap_or_else(|| std::env::current_dir().unwrap());
             doctor::cmd_doctor(&dir)
         }
+        Commands::Bench(args) => bench::run(args).await,
         Commands::Debug { command } => match command {
             DebugCommands::Vdir { file, directory } => cmd_debug_vdir(file, directory),
         },
@@ -836,8 +1213,56 @@ fn cmd_config(command: ConfigCommands) -> Result<()> {
 }
 
 /// Manifest management commands (RFC-0039 Live Ingest)
-fn cmd_manifest(command: ManifestCommands) -> Result<()> {
+async fn cmd_manifest(command: ManifestCommands, cas_root: &Path) -> Result<()> {
+    let cwd = || std::env::current_dir().unwrap();
     match command {
+        ManifestCommands::Rm {
+            path,
+            directory,
+            force,
+        } => manifest_edit::run(
+            manifest_edit::EditOp::Rm { path },
+            &directory.unwrap_or_else(cwd),
+            cas_root,
+            force,
+        ),
+        ManifestCommands::Mv {
+            from,
+            to,
+            directory,
+            force,
+        } => manifest_edit::run(
+            manifest_edit::EditOp::Mv { from, to },
+            &directory.unwrap_or_else(cwd),
+            cas_root,
+            force,
+        ),
+        ManifestCommands::Add {
+            path,
+            from_file,
+            mode,
+            directory,
+            force,
+        } => manifest_edit::run(
+            manifest_edit::EditOp::Add {
+                path,
+                from_file,
+                mode,
+            },
+            &directory.unwrap_or_else(cwd),
+            cas_root,
+            force,
+        ),
+        ManifestCommands::Rehash {
+            path,
+            directory,
+            force,
+        } => manifest_edit::run(
+            manifest_edit::EditOp::Rehash { path },
+            &directory.unwrap_or_else(cwd),
+            cas_root,
+            force,
+        ),
         ManifestCommands::Query { path, directory } => {
             let dir = directory.unwrap_or_else(|| std::env::current_dir().unwrap());
             let project_id = vrift_config::path::compute_project_id(&dir);
@@ -932,11 +1357,36 @@ fn cmd_manifest(command: ManifestCommands) -> Result<()> {
             println!("  Files:      {}", format_number(file_count as u64));
             println!("  Dirs:       {}", format_number(dir_count as u64));
             println!("  Total Size: {}", format_bytes(total_size));
+            print_provenance(&manifest)?;
             Ok(())
         }
+        ManifestCommands::Reload { directory } => {
+            manifest_edit::reload(&directory.unwrap_or_else(cwd)).await
+        }
     }
 }
 
+/// The CAS ingest writes to: explicit root, else VR_THE_SOURCE/config
+fn source_cas_root(cli_override: &Option<PathBuf>) -> PathBuf {
+    cli_override.clone().unwrap_or_else(|| {
+        vrift_manifest::normalize_path(&vrift_config::config().cas_root().to_string_lossy())
+    })
+}
+
+/// Print the provenance block of the last recorded snapshot
+fn print_provenance(manifest: &LmdbManifest) -> Result<()> {
+    let Some(p) = manifest.provenance()? else {
+        println!("  Provenance: (not recorded)");
+        return Ok(());
+    };
+    println!("Provenance:");
+    println!("  Written:    {}", format_timestamp(p.timestamp));
+    println!("  By:         {}@{}", p.user, p.hostname);
+    println!("  Command:    {}", p.command);
+    println!("  Commit:     {}", p.short_commit().unwrap_or("-"));
+    Ok(())
+}
+
 /// Synchronize project files with manifest (compensation scan)
 async fn cmd_sync(directory: &Path) -> Result<()> {
     use walkdir::WalkDir;
@@ -962,17 +1412,19 @@ async fn cmd_sync(directory: &Path) -> Result<()> {
     let mut new_files = 0u64;
     let mut new_dirs = 0u64;
 
-    // Scan filesystem for new files
-    for entry in WalkDir::new(directory).into_iter().filter_map(|e| e.ok()) {
+    // Scan filesystem for new files, skipping what ingest would skip
+    let ignore = vrift_config::ignore::IgnoreMatcher::new().with_root(directory);
+    let walker = WalkDir::new(directory).into_iter().filter_entry(|entry| {
+        let name = entry.file_name();
+        name != ".vrift"
+            && name != ".git"
+            && !ignore.matched(entry.path(), entry.file_type().is_dir())
+    });
+    for entry in walker.filter_map(|e| e.ok()) {
         let path = entry.path();
 
-        // Skip .vrift directory
         if let Ok(rel) = path.strip_prefix(directory) {
-            if rel.starts_with(".vrift") || rel.starts_with(".git") {
-                continue;
-            }
-
-            let manifest_key = format!("/{}", rel.display());
+            let manifest_key = vrift_ipc::path_key_for(rel);
 
             if !existing_paths.contains(&manifest_key) {
                 if path.is_dir() {
@@ -1057,6 +1509,7 @@ fn cmd_resolve(cas_root: &Path, lockfile: &Path) -> Result<()> {
 }
 
 /// Execute a command with Velo VFS shim
+#[allow(clippy::too_many_arguments)]
 fn cmd_run(
     cas_root: &Path,
     manifest: &Path,
@@ -1064,6 +1517,11 @@ fn cmd_run(
     isolate: bool,
     base: Option<&Path>,
     daemon_mode: bool,
+    daemon_log: Option<&Path>,
+    heatmap: bool,
+    shim_override: Option<&Path>,
+    start_daemon: bool,
+    backend: RunBackend,
 ) -> Result<()> {
     if command.is_empty() {
         anyhow::bail!("No command specified");
@@ -1076,7 +1534,13 @@ fn cmd_run(
                 .enable_all()
                 .build()?;
             let dir = std::env::current_dir().context("Failed to get current directory")?;
-            rt.block_on(daemon::spawn_command(command, dir.clone(), &dir))
+            let code = rt.block_on(daemon::spawn_command(
+                command,
+                dir.clone(),
+                &dir,
+                daemon_log,
+            ))?;
+            std::process::exit(code)
         });
     }
 
@@ -1084,14 +1548,46 @@ fn cmd_run(
         anyhow::bail!("Manifest not found: {}", manifest.display());
     }
 
+    // Bundles are served straight from the file, not through the CAS
+    if vrift_pack::is_bundle(manifest) {
+        return pack::run_from_bundle(manifest, command);
+    }
+
     // Handle isolation if requested
     if isolate {
         return isolation::run_isolated(command, manifest, cas_root, base);
     }
 
+    if backend != RunBackend::Preload {
+        return run_traced(cas_root, manifest, command, backend);
+    }
+
     // Standard LD_PRELOAD execution
     // Find the shim library
-    let shim_path = find_shim_library()?;
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let shim_path = match shim_override {
+        Some(path) => {
+            if !path.is_file() {
+                anyhow::bail!("Inception layer not found: {}", path.display());
+            }
+            normalize_or_original(path)
+        }
+        // Rosetta / multilib: inject a build matching the program's architecture
+        None => shim::select_library(&inception::find_inception_library(&cwd)?, &command[0]),
+    };
+
+    let daemon_conn = if start_daemon {
+        let conn = tokio::task::block_in_place(|| {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            rt.block_on(daemon::connect_to_daemon(&cwd))
+        })
+        .context("Failed to reach vriftd")?;
+        Some(conn)
+    } else {
+        None
+    };
 
     let manifest_abs = normalize_for_ipc(manifest)
         .with_context(|| format!("Failed to resolve manifest path: {}", manifest.display()))?;
@@ -1101,16 +1597,50 @@ fn cmd_run(
     println!("  Shim:     {}", shim_path.display());
     println!("  Manifest: {}", manifest_abs.display());
     println!("  CAS:      {}", cas_abs.display());
-    println!("  Command:  {}", command.join(" "));
-    println!();
 
     // Build the command with environment variables
     let mut cmd = std::process::Command::new(&command[0]);
     cmd.args(&command[1..]);
 
+    if heatmap {
+        // Each process in the tree appends its counters at exit
+        let heatmap_file = vrift_ipc::heatmap::heatmap_path(&cwd);
+        if let Some(parent) = heatmap_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        println!("  Heatmap:  {}", heatmap_file.display());
+        cmd.env("VRIFT_HEATMAP", &heatmap_file);
+    }
+    println!("  Command:  {}", command.join(" "));
+    println!();
+
     // Set Velo environment variables
     cmd.env("VRIFT_MANIFEST", &manifest_abs);
     cmd.env("VR_THE_SOURCE", &cas_abs);
+    if let Some((key, value)) = cas::tiers_env(&cas_abs) {
+        cmd.env(key, value);
+    }
+
+    // Nested runs share the outer session; a fresh one is ours to clean up
+    let inherited_session = std::env::var(vrift_config::path::SESSION_ID_ENV).ok();
+    let session_id = inherited_session.clone().unwrap_or_else(|| {
+        daemon_conn
+            .as_ref()
+            .map(|c| c.session_id.clone())
+            .filter(|id| vrift_config::path::is_valid_session_id(id))
+            .unwrap_or_else(vrift_config::path::new_session_id)
+    });
+    cmd.env(vrift_config::path::SESSION_ID_ENV, &session_id);
+
+    // Same vDird hand-off as `vrift shell`, so the shim skips the lookup
+    if let Some(conn) = &daemon_conn {
+        if !conn.vdird_socket.is_empty() {
+            cmd.env("VRIFT_VDIRD_SOCKET", &conn.vdird_socket);
+        }
+        if !conn.vdir_mmap_path.is_empty() {
+            cmd.env("VRIFT_VDIR_MMAP", &conn.vdir_mmap_path);
+        }
+    }
 
     // Set platform-specific library preload
     #[cfg(target_os = "macos")]
@@ -1130,58 +1660,65 @@ fn cmd_run(
         cmd.env("VRIFT_DEBUG", "1");
     }
 
-    let status = cmd
-        .status()
-        .with_context(|| format!("Failed to execute: {}", command[0]))?;
-
-    std::process::exit(status.code().unwrap_or(1));
-}
-
-/// Find the velo-shim library
-fn find_shim_library() -> Result<PathBuf> {
-    // Check standard locations
-    let candidates = [
-        // Development: relative to cargo target
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| {
-                #[cfg(target_os = "macos")]
-                {
-                    p.join("libvrift_shim.dylib")
-                }
-                #[cfg(target_os = "linux")]
-                {
-                    p.join("libvrift_shim.so")
-                }
-                #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-                {
-                    p.join("libvrift_shim.so")
-                }
-            }),
-        // Installed location
-        Some(PathBuf::from("/usr/local/lib/vrift/libvrift_shim.so")),
-        #[cfg(target_os = "macos")]
-        Some(PathBuf::from("/usr/local/lib/vrift/libvrift_shim.dylib")),
-    ];
-
-    for candidate in candidates.into_iter().flatten() {
-        if candidate.exists() {
-            return Ok(candidate);
+    let status =
+        child::run(&mut cmd).with_context(|| format!("Failed to execute: {}", command[0]))?;
+
+    if heatmap {
+        // The process tree has exited: fold its appended lines into the totals
+        let heatmap_file = vrift_ipc::heatmap::heatmap_path(&cwd);
+        if let Err(e) = vrift_ipc::heatmap::Heatmap::compact(&heatmap_file) {
+            eprintln!(
+                "Warning: failed to compact heatmap {}: {}",
+                heatmap_file.display(),
+                e
+            );
         }
     }
 
-    // Provide helpful error message
-    anyhow::bail!(
-        "Could not find velo-shim library. \n\
-        Build with: cargo build -p velo-shim --release\n\
-        Expected at: target/release/libvrift_shim.{}",
-        if cfg!(target_os = "macos") {
-            "dylib"
-        } else {
-            "so"
+    if inherited_session.is_none() {
+        // Same derivation as the shim: project root is the parent of .vrift
+        let project_root = manifest_abs
+            .ancestors()
+            .find(|p| p.file_name().is_some_and(|n| n == ".vrift"))
+            .and_then(Path::parent)
+            .or_else(|| manifest_abs.parent());
+        if let Some(root) = project_root {
+            let _ = vrift_config::path::remove_session_staging(root, &session_id);
         }
-    );
+    }
+
+    child::exit_like(status);
+}
+
+/// `vrift run --backend trace|ptrace`: serve the manifest by rewriting path
+/// syscalls from this process, so static binaries see it too
+fn run_traced(
+    cas_root: &Path,
+    manifest_path: &Path,
+    command: &[String],
+    backend: RunBackend,
+) -> Result<()> {
+    let manifest = pack::load_manifest(manifest_path)?;
+    let root = match std::env::var_os("VRIFT_PROJECT_ROOT") {
+        Some(root) => PathBuf::from(root),
+        None => std::env::current_dir().context("Failed to get current directory")?,
+    };
+    let mode = match backend {
+        RunBackend::Ptrace => vrift_trace::Mode::Ptrace,
+        _ => vrift_trace::Mode::Auto,
+    };
+
+    println!("Running with Velo VFS (syscall trace):");
+    println!("  Manifest: {}", manifest_path.display());
+    println!("  Root:     {}", root.display());
+    println!("  CAS:      {}", cas_root.display());
+    println!("  Command:  {}", command.join(" "));
+    println!();
+
+    let mut cmd = std::process::Command::new(&command[0]);
+    cmd.args(&command[1..]);
+    let code = vrift_trace::run(&manifest, &root, cas_root, mode, &mut cmd)?;
+    std::process::exit(code);
 }
 
 /// Display CAS, manifest, and optionally session statistics
@@ -1277,6 +1814,14 @@ fn cmd_status(
         println!("  Unique blobs: {}", stats.blob_count);
         println!("  Total size:   {}", format_bytes(stats.total_bytes));
         println!("  Avg blob:     {}", format_bytes(stats.avg_blob_size()));
+        if stats.compressed_blobs > 0 {
+            println!(
+                "  Compressed:   {} blobs, {} on disk for {} of content",
+                stats.compressed_blobs,
+                format_bytes(stats.total_bytes),
+                format_bytes(stats.logical_bytes)
+            );
+        }
         println!();
         println!("  Size distribution:");
         println!("    <1KB:      {} blobs", stats.small_blobs);
@@ -1395,7 +1940,19 @@ async fn cmd_watch(_cas_root: &Path, directory: &Path, output: &Path) -> Result<
 
     // Initial ingest via daemon
     println!("\n[Initial Scan]");
-    daemon::ingest_via_daemon(directory, output, None, false, false, None, None, false).await?;
+    daemon::ingest_via_daemon(
+        directory,
+        output,
+        None,
+        false,
+        false,
+        None,
+        None,
+        false,
+        false,
+        Vec::new(),
+    )
+    .await?;
 
     // Create a channel to receive the events.
     let (tx, rx) = channel();
@@ -1428,7 +1985,16 @@ async fn cmd_watch(_cas_root: &Path, directory: &Path, output: &Path) -> Result<
                         if last_ingest.elapsed() > debounce_duration {
                             println!("\n[Change Detected] Re-ingesting...");
                             if let Err(e) = daemon::ingest_via_daemon(
-                                directory, output, None, false, false, None, None, false,
+                                directory,
+                                output,
+                                None,
+                                false,
+                                false,
+                                None,
+                                None,
+                                false,
+                                false,
+                                Vec::new(),
                             )
                             .await
                             {
@@ -0,0 +1,326 @@
+//! # Manifest Edit Verbs
+//!
+//! Non-interactive single-entry fixes for a project's LMDB manifest:
+//! `vrift manifest rm|mv|add|rehash`. Saves a full re-ingest when only one
+//! path is wrong, and avoids hand-editing the binary LMDB files.
+//!
+//! vdir_d keeps its own VDir view of the manifest, so editing underneath a
+//! running daemon would leave it serving stale entries. Edits refuse to run
+//! while the project's vdir_d answers a handshake unless `--force` is given;
+//! `vrift manifest reload` (or SIGHUP to vdir_d) then republishes the VDir.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use vrift_cas::CasStore;
+use vrift_ipc::VnodeEntry;
+use vrift_manifest::lmdb::LmdbManifest;
+
+use crate::daemon::DaemonContext;
+
+/// Normalize a user-supplied path to manifest key form: leading `/`, no trailing `/`
+pub fn normalize_key(path: &str) -> String {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.starts_with('/') {
+        trimmed.to_string()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// Keys equal to `key` or nested under it (a directory and its children)
+fn keys_under(manifest: &LmdbManifest, key: &str) -> Result<Vec<String>> {
+    let prefix = format!("{}/", key);
+    Ok(manifest
+        .iter()?
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| path == key || path.starts_with(&prefix))
+        .collect())
+}
+
+/// Remove `key` and everything beneath it. Returns the number of entries removed.
+pub fn remove_entries(manifest: &LmdbManifest, key: &str) -> Result<usize> {
+    let keys = keys_under(manifest, key)?;
+    if keys.is_empty() {
+        anyhow::bail!("Not found: {}", key);
+    }
+    for k in &keys {
+        manifest.remove(k);
+    }
+    Ok(keys.len())
+}
+
+/// Move `from` (and everything beneath it) to `to`. Refuses to overwrite.
+pub fn move_entries(manifest: &LmdbManifest, from: &str, to: &str) -> Result<usize> {
+    if to == from || to.starts_with(&format!("{}/", from)) {
+        anyhow::bail!("Cannot move {} into itself", from);
+    }
+    let keys = keys_under(manifest, from)?;
+    if keys.is_empty() {
+        anyhow::bail!("Not found: {}", from);
+    }
+    if manifest.get(to)?.is_some() {
+        anyhow::bail!("Destination already exists: {}", to);
+    }
+    for k in &keys {
+        let entry = manifest
+            .get(k)?
+            .with_context(|| format!("Entry vanished during move: {}", k))?;
+        let new_key = format!("{}{}", to, &k[from.len()..]);
+        let xattrs = entry.xattrs();
+        manifest.remove(k);
+        manifest.insert(&new_key, entry.vnode, entry.tier);
+        manifest.set_xattrs(&new_key, &xattrs)?;
+    }
+    Ok(keys.len())
+}
+
+/// Store `file` in the CAS and point `key` at it. An existing entry keeps its
+/// tier (and its mode unless `mode` is given); new entries default to Tier2.
+pub fn put_file(
+    manifest: &LmdbManifest,
+    cas: &CasStore,
+    key: &str,
+    file: &Path,
+    mode: Option<u32>,
+) -> Result<VnodeEntry> {
+    let meta =
+        std::fs::metadata(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    if !meta.is_file() {
+        anyhow::bail!("Not a regular file: {}", file.display());
+    }
+
+    let hash = cas
+        .store_file(file)
+        .with_context(|| format!("Failed to store {} in CAS", file.display()))?;
+
+    let existing = manifest.get(key)?;
+    let vnode = VnodeEntry {
+        content_hash: hash,
+        size: meta.len(),
+        mtime: meta.mtime() as u64,
+        mode: mode
+            .or_else(|| existing.as_ref().map(|e| e.vnode.mode))
+            .unwrap_or_else(|| meta.mode()),
+        flags: 0,
+        _pad: 0,
+    };
+    let tier = existing.map(|e| e.tier).unwrap_or_default();
+    manifest.insert(key, vnode.clone(), tier);
+    Ok(vnode)
+}
+
+/// Parse an octal mode string ("644", "0o755", "0755")
+pub fn parse_mode(s: &str) -> Result<u32> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    u32::from_str_radix(digits, 8).with_context(|| format!("Invalid octal mode: {}", s))
+}
+
+/// Open the project's manifest for editing, after checking vdir_d isn't live
+pub fn open_for_edit(directory: &Path, force: bool) -> Result<LmdbManifest> {
+    let project_id = vrift_config::path::compute_project_id(directory);
+    let manifest_path = vrift_config::path::get_manifest_db_path(&project_id)
+        .ok_or_else(|| anyhow::anyhow!("Could not determine manifest path"))?;
+
+    if !manifest_path.exists() {
+        anyhow::bail!(
+            "Manifest not found at {}. Run 'vrift init' first.",
+            manifest_path.display()
+        );
+    }
+
+    if let Some(socket) = vrift_config::path::get_vdird_socket_path(&project_id) {
+        if vrift_ipc::probe_socket(&socket, vrift_ipc::PROBE_TIMEOUT) {
+            if !force {
+                anyhow::bail!(
+                    "vdir_d is running for {} and would keep serving the old entries.\n\
+                     Stop it first, or pass --force and restart it afterwards.",
+                    directory.display()
+                );
+            }
+            eprintln!("⚠️  vdir_d is running; run 'vrift manifest reload' to pick up this edit.");
+        }
+    }
+
+    Ok(LmdbManifest::open(&manifest_path)?)
+}
+
+/// Ask the project's running vdir_d to republish the manifest on disk
+pub async fn reload(directory: &Path) -> Result<()> {
+    let project_id = vrift_config::path::compute_project_id(directory);
+    let socket = vrift_config::path::get_vdird_socket_path(&project_id)
+        .filter(|s| vrift_ipc::probe_socket(s, vrift_ipc::PROBE_TIMEOUT))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "vdir_d is not running for {}; it reads the manifest when it starts.",
+                directory.display()
+            )
+        })?;
+    let mut client = vrift_ipc::client::DaemonClient::connect_path(&socket).await?;
+    let (entries, generation) = client
+        .manifest_reload()
+        .await
+        .daemon_context("Reload failed")?;
+    println!(
+        "🔄 vdir_d republished {} entries (VDir generation {})",
+        entries, generation
+    );
+    Ok(())
+}
+
+/// One edit verb, already parsed from the command line
+pub enum EditOp {
+    Rm {
+        path: String,
+    },
+    Mv {
+        from: String,
+        to: String,
+    },
+    Add {
+        path: String,
+        from_file: PathBuf,
+        mode: Option<String>,
+    },
+    Rehash {
+        path: String,
+    },
+}
+
+pub fn run(op: EditOp, directory: &Path, cas_root: &Path, force: bool) -> Result<()> {
+    let manifest = open_for_edit(directory, force)?;
+
+    match op {
+        EditOp::Rm { path } => {
+            let key = normalize_key(&path);
+            let n = remove_entries(&manifest, &key)?;
+            manifest.commit()?;
+            println!("🗑️  Removed {} ({} entries)", key, n);
+        }
+        EditOp::Mv { from, to } => {
+            let (from, to) = (normalize_key(&from), normalize_key(&to));
+            let n = move_entries(&manifest, &from, &to)?;
+            manifest.commit()?;
+            println!("📦 Moved {} → {} ({} entries)", from, to, n);
+        }
+        EditOp::Add {
+            path,
+            from_file,
+            mode,
+        } => {
+            let key = normalize_key(&path);
+            let mode = mode.as_deref().map(parse_mode).transpose()?;
+            let cas = CasStore::new(cas_root)?;
+            let vnode = put_file(&manifest, &cas, &key, &from_file, mode)?;
+            manifest.commit()?;
+            println!(
+                "✅ {} → {} ({} bytes, mode {:o})",
+                key,
+                &CasStore::hash_to_hex(&vnode.content_hash)[..16],
+                vnode.size,
+                vnode.mode
+            );
+        }
+        EditOp::Rehash { path } => {
+            let key = normalize_key(&path);
+            if manifest.get(&key)?.is_none() {
+                anyhow::bail!("Not found: {}", key);
+            }
+            let file = directory.join(crate::hydrate::key_to_rel_path(&key)?);
+            let cas = CasStore::new(cas_root)?;
+            let vnode = put_file(&manifest, &cas, &key, &file, None)?;
+            manifest.commit()?;
+            println!(
+                "🔁 Re-hashed {} → {} ({} bytes)",
+                key,
+                &CasStore::hash_to_hex(&vnode.content_hash)[..16],
+                vnode.size
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_manifest::lmdb::AssetTier;
+
+    fn vnode(size: u64) -> VnodeEntry {
+        VnodeEntry {
+            content_hash: [size as u8; 32],
+            size,
+            mtime: 0,
+            mode: 0o644,
+            flags: 0,
+            _pad: 0,
+        }
+    }
+
+    fn manifest() -> (tempfile::TempDir, LmdbManifest) {
+        let dir = tempfile::tempdir().unwrap();
+        let m = LmdbManifest::open(dir.path().join("manifest.lmdb")).unwrap();
+        m.insert("/src", vnode(0), AssetTier::Tier2Mutable);
+        m.insert("/src/a.rs", vnode(1), AssetTier::Tier2Mutable);
+        m.insert("/src/b.rs", vnode(2), AssetTier::Tier1Immutable);
+        m.insert("/srcx.rs", vnode(3), AssetTier::Tier2Mutable);
+        m.commit().unwrap();
+        (dir, m)
+    }
+
+    #[test]
+    fn test_normalize_key() {
+        assert_eq!(normalize_key("src/a.rs"), "/src/a.rs");
+        assert_eq!(normalize_key("/src/"), "/src");
+    }
+
+    #[test]
+    fn test_remove_directory_and_children() {
+        let (_dir, m) = manifest();
+        assert_eq!(remove_entries(&m, "/src").unwrap(), 3);
+        m.commit().unwrap();
+        assert!(m.get("/src/a.rs").unwrap().is_none());
+        // Sibling sharing the prefix string is untouched
+        assert!(m.get("/srcx.rs").unwrap().is_some());
+        assert!(remove_entries(&m, "/missing").is_err());
+    }
+
+    #[test]
+    fn test_move_keeps_tier_and_refuses_overwrite() {
+        let (_dir, m) = manifest();
+        assert_eq!(move_entries(&m, "/src", "/lib").unwrap(), 3);
+        m.commit().unwrap();
+        let moved = m.get("/lib/b.rs").unwrap().unwrap();
+        assert_eq!(moved.vnode.size, 2);
+        assert_eq!(moved.tier, AssetTier::Tier1Immutable);
+        assert!(m.get("/src/b.rs").unwrap().is_none());
+
+        assert!(move_entries(&m, "/lib/a.rs", "/srcx.rs").is_err());
+        assert!(move_entries(&m, "/lib", "/lib/inner").is_err());
+    }
+
+    #[test]
+    fn test_put_file_stores_blob_and_keeps_mode() {
+        let (dir, m) = manifest();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let file = dir.path().join("new.rs");
+        std::fs::write(&file, b"fn main() {}").unwrap();
+
+        let v = put_file(&m, &cas, "/src/a.rs", &file, None).unwrap();
+        assert_eq!(v.size, 12);
+        assert_eq!(v.mode, 0o644);
+        assert!(cas.exists(&v.content_hash));
+
+        let v = put_file(
+            &m,
+            &cas,
+            "/bin/run",
+            &file,
+            Some(parse_mode("755").unwrap()),
+        )
+        .unwrap();
+        assert_eq!(v.mode, 0o755);
+    }
+}
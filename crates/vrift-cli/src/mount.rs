@@ -3,19 +3,32 @@ use clap::Args;
 use std::fs;
 use std::path::{Path, PathBuf};
 #[cfg(feature = "fuse")]
+use std::sync::Arc;
+#[cfg(feature = "fuse")]
 use vrift_cas::CasStore;
 #[cfg(feature = "fuse")]
-use vrift_manifest::Manifest;
+use vrift_manifest::{LmdbManifest, Manifest};
 
 #[derive(Args, Debug)]
 pub struct MountArgs {
-    /// Manifest file to mount
+    /// Manifest file (or `vrift pack` bundle) to mount
     #[arg(short, long, default_value = "vrift.manifest")]
     manifest: PathBuf,
 
     /// Mount point directory
     #[arg(value_name = "MOUNTPOINT")]
     mountpoint: PathBuf,
+
+    /// Refuse writes. Bundles are always mounted read-only; a manifest mount
+    /// otherwise stores new content in the CAS and updates the manifest file.
+    #[arg(long)]
+    read_only: bool,
+
+    /// LMDB manifest whose inode table numbers the files, so they keep the
+    /// inodes the inception layer reports for them (default:
+    /// `.vrift/manifest.lmdb`, if there is one)
+    #[arg(long, value_name = "DIR")]
+    inodes: Option<PathBuf>,
 }
 
 /// Execute the mount command
@@ -26,7 +39,9 @@ pub fn run(args: MountArgs, cas_root: &Path) -> Result<()> {
         anyhow::bail!("Manifest not found: {}", manifest_path.display());
     }
 
-    if !cas_root.exists() {
+    // Bundles carry their own blobs; no CAS needed
+    let is_bundle = vrift_pack::is_bundle(manifest_path);
+    if !is_bundle && !cas_root.exists() {
         anyhow::bail!("CAS root not found: {}", cas_root.display());
     }
 
@@ -38,18 +53,43 @@ pub fn run(args: MountArgs, cas_root: &Path) -> Result<()> {
 
     tracing::info!("Mounting Velo Rift™...");
     tracing::info!("  Manifest:   {}", manifest_path.display());
-    tracing::info!("  CAS:        {}", cas_root.display());
+    if is_bundle {
+        tracing::info!("  Bundle:     {}", manifest_path.display());
+    } else {
+        tracing::info!("  CAS:        {}", cas_root.display());
+    }
+    let writable = !is_bundle && !args.read_only;
     tracing::info!("  Mountpoint: {}", mountpoint.display());
-    tracing::info!("  Mode:       Read-Only");
+    tracing::info!(
+        "  Mode:       {}",
+        if writable { "Read-Write" } else { "Read-Only" }
+    );
 
     #[cfg(feature = "fuse")]
     {
-        let cas = CasStore::new(cas_root)?;
-        let manifest = Manifest::load(manifest_path)?;
-        let fs = vrift_fuse::VeloFs::new(&manifest, cas);
+        let fs = if is_bundle {
+            let bundle = vrift_pack::BundleReader::open(manifest_path)?;
+            let manifest = bundle.manifest().clone();
+            vrift_fuse::VeloFs::new(&manifest, bundle)
+        } else {
+            let cas = CasStore::new(cas_root)?;
+            let manifest = Manifest::load(manifest_path)?;
+            let inodes = inode_table(args.inodes.as_deref())?;
+            if writable {
+                // Built first: it replays changes a crashed mount journaled
+                let writeback =
+                    vrift_fuse::WriteBack::new(CasStore::new(cas_root)?, manifest, manifest_path)?;
+                vrift_fuse::VeloFs::with_inode_table(writeback.manifest(), cas, inodes)
+                    .with_write_back(writeback)
+            } else {
+                vrift_fuse::VeloFs::with_inode_table(&manifest, cas, inodes)
+            }
+        };
+        let stats = fs.stats();
 
         // This will block until unmounted
         fs.mount(mountpoint)?;
+        tracing::info!("Fetch stats: {}", stats.summary());
     }
 
     #[cfg(not(feature = "fuse"))]
@@ -60,3 +100,20 @@ pub fn run(args: MountArgs, cas_root: &Path) -> Result<()> {
 
     Ok(())
 }
+
+/// The LMDB manifest to take inodes from: `path`, else the project's if
+/// there is one
+#[cfg(feature = "fuse")]
+fn inode_table(path: Option<&Path>) -> Result<Option<Arc<LmdbManifest>>> {
+    let path = match path {
+        Some(path) if path.is_dir() => path,
+        // LmdbManifest::open would replace a file with a new manifest
+        Some(path) => anyhow::bail!("Not an LMDB manifest: {}", path.display()),
+        None if Path::new(".vrift/manifest.lmdb").is_dir() => Path::new(".vrift/manifest.lmdb"),
+        None => return Ok(None),
+    };
+    tracing::info!("  Inodes:     {}", path.display());
+    let manifest = LmdbManifest::open(path)
+        .with_context(|| format!("Failed to open inode table in {}", path.display()))?;
+    Ok(Some(Arc::new(manifest)))
+}
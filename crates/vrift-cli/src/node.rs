@@ -0,0 +1,494 @@
+//! # Node.js Profile
+//!
+//! `vrift ingest <project> --profile node-modules` snapshots a project's
+//! `node_modules` for sharing. The CAS already stores each file once, so a
+//! package installed in ten projects (or nested ten times in one) takes the
+//! space of one copy. The profile adds the parts npm leaves tied to the
+//! machine:
+//!
+//! - `node_modules/.cache` and node-gyp intermediates (`build/Release/
+//!   obj.target`, `.deps`) are not ingested.
+//! - Absolute symlinks into the project (as some linkers write for `.bin`
+//!   and workspace packages) are made relative in the manifest, so they
+//!   survive a move. Targets of `.bin` links get their executable bits.
+//! - `node_modules/.vrift-node.json` records installed packages, how often
+//!   each is duplicated, and every native addon (`.node` file, usually
+//!   built by a postinstall script) with the platform it was built for.
+//!
+//! `vrift node <script>` runs node under the inception layer and warns when
+//! a native addon wasn't built for this machine.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use vrift_cas::CasStore;
+use vrift_config::path::normalize_for_ipc;
+use vrift_manifest::lmdb::ManifestEntry;
+use vrift_manifest::LmdbManifest;
+
+use crate::shim::{binary_arches, Arch};
+
+/// Ignore patterns for a Node.js project, on top of the configured ones
+pub const IGNORE_PATTERNS: &[&str] = &[
+    "**/node_modules/.cache/",
+    "**/node_modules/**/build/Release/obj.target/",
+    "**/node_modules/**/build/Release/.deps/",
+];
+
+/// Layout record, relative to the project root
+pub const LAYOUT_FILE: &str = "node_modules/.vrift-node.json";
+
+#[derive(Args, Debug)]
+pub struct NodeArgs {
+    /// Project directory (default: nearest directory above the current one
+    /// with a node_modules)
+    #[arg(short, long, value_name = "DIR")]
+    directory: Option<PathBuf>,
+
+    /// node binary to run
+    #[arg(long, value_name = "PATH", env = "VRIFT_NODE", default_value = "node")]
+    node: String,
+
+    /// Script and its arguments, as passed to node
+    #[arg(
+        value_name = "SCRIPT",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    args: Vec<String>,
+}
+
+/// One `name@version`, and how many copies of it are installed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub installs: usize,
+}
+
+/// A compiled addon and what it was built for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NativeAddon {
+    /// Path relative to the project root
+    pub path: String,
+    /// `linux` or `macos`, from the binary's format
+    pub os: Option<String>,
+    pub arches: Vec<String>,
+}
+
+impl NativeAddon {
+    pub fn runs_here(&self) -> bool {
+        self.os.as_deref() == Some(std::env::consts::OS)
+            && self.arches.iter().any(|a| a == Arch::host().name())
+    }
+
+    pub fn platform(&self) -> String {
+        format!(
+            "{}-{}",
+            self.os.as_deref().unwrap_or("unknown"),
+            if self.arches.is_empty() {
+                "unknown".to_string()
+            } else {
+                self.arches.join("+")
+            }
+        )
+    }
+}
+
+/// What `.vrift-node.json` records
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeLayout {
+    pub packages: Vec<Package>,
+    /// Package directories, counting every copy
+    pub installs: usize,
+    pub native: Vec<NativeAddon>,
+    /// Symlinks under `.bin` directories
+    pub bin_links: usize,
+    /// Absolute symlinks made relative
+    pub links_relativized: usize,
+    /// Bytes under node_modules, counting every copy
+    pub bytes: u64,
+    /// Bytes under node_modules, counting identical files once
+    pub unique_bytes: u64,
+}
+
+impl NodeLayout {
+    /// Copies beyond the first of each `name@version`
+    pub fn duplicate_installs(&self) -> usize {
+        self.installs - self.packages.len()
+    }
+}
+
+/// Fail early unless `root` has a node_modules to ingest
+pub fn detect(root: &Path) -> Result<()> {
+    if !root.join("node_modules").is_dir() {
+        anyhow::bail!(
+            "{} has no node_modules; run `npm install` (or ingest the project root) first",
+            root.display()
+        );
+    }
+    Ok(())
+}
+
+/// Normalize `target` relative to the directory key `dir` into a key
+fn join_key(dir: &str, target: &str) -> String {
+    let mut parts: Vec<&str> = if target.starts_with('/') {
+        Vec::new()
+    } else {
+        dir.split('/').filter(|p| !p.is_empty()).collect()
+    };
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Relative symlink target from directory key `dir` to key `to`
+fn relative_key(dir: &str, to: &str) -> String {
+    let from: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    let to: Vec<&str> = to.split('/').filter(|p| !p.is_empty()).collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec![".."; from.len() - common];
+    parts.extend(&to[common..]);
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+fn parent_key(key: &str) -> &str {
+    key.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+/// `.../node_modules/<name>/package.json` or `.../node_modules/@scope/<name>/package.json`
+fn is_package_manifest(key: &str) -> bool {
+    let Some(dir) = key.strip_suffix("/package.json") else {
+        return false;
+    };
+    let mut parts = dir.rsplit('/');
+    let (Some(name), Some(parent)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    if name.starts_with('.') {
+        return false;
+    }
+    parent == "node_modules" || (parent.starts_with('@') && parts.next() == Some("node_modules"))
+}
+
+/// Rewrite links, fix `.bin` targets and add the layout record in the
+/// manifest ingested from the project at `root`, keys under `prefix`
+pub fn apply(
+    root: &Path,
+    manifest_path: &Path,
+    cas: &CasStore,
+    prefix: &str,
+) -> Result<NodeLayout> {
+    let manifest = LmdbManifest::open(manifest_path)
+        .with_context(|| format!("Failed to open manifest: {}", manifest_path.display()))?;
+    let prefix = prefix.trim_end_matches('/');
+    let roots: Vec<String> = [Some(root.to_path_buf()), root.canonicalize().ok()]
+        .into_iter()
+        .flatten()
+        .map(|p| p.to_string_lossy().trim_end_matches('/').to_string())
+        .collect();
+
+    let entries: HashMap<String, ManifestEntry> = manifest
+        .iter()?
+        .into_iter()
+        .filter(|(key, _)| key.contains("/node_modules/"))
+        .collect();
+    let mut layout = NodeLayout::default();
+    let mut packages: BTreeMap<(String, String), usize> = BTreeMap::new();
+    let mut unique: HashMap<[u8; 32], u64> = HashMap::new();
+    let mut bin_targets = Vec::new();
+
+    for (key, entry) in &entries {
+        let vnode = &entry.vnode;
+        if let Some(target) = entry.symlink_target() {
+            let target = String::from_utf8_lossy(target).into_owned();
+            let dir = parent_key(key);
+            let mut resolved = join_key(dir, &target);
+            if target.starts_with('/') {
+                // An absolute path into the project becomes relative
+                let inside = roots.iter().find_map(|r| {
+                    let rest = target.strip_prefix(r.as_str())?;
+                    (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+                });
+                if let Some(rest) = inside {
+                    resolved = format!("{}{}", prefix, join_key("/", rest));
+                    let relative = relative_key(dir, &resolved);
+                    manifest.insert_symlink(key, relative.as_bytes(), vnode.mtime, entry.tier);
+                    layout.links_relativized += 1;
+                }
+            }
+            if dir.ends_with("/node_modules/.bin") {
+                layout.bin_links += 1;
+                bin_targets.push(resolved);
+            }
+            continue;
+        }
+        if !vnode.is_file() {
+            continue;
+        }
+        layout.bytes += vnode.size;
+        unique.insert(vnode.content_hash, vnode.size);
+
+        if is_package_manifest(key) {
+            layout.installs += 1;
+            let json: serde_json::Value = cas
+                .get(&vnode.content_hash)
+                .ok()
+                .and_then(|data| serde_json::from_slice(&data).ok())
+                .unwrap_or_default();
+            let field = |name: &str| json[name].as_str().unwrap_or("").to_string();
+            *packages
+                .entry((field("name"), field("version")))
+                .or_default() += 1;
+        } else if key.ends_with(".node") {
+            let mut head = Vec::with_capacity(4096);
+            cas.get_reader(&vnode.content_hash)?
+                .take(4096)
+                .read_to_end(&mut head)?;
+            let os = if head.starts_with(b"\x7fELF") {
+                Some("linux")
+            } else if binary_arches(&head).is_empty() {
+                None
+            } else {
+                Some("macos")
+            };
+            layout.native.push(NativeAddon {
+                path: key[prefix.len()..].trim_start_matches('/').to_string(),
+                os: os.map(str::to_string),
+                arches: binary_arches(&head)
+                    .into_iter()
+                    .map(|a| a.name().to_string())
+                    .collect(),
+            });
+        }
+    }
+
+    // npm marks bin targets executable on install; other installers and
+    // tarball extracts don't always
+    for target in bin_targets {
+        if let Some(entry) = entries.get(&target) {
+            let mut vnode = entry.vnode.clone();
+            if vnode.is_file() && vnode.mode & 0o111 == 0 {
+                vnode.mode |= (vnode.mode & 0o444) >> 2;
+                manifest.insert(&target, vnode, entry.tier);
+            }
+        }
+    }
+
+    layout.unique_bytes = unique.values().sum();
+    layout.packages = packages
+        .into_iter()
+        .map(|((name, version), installs)| Package {
+            name,
+            version,
+            installs,
+        })
+        .collect();
+    layout.native.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut record = serde_json::to_vec_pretty(&layout)?;
+    record.push(b'\n');
+    let mtime = std::fs::metadata(root.join("node_modules"))
+        .ok()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let vnode = vrift_manifest::VnodeEntry::new_file(
+        cas.store(&record)?,
+        record.len() as u64,
+        mtime,
+        0o644,
+    );
+    manifest.insert(
+        &format!("{}/{}", prefix, LAYOUT_FILE),
+        vnode,
+        vrift_manifest::lmdb::AssetTier::Tier2Mutable,
+    );
+    manifest.commit()?;
+    Ok(layout)
+}
+
+/// The layout recorded in the project's manifest, if it was ingested with
+/// the node-modules profile
+fn recorded_layout(root: &Path, cas: &CasStore) -> Option<NodeLayout> {
+    let manifest_path = root.join(".vrift").join("manifest.lmdb");
+    if !manifest_path.exists() {
+        return None;
+    }
+    let manifest = LmdbManifest::open(&manifest_path).ok()?;
+    let entry = manifest.get(&format!("/{}", LAYOUT_FILE)).ok()??;
+    let data = cas.get(&entry.vnode.content_hash).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+pub async fn run(args: NodeArgs, cas_root: &Path) -> Result<()> {
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let directory = match args.directory {
+        Some(dir) => dir,
+        None => cwd
+            .ancestors()
+            .find(|dir| dir.join("node_modules").is_dir())
+            .unwrap_or(&cwd)
+            .to_path_buf(),
+    };
+    let root = normalize_for_ipc(&directory)?;
+
+    if let Some(layout) = CasStore::new(cas_root)
+        .ok()
+        .and_then(|cas| recorded_layout(&root, &cas))
+    {
+        let foreign: Vec<&NativeAddon> = layout.native.iter().filter(|a| !a.runs_here()).collect();
+        if let Some(first) = foreign.first() {
+            eprintln!(
+                "⚠️  {} native addon(s) weren't built for this machine, e.g. {} ({})",
+                foreign.len(),
+                first.path,
+                first.platform()
+            );
+            eprintln!("   Rebuild them with: npm rebuild");
+        }
+    }
+
+    let cfg = vrift_config::Config::load_for_project(&root).unwrap_or_else(|e| {
+        eprintln!("Warning: Config load failed: {}. Using defaults.", e);
+        vrift_config::Config::default()
+    });
+    let (conn, _vdird) = crate::daemon::connect_to_vdird(&root).await?;
+    let status = tokio::task::block_in_place(|| {
+        let mut cmd = crate::shim::shimmed_command(&args.node, &root, &cfg, &conn)?;
+        cmd.args(&args.args).current_dir(&cwd);
+        crate::child::run(&mut cmd).with_context(|| format!("Failed to execute: {}", args.node))
+    })?;
+    if !status.success() {
+        crate::child::exit_like(status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_manifest::lmdb::AssetTier;
+    use vrift_manifest::VnodeEntry;
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(
+            join_key("/a/node_modules/.bin", "../x/cli.js"),
+            "/a/node_modules/x/cli.js"
+        );
+        assert_eq!(join_key("/a", "/b/./c"), "/b/c");
+        assert_eq!(
+            relative_key("/node_modules/.bin", "/node_modules/x/cli.js"),
+            "../x/cli.js"
+        );
+        assert_eq!(
+            relative_key("/node_modules", "/packages/ui"),
+            "../packages/ui"
+        );
+        assert!(is_package_manifest("/node_modules/lodash/package.json"));
+        assert!(is_package_manifest(
+            "/node_modules/@babel/core/package.json"
+        ));
+        assert!(is_package_manifest(
+            "/node_modules/a/node_modules/b/package.json"
+        ));
+        assert!(!is_package_manifest("/node_modules/lodash/fp/package.json"));
+        assert!(!is_package_manifest("/node_modules/.bin/package.json"));
+    }
+
+    #[test]
+    fn test_apply_records_layout_and_fixes_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        std::fs::create_dir_all(root.join("node_modules")).unwrap();
+        let root_str = root.to_string_lossy().into_owned();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let manifest_path = dir.path().join("manifest.lmdb");
+        let manifest = LmdbManifest::open(&manifest_path).unwrap();
+        let tier = AssetTier::Tier2Mutable;
+        let file = |key: &str, data: &[u8], mode: u32| {
+            let hash = cas.store(data).unwrap();
+            let vnode = VnodeEntry::new_file(hash, data.len() as u64, 1, mode);
+            manifest.insert(key, vnode, tier);
+        };
+        let pkg = br#"{"name": "left-pad", "version": "1.3.0"}"#;
+        file("/node_modules/left-pad/package.json", pkg, 0o644);
+        file(
+            "/node_modules/left-pad/index.js",
+            b"module.exports = 1",
+            0o644,
+        );
+        file(
+            "/node_modules/x/node_modules/left-pad/package.json",
+            pkg,
+            0o644,
+        );
+        file(
+            "/node_modules/x/node_modules/left-pad/index.js",
+            b"module.exports = 1",
+            0o644,
+        );
+        file(
+            "/node_modules/x/package.json",
+            br#"{"name": "x", "version": "2.0.0"}"#,
+            0o644,
+        );
+        file("/node_modules/x/cli.js", b"#!/usr/bin/env node\n", 0o644);
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(18, 0);
+        elf.extend_from_slice(&62u16.to_le_bytes());
+        file("/node_modules/x/build/Release/x.node", &elf, 0o755);
+        manifest.insert_symlink(
+            "/node_modules/.bin/x",
+            format!("{}/node_modules/x/cli.js", root_str).as_bytes(),
+            1,
+            tier,
+        );
+        manifest.commit().unwrap();
+        drop(manifest);
+
+        let layout = apply(&root, &manifest_path, &cas, "").unwrap();
+        assert_eq!(layout.installs, 3);
+        assert_eq!(layout.duplicate_installs(), 1);
+        assert_eq!(
+            layout.packages[0],
+            Package {
+                name: "left-pad".into(),
+                version: "1.3.0".into(),
+                installs: 2
+            }
+        );
+        assert!(layout.unique_bytes < layout.bytes);
+        assert_eq!((layout.bin_links, layout.links_relativized), (1, 1));
+        assert_eq!(layout.native.len(), 1);
+        assert_eq!(layout.native[0].platform(), "linux-x86_64");
+
+        let manifest = LmdbManifest::open(&manifest_path).unwrap();
+        let link = manifest.get("/node_modules/.bin/x").unwrap().unwrap();
+        assert_eq!(link.symlink_target().unwrap(), b"../x/cli.js");
+        let cli = manifest.get("/node_modules/x/cli.js").unwrap().unwrap();
+        assert_eq!(cli.vnode.mode, 0o755);
+        assert!(manifest
+            .get("/node_modules/.vrift-node.json")
+            .unwrap()
+            .is_some());
+    }
+}
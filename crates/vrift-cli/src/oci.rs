@@ -0,0 +1,980 @@
+//! # OCI Images
+//!
+//! `vrift export-oci <manifest> --tag repo:tag` writes a manifest as a
+//! single-layer OCI image layout: one gzipped tar of the whole tree, an
+//! image config and an image manifest, all under `blobs/sha256`, with
+//! `index.json` naming the image by its tag. The layout can be pushed with
+//! `skopeo copy oci:<dir>:<tag> docker://<registry>/<repo>:<tag>`, or
+//! archived (`tar -C <dir> -cf image.tar .`) and fed to `docker load`, and
+//! runs without the inception layer.
+//!
+//! The layer is reproducible: entries are sorted, owned by root, carry the
+//! manifest's mode and mtime, and the gzip header has no timestamp, so the
+//! same manifest always yields the same digests. Directories the manifest
+//! only implies get mode 0755.
+//!
+//! `vrift import-oci <image>` goes the other way. It takes a `docker save`
+//! or OCI archive, an OCI layout directory, or a registry reference (pulled
+//! with skopeo), applies the image's layers in order with their whiteouts,
+//! and writes the resulting root filesystem as a manifest, with file
+//! content stored in the CAS. Device nodes and FIFOs have no manifest form
+//! and are skipped.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use vrift_cas::CasStore;
+use vrift_manifest::{Manifest, VnodeEntry};
+
+use crate::hydrate::key_to_rel_path;
+
+const MEDIA_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+const MEDIA_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+const MEDIA_CONFIG: &str = "application/vnd.oci.image.config.v1+json";
+const MEDIA_LAYER: &str = "application/vnd.oci.image.layer.v1.tar+gzip";
+
+#[derive(Args, Debug)]
+pub struct ExportOciArgs {
+    /// Manifest to export (manifest file or LMDB manifest directory)
+    #[arg(value_name = "MANIFEST")]
+    manifest: PathBuf,
+
+    /// Image reference, `repo:tag` (`latest` if the tag is left out)
+    #[arg(short, long)]
+    tag: String,
+
+    /// OCI layout directory to create (empty or absent)
+    #[arg(short, long, default_value = "oci")]
+    output: PathBuf,
+
+    /// Image architecture (default: this machine's, in OCI naming)
+    #[arg(long)]
+    arch: Option<String>,
+}
+
+/// A content descriptor: what `index.json` and the image manifest point at
+#[derive(Debug, Clone)]
+pub struct Descriptor {
+    pub media_type: &'static str,
+    pub digest: String,
+    pub size: u64,
+}
+
+impl Descriptor {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "mediaType": self.media_type,
+            "digest": self.digest,
+            "size": self.size,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct OciExport {
+    pub manifest: Descriptor,
+    pub layer: Descriptor,
+    /// Entries written to the layer, implied directories included
+    pub entries: usize,
+}
+
+pub fn run(args: ExportOciArgs, cas_root: &Path) -> Result<()> {
+    let manifest = crate::pack::load_manifest(&args.manifest)?;
+    let cas = CasStore::new(cas_root)
+        .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+    if fs::read_dir(&args.output).is_ok_and(|mut d| d.next().is_some()) {
+        anyhow::bail!("{} is not empty", args.output.display());
+    }
+
+    let arch = args.arch.unwrap_or_else(|| oci_arch().to_string());
+    let started = std::time::Instant::now();
+    let export = export_oci(&manifest, &cas, &args.output, &args.tag, &arch)?;
+
+    println!(
+        "✅ Exported {} as {} ({} entries, {:.1} MB layer) in {:.2}s",
+        args.manifest.display(),
+        args.tag,
+        export.entries,
+        export.layer.size as f64 / 1_048_576.0,
+        started.elapsed().as_secs_f64()
+    );
+    println!("   Layout:   {}", args.output.display());
+    println!("   Manifest: {}", export.manifest.digest);
+    println!(
+        "   Push with: skopeo copy oci:{}:{} docker://<registry>/{}",
+        args.output.display(),
+        split_reference(&args.tag).1,
+        args.tag
+    );
+    Ok(())
+}
+
+/// Write `manifest` as an OCI image layout at `out`, tagged `reference`
+pub fn export_oci(
+    manifest: &Manifest,
+    cas: &CasStore,
+    out: &Path,
+    reference: &str,
+    arch: &str,
+) -> Result<OciExport> {
+    let (_, tag) = split_reference(reference);
+    let blobs = out.join("blobs").join("sha256");
+    fs::create_dir_all(&blobs)?;
+
+    let (layer, diff_id, entries) = write_layer(manifest, cas, &blobs)?;
+    let config = json!({
+        "architecture": arch,
+        "os": "linux",
+        "config": {},
+        "rootfs": {
+            "type": "layers",
+            "diff_ids": [diff_id],
+        },
+        "history": [{ "created_by": "vrift export-oci" }],
+    });
+    let config = write_json_blob(&blobs, MEDIA_CONFIG, &config)?;
+    let image = json!({
+        "schemaVersion": 2,
+        "mediaType": MEDIA_MANIFEST,
+        "config": config.to_json(),
+        "layers": [layer.to_json()],
+    });
+    let image = write_json_blob(&blobs, MEDIA_MANIFEST, &image)?;
+
+    let mut entry = image.to_json();
+    entry["annotations"] = json!({
+        "org.opencontainers.image.ref.name": tag,
+        "io.containerd.image.name": reference,
+    });
+    let index = json!({
+        "schemaVersion": 2,
+        "mediaType": MEDIA_INDEX,
+        "manifests": [entry],
+    });
+    fs::write(out.join("index.json"), serde_json::to_vec_pretty(&index)?)?;
+    fs::write(
+        out.join("oci-layout"),
+        serde_json::to_vec(&json!({ "imageLayoutVersion": "1.0.0" }))?,
+    )?;
+
+    Ok(OciExport {
+        manifest: image,
+        layer,
+        entries,
+    })
+}
+
+/// Stream the tree into a gzipped tar blob; returns its descriptor, the
+/// uncompressed tar's digest (the config's diff ID) and the entry count
+fn write_layer(
+    manifest: &Manifest,
+    cas: &CasStore,
+    blobs: &Path,
+) -> Result<(Descriptor, String, usize)> {
+    let mut tree: BTreeMap<PathBuf, Option<&VnodeEntry>> = BTreeMap::new();
+    for (key, vnode) in manifest.iter() {
+        // Nothing below a single layer for a tombstone to hide
+        if vnode.is_whiteout() {
+            continue;
+        }
+        let rel = key_to_rel_path(key)?;
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+        for parent in rel.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() {
+                break;
+            }
+            tree.entry(parent.to_path_buf()).or_insert(None);
+        }
+        tree.insert(rel, Some(vnode));
+    }
+
+    let temp = tempfile::NamedTempFile::new_in(blobs)?;
+    let compressed = HashingWriter::new(temp.reopen()?);
+    let gzip = GzEncoder::new(compressed, Compression::default());
+    let mut tar = tar::Builder::new(HashingWriter::new(gzip));
+
+    for (rel, vnode) in &tree {
+        let mut header = tar::Header::new_gnu();
+        header.set_uid(0);
+        header.set_gid(0);
+        match vnode {
+            None => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_mtime(0);
+                header.set_size(0);
+                tar.append_data(&mut header, rel, io::empty())?;
+            }
+            Some(vnode) if vnode.is_dir() => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(vnode.mode & 0o7777);
+                header.set_mtime(vnode.mtime);
+                header.set_size(0);
+                tar.append_data(&mut header, rel, io::empty())?;
+            }
+            Some(vnode) if vnode.is_symlink() => {
+                use std::os::unix::ffi::OsStrExt;
+                // Symlink targets are stored in the CAS like file content
+                let link = cas
+                    .get(&vnode.content_hash)
+                    .with_context(|| format!("Failed to read link target of {}", rel.display()))?;
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_mtime(vnode.mtime);
+                header.set_size(0);
+                tar.append_link(&mut header, rel, std::ffi::OsStr::from_bytes(&link))?;
+            }
+            Some(vnode) => {
+                let reader = cas
+                    .get_reader(&vnode.content_hash)
+                    .with_context(|| format!("Failed to read blob for {}", rel.display()))?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(vnode.mode & 0o7777);
+                header.set_mtime(vnode.mtime);
+                header.set_size(vnode.size);
+                tar.append_data(&mut header, rel, reader)?;
+            }
+        }
+    }
+
+    let (gzip, diff_id, _) = tar.into_inner()?.finish();
+    let (_, digest, size) = gzip.finish()?.finish();
+    temp.persist(blobs.join(&digest))?;
+    let layer = Descriptor {
+        media_type: MEDIA_LAYER,
+        digest: format!("sha256:{}", digest),
+        size,
+    };
+    Ok((layer, format!("sha256:{}", diff_id), tree.len()))
+}
+
+fn write_json_blob(
+    blobs: &Path,
+    media_type: &'static str,
+    value: &serde_json::Value,
+) -> Result<Descriptor> {
+    let bytes = serde_json::to_vec(value)?;
+    let digest = hex::encode(Sha256::digest(&bytes));
+    fs::write(blobs.join(&digest), &bytes)?;
+    Ok(Descriptor {
+        media_type,
+        digest: format!("sha256:{}", digest),
+        size: bytes.len() as u64,
+    })
+}
+
+/// `repo:tag` into its parts; a `:` before the last `/` is a registry port
+fn split_reference(reference: &str) -> (&str, &str) {
+    match reference.rsplit_once(':') {
+        Some((repo, tag)) if !tag.contains('/') => (repo, tag),
+        _ => (reference, "latest"),
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ImportOciArgs {
+    /// Image tarball (`docker save` or OCI archive), OCI layout directory,
+    /// or registry reference (pulled with skopeo)
+    #[arg(value_name = "IMAGE")]
+    image: String,
+
+    /// Manifest file to write
+    #[arg(short, long, default_value = "vrift.manifest")]
+    output: PathBuf,
+
+    /// Image to take when the source holds several (`repo:tag` or tag)
+    #[arg(short, long)]
+    tag: Option<String>,
+
+    /// Platform to take from a multi-platform image (default: this machine's)
+    #[arg(long)]
+    arch: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub layers: usize,
+    pub files: usize,
+    pub dirs: usize,
+    pub symlinks: usize,
+    pub bytes: u64,
+    /// Whiteout markers applied (opaque directories included)
+    pub whiteouts: usize,
+    /// Device nodes, FIFOs and other entries a manifest can't hold
+    pub skipped: usize,
+}
+
+pub fn import(args: ImportOciArgs, cas_root: &Path) -> Result<()> {
+    let cas = CasStore::new(cas_root)
+        .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+    let arch = args.arch.unwrap_or_else(|| oci_arch().to_string());
+
+    // Archives and registry images are unpacked to a scratch layout first
+    let source = Path::new(&args.image);
+    let scratch = tempfile::Builder::new().prefix("vrift-oci-").tempdir()?;
+    let (layout, tag) = if source.is_dir() {
+        (source.to_path_buf(), args.tag.as_deref())
+    } else if source.is_file() {
+        tar::Archive::new(fs::File::open(source)?)
+            .unpack(scratch.path())
+            .with_context(|| format!("Failed to unpack {}", source.display()))?;
+        (scratch.path().to_path_buf(), args.tag.as_deref())
+    } else {
+        pull(&args.image, scratch.path(), &arch)?;
+        (scratch.path().to_path_buf(), None)
+    };
+
+    let started = std::time::Instant::now();
+    let (manifest, summary) = import_layout(&layout, &cas, tag, &arch)?;
+    manifest
+        .save(&args.output)
+        .with_context(|| format!("Failed to write manifest: {}", args.output.display()))?;
+
+    println!(
+        "✅ Imported {} ({} layers) in {:.2}s",
+        args.image,
+        summary.layers,
+        started.elapsed().as_secs_f64()
+    );
+    println!(
+        "   {} files, {} directories, {} symlinks ({:.1} MB)",
+        summary.files,
+        summary.dirs,
+        summary.symlinks,
+        summary.bytes as f64 / 1_048_576.0
+    );
+    if summary.whiteouts > 0 {
+        println!("   Whiteouts applied: {}", summary.whiteouts);
+    }
+    if summary.skipped > 0 {
+        println!(
+            "   Skipped: {} device nodes, FIFOs and the like",
+            summary.skipped
+        );
+    }
+    println!("   Manifest: {}", args.output.display());
+    println!(
+        "   Run with: vrift run --isolate --manifest {} -- /bin/sh",
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Copy a registry image into an OCI layout at `into` with skopeo
+fn pull(reference: &str, into: &Path, arch: &str) -> Result<()> {
+    let layout = format!("oci:{}:{}", into.display(), split_reference(reference).1);
+    let status = std::process::Command::new("skopeo")
+        .args(["--override-arch", arch, "--override-os", "linux", "copy"])
+        .arg(format!("docker://{}", reference))
+        .arg(&layout)
+        .status()
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "{} is not a file or directory, and pulling it from a registry needs skopeo ({})",
+                reference,
+                e
+            )
+        })?;
+    if !status.success() {
+        anyhow::bail!("skopeo could not pull {} ({})", reference, status);
+    }
+    Ok(())
+}
+
+/// Flatten the image in an unpacked OCI layout or `docker save` directory
+/// into a manifest, storing its files in the CAS
+pub fn import_layout(
+    dir: &Path,
+    cas: &CasStore,
+    tag: Option<&str>,
+    arch: &str,
+) -> Result<(Manifest, ImportSummary)> {
+    let layers = image_layers(dir, tag, arch)?;
+    let mut tree = BTreeMap::new();
+    let mut summary = ImportSummary {
+        layers: layers.len(),
+        ..Default::default()
+    };
+    for layer in &layers {
+        apply_layer(layer, cas, &mut tree, &mut summary)
+            .with_context(|| format!("Failed to apply layer {}", layer.display()))?;
+    }
+
+    let mut manifest = Manifest::new();
+    for (key, vnode) in tree {
+        if vnode.is_dir() {
+            summary.dirs += 1;
+        } else if vnode.is_symlink() {
+            summary.symlinks += 1;
+        } else {
+            summary.files += 1;
+            summary.bytes += vnode.size;
+        }
+        manifest.insert(&key, vnode);
+    }
+    Ok((manifest, summary))
+}
+
+/// Layer files of the chosen image, bottom first
+fn image_layers(dir: &Path, tag: Option<&str>, arch: &str) -> Result<Vec<PathBuf>> {
+    let read_json = |path: &Path| -> Result<serde_json::Value> {
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&bytes)
+            .with_context(|| format!("Invalid JSON in {}", path.display()))
+    };
+    // The digest names a file, so only accept what a sha256 digest can be
+    let blob = |digest: &str| -> Result<PathBuf> {
+        match digest.strip_prefix("sha256:") {
+            Some(hex) if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                Ok(dir.join("blobs/sha256").join(hex))
+            }
+            _ => anyhow::bail!("Invalid or unsupported digest {:?}", digest),
+        }
+    };
+
+    let index_path = dir.join("index.json");
+    if index_path.exists() {
+        let mut index = read_json(&index_path)?;
+        let mut tag = tag;
+        // Multi-platform images nest an index under the tagged entry
+        let image = loop {
+            let chosen = pick_manifest(&index, tag, arch)?;
+            let doc = read_json(&blob(chosen["digest"].as_str().unwrap_or_default())?)?;
+            if doc.get("manifests").is_none() {
+                break doc;
+            }
+            index = doc;
+            tag = None;
+        };
+        return image["layers"]
+            .as_array()
+            .context("Image manifest has no layers")?
+            .iter()
+            .map(|layer| blob(layer["digest"].as_str().unwrap_or_default()))
+            .collect();
+    }
+
+    // Older `docker save` archives only have manifest.json
+    let docker_path = dir.join("manifest.json");
+    if docker_path.exists() {
+        let images = read_json(&docker_path)?;
+        let images = images.as_array().context("manifest.json is not a list")?;
+        let matching: Vec<_> = images
+            .iter()
+            .filter(|image| {
+                tag.is_none_or(|tag| {
+                    image["RepoTags"].as_array().is_some_and(|tags| {
+                        tags.iter().filter_map(|t| t.as_str()).any(|t| {
+                            t == tag
+                                || split_reference(t).1 == tag
+                                || t == format!("{}:latest", tag)
+                        })
+                    })
+                })
+            })
+            .collect();
+        let image = match matching.as_slice() {
+            [image] => image,
+            [] => anyhow::bail!(
+                "No image tagged {:?} in the archive",
+                tag.unwrap_or_default()
+            ),
+            _ => anyhow::bail!("The archive holds several images; pick one with --tag"),
+        };
+        return image["Layers"]
+            .as_array()
+            .context("manifest.json entry has no layers")?
+            .iter()
+            .map(|layer| {
+                let rel = Path::new(layer.as_str().context("Invalid layer path")?);
+                if !rel
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_)))
+                {
+                    anyhow::bail!("Layer path {} leaves the archive", rel.display());
+                }
+                Ok(dir.join(rel))
+            })
+            .collect();
+    }
+
+    anyhow::bail!(
+        "{} is neither an OCI image layout nor a `docker save` archive",
+        dir.display()
+    )
+}
+
+/// The one index entry for `tag` (any tag if `None`) and, among platform
+/// variants, `arch`
+fn pick_manifest<'a>(
+    index: &'a serde_json::Value,
+    tag: Option<&str>,
+    arch: &str,
+) -> Result<&'a serde_json::Value> {
+    let entries = index["manifests"]
+        .as_array()
+        .context("index.json has no manifests")?;
+    let tagged: Vec<_> = entries
+        .iter()
+        .filter(|entry| {
+            tag.is_none_or(|tag| {
+                let annotations = &entry["annotations"];
+                let name = annotations["org.opencontainers.image.ref.name"].as_str();
+                let image = annotations["io.containerd.image.name"].as_str();
+                name == Some(tag) || image == Some(tag) || name == Some(split_reference(tag).1)
+            })
+        })
+        .collect();
+    let candidates = if tagged.len() > 1 {
+        tagged
+            .into_iter()
+            .filter(|entry| {
+                entry["platform"]["architecture"]
+                    .as_str()
+                    .is_none_or(|a| a == arch)
+            })
+            .collect()
+    } else {
+        tagged
+    };
+    match candidates.as_slice() {
+        [entry] => Ok(entry),
+        [] => match tag {
+            Some(tag) => anyhow::bail!("No image tagged {:?} for {}", tag, arch),
+            None => anyhow::bail!("No image for {}", arch),
+        },
+        _ => anyhow::bail!("The layout holds several images; pick one with --tag"),
+    }
+}
+
+/// Apply one layer tarball (plain, gzip or zstd) on top of `tree`
+fn apply_layer(
+    path: &Path,
+    cas: &CasStore,
+    tree: &mut BTreeMap<String, VnodeEntry>,
+    summary: &mut ImportSummary,
+) -> Result<()> {
+    let mut file = io::BufReader::new(fs::File::open(path)?);
+    let magic = file.fill_buf()?;
+    let reader: Box<dyn Read> = if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Box::new(zstd::Decoder::with_buffer(file)?)
+    } else {
+        Box::new(file)
+    };
+
+    // Opaque markers hide lower layers only, not what this one added
+    let mut added = HashSet::new();
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let Some(rel) = layer_path(&entry.path_bytes())? else {
+            continue;
+        };
+        let (parent, name) = match rel.iter().rposition(|&b| b == b'/') {
+            Some(slash) => (&rel[..slash], &rel[slash + 1..]),
+            None => (&[][..], &rel[..]),
+        };
+
+        if let Some(hidden) = name.strip_prefix(b".wh.") {
+            summary.whiteouts += 1;
+            if hidden == b".wh..opq" {
+                let dir = manifest_key(parent);
+                let prefix = if parent.is_empty() {
+                    "/".to_string()
+                } else {
+                    format!("{}/", dir)
+                };
+                tree.retain(|key, _| !key.starts_with(&prefix) || added.contains(key));
+            } else {
+                let mut target = parent.to_vec();
+                if !target.is_empty() {
+                    target.push(b'/');
+                }
+                target.extend_from_slice(hidden);
+                remove_subtree(tree, &manifest_key(&target));
+            }
+            continue;
+        }
+
+        let key = manifest_key(&rel);
+        let header = entry.header();
+        let mode = header.mode()? & 0o7777;
+        let mtime = header.mtime()?;
+        let vnode = match header.entry_type() {
+            tar::EntryType::Directory => VnodeEntry::new_directory(mtime, mode),
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let size = header.size()?;
+                let hash = cas.store_reader(&mut entry)?;
+                VnodeEntry::new_file(hash, size, mtime, mode)
+            }
+            tar::EntryType::Symlink => {
+                let target = entry.link_name_bytes().unwrap_or_default().into_owned();
+                let hash = cas.store(&target)?;
+                VnodeEntry::new_symlink(hash, target.len() as u64, mtime)
+            }
+            tar::EntryType::Link => {
+                let target = match entry.link_name_bytes() {
+                    Some(t) => layer_path(&t)?.map(|t| manifest_key(&t)),
+                    None => None,
+                };
+                match target.and_then(|t| tree.get(&t)) {
+                    Some(linked) => linked.clone(),
+                    None => {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                summary.skipped += 1;
+                continue;
+            }
+        };
+        // Anything replacing a directory replaces its contents too
+        if !vnode.is_dir() {
+            remove_subtree(tree, &key);
+        }
+        added.insert(key.clone());
+        tree.insert(key, vnode);
+    }
+    Ok(())
+}
+
+/// A layer entry's path relative to the image root, with `.` and empty
+/// components dropped; `None` for the root. A `..` component is an error:
+/// the entry would otherwise land outside the image (or, through a hard
+/// link, copy something from outside it).
+fn layer_path(raw: &[u8]) -> Result<Option<Vec<u8>>> {
+    let mut path = Vec::with_capacity(raw.len());
+    for component in raw.split(|&b| b == b'/') {
+        match component {
+            b"" | b"." => {}
+            b".." => anyhow::bail!(
+                "Layer entry {:?} escapes the image root",
+                String::from_utf8_lossy(raw)
+            ),
+            name => {
+                if !path.is_empty() {
+                    path.push(b'/');
+                }
+                path.extend_from_slice(name);
+            }
+        }
+    }
+    Ok((!path.is_empty()).then_some(path))
+}
+
+fn manifest_key(rel: &[u8]) -> String {
+    format!("/{}", vrift_ipc::encode_path_key(rel))
+}
+
+/// Drop `key` and everything below it
+fn remove_subtree(tree: &mut BTreeMap<String, VnodeEntry>, key: &str) {
+    tree.remove(key);
+    let prefix = format!("{}/", key);
+    let below: Vec<String> = tree
+        .range(prefix.clone()..)
+        .take_while(|(k, _)| k.starts_with(&prefix))
+        .map(|(k, _)| k.clone())
+        .collect();
+    for k in below {
+        tree.remove(&k);
+    }
+}
+
+/// This machine's architecture under its OCI name
+fn oci_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        "powerpc64" => "ppc64le",
+        other => other,
+    }
+}
+
+/// Passes writes through, keeping a SHA-256 and byte count of them
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            len: 0,
+        }
+    }
+
+    /// The writer back, with the hex digest and length of what went through
+    fn finish(self) -> (W, String, u64) {
+        (self.inner, hex::encode(self.hasher.finalize()), self.len)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_split_reference() {
+        assert_eq!(split_reference("app:1.0"), ("app", "1.0"));
+        assert_eq!(split_reference("app"), ("app", "latest"));
+        assert_eq!(
+            split_reference("registry:5000/team/app"),
+            ("registry:5000/team/app", "latest")
+        );
+        assert_eq!(
+            split_reference("registry:5000/app:v2"),
+            ("registry:5000/app", "v2")
+        );
+    }
+
+    #[test]
+    fn test_export_writes_valid_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let mut manifest = Manifest::new();
+        let body = b"#!/bin/sh\necho hi\n";
+        let hash = cas.store(body).unwrap();
+        manifest.insert(
+            "/usr/bin/hi",
+            VnodeEntry::new_file(hash, body.len() as u64, 1_700_000_000, 0o755),
+        );
+        let target = cas.store(b"usr/bin/hi").unwrap();
+        manifest.insert("/hi", VnodeEntry::new_symlink(target, 10, 1_700_000_000));
+        manifest.insert("/gone", VnodeEntry::new_whiteout(0));
+
+        let out = dir.path().join("oci");
+        let export = export_oci(&manifest, &cas, &out, "demo/hi:v1", "amd64").unwrap();
+        assert_eq!(export.entries, 4); // usr, usr/bin, usr/bin/hi, hi
+
+        // Every blob is stored under its own digest
+        for entry in fs::read_dir(out.join("blobs/sha256")).unwrap() {
+            let path = entry.unwrap().path();
+            let digest = hex::encode(Sha256::digest(fs::read(&path).unwrap()));
+            assert_eq!(path.file_name().unwrap().to_str().unwrap(), digest);
+        }
+
+        let index: serde_json::Value =
+            serde_json::from_slice(&fs::read(out.join("index.json")).unwrap()).unwrap();
+        let entry = &index["manifests"][0];
+        assert_eq!(entry["digest"], export.manifest.digest.as_str());
+        assert_eq!(
+            entry["annotations"]["org.opencontainers.image.ref.name"],
+            "v1"
+        );
+
+        let blob = |digest: &str| {
+            fs::read(
+                out.join("blobs/sha256")
+                    .join(digest.trim_start_matches("sha256:")),
+            )
+            .unwrap()
+        };
+        let image: serde_json::Value =
+            serde_json::from_slice(&blob(&export.manifest.digest)).unwrap();
+        assert_eq!(image["layers"][0]["digest"], export.layer.digest.as_str());
+        let config: serde_json::Value =
+            serde_json::from_slice(&blob(image["config"]["digest"].as_str().unwrap())).unwrap();
+
+        let mut tar_bytes = Vec::new();
+        flate2::read::GzDecoder::new(&blob(&export.layer.digest)[..])
+            .read_to_end(&mut tar_bytes)
+            .unwrap();
+        assert_eq!(
+            config["rootfs"]["diff_ids"][0],
+            format!("sha256:{}", hex::encode(Sha256::digest(&tar_bytes)))
+        );
+
+        let mut archive = tar::Archive::new(&tar_bytes[..]);
+        let mut names = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().into_owned();
+            if name == "usr/bin/hi" {
+                assert_eq!(entry.header().mode().unwrap(), 0o755);
+                assert_eq!(entry.header().mtime().unwrap(), 1_700_000_000);
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content).unwrap();
+                assert_eq!(content, body);
+            }
+            if name == "hi" {
+                assert_eq!(
+                    entry.link_name().unwrap().unwrap().to_str(),
+                    Some("usr/bin/hi")
+                );
+            }
+            names.push(name);
+        }
+        assert_eq!(names, ["hi", "usr", "usr/bin", "usr/bin/hi"]);
+
+        // Same manifest, same digests
+        let again = export_oci(
+            &manifest,
+            &cas,
+            &dir.path().join("again"),
+            "demo/hi:v1",
+            "amd64",
+        )
+        .unwrap();
+        assert_eq!(again.manifest.digest, export.manifest.digest);
+    }
+
+    #[test]
+    fn test_import_round_trips_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let mut manifest = Manifest::new();
+        let hash = cas.store(b"hello").unwrap();
+        manifest.insert("/etc", VnodeEntry::new_directory(1_600_000_000, 0o755));
+        manifest.insert(
+            "/etc/motd",
+            VnodeEntry::new_file(hash, 5, 1_700_000_000, 0o644),
+        );
+        let target = cas.store(b"etc/motd").unwrap();
+        manifest.insert("/motd", VnodeEntry::new_symlink(target, 8, 1_700_000_000));
+
+        let out = dir.path().join("oci");
+        export_oci(&manifest, &cas, &out, "demo:v1", "amd64").unwrap();
+        let (imported, summary) = import_layout(&out, &cas, Some("v1"), "amd64").unwrap();
+        assert_eq!(summary.layers, 1);
+        assert_eq!((summary.files, summary.dirs, summary.symlinks), (1, 1, 1));
+        assert_eq!(imported.get("/etc/motd"), manifest.get("/etc/motd"));
+        assert_eq!(imported.get("/motd"), manifest.get("/motd"));
+        assert!(imported.get("/etc").unwrap().is_dir());
+
+        assert!(import_layout(&out, &cas, Some("v2"), "amd64").is_err());
+    }
+
+    #[test]
+    fn test_import_applies_whiteouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let image = dir.path().join("image");
+        fs::create_dir_all(&image).unwrap();
+
+        let layer = |name: &str, entries: &[(&str, Option<&[u8]>)]| {
+            let mut tar = tar::Builder::new(Vec::new());
+            for (path, body) in entries {
+                let mut header = tar::Header::new_gnu();
+                header.set_mode(0o644);
+                match body {
+                    Some(body) => {
+                        header.set_size(body.len() as u64);
+                        tar.append_data(&mut header, path, *body).unwrap();
+                    }
+                    None => {
+                        header.set_entry_type(tar::EntryType::Directory);
+                        header.set_mode(0o755);
+                        header.set_size(0);
+                        tar.append_data(&mut header, path, io::empty()).unwrap();
+                    }
+                }
+            }
+            let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+            gzip.write_all(&tar.into_inner().unwrap()).unwrap();
+            fs::write(image.join(name), gzip.finish().unwrap()).unwrap();
+        };
+        layer(
+            "base.tar",
+            &[
+                ("etc/", None),
+                ("etc/keep", Some(b"keep")),
+                ("etc/drop", Some(b"drop")),
+                ("opt/", None),
+                ("opt/old/file", Some(b"old")),
+                ("var/", None),
+                ("var/cache", Some(b"cache")),
+            ],
+        );
+        layer(
+            "app.tar",
+            &[
+                ("./etc/.wh.drop", Some(b"")),
+                ("./opt/new", Some(b"new")),
+                ("./opt/.wh..wh..opq", Some(b"")),
+                ("./var", Some(b"now a file")),
+            ],
+        );
+        fs::write(
+            image.join("manifest.json"),
+            r#"[{"Config":"c.json","RepoTags":["demo:latest"],"Layers":["base.tar","app.tar"]}]"#,
+        )
+        .unwrap();
+
+        let (manifest, summary) = import_layout(&image, &cas, None, "amd64").unwrap();
+        assert_eq!(summary.layers, 2);
+        assert_eq!(summary.whiteouts, 2);
+        let mut paths: Vec<&str> = manifest.paths().collect();
+        paths.sort();
+        assert_eq!(paths, ["/etc", "/etc/keep", "/opt", "/opt/new", "/var"]);
+        let var = manifest.get("/var").unwrap();
+        assert!(var.is_file());
+        assert_eq!(cas.get(&var.content_hash).unwrap(), b"now a file");
+    }
+
+    #[test]
+    fn test_layer_path_rejects_parent_components() {
+        assert_eq!(layer_path(b"./etc/").unwrap(), Some(b"etc".to_vec()));
+        assert_eq!(layer_path(b"/a/./b//c").unwrap(), Some(b"a/b/c".to_vec()));
+        assert_eq!(layer_path(b"./").unwrap(), None);
+        assert!(layer_path(b"../etc/passwd").is_err());
+        assert!(layer_path(b"a/../../b").is_err());
+    }
+
+    #[test]
+    fn test_import_rejects_unsafe_digests_and_layer_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+
+        let layout = dir.path().join("layout");
+        fs::create_dir_all(&layout).unwrap();
+        fs::write(
+            layout.join("index.json"),
+            r#"{"manifests":[{"digest":"sha256:../../index.json"}]}"#,
+        )
+        .unwrap();
+        let err = import_layout(&layout, &cas, None, "amd64").unwrap_err();
+        assert!(format!("{:#}", err).contains("digest"), "{:#}", err);
+
+        let archive = dir.path().join("archive");
+        fs::create_dir_all(&archive).unwrap();
+        fs::write(
+            archive.join("manifest.json"),
+            r#"[{"RepoTags":["demo:latest"],"Layers":["../layer.tar"]}]"#,
+        )
+        .unwrap();
+        let err = import_layout(&archive, &cas, None, "amd64").unwrap_err();
+        assert!(format!("{:#}", err).contains("leaves"), "{:#}", err);
+    }
+}
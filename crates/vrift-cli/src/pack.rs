@@ -0,0 +1,347 @@
+//! # Single-File Bundles
+//!
+//! `vrift pack` writes a manifest and every blob it references into one
+//! file for air-gapped distribution; `vrift unpack` imports one into the
+//! local CAS plus a manifest file. `vrift mount` and `vrift run --bundle`
+//! serve a bundle directly with no setup: `run` mounts it through FUSE when
+//! built with it, and otherwise extracts it to a scratch directory.
+
+use std::fs::{self, File, FileTimes};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use vrift_cas::CasStore;
+use vrift_manifest::{LmdbManifest, Manifest};
+use vrift_pack::{BundleReader, BundleWriter};
+
+#[derive(Args, Debug)]
+pub struct PackArgs {
+    /// Manifest to bundle (manifest file or LMDB manifest directory)
+    #[arg(value_name = "MANIFEST")]
+    manifest: PathBuf,
+
+    /// Output bundle file
+    #[arg(short, long, default_value = "bundle.vrift")]
+    output: PathBuf,
+
+    /// LZ4-compress blobs (kept raw where that is smaller)
+    #[arg(long)]
+    compress: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct UnpackArgs {
+    /// Bundle written by `vrift pack`
+    #[arg(value_name = "BUNDLE")]
+    bundle: PathBuf,
+
+    /// Manifest file to write
+    #[arg(short, long, default_value = "vrift.manifest")]
+    output: PathBuf,
+}
+
+pub fn run(args: PackArgs, cas_root: &Path) -> Result<()> {
+    let manifest = load_manifest(&args.manifest)?;
+    let cas = CasStore::new(cas_root)
+        .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+
+    // Check completeness before writing anything
+    let mut missing = Vec::new();
+    for (path, entry) in manifest.iter() {
+        if !entry.is_dir() && !cas.exists(&entry.content_hash) {
+            missing.push(path.to_string());
+        }
+    }
+    if !missing.is_empty() {
+        missing.sort();
+        for path in missing.iter().take(10) {
+            eprintln!("  missing blob: {}", path);
+        }
+        anyhow::bail!(
+            "{} manifest entries reference blobs not in the CAS; refusing to write an incomplete bundle",
+            missing.len()
+        );
+    }
+
+    let mut writer = BundleWriter::create(&args.output, &manifest, args.compress)
+        .with_context(|| format!("Failed to create bundle: {}", args.output.display()))?;
+    let mut raw_bytes = 0u64;
+    for (path, entry) in manifest.iter() {
+        if entry.is_dir() {
+            continue;
+        }
+        let data = cas
+            .get(&entry.content_hash)
+            .with_context(|| format!("Failed to read blob for {}", path))?;
+        let before = writer.len();
+        writer.add(entry.content_hash, &data)?;
+        if writer.len() > before {
+            raw_bytes += data.len() as u64;
+        }
+    }
+    let blobs = writer.len();
+    let output = writer.finish()?;
+    let size = std::fs::metadata(&output)?.len();
+
+    println!("📦 Bundle: {}", output.display());
+    println!("   {} entries, {} blobs", manifest.len(), blobs);
+    println!("   {} bytes of blobs → {} bytes on disk", raw_bytes, size);
+    Ok(())
+}
+
+/// Load a flat manifest file, or snapshot an LMDB manifest directory
+pub(crate) fn load_manifest(path: &Path) -> Result<Manifest> {
+    if !path.exists() {
+        anyhow::bail!("Manifest not found: {}", path.display());
+    }
+    if !path.is_dir() {
+        return Manifest::load(path)
+            .with_context(|| format!("Failed to load manifest: {}", path.display()));
+    }
+
+    let lmdb = LmdbManifest::open(path)
+        .with_context(|| format!("Failed to open manifest: {}", path.display()))?;
+    let mut manifest = Manifest::new();
+    for (path, entry) in lmdb.iter()? {
+        manifest.set_xattrs(&path, &entry.xattrs());
+        manifest.insert(&path, entry.vnode);
+    }
+    Ok(manifest)
+}
+
+/// Import a bundle: blobs into the CAS, manifest to a file
+pub fn unpack(args: UnpackArgs, cas_root: &Path) -> Result<()> {
+    let bundle = open_bundle(&args.bundle)?;
+    let cas = CasStore::new(cas_root)
+        .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+
+    let mut stored = 0usize;
+    let mut bytes = 0u64;
+    for hash in bundle.hashes() {
+        if cas.exists(hash) {
+            continue;
+        }
+        let data = bundle.get(hash)?;
+        // store() re-hashes, so a damaged bundle can't poison the CAS
+        let actual = cas.store(&data)?;
+        if actual != *hash {
+            anyhow::bail!(
+                "Bundle blob {} is corrupt (content hashes to {})",
+                CasStore::hash_to_hex(hash),
+                CasStore::hash_to_hex(&actual)
+            );
+        }
+        stored += 1;
+        bytes += data.len() as u64;
+    }
+
+    bundle
+        .manifest()
+        .save(&args.output)
+        .with_context(|| format!("Failed to write manifest: {}", args.output.display()))?;
+
+    println!("📥 Unpacked: {}", args.bundle.display());
+    println!(
+        "   {} blobs ({} new, {} bytes) → {}",
+        bundle.len(),
+        stored,
+        bytes,
+        cas_root.display()
+    );
+    println!("   📄 Manifest: {}", args.output.display());
+    Ok(())
+}
+
+fn open_bundle(path: &Path) -> Result<BundleReader> {
+    BundleReader::open(path).with_context(|| format!("Failed to open bundle: {}", path.display()))
+}
+
+/// Run `command` with its working directory at a read-only view of `bundle`
+pub fn run_from_bundle(bundle: &Path, command: &[String]) -> Result<()> {
+    if command.is_empty() {
+        anyhow::bail!("No command specified");
+    }
+    let reader = open_bundle(bundle)?;
+    let root = tempfile::Builder::new()
+        .prefix("vrift-bundle-")
+        .tempdir()
+        .context("Failed to create bundle root")?;
+
+    println!("Running from bundle:");
+    println!("  Bundle:   {}", bundle.display());
+
+    let status = {
+        // Held until the command exits; dropping it unmounts
+        let _mount = match try_mount(reader, root.path()) {
+            Ok(mount) => {
+                println!("  Root:     {} (FUSE mount)", root.path().display());
+                Some(mount)
+            }
+            Err(e) => {
+                tracing::info!("Bundle mount unavailable ({:#}); extracting instead", e);
+                extract_bundle(&open_bundle(bundle)?, root.path())?;
+                println!("  Root:     {} (extracted)", root.path().display());
+                None
+            }
+        };
+        println!("  Command:  {}", command.join(" "));
+        println!();
+
+        std::process::Command::new(&command[0])
+            .args(&command[1..])
+            .current_dir(root.path())
+            .env("VRIFT_BUNDLE_ROOT", root.path())
+            .status()
+            .with_context(|| format!("Failed to execute: {}", command[0]))?
+    };
+
+    if !status.success() {
+        // exit() skips destructors; clean up the scratch root first
+        drop(root);
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "fuse")]
+type BundleMount = vrift_fuse::BackgroundMount;
+#[cfg(not(feature = "fuse"))]
+type BundleMount = ();
+
+#[cfg(feature = "fuse")]
+fn try_mount(reader: BundleReader, root: &Path) -> Result<BundleMount> {
+    let manifest = reader.manifest().clone();
+    vrift_fuse::VeloFs::new(&manifest, reader).spawn_mount(root)
+}
+
+#[cfg(not(feature = "fuse"))]
+fn try_mount(_reader: BundleReader, _root: &Path) -> Result<BundleMount> {
+    anyhow::bail!("built without FUSE support")
+}
+
+/// Write every manifest entry of `bundle` under `root`.
+///
+/// The bundle may be hostile: keys that would leave `root` are refused,
+/// every blob is checked against its hash before it is written, and
+/// symlinks are made last (children before parents) with files opened
+/// O_NOFOLLOW, so nothing is ever written through a link the bundle made.
+fn extract_bundle(bundle: &BundleReader, root: &Path) -> Result<()> {
+    let mut entries = Vec::new();
+    for (key, vnode) in bundle.manifest().iter() {
+        entries.push((root.join(crate::hydrate::key_to_rel_path(key)?), key, vnode));
+    }
+    entries.sort_by(|a, b| a.1.cmp(b.1));
+
+    let mut symlinks = Vec::new();
+    for (target, key, vnode) in entries {
+        if vnode.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = bundle.get(&vnode.content_hash)?;
+        if CasStore::compute_hash(&data) != vnode.content_hash {
+            anyhow::bail!("Corrupt bundle: content of {} does not match its hash", key);
+        }
+        if vnode.is_symlink() {
+            symlinks.push((target, data));
+            continue;
+        }
+        let mut file = File::options()
+            .write(true)
+            .create_new(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&target)
+            .with_context(|| format!("Failed to extract {}", key))?;
+        file.write_all(&data)
+            .with_context(|| format!("Failed to extract {}", key))?;
+        let mtime = UNIX_EPOCH + Duration::from_secs(vnode.mtime);
+        file.set_times(FileTimes::new().set_modified(mtime).set_accessed(mtime))?;
+        file.set_permissions(fs::Permissions::from_mode(vnode.mode & 0o7777))?;
+    }
+
+    for (target, data) in symlinks.into_iter().rev() {
+        use std::os::unix::ffi::OsStrExt;
+        std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(&data), &target)
+            .with_context(|| format!("Failed to extract {}", target.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_manifest::VnodeEntry;
+
+    /// Bundle holding `entries`; each blob is stored as given, so a
+    /// mismatching hash can be planted
+    fn bundle(dir: &Path, entries: &[(&str, VnodeEntry, &[u8])]) -> BundleReader {
+        let mut manifest = Manifest::new();
+        for (key, vnode, _) in entries {
+            manifest.insert(key, vnode.clone());
+        }
+        let path = dir.join("test.vrift");
+        let mut writer = BundleWriter::create(&path, &manifest, false).unwrap();
+        for (_, vnode, data) in entries {
+            if !vnode.is_dir() {
+                writer.add(vnode.content_hash, data).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+        BundleReader::open(&path).unwrap()
+    }
+
+    fn file(data: &[u8]) -> VnodeEntry {
+        VnodeEntry::new_file(CasStore::compute_hash(data), data.len() as u64, 0, 0o644)
+    }
+
+    #[test]
+    fn test_extract_refuses_keys_outside_root() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("root");
+        fs::create_dir(&root).unwrap();
+        let reader = bundle(temp.path(), &[("/../escaped", file(b"x"), b"x")]);
+
+        assert!(extract_bundle(&reader, &root).is_err());
+        assert!(!temp.path().join("escaped").exists());
+    }
+
+    #[test]
+    fn test_extract_never_writes_through_symlinks() {
+        let temp = tempfile::tempdir().unwrap();
+        let (root, outside) = (temp.path().join("root"), temp.path().join("outside"));
+        fs::create_dir(&root).unwrap();
+        fs::create_dir(&outside).unwrap();
+        let link = outside.to_str().unwrap().as_bytes();
+        let symlink = VnodeEntry::new_symlink(CasStore::compute_hash(link), link.len() as u64, 0);
+        let reader = bundle(
+            temp.path(),
+            &[
+                ("/a", symlink, link),
+                ("/a/passwd", file(b"owned"), b"owned"),
+            ],
+        );
+
+        // "/a" can't be both; whatever happens, nothing lands outside
+        let _ = extract_bundle(&reader, &root);
+        assert!(!outside.join("passwd").exists());
+    }
+
+    #[test]
+    fn test_extract_checks_blob_hashes() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("root");
+        fs::create_dir(&root).unwrap();
+        let reader = bundle(temp.path(), &[("/f", file(b"expected"), b"tampered")]);
+
+        let err = extract_bundle(&reader, &root).unwrap_err();
+        assert!(format!("{:#}", err).contains("does not match"));
+        assert!(!root.join("f").exists());
+    }
+}
@@ -0,0 +1,170 @@
+//! # Blob Pinning
+//!
+//! `vrift pin add|rm|list` manages labelled pins in the CAS pin store
+//! (`vrift_cas::pins`). `vrift gc` and the daemon's sweep never delete a
+//! pinned blob, so a tree pinned here survives even after every manifest
+//! that referenced it is gone.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use vrift_cas::{Blake3Hash, CasStore, PinStore};
+use vrift_manifest::lmdb::LmdbManifest;
+
+use crate::manifest_edit::normalize_key;
+
+#[derive(Subcommand, Debug)]
+pub enum PinCommands {
+    /// Pin the blobs of a project's manifest (optionally only some paths)
+    Add {
+        /// Label to pin under (e.g. "toolchain-1.82")
+        label: String,
+
+        /// Paths or directories to pin, relative to the project root
+        /// (default: the whole manifest)
+        #[arg(value_name = "PATH")]
+        paths: Vec<String>,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+
+        /// Pin explicit blob hashes (hex) instead of manifest paths
+        #[arg(long = "hash", value_name = "HEX")]
+        hashes: Vec<String>,
+    },
+
+    /// Remove a label; its blobs become collectable unless pinned elsewhere
+    Rm {
+        /// Label to remove
+        label: String,
+    },
+
+    /// List labels and how many blobs each pins
+    List,
+}
+
+pub fn run(command: PinCommands, cas_root: &Path) -> Result<()> {
+    match command {
+        PinCommands::Add {
+            label,
+            paths,
+            directory,
+            hashes,
+        } => {
+            let hashes = if hashes.is_empty() {
+                let dir = directory.unwrap_or_else(|| std::env::current_dir().unwrap());
+                let manifest = open_manifest(&dir)?;
+                manifest_hashes(&manifest, &paths)?
+            } else {
+                parse_hashes(&hashes)?
+            };
+            if hashes.is_empty() {
+                anyhow::bail!("Nothing to pin: no matching manifest entries");
+            }
+
+            let cas = CasStore::new(cas_root)?;
+            let missing = hashes.iter().filter(|h| !cas.exists(h)).count();
+            let mut pins = PinStore::load_locked(cas_root).context("Failed to read pin store")?;
+            let added = pins.pin(&label, &hashes);
+            pins.save().context("Failed to save pin store")?;
+
+            println!(
+                "📌 Pinned {} blobs under '{}' ({} new)",
+                hashes.len(),
+                label,
+                added
+            );
+            if missing > 0 {
+                println!("⚠️  {} of them are not in the CAS yet", missing);
+            }
+        }
+        PinCommands::Rm { label } => {
+            let mut pins = PinStore::load_locked(cas_root).context("Failed to read pin store")?;
+            let n = pins
+                .unpin(&label)
+                .with_context(|| format!("No such pin label: {}", label))?;
+            pins.save().context("Failed to save pin store")?;
+            println!("🗑️  Unpinned '{}' ({} blobs)", label, n);
+        }
+        PinCommands::List => {
+            let pins = PinStore::load(cas_root).context("Failed to read pin store")?;
+            let labels: Vec<_> = pins.labels().collect();
+            if labels.is_empty() {
+                println!("No pins.");
+            }
+            for (label, count) in labels {
+                println!("📌 {:<32} {} blobs", label, count);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open_manifest(dir: &Path) -> Result<LmdbManifest> {
+    let project_id = vrift_config::path::compute_project_id(dir);
+    let manifest_path = vrift_config::path::get_manifest_db_path(&project_id)
+        .ok_or_else(|| anyhow::anyhow!("Could not determine manifest path"))?;
+    if !manifest_path.exists() {
+        anyhow::bail!(
+            "Manifest not found at {}. Run 'vrift init' first.",
+            manifest_path.display()
+        );
+    }
+    Ok(LmdbManifest::open(&manifest_path)?)
+}
+
+/// Content hashes of file entries at or beneath any of `paths` (all when empty)
+pub fn manifest_hashes(manifest: &LmdbManifest, paths: &[String]) -> Result<HashSet<Blake3Hash>> {
+    let keys: Vec<String> = paths.iter().map(|p| normalize_key(p)).collect();
+    Ok(manifest
+        .iter()?
+        .into_iter()
+        .filter(|(key, entry)| {
+            !entry.vnode.is_dir()
+                && (keys.is_empty()
+                    || keys
+                        .iter()
+                        .any(|k| key == k || key.starts_with(&format!("{}/", k))))
+        })
+        .map(|(_, entry)| entry.vnode.content_hash)
+        .collect())
+}
+
+fn parse_hashes(hexes: &[String]) -> Result<HashSet<Blake3Hash>> {
+    hexes
+        .iter()
+        .map(|hex| CasStore::hex_to_hash(hex).with_context(|| format!("Invalid hash: {}", hex)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_ipc::VnodeEntry;
+    use vrift_manifest::lmdb::AssetTier;
+
+    #[test]
+    fn test_manifest_hashes_selects_subtree() {
+        let dir = tempfile::tempdir().unwrap();
+        let m = LmdbManifest::open(dir.path().join("manifest.lmdb")).unwrap();
+        for (key, byte) in [("/tools/cc", 1u8), ("/tools/ld", 2), ("/toolsx", 3)] {
+            let vnode = VnodeEntry {
+                content_hash: [byte; 32],
+                size: 1,
+                mtime: 0,
+                mode: 0o755,
+                flags: 0,
+                _pad: 0,
+            };
+            m.insert(key, vnode, AssetTier::Tier1Immutable);
+        }
+        m.commit().unwrap();
+
+        let picked = manifest_hashes(&m, &["tools".to_string()]).unwrap();
+        assert_eq!(picked, HashSet::from([[1u8; 32], [2u8; 32]]));
+        assert_eq!(manifest_hashes(&m, &[]).unwrap().len(), 3);
+    }
+}
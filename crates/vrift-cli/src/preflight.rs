@@ -13,7 +13,7 @@ use console::style;
 /// VDIR_MAGIC must match vrift-vdird/src/vdir.rs
 const VDIR_MAGIC: u32 = 0x56524654; // "VRFT"
 /// VDIR_VERSION must match vrift-vdird/src/vdir.rs
-const VDIR_VERSION: u32 = 2;
+const VDIR_VERSION: u32 = 4;
 
 /// Result of preflight checks
 #[derive(Debug)]
@@ -0,0 +1,169 @@
+//! # Access Heatmap
+//!
+//! `vrift profile heatmap` merges the inception layer's heatmap file
+//! (`vrift run --heatmap`, or `VRIFT_HEATMAP=<file>`) with the lookup counts
+//! vdir_d reports in its metrics, and prints path prefixes hottest first.
+//! `--export` writes the merged cells back out as TSV; exported to
+//! `.vrift/heatmap.tsv`, it decides which entries vdir_d puts in the VDir
+//! first on its next warm start.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use vrift_ipc::heatmap::{heatmap_path, Heatmap};
+
+use crate::{daemon, format_number};
+
+#[derive(Subcommand, Debug)]
+pub enum ProfileCommands {
+    /// Show access counts per path prefix and operation, hottest first
+    Heatmap {
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+
+        /// Heatmap file (default: <DIR>/.vrift/heatmap.tsv)
+        #[arg(short, long, value_name = "FILE")]
+        file: Option<PathBuf>,
+
+        /// Number of prefixes to show
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+
+        /// Write the merged heatmap to this file as TSV
+        #[arg(long, value_name = "FILE")]
+        export: Option<PathBuf>,
+    },
+}
+
+pub async fn run(command: ProfileCommands) -> Result<()> {
+    match command {
+        ProfileCommands::Heatmap {
+            directory,
+            file,
+            top,
+            export,
+        } => {
+            let dir = directory.unwrap_or_else(|| std::env::current_dir().unwrap());
+            let file = file.unwrap_or_else(|| heatmap_path(&dir));
+            let mut map = Heatmap::load(&file)
+                .with_context(|| format!("Failed to read heatmap: {}", file.display()))?;
+
+            // vdir_d counts the lookups that reached it; a stopped daemon just
+            // means the file alone is reported
+            let project_id = vrift_config::path::compute_project_id(&dir);
+            if let Some(socket) = vrift_config::path::get_vdird_socket_path(&project_id) {
+                if let Ok(metrics) = daemon::fetch_metrics(Some(&socket)).await {
+                    map.merge_cells(&metrics.heat);
+                }
+            }
+
+            print!("{}", render(&file, &map, top));
+
+            if let Some(export) = export {
+                write_tsv(&export, &map)?;
+                println!();
+                println!(
+                    "Exported {} cells to {}",
+                    map.cells().len(),
+                    export.display()
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_tsv(path: &Path, map: &Heatmap) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, map.to_tsv())
+        .with_context(|| format!("Failed to write heatmap: {}", path.display()))
+}
+
+fn render(source: &Path, map: &Heatmap, top: usize) -> String {
+    use std::fmt::Write;
+    let mut out = String::new();
+
+    if map.is_empty() {
+        let _ = writeln!(
+            out,
+            "No heatmap data ({} is empty or missing).",
+            source.display()
+        );
+        let _ = writeln!(
+            out,
+            "Record one with `vrift run --heatmap <command>` or VRIFT_HEATMAP=<file>."
+        );
+        return out;
+    }
+
+    let rows = map.by_prefix();
+    let grand_total: u64 = rows.iter().map(|r| r.total).sum();
+    let width = rows
+        .iter()
+        .take(top)
+        .map(|r| r.prefix.len())
+        .max()
+        .unwrap_or(0)
+        .max("PREFIX".len());
+
+    let _ = writeln!(
+        out,
+        "Access heatmap: {} prefixes, {} accesses",
+        format_number(rows.len() as u64),
+        format_number(grand_total)
+    );
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "  {:<width$}  {:>12}  {:>6}  OPS",
+        "PREFIX", "TOTAL", "SHARE"
+    );
+    for row in rows.iter().take(top) {
+        let ops: Vec<String> = row
+            .ops
+            .iter()
+            .map(|(op, count)| format!("{} {}", op, format_number(*count)))
+            .collect();
+        let _ = writeln!(
+            out,
+            "  {:<width$}  {:>12}  {:>5.1}%  {}",
+            row.prefix,
+            format_number(row.total),
+            row.total as f64 * 100.0 / grand_total as f64,
+            ops.join(", ")
+        );
+    }
+    if rows.len() > top {
+        let _ = writeln!(out, "  ... {} more prefixes", rows.len() - top);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_sorted_and_truncated() {
+        let map =
+            Heatmap::parse_tsv("/lib\tstat\t10\n/src/a\topen\t30\n/src/a\tstat\t60\n/x\tstat\t1\n");
+        let out = render(Path::new("h.tsv"), &map, 2);
+
+        let src = out.find("/src/a").unwrap();
+        let lib = out.find("/lib").unwrap();
+        assert!(src < lib, "hottest prefix first:\n{}", out);
+        assert!(out.contains("stat 60, open 30"));
+        assert!(out.contains("... 1 more prefixes"));
+        assert!(!out.contains("/x"));
+    }
+
+    #[test]
+    fn test_render_empty_hint() {
+        let out = render(Path::new("h.tsv"), &Heatmap::new(), 20);
+        assert!(out.contains("No heatmap data"));
+    }
+}
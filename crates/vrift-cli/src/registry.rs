@@ -21,7 +21,7 @@ use std::time::Duration;
 use uuid::Uuid;
 use vrift_cas::Blake3Hash;
 use vrift_config::path::normalize_or_original;
-use vrift_manifest::{LmdbManifest, Manifest};
+use vrift_manifest::{GenerationStore, LmdbManifest, Manifest};
 
 /// Default lock timeout in seconds
 const DEFAULT_LOCK_TIMEOUT_SECS: u64 = 30;
@@ -237,6 +237,23 @@ impl ManifestRegistry {
         Ok(uuid)
     }
 
+    /// Register one manifest for GC as a side job of another command:
+    /// failing to is logged, not fatal
+    pub fn register_or_warn(manifest_path: &Path, project_root: &Path) {
+        let registered = Self::load_or_create().and_then(|mut registry| {
+            let _lock = Self::acquire_lock().ok();
+            registry.register_manifest(manifest_path, project_root)?;
+            registry.save()
+        });
+        if let Err(e) = registered {
+            tracing::warn!(
+                "Failed to register {} for GC tracking: {}",
+                manifest_path.display(),
+                e
+            );
+        }
+    }
+
     /// Verify all manifests and update their status
     ///
     /// Returns count of (active, stale) manifests
@@ -276,9 +293,12 @@ impl ManifestRegistry {
         count
     }
 
-    /// Get all blob hashes referenced by all active manifests
+    /// Get all blob hashes referenced by all active manifests, and by the
+    /// snapshot generations of their projects (`vrift snapshot restore`
+    /// needs those blobs long after the manifest moved on)
     pub fn get_all_blob_hashes(&self) -> Result<HashSet<Blake3Hash>> {
         let mut hashes = HashSet::new();
+        let mut projects = HashSet::new();
 
         for entry in self.manifests.values() {
             if entry.status != ManifestStatus::Active {
@@ -289,6 +309,23 @@ impl ManifestRegistry {
                 continue;
             }
 
+            if projects.insert(entry.project_root.as_path()) {
+                let store = GenerationStore::for_project(&entry.project_root);
+                let ids = store.stored().with_context(|| {
+                    format!("Failed to list snapshots of {:?}", entry.project_root)
+                })?;
+                for id in ids {
+                    let generation = store.load(&id).with_context(|| {
+                        format!(
+                            "Failed to read snapshot {} of {:?}",
+                            vrift_cas::CasStore::hash_to_hex(&id),
+                            entry.project_root
+                        )
+                    })?;
+                    hashes.extend(generation.iter().map(|(_, e)| e.vnode.content_hash));
+                }
+            }
+
             if entry.source_path.is_dir() {
                 // RFC-0039: Load LMDB manifest
                 let lmdb = LmdbManifest::open(&entry.source_path).with_context(|| {
@@ -313,6 +350,44 @@ impl ManifestRegistry {
         Ok(hashes)
     }
 
+    /// Paths of active manifests whose content is one of `hashes`, per
+    /// project root (projects without such paths are left out)
+    pub fn paths_with_hashes(
+        &self,
+        hashes: &HashSet<Blake3Hash>,
+    ) -> Result<Vec<(PathBuf, Vec<String>)>> {
+        let mut affected = Vec::new();
+        for entry in self.manifests.values() {
+            if entry.status != ManifestStatus::Active || !entry.source_path.exists() {
+                continue;
+            }
+
+            let paths = if entry.source_path.is_dir() {
+                let lmdb = LmdbManifest::open(&entry.source_path).with_context(|| {
+                    format!("Failed to open LMDB manifest at {:?}", entry.source_path)
+                })?;
+                lmdb.paths_with_hashes(hashes).with_context(|| {
+                    format!("Failed to iterate LMDB manifest at {:?}", entry.source_path)
+                })?
+            } else {
+                let manifest = Manifest::load(&entry.source_path)
+                    .with_context(|| format!("Failed to load manifest: {:?}", entry.source_path))?;
+                let mut paths: Vec<String> = manifest
+                    .iter()
+                    .filter(|(_, vnode)| hashes.contains(&vnode.content_hash))
+                    .map(|(path, _)| path.to_string())
+                    .collect();
+                paths.sort();
+                paths
+            };
+            if !paths.is_empty() {
+                affected.push((entry.project_root.clone(), paths));
+            }
+        }
+        affected.sort();
+        Ok(affected)
+    }
+
     /// Get list of active manifests
     pub fn active_manifests(&self) -> Vec<(&String, &ManifestEntry)> {
         self.manifests
@@ -416,4 +491,54 @@ mod tests {
         assert_eq!(uuid1, uuid2);
         assert_eq!(registry.manifests.len(), 1);
     }
+
+    #[test]
+    fn test_gc_keeps_snapshot_blobs_for_restore() {
+        use vrift_cas::CasStore;
+        use vrift_manifest::{AssetTier, VnodeEntry};
+
+        let temp = TempDir::new().unwrap();
+        let project = temp.path().join("project");
+        let cas = CasStore::new(temp.path().join("cas")).unwrap();
+        let manifest_path = project.join(".vrift/manifest.lmdb");
+        std::fs::create_dir_all(&manifest_path).unwrap();
+        let manifest = LmdbManifest::open(&manifest_path).unwrap();
+        let file = |body: &[u8]| {
+            let hash = cas.store(body).unwrap();
+            (
+                hash,
+                VnodeEntry::new_file(hash, body.len() as u64, 0, 0o644),
+            )
+        };
+
+        // Snapshot v1, then move the manifest on to v2
+        let (v1, entry) = file(b"v1");
+        manifest.upsert("/a.txt", entry, AssetTier::Tier2Mutable);
+        manifest.commit().unwrap();
+        let store = GenerationStore::for_project(&project);
+        let snapshot = store.create(&manifest, "before").unwrap();
+        let (v2, entry) = file(b"v2");
+        manifest.upsert("/a.txt", entry, AssetTier::Tier2Mutable);
+        manifest.commit().unwrap();
+        let (orphan, _) = file(b"orphan");
+
+        let mut registry = ManifestRegistry::new();
+        registry
+            .register_manifest(&manifest_path, &project)
+            .unwrap();
+        let keep = registry.get_all_blob_hashes().unwrap();
+        assert!(keep.contains(&v1) && keep.contains(&v2));
+        assert!(!keep.contains(&orphan));
+
+        // Sweep as the daemon would, then roll back
+        for hash in cas.iter().unwrap().flatten() {
+            if !keep.contains(&hash) {
+                cas.delete(&hash).unwrap();
+            }
+        }
+        store.restore(&manifest, &snapshot.id).unwrap();
+        let restored = manifest.get("/a.txt").unwrap().unwrap();
+        assert_eq!(cas.get(&restored.vnode.content_hash).unwrap(), b"v1");
+        assert!(cas.get(&orphan).is_err());
+    }
 }
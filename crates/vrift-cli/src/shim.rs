@@ -0,0 +1,676 @@
+//! # Multi-Arch Inception Layer
+//!
+//! A preloaded library only loads into processes of its own architecture;
+//! otherwise dyld / ld.so skips it and the program runs without the VFS,
+//! with no error. On macOS this hits every x86_64 tool running under
+//! Rosetta on an arm64 machine.
+//!
+//! `vrift shim build` builds the inception layer for several targets and
+//! packages the results:
+//!
+//! - macOS: one universal (fat) dylib, plus one thin dylib per arch.
+//! - Linux: one `.so` per arch (`<out>/<arch>/libvrift_inception_layer.so`),
+//!   plus the host build at the top level.
+//!
+//! `vrift run` reads the target program's headers and injects a build that
+//! contains its architecture. When none does, it warns instead of letting
+//! the program run without the VFS.
+//!
+//! Windows has no preload: `vrift-shim-win` is injected by `vrift-win-run`
+//! and reads a portable view of the manifest, written by `vrift shim view`.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use vrift_manifest::VnodeEntry;
+use vrift_view as view;
+
+/// File name of the inception layer on this platform
+pub const LIB_NAME: &str = if cfg!(target_os = "macos") {
+    "libvrift_inception_layer.dylib"
+} else {
+    "libvrift_inception_layer.so"
+};
+
+#[derive(Subcommand, Debug)]
+pub enum ShimCommands {
+    /// Build the inception layer for several architectures and package it
+    Build(BuildArgs),
+
+    /// Show which inception layer build `vrift run` would inject for a program
+    Which {
+        /// Program name or path
+        program: String,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+    },
+
+    /// Flatten a manifest into the view file read by the Windows layer
+    View {
+        /// Manifest (flat file or LMDB directory)
+        manifest: PathBuf,
+
+        /// Output file (default: stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct BuildArgs {
+    /// Rust target triple to build; repeatable (default: both Apple
+    /// architectures on macOS, the host on Linux)
+    #[arg(long = "target", value_name = "TRIPLE")]
+    targets: Vec<String>,
+
+    /// Build with the release profile
+    #[arg(long)]
+    release: bool,
+
+    /// Velo Rift source checkout to build from (default: current directory)
+    #[arg(long, value_name = "DIR")]
+    workspace: Option<PathBuf>,
+
+    /// Output directory (default: ./.vrift, where `vrift run` looks first)
+    #[arg(short, long, value_name = "DIR")]
+    out: Option<PathBuf>,
+}
+
+pub fn run(command: ShimCommands) -> Result<()> {
+    match command {
+        ShimCommands::Build(args) => build(args),
+        ShimCommands::Which { program, directory } => {
+            let dir = directory.unwrap_or_else(|| std::env::current_dir().unwrap());
+            let default = crate::inception::find_inception_library(&dir)?;
+            let program_path = resolve_program(&program);
+            let program_arches = program_path
+                .as_deref()
+                .map(file_arches)
+                .transpose()?
+                .unwrap_or_default();
+            println!(
+                "{}: {}",
+                program_path
+                    .as_deref()
+                    .map_or(program.clone(), |p| p.display().to_string()),
+                arch_list(&program_arches)
+            );
+            let chosen = select_library(&default, &program);
+            println!(
+                "{}: {}",
+                chosen.display(),
+                arch_list(&file_arches(&chosen).unwrap_or_default())
+            );
+            Ok(())
+        }
+        ShimCommands::View { manifest, output } => write_view(&manifest, output.as_deref()),
+    }
+}
+
+// ============================================================================
+// Windows view
+// ============================================================================
+
+fn view_entry(path: &str, vnode: &VnodeEntry) -> view::Entry {
+    let kind = if vnode.is_dir() {
+        view::Kind::Dir
+    } else if vnode.is_whiteout() {
+        view::Kind::Whiteout
+    } else if vnode.is_symlink() {
+        view::Kind::Symlink
+    } else if vnode.is_chunked() {
+        view::Kind::Chunked
+    } else {
+        view::Kind::File
+    };
+    let has_content = !matches!(kind, view::Kind::Dir | view::Kind::Whiteout);
+    view::Entry {
+        key: path.to_string(),
+        kind,
+        mode: vnode.mode,
+        size: vnode.size,
+        mtime: vnode.mtime,
+        hash: has_content.then_some(vnode.content_hash),
+    }
+}
+
+fn write_view(manifest: &Path, output: Option<&Path>) -> Result<()> {
+    let manifest = crate::pack::load_manifest(manifest)?;
+    let mut entries: Vec<view::Entry> = manifest
+        .iter()
+        .map(|(path, vnode)| view_entry(path, vnode))
+        .collect();
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+    match output {
+        Some(path) => {
+            let mut out = io::BufWriter::new(
+                fs::File::create(path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?,
+            );
+            view::write(&mut out, &entries)?;
+            out.flush()?;
+            eprintln!("Wrote {} entries to {}", entries.len(), path.display());
+        }
+        None => view::write(&mut io::stdout().lock(), &entries)?,
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Architectures
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+    I686,
+    Arm,
+}
+
+impl Arch {
+    /// Directory name of this arch's variant
+    pub fn name(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64",
+            Arch::Aarch64 => "aarch64",
+            Arch::I686 => "i686",
+            Arch::Arm => "arm",
+        }
+    }
+
+    pub fn host() -> Arch {
+        if cfg!(target_arch = "aarch64") {
+            Arch::Aarch64
+        } else if cfg!(target_arch = "x86") {
+            Arch::I686
+        } else if cfg!(target_arch = "arm") {
+            Arch::Arm
+        } else {
+            Arch::X86_64
+        }
+    }
+
+    /// Arch of a Rust target triple
+    pub fn from_target(triple: &str) -> Option<Arch> {
+        match triple.split('-').next()? {
+            "x86_64" => Some(Arch::X86_64),
+            "aarch64" | "arm64" | "arm64e" => Some(Arch::Aarch64),
+            "i686" | "i586" | "i386" => Some(Arch::I686),
+            a if a.starts_with("arm") || a.starts_with("thumb") => Some(Arch::Arm),
+            _ => None,
+        }
+    }
+
+    fn from_elf_machine(machine: u16) -> Option<Arch> {
+        match machine {
+            62 => Some(Arch::X86_64),
+            183 => Some(Arch::Aarch64),
+            3 => Some(Arch::I686),
+            40 => Some(Arch::Arm),
+            _ => None,
+        }
+    }
+
+    fn from_macho_cputype(cputype: u32) -> Option<Arch> {
+        match cputype {
+            0x0100_0007 => Some(Arch::X86_64),
+            0x0100_000c => Some(Arch::Aarch64),
+            7 => Some(Arch::I686),
+            12 => Some(Arch::Arm),
+            _ => None,
+        }
+    }
+}
+
+fn arch_list(arches: &[Arch]) -> String {
+    if arches.is_empty() {
+        return "unknown architecture".to_string();
+    }
+    arches
+        .iter()
+        .map(|a| a.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+const MH_MAGIC: u32 = 0xfeed_face;
+const FAT_MAGIC: u32 = 0xcafe_babe;
+/// Slice alignment in a fat file (16K, the arm64 page size)
+const FAT_ALIGN_LOG2: u32 = 14;
+
+/// Architectures in an executable image: ELF, thin Mach-O or fat Mach-O.
+/// Empty if the format isn't recognized.
+pub fn binary_arches(data: &[u8]) -> Vec<Arch> {
+    let u16_le = |at: usize| {
+        data.get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u16_be = |at: usize| {
+        data.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+    let u32_le = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    };
+    let u32_be = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+    };
+
+    if data.starts_with(b"\x7fELF") {
+        // EI_DATA: 1 = little endian, 2 = big endian
+        let machine = if data.get(5) == Some(&2) {
+            u16_be(18)
+        } else {
+            u16_le(18)
+        };
+        return machine
+            .and_then(Arch::from_elf_machine)
+            .into_iter()
+            .collect();
+    }
+    match u32_le(0) {
+        Some(MH_MAGIC_64) | Some(MH_MAGIC) => {
+            return u32_le(4)
+                .and_then(Arch::from_macho_cputype)
+                .into_iter()
+                .collect();
+        }
+        _ => {}
+    }
+    if u32_be(0) == Some(FAT_MAGIC) {
+        let count = u32_be(4).unwrap_or(0) as usize;
+        // Java class files share the magic; their "count" is a version ≥ 45
+        if count > 16 {
+            return Vec::new();
+        }
+        return (0..count)
+            .filter_map(|i| u32_be(8 + i * 20).and_then(Arch::from_macho_cputype))
+            .collect();
+    }
+    Vec::new()
+}
+
+/// Architectures of the executable at `path`, following `#!` interpreters
+pub fn file_arches(path: &Path) -> io::Result<Vec<Arch>> {
+    let mut path = path.to_path_buf();
+    for _ in 0..4 {
+        let mut head = Vec::with_capacity(4096);
+        fs::File::open(&path)?.take(4096).read_to_end(&mut head)?;
+        if !head.starts_with(b"#!") {
+            return Ok(binary_arches(&head));
+        }
+        match interpreter(&head) {
+            Some(next) => path = next,
+            None => break,
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// The program a `#!` line runs (resolving `/usr/bin/env NAME`)
+fn interpreter(head: &[u8]) -> Option<PathBuf> {
+    let line = head[2..].split(|&b| b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut words = line.split_whitespace();
+    let interp = words.next()?;
+    if Path::new(interp).file_name()? == "env" {
+        let name = words.find(|w| !w.starts_with('-') && !w.contains('='))?;
+        return resolve_program(name);
+    }
+    Some(PathBuf::from(interp))
+}
+
+/// Resolve a command name the way `execvp` does
+pub fn resolve_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(PathBuf::from(program));
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+// ============================================================================
+// Selection
+// ============================================================================
+
+/// The inception layer build to inject for `program`.
+///
+/// `default` is used if it contains the architecture the program will run
+/// as (the host arch if the program has it, as with universal binaries,
+/// otherwise its first). Failing that, the per-arch variant next to it
+/// (`<dir>/<arch>/<lib>`) is used. If neither matches, this warns and
+/// returns `default`.
+pub fn select_library(default: &Path, program: &str) -> PathBuf {
+    let Some(program_arches) = resolve_program(program)
+        .and_then(|p| file_arches(&p).ok())
+        .filter(|arches| !arches.is_empty())
+    else {
+        return default.to_path_buf();
+    };
+    let host = Arch::host();
+    let wanted = if program_arches.contains(&host) {
+        host
+    } else {
+        program_arches[0]
+    };
+
+    let covers = |lib: &Path| {
+        file_arches(lib)
+            .map(|arches| arches.contains(&wanted))
+            .unwrap_or(false)
+    };
+    if covers(default) {
+        return default.to_path_buf();
+    }
+    if let Some(dir) = default.parent() {
+        let variant = dir.join(wanted.name()).join(LIB_NAME);
+        if covers(&variant) {
+            return variant;
+        }
+    }
+    eprintln!(
+        "⚠️  {} runs as {} but {} has no {} build; it will run without the VFS.",
+        program,
+        wanted.name(),
+        default.display(),
+        wanted.name()
+    );
+    eprintln!("   Build one with: vrift shim build --target <triple>");
+    default.to_path_buf()
+}
+
+/// `program` with the same inception environment `vrift shell` sets up,
+/// in the session `conn` belongs to
+pub fn shimmed_command(
+    program: &str,
+    root: &Path,
+    cfg: &vrift_config::Config,
+    conn: &crate::daemon::DaemonConnection,
+) -> Result<std::process::Command> {
+    let shim_path = select_library(&crate::inception::find_inception_library(root)?, program);
+
+    let mut cmd = Command::new(program);
+    for (key, value) in cfg.shim_env() {
+        cmd.env(key, value);
+    }
+    if let Some((key, value)) = crate::cas::tiers_env(&cfg.storage.the_source) {
+        cmd.env(key, value);
+    }
+
+    // Nested runs share the outer session
+    let session_id = std::env::var(vrift_config::path::SESSION_ID_ENV)
+        .ok()
+        .or_else(|| {
+            Some(conn.session_id.clone()).filter(|id| vrift_config::path::is_valid_session_id(id))
+        })
+        .unwrap_or_else(vrift_config::path::new_session_id);
+    cmd.env(vrift_config::path::SESSION_ID_ENV, &session_id);
+    cmd.env("VRIFT_VDIRD_SOCKET", &conn.vdird_socket);
+    if !conn.vdir_mmap_path.is_empty() {
+        cmd.env("VRIFT_VDIR_MMAP", &conn.vdir_mmap_path);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        cmd.env("DYLD_INSERT_LIBRARIES", &shim_path);
+        cmd.env("DYLD_FORCE_FLAT_NAMESPACE", "1");
+    }
+    #[cfg(target_os = "linux")]
+    {
+        cmd.env("LD_PRELOAD", &shim_path);
+    }
+    Ok(cmd)
+}
+
+// ============================================================================
+// Build & packaging
+// ============================================================================
+
+fn default_targets() -> Vec<String> {
+    if cfg!(target_os = "macos") {
+        vec![
+            "aarch64-apple-darwin".to_string(),
+            "x86_64-apple-darwin".to_string(),
+        ]
+    } else {
+        let arch = Arch::host().name();
+        vec![format!("{}-unknown-linux-gnu", arch)]
+    }
+}
+
+fn build(args: BuildArgs) -> Result<()> {
+    let workspace = match args.workspace {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let out = args.out.unwrap_or_else(|| PathBuf::from(".vrift"));
+    let targets = if args.targets.is_empty() {
+        default_targets()
+    } else {
+        args.targets
+    };
+    let apple = targets.iter().filter(|t| t.contains("-apple-")).count();
+    if apple != 0 && apple != targets.len() {
+        anyhow::bail!("Cannot package Apple and non-Apple targets together");
+    }
+    let lib_name = if apple > 0 {
+        "libvrift_inception_layer.dylib"
+    } else {
+        "libvrift_inception_layer.so"
+    };
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| workspace.join("target"));
+    let profile = if args.release { "release" } else { "debug" };
+
+    let mut built = Vec::new();
+    for triple in &targets {
+        let arch = Arch::from_target(triple)
+            .with_context(|| format!("Unsupported target architecture: {}", triple))?;
+        println!("🔨 Building inception layer for {}", triple);
+        let mut cargo = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+        cargo.current_dir(&workspace).args([
+            "build",
+            "-p",
+            "vrift-inception-layer",
+            "--target",
+            triple,
+        ]);
+        if args.release {
+            cargo.arg("--release");
+        }
+        let status = cargo.status().context("Failed to run cargo")?;
+        if !status.success() {
+            anyhow::bail!("cargo build failed for {}", triple);
+        }
+
+        let artifact = target_dir.join(triple).join(profile).join(lib_name);
+        let variant = out.join(arch.name()).join(lib_name);
+        fs::create_dir_all(variant.parent().unwrap())?;
+        fs::copy(&artifact, &variant)
+            .with_context(|| format!("Failed to copy {}", artifact.display()))?;
+        built.push((arch, variant));
+    }
+
+    let top = out.join(lib_name);
+    if apple > 0 && built.len() > 1 {
+        let slices = built
+            .iter()
+            .map(|(_, path)| fs::read(path))
+            .collect::<io::Result<Vec<_>>>()?;
+        fs::write(&top, write_fat(&slices)?)?;
+        println!("📦 Universal: {}", top.display());
+    } else {
+        let (_, primary) = built
+            .iter()
+            .find(|(arch, _)| *arch == Arch::host())
+            .unwrap_or(&built[0]);
+        fs::copy(primary, &top)?;
+        println!("📦 Default:   {}", top.display());
+    }
+    for (arch, path) in &built {
+        println!("   {:<8} {}", arch.name(), path.display());
+    }
+    Ok(())
+}
+
+/// Combine thin Mach-O images into one fat (universal) image, as `lipo
+/// -create` does
+pub fn write_fat(slices: &[Vec<u8>]) -> io::Result<Vec<u8>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let align = 1usize << FAT_ALIGN_LOG2;
+    let header_len = 8 + 20 * slices.len();
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&FAT_MAGIC.to_be_bytes());
+    out.extend_from_slice(&(slices.len() as u32).to_be_bytes());
+    let mut offset = header_len.next_multiple_of(align);
+    for slice in slices {
+        let le_at = |at: usize| {
+            slice
+                .get(at..at + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        };
+        if !matches!(le_at(0), Some(MH_MAGIC_64) | Some(MH_MAGIC)) {
+            return Err(invalid("not a thin Mach-O image"));
+        }
+        let (Some(cputype), Some(subtype)) = (le_at(4), le_at(8)) else {
+            return Err(invalid("truncated Mach-O header"));
+        };
+        let size = u32::try_from(slice.len()).map_err(|_| invalid("slice too large"))?;
+        out.extend_from_slice(&cputype.to_be_bytes());
+        out.extend_from_slice(&subtype.to_be_bytes());
+        let at = u32::try_from(offset).map_err(|_| invalid("fat image too large"))?;
+        out.extend_from_slice(&at.to_be_bytes());
+        out.extend_from_slice(&size.to_be_bytes());
+        out.extend_from_slice(&FAT_ALIGN_LOG2.to_be_bytes());
+        offset = (offset + slice.len()).next_multiple_of(align);
+    }
+    for slice in slices {
+        out.resize(out.len().next_multiple_of(align), 0);
+        out.extend_from_slice(slice);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn elf(machine: u16) -> Vec<u8> {
+        let mut data = vec![0u8; 64];
+        data[..4].copy_from_slice(b"\x7fELF");
+        data[4] = 2;
+        data[5] = 1;
+        data[18..20].copy_from_slice(&machine.to_le_bytes());
+        data
+    }
+
+    fn macho(cputype: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 64];
+        data[..4].copy_from_slice(&MH_MAGIC_64.to_le_bytes());
+        data[4..8].copy_from_slice(&cputype.to_le_bytes());
+        data[8..12].copy_from_slice(&3u32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_binary_arches() {
+        assert_eq!(binary_arches(&elf(62)), [Arch::X86_64]);
+        assert_eq!(binary_arches(&elf(183)), [Arch::Aarch64]);
+        assert_eq!(binary_arches(&macho(0x0100_000c)), [Arch::Aarch64]);
+        assert!(binary_arches(b"plain text").is_empty());
+
+        let fat = write_fat(&[macho(0x0100_0007), macho(0x0100_000c)]).unwrap();
+        assert_eq!(binary_arches(&fat), [Arch::X86_64, Arch::Aarch64]);
+        // Slices land on aligned offsets, intact
+        let second = u32::from_be_bytes(fat[36..40].try_into().unwrap()) as usize;
+        assert_eq!(second % (1 << FAT_ALIGN_LOG2), 0);
+        assert_eq!(&fat[second..second + 64], &macho(0x0100_000c)[..]);
+        assert!(write_fat(&[elf(62)]).is_err());
+
+        assert_eq!(Arch::from_target("x86_64-apple-darwin"), Some(Arch::X86_64));
+        assert_eq!(
+            Arch::from_target("armv7-unknown-linux-gnueabihf"),
+            Some(Arch::Arm)
+        );
+    }
+
+    #[test]
+    fn test_file_arches_follows_shebang() {
+        let temp = TempDir::new().unwrap();
+        let interp = temp.path().join("interp");
+        fs::write(&interp, elf(183)).unwrap();
+        let script = temp.path().join("script.sh");
+        fs::write(&script, format!("#!{} -e\necho hi\n", interp.display())).unwrap();
+        assert_eq!(file_arches(&script).unwrap(), [Arch::Aarch64]);
+    }
+
+    #[test]
+    fn test_select_library_prefers_matching_variant() {
+        let temp = TempDir::new().unwrap();
+        let other = if Arch::host() == Arch::Aarch64 {
+            Arch::X86_64
+        } else {
+            Arch::Aarch64
+        };
+        let machine = |arch: Arch| if arch == Arch::Aarch64 { 183 } else { 62 };
+
+        let default = temp.path().join(LIB_NAME);
+        fs::write(&default, elf(machine(Arch::host()))).unwrap();
+        let variant = temp.path().join(other.name()).join(LIB_NAME);
+        fs::create_dir_all(variant.parent().unwrap()).unwrap();
+        fs::write(&variant, elf(machine(other))).unwrap();
+
+        let native = temp.path().join("native");
+        fs::write(&native, elf(machine(Arch::host()))).unwrap();
+        let foreign = temp.path().join("foreign");
+        fs::write(&foreign, elf(machine(other))).unwrap();
+
+        assert_eq!(select_library(&default, native.to_str().unwrap()), default);
+        assert_eq!(select_library(&default, foreign.to_str().unwrap()), variant);
+        // Unknown formats keep the default
+        let script = temp.path().join("data.bin");
+        fs::write(&script, b"not a binary").unwrap();
+        assert_eq!(select_library(&default, script.to_str().unwrap()), default);
+    }
+
+    #[test]
+    fn test_write_view() {
+        let temp = TempDir::new().unwrap();
+        let mut manifest = vrift_manifest::Manifest::new();
+        manifest.insert("/src", VnodeEntry::new_directory(10, 0o755));
+        manifest.insert("/src/main.rs", VnodeEntry::new_file([7; 32], 5, 20, 0o644));
+        manifest.insert("/old.rs", VnodeEntry::new_whiteout(30));
+        manifest.insert("/link", VnodeEntry::new_symlink([8; 32], 7, 40));
+        let manifest_path = temp.path().join("app.manifest");
+        manifest.save(&manifest_path).unwrap();
+
+        let out = temp.path().join("app.view");
+        write_view(&manifest_path, Some(&out)).unwrap();
+        let view = view::View::load(&out).unwrap();
+        assert_eq!(view.len(), 4);
+        assert!(view.is_dir("/SRC"));
+        let main = view.get("/src/main.rs").unwrap();
+        assert_eq!(
+            (main.kind, main.size, main.hash),
+            (view::Kind::File, 5, Some([7; 32]))
+        );
+        let old = view.get("/old.rs").unwrap();
+        assert_eq!((old.kind, old.hash), (view::Kind::Whiteout, None));
+        assert_eq!(view.get("/link").unwrap().kind, view::Kind::Symlink);
+    }
+}
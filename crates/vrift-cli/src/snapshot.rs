@@ -0,0 +1,168 @@
+//! # Manifest Snapshots
+//!
+//! `vrift snapshot create|list|restore|unprotect` drive vdir_d's snapshot
+//! requests. A snapshot is an immutable, content-addressed copy of the
+//! project's manifest; restoring one swaps it back in atomically, which
+//! undoes a bad write-back session without a re-ingest. vdir_d snapshots
+//! the state it replaces, so a restore can itself be undone.
+//!
+//! `create --protect` marks a release: vdir_d then refuses every change to
+//! the snapshot's entries and locks the CAS blobs they reference, until
+//! `unprotect --force` lifts it.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::Subcommand;
+use vrift_ipc::client::DaemonClient;
+use vrift_ipc::SnapshotInfo;
+
+use crate::daemon::DaemonContext;
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotCommands {
+    /// Snapshot the project's current manifest
+    Create {
+        /// Description stored with the snapshot
+        #[arg(short, long, default_value = "")]
+        message: String,
+
+        /// Make the snapshot's entries and blobs immutable
+        #[arg(long)]
+        protect: bool,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+    },
+    /// List snapshots, oldest first
+    List {
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+    },
+    /// Roll the manifest back to a snapshot
+    Restore {
+        /// Snapshot id or a unique prefix of it
+        id: String,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+    },
+    /// Allow changes to a protected snapshot's entries again
+    Unprotect {
+        /// Snapshot id or a unique prefix of it
+        id: String,
+
+        /// Required: later sessions may then rewrite the released content
+        #[arg(long)]
+        force: bool,
+
+        /// Project directory (default: current directory)
+        #[arg(short, long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+    },
+}
+
+pub async fn run(command: SnapshotCommands) -> Result<()> {
+    let cwd = || std::env::current_dir().unwrap();
+    match command {
+        SnapshotCommands::Create {
+            message,
+            protect,
+            directory,
+        } => {
+            let mut client = connect(&directory.unwrap_or_else(cwd)).await?;
+            let snapshot = client
+                .snapshot_create(&message, protect)
+                .await
+                .daemon_context("Snapshot failed")?;
+            println!(
+                "📸 Snapshot {} ({} entries{})",
+                short_id(&snapshot),
+                snapshot.entries,
+                if snapshot.protected {
+                    ", protected"
+                } else {
+                    ""
+                }
+            );
+        }
+        SnapshotCommands::List { directory } => {
+            let mut client = connect(&directory.unwrap_or_else(cwd)).await?;
+            let snapshots = client
+                .snapshot_list()
+                .await
+                .daemon_context("Listing snapshots failed")?;
+            if snapshots.is_empty() {
+                println!("No snapshots yet. Take one with 'vrift snapshot create'.");
+            }
+            for snapshot in &snapshots {
+                println!(
+                    "{} {} {:>18}  {:>8} entries  {}",
+                    short_id(snapshot),
+                    if snapshot.protected { "🔒" } else { "  " },
+                    crate::format_timestamp(snapshot.created_secs),
+                    snapshot.entries,
+                    snapshot.label
+                );
+            }
+        }
+        SnapshotCommands::Restore { id, directory } => {
+            let mut client = connect(&directory.unwrap_or_else(cwd)).await?;
+            let (restored, previous) = client
+                .snapshot_restore(&id)
+                .await
+                .daemon_context("Restore failed")?;
+            println!(
+                "⏪ Restored {} ({} entries)",
+                short_id(&restored),
+                restored.entries
+            );
+            println!(
+                "   Previous manifest saved as {}; 'vrift snapshot restore {}' undoes this.",
+                short_id(&previous),
+                short_id(&previous)
+            );
+        }
+        SnapshotCommands::Unprotect {
+            id,
+            force,
+            directory,
+        } => {
+            if !force {
+                anyhow::bail!(
+                    "Unprotecting lets later sessions rewrite snapshot {}'s entries; \
+                     pass --force to confirm",
+                    id
+                );
+            }
+            let mut client = connect(&directory.unwrap_or_else(cwd)).await?;
+            let snapshot = client
+                .snapshot_unprotect(&id, true)
+                .await
+                .daemon_context("Unprotect failed")?;
+            println!("🔓 Snapshot {} is no longer protected", short_id(&snapshot));
+        }
+    }
+    Ok(())
+}
+
+fn short_id(snapshot: &SnapshotInfo) -> &str {
+    &snapshot.id[..snapshot.id.len().min(12)]
+}
+
+/// Snapshots live in vdir_d, which must be running for the project
+async fn connect(directory: &Path) -> Result<DaemonClient> {
+    let project_id = vrift_config::path::compute_project_id(directory);
+    let socket = vrift_config::path::get_vdird_socket_path(&project_id)
+        .filter(|s| vrift_ipc::probe_socket(s, vrift_ipc::PROBE_TIMEOUT))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "vdir_d is not running for {}. Start the daemon first.",
+                directory.display()
+            )
+        })?;
+    Ok(DaemonClient::connect_path(&socket).await?)
+}
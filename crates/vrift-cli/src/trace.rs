@@ -0,0 +1,202 @@
+//! # Syscall Coverage
+//!
+//! `vrift trace coverage -- <command>` runs the command through `vrift run`
+//! with eBPF tracepoints attached (see `vrift_trace::coverage`), and lists
+//! the filesystem syscalls that reached the kernel on paths the manifest
+//! serves. Each one went past the inception layer: a missing interposer
+//! (`statx`, `getdents64`, a raw syscall in a static binary) or io_uring.
+//!
+//! Linux only; needs root or `CAP_BPF` + `CAP_PERFMON`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::Subcommand;
+
+#[derive(Subcommand, Debug)]
+pub enum TraceCommands {
+    /// Run a command under the inception layer and list syscalls on VFS paths it missed
+    Coverage {
+        /// Manifest file to use
+        #[arg(short, long, default_value = "vrift.manifest")]
+        manifest: PathBuf,
+
+        /// Number of paths to show
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+
+        /// Command to execute
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        command: Vec<String>,
+    },
+}
+
+pub fn run(command: TraceCommands, cas_root: &Path) -> Result<()> {
+    match command {
+        TraceCommands::Coverage {
+            manifest,
+            top,
+            command,
+        } => coverage(&manifest, top, &command, cas_root),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn coverage(manifest_path: &Path, top: usize, command: &[String], cas_root: &Path) -> Result<()> {
+    use anyhow::Context;
+
+    let manifest = crate::pack::load_manifest(manifest_path)?;
+    let root = match std::env::var_os("VRIFT_PROJECT_ROOT") {
+        Some(root) => PathBuf::from(root),
+        None => std::env::current_dir().context("Failed to get current directory")?,
+    };
+
+    let exe = std::env::current_exe().context("Failed to locate vrift binary")?;
+    let mut cmd = std::process::Command::new(exe);
+    cmd.arg("--the-source-root")
+        .arg(cas_root)
+        .arg("run")
+        .arg("--manifest")
+        .arg(manifest_path)
+        .arg("--")
+        .args(command);
+    let (code, report) = vrift_trace::coverage::record(&manifest, &root, &mut cmd, false)?;
+
+    println!();
+    print!("{}", render(&report, top));
+    if code != 0 {
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn coverage(_manifest: &Path, _top: usize, _command: &[String], _cas_root: &Path) -> Result<()> {
+    anyhow::bail!("vrift trace coverage needs eBPF (Linux only)")
+}
+
+#[cfg(target_os = "linux")]
+fn render(report: &vrift_trace::coverage::Coverage, top: usize) -> String {
+    use crate::format_number;
+    use std::fmt::Write;
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "Syscall coverage: {} watched syscalls, {} reached the kernel on VFS paths",
+        format_number(report.syscalls_seen),
+        format_number(report.total())
+    );
+    if report.gaps.is_empty() {
+        let _ = writeln!(
+            out,
+            "No gaps: every watched call on a VFS path went through the inception layer."
+        );
+    } else {
+        let mut rows: Vec<_> = report.gaps.iter().collect();
+        rows.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then(a.0.cmp(b.0)));
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("SYSCALL".len());
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "  {:<width$}  {:>10}  {:>10}  EXAMPLES",
+            "SYSCALL", "CALLS", "VIRTUAL"
+        );
+        for (name, gaps) in rows {
+            let _ = writeln!(
+                out,
+                "  {:<width$}  {:>10}  {:>10}  {}",
+                name,
+                format_number(gaps.calls),
+                format_number(gaps.virtual_only),
+                gaps.examples.join(", ")
+            );
+        }
+
+        let mut paths: Vec<_> = report.paths.iter().collect();
+        paths.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let width = paths
+            .iter()
+            .take(top)
+            .map(|(p, _)| p.len())
+            .max()
+            .unwrap_or(0)
+            .max("PATH".len());
+        let _ = writeln!(out);
+        let _ = writeln!(out, "  {:<width$}  {:>10}", "PATH", "CALLS");
+        for (path, calls) in paths.iter().take(top) {
+            let _ = writeln!(out, "  {:<width$}  {:>10}", path, format_number(**calls));
+        }
+        if paths.len() > top {
+            let _ = writeln!(out, "  ... {} more paths", paths.len() - top);
+        }
+    }
+    if report.io_uring > 0 {
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "io_uring: {} setup/enter calls; the paths they carry aren't visible here or to the inception layer.",
+            format_number(report.io_uring)
+        );
+    }
+    if report.unresolved > 0 {
+        let _ = writeln!(
+            out,
+            "{} fd-based calls couldn't be resolved (the process or fd was gone).",
+            format_number(report.unresolved)
+        );
+    }
+    out
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use vrift_trace::coverage::{Coverage, SyscallGaps};
+
+    #[test]
+    fn test_render_sorted_and_truncated() {
+        let mut report = Coverage {
+            syscalls_seen: 1500,
+            io_uring: 2,
+            ..Coverage::default()
+        };
+        report.gaps.insert(
+            "getdents64",
+            SyscallGaps {
+                calls: 1,
+                virtual_only: 0,
+                examples: vec!["/src".into()],
+            },
+        );
+        report.gaps.insert(
+            "statx",
+            SyscallGaps {
+                calls: 7,
+                virtual_only: 7,
+                examples: vec!["/src/a.rs".into(), "/src/b.rs".into()],
+            },
+        );
+        report.paths.insert("/src/a.rs".into(), 5);
+        report.paths.insert("/src/b.rs".into(), 2);
+        report.paths.insert("/src".into(), 1);
+
+        let out = render(&report, 2);
+        assert!(out.contains("1,500 watched syscalls, 8 reached"), "{}", out);
+        assert!(out.find("statx").unwrap() < out.find("getdents64").unwrap());
+        assert!(out.contains("/src/a.rs, /src/b.rs"));
+        assert!(out.contains("... 1 more paths"));
+        assert!(out.contains("io_uring: 2"));
+    }
+
+    #[test]
+    fn test_render_no_gaps() {
+        let out = render(&Coverage::default(), 20);
+        assert!(out.contains("No gaps"));
+    }
+}
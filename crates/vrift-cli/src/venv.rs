@@ -0,0 +1,425 @@
+//! # Python Virtualenv Profile
+//!
+//! `vrift ingest <venv> --profile python-venv` snapshots a virtualenv so it
+//! can be used from a different path or machine:
+//!
+//! - `__pycache__` directories and stray `.pyc` files are not ingested.
+//!   Python regenerates them, and they differ between interpreters.
+//! - Console scripts in `bin/` hard-code the interpreter path the venv was
+//!   created at. Their shebangs are rewritten in the manifest (the files on
+//!   disk are untouched) to find `python` next to the script at run time,
+//!   the same trampoline `uv venv --relocatable` writes.
+//! - `bin/activate` hard-codes the venv path too, so `bin/activate-vrift`
+//!   is added, which works out `VIRTUAL_ENV` from its own location.
+//! - `vrift-venv.json` records the interpreter, the site-packages
+//!   directories and the distributions installed in them.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use vrift_cas::CasStore;
+use vrift_manifest::lmdb::AssetTier;
+use vrift_manifest::{LmdbManifest, VnodeEntry};
+
+/// Ignore patterns for a venv, on top of the configured ones
+pub const IGNORE_PATTERNS: &[&str] = &["__pycache__/", "*.py[co]"];
+
+/// Layout record, stored as `vrift-venv.json` at the venv root
+pub const LAYOUT_FILE: &str = "vrift-venv.json";
+
+/// Activation helper, stored as `bin/activate-vrift`
+pub const ACTIVATE_FILE: &str = "activate-vrift";
+
+const ACTIVATE_SCRIPT: &str = r#"# Source from bash or zsh: . <venv>/bin/activate-vrift
+# Written by `vrift ingest --profile python-venv`. Unlike bin/activate, it
+# works out the venv from its own location, so it still works after the venv
+# has been moved.
+
+if [ -n "${BASH_SOURCE:-}" ]; then
+    _vrift_self="${BASH_SOURCE[0]}"
+elif [ -n "${ZSH_VERSION:-}" ]; then
+    _vrift_self="${(%):-%x}"
+else
+    _vrift_self="$0"
+fi
+
+if command -v deactivate >/dev/null 2>&1; then
+    deactivate
+fi
+
+VIRTUAL_ENV="$(cd -- "$(dirname -- "$_vrift_self")/.." && pwd)"
+export VIRTUAL_ENV
+unset _vrift_self
+
+_OLD_VIRTUAL_PATH="$PATH"
+PATH="$VIRTUAL_ENV/bin:$PATH"
+export PATH
+
+if [ -n "${PYTHONHOME:-}" ]; then
+    _OLD_VIRTUAL_PYTHONHOME="$PYTHONHOME"
+    unset PYTHONHOME
+fi
+
+deactivate() {
+    PATH="$_OLD_VIRTUAL_PATH"
+    export PATH
+    if [ -n "${_OLD_VIRTUAL_PYTHONHOME:-}" ]; then
+        PYTHONHOME="$_OLD_VIRTUAL_PYTHONHOME"
+        export PYTHONHOME
+    fi
+    unset VIRTUAL_ENV _OLD_VIRTUAL_PATH _OLD_VIRTUAL_PYTHONHOME
+    unset -f deactivate
+    hash -r 2>/dev/null
+}
+
+hash -r 2>/dev/null
+"#;
+
+/// An installed distribution, from its `.dist-info` directory name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Distribution {
+    pub name: String,
+    pub version: String,
+}
+
+/// What `vrift-venv.json` records about a venv
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VenvLayout {
+    /// `version` (or `version_info`) from pyvenv.cfg
+    pub python_version: Option<String>,
+    /// `home` from pyvenv.cfg: the base interpreter's directory, which must
+    /// exist wherever the venv is used
+    pub python_home: Option<String>,
+    /// Where the venv was created, as hard-coded in its scripts
+    pub created_at: Option<String>,
+    /// site-packages directories, relative to the venv root
+    pub site_packages: Vec<String>,
+    pub distributions: Vec<Distribution>,
+}
+
+impl VenvLayout {
+    /// Read the layout of the venv at `root`
+    pub fn detect(root: &Path) -> Result<Self> {
+        let cfg_path = root.join("pyvenv.cfg");
+        let cfg = fs::read_to_string(&cfg_path).with_context(|| {
+            format!(
+                "{} is not a virtualenv (no readable pyvenv.cfg)",
+                root.display()
+            )
+        })?;
+        let value = |key: &str| {
+            cfg.lines().find_map(|line| {
+                let (k, v) = line.split_once('=')?;
+                (k.trim() == key).then(|| v.trim().to_string())
+            })
+        };
+
+        let mut layout = VenvLayout {
+            python_version: value("version").or_else(|| value("version_info")),
+            python_home: value("home"),
+            created_at: activate_venv_path(root),
+            ..Default::default()
+        };
+
+        let mut distributions = BTreeSet::new();
+        for site in site_packages_dirs(root) {
+            for entry in fs::read_dir(root.join(&site))?.flatten() {
+                let name = entry.file_name();
+                let Some(stem) = name.to_str().and_then(|n| n.strip_suffix(".dist-info")) else {
+                    continue;
+                };
+                // Names in dist-info directories are escaped, so the last
+                // dash separates name and version
+                if let Some((name, version)) = stem.rsplit_once('-') {
+                    distributions.insert(Distribution {
+                        name: name.to_string(),
+                        version: version.to_string(),
+                    });
+                }
+            }
+            layout.site_packages.push(site);
+        }
+        layout.distributions = distributions.into_iter().collect();
+        Ok(layout)
+    }
+
+    /// One line for the ingest report
+    pub fn describe(&self) -> String {
+        format!(
+            "Python {}, {} distributions in {}",
+            self.python_version.as_deref().unwrap_or("(unknown)"),
+            self.distributions.len(),
+            match self.site_packages.as_slice() {
+                [] => "no site-packages".to_string(),
+                dirs => dirs.join(", "),
+            }
+        )
+    }
+
+    /// Venv paths that may appear in its scripts' shebangs
+    fn venv_paths(&self, root: &Path) -> Vec<String> {
+        let mut paths: Vec<String> = [
+            Some(root.to_path_buf()),
+            std::path::absolute(root).ok(),
+            root.canonicalize().ok(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|p| p.to_string_lossy().trim_end_matches('/').to_string())
+        .chain(self.created_at.clone())
+        .filter(|p| p.starts_with('/'))
+        .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+/// `lib/pythonX.Y/site-packages` (and `lib64`, unless it's a symlink to lib)
+fn site_packages_dirs(root: &Path) -> Vec<String> {
+    let mut dirs = Vec::new();
+    for lib in ["lib", "lib64"] {
+        let lib_dir = root.join(lib);
+        if lib == "lib64" && lib_dir.is_symlink() {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&lib_dir) else {
+            continue;
+        };
+        let mut found: Vec<String> = entries
+            .flatten()
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter(|name| name.starts_with("python"))
+            .map(|name| format!("{}/{}/site-packages", lib, name))
+            .filter(|rel| root.join(rel).is_dir())
+            .collect();
+        found.sort();
+        dirs.extend(found);
+    }
+    dirs
+}
+
+/// The venv path `bin/activate` was written with
+fn activate_venv_path(root: &Path) -> Option<String> {
+    let activate = fs::read_to_string(root.join("bin/activate")).ok()?;
+    activate.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("VIRTUAL_ENV=")?;
+        let value = value.trim_matches(|c| c == '\'' || c == '"');
+        value.starts_with('/').then(|| value.to_string())
+    })
+}
+
+/// `script` with its shebang made relocatable, if it runs an interpreter
+/// in `<venv>/bin` for one of `venv_paths`
+pub fn relocate_shebang(script: &[u8], venv_paths: &[String]) -> Option<Vec<u8>> {
+    let rest = script.strip_prefix(b"#!")?;
+    let end = rest.iter().position(|&b| b == b'\n')?;
+    let line = std::str::from_utf8(&rest[..end])
+        .ok()?
+        .trim_end_matches('\r');
+    let (interpreter, args) = match line.trim().split_once(char::is_whitespace) {
+        Some((interpreter, args)) => (interpreter, args.trim()),
+        None => (line.trim(), ""),
+    };
+    let name = venv_paths.iter().find_map(|venv| {
+        interpreter
+            .strip_prefix(venv.as_str())?
+            .strip_prefix("/bin/")
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+    })?;
+
+    let args = if args.is_empty() {
+        String::new()
+    } else {
+        format!(" '{}'", args.replace('\'', r"'\''"))
+    };
+    // Valid as both sh and Python: sh execs the interpreter next to the
+    // script, which then reads the first two lines as a string literal
+    let mut out = format!(
+        "#!/bin/sh\n'''exec' \"$(dirname -- \"$(realpath -- \"$0\")\")\"/'{}'{} \"$0\" \"$@\"\n' '''\n",
+        name, args
+    )
+    .into_bytes();
+    out.extend_from_slice(&rest[end + 1..]);
+    Some(out)
+}
+
+/// What [`apply`] changed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VenvSummary {
+    pub scripts_relocated: usize,
+}
+
+/// Rewrite shebangs and add the activation helper and layout record in the
+/// manifest ingested from the venv at `root`, keys under `prefix`
+pub fn apply(
+    layout: &VenvLayout,
+    root: &Path,
+    manifest_path: &Path,
+    cas: &CasStore,
+    prefix: &str,
+) -> Result<VenvSummary> {
+    let manifest = LmdbManifest::open(manifest_path)
+        .with_context(|| format!("Failed to open manifest: {}", manifest_path.display()))?;
+    let prefix = prefix.trim_end_matches('/');
+    let bin = format!("{}/bin/", prefix);
+    let venv_paths = layout.venv_paths(root);
+    // Added files take pyvenv.cfg's mtime, so re-ingesting the same venv
+    // gives the same manifest
+    let mtime = fs::metadata(root.join("pyvenv.cfg"))
+        .map(|m| m.mtime().max(0) as u64)
+        .unwrap_or(0);
+
+    let mut summary = VenvSummary::default();
+    for (key, entry) in manifest.iter()? {
+        let Some(name) = key.strip_prefix(&bin) else {
+            continue;
+        };
+        if name.contains('/') || !entry.vnode.is_file() || entry.vnode.size > 1 << 20 {
+            continue;
+        }
+        let script = cas.get(&entry.vnode.content_hash)?;
+        if let Some(relocated) = relocate_shebang(&script, &venv_paths) {
+            let vnode = VnodeEntry::new_file(
+                cas.store(&relocated)?,
+                relocated.len() as u64,
+                entry.vnode.mtime,
+                entry.vnode.mode,
+            );
+            manifest.insert(&key, vnode, entry.tier);
+            summary.scripts_relocated += 1;
+        }
+    }
+
+    let add = |key: String, data: &[u8], mode: u32| -> Result<()> {
+        let vnode = VnodeEntry::new_file(cas.store(data)?, data.len() as u64, mtime, mode);
+        manifest.insert(&key, vnode, AssetTier::Tier2Mutable);
+        Ok(())
+    };
+    add(
+        format!("{}{}", bin, ACTIVATE_FILE),
+        ACTIVATE_SCRIPT.as_bytes(),
+        0o644,
+    )?;
+    let mut record = serde_json::to_vec_pretty(layout)?;
+    record.push(b'\n');
+    add(format!("{}/{}", prefix, LAYOUT_FILE), &record, 0o644)?;
+
+    manifest.commit()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relocate_shebang() {
+        let venvs = vec!["/home/ci/app/.venv".to_string()];
+        let script = b"#!/home/ci/app/.venv/bin/python3\nimport sys\nfrom numpy import f\n";
+        let out = relocate_shebang(script, &venvs).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("#!/bin/sh\n'''exec' "));
+        assert!(out.contains("/'python3' \"$0\" \"$@\"\n' '''\nimport sys\n"));
+
+        // Interpreter flags survive, quoted
+        let out = relocate_shebang(b"#!/home/ci/app/.venv/bin/python -E\nx\n", &venvs).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("/'python' '-E' \"$0\""));
+
+        // Scripts for other interpreters are left alone
+        assert!(relocate_shebang(b"#!/usr/bin/env python3\n", &venvs).is_none());
+        assert!(relocate_shebang(b"#!/home/ci/app/.venv2/bin/python\n", &venvs).is_none());
+        assert!(relocate_shebang(b"\x7fELF", &venvs).is_none());
+    }
+
+    #[test]
+    fn test_detect_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(
+            root.join("pyvenv.cfg"),
+            "home = /usr/bin\ninclude-system-site-packages = false\nversion = 3.11.6\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(
+            root.join("bin/activate"),
+            "deactivate () {\n}\nVIRTUAL_ENV='/build/venv'\nexport VIRTUAL_ENV\n",
+        )
+        .unwrap();
+        let site = root.join("lib/python3.11/site-packages");
+        fs::create_dir_all(site.join("numpy-1.26.0.dist-info")).unwrap();
+        fs::create_dir_all(site.join("typing_extensions-4.9.0.dist-info")).unwrap();
+        fs::create_dir_all(site.join("numpy")).unwrap();
+
+        let layout = VenvLayout::detect(root).unwrap();
+        assert_eq!(layout.python_version.as_deref(), Some("3.11.6"));
+        assert_eq!(layout.python_home.as_deref(), Some("/usr/bin"));
+        assert_eq!(layout.created_at.as_deref(), Some("/build/venv"));
+        assert_eq!(layout.site_packages, vec!["lib/python3.11/site-packages"]);
+        assert_eq!(
+            layout.distributions,
+            vec![
+                Distribution {
+                    name: "numpy".into(),
+                    version: "1.26.0".into()
+                },
+                Distribution {
+                    name: "typing_extensions".into(),
+                    version: "4.9.0".into()
+                },
+            ]
+        );
+        assert!(layout.venv_paths(root).contains(&"/build/venv".to_string()));
+
+        assert!(VenvLayout::detect(&root.join("bin")).is_err());
+    }
+
+    #[test]
+    fn test_apply_rewrites_manifest_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("venv");
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(
+            root.join("pyvenv.cfg"),
+            "home = /usr/bin\nversion = 3.12.1\n",
+        )
+        .unwrap();
+        let venv = root.canonicalize().unwrap();
+        let pip = format!("#!{}/bin/python\nimport pip\n", venv.display());
+        fs::write(root.join("bin/pip"), &pip).unwrap();
+
+        let cas = CasStore::new(dir.path().join("cas")).unwrap();
+        let manifest_path = dir.path().join("manifest.lmdb");
+        let manifest = LmdbManifest::open(&manifest_path).unwrap();
+        let original = cas.store(pip.as_bytes()).unwrap();
+        let vnode = VnodeEntry::new_file(original, pip.len() as u64, 7, 0o755);
+        manifest.insert("/bin/pip", vnode, AssetTier::Tier2Mutable);
+        manifest.commit().unwrap();
+        drop(manifest);
+
+        let layout = VenvLayout::detect(&root).unwrap();
+        let summary = apply(&layout, &root, &manifest_path, &cas, "").unwrap();
+        assert_eq!(summary.scripts_relocated, 1);
+
+        let manifest = LmdbManifest::open(&manifest_path).unwrap();
+        let pip_entry = manifest.get("/bin/pip").unwrap().unwrap().vnode;
+        let script = cas.get(&pip_entry.content_hash).unwrap();
+        assert!(script.starts_with(b"#!/bin/sh\n"));
+        assert!(script.ends_with(b"import pip\n"));
+        assert_eq!((pip_entry.mode, pip_entry.mtime), (0o755, 7));
+        assert_eq!(fs::read_to_string(root.join("bin/pip")).unwrap(), pip);
+
+        assert!(manifest.get("/bin/activate-vrift").unwrap().is_some());
+        let record = manifest.get("/vrift-venv.json").unwrap().unwrap().vnode;
+        let record: VenvLayout =
+            serde_json::from_slice(&cas.get(&record.content_hash).unwrap()).unwrap();
+        assert_eq!(record.python_version.as_deref(), Some("3.12.1"));
+    }
+}
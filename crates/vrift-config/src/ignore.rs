@@ -0,0 +1,257 @@
+//! Ignore rules shared by ingest, the live watcher and the compensation scan
+//!
+//! Loads ignore patterns from the [ingest] config section and matches them
+//! with full gitignore semantics (`**`, anchoring, trailing `/`, `!` negation).
+//!
+//! When a project root is set, `.vriftignore` files in any directory under it
+//! are honoured too. Deeper files take precedence over shallower ones, and all
+//! of them take precedence over the config patterns.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use tracing::warn;
+
+/// Per-directory ignore file name
+pub const VRIFTIGNORE_FILE: &str = ".vriftignore";
+
+/// Ignore pattern matcher
+#[derive(Debug, Clone)]
+pub struct IgnoreMatcher {
+    patterns: Vec<String>,
+    global: Gitignore,
+    root: Option<PathBuf>,
+    /// Parsed .vriftignore per directory (relative to root); None = no file.
+    /// Shared between clones so the watcher and scanner parse each file once.
+    dir_cache: Arc<RwLock<HashMap<PathBuf, Option<Arc<Gitignore>>>>>,
+}
+
+impl Default for IgnoreMatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IgnoreMatcher {
+    /// Create a new matcher with patterns from config
+    pub fn new() -> Self {
+        // Load entirely from config - no hardcoded fallback
+        Self::with_patterns(&crate::config().ingest.ignore_patterns)
+    }
+
+    /// Create a matcher with custom patterns
+    pub fn with_patterns(patterns: &[String]) -> Self {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in patterns {
+            if let Err(e) = builder.add_line(None, pattern) {
+                warn!(pattern = %pattern, error = %e, "Invalid ignore pattern, skipping");
+            }
+        }
+        let global = builder.build().unwrap_or_else(|e| {
+            warn!(error = %e, "Failed to build ignore patterns");
+            Gitignore::empty()
+        });
+        Self {
+            patterns: patterns.to_vec(),
+            global,
+            root: None,
+            dir_cache: Arc::default(),
+        }
+    }
+
+    /// Anchor patterns at `root` and enable per-directory .vriftignore files
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self.dir_cache = Arc::default();
+        self
+    }
+
+    /// Check if a path should be ignored
+    pub fn should_ignore(&self, path: &Path) -> bool {
+        self.matched(path, path.is_dir())
+    }
+
+    /// Check if a path should be ignored when the caller already knows its type
+    pub fn matched(&self, path: &Path, is_dir: bool) -> bool {
+        let rel = self.relative(path);
+        let components: Vec<_> = rel.iter().collect();
+
+        // Walk top-down: an ignored directory hides everything beneath it,
+        // matching git (a file can't be re-included under an excluded dir).
+        let mut prefix = PathBuf::new();
+        for (i, component) in components.iter().enumerate() {
+            prefix.push(component);
+            let last = i + 1 == components.len();
+            match self.decide(&prefix, if last { is_dir } else { true }) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) if last => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Drop the cached .vriftignore for `dir` so it's re-read on next match
+    pub fn invalidate_dir(&self, dir: &Path) {
+        let rel = self.relative(dir);
+        if let Ok(mut cache) = self.dir_cache.write() {
+            cache.remove(&rel);
+        }
+    }
+
+    /// Get the patterns
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Path relative to root with only normal components (no `/`, `.`, `..`)
+    fn relative(&self, path: &Path) -> PathBuf {
+        let rel = self
+            .root
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        rel.components()
+            .filter_map(|c| match c {
+                Component::Normal(s) => Some(s),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Decide a single relative path: deepest .vriftignore first, then config
+    fn decide(&self, rel: &Path, is_dir: bool) -> Match<()> {
+        if let Some(root) = &self.root {
+            let mut dir = rel.parent();
+            while let Some(d) = dir {
+                if let Some(gi) = self.dir_ignore(root, d) {
+                    let stripped = rel.strip_prefix(d).unwrap_or(rel);
+                    match gi.matched(stripped, is_dir) {
+                        Match::None => {}
+                        Match::Ignore(_) => return Match::Ignore(()),
+                        Match::Whitelist(_) => return Match::Whitelist(()),
+                    }
+                }
+                dir = d.parent();
+            }
+        }
+        match self.global.matched(rel, is_dir) {
+            Match::None => Match::None,
+            Match::Ignore(_) => Match::Ignore(()),
+            Match::Whitelist(_) => Match::Whitelist(()),
+        }
+    }
+
+    /// Load (or fetch from cache) the .vriftignore in `root/dir`
+    fn dir_ignore(&self, root: &Path, dir: &Path) -> Option<Arc<Gitignore>> {
+        if let Ok(cache) = self.dir_cache.read() {
+            if let Some(entry) = cache.get(dir) {
+                return entry.clone();
+            }
+        }
+
+        let abs_dir = root.join(dir);
+        let file = abs_dir.join(VRIFTIGNORE_FILE);
+        let loaded = if file.is_file() {
+            let mut builder = GitignoreBuilder::new(&abs_dir);
+            if let Some(e) = builder.add(&file) {
+                warn!(path = %file.display(), error = %e, "Invalid .vriftignore entries");
+            }
+            match builder.build() {
+                Ok(gi) => Some(Arc::new(gi)),
+                Err(e) => {
+                    warn!(path = %file.display(), error = %e, "Failed to load .vriftignore");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Ok(mut cache) = self.dir_cache.write() {
+            cache.insert(dir.to_path_buf(), loaded.clone());
+        }
+        loaded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn matcher(patterns: &[&str]) -> IgnoreMatcher {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        IgnoreMatcher::with_patterns(&patterns)
+    }
+
+    #[test]
+    fn test_ignore_git() {
+        // Use explicit patterns - .git is NOT in code defaults
+        let matcher = IgnoreMatcher::with_patterns(&[".git".to_string()]);
+        assert!(matcher.should_ignore(&PathBuf::from("/project/.git/config")));
+    }
+
+    #[test]
+    fn test_custom_patterns() {
+        let matcher = IgnoreMatcher::with_patterns(&["custom".to_string()]);
+        assert!(matcher.should_ignore(&PathBuf::from("custom/file.txt")));
+        assert!(!matcher.should_ignore(&PathBuf::from("other/file.txt")));
+    }
+
+    #[test]
+    fn test_extension_and_double_star() {
+        let m = matcher(&["*.pyc", "build/**/tmp"]);
+        assert!(m.matched(Path::new("pkg/mod.pyc"), false));
+        assert!(m.matched(Path::new("build/a/b/tmp/x.o"), false));
+        assert!(m.matched(Path::new("build/tmp"), true));
+        assert!(!m.matched(Path::new("src/tmp/x.o"), false));
+        assert!(!m.matched(Path::new("pkg/mod.py"), false));
+    }
+
+    #[test]
+    fn test_negation() {
+        let m = matcher(&["*.log", "!keep.log"]);
+        assert!(m.matched(Path::new("logs/debug.log"), false));
+        assert!(!m.matched(Path::new("logs/keep.log"), false));
+    }
+
+    #[test]
+    fn test_excluded_dir_cannot_be_reincluded() {
+        let m = matcher(&["out/", "!out/keep.txt"]);
+        assert!(m.matched(Path::new("out/keep.txt"), false));
+        // Trailing slash only matches directories
+        assert!(!m.matched(Path::new("out"), false));
+    }
+
+    #[test]
+    fn test_anchored_to_root() {
+        let m = matcher(&["/dist"]).with_root("/project");
+        assert!(m.matched(Path::new("/project/dist/app.js"), false));
+        assert!(!m.matched(Path::new("/project/web/dist/app.js"), false));
+    }
+
+    #[test]
+    fn test_per_directory_vriftignore() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("web/node_modules")).unwrap();
+        std::fs::write(root.join("web/.vriftignore"), "node_modules/\n!*.keep\n").unwrap();
+
+        let m = matcher(&["*.keep"]).with_root(root);
+        assert!(m.should_ignore(&root.join("web/node_modules/x.js")));
+        // Outside web/, the nested file doesn't apply
+        assert!(!m.should_ignore(&root.join("node_modules/x.js")));
+        // Deeper file overrides config patterns
+        assert!(!m.should_ignore(&root.join("web/a.keep")));
+        assert!(m.should_ignore(&root.join("a.keep")));
+
+        std::fs::write(root.join("web/.vriftignore"), "").unwrap();
+        assert!(m.should_ignore(&root.join("web/node_modules/x.js")));
+        m.invalidate_dir(&root.join("web"));
+        assert!(!m.should_ignore(&root.join("web/node_modules/x.js")));
+    }
+}
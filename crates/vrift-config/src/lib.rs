@@ -7,6 +7,7 @@
 //! 2. `.vrift/config.toml` (project-local, overrides global)
 //! 3. Environment variables (highest priority)
 
+pub mod ignore;
 pub mod logging;
 pub mod path;
 pub mod testing;
@@ -68,6 +69,7 @@ pub struct Config {
     pub tiers: TierConfig,
     pub security: SecurityConfig,
     pub daemon: DaemonConfig,
+    pub hydrate: HydrateConfig,
 }
 
 impl Default for Config {
@@ -80,6 +82,7 @@ impl Default for Config {
             tiers: TierConfig::default(),
             security: SecurityConfig::default(),
             daemon: DaemonConfig::default(),
+            hydrate: HydrateConfig::default(),
         }
     }
 }
@@ -175,6 +178,9 @@ impl Config {
         if has_key("project", "manifest") {
             self.project.manifest = other.project.manifest;
         }
+        if has_key("project", "passthrough") {
+            self.project.passthrough = other.project.passthrough;
+        }
 
         // Storage
         if has_key("storage", "the_source") {
@@ -206,6 +212,14 @@ impl Config {
         if has_section("security") && has_key("security", "exclude_patterns") {
             self.security.exclude_patterns = other.security.exclude_patterns;
         }
+
+        // Hydrate
+        if has_key("hydrate", "enabled") {
+            self.hydrate.enabled = other.hydrate.enabled;
+        }
+        if has_key("hydrate", "paths") {
+            self.hydrate.paths = other.hydrate.paths;
+        }
     }
 
     /// Apply environment variable overrides (highest priority)
@@ -220,6 +234,14 @@ impl Config {
         if let Ok(manifest) = std::env::var("VRIFT_MANIFEST") {
             self.project.manifest = PathBuf::from(manifest);
         }
+        if let Ok(list) = std::env::var("VRIFT_PASSTHROUGH") {
+            self.project.passthrough = list
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(String::from)
+                .collect();
+        }
 
         // Storage
         if let Ok(path) = std::env::var("VR_THE_SOURCE") {
@@ -232,6 +254,11 @@ impl Config {
                 self.ingest.threads = Some(n);
             }
         }
+        if let Ok(threshold) = std::env::var("VRIFT_CHUNK_THRESHOLD") {
+            if let Ok(n) = threshold.parse() {
+                self.ingest.chunk_threshold = n;
+            }
+        }
 
         // Daemon
         if let Ok(socket) = std::env::var("VRIFT_SOCKET_PATH") {
@@ -257,6 +284,9 @@ impl Config {
         if let Ok(log) = std::env::var("VRIFT_LOG_DIR") {
             self.daemon.log_dir = PathBuf::from(log);
         }
+        if let Ok(shared) = std::env::var("VRIFT_SHARED_VDIRD") {
+            self.daemon.shared_vdird = matches!(shared.as_str(), "1" | "true");
+        }
     }
 
     /// Derive environment variables for shim-wrapped processes.
@@ -287,6 +317,15 @@ impl Config {
         if self.daemon.debug {
             env.push(("VRIFT_DEBUG".to_string(), "1".to_string()));
         }
+        if let Some(spec) = self.hydrate.shim_spec() {
+            env.push(("VRIFT_HYDRATE".to_string(), spec));
+        }
+        if !self.project.passthrough.is_empty() {
+            env.push((
+                "VRIFT_PASSTHROUGH".to_string(),
+                self.project.passthrough.join(","),
+            ));
+        }
         env
     }
 
@@ -301,6 +340,7 @@ config_version = 1
 [project]
 vfs_prefix = "{vfs_prefix}"
 # manifest = ".vrift/manifest.lmdb"  # relative to project root
+# passthrough = ["target", ".git"]   # always the real files, never virtualized
 
 [storage]
 the_source = "{the_source}"
@@ -309,6 +349,11 @@ the_source = "{the_source}"
 [daemon]
 # socket = "{socket}"
 # debug = false
+# allowed_uids = []  # other users who may connect (own uid and root always can)
+# scrub_interval_hours = 24  # re-hash CAS blobs in the background (0 = off)
+# scrub_mb_per_sec = 16
+# expanded_cache_mb = 4096   # cap on expanded chunked/compressed blobs (0 = no cap)
+# shared_vdird = false       # one vdir_d serves every project
 
 # [ingest]
 # threads = auto
@@ -317,6 +362,10 @@ the_source = "{the_source}"
 # [tiers]
 # tier1_patterns = ["node_modules/", ".cargo/registry/"]
 # tier2_patterns = ["target/", "build/"]
+
+# [hydrate]
+# enabled = false          # materialize stub files from CAS on first open
+# paths = ["src", "!vendor"]  # per-directory policy (empty = everything)
 "#,
             vfs_prefix = default.project.vfs_prefix,
             the_source = default.storage.the_source.display(),
@@ -377,6 +426,10 @@ pub struct ProjectConfig {
     pub vfs_prefix: String,
     /// Manifest LMDB path (relative to project root)
     pub manifest: PathBuf,
+    /// Directory prefixes, relative to the project root, that the shim
+    /// leaves to the real filesystem. `!dir` re-includes; the longest
+    /// match wins. Empty virtualizes everything under the VFS prefix.
+    pub passthrough: Vec<String>,
 }
 
 impl Default for ProjectConfig {
@@ -385,6 +438,7 @@ impl Default for ProjectConfig {
             root: PathBuf::from("."),
             vfs_prefix: "/vrift".to_string(),
             manifest: PathBuf::from(".vrift/manifest.lmdb"),
+            passthrough: Vec::new(),
         }
     }
 }
@@ -418,7 +472,8 @@ pub struct IngestConfig {
     pub threads: Option<usize>,
     /// Default tier: tier1, tier2, or auto
     pub default_tier: String,
-    /// Deduplication window in milliseconds (default: 200ms)
+    /// How long a path must stay unchanged before the live watcher
+    /// ingests it, in milliseconds (default: 200ms)
     pub dedup_window_ms: u64,
     /// Batch size for high-frequency writes (default: 10)
     pub batch_size: usize,
@@ -426,6 +481,11 @@ pub struct IngestConfig {
     pub batch_timeout_ms: u64,
     /// Patterns to ignore during ingest and live watch
     pub ignore_patterns: Vec<String>,
+    /// Files at least this large (bytes) are stored as content-defined
+    /// chunks for sub-file dedup (0 = never)
+    pub chunk_threshold: u64,
+    /// Extended attributes left out when recording a file's xattrs
+    pub strip_xattrs: Vec<String>,
 }
 
 impl Default for IngestConfig {
@@ -441,6 +501,10 @@ impl Default for IngestConfig {
                 ".vrift".to_string(),    // Vrift system directory (always needed)
                 ".DS_Store".to_string(), // macOS junk
             ],
+            chunk_threshold: 0,
+            // Download provenance: would make Gatekeeper prompt for every
+            // binary served from the CAS
+            strip_xattrs: vec!["com.apple.quarantine".to_string()],
         }
     }
 }
@@ -538,6 +602,18 @@ pub struct DaemonConfig {
     pub cow_temp_dir: PathBuf,
     /// Log directory for daemon and inception-layer
     pub log_dir: PathBuf,
+    /// Users besides the daemon's own (and root) allowed to connect to
+    /// vriftd and vdir_d
+    pub allowed_uids: Vec<u32>,
+    /// Hours between background CAS scrubs by vriftd (0: never)
+    pub scrub_interval_hours: u64,
+    /// Hashing budget of a background scrub in MiB/s (0: unlimited)
+    pub scrub_mb_per_sec: u64,
+    /// Size cap in MiB of the CAS's `cache/` of expanded chunked and
+    /// compressed blobs, trimmed by vriftd (0: no cap)
+    pub expanded_cache_mb: u64,
+    /// Serve every project from one vdir_d instead of one vdir_d each
+    pub shared_vdird: bool,
 }
 
 impl Default for DaemonConfig {
@@ -553,8 +629,97 @@ impl Default for DaemonConfig {
             mmap_path: PathBuf::from("/tmp/vrift-manifest.mmap"),
             cow_temp_dir: PathBuf::from("/tmp"),
             log_dir: PathBuf::from("/tmp"),
+            allowed_uids: Vec::new(),
+            scrub_interval_hours: 24,
+            scrub_mb_per_sec: 16,
+            expanded_cache_mb: 4096,
+            shared_vdird: false,
+        }
+    }
+}
+
+/// Materialize-on-demand working tree ("sparse checkout")
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct HydrateConfig {
+    /// Copy stubbed files from CAS into the project tree on first open
+    pub enabled: bool,
+    /// Directory prefixes to hydrate, relative to the project root.
+    /// `!dir` excludes; the longest match wins. Empty means every path.
+    pub paths: Vec<String>,
+}
+
+impl HydrateConfig {
+    /// Value for VRIFT_HYDRATE, or None when hydration is off
+    pub fn shim_spec(&self) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        if self.paths.is_empty() {
+            Some("1".to_string())
+        } else {
+            Some(self.paths.join(","))
+        }
+    }
+
+    /// Whether `manifest_key` falls under this config's hydrate policy
+    pub fn matches(&self, manifest_key: &str) -> bool {
+        hydrate_policy_matches(self.paths.iter().map(String::as_str), manifest_key)
+    }
+}
+
+/// Evaluate a hydrate policy (`["src", "!src/gen"]`) against a manifest key.
+/// The longest matching prefix wins; with no positive rules, everything not
+/// excluded matches. Allocation-free so the inception layer can share it.
+pub fn hydrate_policy_matches<'a>(
+    rules: impl IntoIterator<Item = &'a str>,
+    manifest_key: &str,
+) -> bool {
+    let (best, has_positive) = longest_prefix_rule(rules, manifest_key);
+    best.unwrap_or(!has_positive)
+}
+
+/// Evaluate a passthrough list (`["target", "!target/doc"]`) against a
+/// manifest key. Same rules as [`hydrate_policy_matches`], except that a key
+/// no rule covers is not passed through.
+pub fn passthrough_matches<'a>(
+    rules: impl IntoIterator<Item = &'a str>,
+    manifest_key: &str,
+) -> bool {
+    longest_prefix_rule(rules, manifest_key).0.unwrap_or(false)
+}
+
+/// The verdict of the longest rule matching `manifest_key`, if any, and
+/// whether the rules include any positive entry
+fn longest_prefix_rule<'a>(
+    rules: impl IntoIterator<Item = &'a str>,
+    manifest_key: &str,
+) -> (Option<bool>, bool) {
+    let key = manifest_key.trim_start_matches('/');
+    let mut best_len = 0usize;
+    let mut best = None;
+    let mut has_positive = false;
+
+    for rule in rules {
+        let rule = rule.trim();
+        let (exclude, prefix) = match rule.strip_prefix('!') {
+            Some(p) => (true, p),
+            None => (false, rule),
+        };
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() {
+            continue;
+        }
+        has_positive |= !exclude;
+
+        let hit = key == prefix
+            || (key.starts_with(prefix) && key.as_bytes().get(prefix.len()) == Some(&b'/'));
+        if hit && prefix.len() >= best_len {
+            best_len = prefix.len();
+            best = Some(!exclude);
         }
     }
+    (best, has_positive)
 }
 
 #[cfg(test)]
@@ -841,4 +1006,79 @@ foo = "bar"
             assert_eq!(config.storage.default_mode, "solid");
         }
     }
+
+    #[test]
+    fn test_hydrate_shim_env() {
+        let mut config = Config::default();
+        assert!(!config.shim_env().iter().any(|(k, _)| k == "VRIFT_HYDRATE"));
+
+        let project: Config = toml::from_str(
+            r#"
+[hydrate]
+enabled = true
+paths = ["src", "!src/generated"]
+"#,
+        )
+        .unwrap();
+        let raw: toml::Value = toml::from_str("[hydrate]\nenabled = true\npaths = []").unwrap();
+        config.merge_with_presence(project, &raw);
+        assert_eq!(
+            config.hydrate.shim_spec().as_deref(),
+            Some("src,!src/generated")
+        );
+        assert!(config.shim_env().contains(&(
+            "VRIFT_HYDRATE".to_string(),
+            "src,!src/generated".to_string()
+        )));
+
+        config.hydrate.paths.clear();
+        assert_eq!(config.hydrate.shim_spec().as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_passthrough_config_and_matching() {
+        let mut config = Config::default();
+        assert!(!config
+            .shim_env()
+            .iter()
+            .any(|(k, _)| k == "VRIFT_PASSTHROUGH"));
+
+        let project: Config =
+            toml::from_str("[project]\npassthrough = [\"target\", \"!target/doc\"]").unwrap();
+        let raw: toml::Value = toml::from_str("[project]\npassthrough = []").unwrap();
+        config.merge_with_presence(project, &raw);
+        assert!(config.shim_env().contains(&(
+            "VRIFT_PASSTHROUGH".to_string(),
+            "target,!target/doc".to_string()
+        )));
+
+        let rules = config.project.passthrough.iter().map(String::as_str);
+        assert!(passthrough_matches(rules.clone(), "/target/debug/app"));
+        assert!(!passthrough_matches(
+            rules.clone(),
+            "/target/doc/index.html"
+        ));
+        assert!(!passthrough_matches(rules.clone(), "/targets/x"));
+        assert!(!passthrough_matches(rules, "/src/main.rs"));
+        // Unlike hydrate, an empty or exclude-only list matches nothing
+        assert!(!passthrough_matches([], "/anything"));
+        assert!(!passthrough_matches(["!vendor"], "/src/a.rs"));
+    }
+
+    #[test]
+    fn test_hydrate_policy_longest_prefix_wins() {
+        let rules = ["src", "!src/generated", "src/generated/keep"];
+        let m = |k| hydrate_policy_matches(rules, k);
+        assert!(m("/src/main.rs"));
+        assert!(!m("/src/generated/a.rs"));
+        assert!(m("/src/generated/keep/b.rs"));
+        assert!(!m("/docs/readme.md"));
+        // Prefix must end on a path boundary
+        assert!(!m("/srcx/a.rs"));
+
+        // Only exclusions: everything else is hydrated
+        assert!(hydrate_policy_matches(["!vendor"], "/src/a.rs"));
+        assert!(!hydrate_policy_matches(["!vendor"], "/vendor/x/y"));
+        assert!(hydrate_policy_matches([], "/anything"));
+    }
 }
@@ -229,6 +229,83 @@ pub fn get_vdird_socket_path(project_id: &str) -> Option<PathBuf> {
     })
 }
 
+/// Env var carrying the session id from the CLI to shimmed processes
+pub const SESSION_ID_ENV: &str = "VRIFT_SESSION_ID";
+
+/// Allocate a session id: `<unix secs hex>-<pid>-<seq>`.
+///
+/// Unique per allocating process; the timestamp keeps ids from colliding
+/// with leftovers of an earlier process that had the same pid.
+pub fn new_session_id() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static SEQ: AtomicU64 = AtomicU64::new(0);
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!(
+        "{:x}-{}-{}",
+        secs,
+        std::process::id(),
+        SEQ.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Session ids become directory names: only `[A-Za-z0-9_-]`, at most 64 bytes.
+pub fn is_valid_session_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Per-session staging directory: `<project>/.vrift/staging/<session_id>`
+pub fn session_staging_dir(project_root: impl AsRef<Path>, session_id: &str) -> PathBuf {
+    project_root
+        .as_ref()
+        .join(".vrift")
+        .join("staging")
+        .join(session_id)
+}
+
+/// Remove a session's staging directory when the session ends.
+/// Returns Ok(false) if there was nothing to remove.
+pub fn remove_session_staging(
+    project_root: impl AsRef<Path>,
+    session_id: &str,
+) -> std::io::Result<bool> {
+    if !is_valid_session_id(session_id) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid session id: {:?}", session_id),
+        ));
+    }
+    let dir = session_staging_dir(project_root, session_id);
+    match std::fs::remove_dir_all(&dir) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Map a host path into this process's root. `root_offset` is where that
+/// root sits on the host (empty for the host root). Paths outside it come
+/// back unchanged: a CAS or socket dir bind-mounted into a container at the
+/// same location needs no translation.
+///
+/// Used by the inception layer on its hot path, so it only borrows.
+pub fn host_to_local<'a>(path: &'a str, root_offset: &str) -> &'a str {
+    if root_offset.is_empty() {
+        return path;
+    }
+    match path.strip_prefix(root_offset) {
+        Some("") => "/",
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => path,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,4 +440,53 @@ mod tests {
         let result = normalize_relative_to("src/main.rs", temp.path()).unwrap();
         assert_eq!(result, PathBuf::from("src/main.rs"));
     }
+
+    #[test]
+    fn test_session_ids_unique_and_valid() {
+        let a = new_session_id();
+        let b = new_session_id();
+        assert_ne!(a, b);
+        assert!(is_valid_session_id(&a));
+        assert!(!is_valid_session_id(""));
+        assert!(!is_valid_session_id("../etc"));
+        assert!(!is_valid_session_id("a/b"));
+    }
+
+    #[test]
+    fn test_host_to_local() {
+        // No offset: the process sees the host root
+        assert_eq!(host_to_local("/home/u/p/a.rs", ""), "/home/u/p/a.rs");
+        assert_eq!(host_to_local("/home/u/p/", ""), "/home/u/p/");
+
+        let offset = "/var/lib/box";
+        assert_eq!(host_to_local("/var/lib/box/p/a.rs", offset), "/p/a.rs");
+        // Exactly the offset is the local root
+        assert_eq!(host_to_local("/var/lib/box", offset), "/");
+        // Trailing slashes are kept
+        assert_eq!(host_to_local("/var/lib/box/", offset), "/");
+        assert_eq!(host_to_local("/var/lib/box/p/", offset), "/p/");
+        // Outside the offset, including a sibling sharing its prefix
+        assert_eq!(host_to_local("/var/cas/ab.bin", offset), "/var/cas/ab.bin");
+        assert_eq!(
+            host_to_local("/var/lib/boxed/x", offset),
+            "/var/lib/boxed/x"
+        );
+        assert_eq!(host_to_local("/var/lib", offset), "/var/lib");
+    }
+
+    #[test]
+    fn test_remove_session_staging() {
+        let temp = tempdir().unwrap();
+        let dir = session_staging_dir(temp.path(), "s1");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("vrift_cow_1.tmp"), b"x").unwrap();
+        let other = session_staging_dir(temp.path(), "s2");
+        fs::create_dir_all(&other).unwrap();
+
+        assert!(remove_session_staging(temp.path(), "s1").unwrap());
+        assert!(!dir.exists());
+        assert!(other.exists());
+        assert!(!remove_session_staging(temp.path(), "s1").unwrap());
+        assert!(remove_session_staging(temp.path(), "..").is_err());
+    }
 }
@@ -11,11 +11,12 @@ async fn main() -> anyhow::Result<()> {
     let mut stream = UnixStream::connect(socket_path).await?;
     println!("[+] Connected to daemon.");
 
-    // VeloRequest::Spawn { command, env, cwd }
+    // VeloRequest::Spawn { command, env, cwd, log_path }
     let req = VeloRequest::Spawn {
         command: vec!["touch".to_string(), "/tmp/vrift_rce_test".to_string()],
         env: vec![],
         cwd: "/tmp".to_string(),
+        log_path: None,
     };
 
     println!("[+] Sending Spawn request (touch /tmp/vrift_rce_test)...");
@@ -3,6 +3,8 @@ use clap::{Parser, Subcommand};
 
 use tokio::signal;
 
+mod supervisor;
+
 #[derive(Parser)]
 #[command(name = "vriftd")]
 #[command(version, about = "Velo Rift Daemon", long_about = None)]
@@ -38,19 +40,31 @@ async fn main() -> Result<()> {
 
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
 use tokio::net::{UnixListener, UnixStream};
+use vrift_config::ignore::IgnoreMatcher;
 use vrift_config::path::is_within_directory;
+use vrift_ipc::peer::{PeerCredentials, PeerPolicy};
 use vrift_ipc::{VeloError, VeloErrorKind, VeloRequest, VeloResponse};
 use vrift_manifest::lmdb::{AssetTier, LmdbManifest};
 
+/// What vriftd offers in `Negotiate`. Requests on a connection are served
+/// one at a time, so no BATCH_REQUESTS; RegisterAck points shims at the
+/// vDird's VDir mmap.
+const VRIFTD_CAPABILITIES: vrift_ipc::Capabilities = vrift_ipc::Capabilities::MMAP_CACHE;
+
+/// How often `[daemon] expanded_cache_mb` is enforced
+const EXPANDED_CACHE_TRIM_INTERVAL: std::time::Duration = std::time::Duration::from_secs(600);
+
 // RFC-0043: Minimal registry for workspace discovery
 // TEMPORARILY DISABLED: Investigating UE blocking issues
 #[allow(dead_code)]
 #[derive(serde::Deserialize)]
 struct MinimalManifestEntry {
     project_root: PathBuf,
+    source_path: PathBuf,
 }
 
 #[allow(dead_code)]
@@ -59,109 +73,92 @@ struct MinimalRegistry {
     manifests: std::collections::HashMap<String, MinimalManifestEntry>,
 }
 
-#[allow(dead_code)]
-fn load_registered_workspaces() -> Vec<PathBuf> {
+fn load_registry() -> Option<MinimalRegistry> {
     let home = std::env::var("HOME").unwrap_or_else(|_| "/root".to_string());
     let path = PathBuf::from(home).join(".vrift/registry/manifests.json");
-    if !path.exists() {
-        return Vec::new();
-    }
+    let file = std::fs::File::open(path).ok()?;
+    serde_json::from_reader(file).ok()
+}
 
-    if let Ok(file) = std::fs::File::open(path) {
-        if let Ok(registry) = serde_json::from_reader::<_, MinimalRegistry>(file) {
-            return registry
+#[allow(dead_code)]
+fn load_registered_workspaces() -> Vec<PathBuf> {
+    load_registry()
+        .map(|registry| {
+            registry
                 .manifests
                 .values()
                 .map(|e| e.project_root.clone())
-                .collect();
-        }
-    }
-    Vec::new()
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
-#[derive(Debug, Clone, Copy)]
-struct PeerCredentials {
-    uid: u32,
-    #[allow(dead_code)]
-    gid: u32,
-    pid: Option<libc::pid_t>,
-}
+/// One background scrub pass. Bad blobs are quarantined, dropped from the
+/// index, and the registered manifest paths using them logged for re-ingest.
+async fn scrub_cas(state: &Arc<DaemonState>, options: vrift_cas::ScrubOptions) {
+    let cas = state.cas.clone();
+    tracing::info!("vriftd: CAS scrub started");
+    let report = match tokio::task::spawn_blocking(move || cas.scrub(&options)).await {
+        Ok(Ok(report)) => report,
+        Ok(Err(e)) => {
+            tracing::error!("vriftd: CAS scrub failed: {}", e);
+            return;
+        }
+        Err(e) => {
+            tracing::error!("vriftd: CAS scrub task panicked: {}", e);
+            return;
+        }
+    };
+    tracing::info!(
+        "vriftd: CAS scrub checked {} blobs, {} bad",
+        report.checked,
+        report.findings.len()
+    );
+    if report.is_clean() {
+        return;
+    }
 
-impl PeerCredentials {
-    #[cfg(target_os = "linux")]
-    fn from_stream(stream: &UnixStream) -> Option<Self> {
-        use std::os::unix::io::AsRawFd;
-        let fd = stream.as_raw_fd();
-        let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
-        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
-        let ret = unsafe {
-            libc::getsockopt(
-                fd,
-                libc::SOL_SOCKET,
-                libc::SO_PEERCRED,
-                &mut cred as *mut _ as *mut libc::c_void,
-                &mut len,
-            )
-        };
-        if ret == 0 {
-            Some(Self {
-                uid: cred.uid,
-                gid: cred.gid,
-                pid: Some(cred.pid),
-            })
-        } else {
-            None
+    for finding in &report.findings {
+        tracing::warn!(
+            "vriftd: bad blob {} ({:?}), quarantined to {:?}",
+            finding.path.display(),
+            finding.problem,
+            finding.quarantined
+        );
+    }
+    let damaged = report.damaged_hashes();
+    {
+        let mut index = state.cas_index.lock().unwrap();
+        for hash in &damaged {
+            index.remove(hash);
         }
     }
 
-    #[cfg(target_os = "macos")]
-    fn from_stream(stream: &UnixStream) -> Option<Self> {
-        use std::os::unix::io::AsRawFd;
-        let fd = stream.as_raw_fd();
-        #[repr(C)]
-        struct XuCred {
-            cr_version: u32,
-            cr_uid: u32,
-            cr_ngroups: i16,
-            cr_groups: [u32; 16],
+    let Some(registry) = load_registry() else {
+        return;
+    };
+    for entry in registry.manifests.values() {
+        if !entry.source_path.is_dir() {
+            continue;
         }
-        let mut cred: XuCred = unsafe { std::mem::zeroed() };
-        cred.cr_version = 0; // XUCRED_VERSION
-        let mut len = std::mem::size_of::<XuCred>() as libc::socklen_t;
-        const LOCAL_PEERCRED: libc::c_int = 1;
-        const LOCAL_PEERPID: libc::c_int = 2;
-
-        let ret = unsafe {
-            libc::getsockopt(
-                fd,
-                0, // SOL_LOCAL = 0 on macOS
-                LOCAL_PEERCRED,
-                &mut cred as *mut _ as *mut libc::c_void,
-                &mut len,
-            )
-        };
-        if ret != 0 {
-            return None;
+        let paths = LmdbManifest::open(&entry.source_path)
+            .and_then(|manifest| manifest.paths_with_hashes(&damaged));
+        match paths {
+            Ok(paths) => {
+                for path in paths {
+                    tracing::warn!(
+                        "vriftd: {}{} needs re-ingest (blob damaged)",
+                        entry.project_root.display(),
+                        path
+                    );
+                }
+            }
+            Err(e) => tracing::warn!(
+                "vriftd: could not check {} for damaged blobs: {}",
+                entry.source_path.display(),
+                e
+            ),
         }
-
-        // Also fetch PID
-        let mut pid: libc::pid_t = 0;
-        let mut pid_len = std::mem::size_of::<libc::pid_t>() as libc::socklen_t;
-        let ret_pid = unsafe {
-            libc::getsockopt(
-                fd,
-                0, // SOL_LOCAL = 0 on macOS
-                LOCAL_PEERPID,
-                &mut pid as *mut _ as *mut libc::c_void,
-                &mut pid_len,
-            )
-        };
-
-        Some(Self {
-            uid: cred.cr_uid,
-            gid: cred.cr_groups[0],
-            pid: if ret_pid == 0 { Some(pid) } else { None },
-        })
     }
 }
 
@@ -281,6 +278,53 @@ struct DaemonState {
     lock_manager: LockManager,
     // Daemon start time (for uptime reporting)
     start_time: std::time::Instant,
+    // Open client connections (for Metrics)
+    connections: AtomicU64,
+    // Children started by Spawn requests
+    supervisor: Arc<supervisor::Supervisor>,
+    // Which peer uids may use the socket
+    peers: PeerPolicy,
+    // Progress of the IngestFullScans running now (for Metrics)
+    ingests: Mutex<Vec<Arc<vrift_cas::IngestProgress>>>,
+}
+
+/// Counts a client connection in `DaemonState::connections` while it's open
+struct ConnectionGuard<'a>(&'a AtomicU64);
+
+impl<'a> ConnectionGuard<'a> {
+    fn new(counter: &'a AtomicU64) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+/// Lists an ingest's progress in `DaemonState::ingests` while it runs
+struct IngestGuard<'a> {
+    ingests: &'a Mutex<Vec<Arc<vrift_cas::IngestProgress>>>,
+    progress: Arc<vrift_cas::IngestProgress>,
+}
+
+impl<'a> IngestGuard<'a> {
+    fn new(ingests: &'a Mutex<Vec<Arc<vrift_cas::IngestProgress>>>) -> Self {
+        let progress = Arc::new(vrift_cas::IngestProgress::default());
+        ingests.lock().unwrap().push(progress.clone());
+        Self { ingests, progress }
+    }
+}
+
+impl Drop for IngestGuard<'_> {
+    fn drop(&mut self) {
+        self.ingests
+            .lock()
+            .unwrap()
+            .retain(|p| !Arc::ptr_eq(p, &self.progress));
+    }
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 async fn start_daemon() -> Result<()> {
@@ -300,9 +344,12 @@ async fn start_daemon() -> Result<()> {
         }
     }
 
-    if path.exists() {
-        tokio::fs::remove_file(path).await?;
-    }
+    // Single instance: held until start_daemon returns, released by the kernel on crash
+    let _instance_lock = vrift_ipc::InstanceLock::acquire(path)
+        .map_err(|e| anyhow::anyhow!("vriftd: another instance is already running ({})", e))?;
+
+    // Take over a stale socket left by a crashed daemon; refuse if one still answers
+    vrift_ipc::reclaim_socket(path)?;
 
     let listener = UnixListener::bind(path)?;
     tracing::info!("vriftd: Listening on {}", socket_str);
@@ -319,6 +366,10 @@ async fn start_daemon() -> Result<()> {
         cas: cas.clone(),
         lock_manager: LockManager::new(),
         start_time: std::time::Instant::now(),
+        connections: AtomicU64::new(0),
+        supervisor: Arc::default(),
+        peers: PeerPolicy::for_current_user(&cfg.daemon.allowed_uids),
+        ingests: Mutex::new(Vec::new()),
     });
 
     // Start background scan (Warm-up)
@@ -390,6 +441,46 @@ async fn start_daemon() -> Result<()> {
         });
     }
 
+    // Background CAS scrub: quarantine blobs that no longer match their hash
+    if cfg.daemon.scrub_interval_hours > 0 {
+        let scrub_state = state.clone();
+        let every = std::time::Duration::from_secs(cfg.daemon.scrub_interval_hours * 3600);
+        let options = vrift_cas::ScrubOptions {
+            max_bytes_per_sec: Some(cfg.daemon.scrub_mb_per_sec << 20),
+            quarantine: true,
+        };
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            interval.tick().await; // not at startup, while shims warm up
+            loop {
+                interval.tick().await;
+                scrub_cas(&scrub_state, options.clone()).await;
+            }
+        });
+    }
+
+    // Keep the expanded copies readers cache under `cache/` within budget
+    if cfg.daemon.expanded_cache_mb > 0 {
+        let trim_state = state.clone();
+        let max_bytes = cfg.daemon.expanded_cache_mb << 20;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EXPANDED_CACHE_TRIM_INTERVAL);
+            loop {
+                interval.tick().await;
+                let cas = trim_state.cas.clone();
+                match tokio::task::spawn_blocking(move || cas.trim_expanded(max_bytes)).await {
+                    Ok(Ok((0, 0))) => {}
+                    Ok(Ok((evicted, bytes))) => tracing::info!(
+                        "vriftd: trimmed expanded blob cache: {} copies, {} bytes",
+                        evicted,
+                        bytes
+                    ),
+                    Ok(Err(e)) => tracing::warn!("vriftd: expanded cache trim failed: {}", e),
+                    Err(e) => tracing::error!("vriftd: expanded cache trim panicked: {}", e),
+                }
+            }
+        });
+    }
     loop {
         tokio::select! {
             accept_result = listener.accept() => {
@@ -457,8 +548,16 @@ async fn cleanup_vdird_processes(state: &DaemonState) {
 
 async fn handle_connection(mut stream: UnixStream, state: Arc<DaemonState>) {
     tracing::info!("[DAEMON] New connection accepted");
-    let peer_creds = PeerCredentials::from_stream(&stream);
-    let daemon_uid = unsafe { libc::getuid() };
+    let _connection = ConnectionGuard::new(&state.connections);
+    let peer_creds = {
+        use std::os::unix::io::AsRawFd;
+        PeerCredentials::from_fd(stream.as_raw_fd())
+    };
+    // Handshakes are answered for anyone so a refused client learns why
+    let mut access = state.peers.check(peer_creds.as_ref());
+    if let Err(e) = &access {
+        tracing::warn!("vriftd: refusing peer {:?}: {}", peer_creds, e.message);
+    }
     let mut current_vdird: Option<Arc<VDirdProcess>> = None;
 
     loop {
@@ -489,8 +588,15 @@ async fn handle_connection(mut stream: UnixStream, state: Arc<DaemonState>) {
                 "[DAEMON] Processing request: {:?}",
                 std::mem::discriminant(&req)
             );
-            let resp =
-                handle_request(req, &state, peer_creds, daemon_uid, &mut current_vdird).await;
+            if access.is_ok() {
+                if let Some(e) = req.handshake_error() {
+                    access = Err(e);
+                }
+            }
+            let resp = match &access {
+                Err(e) if !req.is_handshake() => VeloResponse::Error(e.clone()),
+                _ => handle_request(req, &state, peer_creds, &mut current_vdird).await,
+            };
             tracing::info!(
                 "[DAEMON] Request processed, response: {:?}",
                 std::mem::discriminant(&resp)
@@ -513,7 +619,6 @@ async fn handle_request(
     req: VeloRequest,
     state: &DaemonState,
     peer_creds: Option<PeerCredentials>,
-    daemon_uid: u32,
     current_vdird: &mut Option<Arc<VDirdProcess>>,
 ) -> VeloResponse {
     tracing::debug!("Received request: {:?}", req);
@@ -526,6 +631,25 @@ async fn handle_request(
             protocol_version: vrift_ipc::PROTOCOL_VERSION,
             compatible: vrift_ipc::is_version_compatible(protocol_version),
         },
+        VeloRequest::Negotiate {
+            client_version,
+            protocol_version,
+            capabilities,
+        } => {
+            let (protocol_version, capabilities) =
+                vrift_ipc::negotiate(protocol_version, capabilities, VRIFTD_CAPABILITIES);
+            tracing::info!(
+                "vriftd: client {} negotiated protocol {:?} with {:?}",
+                client_version,
+                protocol_version,
+                capabilities.names()
+            );
+            VeloResponse::NegotiateAck {
+                server_version: env!("CARGO_PKG_VERSION").to_string(),
+                protocol_version,
+                capabilities,
+            }
+        }
         VeloRequest::Status => {
             let blob_count = state.cas_index.lock().unwrap().len();
             let vdird_count = state.vdird_processes.lock().unwrap().len();
@@ -548,6 +672,29 @@ async fn handle_request(
                 ),
             }
         }
+        VeloRequest::Metrics => {
+            let (cas_blobs, cas_bytes) = {
+                let index = state.cas_index.lock().unwrap();
+                (index.len() as u64, index.values().sum())
+            };
+            let mut metrics = vrift_ipc::DaemonMetrics {
+                uptime_secs: state.start_time.elapsed().as_secs(),
+                connections: state.connections.load(Ordering::Relaxed),
+                cas_blobs,
+                cas_bytes,
+                vdird_processes: state.vdird_processes.lock().unwrap().len() as u64,
+                ingest_scan_complete: true,
+                ..Default::default()
+            };
+            for progress in state.ingests.lock().unwrap().iter() {
+                metrics.ingests_running += 1;
+                metrics.ingest_files_found += progress.found.load(Ordering::Relaxed);
+                metrics.ingest_files_done += progress.done.load(Ordering::Relaxed);
+                metrics.ingest_bytes_done += progress.bytes.load(Ordering::Relaxed);
+                metrics.ingest_scan_complete &= progress.scanned.load(Ordering::Relaxed);
+            }
+            VeloResponse::MetricsAck { metrics }
+        }
         VeloRequest::RegisterWorkspace {
             project_root: root_str,
         } => {
@@ -579,6 +726,7 @@ async fn handle_request(
                         workspace_id: vdird.project_id.clone(),
                         vdird_socket: vdird.socket_path.to_string_lossy().to_string(),
                         vdir_mmap_path: vdird.vdir_mmap_path.to_string_lossy().to_string(),
+                        session_id: vrift_config::path::new_session_id(),
                     }
                 }
                 Err(e) => {
@@ -590,15 +738,46 @@ async fn handle_request(
                 }
             }
         }
-        VeloRequest::Spawn { command, env, cwd } => {
-            if let Some(creds) = peer_creds {
-                if creds.uid != daemon_uid && creds.uid != 0 {
-                    return VeloResponse::Error(VeloError::permission_denied("UID mismatch"));
-                }
-            } else {
-                return VeloResponse::Error(VeloError::permission_denied("Verification failed"));
+        VeloRequest::Spawn {
+            command,
+            env,
+            cwd,
+            log_path,
+        } => {
+            if let Err(e) = state.peers.check_owner(peer_creds.as_ref()) {
+                return VeloResponse::Error(e);
+            }
+            match state.supervisor.spawn(command, env, cwd, log_path) {
+                Ok(pid) => VeloResponse::SpawnAck { pid },
+                Err(e) => VeloResponse::Error(e),
+            }
+        }
+        VeloRequest::SpawnPoll {
+            pid,
+            stdout_offset,
+            stderr_offset,
+            wait_ms,
+        } => {
+            if let Err(e) = state.peers.check_owner(peer_creds.as_ref()) {
+                return VeloResponse::Error(e);
+            }
+            match state
+                .supervisor
+                .poll(pid, stdout_offset, stderr_offset, wait_ms)
+                .await
+            {
+                Ok(output) => VeloResponse::SpawnPollAck { output },
+                Err(e) => VeloResponse::Error(e),
+            }
+        }
+        VeloRequest::Kill { pid, signal } => {
+            if let Err(e) = state.peers.check_owner(peer_creds.as_ref()) {
+                return VeloResponse::Error(e);
+            }
+            match state.supervisor.kill(pid, signal) {
+                Ok(()) => VeloResponse::KillAck,
+                Err(e) => VeloResponse::Error(e),
             }
-            handle_spawn(command, env, cwd).await
         }
         VeloRequest::CasInsert { hash, size } => {
             let mut index = state.cas_index.lock().unwrap();
@@ -763,7 +942,7 @@ async fn handle_request(
                 Err(e) => VeloResponse::Error(VeloError::internal(format!("Sweep failed: {}", e))),
             }
         }
-        VeloRequest::ManifestListDir { path } => {
+        VeloRequest::ManifestListDir { path } | VeloRequest::ManifestListDirPage { path, .. } => {
             tracing::warn!(
                 "vriftd: ManifestListDir '{}' received — route to vDird instead",
                 path
@@ -773,6 +952,60 @@ async fn handle_request(
                 "Manifest operations must be routed to vDird. Use the vdird_socket from RegisterAck.",
             ))
         }
+        VeloRequest::ManifestGetXattrs { path } | VeloRequest::ManifestSetXattr { path, .. } => {
+            tracing::warn!(
+                "vriftd: xattr request for '{}' received — route to vDird instead",
+                path
+            );
+            VeloResponse::Error(VeloError::new(
+                VeloErrorKind::WorkspaceNotRegistered,
+                "Manifest operations must be routed to vDird. Use the vdird_socket from RegisterAck.",
+            ))
+        }
+        VeloRequest::ManifestResolve { path, .. } => {
+            tracing::warn!(
+                "vriftd: ManifestResolve '{}' received — route to vDird instead",
+                path
+            );
+            VeloResponse::Error(VeloError::new(
+                VeloErrorKind::WorkspaceNotRegistered,
+                "Manifest operations must be routed to vDird. Use the vdird_socket from RegisterAck.",
+            ))
+        }
+        VeloRequest::ProjectOpen { project_root } => {
+            tracing::warn!(
+                "vriftd: ProjectOpen '{}' received — use RegisterWorkspace instead",
+                project_root
+            );
+            VeloResponse::Error(VeloError::new(
+                VeloErrorKind::WorkspaceNotRegistered,
+                "ProjectOpen is sent to a vDird. Register the workspace with vriftd instead.",
+            ))
+        }
+        VeloRequest::MissReport { path_hashes } => {
+            tracing::warn!(
+                "vriftd: MissReport ({} paths) received — route to vDird instead",
+                path_hashes.len()
+            );
+            VeloResponse::Error(VeloError::new(
+                VeloErrorKind::WorkspaceNotRegistered,
+                "Manifest operations must be routed to vDird. Use the vdird_socket from RegisterAck.",
+            ))
+        }
+        VeloRequest::SnapshotCreate { .. }
+        | VeloRequest::SnapshotList
+        | VeloRequest::SnapshotRestore { .. }
+        | VeloRequest::SnapshotUnprotect { .. }
+        | VeloRequest::ManifestReload
+        | VeloRequest::BuildCacheKey { .. } => {
+            tracing::warn!(
+                "vriftd: snapshot/reload/build-cache request received — route to vDird instead"
+            );
+            VeloResponse::Error(VeloError::new(
+                VeloErrorKind::WorkspaceNotRegistered,
+                "Manifest operations must be routed to vDird. Use the vdird_socket from RegisterAck.",
+            ))
+        }
         // IngestFullScan: Unified ingest architecture
         // CLI becomes thin client, daemon handles all ingest logic
         VeloRequest::IngestFullScan {
@@ -784,12 +1017,22 @@ async fn handle_request(
             prefix,
             cas_root,
             force_hash,
+            compress,
+            ignore,
         } => {
             use std::time::Instant;
             use vrift_cas::{streaming_ingest, streaming_ingest_cached, CacheHint, IngestMode};
 
             let source_path = PathBuf::from(&path);
             let manifest_out = PathBuf::from(&manifest_path);
+            // Same rules as vDird's watcher: [ingest] ignore_patterns plus
+            // any .vriftignore under the source, plus the request's own
+            let ignore: vrift_cas::IgnoreFn = {
+                let mut patterns = vrift_config::config().ingest.ignore_patterns.clone();
+                patterns.extend(ignore);
+                let matcher = IgnoreMatcher::with_patterns(&patterns).with_root(&source_path);
+                Arc::new(move |path, is_dir| matcher.matched(path, is_dir))
+            };
 
             tracing::info!(
                 path = %path,
@@ -802,6 +1045,7 @@ async fn handle_request(
             );
 
             let start = Instant::now();
+            let ingest = IngestGuard::new(&state.ingests);
 
             // Determine mode
             let mode = if phantom {
@@ -864,6 +1108,8 @@ async fn handle_request(
             // Run streaming ingest in blocking task
             let source_clone = source_path.clone();
             let cas_clone = cas_root_path.clone();
+            let ignore_clone = ignore.clone();
+            let progress = ingest.progress.clone();
             let results = match tokio::task::spawn_blocking(move || {
                 if let Some(manifest_arc) = existing_manifest {
                     // P0: Pre-load manifest into HashMap for O(1) cache lookups
@@ -905,6 +1151,8 @@ async fn handle_request(
                         &cas_clone,
                         mode,
                         threads,
+                        Some(ignore_clone),
+                        Some(progress),
                         cache_lookup,
                     );
                     tracing::info!(
@@ -915,7 +1163,14 @@ async fn handle_request(
                 } else {
                     // Standard path (first ingest or non-SolidTier2)
                     tracing::info!("spawn_blocking: starting streaming_ingest");
-                    let r = streaming_ingest(&source_clone, &cas_clone, mode, threads);
+                    let r = streaming_ingest(
+                        &source_clone,
+                        &cas_clone,
+                        mode,
+                        threads,
+                        Some(ignore_clone),
+                        Some(progress),
+                    );
                     tracing::info!("spawn_blocking: streaming_ingest done, {} results", r.len());
                     r
                 }
@@ -965,9 +1220,13 @@ async fn handle_request(
                         .unwrap_or_else(|_| r.source_path.clone());
                     let rel = canon_src.strip_prefix(&canon_root).unwrap_or(&canon_src);
                     let key = if prefix_str.is_empty() || prefix_str == "/" {
-                        format!("/{}", rel.display())
+                        vrift_ipc::path_key_for(rel)
                     } else {
-                        format!("{}/{}", prefix_str.trim_end_matches('/'), rel.display())
+                        format!(
+                            "{}{}",
+                            prefix_str.trim_end_matches('/'),
+                            vrift_ipc::path_key_for(rel)
+                        )
                     };
                     if let Ok(Some(old_entry)) = audit.get(&key) {
                         if old_entry.vnode.content_hash != r.hash {
@@ -989,6 +1248,50 @@ async fn handle_request(
                 );
             }
 
+            // Large files: re-store as content-defined chunks (sub-file dedup)
+            let chunk_threshold = vrift_config::config().ingest.chunk_threshold;
+            let chunked = if chunk_threshold > 0 {
+                let large: HashSet<vrift_cas::Blake3Hash> = results
+                    .iter()
+                    .flatten()
+                    .filter(|r| !r.skipped_by_cache && r.size >= chunk_threshold)
+                    .map(|r| r.hash)
+                    .collect();
+                let cas_root = cas_root_path.clone();
+                tokio::task::spawn_blocking(move || chunk_large_blobs(&cas_root, large))
+                    .await
+                    .unwrap_or_default()
+            } else {
+                HashSet::new()
+            };
+
+            // --compress: re-store what is left whole as zstd where it pays
+            if compress {
+                let whole: HashSet<vrift_cas::Blake3Hash> = results
+                    .iter()
+                    .flatten()
+                    .filter(|r| !r.skipped_by_cache && !chunked.contains(&r.hash))
+                    .map(|r| r.hash)
+                    .collect();
+                let cas_root = cas_root_path.clone();
+                let _ = tokio::task::spawn_blocking(move || compress_blobs(&cas_root, whole)).await;
+            }
+
+            // Symlinks: the file pipelines skip them; record their targets
+            let symlinks = {
+                let (source, cas_root) = (source_path.clone(), cas_root_path.clone());
+                tokio::task::spawn_blocking(move || {
+                    vrift_cas::ingest_symlinks(&source, &cas_root, Some(ignore))
+                        .into_iter()
+                        .filter_map(|r| {
+                            r.map_err(|e| tracing::warn!("Symlink skipped: {}", e)).ok()
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .await
+                .unwrap_or_default()
+            };
+
             let duration = start.elapsed();
 
             // 6. Write LMDB manifest (RFC-0039 compatible with shim)
@@ -996,6 +1299,8 @@ async fn handle_request(
                 &manifest_out,
                 &source_path,
                 &results,
+                &symlinks,
+                &chunked,
                 tier1,
                 prefix.as_deref(),
             ) {
@@ -1026,16 +1331,71 @@ async fn handle_request(
     }
 }
 
+/// Split each blob into chunks, dropping the whole copy. Returns the hashes
+/// now stored chunked; a blob that fails to split stays whole.
+fn chunk_large_blobs(
+    cas_root: &Path,
+    hashes: HashSet<vrift_cas::Blake3Hash>,
+) -> HashSet<vrift_cas::Blake3Hash> {
+    let cas = match vrift_cas::CasStore::new(cas_root) {
+        Ok(cas) => cas,
+        Err(e) => {
+            tracing::warn!("Chunking skipped: {}", e);
+            return HashSet::new();
+        }
+    };
+    hashes
+        .into_iter()
+        .filter(|hash| match cas.split_blob(hash) {
+            Ok(list) => {
+                tracing::debug!(
+                    hash = %hex::encode(hash),
+                    chunks = list.chunks.len(),
+                    "Stored blob chunked"
+                );
+                true
+            }
+            Err(e) => {
+                tracing::warn!("Failed to chunk {}: {}", hex::encode(hash), e);
+                false
+            }
+        })
+        .collect()
+}
+
+/// Re-store each blob zstd-compressed where that pays off, dropping the
+/// whole copy. Incompressible blobs and failures stay whole.
+fn compress_blobs(cas_root: &Path, hashes: HashSet<vrift_cas::Blake3Hash>) {
+    let cas = match vrift_cas::CasStore::new(cas_root) {
+        Ok(cas) => cas,
+        Err(e) => {
+            tracing::warn!("Compression skipped: {}", e);
+            return;
+        }
+    };
+    let mut compressed = 0usize;
+    for hash in &hashes {
+        match cas.compress_blob(hash) {
+            Ok(true) => compressed += 1,
+            Ok(false) => {}
+            Err(e) => tracing::warn!("Failed to compress {}: {}", hex::encode(hash), e),
+        }
+    }
+    tracing::info!("Compressed {} of {} blobs", compressed, hashes.len());
+}
+
 /// Write manifest file from ingest results using LMDB format
 /// (RFC-0039: Compatible with cmd_ingest and shim)
 fn write_ingest_manifest(
     manifest_path: &Path,
     source_root: &Path,
     results: &[Result<vrift_cas::IngestResult, vrift_cas::CasError>],
+    symlinks: &[vrift_cas::SymlinkResult],
+    chunked: &HashSet<vrift_cas::Blake3Hash>,
     tier1: bool,
     prefix: Option<&str>,
 ) -> Result<()> {
-    use vrift_manifest::VnodeEntry;
+    use vrift_manifest::{VnodeEntry, VnodeFlags};
 
     // Open or create LMDB manifest
     let manifest = LmdbManifest::open(manifest_path)?;
@@ -1087,10 +1447,33 @@ fn write_ingest_manifest(
         manifest_key.push_str(&relative_path.to_string_lossy());
 
         // Create VnodeEntry
-        let vnode = VnodeEntry::new_file(result.hash, result.size, mtime, mode);
+        let mut vnode = VnodeEntry::new_file(result.hash, result.size, mtime, mode);
+        if chunked.contains(&result.hash) {
+            vnode.flags |= VnodeFlags::Chunked as u16;
+        }
 
         // Insert into LMDB manifest
         manifest.insert(&manifest_key, vnode, asset_tier);
+        let xattrs = read_xattrs(&result.source_path);
+        if !xattrs.is_empty() {
+            manifest.set_xattrs(&manifest_key, &xattrs)?;
+        }
+        let btime = read_btime(&result.source_path);
+        if btime.is_some() {
+            manifest.set_btime(&manifest_key, btime)?;
+        }
+    }
+
+    for link in symlinks {
+        let relative_path = link
+            .source_path
+            .strip_prefix(&canon_root)
+            .unwrap_or(&link.source_path);
+        manifest_key.clear();
+        manifest_key.push_str(prefix_trimmed);
+        manifest_key.push('/');
+        manifest_key.push_str(&relative_path.to_string_lossy());
+        manifest.insert_symlink(&manifest_key, &link.target, link.mtime, asset_tier);
     }
 
     // Commit delta layer to LMDB base layer (required for persistence!)
@@ -1101,6 +1484,25 @@ fn write_ingest_manifest(
     Ok(())
 }
 
+/// Xattrs to record for an ingested file, less `ingest.strip_xattrs`. The
+/// file may be gone (phantom mode moved it into the CAS); it then has none.
+fn read_xattrs(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let strip = vrift_config::config().ingest.strip_xattrs.clone();
+    vrift_manifest::xattr::read_from_disk(path, &strip).unwrap_or_default()
+}
+
+/// Creation time to record for an ingested file, if it is still there and
+/// its file system keeps one
+fn read_btime(path: &Path) -> Option<u64> {
+    let created = std::fs::symlink_metadata(path).ok()?.created().ok()?;
+    Some(
+        created
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs(),
+    )
+}
+
 async fn handle_protect(path_str: String, immutable: bool, owner: Option<String>) -> VeloResponse {
     // Security: Path sandboxing - reject suspicious paths
     if path_str.contains("..") || path_str.contains('\0') {
@@ -1159,49 +1561,6 @@ async fn handle_protect(path_str: String, immutable: bool, owner: Option<String>
     VeloResponse::ProtectAck
 }
 
-async fn handle_spawn(
-    command: Vec<String>,
-    env: Vec<(String, String)>,
-    cwd: String,
-) -> VeloResponse {
-    if command.is_empty() {
-        return VeloResponse::Error(VeloError::internal("Command cannot be empty"));
-    }
-
-    // For MVP, we just spawn the process and let it run detached
-    // In a real system, we'd track it in a ProcessManager struct
-    println!("Spawning: {:?} in {}", command, cwd);
-
-    let mut cmd = tokio::process::Command::new(&command[0]);
-    cmd.args(&command[1..]);
-    cmd.envs(env);
-    cmd.current_dir(cwd);
-
-    // We direct stdout/stderr to inherit for now, so they appear in daemon logs
-    // Ideally we would capture or stream them
-    // cmd.stdout(std::process::Stdio::inherit());
-    // cmd.stderr(std::process::Stdio::inherit());
-
-    match cmd.spawn() {
-        Ok(child) => {
-            let pid = child.id().unwrap_or(0);
-            println!("Spawned PID: {}", pid);
-
-            // Important: Avoid zombie processes.
-            // Since we're not waiting for it here (async handling), we drop the Child handle.
-            // But tokio::process::Command spawns are automatically reaped by tokio runtime if we don't await?
-            // Actually, we SHOULD store the child handle if we want to manage it.
-            // For this MVP step 1, we'll let it run.
-            tokio::spawn(async move {
-                let _ = child.wait_with_output().await;
-            });
-
-            VeloResponse::SpawnAck { pid }
-        }
-        Err(e) => VeloResponse::Error(VeloError::internal(format!("Failed to spawn: {}", e))),
-    }
-}
-
 async fn scan_cas_root(state: &DaemonState, cas_root_path: &str) -> Result<()> {
     let cas_root = vrift_manifest::normalize_path(cas_root_path);
 
@@ -1260,6 +1619,12 @@ async fn spawn_or_get_vdird(
         }
     }
 
+    if vrift_config::config().daemon.shared_vdird {
+        if let Some(vdird) = open_in_running_vdird(state, &project_root).await {
+            return Ok(vdird);
+        }
+    }
+
     tracing::info!("vriftd: Spawning vDird for: {:?}", project_root);
 
     // Compute project ID and paths
@@ -1338,6 +1703,56 @@ async fn spawn_or_get_vdird(
     Ok(vdird)
 }
 
+/// `[daemon] shared_vdird`: have a vDird already serving another project
+/// serve `project_root` too (`ProjectOpen`). None if there is none, or it
+/// refused; a vDird of its own is spawned then.
+async fn open_in_running_vdird(
+    state: &DaemonState,
+    project_root: &Path,
+) -> Option<Arc<VDirdProcess>> {
+    let running = {
+        let processes = state.vdird_processes.lock().unwrap();
+        processes
+            .values()
+            .find(|vdird| vdird.socket_path.exists())
+            .cloned()
+    }?;
+    let root = project_root.to_string_lossy();
+    let opened = match vrift_ipc::client::DaemonClient::connect_path(&running.socket_path).await {
+        Ok(mut client) => client.project_open(&root).await,
+        Err(e) => Err(e),
+    };
+    let endpoints = match opened {
+        Ok(endpoints) => endpoints,
+        Err(e) => {
+            tracing::warn!(
+                "vriftd: vDird pid={} can't serve {:?} too ({}), spawning one",
+                running.child_pid,
+                project_root,
+                e
+            );
+            return None;
+        }
+    };
+    tracing::info!(
+        "vriftd: vDird pid={} now also serves {:?}, socket={}",
+        running.child_pid,
+        project_root,
+        endpoints.vdird_socket
+    );
+
+    let vdird = Arc::new(VDirdProcess {
+        project_root: project_root.to_path_buf(),
+        project_id: endpoints.project_id,
+        socket_path: PathBuf::from(endpoints.vdird_socket),
+        vdir_mmap_path: PathBuf::from(endpoints.vdir_mmap_path),
+        child_pid: running.child_pid,
+    });
+    let mut processes = state.vdird_processes.lock().unwrap();
+    processes.insert(project_root.to_path_buf(), vdird.clone());
+    Some(vdird)
+}
+
 /// Find the vdir_d binary. Looks in same directory as vriftd, then falls back to PATH.
 fn find_vdird_binary() -> Result<PathBuf> {
     let current_exe = std::env::current_exe()?;
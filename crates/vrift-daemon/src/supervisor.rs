@@ -0,0 +1,419 @@
+//! Supervision of commands started by `Spawn` requests.
+//!
+//! Each child runs in its own process group with stdin closed and
+//! stdout/stderr piped into per-stream buffers, optionally teed to a log
+//! file. Clients read the buffers with `SpawnPoll` (a long poll from byte
+//! offsets) and learn the exit status from the same reply once all output
+//! has been returned. `Kill` signals the child's whole process group, so a
+//! cancelled build doesn't leave compilers running.
+//!
+//! A record stays around for `RETAIN_AFTER_EXIT` after the child exits so a
+//! slow client can still collect the tail and the status.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Notify;
+use vrift_ipc::{SpawnExit, SpawnOutput, VeloError};
+
+/// Output held per stream for pollers; older bytes are dropped
+const MAX_BUFFERED: usize = 4 << 20;
+/// Most bytes returned per stream by one poll
+const MAX_CHUNK: usize = 1 << 20;
+/// Longest a single poll may wait
+const MAX_POLL_WAIT: Duration = Duration::from_secs(30);
+/// How long after exit a child can still be polled
+const RETAIN_AFTER_EXIT: Duration = Duration::from_secs(600);
+/// How long to keep draining pipes after exit (a background grandchild
+/// can hold them open indefinitely)
+const DRAIN_AFTER_EXIT: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct OutputBuffer {
+    buf: Vec<u8>,
+    /// Stream offset of `buf[0]`
+    start: u64,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+        // Trim in batches so a chatty child doesn't memmove on every read
+        if self.buf.len() > MAX_BUFFERED + MAX_BUFFERED / 4 {
+            let excess = self.buf.len() - MAX_BUFFERED;
+            self.buf.drain(..excess);
+            self.start += excess as u64;
+        }
+    }
+
+    fn end(&self) -> u64 {
+        self.start + self.buf.len() as u64
+    }
+
+    /// Held bytes from `offset` on (clamped to what's held) and the offset
+    /// after them
+    fn read_from(&self, offset: u64) -> (Vec<u8>, u64) {
+        let from = offset.clamp(self.start, self.end());
+        let i = (from - self.start) as usize;
+        let n = (self.buf.len() - i).min(MAX_CHUNK);
+        (self.buf[i..i + n].to_vec(), from + n as u64)
+    }
+}
+
+#[derive(Default)]
+struct ChildState {
+    stdout: OutputBuffer,
+    stderr: OutputBuffer,
+    exit: Option<SpawnExit>,
+}
+
+impl ChildState {
+    fn read(&self, stdout_offset: u64, stderr_offset: u64) -> SpawnOutput {
+        let (stdout, stdout_offset) = self.stdout.read_from(stdout_offset);
+        let (stderr, stderr_offset) = self.stderr.read_from(stderr_offset);
+        let drained = stdout_offset == self.stdout.end() && stderr_offset == self.stderr.end();
+        SpawnOutput {
+            stdout,
+            stderr,
+            stdout_offset,
+            stderr_offset,
+            exit: self.exit.filter(|_| drained),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Supervised {
+    state: Mutex<ChildState>,
+    changed: Notify,
+}
+
+#[derive(Clone, Copy)]
+enum Pipe {
+    Stdout,
+    Stderr,
+}
+
+/// Children started by `Spawn`, keyed by pid
+#[derive(Default)]
+pub struct Supervisor {
+    children: Mutex<HashMap<u32, Arc<Supervised>>>,
+}
+
+impl Supervisor {
+    /// Start `command` and supervise it; returns its pid
+    pub fn spawn(
+        self: &Arc<Self>,
+        command: Vec<String>,
+        env: Vec<(String, String)>,
+        cwd: String,
+        log_path: Option<String>,
+    ) -> Result<u32, VeloError> {
+        let Some(program) = command.first() else {
+            return Err(VeloError::internal("Command cannot be empty"));
+        };
+        let log = match &log_path {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| {
+                        VeloError::io_error(format!("Cannot open log: {}", e)).set_path(path)
+                    })?;
+                Some(Arc::new(tokio::sync::Mutex::new(File::from_std(file))))
+            }
+            None => None,
+        };
+
+        let mut cmd = tokio::process::Command::new(program);
+        cmd.args(&command[1..])
+            .envs(env)
+            .current_dir(&cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| VeloError::internal(format!("Failed to spawn: {}", e)))?;
+        let pid = child.id().unwrap_or(0);
+        tracing::info!("vriftd: spawned pid={} {:?} in {}", pid, command, cwd);
+
+        let supervised = Arc::new(Supervised::default());
+        self.children
+            .lock()
+            .unwrap()
+            .insert(pid, supervised.clone());
+
+        let pumps = [
+            child
+                .stdout
+                .take()
+                .map(|p| pump(supervised.clone(), p, Pipe::Stdout, log.clone())),
+            child
+                .stderr
+                .take()
+                .map(|p| pump(supervised.clone(), p, Pipe::Stderr, log)),
+        ];
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let exit = match child.wait().await {
+                Ok(status) => SpawnExit {
+                    code: status.code(),
+                    signal: status.signal(),
+                },
+                Err(e) => {
+                    tracing::warn!("vriftd: wait for pid={} failed: {}", pid, e);
+                    SpawnExit {
+                        code: None,
+                        signal: None,
+                    }
+                }
+            };
+            for pump in pumps.into_iter().flatten() {
+                let abort = pump.abort_handle();
+                if tokio::time::timeout(DRAIN_AFTER_EXIT, pump).await.is_err() {
+                    abort.abort();
+                }
+            }
+            tracing::info!("vriftd: pid={} exited ({:?})", pid, exit);
+            supervised.state.lock().unwrap().exit = Some(exit);
+            supervised.changed.notify_waiters();
+
+            tokio::time::sleep(RETAIN_AFTER_EXIT).await;
+            supervisor.children.lock().unwrap().remove(&pid);
+        });
+        Ok(pid)
+    }
+
+    /// Output past the given offsets, waiting up to `wait_ms` for any
+    pub async fn poll(
+        &self,
+        pid: u32,
+        stdout_offset: u64,
+        stderr_offset: u64,
+        wait_ms: u32,
+    ) -> Result<SpawnOutput, VeloError> {
+        let child = self.get(pid)?;
+        let wait = Duration::from_millis(wait_ms as u64).min(MAX_POLL_WAIT);
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            // Register before checking so a write in between still wakes us
+            let notified = child.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let output = child
+                .state
+                .lock()
+                .unwrap()
+                .read(stdout_offset, stderr_offset);
+            let news = !output.stdout.is_empty() || !output.stderr.is_empty();
+            if news || output.exit.is_some() || tokio::time::Instant::now() >= deadline {
+                return Ok(output);
+            }
+            let _ = tokio::time::timeout_at(deadline, notified).await;
+        }
+    }
+
+    /// Send `signal` to the child's process group. A no-op once it exited.
+    pub fn kill(&self, pid: u32, signal: i32) -> Result<(), VeloError> {
+        let child = self.get(pid)?;
+        if !(1..=64).contains(&signal) {
+            return Err(VeloError::internal(format!("Invalid signal {}", signal)));
+        }
+        if child.state.lock().unwrap().exit.is_some() {
+            return Ok(());
+        }
+        tracing::info!("vriftd: sending signal {} to pid={}", signal, pid);
+        if unsafe { libc::kill(-(pid as libc::pid_t), signal) } != 0 {
+            let err = std::io::Error::last_os_error();
+            // The group leader may have moved on; signal the pid itself
+            if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+                return Err(VeloError::io_error(format!("kill {}: {}", pid, err)));
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, pid: u32) -> Result<Arc<Supervised>, VeloError> {
+        self.children
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .cloned()
+            .ok_or_else(|| VeloError::not_found(format!("No spawned process with pid {}", pid)))
+    }
+}
+
+/// Copy one pipe into the child's buffer (and the log) until EOF. The log
+/// is a tokio file, so a slow disk holds up this pump but not the runtime.
+fn pump(
+    child: Arc<Supervised>,
+    mut pipe: impl AsyncRead + Unpin + Send + 'static,
+    which: Pipe,
+    log: Option<Arc<tokio::sync::Mutex<File>>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 16 * 1024];
+        loop {
+            let n = match pipe.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            if let Some(log) = &log {
+                let _ = log.lock().await.write_all(&buf[..n]).await;
+            }
+            {
+                let mut state = child.state.lock().unwrap();
+                match which {
+                    Pipe::Stdout => state.stdout.push(&buf[..n]),
+                    Pipe::Stderr => state.stderr.push(&buf[..n]),
+                }
+            }
+            child.changed.notify_waiters();
+        }
+        if let Some(log) = &log {
+            let _ = log.lock().await.flush().await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    fn start(supervisor: &Arc<Supervisor>, script: &str, log: Option<String>) -> u32 {
+        supervisor
+            .spawn(sh(script), Vec::new(), "/".to_string(), log)
+            .unwrap()
+    }
+
+    /// Poll from the start until the child has exited and all its output
+    /// has been returned
+    async fn collect(supervisor: &Supervisor, pid: u32) -> (Vec<u8>, SpawnExit) {
+        let mut stdout = Vec::new();
+        let (mut out_off, mut err_off) = (0, 0);
+        loop {
+            let output = supervisor.poll(pid, out_off, err_off, 1000).await.unwrap();
+            stdout.extend_from_slice(&output.stdout);
+            (out_off, err_off) = (output.stdout_offset, output.stderr_offset);
+            if let Some(exit) = output.exit {
+                return (stdout, exit);
+            }
+        }
+    }
+
+    #[test]
+    fn test_output_buffer_trims_and_clamps_offsets() {
+        let mut buffer = OutputBuffer::default();
+        buffer.push(b"hello");
+        assert_eq!(buffer.read_from(2), (b"llo".to_vec(), 5));
+        // Past the end: nothing, at the end
+        assert_eq!(buffer.read_from(99), (Vec::new(), 5));
+
+        // Trimmed only once well over the cap, back down to it
+        let chunk = vec![b'x'; MAX_BUFFERED / 4];
+        for _ in 0..4 {
+            buffer.push(&chunk);
+        }
+        assert_eq!((buffer.start, buffer.buf.len()), (0, MAX_BUFFERED + 5));
+        buffer.push(b"y");
+        buffer.push(&chunk);
+        assert_eq!(buffer.buf.len(), MAX_BUFFERED);
+        assert_eq!(buffer.end(), 5 + 5 * chunk.len() as u64 + 1);
+        assert_eq!(buffer.start, buffer.end() - MAX_BUFFERED as u64);
+
+        // Before what's held: from the oldest byte still held, a chunk at
+        // a time
+        let (data, next) = buffer.read_from(0);
+        assert_eq!(data.len(), MAX_CHUNK);
+        assert_eq!(next, buffer.start + MAX_CHUNK as u64);
+        let (tail, next) = buffer.read_from(buffer.end() - 1);
+        assert_eq!((tail, next), (b"x".to_vec(), buffer.end()));
+    }
+
+    #[tokio::test]
+    async fn test_poll_wakes_on_new_output() {
+        let supervisor = Arc::new(Supervisor::default());
+        let pid = start(&supervisor, "sleep 0.3; echo ready; sleep 5", None);
+
+        let started = std::time::Instant::now();
+        let output = supervisor.poll(pid, 0, 0, 20_000).await.unwrap();
+        assert_eq!(output.stdout, b"ready\n");
+        assert!(output.exit.is_none());
+        assert!(started.elapsed() < Duration::from_secs(4));
+
+        supervisor.kill(pid, libc::SIGKILL).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_kill_reaches_process_group() {
+        let supervisor = Arc::new(Supervisor::default());
+        let pid = start(&supervisor, "sleep 100 & echo $!; wait", None);
+
+        let output = supervisor.poll(pid, 0, 0, 10_000).await.unwrap();
+        let grandchild: libc::pid_t = String::from_utf8(output.stdout)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+
+        supervisor.kill(pid, libc::SIGTERM).unwrap();
+        let (_, exit) = collect(&supervisor, pid).await;
+        assert_eq!(exit.signal, Some(libc::SIGTERM));
+
+        // The background sleep got the signal too: gone, or a zombie
+        // waiting for a reaper
+        let alive = || {
+            std::fs::read_to_string(format!("/proc/{}/stat", grandchild))
+                .is_ok_and(|stat| !stat.contains(") Z "))
+        };
+        for _ in 0..50 {
+            if !alive() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!alive(), "background child {} survived", grandchild);
+    }
+
+    #[tokio::test]
+    async fn test_exited_child_forgotten_after_retention() {
+        let supervisor = Arc::new(Supervisor::default());
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("out.log");
+        let pid = start(
+            &supervisor,
+            "echo out; echo err >&2; exit 3",
+            Some(log.to_string_lossy().into_owned()),
+        );
+
+        let (stdout, exit) = collect(&supervisor, pid).await;
+        assert_eq!(stdout, b"out\n");
+        assert_eq!(exit.code, Some(3));
+        let mut logged = std::fs::read_to_string(&log).unwrap();
+        logged = logged.lines().collect::<Vec<_>>().join(",");
+        assert!(logged == "out,err" || logged == "err,out", "{}", logged);
+
+        // Still there for a slow client, then dropped
+        tokio::time::pause();
+        tokio::time::advance(RETAIN_AFTER_EXIT - Duration::from_secs(1)).await;
+        assert!(supervisor.poll(pid, 0, 0, 0).await.is_ok());
+        tokio::time::advance(Duration::from_secs(2)).await;
+        tokio::task::yield_now().await;
+        let err = supervisor.poll(pid, 0, 0, 0).await.unwrap_err();
+        assert_eq!(err.kind, vrift_ipc::VeloErrorKind::NotFound);
+    }
+}
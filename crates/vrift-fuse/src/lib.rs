@@ -3,92 +3,234 @@
 //! FUSE filesystem implementation for Velo Rift.
 //!
 //! Maps the Velo Manifest and CAS to a FUSE filesystem.
-//! - Inodes are assigned sequentially based on manifest entries.
-//! - Read operations fetch from CAS.
+//! - Inode numbers, link counts and block counts follow
+//!   `vrift_ipc::identity`, the scheme the inception layer reports too.
+//!   Given the project's LMDB manifest ([`VeloFs::with_inode_table`]),
+//!   inodes come from its inode table, the one vDird serves to the
+//!   inception layer.
+//! - Read operations fetch only the requested byte range from the
+//!   [`BlobSource`] (a CAS directory or a single-file bundle).
+//! - Each blob is hash-verified once, on its first read.
 //! - Metadata comes from Manifest.
+//! - With [`WriteBack`] the mount is writable: files are staged while open
+//!   and committed to the CAS and manifest on release.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub mod writeback;
+
+pub use vrift_pack::BlobSource;
+pub use writeback::WriteBack;
+
+/// Fetch counters for a mount, shared with the caller via [`VeloFs::stats`]
+#[derive(Debug, Default)]
+pub struct FetchStats {
+    pub reads: AtomicU64,
+    pub bytes: AtomicU64,
+    pub total_us: AtomicU64,
+    pub max_us: AtomicU64,
+    pub verified_blobs: AtomicU64,
+    pub verify_failures: AtomicU64,
+}
+
+impl FetchStats {
+    pub fn record_fetch(&self, bytes: usize, elapsed: std::time::Duration) {
+        let us = elapsed.as_micros() as u64;
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.total_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    /// One-line summary: reads, bytes, avg/max fetch latency, verification
+    pub fn summary(&self) -> String {
+        let reads = self.reads.load(Ordering::Relaxed);
+        let avg_us = self
+            .total_us
+            .load(Ordering::Relaxed)
+            .checked_div(reads)
+            .unwrap_or(0);
+        format!(
+            "{} reads, {} bytes, fetch avg {}us max {}us, {} blobs verified ({} failed)",
+            reads,
+            self.bytes.load(Ordering::Relaxed),
+            avg_us,
+            self.max_us.load(Ordering::Relaxed),
+            self.verified_blobs.load(Ordering::Relaxed),
+            self.verify_failures.load(Ordering::Relaxed),
+        )
+    }
+}
 
 #[cfg(all(feature = "fuse", target_os = "linux"))]
 mod imp {
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
     use std::ffi::OsStr;
-    use std::path::Path;
-    use std::time::{Duration, UNIX_EPOCH};
+    use std::fs::File;
+    use std::os::unix::fs::FileExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
     use fuser::{
-        FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request,
+        FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
+        ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, ReplyXattr, Request, TimeOrNow,
     };
-    use libc::{c_int, ENOENT};
-    use vrift_cas::CasStore;
-    use vrift_manifest::{Manifest, VnodeEntry};
+    use libc::{c_int, EBADF, EEXIST, EINVAL, EIO, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY, EROFS};
+    use vrift_ipc::identity;
+    use vrift_manifest::{LmdbManifest, Manifest, VnodeEntry};
+
+    use super::writeback::{now_secs, WriteBack};
+    use super::{BlobSource, FetchStats};
 
     const TTL: Duration = Duration::from_secs(60);
-    const BLOCK_SIZE: u64 = 4096;
+
+    /// A live background mount (see [`VeloFs::spawn_mount`])
+    pub struct BackgroundMount {
+        _session: fuser::BackgroundSession,
+    }
 
     struct InodeEntry {
+        path: String,
         path_hash: vrift_manifest::PathHash,
         attr: FileAttr,
         children: Vec<(String, u64)>, // Name -> Inode
+        xattrs: vrift_manifest::Xattrs,
+    }
+
+    /// A file open for writing on a writable mount
+    struct Staged {
+        path: PathBuf,
+        file: File,
+        writers: usize,
     }
 
     pub struct VeloFs {
-        cas: CasStore,
+        source: Box<dyn BlobSource>,
         inodes: HashMap<u64, InodeEntry>,
         path_to_inode: HashMap<String, u64>,
+        /// Where inodes come from, if not the path hash (see `alloc_ino`)
+        inode_table: Option<Arc<LmdbManifest>>,
+        /// Blobs whose content already matched their hash
+        verified: HashSet<vrift_manifest::PathHash>,
+        stats: Arc<FetchStats>,
+        /// Set on writable mounts
+        writeback: Option<WriteBack>,
+        next_fh: u64,
+        /// Write handles: fh -> inode
+        handles: HashMap<u64, u64>,
+        /// Files open for writing, by inode
+        staged: HashMap<u64, Staged>,
     }
 
     impl VeloFs {
-        pub fn new(manifest: &Manifest, cas: CasStore) -> Self {
+        pub fn new(manifest: &Manifest, source: impl BlobSource) -> Self {
+            Self::with_inode_table(manifest, source, None)
+        }
+
+        /// [`new`](Self::new), numbering files from the inode table of
+        /// `inode_table` (the project's LMDB manifest) so they keep the
+        /// inodes the inception layer reports for them. Paths the table
+        /// lacks are allocated there; renames and removals update it.
+        pub fn with_inode_table(
+            manifest: &Manifest,
+            source: impl BlobSource,
+            inode_table: Option<Arc<LmdbManifest>>,
+        ) -> Self {
             let mut fs = Self {
-                cas,
+                source: Box::new(source),
                 inodes: HashMap::new(),
                 path_to_inode: HashMap::new(),
+                inode_table,
+                verified: HashSet::new(),
+                stats: Arc::new(FetchStats::default()),
+                writeback: None,
+                next_fh: 1,
+                handles: HashMap::new(),
+                staged: HashMap::new(),
             };
             fs.init_from_manifest(manifest);
             fs
         }
 
+        /// Make the mount writable, committing changes through `writeback`.
+        /// Its manifest should be the one the filesystem was built from.
+        pub fn with_write_back(mut self, writeback: WriteBack) -> Self {
+            self.writeback = Some(writeback);
+            self
+        }
+
+        fn mount_options(&self) -> Vec<fuser::MountOption> {
+            let mut opts = vec![fuser::MountOption::FSName("vrift".to_string())];
+            if self.writeback.is_none() {
+                opts.push(fuser::MountOption::RO);
+            }
+            opts
+        }
+
+        /// Fetch counters; stays readable after `mount` consumes the filesystem
+        pub fn stats(&self) -> Arc<FetchStats> {
+            self.stats.clone()
+        }
+
         /// Mount the filesystem at the given path (Ref: <https://docs.rs/fuser>)
         pub fn mount(self, mountpoint: &Path) -> anyhow::Result<()> {
             // Performance Optimization: TTL=60s (Implies metadata caching).
             // auto_cache / kernel_cache causing issues with current fuser/libfuse version in CI.
             // TTL=60s provides significant getattr reduction.
 
-            let opts = vec![
-                fuser::MountOption::RO,
-                fuser::MountOption::FSName("vrift".to_string()),
-            ];
-
+            let opts = self.mount_options();
             fuser::mount2(self, mountpoint, &opts)?;
             Ok(())
         }
 
+        /// Mount in a background thread; unmounted when the handle drops
+        pub fn spawn_mount(self, mountpoint: &Path) -> anyhow::Result<BackgroundMount> {
+            let opts = self.mount_options();
+            Ok(BackgroundMount {
+                _session: fuser::spawn_mount2(self, mountpoint, &opts)?,
+            })
+        }
+
         fn init_from_manifest(&mut self, manifest: &Manifest) {
             // 1. Assign inodes to all paths
-            let mut next_inode = 2; // 1 is root
-
             // Ensure root exists
             self.inodes.insert(
-                1,
+                identity::ROOT_INO,
                 InodeEntry {
+                    path: "/".to_string(),
                     path_hash: [0; 32], // Dummy
-                    attr: Self::default_dir_attr(1),
+                    attr: Self::default_dir_attr(identity::ROOT_INO),
                     children: Vec::new(),
+                    xattrs: Vec::new(),
                 },
             );
-            self.path_to_inode.insert("/".to_string(), 1);
+            self.path_to_inode
+                .insert("/".to_string(), identity::ROOT_INO);
 
             // Sort paths to process parents before children (ensures directory structure)
-            let mut paths: Vec<&str> = manifest.paths().collect();
+            let mut paths: Vec<&str> = manifest.paths().filter(|p| *p != "/").collect();
             paths.sort();
 
-            for path in paths {
-                if path == "/" {
-                    continue;
-                } // Already handled
-
-                let inode = next_inode;
-                next_inode += 1;
+            // One transaction numbers every path from the table up front
+            let mut table_inos = match &self.inode_table {
+                Some(table) => table
+                    .inodes()
+                    .assign_all(paths.iter().copied())
+                    .unwrap_or_else(|e| {
+                        log::warn!("Inode table unavailable, using path hashes: {}", e);
+                        Vec::new()
+                    }),
+                None => Vec::new(),
+            }
+            .into_iter();
 
+            for path in paths {
+                let inode = match table_inos.next() {
+                    Some(ino) if !self.inodes.contains_key(&ino) => ino,
+                    _ => self.hash_ino(path),
+                };
                 self.path_to_inode.insert(path.to_string(), inode);
 
                 let entry = manifest.get(path).unwrap();
@@ -97,9 +239,11 @@ mod imp {
                 self.inodes.insert(
                     inode,
                     InodeEntry {
+                        path: path.to_string(),
                         path_hash: entry.content_hash,
                         attr,
                         children: Vec::new(),
+                        xattrs: manifest.xattrs(path),
                     },
                 );
 
@@ -128,6 +272,215 @@ mod imp {
             }
         }
 
+        /// Inode for a new entry at `path`: the one the inode table has for
+        /// it (allocated there if new), else [`hash_ino`](Self::hash_ino)
+        fn alloc_ino(&self, path: &str) -> u64 {
+            if let Some(table) = &self.inode_table {
+                match table.inodes().assign(path) {
+                    Ok(ino) if !self.inodes.contains_key(&ino) => return ino,
+                    Ok(ino) => log::warn!("Inode {} of {} already in use", ino, path),
+                    Err(e) => log::warn!("Inode allocation for {} failed: {}", path, e),
+                }
+            }
+            self.hash_ino(path)
+        }
+
+        /// The identity inode of `path`, probed past any taken by a
+        /// colliding path
+        fn hash_ino(&self, path: &str) -> u64 {
+            let mut ino = identity::ino(path);
+            while self.inodes.contains_key(&ino) {
+                ino = identity::next_ino(ino);
+            }
+            ino
+        }
+
+        fn child(&self, parent: u64, name: &str) -> Option<u64> {
+            self.inodes
+                .get(&parent)?
+                .children
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, ino)| *ino)
+        }
+
+        /// Manifest path of `name` in directory `parent`
+        fn child_path(&self, parent: u64, name: &str) -> Result<String, c_int> {
+            let dir = self.inodes.get(&parent).ok_or(ENOENT)?;
+            if dir.attr.kind != FileType::Directory {
+                return Err(ENOTDIR);
+            }
+            Ok(match dir.path.as_str() {
+                "/" => format!("/{}", name),
+                p => format!("{}/{}", p, name),
+            })
+        }
+
+        /// Check that the mount is writable and `name` is free in `parent`
+        fn new_child_path(&self, parent: u64, name: &OsStr) -> Result<(String, String), c_int> {
+            if self.writeback.is_none() {
+                return Err(EROFS);
+            }
+            let name = name.to_str().ok_or(EINVAL)?;
+            let path = self.child_path(parent, name)?;
+            if self.child(parent, name).is_some() {
+                return Err(EEXIST);
+            }
+            Ok((name.to_string(), path))
+        }
+
+        fn add_inode(
+            &mut self,
+            parent: u64,
+            name: String,
+            path: String,
+            vnode: &VnodeEntry,
+        ) -> FileAttr {
+            let ino = self.alloc_ino(&path);
+            let attr = Self::vnode_to_attr(ino, vnode);
+            self.path_to_inode.insert(path.clone(), ino);
+            self.inodes.insert(
+                ino,
+                InodeEntry {
+                    path,
+                    path_hash: vnode.content_hash,
+                    attr,
+                    children: Vec::new(),
+                    xattrs: Vec::new(),
+                },
+            );
+            if let Some(dir) = self.inodes.get_mut(&parent) {
+                dir.children.push((name, ino));
+            }
+            attr
+        }
+
+        /// Unlink `name` from `parent` and forget its subtree
+        fn detach(&mut self, parent: u64, name: &str) {
+            let Some(ino) = self.child(parent, name) else {
+                return;
+            };
+            if let Some(dir) = self.inodes.get_mut(&parent) {
+                dir.children.retain(|(n, _)| n != name);
+            }
+            let mut stack = vec![ino];
+            while let Some(ino) = stack.pop() {
+                if let Some(entry) = self.inodes.remove(&ino) {
+                    self.path_to_inode.remove(&entry.path);
+                    // Whatever gets created there next is another file
+                    if let Some(table) = &self.inode_table {
+                        if let Err(e) = table.inodes().release(&entry.path) {
+                            log::warn!("Inode of {} not released: {}", entry.path, e);
+                        }
+                    }
+                    stack.extend(entry.children.iter().map(|(_, c)| *c));
+                }
+            }
+        }
+
+        /// Point `ino` and its subtree at `path`
+        fn repath(&mut self, ino: u64, path: String) {
+            let Some(entry) = self.inodes.get_mut(&ino) else {
+                return;
+            };
+            self.path_to_inode.remove(&entry.path);
+            self.path_to_inode.insert(path.clone(), ino);
+            if let Some(table) = &self.inode_table {
+                if let Err(e) = table.inodes().rename(&entry.path, &path) {
+                    log::warn!("Inode of {} not moved to {}: {}", entry.path, path, e);
+                }
+            }
+            entry.path = path.clone();
+            let children = entry.children.clone();
+            for (name, child) in children {
+                self.repath(child, format!("{}/{}", path, name));
+            }
+        }
+
+        /// Copy the manifest entry's blob (or nothing) into a staging file
+        /// for `ino`, unless it is already staged. `truncate` empties an
+        /// existing staged copy, which other writers share.
+        fn stage(&mut self, ino: u64, truncate: bool) -> Result<&mut Staged, c_int> {
+            if let Some(staged) = self.staged.get(&ino) {
+                if truncate {
+                    staged
+                        .file
+                        .set_len(0)
+                        .map_err(|e| e.raw_os_error().unwrap_or(EIO))?;
+                }
+            } else {
+                let entry = self.inodes.get(&ino).ok_or(ENOENT)?;
+                if entry.attr.kind != FileType::RegularFile {
+                    return Err(EISDIR);
+                }
+                let content = (!truncate && entry.attr.size > 0).then_some(entry.path_hash);
+                let writeback = self.writeback.as_mut().ok_or(EROFS)?;
+                let (path, file) = writeback.stage(content.as_ref()).map_err(|e| {
+                    log::error!("Staging inode {} failed: {}", ino, e);
+                    EIO
+                })?;
+                self.staged.insert(
+                    ino,
+                    Staged {
+                        path,
+                        file,
+                        writers: 0,
+                    },
+                );
+            }
+            Ok(self.staged.get_mut(&ino).unwrap())
+        }
+
+        /// Commit a staged file once nothing has it open for writing.
+        /// Dropped if the file was unlinked meanwhile.
+        fn commit(&mut self, ino: u64) -> Result<(), c_int> {
+            if self.staged.get(&ino).is_none_or(|s| s.writers > 0) {
+                return Ok(());
+            }
+            let staged = self.staged.remove(&ino).unwrap();
+            let Some(writeback) = self.writeback.as_mut() else {
+                return Err(EROFS);
+            };
+            let Some(entry) = self.inodes.get_mut(&ino) else {
+                writeback.discard(&staged.path);
+                return Ok(());
+            };
+            drop(staged.file);
+            let vnode = writeback
+                .commit(&entry.path, &staged.path, entry.attr.perm as u32)
+                .map_err(|e| {
+                    log::error!("Committing {} failed: {}", entry.path, e);
+                    EIO
+                })?;
+            entry.path_hash = vnode.content_hash;
+            entry.attr.size = vnode.size;
+            entry.attr.blocks = identity::blocks(vnode.size);
+            // Hashed on the way in, so reads need not verify it again
+            self.verified.insert(vnode.content_hash);
+            Ok(())
+        }
+
+        /// Record attribute changes of an unstaged entry in the manifest
+        /// (staged files pick them up when committed)
+        fn persist_attr(&mut self, ino: u64) -> Result<(), c_int> {
+            if self.staged.contains_key(&ino) {
+                return Ok(());
+            }
+            let entry = self.inodes.get(&ino).ok_or(ENOENT)?;
+            let writeback = self.writeback.as_mut().ok_or(EROFS)?;
+            let Some(mut vnode) = writeback.manifest().get(&entry.path).cloned() else {
+                return Ok(());
+            };
+            vnode.mode = entry.attr.perm as u32;
+            vnode.mtime = entry
+                .attr
+                .mtime
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            writeback.upsert(&entry.path, vnode).map_err(|_| EIO)
+        }
+
         fn default_dir_attr(inode: u64) -> FileAttr {
             FileAttr {
                 ino: inode,
@@ -139,12 +492,12 @@ mod imp {
                 crtime: UNIX_EPOCH,
                 kind: FileType::Directory,
                 perm: 0o755,
-                nlink: 2,
+                nlink: identity::nlink(true),
                 uid: 0,
                 gid: 0,
                 rdev: 0,
                 flags: 0,
-                blksize: BLOCK_SIZE as u32,
+                blksize: identity::BLKSIZE,
             }
         }
 
@@ -152,23 +505,25 @@ mod imp {
             FileAttr {
                 ino: inode,
                 size: vnode.size,
-                blocks: vnode.size.div_ceil(BLOCK_SIZE),
+                blocks: identity::blocks(vnode.size),
                 atime: UNIX_EPOCH + Duration::from_secs(vnode.mtime),
                 mtime: UNIX_EPOCH + Duration::from_secs(vnode.mtime),
                 ctime: UNIX_EPOCH + Duration::from_secs(vnode.mtime),
                 crtime: UNIX_EPOCH + Duration::from_secs(vnode.mtime),
                 kind: if vnode.is_dir() {
                     FileType::Directory
+                } else if vnode.is_symlink() {
+                    FileType::Symlink
                 } else {
                     FileType::RegularFile
                 },
                 perm: vnode.mode as u16,
-                nlink: if vnode.is_dir() { 2 } else { 1 },
+                nlink: identity::nlink(vnode.is_dir()),
                 uid: 0,
                 gid: 0,
                 rdev: 0,
                 flags: 0,
-                blksize: BLOCK_SIZE as u32,
+                blksize: identity::BLKSIZE,
             }
         }
     }
@@ -203,6 +558,59 @@ mod imp {
             }
         }
 
+        /// Attributes recorded in the manifest; read-only, like the
+        /// manifest metadata behind getattr
+        fn getxattr(
+            &mut self,
+            _req: &Request,
+            ino: u64,
+            name: &OsStr,
+            size: u32,
+            reply: ReplyXattr,
+        ) {
+            let Some(entry) = self.inodes.get(&ino) else {
+                reply.error(ENOENT);
+                return;
+            };
+            match entry.xattrs.iter().find(|(n, _)| OsStr::new(n) == name) {
+                Some((_, value)) => reply_xattr(reply, size, value),
+                None => reply.error(libc::ENODATA),
+            }
+        }
+
+        fn listxattr(&mut self, _req: &Request, ino: u64, size: u32, reply: ReplyXattr) {
+            let Some(entry) = self.inodes.get(&ino) else {
+                reply.error(ENOENT);
+                return;
+            };
+            let mut names = Vec::new();
+            for (name, _) in &entry.xattrs {
+                names.extend_from_slice(name.as_bytes());
+                names.push(0);
+            }
+            reply_xattr(reply, size, &names);
+        }
+
+        /// The target is the symlink's blob. The kernel follows it, so
+        /// chains are bounded by its MAXSYMLINKS (40) like everywhere else.
+        fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+            let Some(entry) = self.inodes.get(&ino) else {
+                reply.error(ENOENT);
+                return;
+            };
+            if entry.attr.kind != FileType::Symlink {
+                reply.error(libc::EINVAL);
+                return;
+            }
+            match self
+                .source
+                .read_range(&entry.path_hash, 0, entry.attr.size as usize)
+            {
+                Ok(target) => reply.data(&target),
+                Err(_) => reply.error(libc::EIO),
+            }
+        }
+
         fn read(
             &mut self,
             _req: &Request,
@@ -222,17 +630,44 @@ mod imp {
                 }
             };
 
-            match self.cas.get(&entry.path_hash) {
-                Ok(data) => {
-                    let offset = offset as usize;
-                    let size = size as usize;
-                    if offset >= data.len() {
-                        reply.data(&[]);
-                    } else {
-                        let end = (offset + size).min(data.len());
-                        reply.data(&data[offset..end]);
+            // Being written: the staged copy is the current content
+            if let Some(staged) = self.staged.get(&ino) {
+                let mut buf = vec![0u8; size as usize];
+                match staged.file.read_at(&mut buf, offset.max(0) as u64) {
+                    Ok(n) => reply.data(&buf[..n]),
+                    Err(e) => reply.error(e.raw_os_error().unwrap_or(EIO)),
+                }
+                return;
+            }
+
+            let hash = entry.path_hash;
+
+            // Verify the whole blob once so ranged reads can't serve bytes
+            // from a corrupted object
+            if !self.verified.contains(&hash) {
+                match self.source.verify(&hash) {
+                    Ok(()) => {
+                        self.verified.insert(hash);
+                        self.stats.verified_blobs.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        log::error!("Blob verification failed for inode {}: {}", ino, e);
+                        self.stats.verify_failures.fetch_add(1, Ordering::Relaxed);
+                        reply.error(libc::EIO);
+                        return;
                     }
                 }
+            }
+
+            let start = Instant::now();
+            match self
+                .source
+                .read_range(&hash, offset.max(0) as u64, size as usize)
+            {
+                Ok(data) => {
+                    self.stats.record_fetch(data.len(), start.elapsed());
+                    reply.data(&data);
+                }
                 Err(_) => reply.error(libc::EIO),
             }
         }
@@ -257,8 +692,16 @@ mod imp {
                 if reply.add(ino, 0, FileType::Directory, ".") {
                     return;
                 }
-                if reply.add(1, 1, FileType::Directory, "..") {
-                    // Parent hardcoded to 1 for simplicity for now
+                let parent = match entry.path.rsplit_once('/') {
+                    Some((p, _)) if !p.is_empty() => p,
+                    _ => "/",
+                };
+                let parent_ino = self
+                    .path_to_inode
+                    .get(parent)
+                    .copied()
+                    .unwrap_or(identity::ROOT_INO);
+                if reply.add(parent_ino, 1, FileType::Directory, "..") {
                     return;
                 }
             }
@@ -282,19 +725,575 @@ mod imp {
             }
             reply.ok();
         }
+
+        fn open(&mut self, _req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
+            match self.open_file(ino, flags) {
+                Ok(fh) => reply.opened(fh, 0),
+                Err(e) => reply.error(e),
+            }
+        }
+
+        fn create(
+            &mut self,
+            _req: &Request,
+            parent: u64,
+            name: &OsStr,
+            mode: u32,
+            umask: u32,
+            _flags: i32,
+            reply: ReplyCreate,
+        ) {
+            match self.create_file(parent, name, mode, umask) {
+                Ok((attr, fh)) => reply.created(&TTL, &attr, 0, fh, 0),
+                Err(e) => reply.error(e),
+            }
+        }
+
+        fn write(
+            &mut self,
+            _req: &Request,
+            ino: u64,
+            fh: u64,
+            offset: i64,
+            data: &[u8],
+            _write_flags: u32,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            reply: ReplyWrite,
+        ) {
+            match self.write_file(ino, fh, offset, data) {
+                Ok(written) => reply.written(written),
+                Err(e) => reply.error(e),
+            }
+        }
+
+        fn flush(&mut self, _req: &Request, _ino: u64, _fh: u64, _lock: u64, reply: ReplyEmpty) {
+            reply.ok();
+        }
+
+        fn fsync(
+            &mut self,
+            _req: &Request,
+            ino: u64,
+            _fh: u64,
+            _datasync: bool,
+            reply: ReplyEmpty,
+        ) {
+            match self.staged.get(&ino).map(|s| s.file.sync_all()) {
+                Some(Err(e)) => reply.error(e.raw_os_error().unwrap_or(EIO)),
+                _ => reply.ok(),
+            }
+        }
+
+        fn release(
+            &mut self,
+            _req: &Request,
+            ino: u64,
+            fh: u64,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            _flush: bool,
+            reply: ReplyEmpty,
+        ) {
+            reply_empty(reply, self.release_file(ino, fh));
+        }
+
+        fn setattr(
+            &mut self,
+            _req: &Request,
+            ino: u64,
+            mode: Option<u32>,
+            _uid: Option<u32>,
+            _gid: Option<u32>,
+            size: Option<u64>,
+            _atime: Option<TimeOrNow>,
+            mtime: Option<TimeOrNow>,
+            _ctime: Option<SystemTime>,
+            _fh: Option<u64>,
+            _crtime: Option<SystemTime>,
+            _chgtime: Option<SystemTime>,
+            _bkuptime: Option<SystemTime>,
+            _flags: Option<u32>,
+            reply: ReplyAttr,
+        ) {
+            match self.set_attr(ino, mode, size, mtime) {
+                Ok(attr) => reply.attr(&TTL, &attr),
+                Err(e) => reply.error(e),
+            }
+        }
+
+        fn mkdir(
+            &mut self,
+            _req: &Request,
+            parent: u64,
+            name: &OsStr,
+            mode: u32,
+            umask: u32,
+            reply: ReplyEntry,
+        ) {
+            let (name, path) = match self.new_child_path(parent, name) {
+                Ok(p) => p,
+                Err(e) => {
+                    reply.error(e);
+                    return;
+                }
+            };
+            let vnode = VnodeEntry::new_directory(now_secs(), mode & !umask & 0o7777);
+            if let Err(e) = self
+                .writeback
+                .as_mut()
+                .unwrap()
+                .upsert(&path, vnode.clone())
+            {
+                log::error!("mkdir {} failed: {}", path, e);
+                reply.error(EIO);
+                return;
+            }
+            let attr = self.add_inode(parent, name, path, &vnode);
+            reply.entry(&TTL, &attr, 0);
+        }
+
+        fn unlink(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+            reply_empty(reply, self.remove_child(parent, name, false));
+        }
+
+        fn rmdir(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+            reply_empty(reply, self.remove_child(parent, name, true));
+        }
+
+        fn rename(
+            &mut self,
+            _req: &Request,
+            parent: u64,
+            name: &OsStr,
+            newparent: u64,
+            newname: &OsStr,
+            flags: u32,
+            reply: ReplyEmpty,
+        ) {
+            reply_empty(
+                reply,
+                self.rename_child(parent, name, newparent, newname, flags),
+            );
+        }
+    }
+
+    /// Answer an xattr read: the size alone for a `size` 0 probe, ERANGE if
+    /// the caller's buffer is too small
+    fn reply_xattr(reply: ReplyXattr, size: u32, data: &[u8]) {
+        if size == 0 {
+            reply.size(data.len() as u32);
+        } else if data.len() > size as usize {
+            reply.error(libc::ERANGE);
+        } else {
+            reply.data(data);
+        }
+    }
+
+    fn reply_empty(reply: ReplyEmpty, result: Result<(), c_int>) {
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    impl VeloFs {
+        fn new_handle(&mut self, ino: u64) -> u64 {
+            let fh = self.next_fh;
+            self.next_fh += 1;
+            self.handles.insert(fh, ino);
+            fh
+        }
+
+        /// Open `ino`; opening for writing stages it. Returns the file
+        /// handle (0 for read-only opens, which need none).
+        fn open_file(&mut self, ino: u64, flags: i32) -> Result<u64, c_int> {
+            if flags & libc::O_ACCMODE == libc::O_RDONLY {
+                return Ok(0);
+            }
+            if self.writeback.is_none() {
+                return Err(EROFS);
+            }
+            let truncate = flags & libc::O_TRUNC != 0;
+            self.stage(ino, truncate)?.writers += 1;
+            if truncate {
+                if let Some(entry) = self.inodes.get_mut(&ino) {
+                    entry.attr.size = 0;
+                    entry.attr.blocks = 0;
+                }
+            }
+            Ok(self.new_handle(ino))
+        }
+
+        /// Create an empty file open for writing. It reaches the manifest
+        /// when released.
+        fn create_file(
+            &mut self,
+            parent: u64,
+            name: &OsStr,
+            mode: u32,
+            umask: u32,
+        ) -> Result<(FileAttr, u64), c_int> {
+            let (name, path) = self.new_child_path(parent, name)?;
+            let vnode = VnodeEntry::new_file([0; 32], 0, now_secs(), mode & !umask & 0o7777);
+            let attr = self.add_inode(parent, name.clone(), path, &vnode);
+            match self.stage(attr.ino, true) {
+                Ok(staged) => staged.writers += 1,
+                Err(e) => {
+                    self.detach(parent, &name);
+                    return Err(e);
+                }
+            }
+            Ok((attr, self.new_handle(attr.ino)))
+        }
+
+        fn write_file(
+            &mut self,
+            ino: u64,
+            fh: u64,
+            offset: i64,
+            data: &[u8],
+        ) -> Result<u32, c_int> {
+            let staged = self
+                .handles
+                .get(&fh)
+                .and_then(|i| self.staged.get(i))
+                .ok_or(EBADF)?;
+            let offset = offset.max(0) as u64;
+            staged
+                .file
+                .write_all_at(data, offset)
+                .map_err(|e| e.raw_os_error().unwrap_or(EIO))?;
+            if let Some(entry) = self.inodes.get_mut(&ino) {
+                entry.attr.size = entry.attr.size.max(offset + data.len() as u64);
+                entry.attr.blocks = identity::blocks(entry.attr.size);
+                entry.attr.mtime = SystemTime::now();
+            }
+            Ok(data.len() as u32)
+        }
+
+        /// Close a handle; the last writer commits the staged copy
+        fn release_file(&mut self, ino: u64, fh: u64) -> Result<(), c_int> {
+            if self.handles.remove(&fh).is_none() {
+                return Ok(());
+            }
+            if let Some(staged) = self.staged.get_mut(&ino) {
+                staged.writers -= 1;
+            }
+            self.commit(ino)
+        }
+
+        fn set_attr(
+            &mut self,
+            ino: u64,
+            mode: Option<u32>,
+            size: Option<u64>,
+            mtime: Option<TimeOrNow>,
+        ) -> Result<FileAttr, c_int> {
+            let attr = self.inodes.get(&ino).ok_or(ENOENT)?.attr;
+            if mode.is_none() && size.is_none() && mtime.is_none() {
+                return Ok(attr);
+            }
+            if self.writeback.is_none() {
+                return Err(EROFS);
+            }
+
+            if let Some(size) = size {
+                self.stage(ino, size == 0)?
+                    .file
+                    .set_len(size)
+                    .map_err(|e| e.raw_os_error().unwrap_or(EIO))?;
+                let entry = self.inodes.get_mut(&ino).unwrap();
+                entry.attr.size = size;
+                entry.attr.blocks = identity::blocks(size);
+            }
+            let entry = self.inodes.get_mut(&ino).unwrap();
+            if let Some(mode) = mode {
+                entry.attr.perm = (mode & 0o7777) as u16;
+            }
+            match mtime {
+                Some(TimeOrNow::SpecificTime(t)) => entry.attr.mtime = t,
+                Some(TimeOrNow::Now) => entry.attr.mtime = SystemTime::now(),
+                None if size.is_some() => entry.attr.mtime = SystemTime::now(),
+                None => {}
+            }
+
+            // A truncate outside an open write handle commits right away
+            match size {
+                Some(_) => self.commit(ino)?,
+                None => self.persist_attr(ino)?,
+            }
+            Ok(self.inodes[&ino].attr)
+        }
+
+        fn remove_child(&mut self, parent: u64, name: &OsStr, dir: bool) -> Result<(), c_int> {
+            if self.writeback.is_none() {
+                return Err(EROFS);
+            }
+            let name = name.to_str().ok_or(ENOENT)?;
+            let ino = self.child(parent, name).ok_or(ENOENT)?;
+            let entry = &self.inodes[&ino];
+            match (dir, entry.attr.kind == FileType::Directory) {
+                (false, true) => return Err(EISDIR),
+                (true, false) => return Err(ENOTDIR),
+                (true, true) if !entry.children.is_empty() => return Err(ENOTEMPTY),
+                _ => {}
+            }
+            let path = entry.path.clone();
+            self.writeback
+                .as_mut()
+                .unwrap()
+                .remove(&path)
+                .map_err(|_| EIO)?;
+            // Open writers keep their staged copy; it is dropped on release
+            self.detach(parent, name);
+            Ok(())
+        }
+
+        fn rename_child(
+            &mut self,
+            parent: u64,
+            name: &OsStr,
+            newparent: u64,
+            newname: &OsStr,
+            flags: u32,
+        ) -> Result<(), c_int> {
+            if self.writeback.is_none() {
+                return Err(EROFS);
+            }
+            if flags & !libc::RENAME_NOREPLACE != 0 {
+                return Err(EINVAL);
+            }
+            let (name, newname) = (
+                name.to_str().ok_or(ENOENT)?,
+                newname.to_str().ok_or(EINVAL)?,
+            );
+            let ino = self.child(parent, name).ok_or(ENOENT)?;
+            let from = self.inodes[&ino].path.clone();
+            let to = self.child_path(newparent, newname)?;
+            if from == to {
+                return Ok(());
+            }
+            let is_dir = self.inodes[&ino].attr.kind == FileType::Directory;
+            if is_dir && to.starts_with(&format!("{}/", from)) {
+                return Err(EINVAL);
+            }
+
+            if let Some(target) = self.child(newparent, newname) {
+                if flags & libc::RENAME_NOREPLACE != 0 {
+                    return Err(EEXIST);
+                }
+                let target = &self.inodes[&target];
+                match (is_dir, target.attr.kind == FileType::Directory) {
+                    (true, false) => return Err(ENOTDIR),
+                    (false, true) => return Err(EISDIR),
+                    (true, true) if !target.children.is_empty() => return Err(ENOTEMPTY),
+                    _ => {}
+                }
+                self.writeback
+                    .as_mut()
+                    .unwrap()
+                    .remove(&to)
+                    .map_err(|_| EIO)?;
+                self.detach(newparent, newname);
+            }
+
+            self.writeback
+                .as_mut()
+                .unwrap()
+                .rename(&from, &to)
+                .map_err(|_| EIO)?;
+            if let Some(dir) = self.inodes.get_mut(&parent) {
+                dir.children.retain(|(n, _)| n != name);
+            }
+            if let Some(dir) = self.inodes.get_mut(&newparent) {
+                dir.children.push((newname.to_string(), ino));
+            }
+            self.repath(ino, to);
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use vrift_cas::CasStore;
+
+        /// A writable mount of `/src` holding `main.rs` ("fn main() {}")
+        fn writable() -> (tempfile::TempDir, CasStore, VeloFs) {
+            let temp = tempfile::TempDir::new().unwrap();
+            let cas = CasStore::new(temp.path().join("cas")).unwrap();
+            let content = b"fn main() {}";
+            let hash = cas.store(content).unwrap();
+            let mut manifest = Manifest::new();
+            manifest.insert("/src", VnodeEntry::new_directory(0, 0o755));
+            manifest.insert(
+                "/src/main.rs",
+                VnodeEntry::new_file(hash, content.len() as u64, 0, 0o644),
+            );
+            let wb =
+                WriteBack::new(cas.clone(), manifest, temp.path().join("vrift.manifest")).unwrap();
+            let fs = VeloFs::new(wb.manifest(), cas.clone()).with_write_back(wb);
+            (temp, cas, fs)
+        }
+
+        fn ino(fs: &VeloFs, path: &str) -> u64 {
+            fs.path_to_inode[path]
+        }
+
+        fn saved(fs: &VeloFs, cas: &CasStore, path: &str) -> Option<Vec<u8>> {
+            let entry = fs.writeback.as_ref()?.manifest().get(path)?.clone();
+            Some(cas.get(&entry.content_hash).unwrap())
+        }
+
+        #[test]
+        fn test_write_commits_on_last_release() {
+            let (_temp, cas, mut fs) = writable();
+            let main = ino(&fs, "/src/main.rs");
+
+            let fh1 = fs.open_file(main, libc::O_WRONLY).unwrap();
+            let fh2 = fs.open_file(main, libc::O_RDWR).unwrap();
+            assert_eq!(fs.write_file(main, fh1, 3, b"start").unwrap(), 5);
+            assert_eq!(fs.inodes[&main].attr.size, 12);
+
+            fs.release_file(main, fh1).unwrap();
+            assert_eq!(saved(&fs, &cas, "/src/main.rs").unwrap(), b"fn main() {}");
+            fs.release_file(main, fh2).unwrap();
+            assert_eq!(saved(&fs, &cas, "/src/main.rs").unwrap(), b"fn start) {}");
+            assert!(fs.staged.is_empty());
+
+            assert_eq!(fs.write_file(main, fh1, 0, b"x"), Err(EBADF));
+            assert_eq!(fs.open_file(main, libc::O_RDONLY), Ok(0));
+        }
+
+        #[test]
+        fn test_o_trunc_empties_a_file_already_staged() {
+            let (_temp, cas, mut fs) = writable();
+            let main = ino(&fs, "/src/main.rs");
+
+            let fh1 = fs.open_file(main, libc::O_WRONLY).unwrap();
+            let fh2 = fs.open_file(main, libc::O_WRONLY | libc::O_TRUNC).unwrap();
+            assert_eq!(fs.inodes[&main].attr.size, 0);
+            fs.write_file(main, fh2, 0, b"new").unwrap();
+            fs.release_file(main, fh2).unwrap();
+            fs.release_file(main, fh1).unwrap();
+            assert_eq!(saved(&fs, &cas, "/src/main.rs").unwrap(), b"new");
+        }
+
+        #[test]
+        fn test_create_commits_new_file_and_cleans_up_on_failure() {
+            let (temp, cas, mut fs) = writable();
+            let src = ino(&fs, "/src");
+
+            let (attr, fh) = fs
+                .create_file(src, OsStr::new("lib.rs"), 0o666, 0o022)
+                .unwrap();
+            assert_eq!(attr.perm, 0o644);
+            assert_eq!(
+                fs.create_file(src, OsStr::new("lib.rs"), 0o644, 0),
+                Err(EEXIST)
+            );
+            fs.write_file(attr.ino, fh, 0, b"pub mod x;").unwrap();
+            fs.release_file(attr.ino, fh).unwrap();
+            assert_eq!(saved(&fs, &cas, "/src/lib.rs").unwrap(), b"pub mod x;");
+
+            // Staging fails: the new name must not linger
+            let staging = temp.path().join("cas").join("staging");
+            std::fs::remove_dir_all(&staging).unwrap();
+            assert_eq!(
+                fs.create_file(src, OsStr::new("gone.rs"), 0o644, 0),
+                Err(EIO)
+            );
+            assert!(fs.child(src, "gone.rs").is_none());
+            assert!(!fs.path_to_inode.contains_key("/src/gone.rs"));
+        }
+
+        #[test]
+        fn test_setattr_truncates_and_chmods() {
+            let (_temp, cas, mut fs) = writable();
+            let main = ino(&fs, "/src/main.rs");
+
+            let attr = fs.set_attr(main, None, Some(2), None).unwrap();
+            assert_eq!(attr.size, 2);
+            assert_eq!(saved(&fs, &cas, "/src/main.rs").unwrap(), b"fn");
+
+            let attr = fs.set_attr(main, Some(0o100600), None, None).unwrap();
+            assert_eq!(attr.perm, 0o600);
+            let wb = fs.writeback.as_ref().unwrap();
+            assert_eq!(wb.manifest().get("/src/main.rs").unwrap().mode, 0o600);
+
+            assert_eq!(fs.set_attr(999, Some(0o644), None, None), Err(ENOENT));
+        }
+
+        #[test]
+        fn test_rename_and_unlink() {
+            let (_temp, cas, mut fs) = writable();
+            let root = identity::ROOT_INO;
+            let src = ino(&fs, "/src");
+            let main = ino(&fs, "/src/main.rs");
+
+            fs.rename_child(root, OsStr::new("src"), root, OsStr::new("lib"), 0)
+                .unwrap();
+            assert_eq!(ino(&fs, "/lib/main.rs"), main);
+            assert_eq!(fs.inodes[&main].path, "/lib/main.rs");
+            assert!(saved(&fs, &cas, "/src/main.rs").is_none());
+            assert!(saved(&fs, &cas, "/lib/main.rs").is_some());
+
+            assert_eq!(fs.remove_child(root, OsStr::new("lib"), false), Err(EISDIR));
+            assert_eq!(
+                fs.remove_child(root, OsStr::new("lib"), true),
+                Err(ENOTEMPTY)
+            );
+            fs.remove_child(src, OsStr::new("main.rs"), false).unwrap();
+            assert!(saved(&fs, &cas, "/lib/main.rs").is_none());
+            assert!(!fs.inodes.contains_key(&main));
+            fs.remove_child(root, OsStr::new("lib"), true).unwrap();
+            assert_eq!(fs.remove_child(root, OsStr::new("lib"), true), Err(ENOENT));
+        }
+
+        #[test]
+        fn test_read_only_mount_refuses_writes() {
+            let temp = tempfile::TempDir::new().unwrap();
+            let cas = CasStore::new(temp.path().join("cas")).unwrap();
+            let mut manifest = Manifest::new();
+            manifest.insert("/a", VnodeEntry::new_file([0; 32], 0, 0, 0o644));
+            let mut fs = VeloFs::new(&manifest, cas);
+            let a = ino(&fs, "/a");
+            let root = identity::ROOT_INO;
+
+            assert_eq!(fs.open_file(a, libc::O_RDONLY), Ok(0));
+            assert_eq!(fs.open_file(a, libc::O_WRONLY), Err(EROFS));
+            assert_eq!(fs.create_file(root, OsStr::new("b"), 0o644, 0), Err(EROFS));
+            assert_eq!(fs.set_attr(a, None, Some(0), None), Err(EROFS));
+            assert_eq!(fs.remove_child(root, OsStr::new("a"), false), Err(EROFS));
+        }
     }
 }
 
 #[cfg(not(all(feature = "fuse", target_os = "linux")))]
 mod imp {
-    use vrift_cas::CasStore;
     use vrift_manifest::Manifest;
 
+    use std::sync::Arc;
+
+    use super::{BlobSource, FetchStats};
+
     /// Dummy FUSE filesystem for non-Linux or non-feature builds
     pub struct VeloFs;
 
+    /// Dummy background mount handle
+    pub struct BackgroundMount;
+
     impl VeloFs {
-        pub fn new(_manifest: &Manifest, _cas: CasStore) -> Self {
+        pub fn new(manifest: &Manifest, source: impl BlobSource) -> Self {
+            Self::with_inode_table(manifest, source, None)
+        }
+
+        pub fn with_inode_table(
+            _manifest: &Manifest,
+            _source: impl BlobSource,
+            _inode_table: Option<Arc<vrift_manifest::LmdbManifest>>,
+        ) -> Self {
             #[cfg(not(target_os = "linux"))]
             println!(
                 "⚠️  FUSE support is only available on Linux (current: {}).",
@@ -305,10 +1304,18 @@ mod imp {
             Self
         }
 
+        pub fn stats(&self) -> Arc<FetchStats> {
+            Arc::new(FetchStats::default())
+        }
+
         pub fn mount(self, _mountpoint: &std::path::Path) -> anyhow::Result<()> {
             anyhow::bail!("FUSE not supported on this platform");
         }
+
+        pub fn spawn_mount(self, _mountpoint: &std::path::Path) -> anyhow::Result<BackgroundMount> {
+            anyhow::bail!("FUSE not supported on this platform");
+        }
     }
 }
 
-pub use imp::VeloFs;
+pub use imp::{BackgroundMount, VeloFs};
@@ -0,0 +1,477 @@
+//! Write-back for writable mounts.
+//!
+//! A file opened for writing is staged: its current blob is copied into a
+//! private staging directory and all writes go to that copy. When the last
+//! writer releases it the copy is hashed into the CAS (a rename when the
+//! staging directory shares the CAS's filesystem) and the manifest entry is
+//! replaced. Namespace changes (mkdir, unlink, rename, chmod) go straight to
+//! the manifest.
+//!
+//! Each manifest change is appended to a journal next to the manifest file
+//! (`<manifest>.journal`) rather than rewriting the whole manifest. The
+//! manifest is saved, and the journal restarted, every [`COMPACT_EVERY`]
+//! changes and when the mount goes away. So an unmount or crash still
+//! loses at most the files open for writing: the next mount replays the
+//! journal.
+//!
+//! ```text
+//! <manifest>.journal   magic b"VWBJ", BLAKE3 of the manifest file it
+//!                      applies to, then records { len u32 LE, body }:
+//!                      1 upsert  path, hash[32], size u64, mtime u64,
+//!                                mode u32, flags u16
+//!                      2 remove  path
+//!                      3 rename  from, to
+//!                      (strings are len u32 LE + UTF-8, integers LE)
+//! ```
+//!
+//! The hash ties a journal to one manifest file: a crash after the manifest
+//! was saved but before the journal was restarted leaves a journal whose
+//! changes the manifest already has, and it is then ignored.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use vrift_cas::{Blake3Hash, CasStore};
+use vrift_manifest::{Manifest, VnodeEntry};
+
+const JOURNAL_MAGIC: &[u8; 4] = b"VWBJ";
+
+/// Journaled changes between full manifest saves
+pub const COMPACT_EVERY: usize = 1024;
+
+/// Manifest and CAS a writable mount commits to
+pub struct WriteBack {
+    cas: CasStore,
+    manifest: Manifest,
+    manifest_path: PathBuf,
+    journal_path: PathBuf,
+    journal: File,
+    /// Records in the journal since the last save
+    journaled: usize,
+    staging_dir: PathBuf,
+    next_stage: u64,
+}
+
+/// One manifest change, as journaled
+enum Record<'a> {
+    Upsert(&'a str, &'a VnodeEntry),
+    Remove(&'a str),
+    Rename(&'a str, &'a str),
+}
+
+impl WriteBack {
+    /// Commit to `cas` and journal changes to `manifest`, which should be
+    /// what is at `manifest_path`. A journal a previous mount left there is
+    /// replayed into it first. Staged files live under the CAS root.
+    pub fn new(
+        cas: CasStore,
+        mut manifest: Manifest,
+        manifest_path: impl Into<PathBuf>,
+    ) -> Result<Self> {
+        let manifest_path = manifest_path.into();
+        let journal_path = journal_path(&manifest_path);
+        let replayed = replay_journal(&mut manifest, &manifest_path, &journal_path)?;
+        if replayed > 0 {
+            log::info!(
+                "Replayed {} journaled changes into {}",
+                replayed,
+                manifest_path.display()
+            );
+        }
+        let staging_dir = cas
+            .root()
+            .join("staging")
+            .join(format!("mount-{}", std::process::id()));
+        fs::create_dir_all(&staging_dir)
+            .with_context(|| format!("Failed to create {}", staging_dir.display()))?;
+        let journal = save(&manifest, &manifest_path, &journal_path)?;
+        Ok(Self {
+            cas,
+            manifest,
+            manifest_path,
+            journal_path,
+            journal,
+            journaled: 0,
+            staging_dir,
+            next_stage: 0,
+        })
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub fn cas(&self) -> &CasStore {
+        &self.cas
+    }
+
+    /// A private, writable copy of blob `content` (an empty file for `None`)
+    pub fn stage(&mut self, content: Option<&Blake3Hash>) -> Result<(PathBuf, File)> {
+        self.next_stage += 1;
+        let path = self.staging_dir.join(format!("{}.tmp", self.next_stage));
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        if let Some(hash) = content {
+            match self.cas.blob_path_for_hash(hash) {
+                Some(blob) => {
+                    std::io::copy(&mut File::open(blob)?, &mut file)?;
+                }
+                // Chunked blobs have no single file to copy
+                None => file.write_all(&self.cas.get(hash)?)?,
+            }
+        }
+        Ok((path, file))
+    }
+
+    /// Move a staged file into the CAS and record it at `path`
+    pub fn commit(&mut self, path: &str, staged: &Path, mode: u32) -> Result<VnodeEntry> {
+        let size = fs::metadata(staged)?.len();
+        let hash = self.cas.store_by_move(staged)?;
+        let entry = VnodeEntry::new_file(hash, size, now_secs(), mode);
+        self.upsert(path, entry.clone())?;
+        Ok(entry)
+    }
+
+    /// Drop a staged file that will not be committed
+    pub fn discard(&self, staged: &Path) {
+        let _ = fs::remove_file(staged);
+    }
+
+    pub fn upsert(&mut self, path: &str, entry: VnodeEntry) -> Result<()> {
+        self.log(Record::Upsert(path, &entry))?;
+        self.manifest.insert(path, entry);
+        self.compact_if_due()
+    }
+
+    pub fn remove(&mut self, path: &str) -> Result<()> {
+        self.log(Record::Remove(path))?;
+        self.manifest.remove(path);
+        self.compact_if_due()
+    }
+
+    /// Move the entry at `from`, and everything under it, to `to`
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        self.log(Record::Rename(from, to))?;
+        rename_subtree(&mut self.manifest, from, to);
+        self.compact_if_due()
+    }
+
+    /// Save the manifest now and restart the journal
+    pub fn sync(&mut self) -> Result<()> {
+        self.journal = save(&self.manifest, &self.manifest_path, &self.journal_path)?;
+        self.journaled = 0;
+        Ok(())
+    }
+
+    /// Journaled changes not yet in the saved manifest
+    pub fn pending(&self) -> usize {
+        self.journaled
+    }
+
+    fn log(&mut self, record: Record) -> Result<()> {
+        self.journal
+            .write_all(&encode(&record))
+            .with_context(|| format!("Failed to journal to {}", self.journal_path.display()))?;
+        self.journaled += 1;
+        Ok(())
+    }
+
+    fn compact_if_due(&mut self) -> Result<()> {
+        if self.journaled >= COMPACT_EVERY {
+            self.sync()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for WriteBack {
+    fn drop(&mut self) {
+        match self.sync() {
+            Ok(()) => {
+                let _ = fs::remove_file(&self.journal_path);
+            }
+            // The journal stays for the next mount to replay
+            Err(e) => log::error!("Saving {} failed: {}", self.manifest_path.display(), e),
+        }
+        let _ = fs::remove_dir_all(&self.staging_dir);
+    }
+}
+
+fn journal_path(manifest_path: &Path) -> PathBuf {
+    let mut path = manifest_path.as_os_str().to_owned();
+    path.push(".journal");
+    PathBuf::from(path)
+}
+
+/// Write `manifest` through a temp file (so a crash mid-save leaves the
+/// previous version intact), then start an empty journal for it
+fn save(manifest: &Manifest, manifest_path: &Path, journal_path: &Path) -> Result<File> {
+    let bytes = manifest.to_bytes()?;
+    let tmp = manifest_path.with_extension("saving");
+    fs::write(&tmp, &bytes)?;
+    fs::rename(&tmp, manifest_path)
+        .with_context(|| format!("Failed to save {}", manifest_path.display()))?;
+
+    let tmp = journal_path.with_extension("journal-saving");
+    let mut header = JOURNAL_MAGIC.to_vec();
+    header.extend_from_slice(&CasStore::compute_hash(&bytes));
+    fs::write(&tmp, &header)?;
+    fs::rename(&tmp, journal_path)
+        .with_context(|| format!("Failed to start {}", journal_path.display()))?;
+    Ok(OpenOptions::new().append(true).open(journal_path)?)
+}
+
+/// Apply the journal at `journal_path` to `manifest` if it was written for
+/// the manifest file now at `manifest_path`. A torn last record (a crash
+/// mid-append) ends the replay. Returns the records applied.
+fn replay_journal(
+    manifest: &mut Manifest,
+    manifest_path: &Path,
+    journal_path: &Path,
+) -> Result<usize> {
+    let journal = match fs::read(journal_path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(rest) = journal.strip_prefix(JOURNAL_MAGIC) else {
+        return Ok(0);
+    };
+    let Some((base, mut records)) = rest.split_first_chunk::<32>() else {
+        return Ok(0);
+    };
+    let current = fs::read(manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    if *base != CasStore::compute_hash(&current) {
+        // Written before the manifest was last saved: already in it
+        return Ok(0);
+    }
+
+    let mut applied = 0;
+    while let Some((len, rest)) = records.split_first_chunk::<4>() {
+        let len = u32::from_le_bytes(*len) as usize;
+        let Some(body) = rest.get(..len) else {
+            break;
+        };
+        if apply(manifest, body).is_none() {
+            break;
+        }
+        records = &rest[len..];
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+fn encode(record: &Record) -> Vec<u8> {
+    fn put_str(out: &mut Vec<u8>, s: &str) {
+        out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        out.extend_from_slice(s.as_bytes());
+    }
+    let mut body = Vec::new();
+    match record {
+        Record::Upsert(path, entry) => {
+            body.push(1);
+            put_str(&mut body, path);
+            body.extend_from_slice(&entry.content_hash);
+            body.extend_from_slice(&entry.size.to_le_bytes());
+            body.extend_from_slice(&entry.mtime.to_le_bytes());
+            body.extend_from_slice(&entry.mode.to_le_bytes());
+            body.extend_from_slice(&entry.flags.to_le_bytes());
+        }
+        Record::Remove(path) => {
+            body.push(2);
+            put_str(&mut body, path);
+        }
+        Record::Rename(from, to) => {
+            body.push(3);
+            put_str(&mut body, from);
+            put_str(&mut body, to);
+        }
+    }
+    let mut out = (body.len() as u32).to_le_bytes().to_vec();
+    out.extend_from_slice(&body);
+    out
+}
+
+/// Apply one journal record body; `None` if it doesn't parse
+fn apply(manifest: &mut Manifest, mut body: &[u8]) -> Option<()> {
+    fn take<'a>(body: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+        let (head, tail) = body.split_at_checked(n)?;
+        *body = tail;
+        Some(head)
+    }
+    fn take_str<'a>(body: &mut &'a [u8]) -> Option<&'a str> {
+        let len = u32::from_le_bytes(take(body, 4)?.try_into().ok()?) as usize;
+        std::str::from_utf8(take(body, len)?).ok()
+    }
+    let body = &mut body;
+    match take(body, 1)?[0] {
+        1 => {
+            let path = take_str(body)?;
+            let entry = VnodeEntry {
+                content_hash: take(body, 32)?.try_into().ok()?,
+                size: u64::from_le_bytes(take(body, 8)?.try_into().ok()?),
+                mtime: u64::from_le_bytes(take(body, 8)?.try_into().ok()?),
+                mode: u32::from_le_bytes(take(body, 4)?.try_into().ok()?),
+                flags: u16::from_le_bytes(take(body, 2)?.try_into().ok()?),
+                _pad: 0,
+            };
+            manifest.insert(path, entry);
+        }
+        2 => {
+            manifest.remove(take_str(body)?);
+        }
+        3 => {
+            let from = take_str(body)?;
+            let to = take_str(body)?;
+            rename_subtree(manifest, from, to);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Move the entry at `from`, and everything under it, to `to`
+fn rename_subtree(manifest: &mut Manifest, from: &str, to: &str) {
+    let prefix = format!("{}/", from);
+    let moved: Vec<String> = manifest
+        .paths()
+        .filter(|p| *p == from || p.starts_with(&prefix))
+        .map(str::to_string)
+        .collect();
+    for old in moved {
+        let xattrs = manifest.xattrs(&old);
+        if let Some(entry) = manifest.remove(&old) {
+            let new = format!("{}{}", to, &old[from.len()..]);
+            manifest.insert(&new, entry);
+            manifest.set_xattrs(&new, &xattrs);
+        }
+    }
+}
+
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_writeback_stage_commit_rename() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path().join("cas")).unwrap();
+        let original = cas.store(b"fn main() {}\n").unwrap();
+        let mut manifest = Manifest::new();
+        manifest.insert("/src", VnodeEntry::new_directory(0, 0o755));
+        manifest.insert("/src/main.rs", VnodeEntry::new_file(original, 13, 0, 0o644));
+        let label = vec![("security.selinux".to_string(), b"src_t".to_vec())];
+        manifest.set_xattrs("/src/main.rs", &label);
+        let manifest_path = temp.path().join("vrift.manifest");
+        let mut wb = WriteBack::new(cas, manifest, &manifest_path).unwrap();
+
+        // Edit: the staged copy starts from the old content
+        let (staged, mut file) = wb.stage(Some(&original)).unwrap();
+        file.write_all(b"// edited\n").unwrap();
+        drop(file);
+        assert!(fs::read(&staged).unwrap().starts_with(b"fn main"));
+        let entry = wb.commit("/src/main.rs", &staged, 0o644).unwrap();
+        assert!(!staged.exists());
+        assert_eq!(entry.size, 23);
+        assert_eq!(
+            wb.cas().get(&entry.content_hash).unwrap(),
+            b"fn main() {}\n// edited\n"
+        );
+
+        wb.rename("/src", "/lib").unwrap();
+        wb.sync().unwrap();
+        let saved = Manifest::load(&manifest_path).unwrap();
+        assert!(saved.get("/src/main.rs").is_none());
+        assert_eq!(
+            saved.get("/lib/main.rs").unwrap().content_hash,
+            entry.content_hash
+        );
+        assert!(saved.get("/lib").unwrap().is_dir());
+        assert_eq!(saved.xattrs("/lib/main.rs"), label);
+
+        wb.remove("/lib/main.rs").unwrap();
+        drop(wb);
+        assert!(Manifest::load(&manifest_path)
+            .unwrap()
+            .get("/lib/main.rs")
+            .is_none());
+    }
+
+    #[test]
+    fn test_writeback_journals_instead_of_saving() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path().join("cas")).unwrap();
+        let manifest_path = temp.path().join("vrift.manifest");
+        let mut manifest = Manifest::new();
+        manifest.insert("/old", VnodeEntry::new_file([1; 32], 1, 0, 0o644));
+        let mut wb = WriteBack::new(cas.clone(), manifest, &manifest_path).unwrap();
+        let saved = fs::read(&manifest_path).unwrap();
+
+        wb.upsert("/a", VnodeEntry::new_file([2; 32], 2, 0, 0o644))
+            .unwrap();
+        wb.rename("/a", "/b").unwrap();
+        wb.remove("/old").unwrap();
+        assert_eq!(wb.pending(), 3);
+        // Nothing rewrote the manifest itself
+        assert_eq!(fs::read(&manifest_path).unwrap(), saved);
+
+        // A crash: the journal is all there is. Its last record is torn.
+        let journal = journal_path(&manifest_path);
+        let mut data = fs::read(&journal).unwrap();
+        data.extend_from_slice(&[9, 0, 0, 0, 1]);
+        std::mem::forget(wb);
+        fs::write(&journal, &data).unwrap();
+
+        let reopened = WriteBack::new(
+            cas.clone(),
+            Manifest::load(&manifest_path).unwrap(),
+            &manifest_path,
+        )
+        .unwrap();
+        let manifest = reopened.manifest();
+        assert!(manifest.get("/a").is_none());
+        assert_eq!(manifest.get("/b").unwrap().size, 2);
+        assert!(manifest.get("/old").is_none());
+        assert_eq!(reopened.pending(), 0);
+        drop(reopened);
+        assert!(!journal.exists());
+
+        // A journal for an older manifest file is already folded in
+        fs::write(&journal, &data).unwrap();
+        let again =
+            WriteBack::new(cas, Manifest::load(&manifest_path).unwrap(), &manifest_path).unwrap();
+        assert_eq!(again.manifest().len(), 1);
+    }
+
+    #[test]
+    fn test_writeback_compacts_periodically() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let cas = CasStore::new(temp.path().join("cas")).unwrap();
+        let manifest_path = temp.path().join("vrift.manifest");
+        let mut wb = WriteBack::new(cas, Manifest::new(), &manifest_path).unwrap();
+        for i in 0..COMPACT_EVERY {
+            wb.upsert(
+                &format!("/f{}", i),
+                VnodeEntry::new_file([0; 32], 0, 0, 0o644),
+            )
+            .unwrap();
+        }
+        assert_eq!(wb.pending(), 0);
+        assert_eq!(Manifest::load(&manifest_path).unwrap().len(), COMPACT_EVERY);
+    }
+}
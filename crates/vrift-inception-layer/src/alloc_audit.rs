@@ -0,0 +1,281 @@
+// =============================================================================
+// alloc_audit.rs — Hot-path allocation audit (feature "alloc-audit")
+// =============================================================================
+//
+// The layer is meant to be allocation-free inside intercepted calls. Built
+// with `--features alloc-audit`, a counting global allocator wraps System
+// and flags every allocation made while the calling thread holds an
+// InceptionLayerGuard. The ring-buffer worker is exempt: taking work off the
+// hot path is what it is for.
+//
+// Offending call sites are deduplicated by stack and dumped at exit with
+// backtrace_symbols_fd, to stderr or to
+// $VRIFT_ALLOC_AUDIT_DIR/alloc-audit.<pid>.txt (the integration harness
+// collects those). Frames are printed as `lib(+offset)`; feed the offsets
+// to addr2line against the unstripped library.
+//
+// Recording never allocates: sites live in a fixed table behind a spinlock
+// and sites beyond its capacity are only counted. Processes that exec()
+// or _exit() skip atexit and leave no report.
+// =============================================================================
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::UnsafeCell;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use libc::{c_int, c_void};
+
+#[cfg(target_os = "linux")]
+use crate::syscalls::linux_raw as raw;
+#[cfg(target_os = "macos")]
+use crate::syscalls::macos_raw as raw;
+
+/// Distinct call sites kept for the report
+const MAX_SITES: usize = 64;
+/// Frames captured per site, allocator frames included
+const DEPTH: usize = 24;
+
+extern "C" {
+    fn backtrace(buffer: *mut *mut c_void, size: c_int) -> c_int;
+    fn backtrace_symbols_fd(buffer: *const *mut c_void, size: c_int, fd: c_int);
+}
+
+#[global_allocator]
+static GLOBAL: AuditAlloc = AuditAlloc;
+
+/// All allocations made through the layer's allocator
+pub static ALLOCS: AtomicU64 = AtomicU64::new(0);
+/// Allocations made inside intercepted calls
+pub static HOT_ALLOCS: AtomicU64 = AtomicU64::new(0);
+/// Bytes requested by those
+pub static HOT_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Hot allocations whose site didn't fit in the table
+pub static SITES_DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// pthread_self() of the ring-buffer worker (0 = not started)
+static WORKER: AtomicUsize = AtomicUsize::new(0);
+static DUMP_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// Guard-key value while a site is being recorded, so allocations made by
+/// the recording itself aren't recorded again
+const RECORDING: *const c_void = std::ptr::without_provenance(2);
+
+struct Site {
+    frames: [usize; DEPTH],
+    depth: usize,
+    count: u64,
+    bytes: u64,
+}
+
+struct SiteTable {
+    lock: AtomicBool,
+    len: UnsafeCell<usize>,
+    sites: UnsafeCell<[Site; MAX_SITES]>,
+}
+
+// SAFETY: len and sites are only touched with `lock` held
+unsafe impl Sync for SiteTable {}
+
+static SITES: SiteTable = SiteTable {
+    lock: AtomicBool::new(false),
+    len: UnsafeCell::new(0),
+    sites: UnsafeCell::new(
+        [const {
+            Site {
+                frames: [0; DEPTH],
+                depth: 0,
+                count: 0,
+                bytes: 0,
+            }
+        }; MAX_SITES],
+    ),
+};
+
+impl SiteTable {
+    fn with<R>(&self, f: impl FnOnce(&mut usize, &mut [Site; MAX_SITES]) -> R) -> R {
+        while self
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            std::hint::spin_loop();
+        }
+        let r = unsafe { f(&mut *self.len.get(), &mut *self.sites.get()) };
+        self.lock.store(false, Ordering::Release);
+        r
+    }
+}
+
+struct AuditAlloc;
+
+unsafe impl GlobalAlloc for AuditAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        check(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        check(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        check(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Exempt the calling thread (the ring-buffer worker) from the audit
+pub(crate) fn exempt_current_thread() {
+    WORKER.store(unsafe { libc::pthread_self() } as usize, Ordering::Relaxed);
+}
+
+#[inline(always)]
+fn check(size: usize) {
+    ALLOCS.fetch_add(1, Ordering::Relaxed);
+    let Some(key) = crate::state::recursion_key() else {
+        return;
+    };
+    let slot = unsafe { libc::pthread_getspecific(key) };
+    if slot.is_null() || slot.cast_const() == RECORDING {
+        return;
+    }
+    if WORKER.load(Ordering::Relaxed) == unsafe { libc::pthread_self() } as usize {
+        return;
+    }
+    unsafe { libc::pthread_setspecific(key, RECORDING) };
+    record(size);
+    unsafe { libc::pthread_setspecific(key, slot) };
+}
+
+#[inline(never)]
+#[cold]
+fn record(size: usize) {
+    HOT_ALLOCS.fetch_add(1, Ordering::Relaxed);
+    HOT_BYTES.fetch_add(size as u64, Ordering::Relaxed);
+
+    let mut raw = [std::ptr::null_mut::<c_void>(); DEPTH];
+    let depth = unsafe { backtrace(raw.as_mut_ptr(), DEPTH as c_int) }.max(0) as usize;
+    let mut frames = [0usize; DEPTH];
+    for (dst, src) in frames.iter_mut().zip(&raw[..depth]) {
+        *dst = *src as usize;
+    }
+
+    let stored = SITES.with(|len, sites| {
+        if let Some(site) = sites[..*len]
+            .iter_mut()
+            .find(|s| s.frames[..s.depth] == frames[..depth])
+        {
+            site.count += 1;
+            site.bytes += size as u64;
+            return true;
+        }
+        if *len == MAX_SITES {
+            return false;
+        }
+        sites[*len] = Site {
+            frames,
+            depth,
+            count: 1,
+            bytes: size as u64,
+        };
+        *len += 1;
+        true
+    });
+    if !stored {
+        SITES_DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+    crate::state::register_atexit_once(&DUMP_REGISTERED, dump_at_exit);
+}
+
+/// Report file under $VRIFT_ALLOC_AUDIT_DIR, or stderr
+unsafe fn open_report() -> c_int {
+    let dir = libc::getenv(c"VRIFT_ALLOC_AUDIT_DIR".as_ptr());
+    if dir.is_null() {
+        return libc::STDERR_FILENO;
+    }
+    let mut buf = [0u8; 1024];
+    let mut path = crate::macros::StackWriter::new(&mut buf[..1023]);
+    let _ = write!(
+        path,
+        "{}/alloc-audit.{}.txt",
+        std::ffi::CStr::from_ptr(dir).to_str().unwrap_or("."),
+        libc::getpid()
+    );
+    if path.overflowed() {
+        return libc::STDERR_FILENO;
+    }
+    let len = path.as_str().len();
+    buf[len] = 0;
+    let fd = raw::raw_open(
+        buf.as_ptr() as *const libc::c_char,
+        libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+        0o644,
+    );
+    if fd < 0 {
+        libc::STDERR_FILENO
+    } else {
+        fd
+    }
+}
+
+unsafe fn write_all(fd: c_int, s: &str) {
+    let mut rest = s.as_bytes();
+    while !rest.is_empty() {
+        let n = raw::raw_write(fd, rest.as_ptr() as *const c_void, rest.len());
+        if n <= 0 {
+            return;
+        }
+        rest = &rest[n as usize..];
+    }
+}
+
+extern "C" fn dump_at_exit() {
+    let hot = HOT_ALLOCS.load(Ordering::Relaxed);
+    if hot == 0 {
+        return;
+    }
+    unsafe {
+        let fd = open_report();
+        let mut buf = [0u8; 512];
+        let mut line = crate::macros::StackWriter::new(&mut buf);
+        let _ = writeln!(
+            line,
+            "vrift alloc-audit: pid {}: {} of {} allocations ({} bytes) inside intercepted calls, {} not attributed",
+            libc::getpid(),
+            hot,
+            ALLOCS.load(Ordering::Relaxed),
+            HOT_BYTES.load(Ordering::Relaxed),
+            SITES_DROPPED.load(Ordering::Relaxed)
+        );
+        write_all(fd, line.as_str());
+
+        SITES.with(|len, sites| {
+            for (i, site) in sites[..*len].iter().enumerate() {
+                let mut buf = [0u8; 128];
+                let mut line = crate::macros::StackWriter::new(&mut buf);
+                let _ = writeln!(
+                    line,
+                    "--- site {}: {} allocations, {} bytes",
+                    i + 1,
+                    site.count,
+                    site.bytes
+                );
+                write_all(fd, line.as_str());
+                backtrace_symbols_fd(
+                    site.frames.as_ptr() as *const *mut c_void,
+                    site.depth as c_int,
+                    fd,
+                );
+            }
+        });
+        if fd != libc::STDERR_FILENO {
+            raw::raw_close(fd);
+        }
+    }
+}
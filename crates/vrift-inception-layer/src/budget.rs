@@ -0,0 +1,120 @@
+// =============================================================================
+// budget.rs — Per-operation IPC time budgets (VRIFT_OP_BUDGET)
+// =============================================================================
+//
+// The circuit breaker only reacts to a daemon that is down. A daemon that is
+// up but slow (LMDB compaction, ingest storm) would otherwise stall every
+// stat() for up to the 5s socket timeout. Each syscall class can get a
+// budget for its vDird round-trip; a read-only op that blows it gives up on
+// the VFS answer and takes the passthrough result instead.
+//
+// Budgets are opt-in: the passthrough answer is the real file's, which can
+// differ from what the VFS would have said (a stale or missing file on
+// disk), so no class degrades unless asked to.
+//
+// Format: VRIFT_OP_BUDGET="stat=5ms,open=10ms,dir=50ms"
+//   Units: "ms", "us", or bare number (ms). 0 = unlimited.
+//   Unlisted classes stay unlimited.
+//
+// Mutating ops are never degraded: passthrough there could write past the VFS.
+// =============================================================================
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetClass {
+    /// stat/lstat/fstat/fstatat/statx/access
+    Stat = 0,
+    /// Read-only open
+    Open = 1,
+    /// Directory listing
+    Dir = 2,
+    /// Writes, renames, removes, and existence checks guarding them
+    Mutate = 3,
+}
+
+pub const BUDGET_CLASS_COUNT: usize = 4;
+
+pub static BUDGET_CLASS_NAMES: [&str; BUDGET_CLASS_COUNT] = ["stat", "open", "dir", "mutate"];
+
+/// Budget per class in microseconds (0 = unlimited, the default)
+pub static BUDGET_US: [AtomicU32; BUDGET_CLASS_COUNT] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+
+/// Round-trips that exceeded their budget and were degraded to passthrough
+pub static BUDGET_VIOLATIONS: [AtomicU64; BUDGET_CLASS_COUNT] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+/// Slowest violating round-trip per class in microseconds
+pub static BUDGET_WORST_US: [AtomicU64; BUDGET_CLASS_COUNT] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+#[inline(always)]
+pub fn budget_us(class: BudgetClass) -> u32 {
+    // Mutations are always unlimited, whatever the env says
+    if class == BudgetClass::Mutate {
+        return 0;
+    }
+    BUDGET_US[class as usize].load(Ordering::Relaxed)
+}
+
+/// Count a degraded round-trip
+#[inline(never)]
+pub fn record_violation(class: BudgetClass, elapsed_us: u64) {
+    BUDGET_VIOLATIONS[class as usize].fetch_add(1, Ordering::Relaxed);
+    BUDGET_WORST_US[class as usize].fetch_max(elapsed_us, Ordering::Relaxed);
+}
+
+/// Apply a VRIFT_OP_BUDGET spec. Malformed entries are skipped.
+pub fn apply_budget_spec(spec: &[u8]) {
+    for item in spec.split(|&b| b == b',') {
+        let Some(eq) = item.iter().position(|&b| b == b'=') else {
+            continue;
+        };
+        let (name, value) = (item[..eq].trim_ascii(), item[eq + 1..].trim_ascii());
+        let Some(idx) = BUDGET_CLASS_NAMES[..BudgetClass::Mutate as usize]
+            .iter()
+            .position(|n| n.as_bytes().eq_ignore_ascii_case(name))
+        else {
+            continue;
+        };
+        if let Some(us) = parse_duration_us(value) {
+            BUDGET_US[idx].store(us, Ordering::Relaxed);
+        }
+    }
+}
+
+/// "5ms" / "500us" / "5" (ms) → microseconds
+fn parse_duration_us(value: &[u8]) -> Option<u32> {
+    let (digits, scale) = if let Some(d) = value.strip_suffix(b"us") {
+        (d, 1)
+    } else if let Some(d) = value.strip_suffix(b"ms") {
+        (d, 1_000)
+    } else {
+        (value, 1_000)
+    };
+    std::str::from_utf8(digits)
+        .ok()?
+        .parse::<u32>()
+        .ok()?
+        .checked_mul(scale)
+}
+
+/// Microsecond monotonic clock (vDSO / commpage, never intercepted)
+#[inline(always)]
+pub fn monotonic_us() -> u64 {
+    crate::profile::monotonic_ns() / 1_000
+}
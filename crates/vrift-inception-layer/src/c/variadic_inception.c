@@ -58,6 +58,7 @@ extern int velo_access_impl(const char *path, int mode);
 extern long velo_readlink_impl(const char *path, char *buf, size_t bufsiz);
 extern int velo_fstat_impl(int fd, void *buf);
 extern int velo_fstatat_impl(int dirfd, const char *path, void *buf, int flags);
+extern void vrift_preresolve_reals(void);
 
 /* RFC-0049: Global initialization state (synchronized with InceptionState enum)
  * 0: Ready (Active)
@@ -70,6 +71,8 @@ volatile char INITIALIZING = 2;
 __attribute__((constructor(101))) void inception_init_constructor() {
   // RFC-0051: Ignore SIGPIPE to prevent IPC failures from killing processes
   signal(SIGPIPE, SIG_IGN);
+  // Resolve every REAL_* pointer now, so intercepted syscalls never dlsym
+  vrift_preresolve_reals();
   INITIALIZING = 1; // Transition to RustInit
 }
 
@@ -0,0 +1,446 @@
+// =============================================================================
+// chunked.rs — Reassembly of chunked and compressed CAS blobs
+// =============================================================================
+//
+// Ingest may store a large file as content-defined chunks instead of one
+// blob (see vrift_cas::chunking): `blake3/ab/cd/<hash>_<size>.bin` is then
+// absent and `chunks/ab/cd/<hash>` lists the leaves, each an ordinary
+// `blake3/…/<leaf>_<len>.bin` blob.
+//
+// When a blob open fails with ENOENT, the leaves are concatenated into a temp
+// file under `cache/`, which is then renamed to `cache/ab/cd/<hash>` (see
+// vrift_cas::CasStore::expanded_path). The caller gets an ordinary fd (read,
+// mmap, fstat all behave), and later opens in any process reuse the copy
+// instead of expanding the blob again. Copies are dropped by CAS sweeps along
+// with their blob, and vriftd trims `cache/` to `[daemon] expanded_cache_mb`,
+// least recently used first (see vrift_cas::CasStore::trim_expanded).
+//
+// The expanded content is hashed as it is written. A copy that doesn't match
+// the blob's hash (a damaged leaf or compressed blob) is never cached: the
+// open fails with EIO instead of every later reader getting the bad bytes.
+//
+// A blob may instead be stored zstd-compressed at `zstd/ab/cd/<hash>` (see
+// vrift_cas::compression); it is inflated the same way and cached alongside.
+//
+// Blobs and leaves may also have been moved to a storage tier (another CAS
+// root, see vrift_cas::tiers); those roots come from `VRIFT_CAS_TIERS` and
+// are tried after the primary one. Chunk lists and compressed blobs never
+// leave the primary root.
+// =============================================================================
+
+use std::ffi::CString;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use libc::{c_int, c_void};
+
+#[cfg(target_os = "linux")]
+use crate::syscalls::linux_raw as raw;
+#[cfg(target_os = "macos")]
+use crate::syscalls::macos_raw as raw;
+
+const MAGIC: &[u8; 8] = b"VRCHUNK1";
+const HEADER: usize = 24;
+const RECORD: usize = 40;
+
+/// Chunk lists past this are rejected rather than read (≈1.6M chunks)
+const MAX_LIST_BYTES: usize = 64 << 20;
+
+const ZSTD_MAGIC: &[u8; 8] = b"VRZSTD01";
+
+/// Chunked blobs reassembled on open
+pub static REASSEMBLED_FILES: AtomicU64 = AtomicU64::new(0);
+/// Bytes copied by those reassemblies
+pub static REASSEMBLED_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Compressed blobs inflated on open
+pub static INFLATED_FILES: AtomicU64 = AtomicU64::new(0);
+/// Bytes written by those inflations
+pub static INFLATED_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Opens served from an already expanded copy under `cache/`
+pub static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+
+static TEMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Open a CAS blob read-only, under either name [`open_cas_blob`] accepts,
+/// reassembling it if it is stored chunked and inflating it if it is stored
+/// compressed. Returns -1 with errno set if no form is available.
+pub(crate) unsafe fn open_blob(blob_path: &str, flags: c_int) -> c_int {
+    let Ok(blob_c) = CString::new(blob_path) else {
+        crate::set_errno(libc::EINVAL);
+        return -1;
+    };
+    let flags = flags & !(libc::O_CREAT | libc::O_EXCL);
+    let fd = raw::raw_open(blob_c.as_ptr(), flags, 0);
+    if fd >= 0 || crate::get_errno() != libc::ENOENT {
+        return fd;
+    }
+    let Some((root, hex)) = split_blob_path(blob_path) else {
+        crate::set_errno(libc::ENOENT);
+        return -1;
+    };
+    let rel = &blob_path[root.len()..];
+    // Blobs stored through the plain CAS API have no `.bin` extension
+    let bare = rel.strip_suffix(".bin");
+    let fd = bare.map_or(-1, |bare| open_under(root, bare, flags));
+    if fd >= 0 {
+        return fd;
+    }
+    for tier in tier_roots() {
+        for rel in std::iter::once(rel).chain(bare) {
+            let fd = open_under(tier, rel, flags);
+            if fd >= 0 {
+                return fd;
+            }
+        }
+    }
+    let mut cached = [0u8; 200];
+    let mut w = crate::macros::StackWriter::new(&mut cached);
+    let _ = write!(w, "/cache/{}/{}/{}", &hex[..2], &hex[2..4], hex);
+    let fd = open_under(root, w.as_str(), flags);
+    if fd >= 0 {
+        CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+        return fd;
+    }
+    let cloexec = flags & libc::O_CLOEXEC != 0;
+    match reassemble(root, hex, cloexec).or_else(|| inflate(root, hex, cloexec)) {
+        Some((fd, temp, digest)) if digest.to_hex().as_str() == hex => {
+            publish(root, hex, fd, &temp, cloexec)
+        }
+        Some((fd, temp, _)) => {
+            discard_temp(fd, &temp);
+            inception_log!("expanded blob {} does not match its hash", hex);
+            crate::set_errno(libc::EIO);
+            -1
+        }
+        None => {
+            crate::set_errno(libc::ENOENT);
+            -1
+        }
+    }
+}
+
+/// Storage tier roots, in lookup order
+fn tier_roots() -> impl Iterator<Item = &'static str> {
+    let (tiers, offset) = match crate::state::InceptionLayerState::get() {
+        Some(state) => (state.cas_tiers.as_str(), state.root_offset.as_str()),
+        None => ("", ""),
+    };
+    tiers
+        .split(':')
+        .filter(|t| !t.is_empty())
+        .map(move |t| crate::path::host_to_local(t, offset))
+}
+
+/// `open(<root><rel>)`; -1 if the path doesn't fit
+unsafe fn open_under(root: &str, rel: &str, flags: c_int) -> c_int {
+    let mut buf = [0u8; 1100];
+    let mut w = crate::macros::StackWriter::new(&mut buf);
+    let _ = write!(w, "{}{}", root, rel);
+    if w.overflowed() {
+        return -1;
+    }
+    match CString::new(w.as_str()) {
+        Ok(path) => raw::raw_open(path.as_ptr(), flags, 0),
+        Err(_) => -1,
+    }
+}
+
+/// `<cas_root>/blake3/ab/cd/<hex>_<size>.bin` → (cas_root, hex)
+fn split_blob_path(blob_path: &str) -> Option<(&str, &str)> {
+    let (root, rest) = blob_path.rsplit_once("/blake3/")?;
+    let name = rest.rsplit('/').next()?;
+    let hex = name.get(..64)?;
+    Some((root, hex))
+}
+
+/// An expanded copy: its fd (at offset 0), temp path, and content hash
+type Expanded = (c_int, CString, blake3::Hash);
+
+unsafe fn reassemble(root: &str, hex: &str, cloexec: bool) -> Option<Expanded> {
+    let list = read_list(root, hex)?;
+    let count = u32::from_le_bytes(list[16..20].try_into().ok()?) as usize;
+    if list.len() != HEADER + count.checked_mul(RECORD)? {
+        return None;
+    }
+
+    let (fd, temp) = create_temp(root, cloexec)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut total = 0u64;
+    for record in list[HEADER..].chunks_exact(RECORD) {
+        let len = u64::from_le_bytes(record[32..].try_into().ok()?);
+        match copy_leaf(root, &record[..32], len, fd, &mut hasher) {
+            Some(n) if n == len => total += n,
+            _ => {
+                discard_temp(fd, &temp);
+                return None;
+            }
+        }
+    }
+    raw::raw_lseek(fd, 0, libc::SEEK_SET);
+
+    REASSEMBLED_FILES.fetch_add(1, Ordering::Relaxed);
+    REASSEMBLED_BYTES.fetch_add(total, Ordering::Relaxed);
+    inception_log!("reassembled chunked blob {} ({} bytes)", hex, total);
+    Some((fd, temp, hasher.finalize()))
+}
+
+unsafe fn inflate(root: &str, hex: &str, cloexec: bool) -> Option<Expanded> {
+    let mut buf = [0u8; 1100];
+    let mut w = crate::macros::StackWriter::new(&mut buf);
+    let _ = write!(w, "{}/zstd/{}/{}/{}", root, &hex[..2], &hex[2..4], hex);
+    if w.overflowed() {
+        return None;
+    }
+    let path = CString::new(w.as_str()).ok()?;
+    let src = raw::raw_open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC, 0);
+    if src < 0 {
+        return None;
+    }
+    let temp = create_temp(root, cloexec);
+    let mut hasher = blake3::Hasher::new();
+    let total = temp
+        .as_ref()
+        .and_then(|(fd, _)| decode_into(src, *fd, &mut hasher));
+    raw::raw_close(src);
+    let (fd, temp) = temp?;
+    let Some(total) = total else {
+        discard_temp(fd, &temp);
+        return None;
+    };
+    raw::raw_lseek(fd, 0, libc::SEEK_SET);
+
+    INFLATED_FILES.fetch_add(1, Ordering::Relaxed);
+    INFLATED_BYTES.fetch_add(total, Ordering::Relaxed);
+    inception_log!("inflated compressed blob {} ({} bytes)", hex, total);
+    Some((fd, temp, hasher.finalize()))
+}
+
+/// Decompress the blob open at `src` (header, then one zstd frame) into
+/// `dst` and `hasher`; returns the bytes written if they match the
+/// header's size
+unsafe fn decode_into(src: c_int, dst: c_int, hasher: &mut blake3::Hasher) -> Option<u64> {
+    use std::io::Read;
+
+    let mut reader = RawFd(src);
+    let mut header = [0u8; 16];
+    reader.read_exact(&mut header).ok()?;
+    if &header[..8] != ZSTD_MAGIC {
+        return None;
+    }
+    let size = u64::from_le_bytes(header[8..].try_into().ok()?);
+
+    let mut decoder = zstd::stream::read::Decoder::new(reader).ok()?;
+    let mut total = 0u64;
+    let mut buf = [0u8; 16384];
+    loop {
+        let n = match decoder.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => return None,
+        };
+        if !write_all(dst, &buf[..n]) {
+            return None;
+        }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+    }
+    (total == size).then_some(total)
+}
+
+/// `Read` over a raw fd, for the zstd decoder
+struct RawFd(c_int);
+
+impl std::io::Read for RawFd {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = unsafe { raw::raw_read(self.0, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        if n < 0 {
+            return Err(std::io::Error::from_raw_os_error(unsafe {
+                crate::get_errno()
+            }));
+        }
+        Ok(n as usize)
+    }
+}
+
+/// The whole chunk list of `hex`, header-checked
+unsafe fn read_list(root: &str, hex: &str) -> Option<Vec<u8>> {
+    let mut buf = [0u8; 1100];
+    let mut w = crate::macros::StackWriter::new(&mut buf);
+    let _ = write!(w, "{}/chunks/{}/{}/{}", root, &hex[..2], &hex[2..4], hex);
+    if w.overflowed() {
+        return None;
+    }
+    let path = CString::new(w.as_str()).ok()?;
+    let fd = raw::raw_open(path.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC, 0);
+    if fd < 0 {
+        return None;
+    }
+    let mut list = Vec::with_capacity(HEADER + 64 * RECORD);
+    let mut chunk = [0u8; 8192];
+    let ok = loop {
+        let n = raw::raw_read(fd, chunk.as_mut_ptr() as *mut c_void, chunk.len());
+        if n <= 0 {
+            break n == 0;
+        }
+        list.extend_from_slice(&chunk[..n as usize]);
+        if list.len() > MAX_LIST_BYTES {
+            break false;
+        }
+    };
+    raw::raw_close(fd);
+    (ok && list.len() >= HEADER && &list[..8] == MAGIC).then_some(list)
+}
+
+/// A read/write temp file under `<root>/cache/`, and its path
+unsafe fn create_temp(root: &str, cloexec: bool) -> Option<(c_int, CString)> {
+    let mut buf = [0u8; 1100];
+    let mut w = crate::macros::StackWriter::new(&mut buf);
+    let _ = write!(w, "{}/cache", root);
+    let dir_len = w.as_str().len();
+    let _ = write!(
+        w,
+        "/.reassemble.{}.{}",
+        libc::getpid(),
+        TEMP_SEQ.fetch_add(1, Ordering::Relaxed)
+    );
+    if w.overflowed() {
+        return None;
+    }
+    let path = CString::new(w.as_str()).ok()?;
+    let mut flags = libc::O_RDWR | libc::O_CREAT | libc::O_EXCL;
+    if cloexec {
+        flags |= libc::O_CLOEXEC;
+    }
+    let mut fd = raw::raw_open(path.as_ptr(), flags, 0o600);
+    if fd < 0 && crate::get_errno() == libc::ENOENT {
+        let dir = CString::new(&w.as_str()[..dir_len]).ok()?;
+        raw::raw_mkdir(dir.as_ptr(), 0o755);
+        fd = raw::raw_open(path.as_ptr(), flags, 0o600);
+    }
+    (fd >= 0).then_some((fd, path))
+}
+
+unsafe fn discard_temp(fd: c_int, temp: &CString) {
+    raw::raw_close(fd);
+    raw::raw_unlink(temp.as_ptr());
+}
+
+/// Rename the expanded copy at `temp` to `<root>/cache/ab/cd/<hex>` and
+/// hand back a read-only fd on it. Concurrent expansions of the same blob
+/// race harmlessly: the content is the same. If the copy can't be cached,
+/// it is unlinked and served through `fd` as before, reclaimed on close.
+unsafe fn publish(root: &str, hex: &str, fd: c_int, temp: &CString, cloexec: bool) -> c_int {
+    raw::raw_fchmod(fd, 0o444);
+    let mut buf = [0u8; 1100];
+    let mut w = crate::macros::StackWriter::new(&mut buf);
+    let _ = write!(w, "{}/cache/{}", root, &hex[..2]);
+    let l1 = w.as_str().len();
+    let _ = write!(w, "/{}", &hex[2..4]);
+    let l2 = w.as_str().len();
+    let _ = write!(w, "/{}", hex);
+    let cached = match CString::new(w.as_str()) {
+        Ok(cached) if !w.overflowed() => cached,
+        _ => {
+            raw::raw_unlink(temp.as_ptr());
+            return fd;
+        }
+    };
+    for len in [l1, l2] {
+        if let Ok(dir) = CString::new(&w.as_str()[..len]) {
+            raw::raw_mkdir(dir.as_ptr(), 0o755);
+        }
+    }
+    if raw::raw_rename(temp.as_ptr(), cached.as_ptr()) != 0 {
+        raw::raw_unlink(temp.as_ptr());
+        return fd;
+    }
+    let mut flags = libc::O_RDONLY;
+    if cloexec {
+        flags |= libc::O_CLOEXEC;
+    }
+    let ro = raw::raw_open(cached.as_ptr(), flags, 0);
+    if ro < 0 {
+        return fd;
+    }
+    raw::raw_close(fd);
+    ro
+}
+
+/// Open blob `hash` of `len` bytes read-only, under `root` or a storage
+/// tier; -1 if it isn't stored whole anywhere. Ingest and chunking write
+/// `.bin` names; blobs stored through the plain CAS API (e.g. a leaf that
+/// was already in the store, or a symlink target) have no extension.
+pub(crate) unsafe fn open_cas_blob(root: &str, hash: &[u8], len: u64) -> c_int {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = [0u8; 64];
+    for (i, b) in hash.iter().take(32).enumerate() {
+        hex[i * 2] = HEX[(b >> 4) as usize];
+        hex[i * 2 + 1] = HEX[(b & 0xf) as usize];
+    }
+    let Ok(hex) = std::str::from_utf8(&hex) else {
+        return -1;
+    };
+
+    for base in std::iter::once(root).chain(tier_roots().map(|t| t as &str)) {
+        for ext in [".bin", ""] {
+            let mut buf = [0u8; 200];
+            let mut w = crate::macros::StackWriter::new(&mut buf);
+            let _ = write!(
+                w,
+                "/blake3/{}/{}/{}_{}{}",
+                &hex[..2],
+                &hex[2..4],
+                hex,
+                len,
+                ext
+            );
+            let fd = open_under(base, w.as_str(), libc::O_RDONLY | libc::O_CLOEXEC);
+            if fd >= 0 {
+                return fd;
+            }
+        }
+    }
+    -1
+}
+
+/// Append one leaf to `dst` and `hasher`; returns the bytes copied
+unsafe fn copy_leaf(
+    root: &str,
+    hash: &[u8],
+    len: u64,
+    dst: c_int,
+    hasher: &mut blake3::Hasher,
+) -> Option<u64> {
+    let src = open_cas_blob(root, hash, len);
+    if src < 0 {
+        return None;
+    }
+
+    let mut copied = 0u64;
+    let mut buf = [0u8; 16384];
+    let ok = loop {
+        let n = raw::raw_read(src, buf.as_mut_ptr() as *mut c_void, buf.len());
+        if n <= 0 {
+            break n == 0;
+        }
+        if !write_all(dst, &buf[..n as usize]) {
+            break false;
+        }
+        hasher.update(&buf[..n as usize]);
+        copied += n as u64;
+    };
+    raw::raw_close(src);
+    ok.then_some(copied)
+}
+
+unsafe fn write_all(fd: c_int, mut data: &[u8]) -> bool {
+    while !data.is_empty() {
+        let n = raw::raw_write(fd, data.as_ptr() as *const c_void, data.len());
+        if n <= 0 {
+            return false;
+        }
+        data = &data[n as usize..];
+    }
+    true
+}
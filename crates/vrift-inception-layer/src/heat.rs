@@ -0,0 +1,193 @@
+// =============================================================================
+// heat.rs — Access heatmap counters (VRIFT_HEATMAP)
+// =============================================================================
+//
+// VRIFT_HEATMAP=<file> counts VFS accesses per (path prefix × op) and
+// appends them to <file> as TSV when the process exits (format: see
+// vrift_ipc::heatmap). VRIFT_HEATMAP_DEPTH sets how many path components
+// form a prefix (default 2). `vrift profile heatmap` renders the result and
+// vDird's warm start uses it to place the hottest entries in the VDir first.
+//
+// Counters live in a fixed open-addressed table of HEAT_SLOTS prefixes.
+// A slot is claimed once by CAS on its hash; once the table is full, new
+// prefixes are counted in HEAT_DROPPED only.
+//
+// Zero-allocation and lock-free: safe on every interposed syscall.
+// =============================================================================
+
+use std::cell::UnsafeCell;
+use std::ffi::CStr;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use vrift_ipc::heatmap::{heat_prefix, HEATMAP_DEFAULT_DEPTH, HEAT_OP_NAMES};
+
+#[cfg(target_os = "linux")]
+use crate::syscalls::linux_raw as raw;
+#[cfg(target_os = "macos")]
+use crate::syscalls::macos_raw as raw;
+
+/// Ops counted by the inception layer; indices match HEAT_OP_NAMES
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatOp {
+    /// stat served from the VDir
+    Stat = 0,
+    /// stat that missed the VDir and went to vDird
+    StatMiss = 1,
+    Open = 2,
+    OpenWrite = 3,
+    Readdir = 4,
+}
+
+const HEAT_OP_COUNT: usize = 5;
+const HEAT_SLOTS: usize = 512;
+const HEAT_PREFIX_MAX: usize = 120;
+
+/// Path components per prefix; 0 = heatmap disabled
+pub static HEAT_DEPTH: AtomicU32 = AtomicU32::new(0);
+
+/// Accesses not counted because the table was full
+pub static HEAT_DROPPED: AtomicU64 = AtomicU64::new(0);
+
+static DUMP_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+struct HeatSlot {
+    /// fnv1a of the prefix; 0 = free
+    hash: AtomicU64,
+    /// Set once `prefix`/`len` are written by the claiming thread
+    ready: AtomicBool,
+    len: AtomicU32,
+    prefix: UnsafeCell<[u8; HEAT_PREFIX_MAX]>,
+    counts: [AtomicU64; HEAT_OP_COUNT],
+}
+
+// Safety: `prefix` is written only by the thread that claimed the slot, and
+// read only after `ready` is published with Release.
+unsafe impl Sync for HeatSlot {}
+
+impl HeatSlot {
+    const fn new() -> Self {
+        Self {
+            hash: AtomicU64::new(0),
+            ready: AtomicBool::new(false),
+            len: AtomicU32::new(0),
+            prefix: UnsafeCell::new([0; HEAT_PREFIX_MAX]),
+            counts: [const { AtomicU64::new(0) }; HEAT_OP_COUNT],
+        }
+    }
+}
+
+static HEAT_TABLE: [HeatSlot; HEAT_SLOTS] = [const { HeatSlot::new() }; HEAT_SLOTS];
+
+/// Enable from VRIFT_HEATMAP / VRIFT_HEATMAP_DEPTH (called from init_logger)
+pub(crate) fn init_from_env() {
+    let path = unsafe { libc::getenv(c"VRIFT_HEATMAP".as_ptr()) };
+    if path.is_null() || unsafe { *path } == 0 {
+        return;
+    }
+    let depth_ptr = unsafe { libc::getenv(c"VRIFT_HEATMAP_DEPTH".as_ptr()) };
+    let depth = if depth_ptr.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(depth_ptr) }
+            .to_str()
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .filter(|&d| d > 0)
+    };
+    HEAT_DEPTH.store(
+        depth.unwrap_or(HEATMAP_DEFAULT_DEPTH as u32),
+        Ordering::Relaxed,
+    );
+}
+
+/// Count one `op` on manifest key `key`
+#[inline(always)]
+pub(crate) fn record(key: &str, op: HeatOp) {
+    let depth = HEAT_DEPTH.load(Ordering::Relaxed);
+    if depth != 0 {
+        record_slow(key, op, depth as usize);
+    }
+}
+
+#[inline(never)]
+fn record_slow(key: &str, op: HeatOp, depth: usize) {
+    if !DUMP_REGISTERED.load(Ordering::Relaxed) {
+        crate::state::register_atexit_once(&DUMP_REGISTERED, dump_heatmap);
+    }
+    let prefix = heat_prefix(key, depth);
+    if prefix.len() > HEAT_PREFIX_MAX {
+        HEAT_DROPPED.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    // 0 marks a free slot
+    let hash = vrift_ipc::fnv1a_hash(prefix).max(1);
+
+    let start = (hash as usize) % HEAT_SLOTS;
+    for i in 0..HEAT_SLOTS {
+        let slot = &HEAT_TABLE[(start + i) % HEAT_SLOTS];
+        let current = slot.hash.load(Ordering::Acquire);
+        let owned = current == hash
+            || (current == 0
+                && match slot
+                    .hash
+                    .compare_exchange(0, hash, Ordering::AcqRel, Ordering::Acquire)
+                {
+                    Ok(_) => {
+                        let dst = unsafe { &mut *slot.prefix.get() };
+                        dst[..prefix.len()].copy_from_slice(prefix.as_bytes());
+                        slot.len.store(prefix.len() as u32, Ordering::Relaxed);
+                        slot.ready.store(true, Ordering::Release);
+                        true
+                    }
+                    Err(winner) => winner == hash,
+                });
+        if owned {
+            slot.counts[op as usize].fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+    HEAT_DROPPED.fetch_add(1, Ordering::Relaxed);
+}
+
+extern "C" fn dump_heatmap() {
+    let path = unsafe { libc::getenv(c"VRIFT_HEATMAP".as_ptr()) };
+    if path.is_null() {
+        return;
+    }
+    let fd = unsafe {
+        raw::raw_open(
+            path,
+            libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND | libc::O_CLOEXEC,
+            0o644,
+        )
+    };
+    if fd < 0 {
+        return;
+    }
+
+    for slot in HEAT_TABLE.iter() {
+        if !slot.ready.load(Ordering::Acquire) {
+            continue;
+        }
+        let len = slot.len.load(Ordering::Relaxed) as usize;
+        let bytes = &unsafe { &*slot.prefix.get() }[..len];
+        let Ok(prefix) = std::str::from_utf8(bytes) else {
+            continue;
+        };
+        for (op, count) in slot.counts.iter().enumerate() {
+            let count = count.load(Ordering::Relaxed);
+            if count == 0 {
+                continue;
+            }
+            let mut buf = [0u8; 256];
+            let mut w = crate::macros::StackWriter::new(&mut buf);
+            let _ = writeln!(w, "{}\t{}\t{}", prefix, HEAT_OP_NAMES[op], count);
+            // One write per line: O_APPEND keeps concurrent exits from interleaving
+            let line = w.as_str();
+            unsafe { raw::raw_write(fd, line.as_ptr() as *const libc::c_void, line.len()) };
+        }
+    }
+    unsafe { raw::raw_close(fd) };
+}
@@ -0,0 +1,200 @@
+// =============================================================================
+// hydrate.rs — Materialize-on-demand working tree (VRIFT_HYDRATE)
+// =============================================================================
+//
+// In hydrate mode the project directory on disk only holds stubs: files are
+// either absent or zero-length placeholders. The first open() through the
+// shim copies the CAS blob over the stub, and from then on the path is served
+// as a plain real file (tracked for Live Ingest like any other write).
+//
+// Format: VRIFT_HYDRATE="1"               every manifest path
+//         VRIFT_HYDRATE="src,assets,!vendor"
+//   Entries are directory prefixes relative to the project root. The longest
+//   matching entry wins; `!` excludes. With no positive entries, everything
+//   not excluded is hydrated.
+//
+// A real file that is non-empty (or whose manifest size is 0) is treated as
+// already materialized and never overwritten: hydration only ever replaces
+// a stub, so it can't clobber local edits.
+// =============================================================================
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use crate::state::FixedString;
+use libc::{c_char, c_void};
+
+#[cfg(target_os = "linux")]
+use crate::syscalls::linux_raw as raw;
+#[cfg(target_os = "macos")]
+use crate::syscalls::macos_raw as raw;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SPEC: OnceLock<FixedString<1024>> = OnceLock::new();
+
+/// Files copied from CAS on first open
+pub static HYDRATED_FILES: AtomicU64 = AtomicU64::new(0);
+/// Bytes copied from CAS on first open
+pub static HYDRATED_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Hydrations that failed and fell back to the virtual (CAS-backed) open
+pub static HYDRATE_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Apply a VRIFT_HYDRATE spec. "0"/"off"/empty leaves hydration disabled.
+pub fn apply_hydrate_spec(spec: &[u8]) {
+    let spec = spec.trim_ascii();
+    if spec.is_empty() || spec == b"0" || spec.eq_ignore_ascii_case(b"off") {
+        return;
+    }
+    let all = spec == b"1" || spec == b"*" || spec.eq_ignore_ascii_case(b"all");
+    let mut fs = FixedString::<1024>::new();
+    if !all {
+        fs.set(std::str::from_utf8(spec).unwrap_or(""));
+    }
+    let _ = SPEC.set(fs);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Policy decision for a manifest key ("/src/main.rs")
+pub fn should_hydrate(manifest_key: &str) -> bool {
+    if !is_enabled() {
+        return false;
+    }
+    let spec = SPEC.get().map(|s| s.as_str()).unwrap_or("");
+    vrift_config::hydrate_policy_matches(spec.split(','), manifest_key)
+}
+
+/// Make `real_path` hold the blob's content, unless it already does.
+/// Returns false if the stub couldn't be replaced; the caller then falls back
+/// to the normal virtual open.
+pub(crate) unsafe fn materialize(
+    real_path: &str,
+    blob_path: &str,
+    entry: &vrift_ipc::VnodeEntry,
+) -> bool {
+    let Ok(real_c) = std::ffi::CString::new(real_path) else {
+        return false;
+    };
+
+    let mut st: libc::stat = std::mem::zeroed();
+    if raw::raw_stat(real_c.as_ptr(), &mut st) == 0 {
+        if (st.st_mode & libc::S_IFMT) != libc::S_IFREG {
+            return false;
+        }
+        if st.st_size > 0 || entry.size == 0 {
+            return true; // Already materialized (or nothing to copy)
+        }
+    } else {
+        create_parents(real_path);
+    }
+
+    let ok = copy_blob(real_path, blob_path, entry);
+    if ok {
+        HYDRATED_FILES.fetch_add(1, Ordering::Relaxed);
+        HYDRATED_BYTES.fetch_add(entry.size, Ordering::Relaxed);
+        crate::summary::add(&crate::summary::FILES_SERVED, 1);
+        crate::summary::add(&crate::summary::CAS_BYTES, entry.size);
+    } else {
+        HYDRATE_FAILURES.fetch_add(1, Ordering::Relaxed);
+        crate::warnings::warn_user(crate::warnings::Warning::HydrateFailed, real_path);
+    }
+    ok
+}
+
+/// Copy into a sibling temp file and rename over the stub, so a concurrent
+/// reader never sees a half-written file.
+unsafe fn copy_blob(real_path: &str, blob_path: &str, entry: &vrift_ipc::VnodeEntry) -> bool {
+    use std::fmt::Write;
+    let mut tmp_buf = [0u8; 1100];
+    let mut writer = crate::macros::StackWriter::new(&mut tmp_buf);
+    let _ = write!(writer, "{}.vrift-hydrate.{}", real_path, libc::getpid());
+    let (Ok(tmp_c), Ok(real_c)) = (
+        std::ffi::CString::new(writer.as_str()),
+        std::ffi::CString::new(real_path),
+    ) else {
+        return false;
+    };
+
+    let src = crate::chunked::open_blob(blob_path, libc::O_RDONLY | libc::O_CLOEXEC);
+    if src < 0 {
+        return false;
+    }
+    let dst = raw::raw_open(
+        tmp_c.as_ptr(),
+        libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+        0o600,
+    );
+    if dst < 0 {
+        raw::raw_close(src);
+        return false;
+    }
+
+    let mut copied: u64 = 0;
+    let mut buf = [0u8; 16384];
+    let mut ok = true;
+    loop {
+        let n = raw::raw_read(src, buf.as_mut_ptr() as *mut c_void, buf.len());
+        if n == 0 {
+            break;
+        }
+        if n < 0 || !write_all(dst, &buf[..n as usize]) {
+            ok = false;
+            break;
+        }
+        copied += n as u64;
+    }
+    ok &= copied == entry.size;
+    ok &= raw::raw_fchmod(dst, (entry.mode & 0o7777) as libc::mode_t) == 0;
+    raw::raw_close(src);
+    raw::raw_close(dst);
+
+    if ok {
+        // Keep the manifest mtime so build tools don't see every file as new
+        let tv = libc::timeval {
+            tv_sec: entry.mtime as libc::time_t,
+            tv_usec: 0,
+        };
+        let times = [tv, tv];
+        raw::raw_utimes(tmp_c.as_ptr(), times.as_ptr());
+        ok = raw::raw_rename(tmp_c.as_ptr(), real_c.as_ptr()) == 0;
+    }
+    if !ok {
+        raw::raw_unlink(tmp_c.as_ptr());
+    }
+    ok
+}
+
+unsafe fn write_all(fd: libc::c_int, mut data: &[u8]) -> bool {
+    while !data.is_empty() {
+        let n = raw::raw_write(fd, data.as_ptr() as *const c_void, data.len());
+        if n <= 0 {
+            return false;
+        }
+        data = &data[n as usize..];
+    }
+    true
+}
+
+/// mkdir -p for the parent of `path` (absent stubs may have absent dirs too)
+unsafe fn create_parents(path: &str) {
+    let Some(end) = path.rfind('/') else {
+        return;
+    };
+    let mut buf = [0u8; 1024];
+    if end >= buf.len() {
+        return;
+    }
+    buf[..end].copy_from_slice(&path.as_bytes()[..end]);
+    for i in 1..=end {
+        if i == end || buf[i] == b'/' {
+            let saved = buf[i];
+            buf[i] = 0;
+            raw::raw_mkdir(buf.as_ptr() as *const c_char, 0o755);
+            buf[i] = saved;
+        }
+    }
+}
@@ -31,11 +31,11 @@ extern "C" {
 }
 #[cfg(target_os = "macos")]
 use crate::syscalls::misc::{
-    chflags_inception, chmod_inception, chown_inception, exchangedata_inception, execve_inception,
+    chflags_inception, chmod_inception, chown_inception, exchangedata_inception,
     faccessat_inception, fchflags_inception, fchmod_inception, fchmodat_inception,
     fchown_inception, fchownat_inception, flock_inception, futimens_inception, futimes_inception,
-    lchown_inception, link_inception, linkat_inception, mkdir_inception, mkdirat_inception,
-    posix_spawn_inception, posix_spawnp_inception, readlinkat_inception, removexattr_inception,
+    getxattr_inception, lchown_inception, link_inception, linkat_inception, listxattr_inception,
+    mkdir_inception, mkdirat_inception, readlinkat_inception, removexattr_inception,
     rmdir_inception, setrlimit_inception, setxattr_inception, symlink_inception,
     symlinkat_inception, truncate_inception, unlink_inception, unlinkat_inception,
     utimensat_inception, utimes_inception,
@@ -45,6 +45,10 @@ use crate::syscalls::misc::{
 use crate::syscalls::mmap::{mmap_inception, munmap_inception};
 #[cfg(target_os = "macos")]
 use crate::syscalls::path::realpath_inception;
+#[cfg(target_os = "macos")]
+use crate::syscalls::process::{
+    execve_inception, posix_spawn_inception, posix_spawnp_inception, vfork_inception,
+};
 
 use libc::{c_char, c_int, c_void, mode_t};
 
@@ -93,6 +97,8 @@ extern "C" {
         argv: *const *const c_char,
         envp: *const *const c_char,
     ) -> c_int;
+    #[link_name = "vfork"]
+    fn real_vfork() -> pid_t;
     #[link_name = "posix_spawn"]
     fn real_posix_spawn(
         pid: *mut pid_t,
@@ -199,6 +205,22 @@ extern "C" {
     ) -> c_int;
     #[link_name = "removexattr"]
     fn real_removexattr(path: *const c_char, name: *const c_char, options: c_int) -> c_int;
+    #[link_name = "getxattr"]
+    fn real_getxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *mut c_void,
+        size: size_t,
+        position: u32,
+        options: c_int,
+    ) -> libc::ssize_t;
+    #[link_name = "listxattr"]
+    fn real_listxattr(
+        path: *const c_char,
+        namebuf: *mut c_char,
+        size: size_t,
+        options: c_int,
+    ) -> libc::ssize_t;
     #[link_name = "utimes"]
     fn real_utimes(path: *const c_char, times: *const timeval) -> c_int;
     #[link_name = "dup"]
@@ -547,27 +569,34 @@ pub static IT_LINKAT: Interpose = Interpose {
     old_func: real_linkat as _,
 };
 #[cfg(target_os = "macos")]
-#[link_section = "__DATA,__nointerpose"]
+#[link_section = "__DATA,__interpose"]
 #[used]
 pub static IT_EXECVE: Interpose = Interpose {
     new_func: execve_inception as _,
     old_func: real_execve as _,
 };
 #[cfg(target_os = "macos")]
-#[link_section = "__DATA,__nointerpose"]
+#[link_section = "__DATA,__interpose"]
 #[used]
 pub static IT_POSIX_SPAWN: Interpose = Interpose {
     new_func: posix_spawn_inception as _,
     old_func: real_posix_spawn as _,
 };
 #[cfg(target_os = "macos")]
-#[link_section = "__DATA,__nointerpose"]
+#[link_section = "__DATA,__interpose"]
 #[used]
 pub static IT_POSIX_SPAWNP: Interpose = Interpose {
     new_func: posix_spawnp_inception as _,
     old_func: real_posix_spawnp as _,
 };
 #[cfg(target_os = "macos")]
+#[link_section = "__DATA,__interpose"]
+#[used]
+pub static IT_VFORK: Interpose = Interpose {
+    new_func: vfork_inception as _,
+    old_func: real_vfork as _,
+};
+#[cfg(target_os = "macos")]
 #[link_section = "__DATA,__nointerpose"]
 #[used]
 pub static IT_DLOPEN: Interpose = Interpose {
@@ -640,6 +669,20 @@ pub static IT_REMOVEXATTR: Interpose = Interpose {
 #[cfg(target_os = "macos")]
 #[link_section = "__DATA,__interpose"]
 #[used]
+pub static IT_GETXATTR: Interpose = Interpose {
+    new_func: getxattr_inception as _,
+    old_func: real_getxattr as _,
+};
+#[cfg(target_os = "macos")]
+#[link_section = "__DATA,__interpose"]
+#[used]
+pub static IT_LISTXATTR: Interpose = Interpose {
+    new_func: listxattr_inception as _,
+    old_func: real_listxattr as _,
+};
+#[cfg(target_os = "macos")]
+#[link_section = "__DATA,__interpose"]
+#[used]
 pub static IT_UTIMES: Interpose = Interpose {
     new_func: utimes_inception as _,
     old_func: real_utimes as _,
@@ -794,6 +837,70 @@ pub static IT_READLINKAT: Interpose = Interpose {
 // On Linux, LD_PRELOAD works by symbol interposition. We export functions
 // with the same names as libc functions to intercept them.
 
+// syscall(3) is variadic; on x86_64 and aarch64 Linux variadic longs travel
+// in the same registers (and stack slot) as fixed ones, so six fixed
+// arguments read whatever the caller passed.
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn syscall(
+    nr: libc::c_long,
+    a1: libc::c_long,
+    a2: libc::c_long,
+    a3: libc::c_long,
+    a4: libc::c_long,
+    a5: libc::c_long,
+    a6: libc::c_long,
+) -> libc::c_long {
+    crate::uring::syscall_inception(nr, [a1, a2, a3, a4, a5, a6])
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn opendir(path: *const c_char) -> *mut libc::DIR {
+    crate::syscalls::dir::opendir_inception(path) as *mut libc::DIR
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn readdir(dirp: *mut libc::DIR) -> *mut libc::dirent {
+    // dirent and dirent64 share a layout on 64-bit glibc
+    crate::syscalls::dir::readdir_inception(dirp as *mut c_void) as *mut libc::dirent
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn readdir64(dirp: *mut libc::DIR) -> *mut libc::dirent64 {
+    crate::syscalls::dir::readdir64_inception(dirp as *mut c_void)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn getdents64(
+    fd: c_int,
+    buf: *mut c_void,
+    count: libc::size_t,
+) -> libc::ssize_t {
+    crate::syscalls::dir::getdents64_inception(fd, buf, count)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn rewinddir(dirp: *mut libc::DIR) {
+    crate::syscalls::dir::rewinddir_inception(dirp as *mut c_void)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn dirfd(dirp: *mut libc::DIR) -> c_int {
+    crate::syscalls::dir::dirfd_inception(dirp as *mut c_void)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn closedir(dirp: *mut libc::DIR) -> c_int {
+    crate::syscalls::dir::closedir_inception(dirp as *mut c_void)
+}
+
 #[cfg(target_os = "linux")]
 #[no_mangle]
 pub unsafe extern "C" fn open(path: *const c_char, flags: c_int, mode: mode_t) -> c_int {
@@ -828,6 +935,47 @@ pub unsafe extern "C" fn openat64(
     crate::syscalls::open::velo_openat_impl(dirfd, path, flags, mode)
 }
 
+// Linux fd lifecycle: the last close of a CoW fd triggers reingest, and
+// dups must carry the FdTable entry (and CoW reference) to the new fd.
+// glibc's stdio closes through internal aliases, so fclose() is not seen.
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn close(fd: c_int) -> c_int {
+    crate::syscalls::io::close_inception(fd)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn dup(oldfd: c_int) -> c_int {
+    crate::syscalls::io::dup_inception(oldfd)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn dup2(oldfd: c_int, newfd: c_int) -> c_int {
+    crate::syscalls::io::dup2_inception(oldfd, newfd)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn dup3(oldfd: c_int, newfd: c_int, flags: c_int) -> c_int {
+    crate::syscalls::io::dup3_inception(oldfd, newfd, flags)
+}
+
+// fcntl is variadic; the third argument is always passed in a register on
+// x86_64/aarch64 Linux, so reading it as a long is safe for every command
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn fcntl(fd: c_int, cmd: c_int, arg: libc::c_long) -> c_int {
+    crate::syscalls::io::fcntl_inception(fd, cmd, arg)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn fcntl64(fd: c_int, cmd: c_int, arg: libc::c_long) -> c_int {
+    crate::syscalls::io::fcntl_inception(fd, cmd, arg)
+}
+
 // Linux chmod interception - blocks VFS mutations
 #[cfg(target_os = "linux")]
 #[no_mangle]
@@ -846,6 +994,12 @@ pub unsafe extern "C" fn fchmodat(
     crate::syscalls::misc::fchmodat_inception(dirfd, path, mode, flags)
 }
 
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn fchmod(fd: c_int, mode: mode_t) -> c_int {
+    crate::syscalls::misc::fchmod_inception(fd, mode)
+}
+
 // Linux unlink/rm interception - blocks VFS mutations
 #[cfg(target_os = "linux")]
 #[no_mangle]
@@ -899,6 +1053,56 @@ pub unsafe extern "C" fn access(path: *const c_char, mode: c_int) -> c_int {
     crate::syscalls::stat::access_inception(path, mode)
 }
 
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn faccessat(
+    dirfd: c_int,
+    path: *const c_char,
+    mode: c_int,
+    flags: c_int,
+) -> c_int {
+    crate::syscalls::misc::faccessat_inception(dirfd, path, mode, flags)
+}
+
+// Linux fstat: report the virtual identity for tracked VFS fds (same ino/dev as stat)
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn fstat(fd: c_int, buf: *mut libc::stat) -> c_int {
+    crate::syscalls::stat::fstat_inception(fd, buf)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn fstat64(fd: c_int, buf: *mut libc::stat) -> c_int {
+    crate::syscalls::stat::fstat_inception(fd, buf)
+}
+
+// glibc < 2.33 inlines fstat() as __fxstat(_STAT_VER, fd, buf)
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn __fxstat(_ver: c_int, fd: c_int, buf: *mut libc::stat) -> c_int {
+    crate::syscalls::stat::fstat_inception(fd, buf)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn __fxstat64(_ver: c_int, fd: c_int, buf: *mut libc::stat) -> c_int {
+    crate::syscalls::stat::fstat_inception(fd, buf)
+}
+
+// Linux statx: coreutils and Rust's std stat through it; the only call with btime
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn statx(
+    dirfd: c_int,
+    path: *const c_char,
+    flags: c_int,
+    mask: libc::c_uint,
+    buf: *mut crate::syscalls::stat::statx,
+) -> c_int {
+    crate::syscalls::stat::statx_inception(dirfd, path, flags, mask, buf)
+}
+
 // Linux utimensat/touch interception
 #[cfg(target_os = "linux")]
 #[no_mangle]
@@ -958,6 +1162,58 @@ pub unsafe extern "C" fn lchown(
     crate::syscalls::misc::lchown_inception(path, owner, group)
 }
 
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: libc::size_t,
+) -> libc::ssize_t {
+    crate::syscalls::path::readlink_inception(path, buf, bufsiz)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn execve(
+    path: *const c_char,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    crate::syscalls::process::execve_inception(path, argv, envp)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn vfork() -> libc::pid_t {
+    crate::syscalls::process::vfork_inception()
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn posix_spawn(
+    pid: *mut libc::pid_t,
+    path: *const c_char,
+    fa: *const libc::posix_spawn_file_actions_t,
+    attr: *const libc::posix_spawnattr_t,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    crate::syscalls::process::posix_spawn_inception(pid, path, fa as _, attr as _, argv, envp)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn posix_spawnp(
+    pid: *mut libc::pid_t,
+    file: *const c_char,
+    fa: *const libc::posix_spawn_file_actions_t,
+    attr: *const libc::posix_spawnattr_t,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    crate::syscalls::process::posix_spawnp_inception(pid, file, fa as _, attr as _, argv, envp)
+}
+
 #[cfg(target_os = "linux")]
 #[no_mangle]
 pub unsafe extern "C" fn readlinkat(
@@ -993,6 +1249,19 @@ pub unsafe extern "C" fn ftruncate(fd: c_int, length: libc::off_t) -> c_int {
     crate::syscalls::io::ftruncate_inception(fd, length)
 }
 
+// LFS builds (_FILE_OFFSET_BITS=64, e.g. CPython) link the *64 names
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn truncate64(path: *const c_char, length: libc::off_t) -> c_int {
+    crate::syscalls::misc::truncate_inception(path, length)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn ftruncate64(fd: c_int, length: libc::off_t) -> c_int {
+    crate::syscalls::io::ftruncate_inception(fd, length)
+}
+
 #[cfg(target_os = "linux")]
 #[no_mangle]
 pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
@@ -1010,6 +1279,18 @@ pub unsafe extern "C" fn renameat(
     crate::syscalls::misc::renameat_inception_linux(oldfd, old, newfd, new)
 }
 
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn renameat2(
+    oldfd: c_int,
+    old: *const c_char,
+    newfd: c_int,
+    new: *const c_char,
+    flags: libc::c_uint,
+) -> c_int {
+    crate::syscalls::misc::renameat2_inception(oldfd, old, newfd, new, flags)
+}
+
 #[cfg(target_os = "linux")]
 #[no_mangle]
 pub unsafe extern "C" fn link(oldpath: *const c_char, newpath: *const c_char) -> c_int {
@@ -1034,6 +1315,123 @@ pub unsafe extern "C" fn futimens(fd: c_int, times: *const libc::timespec) -> c_
     crate::syscalls::misc::futimens_inception(fd, times)
 }
 
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn getxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: libc::size_t,
+) -> libc::ssize_t {
+    crate::syscalls::misc::getxattr_inception(path, name, value, size, false)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn lgetxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: libc::size_t,
+) -> libc::ssize_t {
+    crate::syscalls::misc::getxattr_inception(path, name, value, size, true)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn fgetxattr(
+    fd: c_int,
+    name: *const c_char,
+    value: *mut c_void,
+    size: libc::size_t,
+) -> libc::ssize_t {
+    crate::syscalls::misc::fgetxattr_inception(fd, name, value, size)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn listxattr(
+    path: *const c_char,
+    list: *mut c_char,
+    size: libc::size_t,
+) -> libc::ssize_t {
+    crate::syscalls::misc::listxattr_inception(path, list, size, false)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn llistxattr(
+    path: *const c_char,
+    list: *mut c_char,
+    size: libc::size_t,
+) -> libc::ssize_t {
+    crate::syscalls::misc::listxattr_inception(path, list, size, true)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn flistxattr(
+    fd: c_int,
+    list: *mut c_char,
+    size: libc::size_t,
+) -> libc::ssize_t {
+    crate::syscalls::misc::flistxattr_inception(fd, list, size)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn setxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: libc::size_t,
+    flags: c_int,
+) -> c_int {
+    crate::syscalls::misc::setxattr_inception(path, name, value, size, flags, false)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn lsetxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: libc::size_t,
+    flags: c_int,
+) -> c_int {
+    crate::syscalls::misc::setxattr_inception(path, name, value, size, flags, true)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn fsetxattr(
+    fd: c_int,
+    name: *const c_char,
+    value: *const c_void,
+    size: libc::size_t,
+    flags: c_int,
+) -> c_int {
+    crate::syscalls::misc::fsetxattr_inception(fd, name, value, size, flags)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn removexattr(path: *const c_char, name: *const c_char) -> c_int {
+    crate::syscalls::misc::removexattr_inception(path, name, false)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn lremovexattr(path: *const c_char, name: *const c_char) -> c_int {
+    crate::syscalls::misc::removexattr_inception(path, name, true)
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn fremovexattr(fd: c_int, name: *const c_char) -> c_int {
+    crate::syscalls::misc::fremovexattr_inception(fd, name)
+}
+
 #[cfg(target_os = "linux")]
 #[no_mangle]
 pub unsafe extern "C" fn sendfile(
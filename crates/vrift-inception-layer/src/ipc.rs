@@ -9,19 +9,82 @@
 // Allowed:     ctx.access(), ctx.close(), ctx.fcntl(), ctx.read(), ctx.write()
 // Forbidden:   libc::access, libc::close, libc::fcntl, std::fs::*, std::io::*
 // =============================================================================
+use crate::budget::BudgetClass;
 use crate::raw_context::RawContext;
+use crate::sync::RecursiveMutex;
 use libc::c_int;
 use std::ptr;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
+use vrift_ipc::Capabilities;
 
 /// The singleton RawContext for IPC operations.
 /// All raw syscall access must go through this instance.
 const CTX: &RawContext = &RawContext::INSTANCE;
 
+/// Daemon sockets this process has open: the persistent vDird connection
+/// and one per vriftd RPC in flight. A fork() copies them into the child.
+const MAX_OPEN_SOCKETS: usize = 64;
+static OPEN_SOCKETS: [AtomicI32; MAX_OPEN_SOCKETS] =
+    [const { AtomicI32::new(-1) }; MAX_OPEN_SOCKETS];
+
+fn track_socket(fd: c_int) {
+    // Best effort: an untracked socket only leaks into a forked child
+    for slot in &OPEN_SOCKETS {
+        if slot
+            .compare_exchange(-1, fd, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            return;
+        }
+    }
+}
+
+fn untrack_socket(fd: c_int) {
+    for slot in &OPEN_SOCKETS {
+        if slot
+            .compare_exchange(fd, -1, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            return;
+        }
+    }
+}
+
+/// pthread_atfork hooks (see state/fork.rs). VDIRD_CONN is a leaf lock,
+/// so it's taken after the state locks and released before them.
+pub(crate) fn fork_prepare() {
+    VDIRD_CONN.fork_prepare();
+}
+
+pub(crate) fn fork_parent() {
+    VDIRD_CONN.fork_parent();
+}
+
+/// Forked child: close its copies of the parent's sockets. The child can't
+/// finish requests other threads had in flight, mustn't interleave frames
+/// with the parent on the shared vDird connection, and its copies would
+/// keep the daemon's end open after the parent is done with them.
+pub(crate) fn fork_child() {
+    for slot in &OPEN_SOCKETS {
+        let fd = slot.swap(-1, Ordering::AcqRel);
+        if fd >= 0 {
+            unsafe { CTX.close(fd) };
+        }
+    }
+    VDIRD_CONN.fork_child();
+    // Its fd was closed above; the child connects on first use
+    if let Some(conn) = VDIRD_CONN.lock().take() {
+        conn.fd.store(-1, Ordering::Release);
+    }
+}
+
 /// BUG-007b: Raw close for IPC socket FDs — avoids interposed close_inception
 /// which would trigger reingest IPC and recursive socket operations.
 #[inline(always)]
-unsafe fn ipc_raw_close(fd: c_int) -> c_int {
+pub(crate) unsafe fn ipc_raw_close(fd: c_int) -> c_int {
+    untrack_socket(fd);
     CTX.close(fd)
 }
 
@@ -58,6 +121,7 @@ pub(crate) unsafe fn raw_unix_connect(path: &str) -> c_int {
     if fd < 0 {
         return -1;
     }
+    track_socket(fd);
 
     // RFC-0053: Set socket timeouts BEFORE connect to prevent UE process states
     // 5 second timeout for both send and receive
@@ -167,6 +231,7 @@ unsafe fn sync_rpc(
             );
             inception_record!(EventType::CircuitTripped, 0, count as i32);
         }
+        crate::warnings::warn_user(crate::warnings::Warning::DaemonUnreachable, socket_path);
         return None;
     }
 
@@ -230,6 +295,10 @@ fn is_manifest_request(request: &vrift_ipc::VeloRequest) -> bool {
             | vrift_ipc::VeloRequest::ManifestUpdateMtime { .. }
             | vrift_ipc::VeloRequest::ManifestReingest { .. }
             | vrift_ipc::VeloRequest::ManifestListDir { .. }
+            | vrift_ipc::VeloRequest::ManifestListDirPage { .. }
+            | vrift_ipc::VeloRequest::ManifestGetXattrs { .. }
+            | vrift_ipc::VeloRequest::ManifestSetXattr { .. }
+            | vrift_ipc::VeloRequest::ManifestResolve { .. }
     )
 }
 
@@ -246,23 +315,199 @@ unsafe fn cache_vdird_socket(vdird_socket: &str) {
         // and all callers write the same value (deterministic from project root).
         let state = &mut *ptr;
         if state.vdird_socket_path.is_empty() {
-            state.vdird_socket_path.set(vdird_socket);
+            let root_offset = state.root_offset;
+            state.vdird_socket_path.set(crate::path::host_to_local(
+                vdird_socket,
+                root_offset.as_str(),
+            ));
             inception_info!("Cached vDird socket: {}", vdird_socket);
         }
     }
 }
 
+/// A daemon socket as a byte stream for vrift_ipc's framing, over RawContext
+struct RawSocket(c_int);
+
+impl std::io::Read for RawSocket {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = unsafe { CTX.read(self.0, buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+}
+
+impl std::io::Write for RawSocket {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = unsafe { CTX.write(self.0, buf.as_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl vrift_ipc::mux::MuxRead for RawSocket {
+    fn set_read_timeout(&mut self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+        let us = timeout.map_or(0, |t| t.as_micros().max(1) as u64);
+        unsafe { set_socket_opt_timeout_us(self.0, libc::SO_RCVTIMEO, us) };
+        Ok(())
+    }
+}
+
+/// The process's connection to vDird. Every thread multiplexes its manifest
+/// requests over it instead of connecting per request.
+pub(crate) struct VdirdConn {
+    path: String,
+    fd: AtomicI32,
+    mux: vrift_ipc::mux::SyncMux<RawSocket, RawSocket>,
+    /// Agreed by Negotiate when the connection was made
+    capabilities: Capabilities,
+    /// Held around each call when vDird can't take pipelined requests
+    one_at_a_time: Mutex<()>,
+}
+
+impl Drop for VdirdConn {
+    fn drop(&mut self) {
+        let fd = self.fd.swap(-1, Ordering::AcqRel);
+        if fd >= 0 {
+            unsafe { ipc_raw_close(fd) };
+        }
+    }
+}
+
+/// Replaced when it breaks; callers hold an Arc, so the old connection
+/// closes once the last request on it is done
+pub(crate) static VDIRD_CONN: RecursiveMutex<Option<Arc<VdirdConn>>> = RecursiveMutex::new(None);
+
+/// Response wait when the request has no time budget
+const VDIRD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// VDIRD_CAPABILITIES before the first connection to vDird
+const NOT_NEGOTIATED: u64 = u64::MAX;
+
+/// Capabilities of the latest vDird connection, for callers that don't
+/// hold it (the VDir lookup)
+static VDIRD_CAPABILITIES: AtomicU64 = AtomicU64::new(NOT_NEGOTIATED);
+
+/// Whether VDir mmap lookups may be used. Until this process has talked to
+/// vDird the mapping is trusted on its header alone, so lookups that hit
+/// it never need a connection; after that, only if vDird agreed to it.
+pub(crate) fn mmap_cache_agreed() -> bool {
+    mapping_agreed(Capabilities::MMAP_CACHE)
+}
+
+/// Whether the manifest mmap may answer absence, on the same terms as
+/// [`mmap_cache_agreed`]
+pub(crate) fn manifest_mmap_agreed() -> bool {
+    mapping_agreed(Capabilities::MANIFEST_MMAP)
+}
+
+fn mapping_agreed(capability: Capabilities) -> bool {
+    let bits = VDIRD_CAPABILITIES.load(Ordering::Relaxed);
+    bits == NOT_NEGOTIATED || Capabilities(bits).contains(capability)
+}
+
+/// Whether this workspace's vDird agreed to `capability`, connecting to it
+/// if needed. False when it can't be reached.
+pub(crate) unsafe fn vdird_agreed(
+    state: &crate::state::InceptionLayerState,
+    capability: Capabilities,
+) -> bool {
+    if state.vdird_socket_path.is_empty() {
+        // Registering with vriftd (any request does) caches the socket
+        sync_rpc(&state.socket_path, &vrift_ipc::VeloRequest::Status);
+    }
+    let path = state.vdird_socket_path.as_str();
+    !path.is_empty() && vdird_conn(path).is_some_and(|conn| conn.capabilities.contains(capability))
+}
+
+/// Agree on capabilities over a fresh connection. A vDird that predates
+/// Negotiate answers with an error and gets none; one that dropped the
+/// connection instead is reconnected to. Returns the usable fd, or None
+/// when vDird can't talk to this shim at all.
+unsafe fn negotiate_on_fd(path: &str, fd: c_int) -> Option<(c_int, Capabilities)> {
+    let request = vrift_ipc::VeloRequest::Negotiate {
+        client_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_version: vrift_ipc::PROTOCOL_VERSION,
+        capabilities: Capabilities::SUPPORTED,
+    };
+    let response = if send_request_on_fd(fd, &request) {
+        recv_response_on_fd(fd)
+    } else {
+        None
+    };
+    match response {
+        Some(vrift_ipc::VeloResponse::NegotiateAck {
+            protocol_version: Some(_),
+            capabilities,
+            ..
+        }) => Some((fd, capabilities)),
+        Some(vrift_ipc::VeloResponse::Error(e)) if e.kind == vrift_ipc::VeloErrorKind::Internal => {
+            Some((fd, Capabilities::empty()))
+        }
+        None => {
+            ipc_raw_close(fd);
+            let fd = raw_unix_connect(path);
+            (fd >= 0).then_some((fd, Capabilities::empty()))
+        }
+        // No common protocol version, or this peer isn't let in
+        Some(_) => {
+            inception_warn!(
+                "vDird at {} refused Negotiate for protocol {}",
+                path,
+                vrift_ipc::PROTOCOL_VERSION
+            );
+            ipc_raw_close(fd);
+            None
+        }
+    }
+}
+
+/// The live connection to `path`, connecting if there's none.
+/// None when vDird can't be reached.
+unsafe fn vdird_conn(path: &str) -> Option<Arc<VdirdConn>> {
+    // Held across connect so concurrent callers don't each open one
+    let mut slot = VDIRD_CONN.lock();
+    if let Some(conn) = slot.as_ref() {
+        if conn.path == path && !conn.mux.is_broken() {
+            return Some(Arc::clone(conn));
+        }
+    }
+    let fd = raw_unix_connect(path);
+    if fd < 0 {
+        return None;
+    }
+    let (fd, capabilities) = negotiate_on_fd(path, fd)?;
+    inception_record!(crate::state::EventType::IpcSuccess, 0, fd);
+    VDIRD_CAPABILITIES.store(capabilities.bits(), Ordering::Relaxed);
+    let conn = Arc::new(VdirdConn {
+        path: path.to_string(),
+        fd: AtomicI32::new(fd),
+        mux: vrift_ipc::mux::SyncMux::new(RawSocket(fd), RawSocket(fd)),
+        capabilities,
+        one_at_a_time: Mutex::new(()),
+    });
+    *slot = Some(Arc::clone(&conn));
+    Some(conn)
+}
+
 /// Phase 1.2: Send RPC directly to vDird socket (no RegisterWorkspace needed).
 /// vDird is already project-scoped, so no workspace registration is required.
 unsafe fn sync_rpc_vdird(
     vdird_socket_path: &str,
     request: &vrift_ipc::VeloRequest,
+    class: BudgetClass,
 ) -> Option<vrift_ipc::VeloResponse> {
     use crate::state::{
         EventType, CIRCUIT_BREAKER_FAILED_COUNT, CIRCUIT_BREAKER_THRESHOLD, CIRCUIT_RECOVERY_DELAY,
         CIRCUIT_TRIPPED, CIRCUIT_TRIP_TIME,
     };
-    use std::sync::atomic::Ordering;
 
     // If no vDird socket cached yet, fall back to daemon socket via sync_rpc
     if vdird_socket_path.is_empty() {
@@ -294,79 +539,225 @@ unsafe fn sync_rpc_vdird(
         }
     }
 
-    let fd = raw_unix_connect(vdird_socket_path);
-    if fd < 0 {
-        let count = CIRCUIT_BREAKER_FAILED_COUNT.fetch_add(1, Ordering::SeqCst) + 1;
-        let threshold = CIRCUIT_BREAKER_THRESHOLD.load(Ordering::Relaxed);
-        inception_record!(EventType::IpcFail, 0, count as i32);
-        if count >= threshold && !CIRCUIT_TRIPPED.swap(true, Ordering::SeqCst) {
-            let now = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_secs())
-                .unwrap_or(0);
-            CIRCUIT_TRIP_TIME.store(now, Ordering::Relaxed);
-            inception_error!(
-                "VDIRD CONNECTION FAILED {} TIMES. CIRCUIT BREAKER TRIPPED.",
-                count
+    let budget_us = crate::budget::budget_us(class);
+    let start_us = if budget_us != 0 {
+        crate::budget::monotonic_us()
+    } else {
+        0
+    };
+
+    // A broken connection (vDird restarted) gets one retry on a fresh one
+    for _ in 0..2 {
+        let Some(conn) = vdird_conn(vdird_socket_path) else {
+            let count = CIRCUIT_BREAKER_FAILED_COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+            let threshold = CIRCUIT_BREAKER_THRESHOLD.load(Ordering::Relaxed);
+            inception_record!(EventType::IpcFail, 0, count as i32);
+            if count >= threshold && !CIRCUIT_TRIPPED.swap(true, Ordering::SeqCst) {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                CIRCUIT_TRIP_TIME.store(now, Ordering::Relaxed);
+                inception_error!(
+                    "VDIRD CONNECTION FAILED {} TIMES. CIRCUIT BREAKER TRIPPED.",
+                    count
+                );
+                inception_record!(EventType::CircuitTripped, 0, count as i32);
+            }
+            crate::warnings::warn_user(
+                crate::warnings::Warning::VdirdUnreachable,
+                vdird_socket_path,
             );
-            inception_record!(EventType::CircuitTripped, 0, count as i32);
+            return None;
+        };
+        CIRCUIT_BREAKER_FAILED_COUNT.store(0, Ordering::Relaxed);
+
+        // Wait no longer than whatever budget is left
+        let timeout = if budget_us != 0 {
+            let spent = crate::budget::monotonic_us().saturating_sub(start_us);
+            std::time::Duration::from_micros((budget_us as u64).saturating_sub(spent).max(1))
+        } else {
+            VDIRD_TIMEOUT
+        };
+
+        crate::summary::add(&crate::summary::IPC_CALLS, 1);
+        let _serial = (!conn.capabilities.contains(Capabilities::BATCH_REQUESTS)).then(|| {
+            conn.one_at_a_time
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+        });
+        match conn.mux.call(request, Some(timeout)) {
+            Ok(response) => {
+                // Over budget: drop the answer so the caller takes passthrough
+                if budget_us != 0 {
+                    let elapsed_us = crate::budget::monotonic_us().saturating_sub(start_us);
+                    if elapsed_us > budget_us as u64 {
+                        crate::budget::record_violation(class, elapsed_us);
+                        inception_record!(
+                            EventType::BudgetExceeded,
+                            class as u64,
+                            elapsed_us as i32
+                        );
+                        return None;
+                    }
+                }
+                return Some(response);
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                ) =>
+            {
+                if budget_us != 0 {
+                    let elapsed_us = crate::budget::monotonic_us().saturating_sub(start_us);
+                    crate::budget::record_violation(class, elapsed_us);
+                    inception_record!(EventType::BudgetExceeded, class as u64, elapsed_us as i32);
+                }
+                return None;
+            }
+            Err(_) => continue,
         }
-        return None;
     }
+    None
+}
 
-    inception_record!(EventType::IpcSuccess, 0, fd);
-    CIRCUIT_BREAKER_FAILED_COUNT.store(0, Ordering::Relaxed);
+/// Set one of SO_RCVTIMEO / SO_SNDTIMEO on an IPC socket (0 = no timeout)
+unsafe fn set_socket_opt_timeout_us(fd: c_int, opt: c_int, us: u64) {
+    let timeout = libc::timeval {
+        tv_sec: (us / 1_000_000) as _,
+        tv_usec: (us % 1_000_000) as _,
+    };
+    libc::setsockopt(
+        fd,
+        libc::SOL_SOCKET,
+        opt,
+        &timeout as *const _ as *const libc::c_void,
+        std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+    );
+}
 
-    // No RegisterWorkspace needed — vDird is already project-scoped
-    // Send request directly
-    if !send_request_on_fd(fd, request) {
-        ipc_raw_close(fd);
-        return None;
+/// errno for a reply that isn't the expected ack: the daemon's error kind,
+/// or EIO when there was no usable reply at all
+pub(crate) fn response_errno(response: Option<&vrift_ipc::VeloResponse>) -> libc::c_int {
+    match response {
+        Some(vrift_ipc::VeloResponse::Error(e)) => e.kind.errno(),
+        _ => libc::EIO,
     }
+}
 
-    let response = recv_response_on_fd(fd);
-    ipc_raw_close(fd);
-    response
+/// Unwrap a ManifestAck, mapping anything else to an errno
+pub(crate) fn manifest_ack(
+    response: Option<vrift_ipc::VeloResponse>,
+) -> Result<Option<vrift_ipc::VnodeEntry>, libc::c_int> {
+    match response {
+        Some(vrift_ipc::VeloResponse::ManifestAck { entry, .. }) => Ok(entry),
+        other => Err(response_errno(other.as_ref())),
+    }
 }
 
-pub(crate) unsafe fn sync_ipc_manifest_remove(vdird_socket: &str, path: &str) -> bool {
+pub(crate) unsafe fn sync_ipc_manifest_remove(
+    vdird_socket: &str,
+    path: &str,
+) -> Result<(), libc::c_int> {
     let request = vrift_ipc::VeloRequest::ManifestRemove {
         path: path.to_string(),
     };
-    matches!(
-        sync_rpc_vdird(vdird_socket, &request),
-        Some(vrift_ipc::VeloResponse::ManifestAck { .. })
-    )
+    manifest_ack(sync_rpc_vdird(vdird_socket, &request, BudgetClass::Mutate)).map(|_| ())
+}
+
+/// Extended attributes of the entry at `path`, sorted by name
+pub(crate) unsafe fn sync_ipc_manifest_get_xattrs(
+    vdird_socket: &str,
+    path: &str,
+) -> Result<Vec<vrift_ipc::Xattr>, libc::c_int> {
+    let request = vrift_ipc::VeloRequest::ManifestGetXattrs {
+        path: path.to_string(),
+    };
+    match sync_rpc_vdird(vdird_socket, &request, BudgetClass::Stat) {
+        Some(vrift_ipc::VeloResponse::ManifestXattrsAck { xattrs }) => Ok(xattrs),
+        other => Err(response_errno(other.as_ref())),
+    }
+}
+
+/// Manifest key `path` leads to after following its symlinks. A chain
+/// longer than vDird's hop limit is `Err(ELOOP)`.
+pub(crate) unsafe fn sync_ipc_manifest_resolve(
+    vdird_socket: &str,
+    path: &str,
+    follow_final: bool,
+) -> Result<String, libc::c_int> {
+    let request = vrift_ipc::VeloRequest::ManifestResolve {
+        path: path.to_string(),
+        follow_final,
+    };
+    match sync_rpc_vdird(vdird_socket, &request, BudgetClass::Stat) {
+        Some(vrift_ipc::VeloResponse::ManifestResolveAck { path }) => Ok(path),
+        other => Err(response_errno(other.as_ref())),
+    }
+}
+
+/// Set (`Some`) or remove (`None`) one extended attribute of the entry at
+/// `path`
+pub(crate) unsafe fn sync_ipc_manifest_set_xattr(
+    vdird_socket: &str,
+    path: &str,
+    name: &str,
+    value: Option<&[u8]>,
+) -> Result<(), libc::c_int> {
+    let request = vrift_ipc::VeloRequest::ManifestSetXattr {
+        path: path.to_string(),
+        name: name.to_string(),
+        value: value.map(<[u8]>::to_vec),
+    };
+    manifest_ack(sync_rpc_vdird(vdird_socket, &request, BudgetClass::Mutate)).map(|_| ())
 }
 
-pub(crate) unsafe fn sync_ipc_manifest_rename(vdird_socket: &str, old: &str, new: &str) -> bool {
+pub(crate) unsafe fn sync_ipc_manifest_rename(
+    vdird_socket: &str,
+    old: &str,
+    new: &str,
+) -> Result<(), libc::c_int> {
     let request = vrift_ipc::VeloRequest::ManifestRename {
         old_path: old.to_string(),
         new_path: new.to_string(),
     };
-    matches!(
-        sync_rpc_vdird(vdird_socket, &request),
-        Some(vrift_ipc::VeloResponse::ManifestAck { .. })
-    )
+    manifest_ack(sync_rpc_vdird(vdird_socket, &request, BudgetClass::Mutate)).map(|_| ())
 }
 
 pub(crate) unsafe fn sync_ipc_manifest_update_mtime(
     vdird_socket: &str,
     path: &str,
     mtime: u64,
-) -> bool {
+) -> Result<(), libc::c_int> {
     let request = vrift_ipc::VeloRequest::ManifestUpdateMtime {
         path: path.to_string(),
         mtime_ns: mtime,
     };
-    matches!(
-        sync_rpc_vdird(vdird_socket, &request),
-        Some(vrift_ipc::VeloResponse::ManifestAck { .. })
-    )
+    manifest_ack(sync_rpc_vdird(vdird_socket, &request, BudgetClass::Mutate)).map(|_| ())
 }
 
-pub(crate) unsafe fn sync_ipc_manifest_mkdir(vdird_socket: &str, path: &str, _mode: u32) -> bool {
-    // Create a directory entry in the manifest
+/// Replace the entry at `path`; answered once the VDir has it, so the next
+/// stat sees the change
+pub(crate) unsafe fn sync_ipc_manifest_upsert(
+    vdird_socket: &str,
+    path: &str,
+    entry: vrift_ipc::VnodeEntry,
+) -> Result<(), libc::c_int> {
+    let request = vrift_ipc::VeloRequest::ManifestUpsert {
+        path: path.to_string(),
+        entry,
+    };
+    manifest_ack(sync_rpc_vdird(vdird_socket, &request, BudgetClass::Mutate)).map(|_| ())
+}
+
+pub(crate) unsafe fn sync_ipc_manifest_mkdir(
+    vdird_socket: &str,
+    path: &str,
+    mode: u32,
+) -> Result<(), libc::c_int> {
+    // Create a directory entry in the manifest; `mode` already has the
+    // umask applied
     let request = vrift_ipc::VeloRequest::ManifestUpsert {
         path: path.to_string(),
         entry: vrift_ipc::VnodeEntry {
@@ -376,22 +767,20 @@ pub(crate) unsafe fn sync_ipc_manifest_mkdir(vdird_socket: &str, path: &str, _mo
                 .duration_since(UNIX_EPOCH)
                 .map(|d| d.as_secs())
                 .unwrap_or(0),
-            mode: 0o755,
+            mode,
             flags: 1, // is_dir flag
             _pad: 0,
         },
     };
-    matches!(
-        sync_rpc_vdird(vdird_socket, &request),
-        Some(vrift_ipc::VeloResponse::ManifestAck { .. })
-    )
+    manifest_ack(sync_rpc_vdird(vdird_socket, &request, BudgetClass::Mutate)).map(|_| ())
 }
 
+#[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS, u32 on Linux
 pub(crate) unsafe fn sync_ipc_manifest_symlink(
     vdird_socket: &str,
     path: &str,
     _target: &str,
-) -> bool {
+) -> Result<(), libc::c_int> {
     // Symlinks stored as special manifest entries
     let request = vrift_ipc::VeloRequest::ManifestUpsert {
         path: path.to_string(),
@@ -402,30 +791,24 @@ pub(crate) unsafe fn sync_ipc_manifest_symlink(
                 .duration_since(UNIX_EPOCH)
                 .map(|d| d.as_secs())
                 .unwrap_or(0),
-            mode: 0o777,
+            mode: libc::S_IFLNK as u32 | 0o777,
             flags: 2, // is_symlink pseudo-flag
             _pad: 0,
         },
     };
-    matches!(
-        sync_rpc_vdird(vdird_socket, &request),
-        Some(vrift_ipc::VeloResponse::ManifestAck { .. })
-    )
+    manifest_ack(sync_rpc_vdird(vdird_socket, &request, BudgetClass::Mutate)).map(|_| ())
 }
 
 pub(crate) unsafe fn sync_ipc_manifest_reingest(
     vdird_socket: &str,
     vpath: &str,
     temp: &str,
-) -> bool {
+) -> Result<(), libc::c_int> {
     let request = vrift_ipc::VeloRequest::ManifestReingest {
         vpath: vpath.to_string(),
         temp_path: temp.to_string(),
     };
-    matches!(
-        sync_rpc_vdird(vdird_socket, &request),
-        Some(vrift_ipc::VeloResponse::ManifestAck { .. })
-    )
+    manifest_ack(sync_rpc_vdird(vdird_socket, &request, BudgetClass::Mutate)).map(|_| ())
 }
 
 /// Phase 3: Fire-and-forget IPC — push a VeloRequest to the ring buffer
@@ -494,6 +877,7 @@ pub(crate) unsafe fn send_fire_and_forget_sync(socket_path: &str, payload: &[u8]
     }
 
     // Send the pre-serialized request
+    crate::summary::add(&crate::summary::IPC_CALLS, 1);
     let seq_id = vrift_ipc::next_seq_id();
     let header = vrift_ipc::IpcHeader::new_request(payload.len() as u32, seq_id);
     let success = raw_write_all(fd, &header.to_bytes()) && raw_write_all(fd, payload);
@@ -559,7 +943,11 @@ fn get_project_root() -> String {
     }
 }
 
-pub(crate) unsafe fn sync_ipc_flock(socket_path: &str, path: &str, op: i32) -> bool {
+pub(crate) unsafe fn sync_ipc_flock(
+    socket_path: &str,
+    path: &str,
+    op: i32,
+) -> Result<(), libc::c_int> {
     let request = if op & libc::LOCK_UN != 0 {
         vrift_ipc::VeloRequest::FlockRelease {
             path: path.to_string(),
@@ -570,10 +958,10 @@ pub(crate) unsafe fn sync_ipc_flock(socket_path: &str, path: &str, op: i32) -> b
             operation: op,
         }
     };
-    matches!(
-        sync_rpc(socket_path, &request),
-        Some(vrift_ipc::VeloResponse::FlockAck)
-    )
+    match sync_rpc(socket_path, &request) {
+        Some(vrift_ipc::VeloResponse::FlockAck) => Ok(()),
+        other => Err(response_errno(other.as_ref())),
+    }
 }
 
 pub(crate) unsafe fn sync_ipc_fcntl_lock(
@@ -591,33 +979,44 @@ pub(crate) unsafe fn sync_ipc_fcntl_lock(
 pub(crate) unsafe fn sync_ipc_manifest_get(
     vdird_socket: &str,
     path: &str,
+    class: BudgetClass,
 ) -> Option<vrift_ipc::VnodeEntry> {
+    sync_ipc_manifest_lookup(vdird_socket, path, class)
+        .ok()
+        .flatten()
+}
+
+/// `sync_ipc_manifest_get` that keeps "absent" (`Ok(None)`) apart from a
+/// failed lookup (`Err(errno)`)
+pub(crate) unsafe fn sync_ipc_manifest_lookup(
+    vdird_socket: &str,
+    path: &str,
+    class: BudgetClass,
+) -> Result<Option<vrift_ipc::VnodeEntry>, libc::c_int> {
+    sync_ipc_manifest_lookup_ino(vdird_socket, path, class).map(|found| found.map(|(e, _)| e))
+}
+
+/// `sync_ipc_manifest_lookup`, with the inode vDird allocated the entry
+/// (0: none)
+pub(crate) unsafe fn sync_ipc_manifest_lookup_ino(
+    vdird_socket: &str,
+    path: &str,
+    class: BudgetClass,
+) -> Result<Option<(vrift_ipc::VnodeEntry, u64)>, libc::c_int> {
     let request = vrift_ipc::VeloRequest::ManifestGet {
         path: path.to_string(),
     };
-    match sync_rpc_vdird(vdird_socket, &request) {
-        Some(vrift_ipc::VeloResponse::ManifestAck { entry }) => entry,
-        _ => None,
+    match sync_rpc_vdird(vdird_socket, &request, class) {
+        Some(vrift_ipc::VeloResponse::ManifestAck { entry, ino }) => Ok(entry.map(|e| (e, ino))),
+        other => Err(response_errno(other.as_ref())),
     }
 }
 
 // Helper: send request on existing FD (v3 frame protocol)
 unsafe fn send_request_on_fd(fd: libc::c_int, request: &vrift_ipc::VeloRequest) -> bool {
-    use vrift_ipc::{next_seq_id, IpcHeader};
-
-    let payload = match rkyv::to_bytes::<rkyv::rancor::Error>(request) {
-        Ok(b) => b,
-        Err(_) => return false,
-    };
-
-    if payload.len() > vrift_ipc::IpcHeader::MAX_LENGTH {
-        return false;
-    }
-
-    let seq_id = next_seq_id();
-    let header = IpcHeader::new_request(payload.len() as u32, seq_id);
-
-    raw_write_all(fd, &header.to_bytes()) && raw_write_all(fd, &payload)
+    crate::summary::add(&crate::summary::IPC_CALLS, 1);
+    // Framed on the stack unless the request is unusually large
+    vrift_ipc::frame_sync::send_request(&mut RawSocket(fd), request).is_ok()
 }
 
 // Helper: receive response on existing FD (v3 frame protocol)
@@ -640,26 +1039,27 @@ unsafe fn recv_response_on_fd(fd: libc::c_int) -> Option<vrift_ipc::VeloResponse
         return None;
     }
 
-    // Read payload
-    let mut payload = vec![0u8; header.length as usize];
-    if !raw_read_exact(fd, &mut payload) {
-        return None;
-    }
-
-    rkyv::from_bytes::<vrift_ipc::VeloResponse, rkyv::rancor::Error>(&payload).ok()
+    // Read payload, on the stack unless the response is unusually large
+    vrift_ipc::frame_sync::read_payload(&mut RawSocket(fd), header.length as usize).ok()
 }
 
-/// Query directory listing from vDird
-/// Phase 1.2: Routes directly to vDird socket
-pub(crate) unsafe fn sync_ipc_manifest_list_dir(
+/// Query one batch of a directory listing from vDird: packed names,
+/// directory flags and whether more follow
+pub(crate) unsafe fn sync_ipc_manifest_list_page(
     vdird_socket: &str,
     path: &str,
-) -> Option<Vec<vrift_ipc::DirEntry>> {
-    let request = vrift_ipc::VeloRequest::ManifestListDir {
+    after: &str,
+    limit: u32,
+) -> Option<(Vec<u8>, Vec<bool>, bool)> {
+    let request = vrift_ipc::VeloRequest::ManifestListDirPage {
         path: path.to_string(),
+        after: after.to_string(),
+        limit,
     };
-    match sync_rpc_vdird(vdird_socket, &request) {
-        Some(vrift_ipc::VeloResponse::ManifestListAck { entries }) => Some(entries),
+    match sync_rpc_vdird(vdird_socket, &request, BudgetClass::Dir) {
+        Some(vrift_ipc::VeloResponse::ManifestListPageAck { names, dirs, more }) => {
+            Some((names, dirs, more))
+        }
         _ => None,
     }
 }
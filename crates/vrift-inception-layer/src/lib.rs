@@ -42,14 +42,26 @@
 #[macro_use]
 pub mod macros;
 
+#[cfg(feature = "alloc-audit")]
+pub mod alloc_audit;
+pub mod budget;
+pub mod chunked;
+pub mod heat;
+pub mod hydrate;
 pub mod interpose;
 pub mod ipc;
+pub mod miss_report;
 pub mod path;
+pub mod profile;
 pub mod raw_context;
 pub mod reals;
 pub mod state;
+pub mod summary;
 pub mod sync;
 pub mod syscalls;
+#[cfg(target_os = "linux")]
+pub mod uring;
+pub mod warnings;
 
 extern "C" {
     fn set_inception_errno(e: libc::c_int);
@@ -165,7 +177,117 @@ pub unsafe extern "C" fn vrift_get_telemetry(
         }
     }
     let _ = writeln!(writer, "    \"total_recorded\": {}", head);
-    let _ = writeln!(writer, "  }}");
+    let _ = writeln!(writer, "  }},");
+
+    // VRIFT_PROFILE: per-op timings (calls/total_ns are scaled estimates in sample mode)
+    let sample = crate::profile::PROFILE_SAMPLE.load(std::sync::atomic::Ordering::Relaxed);
+    let _ = writeln!(writer, "  \"profile\": {{");
+    let _ = write!(writer, "    \"sample_every\": {}", sample);
+    if sample != 0 {
+        for (i, name) in crate::profile::PROFILE_OP_NAMES.iter().enumerate() {
+            let stats = &crate::profile::PROFILE_STATS[i];
+            let calls = stats.calls.load(std::sync::atomic::Ordering::Relaxed);
+            let total_ns = stats.total_ns.load(std::sync::atomic::Ordering::Relaxed);
+            let _ = write!(
+                writer,
+                ",\n    \"{}\": {{ \"calls\": {}, \"sampled\": {}, \"avg_ns\": {}, \"max_ns\": {} }}",
+                name,
+                calls,
+                stats.sampled.load(std::sync::atomic::Ordering::Relaxed),
+                total_ns.checked_div(calls).unwrap_or(0),
+                stats.max_ns.load(std::sync::atomic::Ordering::Relaxed)
+            );
+        }
+    }
+    let _ = writeln!(writer);
+    let _ = writeln!(writer, "  }},");
+
+    // VRIFT_OP_BUDGET: round-trips degraded to passthrough per syscall class
+    let _ = writeln!(writer, "  \"budget\": {{");
+    for (i, name) in crate::budget::BUDGET_CLASS_NAMES.iter().enumerate() {
+        let _ = write!(
+            writer,
+            "    \"{}\": {{ \"budget_us\": {}, \"violations\": {}, \"worst_us\": {} }}",
+            name,
+            crate::budget::BUDGET_US[i].load(std::sync::atomic::Ordering::Relaxed),
+            crate::budget::BUDGET_VIOLATIONS[i].load(std::sync::atomic::Ordering::Relaxed),
+            crate::budget::BUDGET_WORST_US[i].load(std::sync::atomic::Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            writer,
+            "{}",
+            if i + 1 < crate::budget::BUDGET_CLASS_NAMES.len() {
+                ","
+            } else {
+                ""
+            }
+        );
+    }
+    let _ = writeln!(writer, "  }},");
+
+    // VRIFT_HYDRATE: stubs materialized from CAS on first open
+    let _ = writeln!(
+        writer,
+        "  \"hydrate\": {{ \"enabled\": {}, \"files\": {}, \"bytes\": {}, \"failures\": {} }},",
+        crate::hydrate::is_enabled(),
+        crate::hydrate::HYDRATED_FILES.load(std::sync::atomic::Ordering::Relaxed),
+        crate::hydrate::HYDRATED_BYTES.load(std::sync::atomic::Ordering::Relaxed),
+        crate::hydrate::HYDRATE_FAILURES.load(std::sync::atomic::Ordering::Relaxed)
+    );
+
+    // VDir misses fed back to vDird for hot-cache admission
+    let _ = writeln!(
+        writer,
+        "  \"miss_reports\": {{ \"reported\": {}, \"dropped\": {} }},",
+        crate::miss_report::MISSES_REPORTED.load(std::sync::atomic::Ordering::Relaxed),
+        crate::miss_report::MISSES_DROPPED.load(std::sync::atomic::Ordering::Relaxed)
+    );
+
+    // Chunked CAS blobs reassembled on open
+    let _ = writeln!(
+        writer,
+        "  \"chunked\": {{ \"reassembled\": {}, \"bytes\": {} }},",
+        crate::chunked::REASSEMBLED_FILES.load(std::sync::atomic::Ordering::Relaxed),
+        crate::chunked::REASSEMBLED_BYTES.load(std::sync::atomic::Ordering::Relaxed)
+    );
+
+    // Compressed CAS blobs inflated on open
+    let _ = writeln!(
+        writer,
+        "  \"compressed\": {{ \"inflated\": {}, \"bytes\": {} }},",
+        crate::chunked::INFLATED_FILES.load(std::sync::atomic::Ordering::Relaxed),
+        crate::chunked::INFLATED_BYTES.load(std::sync::atomic::Ordering::Relaxed)
+    );
+
+    // Opens served from a copy an earlier reassembly or inflation cached
+    let _ = writeln!(
+        writer,
+        "  \"expanded_cache\": {{ \"hits\": {} }},",
+        crate::chunked::CACHE_HITS.load(std::sync::atomic::Ordering::Relaxed)
+    );
+
+    // alloc-audit builds: allocations made inside intercepted calls
+    #[cfg(feature = "alloc-audit")]
+    let _ = writeln!(
+        writer,
+        "  \"alloc_audit\": {{ \"allocs\": {}, \"hot_allocs\": {}, \"hot_bytes\": {} }},",
+        crate::alloc_audit::ALLOCS.load(std::sync::atomic::Ordering::Relaxed),
+        crate::alloc_audit::HOT_ALLOCS.load(std::sync::atomic::Ordering::Relaxed),
+        crate::alloc_audit::HOT_BYTES.load(std::sync::atomic::Ordering::Relaxed)
+    );
+
+    // User-facing warnings: occurrences per condition (first one was printed)
+    let _ = write!(writer, "  \"warnings\": {{");
+    for (i, name) in crate::warnings::WARNING_NAMES.iter().enumerate() {
+        let _ = write!(
+            writer,
+            "{} \"{}\": {}",
+            if i == 0 { "" } else { "," },
+            name,
+            crate::warnings::WARNING_COUNTS[i].load(std::sync::atomic::Ordering::Relaxed)
+        );
+    }
+    let _ = writeln!(writer, " }}");
     let _ = write!(writer, "}}"); // End JSON
 
     let out_str = writer.as_str();
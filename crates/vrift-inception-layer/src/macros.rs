@@ -110,25 +110,15 @@ macro_rules! inception_log { ($($arg:tt)*) => { $crate::inception_info!($($arg)*
 
 #[macro_export]
 macro_rules! get_real {
-    ($storage:ident, $name:literal, $t:ty) => {{
-        let p = $storage.load(std::sync::atomic::Ordering::Acquire);
-        if !p.is_null() {
-            std::mem::transmute::<*mut libc::c_void, $t>(p)
-        } else {
-            // zero-alloc C string constant
-            let f = libc::dlsym(
-                libc::RTLD_NEXT,
-                concat!($name, "\0").as_ptr() as *const libc::c_char,
-            );
-            $storage.store(f, std::sync::atomic::Ordering::Release);
-            std::mem::transmute::<*mut libc::c_void, $t>(f)
-        }
+    ($sym:expr, $t:ty) => {{
+        // Preresolved at constructor time; get() only dlsyms if that failed
+        std::mem::transmute::<*mut libc::c_void, $t>($sym.get())
     }};
 }
 
 #[macro_export]
 macro_rules! get_real_inception {
-    ($storage:ident, $name:literal, $it:ident, $t:ty) => {{
+    ($sym:expr, $it:ident, $t:ty) => {{
         #[cfg(target_os = "macos")]
         {
             // RFC-0051: Bypass dlsym(RTLD_NEXT) by using the old_func pointer
@@ -139,33 +129,52 @@ macro_rules! get_real_inception {
         }
         #[cfg(not(target_os = "macos"))]
         {
-            $crate::get_real!($storage, $name, $t)
+            $crate::get_real!($sym, $t)
         }
     }};
 }
 
+/// Formats into a caller-provided stack buffer. Output that doesn't fit is
+/// cut off and `overflowed()` turns true, so callers handling paths can refuse
+/// a truncated result instead of acting on it.
 pub struct StackWriter<'a> {
     buf: &'a mut [u8],
     pos: usize,
+    overflowed: bool,
 }
 
 impl<'a> StackWriter<'a> {
     pub fn new(buf: &'a mut [u8]) -> Self {
-        Self { buf, pos: 0 }
+        Self {
+            buf,
+            pos: 0,
+            overflowed: false,
+        }
     }
 
     pub fn as_str(&self) -> &str {
         std::str::from_utf8(&self.buf[..self.pos]).unwrap_or("")
     }
+
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
 }
 
 impl<'a> std::fmt::Write for StackWriter<'a> {
     fn write_str(&mut self, s: &str) -> std::fmt::Result {
         let bytes = s.as_bytes();
         let remaining = self.buf.len() - self.pos;
-        let to_copy = std::cmp::min(bytes.len(), remaining);
+        let mut to_copy = std::cmp::min(bytes.len(), remaining);
+        while !s.is_char_boundary(to_copy) {
+            to_copy -= 1;
+        }
         self.buf[self.pos..self.pos + to_copy].copy_from_slice(&bytes[..to_copy]);
         self.pos += to_copy;
+        if to_copy < bytes.len() {
+            self.overflowed = true;
+            return Err(std::fmt::Error);
+        }
         Ok(())
     }
 }
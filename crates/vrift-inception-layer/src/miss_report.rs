@@ -0,0 +1,109 @@
+// =============================================================================
+// miss_report.rs — VDir miss feedback for vDird's hot-cache admission
+// =============================================================================
+//
+// With a capped VDir (VRIFT_VDIR_MAX_ENTRIES) vDird only publishes the
+// manifest entries it sees being missed, and only the shim knows a lookup
+// missed the mmap. Lookups that miss it and are then answered over IPC
+// record their path hash here. Each full batch goes to vDird as one
+// fire-and-forget MissReport through the worker's ring buffer; whatever is
+// left at exit is sent synchronously from an atexit hook, since most build
+// processes are short-lived.
+//
+// Recording is a fetch_add and a store. Reports are best effort: a batch
+// that finds the ring buffer full is dropped, and so are misses recorded
+// while a full batch is being drained.
+// =============================================================================
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+/// Path hashes per MissReport
+const BATCH: usize = 256;
+
+static SLOTS: [AtomicU64; BATCH] = [const { AtomicU64::new(0) }; BATCH];
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+static EXIT_FLUSH_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// Path hashes handed to vDird
+pub static MISSES_REPORTED: AtomicU64 = AtomicU64::new(0);
+/// Path hashes lost to a full batch or ring buffer
+pub static MISSES_DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Record a VDir miss for `path_hash` (the VDir key of the manifest path)
+pub(crate) fn record(vdird_socket: &str, path_hash: u64) {
+    if path_hash == 0 {
+        return; // 0 marks an empty slot
+    }
+    let i = NEXT.fetch_add(1, Ordering::AcqRel);
+    if i >= BATCH {
+        MISSES_DROPPED.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    SLOTS[i].store(path_hash, Ordering::Release);
+    if i == BATCH - 1 {
+        send_queued(vdird_socket, drain(BATCH));
+    }
+    crate::state::register_atexit_once(&EXIT_FLUSH_REGISTERED, flush_at_exit);
+}
+
+/// Take the first `n` slots and reopen the batch
+fn drain(n: usize) -> Vec<u64> {
+    let hashes: Vec<u64> = SLOTS[..n]
+        .iter()
+        .map(|slot| slot.swap(0, Ordering::AcqRel))
+        .filter(|&hash| hash != 0)
+        .collect();
+    NEXT.store(0, Ordering::Release);
+    hashes
+}
+
+fn serialize(path_hashes: Vec<u64>) -> Option<Vec<u8>> {
+    let request = vrift_ipc::VeloRequest::MissReport { path_hashes };
+    rkyv::to_bytes::<rkyv::rancor::Error>(&request)
+        .ok()
+        .map(|bytes| bytes.to_vec())
+}
+
+/// Hand a batch to the worker; never blocks the caller
+fn send_queued(vdird_socket: &str, hashes: Vec<u64>) {
+    let count = hashes.len() as u64;
+    let queued = crate::sync::get_reactor().is_some_and(|reactor| {
+        serialize(hashes).is_some_and(|payload| {
+            reactor
+                .ring_buffer
+                .push(crate::sync::Task::IpcFireAndForget {
+                    socket_path: vdird_socket.to_string(),
+                    payload,
+                })
+                .is_ok()
+        })
+    });
+    let counter = if queued {
+        &MISSES_REPORTED
+    } else {
+        &MISSES_DROPPED
+    };
+    counter.fetch_add(count, Ordering::Relaxed);
+}
+
+/// Send the partial batch directly: the worker may not run again
+extern "C" fn flush_at_exit() {
+    let pending = NEXT.load(Ordering::Acquire).min(BATCH);
+    if pending == 0 {
+        return;
+    }
+    let hashes = drain(pending);
+    let count = hashes.len() as u64;
+    let Some(state) = crate::state::InceptionLayerState::get_no_spawn() else {
+        return;
+    };
+    let sent = serialize(hashes).is_some_and(|payload| unsafe {
+        crate::ipc::send_fire_and_forget_sync(&state.vdird_socket_path, &payload)
+    });
+    let counter = if sent {
+        &MISSES_REPORTED
+    } else {
+        &MISSES_DROPPED
+    };
+    counter.fetch_add(count, Ordering::Relaxed);
+}
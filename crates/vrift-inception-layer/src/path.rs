@@ -4,61 +4,128 @@ use std::ptr;
 
 use crate::state::FixedString;
 
+/// Capacity of the stack buffers behind a VfsPath (absolute path and key).
+/// Longer VFS paths take the heap fallback in `normalize_long`.
+pub(crate) const VFS_PATH_CAP: usize = 1024;
+
 /// RFC-0049: Unified path resolution for VFS domain.
 /// Encapsulates absolute path and the corresponding manifest key.
 #[derive(Debug, Clone)]
 pub(crate) struct VfsPath {
-    pub absolute: FixedString<1024>,
-    pub manifest_key: FixedString<1024>,
+    pub absolute: FixedString<VFS_PATH_CAP>,
+    pub manifest_key: FixedString<VFS_PATH_CAP>,
     pub manifest_key_hash: u64,
 }
 
 pub(crate) struct PathResolver {
     pub vfs_prefix: FixedString<256>,
     pub project_root: FixedString<1024>,
+    /// VRIFT_PASSTHROUGH: comma-separated key prefixes left to the real
+    /// filesystem (`target,.git,!target/doc`). A list too long to store is
+    /// dropped rather than truncated into a different prefix.
+    pub passthrough: FixedString<1024>,
 }
 
 impl PathResolver {
-    pub fn new(vfs_prefix: &str, project_root: &str) -> Self {
+    pub fn new(vfs_prefix: &str, project_root: &str, passthrough: &str) -> Self {
         let mut prefix = FixedString::new();
         prefix.set(vfs_prefix);
         let mut root = FixedString::new();
         root.set(project_root);
+        let mut pass = FixedString::new();
+        pass.try_set(passthrough);
         Self {
             vfs_prefix: prefix,
             project_root: root,
+            passthrough: pass,
         }
     }
 
+    /// Is a manifest key excluded from the VFS by VRIFT_PASSTHROUGH?
+    #[inline]
+    pub fn is_passthrough(&self, manifest_key: &str) -> bool {
+        !self.passthrough.is_empty()
+            && vrift_config::passthrough_matches(self.passthrough.as_str().split(','), manifest_key)
+    }
+
     /// Resolve an incoming path (absolute or relative) into a VfsPath.
     /// Returns None if the path is not within the VFS domain.
     pub fn resolve(&self, path: &str) -> Option<VfsPath> {
+        self.resolve_checked(path).ok().flatten()
+    }
+
+    /// Like `resolve`, but a VFS path too long for the fixed VfsPath buffers
+    /// is `Err(ENAMETOOLONG)` rather than `None`: passing it through would hit
+    /// the real filesystem instead of the manifest, and truncating it would
+    /// name a different file.
+    pub fn resolve_checked(&self, path: &str) -> Result<Option<VfsPath>, c_int> {
         // RFC-0050: Early exit if VFS is not configured
         if self.vfs_prefix.is_empty() {
-            return None;
+            return Ok(None);
         }
 
-        let mut abs_buf = [0u8; 1024];
+        let mut abs_buf = [0u8; VFS_PATH_CAP];
         let mut abs_writer = crate::macros::StackWriter::new(&mut abs_buf);
         use std::fmt::Write;
 
         // 1. Resolve relative paths using project_root
         if !path.starts_with('/') {
             if self.project_root.is_empty() {
-                return None;
+                return Ok(None);
             }
             let _ = write!(abs_writer, "{}/{}", self.project_root.as_str(), path);
         } else {
             let _ = write!(abs_writer, "{}", path);
         };
-        let abs_path = abs_writer.as_str();
 
         // 2. Normalize (handle .., ., //)
-        let mut norm_buf = [0u8; 1024];
-        let len = unsafe { raw_path_normalize(abs_path, &mut norm_buf)? };
-        let normalized = std::str::from_utf8(&norm_buf[..len]).ok()?;
+        let mut norm_buf = [0u8; VFS_PATH_CAP];
+        let normalized_len = if abs_writer.overflowed() {
+            None
+        } else {
+            unsafe { raw_path_normalize(abs_writer.as_str(), &mut norm_buf) }
+        };
+        let len = match normalized_len {
+            Some(len) => len,
+            None => match self.normalize_long(path, &mut norm_buf)? {
+                Some(len) => len,
+                None => return Ok(None),
+            },
+        };
+        let Ok(normalized) = std::str::from_utf8(&norm_buf[..len]) else {
+            return Ok(None);
+        };
 
-        // 3. Check VFS applicability
+        Ok(self.resolve_normalized(normalized))
+    }
+
+    /// Heap fallback for inputs that overflow the stack buffers. A long path
+    /// can still normalize to a short one (`..` chains, deep relative paths);
+    /// only a result that really is too long is refused. This allocation is
+    /// confined to the rare long-path branch.
+    fn normalize_long(&self, path: &str, out: &mut [u8]) -> Result<Option<usize>, c_int> {
+        let joined = if path.starts_with('/') {
+            std::borrow::Cow::Borrowed(path)
+        } else {
+            std::borrow::Cow::Owned(format!("{}/{}", self.project_root.as_str(), path))
+        };
+        let mut long_buf = vec![0u8; libc::PATH_MAX as usize];
+        let Some(len) = (unsafe { raw_path_normalize(&joined, &mut long_buf) }) else {
+            // Beyond PATH_MAX: the kernel rejects it on passthrough anyway
+            return Ok(None);
+        };
+        if len <= out.len() {
+            out[..len].copy_from_slice(&long_buf[..len]);
+            return Ok(Some(len));
+        }
+        match std::str::from_utf8(&long_buf[..len]) {
+            Ok(normalized) if self.in_domain(normalized) => Err(libc::ENAMETOOLONG),
+            _ => Ok(None),
+        }
+    }
+
+    /// Step 3: is a normalized absolute path inside the VFS prefix?
+    fn in_domain(&self, normalized: &str) -> bool {
         let prefix = self.vfs_prefix.as_str();
         #[allow(unused_mut)]
         let mut applicable = normalized.starts_with(prefix);
@@ -66,22 +133,30 @@ impl PathResolver {
         // RFC-0050: Handle macOS /tmp symlink invisibility
         #[cfg(target_os = "macos")]
         if !applicable && normalized.starts_with("/tmp/") {
-            let mut alt_buf = [0u8; 1024];
+            use std::fmt::Write;
+            let mut alt_buf = [0u8; VFS_PATH_CAP];
             let mut aw = crate::macros::StackWriter::new(&mut alt_buf);
             let _ = write!(aw, "/private{}", normalized);
-            applicable = aw.as_str().starts_with(prefix);
+            applicable = !aw.overflowed() && aw.as_str().starts_with(prefix);
         }
 
         if !applicable {
-            return None;
+            return false;
         }
 
         // Ensure we match on component boundaries
         let prefix_len = self.vfs_prefix.len;
-        if normalized.len() > prefix_len
+        !(normalized.len() > prefix_len
             && !self.vfs_prefix.as_str().ends_with('/')
-            && normalized.as_bytes()[prefix_len] != b'/'
-        {
+            && normalized.as_bytes()[prefix_len] != b'/')
+    }
+
+    /// Steps 3-4 for a normalized absolute path that fits a VfsPath
+    fn resolve_normalized(&self, normalized: &str) -> Option<VfsPath> {
+        use std::fmt::Write;
+
+        // 3. Check VFS applicability
+        if !self.in_domain(normalized) {
             return None;
         }
 
@@ -161,8 +236,14 @@ impl PathResolver {
             }
         };
 
-        let mut norm_fs = FixedString::<1024>::new();
-        norm_fs.set(normalized);
+        if self.is_passthrough(key_fs.as_str()) {
+            return None;
+        }
+
+        let mut norm_fs = FixedString::<VFS_PATH_CAP>::new();
+        if !norm_fs.try_set(normalized) {
+            return None;
+        }
 
         let manifest_key_hash = vrift_ipc::fnv1a_hash(key_fs.as_str());
         Some(VfsPath {
@@ -256,33 +337,86 @@ pub(crate) unsafe fn raw_path_normalize(path: &str, out: &mut [u8]) -> Option<us
     Some(out_idx)
 }
 
-/// RFC-0049: Generate virtual inode from path
-/// Prevents st_ino collision when CAS dedup causes multiple logical files to share same blob
-/// Uses a simple hash to generate unique inode per logical path
-#[inline]
-pub(crate) fn path_to_virtual_ino(path: &str) -> libc::ino_t {
-    // Simple FNV-1a hash for O(1) inode generation
-    let mut hash: u64 = 0xcbf29ce484222325;
-    for byte in path.bytes() {
-        hash ^= byte as u64;
-        hash = hash.wrapping_mul(0x100000001b3);
-    }
-    hash as libc::ino_t
+/// Unit-tested in vrift-config, since this crate's own tests can't load
+pub(crate) use vrift_config::path::host_to_local;
+
+/// View a C path argument as a string for resolution. Valid UTF-8 is
+/// borrowed; other names are escaped the way ingest builds manifest keys
+/// (`vrift_ipc::encode_path_key`), which allocates only in that rare case.
+/// The result is for lookups: hand the original pointer to real syscalls.
+pub(crate) unsafe fn path_arg<'a>(path: *const c_char) -> std::borrow::Cow<'a, str> {
+    vrift_ipc::encode_path_key(CStr::from_ptr(path).to_bytes())
 }
 
+/// Resolve `path` the way an `*at` call on `dirfd` would, writing the
+/// normalized result to `out`. A directory opened through the layer has its
+/// path in the FD table; other fds ask the OS (`F_GETPATH`, `/proc/self/fd`).
 pub(crate) unsafe fn resolve_path_at(
     dirfd: c_int,
     path: *const c_char,
     out: &mut [u8],
 ) -> Option<usize> {
     let path_str = CStr::from_ptr(path).to_str().ok()?;
-    if path_str.starts_with('/') {
+    if path_str.starts_with('/') || dirfd == AT_FDCWD {
         return raw_path_normalize(path_str, out);
     }
-    if dirfd == AT_FDCWD {
-        // Fallback to basic normalization if no complex resolver is available
-        return raw_path_normalize(path_str, out);
+    if path_str.is_empty() {
+        // AT_EMPTY_PATH names dirfd itself; callers leave that to the kernel
+        return None;
+    }
+
+    use std::fmt::Write;
+    let mut joined = [0u8; VFS_PATH_CAP];
+    let mut writer = crate::macros::StackWriter::new(&mut joined);
+    match crate::syscalls::io::get_fd_entry(dirfd) {
+        Some(entry) if entry.is_dir => {
+            let _ = write!(writer, "{}/{}", entry.vpath, path_str);
+        }
+        _ => {
+            let mut dir_buf = [0u8; VFS_PATH_CAP];
+            let len = os_dir_path(dirfd, &mut dir_buf)?;
+            let dir = std::str::from_utf8(&dir_buf[..len]).ok()?;
+            let _ = write!(writer, "{}/{}", dir, path_str);
+        }
+    }
+    if writer.overflowed() {
+        return None;
+    }
+    raw_path_normalize(writer.as_str(), out)
+}
+
+/// Ask the OS which directory an untracked fd refers to
+unsafe fn os_dir_path(dirfd: c_int, out: &mut [u8]) -> Option<usize> {
+    #[cfg(target_os = "macos")]
+    {
+        // F_GETPATH writes up to MAXPATHLEN (1024) bytes
+        if out.len() < 1024
+            || crate::syscalls::macos_raw::raw_fcntl(
+                dirfd,
+                libc::F_GETPATH,
+                out.as_mut_ptr() as i64,
+            ) != 0
+        {
+            return None;
+        }
+        out.iter().position(|b| *b == 0)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        use std::fmt::Write;
+        let mut link = [0u8; 32];
+        let mut writer = crate::macros::StackWriter::new(&mut link);
+        let _ = write!(writer, "/proc/self/fd/{}\0", dirfd);
+        let n = crate::syscalls::linux_raw::raw_readlink(
+            link.as_ptr() as *const c_char,
+            out.as_mut_ptr() as *mut c_char,
+            out.len(),
+        );
+        // A full buffer may hold a truncated path; anything not starting
+        // with '/' is a pseudo-file such as "anon_inode:[eventfd]"
+        if n <= 0 || n as usize >= out.len() || out[0] != b'/' {
+            return None;
+        }
+        Some(n as usize)
     }
-    // Cannot resolve relative path to arbitrary dirfd easily without OS help.
-    None
 }
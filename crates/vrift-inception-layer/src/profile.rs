@@ -0,0 +1,162 @@
+// =============================================================================
+// profile.rs — Per-operation syscall profiling (VRIFT_PROFILE)
+// =============================================================================
+//
+// Modes (read once from the environment in init_logger()):
+//   unset / "0"   — disabled. begin() is a single relaxed load + branch.
+//   "1" / "full"  — every operation is timed.
+//   "sample:N"    — only 1/N operations are timed; counters of sampled ops
+//                   are scaled by N so totals stay comparable to full mode.
+//
+// Zero-allocation and lock-free: safe on every interposed syscall.
+// =============================================================================
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileOp {
+    Stat = 0,
+    Lstat = 1,
+    Fstat = 2,
+    Open = 3,
+    Openat = 4,
+    Close = 5,
+    Readlink = 6,
+}
+
+pub const PROFILE_OP_COUNT: usize = 7;
+
+pub static PROFILE_OP_NAMES: [&str; PROFILE_OP_COUNT] = [
+    "stat", "lstat", "fstat", "open", "openat", "close", "readlink",
+];
+
+/// Sampling interval: 0 = disabled, 1 = time every call, N = time 1/N calls
+pub static PROFILE_SAMPLE: AtomicU32 = AtomicU32::new(0);
+
+/// Global tick used to pick which calls are sampled (only touched when N > 1)
+static SAMPLE_TICK: AtomicU64 = AtomicU64::new(0);
+
+pub struct OpStats {
+    /// Estimated call count (sampled calls × N)
+    pub calls: AtomicU64,
+    /// Calls actually timed
+    pub sampled: AtomicU64,
+    /// Estimated total time in ns (sampled time × N)
+    pub total_ns: AtomicU64,
+    /// Slowest single sampled call in ns (not scaled)
+    pub max_ns: AtomicU64,
+}
+
+impl OpStats {
+    const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            sampled: AtomicU64::new(0),
+            total_ns: AtomicU64::new(0),
+            max_ns: AtomicU64::new(0),
+        }
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const OP_STATS_INIT: OpStats = OpStats::new();
+pub static PROFILE_STATS: [OpStats; PROFILE_OP_COUNT] = [OP_STATS_INIT; PROFILE_OP_COUNT];
+
+/// Parse a VRIFT_PROFILE value into a sampling interval (0 = disabled)
+pub fn parse_profile_mode(value: &[u8]) -> u32 {
+    if value == b"1" || value.eq_ignore_ascii_case(b"full") {
+        return 1;
+    }
+    if value.len() > 7 && value[..7].eq_ignore_ascii_case(b"sample:") {
+        return std::str::from_utf8(&value[7..])
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(0);
+    }
+    0
+}
+
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    PROFILE_SAMPLE.load(Ordering::Relaxed) != 0
+}
+
+/// RAII timer for one operation. Inactive spans cost nothing on drop.
+pub struct ProfileSpan {
+    op: ProfileOp,
+    scale: u32,
+    start_ns: u64,
+}
+
+/// Start timing `op` if profiling is enabled and this call is sampled
+#[inline(always)]
+pub fn begin(op: ProfileOp) -> ProfileSpan {
+    let n = PROFILE_SAMPLE.load(Ordering::Relaxed);
+    if n == 0 {
+        return ProfileSpan {
+            op,
+            scale: 0,
+            start_ns: 0,
+        };
+    }
+    begin_slow(op, n)
+}
+
+#[inline(never)]
+fn begin_slow(op: ProfileOp, n: u32) -> ProfileSpan {
+    if n > 1
+        && !SAMPLE_TICK
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(n as u64)
+    {
+        return ProfileSpan {
+            op,
+            scale: 0,
+            start_ns: 0,
+        };
+    }
+    ProfileSpan {
+        op,
+        scale: n,
+        start_ns: monotonic_ns(),
+    }
+}
+
+impl Drop for ProfileSpan {
+    #[inline(always)]
+    fn drop(&mut self) {
+        if self.scale != 0 {
+            record(
+                self.op,
+                self.scale,
+                monotonic_ns().saturating_sub(self.start_ns),
+            );
+        }
+    }
+}
+
+#[inline(never)]
+fn record(op: ProfileOp, scale: u32, elapsed_ns: u64) {
+    let stats = &PROFILE_STATS[op as usize];
+    let scale = scale as u64;
+    stats.calls.fetch_add(scale, Ordering::Relaxed);
+    stats.sampled.fetch_add(1, Ordering::Relaxed);
+    stats
+        .total_ns
+        .fetch_add(elapsed_ns.saturating_mul(scale), Ordering::Relaxed);
+    stats.max_ns.fetch_max(elapsed_ns, Ordering::Relaxed);
+}
+
+#[inline(always)]
+pub(crate) fn monotonic_ns() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // vDSO on Linux, commpage on macOS: no kernel transition, no interception
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    (ts.tv_sec as u64)
+        .wrapping_mul(1_000_000_000)
+        .wrapping_add(ts.tv_nsec as u64)
+}
@@ -79,8 +79,7 @@ impl RawContext {
         }
         #[cfg(target_os = "linux")]
         {
-            // Linux fcntl is not interposed, but use raw for consistency
-            libc::fcntl(fd, cmd, arg as i32)
+            crate::syscalls::linux_raw::raw_fcntl(fd, cmd, arg)
         }
     }
 
@@ -7,7 +7,7 @@
 //!           to completely bypass libc and avoid any recursion.
 
 use libc::{c_char, c_void};
-use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
 
 /// Storage for real libc functions to avoid recursion deadlocks
 pub struct RealSymbol {
@@ -24,76 +24,148 @@ impl RealSymbol {
     }
 
     /// Get the real function pointer.
-    /// On macOS: Uses dlsym(RTLD_NEXT)
-    /// On Linux: This is deprecated - use linux_raw module directly
-    #[cfg(target_os = "macos")]
+    ///
+    /// Normally filled by `preresolve_all()` at constructor time, so this is a
+    /// single atomic load. dlsym(RTLD_NEXT) is only reached when preresolution
+    /// did not produce a pointer for this symbol.
+    #[inline(always)]
     pub unsafe fn get(&self) -> *mut c_void {
         let p = self.ptr.load(Ordering::Acquire);
         if !p.is_null() {
             return p;
         }
+        self.resolve_slow()
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn resolve_slow(&self) -> *mut c_void {
         let f = libc::dlsym(libc::RTLD_NEXT, self.name.as_ptr() as *const c_char);
         self.ptr.store(f, Ordering::Release);
         f
     }
 
-    /// On Linux, we still support dlsym for compatibility,
-    /// but prefer using linux_raw module for recursion-safe syscalls.
-    #[cfg(target_os = "linux")]
-    pub unsafe fn get(&self) -> *mut c_void {
-        let p = self.ptr.load(Ordering::Acquire);
-        if !p.is_null() {
-            return p;
+    /// Seed the pointer without dlsym (macOS: interpose old_func)
+    #[inline]
+    fn seed(&self, f: *mut c_void) {
+        if !f.is_null() {
+            self.ptr.store(f, Ordering::Release);
         }
-        let f = libc::dlsym(libc::RTLD_NEXT, self.name.as_ptr() as *const c_char);
-        self.ptr.store(f, Ordering::Release);
-        f
     }
+
+    pub fn is_resolved(&self) -> bool {
+        !self.ptr.load(Ordering::Acquire).is_null()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name.trim_end_matches('\0')
+    }
+}
+
+/// Declares every REAL_* symbol together with its interpose entry, so the
+/// preresolution table can never drift from the statics.
+macro_rules! real_symbols {
+    ($($real:ident => $name:literal, $it:ident;)*) => {
+        $(pub static $real: RealSymbol = RealSymbol::new(concat!($name, "\0"));)*
+
+        /// Every REAL_* symbol, in declaration order
+        pub static ALL_REALS: &[&RealSymbol] = &[$(&$real),*];
+
+        #[cfg(target_os = "macos")]
+        unsafe fn seed_from_interpose() {
+            $($real.seed(crate::interpose::$it.old_func as *mut c_void);)*
+        }
+    };
 }
 
 // Global list of real symbols used by inception layers (primarily macOS)
-pub static REAL_OPEN: RealSymbol = RealSymbol::new("open\0");
-pub static REAL_OPENAT: RealSymbol = RealSymbol::new("openat\0");
-pub static REAL_CLOSE: RealSymbol = RealSymbol::new("close\0");
-pub static REAL_WRITE: RealSymbol = RealSymbol::new("write\0");
-pub static REAL_READ: RealSymbol = RealSymbol::new("read\0");
-pub static REAL_STAT: RealSymbol = RealSymbol::new("stat\0");
-pub static REAL_LSTAT: RealSymbol = RealSymbol::new("lstat\0");
-pub static REAL_FSTAT: RealSymbol = RealSymbol::new("fstat\0");
-pub static REAL_FSTATAT: RealSymbol = RealSymbol::new("fstatat\0");
-pub static REAL_ACCESS: RealSymbol = RealSymbol::new("access\0");
-pub static REAL_READLINK: RealSymbol = RealSymbol::new("readlink\0");
-pub static REAL_REALPATH: RealSymbol = RealSymbol::new("realpath\0");
-pub static REAL_DUP: RealSymbol = RealSymbol::new("dup\0");
-pub static REAL_DUP2: RealSymbol = RealSymbol::new("dup2\0");
-pub static REAL_FCHDIR: RealSymbol = RealSymbol::new("fchdir\0");
-pub static REAL_LSEEK: RealSymbol = RealSymbol::new("lseek\0");
-pub static REAL_FTRUNCATE: RealSymbol = RealSymbol::new("ftruncate\0");
-pub static REAL_UNLINK: RealSymbol = RealSymbol::new("unlink\0");
-pub static REAL_RMDIR: RealSymbol = RealSymbol::new("rmdir\0");
-pub static REAL_RENAME: RealSymbol = RealSymbol::new("rename\0");
-pub static REAL_MKDIR: RealSymbol = RealSymbol::new("mkdir\0");
-pub static REAL_CHMOD: RealSymbol = RealSymbol::new("chmod\0");
-pub static REAL_TRUNCATE: RealSymbol = RealSymbol::new("truncate\0");
-pub static REAL_MMAP: RealSymbol = RealSymbol::new("mmap\0");
-pub static REAL_MUNMAP: RealSymbol = RealSymbol::new("munmap\0");
-pub static REAL_RENAMEAT: RealSymbol = RealSymbol::new("renameat\0");
-pub static REAL_FCHMODAT: RealSymbol = RealSymbol::new("fchmodat\0");
-pub static REAL_CHFLAGS: RealSymbol = RealSymbol::new("chflags\0");
-pub static REAL_LINKAT: RealSymbol = RealSymbol::new("linkat\0");
-pub static REAL_SETXATTR: RealSymbol = RealSymbol::new("setxattr\0");
-pub static REAL_REMOVEXATTR: RealSymbol = RealSymbol::new("removexattr\0");
-pub static REAL_UTIMES: RealSymbol = RealSymbol::new("utimes\0");
-pub static REAL_UTIMENSAT: RealSymbol = RealSymbol::new("utimensat\0");
-pub static REAL_FUTIMENS: RealSymbol = RealSymbol::new("futimens\0");
-pub static REAL_OPENDIR: RealSymbol = RealSymbol::new("opendir\0");
-pub static REAL_READDIR: RealSymbol = RealSymbol::new("readdir\0");
-pub static REAL_CLOSEDIR: RealSymbol = RealSymbol::new("closedir\0");
-pub static REAL_GETCWD: RealSymbol = RealSymbol::new("getcwd\0");
-pub static REAL_CHDIR: RealSymbol = RealSymbol::new("chdir\0");
-pub static REAL_LINK: RealSymbol = RealSymbol::new("link\0");
-pub static REAL_UNLINKAT: RealSymbol = RealSymbol::new("unlinkat\0");
-pub static REAL_MKDIRAT: RealSymbol = RealSymbol::new("mkdirat\0");
-pub static REAL_SYMLINKAT: RealSymbol = RealSymbol::new("symlinkat\0");
-pub static REAL_FCHMOD: RealSymbol = RealSymbol::new("fchmod\0");
-pub static REAL_SETRLIMIT: RealSymbol = RealSymbol::new("setrlimit\0");
+real_symbols! {
+    REAL_OPEN => "open", IT_OPEN;
+    REAL_OPENAT => "openat", IT_OPENAT;
+    REAL_CLOSE => "close", IT_CLOSE;
+    REAL_WRITE => "write", IT_WRITE;
+    REAL_READ => "read", IT_READ;
+    REAL_STAT => "stat", IT_STAT;
+    REAL_LSTAT => "lstat", IT_LSTAT;
+    REAL_FSTAT => "fstat", IT_FSTAT;
+    REAL_FSTATAT => "fstatat", IT_FSTATAT;
+    REAL_ACCESS => "access", IT_ACCESS;
+    REAL_READLINK => "readlink", IT_READLINK;
+    REAL_REALPATH => "realpath", IT_REALPATH;
+    REAL_DUP => "dup", IT_DUP;
+    REAL_DUP2 => "dup2", IT_DUP2;
+    REAL_FCHDIR => "fchdir", IT_FCHDIR;
+    REAL_LSEEK => "lseek", IT_LSEEK;
+    REAL_FTRUNCATE => "ftruncate", IT_FTRUNCATE;
+    REAL_UNLINK => "unlink", IT_UNLINK;
+    REAL_RMDIR => "rmdir", IT_RMDIR;
+    REAL_RENAME => "rename", IT_RENAME;
+    REAL_MKDIR => "mkdir", IT_MKDIR;
+    REAL_CHMOD => "chmod", IT_CHMOD;
+    REAL_TRUNCATE => "truncate", IT_TRUNCATE;
+    REAL_MMAP => "mmap", IT_MMAP;
+    REAL_MUNMAP => "munmap", IT_MUNMAP;
+    REAL_RENAMEAT => "renameat", IT_RENAMEAT;
+    REAL_FCHMODAT => "fchmodat", IT_FCHMODAT;
+    REAL_CHFLAGS => "chflags", IT_CHFLAGS;
+    REAL_LINKAT => "linkat", IT_LINKAT;
+    REAL_SETXATTR => "setxattr", IT_SETXATTR;
+    REAL_REMOVEXATTR => "removexattr", IT_REMOVEXATTR;
+    REAL_GETXATTR => "getxattr", IT_GETXATTR;
+    REAL_LISTXATTR => "listxattr", IT_LISTXATTR;
+    REAL_UTIMES => "utimes", IT_UTIMES;
+    REAL_UTIMENSAT => "utimensat", IT_UTIMENSAT;
+    REAL_FUTIMENS => "futimens", IT_FUTIMENS;
+    REAL_OPENDIR => "opendir", IT_OPENDIR;
+    REAL_READDIR => "readdir", IT_READDIR;
+    REAL_CLOSEDIR => "closedir", IT_CLOSEDIR;
+    REAL_GETCWD => "getcwd", IT_GETCWD;
+    REAL_CHDIR => "chdir", IT_CHDIR;
+    REAL_LINK => "link", IT_LINK;
+    REAL_UNLINKAT => "unlinkat", IT_UNLINKAT;
+    REAL_MKDIRAT => "mkdirat", IT_MKDIRAT;
+    REAL_SYMLINKAT => "symlinkat", IT_SYMLINKAT;
+    REAL_FCHMOD => "fchmod", IT_FCHMOD;
+    REAL_SETRLIMIT => "setrlimit", IT_SETRLIMIT;
+    REAL_EXECVE => "execve", IT_EXECVE;
+    REAL_POSIX_SPAWN => "posix_spawn", IT_POSIX_SPAWN;
+    REAL_POSIX_SPAWNP => "posix_spawnp", IT_POSIX_SPAWNP;
+}
+
+/// Set once the constructor-time pass has run
+static PRERESOLVED: AtomicBool = AtomicBool::new(false);
+
+/// Resolve every REAL_* pointer in one pass, before any interposed syscall
+/// can run Rust code. Returns how many symbols are still unresolved.
+///
+/// macOS: copies the old_func pointers dyld already bound in the interpose
+/// table — no dlsym while dyld holds its lock (BUG-007).
+/// Linux: a single dlsym(RTLD_NEXT) sweep; ld.so is fully relocated by the
+/// time constructors run.
+///
+/// # Safety
+/// Must only be called from the library constructor (or tests).
+pub unsafe fn preresolve_all() -> usize {
+    #[cfg(target_os = "macos")]
+    seed_from_interpose();
+
+    #[cfg(target_os = "linux")]
+    for sym in ALL_REALS {
+        if !sym.is_resolved() {
+            sym.resolve_slow();
+        }
+    }
+
+    PRERESOLVED.store(true, Ordering::Release);
+    ALL_REALS.iter().filter(|s| !s.is_resolved()).count()
+}
+
+pub fn is_preresolved() -> bool {
+    PRERESOLVED.load(Ordering::Acquire)
+}
+
+/// Called from `inception_init_constructor` (priority 101) in variadic_inception.c
+#[no_mangle]
+pub unsafe extern "C" fn vrift_preresolve_reals() {
+    preresolve_all();
+}
@@ -0,0 +1,98 @@
+// =============================================================================
+// state/fork.rs — fork() safety
+// =============================================================================
+//
+// A forked child is a copy of one thread of the parent. Without these handlers
+// it would inherit:
+//   - the state mutexes, possibly held by a thread that doesn't exist in it
+//   - WORKER_STARTED = true with no Worker, and the parent's queued tasks
+//   - the parent's CoW sessions, which it would reingest again on close/exit
+//   - the persistent vDird connection and sockets of other threads' requests
+//
+// prepare/parent/child are registered once with pthread_atfork. Everything
+// else (the Worker, daemon connections) comes back lazily on first use.
+//
+// A child that shares our memory (vfork, clone(CLONE_VM)) runs no handlers
+// and must not touch any of it: it would allocate from, and write to, the
+// parent's heap and state. OWNER_PID tells such a child apart.
+// =============================================================================
+
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+use super::{InceptionLayerState, WORKER_STARTED};
+
+static ATFORK_REGISTERED: AtomicBool = AtomicBool::new(false);
+/// Process the layer's memory belongs to (0 until init)
+static OWNER_PID: AtomicI32 = AtomicI32::new(0);
+
+/// Install the fork handlers (once, after init has completed)
+pub(super) fn register_atfork() {
+    if !ATFORK_REGISTERED.swap(true, Ordering::SeqCst) {
+        OWNER_PID.store(unsafe { libc::getpid() }, Ordering::Relaxed);
+        unsafe { libc::pthread_atfork(Some(prepare), Some(parent), Some(child)) };
+    }
+}
+
+/// Whether we run in a child sharing the parent's memory, where only raw
+/// syscalls on the stack are safe
+pub(crate) fn in_shared_vm_child() -> bool {
+    let owner = OWNER_PID.load(Ordering::Relaxed);
+    owner != 0 && owner != unsafe { libc::getpid() }
+}
+
+/// Take every state lock so no other thread is mid-update at the fork.
+/// Fixed order; no code path holds two of these at once.
+extern "C" fn prepare() {
+    if let Some(state) = InceptionLayerState::get_no_spawn() {
+        state.cow_sessions.fork_prepare();
+        state.open_dirs.fork_prepare();
+        #[cfg(target_os = "linux")]
+        state.dir_fd_listings.fork_prepare();
+        state.active_mmaps.fork_prepare();
+    }
+    crate::ipc::fork_prepare();
+}
+
+extern "C" fn parent() {
+    crate::ipc::fork_parent();
+    if let Some(state) = InceptionLayerState::get_no_spawn() {
+        state.active_mmaps.fork_parent();
+        #[cfg(target_os = "linux")]
+        state.dir_fd_listings.fork_parent();
+        state.open_dirs.fork_parent();
+        state.cow_sessions.fork_parent();
+    }
+}
+
+/// Runs single-threaded in the child, before fork() returns to it
+extern "C" fn child() {
+    OWNER_PID.store(unsafe { libc::getpid() }, Ordering::Relaxed);
+    crate::ipc::fork_child();
+
+    let Some(state) = InceptionLayerState::get_no_spawn() else {
+        return;
+    };
+    state.active_mmaps.fork_child();
+    state.open_dirs.fork_child();
+    #[cfg(target_os = "linux")]
+    state.dir_fd_listings.fork_child();
+    state.cow_sessions.fork_child();
+
+    // The parent reingests its copies; the child closing its duplicates of
+    // those fds (or exiting) must not
+    state.cow_sessions.lock().clear();
+
+    // Queued work belongs to the parent's Worker. Entries being reclaimed
+    // are the child's own copies, so free them; drop everything else.
+    if let Some(reactor) = crate::sync::get_reactor() {
+        reactor.ring_buffer.drain_after_fork(|task| {
+            if let crate::sync::Task::ReclaimFd(_, entry) = task {
+                InceptionLayerState::reclaim_fd_entry(entry);
+            }
+        });
+    }
+    crate::syscalls::io::PENDING_REINGESTS.store(0, Ordering::Release);
+
+    // get() spawns a fresh Worker on the child's next intercepted call
+    WORKER_STARTED.store(false, Ordering::Release);
+}
@@ -5,16 +5,17 @@
 // BUG-007b: All functions here are #[inline(never)] to prevent merging
 // their large stack frames into get()'s prologue. This module contains:
 //
-//   - init_logger()       — read env vars for log level and debug mode
+//   - init_logger()       — read env vars for log level, debug mode and profiling
 //   - boost_fd_limit()    — raise RLIMIT_NOFILE to 80% of hard cap
-//   - open_manifest_mmap()— mmap the manifest file for O(1) stat lookup
+//   - detect_root_offset()— where this process's root sits on the host (chroot)
+//   - open_manifest_mmap()— mmap the VDir and manifest mmap for O(1) lookups
 //   - init()              — primary initialization, allocates state via raw_mmap
 //   - audit_environment() — detect hazardous env vars
 //   - init_reactor()      — initialize the ring buffer reactor
 //   - setup_signal_handler() / dump_logs_atexit() — optional signal/exit handlers
 // =============================================================================
 
-use crate::path::PathResolver;
+use crate::path::{host_to_local, PathResolver};
 use crate::sync::RecursiveMutex;
 use libc::c_void;
 use std::collections::HashMap;
@@ -68,6 +69,46 @@ impl InceptionLayerState {
                 }
             }
         }
+
+        // VRIFT_PROFILE=1|full|sample:N
+        let profile_ptr = unsafe { libc::getenv(c"VRIFT_PROFILE".as_ptr()) };
+        if !profile_ptr.is_null() {
+            let profile_bytes = unsafe { CStr::from_ptr(profile_ptr).to_bytes() };
+            crate::profile::PROFILE_SAMPLE.store(
+                crate::profile::parse_profile_mode(profile_bytes),
+                Ordering::Relaxed,
+            );
+        }
+
+        // VRIFT_OP_BUDGET=stat=5ms,open=10ms,dir=50ms
+        let budget_ptr = unsafe { libc::getenv(c"VRIFT_OP_BUDGET".as_ptr()) };
+        if !budget_ptr.is_null() {
+            crate::budget::apply_budget_spec(unsafe { CStr::from_ptr(budget_ptr).to_bytes() });
+        }
+
+        // VRIFT_HYDRATE=1 | src,assets,!vendor
+        let hydrate_ptr = unsafe { libc::getenv(c"VRIFT_HYDRATE".as_ptr()) };
+        if !hydrate_ptr.is_null() {
+            crate::hydrate::apply_hydrate_spec(unsafe { CStr::from_ptr(hydrate_ptr).to_bytes() });
+        }
+
+        // VRIFT_SUMMARY=1
+        let summary_ptr = unsafe { libc::getenv(c"VRIFT_SUMMARY".as_ptr()) };
+        if !summary_ptr.is_null() {
+            crate::summary::apply_summary_env(unsafe { CStr::from_ptr(summary_ptr).to_bytes() });
+        }
+
+        // VRIFT_IO_URING=warn|deny|allow
+        #[cfg(target_os = "linux")]
+        {
+            let uring_ptr = unsafe { libc::getenv(c"VRIFT_IO_URING".as_ptr()) };
+            if !uring_ptr.is_null() {
+                crate::uring::apply_io_uring_spec(unsafe { CStr::from_ptr(uring_ptr).to_bytes() });
+            }
+        }
+
+        // VRIFT_HEATMAP=<file> [VRIFT_HEATMAP_DEPTH=N]
+        crate::heat::init_from_env();
     }
 
     /// Attempt to raise RLIMIT_NOFILE to exactly 80% of the true hard cap.
@@ -144,6 +185,62 @@ impl InceptionLayerState {
         soft_limit
     }
 
+    /// Where this process's `/` sits on the host. Host-side `vrift run` exports
+    /// host paths; a child that chroots or runs in a container's mount
+    /// namespace sees them shifted. VRIFT_ROOT_OFFSET sets it explicitly;
+    /// otherwise, when VRIFT_MANIFEST doesn't exist as given, leading
+    /// components are stripped until it does. Empty means the host root.
+    #[inline(never)]
+    #[cold]
+    fn detect_root_offset() -> FixedString<1024> {
+        let mut offset = FixedString::<1024>::new();
+
+        let offset_ptr = unsafe { libc::getenv(c"VRIFT_ROOT_OFFSET".as_ptr()) };
+        if !offset_ptr.is_null() {
+            if let Ok(raw) = unsafe { CStr::from_ptr(offset_ptr) }.to_str() {
+                let mut norm_buf = [0u8; 1024];
+                if let Some(len) = unsafe { crate::path::raw_path_normalize(raw, &mut norm_buf) } {
+                    let norm = std::str::from_utf8(&norm_buf[..len]).unwrap_or("/");
+                    if norm.starts_with('/') && norm != "/" {
+                        offset.set(norm);
+                    }
+                }
+            }
+            return offset;
+        }
+
+        let manifest_ptr = unsafe { libc::getenv(c"VRIFT_MANIFEST".as_ptr()) };
+        if manifest_ptr.is_null() {
+            return offset;
+        }
+        let manifest = unsafe { CStr::from_ptr(manifest_ptr) }.to_bytes();
+        if manifest.first() != Some(&b'/') || path_exists(manifest) != Some(false) {
+            // Relative, too long, or visible here: nothing to detect
+            return offset;
+        }
+
+        // "/a/b/p/.vrift/manifest.lmdb" → try "/b/p/...", then "/p/..."
+        for (i, _) in manifest
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, &b)| b == b'/')
+        {
+            if path_exists(&manifest[i..]) == Some(true) {
+                if let Ok(found) = std::str::from_utf8(&manifest[..i]) {
+                    offset.set(found);
+                    let mut buf = [0u8; 1100];
+                    let mut w = crate::macros::StackWriter::new(&mut buf);
+                    use std::fmt::Write;
+                    let _ = write!(w, "Detected root offset '{}' from VRIFT_MANIFEST", found);
+                    LOGGER.log(w.as_str());
+                }
+                break;
+            }
+        }
+        offset
+    }
+
     /// BUG-007b: MUST NOT be inlined into get().
     /// init() + open_manifest_mmap() together allocate ~605KB on stack
     /// (FixedStrings, PATH_MAX buffers, InceptionLayerState struct).
@@ -154,6 +251,7 @@ impl InceptionLayerState {
     pub(crate) fn init() -> Option<*mut Self> {
         let soft_limit = Self::boost_fd_limit();
         unsafe { Self::init_logger() };
+        let root_offset = Self::detect_root_offset();
 
         let mut cas_root = FixedString::<1024>::new();
         let cas_ptr = unsafe { libc::getenv(c"VR_THE_SOURCE".as_ptr()) };
@@ -184,6 +282,15 @@ impl InceptionLayerState {
             cas_root.set(&raw_path);
         }
 
+        localize(&mut cas_root, root_offset.as_str());
+
+        let mut cas_tiers = FixedString::<1024>::new();
+        let tiers_ptr = unsafe { libc::getenv(c"VRIFT_CAS_TIERS".as_ptr()) };
+        if !tiers_ptr.is_null() {
+            // A truncated list would name a different directory
+            let _ = cas_tiers.try_set(&unsafe { CStr::from_ptr(tiers_ptr).to_string_lossy() });
+        }
+
         let mut vfs_prefix = FixedString::<256>::new();
         let prefix_ptr = unsafe { libc::getenv(c"VRIFT_VFS_PREFIX".as_ptr()) };
         if !prefix_ptr.is_null() {
@@ -199,6 +306,7 @@ impl InceptionLayerState {
                 } else {
                     vfs_prefix.set(raw_prefix);
                 }
+                localize(&mut vfs_prefix, root_offset.as_str());
             }
         }
 
@@ -209,14 +317,17 @@ impl InceptionLayerState {
         } else {
             socket_path.set(&unsafe { CStr::from_ptr(socket_ptr).to_string_lossy() });
         }
+        localize(&mut socket_path, root_offset.as_str());
 
-        let (mmap_ptr, mmap_size) = open_manifest_mmap();
+        let ((mmap_ptr, mmap_size), (manifest_mmap_ptr, manifest_mmap_size)) =
+            open_manifest_mmap(root_offset.as_str());
 
         let mut project_root_fs = FixedString::<1024>::new();
         let manifest_ptr = unsafe { libc::getenv(c"VRIFT_MANIFEST".as_ptr()) };
         if !manifest_ptr.is_null() {
             let manifest_path_cstr = unsafe { CStr::from_ptr(manifest_ptr) };
             if let Ok(manifest_path) = manifest_path_cstr.to_str() {
+                let manifest_path = host_to_local(manifest_path, root_offset.as_str());
                 // Manual derivation of project root from manifest path (parent of .vrift)
                 // Replaces PathBuf/std::fs to avoid interposition & allocations.
                 let mut root_path = manifest_path;
@@ -299,6 +410,27 @@ impl InceptionLayerState {
             }
         }
 
+        // VRIFT_PASSTHROUGH=target,.git,!target/doc
+        let passthrough_ptr = unsafe { libc::getenv(c"VRIFT_PASSTHROUGH".as_ptr()) };
+        let passthrough = if passthrough_ptr.is_null() {
+            ""
+        } else {
+            unsafe { CStr::from_ptr(passthrough_ptr) }
+                .to_str()
+                .unwrap_or("")
+        };
+
+        let mut session_id = FixedString::<64>::new();
+        let session_ptr = unsafe { libc::getenv(c"VRIFT_SESSION_ID".as_ptr()) };
+        if !session_ptr.is_null() {
+            if let Ok(id) = unsafe { CStr::from_ptr(session_ptr) }.to_str() {
+                // Becomes a directory name: reject anything path-like
+                if vrift_config::path::is_valid_session_id(id) {
+                    session_id.set(id);
+                }
+            }
+        }
+
         // RFC-CRIT-001: Bootstrap-Safe Allocation using raw_mmap
         // Replaces malloc to avoid fstat->shim->malloc deadlock on macOS (BUG-007)
         let size = std::mem::size_of::<InceptionLayerState>();
@@ -336,17 +468,28 @@ impl InceptionLayerState {
                 ptr,
                 InceptionLayerState {
                     cas_root,
+                    cas_tiers,
                     vfs_prefix,
                     socket_path,
                     vdird_socket_path: FixedString::new(),
                     open_fds: crate::sync::FdTable::new(),
                     active_mmaps: RecursiveMutex::new(HashMap::with_hasher(IdentityBuildHasher)),
                     open_dirs: RecursiveMutex::new(HashMap::with_hasher(IdentityBuildHasher)),
-                    bloom_ptr: ptr::null(),
+                    #[cfg(target_os = "linux")]
+                    dir_fd_listings: RecursiveMutex::new(HashMap::with_hasher(IdentityBuildHasher)),
+                    cow_sessions: RecursiveMutex::new(HashMap::with_hasher(IdentityBuildHasher)),
                     mmap_ptr,
                     mmap_size,
+                    manifest_mmap_ptr,
+                    manifest_mmap_size,
                     project_root: project_root_fs,
-                    path_resolver: PathResolver::new(vfs_prefix.as_str(), project_root_fs.as_str()),
+                    session_id,
+                    root_offset,
+                    path_resolver: PathResolver::new(
+                        vfs_prefix.as_str(),
+                        project_root_fs.as_str(),
+                        passthrough,
+                    ),
                     cached_soft_limit: std::sync::atomic::AtomicUsize::new(soft_limit),
                     last_usage_alert: std::sync::atomic::AtomicU64::new(0),
                     tasks: Self::init_reactor(),
@@ -414,19 +557,45 @@ impl InceptionLayerState {
     }
 }
 
+/// Rewrite a host path in place to where it is visible from this root
+fn localize<const N: usize>(path: &mut FixedString<N>, root_offset: &str) {
+    let host = *path;
+    path.set(host_to_local(host.as_str(), root_offset));
+}
+
+/// Raw-stat existence check; `None` if the path doesn't fit the stack buffer
+fn path_exists(path: &[u8]) -> Option<bool> {
+    let mut buf = [0u8; 1024];
+    if path.len() >= buf.len() {
+        return None;
+    }
+    buf[..path.len()].copy_from_slice(path);
+    let mut st: libc::stat = unsafe { std::mem::zeroed() };
+    #[cfg(target_os = "macos")]
+    let rc = unsafe { crate::syscalls::macos_raw::raw_stat(buf.as_ptr() as *const _, &mut st) };
+    #[cfg(target_os = "linux")]
+    let rc = unsafe { crate::syscalls::linux_raw::raw_stat(buf.as_ptr() as *const _, &mut st) };
+    Some(rc == 0)
+}
+
 // =============================================================================
 // open_manifest_mmap: mmap-based O(1) stat lookup (BUG-007b: #[inline(never)])
 // =============================================================================
 
-/// Open mmap'd manifest file for O(1) stat lookup.
-/// Returns (ptr, size) or (null, 0) if unavailable.
+/// A mapping as (ptr, size): the VDir, then the manifest mmap
+type Mappings = ((*const u8, usize), (*const u8, usize));
+
+const NO_MAPPINGS: Mappings = ((ptr::null(), 0), (ptr::null(), 0));
+
+/// Open the mmap'd VDir for O(1) stat lookup, and the manifest mmap beside
+/// it for O(1) absence checks. Either is (null, 0) if unavailable; without
+/// a VDir the manifest mmap isn't tried.
 /// Uses raw libc to avoid recursion through inception layer.
 /// BUG-007b: MUST NOT be inlined — allocates large stack buffers (PATH_MAX etc.)
 /// that would overflow the 512KB default pthread stack if merged into get().
 #[inline(never)]
 #[cold]
-#[allow(deprecated)]
-pub(crate) fn open_manifest_mmap() -> (*const u8, usize) {
+pub(crate) fn open_manifest_mmap(root_offset: &str) -> Mappings {
     // Check if mmap is explicitly disabled
     unsafe {
         let env_key = c"VRIFT_DISABLE_MMAP";
@@ -434,7 +603,7 @@ pub(crate) fn open_manifest_mmap() -> (*const u8, usize) {
         if !env_val.is_null() {
             let val = CStr::from_ptr(env_val).to_str().unwrap_or("0");
             if val == "1" || val == "true" {
-                return (ptr::null(), 0);
+                return NO_MAPPINGS;
             }
         }
     }
@@ -450,15 +619,20 @@ pub(crate) fn open_manifest_mmap() -> (*const u8, usize) {
     if !vdir_mmap_ptr.is_null() {
         // Phase 1.3: Direct path from env — no derivation needed
         let vdir_str = unsafe { CStr::from_ptr(vdir_mmap_ptr) };
-        let _ = write!(writer, "{}\0", vdir_str.to_str().unwrap_or(""));
+        let vdir_path = host_to_local(vdir_str.to_str().unwrap_or(""), root_offset);
+        let _ = write!(writer, "{}", vdir_path);
     } else {
         // Fallback: Derive from VRIFT_MANIFEST (legacy path)
         let manifest_ptr = unsafe { libc::getenv(c"VRIFT_MANIFEST".as_ptr()) };
         if manifest_ptr.is_null() {
-            return (ptr::null(), 0);
+            return NO_MAPPINGS;
         }
 
         let root_bytes = unsafe { CStr::from_ptr(manifest_ptr).to_bytes() };
+        let root_bytes = match std::str::from_utf8(root_bytes) {
+            Ok(host) => host_to_local(host, root_offset).as_bytes(),
+            Err(_) => root_bytes,
+        };
 
         // Naively assume project root is parent of manifest
         let mut last_slash = 0;
@@ -509,24 +683,70 @@ pub(crate) fn open_manifest_mmap() -> (*const u8, usize) {
         };
         let canon_root_str = canon_root.to_string_lossy();
 
-        let project_id = vrift_config::path::compute_project_id(canon_root_str.as_ref());
+        // The project id is derived from the host path, as vriftd sees it
+        let host_root = if root_offset.is_empty() {
+            canon_root_str.to_string()
+        } else if canon_root_str == "/" {
+            root_offset.to_string()
+        } else {
+            format!("{}{}", root_offset, canon_root_str)
+        };
+        let project_id = vrift_config::path::compute_project_id(&host_root);
         let mmap_path = vrift_config::path::get_vdir_mmap_path(&project_id)
-            .unwrap_or_else(|| PathBuf::from(format!("{}/.vrift/manifest.mmap", canon_root_str)));
+            .unwrap_or_else(|| PathBuf::from(format!("{}/.vrift/manifest.mmap", host_root)));
+        let mmap_path = mmap_path.to_string_lossy();
 
-        let _ = write!(writer, "{}\0", mmap_path.display());
+        let _ = write!(writer, "{}", host_to_local(&mmap_path, root_offset));
     }
 
-    let mmap_path_ptr = path_buf.as_ptr() as *const libc::c_char;
+    // vDird publishes the manifest keys next to the VDir
+    let mut manifest_buf = [0u8; 1024];
+    let mut manifest_writer = crate::macros::StackWriter::new(&mut manifest_buf);
+    let _ = write!(
+        manifest_writer,
+        "{}{}\0",
+        writer.as_str(),
+        vrift_ipc::manifest_mmap::MANIFEST_MMAP_SUFFIX
+    );
+    let _ = writer.write_str("\0");
+
+    let (ptr, size) = map_readonly(path_buf.as_ptr() as *const libc::c_char);
+    if ptr.is_null() {
+        return NO_MAPPINGS;
+    }
 
+    // Phase 1.3: Validate VDirHeader magic
+    use vrift_ipc::vdir_types::{VDIR_HEADER_SIZE, VDIR_MAGIC};
+    if size < VDIR_HEADER_SIZE || unsafe { *(ptr as *const u32) } != VDIR_MAGIC {
+        unsafe { libc::munmap(ptr as *mut c_void, size) };
+        return NO_MAPPINGS;
+    }
+
+    let (manifest_ptr, manifest_size) = map_readonly(manifest_buf.as_ptr() as *const libc::c_char);
+    if !manifest_ptr.is_null() {
+        use vrift_ipc::manifest_mmap::ManifestMmapControl;
+        let valid = manifest_size >= ManifestMmapControl::SIZE
+            && unsafe { &*(manifest_ptr as *const ManifestMmapControl) }.is_valid();
+        if !valid {
+            unsafe { libc::munmap(manifest_ptr as *mut c_void, manifest_size) };
+            return ((ptr, size), (ptr::null(), 0));
+        }
+    }
+
+    ((ptr, size), (manifest_ptr, manifest_size))
+}
+
+/// mmap `path` (NUL-terminated) read-only and shared, so vDird's updates
+/// show. Returns (ptr, size) or (null, 0).
+fn map_readonly(path: *const libc::c_char) -> (*const u8, usize) {
     #[cfg(target_os = "macos")]
-    let fd = unsafe {
-        crate::syscalls::macos_raw::raw_open(mmap_path_ptr, libc::O_RDONLY | libc::O_CLOEXEC, 0)
-    };
+    let fd =
+        unsafe { crate::syscalls::macos_raw::raw_open(path, libc::O_RDONLY | libc::O_CLOEXEC, 0) };
     #[cfg(target_os = "linux")]
     let fd = unsafe {
         crate::syscalls::linux_raw::raw_openat(
             libc::AT_FDCWD,
-            mmap_path_ptr,
+            path,
             libc::O_RDONLY | libc::O_CLOEXEC,
             0,
         )
@@ -590,25 +810,6 @@ pub(crate) fn open_manifest_mmap() -> (*const u8, usize) {
         return (ptr::null(), 0);
     }
 
-    // Phase 1.3: Validate VDirHeader magic instead of ManifestMmapHeader
-    use vrift_ipc::vdir_types::{VDIR_HEADER_SIZE, VDIR_MAGIC};
-    if size < VDIR_HEADER_SIZE {
-        unsafe { libc::munmap(ptr, size) };
-        return (ptr::null(), 0);
-    }
-    let magic = unsafe { *(ptr as *const u32) };
-    if magic != VDIR_MAGIC {
-        // Fallback: Try legacy ManifestMmapHeader format
-        if size >= vrift_ipc::ManifestMmapHeader::SIZE {
-            let header = unsafe { &*(ptr as *const vrift_ipc::ManifestMmapHeader) };
-            if header.is_valid() {
-                return (ptr as *const u8, size);
-            }
-        }
-        unsafe { libc::munmap(ptr, size) };
-        return (ptr::null(), 0);
-    }
-
     (ptr as *const u8, size)
 }
 
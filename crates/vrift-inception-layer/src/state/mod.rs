@@ -10,11 +10,15 @@
 //
 // Cold-path init code lives in state/init.rs (behind #[inline(never)])
 // Background worker code lives in state/worker.rs
+// pthread_atfork handlers live in state/fork.rs
 // =============================================================================
 
+mod fork;
 mod init;
 mod worker;
 
+pub(crate) use fork::in_shared_vm_child;
+
 use crate::ipc::*;
 use crate::path::{PathResolver, VfsPath};
 use crate::sync::RecursiveMutex;
@@ -125,6 +129,8 @@ pub enum EventType {
     Close = 10,
     ReingestSuccess = 11,
     ReingestFail = 12,
+    BudgetExceeded = 13,
+    Hydrated = 14,
 }
 
 #[repr(C)]
@@ -202,6 +208,8 @@ pub static EVENT_NAMES: &[&str] = &[
     "Close",
     "ReingestSuccess",
     "ReingestFail",
+    "BudgetExceeded",
+    "Hydrated",
 ];
 
 // ============================================================================
@@ -418,6 +426,16 @@ pub fn is_vfs_ready() -> bool {
     VFS_READY.load(Ordering::Acquire) != 0
 }
 
+/// Register `hook` with atexit at most once per `registered` flag.
+/// atexit is unsafe while the loader is still bootstrapping (BUG-004), so
+/// nothing is registered (and the flag stays clear) until init is Ready.
+pub(crate) fn register_atexit_once(registered: &AtomicBool, hook: extern "C" fn()) {
+    let ready = unsafe { INITIALIZING.load(Ordering::Relaxed) } == InceptionState::Ready as u8;
+    if ready && !registered.swap(true, Ordering::SeqCst) {
+        unsafe { libc::atexit(hook) };
+    }
+}
+
 // Lock-free recursion key using atomic instead of OnceLock (avoids mutex deadlock during library init)
 static RECURSION_KEY_INIT: AtomicBool = AtomicBool::new(false);
 static RECURSION_KEY_VALUE: AtomicUsize = AtomicUsize::new(0);
@@ -469,6 +487,21 @@ pub(crate) fn get_recursion_key() -> libc::pthread_key_t {
     }
 }
 
+/// Whether this thread holds an InceptionLayerGuard, i.e. is inside an
+/// intercepted call. Never creates the TLS key, so the allocator may ask.
+#[inline]
+pub(crate) fn in_inception_layer() -> bool {
+    recursion_key().is_some_and(|key| !unsafe { libc::pthread_getspecific(key) }.is_null())
+}
+
+/// The recursion guard's TLS key, if it has been created
+#[inline]
+pub(crate) fn recursion_key() -> Option<libc::pthread_key_t> {
+    RECURSION_KEY_INIT
+        .load(Ordering::Acquire)
+        .then(|| RECURSION_KEY_VALUE.load(Ordering::Relaxed) as libc::pthread_key_t)
+}
+
 pub(crate) struct InceptionLayerGuard(bool); // bool: true = has active TLS guard
 impl InceptionLayerGuard {
     pub(crate) fn enter() -> Option<Self> {
@@ -669,13 +702,26 @@ impl<const N: usize> FixedString<N> {
         }
     }
 
+    /// Store `s`, truncated to capacity on a char boundary
     pub fn set(&mut self, s: &str) {
-        let bytes = s.as_bytes();
-        let to_copy = std::cmp::min(bytes.len(), N);
-        self.data[..to_copy].copy_from_slice(&bytes[..to_copy]);
+        let mut to_copy = std::cmp::min(s.len(), N);
+        while !s.is_char_boundary(to_copy) {
+            to_copy -= 1;
+        }
+        self.data[..to_copy].copy_from_slice(&s.as_bytes()[..to_copy]);
         self.len = to_copy;
     }
 
+    /// Store `s` only if it fits whole. Use for paths, where a truncated
+    /// value would name a different file.
+    pub fn try_set(&mut self, s: &str) -> bool {
+        if s.len() > N {
+            return false;
+        }
+        self.set(s);
+        true
+    }
+
     pub fn as_str(&self) -> &str {
         std::str::from_utf8(&self.data[..self.len]).unwrap_or("")
     }
@@ -773,17 +819,147 @@ pub(crate) struct MmapInfo {
     pub len: usize,
 }
 
-pub(crate) struct SyntheticDir {
+/// One CoW copy shared by every writable fd of a vpath in this process.
+/// Opens, dups and closes adjust `refs`; the last close reingests.
+pub(crate) struct CowSession {
     pub vpath: FixedString<1024>,
-    pub entries: Vec<vrift_ipc::DirEntry>,
+    pub temp_path: FixedString<1024>,
+    pub refs: usize,
+    /// Manifest content hash when the copy was taken, to spot a concurrent reingest
+    pub base_hash: [u8; 32],
+}
+
+/// Children a synthetic directory stream asks vDird for at a time
+pub(crate) const DIR_BATCH: usize = 256;
+/// Name bytes one batch can hold; a batch of longer names is cut short and
+/// the rest comes with the next one
+const DIR_ARENA_BYTES: usize = 32 * 1024;
+
+/// The current batch of a synthetic directory stream. Allocated once per
+/// stream and refilled in place, so a 100k-entry listing costs a few
+/// hundred IPC replies and no allocation per entry.
+pub(crate) struct DirentArena {
+    /// Names of the batch, back to back
+    names: [u8; DIR_ARENA_BYTES],
+    /// End offset in `names` and directory flag of each child
+    slots: [(u32, bool); DIR_BATCH],
+    len: usize,
+    /// Next slot readdir hands out
+    next: usize,
+    /// Whether vDird has children after this batch
+    more: bool,
+}
+
+impl DirentArena {
+    /// An empty arena that pages from the start of the listing
+    pub fn new() -> Box<Self> {
+        // SAFETY: all-zero is a valid arena (ints and `false`); built on the
+        // heap as it is too large for small thread stacks
+        let mut arena = unsafe { Box::<Self>::new_zeroed().assume_init() };
+        arena.rewind();
+        arena
+    }
+
+    /// Page from the start again on the next `refill`
+    pub fn rewind(&mut self) {
+        self.len = 0;
+        self.next = 0;
+        self.more = true;
+    }
+
+    /// Every child of the current batch has been handed out
+    pub fn drained(&self) -> bool {
+        self.next == self.len
+    }
+
+    /// Whether a `refill` can return more children
+    pub fn has_more(&self) -> bool {
+        self.more
+    }
+
+    fn name(&self, slot: usize) -> &[u8] {
+        let start = if slot == 0 { 0 } else { self.slots[slot - 1].0 };
+        &self.names[start as usize..self.slots[slot].0 as usize]
+    }
+
+    /// Name the next batch resumes after ("" before the first batch)
+    pub fn resume_after(&self) -> &str {
+        if self.len == 0 {
+            return "";
+        }
+        std::str::from_utf8(self.name(self.len - 1)).unwrap_or("")
+    }
+
+    /// Replace the batch with a ManifestListPageAck's children, as many as fit
+    pub fn fill(&mut self, names: &[u8], dirs: &[bool], more: bool) {
+        self.len = 0;
+        self.next = 0;
+        self.more = more;
+        let mut end = 0usize;
+        for (name, &is_dir) in names.split(|&b| b == 0).zip(dirs) {
+            if self.len == DIR_BATCH || end + name.len() > DIR_ARENA_BYTES {
+                self.more = true;
+                break;
+            }
+            self.names[end..end + name.len()].copy_from_slice(name);
+            end += name.len();
+            self.slots[self.len] = (end as u32, is_dir);
+            self.len += 1;
+        }
+    }
+
+    /// Put the child `take` just handed out back
+    pub fn unget(&mut self) {
+        self.next = self.next.saturating_sub(1);
+    }
+
+    /// Hand out the next child of the batch
+    pub fn take(&mut self) -> Option<(&str, bool)> {
+        if self.drained() {
+            return None;
+        }
+        let slot = self.next;
+        self.next += 1;
+        let name = std::str::from_utf8(self.name(slot)).ok()?;
+        Some((name, self.slots[slot].1))
+    }
+}
+
+/// DIR stream for a directory that only exists in the manifest. The DIR*
+/// handed out is the address of the boxed value in `open_dirs`.
+pub(crate) struct SyntheticDir {
+    /// Manifest key of the directory
+    pub key: FixedString<1024>,
+    /// Children paged in from vDird
+    pub batch: Box<DirentArena>,
+    /// Next entry; 0 and 1 are "." and ".."
     pub position: usize,
+    /// What readdir returns a pointer to, valid until the next call on this stream
+    #[cfg(target_os = "linux")]
+    pub dirent: libc::dirent64,
+    #[cfg(target_os = "macos")]
+    pub dirent: libc::dirent,
 }
-unsafe impl Send for SyntheticDir {} // Raw pointers in open_dirs HashMap
-unsafe impl Sync for SyntheticDir {}
 
 pub(crate) static SYNTHETIC_DIR_COUNTER: std::sync::atomic::AtomicUsize =
     std::sync::atomic::AtomicUsize::new(0);
 
+/// getdents64 progress on a directory fd inside the VFS: the kernel's
+/// entries come first, then the manifest children that aren't on disk
+#[cfg(target_os = "linux")]
+pub(crate) struct DirFdListing {
+    /// Children paged in from vDird
+    pub batch: Box<DirentArena>,
+    /// The kernel has no entries left; listing the manifest's
+    pub virtual_phase: bool,
+    /// d_off of the last manifest child handed out
+    pub offset: i64,
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) static DIR_FD_LISTING_COUNTER: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+
 // ============================================================================
 // mmap_dir_lookup: O(1) directory listing from legacy ManifestMmapHeader format
 // NOTE: mmap_lookup removed — replaced by vdir_lookup (seqlock-protected VDir)
@@ -793,7 +969,9 @@ pub(crate) static SYNTHETIC_DIR_COUNTER: std::sync::atomic::AtomicUsize =
 // Phase 1.3: vdir_lookup — seqlock-protected O(1) stat from VDir mmap
 // ============================================================================
 
-use vrift_ipc::vdir_types::{VDirEntry, VDIR_ENTRY_SIZE, VDIR_HEADER_SIZE, VDIR_MAGIC};
+use vrift_ipc::vdir_types::{
+    VDirEntry, VDIR_ENTRY_SIZE, VDIR_HEADER_SIZE, VDIR_MAGIC, VDIR_VERSION,
+};
 
 /// Result from VDir lookup (VDirEntry fields needed for stat)
 #[derive(Debug, Clone, Copy)]
@@ -804,6 +982,17 @@ pub(crate) struct VDirStatResult {
     pub mode: u32,
     pub flags: u16,
     pub cas_hash: [u8; 32],
+    /// Creation time, 0 if the manifest has none
+    pub btime_sec: i64,
+    /// Inode vDird allocated, 0 if none
+    pub ino: u64,
+}
+
+impl VDirStatResult {
+    /// The entry is a tombstone: the path was removed this session
+    pub fn is_deleted(&self) -> bool {
+        self.flags & vrift_ipc::vdir_types::FLAG_DELETED != 0
+    }
 }
 
 /// Maximum seqlock spins before giving up and falling back to IPC.
@@ -821,12 +1010,21 @@ pub(crate) fn vdir_lookup(
     if mmap_ptr.is_null() || mmap_size < VDIR_HEADER_SIZE {
         return None;
     }
+    // vDird declined mmap-cache in Negotiate
+    if !crate::ipc::mmap_cache_agreed() {
+        return None;
+    }
 
     // Validate magic (first 4 bytes of header)
     let magic = unsafe { *(mmap_ptr as *const u32) };
     if magic != VDIR_MAGIC {
         return None;
     }
+    // A VDir from an older vDird has another slot size: use IPC instead
+    let version = unsafe { *((mmap_ptr as usize + 4) as *const u32) };
+    if version != VDIR_VERSION {
+        return None;
+    }
 
     // Read header fields we need (offsets from VDirHeader layout)
     // generation is at offset 8 (after magic:u32 + version:u32)
@@ -883,6 +1081,8 @@ pub(crate) fn vdir_lookup(
                     mode: entry.mode,
                     flags: entry.flags,
                     cas_hash: entry.cas_hash,
+                    btime_sec: entry.btime_sec,
+                    ino: entry.ino,
                 });
                 break;
             }
@@ -913,6 +1113,9 @@ pub(crate) fn vdir_lookup(
 
 pub(crate) struct InceptionLayerState {
     pub cas_root: FixedString<1024>,
+    /// Extra CAS roots (`VRIFT_CAS_TIERS`, colon-separated) searched when
+    /// a blob isn't under `cas_root`
+    pub cas_tiers: FixedString<1024>,
     pub vfs_prefix: FixedString<256>,
     pub socket_path: FixedString<1024>,
     /// Phase 1.2: vDird socket path, populated from RegisterAck.
@@ -920,11 +1123,23 @@ pub(crate) struct InceptionLayerState {
     pub vdird_socket_path: FixedString<1024>,
     pub open_fds: crate::sync::FdTable,
     pub active_mmaps: RecursiveMutex<HashMap<usize, MmapInfo, IdentityBuildHasher>>,
-    pub open_dirs: RecursiveMutex<HashMap<usize, SyntheticDir, IdentityBuildHasher>>,
-    pub bloom_ptr: *const u8,
+    pub open_dirs: RecursiveMutex<HashMap<usize, Box<SyntheticDir>, IdentityBuildHasher>>,
+    /// Keyed by fd
+    #[cfg(target_os = "linux")]
+    pub dir_fd_listings: RecursiveMutex<HashMap<c_int, Box<DirFdListing>, IdentityBuildHasher>>,
+    /// Keyed by manifest_key_hash
+    pub cow_sessions: RecursiveMutex<HashMap<u64, CowSession, IdentityBuildHasher>>,
     pub mmap_ptr: *const u8,
     pub mmap_size: usize,
+    /// Published manifest keys (see `vrift_ipc::manifest_mmap`), null if none
+    pub manifest_mmap_ptr: *const u8,
+    pub manifest_mmap_size: usize,
     pub project_root: FixedString<1024>,
+    /// VRIFT_SESSION_ID: COW staging goes to .vrift/staging/<session_id>/
+    /// (empty = shared .vrift/staging/, for sessions started by older CLIs)
+    pub session_id: FixedString<64>,
+    /// Host path of this process's root (empty unless chrooted/containerized)
+    pub root_offset: FixedString<1024>,
     pub path_resolver: PathResolver,
     pub cached_soft_limit: AtomicUsize,
     pub last_usage_alert: std::sync::atomic::AtomicU64,
@@ -1022,6 +1237,9 @@ impl InceptionLayerState {
             unsafe { init::setup_signal_handler() };
             unsafe { libc::atexit(init::dump_logs_atexit) };
         }
+        fork::register_atfork();
+        crate::summary::register();
+        crate::warnings::register();
 
         // Activate VFS - now it's safe to call into Rust from C wrappers.
         activate_vfs();
@@ -1029,28 +1247,126 @@ impl InceptionLayerState {
         unsafe { Some(&*ptr) }
     }
 
-    pub(crate) fn query_manifest(&self, vpath: &VfsPath) -> Option<vrift_ipc::VnodeEntry> {
+    /// `class` sets the IPC time budget; see crate::budget. A tombstone
+    /// reads as absent here; stat and open use `lookup_manifest` to turn it
+    /// into ENOENT rather than fall through to the file on disk.
+    pub(crate) fn query_manifest(
+        &self,
+        vpath: &VfsPath,
+        class: crate::budget::BudgetClass,
+    ) -> Option<vrift_ipc::VnodeEntry> {
+        self.lookup_manifest(vpath, class)
+            .ok()
+            .flatten()
+            .filter(|e| !e.is_whiteout())
+    }
+
+    /// Inode reported for the manifest key `key`: the one vDird allocated,
+    /// from the VDir or else asked for over IPC. Derived from `key_hash`
+    /// only when vDird has none to give.
+    pub(crate) fn virtual_ino(&self, key: &str, key_hash: u64) -> u64 {
+        if let Some(entry) = vdir_lookup(self.mmap_ptr, self.mmap_size, key) {
+            if entry.ino != 0 {
+                return entry.ino;
+            }
+        }
+        let found = unsafe {
+            sync_ipc_manifest_lookup_ino(
+                &self.vdird_socket_path,
+                key,
+                crate::budget::BudgetClass::Stat,
+            )
+        };
+        match found {
+            Ok(Some((_, ino))) if ino != 0 => ino,
+            _ => vrift_ipc::identity::ino_from_hash(key_hash),
+        }
+    }
+
+    /// `query_manifest`, but a failed lookup is `Err(errno)` from the daemon's
+    /// error kind instead of looking like a missing entry
+    pub(crate) fn lookup_manifest(
+        &self,
+        vpath: &VfsPath,
+        class: crate::budget::BudgetClass,
+    ) -> Result<Option<vrift_ipc::VnodeEntry>, c_int> {
+        self.lookup_manifest_ino(vpath, class)
+            .map(|found| found.map(|(entry, _)| entry))
+    }
+
+    /// `lookup_manifest`, with the inode [`virtual_ino`](Self::virtual_ino)
+    /// reports for the entry, taken from the same VDir entry or IPC reply
+    pub(crate) fn lookup_manifest_ino(
+        &self,
+        vpath: &VfsPath,
+        class: crate::budget::BudgetClass,
+    ) -> Result<Option<(vrift_ipc::VnodeEntry, u64)>, c_int> {
+        let key = vpath.manifest_key.as_str();
+        let or_hash = |ino: u64| match ino {
+            0 => vrift_ipc::identity::ino_from_hash(vpath.manifest_key_hash),
+            ino => ino,
+        };
         // Seqlock-protected VDir lookup (zero alloc/lock/syscall)
-        if let Some(entry) = vdir_lookup(self.mmap_ptr, self.mmap_size, vpath.manifest_key.as_str())
-        {
-            return Some(vrift_ipc::VnodeEntry {
+        if let Some(entry) = vdir_lookup(self.mmap_ptr, self.mmap_size, key) {
+            crate::summary::add(&crate::summary::CACHE_HITS, 1);
+            let vnode = vrift_ipc::VnodeEntry {
                 content_hash: entry.cas_hash,
                 size: entry.size,
                 mtime: entry.mtime_sec as u64,
                 mode: entry.mode,
-                flags: entry.flags,
+                flags: vrift_ipc::vdir_types::flags_to_vnode(entry.flags),
                 _pad: 0,
-            });
+            };
+            return Ok(Some((vnode, or_hash(entry.ino))));
+        }
+        if self.manifest_lacks(key) {
+            crate::summary::add(&crate::summary::CACHE_HITS, 1);
+            return Ok(None);
         }
         // Fallback to IPC query (vDird → LMDB)
-        unsafe { sync_ipc_manifest_get(&self.vdird_socket_path, vpath.manifest_key.as_str()) }
+        crate::summary::add(&crate::summary::CACHE_MISSES, 1);
+        let result = unsafe { sync_ipc_manifest_lookup_ino(&self.vdird_socket_path, key, class) };
+        // A real miss: the VDir is mapped but lacks an entry the manifest has
+        if matches!(result, Ok(Some(_))) && !self.mmap_ptr.is_null() {
+            crate::miss_report::record(&self.vdird_socket_path, vpath.manifest_key_hash);
+        }
+        result.map(|found| found.map(|(entry, ino)| (entry, or_hash(ino))))
+    }
+
+    /// The published manifest mmap shows `key` isn't in the manifest, so
+    /// asking vDird would only confirm it (zero alloc/lock/syscall)
+    fn manifest_lacks(&self, key: &str) -> bool {
+        crate::ipc::manifest_mmap_agreed()
+            && unsafe {
+                vrift_ipc::manifest_mmap::proves_absent(
+                    self.manifest_mmap_ptr,
+                    self.manifest_mmap_size,
+                    key,
+                )
+            }
     }
 
     /// Query manifest directly via IPC (bypasses mmap cache)
-    /// Required for open() which needs content_hash to locate CAS blob
-    pub(crate) fn query_manifest_ipc(&self, vpath: &VfsPath) -> Option<vrift_ipc::VnodeEntry> {
+    /// Required for open() which needs content_hash to locate CAS blob.
+    /// Comes with the entry's inode, as `virtual_ino` reports it.
+    pub(crate) fn query_manifest_ipc(
+        &self,
+        vpath: &VfsPath,
+        class: crate::budget::BudgetClass,
+    ) -> Option<(vrift_ipc::VnodeEntry, u64)> {
         // Use the centrally resolved manifest key
-        unsafe { sync_ipc_manifest_get(&self.vdird_socket_path, &vpath.manifest_key) }
+        let key = vpath.manifest_key.as_str();
+        if self.manifest_lacks(key) {
+            return None;
+        }
+        let found = unsafe { sync_ipc_manifest_lookup_ino(&self.vdird_socket_path, key, class) };
+        found.ok().flatten().map(|(entry, ino)| match ino {
+            0 => (
+                entry,
+                vrift_ipc::identity::ino_from_hash(vpath.manifest_key_hash),
+            ),
+            ino => (entry, ino),
+        })
     }
 
     /// Resolve an incoming path into a VfsPath if it belongs to the VFS.
@@ -1058,6 +1374,11 @@ impl InceptionLayerState {
         self.path_resolver.resolve(path)
     }
 
+    /// `resolve_path`, but `Err(ENAMETOOLONG)` for a VFS path too long to serve
+    pub(crate) fn resolve_path_checked(&self, path: &str) -> Result<Option<VfsPath>, c_int> {
+        self.path_resolver.resolve_checked(path)
+    }
+
     /// Check if path is in VFS domain
     pub(crate) fn inception_applicable(&self, path: &str) -> bool {
         self.resolve_path(path).is_some()
@@ -1085,22 +1406,9 @@ impl InceptionLayerState {
         }
     }
 
-    /// RFC-0047: Rename/move entry in manifest
-    /// Phase 3: Fire-and-forget — queued to worker thread
-    pub(crate) fn manifest_rename(&self, old: &str, new: &str) -> Result<(), ()> {
-        let request = vrift_ipc::VeloRequest::ManifestRename {
-            old_path: old.to_string(),
-            new_path: new.to_string(),
-        };
-        if unsafe { fire_and_forget_ipc(&self.vdird_socket_path, &request) } {
-            Ok(())
-        } else {
-            Err(())
-        }
-    }
-
     /// RFC-0047: Create directory entry in manifest
     /// Phase 3: Fire-and-forget — queued to worker thread
+    /// `mode` is the effective permission bits, with the umask applied.
     #[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS, u32 on Linux
     pub(crate) fn manifest_mkdir(&self, path: &str, mode: libc::mode_t) -> Result<(), ()> {
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -1127,6 +1435,7 @@ impl InceptionLayerState {
 
     /// RFC-0039: Create symlink entry in manifest for Live Ingest
     /// Phase 3: Fire-and-forget — queued to worker thread
+    #[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS, u32 on Linux
     pub(crate) fn manifest_symlink(&self, path: &str, _target: &str) -> Result<(), ()> {
         use std::time::{SystemTime, UNIX_EPOCH};
         let request = vrift_ipc::VeloRequest::ManifestUpsert {
@@ -1138,7 +1447,7 @@ impl InceptionLayerState {
                     .duration_since(UNIX_EPOCH)
                     .map(|d| d.as_secs())
                     .unwrap_or(0),
-                mode: 0o777,
+                mode: libc::S_IFLNK as u32 | 0o777,
                 flags: 2, // is_symlink pseudo-flag
                 _pad: 0,
             },
@@ -1150,11 +1459,25 @@ impl InceptionLayerState {
         }
     }
 
-    /// Query daemon for directory listing (for opendir/readdir)
-    #[allow(dead_code)]
-    pub(crate) fn query_dir_listing(&self, path: &str) -> Option<Vec<vrift_ipc::DirEntry>> {
-        // Fall back to IPC (readdir is not on the PSFS hot path and VDir doesn't store filenames)
-        unsafe { sync_ipc_manifest_list_dir(&self.vdird_socket_path, path) }
+    /// Page the next batch of a directory listing into `batch` (for
+    /// opendir/readdir). False if vDird couldn't be asked.
+    pub(crate) fn query_dir_page(&self, path: &str, batch: &mut DirentArena) -> bool {
+        // IPC only: VDir doesn't store filenames (readdir is not on the PSFS hot path)
+        let page = unsafe {
+            sync_ipc_manifest_list_page(
+                &self.vdird_socket_path,
+                path,
+                batch.resume_after(),
+                DIR_BATCH as u32,
+            )
+        };
+        match page {
+            Some((names, dirs, more)) => {
+                batch.fill(&names, &dirs, more);
+                true
+            }
+            None => false,
+        }
     }
 
     fn try_connect(&self) -> i32 {
@@ -1183,7 +1506,7 @@ impl InceptionLayerState {
             // Serialize payload
             let payload = rkyv::to_bytes::<rkyv::rancor::Error>(request).ok()?;
             if payload.len() > vrift_ipc::IpcHeader::MAX_LENGTH {
-                libc::close(fd);
+                ipc_raw_close(fd);
                 return None;
             }
 
@@ -1191,31 +1514,31 @@ impl InceptionLayerState {
             let seq_id = next_seq_id();
             let header = IpcHeader::new_request(payload.len() as u32, seq_id);
             if !raw_write_all(fd, &header.to_bytes()) || !raw_write_all(fd, &payload) {
-                libc::close(fd);
+                ipc_raw_close(fd);
                 return None;
             }
 
             // Read response header
             let mut header_buf = [0u8; IpcHeader::SIZE];
             if !raw_read_exact(fd, &mut header_buf) {
-                libc::close(fd);
+                ipc_raw_close(fd);
                 return None;
             }
 
             let resp_header = IpcHeader::from_bytes(&header_buf);
             if !resp_header.is_valid() {
-                libc::close(fd);
+                ipc_raw_close(fd);
                 return None;
             }
 
             // Read response payload
             let mut resp_buf = vec![0u8; resp_header.length as usize];
             if !raw_read_exact(fd, &mut resp_buf) {
-                libc::close(fd);
+                ipc_raw_close(fd);
                 return None;
             }
 
-            libc::close(fd);
+            ipc_raw_close(fd);
             rkyv::from_bytes::<vrift_ipc::VeloResponse, rkyv::rancor::Error>(&resp_buf).ok()
         }
     }
@@ -11,7 +11,7 @@
 
 use std::sync::atomic::Ordering;
 
-use super::{InceptionLayerState, DIRTY_TRACKER, WORKER_STARTED};
+use super::{InceptionLayerState, WORKER_STARTED};
 
 impl InceptionLayerState {
     /// BUG-007b: Must not inline — pthread_create internally calls mmap (interposed).
@@ -42,6 +42,8 @@ impl InceptionLayerState {
             libc::sigfillset(&mut mask);
             libc::pthread_sigmask(libc::SIG_BLOCK, &mask, std::ptr::null_mut());
         }
+        #[cfg(feature = "alloc-audit")]
+        crate::alloc_audit::exempt_current_thread();
 
         let reactor = match crate::sync::get_reactor() {
             Some(r) => r,
@@ -73,29 +75,27 @@ impl InceptionLayerState {
         }
     }
 
+    pub(super) fn reclaim_fd_entry(entry: *mut crate::syscalls::io::FdEntry) {
+        if !entry.is_null() {
+            let e = unsafe { Box::from_raw(entry) };
+            if e.lock_fd >= 0 {
+                unsafe { libc::close(e.lock_fd) };
+            }
+        }
+    }
+
     fn process_task(task: crate::sync::Task) {
         match task {
-            crate::sync::Task::ReclaimFd(_fd, entry) => {
-                if !entry.is_null() {
-                    let e = unsafe { Box::from_raw(entry) };
-                    if e.lock_fd >= 0 {
-                        unsafe { libc::close(e.lock_fd) };
-                    }
-                }
-            }
-            crate::sync::Task::Reingest { vpath, temp_path } => {
+            crate::sync::Task::ReclaimFd(_fd, entry) => Self::reclaim_fd_entry(entry),
+            crate::sync::Task::Reingest {
+                vpath,
+                temp_path,
+                base_hash,
+            } => {
                 if let Some(state) = InceptionLayerState::get_no_spawn() {
-                    unsafe {
-                        if crate::ipc::sync_ipc_manifest_reingest(
-                            &state.socket_path,
-                            &vpath,
-                            &temp_path,
-                        ) {
-                            // M4: Clear dirty status ONLY after the daemon confirms reingest.
-                            DIRTY_TRACKER.clear_dirty(&vpath);
-                        }
-                    }
+                    crate::syscalls::io::reingest_cow(state, &vpath, &temp_path, &base_hash);
                 }
+                crate::syscalls::io::PENDING_REINGESTS.fetch_sub(1, Ordering::AcqRel);
             }
             crate::sync::Task::Log(msg) => {
                 unsafe { libc::write(2, msg.as_ptr() as *const _, msg.len()) };
@@ -0,0 +1,115 @@
+// =============================================================================
+// summary.rs — One-line session summary at exit (VRIFT_SUMMARY=1)
+// =============================================================================
+//
+// The profile JSON is for digging into a slow build; this is the line a user
+// glances at to see whether the VFS did anything at all:
+//
+//   vrift: 1203 files from VFS, 48.2 MiB from CAS, 3 reingested,
+//          5120 IPC calls, 97.1% cache hits
+//
+// "Cache hits" are manifest lookups answered by the VDir mmap without asking
+// vDird. Counters are only touched while enabled.
+//
+// Zero-allocation and lock-free; output uses the raw write syscall so it
+// never re-enters the shim.
+// =============================================================================
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+#[cfg(target_os = "linux")]
+use crate::syscalls::linux_raw as raw;
+#[cfg(target_os = "macos")]
+use crate::syscalls::macos_raw as raw;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Files opened from a CAS blob, read-only or as a CoW copy
+pub static FILES_SERVED: AtomicU64 = AtomicU64::new(0);
+/// Size of the blobs behind FILES_SERVED
+pub static CAS_BYTES: AtomicU64 = AtomicU64::new(0);
+/// CoW copies the daemon accepted back into the manifest
+pub static FILES_REINGESTED: AtomicU64 = AtomicU64::new(0);
+/// Requests sent to vriftd or vDird
+pub static IPC_CALLS: AtomicU64 = AtomicU64::new(0);
+/// Manifest lookups answered by the VDir
+pub static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+/// Manifest lookups that fell back to vDird
+pub static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Apply VRIFT_SUMMARY; only "1" enables it
+pub fn apply_summary_env(value: &[u8]) {
+    if value.trim_ascii() == b"1" {
+        ENABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Add `n` to `counter` if the summary is on
+#[inline(always)]
+pub fn add(counter: &AtomicU64, n: u64) {
+    if is_enabled() {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+/// Register the exit hook. Called once init is complete (BUG-004), so it
+/// runs after hooks registered later, such as the CoW exit flush, and
+/// their reingests are counted.
+pub(crate) fn register() {
+    if is_enabled() {
+        unsafe { libc::atexit(print_summary) };
+    }
+}
+
+extern "C" fn print_summary() {
+    let mut buf = [0u8; 256];
+    let mut w = crate::macros::StackWriter::new(&mut buf);
+    let files = FILES_SERVED.load(Ordering::Relaxed);
+    let _ = write!(
+        w,
+        "vrift: {} file{} from VFS, ",
+        files,
+        if files == 1 { "" } else { "s" }
+    );
+    write_bytes(&mut w, CAS_BYTES.load(Ordering::Relaxed));
+    let _ = write!(
+        w,
+        " from CAS, {} reingested, {} IPC calls, ",
+        FILES_REINGESTED.load(Ordering::Relaxed),
+        IPC_CALLS.load(Ordering::Relaxed)
+    );
+    let hits = CACHE_HITS.load(Ordering::Relaxed);
+    let lookups = hits + CACHE_MISSES.load(Ordering::Relaxed);
+    match (hits * 1000).checked_div(lookups) {
+        Some(permille) => {
+            let _ = writeln!(w, "{}.{}% cache hits", permille / 10, permille % 10);
+        }
+        None => {
+            let _ = writeln!(w, "no manifest lookups");
+        }
+    }
+    let line = w.as_str();
+    unsafe { raw::raw_write(2, line.as_ptr() as *const libc::c_void, line.len()) };
+}
+
+/// "512 B", "3.4 KiB", "48.2 MiB", ... with one decimal
+fn write_bytes(w: &mut crate::macros::StackWriter<'_>, bytes: u64) {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        let _ = write!(w, "{} B", bytes);
+        return;
+    }
+    let mut unit = 0;
+    let mut scaled = bytes as f64;
+    while scaled >= 1024.0 && unit < UNITS.len() - 1 {
+        scaled /= 1024.0;
+        unit += 1;
+    }
+    let _ = write!(w, "{:.1} {}", scaled, UNITS[unit]);
+}
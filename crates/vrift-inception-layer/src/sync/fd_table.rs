@@ -47,6 +47,10 @@ impl FdTable {
 
         let mut tier2_ptr = self.table[i1].load(Ordering::Acquire);
         if tier2_ptr.is_null() {
+            if entry.is_null() {
+                // Clearing an untracked fd (close() of any fd): nothing to do
+                return ptr::null_mut();
+            }
             // Lazy allocation of the second tier
             let new_tier = Box::into_raw(Box::new(Tier2 {
                 entries: [const { AtomicPtr::new(ptr::null_mut()) }; TIER2_SIZE],
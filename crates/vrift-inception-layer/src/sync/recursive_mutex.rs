@@ -26,6 +26,14 @@ impl<T> RecursiveMutex<T> {
         }
     }
 
+    unsafe fn init_mutex(&self) {
+        let mut attr: libc::pthread_mutexattr_t = std::mem::zeroed();
+        libc::pthread_mutexattr_init(&mut attr);
+        libc::pthread_mutexattr_settype(&mut attr, libc::PTHREAD_MUTEX_RECURSIVE);
+        libc::pthread_mutex_init(self.inner.get(), &attr);
+        libc::pthread_mutexattr_destroy(&mut attr);
+    }
+
     fn ensure_init(&self) {
         if self.initialized.load(Ordering::Acquire) {
             return;
@@ -41,14 +49,8 @@ impl<T> RecursiveMutex<T> {
         }
 
         if !self.initialized.load(Ordering::Relaxed) {
-            unsafe {
-                let mut attr: libc::pthread_mutexattr_t = std::mem::zeroed();
-                libc::pthread_mutexattr_init(&mut attr);
-                libc::pthread_mutexattr_settype(&mut attr, libc::PTHREAD_MUTEX_RECURSIVE);
-                libc::pthread_mutex_init(self.inner.get(), &attr);
-                libc::pthread_mutexattr_destroy(&mut attr);
-                self.initialized.store(true, Ordering::Release);
-            }
+            unsafe { self.init_mutex() };
+            self.initialized.store(true, Ordering::Release);
         }
 
         self.init_lock.store(false, Ordering::Release);
@@ -61,6 +63,33 @@ impl<T> RecursiveMutex<T> {
         }
         RecursiveMutexGuard { mutex: self }
     }
+
+    /// `pthread_atfork` prepare: hold the lock across fork() so the child
+    /// never inherits the data mid-update. Paired with exactly one of
+    /// `fork_parent` / `fork_child`.
+    pub fn fork_prepare(&self) {
+        self.ensure_init();
+        unsafe {
+            libc::pthread_mutex_lock(self.inner.get());
+        }
+    }
+
+    /// `pthread_atfork` parent: release the lock taken in `fork_prepare`
+    pub fn fork_parent(&self) {
+        unsafe {
+            libc::pthread_mutex_unlock(self.inner.get());
+        }
+    }
+
+    /// `pthread_atfork` child: the inherited lock is owned by the parent's
+    /// thread id, so it can't be unlocked here; start over with a fresh one.
+    pub fn fork_child(&self) {
+        if !self.initialized.load(Ordering::Acquire) {
+            return;
+        }
+        unsafe { self.init_mutex() };
+        self.init_lock.store(false, Ordering::Release);
+    }
 }
 
 impl<T> Drop for RecursiveMutex<T> {
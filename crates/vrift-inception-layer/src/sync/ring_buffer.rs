@@ -13,6 +13,8 @@ pub enum Task {
     Reingest {
         vpath: String,
         temp_path: String,
+        /// Manifest hash the copy started from (boxed to keep slots small)
+        base_hash: Box<[u8; 32]>,
     },
     Log(String),
     /// Phase 3: Fire-and-forget IPC — pre-serialized request bytes pushed to worker.
@@ -209,6 +211,23 @@ impl RingBuffer {
 
         count
     }
+
+    /// Empty the buffer in a forked child, handing each queued task to `f`.
+    /// A slot claimed by a producer thread that didn't survive the fork is
+    /// never filled, so unlike `pop` this skips empty slots instead of
+    /// waiting on them.
+    pub fn drain_after_fork(&self, mut f: impl FnMut(Task)) {
+        let mut tail = self.tail.0.load(Ordering::Relaxed);
+        let head = self.head.0.load(Ordering::Acquire);
+        while tail != head {
+            let task = unsafe { (&mut *self.buffer[tail & BUFFER_MASK].get()).take() };
+            if let Some(task) = task {
+                f(task);
+            }
+            tail = tail.wrapping_add(1);
+        }
+        self.tail.0.store(tail, Ordering::Release);
+    }
 }
 
 /// Helper for static initialization
@@ -1,175 +1,488 @@
-// Symbols imported from reals.rs via crate::reals
-#[cfg(target_os = "macos")]
-use crate::state::*;
+// =============================================================================
+// dir.rs — Synthetic directory streams, getcwd and chdir
+// =============================================================================
+//
+// A directory that exists only in the manifest (e.g. under VRIFT_VFS_PREFIX)
+// has nothing on disk for libc's opendir to read, so `ls` shows it empty
+// even though stat works. When the real opendir fails with ENOENT and the
+// manifest knows the directory, opendir returns a synthetic DIR*: the
+// address of a boxed SyntheticDir in `open_dirs`. Its children are paged in
+// from vDird (ManifestListDirPage) DIR_BATCH at a time into an arena
+// allocated with the stream, so huge directories stream without holding
+// the whole listing or allocating per entry. readdir/rewinddir/dirfd/closedir
+// recognise those handles and pass every other DIR* through to libc.
+//
+// Programs that list a directory fd with getdents64 themselves never reach
+// readdir. On a directory fd inside the VFS (Linux), getdents64 returns the
+// kernel's entries first and then the manifest's children that aren't on
+// disk, paged the same way. A manifest-only directory opens as an empty
+// stand-in (see open.rs), so its listing is "." and ".." plus the manifest.
+// =============================================================================
+
 #[cfg(target_os = "linux")]
-use libc::c_int;
-#[cfg(target_os = "macos")]
-use libc::{c_int, c_void};
-#[cfg(target_os = "macos")]
+use crate::state::{DirFdListing, DIR_FD_LISTING_COUNTER};
+use crate::state::{
+    DirentArena, InceptionLayerGuard, InceptionLayerState, SyntheticDir, SYNTHETIC_DIR_COUNTER,
+};
+use libc::{c_char, c_int, c_void};
 use std::ffi::CStr;
+use std::sync::atomic::Ordering;
 
-#[no_mangle]
+#[cfg(target_os = "linux")]
+type Dirent = libc::dirent64;
 #[cfg(target_os = "macos")]
-pub unsafe extern "C" fn opendir_inception(path: *const libc::c_char) -> *mut c_void {
-    // RFC-0050: Use IT_OPENDIR.old_func from interpose table to avoid recursion.
-    // dlsym(RTLD_NEXT) returns the inception layer itself due to __interpose mechanism.
-    let real = std::mem::transmute::<
-        *const (),
-        unsafe extern "C" fn(*const libc::c_char) -> *mut c_void,
-    >(crate::interpose::IT_OPENDIR.old_func);
+type Dirent = libc::dirent;
 
-    // Early-boot passthrough
-    passthrough_if_init!(real, path);
+// Symbols imported from reals.rs via crate::reals. readdir64, rewinddir and
+// dirfd are only wrapped on Linux, so they are resolved here on first use.
+#[cfg(target_os = "linux")]
+static REAL_READDIR64: crate::reals::RealSymbol = crate::reals::RealSymbol::new("readdir64\0");
+#[cfg(target_os = "linux")]
+static REAL_REWINDDIR: crate::reals::RealSymbol = crate::reals::RealSymbol::new("rewinddir\0");
+#[cfg(target_os = "linux")]
+static REAL_DIRFD: crate::reals::RealSymbol = crate::reals::RealSymbol::new("dirfd\0");
 
-    if path.is_null() {
-        return real(path);
+unsafe fn real_opendir(path: *const c_char) -> *mut c_void {
+    // RFC-0050: on macOS use IT_OPENDIR.old_func; dlsym(RTLD_NEXT) returns
+    // the inception layer itself due to the __interpose mechanism.
+    #[cfg(target_os = "macos")]
+    let real = std::mem::transmute::<*const (), unsafe extern "C" fn(*const c_char) -> *mut c_void>(
+        crate::interpose::IT_OPENDIR.old_func,
+    );
+    #[cfg(target_os = "linux")]
+    let real = crate::get_real!(
+        crate::reals::REAL_OPENDIR,
+        unsafe extern "C" fn(*const c_char) -> *mut c_void
+    );
+    real(path)
+}
+
+unsafe fn real_readdir(dir: *mut c_void) -> *mut Dirent {
+    #[cfg(target_os = "macos")]
+    let real = std::mem::transmute::<*const (), unsafe extern "C" fn(*mut c_void) -> *mut Dirent>(
+        crate::interpose::IT_READDIR.old_func,
+    );
+    #[cfg(target_os = "linux")]
+    let real = crate::get_real!(
+        crate::reals::REAL_READDIR,
+        unsafe extern "C" fn(*mut c_void) -> *mut Dirent
+    );
+    real(dir)
+}
+
+unsafe fn real_closedir(dir: *mut c_void) -> c_int {
+    #[cfg(target_os = "macos")]
+    let real = std::mem::transmute::<*const (), unsafe extern "C" fn(*mut c_void) -> c_int>(
+        crate::interpose::IT_CLOSEDIR.old_func,
+    );
+    #[cfg(target_os = "linux")]
+    let real = crate::get_real!(
+        crate::reals::REAL_CLOSEDIR,
+        unsafe extern "C" fn(*mut c_void) -> c_int
+    );
+    real(dir)
+}
+
+/// Build a synthetic stream for `path` if the manifest has it as a directory
+unsafe fn synthetic_opendir(path: *const c_char) -> Option<*mut c_void> {
+    let _guard = InceptionLayerGuard::enter()?;
+    let state = InceptionLayerState::get()?;
+    let path_str = CStr::from_ptr(path).to_str().ok()?;
+    let vpath = state.resolve_path(path_str)?;
+
+    // vDird answers an empty listing for unknown paths, so only an explicit
+    // directory entry, the VFS root or a non-empty first batch counts
+    let key = vpath.manifest_key.as_str();
+    let mut batch = DirentArena::new();
+    if !state.query_dir_page(key, &mut batch) {
+        return None;
     }
+    let is_dir = key.is_empty()
+        || key == "/"
+        || !batch.drained()
+        || state
+            .query_manifest(&vpath, crate::budget::BudgetClass::Dir)
+            .is_some_and(|e| e.is_dir());
+    if !is_dir {
+        return None;
+    }
+    crate::heat::record(key, crate::heat::HeatOp::Readdir);
+
+    let dir = Box::new(SyntheticDir {
+        key: vpath.manifest_key,
+        batch,
+        position: 0,
+        dirent: std::mem::zeroed(),
+    });
+    let handle = &*dir as *const SyntheticDir as usize;
+    state.open_dirs.lock().insert(handle, dir);
+    SYNTHETIC_DIR_COUNTER.fetch_add(1, Ordering::Relaxed);
+    inception_log!("opendir '{}': synthetic ({})", path_str, handle);
+    Some(handle as *mut c_void)
+}
 
-    let path_str = match CStr::from_ptr(path).to_str() {
-        Ok(s) => s,
-        Err(_) => return real(path),
-    };
+/// Run `f` on the synthetic stream behind `dir`; `None` if it isn't one of ours
+unsafe fn with_synthetic<R>(dir: *mut c_void, f: impl FnOnce(&mut SyntheticDir) -> R) -> Option<R> {
+    // Skip the table lock entirely in the common no-synthetic-streams case
+    if SYNTHETIC_DIR_COUNTER.load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    let state = InceptionLayerState::get()?;
+    let mut dirs = state.open_dirs.lock();
+    dirs.get_mut(&(dir as usize)).map(|d| f(d))
+}
 
-    // Get inception layer state
-    let state = match InceptionLayerState::get() {
-        Some(s) => s,
-        None => return real(path),
+/// Page in the batch after the drained one. Runs with `open_dirs` held, so
+/// other synthetic streams wait out the round trip.
+fn refill(dir: &mut SyntheticDir) -> bool {
+    let Some(_guard) = InceptionLayerGuard::enter() else {
+        return false;
     };
+    let Some(state) = InceptionLayerState::get() else {
+        return false;
+    };
+    state.query_dir_page(dir.key.as_str(), &mut dir.batch)
+}
 
-    // Check if path is in VFS domain
-    if !state.inception_applicable(path_str) {
-        return real(path);
+/// Same inode stat() reports for the entry at manifest key `key`
+fn entry_ino(key: &str) -> u64 {
+    match InceptionLayerState::get() {
+        Some(state) => state.virtual_ino(key, vrift_ipc::fnv1a_hash(key)),
+        None => vrift_ipc::identity::ino(key),
     }
+}
 
-    // Query directory listing from daemon
-    if let Some(entries) = state.query_dir_listing(path_str) {
-        // Create synthetic directory
-        let mut fs_vpath = crate::state::FixedString::<1024>::new();
-        fs_vpath.set(path_str);
-        let syn_dir = Box::new(SyntheticDir {
-            vpath: fs_vpath,
-            entries,
-            position: 0,
-        });
-        let ptr = Box::into_raw(syn_dir) as *mut c_void;
-
-        // Track in open_dirs
-        let mut dirs = state.open_dirs.lock();
-        dirs.insert(
-            ptr as usize,
-            SyntheticDir {
-                vpath: crate::state::FixedString::new(),
-                entries: vec![],
-                position: 0,
+/// Fill the stream's dirent with the next entry; null at the end, or on
+/// error with errno set
+fn next_dirent(dir: &mut SyntheticDir) -> *mut Dirent {
+    loop {
+        let position = dir.position;
+        if position >= 2 && dir.batch.drained() {
+            if !dir.batch.has_more() {
+                return std::ptr::null_mut();
+            }
+            if !refill(dir) {
+                unsafe { crate::set_errno(libc::EIO) };
+                return std::ptr::null_mut();
+            }
+            if dir.batch.drained() {
+                return std::ptr::null_mut();
+            }
+        }
+        let (name, is_dir) = match position {
+            0 => (".", true),
+            1 => ("..", true),
+            // A name that isn't UTF-8 can't come from a manifest key
+            _ => match dir.batch.take() {
+                Some(child) => child,
+                None => continue,
             },
-        );
+        };
+        dir.position += 1;
 
-        return ptr;
-    }
+        // A name libc's dirent can't hold can't be listed either
+        if name.len() >= dir.dirent.d_name.len() {
+            continue;
+        }
 
-    // Fallback to real
-    real(path)
-}
+        let base = dir.key.as_str();
+        let ino = match name {
+            "." => entry_ino(base),
+            ".." => entry_ino(
+                base.rsplit_once('/')
+                    .map(|(p, _)| p)
+                    .filter(|p| !p.is_empty())
+                    .unwrap_or("/"),
+            ),
+            _ => {
+                let mut buf = [0u8; 2048];
+                let mut w = crate::macros::StackWriter::new(&mut buf);
+                use std::fmt::Write;
+                let _ = write!(w, "{}/{}", base.trim_end_matches('/'), name);
+                entry_ino(w.as_str())
+            }
+        };
 
-/// Static buffer for readdir dirent (readdir returns pointer to static data)
-#[cfg(target_os = "macos")]
-static mut DIRENT_BUF: libc::dirent = libc::dirent {
-    d_ino: 0,
-    d_seekoff: 0,
-    d_reclen: 0,
-    d_namlen: 0,
-    d_type: 0,
-    d_name: [0; 1024],
-};
+        let d = &mut dir.dirent;
+        d.d_ino = ino as _;
+        d.d_reclen = std::mem::size_of::<Dirent>() as u16;
+        d.d_type = if is_dir { libc::DT_DIR } else { libc::DT_REG };
+        #[cfg(target_os = "linux")]
+        {
+            d.d_off = position as i64 + 1;
+        }
+        #[cfg(target_os = "macos")]
+        {
+            d.d_seekoff = position as u64 + 1;
+            d.d_namlen = name.len() as u16;
+        }
+        for (dst, &src) in d.d_name.iter_mut().zip(name.as_bytes()) {
+            *dst = src as c_char;
+        }
+        d.d_name[name.len()] = 0;
+        return d as *mut Dirent;
+    }
+}
 
 #[no_mangle]
-#[cfg(target_os = "macos")]
-pub unsafe extern "C" fn readdir_inception(dir: *mut c_void) -> *mut libc::dirent {
-    // RFC-0050: Use IT_READDIR.old_func to avoid recursion (same fix as opendir_inception)
-    let real = std::mem::transmute::<
-        *const (),
-        unsafe extern "C" fn(*mut c_void) -> *mut libc::dirent,
-    >(crate::interpose::IT_READDIR.old_func);
+pub unsafe extern "C" fn opendir_inception(path: *const c_char) -> *mut c_void {
+    // Early-boot passthrough
+    passthrough_if_init!(real_opendir, path);
+
+    if path.is_null() {
+        return real_opendir(path);
+    }
+
+    // Anything on disk wins; only a missing directory can be virtual
+    let dir = real_opendir(path);
+    if !dir.is_null() {
+        return dir;
+    }
+    let errno = crate::get_errno();
+    if errno == libc::ENOENT {
+        if let Some(handle) = synthetic_opendir(path) {
+            return handle;
+        }
+    }
+    crate::set_errno(errno);
+    dir
+}
 
+#[no_mangle]
+pub unsafe extern "C" fn readdir_inception(dir: *mut c_void) -> *mut Dirent {
     // Pattern 2648/2649: Passthrough during initialization to avoid TLS hazard
-    passthrough_if_init!(real, dir);
+    passthrough_if_init!(real_readdir, dir);
 
     if dir.is_null() {
-        return real(dir);
+        return real_readdir(dir);
     }
+    with_synthetic(dir, next_dirent).unwrap_or_else(|| real_readdir(dir))
+}
 
-    // Check if this is a synthetic directory
-    let syn_dir = dir as *mut SyntheticDir;
-
-    // Try to read from synthetic dir if it's one of ours
-    if let Some(state) = InceptionLayerState::get() {
-        let dirs = state.open_dirs.lock();
-        if dirs.contains_key(&(dir as usize)) {
-            drop(dirs); // Release lock before accessing syn_dir
+/// glibc's readdir64 (what LFS builds such as coreutils call)
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn readdir64_inception(dir: *mut c_void) -> *mut libc::dirent64 {
+    let real = crate::get_real!(
+        REAL_READDIR64,
+        unsafe extern "C" fn(*mut c_void) -> *mut libc::dirent64
+    );
+    passthrough_if_init!(real, dir);
 
-            let sd = &mut *syn_dir;
-            if sd.position >= sd.entries.len() {
-                return std::ptr::null_mut();
-            }
+    if dir.is_null() {
+        return real(dir);
+    }
+    with_synthetic(dir, next_dirent).unwrap_or_else(|| real(dir))
+}
 
-            let entry = &sd.entries[sd.position];
-            sd.position += 1;
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn rewinddir_inception(dir: *mut c_void) {
+    let real = crate::get_real!(REAL_REWINDDIR, unsafe extern "C" fn(*mut c_void));
+    passthrough_if_init!(real, dir);
 
-            // Fill dirent buffer
-            DIRENT_BUF.d_ino = 1; // Synthetic inode
-            DIRENT_BUF.d_type = if entry.is_dir {
-                libc::DT_DIR
-            } else {
-                libc::DT_REG
-            };
-            DIRENT_BUF.d_namlen = entry.name.len() as u16;
+    let rewind = |d: &mut SyntheticDir| {
+        d.position = 0;
+        d.batch.rewind();
+    };
+    if with_synthetic(dir, rewind).is_none() {
+        real(dir)
+    }
+}
 
-            // Copy name to buffer
-            let name_bytes = entry.name.as_bytes();
-            let copy_len = name_bytes.len().min(1023);
-            let dirent_ptr = std::ptr::addr_of_mut!(DIRENT_BUF);
-            let d_name_ptr = std::ptr::addr_of_mut!((*dirent_ptr).d_name);
-            std::ptr::copy_nonoverlapping(name_bytes.as_ptr(), d_name_ptr as *mut u8, copy_len);
-            (*dirent_ptr).d_name[copy_len] = 0;
+/// A synthetic stream has no descriptor behind it
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn dirfd_inception(dir: *mut c_void) -> c_int {
+    let real = crate::get_real!(REAL_DIRFD, unsafe extern "C" fn(*mut c_void) -> c_int);
+    passthrough_if_init!(real, dir);
 
-            return dirent_ptr;
-        }
+    if with_synthetic(dir, |_| ()).is_some() {
+        crate::set_errno(libc::ENOTSUP);
+        return -1;
     }
-
     real(dir)
 }
 
 #[no_mangle]
-#[cfg(target_os = "macos")]
 pub unsafe extern "C" fn closedir_inception(dir: *mut c_void) -> c_int {
-    // RFC-0050: Use IT_CLOSEDIR.old_func to avoid recursion (same fix as opendir_inception)
-    let real = std::mem::transmute::<*const (), unsafe extern "C" fn(*mut c_void) -> c_int>(
-        crate::interpose::IT_CLOSEDIR.old_func,
-    );
-
     // Pattern 2648/2649: Passthrough during initialization to avoid TLS hazard
-    passthrough_if_init!(real, dir);
+    passthrough_if_init!(real_closedir, dir);
 
-    if dir.is_null() {
-        return real(dir);
+    if dir.is_null() || SYNTHETIC_DIR_COUNTER.load(Ordering::Relaxed) == 0 {
+        return real_closedir(dir);
     }
 
-    // Check if this is a synthetic directory
-    if let Some(state) = InceptionLayerState::get() {
-        let is_synthetic = {
-            let mut dirs = state.open_dirs.lock();
-            dirs.remove(&(dir as usize)).is_some()
+    // Free the synthetic directory
+    let removed =
+        InceptionLayerState::get().and_then(|s| s.open_dirs.lock().remove(&(dir as usize)));
+    if removed.is_some() {
+        SYNTHETIC_DIR_COUNTER.fetch_sub(1, Ordering::Relaxed);
+        return 0;
+    }
+    real_closedir(dir)
+}
+
+/// Size of a linux_dirent64 record for `name`: ino, off, reclen and type,
+/// the NUL-terminated name, padded to 8 bytes
+#[cfg(target_os = "linux")]
+const fn dirent64_reclen(name_len: usize) -> usize {
+    (19 + name_len + 1 + 7) & !7
+}
+
+/// Whether `name` exists in the directory behind `fd` (the kernel listed it)
+#[cfg(target_os = "linux")]
+unsafe fn on_disk_at(fd: c_int, name: &str) -> bool {
+    let mut buf = [0u8; 256];
+    if name.len() >= buf.len() {
+        return false;
+    }
+    buf[..name.len()].copy_from_slice(name.as_bytes());
+    let mut st: libc::stat = std::mem::zeroed();
+    crate::syscalls::linux_raw::raw_fstatat(
+        fd,
+        buf.as_ptr() as *const c_char,
+        &mut st,
+        libc::AT_SYMLINK_NOFOLLOW,
+    ) == 0
+}
+
+/// Pack the next manifest children of `key` into `buf`. Returns the bytes
+/// written, 0 at the end, or -1 with errno set.
+#[cfg(target_os = "linux")]
+unsafe fn fill_manifest_dirents(
+    state: &InceptionLayerState,
+    fd: c_int,
+    key: &str,
+    listing: &mut DirFdListing,
+    buf: *mut u8,
+    count: usize,
+) -> libc::ssize_t {
+    let mut written = 0usize;
+    loop {
+        if listing.batch.drained() {
+            if !listing.batch.has_more() {
+                break;
+            }
+            if !state.query_dir_page(key, &mut listing.batch) {
+                if written == 0 {
+                    crate::set_errno(libc::EIO);
+                    return -1;
+                }
+                break;
+            }
+            if listing.batch.drained() {
+                break;
+            }
+        }
+        // A name that isn't UTF-8 can't come from a manifest key
+        let Some((name, is_dir)) = listing.batch.take() else {
+            continue;
         };
+        // Longer than NAME_MAX, or already listed by the kernel
+        if name.len() > 255 || on_disk_at(fd, name) {
+            continue;
+        }
+        let reclen = dirent64_reclen(name.len());
+        if written + reclen > count {
+            listing.batch.unget();
+            if written == 0 {
+                crate::set_errno(libc::EINVAL);
+                return -1;
+            }
+            break;
+        }
+
+        // Same inode stat() reports for the entry
+        let mut path = [0u8; 2048];
+        let mut w = crate::macros::StackWriter::new(&mut path);
+        use std::fmt::Write;
+        let _ = write!(w, "{}/{}", key.trim_end_matches('/'), name);
+        let ino = state.virtual_ino(w.as_str(), vrift_ipc::fnv1a_hash(w.as_str()));
+        listing.offset += 1;
+
+        let rec = buf.add(written);
+        std::ptr::write_bytes(rec, 0, reclen);
+        std::ptr::write_unaligned(rec as *mut u64, ino);
+        std::ptr::write_unaligned(rec.add(8) as *mut i64, listing.offset);
+        std::ptr::write_unaligned(rec.add(16) as *mut u16, reclen as u16);
+        *rec.add(18) = if is_dir { libc::DT_DIR } else { libc::DT_REG };
+        std::ptr::copy_nonoverlapping(name.as_ptr(), rec.add(19), name.len());
+        written += reclen;
+    }
+    written as libc::ssize_t
+}
+
+/// getdents64 for programs that read directory fds themselves instead of
+/// going through readdir. Non-VFS fds go straight to the kernel.
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn getdents64_inception(
+    fd: c_int,
+    buf: *mut c_void,
+    count: libc::size_t,
+) -> libc::ssize_t {
+    use crate::syscalls::linux_raw::{raw_getdents64, raw_lseek};
+    passthrough_if_init!(raw_getdents64, fd, buf, count);
+
+    let Some(entry) = crate::syscalls::io::get_fd_entry(fd).filter(|e| e.is_vfs && e.is_dir) else {
+        return raw_getdents64(fd, buf, count);
+    };
+    let Some(_guard) = InceptionLayerGuard::enter() else {
+        return raw_getdents64(fd, buf, count);
+    };
+    let Some(state) = InceptionLayerState::get() else {
+        return raw_getdents64(fd, buf, count);
+    };
 
-        if is_synthetic {
-            // Free the synthetic directory
-            let _ = Box::from_raw(dir as *mut SyntheticDir);
-            return 0;
+    // Held across vDird round trips, like open_dirs in refill()
+    let mut listings = state.dir_fd_listings.lock();
+    let listing = listings.entry(fd).or_insert_with(|| {
+        DIR_FD_LISTING_COUNTER.fetch_add(1, Ordering::Relaxed);
+        Box::new(DirFdListing {
+            batch: DirentArena::new(),
+            virtual_phase: false,
+            offset: 0,
+        })
+    });
+    // lseek(fd, 0, SEEK_SET) after the kernel's entries ran out: start over
+    if listing.virtual_phase && raw_lseek(fd, 0, libc::SEEK_CUR) == 0 {
+        listing.virtual_phase = false;
+        listing.offset = 0;
+        listing.batch.rewind();
+    }
+    if !listing.virtual_phase {
+        let n = raw_getdents64(fd, buf, count);
+        if n != 0 {
+            return n;
         }
+        listing.virtual_phase = true;
+        crate::heat::record(entry.manifest_key.as_str(), crate::heat::HeatOp::Readdir);
     }
+    fill_manifest_dirents(
+        state,
+        fd,
+        entry.manifest_key.as_str(),
+        listing,
+        buf as *mut u8,
+        count,
+    )
+}
 
-    real(dir)
+/// Drop the getdents64 progress of `fd` (closed, or reopened)
+#[cfg(target_os = "linux")]
+pub(crate) fn forget_dir_fd(fd: c_int) {
+    if DIR_FD_LISTING_COUNTER.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let Some(state) = InceptionLayerState::get() else {
+        return;
+    };
+    if state.dir_fd_listings.lock().remove(&fd).is_some() {
+        DIR_FD_LISTING_COUNTER.fetch_sub(1, Ordering::Relaxed);
+    }
 }
+
 #[no_mangle]
 pub unsafe extern "C" fn getcwd_inception(
     buf: *mut libc::c_char,
@@ -5,11 +5,18 @@
 
 use crate::state::InceptionLayerGuard;
 use libc::{c_int, c_void, off_t, size_t, ssize_t};
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 /// Global counter for open FDs to monitor saturation (RFC-0051)
 pub static OPEN_FD_COUNT: AtomicUsize = AtomicUsize::new(0);
 
+/// CoW copies queued for the Worker and not yet reingested
+pub static PENDING_REINGESTS: AtomicUsize = AtomicUsize::new(0);
+static COW_EXIT_FLUSH_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// Longest the exit hook waits for queued reingests
+const EXIT_REINGEST_WAIT: std::time::Duration = std::time::Duration::from_secs(5);
+
 // RFC-0051 / Pattern 2648: Lock-Free FD tracking via Tiered Atomic Array.
 // The legacy Mutex-protected Map is replaced by REACTOR.fd_table.
 
@@ -20,11 +27,21 @@ pub struct FdEntry {
     pub manifest_key_hash: u64,
     pub temp_path: crate::state::FixedString<1024>,
     pub is_vfs: bool,
+    /// A directory: `vpath` is its absolute path, the base for
+    /// dirfd-relative (`*at`) lookups
+    pub is_dir: bool,
     pub cached_stat: Option<libc::stat>,
     pub mmap_count: usize,
     pub lock_fd: i32, // -1 if no lock FD held
 }
 
+impl FdEntry {
+    /// Opened straight onto a CAS blob: read-only, not a CoW copy
+    pub fn serves_blob(&self) -> bool {
+        self.is_vfs && self.temp_path.is_empty() && self.cached_stat.is_some()
+    }
+}
+
 // RFC-0051 / Pattern 2648: Using Mutex for FD_TABLE to avoid RwLock hazards during dyld bootstrap.
 // Mutation (track_fd) and Read (get_fd_entry) ratio is balanced, but safety is paramount.
 
@@ -44,16 +61,70 @@ pub fn track_fd(
     let mut vpath_fs = crate::state::FixedString::<1024>::new();
     vpath_fs.set(path);
 
-    let entry = Box::into_raw(Box::new(FdEntry {
-        vpath: vpath_fs,
-        manifest_key: vpath_fs, // For now assume path is the manifest key if not otherwise specified
-        manifest_key_hash,
-        temp_path: crate::state::FixedString::new(),
-        is_vfs,
-        cached_stat,
-        mmap_count: 0,
-        lock_fd: -1,
-    }));
+    set_fd_entry(
+        fd,
+        FdEntry {
+            vpath: vpath_fs,
+            manifest_key: vpath_fs, // For now assume path is the manifest key if not otherwise specified
+            manifest_key_hash,
+            temp_path: crate::state::FixedString::new(),
+            is_vfs,
+            is_dir: false,
+            cached_stat,
+            mmap_count: 0,
+            lock_fd: -1,
+        },
+    );
+}
+
+/// Track a directory FD inside the VFS so `openat(fd, "src/lib.rs")` and
+/// friends can be resolved against it
+pub(crate) fn track_dir_fd(fd: c_int, vpath: &crate::path::VfsPath) {
+    if fd < 0 {
+        return;
+    }
+    #[cfg(target_os = "linux")]
+    crate::syscalls::dir::forget_dir_fd(fd);
+    set_fd_entry(
+        fd,
+        FdEntry {
+            vpath: vpath.absolute,
+            manifest_key: vpath.manifest_key,
+            manifest_key_hash: vpath.manifest_key_hash,
+            temp_path: crate::state::FixedString::new(),
+            is_vfs: true,
+            is_dir: true,
+            cached_stat: None,
+            mmap_count: 0,
+            lock_fd: -1,
+        },
+    );
+}
+
+/// Track an FD serving a file's content straight from the CAS (a blob, or a
+/// reassembled copy of one); `cached_stat` is what fstat reports for it
+pub(crate) fn track_blob_fd(fd: c_int, vpath: &crate::path::VfsPath, cached_stat: libc::stat) {
+    if fd < 0 {
+        return;
+    }
+    set_fd_entry(
+        fd,
+        FdEntry {
+            vpath: vpath.absolute,
+            manifest_key: vpath.manifest_key,
+            manifest_key_hash: vpath.manifest_key_hash,
+            temp_path: crate::state::FixedString::new(),
+            is_vfs: true,
+            is_dir: false,
+            cached_stat: Some(cached_stat),
+            mmap_count: 0,
+            lock_fd: -1,
+        },
+    );
+}
+
+fn set_fd_entry(fd: c_int, entry: FdEntry) {
+    let entry = Box::into_raw(Box::new(entry));
 
     if let Some(state) = crate::state::InceptionLayerState::get() {
         let old = state.open_fds.set(fd as u32, entry);
@@ -75,6 +146,155 @@ pub fn track_fd(
     }
 }
 
+/// Track `newfd` as a duplicate of `oldfd`. A CoW fd keeps writing the same
+/// copy, and its session gains a reference so reingest waits for both.
+fn track_dup(oldfd: c_int, newfd: c_int) {
+    let Some(mut entry) = get_fd_entry(oldfd) else {
+        return;
+    };
+    if !entry.temp_path.is_empty() {
+        if let Some(state) = crate::state::InceptionLayerState::get() {
+            if let Some(session) = state.cow_sessions.lock().get_mut(&entry.manifest_key_hash) {
+                session.refs += 1;
+            }
+        }
+    }
+    // The flock fd stays with the original entry; reclaiming closes it
+    entry.lock_fd = -1;
+    set_fd_entry(newfd, entry);
+}
+
+/// Drop one CoW session reference held by `entry`. After the last one the
+/// session is gone and its copy is queued for reingest.
+fn release_cow(state: &crate::state::InceptionLayerState, entry: &FdEntry) {
+    if entry.temp_path.is_empty() {
+        return;
+    }
+    let finished = {
+        let mut sessions = state.cow_sessions.lock();
+        match sessions.get_mut(&entry.manifest_key_hash) {
+            Some(session) if session.refs > 1 => {
+                session.refs -= 1;
+                None
+            }
+            Some(_) => sessions.remove(&entry.manifest_key_hash),
+            None => None,
+        }
+    };
+    let Some(session) = finished else {
+        return;
+    };
+
+    inception_log!(
+        "COW REINGEST: vpath='{}' temp='{}'",
+        entry.vpath,
+        session.temp_path
+    );
+    // Offload reingest to Worker (non-blocking); reingest here if it can't
+    // take the task, since the copy would otherwise never reach the manifest
+    PENDING_REINGESTS.fetch_add(1, Ordering::AcqRel);
+    let task = crate::sync::Task::Reingest {
+        vpath: entry.vpath.to_string(),
+        temp_path: session.temp_path.to_string(),
+        base_hash: Box::new(session.base_hash),
+    };
+    let rejected = match crate::sync::get_reactor() {
+        Some(reactor) => reactor.ring_buffer.push(task).err(),
+        None => Some(task),
+    };
+    if let Some(crate::sync::Task::Reingest {
+        vpath,
+        temp_path,
+        base_hash,
+    }) = rejected
+    {
+        reingest_cow(state, &vpath, &temp_path, &base_hash);
+        PENDING_REINGESTS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Fold a finished CoW copy back into the manifest through vDird
+pub(crate) fn reingest_cow(
+    state: &crate::state::InceptionLayerState,
+    vpath: &str,
+    temp_path: &str,
+    base_hash: &[u8; 32],
+) {
+    let Some(resolved) = state.resolve_path(vpath) else {
+        return;
+    };
+    // Someone else reingested this path after our copy was taken: ours
+    // still wins, but the user should know
+    let current = unsafe {
+        crate::ipc::sync_ipc_manifest_lookup(
+            &state.vdird_socket_path,
+            resolved.manifest_key.as_str(),
+            crate::budget::BudgetClass::Mutate,
+        )
+    };
+    if current
+        .ok()
+        .flatten()
+        .is_some_and(|e| e.content_hash != *base_hash)
+    {
+        inception_warn!(
+            "COW CONFLICT: '{}' changed since it was opened for write",
+            vpath
+        );
+        crate::warnings::warn_user(crate::warnings::Warning::CowConflict, vpath);
+    }
+
+    // vDird keys entries by manifest key, not by where the project sits
+    match unsafe {
+        crate::ipc::sync_ipc_manifest_reingest(
+            &state.vdird_socket_path,
+            resolved.manifest_key.as_str(),
+            temp_path,
+        )
+    } {
+        // M4: Clear dirty status ONLY after the daemon confirms reingest.
+        // Marked by manifest key in open_impl, so cleared by it too.
+        Ok(()) => {
+            crate::state::DIRTY_TRACKER.clear_dirty(&resolved.manifest_key);
+            crate::summary::add(&crate::summary::FILES_REINGESTED, 1);
+        }
+        Err(errno) => inception_warn!(
+            "COW REINGEST FAILED: '{}' (errno {}), copy left at '{}'",
+            vpath,
+            errno,
+            temp_path
+        ),
+    }
+}
+
+/// Make sure CoW copies reach the manifest even if the process exits first:
+/// build tools rarely live long enough for the Worker to catch up.
+pub(crate) fn register_cow_exit_flush() {
+    crate::state::register_atexit_once(&COW_EXIT_FLUSH_REGISTERED, flush_cow_at_exit);
+}
+
+/// Reingest copies whose fds are still open (the kernel closes them after
+/// us), then give the Worker a bounded time to finish queued reingests.
+extern "C" fn flush_cow_at_exit() {
+    let Some(state) = crate::state::InceptionLayerState::get_no_spawn() else {
+        return;
+    };
+    let open: Vec<_> = state.cow_sessions.lock().drain().collect();
+    for (_, session) in open {
+        reingest_cow(
+            state,
+            &session.vpath,
+            &session.temp_path,
+            &session.base_hash,
+        );
+    }
+
+    let deadline = std::time::Instant::now() + EXIT_REINGEST_WAIT;
+    while PENDING_REINGESTS.load(Ordering::Acquire) > 0 && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+}
+
 /// Stop tracking an FD
 #[inline(always)]
 pub fn untrack_fd(fd: c_int) {
@@ -156,15 +376,7 @@ pub unsafe extern "C" fn dup_inception(oldfd: c_int) -> c_int {
 
     if newfd >= 0 {
         // Copy tracking from oldfd to newfd
-        if let Some(entry) = get_fd_entry(oldfd) {
-            track_fd(
-                newfd,
-                entry.vpath.as_str(),
-                entry.is_vfs,
-                entry.cached_stat,
-                entry.manifest_key_hash,
-            );
-        }
+        track_dup(oldfd, newfd);
     }
     newfd
 }
@@ -196,28 +408,127 @@ pub unsafe extern "C" fn dup2_inception(oldfd: c_int, newfd: c_int) -> c_int {
     };
 
     // If newfd was tracked, untrack it (it's being replaced)
-    untrack_fd(newfd);
+    if oldfd != newfd {
+        if let (Some(state), Some(replaced)) = (
+            crate::state::InceptionLayerState::get(),
+            get_fd_entry(newfd),
+        ) {
+            release_cow(state, &replaced);
+        }
+        untrack_fd(newfd);
+    }
 
     #[cfg(target_os = "macos")]
     let result = crate::syscalls::macos_raw::raw_dup2(oldfd, newfd);
     #[cfg(target_os = "linux")]
     let result = crate::syscalls::linux_raw::raw_dup2(oldfd, newfd);
 
-    if result >= 0 {
+    if result >= 0 && oldfd != newfd {
         // Copy tracking from oldfd to newfd
-        if let Some(entry) = get_fd_entry(oldfd) {
-            track_fd(
-                result,
-                entry.vpath.as_str(),
-                entry.is_vfs,
-                entry.cached_stat,
-                entry.manifest_key_hash,
-            );
+        track_dup(oldfd, result);
+    }
+    result
+}
+
+/// dup3 (Linux only): dup2 plus O_CLOEXEC, same tracking
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn dup3_inception(oldfd: c_int, newfd: c_int, flags: c_int) -> c_int {
+    use crate::syscalls::linux_raw::raw_dup3;
+
+    let init_state = crate::state::INITIALIZING.load(std::sync::atomic::Ordering::Relaxed);
+    if init_state != 0
+        || crate::state::INCEPTION_LAYER_STATE
+            .load(std::sync::atomic::Ordering::Acquire)
+            .is_null()
+    {
+        return raw_dup3(oldfd, newfd, flags);
+    }
+    let _guard = match InceptionLayerGuard::enter() {
+        Some(g) => g,
+        None => return raw_dup3(oldfd, newfd, flags),
+    };
+
+    // oldfd == newfd is EINVAL for dup3: leave the tracking alone
+    if oldfd != newfd {
+        if let (Some(state), Some(replaced)) = (
+            crate::state::InceptionLayerState::get(),
+            get_fd_entry(newfd),
+        ) {
+            release_cow(state, &replaced);
         }
+        untrack_fd(newfd);
+    }
+
+    let result = raw_dup3(oldfd, newfd, flags);
+    if result >= 0 {
+        track_dup(oldfd, result);
     }
     result
 }
 
+// ============================================================================
+// fcntl inception layer - F_DUPFD copies FD tracking like dup
+// ============================================================================
+
+/// fcntl with the third argument passed as a long (the variadic bridges
+/// widen it). F_DUPFD/F_DUPFD_CLOEXEC copy tracking to the new fd; on macOS
+/// F_GETPATH on a redirected fd reports the virtual path, not the CAS blob
+/// or staging copy behind it.
+pub unsafe fn fcntl_inception(fd: c_int, cmd: c_int, arg: libc::c_long) -> c_int {
+    #[cfg(target_os = "linux")]
+    use crate::syscalls::linux_raw::raw_fcntl;
+    #[cfg(target_os = "macos")]
+    use crate::syscalls::macos_raw::raw_fcntl;
+
+    let init_state = crate::state::INITIALIZING.load(std::sync::atomic::Ordering::Relaxed);
+    if init_state != 0
+        || crate::state::INCEPTION_LAYER_STATE
+            .load(std::sync::atomic::Ordering::Acquire)
+            .is_null()
+    {
+        return raw_fcntl(fd, cmd, arg);
+    }
+
+    let _guard = match InceptionLayerGuard::enter() {
+        Some(g) => g,
+        None => return raw_fcntl(fd, cmd, arg),
+    };
+
+    match cmd {
+        libc::F_DUPFD | libc::F_DUPFD_CLOEXEC => {
+            let newfd = raw_fcntl(fd, cmd, arg);
+            if newfd >= 0 {
+                track_dup(fd, newfd);
+            }
+            newfd
+        }
+        #[cfg(target_os = "macos")]
+        libc::F_GETPATH => match get_fd_entry(fd) {
+            // Blob, staging-copy and directory fds keep the absolute vpath;
+            // anything else is the real file and the kernel answer is right
+            Some(entry)
+                if entry.is_vfs
+                    && (entry.is_dir
+                        || entry.cached_stat.is_some()
+                        || !entry.temp_path.is_empty()) =>
+            {
+                let vpath = entry.vpath.as_str().as_bytes();
+                if vpath.len() >= libc::PATH_MAX as usize {
+                    crate::set_errno(libc::ENAMETOOLONG);
+                    return -1;
+                }
+                let buf = arg as *mut u8;
+                std::ptr::copy_nonoverlapping(vpath.as_ptr(), buf, vpath.len());
+                *buf.add(vpath.len()) = 0;
+                0
+            }
+            _ => raw_fcntl(fd, cmd, arg),
+        },
+        _ => raw_fcntl(fd, cmd, arg),
+    }
+}
+
 // ============================================================================
 // fchdir inception layer - update virtual CWD from FD
 // ============================================================================
@@ -265,9 +576,111 @@ pub unsafe extern "C" fn lseek_inception(fd: c_int, offset: off_t, whence: c_int
 pub unsafe extern "C" fn ftruncate_inception(fd: c_int, length: off_t) -> c_int {
     // Pattern 2930: Use raw syscall to avoid post-init dlsym hazard
     #[cfg(target_os = "macos")]
-    return crate::syscalls::macos_raw::raw_ftruncate(fd, length);
+    let real = |fd, length| crate::syscalls::macos_raw::raw_ftruncate(fd, length);
     #[cfg(target_os = "linux")]
-    return crate::syscalls::linux_raw::raw_ftruncate(fd, length);
+    let real = |fd, length| crate::syscalls::linux_raw::raw_ftruncate(fd, length);
+    passthrough_if_init!(real, fd, length);
+
+    let Some(_guard) = InceptionLayerGuard::enter() else {
+        return real(fd, length);
+    };
+    // An fd still on the CAS moves to a staging copy first: the blob is
+    // read-only, and so is the cached expanded copy of a chunked one
+    if let (Some(state), Some(entry)) = (
+        crate::state::InceptionLayerState::get(),
+        get_fd_entry(fd).filter(FdEntry::serves_blob),
+    ) {
+        if let Err(errno) = cow_blob_fd(state, fd, &entry) {
+            crate::set_errno(errno);
+            return -1;
+        }
+    }
+    real(fd, length)
+}
+
+/// Move the CAS-served `fd` onto the staging copy of its file, the one a
+/// writable open would have got. The offset and close-on-exec flag carry
+/// over, and the copy is reingested after the last close like any other.
+unsafe fn cow_blob_fd(
+    state: &crate::state::InceptionLayerState,
+    fd: c_int,
+    entry: &FdEntry,
+) -> Result<(), c_int> {
+    #[cfg(target_os = "linux")]
+    use crate::syscalls::linux_raw as raw;
+    #[cfg(target_os = "macos")]
+    use crate::syscalls::macos_raw as raw;
+
+    let vpath = state
+        .resolve_path(entry.vpath.as_str())
+        .ok_or(libc::EPERM)?;
+    let vnode = match state.lookup_manifest(&vpath, crate::budget::BudgetClass::Mutate) {
+        Ok(Some(e)) if !e.is_whiteout() => e,
+        // Removed since it was opened: nothing to copy, and the blob stays untouched
+        Ok(_) => return Err(libc::EPERM),
+        Err(errno) => return Err(errno),
+    };
+    crate::state::DIRTY_TRACKER.mark_dirty(&vpath.manifest_key);
+
+    let (mut sessions, temp_path) =
+        crate::syscalls::open::join_cow_session(state, &vpath, &vnode).ok_or(libc::EIO)?;
+    let temp_cpath = std::ffi::CString::new(temp_path.as_str()).map_err(|_| libc::EINVAL)?;
+    let cloexec = raw::raw_fcntl(fd, libc::F_GETFD, 0) & libc::FD_CLOEXEC != 0;
+    let copy_fd = libc::open(temp_cpath.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC);
+    if copy_fd < 0 {
+        let errno = crate::get_errno();
+        if sessions
+            .get(&vpath.manifest_key_hash)
+            .is_some_and(|s| s.refs == 0)
+        {
+            sessions.remove(&vpath.manifest_key_hash);
+            libc::unlink(temp_cpath.as_ptr());
+        }
+        return Err(errno);
+    }
+    let offset = raw::raw_lseek(fd, 0, libc::SEEK_CUR);
+    let res = raw::raw_dup2(copy_fd, fd);
+    raw::raw_close(copy_fd);
+    if res < 0 {
+        return Err(crate::get_errno());
+    }
+    if cloexec {
+        raw::raw_fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC as i64);
+    }
+    if offset > 0 {
+        raw::raw_lseek(fd, offset, libc::SEEK_SET);
+    }
+    if let Some(session) = sessions.get_mut(&vpath.manifest_key_hash) {
+        session.refs += 1;
+    }
+    drop(sessions);
+
+    inception_log!(
+        "COW on ftruncate: fd={} '{}' -> '{}'",
+        fd,
+        vpath.absolute,
+        temp_path
+    );
+    // The flock fd moves to the new entry; reclaiming the old one would close it
+    let old = state.open_fds.get(fd as u32);
+    if !old.is_null() {
+        (*old).lock_fd = -1;
+    }
+    set_fd_entry(
+        fd,
+        FdEntry {
+            vpath: vpath.absolute,
+            manifest_key: vpath.manifest_key,
+            manifest_key_hash: vpath.manifest_key_hash,
+            temp_path,
+            is_vfs: true,
+            is_dir: false,
+            cached_stat: None,
+            mmap_count: entry.mmap_count,
+            lock_fd: entry.lock_fd,
+        },
+    );
+    Ok(())
 }
 
 // ============================================================================
@@ -302,6 +715,8 @@ pub unsafe extern "C" fn close_inception(fd: c_int) -> c_int {
         return crate::syscalls::linux_raw::raw_close(fd);
     }
 
+    let _span = crate::profile::begin(crate::profile::ProfileOp::Close);
+
     let _guard = match InceptionLayerGuard::enter() {
         Some(g) => g,
         None => {
@@ -330,6 +745,9 @@ pub unsafe extern "C" fn close_inception(fd: c_int) -> c_int {
     );
     state.check_fd_usage();
 
+    #[cfg(target_os = "linux")]
+    crate::syscalls::dir::forget_dir_fd(fd);
+
     // Check if this FD is a COW session
     let cow_info = {
         let entry_ptr = state.open_fds.remove(fd as u32);
@@ -350,7 +768,7 @@ pub unsafe extern "C" fn close_inception(fd: c_int) -> c_int {
     #[cfg(target_os = "linux")]
     let res = crate::syscalls::linux_raw::raw_close(fd);
 
-    // Offload IPC task to Worker (asynchronous)
+    // The last close of a CoW copy hands it to the Worker for reingest
     if let Some(info) = cow_info {
         inception_log!(
             "COW CLOSE: fd={} vpath='{}' temp='{}'",
@@ -358,24 +776,9 @@ pub unsafe extern "C" fn close_inception(fd: c_int) -> c_int {
             info.vpath,
             info.temp_path
         );
-
-        // Offload reingest to Worker (non-blocking)
-        if let Some(reactor) = crate::sync::get_reactor() {
-            let _ = reactor.ring_buffer.push(crate::sync::Task::Reingest {
-                vpath: info.vpath.to_string(),
-                temp_path: info.temp_path.to_string(),
-            });
-        }
-
-        res
-    } else {
-        // Not a COW file, but might be a VFS read-only file or non-VFS file
-        untrack_fd(fd);
-        #[cfg(target_os = "macos")]
-        return crate::syscalls::macos_raw::raw_close(fd);
-        #[cfg(target_os = "linux")]
-        return crate::syscalls::linux_raw::raw_close(fd);
+        release_cow(state, &info);
     }
+    res
 }
 
 // ============================================================================
@@ -399,6 +802,11 @@ pub unsafe extern "C" fn sendfile_inception(
     crate::syscalls::macos_raw::raw_sendfile(fd, s, offset, len, hdtr, flags)
 }
 
+// Linux: a CAS-served source is streamed from its blob in userspace (the
+// CAS may sit on another filesystem, where the kernel copy fails with
+// EXDEV); a CAS-served destination is read-only. Other destinations are
+// plain files or CoW copies, which write() reaches as well.
+
 #[cfg(target_os = "linux")]
 #[no_mangle]
 pub unsafe extern "C" fn sendfile_inception(
@@ -407,8 +815,15 @@ pub unsafe extern "C" fn sendfile_inception(
     offset: *mut libc::off_t,
     count: libc::size_t,
 ) -> libc::ssize_t {
-    if crate::syscalls::misc::quick_block_vfs_fd_mutation(out_fd).is_some() {
-        return -1;
+    passthrough_if_init!(
+        crate::syscalls::linux_raw::raw_sendfile,
+        out_fd,
+        in_fd,
+        offset,
+        count
+    );
+    if let Some(res) = copy_vfs_fds(in_fd, offset, out_fd, std::ptr::null_mut(), count) {
+        return res;
     }
     crate::syscalls::linux_raw::raw_sendfile(out_fd, in_fd, offset, count)
 }
@@ -423,8 +838,111 @@ pub unsafe extern "C" fn copy_file_range_inception(
     len: libc::size_t,
     flags: libc::c_uint,
 ) -> libc::ssize_t {
-    if crate::syscalls::misc::quick_block_vfs_fd_mutation(fd_out).is_some() {
+    passthrough_if_init!(
+        crate::syscalls::linux_raw::raw_copy_file_range,
+        fd_in,
+        off_in,
+        fd_out,
+        off_out,
+        len,
+        flags
+    );
+    if flags != 0 && get_fd_entry(fd_in).is_some_and(|e| e.serves_blob()) {
+        crate::set_errno(libc::EINVAL);
         return -1;
     }
+    if let Some(res) = copy_vfs_fds(fd_in, off_in, fd_out, off_out, len) {
+        return res;
+    }
     crate::syscalls::linux_raw::raw_copy_file_range(fd_in, off_in, fd_out, off_out, len, flags)
 }
+
+/// Bytes per read/write round of `copy_from_blob`
+#[cfg(target_os = "linux")]
+const BLOB_COPY_CHUNK: usize = 128 * 1024;
+
+/// sendfile/copy_file_range between fds the layer tracks. None when neither
+/// side is a CAS-served fd: the kernel does the copy.
+#[cfg(target_os = "linux")]
+unsafe fn copy_vfs_fds(
+    fd_in: c_int,
+    off_in: *mut off_t,
+    fd_out: c_int,
+    off_out: *mut off_t,
+    len: size_t,
+) -> Option<ssize_t> {
+    let _guard = InceptionLayerGuard::enter()?;
+    if get_fd_entry(fd_out).is_some_and(|e| e.serves_blob()) {
+        crate::set_errno(libc::EBADF);
+        return Some(-1);
+    }
+    if !get_fd_entry(fd_in).is_some_and(|e| e.serves_blob()) {
+        return None;
+    }
+    Some(copy_from_blob(fd_in, off_in, fd_out, off_out, len))
+}
+
+/// A read/write loop with the offset rules of copy_file_range: a non-null
+/// offset is used and advanced in place of the fd's own. Returns the bytes
+/// written, or -1 if nothing was.
+#[cfg(target_os = "linux")]
+unsafe fn copy_from_blob(
+    fd_in: c_int,
+    off_in: *mut off_t,
+    fd_out: c_int,
+    off_out: *mut off_t,
+    len: size_t,
+) -> ssize_t {
+    use crate::syscalls::linux_raw as raw;
+
+    let mut buf = vec![0u8; len.clamp(1, BLOB_COPY_CHUNK)];
+    let mut done = 0usize;
+    while done < len {
+        let want = (len - done).min(buf.len());
+        let n = if off_in.is_null() {
+            raw::raw_read(fd_in, buf.as_mut_ptr().cast(), want)
+        } else {
+            raw::raw_pread(fd_in, buf.as_mut_ptr().cast(), want, *off_in)
+        };
+        if n <= 0 {
+            return if n < 0 && done == 0 {
+                -1
+            } else {
+                done as ssize_t
+            };
+        }
+        let n = n as usize;
+
+        let mut written = 0usize;
+        let mut failed = false;
+        while written < n {
+            let chunk = buf[written..n].as_ptr().cast();
+            let w = if off_out.is_null() {
+                raw::raw_write(fd_out, chunk, n - written)
+            } else {
+                raw::raw_pwrite(fd_out, chunk, n - written, *off_out)
+            };
+            if w <= 0 {
+                failed = true;
+                break;
+            }
+            written += w as usize;
+            if !off_out.is_null() {
+                *off_out += w as off_t;
+            }
+        }
+        // Only what reached fd_out counts as consumed
+        if off_in.is_null() {
+            if written < n {
+                raw::raw_lseek(fd_in, written as off_t - n as off_t, libc::SEEK_CUR);
+            }
+        } else {
+            *off_in += written as off_t;
+        }
+        done += written;
+        if failed {
+            return if done == 0 { -1 } else { done as ssize_t };
+        }
+    }
+    done as ssize_t
+}
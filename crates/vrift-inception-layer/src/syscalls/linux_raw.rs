@@ -320,6 +320,96 @@ pub unsafe fn raw_write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t
     }
 }
 
+/// Raw read64 syscall (positioned read, the fd offset is left alone)
+#[inline(always)]
+pub unsafe fn raw_pread(fd: c_int, buf: *mut c_void, count: size_t, offset: off_t) -> ssize_t {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "syscall",
+            in("rax") 17i64, // SYS_pread64
+            in("rdi") fd as i64,
+            in("rsi") buf,
+            in("rdx") count as i64,
+            in("r10") offset,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as ssize_t
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "svc #0",
+            in("x8") 67i64, // SYS_pread64
+            in("x0") fd as i64,
+            in("x1") buf,
+            in("x2") count as i64,
+            in("x3") offset,
+            lateout("x0") ret,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as ssize_t
+        }
+    }
+}
+
+/// Raw write64 syscall (positioned write, the fd offset is left alone)
+#[inline(always)]
+pub unsafe fn raw_pwrite(fd: c_int, buf: *const c_void, count: size_t, offset: off_t) -> ssize_t {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "syscall",
+            in("rax") 18i64, // SYS_pwrite64
+            in("rdi") fd as i64,
+            in("rsi") buf,
+            in("rdx") count as i64,
+            in("r10") offset,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as ssize_t
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "svc #0",
+            in("x8") 68i64, // SYS_pwrite64
+            in("x0") fd as i64,
+            in("x1") buf,
+            in("x2") count as i64,
+            in("x3") offset,
+            lateout("x0") ret,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as ssize_t
+        }
+    }
+}
+
 // =============================================================================
 // Directory Operations
 // =============================================================================
@@ -696,6 +786,59 @@ pub unsafe fn raw_renameat(
     }
 }
 
+/// Raw renameat2 syscall (renameat plus RENAME_* flags)
+#[inline(always)]
+pub unsafe fn raw_renameat2(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    flags: libc::c_uint,
+) -> c_int {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "syscall",
+            in("rax") 316i64, // SYS_renameat2
+            in("rdi") olddirfd as i64,
+            in("rsi") old,
+            in("rdx") newdirfd as i64,
+            in("r10") new,
+            in("r8") flags as i64,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as c_int
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "svc #0",
+            in("x8") 276i64, // SYS_renameat2
+            in("x0") olddirfd as i64,
+            in("x1") old,
+            in("x2") newdirfd as i64,
+            in("x3") new,
+            in("x4") flags as i64,
+            lateout("x0") ret,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as c_int
+        }
+    }
+}
+
 /// Raw symlink syscall
 #[inline(always)]
 pub unsafe fn raw_symlink(old: *const c_char, new: *const c_char) -> c_int {
@@ -932,6 +1075,29 @@ pub unsafe fn raw_fchmod(fd: c_int, mode: mode_t) -> c_int {
     }
 }
 
+/// Raw umask syscall (cannot fail; returns the previous mask)
+#[inline(always)]
+pub unsafe fn raw_umask(mask: mode_t) -> mode_t {
+    let ret: i64;
+    #[cfg(target_arch = "x86_64")]
+    std::arch::asm!(
+        "syscall",
+        in("rax") 95i64, // SYS_umask
+        in("rdi") mask as i64,
+        lateout("rax") ret,
+        lateout("rcx") _,
+        lateout("r11") _,
+    );
+    #[cfg(target_arch = "aarch64")]
+    std::arch::asm!(
+        "svc #0",
+        in("x8") 166i64, // SYS_umask
+        in("x0") mask as i64,
+        lateout("x0") ret,
+    );
+    ret as mode_t
+}
+
 /// Raw utimensat syscall (for touch interception)
 #[inline(always)]
 pub unsafe fn raw_utimensat(
@@ -1144,6 +1310,49 @@ pub unsafe fn raw_dup2(oldfd: c_int, newfd: c_int) -> c_int {
     }
 }
 
+/// Raw dup3 syscall
+#[inline(always)]
+pub unsafe fn raw_dup3(oldfd: c_int, newfd: c_int, flags: c_int) -> c_int {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "syscall",
+            in("rax") 292i64, // SYS_dup3
+            in("rdi") oldfd as i64,
+            in("rsi") newfd as i64,
+            in("rdx") flags as i64,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as c_int
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "svc #0",
+            in("x8") 24i64, // SYS_dup3
+            in("x0") oldfd as i64,
+            in("x1") newfd as i64,
+            in("x2") flags as i64,
+            lateout("x0") ret,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as c_int
+        }
+    }
+}
+
 /// Raw lseek syscall
 #[inline(always)]
 pub unsafe fn raw_lseek(fd: c_int, offset: off_t, whence: c_int) -> off_t {
@@ -1187,6 +1396,49 @@ pub unsafe fn raw_lseek(fd: c_int, offset: off_t, whence: c_int) -> off_t {
     }
 }
 
+/// Raw fcntl syscall. The shim exports `fcntl`, so libc's would come back here
+#[inline(always)]
+pub unsafe fn raw_fcntl(fd: c_int, cmd: c_int, arg: i64) -> c_int {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "syscall",
+            in("rax") 72i64, // SYS_fcntl
+            in("rdi") fd as i64,
+            in("rsi") cmd as i64,
+            in("rdx") arg,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as c_int
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "svc #0",
+            in("x8") 25i64, // SYS_fcntl
+            in("x0") fd as i64,
+            in("x1") cmd as i64,
+            in("x2") arg,
+            lateout("x0") ret,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as c_int
+        }
+    }
+}
+
 /// Raw ftruncate syscall
 #[inline(always)]
 pub unsafe fn raw_ftruncate(fd: c_int, length: off_t) -> c_int {
@@ -1361,6 +1613,175 @@ pub unsafe fn raw_rename(old: *const c_char, new: *const c_char) -> c_int {
     }
 }
 
+// =============================================================================
+// Extended Attribute Operations
+// =============================================================================
+
+/// Syscall numbers (x86_64, aarch64) of the xattr calls, in the kernel's
+/// order: set, lset, fset, get, lget, fget, list, llist, flist, remove,
+/// lremove, fremove
+const XATTR_NR: [(i64, i64); 12] = [
+    (188, 5),
+    (189, 6),
+    (190, 7),
+    (191, 8),
+    (192, 9),
+    (193, 10),
+    (194, 11),
+    (195, 12),
+    (196, 13),
+    (197, 14),
+    (198, 15),
+    (199, 16),
+];
+
+/// Issue xattr call `XATTR_NR[which]`; the calls take at most five
+/// arguments
+#[inline(always)]
+unsafe fn raw_xattr_call(which: usize, args: [i64; 5]) -> ssize_t {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "syscall",
+            in("rax") XATTR_NR[which].0,
+            in("rdi") args[0],
+            in("rsi") args[1],
+            in("rdx") args[2],
+            in("r10") args[3],
+            in("r8") args[4],
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as ssize_t
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "svc #0",
+            in("x8") XATTR_NR[which].1,
+            in("x0") args[0],
+            in("x1") args[1],
+            in("x2") args[2],
+            in("x3") args[3],
+            in("x4") args[4],
+            lateout("x0") ret,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as ssize_t
+        }
+    }
+}
+
+/// Raw setxattr (`nofollow`: lsetxattr)
+#[inline(always)]
+pub unsafe fn raw_setxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const c_void,
+    size: size_t,
+    flags: c_int,
+    nofollow: bool,
+) -> c_int {
+    let args = [
+        path as i64,
+        name as i64,
+        value as i64,
+        size as i64,
+        flags as i64,
+    ];
+    raw_xattr_call(nofollow as usize, args) as c_int
+}
+
+/// Raw fsetxattr
+#[inline(always)]
+pub unsafe fn raw_fsetxattr(
+    fd: c_int,
+    name: *const c_char,
+    value: *const c_void,
+    size: size_t,
+    flags: c_int,
+) -> c_int {
+    raw_xattr_call(
+        2,
+        [
+            fd as i64,
+            name as i64,
+            value as i64,
+            size as i64,
+            flags as i64,
+        ],
+    ) as c_int
+}
+
+/// Raw getxattr (`nofollow`: lgetxattr)
+#[inline(always)]
+pub unsafe fn raw_getxattr(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: size_t,
+    nofollow: bool,
+) -> ssize_t {
+    raw_xattr_call(
+        3 + nofollow as usize,
+        [path as i64, name as i64, value as i64, size as i64, 0],
+    )
+}
+
+/// Raw fgetxattr
+#[inline(always)]
+pub unsafe fn raw_fgetxattr(
+    fd: c_int,
+    name: *const c_char,
+    value: *mut c_void,
+    size: size_t,
+) -> ssize_t {
+    raw_xattr_call(5, [fd as i64, name as i64, value as i64, size as i64, 0])
+}
+
+/// Raw listxattr (`nofollow`: llistxattr)
+#[inline(always)]
+pub unsafe fn raw_listxattr(
+    path: *const c_char,
+    list: *mut c_char,
+    size: size_t,
+    nofollow: bool,
+) -> ssize_t {
+    raw_xattr_call(
+        6 + nofollow as usize,
+        [path as i64, list as i64, size as i64, 0, 0],
+    )
+}
+
+/// Raw flistxattr
+#[inline(always)]
+pub unsafe fn raw_flistxattr(fd: c_int, list: *mut c_char, size: size_t) -> ssize_t {
+    raw_xattr_call(8, [fd as i64, list as i64, size as i64, 0, 0])
+}
+
+/// Raw removexattr (`nofollow`: lremovexattr)
+#[inline(always)]
+pub unsafe fn raw_removexattr(path: *const c_char, name: *const c_char, nofollow: bool) -> c_int {
+    raw_xattr_call(9 + nofollow as usize, [path as i64, name as i64, 0, 0, 0]) as c_int
+}
+
+/// Raw fremovexattr
+#[inline(always)]
+pub unsafe fn raw_fremovexattr(fd: c_int, name: *const c_char) -> c_int {
+    raw_xattr_call(11, [fd as i64, name as i64, 0, 0, 0]) as c_int
+}
+
 // =============================================================================
 // Memory Mapping Operations (for BUG-007 cross-platform support)
 // =============================================================================
@@ -2104,3 +2525,87 @@ pub unsafe fn raw_flock(fd: c_int, operation: c_int) -> c_int {
         }
     }
 }
+
+/// Raw io_uring_setup syscall (same number on x86_64 and aarch64)
+#[inline(always)]
+pub unsafe fn raw_io_uring_setup(entries: u32, params: *mut c_void) -> c_int {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "syscall",
+            in("rax") 425i64, // SYS_io_uring_setup
+            in("rdi") entries as i64,
+            in("rsi") params,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as c_int
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "svc #0",
+            in("x8") 425i64, // SYS_io_uring_setup
+            in("x0") entries as i64,
+            in("x1") params,
+            lateout("x0") ret,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as c_int
+        }
+    }
+}
+
+/// Raw getdents64 syscall
+#[inline(always)]
+pub unsafe fn raw_getdents64(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "syscall",
+            in("rax") 217i64, // SYS_getdents64
+            in("rdi") fd as i64,
+            in("rsi") buf,
+            in("rdx") count as i64,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as ssize_t
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ret: i64;
+        std::arch::asm!(
+            "svc #0",
+            in("x8") 61i64, // SYS_getdents64
+            in("x0") fd as i64,
+            in("x1") buf,
+            in("x2") count as i64,
+            lateout("x0") ret,
+        );
+        if ret < 0 {
+            set_errno_from_ret(ret);
+            -1
+        } else {
+            ret as ssize_t
+        }
+    }
+}
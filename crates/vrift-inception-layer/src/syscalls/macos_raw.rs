@@ -1054,10 +1054,18 @@ const SYS_CHFLAGS: i64 = 34;
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
 const SYS_SETXATTR: i64 = 236;
 
+/// SYS_getxattr = 234 on macOS
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+const SYS_GETXATTR: i64 = 234;
+
 /// SYS_removexattr = 238 on macOS
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
 const SYS_REMOVEXATTR: i64 = 238;
 
+/// SYS_listxattr = 240 on macOS
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+const SYS_LISTXATTR: i64 = 240;
+
 const SYS_UTIMES: i64 = 138;
 const SYS_GETATTRLIST: i64 = 220;
 const SYS_SETATTRLIST: i64 = 221;
@@ -1161,6 +1169,72 @@ pub unsafe fn raw_setxattr(
     ret as libc::c_int
 }
 
+/// Raw getxattr syscall for macOS ARM64.
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+#[inline(never)]
+pub unsafe fn raw_getxattr(
+    path: *const libc::c_char,
+    name: *const libc::c_char,
+    value: *mut libc::c_void,
+    size: libc::size_t,
+    position: u32,
+    options: libc::c_int,
+) -> libc::ssize_t {
+    let ret: i64;
+    let err: i64;
+    asm!(
+        "mov x16, {syscall}",
+        "svc #0x80",
+        "cset {err}, cs",
+        syscall = in(reg) SYS_GETXATTR,
+        in("x0") path as i64,
+        in("x1") name as i64,
+        in("x2") value as i64,
+        in("x3") size as i64,
+        in("x4") position as i64,
+        in("x5") options as i64,
+        lateout("x0") ret,
+        err = out(reg) err,
+        options(nostack)
+    );
+    if err != 0 {
+        crate::set_errno(ret as libc::c_int);
+        return -1;
+    }
+    ret as libc::ssize_t
+}
+
+/// Raw listxattr syscall for macOS ARM64.
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+#[inline(never)]
+pub unsafe fn raw_listxattr(
+    path: *const libc::c_char,
+    namebuf: *mut libc::c_char,
+    size: libc::size_t,
+    options: libc::c_int,
+) -> libc::ssize_t {
+    let ret: i64;
+    let err: i64;
+    asm!(
+        "mov x16, {syscall}",
+        "svc #0x80",
+        "cset {err}, cs",
+        syscall = in(reg) SYS_LISTXATTR,
+        in("x0") path as i64,
+        in("x1") namebuf as i64,
+        in("x2") size as i64,
+        in("x3") options as i64,
+        lateout("x0") ret,
+        err = out(reg) err,
+        options(nostack)
+    );
+    if err != 0 {
+        crate::set_errno(ret as libc::c_int);
+        return -1;
+    }
+    ret as libc::ssize_t
+}
+
 /// Raw removexattr syscall for macOS ARM64.
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
 #[inline(never)]
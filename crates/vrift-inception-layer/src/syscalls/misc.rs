@@ -1,4 +1,6 @@
+use crate::budget::BudgetClass;
 use crate::state::*;
+use crate::syscalls::vfs_ops::{MetaChange, XattrOp};
 #[cfg(target_os = "macos")]
 use libc::c_void;
 use libc::{c_char, c_int};
@@ -7,48 +9,10 @@ use std::ffi::CStr;
 use std::fmt::Write;
 use std::sync::atomic::Ordering;
 
-/// RFC-0047: Rename implementation with VFS boundary enforcement
-/// Returns EXDEV (18) for cross-domain renames
+/// RFC-0047: rename is renameat from the cwd, so all three calls share the
+/// boundary checks and the manifest move
 unsafe fn rename_impl(old: *const c_char, new: *const c_char) -> Option<c_int> {
-    if old.is_null() || new.is_null() {
-        return None;
-    }
-
-    let _guard = InceptionLayerGuard::enter()?;
-    let state = InceptionLayerState::get()?;
-
-    let old_str = CStr::from_ptr(old).to_str().ok()?;
-    let new_str = CStr::from_ptr(new).to_str().ok()?;
-
-    let old_in_vfs = state.inception_applicable(old_str);
-    let new_in_vfs = state.inception_applicable(new_str);
-
-    // RFC-0047: Cross-boundary rename is forbidden
-    if old_in_vfs != new_in_vfs {
-        crate::set_errno(libc::EXDEV);
-        return Some(-1);
-    }
-
-    // Both in VFS territory -> Virtual Rename via Daemon IPC
-    if old_in_vfs && new_in_vfs {
-        if let (Some(v1), Some(v2)) = (state.resolve_path(old_str), state.resolve_path(new_str)) {
-            // RFC-0047: Only use Virtual Rename for managed files.
-            // For local files in VFS territory, let raw_rename handle it.
-            if state.query_manifest_ipc(&v1).is_some() {
-                if state
-                    .manifest_rename(&v1.manifest_key, &v2.manifest_key)
-                    .is_ok()
-                {
-                    return Some(0);
-                }
-                crate::set_errno(libc::EPERM);
-                return Some(-1);
-            }
-        }
-        return None; // Fallback to raw syscall for local files
-    }
-
-    None // Let real syscall handle non-VFS renames
+    renameat_vfs(libc::AT_FDCWD, old, libc::AT_FDCWD, new, 0)
 }
 
 #[no_mangle]
@@ -94,12 +58,28 @@ pub unsafe extern "C" fn renameat_inception_linux(
     newfd: c_int,
     new: *const c_char,
 ) -> c_int {
-    if let Some(res) = renameat_impl(old, new) {
+    if let Some(res) = renameat_vfs(oldfd, old, newfd, new, 0) {
         return res;
     }
     crate::syscalls::linux_raw::raw_renameat(oldfd, old, newfd, new)
 }
 
+/// renameat2 (Linux only): renameat plus RENAME_NOREPLACE/RENAME_EXCHANGE
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub unsafe extern "C" fn renameat2_inception(
+    oldfd: c_int,
+    old: *const c_char,
+    newfd: c_int,
+    new: *const c_char,
+    flags: libc::c_uint,
+) -> c_int {
+    if let Some(res) = renameat_vfs(oldfd, old, newfd, new, flags) {
+        return res;
+    }
+    crate::syscalls::linux_raw::raw_renameat2(oldfd, old, newfd, new, flags)
+}
+
 #[no_mangle]
 #[cfg(target_os = "macos")]
 pub unsafe extern "C" fn renameat_inception(
@@ -126,11 +106,8 @@ pub unsafe extern "C" fn velo_renameat_impl(
     newfd: c_int,
     new: *const c_char,
 ) -> c_int {
-    // Resolve relative paths using getcwd for AT_FDCWD case
-    if oldfd == libc::AT_FDCWD && newfd == libc::AT_FDCWD {
-        if let Some(result) = renameat_impl(old, new) {
-            return result;
-        }
+    if let Some(res) = renameat_vfs(oldfd, old, newfd, new, 0) {
+        return res;
     }
     #[cfg(target_os = "macos")]
     {
@@ -142,47 +119,58 @@ pub unsafe extern "C" fn velo_renameat_impl(
     }
 }
 
-/// renameat path resolution helper - resolves relative paths to absolute
-unsafe fn renameat_impl(old: *const c_char, new: *const c_char) -> Option<c_int> {
-    if old.is_null() || new.is_null() {
+/// `path` as an `*at` call on `dirfd` sees it, written to `buf`
+unsafe fn path_at(dirfd: c_int, path: *const c_char, buf: &mut [u8]) -> Option<&str> {
+    if path.is_null() {
         return None;
     }
+    let len = crate::path::resolve_path_at(dirfd, path, buf)?;
+    std::str::from_utf8(&buf[..len]).ok()
+}
 
+/// Hand the absolute path of a one-path `*at` call to a `vfs_ops` helper.
+/// None (the OS gets the call) when the layer can't resolve it.
+unsafe fn at_vfs(
+    dirfd: c_int,
+    path: *const c_char,
+    op: impl FnOnce(&str, &InceptionLayerState) -> Option<c_int>,
+) -> Option<c_int> {
     let _guard = InceptionLayerGuard::enter()?;
     let state = InceptionLayerState::get()?;
+    let mut buf = [0u8; crate::path::VFS_PATH_CAP];
+    op(path_at(dirfd, path, &mut buf)?, state)
+}
 
-    let old_str = CStr::from_ptr(old).to_str().ok()?;
-    let new_str = CStr::from_ptr(new).to_str().ok()?;
-
-    // Resolve relative paths via getcwd
-    let resolve_path = |path: &str| -> Option<String> {
-        if path.starts_with('/') {
-            Some(path.to_string())
-        } else {
-            let mut buf = [0u8; 1024];
-            let cwd = libc::getcwd(buf.as_mut_ptr().cast(), buf.len());
-            if cwd.is_null() {
-                None
-            } else {
-                let cwd_str = CStr::from_ptr(cwd).to_str().ok()?;
-                Some(format!("{}/{}", cwd_str, path))
-            }
-        }
-    };
-
-    let old_abs = resolve_path(old_str)?;
-    let new_abs = resolve_path(new_str)?;
-
-    let old_in_vfs = state.inception_applicable(&old_abs);
-    let new_in_vfs = state.inception_applicable(&new_abs);
-
-    // RFC-0047: Cross-boundary rename is forbidden
-    if old_in_vfs != new_in_vfs {
-        crate::set_errno(libc::EXDEV);
-        return Some(-1);
-    }
+/// `at_vfs` for the two-path calls (renameat, linkat)
+unsafe fn at2_vfs(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    op: impl FnOnce(&str, &str, &InceptionLayerState) -> Option<c_int>,
+) -> Option<c_int> {
+    let _guard = InceptionLayerGuard::enter()?;
+    let state = InceptionLayerState::get()?;
+    let mut old_buf = [0u8; crate::path::VFS_PATH_CAP];
+    let mut new_buf = [0u8; crate::path::VFS_PATH_CAP];
+    op(
+        path_at(olddirfd, old, &mut old_buf)?,
+        path_at(newdirfd, new, &mut new_buf)?,
+        state,
+    )
+}
 
-    None // Let real syscall handle
+/// renameat/renameat2 on VFS paths (RFC-0047)
+unsafe fn renameat_vfs(
+    oldfd: c_int,
+    old: *const c_char,
+    newfd: c_int,
+    new: *const c_char,
+    flags: libc::c_uint,
+) -> Option<c_int> {
+    at2_vfs(oldfd, old, newfd, new, |old, new, state| {
+        crate::syscalls::vfs_ops::rename_vfs(old, new, flags, state)
+    })
 }
 
 /// Helper to block mutation on VFS-managed files via FD
@@ -207,18 +195,17 @@ pub(crate) unsafe fn quick_block_vfs_fd_mutation(fd: c_int) -> Option<c_int> {
     #[cfg(target_os = "linux")]
     {
         let fd_path = format!("/proc/self/fd/{}\0", fd);
-        let mut path_buf = [0u8; 1024];
+        let mut path_buf = vec![0u8; libc::PATH_MAX as usize];
         let n = libc::readlink(
             fd_path.as_ptr() as *const c_char,
             path_buf.as_mut_ptr() as *mut c_char,
             path_buf.len(),
         );
         if n > 0 && (n as usize) < path_buf.len() {
-            if let Ok(path_str) = std::str::from_utf8(&path_buf[..n as usize]) {
-                if state.inception_applicable(path_str) {
-                    crate::set_errno(libc::EPERM);
-                    return Some(-1);
-                }
+            let path_str = vrift_ipc::encode_path_key(&path_buf[..n as usize]);
+            if state.inception_applicable(&path_str) {
+                crate::set_errno(libc::EPERM);
+                return Some(-1);
             }
         }
     }
@@ -235,8 +222,8 @@ pub(crate) unsafe fn quick_block_vfs_fd_mutation(fd: c_int) -> Option<c_int> {
 #[no_mangle]
 #[cfg(target_os = "macos")]
 pub unsafe extern "C" fn futimes_inception(fd: c_int, times: *const libc::timeval) -> c_int {
-    if let Some(err) = quick_block_vfs_fd_mutation(fd) {
-        return err;
+    if let Some(res) = set_meta_fd(fd, MetaChange::from_timevals(times)) {
+        return res;
     }
     crate::syscalls::macos_raw::raw_futimes(fd, times)
 }
@@ -244,8 +231,8 @@ pub unsafe extern "C" fn futimes_inception(fd: c_int, times: *const libc::timeva
 #[no_mangle]
 #[cfg(target_os = "linux")]
 pub unsafe extern "C" fn futimes_inception(fd: c_int, times: *const libc::timeval) -> c_int {
-    if let Some(err) = quick_block_vfs_fd_mutation(fd) {
-        return err;
+    if let Some(res) = set_meta_fd(fd, MetaChange::from_timevals(times)) {
+        return res;
     }
     // Linux: use raw syscall to avoid LD_PRELOAD recursion
     crate::syscalls::linux_raw::raw_futimes(fd, times)
@@ -266,18 +253,8 @@ pub unsafe extern "C" fn futimens_inception(fd: c_int, times: *const libc::times
         return crate::syscalls::linux_raw::raw_utimensat(fd, std::ptr::null(), times, 0);
     }
 
-    let _guard = match InceptionLayerGuard::enter() {
-        Some(g) => g,
-        None => {
-            #[cfg(target_os = "macos")]
-            return crate::syscalls::macos_raw::raw_futimens(fd, times);
-            #[cfg(target_os = "linux")]
-            return crate::syscalls::linux_raw::raw_utimensat(fd, std::ptr::null(), times, 0);
-        }
-    };
-
-    if let Some(err) = quick_block_vfs_fd_mutation(fd) {
-        return err;
+    if let Some(res) = set_meta_fd(fd, MetaChange::from_timespecs(times)) {
+        return res;
     }
 
     #[cfg(target_os = "macos")]
@@ -309,17 +286,14 @@ pub unsafe extern "C" fn utimensat_inception(
         return crate::syscalls::linux_raw::raw_utimensat(dirfd, path, times, flags);
     }
 
-    let _guard = match InceptionLayerGuard::enter() {
-        Some(g) => g,
-        None => {
-            #[cfg(target_os = "macos")]
-            return crate::syscalls::macos_raw::raw_utimensat(dirfd, path, times, flags);
-            #[cfg(target_os = "linux")]
-            return crate::syscalls::linux_raw::raw_utimensat(dirfd, path, times, flags);
-        }
+    let change = MetaChange::from_timespecs(times);
+    // utimensat(fd, NULL, ...) is futimens
+    let res = if path.is_null() {
+        set_meta_fd(dirfd, change)
+    } else {
+        set_meta_at(dirfd, path, change)
     };
-    // RFC-0039: Block timestamp mutation on VFS-managed files (returns EPERM)
-    block_vfs_mutation(path).unwrap_or_else(|| {
+    res.unwrap_or_else(|| {
         #[cfg(target_os = "macos")]
         return crate::syscalls::macos_raw::raw_utimensat(dirfd, path, times, flags);
         #[cfg(target_os = "linux")]
@@ -453,21 +427,15 @@ pub unsafe extern "C" fn linkat_inception(
         #[cfg(target_os = "linux")]
         return crate::syscalls::linux_raw::raw_linkat(olddirfd, oldpath, newdirfd, path, flags);
     }
-    if let Some(res) = link_impl(oldpath, path) {
-        return res;
-    }
-    block_vfs_mutation(oldpath)
-        .or_else(|| block_vfs_mutation(path))
-        .unwrap_or_else(|| {
-            #[cfg(target_os = "macos")]
-            return crate::syscalls::macos_raw::raw_linkat(
-                olddirfd, oldpath, newdirfd, path, flags,
-            );
-            #[cfg(target_os = "linux")]
-            return crate::syscalls::linux_raw::raw_linkat(
-                olddirfd, oldpath, newdirfd, path, flags,
-            );
-        })
+    at2_vfs(olddirfd, oldpath, newdirfd, path, |old, new, state| {
+        crate::syscalls::vfs_ops::link_vfs(old, new, state)
+    })
+    .unwrap_or_else(|| {
+        #[cfg(target_os = "macos")]
+        return crate::syscalls::macos_raw::raw_linkat(olddirfd, oldpath, newdirfd, path, flags);
+        #[cfg(target_os = "linux")]
+        return crate::syscalls::linux_raw::raw_linkat(olddirfd, oldpath, newdirfd, path, flags);
+    })
 }
 
 // RFC-0047: Mutation Perimeter - Block modifications to VFS-managed files
@@ -530,7 +498,7 @@ pub unsafe extern "C" fn unlinkat_inception(
             }
             return crate::syscalls::macos_raw::raw_unlinkat(dirfd, path, flags);
         } // Pattern 2930: Use raw syscall to avoid post-init dlsym hazard
-        block_existing_vfs_entry(path)
+        unlinkat_vfs(dirfd, path, flags)
             .unwrap_or_else(|| crate::syscalls::macos_raw::raw_unlinkat(dirfd, path, flags))
     }
     #[cfg(target_os = "linux")]
@@ -546,11 +514,22 @@ pub unsafe extern "C" fn unlinkat_inception(
             return crate::syscalls::linux_raw::raw_unlinkat(dirfd, path, flags);
         }
         // RFC-0039: Allow unlink if file is NOT in manifest (cross-domain mv cleanup)
-        block_existing_vfs_entry(path)
+        unlinkat_vfs(dirfd, path, flags)
             .unwrap_or_else(|| crate::syscalls::linux_raw::raw_unlinkat(dirfd, path, flags))
     }
 }
 
+/// unlinkat on a manifest entry removes it from the manifest (RFC-0047)
+unsafe fn unlinkat_vfs(dirfd: c_int, path: *const c_char, flags: c_int) -> Option<c_int> {
+    at_vfs(dirfd, path, |abs, state| {
+        if flags & libc::AT_REMOVEDIR != 0 {
+            crate::syscalls::vfs_ops::rmdir_vfs(abs, state)
+        } else {
+            crate::syscalls::vfs_ops::unlink_vfs(abs, state)
+        }
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn mkdirat_inception(
     dirfd: c_int,
@@ -568,9 +547,11 @@ pub unsafe extern "C" fn mkdirat_inception(
             // RFC-0039: During early init, allow mkdirat passthrough
             return crate::syscalls::macos_raw::raw_mkdirat(dirfd, path, mode);
         }
-        // RFC-0039: Only block if path EXISTS in manifest, allow new dir creation
-        block_existing_vfs_entry_at(dirfd, path)
-            .unwrap_or_else(|| crate::syscalls::macos_raw::raw_mkdirat(dirfd, path, mode))
+        // RFC-0039: EEXIST over a manifest entry, new dirs are registered
+        at_vfs(dirfd, path, |abs, state| {
+            crate::syscalls::vfs_ops::mkdir_vfs(abs, mode, state)
+        })
+        .unwrap_or_else(|| crate::syscalls::macos_raw::raw_mkdirat(dirfd, path, mode))
     }
     #[cfg(target_os = "linux")]
     {
@@ -582,9 +563,11 @@ pub unsafe extern "C" fn mkdirat_inception(
             // RFC-0039: During early init, allow mkdirat passthrough
             return crate::syscalls::linux_raw::raw_mkdirat(dirfd, path, mode);
         }
-        // RFC-0039: Only block if path EXISTS in manifest, allow new dir creation
-        block_existing_vfs_entry(path)
-            .unwrap_or_else(|| crate::syscalls::linux_raw::raw_mkdirat(dirfd, path, mode))
+        // RFC-0039: EEXIST over a manifest entry, new dirs are registered
+        at_vfs(dirfd, path, |abs, state| {
+            crate::syscalls::vfs_ops::mkdir_vfs(abs, mode, state)
+        })
+        .unwrap_or_else(|| crate::syscalls::linux_raw::raw_mkdirat(dirfd, path, mode))
     }
 }
 
@@ -609,8 +592,10 @@ pub unsafe extern "C" fn symlinkat_inception(
             return crate::syscalls::macos_raw::raw_symlinkat(p1, dirfd, p2);
         }
 
-        // Block mutation on existing VFS entries
-        if let Some(err) = block_vfs_mutation(p1).or_else(|| block_vfs_mutation(p2)) {
+        // EEXIST over a manifest entry; the target is only a string
+        if let Some(err) = at_vfs(dirfd, p2, |abs, state| {
+            crate::syscalls::vfs_ops::create_vfs(abs, state)
+        }) {
             return err;
         }
 
@@ -619,13 +604,7 @@ pub unsafe extern "C" fn symlinkat_inception(
 
         // RFC-0039 Live Ingest: Notify daemon of successful symlink
         if result == 0 {
-            if let Some(state) = crate::state::InceptionLayerState::get() {
-                let target_str = CStr::from_ptr(p1).to_string_lossy();
-                let link_str = CStr::from_ptr(p2).to_string_lossy();
-                if let Some(vpath) = state.resolve_path(&link_str) {
-                    let _ = state.manifest_symlink(&vpath.manifest_key, &target_str);
-                }
-            }
+            register_symlink_at(p1, dirfd, p2);
         }
 
         result
@@ -644,8 +623,10 @@ pub unsafe extern "C" fn symlinkat_inception(
             return crate::syscalls::linux_raw::raw_symlinkat(p1, dirfd, p2);
         }
 
-        // Block mutation on existing VFS entries
-        if let Some(err) = block_vfs_mutation(p1).or_else(|| block_vfs_mutation(p2)) {
+        // EEXIST over a manifest entry; the target is only a string
+        if let Some(err) = at_vfs(dirfd, p2, |abs, state| {
+            crate::syscalls::vfs_ops::create_vfs(abs, state)
+        }) {
             return err;
         }
 
@@ -654,19 +635,24 @@ pub unsafe extern "C" fn symlinkat_inception(
 
         // RFC-0039 Live Ingest: Notify daemon of successful symlink
         if result == 0 {
-            if let Some(state) = crate::state::InceptionLayerState::get() {
-                let target_str = CStr::from_ptr(p1).to_string_lossy();
-                let link_str = CStr::from_ptr(p2).to_string_lossy();
-                if let Some(vpath) = state.resolve_path(&link_str) {
-                    let _ = state.manifest_symlink(&vpath.manifest_key, &target_str);
-                }
-            }
+            register_symlink_at(p1, dirfd, p2);
         }
 
         result
     }
 }
 
+/// Add a symlink just made by symlinkat to the manifest
+unsafe fn register_symlink_at(target: *const c_char, dirfd: c_int, link: *const c_char) {
+    at_vfs(dirfd, link, |abs, state| {
+        if let Some(vpath) = state.resolve_path(abs) {
+            let target_str = CStr::from_ptr(target).to_string_lossy();
+            let _ = state.manifest_symlink(&vpath.manifest_key, &target_str);
+        }
+        None
+    });
+}
+
 #[no_mangle]
 #[cfg(target_os = "macos")]
 pub unsafe extern "C" fn rmdir_inception(path: *const c_char) -> c_int {
@@ -730,7 +716,7 @@ pub unsafe extern "C" fn mkdir_inception(path: *const c_char, mode: libc::mode_t
             let path_str = CStr::from_ptr(path).to_string_lossy();
             if let Some(vpath) = state.resolve_path(&path_str) {
                 // Fire-and-forget IPC to register new dir in manifest
-                let _ = state.manifest_mkdir(&vpath.manifest_key, mode);
+                let _ = state.manifest_mkdir(&vpath.manifest_key, created_dir_mode(path, mode));
             }
         }
     }
@@ -764,7 +750,7 @@ pub unsafe extern "C" fn mkdir_inception(path: *const c_char, mode: libc::mode_t
         if let Some(state) = crate::state::InceptionLayerState::get() {
             let path_str = CStr::from_ptr(path).to_string_lossy();
             if let Some(vpath) = state.resolve_path(&path_str) {
-                let _ = state.manifest_mkdir(&vpath.manifest_key, mode);
+                let _ = state.manifest_mkdir(&vpath.manifest_key, created_dir_mode(path, mode));
             }
         }
     }
@@ -791,7 +777,7 @@ pub unsafe extern "C" fn utimes_inception(
         #[cfg(target_os = "linux")]
         return crate::syscalls::linux_raw::raw_utimes(path, times);
     }
-    block_vfs_mutation(path).unwrap_or_else(|| {
+    set_meta_at(libc::AT_FDCWD, path, MetaChange::from_timevals(times)).unwrap_or_else(|| {
         #[cfg(target_os = "macos")]
         return crate::syscalls::macos_raw::raw_utimes(path, times);
         #[cfg(target_os = "linux")]
@@ -803,17 +789,20 @@ pub unsafe extern "C" fn utimes_inception(
 pub unsafe extern "C" fn utime_inception(path: *const c_char, times: *const libc::c_void) -> c_int {
     // utime(path, utimbuf) is legacy but used by some tools
     // utimbuf has 2 longs (actime, modtime)
-    // We treat it generically and block if in VFS
-    if let Some(err) = block_vfs_mutation(path) {
-        return err;
+    let change = if times.is_null() {
+        MetaChange::from_timespecs(std::ptr::null())
+    } else {
+        let buf = &*(times as *const libc::utimbuf);
+        MetaChange::Mtime(Some(buf.modtime.max(0) as u64))
+    };
+    if let Some(res) = set_meta_at(libc::AT_FDCWD, path, change) {
+        return res;
     }
     // Fallback to raw utimes with NULL (current time) if times is NULL
     if times.is_null() {
         return crate::syscalls::linux_raw::raw_utimes(path, std::ptr::null());
     }
     // Otherwise use real libc utime via dlsym or just let it pass to libc
-    // For simplicity, we just use the raw assembly for utimes(path, NULL)
-    // if it's in VFS we already blocked it.
     libc::utime(path, times as _)
 }
 
@@ -861,6 +850,62 @@ pub unsafe extern "C" fn setattrlist_inception(
     crate::syscalls::macos_raw::raw_setattrlist(path, attrlist, attrbuf, attrbufsize, options)
 }
 
+/// chmod/chown/utimes on a path: a manifest entry takes the change as
+/// metadata; a path the manifest doesn't have (a build output, say) is left
+/// to the OS
+unsafe fn set_meta_at(dirfd: c_int, path: *const c_char, change: MetaChange) -> Option<c_int> {
+    if path.is_null() {
+        return None;
+    }
+    let _guard = InceptionLayerGuard::enter()?;
+    let state = InceptionLayerState::get()?;
+    let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+    let len = crate::path::resolve_path_at(dirfd, path, &mut abs)?;
+    let path_str = std::str::from_utf8(&abs[..len]).ok()?;
+    crate::syscalls::vfs_ops::set_meta_vfs(path_str, change, state)
+}
+
+/// fchmod/fchown/futimens: as `set_meta_at`, for the file behind `fd`
+unsafe fn set_meta_fd(fd: c_int, change: MetaChange) -> Option<c_int> {
+    let _guard = InceptionLayerGuard::enter()?;
+    let state = InceptionLayerState::get()?;
+    if let Some(entry) = crate::syscalls::io::get_fd_entry(fd) {
+        if entry.is_vfs {
+            return crate::syscalls::vfs_ops::set_meta_fd(&entry, change, state);
+        }
+    }
+
+    // Not opened through the shim: go by the path the kernel has for it
+    let path_str = untracked_fd_path(fd)?;
+    crate::syscalls::vfs_ops::set_meta_vfs(&path_str, change, state)
+}
+
+/// The path the kernel has for `fd`: F_GETPATH on macOS, /proc/self/fd on
+/// Linux
+unsafe fn untracked_fd_path(fd: c_int) -> Option<String> {
+    #[cfg(target_os = "macos")]
+    {
+        let mut path_buf = [0 as c_char; 1024];
+        if libc::fcntl(fd, libc::F_GETPATH, path_buf.as_mut_ptr()) == 0 {
+            return Some(CStr::from_ptr(path_buf.as_ptr()).to_str().ok()?.to_string());
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let fd_path = format!("/proc/self/fd/{}\0", fd);
+        let mut path_buf = [0u8; 1024];
+        let n = libc::readlink(
+            fd_path.as_ptr() as *const c_char,
+            path_buf.as_mut_ptr() as *mut c_char,
+            path_buf.len(),
+        );
+        if n > 0 && (n as usize) < path_buf.len() {
+            return Some(vrift_ipc::encode_path_key(&path_buf[..n as usize]).into_owned());
+        }
+    }
+    None
+}
+
 /// Helper: Check if path is in VFS and return EPERM if so
 /// RFC-0048: Must check is_vfs_ready() FIRST to avoid deadlock during init (Pattern 2543)
 /// RFC-0052: Standalone mode - check VRIFT_VFS_PREFIX even without daemon
@@ -920,38 +965,20 @@ pub(crate) unsafe fn block_existing_vfs_entry_at(
     if path_str.starts_with('/') || dirfd == libc::AT_FDCWD {
         resolved_vpath = state.resolve_path(path_str);
     } else {
-        // Resolve dirfd
-        let mut _dir_path_buf = [0i8; 1024];
-        #[cfg(target_os = "macos")]
-        if crate::syscalls::macos_raw::raw_fcntl(
-            dirfd,
-            libc::F_GETPATH,
-            _dir_path_buf.as_mut_ptr() as i64,
-        ) == 0
-        {
-            let mut abs_buf = [0u8; 1024];
-            let mut aw = crate::macros::StackWriter::new(&mut abs_buf);
-            let dir_path_ptr = _dir_path_buf.as_ptr() as *const u8;
-            let dir_path_len = unsafe { libc::strlen(dir_path_ptr as *const i8) };
-
-            // Safety: libc::fcntl with F_GETPATH fills the buffer with a NUL-terminated C string.
-            // We use bytes directly to avoid to_string_lossy (which can allocate).
-            let dir_path_bytes = unsafe { std::slice::from_raw_parts(dir_path_ptr, dir_path_len) };
-            let _ = aw.write_str(std::str::from_utf8(dir_path_bytes).unwrap_or(""));
-            let _ = aw.write_char('/');
-            let _ = aw.write_str(path_str);
-
-            resolved_vpath = state.resolve_path(aw.as_str());
-        }
-        #[cfg(target_os = "linux")]
-        {
-            // TODO: linux dirfd resolution
+        let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+        if let Some(len) = crate::path::resolve_path_at(dirfd, path, &mut abs) {
+            resolved_vpath = std::str::from_utf8(&abs[..len])
+                .ok()
+                .and_then(|p| state.resolve_path(p));
         }
     }
 
     if let Some(vpath) = resolved_vpath {
         // Check if this path exists in manifest
-        if state.query_manifest_ipc(&vpath).is_some() {
+        if state
+            .query_manifest_ipc(&vpath, BudgetClass::Mutate)
+            .is_some_and(|(e, _)| !e.is_whiteout())
+        {
             inception_log!(
                 "blocking creation on EXISTING VFS entry: '{}'",
                 vpath.absolute
@@ -964,6 +991,66 @@ pub(crate) unsafe fn block_existing_vfs_entry_at(
     None
 }
 
+/// The caller's current umask. Read fresh on every call (processes and
+/// shells change it), from /proc on Linux so no other thread ever sees a
+/// temporarily swapped mask.
+pub(crate) unsafe fn current_umask() -> libc::mode_t {
+    #[cfg(target_os = "linux")]
+    {
+        use crate::syscalls::linux_raw::{raw_close, raw_open, raw_read};
+        let fd = raw_open(
+            c"/proc/self/status".as_ptr(),
+            libc::O_RDONLY | libc::O_CLOEXEC,
+            0,
+        );
+        if fd >= 0 {
+            // "Umask:" is the second line; the head of the file is enough
+            let mut buf = [0u8; 512];
+            let n = raw_read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len());
+            raw_close(fd);
+            let head = &buf[..n.max(0) as usize];
+            let mask = head
+                .split(|&b| b == b'\n')
+                .find_map(|line| line.strip_prefix(b"Umask:"))
+                .and_then(|v| std::str::from_utf8(v).ok())
+                .and_then(|v| libc::mode_t::from_str_radix(v.trim(), 8).ok());
+            if let Some(mask) = mask {
+                return mask;
+            }
+        }
+        let mask = crate::syscalls::linux_raw::raw_umask(0o022);
+        crate::syscalls::linux_raw::raw_umask(mask);
+        mask
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mask = libc::umask(0o022);
+        libc::umask(mask);
+        mask
+    }
+}
+
+/// Permission bits to record for a directory the caller just created on
+/// disk: the kernel already applied the umask (or the parent's default
+/// ACL, which overrides it), so read them back. Falls back to masking the
+/// requested mode if the stat fails.
+#[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS, u32 on Linux
+pub(crate) unsafe fn created_dir_mode(
+    path: *const c_char,
+    requested: libc::mode_t,
+) -> libc::mode_t {
+    let mut st: libc::stat = std::mem::zeroed();
+    #[cfg(target_os = "linux")]
+    let rc = crate::syscalls::linux_raw::raw_lstat(path, &mut st);
+    #[cfg(target_os = "macos")]
+    let rc = crate::syscalls::macos_raw::raw_lstat(path, &mut st);
+    if rc == 0 {
+        (st.st_mode as libc::mode_t) & 0o7777
+    } else {
+        requested & !current_umask() & 0o7777
+    }
+}
+
 pub(crate) unsafe fn block_existing_vfs_entry(path: *const c_char) -> Option<c_int> {
     block_existing_vfs_entry_at(libc::AT_FDCWD, path)
 }
@@ -1037,7 +1124,7 @@ pub unsafe extern "C" fn chmod_inception(path: *const c_char, mode: libc::mode_t
     }
 
     // Pattern 2930: Use raw syscall to avoid post-init dlsym hazard
-    block_vfs_mutation(path).unwrap_or_else(|| {
+    set_meta_at(libc::AT_FDCWD, path, MetaChange::Mode(mode)).unwrap_or_else(|| {
         #[cfg(target_os = "macos")]
         return crate::syscalls::macos_raw::raw_chmod(path, mode);
         #[cfg(target_os = "linux")]
@@ -1067,7 +1154,7 @@ pub unsafe extern "C" fn fchmodat_inception(
         return crate::syscalls::linux_raw::raw_fchmodat(dirfd, path, mode, flags);
     }
     // Pattern 2930: Use raw syscall to avoid post-init dlsym hazard
-    block_vfs_mutation(path).unwrap_or_else(|| {
+    set_meta_at(dirfd, path, MetaChange::Mode(mode)).unwrap_or_else(|| {
         #[cfg(target_os = "macos")]
         return crate::syscalls::macos_raw::raw_fchmodat(dirfd, path, mode, flags);
         #[cfg(target_os = "linux")]
@@ -1088,36 +1175,9 @@ pub unsafe extern "C" fn fchmod_inception(fd: c_int, mode: libc::mode_t) -> c_in
             return crate::syscalls::macos_raw::raw_fchmod(fd, mode);
         }
 
-        // RFC-OPT-001: Recursion protection
-        let _guard = match InceptionLayerGuard::enter() {
-            Some(g) => g,
-            None => return crate::syscalls::macos_raw::raw_fchmod(fd, mode),
-        };
-
-        // VFS logic: if FD points to a VFS file, block mutation
-        // Strategy: Try to get path from FD (robust)
-        let mut path_buf = [0; 1024];
-        if unsafe { libc::fcntl(fd, libc::F_GETPATH, path_buf.as_mut_ptr()) } == 0 {
-            let path_cstr = unsafe { CStr::from_ptr(path_buf.as_ptr()) };
-            if let Ok(path_str) = path_cstr.to_str() {
-                if let Some(state) = InceptionLayerState::get() {
-                    if state.inception_applicable(path_str) {
-                        crate::set_errno(libc::EPERM);
-                        return -1;
-                    }
-                }
-            }
-        }
-
-        // Fallback to FD table if F_GETPATH failed or for extra safety
-        use crate::syscalls::io::get_fd_entry;
-        if let Some(entry) = get_fd_entry(fd) {
-            if entry.is_vfs {
-                crate::set_errno(libc::EPERM);
-                return -1;
-            }
+        if let Some(res) = set_meta_fd(fd, MetaChange::Mode(mode)) {
+            return res;
         }
-
         crate::syscalls::macos_raw::raw_fchmod(fd, mode)
     }
     #[cfg(target_os = "linux")]
@@ -1130,49 +1190,18 @@ pub unsafe extern "C" fn fchmod_inception(fd: c_int, mode: libc::mode_t) -> c_in
             return crate::syscalls::linux_raw::raw_fchmod(fd, mode);
         }
 
-        let _guard = match InceptionLayerGuard::enter() {
-            Some(g) => g,
-            None => return crate::syscalls::linux_raw::raw_fchmod(fd, mode),
-        };
-
-        // Strategy: Use /proc/self/fd/N to get path
-        let fd_path = format!("/proc/self/fd/{}\0", fd);
-        let mut path_buf = [0u8; 1024];
-        let n = unsafe {
-            libc::readlink(
-                fd_path.as_ptr() as *const c_char,
-                path_buf.as_mut_ptr() as *mut c_char,
-                path_buf.len(),
-            )
-        };
-        if n > 0 && (n as usize) < path_buf.len() {
-            if let Ok(path_str) = std::str::from_utf8(&path_buf[..n as usize]) {
-                if let Some(state) = InceptionLayerState::get() {
-                    if state.inception_applicable(path_str) {
-                        crate::set_errno(libc::EPERM);
-                        return -1;
-                    }
-                }
-            }
-        }
-
-        use crate::syscalls::io::get_fd_entry;
-        if let Some(entry) = get_fd_entry(fd) {
-            if entry.is_vfs {
-                crate::set_errno(libc::EPERM);
-                return -1;
-            }
+        if let Some(res) = set_meta_fd(fd, MetaChange::Mode(mode)) {
+            return res;
         }
-
         crate::syscalls::linux_raw::raw_fchmod(fd, mode)
     }
 }
 
-// P0-P1 Gap Fix: fchown/fchownat - Block ownership changes on VFS files via FD
+// P0-P1 Gap Fix: fchown/fchownat - ownership changes on VFS files via FD
 // Pattern: Same as fchmod_inception - resolve FD to path, check VFS
 
-/// fchown_inception: Block ownership changes on VFS files via FD
-/// Uses F_GETPATH (macOS) or /proc/self/fd (Linux) to resolve FD to path
+/// fchown_inception: Ownership changes on VFS files via FD
+/// Uses the fd table, then F_GETPATH (macOS) or /proc/self/fd (Linux)
 #[no_mangle]
 pub unsafe extern "C" fn fchown_inception(
     fd: c_int,
@@ -1190,36 +1219,9 @@ pub unsafe extern "C" fn fchown_inception(
             return crate::syscalls::macos_raw::raw_fchown(fd, owner, group);
         }
 
-        // RFC-OPT-001: Recursion protection
-        let _guard = match InceptionLayerGuard::enter() {
-            Some(g) => g,
-            None => return crate::syscalls::macos_raw::raw_fchown(fd, owner, group),
-        };
-
-        // VFS logic: if FD points to a VFS file, block mutation
-        // Strategy: Try to get path from FD via F_GETPATH
-        let mut path_buf = [0i8; 1024];
-        if libc::fcntl(fd, libc::F_GETPATH, path_buf.as_mut_ptr()) == 0 {
-            let path_cstr = CStr::from_ptr(path_buf.as_ptr());
-            if let Ok(path_str) = path_cstr.to_str() {
-                if let Some(state) = InceptionLayerState::get() {
-                    if state.inception_applicable(path_str) {
-                        crate::set_errno(libc::EPERM);
-                        return -1;
-                    }
-                }
-            }
-        }
-
-        // Fallback to FD table if F_GETPATH failed
-        use crate::syscalls::io::get_fd_entry;
-        if let Some(entry) = get_fd_entry(fd) {
-            if entry.is_vfs {
-                crate::set_errno(libc::EPERM);
-                return -1;
-            }
+        if let Some(res) = set_meta_fd(fd, MetaChange::Owner(owner, group)) {
+            return res;
         }
-
         crate::syscalls::macos_raw::raw_fchown(fd, owner, group)
     }
     #[cfg(target_os = "linux")]
@@ -1232,43 +1234,14 @@ pub unsafe extern "C" fn fchown_inception(
             return crate::syscalls::linux_raw::raw_fchown(fd, owner, group);
         }
 
-        let _guard = match InceptionLayerGuard::enter() {
-            Some(g) => g,
-            None => return crate::syscalls::linux_raw::raw_fchown(fd, owner, group),
-        };
-
-        // Strategy: Use /proc/self/fd/N to get path
-        let fd_path = format!("/proc/self/fd/{}\0", fd);
-        let mut path_buf = [0u8; 1024];
-        let n = libc::readlink(
-            fd_path.as_ptr() as *const c_char,
-            path_buf.as_mut_ptr() as *mut c_char,
-            path_buf.len(),
-        );
-        if n > 0 && (n as usize) < path_buf.len() {
-            if let Ok(path_str) = std::str::from_utf8(&path_buf[..n as usize]) {
-                if let Some(state) = InceptionLayerState::get() {
-                    if state.inception_applicable(path_str) {
-                        crate::set_errno(libc::EPERM);
-                        return -1;
-                    }
-                }
-            }
-        }
-
-        use crate::syscalls::io::get_fd_entry;
-        if let Some(entry) = get_fd_entry(fd) {
-            if entry.is_vfs {
-                crate::set_errno(libc::EPERM);
-                return -1;
-            }
+        if let Some(res) = set_meta_fd(fd, MetaChange::Owner(owner, group)) {
+            return res;
         }
-
         crate::syscalls::linux_raw::raw_fchown(fd, owner, group)
     }
 }
 
-/// fchownat_inception: Block ownership changes on VFS files via dirfd + path
+/// fchownat_inception: Ownership changes on VFS files via dirfd + path
 #[no_mangle]
 pub unsafe extern "C" fn fchownat_inception(
     dirfd: c_int,
@@ -1291,7 +1264,7 @@ pub unsafe extern "C" fn fchownat_inception(
             return crate::syscalls::macos_raw::raw_fchownat(dirfd, path, owner, group, flags);
         }
         // Pattern 2930: Use raw syscall to avoid post-init dlsym hazard
-        block_vfs_mutation(path).unwrap_or_else(|| {
+        set_meta_at(dirfd, path, MetaChange::Owner(owner, group)).unwrap_or_else(|| {
             crate::syscalls::macos_raw::raw_fchownat(dirfd, path, owner, group, flags)
         })
     }
@@ -1307,7 +1280,7 @@ pub unsafe extern "C" fn fchownat_inception(
             }
             return crate::syscalls::linux_raw::raw_fchownat(dirfd, path, owner, group, flags);
         }
-        block_vfs_mutation(path).unwrap_or_else(|| {
+        set_meta_at(dirfd, path, MetaChange::Owner(owner, group)).unwrap_or_else(|| {
             crate::syscalls::linux_raw::raw_fchownat(dirfd, path, owner, group, flags)
         })
     }
@@ -1317,7 +1290,7 @@ pub unsafe extern "C" fn fchownat_inception(
 
 // Gap Fix: chown/lchown path-based ownership interposition
 
-/// chown_inception: Block ownership changes on VFS files via path
+/// chown_inception: Ownership changes on VFS files via path
 #[no_mangle]
 pub unsafe extern "C" fn chown_inception(
     path: *const c_char,
@@ -1338,7 +1311,7 @@ pub unsafe extern "C" fn chown_inception(
         #[cfg(target_os = "linux")]
         return crate::syscalls::linux_raw::raw_chown(path, owner, group);
     }
-    block_vfs_mutation(path).unwrap_or_else(|| {
+    set_meta_at(libc::AT_FDCWD, path, MetaChange::Owner(owner, group)).unwrap_or_else(|| {
         #[cfg(target_os = "macos")]
         return crate::syscalls::macos_raw::raw_chown(path, owner, group);
         #[cfg(target_os = "linux")]
@@ -1346,7 +1319,7 @@ pub unsafe extern "C" fn chown_inception(
     })
 }
 
-/// lchown_inception: Block ownership changes on VFS symlinks via path (no-follow)
+/// lchown_inception: Ownership changes on VFS files via path (no-follow)
 #[no_mangle]
 pub unsafe extern "C" fn lchown_inception(
     path: *const c_char,
@@ -1367,7 +1340,7 @@ pub unsafe extern "C" fn lchown_inception(
         #[cfg(target_os = "linux")]
         return crate::syscalls::linux_raw::raw_lchown(path, owner, group);
     }
-    block_vfs_mutation(path).unwrap_or_else(|| {
+    set_meta_at(libc::AT_FDCWD, path, MetaChange::Owner(owner, group)).unwrap_or_else(|| {
         #[cfg(target_os = "macos")]
         return crate::syscalls::macos_raw::raw_lchown(path, owner, group);
         #[cfg(target_os = "linux")]
@@ -1378,8 +1351,8 @@ pub unsafe extern "C" fn lchown_inception(
 // Gap Fix: readlinkat interposition
 
 /// readlinkat_inception: Interpose readlinkat to handle VFS symlinks
-/// For absolute paths or AT_FDCWD, delegates to readlink VFS logic.
-/// For dirfd-relative paths, passes through to real readlinkat.
+/// The path is resolved against dirfd and manifest symlinks are answered
+/// like readlink(); everything else goes to the real readlinkat.
 #[no_mangle]
 pub unsafe extern "C" fn readlinkat_inception(
     dirfd: c_int,
@@ -1400,19 +1373,15 @@ pub unsafe extern "C" fn readlinkat_inception(
         return crate::syscalls::linux_raw::raw_readlinkat(dirfd, path, buf, bufsiz);
     }
 
-    // For AT_FDCWD with absolute paths, use VFS-resolved readlink path
-    if !path.is_null() && (dirfd == libc::AT_FDCWD || *path == b'/' as c_char) {
-        // Delegate to readlink inception logic which handles VFS paths
+    if !path.is_null() {
         if let Some(_guard) = InceptionLayerGuard::enter() {
-            if let Some(state) = InceptionLayerState::get() {
-                let path_cstr = CStr::from_ptr(path);
-                if let Ok(path_str) = path_cstr.to_str() {
-                    if let Some(vpath) = state.resolve_path(path_str) {
-                        // VFS path: use raw readlinkat to read the underlying symlink
-                        // (the real symlink target is in the materialized workspace)
-                        inception_log!("readlinkat on VFS path: '{}'", vpath.absolute);
-                    }
-                }
+            let _span = crate::profile::begin(crate::profile::ProfileOp::Readlink);
+            let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+            let hit = crate::path::resolve_path_at(dirfd, path, &mut abs)
+                .and_then(|len| std::str::from_utf8(&abs[..len]).ok())
+                .and_then(|p| crate::syscalls::path::manifest_readlink(p, buf, bufsiz));
+            if let Some(n) = hit {
+                return n;
             }
         }
     }
@@ -1491,13 +1460,78 @@ pub unsafe extern "C" fn truncate_inception(path: *const c_char, length: libc::o
     }
     // Pattern 2930: Use raw syscall to avoid post-init dlsym hazard
     #[cfg(target_os = "macos")]
-    return block_vfs_mutation(path)
+    return truncate_vfs(path, length)
         .unwrap_or_else(|| crate::syscalls::macos_raw::raw_truncate(path, length));
     #[cfg(target_os = "linux")]
-    return block_vfs_mutation(path)
+    return truncate_vfs(path, length)
         .unwrap_or_else(|| crate::syscalls::linux_raw::raw_truncate(path, length));
 }
 
+/// truncate() on a manifest file: truncate the staging copy its writers
+/// share (or a fresh one) and, unless one of them still holds it, reingest
+/// it straight away. Paths the manifest doesn't have go to the OS.
+unsafe fn truncate_vfs(path: *const c_char, length: libc::off_t) -> Option<c_int> {
+    if path.is_null() {
+        return None;
+    }
+    let _guard = InceptionLayerGuard::enter()?;
+    let state = InceptionLayerState::get()?;
+    let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+    let len = crate::path::resolve_path_at(libc::AT_FDCWD, path, &mut abs)?;
+    let vpath = state.resolve_path(std::str::from_utf8(&abs[..len]).ok()?)?;
+    let entry = match state.lookup_manifest(&vpath, BudgetClass::Mutate) {
+        Ok(Some(e)) if !e.is_whiteout() => e,
+        Ok(_) => return None,
+        Err(errno) => {
+            crate::set_errno(errno);
+            return Some(-1);
+        }
+    };
+    if entry.is_dir() {
+        crate::set_errno(libc::EISDIR);
+        return Some(-1);
+    }
+    if entry.is_symlink() {
+        return None;
+    }
+
+    DIRTY_TRACKER.mark_dirty(&vpath.manifest_key);
+    let Some((mut sessions, temp_path)) =
+        crate::syscalls::open::join_cow_session(state, &vpath, &entry)
+    else {
+        crate::set_errno(libc::EIO);
+        return Some(-1);
+    };
+    let temp_cpath = std::ffi::CString::new(temp_path.as_str()).ok()?;
+    #[cfg(target_os = "macos")]
+    let res = crate::syscalls::macos_raw::raw_truncate(temp_cpath.as_ptr(), length);
+    #[cfg(target_os = "linux")]
+    let res = crate::syscalls::linux_raw::raw_truncate(temp_cpath.as_ptr(), length);
+    let errno = crate::get_errno();
+
+    // No open writer: this call is the whole session
+    let finished = match sessions.get(&vpath.manifest_key_hash) {
+        Some(session) if session.refs == 0 => sessions.remove(&vpath.manifest_key_hash),
+        _ => None,
+    };
+    drop(sessions);
+    match finished {
+        Some(session) if res == 0 => crate::syscalls::io::reingest_cow(
+            state,
+            &session.vpath,
+            &session.temp_path,
+            &session.base_hash,
+        ),
+        Some(_) => {
+            libc::unlink(temp_cpath.as_ptr());
+            DIRTY_TRACKER.clear_dirty(&vpath.manifest_key);
+        }
+        None => {}
+    }
+    crate::set_errno(errno);
+    Some(res)
+}
+
 #[no_mangle]
 #[cfg(target_os = "macos")]
 pub unsafe extern "C" fn chflags_inception(path: *const c_char, flags: libc::c_uint) -> c_int {
@@ -1517,6 +1551,142 @@ pub unsafe extern "C" fn chflags_inception(path: *const c_char, flags: libc::c_u
 }
 
 // --- xattr ---
+//
+// get/list/set/remove on a manifest entry are served from the xattrs vDird
+// keeps with it (see vfs_ops); anything else goes to the kernel. Until the
+// layer is up, changes are still refused in VFS territory, as for chmod.
+
+/// Where an xattr call points
+#[derive(Clone, Copy)]
+enum XattrTarget {
+    Path(*const c_char),
+    #[cfg(target_os = "linux")]
+    Fd(c_int),
+}
+
+impl XattrTarget {
+    /// Serve `op` from the manifest; None hands the call to the kernel
+    unsafe fn serve(self, op: XattrOp<'_>) -> Option<isize> {
+        let init_state = INITIALIZING.load(Ordering::Relaxed);
+        #[cfg(target_os = "macos")]
+        let early = init_state != 0;
+        #[cfg(target_os = "linux")]
+        let early = init_state >= 2;
+        if early
+            || crate::state::INCEPTION_LAYER_STATE
+                .load(Ordering::Acquire)
+                .is_null()
+        {
+            if let (Self::Path(path), XattrOp::Set { .. } | XattrOp::Remove { .. }) = (self, &op) {
+                return quick_block_vfs_mutation(path).map(|r| r as isize);
+            }
+            return None;
+        }
+
+        let _guard = InceptionLayerGuard::enter()?;
+        let state = InceptionLayerState::get()?;
+        match self {
+            Self::Path(path) => {
+                if path.is_null() {
+                    return None;
+                }
+                let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+                let len = crate::path::resolve_path_at(libc::AT_FDCWD, path, &mut abs)?;
+                let path_str = std::str::from_utf8(&abs[..len]).ok()?;
+                crate::syscalls::vfs_ops::xattr_vfs(path_str, op, state)
+            }
+            #[cfg(target_os = "linux")]
+            Self::Fd(fd) => {
+                if let Some(entry) = crate::syscalls::io::get_fd_entry(fd) {
+                    if entry.is_vfs {
+                        return crate::syscalls::vfs_ops::xattr_fd(&entry, op, state);
+                    }
+                }
+                let path_str = untracked_fd_path(fd)?;
+                crate::syscalls::vfs_ops::xattr_vfs(&path_str, op, state)
+            }
+        }
+    }
+}
+
+/// An attribute name argument; None (kernel) for null or non-UTF-8 names
+unsafe fn xattr_name<'a>(name: *const c_char) -> Option<&'a str> {
+    if name.is_null() {
+        return None;
+    }
+    CStr::from_ptr(name).to_str().ok()
+}
+
+unsafe fn get_xattr(
+    target: XattrTarget,
+    name: *const c_char,
+    buf: *mut libc::c_void,
+    size: libc::size_t,
+) -> Option<isize> {
+    let name = xattr_name(name)?;
+    target.serve(XattrOp::Get { name, buf, size })
+}
+
+unsafe fn list_xattr(target: XattrTarget, buf: *mut c_char, size: libc::size_t) -> Option<isize> {
+    target.serve(XattrOp::List { buf, size })
+}
+
+unsafe fn set_xattr(
+    target: XattrTarget,
+    name: *const c_char,
+    value: *const libc::c_void,
+    size: libc::size_t,
+    flags: c_int,
+) -> Option<c_int> {
+    let name = xattr_name(name)?;
+    let value = if size == 0 {
+        &[][..]
+    } else if value.is_null() {
+        crate::set_errno(libc::EFAULT);
+        return Some(-1);
+    } else {
+        std::slice::from_raw_parts(value as *const u8, size)
+    };
+    target
+        .serve(XattrOp::Set { name, value, flags })
+        .map(|r| r as c_int)
+}
+
+unsafe fn remove_xattr(target: XattrTarget, name: *const c_char) -> Option<c_int> {
+    let name = xattr_name(name)?;
+    target.serve(XattrOp::Remove { name }).map(|r| r as c_int)
+}
+
+#[no_mangle]
+#[cfg(target_os = "macos")]
+pub unsafe extern "C" fn getxattr_inception(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut c_void,
+    size: libc::size_t,
+    position: u32,
+    options: c_int,
+) -> libc::ssize_t {
+    // Resource fork offsets: not something a manifest entry has
+    if position == 0 {
+        if let Some(res) = get_xattr(XattrTarget::Path(path), name, value, size) {
+            return res;
+        }
+    }
+    crate::syscalls::macos_raw::raw_getxattr(path, name, value, size, position, options)
+}
+
+#[no_mangle]
+#[cfg(target_os = "macos")]
+pub unsafe extern "C" fn listxattr_inception(
+    path: *const c_char,
+    namebuf: *mut c_char,
+    size: libc::size_t,
+    options: c_int,
+) -> libc::ssize_t {
+    list_xattr(XattrTarget::Path(path), namebuf, size)
+        .unwrap_or_else(|| crate::syscalls::macos_raw::raw_listxattr(path, namebuf, size, options))
+}
 
 #[no_mangle]
 #[cfg(target_os = "macos")]
@@ -1528,23 +1698,13 @@ pub unsafe extern "C" fn setxattr_inception(
     position: u32,
     options: c_int,
 ) -> c_int {
-    let init_state = INITIALIZING.load(Ordering::Relaxed);
-    if init_state != 0
-        || crate::state::INCEPTION_LAYER_STATE
-            .load(std::sync::atomic::Ordering::Acquire)
-            .is_null()
-    {
-        if let Some(err) = quick_block_vfs_mutation(path) {
-            return err;
+    if position == 0 {
+        let flags = options & (libc::XATTR_CREATE | libc::XATTR_REPLACE);
+        if let Some(res) = set_xattr(XattrTarget::Path(path), name, value, size, flags) {
+            return res;
         }
-        return crate::syscalls::macos_raw::raw_setxattr(
-            path, name, value, size, position, options,
-        );
     }
-    // Pattern 2930: Use raw syscall to avoid post-init dlsym hazard
-    block_vfs_mutation(path).unwrap_or_else(|| {
-        crate::syscalls::macos_raw::raw_setxattr(path, name, value, size, position, options)
-    })
+    crate::syscalls::macos_raw::raw_setxattr(path, name, value, size, position, options)
 }
 
 #[no_mangle]
@@ -1554,22 +1714,105 @@ pub unsafe extern "C" fn removexattr_inception(
     name: *const c_char,
     options: c_int,
 ) -> c_int {
-    let init_state = INITIALIZING.load(Ordering::Relaxed);
-    if init_state != 0
-        || crate::state::INCEPTION_LAYER_STATE
-            .load(std::sync::atomic::Ordering::Acquire)
-            .is_null()
-    {
-        if let Some(err) = quick_block_vfs_mutation(path) {
-            return err;
-        }
-        return crate::syscalls::macos_raw::raw_removexattr(path, name, options);
-    }
-    // Pattern 2930: Use raw syscall to avoid post-init dlsym hazard
-    block_vfs_mutation(path)
+    remove_xattr(XattrTarget::Path(path), name)
         .unwrap_or_else(|| crate::syscalls::macos_raw::raw_removexattr(path, name, options))
 }
 
+#[no_mangle]
+#[cfg(target_os = "linux")]
+pub unsafe extern "C" fn getxattr_inception(
+    path: *const c_char,
+    name: *const c_char,
+    value: *mut libc::c_void,
+    size: libc::size_t,
+    nofollow: bool,
+) -> libc::ssize_t {
+    get_xattr(XattrTarget::Path(path), name, value, size).unwrap_or_else(|| {
+        crate::syscalls::linux_raw::raw_getxattr(path, name, value, size, nofollow)
+    })
+}
+
+#[no_mangle]
+#[cfg(target_os = "linux")]
+pub unsafe extern "C" fn fgetxattr_inception(
+    fd: c_int,
+    name: *const c_char,
+    value: *mut libc::c_void,
+    size: libc::size_t,
+) -> libc::ssize_t {
+    get_xattr(XattrTarget::Fd(fd), name, value, size)
+        .unwrap_or_else(|| crate::syscalls::linux_raw::raw_fgetxattr(fd, name, value, size))
+}
+
+#[no_mangle]
+#[cfg(target_os = "linux")]
+pub unsafe extern "C" fn listxattr_inception(
+    path: *const c_char,
+    list: *mut c_char,
+    size: libc::size_t,
+    nofollow: bool,
+) -> libc::ssize_t {
+    list_xattr(XattrTarget::Path(path), list, size)
+        .unwrap_or_else(|| crate::syscalls::linux_raw::raw_listxattr(path, list, size, nofollow))
+}
+
+#[no_mangle]
+#[cfg(target_os = "linux")]
+pub unsafe extern "C" fn flistxattr_inception(
+    fd: c_int,
+    list: *mut c_char,
+    size: libc::size_t,
+) -> libc::ssize_t {
+    list_xattr(XattrTarget::Fd(fd), list, size)
+        .unwrap_or_else(|| crate::syscalls::linux_raw::raw_flistxattr(fd, list, size))
+}
+
+#[no_mangle]
+#[cfg(target_os = "linux")]
+pub unsafe extern "C" fn setxattr_inception(
+    path: *const c_char,
+    name: *const c_char,
+    value: *const libc::c_void,
+    size: libc::size_t,
+    flags: c_int,
+    nofollow: bool,
+) -> c_int {
+    set_xattr(XattrTarget::Path(path), name, value, size, flags).unwrap_or_else(|| {
+        crate::syscalls::linux_raw::raw_setxattr(path, name, value, size, flags, nofollow)
+    })
+}
+
+#[no_mangle]
+#[cfg(target_os = "linux")]
+pub unsafe extern "C" fn fsetxattr_inception(
+    fd: c_int,
+    name: *const c_char,
+    value: *const libc::c_void,
+    size: libc::size_t,
+    flags: c_int,
+) -> c_int {
+    set_xattr(XattrTarget::Fd(fd), name, value, size, flags)
+        .unwrap_or_else(|| crate::syscalls::linux_raw::raw_fsetxattr(fd, name, value, size, flags))
+}
+
+#[no_mangle]
+#[cfg(target_os = "linux")]
+pub unsafe extern "C" fn removexattr_inception(
+    path: *const c_char,
+    name: *const c_char,
+    nofollow: bool,
+) -> c_int {
+    remove_xattr(XattrTarget::Path(path), name)
+        .unwrap_or_else(|| crate::syscalls::linux_raw::raw_removexattr(path, name, nofollow))
+}
+
+#[no_mangle]
+#[cfg(target_os = "linux")]
+pub unsafe extern "C" fn fremovexattr_inception(fd: c_int, name: *const c_char) -> c_int {
+    remove_xattr(XattrTarget::Fd(fd), name)
+        .unwrap_or_else(|| crate::syscalls::linux_raw::raw_fremovexattr(fd, name))
+}
+
 // RFC-0047: Timestamp Modification Protection
 
 /// utimensat_inception: Block timestamp modifications on VFS files (at variant)
@@ -1714,70 +1957,45 @@ pub unsafe extern "C" fn symlink_inception(p1: *const c_char, p2: *const c_char)
 }
 
 #[no_mangle]
-#[cfg(target_os = "macos")]
-pub unsafe extern "C" fn execve_inception(
-    path: *const c_char,
-    argv: *const *const c_char,
-    envp: *const *const c_char,
-) -> c_int {
-    libc::execve(path, argv, envp)
-}
-
-#[no_mangle]
-#[cfg(target_os = "macos")]
-pub unsafe extern "C" fn posix_spawn_inception(
-    pid: *mut libc::pid_t,
-    path: *const c_char,
-    fa: *const c_void,
-    attr: *const c_void,
-    argv: *const *const c_char,
-    envp: *const *const c_char,
-) -> c_int {
-    libc::posix_spawn(
-        pid,
-        path,
-        fa as *const libc::posix_spawn_file_actions_t,
-        attr as *const libc::posix_spawnattr_t,
-        argv as *const *mut c_char,
-        envp as *const *mut c_char,
-    )
-}
-
-#[no_mangle]
-#[cfg(target_os = "macos")]
-pub unsafe extern "C" fn posix_spawnp_inception(
-    pid: *mut libc::pid_t,
-    file: *const c_char,
-    fa: *const c_void,
-    attr: *const c_void,
-    argv: *const *const c_char,
-    envp: *const *const c_char,
-) -> c_int {
-    libc::posix_spawnp(
-        pid,
-        file,
-        fa as *const libc::posix_spawn_file_actions_t,
-        attr as *const libc::posix_spawnattr_t,
-        argv as *const *mut c_char,
-        envp as *const *mut c_char,
-    )
-}
-
-#[no_mangle]
-#[cfg(target_os = "macos")]
 pub unsafe extern "C" fn faccessat_inception(
     dirfd: c_int,
     path: *const c_char,
     mode: c_int,
     flags: c_int,
 ) -> c_int {
-    libc::faccessat(dirfd, path, mode, flags)
+    #[cfg(target_os = "macos")]
+    let real = |d, p, m, f| libc::faccessat(d, p, m, f);
+    #[cfg(target_os = "linux")]
+    let real = |d, p, m, f| crate::syscalls::linux_raw::raw_faccessat(d, p, m, f);
+    passthrough_if_init!(real, dirfd, path, mode, flags);
+
+    if path.is_null() {
+        return real(dirfd, path, mode, flags);
+    }
+    let Some(_guard) = InceptionLayerGuard::enter() else {
+        return real(dirfd, path, mode, flags);
+    };
+    // The manifest's mode decides, whatever the stub on disk says
+    let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+    let res = crate::path::resolve_path_at(dirfd, path, &mut abs)
+        .and_then(|len| std::str::from_utf8(&abs[..len]).ok())
+        .and_then(|p| {
+            crate::syscalls::stat::access_impl_common(
+                p,
+                mode,
+                flags & libc::AT_SYMLINK_NOFOLLOW == 0,
+                flags & libc::AT_EACCESS != 0,
+            )
+        });
+    if let Some(res) = res {
+        return res;
+    }
+    real(dirfd, path, mode, flags)
 }
 
 /// fcntl implementation called from C bridge (variadic_inception.c)
 #[no_mangle]
 #[cfg(target_os = "macos")]
 pub unsafe extern "C" fn velo_fcntl_impl(fd: c_int, cmd: c_int, arg: libc::c_long) -> c_int {
-    // Simple passthrough for now - fcntl doesn't need VFS virtualization
-    libc::fcntl(fd, cmd, arg)
+    crate::syscalls::io::fcntl_inception(fd, cmd, arg)
 }
@@ -14,6 +14,7 @@ pub mod path_ops;
 pub mod process;
 pub mod stat;
 pub mod vfs_ops;
+pub mod vstat;
 
 // Re-export specific inception layers that need to be visible to interpose or extern C
 #[cfg(target_os = "macos")]
@@ -1,3 +1,4 @@
+use crate::budget::BudgetClass;
 use crate::state::*;
 use libc::{c_char, c_int, c_void, mode_t};
 use std::ffi::CStr;
@@ -15,16 +16,16 @@ pub(crate) unsafe fn open_impl(path: *const c_char, flags: c_int, mode: mode_t)
         return None;
     }
 
-    let path_cstr = CStr::from_ptr(path);
-    let path_str = match path_cstr.to_str() {
-        Ok(s) => s,
-        Err(_) => return None,
-    };
+    let path_arg = crate::path::path_arg(path);
+    // Non-UTF-8 names resolve through their escaped key, but the escaped
+    // string is not a real on-disk path, so they never hydrate
+    let path_is_utf8 = matches!(path_arg, std::borrow::Cow::Borrowed(_));
+    let path_str: &str = &path_arg;
 
     let state = InceptionLayerState::get()?;
 
-    let vpath = match state.resolve_path(path_str) {
-        Some(p) => {
+    let vpath = match state.resolve_path_checked(path_str) {
+        Ok(Some(p)) => {
             inception_log!(
                 "open path='{}' -> resolved='{}' (HIT)",
                 path_str,
@@ -33,18 +34,43 @@ pub(crate) unsafe fn open_impl(path: *const c_char, flags: c_int, mode: mode_t)
             inception_record!(EventType::OpenHit, p.manifest_key_hash, 0);
             p
         }
-        None => return None,
+        Ok(None) => return None,
+        Err(errno) => {
+            crate::set_errno(errno);
+            return Some(-1);
+        }
     };
 
-    let entry = match state.query_manifest_ipc(&vpath) {
-        Some(e) => {
+    // Only read-only opens may degrade to passthrough when vDird is slow
+    let is_write = (flags & (libc::O_WRONLY | libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC)) != 0;
+    let (class, heat_op) = if is_write {
+        (BudgetClass::Mutate, crate::heat::HeatOp::OpenWrite)
+    } else {
+        (BudgetClass::Open, crate::heat::HeatOp::Open)
+    };
+    crate::heat::record(&vpath.manifest_key, heat_op);
+    // A tombstone hides the file on disk: gone unless this open creates it
+    // again, and then without the old contents
+    let (found, flags) = match state.query_manifest_ipc(&vpath, class) {
+        Some((e, _)) if e.is_whiteout() => {
+            if flags & libc::O_CREAT == 0 {
+                inception_log!("manifest lookup '{}': DELETED", vpath.manifest_key);
+                crate::set_errno(libc::ENOENT);
+                return Some(-1);
+            }
+            (None, flags | libc::O_TRUNC)
+        }
+        found => (found, flags),
+    };
+    let (entry, ino) = match found {
+        Some((e, ino)) => {
             inception_log!(
                 "manifest lookup '{}': FOUND (mode=0o{:o}, size={})",
                 vpath.manifest_key,
                 e.mode,
                 e.size
             );
-            e
+            (e, ino)
         }
         None => {
             // RFC-0039 Solid Mode: Allow new file creation in VFS territory
@@ -61,6 +87,10 @@ pub(crate) unsafe fn open_impl(path: *const c_char, flags: c_int, mode: mode_t)
             inception_record!(EventType::OpenMiss, vpath.manifest_key_hash, 0);
 
             let fd = unsafe { raw_open(path, flags, mode) };
+            if fd >= 0 && flags & libc::O_DIRECTORY != 0 {
+                crate::syscalls::io::track_dir_fd(fd, &vpath);
+                return Some(fd);
+            }
             if fd >= 0 {
                 // Track FD for Live Ingest on close() - especially important for writes
                 crate::syscalls::io::track_fd(
@@ -76,18 +106,58 @@ pub(crate) unsafe fn open_impl(path: *const c_char, flags: c_int, mode: mode_t)
         }
     };
 
-    let hash_hex = hex_encode(&entry.content_hash);
-    let blob_path = format!(
-        "{}/blake3/{}/{}/{}_{}.bin",
-        state.cas_root,
-        &hash_hex[0..2],
-        &hash_hex[2..4],
-        hash_hex,
-        entry.size
-    );
+    if entry.is_dir() {
+        // No blob behind a directory: open the real one and remember where
+        // it is for dirfd-relative lookups
+        #[allow(unused_mut)]
+        let mut fd = unsafe { raw_open(path, flags, mode) };
+        // Manifest-only: an empty stand-in that getdents64 fills from the
+        // manifest
+        #[cfg(target_os = "linux")]
+        if fd < 0
+            && crate::get_errno() == libc::ENOENT
+            && flags & libc::O_ACCMODE == libc::O_RDONLY
+            && flags & libc::O_CREAT == 0
+        {
+            fd = open_empty_dir(state, flags);
+            if fd < 0 {
+                crate::set_errno(libc::ENOENT);
+            }
+        }
+        if fd >= 0 {
+            crate::syscalls::io::track_dir_fd(fd, &vpath);
+            return Some(fd);
+        }
+        return None;
+    }
+
+    let blob_path = blob_path(state, &entry);
 
     inception_log!("redirection path: '{}'", blob_path);
 
+    // VRIFT_HYDRATE: replace the on-disk stub with the blob once, then the
+    // path is an ordinary real file (writes go straight to it, no COW)
+    if path_is_utf8
+        && crate::hydrate::should_hydrate(vpath.manifest_key.as_str())
+        && crate::hydrate::materialize(vpath.absolute.as_str(), &blob_path, &entry)
+    {
+        inception_log!("HYDRATED: '{}'", vpath.absolute);
+        inception_record!(EventType::Hydrated, vpath.manifest_key_hash, 0);
+
+        let fd = unsafe { raw_open(path, flags, mode) };
+        if fd >= 0 {
+            crate::syscalls::io::track_fd(
+                fd,
+                &vpath.manifest_key,
+                true,
+                None,
+                vpath.manifest_key_hash,
+            );
+            return Some(fd);
+        }
+        return None;
+    }
+
     let is_write = (flags & (libc::O_WRONLY | libc::O_RDWR | libc::O_APPEND | libc::O_TRUNC)) != 0;
 
     if is_write {
@@ -96,102 +166,28 @@ pub(crate) unsafe fn open_impl(path: *const c_char, flags: c_int, mode: mode_t)
         // M4: Mark path as dirty in DirtyTracker (enables stat redirect to staging)
         DIRTY_TRACKER.mark_dirty(&vpath.manifest_key);
 
-        let mut attempts = 0;
-        let mut fd = -1;
-        let mut temp_path_fs = FixedString::<1024>::new();
-        let pid = unsafe { libc::getpid() };
-        let tid_addr = &attempts as *const _ as usize;
-
-        while attempts < 100 {
-            let timestamp = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_nanos();
-
-            let mut buf = [0u8; 1024];
-            let mut writer = crate::macros::StackWriter::new(&mut buf);
-            let _ = write!(
-                writer,
-                "{}/.vrift/staging/vrift_cow_{}_{}_{}_{}.tmp",
-                state.project_root.as_str(),
-                pid,
-                timestamp,
-                tid_addr,
-                attempts
-            );
-            temp_path_fs.set(writer.as_str());
-
-            let c_temp = match std::ffi::CString::new(temp_path_fs.as_str()) {
-                Ok(c) => c,
-                Err(_) => break,
-            };
-            fd = unsafe {
-                libc::open(
-                    c_temp.as_ptr(),
-                    libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC,
-                    0o600,
-                )
-            };
-            if fd >= 0 {
-                break;
-            }
-            if unsafe { crate::get_errno() } != libc::EEXIST {
-                break;
-            }
-            attempts += 1;
-        }
-
-        if fd < 0 {
-            return None;
-        }
-        let temp_fd = fd;
-        let temp_path = temp_path_fs;
-        unsafe { libc::close(temp_fd) };
+        // Every writer of this vpath shares one copy
+        let (mut sessions, temp_path) = unsafe { join_cow_session(state, &vpath, &entry) }?;
         let temp_cpath = std::ffi::CString::new(temp_path.as_str()).ok()?;
 
-        inception_log!("COW TRIGGERED: '{}' -> '{}'", vpath.absolute, temp_path);
-        inception_record!(EventType::CowTriggered, vpath.manifest_key_hash, 0);
-
-        let blob_cpath = std::ffi::CString::new(blob_path.as_str()).ok()?;
-        let src_fd = unsafe { libc::open(blob_cpath.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC) };
-        if src_fd >= 0 {
-            let dst_fd = unsafe {
-                libc::open(
-                    temp_cpath.as_ptr(),
-                    libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
-                    0o644,
-                )
-            };
-            if dst_fd >= 0 {
-                let mut buf = [0u8; 8192];
-                loop {
-                    let n =
-                        unsafe { libc::read(src_fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
-                    if n <= 0 {
-                        break;
-                    }
-                    unsafe { libc::write(dst_fd, buf.as_ptr() as *const c_void, n as usize) };
-                }
-                unsafe { libc::close(dst_fd) };
-            }
-            unsafe { libc::close(src_fd) };
-        } else {
-            let dst_fd = unsafe {
-                libc::open(
-                    temp_cpath.as_ptr(),
-                    libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
-                    0o644,
-                )
-            };
-            if dst_fd >= 0 {
-                unsafe { libc::close(dst_fd) };
-            }
-        }
-
         let fd = unsafe { libc::open(temp_cpath.as_ptr(), flags, mode as libc::c_uint) };
         if fd < 0 {
+            // Nobody else holds a session we just started: discard it
+            if sessions
+                .get(&vpath.manifest_key_hash)
+                .is_some_and(|s| s.refs == 0)
+            {
+                sessions.remove(&vpath.manifest_key_hash);
+                unsafe { libc::unlink(temp_cpath.as_ptr()) };
+            }
             None
         } else {
+            if let Some(session) = sessions.get_mut(&vpath.manifest_key_hash) {
+                session.refs += 1;
+            }
+            drop(sessions);
+            crate::summary::add(&crate::summary::FILES_SERVED, 1);
+
             // Allocate entry manually for lock-free insertion
             let entry = Box::into_raw(Box::new(crate::syscalls::io::FdEntry {
                 vpath: vpath.absolute,
@@ -199,6 +195,7 @@ pub(crate) unsafe fn open_impl(path: *const c_char, flags: c_int, mode: mode_t)
                 manifest_key_hash: vpath.manifest_key_hash,
                 temp_path,
                 is_vfs: true,
+                is_dir: false,
                 cached_stat: None,
                 mmap_count: 0,
                 lock_fd: -1,
@@ -214,44 +211,230 @@ pub(crate) unsafe fn open_impl(path: *const c_char, flags: c_int, mode: mode_t)
             Some(fd)
         }
     } else {
-        let blob_cpath = std::ffi::CString::new(blob_path.as_str()).ok()?;
-        let fd = unsafe { libc::open(blob_cpath.as_ptr(), flags, mode as libc::c_uint) };
+        let fd = unsafe { crate::chunked::open_blob(&blob_path, flags) };
         if fd >= 0 {
+            crate::summary::add(&crate::summary::FILES_SERVED, 1);
+            crate::summary::add(&crate::summary::CAS_BYTES, entry.size);
             // 🔥 Build and cache stat for VFS file
-            let mut cached_stat: libc::stat = unsafe { std::mem::zeroed() };
-            cached_stat.st_size = entry.size as _;
-            cached_stat.st_mode = entry.mode as _;
-            cached_stat.st_mtime = entry.mtime as _;
-            cached_stat.st_dev = 0x52494654; // "RIFT"
-            cached_stat.st_nlink = 1;
-            cached_stat.st_ino = vpath.manifest_key_hash as _;
-
-            crate::syscalls::io::track_fd(
-                fd,
-                &vpath.manifest_key,
-                true,
-                Some(cached_stat),
-                vpath.manifest_key_hash,
+            let cached_stat = crate::syscalls::vstat::make_stat(
+                &crate::syscalls::vstat::VStat::from_vnode(&entry, ino),
             );
+
+            crate::syscalls::io::track_blob_fd(fd, &vpath, cached_stat);
             Some(fd)
         } else {
+            if crate::get_errno() == libc::ENOENT {
+                crate::warnings::warn_user(crate::warnings::Warning::CasBlobMissing, &blob_path);
+            }
             None
         }
     }
 }
 
+/// Path of the CAS blob holding `entry`'s content
+pub(crate) fn blob_path(state: &InceptionLayerState, entry: &vrift_ipc::VnodeEntry) -> String {
+    let mut path = String::new();
+    let _ = write_blob_path(&mut path, state.cas_root.as_str(), entry);
+    path
+}
+
+/// `blob_path` into any writer, e.g. a `StackWriter` where allocating is
+/// not allowed
+pub(crate) fn write_blob_path(
+    w: &mut impl std::fmt::Write,
+    cas_root: &str,
+    entry: &vrift_ipc::VnodeEntry,
+) -> std::fmt::Result {
+    let hex = hex_encode(&entry.content_hash);
+    let hex = std::str::from_utf8(&hex).unwrap_or_default();
+    write!(
+        w,
+        "{}/blake3/{}/{}/{}_{}.bin",
+        cas_root,
+        &hex[0..2],
+        &hex[2..4],
+        hex,
+        entry.size
+    )
+}
+
+/// CoW sessions of a process, while locked
+pub(crate) type CowSessions<'a> = crate::sync::recursive_mutex::RecursiveMutexGuard<
+    'a,
+    std::collections::HashMap<u64, CowSession, IdentityBuildHasher>,
+>;
+
+/// Staging copy of `vpath` shared by its writers, started from `entry`'s
+/// blob if nobody is writing it yet. The new session holds no references.
+/// Returned with the sessions locked, so the caller can take one.
+///
+/// The copy is made without the lock, so writers of other paths (and
+/// closes) don't wait behind a large blob. Two racing openers can each make
+/// one; the second to finish discards its own and joins the first's.
+pub(crate) unsafe fn join_cow_session<'a>(
+    state: &'a InceptionLayerState,
+    vpath: &crate::path::VfsPath,
+    entry: &vrift_ipc::VnodeEntry,
+) -> Option<(CowSessions<'a>, FixedString<1024>)> {
+    {
+        let sessions = state.cow_sessions.lock();
+        if let Some(session) = sessions.get(&vpath.manifest_key_hash) {
+            let temp_path = session.temp_path;
+            return Some((sessions, temp_path));
+        }
+    }
+    let temp_path = unsafe { create_cow_copy(state, vpath, &blob_path(state, entry)) }?;
+
+    let mut sessions = state.cow_sessions.lock();
+    if let Some(session) = sessions.get(&vpath.manifest_key_hash) {
+        if let Ok(c) = std::ffi::CString::new(temp_path.as_str()) {
+            unsafe { libc::unlink(c.as_ptr()) };
+        }
+        let temp_path = session.temp_path;
+        return Some((sessions, temp_path));
+    }
+    crate::summary::add(&crate::summary::CAS_BYTES, entry.size);
+    sessions.insert(
+        vpath.manifest_key_hash,
+        CowSession {
+            vpath: vpath.absolute,
+            temp_path,
+            refs: 0,
+            base_hash: entry.content_hash,
+        },
+    );
+    crate::syscalls::io::register_cow_exit_flush();
+    Some((sessions, temp_path))
+}
+
+/// Copy the blob for `vpath` into a fresh staging file and return its path.
+unsafe fn create_cow_copy(
+    state: &InceptionLayerState,
+    vpath: &crate::path::VfsPath,
+    blob_path: &str,
+) -> Option<FixedString<1024>> {
+    let mut attempts = 0;
+    let mut fd = -1;
+    let mut temp_path_fs = FixedString::<1024>::new();
+    let pid = unsafe { libc::getpid() };
+    let tid_addr = &attempts as *const _ as usize;
+    let mut session_dir_created = false;
+
+    while attempts < 100 {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        let mut buf = [0u8; 1024];
+        let mut writer = crate::macros::StackWriter::new(&mut buf);
+        let _ = write!(writer, "{}/.vrift/staging/", state.project_root.as_str());
+        if !state.session_id.is_empty() {
+            let _ = write!(writer, "{}/", state.session_id.as_str());
+        }
+        let _ = write!(
+            writer,
+            "vrift_cow_{}_{}_{}_{}.tmp",
+            pid, timestamp, tid_addr, attempts
+        );
+        if writer.overflowed() || !temp_path_fs.try_set(writer.as_str()) {
+            break; // Project root too long for a staging path
+        }
+
+        let c_temp = match std::ffi::CString::new(temp_path_fs.as_str()) {
+            Ok(c) => c,
+            Err(_) => break,
+        };
+        fd = unsafe {
+            libc::open(
+                c_temp.as_ptr(),
+                libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC,
+                0o600,
+            )
+        };
+        if fd >= 0 {
+            break;
+        }
+        let errno = unsafe { crate::get_errno() };
+        if errno == libc::ENOENT && !state.session_id.is_empty() && !session_dir_created {
+            // First COW of this session: create .vrift/staging/<session_id>/
+            if let Some(dir) = temp_path_fs.as_str().rsplit_once('/').map(|(d, _)| d) {
+                if let Ok(c_dir) = std::ffi::CString::new(dir) {
+                    unsafe { libc::mkdir(c_dir.as_ptr(), 0o700) };
+                }
+            }
+            session_dir_created = true;
+            continue;
+        }
+        if errno != libc::EEXIST {
+            break;
+        }
+        attempts += 1;
+    }
+
+    if fd < 0 {
+        return None;
+    }
+    let temp_fd = fd;
+    let temp_path = temp_path_fs;
+    unsafe { libc::close(temp_fd) };
+    let temp_cpath = std::ffi::CString::new(temp_path.as_str()).ok()?;
+
+    inception_log!("COW TRIGGERED: '{}' -> '{}'", vpath.absolute, temp_path);
+    inception_record!(EventType::CowTriggered, vpath.manifest_key_hash, 0);
+
+    let src_fd = unsafe { crate::chunked::open_blob(blob_path, libc::O_RDONLY | libc::O_CLOEXEC) };
+    if src_fd >= 0 {
+        let dst_fd = unsafe {
+            libc::open(
+                temp_cpath.as_ptr(),
+                libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+                0o644,
+            )
+        };
+        if dst_fd >= 0 {
+            let mut buf = [0u8; 8192];
+            loop {
+                let n = unsafe { libc::read(src_fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+                if n <= 0 {
+                    break;
+                }
+                unsafe { libc::write(dst_fd, buf.as_ptr() as *const c_void, n as usize) };
+            }
+            unsafe { libc::close(dst_fd) };
+        }
+        unsafe { libc::close(src_fd) };
+    } else {
+        crate::warnings::warn_user(crate::warnings::Warning::CasBlobMissing, blob_path);
+        let dst_fd = unsafe {
+            libc::open(
+                temp_cpath.as_ptr(),
+                libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+                0o644,
+            )
+        };
+        if dst_fd >= 0 {
+            unsafe { libc::close(dst_fd) };
+        }
+    }
+
+    Some(temp_path)
+}
+
 // Called by C bridge (c_open_bridge) after INITIALIZING check passes
 #[no_mangle]
 pub unsafe extern "C" fn velo_open_impl(path: *const c_char, flags: c_int, mode: mode_t) -> c_int {
+    let _span = crate::profile::begin(crate::profile::ProfileOp::Open);
     open_impl(path, flags, mode).unwrap_or_else(|| raw_open(path, flags, mode))
 }
 
-fn hex_encode(hash: &[u8; 32]) -> String {
+/// Lowercase hex of a content hash, on the stack
+pub(crate) fn hex_encode(hash: &[u8; 32]) -> [u8; 64] {
     const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
-    let mut result = String::with_capacity(64);
-    for byte in hash {
-        result.push(HEX_CHARS[(byte >> 4) as usize] as char);
-        result.push(HEX_CHARS[(byte & 0x0f) as usize] as char);
+    let mut result = [0u8; 64];
+    for (i, byte) in hash.iter().enumerate() {
+        result[i * 2] = HEX_CHARS[(byte >> 4) as usize];
+        result[i * 2 + 1] = HEX_CHARS[(byte & 0x0f) as usize];
     }
     result
 }
@@ -282,6 +465,21 @@ pub unsafe extern "C" fn openat_inception(
     c_openat_bridge(dfd, p, f, m)
 }
 
+/// Open `<cas_root>/.empty-dir`, created read-only on first use
+#[cfg(target_os = "linux")]
+unsafe fn open_empty_dir(state: &InceptionLayerState, flags: c_int) -> c_int {
+    let mut buf = [0u8; 1100];
+    let mut w = crate::macros::StackWriter::new(&mut buf);
+    let _ = write!(w, "{}/.empty-dir\0", state.cas_root);
+    let path = w.as_str().as_ptr() as *const c_char;
+    let fd = raw_open(path, flags | libc::O_DIRECTORY, 0);
+    if fd >= 0 || crate::get_errno() != libc::ENOENT {
+        return fd;
+    }
+    crate::syscalls::linux_raw::raw_mkdir(path, 0o555);
+    raw_open(path, flags | libc::O_DIRECTORY, 0)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn open_inception_c_impl(p: *const c_char, f: c_int, m: mode_t) -> c_int {
     #[inline(always)]
@@ -359,6 +557,8 @@ pub unsafe extern "C" fn velo_openat_impl(
     f: c_int,
     m: mode_t,
 ) -> c_int {
+    let _span = crate::profile::begin(crate::profile::ProfileOp::Openat);
+
     #[inline(always)]
     unsafe fn raw_openat_internal(
         dirfd: c_int,
@@ -405,7 +605,21 @@ pub unsafe extern "C" fn velo_openat_impl(
         Some(g) => g,
         None => return raw_openat_internal(dirfd, p, f, m),
     };
-    open_impl(p, f, m).unwrap_or_else(|| raw_openat_internal(dirfd, p, f, m))
+    if dirfd == libc::AT_FDCWD || p.is_null() || *p == b'/' as c_char {
+        return open_impl(p, f, m).unwrap_or_else(|| raw_openat_internal(dirfd, p, f, m));
+    }
+
+    // Relative to a directory fd: open_impl only understands absolute (or
+    // project-relative) paths, so hand it the path dirfd names
+    let mut buf = [0u8; crate::path::VFS_PATH_CAP + 1];
+    match crate::path::resolve_path_at(dirfd, p, &mut buf[..crate::path::VFS_PATH_CAP]) {
+        Some(len) => {
+            buf[len] = 0;
+            open_impl(buf.as_ptr() as *const c_char, f, m)
+        }
+        None => None,
+    }
+    .unwrap_or_else(|| raw_openat_internal(dirfd, p, f, m))
 }
 
 #[cfg(target_os = "linux")]
@@ -8,6 +8,8 @@ pub unsafe extern "C" fn velo_readlink_impl(
     buf: *mut c_char,
     bufsiz: size_t,
 ) -> ssize_t {
+    let _span = crate::profile::begin(crate::profile::ProfileOp::Readlink);
+
     // Use raw syscall for fallback to avoid dlsym deadlock (Pattern 2682.v2)
     let _guard = match InceptionLayerGuard::enter() {
         Some(g) => g,
@@ -19,12 +21,73 @@ pub unsafe extern "C" fn velo_readlink_impl(
         }
     };
 
+    if !path.is_null() {
+        if let Some(n) = manifest_readlink(&crate::path::path_arg(path), buf, bufsiz) {
+            return n;
+        }
+    }
+
     #[cfg(target_os = "macos")]
     return crate::syscalls::macos_raw::raw_readlink(path, buf, bufsiz);
     #[cfg(target_os = "linux")]
     return crate::syscalls::linux_raw::raw_readlink(path, buf, bufsiz);
 }
 
+/// readlink() of a manifest symlink. The target is the link's CAS blob
+/// (`VnodeEntry::new_symlink`), so the link needn't exist on disk. Links
+/// without a blob, like ones created during the session, are real links
+/// and are left to the kernel (`None`), as is every non-symlink entry.
+pub(crate) unsafe fn manifest_readlink(
+    path_str: &str,
+    buf: *mut c_char,
+    bufsiz: size_t,
+) -> Option<ssize_t> {
+    let state = InceptionLayerState::get()?;
+    let vpath = state.resolve_path(path_str)?;
+    let (hash, size, mode, symlink_flag) =
+        match vdir_lookup(state.mmap_ptr, state.mmap_size, vpath.manifest_key.as_str()) {
+            Some(e) => (
+                e.cas_hash,
+                e.size,
+                e.mode,
+                e.flags & vrift_ipc::vdir_types::FLAG_SYMLINK != 0,
+            ),
+            None => {
+                let e = state.query_manifest(&vpath, crate::budget::BudgetClass::Stat)?;
+                (e.content_hash, e.size, e.mode, e.is_symlink())
+            }
+        };
+    #[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS
+    let is_link = symlink_flag || mode & libc::S_IFMT as u32 == libc::S_IFLNK as u32;
+    if !is_link || hash == [0u8; 32] {
+        return None;
+    }
+
+    let fd = crate::chunked::open_cas_blob(state.cas_root.as_str(), &hash, size);
+    if fd < 0 {
+        return None;
+    }
+    // Like the kernel: at most bufsiz bytes, no terminating NUL
+    let want = size.min(bufsiz as u64) as usize;
+    let mut got = 0usize;
+    while got < want {
+        #[cfg(target_os = "macos")]
+        let n = crate::syscalls::macos_raw::raw_read(fd, buf.add(got) as *mut _, want - got);
+        #[cfg(target_os = "linux")]
+        let n = crate::syscalls::linux_raw::raw_read(fd, buf.add(got) as *mut _, want - got);
+        if n <= 0 {
+            break;
+        }
+        got += n as usize;
+    }
+    #[cfg(target_os = "macos")]
+    crate::syscalls::macos_raw::raw_close(fd);
+    #[cfg(target_os = "linux")]
+    crate::syscalls::linux_raw::raw_close(fd);
+    inception_log!("readlink from manifest: '{}'", vpath.absolute);
+    Some(got as ssize_t)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn readlink_inception(
     path: *const c_char,
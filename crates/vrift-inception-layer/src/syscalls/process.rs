@@ -1 +1,356 @@
+// =============================================================================
+// process.rs — execve / posix_spawn: run VFS binaries, keep children shimmed
+// =============================================================================
+//
+// The kernel opens an exec target itself, so a manifest file that isn't a
+// runnable file on disk (a hydrate stub, a file removed from a Solid
+// project, a tier-1 link to a read-only blob) can be stat'ed and read
+// through the layer and still fail to exec. Such a target is copied once
+// into a content-addressed exec cache,
+//     <project>/.vrift/exec/<hash>/<basename>
+// and exec'd from there; argv[0] follows when it named the original path.
+// The basename is kept so multi-call binaries still dispatch on it.
+//
+// Children must stay inside the VFS too. Build tools routinely exec with a
+// trimmed environment (env -i, env_clear()), so layer variables (VRIFT_*,
+// VR_*) missing from the child's envp are copied from ours, and the shim is
+// put back at the front of LD_PRELOAD / DYLD_INSERT_LIBRARIES if the caller
+// dropped it. Values the caller did set are left alone.
+//
+// Nothing is allocated unless a target is hydrated or the environment has
+// to be rebuilt. posix_spawnp only hydrates names containing a '/'; a bare
+// name is searched in PATH by libc as usual.
+//
+// Hydrating asks vDird and allocates, which a vfork() child must not do:
+// it shares the parent's heap and the parent's thread may be suspended
+// holding its locks. vfork() is therefore run as fork(), so execve() after
+// it gets the full treatment. A child that shares our memory anyway
+// (a raw clone(CLONE_VM)) is detected and its exec passed through as is.
+// =============================================================================
 
+use std::ffi::{CStr, CString};
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+
+use libc::{c_char, c_int, c_void};
+
+use crate::state::{FixedString, InceptionLayerGuard, InceptionLayerState};
+
+#[cfg(target_os = "linux")]
+use crate::syscalls::linux_raw as raw;
+#[cfg(target_os = "macos")]
+use crate::syscalls::macos_raw as raw;
+
+#[cfg(target_os = "linux")]
+const PRELOAD_VAR: &[u8] = b"LD_PRELOAD";
+#[cfg(target_os = "macos")]
+const PRELOAD_VAR: &[u8] = b"DYLD_INSERT_LIBRARIES";
+
+type ExecveFn =
+    unsafe extern "C" fn(*const c_char, *const *const c_char, *const *const c_char) -> c_int;
+type SpawnFn = unsafe extern "C" fn(
+    *mut libc::pid_t,
+    *const c_char,
+    *const c_void,
+    *const c_void,
+    *const *const c_char,
+    *const *const c_char,
+) -> c_int;
+
+/// This library's path as the loader opened it
+static SHIM_PATH: OnceLock<FixedString<1024>> = OnceLock::new();
+
+fn shim_path() -> &'static [u8] {
+    SHIM_PATH
+        .get_or_init(|| {
+            let mut fs = FixedString::new();
+            unsafe {
+                let mut info: libc::Dl_info = std::mem::zeroed();
+                if libc::dladdr(shim_path as *const c_void, &mut info) != 0
+                    && !info.dli_fname.is_null()
+                {
+                    let _ = fs.try_set(CStr::from_ptr(info.dli_fname).to_str().unwrap_or(""));
+                }
+            }
+            fs
+        })
+        .as_str()
+        .as_bytes()
+}
+
+unsafe fn own_environ() -> *const *const c_char {
+    #[cfg(target_os = "linux")]
+    {
+        extern "C" {
+            static environ: *const *const c_char;
+        }
+        environ
+    }
+    #[cfg(target_os = "macos")]
+    {
+        *libc::_NSGetEnviron() as *const *const c_char
+    }
+}
+
+/// Pointers of a NULL-terminated `char *[]` (none for a NULL array)
+unsafe fn ptrs(list: *const *const c_char) -> impl Iterator<Item = *const c_char> {
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        if list.is_null() {
+            return None;
+        }
+        let p = *list.add(i);
+        if p.is_null() {
+            return None;
+        }
+        i += 1;
+        Some(p)
+    })
+}
+
+unsafe fn bytes<'a>(p: *const c_char) -> &'a [u8] {
+    CStr::from_ptr(p).to_bytes()
+}
+
+fn var_name(entry: &[u8]) -> &[u8] {
+    entry.split(|&b| b == b'=').next().unwrap_or(entry)
+}
+
+/// Value of `name` in an envp array
+unsafe fn lookup<'a>(envp: *const *const c_char, name: &[u8]) -> Option<&'a [u8]> {
+    ptrs(envp)
+        .map(|p| bytes(p))
+        .find(|e| var_name(e) == name)
+        .map(|e| e.get(name.len() + 1..).unwrap_or_default())
+}
+
+/// Variables the layer is configured through
+fn is_layer_var(name: &[u8]) -> bool {
+    name.starts_with(b"VRIFT_")
+        || name.starts_with(b"VR_")
+        || (cfg!(target_os = "macos") && name == b"DYLD_FORCE_FLAT_NAMESPACE")
+}
+
+/// Whether a preload list (':' or ' ' separated) names the shim
+fn lists_shim(value: &[u8], shim: &[u8]) -> bool {
+    value.split(|&b| b == b':' || b == b' ').any(|p| p == shim)
+}
+
+/// The child's envp: the caller's plus whatever it dropped that the layer
+/// needs, or None when nothing is missing
+unsafe fn child_env(
+    envp: *const *const c_char,
+    strings: &mut Vec<CString>,
+) -> Option<Vec<*const c_char>> {
+    let shim = shim_path();
+    let preload = lookup(envp, PRELOAD_VAR);
+    let preload_ok = shim.is_empty() || preload.is_some_and(|v| lists_shim(v, shim));
+    let missing = |p: *const c_char| {
+        let name = var_name(bytes(p));
+        is_layer_var(name) && lookup(envp, name).is_none()
+    };
+    if preload_ok && !ptrs(own_environ()).any(missing) {
+        return None;
+    }
+
+    let mut out: Vec<*const c_char> = ptrs(envp)
+        .filter(|&p| preload_ok || var_name(bytes(p)) != PRELOAD_VAR)
+        .collect();
+    out.extend(ptrs(own_environ()).filter(|&p| missing(p)));
+    if !preload_ok {
+        let mut var = PRELOAD_VAR.to_vec();
+        var.push(b'=');
+        var.extend_from_slice(shim);
+        if let Some(rest) = preload.filter(|v| !v.is_empty()) {
+            var.push(b':');
+            var.extend_from_slice(rest);
+        }
+        if let Ok(var) = CString::new(var) {
+            out.push(var.as_ptr());
+            strings.push(var);
+        }
+    }
+    out.push(std::ptr::null());
+    Some(out)
+}
+
+/// Exec-cache copy of `path` when it names a manifest file the kernel can't
+/// run from disk
+unsafe fn exec_target(path: *const c_char) -> Option<CString> {
+    let _guard = InceptionLayerGuard::enter()?;
+    let state = InceptionLayerState::get()?;
+    let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+    let len = crate::path::resolve_path_at(libc::AT_FDCWD, path, &mut abs)?;
+    let path_str = std::str::from_utf8(&abs[..len]).ok()?;
+    let vpath = state.resolve_path(path_str)?;
+    let entry = state.query_manifest(&vpath, crate::budget::BudgetClass::Open)?;
+    #[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS
+    let (kind, dir, link) = (
+        entry.mode & libc::S_IFMT as u32,
+        libc::S_IFDIR as u32,
+        libc::S_IFLNK as u32,
+    );
+    if entry.is_dir() || kind == dir || kind == link || entry.mode & 0o111 == 0 {
+        return None;
+    }
+
+    let mut st: libc::stat = std::mem::zeroed();
+    if raw::raw_stat(path, &mut st) == 0
+        && (st.st_mode & libc::S_IFMT) == libc::S_IFREG
+        && (st.st_size > 0 || entry.size == 0)
+        && st.st_mode & 0o111 != 0
+    {
+        return None; // Runnable as it is
+    }
+
+    let hex = crate::syscalls::open::hex_encode(&entry.content_hash);
+    let hex = std::str::from_utf8(&hex).ok()?;
+    let name = path_str.rsplit('/').next().filter(|n| !n.is_empty())?;
+
+    let mut blob_buf = [0u8; 1200];
+    let mut blob = crate::macros::StackWriter::new(&mut blob_buf);
+    let _ = crate::syscalls::open::write_blob_path(&mut blob, state.cas_root.as_str(), &entry);
+    let mut cache_buf = [0u8; 1200];
+    let mut cache = crate::macros::StackWriter::new(&mut cache_buf);
+    let _ = write!(cache, "{}/.vrift/exec/{}/{}", state.project_root, hex, name);
+    if blob.overflowed() || cache.overflowed() {
+        return None;
+    }
+    if !crate::hydrate::materialize(cache.as_str(), blob.as_str(), &entry) {
+        return None;
+    }
+    inception_log!(
+        "exec from cache: '{}' -> '{}'",
+        vpath.absolute,
+        cache.as_str()
+    );
+    CString::new(cache.as_str()).ok()
+}
+
+/// Replacements for the arguments of an exec call; None keeps the caller's
+#[derive(Default)]
+struct ExecArgs {
+    path: Option<CString>,
+    argv: Option<Vec<*const c_char>>,
+    envp: Option<Vec<*const c_char>>,
+    strings: Vec<CString>,
+}
+
+impl ExecArgs {
+    unsafe fn prepare(
+        path: *const c_char,
+        argv: *const *const c_char,
+        envp: *const *const c_char,
+        hydrate: bool,
+    ) -> Self {
+        let mut args = Self::default();
+        if path.is_null()
+            || crate::state::INITIALIZING.load(Ordering::Relaxed)
+                >= crate::state::InceptionState::EarlyInit as u8
+            || crate::state::CIRCUIT_TRIPPED.load(Ordering::Relaxed)
+            || crate::state::in_shared_vm_child()
+        {
+            return args;
+        }
+
+        if let Some(target) = hydrate.then(|| exec_target(path)).flatten() {
+            let argv0 = ptrs(argv).next();
+            if argv0.is_some_and(|a| bytes(a) == bytes(path)) {
+                let mut list: Vec<*const c_char> = ptrs(argv).collect();
+                list[0] = target.as_ptr();
+                list.push(std::ptr::null());
+                args.argv = Some(list);
+            }
+            args.path = Some(target);
+        }
+        args.envp = child_env(envp, &mut args.strings);
+        args
+    }
+
+    fn path(&self, orig: *const c_char) -> *const c_char {
+        self.path.as_ref().map_or(orig, |p| p.as_ptr())
+    }
+
+    fn argv(&self, orig: *const *const c_char) -> *const *const c_char {
+        self.argv.as_ref().map_or(orig, |v| v.as_ptr())
+    }
+
+    fn envp(&self, orig: *const *const c_char) -> *const *const c_char {
+        self.envp.as_ref().map_or(orig, |v| v.as_ptr())
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn execve_inception(
+    path: *const c_char,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    let real = crate::reals::REAL_EXECVE.get();
+    if real.is_null() {
+        crate::set_errno(libc::ENOSYS);
+        return -1;
+    }
+    let real: ExecveFn = std::mem::transmute(real);
+    let args = ExecArgs::prepare(path, argv, envp, true);
+    real(args.path(path), args.argv(argv), args.envp(envp))
+}
+
+/// vfork() as fork(): the child gets its own memory, so the execve() it
+/// is about to make may hydrate the target and rebuild the environment
+#[no_mangle]
+pub unsafe extern "C" fn vfork_inception() -> libc::pid_t {
+    libc::fork()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn posix_spawn_inception(
+    pid: *mut libc::pid_t,
+    path: *const c_char,
+    fa: *const c_void,
+    attr: *const c_void,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    let real = crate::reals::REAL_POSIX_SPAWN.get();
+    if real.is_null() {
+        return libc::ENOSYS;
+    }
+    let real: SpawnFn = std::mem::transmute(real);
+    let args = ExecArgs::prepare(path, argv, envp, true);
+    real(
+        pid,
+        args.path(path),
+        fa,
+        attr,
+        args.argv(argv),
+        args.envp(envp),
+    )
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn posix_spawnp_inception(
+    pid: *mut libc::pid_t,
+    file: *const c_char,
+    fa: *const c_void,
+    attr: *const c_void,
+    argv: *const *const c_char,
+    envp: *const *const c_char,
+) -> c_int {
+    let real = crate::reals::REAL_POSIX_SPAWNP.get();
+    if real.is_null() {
+        return libc::ENOSYS;
+    }
+    let real: SpawnFn = std::mem::transmute(real);
+    let has_slash = !file.is_null() && bytes(file).contains(&b'/');
+    let args = ExecArgs::prepare(file, argv, envp, has_slash);
+    real(
+        pid,
+        args.path(file),
+        fa,
+        attr,
+        args.argv(argv),
+        args.envp(envp),
+    )
+}
@@ -1,6 +1,8 @@
+use crate::budget::BudgetClass;
 #[allow(unused_imports)]
 use crate::reals::*;
 use crate::state::*;
+use crate::syscalls::vstat::{self, VStat};
 use libc::{c_char, c_int, stat as libc_stat};
 use std::ffi::CStr;
 use std::sync::atomic::Ordering;
@@ -46,7 +48,13 @@ unsafe fn stat_impl_common(path_str: &str, buf: *mut libc_stat) -> Option<c_int>
     let state = InceptionLayerState::get()?;
 
     // 1. Resolve path to VFS domain
-    let vpath = state.resolve_path(path_str)?;
+    let vpath = match state.resolve_path_checked(path_str) {
+        Ok(vpath) => vpath?,
+        Err(errno) => {
+            crate::set_errno(errno);
+            return Some(-1);
+        }
+    };
 
     let manifest_path = vpath.manifest_key.as_str();
 
@@ -66,11 +74,9 @@ unsafe fn stat_impl_common(path_str: &str, buf: *mut libc_stat) -> Option<c_int>
             let res = unsafe { crate::syscalls::linux_raw::raw_stat(temp_path_cstr.as_ptr(), buf) };
 
             if res == 0 {
-                // Virtualize the dev/ino to match VFS expectations
-                unsafe {
-                    (*buf).st_dev = 0x52494654; // "RIFT"
-                    (*buf).st_ino = vpath.manifest_key_hash as _;
-                }
+                // Live size/mtime from the temp file, virtual dev/ino
+                let ino = state.virtual_ino(manifest_path, vpath.manifest_key_hash);
+                unsafe { vstat::set_identity(buf, ino) };
                 inception_record!(EventType::StatHit, vpath.manifest_key_hash, 10); // 10 = dirty_hit (temp file stat)
                 return Some(0);
             }
@@ -80,46 +86,28 @@ unsafe fn stat_impl_common(path_str: &str, buf: *mut libc_stat) -> Option<c_int>
     } else {
         // Try Hot Stat Cache — Phase 1.3: seqlock-protected VDir lookup
         if let Some(entry) = vdir_lookup(state.mmap_ptr, state.mmap_size, manifest_path) {
-            inception_record!(EventType::StatHit, vpath.manifest_key_hash, 11); // 11 = vdir_hit (seqlock)
-            std::ptr::write_bytes(buf, 0, 1);
-            (*buf).st_size = entry.size as _;
-            #[cfg(target_os = "macos")]
-            {
-                (*buf).st_mode = entry.mode as u16;
-                (*buf).st_mtime = entry.mtime_sec as _;
+            if entry.is_deleted() {
+                crate::set_errno(libc::ENOENT);
+                return Some(-1);
             }
-            #[cfg(target_os = "linux")]
-            {
-                (*buf).st_mode = entry.mode as _;
-                (*buf).st_mtime = entry.mtime_sec as _;
-            }
-            (*buf).st_dev = 0x52494654; // "RIFT"
-            (*buf).st_nlink = 1;
-            (*buf).st_ino = vpath.manifest_key_hash as _;
+            inception_record!(EventType::StatHit, vpath.manifest_key_hash, 11); // 11 = vdir_hit (seqlock)
+            crate::heat::record(manifest_path, crate::heat::HeatOp::Stat);
+            vstat::fill_stat(buf, &VStat::from_vdir(&entry, vpath.manifest_key_hash));
             // duplicate record removed — line 83 already records the vdir_hit
             return Some(0);
         }
     }
 
     inception_record!(EventType::StatMiss, vpath.manifest_key_hash, 20); // 20 = vdir_miss, trying IPC
+    crate::heat::record(manifest_path, crate::heat::HeatOp::StatMiss);
 
     // Try IPC query (also use manifest path format)
-    if let Some(entry) = state.query_manifest(&vpath) {
-        std::ptr::write_bytes(buf, 0, 1);
-        (*buf).st_size = entry.size as _;
-        #[cfg(target_os = "macos")]
-        {
-            (*buf).st_mode = entry.mode as u16;
-            (*buf).st_mtime = entry.mtime as _;
+    if let Ok(Some((entry, ino))) = state.lookup_manifest_ino(&vpath, BudgetClass::Stat) {
+        if entry.is_whiteout() {
+            crate::set_errno(libc::ENOENT);
+            return Some(-1);
         }
-        #[cfg(target_os = "linux")]
-        {
-            (*buf).st_mode = entry.mode as _;
-            (*buf).st_mtime = entry.mtime as _;
-        }
-        (*buf).st_dev = 0x52494654; // "RIFT"
-        (*buf).st_nlink = 1;
-        (*buf).st_ino = vpath.manifest_key_hash as _;
+        vstat::fill_stat(buf, &VStat::from_vnode(&entry, ino));
         inception_record!(EventType::StatHit, vpath.manifest_key_hash, 12); // 12 = ipc_hit
         return Some(0);
     }
@@ -133,24 +121,85 @@ unsafe fn stat_impl_common(path_str: &str, buf: *mut libc_stat) -> Option<c_int>
     None
 }
 
-unsafe fn stat_impl(
-    path: *const c_char,
-    buf: *mut libc_stat,
-    _follow_links: bool,
-) -> Option<c_int> {
+unsafe fn stat_impl(path: *const c_char, buf: *mut libc_stat, follow_links: bool) -> Option<c_int> {
     if path.is_null() {
         return None;
     }
 
     let _guard = InceptionLayerGuard::enter()?;
-    let path_str = CStr::from_ptr(path).to_str().ok()?;
+    let path_str = crate::path::path_arg(path);
 
-    // RFC-0044: Symlink following logic not yet implemented for VFS
-    stat_impl_common(path_str, buf)
+    stat_follow(&path_str, buf, follow_links)
+}
+
+/// `stat_impl_common`, following a manifest symlink at the end of the path
+/// when `follow_links` is set
+#[allow(clippy::unnecessary_cast)] // st_mode is u16 on macOS
+unsafe fn stat_follow(path_str: &str, buf: *mut libc_stat, follow_links: bool) -> Option<c_int> {
+    let res = stat_impl_common(path_str, buf)?;
+    if res == 0
+        && follow_links
+        && ((*buf).st_mode as u32 & libc::S_IFMT as u32) == libc::S_IFLNK as u32
+    {
+        return stat_through_link(path_str, buf);
+    }
+    Some(res)
+}
+
+/// stat() through a manifest symlink, stat'ing where `link_target` leads
+#[allow(clippy::unnecessary_cast)] // st_mode is u16 on macOS
+unsafe fn stat_through_link(path_str: &str, buf: *mut libc_stat) -> Option<c_int> {
+    let state = InceptionLayerState::get()?;
+    let vpath = state.resolve_path(path_str)?;
+    let target = match link_target(state, &vpath) {
+        Ok(target) => target?,
+        Err(errno) => {
+            crate::set_errno(errno);
+            return Some(-1);
+        }
+    };
+    match stat_impl_common(target.absolute.as_str(), buf)? {
+        0 if ((*buf).st_mode as u32 & libc::S_IFMT as u32) == libc::S_IFLNK as u32 => None,
+        res => Some(res),
+    }
+}
+
+/// Where the manifest symlink at `vpath` leads. vDird follows the chain
+/// with the bounded resolver (MAX_SYMLINK_HOPS, the kernel's MAXSYMLINKS),
+/// so a cycle is `Err(ELOOP)`. `Ok(None)` (no answer from vDird, or a
+/// target outside the VFS) leaves it to the kernel, which follows the
+/// links that exist on disk under the same limit.
+unsafe fn link_target(
+    state: &InceptionLayerState,
+    vpath: &crate::path::VfsPath,
+) -> Result<Option<crate::path::VfsPath>, c_int> {
+    let target = match crate::ipc::sync_ipc_manifest_resolve(
+        &state.vdird_socket_path,
+        vpath.manifest_key.as_str(),
+        true,
+    ) {
+        Ok(target) => target,
+        Err(libc::ELOOP) => return Err(libc::ELOOP),
+        Err(_) => return Ok(None),
+    };
+
+    let root = state.path_resolver.project_root.as_str();
+    if root.is_empty() {
+        return Ok(None);
+    }
+    let mut abs_buf = [0u8; crate::path::VFS_PATH_CAP];
+    let mut abs = crate::macros::StackWriter::new(&mut abs_buf);
+    use std::fmt::Write;
+    let _ = write!(abs, "{}{}", root.trim_end_matches('/'), target);
+    if abs.overflowed() {
+        return Ok(None);
+    }
+    Ok(state.resolve_path(abs.as_str()))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn velo_stat_impl(path: *const c_char, buf: *mut libc_stat) -> c_int {
+    let _span = crate::profile::begin(crate::profile::ProfileOp::Stat);
     stat_impl(path, buf, true).unwrap_or_else(|| {
         #[cfg(target_os = "macos")]
         return crate::syscalls::macos_raw::raw_stat(path, buf);
@@ -174,6 +223,7 @@ pub unsafe extern "C" fn stat_inception(path: *const c_char, buf: *mut libc_stat
 
 #[no_mangle]
 pub unsafe extern "C" fn velo_lstat_impl(path: *const c_char, buf: *mut libc_stat) -> c_int {
+    let _span = crate::profile::begin(crate::profile::ProfileOp::Lstat);
     stat_impl(path, buf, false).unwrap_or_else(|| {
         #[cfg(target_os = "macos")]
         return crate::syscalls::macos_raw::raw_lstat(path, buf);
@@ -194,8 +244,27 @@ pub unsafe extern "C" fn lstat_inception(path: *const c_char, buf: *mut libc_sta
     velo_lstat_impl(path, buf)
 }
 
+/// Fill `buf` for a tracked VFS fd so it agrees with stat() on the same path.
+///
+/// The backing CAS blob supplies uid/gid/atime/ctime; everything stat()
+/// virtualizes is overlaid from `virt`. If the blob can't be fstat'ed,
+/// `virt` is returned as-is.
+#[inline(always)]
+unsafe fn fstat_backing_then_overlay(fd: c_int, buf: *mut libc_stat, virt: &libc_stat) {
+    #[cfg(target_os = "macos")]
+    let res = crate::syscalls::macos_raw::raw_fstat64(fd, buf);
+    #[cfg(target_os = "linux")]
+    let res = crate::syscalls::linux_raw::raw_fstat(fd, buf);
+    if res != 0 {
+        *buf = *virt;
+        return;
+    }
+    vstat::overlay_stat(buf, &VStat::from_stat(virt));
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn velo_fstat_impl(fd: c_int, buf: *mut libc_stat) -> c_int {
+    let _span = crate::profile::begin(crate::profile::ProfileOp::Fstat);
     // 🔥 ULTRA-FAST PATH: Lock-free, Allocation-free, TLS-free logic
     // This supports usage inside malloc() without deadlock.
 
@@ -206,28 +275,25 @@ pub unsafe extern "C" fn velo_fstat_impl(fd: c_int, buf: *mut libc_stat) -> c_in
         if !entry_ptr.is_null() {
             let entry = &*entry_ptr;
 
-            // M4: If this is a COW file with a temp_path, return live metadata from temp file
+            // M4: If this is a COW file with a temp_path, return live metadata from temp file.
+            // The fd itself refers to the temp file, so fstat it directly (no path alloc).
             if !entry.temp_path.is_empty() {
-                let temp_path_cstr = match std::ffi::CString::new(entry.temp_path.as_str()) {
-                    Ok(c) => c,
-                    Err(_) => return -1,
-                };
                 #[cfg(target_os = "macos")]
-                let res = crate::syscalls::macos_raw::raw_stat(temp_path_cstr.as_ptr(), buf);
+                let res = crate::syscalls::macos_raw::raw_fstat64(fd, buf);
                 #[cfg(target_os = "linux")]
-                let res = crate::syscalls::linux_raw::raw_stat(temp_path_cstr.as_ptr(), buf);
+                let res = crate::syscalls::linux_raw::raw_fstat(fd, buf);
 
                 if res == 0 {
-                    // Virtualize the dev/ino to match VFS expectations
-                    (*buf).st_dev = 0x52494654;
-                    (*buf).st_ino = entry.manifest_key_hash as _;
+                    let ino =
+                        state.virtual_ino(entry.manifest_key.as_str(), entry.manifest_key_hash);
+                    vstat::set_identity(buf, ino);
                     return 0;
                 }
             }
 
             // If we have a cached stat (standard case for VFS files)
             if let Some(ref cached) = entry.cached_stat {
-                *buf = *cached;
+                fstat_backing_then_overlay(fd, buf, cached);
                 return 0;
             }
 
@@ -235,21 +301,11 @@ pub unsafe extern "C" fn velo_fstat_impl(fd: c_int, buf: *mut libc_stat) -> c_in
             if entry.is_vfs {
                 // BUG FIX: Use resolve_path to get a VfsPath for query_manifest
                 if let Some(vpath) = state.resolve_path(entry.vpath.as_str()) {
-                    if let Some(vnode) = state.query_manifest(&vpath) {
-                        std::ptr::write_bytes(buf, 0, 1);
-                        (*buf).st_size = vnode.size as _;
-                        #[cfg(target_os = "macos")]
-                        {
-                            (*buf).st_mode = vnode.mode as u16;
-                        }
-                        #[cfg(target_os = "linux")]
-                        {
-                            (*buf).st_mode = vnode.mode as _;
-                        }
-                        (*buf).st_mtime = vnode.mtime as _;
-                        (*buf).st_dev = 0x52494654;
-                        (*buf).st_nlink = 1;
-                        (*buf).st_ino = vpath.manifest_key_hash as _;
+                    if let Ok(Some((vnode, ino))) =
+                        state.lookup_manifest_ino(&vpath, BudgetClass::Stat)
+                    {
+                        let virt = vstat::make_stat(&VStat::from_vnode(&vnode, ino));
+                        fstat_backing_then_overlay(fd, buf, &virt);
                         inception_record!(EventType::StatHit, vpath.manifest_key_hash, 0);
                         return 0;
                     }
@@ -279,40 +335,68 @@ pub unsafe extern "C" fn fstat_inception(fd: c_int, buf: *mut libc_stat) -> c_in
     velo_fstat_impl(fd, buf)
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn velo_access_impl(path: *const c_char, mode: c_int) -> c_int {
-    // Use raw syscall for fallback to avoid dlsym deadlock (Pattern 2682.v2)
-    let _guard = match InceptionLayerGuard::enter() {
-        Some(g) => g,
-        None => {
-            #[cfg(target_os = "macos")]
-            return crate::syscalls::macos_raw::raw_access(path, mode);
-            #[cfg(target_os = "linux")]
-            return crate::syscalls::linux_raw::raw_access(path, mode);
-        }
-    };
+/// Whether a manifest entry with `st_mode` grants `mode` (R_OK/W_OK/X_OK).
+/// Manifest entries belong to whoever runs the build, so the owner bits
+/// decide; root passes R_OK/W_OK, and X_OK if any execute bit is set (or
+/// it's a directory). Writes go to a CoW copy, so W_OK only needs the bit.
+#[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS
+fn access_allowed(st_mode: u32, mode: c_int, root: bool) -> bool {
+    if root {
+        let is_dir = st_mode & libc::S_IFMT as u32 == libc::S_IFDIR as u32;
+        return mode & libc::X_OK == 0 || is_dir || st_mode & 0o111 != 0;
+    }
+    [
+        (libc::R_OK, 0o400),
+        (libc::W_OK, 0o200),
+        (libc::X_OK, 0o100),
+    ]
+    .iter()
+    .all(|&(bit, owner)| mode & bit == 0 || st_mode & owner != 0)
+}
 
-    let path_str = match CStr::from_ptr(path).to_str() {
-        Ok(s) => s,
-        Err(_) => {
-            #[cfg(target_os = "macos")]
-            return crate::syscalls::macos_raw::raw_access(path, mode);
-            #[cfg(target_os = "linux")]
-            return crate::syscalls::linux_raw::raw_access(path, mode);
-        }
+/// access()/faccessat() against the manifest, through the same hot-cache
+/// lookup as stat(). `effective`: check as the effective user (AT_EACCESS).
+/// Returns None to fall back to the OS.
+#[allow(clippy::unnecessary_cast)] // st_mode is u16 on macOS
+pub(crate) unsafe fn access_impl_common(
+    path_str: &str,
+    mode: c_int,
+    follow_links: bool,
+    effective: bool,
+) -> Option<c_int> {
+    let mut st: libc_stat = std::mem::zeroed();
+    let res = stat_follow(path_str, &mut st, follow_links)?;
+    if res != 0 {
+        return Some(res);
+    }
+    let uid = if effective {
+        libc::geteuid()
+    } else {
+        libc::getuid()
     };
-
-    if InceptionLayerState::get()
-        .map(|s| s.inception_applicable(path_str))
-        .unwrap_or(false)
-    {
-        return 0;
+    if mode == libc::F_OK || access_allowed(st.st_mode as u32, mode, uid == 0) {
+        return Some(0);
     }
+    crate::set_errno(libc::EACCES);
+    Some(-1)
+}
 
+#[no_mangle]
+pub unsafe extern "C" fn velo_access_impl(path: *const c_char, mode: c_int) -> c_int {
     #[cfg(target_os = "macos")]
-    return crate::syscalls::macos_raw::raw_access(path, mode);
+    let real = |p, m| crate::syscalls::macos_raw::raw_access(p, m);
     #[cfg(target_os = "linux")]
-    return crate::syscalls::linux_raw::raw_access(path, mode);
+    let real = |p, m| crate::syscalls::linux_raw::raw_access(p, m);
+
+    // Use raw syscall for fallback to avoid dlsym deadlock (Pattern 2682.v2)
+    if path.is_null() {
+        return real(path, mode);
+    }
+    let Some(_guard) = InceptionLayerGuard::enter() else {
+        return real(path, mode);
+    };
+    let path_str = crate::path::path_arg(path);
+    access_impl_common(&path_str, mode, true, false).unwrap_or_else(|| real(path, mode))
 }
 
 #[no_mangle]
@@ -347,9 +431,19 @@ pub unsafe extern "C" fn velo_fstatat_impl(
         }
     };
 
+    let follow_links = flags & libc::AT_SYMLINK_NOFOLLOW == 0;
     if dirfd == libc::AT_FDCWD || (!path.is_null() && unsafe { *path == b'/' as libc::c_char }) {
-        if let Ok(path_str) = unsafe { CStr::from_ptr(path).to_str() } {
-            if let Some(res) = stat_impl_common(path_str, buf) {
+        let path_str = unsafe { crate::path::path_arg(path) };
+        if let Some(res) = stat_follow(&path_str, buf, follow_links) {
+            return res;
+        }
+    } else if !path.is_null() {
+        let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+        if let Some(len) = crate::path::resolve_path_at(dirfd, path, &mut abs) {
+            if let Some(res) = std::str::from_utf8(&abs[..len])
+                .ok()
+                .and_then(|p| stat_follow(p, buf, follow_links))
+            {
                 return res;
             }
         }
@@ -391,11 +485,12 @@ pub unsafe fn fstatat_inception_linux(
     dirfd: c_int,
     path: *const c_char,
     buf: *mut libc_stat,
-    _flags: c_int,
+    flags: c_int,
 ) -> c_int {
     if path.is_null() {
         return -libc::EFAULT;
     }
+    let follow_links = flags & libc::AT_SYMLINK_NOFOLLOW == 0;
 
     let path_str = match CStr::from_ptr(path).to_str() {
         Ok(s) => s,
@@ -403,10 +498,14 @@ pub unsafe fn fstatat_inception_linux(
     };
 
     if dirfd != libc::AT_FDCWD && !path_str.starts_with('/') {
-        return -2;
+        let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+        return crate::path::resolve_path_at(dirfd, path, &mut abs)
+            .and_then(|len| std::str::from_utf8(&abs[..len]).ok())
+            .and_then(|p| stat_follow(p, buf, follow_links))
+            .unwrap_or(-2);
     }
 
-    stat_impl_common(path_str, buf).unwrap_or(-2)
+    stat_follow(path_str, buf, follow_links).unwrap_or(-2)
 }
 
 #[no_mangle]
@@ -428,12 +527,10 @@ pub unsafe extern "C" fn statx_inception(
         );
     }
 
+    // No INCEPTION_LAYER_STATE check: coreutils' first intercepted call is
+    // often statx, and it has to bring the state up
     let init_state = INITIALIZING.load(Ordering::Relaxed);
-    if init_state != 0
-        || crate::state::INCEPTION_LAYER_STATE
-            .load(Ordering::Acquire)
-            .is_null()
-    {
+    if init_state != 0 {
         return crate::syscalls::linux_raw::raw_statx(
             dirfd,
             path,
@@ -443,34 +540,56 @@ pub unsafe extern "C" fn statx_inception(
         );
     }
 
-    let path_str = match CStr::from_ptr(path).to_str() {
-        Ok(s) => s,
-        Err(_) => {
-            return crate::syscalls::linux_raw::raw_statx(
-                dirfd,
-                path,
-                flags,
-                mask,
-                buf as *mut libc::c_void,
-            )
+    let path_str = crate::path::path_arg(path);
+    let mut abs = [0u8; crate::path::VFS_PATH_CAP];
+    let path_str = if dirfd != libc::AT_FDCWD && !path_str.starts_with('/') {
+        // AT_EMPTY_PATH and unresolvable dirfds are the kernel's
+        match crate::path::resolve_path_at(dirfd, path, &mut abs)
+            .and_then(|len| std::str::from_utf8(&abs[..len]).ok())
+        {
+            Some(p) => std::borrow::Cow::Borrowed(p),
+            None => {
+                return crate::syscalls::linux_raw::raw_statx(
+                    dirfd,
+                    path,
+                    flags,
+                    mask,
+                    buf as *mut libc::c_void,
+                )
+            }
         }
+    } else {
+        path_str
     };
 
     // VFS lookup
     if let Some(state) = InceptionLayerState::get() {
-        if let Some(vpath) = state.resolve_path(path_str) {
-            if let Some(entry) = state.query_manifest(&vpath) {
-                std::ptr::write_bytes(buf, 0, 1);
-                (*buf).stx_mask = 0x7FF; // basic stats
-                (*buf).stx_size = entry.size as _;
-                (*buf).stx_mode = entry.mode as _;
-                (*buf).stx_ino = vrift_ipc::fnv1a_hash(path_str) as _;
-                (*buf).stx_nlink = 1;
-                (*buf).stx_mtime.tv_sec = entry.mtime as _;
-                (*buf).stx_blksize = 4096;
-                (*buf).stx_blocks = entry.size.div_ceil(512);
-                inception_record!(EventType::StatHit, (*buf).stx_ino, 0);
-                return 0;
+        let resolved = match state.resolve_path_checked(&path_str) {
+            Ok(resolved) => resolved,
+            Err(errno) => {
+                crate::set_errno(errno);
+                return -1;
+            }
+        };
+        if let Some(vpath) = resolved {
+            let is_link = |buf: *mut statx| (*buf).stx_mode as u32 & libc::S_IFMT == libc::S_IFLNK;
+            match statx_vpath(state, &vpath, buf) {
+                Some(0) if flags & libc::AT_SYMLINK_NOFOLLOW == 0 && is_link(buf) => {
+                    match link_target(state, &vpath) {
+                        Ok(Some(target)) => match statx_vpath(state, &target, buf) {
+                            Some(0) if is_link(buf) => {}
+                            Some(res) => return res,
+                            None => {}
+                        },
+                        Ok(None) => {}
+                        Err(errno) => {
+                            crate::set_errno(errno);
+                            return -1;
+                        }
+                    }
+                }
+                Some(res) => return res,
+                None => {}
             }
         }
     }
@@ -478,6 +597,36 @@ pub unsafe extern "C" fn statx_inception(
     crate::syscalls::linux_raw::raw_statx(dirfd, path, flags, mask, buf as *mut libc::c_void)
 }
 
+/// statx() of a manifest path; None if it has no entry
+#[cfg(target_os = "linux")]
+unsafe fn statx_vpath(
+    state: &InceptionLayerState,
+    vpath: &crate::path::VfsPath,
+    buf: *mut statx,
+) -> Option<c_int> {
+    // The VDir first: unlike a vnode, it has the btime
+    let key = vpath.manifest_key.as_str();
+    if let Some(entry) = vdir_lookup(state.mmap_ptr, state.mmap_size, key) {
+        if entry.is_deleted() {
+            crate::set_errno(libc::ENOENT);
+            return Some(-1);
+        }
+        vstat::fill_statx(buf, &VStat::from_vdir(&entry, vpath.manifest_key_hash));
+        inception_record!(EventType::StatHit, (*buf).stx_ino, 0);
+        return Some(0);
+    }
+    if let Ok(Some((entry, ino))) = state.lookup_manifest_ino(vpath, BudgetClass::Stat) {
+        if entry.is_whiteout() {
+            crate::set_errno(libc::ENOENT);
+            return Some(-1);
+        }
+        vstat::fill_statx(buf, &VStat::from_vnode(&entry, ino));
+        inception_record!(EventType::StatHit, (*buf).stx_ino, 0);
+        return Some(0);
+    }
+    None
+}
+
 /// Helper: Find an open temp_path for a given manifest path.
 unsafe fn find_live_temp_path(manifest_path: &str) -> Option<crate::state::FixedString<1024>> {
     let state = InceptionLayerState::get()?;
@@ -3,81 +3,528 @@
 //! Handles unlink, rmdir, rename for VFS paths by sending IPC
 //! requests to the daemon to update the Manifest.
 //!
-//! Note: These functions are currently not used directly but provide
-//! reference implementation for future VFS path operations.
+//! The `*at()` calls (unlinkat, mkdirat, renameat/renameat2, symlinkat,
+//! linkat) resolve their dirfd and come here with an absolute path. A path
+//! the manifest doesn't have is None: the caller hands it to the OS.
+//!
+//! chmod, chown and utimensat on a manifest entry (`set_meta_vfs`) rewrite
+//! the entry's mode or mtime with a ManifestUpsert. The content stays in the
+//! CAS: nothing is materialized and the stub on disk is left alone.
+//!
+//! getxattr/listxattr/setxattr/removexattr on a manifest entry (`xattr_vfs`)
+//! read and write the xattrs vDird keeps with the entry, so labels and
+//! signatures recorded at ingest survive without touching the CAS blob.
 
 #![allow(dead_code)]
 
-use crate::state::InceptionLayerState;
-use libc::c_int;
+use crate::budget::BudgetClass;
+use crate::state::{DirentArena, InceptionLayerState};
+use crate::syscalls::io::FdEntry;
+#[cfg(target_os = "linux")]
+use crate::syscalls::linux_raw as raw;
+#[cfg(target_os = "macos")]
+use crate::syscalls::macos_raw as raw;
+use libc::{c_char, c_int};
+use std::ffi::{CStr, CString};
 
-/// RFC-0047: unlink for VFS paths
-/// Sends ManifestRemove IPC to daemon instead of hitting real FS
-pub(crate) unsafe fn unlink_vfs(path: &str, state: &InceptionLayerState) -> Option<c_int> {
-    // Only handle VFS paths
+/// A metadata change a manifest entry can carry
+#[derive(Clone, Copy)]
+pub(crate) enum MetaChange {
+    /// chmod: permission bits (the file type is kept)
+    Mode(libc::mode_t),
+    /// chown: the manifest stores no owner, so only a change to the caller
+    /// itself (or -1, "unchanged") succeeds
+    Owner(libc::uid_t, libc::gid_t),
+    /// utimensat: the new mtime, `None` for UTIME_OMIT
+    Mtime(Option<u64>),
+}
+
+impl MetaChange {
+    /// The mtime utimensat/futimens/utimes ask for: `times[1]`, or now when
+    /// `times` is null
+    pub(crate) unsafe fn from_timespecs(times: *const libc::timespec) -> Self {
+        let now = || {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        };
+        if times.is_null() {
+            return MetaChange::Mtime(Some(now()));
+        }
+        let mtime = *times.add(1);
+        match mtime.tv_nsec {
+            libc::UTIME_OMIT => MetaChange::Mtime(None),
+            libc::UTIME_NOW => MetaChange::Mtime(Some(now())),
+            _ => MetaChange::Mtime(Some(mtime.tv_sec.max(0) as u64)),
+        }
+    }
+
+    /// `from_timespecs` for utimes/futimes
+    pub(crate) unsafe fn from_timevals(times: *const libc::timeval) -> Self {
+        if times.is_null() {
+            return Self::from_timespecs(std::ptr::null());
+        }
+        MetaChange::Mtime(Some((*times.add(1)).tv_sec.max(0) as u64))
+    }
+}
+
+/// Apply `change` to the manifest entry at `path`. Returns None when the
+/// manifest has no live entry there (a real file on disk, or outside the
+/// VFS): the caller then hands the call to the OS.
+pub(crate) unsafe fn set_meta_vfs(
+    path: &str,
+    change: MetaChange,
+    state: &InceptionLayerState,
+) -> Option<c_int> {
     let vpath = state.resolve_path(path)?;
+    set_meta_entry(&vpath, change, state)
+}
 
-    // Check if file exists in manifest
-    if state.query_manifest(&vpath).is_none() {
-        crate::set_errno(libc::ENOENT);
+/// `set_meta_vfs` for a tracked fd. An fd onto a CAS blob must never reach
+/// the OS, so it fails with EPERM if its entry can't be updated.
+pub(crate) unsafe fn set_meta_fd(
+    entry: &FdEntry,
+    change: MetaChange,
+    state: &InceptionLayerState,
+) -> Option<c_int> {
+    let vpath = crate::path::VfsPath {
+        absolute: entry.vpath,
+        manifest_key: entry.manifest_key,
+        manifest_key_hash: entry.manifest_key_hash,
+    };
+    let res = set_meta_entry(&vpath, change, state);
+    if res.is_none() && entry.serves_blob() {
+        crate::set_errno(libc::EPERM);
         return Some(-1);
     }
+    res
+}
 
-    // Send ManifestRemove IPC
-    match state.manifest_remove(vpath.manifest_key.as_str()) {
-        Ok(()) => Some(0),
-        Err(_) => {
-            crate::set_errno(libc::EIO);
+#[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS, u32 on Linux
+unsafe fn set_meta_entry(
+    vpath: &crate::path::VfsPath,
+    change: MetaChange,
+    state: &InceptionLayerState,
+) -> Option<c_int> {
+    let mut vnode = match state.lookup_manifest(vpath, BudgetClass::Mutate) {
+        Ok(Some(e)) if !e.is_whiteout() => e,
+        Ok(_) => return None,
+        Err(errno) => {
+            crate::set_errno(errno);
+            return Some(-1);
+        }
+    };
+    // Symlinks: let the kernel follow (or refuse) a live-ingested link
+    if vnode.mode & libc::S_IFMT as u32 == libc::S_IFLNK as u32 {
+        return None;
+    }
+    match change {
+        MetaChange::Mode(mode) => {
+            vnode.mode = (vnode.mode & libc::S_IFMT as u32) | (mode as u32 & 0o7777);
+        }
+        MetaChange::Owner(uid, gid) => {
+            let same_uid = uid == libc::uid_t::MAX || uid == libc::geteuid();
+            let same_gid = gid == libc::gid_t::MAX || gid == libc::getegid();
+            if same_uid && same_gid {
+                return Some(0);
+            }
+            crate::set_errno(libc::EPERM);
+            return Some(-1);
+        }
+        MetaChange::Mtime(None) => return Some(0),
+        MetaChange::Mtime(Some(mtime)) => vnode.mtime = mtime,
+    }
+    let key = vpath.manifest_key.as_str();
+    match crate::ipc::sync_ipc_manifest_upsert(&state.vdird_socket_path, key, vnode) {
+        Ok(()) => {
+            inception_log!("metadata updated in manifest: '{}'", key);
+            Some(0)
+        }
+        Err(errno) => {
+            crate::set_errno(errno);
             Some(-1)
         }
     }
 }
+/// An extended-attribute call on a manifest entry
+pub(crate) enum XattrOp<'a> {
+    /// getxattr: copy the value of `name` into `buf`
+    Get {
+        name: &'a str,
+        buf: *mut libc::c_void,
+        size: usize,
+    },
+    /// listxattr: copy the NUL-terminated names into `buf`
+    List { buf: *mut c_char, size: usize },
+    /// setxattr with XATTR_CREATE/XATTR_REPLACE `flags`
+    Set {
+        name: &'a str,
+        value: &'a [u8],
+        flags: c_int,
+    },
+    /// removexattr
+    Remove { name: &'a str },
+}
 
-/// RFC-0047: rmdir for VFS paths
-/// Sends ManifestRemove IPC to daemon for directory removal
-pub(crate) unsafe fn rmdir_vfs(path: &str, state: &InceptionLayerState) -> Option<c_int> {
-    // Only handle VFS paths
+/// errno for a missing attribute
+#[cfg(target_os = "linux")]
+const ENOATTR: c_int = libc::ENODATA;
+#[cfg(target_os = "macos")]
+const ENOATTR: c_int = libc::ENOATTR;
+
+/// Serve `op` from the xattrs of the manifest entry at `path`. None when
+/// the manifest has no live entry there, as for `set_meta_vfs`.
+pub(crate) unsafe fn xattr_vfs(
+    path: &str,
+    op: XattrOp<'_>,
+    state: &InceptionLayerState,
+) -> Option<isize> {
     let vpath = state.resolve_path(path)?;
+    xattr_entry(&vpath, op, state)
+}
 
-    // Check if directory exists in manifest
-    let entry = state.query_manifest(&vpath);
-    if entry.is_none() {
-        crate::set_errno(libc::ENOENT);
+/// `xattr_vfs` for a tracked fd. Changes through an fd onto a CAS blob
+/// fail with EPERM if its entry is gone; reads go to the OS.
+pub(crate) unsafe fn xattr_fd(
+    entry: &FdEntry,
+    op: XattrOp<'_>,
+    state: &InceptionLayerState,
+) -> Option<isize> {
+    let vpath = crate::path::VfsPath {
+        absolute: entry.vpath,
+        manifest_key: entry.manifest_key,
+        manifest_key_hash: entry.manifest_key_hash,
+    };
+    let mutates = matches!(op, XattrOp::Set { .. } | XattrOp::Remove { .. });
+    let res = xattr_entry(&vpath, op, state);
+    if res.is_none() && mutates && entry.serves_blob() {
+        crate::set_errno(libc::EPERM);
         return Some(-1);
     }
+    res
+}
 
-    // Send ManifestRemove IPC
-    match state.manifest_remove(vpath.manifest_key.as_str()) {
-        Ok(()) => Some(0),
-        Err(_) => {
-            crate::set_errno(libc::EIO);
-            Some(-1)
+#[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS, u32 on Linux
+unsafe fn xattr_entry(
+    vpath: &crate::path::VfsPath,
+    op: XattrOp<'_>,
+    state: &InceptionLayerState,
+) -> Option<isize> {
+    let class = match op {
+        XattrOp::Get { .. } | XattrOp::List { .. } => BudgetClass::Stat,
+        XattrOp::Set { .. } | XattrOp::Remove { .. } => BudgetClass::Mutate,
+    };
+    let vnode = match state.lookup_manifest(vpath, class) {
+        Ok(Some(e)) if !e.is_whiteout() => e,
+        Ok(_) => return None,
+        Err(errno) => return fail(errno),
+    };
+    // Symlinks: left to the kernel, as for chmod
+    if vnode.mode & libc::S_IFMT as u32 == libc::S_IFLNK as u32 {
+        return None;
+    }
+
+    let socket = &state.vdird_socket_path;
+    let key = vpath.manifest_key.as_str();
+    let attrs = match crate::ipc::sync_ipc_manifest_get_xattrs(socket, key) {
+        Ok(attrs) => attrs,
+        Err(errno) => return fail(errno),
+    };
+    let (name, value) = match op {
+        XattrOp::Get { name, buf, size } => {
+            return match attrs.iter().find(|a| a.name == name) {
+                Some(a) => Some(copy_out(&a.value, buf.cast(), size)),
+                None => fail(ENOATTR),
+            };
         }
+        XattrOp::List { buf, size } => {
+            let mut names = Vec::new();
+            for a in &attrs {
+                names.extend_from_slice(a.name.as_bytes());
+                names.push(0);
+            }
+            return Some(copy_out(&names, buf.cast(), size));
+        }
+        XattrOp::Set { name, value, flags } => {
+            // Linux only takes names in a known namespace
+            #[cfg(target_os = "linux")]
+            if !["user.", "trusted.", "security.", "system."]
+                .iter()
+                .any(|ns| name.starts_with(ns))
+            {
+                return fail(libc::EOPNOTSUPP);
+            }
+            let exists = attrs.iter().any(|a| a.name == name);
+            if flags & libc::XATTR_CREATE != 0 && exists {
+                return fail(libc::EEXIST);
+            }
+            if flags & libc::XATTR_REPLACE != 0 && !exists {
+                return fail(ENOATTR);
+            }
+            (name, Some(value))
+        }
+        XattrOp::Remove { name } => {
+            if !attrs.iter().any(|a| a.name == name) {
+                return fail(ENOATTR);
+            }
+            (name, None)
+        }
+    };
+    match crate::ipc::sync_ipc_manifest_set_xattr(socket, key, name, value) {
+        Ok(()) => {
+            inception_log!("xattr '{}' updated in manifest: '{}'", name, key);
+            Some(0)
+        }
+        Err(errno) => fail(errno),
     }
 }
 
-/// RFC-0047: mkdir for VFS paths
-/// Sends ManifestUpsert IPC to daemon to create directory entry
+/// Copy `data` out as getxattr/listxattr do: a zero `size` asks for the
+/// length only, a short buffer is ERANGE
+unsafe fn copy_out(data: &[u8], buf: *mut u8, size: usize) -> isize {
+    if size == 0 {
+        return data.len() as isize;
+    }
+    if data.len() > size {
+        crate::set_errno(libc::ERANGE);
+        return -1;
+    }
+    std::ptr::copy_nonoverlapping(data.as_ptr(), buf, data.len());
+    data.len() as isize
+}
+
+/// renameat2 flags, with their Linux values (macOS callers pass 0)
+pub(crate) const RENAME_NOREPLACE: libc::c_uint = 1;
+pub(crate) const RENAME_EXCHANGE: libc::c_uint = 2;
+
+unsafe fn fail<T: From<i8>>(errno: c_int) -> Option<T> {
+    crate::set_errno(errno);
+    Some(T::from(-1))
+}
+
+/// The live manifest entry at `vpath` (tombstones count as missing)
+unsafe fn live_entry(
+    vpath: &crate::path::VfsPath,
+    state: &InceptionLayerState,
+) -> Result<Option<vrift_ipc::VnodeEntry>, c_int> {
+    match state.lookup_manifest(vpath, BudgetClass::Mutate) {
+        Ok(Some(e)) if !e.is_whiteout() => Ok(Some(e)),
+        Ok(_) => Ok(None),
+        Err(errno) => Err(errno),
+    }
+}
+
+/// Whether `path` names something on disk, as lstat sees it
+unsafe fn on_disk(path: &CStr) -> bool {
+    let mut st: libc::stat = std::mem::zeroed();
+    raw::raw_lstat(path.as_ptr(), &mut st) == 0
+}
+
+/// Tombstone the entry; answered once vDird has it, so the next lookup
+/// misses
+unsafe fn remove_entry(vpath: &crate::path::VfsPath, state: &InceptionLayerState) -> Option<c_int> {
+    let key = vpath.manifest_key.as_str();
+    match crate::ipc::sync_ipc_manifest_remove(&state.vdird_socket_path, key) {
+        Ok(()) => {
+            inception_log!("removed from manifest: '{}'", key);
+            Some(0)
+        }
+        Err(errno) => fail(errno),
+    }
+}
+
+/// RFC-0047: unlink for VFS paths. A manifest file is tombstoned (its copy
+/// on disk, if any, goes too); anything else is None and left to the OS.
+pub(crate) unsafe fn unlink_vfs(path: &str, state: &InceptionLayerState) -> Option<c_int> {
+    let vpath = state.resolve_path(path)?;
+    match live_entry(&vpath, state) {
+        Ok(Some(e)) if e.is_dir() => return fail(libc::EISDIR),
+        Ok(Some(_)) => {}
+        Ok(None) => return None,
+        Err(errno) => return fail(errno),
+    }
+    let cpath = CString::new(path).ok()?;
+    if raw::raw_unlink(cpath.as_ptr()) != 0 && crate::get_errno() != libc::ENOENT {
+        return Some(-1);
+    }
+    remove_entry(&vpath, state)
+}
+
+/// RFC-0047: rmdir for VFS paths. Children only the manifest has count as
+/// well as the ones on disk.
+pub(crate) unsafe fn rmdir_vfs(path: &str, state: &InceptionLayerState) -> Option<c_int> {
+    let vpath = state.resolve_path(path)?;
+    match live_entry(&vpath, state) {
+        Ok(Some(e)) if !e.is_dir() => return fail(libc::ENOTDIR),
+        Ok(Some(_)) => {}
+        Ok(None) => return None,
+        Err(errno) => return fail(errno),
+    }
+    let mut children = DirentArena::new();
+    if state.query_dir_page(vpath.manifest_key.as_str(), &mut children) && !children.drained() {
+        return fail(libc::ENOTEMPTY);
+    }
+    let cpath = CString::new(path).ok()?;
+    if raw::raw_rmdir(cpath.as_ptr()) != 0 && crate::get_errno() != libc::ENOENT {
+        return Some(-1);
+    }
+    remove_entry(&vpath, state)
+}
+
+/// RFC-0047: mkdir for VFS paths. EEXIST over a manifest entry; otherwise
+/// the directory is made on disk and registered. Under a directory only the
+/// manifest has, the new one is manifest-only too.
+#[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS, u32 on Linux
 pub(crate) unsafe fn mkdir_vfs(
     path: &str,
     mode: libc::mode_t,
     state: &InceptionLayerState,
 ) -> Option<c_int> {
-    // Only handle VFS paths
     let vpath = state.resolve_path(path)?;
+    match live_entry(&vpath, state) {
+        Ok(None) => {}
+        Ok(Some(_)) => return fail(libc::EEXIST),
+        Err(errno) => return fail(errno),
+    }
 
-    // Check if already exists
-    if state.query_manifest(&vpath).is_some() {
-        crate::set_errno(libc::EEXIST);
-        return Some(-1);
+    let cpath = CString::new(path).ok()?;
+    if raw::raw_mkdir(cpath.as_ptr(), mode) == 0 {
+        let mode = crate::syscalls::misc::created_dir_mode(cpath.as_ptr(), mode);
+        let _ = state.manifest_mkdir(vpath.manifest_key.as_str(), mode);
+        return Some(0);
+    }
+    let errno = crate::get_errno();
+    let parent_is_dir = path
+        .rsplit_once('/')
+        .and_then(|(parent, _)| state.resolve_path(parent))
+        .is_some_and(|parent| matches!(live_entry(&parent, state), Ok(Some(e)) if e.is_dir()));
+    if errno != libc::ENOENT || !parent_is_dir {
+        return fail(errno);
     }
 
-    // Send ManifestUpsert IPC for directory
-    match state.manifest_mkdir(vpath.manifest_key.as_str(), mode) {
+    // Nothing is created on disk, so apply the umask here the way the kernel
+    // would have
+    let mode = mode & !crate::syscalls::misc::current_umask() & 0o7777;
+    let entry = vrift_ipc::VnodeEntry {
+        content_hash: [0u8; 32],
+        size: 0,
+        mtime: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        mode: libc::S_IFDIR as u32 | mode as u32,
+        flags: 1, // is_dir flag
+        _pad: 0,
+    };
+    let key = vpath.manifest_key.as_str();
+    match crate::ipc::sync_ipc_manifest_upsert(&state.vdird_socket_path, key, entry) {
         Ok(()) => Some(0),
-        Err(_) => {
-            crate::set_errno(libc::EIO);
-            Some(-1)
+        Err(errno) => fail(errno),
+    }
+}
+
+/// RFC-0047: rename for VFS paths. Crossing the VFS boundary is EXDEV; a
+/// manifest entry moves in the manifest (and its copy on disk with it), a
+/// directory with everything beneath it. `flags` takes RENAME_NOREPLACE;
+/// any other flag on a manifest entry, RENAME_EXCHANGE included, is EINVAL.
+pub(crate) unsafe fn rename_vfs(
+    old: &str,
+    new: &str,
+    flags: libc::c_uint,
+    state: &InceptionLayerState,
+) -> Option<c_int> {
+    let (v1, v2) = match (state.resolve_path(old), state.resolve_path(new)) {
+        (None, None) => return None,
+        (Some(v1), Some(v2)) => (v1, v2),
+        _ => return fail(libc::EXDEV),
+    };
+    match live_entry(&v1, state) {
+        Ok(Some(_)) => {}
+        // A local file in VFS territory: the OS renames it
+        Ok(None) => return None,
+        Err(errno) => return fail(errno),
+    }
+    if flags & !RENAME_NOREPLACE != 0 {
+        return fail(libc::EINVAL);
+    }
+    // A vDird without ManifestRename can't move the entry: like across
+    // devices, callers fall back to copy and unlink
+    if !crate::ipc::vdird_agreed(state, vrift_ipc::Capabilities::RENAME) {
+        return fail(libc::EXDEV);
+    }
+
+    let mut old_buf = [0u8; crate::path::VFS_PATH_CAP + 1];
+    let mut new_buf = [0u8; crate::path::VFS_PATH_CAP + 1];
+    let old_c = c_path(old, &mut old_buf)?;
+    let new_c = c_path(new, &mut new_buf)?;
+    if flags & RENAME_NOREPLACE != 0 {
+        match live_entry(&v2, state) {
+            Ok(Some(_)) => return fail(libc::EEXIST),
+            Ok(None) if on_disk(new_c) => return fail(libc::EEXIST),
+            Ok(None) => {}
+            Err(errno) => return fail(errno),
+        }
+    }
+    let moved = raw::raw_rename(old_c.as_ptr(), new_c.as_ptr()) == 0;
+    if !moved && crate::get_errno() != libc::ENOENT {
+        return Some(-1);
+    }
+    let (k1, k2) = (v1.manifest_key.as_str(), v2.manifest_key.as_str());
+    match crate::ipc::sync_ipc_manifest_rename(&state.vdird_socket_path, k1, k2) {
+        Ok(()) => Some(0),
+        Err(errno) => {
+            // Put the copy on disk back, so it still matches the manifest
+            if moved {
+                raw::raw_rename(new_c.as_ptr(), old_c.as_ptr());
+            }
+            fail(errno)
         }
     }
 }
+
+/// `path` NUL-terminated in `buf`, for the raw calls; None if it doesn't
+/// fit or holds a NUL
+fn c_path<'a>(path: &str, buf: &'a mut [u8; crate::path::VFS_PATH_CAP + 1]) -> Option<&'a CStr> {
+    let len = path.len();
+    if len >= buf.len() {
+        return None;
+    }
+    buf[..len].copy_from_slice(path.as_bytes());
+    buf[len] = 0;
+    CStr::from_bytes_with_nul(&buf[..=len]).ok()
+}
+
+/// RFC-0047: hard links for VFS paths. A manifest entry can't be linked
+/// (its content is a shared CAS blob), nor can a link cross the VFS
+/// boundary: both are EXDEV. EEXIST over a manifest entry.
+pub(crate) unsafe fn link_vfs(old: &str, new: &str, state: &InceptionLayerState) -> Option<c_int> {
+    let (v1, v2) = match (state.resolve_path(old), state.resolve_path(new)) {
+        (None, None) => return None,
+        (Some(v1), Some(v2)) => (v1, v2),
+        _ => return fail(libc::EXDEV),
+    };
+    match live_entry(&v1, state) {
+        Ok(Some(_)) => return fail(libc::EXDEV),
+        Ok(None) => {}
+        Err(errno) => return fail(errno),
+    }
+    refuse_existing(&v2, state)
+}
+
+/// EEXIST if `path` is a manifest entry, for calls that create it
+/// (symlinkat); None when the OS should go ahead
+pub(crate) unsafe fn create_vfs(path: &str, state: &InceptionLayerState) -> Option<c_int> {
+    refuse_existing(&state.resolve_path(path)?, state)
+}
+
+unsafe fn refuse_existing(
+    vpath: &crate::path::VfsPath,
+    state: &InceptionLayerState,
+) -> Option<c_int> {
+    match live_entry(vpath, state) {
+        Ok(Some(_)) => fail(libc::EEXIST),
+        Ok(None) => None,
+        Err(errno) => fail(errno),
+    }
+}
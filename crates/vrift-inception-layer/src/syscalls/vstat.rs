@@ -0,0 +1,174 @@
+//! Canonical virtual stat synthesis
+//!
+//! Every stat/lstat/fstat/fstatat/statx path that reports a VFS file builds
+//! its result through this module, so the virtual identity (st_dev, st_ino,
+//! st_nlink) and platform field widths can't drift between entry points.
+//! The identity itself comes from `vrift_ipc::identity`, which the FUSE
+//! backend uses too.
+//!
+//! All helpers are allocation-free and syscall-free: they run on the PSFS hot
+//! path and inside fstat() calls made from malloc.
+
+use crate::state::VDirStatResult;
+use libc::stat as libc_stat;
+use vrift_ipc::identity;
+
+/// `mode` with its file type. Symlinks from `VnodeEntry::new_symlink`
+/// carry only the permission bits and the symlink flag.
+#[inline(always)]
+#[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS
+fn typed_mode(mode: u32, symlink: bool) -> u32 {
+    if symlink && mode & libc::S_IFMT as u32 == 0 {
+        mode | libc::S_IFLNK as u32
+    } else {
+        mode
+    }
+}
+
+/// Metadata for one VFS file, independent of where it was looked up
+#[derive(Debug, Clone, Copy)]
+pub struct VStat {
+    pub size: u64,
+    pub mode: u32,
+    pub mtime_sec: i64,
+    pub mtime_nsec: u32,
+    /// Virtual inode: vDird's allocation, else the manifest key hash's
+    pub ino: u64,
+    /// Creation time recorded at ingest, 0 if unknown
+    pub btime_sec: i64,
+}
+
+impl VStat {
+    /// From a VDir seqlock hit
+    #[inline(always)]
+    pub(crate) fn from_vdir(entry: &VDirStatResult, key_hash: u64) -> Self {
+        Self {
+            size: entry.size,
+            mode: typed_mode(
+                entry.mode,
+                entry.flags & vrift_ipc::vdir_types::FLAG_SYMLINK != 0,
+            ),
+            mtime_sec: entry.mtime_sec,
+            mtime_nsec: entry.mtime_nsec,
+            ino: match entry.ino {
+                0 => identity::ino_from_hash(key_hash),
+                ino => ino,
+            },
+            btime_sec: entry.btime_sec,
+        }
+    }
+
+    /// From a manifest IPC reply (mtime in whole seconds; no btime), for
+    /// the file with inode `ino` (`InceptionLayerState::virtual_ino`)
+    #[inline(always)]
+    pub fn from_vnode(entry: &vrift_ipc::VnodeEntry, ino: u64) -> Self {
+        Self {
+            size: entry.size,
+            mode: typed_mode(entry.mode, entry.is_symlink()),
+            mtime_sec: entry.mtime as i64,
+            mtime_nsec: 0,
+            ino,
+            btime_sec: 0,
+        }
+    }
+
+    /// Recover the virtual fields from a stat built by `make_stat`
+    #[inline(always)]
+    #[allow(clippy::unnecessary_cast)] // field widths differ between macOS and Linux
+    pub fn from_stat(st: &libc_stat) -> Self {
+        Self {
+            size: st.st_size as u64,
+            mode: st.st_mode as u32,
+            mtime_sec: st.st_mtime as i64,
+            mtime_nsec: st.st_mtime_nsec as u32,
+            ino: st.st_ino as u64,
+            #[cfg(target_os = "macos")]
+            btime_sec: st.st_birthtime as i64,
+            // Linux's stat has no btime: only statx reports it
+            #[cfg(target_os = "linux")]
+            btime_sec: 0,
+        }
+    }
+}
+
+/// Zero `buf` and fill it from `v`. This is what stat() returns for a VFS path.
+#[inline(always)]
+pub unsafe fn fill_stat(buf: *mut libc_stat, v: &VStat) {
+    std::ptr::write_bytes(buf, 0, 1);
+    overlay_stat(buf, v);
+}
+
+/// Build an owned stat (e.g. to cache in an FdEntry at open time)
+#[inline(always)]
+pub fn make_stat(v: &VStat) -> libc_stat {
+    let mut st: libc_stat = unsafe { std::mem::zeroed() };
+    unsafe { overlay_stat(&mut st, v) };
+    st
+}
+
+/// Overwrite every virtualized field of an already-populated `buf`, keeping
+/// whatever the OS reported for the rest (uid/gid/atime/ctime).
+#[inline(always)]
+pub unsafe fn overlay_stat(buf: *mut libc_stat, v: &VStat) {
+    // `as _` adapts to st_mode being u16 on macOS and u32 on Linux
+    (*buf).st_mode = v.mode as _;
+    (*buf).st_size = v.size as _;
+    (*buf).st_mtime = v.mtime_sec as _;
+    (*buf).st_mtime_nsec = v.mtime_nsec as _;
+    (*buf).st_blksize = identity::BLKSIZE as _;
+    (*buf).st_blocks = identity::blocks(v.size) as _;
+    // Unknown birth: the last modification is the best bound we have
+    #[cfg(target_os = "macos")]
+    if v.btime_sec != 0 {
+        (*buf).st_birthtime = v.btime_sec as _;
+        (*buf).st_birthtime_nsec = 0;
+    } else {
+        (*buf).st_birthtime = v.mtime_sec as _;
+        (*buf).st_birthtime_nsec = v.mtime_nsec as _;
+    }
+    write_identity(buf, v.ino);
+}
+
+/// Overwrite only the virtual identity, with inode `ino`. Used when
+/// size/mtime come from a live COW temp file that is newer than the manifest.
+#[inline(always)]
+pub unsafe fn set_identity(buf: *mut libc_stat, ino: u64) {
+    write_identity(buf, ino);
+}
+
+#[inline(always)]
+#[allow(clippy::unnecessary_cast)] // mode_t is u16 on macOS
+fn is_dir(mode: u32) -> bool {
+    mode & libc::S_IFMT as u32 == libc::S_IFDIR as u32
+}
+
+#[inline(always)]
+#[allow(clippy::unnecessary_cast)]
+unsafe fn write_identity(buf: *mut libc_stat, ino: u64) {
+    (*buf).st_dev = identity::DEV as _;
+    (*buf).st_ino = ino as _;
+    (*buf).st_nlink = identity::nlink(is_dir((*buf).st_mode as u32)) as _;
+}
+
+/// statx counterpart of `fill_stat`
+#[cfg(target_os = "linux")]
+#[inline(always)]
+pub unsafe fn fill_statx(buf: *mut crate::syscalls::stat::statx, v: &VStat) {
+    std::ptr::write_bytes(buf, 0, 1);
+    (*buf).stx_mask = 0x7FF; // STATX_BASIC_STATS
+    (*buf).stx_mode = v.mode as _;
+    (*buf).stx_size = v.size;
+    (*buf).stx_mtime.tv_sec = v.mtime_sec;
+    (*buf).stx_mtime.tv_nsec = v.mtime_nsec;
+    (*buf).stx_blksize = identity::BLKSIZE;
+    (*buf).stx_blocks = identity::blocks(v.size);
+    if v.btime_sec != 0 {
+        (*buf).stx_mask |= 0x800; // STATX_BTIME
+        (*buf).stx_btime.tv_sec = v.btime_sec;
+    }
+    (*buf).stx_ino = v.ino;
+    (*buf).stx_nlink = identity::nlink(is_dir(v.mode));
+    // Split so that glibc's makedev() rebuilds the same st_dev as fill_stat
+    (*buf).stx_dev_major = libc::major(identity::DEV as libc::dev_t);
+    (*buf).stx_dev_minor = libc::minor(identity::DEV as libc::dev_t);
+}
@@ -0,0 +1,182 @@
+// =============================================================================
+// uring.rs — io_uring passthrough detection (VRIFT_IO_URING, Linux)
+// =============================================================================
+//
+// Reads and opens submitted through an io_uring (IORING_OP_OPENAT, STATX,
+// READ on a ring-registered fd...) are executed by the kernel from the
+// submission queue: no libc function runs, so the shim never sees them and
+// the process quietly reads the real filesystem instead of the VFS.
+//
+// The ring itself has to be created with io_uring_setup, which glibc has no
+// wrapper for. Programs reach it through syscall(3) (the `io-uring` crate,
+// tokio-uring, liburing < 2.2) or inline assembly (newer liburing).
+//
+// Format: VRIFT_IO_URING="warn"   (default) one loud warning per process on
+//                                 the first io_uring_setup seen via syscall()
+//         VRIFT_IO_URING="deny"   io_uring_setup fails with ENOSYS, so
+//                                 runtimes fall back to plain libc I/O
+//         VRIFT_IO_URING="allow"  silent
+//
+// deny installs a seccomp filter for the whole process, which also catches
+// the inline-assembly callers; the filter is inherited across fork and exec,
+// and a process that already has one doesn't stack another. The kernel
+// requires no_new_privs for an unprivileged filter, so setuid programs
+// started from a denied process run without their privileges.
+// =============================================================================
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::reals::RealSymbol;
+use crate::syscalls::linux_raw as raw;
+use crate::warnings::{warn_user, Warning};
+
+/// Same number on x86_64 and aarch64
+pub const SYS_IO_URING_SETUP: libc::c_long = 425;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+const MODE_UNSET: u8 = 0;
+const MODE_WARN: u8 = 1;
+const MODE_DENY: u8 = 2;
+const MODE_ALLOW: u8 = 3;
+
+static MODE: AtomicU8 = AtomicU8::new(MODE_UNSET);
+
+/// io_uring_setup calls seen through syscall()
+pub static SETUP_CALLS: AtomicU64 = AtomicU64::new(0);
+
+static REAL_SYSCALL: RealSymbol = RealSymbol::new("syscall\0");
+
+/// Apply a VRIFT_IO_URING spec. Unknown values keep the default (warn).
+pub fn apply_io_uring_spec(spec: &[u8]) {
+    let spec = spec.trim_ascii();
+    let mode = if spec.eq_ignore_ascii_case(b"deny") {
+        MODE_DENY
+    } else if spec.eq_ignore_ascii_case(b"allow") {
+        MODE_ALLOW
+    } else {
+        MODE_WARN
+    };
+    MODE.store(mode, Ordering::Relaxed);
+    if mode == MODE_DENY {
+        unsafe { install_deny_filter() };
+    }
+}
+
+/// Mode, reading the environment if io_uring_setup beats the shim's init
+fn mode() -> u8 {
+    let mode = MODE.load(Ordering::Relaxed);
+    if mode != MODE_UNSET {
+        return mode;
+    }
+    let ptr = unsafe { libc::getenv(c"VRIFT_IO_URING".as_ptr()) };
+    if ptr.is_null() {
+        MODE.store(MODE_WARN, Ordering::Relaxed);
+    } else {
+        apply_io_uring_spec(unsafe { std::ffi::CStr::from_ptr(ptr).to_bytes() });
+    }
+    MODE.load(Ordering::Relaxed)
+}
+
+/// syscall(3) entry point (see interpose.rs)
+pub(crate) unsafe fn syscall_inception(nr: libc::c_long, args: [libc::c_long; 6]) -> libc::c_long {
+    if nr == SYS_IO_URING_SETUP {
+        SETUP_CALLS.fetch_add(1, Ordering::Relaxed);
+        match mode() {
+            MODE_DENY => {
+                crate::set_errno(libc::ENOSYS);
+                return -1;
+            }
+            MODE_WARN => warn_user(Warning::IoUring, ""),
+            _ => {}
+        }
+    }
+    real_syscall(nr, args)
+}
+
+unsafe fn real_syscall(nr: libc::c_long, a: [libc::c_long; 6]) -> libc::c_long {
+    type SyscallFn = unsafe extern "C" fn(
+        libc::c_long,
+        libc::c_long,
+        libc::c_long,
+        libc::c_long,
+        libc::c_long,
+        libc::c_long,
+        libc::c_long,
+    ) -> libc::c_long;
+    let f = REAL_SYSCALL.get();
+    if f.is_null() {
+        crate::set_errno(libc::ENOSYS);
+        return -1;
+    }
+    let f: SyscallFn = std::mem::transmute(f);
+    f(nr, a[0], a[1], a[2], a[3], a[4], a[5])
+}
+
+fn stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jeq(k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Make io_uring_setup return ENOSYS in every thread of this process and
+/// everything it starts
+unsafe fn install_deny_filter() {
+    // entries = 0 is EINVAL on a kernel with io_uring; ENOSYS means it's
+    // compiled out or a filter (ours, inherited) is already in place
+    if raw::raw_io_uring_setup(0, std::ptr::null_mut()) < 0 && crate::get_errno() == libc::ENOSYS {
+        return;
+    }
+
+    let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+    let ret = libc::BPF_RET | libc::BPF_K;
+    let prog = [
+        stmt(load, 4), // seccomp_data.arch
+        jeq(AUDIT_ARCH, 0, 3),
+        stmt(load, 0), // seccomp_data.nr
+        jeq(SYS_IO_URING_SETUP as u32, 0, 1),
+        stmt(ret, libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+        stmt(ret, libc::SECCOMP_RET_ALLOW),
+    ];
+    let fprog = libc::sock_fprog {
+        len: prog.len() as u16,
+        filter: prog.as_ptr() as *mut libc::sock_filter,
+    };
+    let set_filter = || {
+        real_syscall(
+            libc::SYS_seccomp,
+            [
+                libc::SECCOMP_SET_MODE_FILTER as libc::c_long,
+                libc::SECCOMP_FILTER_FLAG_TSYNC as libc::c_long,
+                &fprog as *const _ as libc::c_long,
+                0,
+                0,
+                0,
+            ],
+        )
+    };
+    // Without CAP_SYS_ADMIN the kernel insists on no_new_privs
+    if set_filter() != 0
+        && (libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 || set_filter() != 0)
+    {
+        crate::inception_warn!(
+            "VRIFT_IO_URING=deny: could not install the seccomp filter (errno {})",
+            crate::get_errno()
+        );
+    }
+}
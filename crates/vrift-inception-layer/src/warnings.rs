@@ -0,0 +1,115 @@
+// =============================================================================
+// warnings.rs — User-facing warnings, once per condition
+// =============================================================================
+//
+// inception_warn!/inception_error! go to the in-memory log and only reach
+// stderr with VRIFT_DEBUG, so a user whose build silently reads stale files
+// never learns the daemon was down. Writing every failure to stderr instead
+// would bury the build output under thousands of identical lines.
+//
+// Each distinct condition (see `Warning`) is printed once, on its first
+// occurrence, to stderr and the audit log (LOGGER). Later occurrences are
+// only counted; an atexit hook prints one summary line per repeated
+// condition when the process exits.
+//
+// Zero-allocation and lock-free; output uses the raw write syscall so it
+// never re-enters the shim.
+// =============================================================================
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+#[cfg(target_os = "linux")]
+use crate::syscalls::linux_raw as raw;
+#[cfg(target_os = "macos")]
+use crate::syscalls::macos_raw as raw;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    /// vriftd socket refused or missing
+    DaemonUnreachable = 0,
+    /// vdir_d socket refused or missing
+    VdirdUnreachable = 1,
+    /// Manifest points at a blob that isn't in the CAS
+    CasBlobMissing = 2,
+    /// VRIFT_HYDRATE could not materialize a stub
+    HydrateFailed = 3,
+    /// A file written through CoW was changed by someone else meanwhile
+    CowConflict = 4,
+    /// An io_uring was set up; its submissions bypass the shim
+    IoUring = 5,
+}
+
+pub const WARNING_COUNT: usize = 6;
+
+pub static WARNING_NAMES: [&str; WARNING_COUNT] = [
+    "daemon_unreachable",
+    "vdird_unreachable",
+    "cas_blob_missing",
+    "hydrate_failed",
+    "cow_conflict",
+    "io_uring",
+];
+
+static WARNING_TEXT: [&str; WARNING_COUNT] = [
+    "cannot reach vriftd; files are served without the VFS",
+    "cannot reach vdir_d; virtual entries may be missing or stale",
+    "manifest entry has no blob in the CAS",
+    "could not hydrate a file from the CAS; serving it virtually",
+    "file was modified elsewhere while open for write; keeping this write",
+    "io_uring in use: its file I/O bypasses the VFS (VRIFT_IO_URING=deny forces libc I/O)",
+];
+
+/// Occurrences per condition (first one included)
+pub static WARNING_COUNTS: [AtomicU32; WARNING_COUNT] =
+    [const { AtomicU32::new(0) }; WARNING_COUNT];
+
+static SUMMARY_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// Report `warning`. `detail` (a path or socket) is shown with the first
+/// occurrence only.
+pub(crate) fn warn_user(warning: Warning, detail: &str) {
+    // Later occurrences are only counted, for the exit summary
+    if WARNING_COUNTS[warning as usize].fetch_add(1, Ordering::Relaxed) != 0 {
+        return;
+    }
+    let mut buf = [0u8; 512];
+    let mut w = crate::macros::StackWriter::new(&mut buf);
+    let _ = write!(w, "vrift: warning: {}", WARNING_TEXT[warning as usize]);
+    if !detail.is_empty() {
+        let _ = write!(w, " ({})", detail);
+    }
+    let _ = writeln!(w);
+    emit(w.as_str());
+}
+
+/// Register the exit summary. Called once init is complete (BUG-004), so a
+/// warning never has to take the atexit path itself.
+pub(crate) fn register() {
+    crate::state::register_atexit_once(&SUMMARY_REGISTERED, print_summary);
+}
+
+fn emit(line: &str) {
+    crate::state::LOGGER.log(line);
+    unsafe { raw::raw_write(2, line.as_ptr() as *const libc::c_void, line.len()) };
+}
+
+extern "C" fn print_summary() {
+    for (i, count) in WARNING_COUNTS.iter().enumerate() {
+        let repeats = count.load(Ordering::Relaxed).saturating_sub(1);
+        if repeats == 0 {
+            continue;
+        }
+        let mut buf = [0u8; 256];
+        let mut w = crate::macros::StackWriter::new(&mut buf);
+        let _ = writeln!(
+            w,
+            "vrift: warning repeated {} more time{}: {}",
+            repeats,
+            if repeats == 1 { "" } else { "s" },
+            WARNING_TEXT[i]
+        );
+        emit(w.as_str());
+    }
+}
@@ -0,0 +1,294 @@
+//! Access heatmap: request counts per (path prefix × operation).
+//!
+//! Two sources feed it. The inception layer counts what it serves locally
+//! (VDir stat hits, opens, readdirs) and appends them to
+//! `<project>/.vrift/heatmap.tsv` at process exit. vDird counts the lookups
+//! that reach it over IPC and reports them in [`crate::DaemonMetrics`].
+//! `vrift profile heatmap` merges both, and vDird's warm start uses the file
+//! to decide which entries go into the VDir first.
+//!
+//! TSV format, one cell per line (repeated cells are summed, and
+//! `vrift run --heatmap` folds them together once the command exits):
+//!
+//! ```text
+//! <prefix>\t<op>\t<count>
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Operation columns, in the inception layer's counter order
+pub const HEAT_OP_NAMES: [&str; 7] = [
+    "stat",
+    "stat_miss",
+    "open",
+    "open_write",
+    "readdir",
+    "ipc_get",
+    "ipc_list",
+];
+
+/// Path components kept in a heat prefix unless configured otherwise
+pub const HEATMAP_DEFAULT_DEPTH: usize = 2;
+
+/// Default heatmap location for a project
+pub fn heatmap_path(project_root: &Path) -> PathBuf {
+    project_root.join(".vrift").join("heatmap.tsv")
+}
+
+/// Leading `depth` components of a manifest key (`/a/b/c.rs`, 2 → `/a/b`).
+///
+/// Allocation-free so the inception layer can call it on the syscall path.
+pub fn heat_prefix(key: &str, depth: usize) -> &str {
+    let bytes = key.as_bytes();
+    let mut seen = 0;
+    for (i, &b) in bytes.iter().enumerate().skip(1) {
+        if b == b'/' {
+            seen += 1;
+            if seen == depth {
+                return &key[..i];
+            }
+        }
+    }
+    if key.is_empty() {
+        "/"
+    } else {
+        key
+    }
+}
+
+/// One (prefix, op) counter as carried over IPC
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct HeatCell {
+    pub prefix: String,
+    pub op: String,
+    pub count: u64,
+}
+
+/// Per-prefix totals for reporting, hottest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefixHeat {
+    pub prefix: String,
+    pub total: u64,
+    /// Non-zero op counts, largest first
+    pub ops: Vec<(String, u64)>,
+}
+
+/// Merged heatmap
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Heatmap {
+    cells: BTreeMap<(String, String), u64>,
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a TSV heatmap; a missing file is an empty heatmap
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse_tsv(&text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fold repeated cells in the file at `path` into one line each.
+    ///
+    /// Every process appends its own lines at exit, so without this the
+    /// file grows with every run rather than with the number of prefixes.
+    /// The rewrite goes through a temp file and a rename; lines appended by
+    /// a process exiting in between are lost. Returns whether it rewrote.
+    pub fn compact(path: &Path) -> std::io::Result<bool> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let map = Self::parse_tsv(&text);
+        let tsv = map.to_tsv();
+        if tsv == text {
+            return Ok(false);
+        }
+        let tmp = path.with_extension("tsv.tmp");
+        std::fs::write(&tmp, tsv)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(true)
+    }
+
+    /// Parse TSV lines, summing repeats and skipping malformed lines
+    pub fn parse_tsv(text: &str) -> Self {
+        let mut map = Self::new();
+        for line in text.lines() {
+            let mut parts = line.split('\t');
+            let (Some(prefix), Some(op), Some(count), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            if let Ok(count) = count.trim().parse() {
+                map.add(prefix, op, count);
+            }
+        }
+        map
+    }
+
+    /// Serialize as TSV, one line per cell
+    pub fn to_tsv(&self) -> String {
+        let mut out = String::new();
+        for ((prefix, op), count) in &self.cells {
+            out.push_str(&format!("{}\t{}\t{}\n", prefix, op, count));
+        }
+        out
+    }
+
+    pub fn add(&mut self, prefix: &str, op: &str, count: u64) {
+        if count == 0 {
+            return;
+        }
+        *self
+            .cells
+            .entry((prefix.to_string(), op.to_string()))
+            .or_default() += count;
+    }
+
+    pub fn merge_cells(&mut self, cells: &[HeatCell]) {
+        for c in cells {
+            self.add(&c.prefix, &c.op, c.count);
+        }
+    }
+
+    pub fn cells(&self) -> Vec<HeatCell> {
+        self.cells
+            .iter()
+            .map(|((prefix, op), &count)| HeatCell {
+                prefix: prefix.clone(),
+                op: op.clone(),
+                count,
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Prefixes ordered by total count (ties by prefix)
+    pub fn by_prefix(&self) -> Vec<PrefixHeat> {
+        let mut rows: Vec<PrefixHeat> = Vec::new();
+        for ((prefix, op), &count) in &self.cells {
+            match rows.last_mut() {
+                Some(row) if &row.prefix == prefix => {
+                    row.total += count;
+                    row.ops.push((op.clone(), count));
+                }
+                _ => rows.push(PrefixHeat {
+                    prefix: prefix.clone(),
+                    total: count,
+                    ops: vec![(op.clone(), count)],
+                }),
+            }
+        }
+        for row in &mut rows {
+            row.ops
+                .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        }
+        rows.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.prefix.cmp(&b.prefix)));
+        rows
+    }
+
+    /// Total count of every prefix that covers `key` (itself or an ancestor)
+    pub fn score(&self, key: &str) -> u64 {
+        let mut total = self.prefix_total("/");
+        for (i, b) in key.bytes().enumerate().skip(1) {
+            if b == b'/' {
+                total += self.prefix_total(&key[..i]);
+            }
+        }
+        if !key.is_empty() && key != "/" {
+            total += self.prefix_total(key);
+        }
+        total
+    }
+
+    fn prefix_total(&self, prefix: &str) -> u64 {
+        self.cells
+            .range((prefix.to_string(), String::new())..)
+            .take_while(|((p, _), _)| p == prefix)
+            .map(|(_, &count)| count)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heat_prefix_depth() {
+        assert_eq!(heat_prefix("/src/foo/bar.rs", 2), "/src/foo");
+        assert_eq!(heat_prefix("/src/foo/bar.rs", 1), "/src");
+        assert_eq!(heat_prefix("/main.py", 2), "/main.py");
+        assert_eq!(heat_prefix("", 2), "/");
+    }
+
+    #[test]
+    fn test_tsv_roundtrip_sums_repeats() {
+        let map = Heatmap::parse_tsv(
+            "/src\tstat\t3\n/src\tstat\t2\n/lib\topen\t1\nbogus line\n/x\tstat\tNaN\n",
+        );
+        assert_eq!(Heatmap::parse_tsv(&map.to_tsv()), map);
+        assert_eq!(map.score("/src/a.rs"), 5);
+        assert_eq!(map.score("/srcx/a.rs"), 0);
+    }
+
+    #[test]
+    fn test_by_prefix_sorted_hottest_first() {
+        let mut map = Heatmap::new();
+        map.add("/a", "stat", 1);
+        map.add("/b", "stat", 4);
+        map.add("/b", "open", 6);
+        map.merge_cells(&[HeatCell {
+            prefix: "/a".into(),
+            op: "ipc_get".into(),
+            count: 2,
+        }]);
+
+        let rows = map.by_prefix();
+        assert_eq!(rows[0].prefix, "/b");
+        assert_eq!(rows[0].total, 10);
+        assert_eq!(rows[0].ops[0], ("open".to_string(), 6));
+        assert_eq!(rows[1].total, 3);
+    }
+
+    #[test]
+    fn test_compact_folds_repeated_cells() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = heatmap_path(dir.path());
+        assert!(!Heatmap::compact(&path).unwrap());
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let runs = "/src\tstat\t3\n/lib\topen\t1\n".repeat(10);
+        std::fs::write(&path, &runs).unwrap();
+        assert!(Heatmap::compact(&path).unwrap());
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text, "/lib\topen\t10\n/src\tstat\t30\n");
+        assert_eq!(Heatmap::parse_tsv(&text), Heatmap::parse_tsv(&runs));
+        // Already compact: left alone
+        assert!(!Heatmap::compact(&path).unwrap());
+    }
+}
@@ -0,0 +1,111 @@
+//! Virtual file identity
+//!
+//! The stat fields that no manifest entry carries (device, inode, link
+//! count, block size) are derived here, so the inception layer and the FUSE
+//! mount report the same values for the same manifest:
+//!
+//! - `st_dev` is [`DEV`] ("RIFT") for every file. FUSE can't choose its
+//!   device id; the kernel assigns one per mount.
+//! - `st_ino` is the inode allocated for the path in the manifest's inode
+//!   table; it survives renames and restarts. The inception layer gets it
+//!   from vDird (in the VDir entry or the ManifestGet reply), the FUSE mount
+//!   reads the same table. Only when there is no table to ask do both fall
+//!   back to the FNV-1a hash of the manifest key ([`ino`]). The root key
+//!   maps to [`ROOT_INO`], which is also FUSE's root id.
+//! - `st_nlink` is 2 for directories and 1 for files and symlinks
+//!   ([`nlink`]); subdirectories aren't counted.
+//! - `st_blocks` counts 512-byte units of the logical size ([`blocks`]).
+//!
+//! Everything here is `const` and allocation-free: the inception layer calls
+//! it on its stat hot path.
+
+use crate::fnv1a_hash;
+
+/// Device id reported for every virtual file ("RIFT")
+pub const DEV: u64 = 0x52494654;
+
+/// Preferred I/O size reported for virtual files
+pub const BLKSIZE: u32 = 4096;
+
+/// Inode of the manifest root ("/"), same as FUSE_ROOT_ID
+pub const ROOT_INO: u64 = 1;
+
+const ROOT_KEY_HASH: u64 = fnv1a_hash("/");
+const EMPTY_KEY_HASH: u64 = fnv1a_hash("");
+
+/// Inode for a manifest key hash ([`fnv1a_hash`] of the key)
+///
+/// Both spellings of the root map to [`ROOT_INO`]; the values FUSE
+/// reserves (0 and the root id) are moved to the top half of the range.
+#[inline(always)]
+pub const fn ino_from_hash(key_hash: u64) -> u64 {
+    if key_hash == ROOT_KEY_HASH || key_hash == EMPTY_KEY_HASH {
+        ROOT_INO
+    } else if key_hash <= ROOT_INO {
+        key_hash | (1 << 63)
+    } else {
+        key_hash
+    }
+}
+
+/// Inode for a manifest key
+#[inline(always)]
+pub const fn ino(key: &str) -> u64 {
+    ino_from_hash(fnv1a_hash(key))
+}
+
+/// Next inode to try when `ino` is already taken by another path
+///
+/// Only a backend that sees every path (FUSE) can detect a collision; it
+/// probes with this so the result never lands on a reserved value.
+#[inline(always)]
+pub const fn next_ino(ino: u64) -> u64 {
+    match ino.wrapping_add(1) {
+        0 => 2,
+        next => next,
+    }
+}
+
+/// Link count for a directory (`is_dir`), file or symlink
+#[inline(always)]
+pub const fn nlink(is_dir: bool) -> u32 {
+    if is_dir {
+        2
+    } else {
+        1
+    }
+}
+
+/// `st_blocks` for a file of `size` bytes
+#[inline(always)]
+pub const fn blocks(size: u64) -> u64 {
+    size.div_ceil(512)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_keys_map_to_root_ino() {
+        assert_eq!(ino("/"), ROOT_INO);
+        assert_eq!(ino(""), ROOT_INO);
+    }
+
+    #[test]
+    fn test_ino_is_key_hash() {
+        assert_eq!(ino("/src/main.rs"), fnv1a_hash("/src/main.rs"));
+        assert_ne!(ino("/src/main.rs"), ino("/src/lib.rs"));
+        assert_eq!(ino_from_hash(0), 1 << 63);
+        assert_eq!(ino_from_hash(1), (1 << 63) | 1);
+        assert_eq!(next_ino(u64::MAX), 2);
+    }
+
+    #[test]
+    fn test_nlink_by_type() {
+        assert_eq!(nlink(true), 2);
+        assert_eq!(nlink(false), 1);
+        assert_eq!(blocks(0), 0);
+        assert_eq!(blocks(513), 2);
+    }
+}
@@ -1,3 +1,8 @@
+pub mod heatmap;
+pub mod identity;
+pub mod manifest_mmap;
+pub mod mux;
+pub mod peer;
 pub mod vdir_types;
 use rkyv::Archive;
 use serde::{Deserialize, Serialize};
@@ -12,6 +17,114 @@ pub const PROTOCOL_VERSION: u32 = 4;
 /// Minimum protocol version this server supports
 pub const MIN_PROTOCOL_VERSION: u32 = 1;
 
+/// Optional features a peer understands, exchanged by `Negotiate`
+///
+/// Additive features are announced here instead of by bumping
+/// `PROTOCOL_VERSION`, so an older peer simply doesn't get them. A peer
+/// that only knows the legacy `Handshake` is assumed to have none.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct Capabilities(pub u64);
+
+impl Capabilities {
+    /// Workspace VDir published as a shared mmap for shim lookups
+    pub const MMAP_CACHE: Self = Self(1 << 0);
+    /// Several requests in flight on one connection (see `mux`)
+    pub const BATCH_REQUESTS: Self = Self(1 << 1);
+    /// `ManifestRename` moves a subtree in one request
+    pub const RENAME: Self = Self(1 << 2);
+    /// CAS blobs transferred in chunks (reserved, nothing offers it yet)
+    pub const CHUNKED_CAS: Self = Self(1 << 3);
+    /// `ManifestGetXattrs` / `ManifestSetXattr` serve manifest entry xattrs
+    pub const XATTRS: Self = Self(1 << 4);
+    /// Manifest keys published as a shared mmap (see `manifest_mmap`), so
+    /// shims answer "not in the manifest" without asking
+    pub const MANIFEST_MMAP: Self = Self(1 << 5);
+
+    /// Everything this build understands, as a client
+    pub const SUPPORTED: Self = Self(
+        Self::MMAP_CACHE.0
+            | Self::BATCH_REQUESTS.0
+            | Self::RENAME.0
+            | Self::XATTRS.0
+            | Self::MANIFEST_MMAP.0,
+    );
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Features both sides have
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Names of the known bits that are set, for logs
+    pub fn names(self) -> Vec<&'static str> {
+        [
+            (Self::MMAP_CACHE, "mmap-cache"),
+            (Self::BATCH_REQUESTS, "batch-requests"),
+            (Self::RENAME, "rename"),
+            (Self::CHUNKED_CAS, "chunked-cas"),
+            (Self::XATTRS, "xattrs"),
+            (Self::MANIFEST_MMAP, "manifest-mmap"),
+        ]
+        .into_iter()
+        .filter(|(cap, _)| self.contains(*cap))
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// What the two ends of a connection agreed on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negotiated {
+    pub server_version: String,
+    /// Highest protocol version both sides speak
+    pub protocol_version: u32,
+    /// Features both sides have
+    pub capabilities: Capabilities,
+}
+
+/// Server side of `Negotiate`: the agreed protocol version, or None if the
+/// ranges don't overlap, and the shared capabilities
+pub fn negotiate(
+    client_protocol: u32,
+    client_capabilities: Capabilities,
+    server_capabilities: Capabilities,
+) -> (Option<u32>, Capabilities) {
+    let version = client_protocol.min(PROTOCOL_VERSION);
+    let version = is_version_compatible(version).then_some(version);
+    (
+        version,
+        client_capabilities.intersection(server_capabilities),
+    )
+}
+
 // ============================================================================
 // IPC Wire Format (v3+)
 // ============================================================================
@@ -157,15 +270,55 @@ pub fn next_seq_id() -> u32 {
 }
 
 /// Synchronous frame IO (for vrift-shim and blocking contexts)
+///
+/// The shim calls these from inside intercepted syscalls, where a heap
+/// allocation may land in a malloc that is itself being intercepted. Frames
+/// up to [`STACK_FRAME`](frame_sync::STACK_FRAME) bytes (every ManifestGet,
+/// ManifestUpsert and their acks) are therefore serialized and read on the
+/// stack; only larger ones fall back to the heap.
 pub mod frame_sync {
     use super::*;
     use std::io::{Read, Write};
+    use std::mem::MaybeUninit;
+
+    /// Largest payload framed without touching the heap
+    pub const STACK_FRAME: usize = 1024;
+
+    /// Scratch space rkyv may need while serializing on the stack
+    const STACK_SCRATCH: usize = 256;
+
+    /// Payload buffer with the alignment rkyv expects of its input
+    #[repr(C, align(16))]
+    struct StackFrame([u8; STACK_FRAME]);
+
+    /// Serialize `value` into `buf`; None if it doesn't fit
+    fn serialize_on_stack<'a, T>(value: &T, buf: &'a mut StackFrame) -> Option<&'a [u8]>
+    where
+        T: for<'b> rkyv::Serialize<
+            rkyv::api::high::HighSerializer<
+                rkyv::ser::writer::Buffer<'b>,
+                rkyv::ser::allocator::SubAllocator<'b>,
+                rkyv::rancor::Failure,
+            >,
+        >,
+    {
+        let mut scratch = [MaybeUninit::<u8>::uninit(); STACK_SCRATCH];
+        let written = rkyv::api::high::to_bytes_in_with_alloc::<_, _, rkyv::rancor::Failure>(
+            value,
+            rkyv::ser::writer::Buffer::from(&mut buf.0[..]),
+            rkyv::ser::allocator::SubAllocator::new(&mut scratch),
+        )
+        .ok()?;
+        let len = written.len();
+        Some(&buf.0[..len])
+    }
 
-    /// Send a request frame (header + rkyv payload)
-    pub fn send_request<W: Write>(writer: &mut W, request: &VeloRequest) -> std::io::Result<u32> {
-        let payload = rkyv::to_bytes::<rkyv::rancor::Error>(request)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-
+    /// Write one frame: header, then `payload`
+    fn write_frame<W: Write>(
+        writer: &mut W,
+        header: IpcHeader,
+        payload: &[u8],
+    ) -> std::io::Result<()> {
         if payload.len() > IpcHeader::MAX_LENGTH {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -176,15 +329,67 @@ pub mod frame_sync {
                 ),
             ));
         }
+        writer.write_all(&header.to_bytes())?;
+        writer.write_all(payload)?;
+        writer.flush()
+    }
+
+    /// Read a `len`-byte payload and decode it, on the stack when it fits.
+    /// For callers that read and check the header themselves.
+    pub fn read_payload<R, T>(reader: &mut R, len: usize) -> std::io::Result<T>
+    where
+        R: Read,
+        T: rkyv::Archive,
+        T::Archived: for<'a> rkyv::bytecheck::CheckBytes<
+                rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>,
+            > + rkyv::Deserialize<T, rkyv::api::high::HighDeserializer<rkyv::rancor::Error>>,
+    {
+        let decode = |bytes: &[u8]| {
+            rkyv::from_bytes::<T, rkyv::rancor::Error>(bytes)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+        };
+        if len > IpcHeader::MAX_LENGTH {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("payload too large: {} > {}", len, IpcHeader::MAX_LENGTH),
+            ));
+        }
+        if len <= STACK_FRAME {
+            let mut buf = StackFrame([0; STACK_FRAME]);
+            reader.read_exact(&mut buf.0[..len])?;
+            decode(&buf.0[..len])
+        } else {
+            let mut payload = rkyv::util::AlignedVec::<16>::with_capacity(len);
+            payload.resize(len, 0);
+            reader.read_exact(&mut payload)?;
+            decode(&payload)
+        }
+    }
 
+    /// Send a request frame (header + rkyv payload)
+    pub fn send_request<W: Write>(writer: &mut W, request: &VeloRequest) -> std::io::Result<u32> {
         let seq_id = next_seq_id();
-        let header = IpcHeader::new_request(payload.len() as u32, seq_id);
+        send_request_with_seq(writer, request, seq_id)?;
+        Ok(seq_id)
+    }
 
-        writer.write_all(&header.to_bytes())?;
-        writer.write_all(&payload)?;
-        writer.flush()?;
+    /// Send a request frame under a seq_id the caller picked (multiplexed
+    /// clients register the id before the response can arrive)
+    pub fn send_request_with_seq<W: Write>(
+        writer: &mut W,
+        request: &VeloRequest,
+        seq_id: u32,
+    ) -> std::io::Result<()> {
+        let mut buf = StackFrame([0; STACK_FRAME]);
+        if let Some(payload) = serialize_on_stack(request, &mut buf) {
+            let header = IpcHeader::new_request(payload.len() as u32, seq_id);
+            return write_frame(writer, header, payload);
+        }
 
-        Ok(seq_id)
+        let payload = rkyv::to_bytes::<rkyv::rancor::Error>(request)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let header = IpcHeader::new_request(payload.len() as u32, seq_id);
+        write_frame(writer, header, &payload)
     }
 
     /// Send a response frame
@@ -193,27 +398,16 @@ pub mod frame_sync {
         response: &VeloResponse,
         seq_id: u32,
     ) -> std::io::Result<()> {
-        let payload = rkyv::to_bytes::<rkyv::rancor::Error>(response)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-
-        if payload.len() > IpcHeader::MAX_LENGTH {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "payload too large: {} > {}",
-                    payload.len(),
-                    IpcHeader::MAX_LENGTH
-                ),
-            ));
+        let mut buf = StackFrame([0; STACK_FRAME]);
+        if let Some(payload) = serialize_on_stack(response, &mut buf) {
+            let header = IpcHeader::new_response(payload.len() as u32, seq_id);
+            return write_frame(writer, header, payload);
         }
 
+        let payload = rkyv::to_bytes::<rkyv::rancor::Error>(response)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
         let header = IpcHeader::new_response(payload.len() as u32, seq_id);
-
-        writer.write_all(&header.to_bytes())?;
-        writer.write_all(&payload)?;
-        writer.flush()?;
-
-        Ok(())
+        write_frame(writer, header, &payload)
     }
 
     /// Read a frame header
@@ -265,14 +459,7 @@ pub mod frame_sync {
                 ));
             }
 
-            let mut payload = vec![0u8; header.length as usize];
-            reader.read_exact(&mut payload)?;
-
-            let request: VeloRequest =
-                rkyv::from_bytes::<VeloRequest, rkyv::rancor::Error>(&payload).map_err(|e| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
-                })?;
-
+            let request = read_payload(reader, header.length as usize)?;
             return Ok((header, request));
         }
     }
@@ -294,14 +481,7 @@ pub mod frame_sync {
                 ));
             }
 
-            let mut payload = vec![0u8; header.length as usize];
-            reader.read_exact(&mut payload)?;
-
-            let response: VeloResponse =
-                rkyv::from_bytes::<VeloResponse, rkyv::rancor::Error>(&payload).map_err(|e| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
-                })?;
-
+            let response = read_payload(reader, header.length as usize)?;
             return Ok((header, response));
         }
     }
@@ -334,6 +514,17 @@ pub mod frame_async {
         writer: &mut W,
         request: &VeloRequest,
     ) -> std::io::Result<u32> {
+        let seq_id = next_seq_id();
+        send_request_with_seq(writer, request, seq_id).await?;
+        Ok(seq_id)
+    }
+
+    /// Send a request frame under a seq_id the caller picked
+    pub async fn send_request_with_seq<W: AsyncWriteExt + Unpin>(
+        writer: &mut W,
+        request: &VeloRequest,
+        seq_id: u32,
+    ) -> std::io::Result<()> {
         let payload = rkyv::to_bytes::<rkyv::rancor::Error>(request)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
 
@@ -348,14 +539,13 @@ pub mod frame_async {
             ));
         }
 
-        let seq_id = next_seq_id();
         let header = IpcHeader::new_request(payload.len() as u32, seq_id);
 
         writer.write_all(&header.to_bytes()).await?;
         writer.write_all(&payload).await?;
         writer.flush().await?;
 
-        Ok(seq_id)
+        Ok(())
     }
 
     /// Send a response frame
@@ -559,10 +749,13 @@ pub enum VeloRequest {
         protocol_version: u32,
     },
     Status,
+    /// Run a command under vriftd's supervision. Its output is kept for
+    /// `SpawnPoll` and, with `log_path`, also appended to that file.
     Spawn {
         command: Vec<String>,
         env: Vec<(String, String)>,
         cwd: String,
+        log_path: Option<String>,
     },
     CasInsert {
         hash: [u8; 32],
@@ -584,7 +777,9 @@ pub enum VeloRequest {
         path: String,
         entry: VnodeEntry,
     },
-    /// RFC-0047: Remove a manifest entry (for unlink/rmdir)
+    /// RFC-0047: Remove a manifest entry (for unlink/rmdir). The path is
+    /// left as a tombstone (`VnodeEntry::is_whiteout`), so ManifestGet
+    /// answers with it and the file on disk stays hidden.
     ManifestRemove {
         path: String,
     },
@@ -609,6 +804,13 @@ pub enum VeloRequest {
     ManifestListDir {
         path: String,
     },
+    /// One batch of a directory listing, for streaming large directories:
+    /// up to `limit` children whose name sorts after `after` ("" = from the start)
+    ManifestListDirPage {
+        path: String,
+        after: String,
+        limit: u32,
+    },
     /// RFC-0049: Acquire advisory lock on logical file
     FlockAcquire {
         path: String,
@@ -649,15 +851,267 @@ pub enum VeloRequest {
         cas_root: Option<String>,
         /// Force full file read+hash, bypassing mtime+size cache skip (P0)
         force_hash: bool,
+        /// Store compressible blobs zstd-compressed
+        compress: bool,
+        /// Gitignore-style patterns to skip on top of the configured ignore
+        /// rules (e.g. from an ingest profile)
+        ignore: Vec<String>,
+    },
+    /// Structured counters for `vrift status --watch` (answered by vriftd and vDird)
+    Metrics,
+    /// Shim → vDird, fire-and-forget: VDir misses since the last report,
+    /// as VDir path hashes ([`fnv1a_hash`] of the manifest key). Feeds the
+    /// hot-cache admission policy.
+    MissReport {
+        path_hashes: Vec<u64>,
+    },
+    /// Output of a spawned child past the given byte offsets, and its exit
+    /// status once it has exited. Waits up to `wait_ms` for either.
+    SpawnPoll {
+        pid: u32,
+        stdout_offset: u64,
+        stderr_offset: u64,
+        wait_ms: u32,
+    },
+    /// Send `signal` to a spawned child's process group
+    Kill {
+        pid: u32,
+        signal: i32,
+    },
+    /// Store the workspace manifest as an immutable generation (vDird).
+    /// With `protect`, vDird refuses later changes to the snapshot's
+    /// entries and locks down the CAS blobs they reference.
+    SnapshotCreate {
+        label: String,
+        protect: bool,
+    },
+    /// Snapshots taken for the workspace, oldest first (vDird)
+    SnapshotList,
+    /// Atomically replace the workspace manifest with a snapshot (vDird).
+    /// `id` may be a unique hex prefix. The current state is snapshotted
+    /// first so the rollback itself can be undone.
+    SnapshotRestore {
+        id: String,
     },
+    /// Lift a snapshot's protection (vDird). Refused unless `force` is set.
+    SnapshotUnprotect {
+        id: String,
+        force: bool,
+    },
+    /// Republish the VDir from the manifest on disk (vDird), picking up
+    /// edits made underneath a running vDird. Same as sending it SIGHUP.
+    ManifestReload,
+    /// Handshake that also agrees on a protocol version and feature set.
+    /// Daemons that predate it fail to decode it; clients then fall back
+    /// to `Handshake`.
+    Negotiate {
+        client_version: String,
+        /// Highest protocol version the client speaks
+        protocol_version: u32,
+        capabilities: Capabilities,
+    },
+    /// Build-cache key for running `command` over the workspace (vDird).
+    /// Covers every entry under an `inputs` prefix (all entries if there
+    /// are none) and not under an `exclude` prefix, by path, content, size,
+    /// mode and type, but not mtime, so the same sources give the same key
+    /// in any checkout.
+    BuildCacheKey {
+        inputs: Vec<String>,
+        exclude: Vec<String>,
+        command: Vec<String>,
+    },
+    /// Extended attributes of a manifest entry (vDird)
+    ManifestGetXattrs {
+        path: String,
+    },
+    /// Set (`Some`) or remove (`None`) one extended attribute of a manifest
+    /// entry (vDird). Answered with `ManifestAck`.
+    ManifestSetXattr {
+        path: String,
+        name: String,
+        value: Option<Vec<u8>>,
+    },
+    /// Follow the manifest symlinks in `path` (vDird), at most
+    /// `vrift_manifest::MAX_SYMLINK_HOPS` of them; more is a `SymlinkLoop`
+    /// error. The final component is followed only with `follow_final`.
+    ManifestResolve {
+        path: String,
+        follow_final: bool,
+    },
+    /// Serve another project from the vDird this is sent to, with its own
+    /// manifest, staging dir, VDir, socket and Metrics. A project it
+    /// already serves is just answered with its endpoints.
+    ProjectOpen {
+        /// Absolute path to the project root
+        project_root: String,
+    },
+}
+
+impl VeloRequest {
+    /// `Handshake` or `Negotiate`: answered for any peer, so a refused or
+    /// incompatible client learns why
+    pub fn is_handshake(&self) -> bool {
+        matches!(self, Self::Handshake { .. } | Self::Negotiate { .. })
+    }
+
+    /// Why this build can't talk to the client sending this handshake, if
+    /// it can't. A `Negotiate` from a newer client is fine as long as it
+    /// still speaks a version this build does.
+    pub fn handshake_error(&self) -> Option<VeloError> {
+        let (protocol_version, compatible) = match self {
+            Self::Handshake {
+                protocol_version, ..
+            } => (*protocol_version, is_version_compatible(*protocol_version)),
+            Self::Negotiate {
+                protocol_version,
+                capabilities,
+                ..
+            } => (
+                *protocol_version,
+                negotiate(*protocol_version, *capabilities, Capabilities::empty())
+                    .0
+                    .is_some(),
+            ),
+            _ => return None,
+        };
+        (!compatible).then(|| {
+            VeloError::version_mismatch(format!(
+                "Client protocol {} is not supported (daemon speaks {}..={})",
+                protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ))
+        })
+    }
+}
+
+/// How a spawned child ended
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub struct SpawnExit {
+    /// Exit code, if it exited normally
+    pub code: Option<i32>,
+    /// Terminating signal, if it was killed
+    pub signal: Option<i32>,
+}
+
+impl SpawnExit {
+    /// Shell convention: the exit code, or 128 + signal
+    pub fn shell_code(&self) -> i32 {
+        match (self.code, self.signal) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => 1,
+        }
+    }
+}
+
+/// Answer to `SpawnPoll`. Offsets count bytes since the child started; pass
+/// the returned ones to the next poll. A client that falls too far behind
+/// resumes at the oldest output the daemon still holds.
+#[derive(
+    Debug, Clone, Default, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+pub struct SpawnOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub stdout_offset: u64,
+    pub stderr_offset: u64,
+    /// Set once the child has exited and all its output has been returned
+    pub exit: Option<SpawnExit>,
+}
+
+/// One manifest snapshot. `id` is the hex BLAKE3 hash of the stored
+/// generation, so equal manifests have equal ids.
+#[derive(Debug, Clone, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    /// Unix time (seconds) the snapshot was taken
+    pub created_secs: u64,
+    pub entries: u64,
+    pub label: String,
+    /// vDird refuses changes to this snapshot's entries
+    pub protected: bool,
+}
+
+/// A CoW commit recently folded back into the manifest by vDird
+#[derive(Debug, Clone, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct ReingestEvent {
+    pub vpath: String,
+    pub size: u64,
+    /// Unix time (seconds) the reingest completed
+    pub at_secs: u64,
+}
+
+/// Daemon counters. vriftd fills the CAS/process fields, vDird the
+/// manifest/journal/reingest fields; the rest stay zero.
+#[derive(
+    Debug, Clone, Default, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+pub struct DaemonMetrics {
+    pub uptime_secs: u64,
+    /// Currently open client connections
+    pub connections: u64,
+    pub cas_blobs: u64,
+    pub cas_bytes: u64,
+    pub vdird_processes: u64,
+    pub manifest_entries: u64,
+    /// Reingests journaled but not yet completed
+    pub pending_journal: u64,
+    /// Reingests completed since startup
+    pub reingests: u64,
+    /// Most recent reingests, newest last
+    pub recent_reingests: Vec<ReingestEvent>,
+    /// Lookups served over IPC per path prefix (vDird; see [`heatmap`])
+    pub heat: Vec<heatmap::HeatCell>,
+    /// VDir hot-cache admissions and evictions since startup (vDird, capped VDir only)
+    pub vdir_admissions: u64,
+    pub vdir_evictions: u64,
+    /// VDir misses reported by shims (vDird)
+    pub vdir_misses_reported: u64,
+    /// Full-scan ingests running now and their combined progress (vriftd)
+    pub ingests_running: u64,
+    pub ingest_files_found: u64,
+    pub ingest_files_done: u64,
+    pub ingest_bytes_done: u64,
+    /// Every running ingest has finished its walk (`ingest_files_found` is final)
+    pub ingest_scan_complete: bool,
 }
 
+/// One child in a synthesized directory listing.
+///
+/// Ordering contract: every listing (`ManifestListAck`, `ManifestListPageAck`
+/// and the shim's readdir on top of them) is sorted by `name` in plain byte
+/// order, the same order `LC_ALL=C ls` gives. Builds that glob a virtual directory then see
+/// the same input order on every run. Use [`sort_dir_entries`] to apply it.
 #[derive(Debug, Clone, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct DirEntry {
     pub name: String,
     pub is_dir: bool,
 }
 
+/// One extended attribute of a manifest entry
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+pub struct Xattr {
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+/// Put a listing into the documented [`DirEntry`] order
+pub fn sort_dir_entries(entries: &mut [DirEntry]) {
+    entries.sort_unstable_by(|a, b| a.name.as_bytes().cmp(b.name.as_bytes()));
+}
+
 #[cfg(feature = "manifest")]
 pub use vrift_manifest::VnodeEntry;
 
@@ -690,6 +1144,14 @@ impl VnodeEntry {
     pub fn is_dir(&self) -> bool {
         (self.flags & 1) != 0
     }
+
+    pub fn is_chunked(&self) -> bool {
+        (self.flags & vdir_types::FLAG_CHUNKED) != 0
+    }
+
+    pub fn is_whiteout(&self) -> bool {
+        (self.flags & 0x20) != 0
+    }
 }
 
 // ============================================================================
@@ -726,6 +1188,56 @@ pub enum VeloErrorKind {
     LockFailed,
     /// Internal server error
     Internal,
+    // New kinds go last: the rkyv discriminant is the variant index
+    /// Target already exists, or a concurrent change won
+    Conflict,
+    /// Temporarily unable to serve (draining, queue full); retrying may succeed
+    Busy,
+    /// Stored data failed validation (bad checksum, undecodable manifest)
+    Corrupt,
+    /// Path resolution followed too many symlinks (a cycle or a very long chain)
+    SymlinkLoop,
+    /// Client and daemon speak incompatible protocol versions
+    VersionMismatch,
+}
+
+impl VeloErrorKind {
+    /// errno the inception layer reports for this kind
+    pub fn errno(self) -> i32 {
+        match self {
+            Self::NotFound => libc::ENOENT,
+            Self::PermissionDenied => libc::EACCES,
+            Self::InvalidPath => libc::EINVAL,
+            Self::Conflict => libc::EEXIST,
+            Self::Busy => libc::EAGAIN,
+            Self::LockFailed => libc::EWOULDBLOCK,
+            Self::SymlinkLoop => libc::ELOOP,
+            Self::VersionMismatch => libc::EPROTO,
+            Self::WorkspaceNotRegistered
+            | Self::IngestFailed
+            | Self::IoError
+            | Self::Corrupt
+            | Self::Internal => libc::EIO,
+        }
+    }
+
+    /// Classify an I/O failure; kinds with no better match become `fallback`
+    pub fn from_io(err: &std::io::Error, fallback: Self) -> Self {
+        use std::io::ErrorKind;
+        match err.kind() {
+            ErrorKind::NotFound => Self::NotFound,
+            ErrorKind::PermissionDenied => Self::PermissionDenied,
+            ErrorKind::AlreadyExists => Self::Conflict,
+            ErrorKind::WouldBlock | ErrorKind::Interrupted | ErrorKind::TimedOut => Self::Busy,
+            ErrorKind::InvalidData | ErrorKind::UnexpectedEof => Self::Corrupt,
+            _ => fallback,
+        }
+    }
+
+    /// Whether the same request may succeed if simply retried later
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Busy | Self::LockFailed)
+    }
 }
 
 /// Structured error for IPC responses
@@ -792,6 +1304,22 @@ impl VeloError {
         Self::new(VeloErrorKind::Internal, message)
     }
 
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(VeloErrorKind::Conflict, message)
+    }
+
+    pub fn busy(message: impl Into<String>) -> Self {
+        Self::new(VeloErrorKind::Busy, message)
+    }
+
+    pub fn corrupt(message: impl Into<String>) -> Self {
+        Self::new(VeloErrorKind::Corrupt, message)
+    }
+
+    pub fn version_mismatch(message: impl Into<String>) -> Self {
+        Self::new(VeloErrorKind::VersionMismatch, message)
+    }
+
     /// Set path on an existing error (builder pattern)
     pub fn set_path(mut self, path: impl Into<String>) -> Self {
         self.path = Some(path.into());
@@ -803,20 +1331,28 @@ impl VeloError {
     /// Uses standard Unix exit code conventions:
     /// - 1: General error (Internal, IoError)
     /// - 2: Not found (NotFound, WorkspaceNotRegistered)
-    /// - 22: Invalid argument (InvalidPath)
+    /// - 22: Invalid argument (InvalidPath, SymlinkLoop)
     /// - 77: Permission denied (PermissionDenied)
     /// - 78: Lock failure (LockFailed)
     /// - 79: Ingest failure (IngestFailed)
+    /// - 65: Corrupt data (Corrupt, EX_DATAERR)
+    /// - 73: Conflict (Conflict, EX_CANTCREAT)
+    /// - 75: Busy, try again (Busy, EX_TEMPFAIL)
+    /// - 76: Protocol version mismatch (VersionMismatch, EX_PROTOCOL)
     pub fn exit_code(&self) -> i32 {
         match self.kind {
             VeloErrorKind::NotFound => 2,
             VeloErrorKind::WorkspaceNotRegistered => 2,
-            VeloErrorKind::InvalidPath => 22,
+            VeloErrorKind::InvalidPath | VeloErrorKind::SymlinkLoop => 22,
             VeloErrorKind::PermissionDenied => 77,
             VeloErrorKind::LockFailed => 78,
             VeloErrorKind::IngestFailed => 79,
             VeloErrorKind::IoError => 1,
             VeloErrorKind::Internal => 1,
+            VeloErrorKind::Corrupt => 65,
+            VeloErrorKind::Conflict => 73,
+            VeloErrorKind::Busy => 75,
+            VeloErrorKind::VersionMismatch => 76,
         }
     }
 }
@@ -855,11 +1391,22 @@ pub enum VeloResponse {
     CasNotFound,
     ManifestAck {
         entry: Option<VnodeEntry>,
+        /// Inode vDird allocated the entry (ManifestGet replies; 0 otherwise)
+        #[serde(default)]
+        ino: u64,
     },
-    /// Directory listing response for VFS synthesis
+    /// Directory listing response for VFS synthesis (byte-ordered, see [`DirEntry`])
     ManifestListAck {
         entries: Vec<DirEntry>,
     },
+    /// One batch of a listing, in [`DirEntry`] order. `names` holds each
+    /// name followed by a NUL, `dirs` whether each is a directory; `more`
+    /// is set if children follow the last one.
+    ManifestListPageAck {
+        names: Vec<u8>,
+        dirs: Vec<bool>,
+        more: bool,
+    },
     ProtectAck,
     /// Result of Garbage Collection sweep
     CasSweepAck {
@@ -875,6 +1422,9 @@ pub enum VeloResponse {
         vdird_socket: String,
         /// VDir mmap file path for O(1) stat lookups
         vdir_mmap_path: String,
+        /// Session id for this registration; keys the session's staging dir
+        #[serde(default)]
+        session_id: String,
     },
     /// Ingest completion acknowledgement
     IngestAck {
@@ -891,8 +1441,65 @@ pub enum VeloResponse {
         /// Manifest path
         manifest_path: String,
     },
+    /// Structured daemon counters
+    MetricsAck {
+        metrics: DaemonMetrics,
+    },
+    MissReportAck,
+    SpawnPollAck {
+        output: SpawnOutput,
+    },
+    KillAck,
+    SnapshotAck {
+        snapshot: SnapshotInfo,
+    },
+    SnapshotListAck {
+        snapshots: Vec<SnapshotInfo>,
+    },
+    SnapshotRestoreAck {
+        /// The snapshot now in effect
+        restored: SnapshotInfo,
+        /// Snapshot of the manifest as it was before the restore
+        previous: SnapshotInfo,
+    },
+    ManifestReloadAck {
+        /// Entries published in the rebuilt VDir
+        entries: u64,
+        /// VDir generation after the reload; shims holding an older one
+        /// re-read
+        generation: u64,
+    },
+    NegotiateAck {
+        server_version: String,
+        /// Version both sides use; None if the client's is unsupported
+        protocol_version: Option<u32>,
+        /// Features both sides have
+        capabilities: Capabilities,
+    },
     /// Structured error response (Phase 3: replaces Error(String))
     Error(VeloError),
+    BuildCacheKeyAck {
+        key: [u8; 32],
+        /// Manifest entries the key covers
+        entries: u64,
+    },
+    /// An entry's extended attributes, sorted by name
+    ManifestXattrsAck {
+        xattrs: Vec<Xattr>,
+    },
+    /// Where a `ManifestResolve` path leads. Components that don't exist
+    /// are kept, so the path need not have an entry.
+    ManifestResolveAck {
+        path: String,
+    },
+    /// Where a project opened by `ProjectOpen` is served
+    ProjectAck {
+        project_id: String,
+        /// vDird socket path for that project's manifest operations
+        vdird_socket: String,
+        /// VDir mmap file path for O(1) stat lookups
+        vdir_mmap_path: String,
+    },
 }
 
 /// Check if a protocol version is compatible with this build
@@ -933,422 +1540,319 @@ pub fn bloom_hashes(s: &str) -> (usize, usize) {
 }
 
 // ============================================================================
-// Manifest Mmap Shared Memory (RFC-0044 Hot Stat Cache)
+// Manifest key encoding for non-UTF-8 filenames
 // ============================================================================
 
-/// Magic number for manifest mmap file: "VMMP" (Vrift Manifest MmaP)
-pub const MMAP_MAGIC: u32 = 0x504D4D56;
-/// Current mmap format version
-pub const MMAP_VERSION: u32 = 1;
-/// Maximum entries in the hash table (power of 2 for fast modulo)
-pub const MMAP_MAX_ENTRIES: usize = 65536;
-
-/// Header for the mmap'd manifest file
-/// Layout: [Header][Bloom Filter][Hash Table]
-#[deprecated(note = "Phase 2: Use VDirHeader from vrift-vdird instead (MAP_SHARED + seqlock)")]
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct ManifestMmapHeader {
-    pub magic: u32,
-    pub version: u32,
-    pub entry_count: u32,
-    pub bloom_offset: u32,       // Offset to bloom filter (BLOOM_SIZE)
-    pub table_offset: u32,       // Offset to stat hash table (table_capacity * MmapStatEntry::SIZE)
-    pub table_capacity: u32,     // Number of slots in stat hash table
-    pub dir_index_offset: u32,   // Offset to directory index table
-    pub dir_index_capacity: u32, // Capacity of directory index table
-    pub children_offset: u32,    // Offset to children pool
-    pub children_count: u32,     // Total children across all directories
-}
-
-#[allow(deprecated)]
-impl ManifestMmapHeader {
-    pub const SIZE: usize = std::mem::size_of::<Self>();
-
-    pub fn new(
-        entry_count: u32,
-        table_capacity: u32,
-        dir_index_capacity: u32,
-        children_count: u32,
-    ) -> Self {
-        let bloom_offset = Self::SIZE as u32;
-        let table_offset = bloom_offset + BLOOM_SIZE as u32;
-        let dir_index_offset = table_offset + (table_capacity * MmapStatEntry::SIZE as u32);
-        let children_offset =
-            dir_index_offset + (dir_index_capacity * MmapDirIndexEntry::SIZE as u32);
-
-        Self {
-            magic: MMAP_MAGIC,
-            version: MMAP_VERSION,
-            entry_count,
-            bloom_offset,
-            table_offset,
-            table_capacity,
-            dir_index_offset,
-            dir_index_capacity,
-            children_offset,
-            children_count,
+/// First code point used to carry a raw byte in a manifest key.
+///
+/// Manifest keys travel as UTF-8 strings, but Unix filenames are arbitrary
+/// bytes. Every byte that is not part of valid UTF-8 (always >= 0x80) is
+/// stored as `U+10FF00 + byte`, in the Plane 16 private use area, so two
+/// different names never collapse onto the same key the way
+/// `to_string_lossy()` collapses them onto U+FFFD. Valid UTF-8 maps to itself.
+pub const PATH_KEY_ESCAPE_BASE: u32 = 0x10FF00;
+
+/// Encode raw filename bytes as a manifest key (lossless, see [`PATH_KEY_ESCAPE_BASE`])
+pub fn encode_path_key(mut bytes: &[u8]) -> std::borrow::Cow<'_, str> {
+    let first_err = match std::str::from_utf8(bytes) {
+        Ok(s) => return std::borrow::Cow::Borrowed(s),
+        Err(e) => e,
+    };
+    let mut out = String::with_capacity(bytes.len() + 8);
+    let mut err = first_err;
+    loop {
+        let (valid, rest) = bytes.split_at(err.valid_up_to());
+        // SAFETY: from_utf8 just validated this prefix
+        out.push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+        // A truncated sequence at the end has no error_len: escape what's left
+        let bad = err.error_len().unwrap_or(rest.len());
+        for &b in &rest[..bad] {
+            out.push(char::from_u32(PATH_KEY_ESCAPE_BASE + b as u32).unwrap_or('\u{FFFD}'));
+        }
+        bytes = &rest[bad..];
+        match std::str::from_utf8(bytes) {
+            Ok(s) => {
+                out.push_str(s);
+                return std::borrow::Cow::Owned(out);
+            }
+            Err(e) => err = e,
         }
-    }
-
-    pub fn is_valid(&self) -> bool {
-        self.magic == MMAP_MAGIC && self.version == MMAP_VERSION
     }
 }
 
-/// Single stat entry in the hash table
-/// Uses open addressing with linear probing
-#[deprecated(note = "Phase 2: Use VDirEntry from vrift-vdird instead")]
-#[repr(C)]
-#[derive(Debug, Clone, Copy, Default)]
-pub struct MmapStatEntry {
-    pub path_hash: u64, // FNV-1a hash of path (0 = empty slot)
-    pub size: u64,
-    pub mtime: i64,
-    pub mtime_nsec: i64,
-    pub mode: u32,
-    pub flags: u32, // EntryFlags: is_dir, is_symlink, etc.
-}
-
-#[allow(deprecated)]
-impl MmapStatEntry {
-    pub const SIZE: usize = std::mem::size_of::<Self>();
-
-    pub fn is_empty(&self) -> bool {
-        self.path_hash == 0
-    }
-
-    pub fn is_dir(&self) -> bool {
-        (self.flags & 0x01) != 0
-    }
-
-    pub fn is_symlink(&self) -> bool {
-        (self.flags & 0x02) != 0
+/// Recover the filename bytes from a key produced by [`encode_path_key`]
+pub fn decode_path_key(key: &str) -> std::borrow::Cow<'_, [u8]> {
+    let escaped =
+        |c: char| (PATH_KEY_ESCAPE_BASE + 0x80..=PATH_KEY_ESCAPE_BASE + 0xFF).contains(&(c as u32));
+    if !key.chars().any(escaped) {
+        return std::borrow::Cow::Borrowed(key.as_bytes());
+    }
+    let mut out = Vec::with_capacity(key.len());
+    let mut buf = [0u8; 4];
+    for c in key.chars() {
+        if escaped(c) {
+            out.push((c as u32 - PATH_KEY_ESCAPE_BASE) as u8);
+        } else {
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
     }
+    std::borrow::Cow::Owned(out)
 }
 
-/// Directory index entry (parent -> children)
-#[repr(C)]
-#[derive(Debug, Clone, Copy, Default)]
-pub struct MmapDirIndexEntry {
-    pub parent_hash: u64,    // FNV-1a hash of parent directory path
-    pub children_start: u32, // Index into children pool
-    pub children_count: u32, // Number of children
-}
-
-impl MmapDirIndexEntry {
-    pub const SIZE: usize = std::mem::size_of::<Self>();
-}
-
-/// Child entry in the directory listing
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct MmapDirChild {
-    pub name: [u8; 128], // Name of the entry (max 127 bytes + null)
-    pub stat_index: u32, // Index in the stat hash table (for stat-on-readdir)
-    pub is_dir: u8,
-    pub _pad: [u8; 3],
-}
-
-impl MmapDirChild {
-    pub const SIZE: usize = std::mem::size_of::<Self>();
-
-    pub fn name_as_str(&self) -> &str {
-        let len = self
-            .name
-            .iter()
-            .position(|&b| b == 0)
-            .unwrap_or(self.name.len());
-        std::str::from_utf8(&self.name[..len]).unwrap_or("")
-    }
+/// Manifest key for a path relative to the project root: `/` + encoded path
+pub fn path_key_for(rel: &std::path::Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    format!("/{}", encode_path_key(rel.as_os_str().as_bytes()))
 }
 
 /// Calculate FNV-1a hash for path strings (deterministic, no alloc)
 #[inline(always)]
-pub fn fnv1a_hash(s: &str) -> u64 {
+pub const fn fnv1a_hash(s: &str) -> u64 {
     const FNV_OFFSET: u64 = 0xcbf29ce484222325;
     const FNV_PRIME: u64 = 0x100000001b3;
 
+    let bytes = s.as_bytes();
     let mut hash = FNV_OFFSET;
-    for byte in s.as_bytes() {
-        hash ^= *byte as u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
         hash = hash.wrapping_mul(FNV_PRIME);
+        i += 1;
     }
     hash
 }
 
-/// Calculate total mmap file size for given capacities
-#[allow(deprecated)]
-pub fn mmap_file_size(
-    table_capacity: usize,
-    dir_index_capacity: usize,
-    children_count: usize,
-) -> usize {
-    ManifestMmapHeader::SIZE
-        + BLOOM_SIZE
-        + (table_capacity * MmapStatEntry::SIZE)
-        + (dir_index_capacity * MmapDirIndexEntry::SIZE)
-        + (children_count * MmapDirChild::SIZE)
+/// Timeout for liveness probes against a daemon socket
+pub const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Check if daemon is running (socket connectable and answering Handshake)
+pub fn is_daemon_running() -> bool {
+    probe_socket(std::path::Path::new(&default_socket_path()), PROBE_TIMEOUT)
 }
 
-/// Builder for creating mmap manifest files (RFC-0044 Hot Stat Cache)
-/// Used by daemon to export manifest to shared memory for O(1) shim access
-#[deprecated(note = "Phase 2: VDir mmap is now managed by vDird directly")]
-#[allow(deprecated)]
-#[derive(Debug)]
-pub struct ManifestMmapBuilder {
-    entries: Vec<(String, MmapStatEntry)>,
-    bloom: Vec<u8>,
+/// Probe a daemon socket with a Handshake round-trip.
+///
+/// Returns true only when a live daemon answers with `HandshakeAck`. A socket
+/// file left behind by a crashed daemon refuses the connection and reports false.
+pub fn probe_socket(socket_path: &std::path::Path, timeout: std::time::Duration) -> bool {
+    let mut stream = match std::os::unix::net::UnixStream::connect(socket_path) {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+    if stream.set_read_timeout(Some(timeout)).is_err()
+        || stream.set_write_timeout(Some(timeout)).is_err()
+    {
+        return false;
+    }
+
+    let request = VeloRequest::Handshake {
+        client_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_version: PROTOCOL_VERSION,
+    };
+    if frame_sync::send_request(&mut stream, &request).is_err() {
+        return false;
+    }
+    matches!(
+        frame_sync::read_response(&mut stream),
+        Ok((_, VeloResponse::HandshakeAck { .. }))
+    )
 }
 
-#[allow(deprecated)]
-impl Default for ManifestMmapBuilder {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Prepare `socket_path` for binding.
+///
+/// Fails with `AddrInUse` if a live daemon still answers on it; otherwise a
+/// stale socket file is removed so the caller can take over.
+pub fn reclaim_socket(socket_path: &std::path::Path) -> std::io::Result<()> {
+    if !socket_path.exists() {
+        return Ok(());
+    }
+    if probe_socket(socket_path, PROBE_TIMEOUT) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AddrInUse,
+            format!("daemon already listening on {}", socket_path.display()),
+        ));
+    }
+    std::fs::remove_file(socket_path)
 }
 
-#[allow(deprecated)]
-impl ManifestMmapBuilder {
-    pub fn new() -> Self {
-        Self {
-            entries: Vec::new(),
-            bloom: vec![0u8; BLOOM_SIZE],
-        }
-    }
+/// Exclusive single-instance lock held for the lifetime of a daemon.
+///
+/// Backed by `flock(LOCK_EX | LOCK_NB)` on a `.lock` file next to the socket,
+/// so the kernel releases it automatically if the daemon crashes.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: std::fs::File,
+    path: std::path::PathBuf,
+}
 
-    /// Add a manifest entry to the builder
-    pub fn add_entry(
-        &mut self,
-        path: &str,
-        size: u64,
-        mtime: i64,
-        mode: u32,
-        is_dir: bool,
-        is_symlink: bool,
-    ) {
-        let path_hash = fnv1a_hash(path);
-        let flags = if is_dir { 0x01 } else { 0 } | if is_symlink { 0x02 } else { 0 };
-
-        // Add to bloom filter
-        let (h1, h2) = bloom_hashes(path);
-        let b1 = h1 % (BLOOM_SIZE * 8);
-        let b2 = h2 % (BLOOM_SIZE * 8);
-        self.bloom[b1 / 8] |= 1 << (b1 % 8);
-        self.bloom[b2 / 8] |= 1 << (b2 % 8);
-
-        let entry = MmapStatEntry {
-            path_hash,
-            size,
-            mtime,
-            mtime_nsec: 0,
-            mode,
-            flags,
-        };
-        self.entries.push((path.to_string(), entry));
-    }
-
-    /// Write mmap file to disk (now includes directory indexing)
-    pub fn write_to_file(&self, path: &str) -> std::io::Result<()> {
-        use std::collections::HashMap;
-        use std::io::Write;
-
-        // 1. Group children by parent directory
-        let mut dir_map: HashMap<String, Vec<(String, usize)>> = HashMap::new();
-        for (idx, (path_str, _entry)) in self.entries.iter().enumerate() {
-            let p = std::path::Path::new(path_str);
-            if let Some(parent) = p.parent() {
-                let parent_str = parent.to_str().unwrap_or("/");
-                // Ensure "/" is used for root
-                let parent_key = if parent_str.is_empty() {
-                    "/"
-                } else {
-                    parent_str
-                };
-                dir_map.entry(parent_key.to_string()).or_default().push((
-                    p.file_name()
-                        .unwrap_or_default()
-                        .to_str()
-                        .unwrap_or("")
-                        .to_string(),
-                    idx,
+impl InstanceLock {
+    /// Lock file path used for a given socket path
+    pub fn path_for_socket(socket_path: &std::path::Path) -> std::path::PathBuf {
+        let mut name = socket_path.as_os_str().to_owned();
+        name.push(".lock");
+        std::path::PathBuf::from(name)
+    }
+
+    /// Acquire the lock guarding `socket_path`, failing with `WouldBlock`
+    /// if another instance already holds it.
+    pub fn acquire(socket_path: &std::path::Path) -> std::io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let path = Self::path_for_socket(socket_path);
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+
+        let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if ret != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::WouldBlock {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::WouldBlock,
+                    format!("another instance holds {}", path.display()),
                 ));
             }
+            return Err(err);
         }
 
-        // 2. Calculate capacities
-        let table_capacity = (self.entries.len() * 2).clamp(1024, MMAP_MAX_ENTRIES);
-        let dir_index_capacity = (dir_map.len() * 2).clamp(256, MMAP_MAX_ENTRIES);
-        let children_count: usize = dir_map.values().map(|v| v.len()).sum();
-
-        let file_size = mmap_file_size(table_capacity, dir_index_capacity, children_count);
+        Ok(Self { _file: file, path })
+    }
 
-        // 3. Create buffer
-        let mut buffer = vec![0u8; file_size];
+    /// Path of the held lock file
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
 
-        // 4. Write header
-        let header = ManifestMmapHeader::new(
-            self.entries.len() as u32,
-            table_capacity as u32,
-            dir_index_capacity as u32,
-            children_count as u32,
-        );
-        let header_bytes = unsafe {
-            std::slice::from_raw_parts(&header as *const _ as *const u8, ManifestMmapHeader::SIZE)
-        };
-        buffer[..ManifestMmapHeader::SIZE].copy_from_slice(header_bytes);
-
-        // 5. Write bloom filter
-        let bloom_start = header.bloom_offset as usize;
-        buffer[bloom_start..bloom_start + BLOOM_SIZE].copy_from_slice(&self.bloom);
-
-        // DEBUG: Check bloom filter content
-        let bloom_set_bits: usize = self.bloom.iter().map(|b| b.count_ones() as usize).sum();
-        println!(
-            "[DEBUG-BUILDER] Bloom filter has {} set bits out of {} total bits",
-            bloom_set_bits,
-            BLOOM_SIZE * 8
-        );
-        println!(
-            "[DEBUG-BUILDER] Bloom filter first 32 bytes: {:?}",
-            &self.bloom[..32]
-        );
+/// IPC Client for communicating with vrift-daemon
+///
+/// `DaemonClient` has one typed method per request. Each returns the domain
+/// value from the matching ack; a daemon `Error` reply surfaces as
+/// [`ClientError::Daemon`] with its `VeloError` intact, so callers can branch
+/// on the kind or map it to an exit code.
+#[cfg(feature = "tokio")]
+pub mod client {
+    use super::*;
+    use std::path::Path;
+    use tokio::net::UnixStream;
 
-        // 6. Write stat hash table with linear probing
-        // We'll also need a way to map original index to actual slot for dir entries
-        let table_start = header.table_offset as usize;
-        let mut index_to_slot = vec![0u32; self.entries.len()];
-
-        for (idx, (_path, entry)) in self.entries.iter().enumerate() {
-            let start_slot = (entry.path_hash as usize) % table_capacity;
-            for i in 0..table_capacity {
-                let slot = (start_slot + i) % table_capacity;
-                let offset = table_start + slot * MmapStatEntry::SIZE;
-
-                let existing_hash =
-                    u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
-                if existing_hash == 0 {
-                    let entry_bytes = unsafe {
-                        std::slice::from_raw_parts(
-                            entry as *const _ as *const u8,
-                            MmapStatEntry::SIZE,
-                        )
-                    };
-                    buffer[offset..offset + MmapStatEntry::SIZE].copy_from_slice(entry_bytes);
-                    index_to_slot[idx] = slot as u32;
-                    break;
-                }
-            }
-        }
+    /// Errors from a `DaemonClient` round-trip
+    #[derive(Debug, thiserror::Error)]
+    pub enum ClientError {
+        #[error("I/O error talking to daemon: {0}")]
+        Io(#[from] std::io::Error),
 
-        // 7. Write children pool and directory index
-        let dir_index_start = header.dir_index_offset as usize;
-        let children_start = header.children_offset as usize;
-        let mut current_child_idx = 0;
+        #[error("Response seq_id mismatch: expected {expected}, got {got}")]
+        SeqMismatch { expected: u32, got: u32 },
 
-        for (parent_path, children) in dir_map {
-            let parent_hash = fnv1a_hash(&parent_path);
-            let dir_entry = MmapDirIndexEntry {
-                parent_hash,
-                children_start: current_child_idx as u32,
-                children_count: children.len() as u32,
-            };
+        #[error("Daemon error: {0}")]
+        Daemon(#[from] VeloError),
 
-            // Write to dir index hash table
-            let start_slot = (parent_hash as usize) % dir_index_capacity;
-            for i in 0..dir_index_capacity {
-                let slot = (start_slot + i) % dir_index_capacity;
-                let offset = dir_index_start + slot * MmapDirIndexEntry::SIZE;
-
-                let existing_hash =
-                    u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
-                if existing_hash == 0 {
-                    let entry_bytes = unsafe {
-                        std::slice::from_raw_parts(
-                            &dir_entry as *const _ as *const u8,
-                            MmapDirIndexEntry::SIZE,
-                        )
-                    };
-                    buffer[offset..offset + MmapDirIndexEntry::SIZE].copy_from_slice(entry_bytes);
-                    break;
-                }
-            }
+        #[error("Unexpected response to {request}: {response}")]
+        Unexpected {
+            request: &'static str,
+            response: String,
+        },
+    }
 
-            // Write children to pool
-            for (name, stat_idx) in children {
-                let mut child = MmapDirChild {
-                    name: [0u8; 128],
-                    stat_index: index_to_slot[stat_idx],
-                    is_dir: if self.entries[stat_idx].1.is_dir() {
-                        1
-                    } else {
-                        0
-                    },
-                    _pad: [0; 3],
-                };
-                let name_bytes = name.as_bytes();
-                let copy_len = name_bytes.len().min(127);
-                child.name[..copy_len].copy_from_slice(&name_bytes[..copy_len]);
+    pub type ClientResult<T> = std::result::Result<T, ClientError>;
 
-                let offset = children_start + current_child_idx * MmapDirChild::SIZE;
-                let entry_bytes = unsafe {
-                    std::slice::from_raw_parts(&child as *const _ as *const u8, MmapDirChild::SIZE)
-                };
-                buffer[offset..offset + MmapDirChild::SIZE].copy_from_slice(entry_bytes);
-                current_child_idx += 1;
-            }
+    /// Turn a reply that isn't the expected ack into an error
+    fn unexpected(request: &'static str, response: VeloResponse) -> ClientError {
+        match response {
+            VeloResponse::Error(e) => ClientError::Daemon(e),
+            other => ClientError::Unexpected {
+                request,
+                response: format!("{:?}", other),
+            },
         }
-
-        // 8. Write atomically
-        let temp_path = format!("{}.tmp", path);
-        let mut file = std::fs::File::create(&temp_path)?;
-        file.write_all(&buffer)?;
-        file.sync_all()?;
-        std::fs::rename(&temp_path, path)?;
-
-        Ok(())
-    }
-
-    /// Get entry count
-    pub fn len(&self) -> usize {
-        self.entries.len()
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.entries.is_empty()
+    /// Workspace registration details from `RegisterAck`
+    #[derive(Debug, Clone)]
+    pub struct Registration {
+        pub workspace_id: String,
+        pub vdird_socket: String,
+        pub vdir_mmap_path: String,
+        pub session_id: String,
+    }
+
+    /// A project's endpoints from `ProjectAck`
+    #[derive(Debug, Clone)]
+    pub struct ProjectEndpoints {
+        pub project_id: String,
+        pub vdird_socket: String,
+        pub vdir_mmap_path: String,
+    }
+
+    /// Parameters of an `IngestFullScan` request
+    #[derive(Debug, Clone, Default)]
+    pub struct IngestOptions {
+        pub path: String,
+        pub manifest_path: String,
+        pub threads: Option<usize>,
+        pub phantom: bool,
+        pub tier1: bool,
+        pub prefix: Option<String>,
+        pub cas_root: Option<String>,
+        pub force_hash: bool,
+        pub compress: bool,
+        pub ignore: Vec<String>,
+    }
+
+    /// Totals from `IngestAck`
+    #[derive(Debug, Clone)]
+    pub struct IngestSummary {
+        pub files: u64,
+        pub blobs: u64,
+        pub new_bytes: u64,
+        pub total_bytes: u64,
+        pub duration_ms: u64,
+        pub manifest_path: String,
     }
-}
-
-/// Check if daemon is running (socket exists and connectable)
-pub fn is_daemon_running() -> bool {
-    std::path::Path::new(&default_socket_path()).exists()
-}
-
-/// IPC Client for communicating with vrift-daemon
-#[cfg(feature = "tokio")]
-pub mod client {
-    use super::*;
-    use std::path::Path;
-    use tokio::net::UnixStream;
 
     pub struct DaemonClient {
         stream: UnixStream,
+        socket_path: std::path::PathBuf,
+        capabilities: Capabilities,
     }
 
     impl DaemonClient {
         /// Connect to daemon at default socket path
-        pub async fn connect() -> anyhow::Result<Self> {
+        pub async fn connect() -> ClientResult<Self> {
             Self::connect_to(&default_socket_path()).await
         }
 
         /// Connect to daemon at custom socket path
-        pub async fn connect_to(socket_path: &str) -> anyhow::Result<Self> {
-            let stream = UnixStream::connect(Path::new(socket_path)).await?;
-            Ok(Self { stream })
+        pub async fn connect_to(socket_path: &str) -> ClientResult<Self> {
+            Self::connect_path(Path::new(socket_path)).await
+        }
+
+        /// Connect to a daemon (vriftd or a vDird) listening on `socket_path`
+        pub async fn connect_path(socket_path: &Path) -> ClientResult<Self> {
+            let stream = UnixStream::connect(socket_path).await?;
+            Ok(Self {
+                stream,
+                socket_path: socket_path.to_path_buf(),
+                capabilities: Capabilities::empty(),
+            })
+        }
+
+        /// Features agreed on by the last handshake; empty before one
+        pub fn capabilities(&self) -> Capabilities {
+            self.capabilities
+        }
+
+        /// Hand the connection to a multiplexed client, for requests that
+        /// are pipelined or sent from several tasks at once
+        pub fn into_mux(self) -> crate::mux::AsyncMux {
+            crate::mux::AsyncMux::from_stream(self.stream)
         }
 
         /// Send a request and receive response using v3 frame protocol
-        pub async fn send(&mut self, request: VeloRequest) -> anyhow::Result<VeloResponse> {
+        pub async fn send(&mut self, request: VeloRequest) -> ClientResult<VeloResponse> {
             use crate::frame_async;
 
             // Send request frame
@@ -1359,18 +1863,66 @@ pub mod client {
 
             // Verify seq_id matches (optional but good for debugging)
             if header.seq_id != seq_id {
-                anyhow::bail!(
-                    "Response seq_id mismatch: expected {}, got {}",
-                    seq_id,
-                    header.seq_id
-                );
+                return Err(ClientError::SeqMismatch {
+                    expected: seq_id,
+                    got: header.seq_id,
+                });
             }
 
             Ok(response)
         }
 
-        /// Handshake with daemon
-        pub async fn handshake(&mut self) -> anyhow::Result<String> {
+        /// Handshake with daemon, returning its version
+        pub async fn handshake(&mut self) -> ClientResult<String> {
+            Ok(self
+                .negotiate(Capabilities::SUPPORTED)
+                .await?
+                .server_version)
+        }
+
+        /// Agree on a protocol version and on which of `capabilities` to
+        /// use. Falls back to the legacy `Handshake` (no capabilities) when
+        /// the daemon predates `Negotiate`.
+        pub async fn negotiate(&mut self, capabilities: Capabilities) -> ClientResult<Negotiated> {
+            let request = VeloRequest::Negotiate {
+                client_version: env!("CARGO_PKG_VERSION").to_string(),
+                protocol_version: PROTOCOL_VERSION,
+                capabilities,
+            };
+            match self.send(request).await {
+                Ok(VeloResponse::NegotiateAck {
+                    server_version,
+                    protocol_version: Some(protocol_version),
+                    capabilities,
+                }) => {
+                    self.capabilities = capabilities;
+                    Ok(Negotiated {
+                        server_version,
+                        protocol_version,
+                        capabilities,
+                    })
+                }
+                Ok(VeloResponse::NegotiateAck { server_version, .. }) => {
+                    Err(ClientError::Daemon(VeloError::version_mismatch(format!(
+                        "Protocol version mismatch (daemon {})",
+                        server_version
+                    ))))
+                }
+                // An older vDird answers a request it can't decode with an
+                // internal error; an older vriftd drops the connection
+                Ok(VeloResponse::Error(e)) if e.kind == VeloErrorKind::Internal => {
+                    self.legacy_handshake().await
+                }
+                Err(ClientError::Io(_)) => {
+                    self.stream = UnixStream::connect(&self.socket_path).await?;
+                    self.legacy_handshake().await
+                }
+                Ok(other) => Err(unexpected("Negotiate", other)),
+                Err(e) => Err(e),
+            }
+        }
+
+        async fn legacy_handshake(&mut self) -> ClientResult<Negotiated> {
             let request = VeloRequest::Handshake {
                 client_version: env!("CARGO_PKG_VERSION").to_string(),
                 protocol_version: PROTOCOL_VERSION,
@@ -1378,25 +1930,390 @@ pub mod client {
             match self.send(request).await? {
                 VeloResponse::HandshakeAck {
                     server_version,
+                    protocol_version,
                     compatible,
-                    ..
                 } => {
                     if !compatible {
-                        anyhow::bail!("Protocol version mismatch");
+                        return Err(ClientError::Daemon(VeloError::version_mismatch(format!(
+                            "Protocol version mismatch (daemon {})",
+                            server_version
+                        ))));
                     }
-                    Ok(server_version)
+                    self.capabilities = Capabilities::empty();
+                    Ok(Negotiated {
+                        server_version,
+                        protocol_version: protocol_version.min(PROTOCOL_VERSION),
+                        capabilities: Capabilities::empty(),
+                    })
                 }
-                VeloResponse::Error(e) => anyhow::bail!("Handshake failed: {}", e),
-                _ => anyhow::bail!("Unexpected response"),
+                other => Err(unexpected("Handshake", other)),
             }
         }
 
         /// Get daemon status
-        pub async fn status(&mut self) -> anyhow::Result<String> {
+        pub async fn status(&mut self) -> ClientResult<String> {
             match self.send(VeloRequest::Status).await? {
                 VeloResponse::StatusAck { status } => Ok(status),
-                VeloResponse::Error(e) => anyhow::bail!("Status failed: {}", e),
-                _ => anyhow::bail!("Unexpected response"),
+                other => Err(unexpected("Status", other)),
+            }
+        }
+
+        /// Get structured daemon counters
+        pub async fn metrics(&mut self) -> ClientResult<DaemonMetrics> {
+            match self.send(VeloRequest::Metrics).await? {
+                VeloResponse::MetricsAck { metrics } => Ok(metrics),
+                other => Err(unexpected("Metrics", other)),
+            }
+        }
+
+        /// Register a project with vriftd and learn its vDird endpoints
+        pub async fn register_workspace(
+            &mut self,
+            project_root: &str,
+        ) -> ClientResult<Registration> {
+            let request = VeloRequest::RegisterWorkspace {
+                project_root: project_root.to_string(),
+            };
+            match self.send(request).await? {
+                VeloResponse::RegisterAck {
+                    workspace_id,
+                    vdird_socket,
+                    vdir_mmap_path,
+                    session_id,
+                } => Ok(Registration {
+                    workspace_id,
+                    vdird_socket,
+                    vdir_mmap_path,
+                    session_id,
+                }),
+                other => Err(unexpected("RegisterWorkspace", other)),
+            }
+        }
+
+        /// Have the vDird this is connected to serve `project_root` too
+        pub async fn project_open(&mut self, project_root: &str) -> ClientResult<ProjectEndpoints> {
+            let request = VeloRequest::ProjectOpen {
+                project_root: project_root.to_string(),
+            };
+            match self.send(request).await? {
+                VeloResponse::ProjectAck {
+                    project_id,
+                    vdird_socket,
+                    vdir_mmap_path,
+                } => Ok(ProjectEndpoints {
+                    project_id,
+                    vdird_socket,
+                    vdir_mmap_path,
+                }),
+                other => Err(unexpected("ProjectOpen", other)),
+            }
+        }
+
+        /// Have the daemon spawn a command; returns its pid
+        pub async fn spawn(
+            &mut self,
+            command: Vec<String>,
+            env: Vec<(String, String)>,
+            cwd: String,
+            log_path: Option<String>,
+        ) -> ClientResult<u32> {
+            let request = VeloRequest::Spawn {
+                command,
+                env,
+                cwd,
+                log_path,
+            };
+            match self.send(request).await? {
+                VeloResponse::SpawnAck { pid } => Ok(pid),
+                other => Err(unexpected("Spawn", other)),
+            }
+        }
+
+        /// New output of a spawned child since the given offsets
+        pub async fn spawn_poll(
+            &mut self,
+            pid: u32,
+            stdout_offset: u64,
+            stderr_offset: u64,
+            wait_ms: u32,
+        ) -> ClientResult<SpawnOutput> {
+            let request = VeloRequest::SpawnPoll {
+                pid,
+                stdout_offset,
+                stderr_offset,
+                wait_ms,
+            };
+            match self.send(request).await? {
+                VeloResponse::SpawnPollAck { output } => Ok(output),
+                other => Err(unexpected("SpawnPoll", other)),
+            }
+        }
+
+        /// Signal a spawned child's process group
+        pub async fn kill(&mut self, pid: u32, signal: i32) -> ClientResult<()> {
+            match self.send(VeloRequest::Kill { pid, signal }).await? {
+                VeloResponse::KillAck => Ok(()),
+                other => Err(unexpected("Kill", other)),
+            }
+        }
+
+        /// Snapshot the workspace manifest (vDird), optionally protecting it
+        pub async fn snapshot_create(
+            &mut self,
+            label: &str,
+            protect: bool,
+        ) -> ClientResult<SnapshotInfo> {
+            let request = VeloRequest::SnapshotCreate {
+                label: label.to_string(),
+                protect,
+            };
+            match self.send(request).await? {
+                VeloResponse::SnapshotAck { snapshot } => Ok(snapshot),
+                other => Err(unexpected("SnapshotCreate", other)),
+            }
+        }
+
+        /// Snapshots of the workspace manifest, oldest first (vDird)
+        pub async fn snapshot_list(&mut self) -> ClientResult<Vec<SnapshotInfo>> {
+            match self.send(VeloRequest::SnapshotList).await? {
+                VeloResponse::SnapshotListAck { snapshots } => Ok(snapshots),
+                other => Err(unexpected("SnapshotList", other)),
+            }
+        }
+
+        /// Roll the workspace manifest back to a snapshot; returns the
+        /// restored snapshot and the one taken of the state it replaced
+        pub async fn snapshot_restore(
+            &mut self,
+            id: &str,
+        ) -> ClientResult<(SnapshotInfo, SnapshotInfo)> {
+            let request = VeloRequest::SnapshotRestore { id: id.to_string() };
+            match self.send(request).await? {
+                VeloResponse::SnapshotRestoreAck { restored, previous } => Ok((restored, previous)),
+                other => Err(unexpected("SnapshotRestore", other)),
+            }
+        }
+
+        /// Lift a snapshot's protection; vDird refuses unless `force`
+        pub async fn snapshot_unprotect(
+            &mut self,
+            id: &str,
+            force: bool,
+        ) -> ClientResult<SnapshotInfo> {
+            let request = VeloRequest::SnapshotUnprotect {
+                id: id.to_string(),
+                force,
+            };
+            match self.send(request).await? {
+                VeloResponse::SnapshotAck { snapshot } => Ok(snapshot),
+                other => Err(unexpected("SnapshotUnprotect", other)),
+            }
+        }
+
+        /// Have vDird republish its VDir from the manifest on disk; returns
+        /// the entries published and the new VDir generation
+        pub async fn manifest_reload(&mut self) -> ClientResult<(u64, u64)> {
+            match self.send(VeloRequest::ManifestReload).await? {
+                VeloResponse::ManifestReloadAck {
+                    entries,
+                    generation,
+                } => Ok((entries, generation)),
+                other => Err(unexpected("ManifestReload", other)),
+            }
+        }
+
+        /// Tell the daemon a blob was stored
+        pub async fn cas_insert(&mut self, hash: [u8; 32], size: u64) -> ClientResult<()> {
+            match self.send(VeloRequest::CasInsert { hash, size }).await? {
+                VeloResponse::CasAck => Ok(()),
+                other => Err(unexpected("CasInsert", other)),
+            }
+        }
+
+        /// Size of a blob, or `None` if the CAS doesn't have it
+        pub async fn cas_get(&mut self, hash: [u8; 32]) -> ClientResult<Option<u64>> {
+            match self.send(VeloRequest::CasGet { hash }).await? {
+                VeloResponse::CasFound { size } => Ok(Some(size)),
+                VeloResponse::CasNotFound => Ok(None),
+                other => Err(unexpected("CasGet", other)),
+            }
+        }
+
+        /// Delete blobs absent from `bloom_filter`; returns (deleted, reclaimed bytes)
+        pub async fn cas_sweep(&mut self, bloom_filter: Vec<u8>) -> ClientResult<(u32, u64)> {
+            match self.send(VeloRequest::CasSweep { bloom_filter }).await? {
+                VeloResponse::CasSweepAck {
+                    deleted_count,
+                    reclaimed_bytes,
+                } => Ok((deleted_count, reclaimed_bytes)),
+                other => Err(unexpected("CasSweep", other)),
+            }
+        }
+
+        /// Set or clear protection on a path
+        pub async fn protect(
+            &mut self,
+            path: &str,
+            immutable: bool,
+            owner: Option<String>,
+        ) -> ClientResult<()> {
+            let request = VeloRequest::Protect {
+                path: path.to_string(),
+                immutable,
+                owner,
+            };
+            match self.send(request).await? {
+                VeloResponse::ProtectAck => Ok(()),
+                other => Err(unexpected("Protect", other)),
+            }
+        }
+
+        /// Look up a manifest entry
+        pub async fn manifest_get(&mut self, path: &str) -> ClientResult<Option<VnodeEntry>> {
+            let request = VeloRequest::ManifestGet {
+                path: path.to_string(),
+            };
+            match self.send(request).await? {
+                VeloResponse::ManifestAck { entry, .. } => Ok(entry),
+                other => Err(unexpected("ManifestGet", other)),
+            }
+        }
+
+        /// Insert or replace a manifest entry
+        pub async fn manifest_upsert(&mut self, path: &str, entry: VnodeEntry) -> ClientResult<()> {
+            let request = VeloRequest::ManifestUpsert {
+                path: path.to_string(),
+                entry,
+            };
+            match self.send(request).await? {
+                VeloResponse::ManifestAck { .. } => Ok(()),
+                other => Err(unexpected("ManifestUpsert", other)),
+            }
+        }
+
+        /// Remove a manifest entry
+        pub async fn manifest_remove(&mut self, path: &str) -> ClientResult<()> {
+            let request = VeloRequest::ManifestRemove {
+                path: path.to_string(),
+            };
+            match self.send(request).await? {
+                VeloResponse::ManifestAck { .. } => Ok(()),
+                other => Err(unexpected("ManifestRemove", other)),
+            }
+        }
+
+        /// Move a manifest entry
+        pub async fn manifest_rename(
+            &mut self,
+            old_path: &str,
+            new_path: &str,
+        ) -> ClientResult<()> {
+            let request = VeloRequest::ManifestRename {
+                old_path: old_path.to_string(),
+                new_path: new_path.to_string(),
+            };
+            match self.send(request).await? {
+                VeloResponse::ManifestAck { .. } => Ok(()),
+                other => Err(unexpected("ManifestRename", other)),
+            }
+        }
+
+        /// Update a manifest entry's mtime
+        pub async fn manifest_update_mtime(
+            &mut self,
+            path: &str,
+            mtime_ns: u64,
+        ) -> ClientResult<()> {
+            let request = VeloRequest::ManifestUpdateMtime {
+                path: path.to_string(),
+                mtime_ns,
+            };
+            match self.send(request).await? {
+                VeloResponse::ManifestAck { .. } => Ok(()),
+                other => Err(unexpected("ManifestUpdateMtime", other)),
+            }
+        }
+
+        /// Build-cache key for `command` over the given manifest subset,
+        /// and how many entries it covers
+        pub async fn build_cache_key(
+            &mut self,
+            inputs: &[String],
+            exclude: &[String],
+            command: &[String],
+        ) -> ClientResult<([u8; 32], u64)> {
+            let request = VeloRequest::BuildCacheKey {
+                inputs: inputs.to_vec(),
+                exclude: exclude.to_vec(),
+                command: command.to_vec(),
+            };
+            match self.send(request).await? {
+                VeloResponse::BuildCacheKeyAck { key, entries } => Ok((key, entries)),
+                other => Err(unexpected("BuildCacheKey", other)),
+            }
+        }
+
+        /// Children of a virtual directory, in [`DirEntry`] order
+        pub async fn manifest_list(&mut self, path: &str) -> ClientResult<Vec<DirEntry>> {
+            let request = VeloRequest::ManifestListDir {
+                path: path.to_string(),
+            };
+            match self.send(request).await? {
+                VeloResponse::ManifestListAck { entries } => Ok(entries),
+                other => Err(unexpected("ManifestListDir", other)),
+            }
+        }
+
+        /// Fold a CoW temp file back into CAS and the manifest
+        pub async fn manifest_reingest(
+            &mut self,
+            vpath: &str,
+            temp_path: &str,
+        ) -> ClientResult<Option<VnodeEntry>> {
+            let request = VeloRequest::ManifestReingest {
+                vpath: vpath.to_string(),
+                temp_path: temp_path.to_string(),
+            };
+            match self.send(request).await? {
+                VeloResponse::ManifestAck { entry, .. } => Ok(entry),
+                other => Err(unexpected("ManifestReingest", other)),
+            }
+        }
+
+        /// Run a full-scan ingest on the daemon side
+        pub async fn ingest_full_scan(
+            &mut self,
+            opts: IngestOptions,
+        ) -> ClientResult<IngestSummary> {
+            let request = VeloRequest::IngestFullScan {
+                path: opts.path,
+                manifest_path: opts.manifest_path,
+                threads: opts.threads,
+                phantom: opts.phantom,
+                tier1: opts.tier1,
+                prefix: opts.prefix,
+                cas_root: opts.cas_root,
+                force_hash: opts.force_hash,
+                compress: opts.compress,
+                ignore: opts.ignore,
+            };
+            match self.send(request).await? {
+                VeloResponse::IngestAck {
+                    files,
+                    blobs,
+                    new_bytes,
+                    total_bytes,
+                    duration_ms,
+                    manifest_path,
+                } => Ok(IngestSummary {
+                    files,
+                    blobs,
+                    new_bytes,
+                    total_bytes,
+                    duration_ms,
+                    manifest_path,
+                }),
+                other => Err(unexpected("IngestFullScan", other)),
             }
         }
     }
@@ -1426,6 +2343,143 @@ mod tests {
         assert!(matches!(decoded, VeloResponse::StatusAck { .. }));
     }
 
+    #[test]
+    fn test_metrics_serialization() {
+        let resp = VeloResponse::MetricsAck {
+            metrics: DaemonMetrics {
+                manifest_entries: 42,
+                recent_reingests: vec![ReingestEvent {
+                    vpath: "/src/main.rs".to_string(),
+                    size: 7,
+                    at_secs: 1_700_000_000,
+                }],
+                ..Default::default()
+            },
+        };
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&resp).unwrap();
+        let decoded: VeloResponse =
+            rkyv::from_bytes::<VeloResponse, rkyv::rancor::Error>(&bytes).unwrap();
+        match decoded {
+            VeloResponse::MetricsAck { metrics } => {
+                assert_eq!(metrics.manifest_entries, 42);
+                assert_eq!(metrics.recent_reingests[0].vpath, "/src/main.rs");
+            }
+            other => panic!("Expected MetricsAck, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_spawn_poll_serialization() {
+        let resp = VeloResponse::SpawnPollAck {
+            output: SpawnOutput {
+                stdout: b"Compiling vrift\n".to_vec(),
+                stdout_offset: 16,
+                exit: Some(SpawnExit {
+                    code: None,
+                    signal: Some(2),
+                }),
+                ..Default::default()
+            },
+        };
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&resp).unwrap();
+        let decoded: VeloResponse =
+            rkyv::from_bytes::<VeloResponse, rkyv::rancor::Error>(&bytes).unwrap();
+        match decoded {
+            VeloResponse::SpawnPollAck { output } => {
+                assert_eq!(output.stdout_offset, 16);
+                assert_eq!(output.exit.unwrap().shell_code(), 130);
+            }
+            other => panic!("Expected SpawnPollAck, got {:?}", other),
+        }
+        let clean = SpawnExit {
+            code: Some(3),
+            signal: None,
+        };
+        assert_eq!(clean.shell_code(), 3);
+    }
+
+    #[test]
+    fn test_path_key_roundtrip() {
+        assert_eq!(encode_path_key(b"src/main.rs"), "src/main.rs");
+        assert_eq!(encode_path_key("café/ü.txt".as_bytes()), "café/ü.txt");
+
+        // Latin-1 names and a truncated sequence at the end stay distinct
+        for raw in [&b"caf\xe9.txt"[..], b"caf\xe8.txt", b"x\xc3", b"\xff\xfe/a"] {
+            let key = encode_path_key(raw);
+            assert!(!key.contains('\u{FFFD}'));
+            assert_eq!(decode_path_key(&key).as_ref(), raw);
+        }
+        assert_ne!(encode_path_key(b"\xe9"), encode_path_key(b"\xe8"));
+        assert_eq!(
+            path_key_for(std::path::Path::new("a/b.rs")),
+            "/a/b.rs".to_string()
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_client_maps_replies_to_domain_types() {
+        use client::{ClientError, DaemonClient};
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("d.sock");
+        let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while let Ok((header, request)) = frame_async::read_request(&mut stream).await {
+                let response = match request {
+                    VeloRequest::CasGet { .. } => VeloResponse::CasNotFound,
+                    _ => VeloResponse::Error(VeloError::not_found("no such entry")),
+                };
+                frame_async::send_response(&mut stream, &response, header.seq_id)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut client = DaemonClient::connect_path(&socket).await.unwrap();
+        assert_eq!(client.cas_get([0; 32]).await.unwrap(), None);
+        match client.manifest_get("/missing").await {
+            Err(ClientError::Daemon(e)) => assert_eq!(e.kind, VeloErrorKind::NotFound),
+            other => panic!("Expected daemon error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_falls_back_for_older_daemon() {
+        use client::DaemonClient;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("old.sock");
+        let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+        // A vriftd from before Negotiate: it can't decode the request and
+        // drops the connection, but answers Handshake
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                while let Ok((header, request)) = frame_async::read_request(&mut stream).await {
+                    let response = match request {
+                        VeloRequest::Handshake { .. } => VeloResponse::HandshakeAck {
+                            server_version: "old".to_string(),
+                            protocol_version: PROTOCOL_VERSION,
+                            compatible: true,
+                        },
+                        _ => break,
+                    };
+                    frame_async::send_response(&mut stream, &response, header.seq_id)
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        let mut client = DaemonClient::connect_path(&socket).await.unwrap();
+        let negotiated = client.negotiate(Capabilities::SUPPORTED).await.unwrap();
+        assert_eq!(negotiated.server_version, "old");
+        assert_eq!(negotiated.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(client.capabilities(), Capabilities::empty());
+    }
+
     #[test]
     fn test_default_socket_path() {
         // Verify default socket path is set
@@ -1501,6 +2555,29 @@ mod tests {
         assert!(!is_version_compatible(5));
         // Very high version not supported
         assert!(!is_version_compatible(100));
+
+        // Negotiate settles on the lower version and the shared features
+        let client = Capabilities::RENAME.union(Capabilities::CHUNKED_CAS);
+        let (version, caps) = negotiate(100, client, Capabilities::SUPPORTED);
+        assert_eq!(version, Some(PROTOCOL_VERSION));
+        assert_eq!(caps, Capabilities::RENAME);
+        assert_eq!(caps.names(), vec!["rename"]);
+        assert_eq!(negotiate(0, client, Capabilities::SUPPORTED).0, None);
+
+        let legacy = VeloRequest::Handshake {
+            client_version: "x".to_string(),
+            protocol_version: 100,
+        };
+        assert_eq!(
+            legacy.handshake_error().unwrap().kind,
+            VeloErrorKind::VersionMismatch
+        );
+        let newer = VeloRequest::Negotiate {
+            client_version: "x".to_string(),
+            protocol_version: 100,
+            capabilities: client,
+        };
+        assert!(newer.is_handshake() && newer.handshake_error().is_none());
     }
 
     #[test]
@@ -1521,6 +2598,46 @@ mod tests {
         assert!(matches!(decoded, VeloRequest::Status));
     }
 
+    #[test]
+    fn test_frame_sync_stack_and_heap_payloads() {
+        use crate::frame_sync;
+        use std::io::Cursor;
+
+        let small = VeloRequest::ManifestGet {
+            path: "/src/main.rs".to_string(),
+        };
+        let large = VeloRequest::ManifestGet {
+            path: "x".repeat(frame_sync::STACK_FRAME * 2),
+        };
+        for request in [small, large] {
+            let mut buf = Vec::new();
+            frame_sync::send_request(&mut buf, &request).unwrap();
+            // Same bytes whichever buffer serialized them
+            let heap = rkyv::to_bytes::<rkyv::rancor::Error>(&request).unwrap();
+            assert_eq!(&buf[IpcHeader::SIZE..], &heap[..]);
+
+            let (_, decoded) = frame_sync::read_request(&mut Cursor::new(&buf)).unwrap();
+            match (decoded, &request) {
+                (
+                    VeloRequest::ManifestGet { path },
+                    VeloRequest::ManifestGet { path: expected },
+                ) => assert_eq!(&path, expected),
+                other => panic!("Expected ManifestGet, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_sync_rejects_oversized_payload() {
+        use crate::frame_sync;
+        use std::io::Cursor;
+
+        // Refused from the header alone, before any buffer is sized by it
+        let header = IpcHeader::new_response((IpcHeader::MAX_LENGTH + 1) as u32, 1);
+        let err = frame_sync::read_response(&mut Cursor::new(header.to_bytes())).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_frame_sync_response_roundtrip() {
         use crate::frame_sync;
@@ -1662,6 +2779,41 @@ mod tests {
         );
         assert_eq!(VeloError::io_error("").exit_code(), 1);
         assert_eq!(VeloError::internal("").exit_code(), 1);
+        assert_eq!(VeloError::corrupt("").exit_code(), 65);
+        assert_eq!(VeloError::conflict("").exit_code(), 73);
+        assert_eq!(VeloError::busy("").exit_code(), 75);
+        assert_eq!(VeloError::version_mismatch("").exit_code(), 76);
+    }
+
+    #[test]
+    fn test_velo_error_kind_errno_and_wire() {
+        assert_eq!(VeloErrorKind::NotFound.errno(), libc::ENOENT);
+        assert_eq!(VeloErrorKind::PermissionDenied.errno(), libc::EACCES);
+        assert_eq!(VeloErrorKind::Conflict.errno(), libc::EEXIST);
+        assert_eq!(VeloErrorKind::Busy.errno(), libc::EAGAIN);
+        assert_eq!(VeloErrorKind::Corrupt.errno(), libc::EIO);
+        assert_eq!(VeloErrorKind::SymlinkLoop.errno(), libc::ELOOP);
+        assert_eq!(VeloErrorKind::VersionMismatch.errno(), libc::EPROTO);
+        assert!(VeloErrorKind::Busy.is_transient());
+        assert!(!VeloErrorKind::Corrupt.is_transient());
+        let io = std::io::Error::from(std::io::ErrorKind::AlreadyExists);
+        assert_eq!(
+            VeloErrorKind::from_io(&io, VeloErrorKind::IoError),
+            VeloErrorKind::Conflict
+        );
+        let io = std::io::Error::other("disk on fire");
+        assert_eq!(
+            VeloErrorKind::from_io(&io, VeloErrorKind::IoError),
+            VeloErrorKind::IoError
+        );
+
+        // The kind survives the wire, so callers never parse the message
+        let response = VeloResponse::Error(VeloError::busy("draining"));
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&response).unwrap();
+        match rkyv::from_bytes::<VeloResponse, rkyv::rancor::Error>(&bytes).unwrap() {
+            VeloResponse::Error(e) => assert_eq!(e.kind, VeloErrorKind::Busy),
+            other => panic!("Expected Error, got {:?}", other),
+        }
     }
 
     #[test]
@@ -1676,4 +2828,57 @@ mod tests {
             panic!("Expected VeloResponse::Error");
         }
     }
+
+    #[test]
+    fn test_probe_stale_socket_is_not_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("stale.sock");
+        // Bind then drop: leaves the socket file behind with no listener
+        drop(std::os::unix::net::UnixListener::bind(&sock).unwrap());
+        assert!(sock.exists());
+
+        assert!(!probe_socket(&sock, PROBE_TIMEOUT));
+        reclaim_socket(&sock).unwrap();
+        assert!(!sock.exists());
+    }
+
+    #[test]
+    fn test_probe_live_socket_answers_handshake() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("live.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&sock).unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let (header, _) = frame_sync::read_request(&mut stream).unwrap();
+            let ack = VeloResponse::HandshakeAck {
+                server_version: "test".to_string(),
+                protocol_version: PROTOCOL_VERSION,
+                compatible: true,
+            };
+            frame_sync::send_response(&mut stream, &ack, header.seq_id).unwrap();
+        });
+
+        assert!(probe_socket(&sock, PROBE_TIMEOUT));
+        server.join().unwrap();
+
+        // Listener is gone now, so the socket is stale and gets reclaimed
+        reclaim_socket(&sock).unwrap();
+        assert!(!sock.exists());
+    }
+
+    #[test]
+    fn test_instance_lock_is_exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("daemon.sock");
+
+        let lock = InstanceLock::acquire(&sock).unwrap();
+        assert_eq!(lock.path(), dir.path().join("daemon.sock.lock"));
+
+        let second = InstanceLock::acquire(&sock).unwrap_err();
+        assert_eq!(second.kind(), std::io::ErrorKind::WouldBlock);
+
+        drop(lock);
+        assert!(InstanceLock::acquire(&sock).is_ok());
+    }
 }
@@ -0,0 +1,422 @@
+//! Published manifest mmap (RFC-0044 hot stat cache) — SSOT for both vDird
+//! (writer) and InceptionLayer (reader)
+//!
+//! vDird publishes every key of the project manifest next to the VDir, at
+//! the VDir path plus [`MANIFEST_MMAP_SUFFIX`]. The VDir only holds what the
+//! hot cache admitted, so a VDir miss says nothing; this snapshot lets a
+//! shim answer "not in the manifest" without a ManifestGet round trip.
+//!
+//! File layout: `[control][added 0][added 1][slot 0][slot 1]`. A slot is a
+//! complete image from [`ManifestMmapBuilder`]: a [`ManifestMmapHeader`],
+//! a bloom filter and an open-addressing table of [`MmapStatEntry`].
+//! vDird builds the next image into the inactive slot and flips
+//! `generation`, whose low bit names the active slot. Keys added after an
+//! image was built have their bits set in the active slot's `added` bloom
+//! before the write is acknowledged, so a snapshot never hides an entry.
+//!
+//! Any field change here MUST bump [`MMAP_VERSION`].
+
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+
+/// Magic number for manifest mmap file: "VMMP" (Vrift Manifest MmaP)
+pub const MMAP_MAGIC: u32 = 0x504D4D56;
+/// Current mmap format version (v2: double-buffered slots, own bloom hash)
+pub const MMAP_VERSION: u32 = 2;
+/// Appended to the VDir path to name the manifest mmap
+pub const MANIFEST_MMAP_SUFFIX: &str = ".manifest";
+/// Bytes in each bloom filter. Fixed here rather than taken from
+/// `BLOOM_SIZE`, which depends on the `cas` feature the shim builds without.
+pub const MMAP_BLOOM_SIZE: usize = 128 * 1024;
+/// Smallest stat table, in slots
+pub const MMAP_MIN_CAPACITY: usize = 1024;
+
+/// Readers give up and ask vDird after this many flips under their feet
+const MAX_READ_RETRIES: u32 = 8;
+
+/// Start of the manifest mmap file
+#[repr(C)]
+#[derive(Debug)]
+pub struct ManifestMmapControl {
+    pub magic: u32,
+    pub version: u32,
+    /// Bumped by each flip; the active slot is `generation & 1`
+    pub generation: AtomicU64,
+    /// Bytes per slot
+    pub slot_size: u64,
+    /// Nonzero once vDird replaced the file (to grow it, or on restart);
+    /// a mapping of a retired file proves nothing
+    pub retired: AtomicU32,
+    pub _pad: [u32; 9],
+}
+
+impl ManifestMmapControl {
+    pub const SIZE: usize = std::mem::size_of::<Self>();
+
+    pub fn new(slot_size: usize) -> Self {
+        Self {
+            magic: MMAP_MAGIC,
+            version: MMAP_VERSION,
+            generation: AtomicU64::new(0),
+            slot_size: slot_size as u64,
+            retired: AtomicU32::new(0),
+            _pad: [0; 9],
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.magic == MMAP_MAGIC && self.version == MMAP_VERSION
+    }
+
+    /// Offset of slot `slot`'s `added` bloom
+    pub const fn added_offset(slot: usize) -> usize {
+        Self::SIZE + slot * MMAP_BLOOM_SIZE
+    }
+
+    /// Offset of slot `slot`'s image
+    pub const fn slot_offset(slot: usize, slot_size: usize) -> usize {
+        Self::SIZE + 2 * MMAP_BLOOM_SIZE + slot * slot_size
+    }
+
+    /// Size of a file with two slots of `slot_size` bytes
+    pub const fn file_size(slot_size: usize) -> usize {
+        Self::slot_offset(2, slot_size)
+    }
+}
+
+/// Header of a slot image
+/// Layout: [Header][Bloom Filter][Hash Table]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ManifestMmapHeader {
+    pub entry_count: u32,
+    pub table_capacity: u32, // Number of slots in stat hash table
+    pub bloom_offset: u32,   // Offset to bloom filter (MMAP_BLOOM_SIZE)
+    pub table_offset: u32,   // Offset to stat hash table (table_capacity * MmapStatEntry::SIZE)
+}
+
+impl ManifestMmapHeader {
+    pub const SIZE: usize = std::mem::size_of::<Self>();
+
+    pub fn new(entry_count: u32, table_capacity: u32) -> Self {
+        let bloom_offset = Self::SIZE as u32;
+        Self {
+            entry_count,
+            table_capacity,
+            bloom_offset,
+            table_offset: bloom_offset + MMAP_BLOOM_SIZE as u32,
+        }
+    }
+
+    /// Bytes of the image this header describes
+    pub fn image_size(&self) -> usize {
+        self.table_offset as usize + self.table_capacity as usize * MmapStatEntry::SIZE
+    }
+}
+
+/// Single stat entry in the hash table
+/// Uses open addressing with linear probing
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MmapStatEntry {
+    pub path_hash: u64, // mmap_key_hash of path (0 = empty slot)
+    pub size: u64,
+    pub mtime: i64,
+    pub mtime_nsec: i64,
+    pub mode: u32,
+    pub flags: u32, // EntryFlags: is_dir, is_symlink, etc.
+}
+
+impl MmapStatEntry {
+    pub const SIZE: usize = std::mem::size_of::<Self>();
+
+    pub fn is_empty(&self) -> bool {
+        self.path_hash == 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        (self.flags & 0x01) != 0
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        (self.flags & 0x02) != 0
+    }
+}
+
+/// Table hash of a manifest key: FNV-1a, with 0 kept for empty slots
+#[inline(always)]
+pub const fn mmap_key_hash(key: &str) -> u64 {
+    match crate::fnv1a_hash(key) {
+        0 => 1,
+        hash => hash,
+    }
+}
+
+/// The two bloom bits of a key
+#[inline(always)]
+const fn bloom_bits(key_hash: u64) -> (usize, usize) {
+    let nbits = (MMAP_BLOOM_SIZE * 8) as u64;
+    let h2 = key_hash.rotate_left(32).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    ((key_hash % nbits) as usize, (h2 % nbits) as usize)
+}
+
+/// Set `key_hash`'s bits in the bloom filter at `bloom`, which others may
+/// be reading
+///
+/// # Safety
+/// `bloom` must point to [`MMAP_BLOOM_SIZE`] writable bytes
+pub unsafe fn bloom_insert(bloom: *mut u8, key_hash: u64) {
+    let (b1, b2) = bloom_bits(key_hash);
+    for bit in [b1, b2] {
+        let byte = unsafe { &*(bloom.add(bit / 8) as *const AtomicU8) };
+        byte.fetch_or(1 << (bit % 8), Ordering::Release);
+    }
+}
+
+/// Whether `key_hash` may be in the bloom filter at `bloom`
+///
+/// # Safety
+/// `bloom` must point to [`MMAP_BLOOM_SIZE`] readable bytes
+#[inline(always)]
+pub unsafe fn bloom_contains(bloom: *const u8, key_hash: u64) -> bool {
+    let (b1, b2) = bloom_bits(key_hash);
+    [b1, b2].iter().all(|&bit| {
+        let byte = unsafe { &*(bloom.add(bit / 8) as *const AtomicU8) };
+        byte.load(Ordering::Acquire) & (1 << (bit % 8)) != 0
+    })
+}
+
+/// Whether the manifest mmap mapped at `ptr` proves `key` is not in the
+/// manifest. `false` means ask vDird: the key may be there, or there was
+/// no consistent snapshot to read.
+/// ZERO ALLOCATIONS, ZERO LOCKS, ZERO SYSCALLS — safe for the shim hot path.
+///
+/// # Safety
+/// `ptr` must be null or point to `size` readable bytes
+pub unsafe fn proves_absent(ptr: *const u8, size: usize, key: &str) -> bool {
+    if ptr.is_null() || size < ManifestMmapControl::SIZE {
+        return false;
+    }
+    let control = unsafe { &*(ptr as *const ManifestMmapControl) };
+    if !control.is_valid() {
+        return false;
+    }
+    let slot_size = control.slot_size as usize;
+    if ManifestMmapControl::file_size(slot_size) > size {
+        return false;
+    }
+    let key_hash = mmap_key_hash(key);
+
+    for _ in 0..MAX_READ_RETRIES {
+        if control.retired.load(Ordering::Acquire) != 0 {
+            return false;
+        }
+        let g1 = control.generation.load(Ordering::Acquire);
+        let slot = (g1 & 1) as usize;
+        let added = unsafe { ptr.add(ManifestMmapControl::added_offset(slot)) };
+        if unsafe { bloom_contains(added, key_hash) } {
+            return false;
+        }
+        let image = unsafe { ptr.add(ManifestMmapControl::slot_offset(slot, slot_size)) };
+        let absent = unsafe { image_lacks(image, slot_size, key_hash) };
+
+        // Seqlock-style check: the slot may have been rebuilt meanwhile
+        std::sync::atomic::fence(Ordering::Acquire);
+        if control.generation.load(Ordering::Relaxed) == g1 {
+            return absent;
+        }
+    }
+    false
+}
+
+/// Whether the image at `image` shows no entry for `key_hash`
+///
+/// # Safety
+/// `image` must point to `slot_size` readable bytes
+unsafe fn image_lacks(image: *const u8, slot_size: usize, key_hash: u64) -> bool {
+    if slot_size < ManifestMmapHeader::SIZE {
+        return false;
+    }
+    let header = unsafe { std::ptr::read_unaligned(image as *const ManifestMmapHeader) };
+    let capacity = header.table_capacity as usize;
+    if capacity == 0
+        || header.bloom_offset as usize + MMAP_BLOOM_SIZE > slot_size
+        || header.image_size() > slot_size
+    {
+        return false;
+    }
+    if !unsafe { bloom_contains(image.add(header.bloom_offset as usize), key_hash) } {
+        return true;
+    }
+    let table = unsafe { image.add(header.table_offset as usize) } as *const MmapStatEntry;
+    let start = (key_hash % capacity as u64) as usize;
+    for i in 0..capacity {
+        let entry = unsafe { std::ptr::read_unaligned(table.add((start + i) % capacity)) };
+        if entry.is_empty() {
+            return true;
+        }
+        if entry.path_hash == key_hash {
+            return false;
+        }
+    }
+    false
+}
+
+/// Builder for a slot image of the manifest mmap
+#[derive(Debug, Default)]
+pub struct ManifestMmapBuilder {
+    entries: Vec<MmapStatEntry>,
+}
+
+impl ManifestMmapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a manifest entry to the builder
+    pub fn add_entry(
+        &mut self,
+        path: &str,
+        size: u64,
+        mtime: i64,
+        mode: u32,
+        is_dir: bool,
+        is_symlink: bool,
+    ) {
+        let flags = if is_dir { 0x01 } else { 0 } | if is_symlink { 0x02 } else { 0 };
+        self.entries.push(MmapStatEntry {
+            path_hash: mmap_key_hash(path),
+            size,
+            mtime,
+            mtime_nsec: 0,
+            mode,
+            flags,
+        });
+    }
+
+    /// Add a key known only by its [`mmap_key_hash`], with no stat
+    pub fn add_key_hash(&mut self, key_hash: u64) {
+        self.entries.push(MmapStatEntry {
+            path_hash: key_hash.max(1),
+            ..Default::default()
+        });
+    }
+
+    /// The slot image: header, bloom filter, then the stat table at no
+    /// more than half full
+    pub fn build(&self) -> Vec<u8> {
+        let capacity = (self.entries.len() * 2)
+            .max(MMAP_MIN_CAPACITY)
+            .next_power_of_two();
+        let header = ManifestMmapHeader::new(self.entries.len() as u32, capacity as u32);
+        let mut image = vec![0u8; header.image_size()];
+        unsafe { std::ptr::write_unaligned(image.as_mut_ptr() as *mut ManifestMmapHeader, header) };
+
+        let bloom = header.bloom_offset as usize;
+        let table = header.table_offset as usize;
+        for entry in &self.entries {
+            unsafe { bloom_insert(image.as_mut_ptr().add(bloom), entry.path_hash) };
+            let start = (entry.path_hash % capacity as u64) as usize;
+            for i in 0..capacity {
+                let offset = table + (start + i) % capacity * MmapStatEntry::SIZE;
+                let slot = unsafe { image.as_mut_ptr().add(offset) } as *mut MmapStatEntry;
+                if unsafe { std::ptr::read_unaligned(slot) }.is_empty() {
+                    unsafe { std::ptr::write_unaligned(slot, *entry) };
+                    break;
+                }
+            }
+        }
+        image
+    }
+
+    /// Get entry count
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file holding `image` in slot 0
+    fn file_with(image: &[u8]) -> Vec<u64> {
+        let slot_size = image.len();
+        let size = ManifestMmapControl::file_size(slot_size);
+        // u64s keep the control header's atomics aligned
+        let mut file = vec![0u64; size.div_ceil(8)];
+        let ptr = file.as_mut_ptr() as *mut u8;
+        unsafe {
+            std::ptr::write(
+                ptr as *mut ManifestMmapControl,
+                ManifestMmapControl::new(slot_size),
+            );
+            let slot = ptr.add(ManifestMmapControl::slot_offset(0, slot_size));
+            std::ptr::copy_nonoverlapping(image.as_ptr(), slot, slot_size);
+        }
+        file
+    }
+
+    fn absent(file: &[u64], key: &str) -> bool {
+        unsafe { proves_absent(file.as_ptr() as *const u8, file.len() * 8, key) }
+    }
+
+    #[test]
+    fn test_snapshot_answers_absence() {
+        let mut builder = ManifestMmapBuilder::new();
+        builder.add_entry("/src/main.rs", 10, 0, 0o100644, false, false);
+        builder.add_entry("/src", 0, 0, 0o40755, true, false);
+        let file = file_with(&builder.build());
+
+        assert!(!absent(&file, "/src/main.rs"));
+        assert!(!absent(&file, "/src"));
+        assert!(absent(&file, "/src/lib.rs"));
+        assert!(absent(&file, "/target"));
+    }
+
+    #[test]
+    fn test_added_keys_are_never_absent() {
+        let file = file_with(&ManifestMmapBuilder::new().build());
+        assert!(absent(&file, "/new.rs"));
+
+        let ptr = file.as_ptr() as *mut u8;
+        unsafe {
+            bloom_insert(
+                ptr.add(ManifestMmapControl::added_offset(0)),
+                mmap_key_hash("/new.rs"),
+            )
+        };
+        assert!(!absent(&file, "/new.rs"));
+        assert!(absent(&file, "/other.rs"));
+    }
+
+    #[test]
+    fn test_unusable_files_prove_nothing() {
+        let file = file_with(&ManifestMmapBuilder::new().build());
+        let control = unsafe { &*(file.as_ptr() as *const ManifestMmapControl) };
+        control.retired.store(1, Ordering::Release);
+        assert!(!absent(&file, "/x"));
+
+        let mut file = file_with(&ManifestMmapBuilder::new().build());
+        file[0] = 0;
+        assert!(!absent(&file, "/x"));
+        assert!(!unsafe { proves_absent(std::ptr::null(), 0, "/x") });
+        let file = file_with(&ManifestMmapBuilder::new().build());
+        assert!(!unsafe { proves_absent(file.as_ptr() as *const u8, 64, "/x") });
+    }
+
+    #[test]
+    fn test_table_holds_more_than_the_minimum() {
+        let mut builder = ManifestMmapBuilder::new();
+        let keys: Vec<String> = (0..5000).map(|i| format!("/gen/{}.o", i)).collect();
+        for key in &keys {
+            builder.add_entry(key, 1, 0, 0o100644, false, false);
+        }
+        let file = file_with(&builder.build());
+        assert!(keys.iter().all(|key| !absent(&file, key)));
+        assert!(absent(&file, "/gen/5000.o"));
+    }
+}
@@ -0,0 +1,474 @@
+//! Multiplexed requests over one persistent connection
+//!
+//! A response frame carries the seq_id of the request it answers, so one
+//! connection can have any number of requests in flight and the server may
+//! answer them in any order. [`SyncMux`] is the blocking client (the
+//! inception layer keeps one per process); [`AsyncMux`] is the tokio one.
+//!
+//! Any I/O error breaks the connection: a frame may have been half read or
+//! half written. Every request waiting on it fails and callers reconnect.
+
+use crate::{frame_sync, next_seq_id, VeloRequest, VeloResponse};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Read half of a [`SyncMux`] connection
+pub trait MuxRead: Read {
+    /// Bound each following read; `None` blocks indefinitely
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl MuxRead for std::os::unix::net::UnixStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+fn broken() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "IPC connection broken")
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "IPC response timed out")
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn remaining(deadline: Option<Instant>) -> Option<Duration> {
+    deadline.map(|d| d.saturating_duration_since(Instant::now()))
+}
+
+#[derive(Default)]
+struct Inbox {
+    /// Responses read by one caller on behalf of another
+    ready: HashMap<u32, VeloResponse>,
+    /// Requests whose caller gave up waiting; their responses are dropped
+    abandoned: HashSet<u32>,
+    /// Some caller is blocked reading the connection
+    reading: bool,
+    broken: bool,
+}
+
+/// Blocking multiplexed client, shared by reference between threads
+///
+/// There's no reader thread: whichever caller is waiting reads the next
+/// frame, keeps it if it's its own and hands it over otherwise.
+pub struct SyncMux<R, W> {
+    reader: Mutex<R>,
+    writer: Mutex<W>,
+    inbox: Mutex<Inbox>,
+    arrived: Condvar,
+}
+
+impl<R: MuxRead, W: Write> SyncMux<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+            inbox: Mutex::new(Inbox::default()),
+            arrived: Condvar::new(),
+        }
+    }
+
+    /// The connection failed; every call on it will fail too
+    pub fn is_broken(&self) -> bool {
+        lock(&self.inbox).broken
+    }
+
+    fn mark_broken(&self) {
+        lock(&self.inbox).broken = true;
+        self.arrived.notify_all();
+    }
+
+    /// Send `request` without waiting for its response. Returns the seq_id
+    /// to [`recv`](Self::recv) it with.
+    pub fn send(&self, request: &VeloRequest) -> io::Result<u32> {
+        if self.is_broken() {
+            return Err(broken());
+        }
+        let seq_id = next_seq_id();
+        let mut writer = lock(&self.writer);
+        match frame_sync::send_request_with_seq(&mut *writer, request, seq_id) {
+            Ok(()) => Ok(seq_id),
+            // Rejected before anything was written
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Err(e),
+            Err(e) => {
+                drop(writer);
+                self.mark_broken();
+                Err(e)
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the response to `seq_id`
+    ///
+    /// A timeout while this caller is reading breaks the connection (the
+    /// frame may be half read); one while another caller reads doesn't.
+    pub fn recv(&self, seq_id: u32, timeout: Option<Duration>) -> io::Result<VeloResponse> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut inbox = lock(&self.inbox);
+        loop {
+            if let Some(response) = inbox.ready.remove(&seq_id) {
+                return Ok(response);
+            }
+            if inbox.broken {
+                return Err(broken());
+            }
+            let left = remaining(deadline);
+            if left.is_some_and(|left| left.is_zero()) {
+                inbox.abandoned.insert(seq_id);
+                return Err(timed_out());
+            }
+
+            if inbox.reading {
+                inbox = match left {
+                    Some(left) => {
+                        self.arrived
+                            .wait_timeout(inbox, left)
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .0
+                    }
+                    None => self
+                        .arrived
+                        .wait(inbox)
+                        .unwrap_or_else(PoisonError::into_inner),
+                };
+                continue;
+            }
+
+            inbox.reading = true;
+            drop(inbox);
+            let read = self.read_frame(left);
+            inbox = lock(&self.inbox);
+            inbox.reading = false;
+            // Wake the others: one of them reads next
+            self.arrived.notify_all();
+            match read {
+                Ok((id, response)) if id == seq_id => return Ok(response),
+                Ok((id, response)) => {
+                    if !inbox.abandoned.remove(&id) {
+                        inbox.ready.insert(id, response);
+                    }
+                }
+                Err(e) => {
+                    inbox.broken = true;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    fn read_frame(&self, timeout: Option<Duration>) -> io::Result<(u32, VeloResponse)> {
+        let mut reader = lock(&self.reader);
+        // A zero timeout means "block" to the socket layer
+        reader.set_read_timeout(timeout.map(|t| t.max(Duration::from_micros(1))))?;
+        let (header, response) = frame_sync::read_response(&mut *reader)?;
+        Ok((header.seq_id, response))
+    }
+
+    /// Send `request` and wait up to `timeout` for its response
+    pub fn call(
+        &self,
+        request: &VeloRequest,
+        timeout: Option<Duration>,
+    ) -> io::Result<VeloResponse> {
+        let seq_id = self.send(request)?;
+        self.recv(seq_id, timeout)
+    }
+
+    /// Pipeline `requests`: all of them are sent before the first response
+    /// is read. Responses come back in request order.
+    pub fn call_all(
+        &self,
+        requests: &[VeloRequest],
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<VeloResponse>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut ids = Vec::with_capacity(requests.len());
+        let mut responses = Vec::with_capacity(requests.len());
+        let result = (|| {
+            for request in requests {
+                ids.push(self.send(request)?);
+            }
+            for &seq_id in &ids {
+                responses.push(self.recv(seq_id, remaining(deadline))?);
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            // Nobody will collect the rest
+            let mut inbox = lock(&self.inbox);
+            for &seq_id in &ids[responses.len()..] {
+                if inbox.ready.remove(&seq_id).is_none() {
+                    inbox.abandoned.insert(seq_id);
+                }
+            }
+            return Err(e);
+        }
+        Ok(responses)
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::tokio_mux::AsyncMux;
+
+#[cfg(feature = "tokio")]
+mod tokio_mux {
+    use super::{broken, lock};
+    use crate::{frame_async, next_seq_id, VeloRequest, VeloResponse};
+    use std::collections::HashMap;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    #[derive(Default)]
+    struct Pending {
+        waiting: HashMap<u32, oneshot::Sender<VeloResponse>>,
+        closed: bool,
+    }
+
+    /// Tokio multiplexed client
+    ///
+    /// A reader task routes each response to the call waiting for it.
+    /// Dropping a call future mid-send breaks the connection, like any
+    /// other write error.
+    pub struct AsyncMux {
+        writer: tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>,
+        pending: Arc<Mutex<Pending>>,
+        reader: JoinHandle<()>,
+    }
+
+    impl AsyncMux {
+        pub fn new<R, W>(mut reader: R, writer: W) -> Self
+        where
+            R: AsyncRead + Send + Unpin + 'static,
+            W: AsyncWrite + Send + Unpin + 'static,
+        {
+            let pending = Arc::new(Mutex::new(Pending::default()));
+            let routes = Arc::clone(&pending);
+            let reader = tokio::spawn(async move {
+                loop {
+                    match frame_async::read_response(&mut reader).await {
+                        Ok((header, response)) => {
+                            let waiter = lock(&routes).waiting.remove(&header.seq_id);
+                            // Nobody waiting: the call was dropped
+                            if let Some(tx) = waiter {
+                                let _ = tx.send(response);
+                            }
+                        }
+                        Err(_) => {
+                            let mut routes = lock(&routes);
+                            routes.closed = true;
+                            // Dropping the senders fails every waiting call
+                            routes.waiting.clear();
+                            return;
+                        }
+                    }
+                }
+            });
+            Self {
+                writer: tokio::sync::Mutex::new(Box::new(writer)),
+                pending,
+                reader,
+            }
+        }
+
+        pub fn from_stream(stream: tokio::net::UnixStream) -> Self {
+            let (reader, writer) = stream.into_split();
+            Self::new(reader, writer)
+        }
+
+        /// The connection failed; every call on it will fail too
+        pub fn is_broken(&self) -> bool {
+            lock(&self.pending).closed
+        }
+
+        fn register(&self) -> io::Result<(u32, oneshot::Receiver<VeloResponse>)> {
+            let mut pending = lock(&self.pending);
+            if pending.closed {
+                return Err(broken());
+            }
+            let seq_id = next_seq_id();
+            let (tx, rx) = oneshot::channel();
+            pending.waiting.insert(seq_id, tx);
+            Ok((seq_id, rx))
+        }
+
+        fn fail(&self, seq_ids: &[u32], e: io::Error) -> io::Error {
+            let mut pending = lock(&self.pending);
+            if e.kind() != io::ErrorKind::InvalidData {
+                pending.closed = true;
+            }
+            for seq_id in seq_ids {
+                pending.waiting.remove(seq_id);
+            }
+            e
+        }
+
+        /// Send `request` and wait for its response
+        pub async fn call(&self, request: &VeloRequest) -> io::Result<VeloResponse> {
+            let mut responses = self.call_all(std::slice::from_ref(request)).await?;
+            Ok(responses.remove(0))
+        }
+
+        /// Pipeline `requests`: all of them are sent before the first
+        /// response is awaited. Responses come back in request order.
+        pub async fn call_all(&self, requests: &[VeloRequest]) -> io::Result<Vec<VeloResponse>> {
+            let mut ids = Vec::with_capacity(requests.len());
+            let mut receivers = Vec::with_capacity(requests.len());
+            {
+                let mut writer = self.writer.lock().await;
+                for request in requests {
+                    let (seq_id, rx) = match self.register() {
+                        Ok(registered) => registered,
+                        Err(e) => return Err(self.fail(&ids, e)),
+                    };
+                    ids.push(seq_id);
+                    receivers.push(rx);
+                    if let Err(e) =
+                        frame_async::send_request_with_seq(&mut *writer, request, seq_id).await
+                    {
+                        return Err(self.fail(&ids, e));
+                    }
+                }
+            }
+            let mut responses = Vec::with_capacity(requests.len());
+            for rx in receivers {
+                responses.push(rx.await.map_err(|_| broken())?);
+            }
+            Ok(responses)
+        }
+    }
+
+    impl Drop for AsyncMux {
+        fn drop(&mut self) {
+            self.reader.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    fn status(text: &str) -> VeloResponse {
+        VeloResponse::StatusAck {
+            status: text.to_string(),
+        }
+    }
+
+    /// Reads `n` requests, then answers them last to first, each with the
+    /// seq_id of the request it answers as its status text
+    fn reverse_server(mut stream: UnixStream, n: usize) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || {
+            let mut ids = Vec::new();
+            for _ in 0..n {
+                let (header, _) = frame_sync::read_request(&mut stream).unwrap();
+                ids.push(header.seq_id);
+            }
+            for id in ids.into_iter().rev() {
+                frame_sync::send_response(&mut stream, &status(&id.to_string()), id).unwrap();
+            }
+        })
+    }
+
+    fn sync_mux(stream: UnixStream) -> SyncMux<UnixStream, UnixStream> {
+        SyncMux::new(stream.try_clone().unwrap(), stream)
+    }
+
+    #[test]
+    fn test_sync_call_all_matches_out_of_order_responses() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = reverse_server(server, 3);
+        let mux = sync_mux(client);
+
+        let requests: Vec<_> = (0..3).map(|_| VeloRequest::Status).collect();
+        let responses = mux
+            .call_all(&requests, Some(Duration::from_secs(5)))
+            .unwrap();
+        server.join().unwrap();
+
+        // Each response names a distinct request, in the order they were sent
+        let ids: Vec<u32> = responses
+            .iter()
+            .map(|r| match r {
+                VeloResponse::StatusAck { status } => status.parse().unwrap(),
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]), "{:?}", ids);
+        assert!(!mux.is_broken());
+    }
+
+    #[test]
+    fn test_sync_callers_on_threads_share_connection() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = reverse_server(server, 2);
+        let mux = sync_mux(client);
+
+        let (first, second) = std::thread::scope(|s| {
+            let first = s.spawn(|| mux.call(&VeloRequest::Status, None).unwrap());
+            let second = s.spawn(|| mux.call(&VeloRequest::Status, None).unwrap());
+            (first.join().unwrap(), second.join().unwrap())
+        });
+        server.join().unwrap();
+        assert_ne!(format!("{:?}", first), format!("{:?}", second));
+    }
+
+    #[test]
+    fn test_sync_timeout_breaks_connection_while_reading() {
+        let (client, _server) = UnixStream::pair().unwrap();
+        let mux = sync_mux(client);
+
+        let seq_id = mux.send(&VeloRequest::Status).unwrap();
+        let err = mux
+            .recv(seq_id, Some(Duration::from_millis(20)))
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+        assert!(mux.is_broken());
+        assert!(mux.send(&VeloRequest::Status).is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_call_all_matches_out_of_order_responses() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = reverse_server(server, 3);
+        client.set_nonblocking(true).unwrap();
+        let mux = AsyncMux::from_stream(tokio::net::UnixStream::from_std(client).unwrap());
+
+        let requests: Vec<_> = (0..3).map(|_| VeloRequest::Status).collect();
+        let responses = mux.call_all(&requests).await.unwrap();
+        server.join().unwrap();
+        let ids: Vec<u32> = responses
+            .iter()
+            .map(|r| match r {
+                VeloResponse::StatusAck { status } => status.parse().unwrap(),
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]), "{:?}", ids);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_calls_fail_when_server_hangs_up() {
+        let (client, server) = UnixStream::pair().unwrap();
+        client.set_nonblocking(true).unwrap();
+        let mux = AsyncMux::from_stream(tokio::net::UnixStream::from_std(client).unwrap());
+        drop(server);
+
+        assert!(mux.call(&VeloRequest::Status).await.is_err());
+        assert!(mux.is_broken());
+    }
+}
@@ -0,0 +1,181 @@
+//! Who is on the other end of a daemon socket
+//!
+//! Both daemons listen on Unix sockets under world-writable directories, so
+//! any local user can connect. The kernel reports the connecting process's
+//! credentials (`SO_PEERCRED` on Linux, `LOCAL_PEERCRED` on macOS) and
+//! [`PeerPolicy`] decides from them whether the client may be served.
+
+use std::os::unix::io::RawFd;
+
+use crate::VeloError;
+
+/// Credentials of a connected client, as reported by the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<libc::pid_t>,
+}
+
+impl PeerCredentials {
+    /// Credentials of the peer of connected socket `fd`
+    #[cfg(target_os = "linux")]
+    pub fn from_fd(fd: RawFd) -> Option<Self> {
+        let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut _ as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret == 0 {
+            Some(Self {
+                uid: cred.uid,
+                gid: cred.gid,
+                pid: Some(cred.pid),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Credentials of the peer of connected socket `fd`
+    #[cfg(target_os = "macos")]
+    pub fn from_fd(fd: RawFd) -> Option<Self> {
+        #[repr(C)]
+        struct XuCred {
+            cr_version: u32,
+            cr_uid: u32,
+            cr_ngroups: i16,
+            cr_groups: [u32; 16],
+        }
+        let mut cred: XuCred = unsafe { std::mem::zeroed() };
+        cred.cr_version = 0; // XUCRED_VERSION
+        let mut len = std::mem::size_of::<XuCred>() as libc::socklen_t;
+        const LOCAL_PEERCRED: libc::c_int = 1;
+        const LOCAL_PEERPID: libc::c_int = 2;
+
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                0, // SOL_LOCAL = 0 on macOS
+                LOCAL_PEERCRED,
+                &mut cred as *mut _ as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret != 0 {
+            return None;
+        }
+
+        // Also fetch PID
+        let mut pid: libc::pid_t = 0;
+        let mut pid_len = std::mem::size_of::<libc::pid_t>() as libc::socklen_t;
+        let ret_pid = unsafe {
+            libc::getsockopt(
+                fd,
+                0, // SOL_LOCAL = 0 on macOS
+                LOCAL_PEERPID,
+                &mut pid as *mut _ as *mut libc::c_void,
+                &mut pid_len,
+            )
+        };
+
+        Some(Self {
+            uid: cred.cr_uid,
+            gid: cred.cr_groups[0],
+            pid: if ret_pid == 0 { Some(pid) } else { None },
+        })
+    }
+}
+
+/// Which users a daemon serves: its own, root, and an explicit allowlist
+/// (`[daemon] allowed_uids` in the config)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerPolicy {
+    owner: u32,
+    allowed_uids: Vec<u32>,
+}
+
+impl PeerPolicy {
+    pub fn new(owner: u32, allowed_uids: &[u32]) -> Self {
+        Self {
+            owner,
+            allowed_uids: allowed_uids.to_vec(),
+        }
+    }
+
+    /// Policy for a daemon running as the current user
+    pub fn for_current_user(allowed_uids: &[u32]) -> Self {
+        Self::new(unsafe { libc::getuid() }, allowed_uids)
+    }
+
+    /// Whether `peer` may be served. Unknown credentials are refused.
+    pub fn check(&self, peer: Option<&PeerCredentials>) -> Result<(), VeloError> {
+        match peer {
+            Some(p) if self.is_owner(p) || self.allowed_uids.contains(&p.uid) => Ok(()),
+            Some(p) => Err(VeloError::permission_denied(format!(
+                "uid {} may not use this daemon (add it to [daemon] allowed_uids)",
+                p.uid
+            ))),
+            None => Err(VeloError::permission_denied("Peer credentials unavailable")),
+        }
+    }
+
+    /// Whether `peer` may do what the daemon's own user can (process
+    /// control): the owner and root only, never the allowlist
+    pub fn check_owner(&self, peer: Option<&PeerCredentials>) -> Result<(), VeloError> {
+        match peer {
+            Some(p) if self.is_owner(p) => Ok(()),
+            Some(_) => Err(VeloError::permission_denied("UID mismatch")),
+            None => Err(VeloError::permission_denied("Verification failed")),
+        }
+    }
+
+    fn is_owner(&self, peer: &PeerCredentials) -> bool {
+        peer.uid == self.owner || peer.uid == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VeloErrorKind;
+
+    fn peer(uid: u32) -> PeerCredentials {
+        PeerCredentials {
+            uid,
+            gid: uid,
+            pid: None,
+        }
+    }
+
+    #[test]
+    fn test_policy_serves_owner_root_and_allowlist() {
+        let policy = PeerPolicy::new(1000, &[1001]);
+        for uid in [1000, 0, 1001] {
+            assert!(policy.check(Some(&peer(uid))).is_ok(), "uid {}", uid);
+        }
+        let denied = policy.check(Some(&peer(1002))).unwrap_err();
+        assert_eq!(denied.kind, VeloErrorKind::PermissionDenied);
+        assert!(policy.check(None).is_err());
+
+        // Process control stays with the owner
+        assert!(policy.check_owner(Some(&peer(0))).is_ok());
+        assert!(policy.check_owner(Some(&peer(1001))).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_credentials_of_socketpair_peer() {
+        use std::os::unix::io::AsRawFd;
+        let (a, _b) = std::os::unix::net::UnixStream::pair().unwrap();
+        let creds = PeerCredentials::from_fd(a.as_raw_fd()).unwrap();
+        assert_eq!(creds.uid, unsafe { libc::getuid() });
+        assert_eq!(creds.pid, Some(std::process::id() as libc::pid_t));
+    }
+}
@@ -7,7 +7,7 @@
 pub const VDIR_MAGIC: u32 = 0x56524654;
 
 /// VDir format version. Bump on incompatible changes.
-pub const VDIR_VERSION: u32 = 2; // v2: Added CRC32 checksum
+pub const VDIR_VERSION: u32 = 4; // v2: Added CRC32 checksum; v3: btime_sec; v4: ino
 
 /// Default hash table capacity (slots)
 pub const VDIR_DEFAULT_CAPACITY: usize = 65536;
@@ -30,6 +30,40 @@ pub const FLAG_DELETED: u16 = 0x0002;
 pub const FLAG_SYMLINK: u16 = 0x0004;
 /// Entry is a directory
 pub const FLAG_DIR: u16 = 0x0008;
+/// Content is stored as a CAS chunk list (same bit as the manifest's)
+pub const FLAG_CHUNKED: u16 = 0x0010;
+
+/// VDir flags for a manifest vnode. Vnode flags use different bits for
+/// directories (1) and symlinks (2), which here mean dirty and deleted; a
+/// vnode tombstone (0x20) maps to `FLAG_DELETED`.
+pub const fn flags_from_vnode(vnode_flags: u16) -> u16 {
+    let mut flags = vnode_flags & FLAG_CHUNKED;
+    if vnode_flags & 0x1 != 0 {
+        flags |= FLAG_DIR;
+    }
+    if vnode_flags & 0x2 != 0 {
+        flags |= FLAG_SYMLINK;
+    }
+    if vnode_flags & 0x20 != 0 {
+        flags |= FLAG_DELETED;
+    }
+    flags
+}
+
+/// Inverse of [`flags_from_vnode`], for serving VDir entries as vnodes
+pub const fn flags_to_vnode(flags: u16) -> u16 {
+    let mut vnode_flags = flags & FLAG_CHUNKED;
+    if flags & FLAG_DIR != 0 {
+        vnode_flags |= 0x1;
+    }
+    if flags & FLAG_SYMLINK != 0 {
+        vnode_flags |= 0x2;
+    }
+    if flags & FLAG_DELETED != 0 {
+        vnode_flags |= 0x20;
+    }
+    vnode_flags
+}
 
 // ---------------------------------------------------------------------------
 // VDirHeader — occupies first 64 bytes of the mmap file
@@ -67,12 +101,12 @@ pub struct VDirHeader {
 const _: () = assert!(std::mem::size_of::<VDirHeader>() == 64);
 
 // ---------------------------------------------------------------------------
-// VDirEntry — 72 bytes per slot in the hash table
+// VDirEntry — 88 bytes per slot in the hash table
 // ---------------------------------------------------------------------------
 
 /// Single VDir entry in the hash table (open addressing, linear probing).
 ///
-/// Layout (72 bytes total):
+/// Layout (88 bytes total):
 /// ```text
 /// offset  field         size
 /// ------  -----------   ----
@@ -84,6 +118,8 @@ const _: () = assert!(std::mem::size_of::<VDirHeader>() == 64);
 /// 60      mode           4
 /// 64      flags          2
 /// 66      _pad           6
+/// 72      btime_sec      8   (creation time, 0 = unknown)
+/// 80      ino            8   (vDird's inode table, 0 = none allocated)
 /// ```
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default)]
@@ -96,10 +132,12 @@ pub struct VDirEntry {
     pub mode: u32,
     pub flags: u16, // FLAG_DIRTY | FLAG_DELETED | FLAG_SYMLINK | FLAG_DIR
     pub _pad: [u16; 3],
+    pub btime_sec: i64, // Creation time recorded at ingest (0 = unknown)
+    pub ino: u64,       // Allocated inode (0 = derive from path_hash)
 }
 
-// Compile-time assertion: VDirEntry must be exactly 72 bytes
-const _: () = assert!(std::mem::size_of::<VDirEntry>() == 72);
+// Compile-time assertion: VDirEntry must be exactly 88 bytes
+const _: () = assert!(std::mem::size_of::<VDirEntry>() == 88);
 
 impl VDirEntry {
     /// True if slot is empty (never written)
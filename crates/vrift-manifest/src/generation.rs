@@ -0,0 +1,413 @@
+//! Immutable manifest generations for snapshot and rollback.
+//!
+//! A generation is the full entry set of an [`LmdbManifest`] at one point,
+//! serialized deterministically (sorted by path, entries in the
+//! [`crate::record`] format) and stored under the BLAKE3 hash of those
+//! bytes. Identical manifests therefore share one file, and a generation
+//! can be verified against its id before it is restored.
+//!
+//! ```text
+//! <project>/.vrift/snapshots/
+//!     <hex id>.gen   magic b"VGEN" version u8, count u64 LE, then per entry
+//!                    { path_len u32 LE, path, record_len u32 LE, record }
+//!     index.tsv      one line per snapshot taken:
+//!                    id \t created_secs \t entries \t label
+//!     protected      one hex id per line: generations whose entries vdir_d
+//!                    refuses to change (see `vrift snapshot create --protect`)
+//! ```
+//!
+//! The index is append-only: taking a snapshot of an unchanged manifest
+//! adds a line but no new generation file.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use vrift_cas::Blake3Hash;
+
+use crate::lmdb::{LmdbError, LmdbManifest, LmdbResult, ManifestEntry};
+use crate::record;
+
+const MAGIC: &[u8; 4] = b"VGEN";
+const GENERATION_VERSION: u8 = 1;
+const INDEX_FILE: &str = "index.tsv";
+const PROTECTED_FILE: &str = "protected";
+
+/// One snapshot recorded in the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationInfo {
+    /// BLAKE3 hash of the generation file
+    pub id: Blake3Hash,
+    /// When the snapshot was taken (seconds since epoch)
+    pub created_secs: u64,
+    /// Number of manifest entries
+    pub entries: u64,
+    /// Free-form description given when the snapshot was taken
+    pub label: String,
+}
+
+impl GenerationInfo {
+    /// Hex form of `id`, as used in file names and on the command line
+    pub fn id_hex(&self) -> String {
+        vrift_cas::CasStore::hash_to_hex(&self.id)
+    }
+}
+
+/// Serialize entries as a generation (sorts them by path first)
+pub fn encode(entries: &mut [(String, ManifestEntry)]) -> LmdbResult<Vec<u8>> {
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut out = Vec::with_capacity(13 + entries.len() * 96);
+    out.extend_from_slice(MAGIC);
+    out.push(GENERATION_VERSION);
+    out.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for (path, entry) in entries.iter() {
+        let rec = record::encode(entry)?;
+        out.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        out.extend_from_slice(path.as_bytes());
+        out.extend_from_slice(&(rec.len() as u32).to_le_bytes());
+        out.extend_from_slice(&rec);
+    }
+    Ok(out)
+}
+
+/// Parse a generation written by [`encode`]
+pub fn decode(bytes: &[u8]) -> LmdbResult<Vec<(String, ManifestEntry)>> {
+    let corrupt = |what: &str| LmdbError::Corrupted(format!("manifest generation: {what}"));
+    if bytes.len() < 13 || &bytes[..4] != MAGIC {
+        return Err(corrupt("unrecognized file"));
+    }
+    if bytes[4] != GENERATION_VERSION {
+        return Err(corrupt(&format!("unsupported version {}", bytes[4])));
+    }
+    let count = u64::from_le_bytes(bytes[5..13].try_into().unwrap());
+
+    let mut rest = &bytes[13..];
+    let mut take = |n: usize| -> LmdbResult<&[u8]> {
+        if rest.len() < n {
+            return Err(corrupt("truncated"));
+        }
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        Ok(head)
+    };
+    let mut entries = Vec::with_capacity(count.min(1 << 20) as usize);
+    for _ in 0..count {
+        let len = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        let path = std::str::from_utf8(take(len)?)
+            .map_err(|_| corrupt("path is not UTF-8"))?
+            .to_string();
+        let len = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        entries.push((path, record::decode(take(len)?)?));
+    }
+    Ok(entries)
+}
+
+/// Generation files and the snapshot index for one project
+pub struct GenerationStore {
+    dir: PathBuf,
+}
+
+impl GenerationStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `<project_root>/.vrift/snapshots`
+    pub fn for_project(project_root: &Path) -> Self {
+        Self::new(project_root.join(".vrift").join("snapshots"))
+    }
+
+    fn generation_path(&self, id: &Blake3Hash) -> PathBuf {
+        self.dir
+            .join(format!("{}.gen", vrift_cas::CasStore::hash_to_hex(id)))
+    }
+
+    /// Store the manifest's current entries (base and uncommitted delta) as
+    /// a generation and record it in the index. The entries come from one
+    /// [`LmdbManifest::snapshot`], so ingestion may keep writing meanwhile.
+    pub fn create(&self, manifest: &LmdbManifest, label: &str) -> LmdbResult<GenerationInfo> {
+        let mut entries = manifest.snapshot()?.iter()?;
+        let bytes = encode(&mut entries)?;
+        let id = *blake3::hash(&bytes).as_bytes();
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.generation_path(&id);
+        if !path.exists() {
+            let tmp = path.with_extension("tmp");
+            let mut file = File::create(&tmp)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            fs::rename(&tmp, &path)?;
+        }
+
+        let info = GenerationInfo {
+            id,
+            created_secs: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            entries: entries.len() as u64,
+            // The index is line- and tab-delimited
+            label: label.replace(['\t', '\n', '\r'], " "),
+        };
+        let mut index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(INDEX_FILE))?;
+        writeln!(
+            index,
+            "{}\t{}\t{}\t{}",
+            info.id_hex(),
+            info.created_secs,
+            info.entries,
+            info.label
+        )?;
+        index.sync_all()?;
+        Ok(info)
+    }
+
+    /// Snapshots in the order they were taken. Unparseable index lines are
+    /// skipped.
+    pub fn list(&self) -> LmdbResult<Vec<GenerationInfo>> {
+        let file = match File::open(self.dir.join(INDEX_FILE)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut out = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let mut fields = line.splitn(4, '\t');
+            let parsed = (|| {
+                Some(GenerationInfo {
+                    id: vrift_cas::CasStore::hex_to_hash(fields.next()?)?,
+                    created_secs: fields.next()?.parse().ok()?,
+                    entries: fields.next()?.parse().ok()?,
+                    label: fields.next().unwrap_or("").to_string(),
+                })
+            })();
+            out.extend(parsed);
+        }
+        Ok(out)
+    }
+
+    /// Latest snapshot whose id starts with `prefix` (hex). Errors if no
+    /// snapshot matches or the prefix names more than one generation.
+    pub fn find(&self, prefix: &str) -> LmdbResult<GenerationInfo> {
+        let prefix = prefix.to_ascii_lowercase();
+        let matches: Vec<_> = self
+            .list()?
+            .into_iter()
+            .filter(|g| !prefix.is_empty() && g.id_hex().starts_with(&prefix))
+            .collect();
+        let Some(latest) = matches.last() else {
+            return Err(LmdbError::NotFound(format!("snapshot {prefix}")));
+        };
+        if matches.iter().any(|g| g.id != latest.id) {
+            return Err(LmdbError::NotFound(format!(
+                "snapshot id {prefix} is ambiguous"
+            )));
+        }
+        Ok(latest.clone())
+    }
+
+    /// Ids of every generation file in the store, whether or not the index
+    /// still lists it: anything here can be restored, so GC must keep its
+    /// blobs
+    pub fn stored(&self) -> LmdbResult<Vec<Blake3Hash>> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut ids = Vec::new();
+        for entry in dir {
+            let name = entry?.file_name();
+            let id = name
+                .to_str()
+                .and_then(|n| n.strip_suffix(".gen"))
+                .and_then(vrift_cas::CasStore::hex_to_hash);
+            ids.extend(id);
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Read a generation, verifying it against its id
+    pub fn load(&self, id: &Blake3Hash) -> LmdbResult<Vec<(String, ManifestEntry)>> {
+        let bytes = fs::read(self.generation_path(id))?;
+        if blake3::hash(&bytes).as_bytes() != id {
+            return Err(LmdbError::Corrupted(format!(
+                "generation {} does not match its hash",
+                vrift_cas::CasStore::hash_to_hex(id)
+            )));
+        }
+        decode(&bytes)
+    }
+
+    /// Replace the manifest's contents with generation `id` in one LMDB
+    /// transaction. Uncommitted changes are discarded.
+    pub fn restore(&self, manifest: &LmdbManifest, id: &Blake3Hash) -> LmdbResult<usize> {
+        let entries = self.load(id)?;
+        manifest.replace_all(&entries)?;
+        Ok(entries.len())
+    }
+
+    /// Ids of protected generations, in the order they were protected
+    pub fn protected(&self) -> LmdbResult<Vec<Blake3Hash>> {
+        let text = match fs::read_to_string(self.dir.join(PROTECTED_FILE)) {
+            Ok(t) => t,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(text
+            .lines()
+            .filter_map(|l| vrift_cas::CasStore::hex_to_hash(l.trim()))
+            .collect())
+    }
+
+    /// Add or remove generation `id` from the protected list. Returns false
+    /// if it already was (or wasn't) protected.
+    pub fn set_protected(&self, id: &Blake3Hash, protected: bool) -> LmdbResult<bool> {
+        let mut ids = self.protected()?;
+        let present = ids.contains(id);
+        if present == protected {
+            return Ok(false);
+        }
+        if protected {
+            ids.push(*id);
+        } else {
+            ids.retain(|p| p != id);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(PROTECTED_FILE);
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        for id in &ids {
+            writeln!(file, "{}", vrift_cas::CasStore::hash_to_hex(id))?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lmdb::AssetTier;
+    use crate::VnodeEntry;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_generation_snapshot_and_restore() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest.lmdb")).unwrap();
+        let store = GenerationStore::new(temp.path().join("snapshots"));
+
+        manifest.insert(
+            "/src/main.rs",
+            VnodeEntry::new_file([1u8; 32], 100, 0, 0o644),
+            AssetTier::Tier2Mutable,
+        );
+        manifest.insert(
+            "/Cargo.toml",
+            VnodeEntry::new_file([2u8; 32], 50, 0, 0o644),
+            AssetTier::Tier1Immutable,
+        );
+        manifest.commit().unwrap();
+        let good = store.create(&manifest, "before\tbuild").unwrap();
+        assert_eq!(good.entries, 2);
+        assert_eq!(good.label, "before build");
+
+        // Same content, same generation
+        let again = store.create(&manifest, "again").unwrap();
+        assert_eq!(again.id, good.id);
+
+        // A bad session: one file clobbered, one added, one deleted
+        manifest.insert(
+            "/src/main.rs",
+            VnodeEntry::new_file([9u8; 32], 1, 0, 0o644),
+            AssetTier::Tier2Mutable,
+        );
+        manifest.insert(
+            "/junk.o",
+            VnodeEntry::new_file([3u8; 32], 10, 0, 0o644),
+            AssetTier::Tier2Mutable,
+        );
+        manifest.remove("/Cargo.toml");
+        manifest.commit().unwrap();
+        let bad = store.create(&manifest, "after").unwrap();
+        assert_ne!(bad.id, good.id);
+
+        let prefix = &good.id_hex()[..8];
+        assert_eq!(store.find(prefix).unwrap().label, "again");
+        assert_eq!(store.restore(&manifest, &good.id).unwrap(), 2);
+        assert_eq!(
+            manifest.get("/src/main.rs").unwrap().unwrap().vnode.size,
+            100
+        );
+        assert_eq!(
+            manifest.get("/Cargo.toml").unwrap().unwrap().tier,
+            AssetTier::Tier1Immutable
+        );
+        assert!(manifest.get("/junk.o").unwrap().is_none());
+        assert_eq!(manifest.len().unwrap(), 2);
+
+        assert_eq!(store.list().unwrap().len(), 3);
+        assert!(store.find("zz").is_err());
+    }
+
+    #[test]
+    fn test_generation_protect_and_unprotect() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest.lmdb")).unwrap();
+        let store = GenerationStore::new(temp.path().join("snapshots"));
+        assert!(store.protected().unwrap().is_empty());
+
+        manifest.insert(
+            "/a",
+            VnodeEntry::new_file([1u8; 32], 1, 0, 0o644),
+            AssetTier::Tier2Mutable,
+        );
+        let first = store.create(&manifest, "first").unwrap();
+        manifest.insert(
+            "/b",
+            VnodeEntry::new_file([2u8; 32], 1, 0, 0o644),
+            AssetTier::Tier2Mutable,
+        );
+        let second = store.create(&manifest, "second").unwrap();
+
+        assert!(store.set_protected(&first.id, true).unwrap());
+        assert!(store.set_protected(&second.id, true).unwrap());
+        assert!(!store.set_protected(&first.id, true).unwrap());
+        assert_eq!(store.protected().unwrap(), vec![first.id, second.id]);
+
+        assert!(store.set_protected(&first.id, false).unwrap());
+        assert!(!store.set_protected(&first.id, false).unwrap());
+        assert_eq!(store.protected().unwrap(), vec![second.id]);
+    }
+
+    #[test]
+    fn test_generation_rejects_tampering() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest.lmdb")).unwrap();
+        let store = GenerationStore::new(temp.path().join("snapshots"));
+        manifest.insert(
+            "/a",
+            VnodeEntry::new_file([1u8; 32], 1, 0, 0o644),
+            AssetTier::Tier2Mutable,
+        );
+        let info = store.create(&manifest, "").unwrap();
+
+        let path = store.generation_path(&info.id);
+        let mut bytes = fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(&path, bytes).unwrap();
+        assert!(matches!(store.load(&info.id), Err(LmdbError::Corrupted(_))));
+    }
+}
@@ -0,0 +1,171 @@
+//! Persistent inode allocation for manifest paths.
+//!
+//! Inodes used to be the FNV-1a hash of the path, so a rename changed a
+//! file's inode and two paths could share one. The `inodes` database of an
+//! [`LmdbManifest`](crate::lmdb::LmdbManifest) instead hands out numbers
+//! from a counter and remembers them by path:
+//!
+//! ```text
+//! key                 value
+//! path hash [32]      inode u64 (BE)
+//! "next"              next inode to hand out u64 (BE)
+//! ```
+//!
+//! vDird allocates when it publishes or serves an entry, moves the inode
+//! along with a rename and releases it when the entry is removed. A FUSE
+//! mount of the project does the same for the files it serves, so both
+//! report one inode per file. Released numbers are never reused. The root
+//! always has [`ROOT_INO`].
+
+use heed::byteorder::BE;
+use heed::types::{Bytes, U64};
+use heed::{Database, Env};
+
+use crate::compute_path_hash;
+use crate::lmdb::LmdbResult;
+
+/// Inode of the manifest root, same as `vrift_ipc::identity::ROOT_INO`
+pub const ROOT_INO: u64 = 1;
+
+/// First inode the counter hands out
+pub const FIRST_INO: u64 = ROOT_INO + 1;
+
+/// Key of the allocation counter (path hash keys are 32 bytes)
+const NEXT_KEY: &[u8] = b"next";
+
+pub(crate) type InodeDb = Database<Bytes, U64<BE>>;
+
+/// The inode table of one manifest, see the module docs
+pub struct InodeTable<'a> {
+    pub(crate) env: &'a Env,
+    pub(crate) db: InodeDb,
+}
+
+impl InodeTable<'_> {
+    /// Inode allocated to `path`, if any
+    pub fn get(&self, path: &str) -> LmdbResult<Option<u64>> {
+        if is_root(path) {
+            return Ok(Some(ROOT_INO));
+        }
+        let rtxn = self.env.read_txn()?;
+        Ok(self.db.get(&rtxn, &compute_path_hash(path))?)
+    }
+
+    /// Inode of `path`, allocating the next free one on first use
+    pub fn assign(&self, path: &str) -> LmdbResult<u64> {
+        // Most paths have one already: skip the write transaction
+        if let Some(ino) = self.get(path)? {
+            return Ok(ino);
+        }
+        Ok(self.assign_all([path])?[0])
+    }
+
+    /// [`assign`](Self::assign) for many paths in one transaction, in order
+    pub fn assign_all<'p>(&self, paths: impl IntoIterator<Item = &'p str>) -> LmdbResult<Vec<u64>> {
+        let mut wtxn = self.env.write_txn()?;
+        let mut next = self.db.get(&wtxn, NEXT_KEY)?.unwrap_or(FIRST_INO);
+        let start = next;
+        let mut inos = Vec::new();
+        for path in paths {
+            if is_root(path) {
+                inos.push(ROOT_INO);
+                continue;
+            }
+            let hash = compute_path_hash(path);
+            let ino = match self.db.get(&wtxn, &hash)? {
+                Some(ino) => ino,
+                None => {
+                    let ino = next;
+                    next += 1;
+                    self.db.put(&mut wtxn, &hash, &ino)?;
+                    ino
+                }
+            };
+            inos.push(ino);
+        }
+        if next != start {
+            self.db.put(&mut wtxn, NEXT_KEY, &next)?;
+            wtxn.commit()?;
+        }
+        Ok(inos)
+    }
+
+    /// Give `to` the inode of `from`, replacing any `to` had. Returns the
+    /// moved inode, or `None` if `from` had none (`to` then has none either).
+    pub fn rename(&self, from: &str, to: &str) -> LmdbResult<Option<u64>> {
+        if is_root(from) || is_root(to) {
+            return Ok(None);
+        }
+        let mut wtxn = self.env.write_txn()?;
+        let from_hash = compute_path_hash(from);
+        let to_hash = compute_path_hash(to);
+        let ino = self.db.get(&wtxn, &from_hash)?;
+        match ino {
+            Some(ino) => {
+                self.db.delete(&mut wtxn, &from_hash)?;
+                self.db.put(&mut wtxn, &to_hash, &ino)?;
+            }
+            None => {
+                self.db.delete(&mut wtxn, &to_hash)?;
+            }
+        }
+        wtxn.commit()?;
+        Ok(ino)
+    }
+
+    /// Forget the inode of `path`; a file created there later gets a new one
+    pub fn release(&self, path: &str) -> LmdbResult<()> {
+        if is_root(path) {
+            return Ok(());
+        }
+        let mut wtxn = self.env.write_txn()?;
+        self.db.delete(&mut wtxn, &compute_path_hash(path))?;
+        wtxn.commit()?;
+        Ok(())
+    }
+}
+
+fn is_root(path: &str) -> bool {
+    path.is_empty() || path == "/"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lmdb::LmdbManifest;
+
+    use super::*;
+
+    #[test]
+    fn test_inodes_stable_across_reopen_and_rename() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("manifest.lmdb");
+        {
+            let manifest = LmdbManifest::open(&path).unwrap();
+            let inodes = manifest.inodes();
+            assert_eq!(
+                inodes.assign_all(["/a", "/", "/b", "/a"]).unwrap(),
+                [FIRST_INO, ROOT_INO, FIRST_INO + 1, FIRST_INO]
+            );
+            assert_eq!(inodes.get("").unwrap(), Some(ROOT_INO));
+            assert_eq!(inodes.get("/c").unwrap(), None);
+        }
+
+        let manifest = LmdbManifest::open(&path).unwrap();
+        let inodes = manifest.inodes();
+        assert_eq!(inodes.get("/b").unwrap(), Some(FIRST_INO + 1));
+
+        // The inode follows the file, replacing the target's
+        assert_eq!(inodes.rename("/a", "/b").unwrap(), Some(FIRST_INO));
+        assert_eq!(inodes.get("/a").unwrap(), None);
+        assert_eq!(inodes.get("/b").unwrap(), Some(FIRST_INO));
+
+        // Released and new paths get fresh numbers, never reused ones
+        inodes.release("/b").unwrap();
+        assert_eq!(inodes.assign("/b").unwrap(), FIRST_INO + 2);
+        assert_eq!(inodes.assign("/a").unwrap(), FIRST_INO + 3);
+
+        // A source without an inode leaves none behind at the target
+        assert_eq!(inodes.rename("/x", "/a").unwrap(), None);
+        assert_eq!(inodes.get("/a").unwrap(), None);
+    }
+}
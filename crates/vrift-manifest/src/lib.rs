@@ -9,17 +9,51 @@
 //!
 //! - `Manifest`: In-memory HashMap with rkyv file persistence
 //! - `LmdbManifest`: LMDB-backed with ACID transactions (RFC-0039)
+//!
+//! ## File Format
+//!
+//! `Manifest::save` writes a fixed 64-byte header, the rkyv payload, then a
+//! 32-byte BLAKE3 of everything before it:
+//!
+//! ```text
+//! 0   magic          b"VRIFTMF\0"
+//! 8   format version u32 LE
+//! 12  header length  u32 LE (64)
+//! 16  entry count    u64 LE
+//! 24  created at     u64 LE (unix seconds)
+//! 32  payload length u64 LE
+//! 40  writer         24 bytes, NUL-padded ("vrift-manifest <version>")
+//! 64  payload        rkyv-archived `Manifest`
+//! ..  checksum       BLAKE3(header || payload)
+//! ```
+//!
+//! Version 3 added the xattr table to the payload; version 2 payloads
+//! (without it) still load. Files without the magic are the original
+//! headerless format: they still load (with a warning) and are upgraded the
+//! next time they are saved.
 
+pub mod generation;
+pub mod inode;
 pub mod lmdb;
+pub mod provenance;
+pub mod record;
+pub mod resolve;
 pub mod tier;
+pub mod xattr;
 
-pub use lmdb::{AssetTier, LmdbError, LmdbManifest, LmdbResult, ManifestEntry};
+pub use generation::{GenerationInfo, GenerationStore};
+pub use lmdb::{AssetTier, LmdbError, LmdbManifest, LmdbResult, ManifestEntry, ManifestSnapshot};
+pub use provenance::Provenance;
+pub use record::EntryExt;
+pub use resolve::{resolve_symlinks, SymlinkLoop, MAX_SYMLINK_HOPS};
 pub use tier::{classify_tier, TierClassifier, DEFAULT_TIER1_PATTERNS, DEFAULT_TIER2_PATTERNS};
+pub use xattr::Xattrs;
 
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, BufReader, BufWriter};
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use rkyv::Archive;
 use serde::{Deserialize, Serialize};
@@ -38,10 +72,97 @@ pub enum ManifestError {
 
     #[error("Path not found: {0}")]
     PathNotFound(String),
+
+    #[error("Unsupported manifest format version {found} (this build reads up to {supported})")]
+    UnsupportedFormat { found: u32, supported: u32 },
+
+    #[error("Manifest truncated: expected {expected} bytes, found {actual}")]
+    Truncated { expected: u64, actual: u64 },
+
+    #[error("Manifest checksum mismatch (file is corrupt)")]
+    ChecksumMismatch,
+
+    #[error("Manifest header claims {header} entries but payload has {actual}")]
+    EntryCountMismatch { header: u64, actual: u64 },
+
+    #[error("Manifest is corrupt: {0}")]
+    Corrupt(String),
 }
 
 pub type Result<T> = std::result::Result<T, ManifestError>;
 
+/// Leading bytes of every headered manifest file
+pub const MANIFEST_MAGIC: &[u8; 8] = b"VRIFTMF\0";
+/// Current on-disk format version written by `Manifest::save`
+pub const MANIFEST_FORMAT_VERSION: u32 = 3;
+/// Last format version whose payload has no xattr table
+const PRE_XATTR_FORMAT_VERSION: u32 = 2;
+
+const HEADER_LEN: usize = 64;
+const WRITER_LEN: usize = 24;
+const CHECKSUM_LEN: usize = 32;
+
+/// Decoded manifest file header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestHeader {
+    pub format_version: u32,
+    pub entry_count: u64,
+    /// Unix seconds at save time
+    pub created_at: u64,
+    pub payload_len: u64,
+    /// Tool and version that wrote the file
+    pub writer: String,
+}
+
+impl ManifestHeader {
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut buf = [0u8; HEADER_LEN];
+        buf[0..8].copy_from_slice(MANIFEST_MAGIC);
+        buf[8..12].copy_from_slice(&self.format_version.to_le_bytes());
+        buf[12..16].copy_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+        buf[16..24].copy_from_slice(&self.entry_count.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.created_at.to_le_bytes());
+        buf[32..40].copy_from_slice(&self.payload_len.to_le_bytes());
+        let writer = self.writer.as_bytes();
+        let n = writer.len().min(WRITER_LEN);
+        buf[40..40 + n].copy_from_slice(&writer[..n]);
+        buf
+    }
+
+    /// Parse the header at the start of `data`. `Ok(None)` means no magic,
+    /// i.e. a legacy headerless file.
+    fn decode(data: &[u8]) -> Result<Option<Self>> {
+        if !data.starts_with(MANIFEST_MAGIC) {
+            return Ok(None);
+        }
+        if data.len() < HEADER_LEN {
+            return Err(ManifestError::Truncated {
+                expected: HEADER_LEN as u64,
+                actual: data.len() as u64,
+            });
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+
+        let format_version = u32_at(8);
+        if format_version > MANIFEST_FORMAT_VERSION {
+            return Err(ManifestError::UnsupportedFormat {
+                found: format_version,
+                supported: MANIFEST_FORMAT_VERSION,
+            });
+        }
+        let writer = &data[40..HEADER_LEN];
+        let writer_end = writer.iter().position(|&b| b == 0).unwrap_or(WRITER_LEN);
+        Ok(Some(Self {
+            format_version,
+            entry_count: u64_at(16),
+            created_at: u64_at(24),
+            payload_len: u64_at(32),
+            writer: String::from_utf8_lossy(&writer[..writer_end]).into_owned(),
+        }))
+    }
+}
+
 /// Flags for VnodeEnt#[repr(u8)]
 #[derive(
     Debug,
@@ -70,6 +191,12 @@ pub enum VnodeFlags {
     Symlink = 2,
     /// Executable file
     Executable = 3,
+    /// Set alongside `File`: the content is stored as a chunk list in the
+    /// CAS (same bit as the VDir's `FLAG_CHUNKED`)
+    Chunked = 0x10,
+    /// Tombstone: the path is deleted in this layer, hiding whatever a lower
+    /// layer or the disk has there (the VDir's `FLAG_DELETED`)
+    Whiteout = 0x20,
 }
 
 /// Virtual node entry representing a file or directory in the manifest.
@@ -81,6 +208,10 @@ pub enum VnodeFlags {
 /// - mode: 4 bytes
 /// - flags: 2 bytes
 /// - _pad: 2 bytes
+///
+/// The layout is shared with the inception layer over IPC and is not
+/// extended in place; per-entry data added later goes in the LMDB record's
+/// extensions (see [`record`]).
 #[derive(
     Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize,
 )]
@@ -142,6 +273,18 @@ impl VnodeEntry {
         }
     }
 
+    /// Create a tombstone for a path deleted at `mtime`
+    pub fn new_whiteout(mtime: u64) -> Self {
+        Self {
+            content_hash: [0u8; 32],
+            size: 0,
+            mtime,
+            mode: 0,
+            flags: VnodeFlags::Whiteout as u16,
+            _pad: 0,
+        }
+    }
+
     /// Check if this entry is a directory
     pub fn is_dir(&self) -> bool {
         self.flags & (VnodeFlags::Directory as u16) != 0
@@ -149,7 +292,12 @@ impl VnodeEntry {
 
     /// Check if this entry is a regular file
     pub fn is_file(&self) -> bool {
-        self.flags == VnodeFlags::File as u16
+        self.flags & !(VnodeFlags::Chunked as u16) == VnodeFlags::File as u16
+    }
+
+    /// Check if this entry's content is stored chunked
+    pub fn is_chunked(&self) -> bool {
+        self.flags & (VnodeFlags::Chunked as u16) != 0
     }
 
     /// Check if this entry is a symbolic link
@@ -161,6 +309,11 @@ impl VnodeEntry {
     pub fn is_executable(&self) -> bool {
         self.flags & (VnodeFlags::Executable as u16) != 0
     }
+
+    /// Check if this entry is a tombstone rather than a file system object
+    pub fn is_whiteout(&self) -> bool {
+        self.flags & (VnodeFlags::Whiteout as u16) != 0
+    }
 }
 
 /// Path hash type - hash of the normalized path string
@@ -199,6 +352,18 @@ pub struct Manifest {
     /// Path hash to original path string (for debugging/listing)
     #[serde(default)]
     paths: HashMap<PathHash, String>,
+    /// Path hash to the entry's xattrs, in [`xattr`] encoding (entries
+    /// without any are absent)
+    #[serde(default)]
+    xattrs: HashMap<PathHash, Vec<u8>>,
+}
+
+/// Payload layout of format versions up to [`PRE_XATTR_FORMAT_VERSION`]
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize)]
+struct ManifestV2 {
+    version: u32,
+    entries: HashMap<PathHash, VnodeEntry>,
+    paths: HashMap<PathHash, String>,
 }
 
 impl Manifest {
@@ -208,6 +373,7 @@ impl Manifest {
             version: 1,
             entries: HashMap::new(),
             paths: HashMap::new(),
+            xattrs: HashMap::new(),
         }
     }
 
@@ -235,13 +401,33 @@ impl Manifest {
         self.entries.contains_key(&hash)
     }
 
-    /// Remove an entry from the manifest
+    /// Remove an entry (and its xattrs) from the manifest
     pub fn remove(&mut self, path: &str) -> Option<VnodeEntry> {
         let hash = compute_path_hash(path);
         self.paths.remove(&hash);
+        self.xattrs.remove(&hash);
         self.entries.remove(&hash)
     }
 
+    /// Extended attributes of the entry at `path`, sorted by name
+    pub fn xattrs(&self, path: &str) -> Xattrs {
+        let hash = compute_path_hash(path);
+        self.xattrs
+            .get(&hash)
+            .and_then(|data| xattr::decode(data))
+            .unwrap_or_default()
+    }
+
+    /// Replace the extended attributes of the entry at `path`
+    pub fn set_xattrs(&mut self, path: &str, attrs: &[(String, Vec<u8>)]) {
+        let hash = compute_path_hash(path);
+        if attrs.is_empty() {
+            self.xattrs.remove(&hash);
+        } else {
+            self.xattrs.insert(hash, xattr::encode(attrs));
+        }
+    }
+
     /// Get the number of entries
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -264,27 +450,158 @@ impl Manifest {
         self.paths.values().map(|s| s.as_str())
     }
 
-    /// Save the manifest to a file using rkyv
+    /// Compare against `other` (the newer manifest), path by path.
+    ///
+    /// An entry counts as modified when its content hash, size, mode or
+    /// type flags differ; mtime alone is ignored, so re-ingesting an
+    /// unchanged tree produces an empty diff. Each list is sorted by path.
+    pub fn diff(&self, other: &Manifest) -> ManifestDiff {
+        let mut diff = ManifestDiff::default();
+        for (hash, old) in &self.entries {
+            let path = self.paths.get(hash).cloned().unwrap_or_default();
+            match other.entries.get(hash) {
+                None => diff.removed.push((path, old.clone())),
+                Some(new) if !same_content(old, new) => {
+                    diff.modified.push((path, old.clone(), new.clone()))
+                }
+                Some(_) => {}
+            }
+        }
+        for (hash, new) in &other.entries {
+            if !self.entries.contains_key(hash) {
+                let path = other.paths.get(hash).cloned().unwrap_or_default();
+                diff.added.push((path, new.clone()));
+            }
+        }
+        diff.added.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        diff.removed.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        diff.modified.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        diff
+    }
+
+    /// Save the manifest to a file: header, rkyv payload, BLAKE3 trailer
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let data = rkyv::to_bytes::<rkyv::rancor::Error>(self)
-            .map_err(|e| ManifestError::Rkyv(e.to_string()))?;
+        let bytes = self.to_bytes()?;
         let file = File::create(path)?;
         let mut writer = BufWriter::new(file);
-        std::io::Write::write_all(&mut writer, &data)?;
+        writer.write_all(&bytes)?;
+        writer.flush()?;
         Ok(())
     }
 
-    /// Load a manifest from a file
+    /// Encode the manifest in its on-disk format (see crate docs)
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let payload = rkyv::to_bytes::<rkyv::rancor::Error>(self)
+            .map_err(|e| ManifestError::Rkyv(e.to_string()))?;
+        let header = ManifestHeader {
+            format_version: MANIFEST_FORMAT_VERSION,
+            entry_count: self.entries.len() as u64,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            payload_len: payload.len() as u64,
+            writer: concat!("vrift-manifest ", env!("CARGO_PKG_VERSION")).to_string(),
+        }
+        .encode();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&header);
+        hasher.update(&payload);
+
+        let mut out = Vec::with_capacity(HEADER_LEN + payload.len() + CHECKSUM_LEN);
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&payload);
+        out.extend_from_slice(hasher.finalize().as_bytes());
+        Ok(out)
+    }
+
+    /// Load a manifest from a file, validating header and checksum.
+    ///
+    /// Legacy headerless files are still accepted; saving the result
+    /// rewrites them in the current format.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
         let file = File::open(path)?;
         let mut reader = BufReader::new(file);
         let mut data = Vec::new();
-        std::io::Read::read_to_end(&mut reader, &mut data)?;
-        let manifest = rkyv::from_bytes::<Self, rkyv::rancor::Error>(&data)
-            .map_err(|e| ManifestError::Rkyv(e.to_string()))?;
+        reader.read_to_end(&mut data)?;
+
+        if ManifestHeader::decode(&data)?.is_none() {
+            tracing::warn!(
+                "Manifest {} has no header (legacy format); it will be upgraded on next save",
+                path.display()
+            );
+        }
+        Self::from_bytes(&data)
+    }
+
+    /// Decode a manifest produced by [`Manifest::to_bytes`] (or a legacy
+    /// headerless payload)
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let Some(header) = ManifestHeader::decode(data)? else {
+            return Self::from_payload(data, PRE_XATTR_FORMAT_VERSION);
+        };
+
+        // The length comes from the file: it must not overflow the sums below
+        let (body_end, expected) = usize::try_from(header.payload_len)
+            .ok()
+            .and_then(|len| HEADER_LEN.checked_add(len))
+            .and_then(|end| Some((end, end.checked_add(CHECKSUM_LEN)?)))
+            .ok_or_else(|| {
+                ManifestError::Corrupt(format!(
+                    "payload length {} out of range",
+                    header.payload_len
+                ))
+            })?;
+        let Some(stored) = data.get(body_end..expected) else {
+            return Err(ManifestError::Truncated {
+                expected: expected as u64,
+                actual: data.len() as u64,
+            });
+        };
+        if blake3::hash(&data[..body_end]).as_bytes() != stored {
+            return Err(ManifestError::ChecksumMismatch);
+        }
+
+        let manifest = Self::from_payload(&data[HEADER_LEN..body_end], header.format_version)?;
+        if manifest.entries.len() as u64 != header.entry_count {
+            return Err(ManifestError::EntryCountMismatch {
+                header: header.entry_count,
+                actual: manifest.entries.len() as u64,
+            });
+        }
         Ok(manifest)
     }
 
+    /// Read only the header of a manifest file (`None` for legacy files)
+    pub fn read_header<P: AsRef<Path>>(path: P) -> Result<Option<ManifestHeader>> {
+        let mut data = Vec::with_capacity(HEADER_LEN);
+        File::open(path)?
+            .take(HEADER_LEN as u64)
+            .read_to_end(&mut data)?;
+        ManifestHeader::decode(&data)
+    }
+
+    fn from_payload(payload: &[u8], format_version: u32) -> Result<Self> {
+        // The payload sits at an offset in the file buffer; rkyv validation
+        // needs it aligned.
+        let mut aligned = rkyv::util::AlignedVec::<16>::with_capacity(payload.len());
+        aligned.extend_from_slice(payload);
+        if format_version > PRE_XATTR_FORMAT_VERSION {
+            return rkyv::from_bytes::<Self, rkyv::rancor::Error>(&aligned)
+                .map_err(|e| ManifestError::Rkyv(e.to_string()));
+        }
+        let v2 = rkyv::from_bytes::<ManifestV2, rkyv::rancor::Error>(&aligned)
+            .map_err(|e| ManifestError::Rkyv(e.to_string()))?;
+        Ok(Self {
+            version: v2.version,
+            entries: v2.entries,
+            paths: v2.paths,
+            xattrs: HashMap::new(),
+        })
+    }
+
     /// Get manifest statistics
     pub fn stats(&self) -> ManifestStats {
         let mut file_count = 0u64;
@@ -292,6 +609,9 @@ impl Manifest {
         let mut total_size = 0u64;
 
         for entry in self.entries.values() {
+            if entry.is_whiteout() {
+                continue;
+            }
             if entry.is_dir() {
                 dir_count += 1;
             } else {
@@ -308,6 +628,33 @@ impl Manifest {
     }
 }
 
+fn same_content(a: &VnodeEntry, b: &VnodeEntry) -> bool {
+    a.content_hash == b.content_hash && a.size == b.size && a.mode == b.mode && a.flags == b.flags
+}
+
+/// Result of [`Manifest::diff`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestDiff {
+    /// Paths only in the newer manifest
+    pub added: Vec<(String, VnodeEntry)>,
+    /// Paths only in the older manifest
+    pub removed: Vec<(String, VnodeEntry)>,
+    /// Paths in both whose entries differ: (path, old, new)
+    pub modified: Vec<(String, VnodeEntry, VnodeEntry)>,
+}
+
+impl ManifestDiff {
+    /// Total number of changed paths
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.modified.len()
+    }
+
+    /// True if the manifests have the same content
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 /// Robust path normalization (expands tilde and resolves absolute)
 pub fn normalize_path(p: &str) -> std::path::PathBuf {
     if let Some(stripped) = p.strip_prefix("~/") {
@@ -383,6 +730,180 @@ mod tests {
         let loaded = Manifest::load(&manifest_path).unwrap();
         assert_eq!(loaded.len(), 2);
         assert!(loaded.get("/test/file.txt").is_some());
+
+        let header = Manifest::read_header(&manifest_path).unwrap().unwrap();
+        assert_eq!(header.format_version, MANIFEST_FORMAT_VERSION);
+        assert_eq!(header.entry_count, 2);
+        assert!(header.writer.starts_with("vrift-manifest "));
+    }
+
+    #[test]
+    fn test_manifest_load_rejects_damage() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("test.manifest");
+        let mut manifest = Manifest::new();
+        manifest.insert("/a.txt", VnodeEntry::new_file([7u8; 32], 10, 0, 0o644));
+        manifest.save(&path).unwrap();
+        let good = std::fs::read(&path).unwrap();
+
+        std::fs::write(&path, &good[..good.len() - 10]).unwrap();
+        assert!(matches!(
+            Manifest::load(&path),
+            Err(ManifestError::Truncated { .. })
+        ));
+
+        let mut flipped = good.clone();
+        flipped[HEADER_LEN + 4] ^= 0xFF;
+        std::fs::write(&path, &flipped).unwrap();
+        assert!(matches!(
+            Manifest::load(&path),
+            Err(ManifestError::ChecksumMismatch)
+        ));
+
+        let mut future = good.clone();
+        future[8..12].copy_from_slice(&99u32.to_le_bytes());
+        std::fs::write(&path, &future).unwrap();
+        assert!(matches!(
+            Manifest::load(&path),
+            Err(ManifestError::UnsupportedFormat { found: 99, .. })
+        ));
+
+        // Lengths near u64::MAX must not overflow
+        for payload_len in [u64::MAX, u64::MAX - HEADER_LEN as u64] {
+            let mut huge = good.clone();
+            huge[32..40].copy_from_slice(&payload_len.to_le_bytes());
+            assert!(matches!(
+                Manifest::from_bytes(&huge),
+                Err(ManifestError::Corrupt(_))
+            ));
+        }
+        let mut long = good.clone();
+        long[32..40].copy_from_slice(&(good.len() as u64).to_le_bytes());
+        assert!(matches!(
+            Manifest::from_bytes(&long),
+            Err(ManifestError::Truncated { .. })
+        ));
+
+        // Garbage after the magic is an error, never a panic
+        let mut garbage = good[..HEADER_LEN].to_vec();
+        garbage[32..40].copy_from_slice(&8u64.to_le_bytes());
+        garbage.extend_from_slice(&[0xAB; 8]);
+        let checksum = blake3::hash(&garbage);
+        garbage.extend_from_slice(checksum.as_bytes());
+        assert!(Manifest::from_bytes(&garbage).is_err());
+    }
+
+    #[test]
+    fn test_manifest_loads_legacy_headerless_file() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("legacy.manifest");
+        let mut manifest = Manifest::new();
+        manifest.insert("/old.txt", VnodeEntry::new_file([3u8; 32], 5, 0, 0o644));
+        let legacy = ManifestV2 {
+            version: manifest.version,
+            entries: manifest.entries,
+            paths: manifest.paths,
+        };
+        let raw = rkyv::to_bytes::<rkyv::rancor::Error>(&legacy).unwrap();
+        std::fs::write(&path, &raw).unwrap();
+
+        assert!(Manifest::read_header(&path).unwrap().is_none());
+        let loaded = Manifest::load(&path).unwrap();
+        assert!(loaded.get("/old.txt").is_some());
+
+        // Re-saving upgrades to the headered format
+        loaded.save(&path).unwrap();
+        assert!(Manifest::read_header(&path).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_manifest_xattrs() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("x.manifest");
+        let mut manifest = Manifest::new();
+        manifest.insert("/app", VnodeEntry::new_file([1u8; 32], 1, 0, 0o755));
+        manifest.set_xattrs(
+            "/app",
+            &[("com.apple.cs.CodeSignature".to_string(), vec![0xFA, 0xDE])],
+        );
+        manifest.save(&path).unwrap();
+
+        let mut loaded = Manifest::load(&path).unwrap();
+        assert_eq!(
+            loaded.xattrs("/app"),
+            vec![("com.apple.cs.CodeSignature".to_string(), vec![0xFA, 0xDE])]
+        );
+        assert!(loaded.xattrs("/other").is_empty());
+        loaded.remove("/app");
+        assert!(loaded.xattrs("/app").is_empty());
+
+        // A version 2 file (no xattr table) still loads
+        let mut old = Manifest::new();
+        old.insert("/v2.txt", VnodeEntry::new_file([2u8; 32], 2, 0, 0o644));
+        let payload = rkyv::to_bytes::<rkyv::rancor::Error>(&ManifestV2 {
+            version: old.version,
+            entries: old.entries,
+            paths: old.paths,
+        })
+        .unwrap();
+        let header = ManifestHeader {
+            format_version: PRE_XATTR_FORMAT_VERSION,
+            entry_count: 1,
+            created_at: 0,
+            payload_len: payload.len() as u64,
+            writer: "vrift-manifest 0.1".to_string(),
+        }
+        .encode();
+        let mut data = header.to_vec();
+        data.extend_from_slice(&payload);
+        let checksum = *blake3::hash(&data).as_bytes();
+        data.extend_from_slice(&checksum);
+        let loaded = Manifest::from_bytes(&data).unwrap();
+        assert!(loaded.get("/v2.txt").is_some());
+        assert!(loaded.xattrs("/v2.txt").is_empty());
+    }
+
+    #[test]
+    fn test_manifest_diff() {
+        let mut old = Manifest::new();
+        old.insert("/same.txt", VnodeEntry::new_file([1u8; 32], 10, 100, 0o644));
+        old.insert(
+            "/touched.txt",
+            VnodeEntry::new_file([2u8; 32], 10, 100, 0o644),
+        );
+        old.insert(
+            "/edited.txt",
+            VnodeEntry::new_file([3u8; 32], 10, 100, 0o644),
+        );
+        old.insert("/chmod.sh", VnodeEntry::new_file([4u8; 32], 10, 100, 0o644));
+        old.insert("/gone.txt", VnodeEntry::new_file([5u8; 32], 10, 100, 0o644));
+
+        let mut new = Manifest::new();
+        new.insert("/same.txt", VnodeEntry::new_file([1u8; 32], 10, 100, 0o644));
+        new.insert(
+            "/touched.txt",
+            VnodeEntry::new_file([2u8; 32], 10, 999, 0o644),
+        );
+        new.insert(
+            "/edited.txt",
+            VnodeEntry::new_file([6u8; 32], 12, 100, 0o644),
+        );
+        new.insert("/chmod.sh", VnodeEntry::new_file([4u8; 32], 10, 100, 0o755));
+        new.insert("/b_new.txt", VnodeEntry::new_file([7u8; 32], 1, 100, 0o644));
+        new.insert("/a_new", VnodeEntry::new_directory(100, 0o755));
+
+        let diff = old.diff(&new);
+        let paths = |v: &[(String, VnodeEntry)]| v.iter().map(|e| e.0.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&diff.added), ["/a_new", "/b_new.txt"]);
+        assert_eq!(paths(&diff.removed), ["/gone.txt"]);
+        let modified: Vec<_> = diff.modified.iter().map(|m| m.0.as_str()).collect();
+        assert_eq!(modified, ["/chmod.sh", "/edited.txt"]);
+        assert_eq!(diff.modified[1].2.size, 12);
+        assert_eq!(diff.len(), 5);
+
+        assert!(new.diff(&new).is_empty());
+        let reverse = new.diff(&old);
+        assert_eq!(paths(&reverse.removed), ["/a_new", "/b_new.txt"]);
     }
 
     #[test]
@@ -397,4 +918,21 @@ mod tests {
         assert_eq!(stats.dir_count, 1);
         assert_eq!(stats.total_size, 300);
     }
+
+    #[test]
+    fn test_whiteout_entry() {
+        let tombstone = VnodeEntry::new_whiteout(1706448000);
+        assert!(tombstone.is_whiteout());
+        assert!(!tombstone.is_file());
+        assert!(!tombstone.is_dir());
+        assert!(!tombstone.is_symlink());
+        assert!(!VnodeEntry::new_file([0u8; 32], 1, 0, 0o644).is_whiteout());
+
+        let mut manifest = Manifest::new();
+        manifest.insert("/a.txt", VnodeEntry::new_file([0u8; 32], 100, 0, 0o644));
+        manifest.insert("/gone.txt", tombstone);
+        let stats = manifest.stats();
+        assert_eq!(stats.file_count, 1);
+        assert_eq!(stats.total_size, 100);
+    }
 }
@@ -3,18 +3,27 @@
 //! Implements dual-layer architecture:
 //! - Base Layer: Immutable entries (LMDB)
 //! - Delta Layer: Mutable modifications (DashMap)
+//!
+//! [`LmdbManifest::snapshot`] freezes both layers into a stable view, so a
+//! full export never mixes states from before and after a concurrent
+//! commit.
 
+use std::collections::HashMap;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
 
 use dashmap::DashMap;
 use heed::types::{Bytes, SerdeBincode, Str};
-use heed::{Database, Env, EnvOpenOptions};
+use heed::{Database, Env, EnvFlags, EnvOpenOptions, RoTxn};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::debug;
 
-use crate::{compute_path_hash, PathHash, VnodeEntry};
+use crate::inode::{InodeDb, InodeTable};
+use crate::provenance::Provenance;
+use crate::record::{EntryCodec, EntryExt};
+use crate::{compute_path_hash, PathHash, VnodeEntry, Xattrs};
 
 /// LMDB Manifest errors
 #[derive(Error, Debug)]
@@ -30,6 +39,9 @@ pub enum LmdbError {
 
     #[error("Manifest corrupted: {0}")]
     Corrupted(String),
+
+    #[error(transparent)]
+    SymlinkLoop(#[from] crate::resolve::SymlinkLoop),
 }
 
 pub type LmdbResult<T> = std::result::Result<T, LmdbError>;
@@ -60,6 +72,76 @@ pub struct ManifestEntry {
     /// Whether this entry is "stale" (pending re-ingest)
     #[serde(default)]
     pub stale: bool,
+
+    /// Optional extension records (see [`crate::record`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ext: Vec<EntryExt>,
+}
+
+impl ManifestEntry {
+    /// Data of the extension with `tag`, if present
+    pub fn ext(&self, tag: u16) -> Option<&[u8]> {
+        self.ext
+            .iter()
+            .find(|e| e.tag == tag)
+            .map(|e| e.data.as_slice())
+    }
+
+    /// Set (or replace) the extension with `tag`
+    pub fn set_ext(&mut self, tag: u16, data: Vec<u8>) {
+        match self.ext.iter_mut().find(|e| e.tag == tag) {
+            Some(existing) => existing.data = data,
+            None => self.ext.push(EntryExt { tag, data }),
+        }
+    }
+
+    /// Drop the extension with `tag`
+    pub fn remove_ext(&mut self, tag: u16) {
+        self.ext.retain(|e| e.tag != tag);
+    }
+
+    /// Target of a symlink entry, if one was recorded
+    pub fn symlink_target(&self) -> Option<&[u8]> {
+        if !self.vnode.is_symlink() {
+            return None;
+        }
+        self.ext(crate::record::EXT_SYMLINK_TARGET)
+    }
+
+    /// Whether the entry was ingested from a file in the project tree
+    pub fn is_from_disk(&self) -> bool {
+        self.ext(crate::record::EXT_DISK_ORIGIN).is_some()
+    }
+
+    /// Extended attributes recorded for the entry, sorted by name
+    pub fn xattrs(&self) -> Xattrs {
+        self.ext(crate::record::EXT_XATTRS)
+            .and_then(crate::xattr::decode)
+            .unwrap_or_default()
+    }
+
+    /// Replace the entry's extended attributes (none drops the extension)
+    pub fn set_xattrs(&mut self, attrs: &[(String, Vec<u8>)]) {
+        if attrs.is_empty() {
+            self.remove_ext(crate::record::EXT_XATTRS);
+        } else {
+            self.set_ext(crate::record::EXT_XATTRS, crate::xattr::encode(attrs));
+        }
+    }
+
+    /// Creation time recorded at ingest (seconds), if the file system had one
+    pub fn btime(&self) -> Option<u64> {
+        let data = self.ext(crate::record::EXT_BTIME)?;
+        Some(u64::from_le_bytes(data.try_into().ok()?))
+    }
+
+    /// Set or clear the entry's creation time
+    pub fn set_btime(&mut self, btime: Option<u64>) {
+        match btime {
+            Some(secs) => self.set_ext(crate::record::EXT_BTIME, secs.to_le_bytes().to_vec()),
+            None => self.remove_ext(crate::record::EXT_BTIME),
+        }
+    }
 }
 
 /// Delta entry for in-memory modifications
@@ -79,19 +161,39 @@ pub struct LmdbManifest {
     /// LMDB environment
     env: Env,
 
-    /// Path hash → ManifestEntry database
-    entries_db: Database<Bytes, SerdeBincode<ManifestEntry>>,
+    /// Path hash → ManifestEntry database (versioned records)
+    entries_db: Database<Bytes, EntryCodec>,
 
     /// Path hash → original path string database
     paths_db: Database<Bytes, Str>,
 
+    /// Snapshot-level metadata (provenance)
+    meta_db: Database<Str, SerdeBincode<Provenance>>,
+
+    /// Path hash → allocated inode (see [`crate::inode`])
+    inodes_db: InodeDb,
+
     /// Delta layer for uncommitted modifications
     delta: Arc<DashMap<PathHash, DeltaEntry>>,
 
     /// Path hash → path string for delta entries
     delta_paths: Arc<DashMap<PathHash, String>>,
+
+    /// Mutations hold this shared; [`Self::snapshot`] takes it exclusively
+    /// just long enough to pin a read transaction and copy the delta
+    freeze: RwLock<()>,
+
+    /// Bumped by every mutation
+    generation: AtomicU64,
+
+    /// Told the path of every entry about to be added, see
+    /// [`Self::set_insert_hook`]
+    insert_hook: OnceLock<InsertHook>,
 }
 
+/// Callback for [`LmdbManifest::set_insert_hook`]
+pub type InsertHook = Box<dyn Fn(&str) + Send + Sync>;
+
 impl LmdbManifest {
     /// Default LMDB map size: 1GB (expandable)
     const DEFAULT_MAP_SIZE: usize = 1024 * 1024 * 1024;
@@ -99,6 +201,9 @@ impl LmdbManifest {
     /// Maximum readers
     const MAX_READERS: u32 = 128;
 
+    /// `meta` database key for the snapshot provenance block
+    const PROVENANCE_KEY: &'static str = "provenance";
+
     /// Open or create an LMDB manifest at the given path
     ///
     /// Path should point to a directory that will contain the LMDB files.
@@ -114,19 +219,25 @@ impl LmdbManifest {
         // Create directory if needed
         std::fs::create_dir_all(path)?;
 
-        // Open LMDB environment
+        // Open LMDB environment. NO_TLS ties reader slots to transactions
+        // rather than threads, so a thread holding a snapshot can still
+        // read through the manifest.
         let env = unsafe {
-            EnvOpenOptions::new()
+            let mut options = EnvOpenOptions::new();
+            options
                 .map_size(Self::DEFAULT_MAP_SIZE)
                 .max_readers(Self::MAX_READERS)
-                .max_dbs(2)
-                .open(path)?
+                .max_dbs(4)
+                .flags(EnvFlags::NO_TLS);
+            options.open(path)?
         };
 
         // Open databases
         let mut wtxn = env.write_txn()?;
         let entries_db = env.create_database(&mut wtxn, Some("entries"))?;
         let paths_db = env.create_database(&mut wtxn, Some("paths"))?;
+        let meta_db = env.create_database(&mut wtxn, Some("meta"))?;
+        let inodes_db = env.create_database(&mut wtxn, Some("inodes"))?;
         wtxn.commit()?;
 
         debug!("Opened LMDB manifest at {:?}", path);
@@ -135,8 +246,13 @@ impl LmdbManifest {
             env,
             entries_db,
             paths_db,
+            meta_db,
+            inodes_db,
             delta: Arc::new(DashMap::new()),
             delta_paths: Arc::new(DashMap::new()),
+            freeze: RwLock::new(()),
+            generation: AtomicU64::new(0),
+            insert_hook: OnceLock::new(),
         })
     }
 
@@ -152,17 +268,183 @@ impl LmdbManifest {
             vnode,
             tier,
             stale: false,
+            ext: Vec::new(),
+        };
+        let _frozen = self.inserting(path);
+        self.delta.insert(hash, DeltaEntry::Modified(entry));
+        self.delta_paths.insert(hash, path.to_string());
+    }
+
+    /// Insert an entry ingested from a file in the project tree, with the
+    /// xattrs and creation time read from it (uncommitted)
+    pub fn insert_from_disk(
+        &self,
+        path: &str,
+        vnode: VnodeEntry,
+        tier: AssetTier,
+        xattrs: &[(String, Vec<u8>)],
+        btime: Option<u64>,
+    ) {
+        let hash = compute_path_hash(path);
+        let mut entry = ManifestEntry {
+            vnode,
+            tier,
+            stale: false,
+            ext: Vec::new(),
+        };
+        entry.set_ext(crate::record::EXT_DISK_ORIGIN, Vec::new());
+        entry.set_xattrs(xattrs);
+        entry.set_btime(btime);
+        let _frozen = self.inserting(path);
+        self.delta.insert(hash, DeltaEntry::Modified(entry));
+        self.delta_paths.insert(hash, path.to_string());
+    }
+
+    /// Insert a symlink (uncommitted). The vnode carries the target's hash
+    /// and length, as readers that only see vnodes fetch the target from
+    /// the CAS; the target itself is kept with the entry for resolution.
+    pub fn insert_symlink(&self, path: &str, target: &[u8], mtime: u64, tier: AssetTier) {
+        let hash = compute_path_hash(path);
+        let vnode =
+            VnodeEntry::new_symlink(*blake3::hash(target).as_bytes(), target.len() as u64, mtime);
+        let mut entry = ManifestEntry {
+            vnode,
+            tier,
+            stale: false,
+            ext: Vec::new(),
         };
+        entry.set_ext(crate::record::EXT_SYMLINK_TARGET, target.to_vec());
+        let _frozen = self.inserting(path);
         self.delta.insert(hash, DeltaEntry::Modified(entry));
         self.delta_paths.insert(hash, path.to_string());
     }
 
+    /// Replace the vnode at `path` (uncommitted), keeping the xattrs and
+    /// creation time of the entry it replaces, as a chmod or rewrite of a
+    /// real file does. A tombstone on either side starts over without them.
+    pub fn upsert(&self, path: &str, vnode: VnodeEntry, tier: AssetTier) {
+        let hash = compute_path_hash(path);
+        let mut entry = ManifestEntry {
+            vnode,
+            tier,
+            stale: false,
+            ext: Vec::new(),
+        };
+        let _frozen = self.inserting(path);
+        if !entry.vnode.is_whiteout() {
+            if let Ok(Some(old)) = self.get_by_hash(&hash) {
+                if !old.vnode.is_whiteout() {
+                    for tag in [crate::record::EXT_XATTRS, crate::record::EXT_BTIME] {
+                        if let Some(data) = old.ext(tag) {
+                            entry.set_ext(tag, data.to_vec());
+                        }
+                    }
+                }
+            }
+        }
+        self.delta.insert(hash, DeltaEntry::Modified(entry));
+        self.delta_paths.insert(hash, path.to_string());
+    }
+
+    /// Replace the xattrs of the entry at `path` (uncommitted). Returns
+    /// false, changing nothing, if there is no entry or only a tombstone.
+    pub fn set_xattrs(&self, path: &str, attrs: &[(String, Vec<u8>)]) -> LmdbResult<bool> {
+        self.update_live(path, |entry| entry.set_xattrs(attrs))
+    }
+
+    /// Set the creation time of the entry at `path` (uncommitted), as
+    /// [`set_xattrs`](Self::set_xattrs)
+    pub fn set_btime(&self, path: &str, btime: Option<u64>) -> LmdbResult<bool> {
+        self.update_live(path, |entry| entry.set_btime(btime))
+    }
+
+    fn update_live(&self, path: &str, f: impl FnOnce(&mut ManifestEntry)) -> LmdbResult<bool> {
+        let hash = compute_path_hash(path);
+        let _frozen = self.mutating();
+        let Some(mut entry) = self.get_by_hash(&hash)? else {
+            return Ok(false);
+        };
+        if entry.vnode.is_whiteout() {
+            return Ok(false);
+        }
+        f(&mut entry);
+        self.delta.insert(hash, DeltaEntry::Modified(entry));
+        self.delta_paths.insert(hash, path.to_string());
+        Ok(true)
+    }
+
+    /// Shared side of the freeze lock, held across a mutation so a snapshot
+    /// sees it either whole or not at all
+    fn mutating(&self) -> std::sync::RwLockReadGuard<'_, ()> {
+        let guard = self.freeze.read().unwrap_or_else(|e| e.into_inner());
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        guard
+    }
+
+    /// Run `hook` with the path of every entry (tombstones included) before
+    /// it is added or replaced, while readers can't see it yet. Removals
+    /// don't call it. Only the first hook set sticks; returns false for
+    /// later ones.
+    pub fn set_insert_hook(&self, hook: InsertHook) -> bool {
+        self.insert_hook.set(hook).is_ok()
+    }
+
+    /// [`mutating`](Self::mutating) for a mutation that adds `path`
+    fn inserting(&self, path: &str) -> std::sync::RwLockReadGuard<'_, ()> {
+        let guard = self.mutating();
+        if let Some(hook) = self.insert_hook.get() {
+            hook(path);
+        }
+        guard
+    }
+
+    /// Mutation counter. Equal values from two calls mean nothing changed
+    /// in between.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Freeze the current state (base + delta) into a stable view.
+    ///
+    /// Writers wait only while the delta is copied; iterating the snapshot
+    /// afterwards runs alongside them. The view pins an LMDB read
+    /// transaction, so drop it once done to let LMDB reclaim pages.
+    pub fn snapshot(&self) -> LmdbResult<ManifestSnapshot<'_>> {
+        let _frozen = self.freeze.write().unwrap_or_else(|e| e.into_inner());
+        let rtxn = self.env.read_txn()?;
+        let delta = self
+            .delta
+            .iter()
+            .map(|entry| {
+                let path = self.delta_paths.get(entry.key()).map(|p| p.value().clone());
+                (*entry.key(), (entry.value().clone(), path))
+            })
+            .collect();
+        Ok(ManifestSnapshot {
+            manifest: self,
+            rtxn,
+            delta,
+            generation: self.generation(),
+        })
+    }
+
     /// Get an entry by path (checks delta first, then base)
     pub fn get(&self, path: &str) -> LmdbResult<Option<ManifestEntry>> {
         let hash = compute_path_hash(path);
         self.get_by_hash(&hash)
     }
 
+    /// Resolve the symlinks in `path` using the targets recorded with
+    /// symlink entries. Links without a recorded target are treated as
+    /// plain entries. See [`crate::resolve`] for the rules.
+    pub fn resolve(&self, path: &str, follow_final: bool) -> LmdbResult<String> {
+        crate::resolve::resolve_symlinks(path, follow_final, |p| {
+            Ok(self
+                .get(p)?
+                .and_then(|entry| String::from_utf8(entry.symlink_target()?.to_vec()).ok()))
+        })
+    }
+
     /// Get an entry by path hash
     pub fn get_by_hash(&self, hash: &PathHash) -> LmdbResult<Option<ManifestEntry>> {
         // Check delta layer first
@@ -185,6 +467,7 @@ impl LmdbManifest {
     /// Mark an entry as stale (pending re-ingest after write)
     pub fn mark_stale(&self, path: &str) {
         let hash = compute_path_hash(path);
+        let _frozen = self.mutating();
 
         if let Some(mut delta_ref) = self.delta.get_mut(&hash) {
             if let DeltaEntry::Modified(entry) = delta_ref.value_mut() {
@@ -206,6 +489,7 @@ impl LmdbManifest {
     /// Remove an entry (creates whiteout in delta)
     pub fn remove(&self, path: &str) {
         let hash = compute_path_hash(path);
+        let _frozen = self.mutating();
         self.delta.insert(hash, DeltaEntry::Deleted);
         self.delta_paths.remove(&hash);
     }
@@ -226,11 +510,53 @@ impl LmdbManifest {
         Ok(None)
     }
 
+    /// True if the delta holds writes a commit would apply
+    pub fn has_pending(&self) -> bool {
+        !self.delta.is_empty()
+    }
+
     /// Commit delta layer to base layer (ACID transaction)
     pub fn commit(&self) -> LmdbResult<()> {
+        self.commit_inner(None).map(|_| ())
+    }
+
+    /// Commit delta layer and stamp the snapshot's provenance in the same
+    /// transaction. Returns `false` (and records nothing) if the delta was
+    /// empty.
+    pub fn commit_with_provenance(&self, provenance: &Provenance) -> LmdbResult<bool> {
+        self.commit_inner(Some(provenance))
+    }
+
+    /// Record provenance for the current base layer without touching entries
+    pub fn set_provenance(&self, provenance: &Provenance) -> LmdbResult<()> {
+        let mut wtxn = self.env.write_txn()?;
+        self.meta_db
+            .put(&mut wtxn, Self::PROVENANCE_KEY, provenance)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// The inode allocation table kept with this manifest
+    pub fn inodes(&self) -> InodeTable<'_> {
+        InodeTable {
+            env: &self.env,
+            db: self.inodes_db,
+        }
+    }
+
+    /// Provenance of the last recorded snapshot, if any
+    pub fn provenance(&self) -> LmdbResult<Option<Provenance>> {
+        let rtxn = self.env.read_txn()?;
+        Ok(self.meta_db.get(&rtxn, Self::PROVENANCE_KEY)?)
+    }
+
+    fn commit_inner(&self, provenance: Option<&Provenance>) -> LmdbResult<bool> {
         if self.delta.is_empty() {
-            return Ok(());
+            return Ok(false);
         }
+        // Held until the delta is cleared: a snapshot must not see entries
+        // both in the new base and still in the delta, or in neither
+        let _frozen = self.mutating();
 
         let mut wtxn = self.env.write_txn()?;
 
@@ -251,6 +577,11 @@ impl LmdbManifest {
             }
         }
 
+        if let Some(provenance) = provenance {
+            self.meta_db
+                .put(&mut wtxn, Self::PROVENANCE_KEY, provenance)?;
+        }
+
         wtxn.commit()?;
 
         // Clear delta
@@ -258,35 +589,38 @@ impl LmdbManifest {
         self.delta_paths.clear();
 
         debug!("Committed delta to LMDB");
+        Ok(true)
+    }
+
+    /// Replace every committed entry with `entries` in one transaction and
+    /// drop the delta layer. Provenance is left as it was.
+    pub fn replace_all(&self, entries: &[(String, ManifestEntry)]) -> LmdbResult<()> {
+        let _frozen = self.mutating();
+        if let Some(hook) = self.insert_hook.get() {
+            entries.iter().for_each(|(path, _)| hook(path));
+        }
+        let mut wtxn = self.env.write_txn()?;
+        self.entries_db.clear(&mut wtxn)?;
+        self.paths_db.clear(&mut wtxn)?;
+        for (path, entry) in entries {
+            let hash = compute_path_hash(path);
+            self.entries_db.put(&mut wtxn, &hash, entry)?;
+            self.paths_db.put(&mut wtxn, &hash, path)?;
+        }
+        wtxn.commit()?;
+
+        self.delta.clear();
+        self.delta_paths.clear();
+        debug!(
+            "Replaced LMDB manifest contents ({} entries)",
+            entries.len()
+        );
         Ok(())
     }
 
     /// Get the number of entries (base + delta)
     pub fn len(&self) -> LmdbResult<usize> {
-        let rtxn = self.env.read_txn()?;
-        let base_len = self.entries_db.len(&rtxn)?;
-
-        // Adjust for delta
-        let mut delta_added = 0usize;
-        let mut delta_removed = 0usize;
-
-        for entry in self.delta.iter() {
-            match entry.value() {
-                DeltaEntry::Modified(_) => {
-                    // Check if it's a new entry or modification
-                    if self.entries_db.get(&rtxn, entry.key())?.is_none() {
-                        delta_added += 1;
-                    }
-                }
-                DeltaEntry::Deleted => {
-                    if self.entries_db.get(&rtxn, entry.key())?.is_some() {
-                        delta_removed += 1;
-                    }
-                }
-            }
-        }
-
-        Ok(base_len as usize + delta_added - delta_removed)
+        self.snapshot()?.len()
     }
 
     /// Check if manifest is empty
@@ -294,42 +628,27 @@ impl LmdbManifest {
         Ok(self.len()? == 0)
     }
 
-    /// Iterate over all entries (base + delta merged)
+    /// Iterate over all entries (base + delta merged), as of one
+    /// [`snapshot`](Self::snapshot)
     ///
     /// Note: This is an expensive operation for large manifests
     pub fn iter(&self) -> LmdbResult<Vec<(String, ManifestEntry)>> {
-        let rtxn = self.env.read_txn()?;
-        let mut result = Vec::new();
-        let mut deleted_hashes = std::collections::HashSet::new();
-
-        // Collect delta deletions
-        for entry in self.delta.iter() {
-            if matches!(entry.value(), DeltaEntry::Deleted) {
-                deleted_hashes.insert(*entry.key());
-            }
-        }
-
-        // Add delta modifications first
-        for entry in self.delta.iter() {
-            if let DeltaEntry::Modified(manifest_entry) = entry.value() {
-                if let Some(path_ref) = self.delta_paths.get(entry.key()) {
-                    result.push((path_ref.value().clone(), manifest_entry.clone()));
-                }
-            }
-        }
-
-        // Add base entries not in delta
-        let mut iter = self.entries_db.iter(&rtxn)?;
-        while let Some(Ok((hash_bytes, entry))) = iter.next() {
-            let hash: PathHash = hash_bytes.try_into().unwrap_or([0u8; 32]);
-            if !self.delta.contains_key(&hash) && !deleted_hashes.contains(&hash) {
-                if let Some(path) = self.paths_db.get(&rtxn, &hash)? {
-                    result.push((path.to_string(), entry));
-                }
-            }
-        }
+        self.snapshot()?.iter()
+    }
 
-        Ok(result)
+    /// Paths whose content is one of `hashes`, sorted
+    pub fn paths_with_hashes(
+        &self,
+        hashes: &std::collections::HashSet<[u8; 32]>,
+    ) -> LmdbResult<Vec<String>> {
+        let mut paths: Vec<String> = self
+            .iter()?
+            .into_iter()
+            .filter(|(_, entry)| hashes.contains(&entry.vnode.content_hash))
+            .map(|(path, _)| path)
+            .collect();
+        paths.sort();
+        Ok(paths)
     }
 
     /// Sync/flush LMDB to disk
@@ -372,6 +691,74 @@ impl LmdbManifest {
     }
 }
 
+/// A frozen view of an [`LmdbManifest`]; see [`LmdbManifest::snapshot`]
+pub struct ManifestSnapshot<'m> {
+    manifest: &'m LmdbManifest,
+    rtxn: RoTxn<'m>,
+    /// Delta entries and their paths at freeze time
+    delta: HashMap<PathHash, (DeltaEntry, Option<String>)>,
+    generation: u64,
+}
+
+impl ManifestSnapshot<'_> {
+    /// [`LmdbManifest::generation`] when the snapshot was taken
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Get an entry by path as of the snapshot
+    pub fn get(&self, path: &str) -> LmdbResult<Option<ManifestEntry>> {
+        let hash = compute_path_hash(path);
+        match self.delta.get(&hash) {
+            Some((DeltaEntry::Modified(entry), _)) => Ok(Some(entry.clone())),
+            Some((DeltaEntry::Deleted, _)) => Ok(None),
+            None => Ok(self.manifest.entries_db.get(&self.rtxn, &hash)?),
+        }
+    }
+
+    /// Number of entries (base + delta)
+    pub fn len(&self) -> LmdbResult<usize> {
+        let db = &self.manifest.entries_db;
+        let mut len = db.len(&self.rtxn)? as usize;
+        for (hash, (entry, _)) in &self.delta {
+            let in_base = db.get(&self.rtxn, hash)?.is_some();
+            match entry {
+                DeltaEntry::Modified(_) if !in_base => len += 1,
+                DeltaEntry::Deleted if in_base => len -= 1,
+                _ => {}
+            }
+        }
+        Ok(len)
+    }
+
+    /// Check if the snapshot is empty
+    pub fn is_empty(&self) -> LmdbResult<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// All entries (base + delta merged), delta entries first
+    pub fn iter(&self) -> LmdbResult<Vec<(String, ManifestEntry)>> {
+        let mut result = Vec::new();
+        for (entry, path) in self.delta.values() {
+            if let (DeltaEntry::Modified(entry), Some(path)) = (entry, path) {
+                result.push((path.clone(), entry.clone()));
+            }
+        }
+
+        let mut iter = self.manifest.entries_db.iter(&self.rtxn)?;
+        while let Some(Ok((hash_bytes, entry))) = iter.next() {
+            let hash: PathHash = hash_bytes.try_into().unwrap_or([0u8; 32]);
+            if self.delta.contains_key(&hash) {
+                continue;
+            }
+            if let Some(path) = self.manifest.paths_db.get(&self.rtxn, &hash)? {
+                result.push((path.to_string(), entry));
+            }
+        }
+        Ok(result)
+    }
+}
+
 /// Statistics about the LMDB manifest
 #[derive(Debug, Clone, Default)]
 pub struct ManifestStats {
@@ -425,6 +812,244 @@ mod tests {
         assert_eq!(retrieved.tier, AssetTier::Tier1Immutable);
     }
 
+    #[test]
+    fn test_lmdb_manifest_extensions_persist() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        manifest.insert(
+            "/link",
+            VnodeEntry::new_symlink([1u8; 32], 6, 0),
+            AssetTier::Tier2Mutable,
+        );
+        manifest.commit().unwrap();
+        manifest.mark_stale("/link");
+        manifest.commit().unwrap();
+
+        // Stamp an extension directly, as a newer writer would
+        let mut entry = manifest.get("/link").unwrap().unwrap();
+        entry.set_ext(crate::record::EXT_SYMLINK_TARGET, b"target".to_vec());
+        let mut wtxn = manifest.env.write_txn().unwrap();
+        let hash = compute_path_hash("/link");
+        manifest.entries_db.put(&mut wtxn, &hash, &entry).unwrap();
+        wtxn.commit().unwrap();
+        drop(manifest);
+
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        let entry = manifest.get("/link").unwrap().unwrap();
+        assert!(entry.stale);
+        assert_eq!(
+            entry.ext(crate::record::EXT_SYMLINK_TARGET),
+            Some(&b"target"[..])
+        );
+    }
+
+    #[test]
+    fn test_lmdb_manifest_resolve_symlinks() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        let link = |target: &str| {
+            let mut entry = ManifestEntry {
+                vnode: VnodeEntry::new_symlink([0u8; 32], target.len() as u64, 0),
+                tier: AssetTier::Tier2Mutable,
+                stale: false,
+                ext: Vec::new(),
+            };
+            entry.set_ext(
+                crate::record::EXT_SYMLINK_TARGET,
+                target.as_bytes().to_vec(),
+            );
+            entry
+        };
+        let file = ManifestEntry {
+            vnode: VnodeEntry::new_file([1u8; 32], 1, 0, 0o644),
+            tier: AssetTier::Tier2Mutable,
+            stale: false,
+            ext: Vec::new(),
+        };
+        manifest
+            .replace_all(&[
+                ("/lib/libz.so".to_string(), link("libz.so.1")),
+                ("/lib/libz.so.1".to_string(), file),
+                ("/loop/a".to_string(), link("b")),
+                ("/loop/b".to_string(), link("../loop/a")),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            manifest.resolve("/lib/libz.so", true).unwrap(),
+            "/lib/libz.so.1"
+        );
+        assert_eq!(
+            manifest.resolve("/lib/libz.so", false).unwrap(),
+            "/lib/libz.so"
+        );
+        assert!(matches!(
+            manifest.resolve("/loop/a", true),
+            Err(LmdbError::SymlinkLoop(_))
+        ));
+    }
+
+    #[test]
+    fn test_lmdb_manifest_insert_symlink() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        manifest.insert_symlink("/lib/libz.so", b"libz.so.1", 7, AssetTier::Tier1Immutable);
+        manifest.insert(
+            "/lib/libz.so.1",
+            VnodeEntry::new_file([1u8; 32], 1, 0, 0o644),
+            AssetTier::Tier1Immutable,
+        );
+        manifest.commit().unwrap();
+
+        let entry = manifest.get("/lib/libz.so").unwrap().unwrap();
+        assert!(entry.vnode.is_symlink());
+        assert_eq!(
+            entry.vnode.content_hash,
+            *blake3::hash(b"libz.so.1").as_bytes()
+        );
+        assert_eq!(entry.vnode.size, 9);
+        assert_eq!(entry.symlink_target(), Some(&b"libz.so.1"[..]));
+        assert_eq!(
+            manifest.resolve("/lib/libz.so", true).unwrap(),
+            "/lib/libz.so.1"
+        );
+        let file = manifest.get("/lib/libz.so.1").unwrap().unwrap();
+        assert_eq!(file.symlink_target(), None);
+    }
+
+    #[test]
+    fn test_lmdb_manifest_xattrs() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        let file = |hash: u8, mode: u32| VnodeEntry::new_file([hash; 32], 1, 0, mode);
+        manifest.insert_from_disk(
+            "/bin/tool",
+            file(1, 0o644),
+            AssetTier::Tier2Mutable,
+            &[("security.selinux".to_string(), b"bin_t\0".to_vec())],
+            None,
+        );
+        assert!(manifest
+            .set_xattrs(
+                "/bin/tool",
+                &[
+                    ("user.b".to_string(), b"2".to_vec()),
+                    ("user.a".to_string(), b"1".to_vec()),
+                ],
+            )
+            .unwrap());
+        assert!(!manifest.set_xattrs("/missing", &[]).unwrap());
+        manifest.commit().unwrap();
+
+        // A metadata or content change keeps them
+        manifest.upsert("/bin/tool", file(2, 0o755), AssetTier::Tier2Mutable);
+        manifest.commit().unwrap();
+        let entry = manifest.get("/bin/tool").unwrap().unwrap();
+        assert_eq!(entry.vnode.mode, 0o755);
+        assert_eq!(
+            entry.xattrs(),
+            vec![
+                ("user.a".to_string(), b"1".to_vec()),
+                ("user.b".to_string(), b"2".to_vec()),
+            ]
+        );
+
+        // Deleting and recreating the path doesn't bring them back
+        manifest.upsert(
+            "/bin/tool",
+            VnodeEntry::new_whiteout(1),
+            AssetTier::Tier2Mutable,
+        );
+        assert!(!manifest.set_xattrs("/bin/tool", &[]).unwrap());
+        manifest.upsert("/bin/tool", file(3, 0o644), AssetTier::Tier2Mutable);
+        let entry = manifest.get("/bin/tool").unwrap().unwrap();
+        assert!(entry.xattrs().is_empty());
+        assert_eq!(entry.ext(crate::record::EXT_XATTRS), None);
+    }
+
+    #[test]
+    fn test_lmdb_manifest_btime() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        let file = |hash: u8| VnodeEntry::new_file([hash; 32], 1, 0, 0o644);
+        let tier = AssetTier::Tier2Mutable;
+        manifest.insert_from_disk("/a.rs", file(1), tier, &[], Some(1_700_000_000));
+        manifest.insert_from_disk("/b.rs", file(1), tier, &[], None);
+        manifest.commit().unwrap();
+        assert_eq!(manifest.get("/b.rs").unwrap().unwrap().btime(), None);
+        assert!(manifest.set_btime("/b.rs", Some(5)).unwrap());
+        assert!(!manifest.set_btime("/missing.rs", Some(5)).unwrap());
+        assert_eq!(manifest.get("/b.rs").unwrap().unwrap().btime(), Some(5));
+
+        // Rewriting the file keeps its birth time; recreating it doesn't
+        manifest.upsert("/a.rs", file(2), tier);
+        manifest.commit().unwrap();
+        assert_eq!(
+            manifest.get("/a.rs").unwrap().unwrap().btime(),
+            Some(1_700_000_000)
+        );
+        manifest.upsert("/a.rs", VnodeEntry::new_whiteout(1), tier);
+        manifest.upsert("/a.rs", file(3), tier);
+        assert_eq!(manifest.get("/a.rs").unwrap().unwrap().btime(), None);
+    }
+
+    #[test]
+    fn test_lmdb_manifest_provenance() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        assert!(manifest.provenance().unwrap().is_none());
+
+        let prov = Provenance {
+            user: "alice".to_string(),
+            hostname: "build-1".to_string(),
+            timestamp: 1706448000,
+            command: "vrift ingest .".to_string(),
+            vcs_commit: Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+        };
+
+        // Empty delta: nothing committed, nothing stamped
+        assert!(!manifest.has_pending());
+        assert!(!manifest.commit_with_provenance(&prov).unwrap());
+        assert!(manifest.provenance().unwrap().is_none());
+
+        manifest.insert(
+            "/a.txt",
+            VnodeEntry::new_file([0x01u8; 32], 1, 0, 0o644),
+            AssetTier::Tier2Mutable,
+        );
+        assert!(manifest.has_pending());
+        assert!(manifest.commit_with_provenance(&prov).unwrap());
+        assert!(!manifest.has_pending());
+
+        drop(manifest);
+        let manifest2 = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        assert_eq!(manifest2.provenance().unwrap(), Some(prov));
+    }
+
+    #[test]
+    fn test_insert_hook_sees_additions_only() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        assert!(manifest.set_insert_hook(Box::new(move |path| {
+            sink.lock().unwrap().push(path.to_string())
+        })));
+        assert!(!manifest.set_insert_hook(Box::new(|_| {})));
+
+        let tier = AssetTier::Tier2Mutable;
+        let file = VnodeEntry::new_file([0x01u8; 32], 1, 0, 0o644);
+        manifest.insert("/a.txt", file.clone(), tier);
+        manifest.upsert("/b.txt", VnodeEntry::new_whiteout(1), tier);
+        manifest.insert_symlink("/c", b"a.txt", 1, tier);
+        manifest.remove("/a.txt");
+        manifest.commit().unwrap();
+        let restored = vec![("/d.txt".to_string(), manifest.get("/c").unwrap().unwrap())];
+        manifest.replace_all(&restored).unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), ["/a.txt", "/b.txt", "/c", "/d.txt"]);
+    }
+
     #[test]
     fn test_lmdb_manifest_delta_override() {
         let temp = TempDir::new().unwrap();
@@ -473,6 +1098,50 @@ mod tests {
         assert!(manifest.get("/to_delete.txt").unwrap().is_none());
     }
 
+    #[test]
+    fn test_lmdb_manifest_snapshot_is_stable() {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        let file = |byte: u8| VnodeEntry::new_file([byte; 32], byte as u64, 0, 0o644);
+
+        manifest.insert("/base.rs", file(1), AssetTier::Tier2Mutable);
+        manifest.insert("/gone.rs", file(2), AssetTier::Tier2Mutable);
+        manifest.commit().unwrap();
+        manifest.insert("/delta.rs", file(3), AssetTier::Tier2Mutable);
+        manifest.remove("/gone.rs");
+
+        let snapshot = manifest.snapshot().unwrap();
+        let before = manifest.generation();
+        assert_eq!(snapshot.generation(), before);
+
+        // Writers carry on while the snapshot is alive
+        manifest.insert("/base.rs", file(9), AssetTier::Tier2Mutable);
+        manifest.insert("/late.rs", file(4), AssetTier::Tier2Mutable);
+        manifest.commit().unwrap();
+        manifest.remove("/delta.rs");
+        assert!(manifest.generation() > before);
+
+        let mut paths: Vec<_> = snapshot
+            .iter()
+            .unwrap()
+            .into_iter()
+            .map(|(p, e)| (p, e.vnode.size))
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [("/base.rs".to_string(), 1), ("/delta.rs".to_string(), 3)]
+        );
+        assert_eq!(snapshot.len().unwrap(), 2);
+        assert!(snapshot.get("/gone.rs").unwrap().is_none());
+        assert!(snapshot.get("/late.rs").unwrap().is_none());
+        assert_eq!(manifest.get("/late.rs").unwrap().unwrap().vnode.size, 4);
+        drop(snapshot);
+
+        assert_eq!(manifest.len().unwrap(), 2);
+        assert!(manifest.get("/delta.rs").unwrap().is_none());
+    }
+
     #[test]
     fn test_tier_classification() {
         assert_eq!(AssetTier::default(), AssetTier::Tier2Mutable);
@@ -0,0 +1,186 @@
+//! Snapshot provenance: who produced a manifest, when, with which command,
+//! and from which source commit.
+//!
+//! Provenance is captured by the writer (ingest, auto-commit) and stored
+//! alongside the entries in the LMDB `meta` database. Every field is
+//! best-effort: a missing user, hostname or VCS checkout never fails a write.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Provenance block recorded with each committed manifest snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Login name of the writing process
+    pub user: String,
+    /// Host the snapshot was written on
+    pub hostname: String,
+    /// Commit time (unix seconds)
+    pub timestamp: u64,
+    /// Command that produced the snapshot (e.g. `vrift ingest .`)
+    pub command: String,
+    /// Source commit of the project tree, if it is a git checkout
+    pub vcs_commit: Option<String>,
+}
+
+impl Provenance {
+    /// Capture provenance for the current process.
+    ///
+    /// `project_root` is searched upward for a git checkout to fill in
+    /// `vcs_commit`.
+    pub fn capture(command: impl Into<String>, project_root: Option<&Path>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            user: current_user(),
+            hostname: current_hostname(),
+            timestamp,
+            command: command.into(),
+            vcs_commit: project_root.and_then(git_head_commit),
+        }
+    }
+
+    /// Abbreviated source commit for display (12 hex digits)
+    pub fn short_commit(&self) -> Option<&str> {
+        self.vcs_commit.as_deref().map(|c| c.get(..12).unwrap_or(c))
+    }
+}
+
+fn current_user() -> String {
+    ["USER", "LOGNAME", "USERNAME"]
+        .iter()
+        .find_map(|k| std::env::var(k).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn current_hostname() -> String {
+    let from_file = |p: &str| {
+        std::fs::read_to_string(p)
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+
+    from_file("/proc/sys/kernel/hostname")
+        .or_else(|| from_file("/etc/hostname"))
+        .or_else(|| std::env::var("HOSTNAME").ok().filter(|v| !v.is_empty()))
+        .or_else(|| {
+            std::process::Command::new("hostname")
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .and_then(|o| String::from_utf8(o.stdout).ok())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Resolve the commit `HEAD` points at for the checkout containing `start`.
+///
+/// Reads `.git` directly (loose refs, then `packed-refs`) rather than
+/// shelling out, so it works without a git binary on the PATH.
+pub fn git_head_commit(start: &Path) -> Option<String> {
+    let git_dir = find_git_dir(start)?;
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+
+    let Some(refname) = head.strip_prefix("ref:").map(str::trim) else {
+        return is_object_id(head).then(|| head.to_string());
+    };
+
+    // Linked worktrees keep their refs in the common dir
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+        .ok()
+        .map(|c| git_dir.join(c.trim()))
+        .unwrap_or_else(|| git_dir.clone());
+
+    for dir in [&git_dir, &common_dir] {
+        if let Ok(id) = std::fs::read_to_string(dir.join(refname)) {
+            let id = id.trim();
+            if is_object_id(id) {
+                return Some(id.to_string());
+            }
+        }
+    }
+
+    let packed = std::fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed.lines().find_map(|line| {
+        let (id, name) = line.split_once(' ')?;
+        (name == refname && is_object_id(id)).then(|| id.to_string())
+    })
+}
+
+fn find_git_dir(start: &Path) -> Option<PathBuf> {
+    let mut dir = Some(start);
+    while let Some(d) = dir {
+        let candidate = d.join(".git");
+        if candidate.is_dir() {
+            return Some(candidate);
+        }
+        // Worktrees and submodules: `.git` is a file containing `gitdir: <path>`
+        if let Ok(contents) = std::fs::read_to_string(&candidate) {
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            return Some(d.join(target));
+        }
+        dir = d.parent();
+    }
+    None
+}
+
+fn is_object_id(s: &str) -> bool {
+    matches!(s.len(), 40 | 64) && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const COMMIT: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn test_git_head_loose_ref() {
+        let temp = TempDir::new().unwrap();
+        let git = temp.path().join(".git");
+        std::fs::create_dir_all(git.join("refs/heads")).unwrap();
+        std::fs::write(git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(git.join("refs/heads/main"), format!("{COMMIT}\n")).unwrap();
+
+        let nested = temp.path().join("src/deep");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(git_head_commit(&nested).as_deref(), Some(COMMIT));
+    }
+
+    #[test]
+    fn test_git_head_packed_and_detached() {
+        let temp = TempDir::new().unwrap();
+        let git = temp.path().join(".git");
+        std::fs::create_dir_all(&git).unwrap();
+        std::fs::write(git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(
+            git.join("packed-refs"),
+            format!("# pack-refs with: peeled\n{COMMIT} refs/heads/main\n"),
+        )
+        .unwrap();
+        assert_eq!(git_head_commit(temp.path()).as_deref(), Some(COMMIT));
+
+        std::fs::write(git.join("HEAD"), format!("{COMMIT}\n")).unwrap();
+        assert_eq!(git_head_commit(temp.path()).as_deref(), Some(COMMIT));
+    }
+
+    #[test]
+    fn test_capture_without_checkout() {
+        let temp = TempDir::new().unwrap();
+        let p = Provenance::capture("vrift ingest .", Some(temp.path()));
+        assert_eq!(p.command, "vrift ingest .");
+        assert!(p.timestamp > 0);
+        assert!(!p.user.is_empty());
+        assert!(!p.hostname.is_empty());
+    }
+}
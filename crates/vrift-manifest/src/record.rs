@@ -0,0 +1,277 @@
+//! Versioned on-disk encoding of LMDB manifest entries.
+//!
+//! Entries used to be stored as plain bincode of [`ManifestEntry`], so any
+//! added field made every record unreadable to older builds (and older
+//! records unreadable to newer ones). Records are now self-delimiting:
+//!
+//! ```text
+//! 0    magic        b"VE"
+//! 2    version      u8  (ENTRY_VERSION)
+//! 3    core length  u8  (bytes of core that follow, 56 in version 1)
+//! 4    core         content_hash[32] size u64 mtime u64 mode u32 flags u16
+//!                   tier u8 stale u8   (integers LE)
+//! ..   ext count    u16 LE
+//! ..   extensions   { tag u16 LE, len u32 LE, data[len] } × count
+//! ```
+//!
+//! Compatibility rules:
+//!
+//! - New fixed fields are appended to the core and the core length grows;
+//!   readers skip core bytes they don't know.
+//! - Optional or variable-size data (symlink targets, xattrs, ...) goes in an
+//!   extension record with its own tag. Readers keep unknown extensions so a
+//!   rewrite by an older build doesn't drop them, and otherwise ignore them.
+//! - `version` is bumped only for changes the above can't express; readers
+//!   reject versions newer than their own.
+//!
+//! Legacy bincode records (always [`LEGACY_RECORD_LEN`] bytes) are still
+//! decoded and are rewritten in this format the next time they are
+//! committed.
+
+use std::borrow::Cow;
+
+use heed::types::SerdeBincode;
+use heed::{BoxedError, BytesDecode, BytesEncode};
+use serde::{Deserialize, Serialize};
+
+use crate::lmdb::{AssetTier, LmdbError, ManifestEntry};
+use crate::VnodeEntry;
+
+/// Current entry record version
+pub const ENTRY_VERSION: u8 = 1;
+
+/// Size of a pre-versioning bincode record
+pub const LEGACY_RECORD_LEN: usize = 59;
+
+const MAGIC: &[u8; 2] = b"VE";
+const PREFIX_LEN: usize = 4;
+const CORE_V1_LEN: usize = 56;
+
+/// Extension tag: symlink target path bytes
+pub const EXT_SYMLINK_TARGET: u16 = 1;
+/// Extension tag: extended attributes
+pub const EXT_XATTRS: u16 = 2;
+/// Extension tag: entry mirrors a file in the project tree (no data).
+/// Only these may be dropped when the file is found missing on disk.
+pub const EXT_DISK_ORIGIN: u16 = 3;
+/// Extension tag: creation (birth) time, seconds since the epoch (u64 LE)
+pub const EXT_BTIME: u16 = 4;
+
+/// One optional, tagged extension record on a manifest entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryExt {
+    pub tag: u16,
+    pub data: Vec<u8>,
+}
+
+/// heed codec for [`ManifestEntry`] records in the format above
+pub enum EntryCodec {}
+
+impl<'a> BytesEncode<'a> for EntryCodec {
+    type EItem = ManifestEntry;
+
+    fn bytes_encode(entry: &'a ManifestEntry) -> Result<Cow<'a, [u8]>, BoxedError> {
+        Ok(Cow::Owned(encode(entry)?))
+    }
+}
+
+impl<'a> BytesDecode<'a> for EntryCodec {
+    type DItem = ManifestEntry;
+
+    fn bytes_decode(bytes: &'a [u8]) -> Result<ManifestEntry, BoxedError> {
+        Ok(decode(bytes)?)
+    }
+}
+
+/// Encode an entry in the current record format
+pub fn encode(entry: &ManifestEntry) -> Result<Vec<u8>, LmdbError> {
+    let ext_len: usize = entry.ext.iter().map(|e| 6 + e.data.len()).sum();
+    let mut out = Vec::with_capacity(PREFIX_LEN + CORE_V1_LEN + 2 + ext_len);
+    out.extend_from_slice(MAGIC);
+    out.push(ENTRY_VERSION);
+    out.push(CORE_V1_LEN as u8);
+
+    let vnode = &entry.vnode;
+    out.extend_from_slice(&vnode.content_hash);
+    out.extend_from_slice(&vnode.size.to_le_bytes());
+    out.extend_from_slice(&vnode.mtime.to_le_bytes());
+    out.extend_from_slice(&vnode.mode.to_le_bytes());
+    out.extend_from_slice(&vnode.flags.to_le_bytes());
+    out.push(entry.tier as u8);
+    out.push(entry.stale as u8);
+
+    let count = u16::try_from(entry.ext.len())
+        .map_err(|_| LmdbError::Corrupted("too many entry extensions".into()))?;
+    out.extend_from_slice(&count.to_le_bytes());
+    for ext in &entry.ext {
+        let len = u32::try_from(ext.data.len())
+            .map_err(|_| LmdbError::Corrupted(format!("extension {} too large", ext.tag)))?;
+        out.extend_from_slice(&ext.tag.to_le_bytes());
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&ext.data);
+    }
+    Ok(out)
+}
+
+/// Decode a record in the current or legacy format
+pub fn decode(bytes: &[u8]) -> Result<ManifestEntry, LmdbError> {
+    if bytes.len() == LEGACY_RECORD_LEN {
+        return decode_legacy(bytes);
+    }
+    let corrupt = |what: &str| LmdbError::Corrupted(format!("manifest entry: {what}"));
+
+    if bytes.len() < PREFIX_LEN || &bytes[..2] != MAGIC {
+        return Err(corrupt("unrecognized record"));
+    }
+    let version = bytes[2];
+    if version > ENTRY_VERSION {
+        return Err(corrupt(&format!(
+            "record version {version} is newer than supported {ENTRY_VERSION}"
+        )));
+    }
+    let core_len = bytes[3] as usize;
+    if core_len < CORE_V1_LEN {
+        return Err(corrupt("core too short"));
+    }
+    let core = bytes
+        .get(PREFIX_LEN..PREFIX_LEN + core_len)
+        .ok_or_else(|| corrupt("truncated core"))?;
+
+    let u64_at = |at: usize| u64::from_le_bytes(core[at..at + 8].try_into().unwrap());
+    let vnode = VnodeEntry {
+        content_hash: core[..32].try_into().unwrap(),
+        size: u64_at(32),
+        mtime: u64_at(40),
+        mode: u32::from_le_bytes(core[48..52].try_into().unwrap()),
+        flags: u16::from_le_bytes(core[52..54].try_into().unwrap()),
+        _pad: 0,
+    };
+    let tier = match core[54] {
+        1 => AssetTier::Tier1Immutable,
+        // Unknown tiers from a newer writer get the conservative default
+        _ => AssetTier::Tier2Mutable,
+    };
+    let stale = core[55] != 0;
+
+    let mut rest = &bytes[PREFIX_LEN + core_len..];
+    let mut take = |n: usize| -> Result<&[u8], LmdbError> {
+        if rest.len() < n {
+            return Err(corrupt("truncated extensions"));
+        }
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        Ok(head)
+    };
+    let count = u16::from_le_bytes(take(2)?.try_into().unwrap());
+    let mut ext = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let tag = u16::from_le_bytes(take(2)?.try_into().unwrap());
+        let len = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        ext.push(EntryExt {
+            tag,
+            data: take(len as usize)?.to_vec(),
+        });
+    }
+
+    Ok(ManifestEntry {
+        vnode,
+        tier,
+        stale,
+        ext,
+    })
+}
+
+/// Field layout of records written before versioning
+#[derive(Serialize, Deserialize)]
+struct LegacyEntry {
+    vnode: VnodeEntry,
+    tier: AssetTier,
+    stale: bool,
+}
+
+fn decode_legacy(bytes: &[u8]) -> Result<ManifestEntry, LmdbError> {
+    let legacy = <SerdeBincode<LegacyEntry> as BytesDecode>::bytes_decode(bytes)
+        .map_err(|e| LmdbError::Corrupted(format!("legacy manifest entry: {e}")))?;
+    Ok(ManifestEntry {
+        vnode: legacy.vnode,
+        tier: legacy.tier,
+        stale: legacy.stale,
+        ext: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ManifestEntry {
+        ManifestEntry {
+            vnode: VnodeEntry::new_symlink([9u8; 32], 11, 1706448000),
+            tier: AssetTier::Tier1Immutable,
+            stale: true,
+            ext: vec![EntryExt {
+                tag: EXT_SYMLINK_TARGET,
+                data: b"../lib/a.so".to_vec(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_record_roundtrip() {
+        let entry = sample();
+        let bytes = encode(&entry).unwrap();
+        assert_eq!(&bytes[..3], b"VE\x01");
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(decoded.vnode, entry.vnode);
+        assert_eq!(decoded.tier, entry.tier);
+        assert!(decoded.stale);
+        assert_eq!(decoded.ext(EXT_SYMLINK_TARGET), Some(&b"../lib/a.so"[..]));
+    }
+
+    #[test]
+    fn test_record_from_newer_writer() {
+        // A future writer with a longer core and an extension we don't know
+        let mut bytes = encode(&sample()).unwrap();
+        let ext_start = PREFIX_LEN + CORE_V1_LEN;
+        let ext_tail = bytes.split_off(ext_start);
+        bytes[3] = (CORE_V1_LEN + 8) as u8;
+        bytes.extend_from_slice(&[0xAA; 8]);
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&ext_tail[2..]);
+        bytes.extend_from_slice(&0x7777u16.to_le_bytes());
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(b"new");
+
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(decoded.vnode, sample().vnode);
+        assert_eq!(decoded.ext(EXT_SYMLINK_TARGET), Some(&b"../lib/a.so"[..]));
+        // Unknown extensions survive a rewrite
+        assert_eq!(decoded.ext(0x7777), Some(&b"new"[..]));
+        let again = decode(&encode(&decoded).unwrap()).unwrap();
+        assert_eq!(again.ext, decoded.ext);
+    }
+
+    #[test]
+    fn test_record_rejects_newer_version_and_truncation() {
+        let mut bytes = encode(&sample()).unwrap();
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+        bytes[2] = ENTRY_VERSION + 1;
+        assert!(matches!(decode(&bytes), Err(LmdbError::Corrupted(_))));
+    }
+
+    #[test]
+    fn test_legacy_bincode_record() {
+        let legacy = LegacyEntry {
+            vnode: VnodeEntry::new_file([5u8; 32], 42, 7, 0o644),
+            tier: AssetTier::Tier1Immutable,
+            stale: false,
+        };
+        let bytes = <SerdeBincode<LegacyEntry> as BytesEncode>::bytes_encode(&legacy).unwrap();
+        assert_eq!(bytes.len(), LEGACY_RECORD_LEN);
+
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(decoded.vnode, legacy.vnode);
+        assert_eq!(decoded.tier, AssetTier::Tier1Immutable);
+        assert!(decoded.ext.is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+//! Bounded symlink resolution over manifest paths.
+//!
+//! Manifest symlinks can point at each other, so resolving a path by
+//! following them must terminate. [`resolve_symlinks`] walks the path one
+//! component at a time, the way the kernel's `namei` does, and gives up with
+//! [`SymlinkLoop`] after [`MAX_SYMLINK_HOPS`] links. The limit is Linux's
+//! `MAXSYMLINKS`, so a chain that resolves through the manifest also
+//! resolves when the kernel follows it (passthrough paths, FUSE mounts) and
+//! vice versa.
+//!
+//! Paths are manifest keys (`/src/main.rs`). Absolute link targets restart
+//! from the manifest root, relative ones from the link's directory, and
+//! `..` after a link climbs from where the link pointed, not from where it
+//! was found. `..` at the root stays at the root.
+
+use thiserror::Error;
+
+/// Most symlinks followed while resolving one path (Linux `MAXSYMLINKS`)
+pub const MAX_SYMLINK_HOPS: usize = 40;
+
+/// Resolution followed more than [`MAX_SYMLINK_HOPS`] links (`ELOOP`)
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Too many levels of symbolic links: {path}")]
+pub struct SymlinkLoop {
+    pub path: String,
+}
+
+/// Resolve every symlink in `path`. `read_link` returns the target of a
+/// symlink at a (normalized) path, or `None` if the path is not a link.
+/// The final component is only followed when `follow_final` is set or the
+/// path ends in `/`.
+///
+/// Components that do not exist are kept as they are; the caller's lookup
+/// of the result reports them.
+pub fn resolve_symlinks<E, F>(path: &str, follow_final: bool, mut read_link: F) -> Result<String, E>
+where
+    E: From<SymlinkLoop>,
+    F: FnMut(&str) -> Result<Option<String>, E>,
+{
+    let follow_final = follow_final || path.ends_with('/');
+    // Components still to walk, next one last
+    let mut pending: Vec<String> = split_rev(path);
+    let mut resolved: Vec<String> = Vec::new();
+    let mut hops = 0;
+
+    while let Some(component) = pending.pop() {
+        match component.as_str() {
+            "" | "." => continue,
+            ".." => {
+                resolved.pop();
+                continue;
+            }
+            _ => {}
+        }
+        resolved.push(component);
+        if !follow_final && pending.iter().all(|c| c.is_empty() || c == ".") {
+            break;
+        }
+
+        let current = join(&resolved);
+        if let Some(target) = read_link(&current)? {
+            hops += 1;
+            if hops > MAX_SYMLINK_HOPS {
+                return Err(SymlinkLoop {
+                    path: path.to_string(),
+                }
+                .into());
+            }
+            resolved.pop();
+            if target.starts_with('/') {
+                resolved.clear();
+            }
+            pending.extend(split_rev(&target));
+        }
+    }
+    Ok(join(&resolved))
+}
+
+fn split_rev(path: &str) -> Vec<String> {
+    path.split('/').rev().map(str::to_string).collect()
+}
+
+fn join(components: &[String]) -> String {
+    if components.is_empty() {
+        return "/".to_string();
+    }
+    let mut out = String::new();
+    for c in components {
+        out.push('/');
+        out.push_str(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn resolve(links: &[(&str, &str)], path: &str, follow: bool) -> Result<String, SymlinkLoop> {
+        let links: HashMap<_, _> = links.iter().copied().collect();
+        resolve_symlinks(path, follow, |p| {
+            Ok::<_, SymlinkLoop>(links.get(p).map(|t| t.to_string()))
+        })
+    }
+
+    #[test]
+    fn test_resolve_follows_relative_absolute_and_intermediate_links() {
+        let links = [
+            ("/lib/libfoo.so", "libfoo.so.1"),
+            ("/lib/libfoo.so.1", "libfoo.so.1.2.3"),
+            ("/current", "/releases/v2"),
+            ("/releases/v2/bin", "../../tools/bin"),
+        ];
+        assert_eq!(
+            resolve(&links, "/lib/libfoo.so", true).unwrap(),
+            "/lib/libfoo.so.1.2.3"
+        );
+        assert_eq!(
+            resolve(&links, "/current/bin/cc", true).unwrap(),
+            "/tools/bin/cc"
+        );
+        // `..` climbs from the link's target
+        assert_eq!(
+            resolve(&links, "/current/../v1/x", true).unwrap(),
+            "/releases/v1/x"
+        );
+        // lstat-style: the final link is left alone, unless asked for a directory
+        assert_eq!(resolve(&links, "/current", false).unwrap(), "/current");
+        assert_eq!(resolve(&links, "/current/", false).unwrap(), "/releases/v2");
+        assert_eq!(resolve(&links, "/../../a/./b", true).unwrap(), "/a/b");
+    }
+
+    #[test]
+    fn test_resolve_detects_cycles() {
+        assert!(resolve(&[("/a", "a")], "/a", true).is_err());
+        assert!(resolve(&[("/a", "/b"), ("/b", "/a")], "/a/x", false).is_err());
+        // A cycle through a parent directory
+        assert!(resolve(&[("/d/up", "../d/up/x")], "/d/up", true).is_err());
+        // Not followed, so not a loop
+        assert_eq!(resolve(&[("/a", "a")], "/a", false).unwrap(), "/a");
+    }
+
+    #[test]
+    fn test_resolve_hop_limit() {
+        // /l0 -> /l1 -> ... -> /lN -> /end
+        let chain = |n: usize| -> Vec<(String, String)> {
+            (0..n)
+                .map(|i| {
+                    let next = if i + 1 == n {
+                        "/end".to_string()
+                    } else {
+                        format!("/l{}", i + 1)
+                    };
+                    (format!("/l{}", i), next)
+                })
+                .collect()
+        };
+        let run = |links: Vec<(String, String)>| {
+            let links: HashMap<_, _> = links.into_iter().collect();
+            resolve_symlinks("/l0", true, |p| Ok::<_, SymlinkLoop>(links.get(p).cloned()))
+        };
+        assert_eq!(run(chain(MAX_SYMLINK_HOPS)).unwrap(), "/end");
+        assert_eq!(
+            run(chain(MAX_SYMLINK_HOPS + 1)).unwrap_err().path,
+            "/l0".to_string()
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Extended attributes kept with manifest entries.
+//!
+//! An entry's xattrs live in its [`EXT_XATTRS`](crate::record::EXT_XATTRS)
+//! extension (LMDB) or the manifest file's xattr table, in this encoding:
+//!
+//! ```text
+//! count   u16 LE
+//! attrs   { name_len u16 LE, name[name_len], value_len u32 LE, value[value_len] } × count
+//! ```
+//!
+//! Attributes are sorted by name, so `listxattr` order is stable and equal
+//! sets encode to equal bytes. An entry without attributes has no
+//! extension at all.
+
+use std::io;
+use std::path::Path;
+
+/// Attribute names and values, sorted by name
+pub type Xattrs = Vec<(String, Vec<u8>)>;
+
+/// errno for a missing attribute
+#[cfg(target_os = "macos")]
+pub const NO_ATTR_ERRNO: i32 = libc::ENOATTR;
+/// errno for a missing attribute
+#[cfg(not(target_os = "macos"))]
+pub const NO_ATTR_ERRNO: i32 = libc::ENODATA;
+
+/// Encode `attrs` (in any order) in the format above
+pub fn encode(attrs: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut sorted: Vec<&(String, Vec<u8>)> = attrs.iter().collect();
+    sorted.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    let len: usize = sorted.iter().map(|(n, v)| 6 + n.len() + v.len()).sum();
+    let mut out = Vec::with_capacity(2 + len);
+    out.extend_from_slice(&(sorted.len() as u16).to_le_bytes());
+    for (name, value) in sorted {
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(value);
+    }
+    out
+}
+
+/// Decode attributes encoded by [`encode`]; `None` if `data` is malformed
+pub fn decode(data: &[u8]) -> Option<Xattrs> {
+    let mut rest = data;
+    let mut take = |n: usize| -> Option<&[u8]> {
+        if rest.len() < n {
+            return None;
+        }
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        Some(head)
+    };
+    let count = u16::from_le_bytes(take(2)?.try_into().ok()?);
+    let mut attrs = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name_len = u16::from_le_bytes(take(2)?.try_into().ok()?);
+        let name = String::from_utf8(take(name_len as usize)?.to_vec()).ok()?;
+        let value_len = u32::from_le_bytes(take(4)?.try_into().ok()?);
+        attrs.push((name, take(value_len as usize)?.to_vec()));
+    }
+    Some(attrs)
+}
+
+/// Read the xattrs of `path` (not following a final symlink), leaving out
+/// names in `strip`. File systems without xattr support give an empty list.
+pub fn read_from_disk(path: &Path, strip: &[String]) -> io::Result<Xattrs> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let cpath = CString::new(path.as_os_str().as_bytes())?;
+    let names = match fetch(|buf, size| unsafe { list(&cpath, buf, size) }) {
+        Ok(names) => names,
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut attrs = Vec::new();
+    for raw_name in names.split(|&b| b == 0).filter(|n| !n.is_empty()) {
+        let Ok(name) = std::str::from_utf8(raw_name) else {
+            continue;
+        };
+        if strip.iter().any(|s| s == name) {
+            continue;
+        }
+        let cname = CString::new(raw_name)?;
+        match fetch(|buf, size| unsafe { get(&cpath, &cname, buf, size) }) {
+            Ok(value) => attrs.push((name.to_string(), value)),
+            // Removed between the list and the read
+            Err(e) if e.raw_os_error() == Some(NO_ATTR_ERRNO) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    attrs.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    Ok(attrs)
+}
+
+/// Size the buffer with a probe call, then fill it; retries if the value
+/// grew in between
+fn fetch(call: impl Fn(*mut libc::c_void, usize) -> isize) -> io::Result<Vec<u8>> {
+    loop {
+        let size = call(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0u8; size as usize];
+        let got = call(buf.as_mut_ptr().cast(), buf.len());
+        if got >= 0 {
+            buf.truncate(got as usize);
+            return Ok(buf);
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ERANGE) {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn list(path: &std::ffi::CStr, buf: *mut libc::c_void, size: usize) -> isize {
+    libc::llistxattr(path.as_ptr(), buf.cast(), size)
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn get(
+    path: &std::ffi::CStr,
+    name: &std::ffi::CStr,
+    buf: *mut libc::c_void,
+    size: usize,
+) -> isize {
+    libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf, size)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn list(path: &std::ffi::CStr, buf: *mut libc::c_void, size: usize) -> isize {
+    libc::listxattr(path.as_ptr(), buf.cast(), size, libc::XATTR_NOFOLLOW)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn get(
+    path: &std::ffi::CStr,
+    name: &std::ffi::CStr,
+    buf: *mut libc::c_void,
+    size: usize,
+) -> isize {
+    libc::getxattr(
+        path.as_ptr(),
+        name.as_ptr(),
+        buf,
+        size,
+        0,
+        libc::XATTR_NOFOLLOW,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xattrs_roundtrip_sorted() {
+        let attrs = vec![
+            ("user.b".to_string(), b"2".to_vec()),
+            (
+                "security.selinux".to_string(),
+                b"system_u:object_r:bin_t:s0\0".to_vec(),
+            ),
+            ("user.a".to_string(), Vec::new()),
+        ];
+        let data = encode(&attrs);
+        let decoded = decode(&data).unwrap();
+        let names: Vec<&str> = decoded.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["security.selinux", "user.a", "user.b"]);
+        assert_eq!(decoded[2].1, b"2");
+        assert!(decoded[1].1.is_empty());
+
+        // Order of the input doesn't change the encoding
+        let mut reversed = attrs.clone();
+        reversed.reverse();
+        assert_eq!(encode(&reversed), data);
+    }
+
+    #[test]
+    fn test_xattrs_decode_rejects_truncation() {
+        let data = encode(&[("user.k".to_string(), b"value".to_vec())]);
+        assert!(decode(&data[..data.len() - 1]).is_none());
+        assert!(decode(&[]).is_none());
+    }
+
+    #[test]
+    fn test_read_from_disk_strips_names() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("f");
+        std::fs::write(&path, b"x").unwrap();
+        let cpath = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        let set = |name: &str, value: &[u8]| {
+            let cname = std::ffi::CString::new(name).unwrap();
+            #[cfg(target_os = "linux")]
+            let rc = unsafe {
+                libc::setxattr(
+                    cpath.as_ptr(),
+                    cname.as_ptr(),
+                    value.as_ptr().cast(),
+                    value.len(),
+                    0,
+                )
+            };
+            #[cfg(target_os = "macos")]
+            let rc = unsafe {
+                libc::setxattr(
+                    cpath.as_ptr(),
+                    cname.as_ptr(),
+                    value.as_ptr().cast(),
+                    value.len(),
+                    0,
+                    0,
+                )
+            };
+            rc == 0
+        };
+        // tmpfs without user xattrs: nothing to check
+        if !set("user.keep", b"1") || !set("user.drop", b"2") {
+            return;
+        }
+
+        let attrs = read_from_disk(&path, &["user.drop".to_string()]).unwrap();
+        assert_eq!(attrs, vec![("user.keep".to_string(), b"1".to_vec())]);
+    }
+}
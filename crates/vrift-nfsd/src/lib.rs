@@ -0,0 +1,389 @@
+//! # vrift-nfsd
+//!
+//! Userspace NFSv3 server for Velo Rift manifests, for hosts where FUSE is
+//! unavailable (stock macOS has no FUSE, but every macOS and Linux ships an
+//! NFS client).
+//!
+//! - Serves one manifest, read-only, from a CAS directory or a bundle.
+//! - NFS (program 100003, v3) and MOUNT (program 100005, v3) share a single
+//!   TCP port, so no portmapper is needed: pass `port=` and `mountport=`.
+//! - File handles are node ids, which are stable for a given manifest.
+//! - Blob reads go through the same [`BlobSource`] as `vrift mount`, and
+//!   each blob is hash-verified on its first read.
+
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+use vrift_cas::Blake3Hash;
+use vrift_manifest::Manifest;
+use vrift_pack::BlobSource;
+
+mod nfs;
+pub mod tree;
+pub mod xdr;
+
+use tree::Tree;
+use xdr::{XdrReader, XdrWriter};
+
+pub const NFS_PROGRAM: u32 = 100003;
+pub const MOUNT_PROGRAM: u32 = 100005;
+
+/// Largest request record accepted. We advertise small write sizes and
+/// reject writes anyway, so anything bigger is a broken or hostile client.
+const MAX_RECORD: usize = 4 << 20;
+
+// accept_stat
+const SUCCESS: u32 = 0;
+const PROG_UNAVAIL: u32 = 1;
+const PROG_MISMATCH: u32 = 2;
+const PROC_UNAVAIL: u32 = 3;
+const GARBAGE_ARGS: u32 = 4;
+
+/// A manifest exported over NFSv3
+pub struct NfsServer {
+    tree: Tree,
+    source: Mutex<Box<dyn BlobSource>>,
+    verified: Mutex<HashSet<Blake3Hash>>,
+}
+
+impl NfsServer {
+    pub fn new(manifest: &Manifest, source: impl BlobSource) -> Self {
+        Self {
+            tree: Tree::from_manifest(manifest),
+            source: Mutex::new(Box::new(source)),
+            verified: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Accept connections until the listener fails, one thread each
+    pub fn serve(self, listener: TcpListener) -> anyhow::Result<()> {
+        let server = Arc::new(self);
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = Arc::clone(&server);
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = server.serve_connection(stream) {
+                    tracing::debug!("Connection {:?} closed: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn serve_connection(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        while let Some(record) = read_record(&mut stream)? {
+            if let Some(reply) = self.handle_call(&record) {
+                let mut out = Vec::with_capacity(4 + reply.len());
+                out.extend_from_slice(&(reply.len() as u32 | 0x8000_0000).to_be_bytes());
+                out.extend_from_slice(&reply);
+                stream.write_all(&out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer one ONC RPC call message (RFC 5531). Returns `None` for
+    /// messages that are not calls, which get no reply.
+    pub fn handle_call(&self, msg: &[u8]) -> Option<Vec<u8>> {
+        let mut r = XdrReader::new(msg);
+        let xid = r.u32()?;
+        if r.u32()? != 0 {
+            return None;
+        }
+        let mut reply = XdrWriter::new();
+        reply.u32(xid).u32(1);
+        if r.u32()? != 2 {
+            // MSG_DENIED, RPC_MISMATCH, supported range 2..=2
+            reply.u32(1).u32(0).u32(2).u32(2);
+            return Some(reply.into_bytes());
+        }
+        let (Some(prog), Some(vers), Some(procedure)) = (r.u32(), r.u32(), r.u32()) else {
+            return Some(accepted(reply, GARBAGE_ARGS));
+        };
+        // Credentials and verifier: any flavor, ignored
+        if skip_auth(&mut r).and_then(|_| skip_auth(&mut r)).is_none() {
+            return Some(accepted(reply, GARBAGE_ARGS));
+        }
+
+        let mut body = XdrWriter::new();
+        let handled = match (prog, vers) {
+            (NFS_PROGRAM, 3) => self.nfs3(procedure, &mut r, &mut body),
+            (MOUNT_PROGRAM, 3) => self.mount3(procedure, &mut r, &mut body),
+            (NFS_PROGRAM | MOUNT_PROGRAM, _) => {
+                let mut reply = accepted(reply, PROG_MISMATCH);
+                reply.extend_from_slice(&[0, 0, 0, 3, 0, 0, 0, 3]);
+                return Some(reply);
+            }
+            _ => return Some(accepted(reply, PROG_UNAVAIL)),
+        };
+        Some(match handled {
+            Some(true) => {
+                let mut reply = accepted(reply, SUCCESS);
+                reply.extend_from_slice(&body.into_bytes());
+                reply
+            }
+            Some(false) => accepted(reply, PROC_UNAVAIL),
+            None => accepted(reply, GARBAGE_ARGS),
+        })
+    }
+
+    /// Check a blob against its hash the first time it is read
+    fn verify_once(&self, hash: &Blake3Hash) -> anyhow::Result<()> {
+        if self.verified.lock().unwrap().contains(hash) {
+            return Ok(());
+        }
+        self.source.lock().unwrap().verify(hash)?;
+        self.verified.lock().unwrap().insert(*hash);
+        Ok(())
+    }
+
+    fn read_blob(&self, hash: &Blake3Hash, offset: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        self.source.lock().unwrap().read_range(hash, offset, len)
+    }
+}
+
+/// Skip an opaque_auth (flavor and body, at most 400 bytes)
+fn skip_auth(r: &mut XdrReader) -> Option<()> {
+    r.u32()?;
+    r.opaque(400)?;
+    Some(())
+}
+
+/// MSG_ACCEPTED with an AUTH_NONE verifier
+fn accepted(mut reply: XdrWriter, stat: u32) -> Vec<u8> {
+    reply.u32(0).u32(0).u32(0).u32(stat);
+    reply.into_bytes()
+}
+
+/// Read one record-marked message (RFC 5531 §11), `None` at a clean EOF
+fn read_record(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut record = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        match stream.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && record.is_empty() => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        }
+        let header = u32::from_be_bytes(header);
+        let len = (header & 0x7fff_ffff) as usize;
+        if record.len() + len > MAX_RECORD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "RPC record too large",
+            ));
+        }
+        let start = record.len();
+        record.resize(start + len, 0);
+        stream.read_exact(&mut record[start..])?;
+        if header & 0x8000_0000 != 0 {
+            return Ok(Some(record));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_cas::CasStore;
+    use vrift_manifest::VnodeEntry;
+
+    struct Client<'a> {
+        server: &'a NfsServer,
+        xid: u32,
+    }
+
+    impl Client<'_> {
+        /// Make a call and return the reply body after a SUCCESS accept_stat
+        fn call(&mut self, prog: u32, procedure: u32, args: &XdrWriter) -> Vec<u8> {
+            let (stat, body) = self.call_raw(prog, 3, procedure, args);
+            assert_eq!(stat, SUCCESS);
+            body
+        }
+
+        fn call_raw(
+            &mut self,
+            prog: u32,
+            vers: u32,
+            procedure: u32,
+            args: &XdrWriter,
+        ) -> (u32, Vec<u8>) {
+            self.xid += 1;
+            let mut msg = XdrWriter::new();
+            msg.u32(self.xid)
+                .u32(0)
+                .u32(2)
+                .u32(prog)
+                .u32(vers)
+                .u32(procedure);
+            // AUTH_UNIX credential, AUTH_NONE verifier
+            msg.u32(1).opaque(&[0; 20]).u32(0).opaque(&[]);
+            let mut msg = msg.into_bytes();
+            msg.extend_from_slice(args.as_bytes());
+
+            let reply = self.server.handle_call(&msg).unwrap();
+            let mut r = XdrReader::new(&reply);
+            assert_eq!(r.u32(), Some(self.xid));
+            assert_eq!(r.u32(), Some(1));
+            assert_eq!(r.u32(), Some(0), "MSG_ACCEPTED");
+            r.u32().unwrap();
+            r.opaque(400).unwrap();
+            let stat = r.u32().unwrap();
+            (stat, reply[24..].to_vec())
+        }
+    }
+
+    fn fh(id: u64) -> XdrWriter {
+        let mut w = XdrWriter::new();
+        w.opaque(&id.to_be_bytes());
+        w
+    }
+
+    fn server(temp: &tempfile::TempDir) -> NfsServer {
+        let cas = CasStore::new(temp.path().join("cas")).unwrap();
+        let main_rs = cas.store(b"fn main() {}\n").unwrap();
+        let target = cas.store(b"src/main.rs").unwrap();
+        let mut manifest = Manifest::new();
+        manifest.insert("/src", VnodeEntry::new_directory(0, 0o755));
+        manifest.insert("/src/main.rs", VnodeEntry::new_file(main_rs, 13, 0, 0o644));
+        manifest.insert("/entry", VnodeEntry::new_symlink(target, 11, 0));
+        // Parent directories only implied
+        manifest.insert("/deep/a/b.txt", VnodeEntry::new_file(main_rs, 13, 0, 0o600));
+        NfsServer::new(&manifest, cas)
+    }
+
+    /// LOOKUP `name` in `dir`, returning the child's id
+    fn lookup(client: &mut Client, dir: u64, name: &str) -> u64 {
+        let mut args = fh(dir);
+        args.string(name);
+        let body = client.call(NFS_PROGRAM, 3, &args);
+        let mut r = XdrReader::new(&body);
+        assert_eq!(r.u32(), Some(0), "lookup {}", name);
+        u64::from_be_bytes(r.opaque(64).unwrap().try_into().unwrap())
+    }
+
+    #[test]
+    fn test_mount_lookup_read_and_readonly() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let server = server(&temp);
+        let mut client = Client {
+            server: &server,
+            xid: 0,
+        };
+
+        // MNT "/" returns the root handle
+        let mut args = XdrWriter::new();
+        args.string("/");
+        let body = client.call(MOUNT_PROGRAM, 1, &args);
+        let mut r = XdrReader::new(&body);
+        assert_eq!(r.u32(), Some(0));
+        let root = u64::from_be_bytes(r.opaque(64).unwrap().try_into().unwrap());
+        assert_eq!(root, tree::ROOT_ID);
+
+        let src = lookup(&mut client, root, "src");
+        let main_rs = lookup(&mut client, src, "main.rs");
+        assert_eq!(lookup(&mut client, src, ".."), root);
+
+        // READ 4 bytes at offset 3
+        let mut args = fh(main_rs);
+        args.u64(3).u32(4);
+        let body = client.call(NFS_PROGRAM, 6, &args);
+        let mut r = XdrReader::new(&body);
+        assert_eq!(r.u32(), Some(0));
+        assert_eq!(r.bool(), Some(true));
+        r.fixed(84).unwrap();
+        assert_eq!(r.u32(), Some(4));
+        assert_eq!(r.bool(), Some(false), "not at eof");
+        assert_eq!(r.opaque(4), Some(&b"main"[..]));
+
+        // Implied directories resolve
+        let deep = lookup(&mut client, root, "deep");
+        let a = lookup(&mut client, deep, "a");
+        lookup(&mut client, a, "b.txt");
+
+        // READLINK returns the stored target
+        let entry = lookup(&mut client, root, "entry");
+        let body = client.call(NFS_PROGRAM, 5, &fh(entry));
+        let mut r = XdrReader::new(&body);
+        assert_eq!(r.u32(), Some(0));
+        r.bool().unwrap();
+        r.fixed(84).unwrap();
+        assert_eq!(r.string(1024), Some("src/main.rs"));
+
+        // Missing names and bad handles
+        let mut args = fh(root);
+        args.string("nope");
+        let body = client.call(NFS_PROGRAM, 3, &args);
+        assert_eq!(XdrReader::new(&body).u32(), Some(2));
+        let body = client.call(NFS_PROGRAM, 1, &fh(9999));
+        assert_eq!(XdrReader::new(&body).u32(), Some(70));
+
+        // Writes are refused
+        let mut args = fh(main_rs);
+        args.u64(0).u32(1).u32(0).opaque(b"x");
+        let body = client.call(NFS_PROGRAM, 7, &args);
+        assert_eq!(XdrReader::new(&body).u32(), Some(30));
+
+        // Other programs and versions
+        assert_eq!(
+            client.call_raw(100000, 2, 0, &XdrWriter::new()).0,
+            PROG_UNAVAIL
+        );
+        assert_eq!(
+            client.call_raw(NFS_PROGRAM, 4, 0, &XdrWriter::new()).0,
+            PROG_MISMATCH
+        );
+    }
+
+    #[test]
+    fn test_readdir_pages_with_cookies() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let server = server(&temp);
+        let mut client = Client {
+            server: &server,
+            xid: 0,
+        };
+
+        // A small reply buffer forces several pages
+        let mut names = Vec::new();
+        let mut cookie = 0;
+        loop {
+            let mut args = fh(tree::ROOT_ID);
+            args.u64(cookie).fixed(&[0; 8]).u32(180);
+            let body = client.call(NFS_PROGRAM, 16, &args);
+            let mut r = XdrReader::new(&body);
+            assert_eq!(r.u32(), Some(0));
+            if r.bool().unwrap() {
+                r.fixed(84).unwrap();
+            }
+            r.fixed(8).unwrap();
+            while r.bool().unwrap() {
+                r.u64().unwrap();
+                names.push(r.string(255).unwrap().to_string());
+                cookie = r.u64().unwrap();
+            }
+            if r.bool().unwrap() {
+                break;
+            }
+        }
+        assert_eq!(names, [".", "..", "deep", "entry", "src"]);
+    }
+
+    #[test]
+    fn test_record_marking_reassembles_fragments() {
+        let mut wire = Vec::new();
+        wire.extend_from_slice(&3u32.to_be_bytes());
+        wire.extend_from_slice(b"abc");
+        wire.extend_from_slice(&(2u32 | 0x8000_0000).to_be_bytes());
+        wire.extend_from_slice(b"de");
+        let mut cursor = io::Cursor::new(wire);
+        assert_eq!(read_record(&mut cursor).unwrap().unwrap(), b"abcde");
+        assert!(read_record(&mut cursor).unwrap().is_none());
+    }
+}
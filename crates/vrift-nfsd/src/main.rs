@@ -0,0 +1,100 @@
+//! vrift-nfsd: serve a manifest over NFSv3 on localhost.
+//!
+//! ```text
+//! vrift-nfsd --manifest vrift.manifest --listen 127.0.0.1:12049
+//! # macOS
+//! mount -t nfs -o vers=3,tcp,port=12049,mountport=12049,nolocks,locallocks,rdonly localhost:/ /mnt/velo
+//! # Linux
+//! mount -t nfs -o vers=3,tcp,port=12049,mountport=12049,mountproto=tcp,nolock,ro localhost:/ /mnt/velo
+//! ```
+
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use tracing_subscriber::EnvFilter;
+use vrift_cas::CasStore;
+use vrift_manifest::{LmdbManifest, Manifest};
+use vrift_nfsd::NfsServer;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "vrift-nfsd",
+    version,
+    about = "Serve a Velo manifest over NFSv3"
+)]
+struct Args {
+    /// Manifest file, LMDB manifest directory or `vrift pack` bundle to export
+    #[arg(short, long, default_value = "vrift.manifest")]
+    manifest: PathBuf,
+
+    /// CAS root holding the manifest's blobs (ignored for bundles)
+    #[arg(long, env = "VR_THE_SOURCE")]
+    cas: Option<PathBuf>,
+
+    /// Address for both NFS and MOUNT. Keep it on loopback: the server
+    /// does no authentication.
+    #[arg(short, long, default_value = "127.0.0.1:12049")]
+    listen: SocketAddr,
+}
+
+fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_env("VRIFT_LOG")
+                .or_else(|_| EnvFilter::try_from_default_env())
+                .unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+    let args = Args::parse();
+
+    if !args.manifest.exists() {
+        anyhow::bail!("Manifest not found: {}", args.manifest.display());
+    }
+    let server = if vrift_pack::is_bundle(&args.manifest) {
+        let bundle = vrift_pack::BundleReader::open(&args.manifest)?;
+        let manifest = bundle.manifest().clone();
+        NfsServer::new(&manifest, bundle)
+    } else {
+        let cas_root = match &args.cas {
+            Some(p) => vrift_manifest::normalize_path(&p.to_string_lossy()),
+            None => vrift_manifest::normalize_path(vrift_config::DEFAULT_CAS_ROOT),
+        };
+        if !cas_root.exists() {
+            anyhow::bail!("CAS root not found: {}", cas_root.display());
+        }
+        let manifest = load_manifest(&args.manifest)?;
+        NfsServer::new(&manifest, CasStore::new(&cas_root)?)
+    };
+
+    let listener = TcpListener::bind(args.listen)
+        .with_context(|| format!("Failed to listen on {}", args.listen))?;
+    if !args.listen.ip().is_loopback() {
+        tracing::warn!(
+            "Listening on {}: any host that can reach it can read the export",
+            args.listen
+        );
+    }
+    tracing::info!("Exporting {} on {}", args.manifest.display(), args.listen);
+    server.serve(listener)
+}
+
+/// Load a flat manifest file, or snapshot an LMDB manifest directory
+fn load_manifest(path: &Path) -> Result<Manifest> {
+    if !path.is_dir() {
+        return Manifest::load(path)
+            .with_context(|| format!("Failed to load manifest: {}", path.display()));
+    }
+    let lmdb = LmdbManifest::open(path)
+        .with_context(|| format!("Failed to open manifest: {}", path.display()))?;
+    let mut manifest = Manifest::new();
+    for (path, entry) in lmdb.iter()? {
+        if entry.vnode.is_whiteout() {
+            continue;
+        }
+        manifest.set_xattrs(&path, &entry.xattrs());
+        manifest.insert(&path, entry.vnode);
+    }
+    Ok(manifest)
+}
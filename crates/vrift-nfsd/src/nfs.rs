@@ -0,0 +1,457 @@
+//! MOUNT v3 and NFS v3 procedures (RFC 1813).
+//!
+//! Each procedure decodes its arguments from the call and encodes its
+//! result into `out`. Handlers return `Some(true)` when they produced a
+//! result, `Some(false)` for an unknown procedure and `None` when the
+//! arguments did not decode.
+
+use crate::tree::{Kind, Node, ROOT_ID};
+use crate::xdr::{XdrReader, XdrWriter};
+use crate::NfsServer;
+
+// nfsstat3
+const NFS3_OK: u32 = 0;
+const NFS3ERR_NOENT: u32 = 2;
+const NFS3ERR_IO: u32 = 5;
+const NFS3ERR_NOTDIR: u32 = 20;
+const NFS3ERR_ISDIR: u32 = 21;
+const NFS3ERR_INVAL: u32 = 22;
+const NFS3ERR_ROFS: u32 = 30;
+const NFS3ERR_STALE: u32 = 70;
+const NFS3ERR_BADHANDLE: u32 = 10001;
+const NFS3ERR_BAD_COOKIE: u32 = 10003;
+const NFS3ERR_TOOSMALL: u32 = 10005;
+
+// ftype3
+const NF3REG: u32 = 1;
+const NF3DIR: u32 = 2;
+const NF3LNK: u32 = 5;
+
+// ACCESS bits
+const ACCESS3_READ: u32 = 0x01;
+const ACCESS3_LOOKUP: u32 = 0x02;
+const ACCESS3_EXECUTE: u32 = 0x20;
+
+/// Largest READ served; advertised as rtmax
+const MAX_READ: u32 = 1 << 20;
+/// Advertised wtmax. Writes are refused, but clients want a sane value.
+const MAX_WRITE: u32 = 64 << 10;
+/// Fixed fsid for the export
+const FSID: u64 = 0x7672_6966_7400_0001;
+/// Encoded size of a fattr3
+const FATTR3_LEN: usize = 84;
+/// Encoded size of a file handle (length prefix + 8-byte id)
+const FH_LEN: usize = 12;
+/// Cookies 1 and 2 are `.` and `..`; children start after them
+const FIRST_CHILD_COOKIE: u64 = 3;
+
+type Result<T> = std::result::Result<T, u32>;
+
+impl NfsServer {
+    pub(crate) fn mount3(
+        &self,
+        procedure: u32,
+        r: &mut XdrReader,
+        out: &mut XdrWriter,
+    ) -> Option<bool> {
+        match procedure {
+            // NULL, UMNT, UMNTALL: void results, nothing to track
+            0 | 4 => {}
+            3 => {
+                r.string(1024)?;
+            }
+            // MNT: every path under the export resolves to the root
+            1 => {
+                let path = r.string(1024)?;
+                if path.is_empty() || path == "/" {
+                    out.u32(0).opaque(&ROOT_ID.to_be_bytes());
+                    // AUTH_UNIX only
+                    out.u32(1).u32(1);
+                } else {
+                    // MNT3ERR_NOENT
+                    out.u32(2);
+                }
+            }
+            // DUMP: no mount list kept
+            2 => {
+                out.bool(false);
+            }
+            // EXPORT: "/" to everyone
+            5 => {
+                out.bool(true).string("/").bool(false).bool(false);
+            }
+            _ => return Some(false),
+        }
+        Some(true)
+    }
+
+    pub(crate) fn nfs3(
+        &self,
+        procedure: u32,
+        r: &mut XdrReader,
+        out: &mut XdrWriter,
+    ) -> Option<bool> {
+        match procedure {
+            0 => {}
+            1 => self.getattr(r, out)?,
+            3 => self.lookup(r, out)?,
+            4 => self.access(r, out)?,
+            5 => self.readlink(r, out)?,
+            6 => self.read(r, out)?,
+            16 => self.readdir(r, out, false)?,
+            17 => self.readdir(r, out, true)?,
+            18 => self.fsstat(r, out)?,
+            19 => self.fsinfo(r, out)?,
+            20 => self.pathconf(r, out)?,
+            // SETATTR, WRITE, CREATE, MKDIR, SYMLINK, MKNOD, REMOVE, RMDIR,
+            // COMMIT: status and one empty wcc_data
+            2 | 7 | 8 | 9 | 10 | 11 | 12 | 13 | 21 => {
+                out.u32(NFS3ERR_ROFS).u32(0).u32(0);
+            }
+            // RENAME: two wcc_data
+            14 => {
+                out.u32(NFS3ERR_ROFS).u32(0).u32(0).u32(0).u32(0);
+            }
+            // LINK: post_op_attr and wcc_data
+            15 => {
+                out.u32(NFS3ERR_ROFS).u32(0).u32(0).u32(0);
+            }
+            _ => return Some(false),
+        }
+        Some(true)
+    }
+
+    /// Decode a file handle. The outer `None` is undecodable arguments; the
+    /// inner error is the status to reply with.
+    fn handle(&self, r: &mut XdrReader) -> Option<Result<(u64, &Node)>> {
+        let fh = r.opaque(64)?;
+        let Ok(bytes) = <[u8; 8]>::try_from(fh) else {
+            return Some(Err(NFS3ERR_BADHANDLE));
+        };
+        let id = u64::from_be_bytes(bytes);
+        Some(
+            self.tree
+                .get(id)
+                .map(|node| (id, node))
+                .ok_or(NFS3ERR_STALE),
+        )
+    }
+
+    fn getattr(&self, r: &mut XdrReader, out: &mut XdrWriter) -> Option<()> {
+        match self.handle(r)? {
+            Ok((id, node)) => {
+                out.u32(NFS3_OK);
+                fattr3(out, id, node);
+            }
+            Err(status) => {
+                out.u32(status);
+            }
+        }
+        Some(())
+    }
+
+    fn lookup(&self, r: &mut XdrReader, out: &mut XdrWriter) -> Option<()> {
+        let dir = self.handle(r)?;
+        let name = r.string(255)?;
+        let (dir_id, dir) = match dir {
+            Ok(d) => d,
+            Err(status) => {
+                out.u32(status).bool(false);
+                return Some(());
+            }
+        };
+        if dir.kind != Kind::Dir {
+            out.u32(NFS3ERR_NOTDIR);
+            post_op_attr(out, dir_id, dir);
+            return Some(());
+        }
+        match self.tree.lookup(dir_id, name) {
+            Some(id) => {
+                let node = self.tree.get(id)?;
+                out.u32(NFS3_OK).opaque(&id.to_be_bytes());
+                post_op_attr(out, id, node);
+                post_op_attr(out, dir_id, dir);
+            }
+            None => {
+                out.u32(NFS3ERR_NOENT);
+                post_op_attr(out, dir_id, dir);
+            }
+        }
+        Some(())
+    }
+
+    /// Access follows the mode bits alone; the export is read-only, so
+    /// MODIFY, EXTEND and DELETE are never granted
+    fn access(&self, r: &mut XdrReader, out: &mut XdrWriter) -> Option<()> {
+        let obj = self.handle(r)?;
+        let wanted = r.u32()?;
+        let (id, node) = match obj {
+            Ok(o) => o,
+            Err(status) => {
+                out.u32(status).bool(false);
+                return Some(());
+            }
+        };
+        let mode = mode(node);
+        let mut granted = 0;
+        if mode & 0o444 != 0 {
+            granted |= ACCESS3_READ;
+        }
+        if mode & 0o111 != 0 {
+            granted |= match node.kind {
+                Kind::Dir => ACCESS3_LOOKUP,
+                _ => ACCESS3_EXECUTE,
+            };
+        }
+        out.u32(NFS3_OK);
+        post_op_attr(out, id, node);
+        out.u32(granted & wanted);
+        Some(())
+    }
+
+    fn readlink(&self, r: &mut XdrReader, out: &mut XdrWriter) -> Option<()> {
+        let (id, node) = match self.handle(r)? {
+            Ok(o) => o,
+            Err(status) => {
+                out.u32(status).bool(false);
+                return Some(());
+            }
+        };
+        let vnode = match (&node.vnode, node.kind) {
+            (Some(v), Kind::Symlink) => v,
+            _ => {
+                out.u32(NFS3ERR_INVAL);
+                post_op_attr(out, id, node);
+                return Some(());
+            }
+        };
+        match self.read_blob(&vnode.content_hash, 0, vnode.size as usize) {
+            Ok(target) => {
+                out.u32(NFS3_OK);
+                post_op_attr(out, id, node);
+                out.opaque(&target);
+            }
+            Err(e) => {
+                tracing::error!("readlink of {} failed: {}", node.name, e);
+                out.u32(NFS3ERR_IO);
+                post_op_attr(out, id, node);
+            }
+        }
+        Some(())
+    }
+
+    fn read(&self, r: &mut XdrReader, out: &mut XdrWriter) -> Option<()> {
+        let obj = self.handle(r)?;
+        let offset = r.u64()?;
+        let count = r.u32()?.min(MAX_READ);
+        let (id, node) = match obj {
+            Ok(o) => o,
+            Err(status) => {
+                out.u32(status).bool(false);
+                return Some(());
+            }
+        };
+        let vnode = match (&node.vnode, node.kind) {
+            (Some(v), Kind::File) => v,
+            (_, Kind::Dir) => {
+                out.u32(NFS3ERR_ISDIR);
+                post_op_attr(out, id, node);
+                return Some(());
+            }
+            _ => {
+                out.u32(NFS3ERR_INVAL);
+                post_op_attr(out, id, node);
+                return Some(());
+            }
+        };
+
+        let len = vnode.size.saturating_sub(offset).min(count as u64) as usize;
+        let data = if len == 0 {
+            Ok(Vec::new())
+        } else {
+            self.verify_once(&vnode.content_hash)
+                .and_then(|_| self.read_blob(&vnode.content_hash, offset, len))
+        };
+        match data {
+            Ok(data) => {
+                out.u32(NFS3_OK);
+                post_op_attr(out, id, node);
+                out.u32(data.len() as u32)
+                    .bool(offset + data.len() as u64 >= vnode.size)
+                    .opaque(&data);
+            }
+            Err(e) => {
+                tracing::error!("read of {} failed: {}", node.name, e);
+                out.u32(NFS3ERR_IO);
+                post_op_attr(out, id, node);
+            }
+        }
+        Some(())
+    }
+
+    /// READDIR and READDIRPLUS. The tree never changes, so the cookie
+    /// verifier is always zero and a cookie is just a position.
+    fn readdir(&self, r: &mut XdrReader, out: &mut XdrWriter, plus: bool) -> Option<()> {
+        let dir = self.handle(r)?;
+        let cookie = r.u64()?;
+        r.fixed(8)?;
+        let max = if plus {
+            r.u32()?; // dircount: the maxcount limit is the one that matters
+            r.u32()?
+        } else {
+            r.u32()?
+        } as usize;
+        let (dir_id, dir) = match dir {
+            Ok(d) => d,
+            Err(status) => {
+                out.u32(status).bool(false);
+                return Some(());
+            }
+        };
+        if dir.kind != Kind::Dir {
+            out.u32(NFS3ERR_NOTDIR);
+            post_op_attr(out, dir_id, dir);
+            return Some(());
+        }
+        let total = FIRST_CHILD_COOKIE - 1 + dir.children.len() as u64;
+        if cookie > total {
+            out.u32(NFS3ERR_BAD_COOKIE);
+            post_op_attr(out, dir_id, dir);
+            return Some(());
+        }
+
+        let mut entries = XdrWriter::new();
+        // status, dir attributes, verifier, list terminator and eof
+        let budget = max.saturating_sub(4 + 4 + FATTR3_LEN + 8 + 4 + 4);
+        let mut next = cookie + 1;
+        while next <= total {
+            let (name, id) = match next {
+                1 => (".", dir_id),
+                2 => ("..", dir.parent),
+                n => {
+                    let id = dir.children[(n - FIRST_CHILD_COOKIE) as usize];
+                    (self.tree.get(id)?.name.as_str(), id)
+                }
+            };
+            let mut entry_len = 4 + 8 + 4 + name.len().div_ceil(4) * 4 + 8;
+            if plus {
+                entry_len += 4 + FATTR3_LEN + 4 + FH_LEN;
+            }
+            if entries.len() + entry_len > budget {
+                break;
+            }
+            entries.bool(true).u64(id).string(name).u64(next);
+            if plus {
+                post_op_attr(&mut entries, id, self.tree.get(id)?);
+                entries.bool(true).opaque(&id.to_be_bytes());
+            }
+            next += 1;
+        }
+        if entries.is_empty() && next <= total {
+            out.u32(NFS3ERR_TOOSMALL);
+            post_op_attr(out, dir_id, dir);
+            return Some(());
+        }
+
+        out.u32(NFS3_OK);
+        post_op_attr(out, dir_id, dir);
+        out.fixed(&[0; 8])
+            .fixed(entries.as_bytes())
+            .bool(false)
+            .bool(next > total);
+        Some(())
+    }
+
+    fn fsstat(&self, r: &mut XdrReader, out: &mut XdrWriter) -> Option<()> {
+        let (id, node) = match self.handle(r)? {
+            Ok(o) => o,
+            Err(status) => {
+                out.u32(status).bool(false);
+                return Some(());
+            }
+        };
+        let bytes: u64 = (1..=self.tree.len() as u64)
+            .filter_map(|id| self.tree.get(id)?.vnode.as_ref())
+            .map(|v| v.size)
+            .sum();
+        out.u32(NFS3_OK);
+        post_op_attr(out, id, node);
+        // Total, free and available bytes, then files; nothing is free
+        out.u64(bytes).u64(0).u64(0);
+        out.u64(self.tree.len() as u64).u64(0).u64(0);
+        // invarsec: the export never changes
+        out.u32(u32::MAX);
+        Some(())
+    }
+
+    fn fsinfo(&self, r: &mut XdrReader, out: &mut XdrWriter) -> Option<()> {
+        let (id, node) = match self.handle(r)? {
+            Ok(o) => o,
+            Err(status) => {
+                out.u32(status).bool(false);
+                return Some(());
+            }
+        };
+        out.u32(NFS3_OK);
+        post_op_attr(out, id, node);
+        // rtmax, rtpref, rtmult, wtmax, wtpref, wtmult, dtpref
+        out.u32(MAX_READ).u32(MAX_READ).u32(4096);
+        out.u32(MAX_WRITE).u32(MAX_WRITE).u32(4096);
+        out.u32(64 << 10);
+        // maxfilesize, time_delta (1s: manifests store whole seconds)
+        out.u64(u64::MAX).u32(1).u32(0);
+        // FSF3_SYMLINK | FSF3_HOMOGENEOUS
+        out.u32(0x0002 | 0x0008);
+        Some(())
+    }
+
+    fn pathconf(&self, r: &mut XdrReader, out: &mut XdrWriter) -> Option<()> {
+        let (id, node) = match self.handle(r)? {
+            Ok(o) => o,
+            Err(status) => {
+                out.u32(status).bool(false);
+                return Some(());
+            }
+        };
+        out.u32(NFS3_OK);
+        post_op_attr(out, id, node);
+        // linkmax, name_max, no_trunc, chown_restricted, case_insensitive,
+        // case_preserving
+        out.u32(1).u32(255);
+        out.bool(true).bool(true).bool(false).bool(true);
+        Some(())
+    }
+}
+
+fn mode(node: &Node) -> u32 {
+    match &node.vnode {
+        Some(v) => v.mode & 0o7777,
+        // Directories the manifest only implies
+        None => 0o755,
+    }
+}
+
+fn post_op_attr(out: &mut XdrWriter, id: u64, node: &Node) {
+    out.bool(true);
+    fattr3(out, id, node);
+}
+
+fn fattr3(out: &mut XdrWriter, id: u64, node: &Node) {
+    let (ftype, nlink) = match node.kind {
+        Kind::File => (NF3REG, 1),
+        Kind::Dir => (NF3DIR, 2),
+        Kind::Symlink => (NF3LNK, 1),
+    };
+    let size = node.vnode.as_ref().map_or(0, |v| v.size);
+    let mtime = node.vnode.as_ref().map_or(0, |v| v.mtime) as u32;
+    out.u32(ftype).u32(mode(node)).u32(nlink);
+    // uid, gid: root, as in vrift-fuse
+    out.u32(0).u32(0);
+    // size, used, rdev
+    out.u64(size).u64(size.div_ceil(4096) * 4096).u64(0);
+    out.u64(FSID).u64(id);
+    // atime, mtime, ctime
+    for _ in 0..3 {
+        out.u32(mtime).u32(0);
+    }
+}
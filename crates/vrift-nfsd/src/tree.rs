@@ -0,0 +1,136 @@
+//! The exported namespace: a manifest laid out as a tree of numbered nodes.
+//!
+//! Node ids double as NFS file ids and file handles. They are assigned in
+//! sorted path order, so the same manifest always gets the same ids and a
+//! client's handles stay valid across server restarts.
+
+use std::collections::HashMap;
+
+use vrift_manifest::{Manifest, VnodeEntry};
+
+/// Id of the export root
+pub const ROOT_ID: u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+    Symlink,
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub parent: u64,
+    pub kind: Kind,
+    /// `None` for directories the manifest only implies
+    pub vnode: Option<VnodeEntry>,
+    /// Child ids, sorted by name (READDIR cookies index into this)
+    pub children: Vec<u64>,
+}
+
+pub struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    pub fn from_manifest(manifest: &Manifest) -> Self {
+        let mut tree = Self {
+            nodes: vec![Node {
+                name: String::new(),
+                parent: ROOT_ID,
+                kind: Kind::Dir,
+                vnode: None,
+                children: Vec::new(),
+            }],
+        };
+        let mut index: HashMap<(u64, String), u64> = HashMap::new();
+
+        let mut paths: Vec<&str> = manifest.paths().collect();
+        paths.sort();
+        for path in paths {
+            let vnode = manifest.get(path).cloned();
+            let components: Vec<&str> = path
+                .split('/')
+                .filter(|c| !c.is_empty() && *c != ".")
+                .collect();
+            let Some((last, dirs)) = components.split_last() else {
+                // The root itself
+                tree.nodes[0].vnode = vnode;
+                continue;
+            };
+            let mut parent = ROOT_ID;
+            for dir in dirs {
+                parent = tree.child_or_insert(&mut index, parent, dir, Kind::Dir);
+            }
+            let kind = match &vnode {
+                Some(v) if v.is_dir() => Kind::Dir,
+                Some(v) if v.is_symlink() => Kind::Symlink,
+                _ => Kind::File,
+            };
+            let id = tree.child_or_insert(&mut index, parent, last, kind);
+            let node = &mut tree.nodes[(id - 1) as usize];
+            node.kind = kind;
+            node.vnode = vnode;
+        }
+
+        for i in 0..tree.nodes.len() {
+            let mut children = std::mem::take(&mut tree.nodes[i].children);
+            children.sort_by(|a, b| {
+                tree.nodes[(*a - 1) as usize]
+                    .name
+                    .cmp(&tree.nodes[(*b - 1) as usize].name)
+            });
+            tree.nodes[i].children = children;
+        }
+        tree
+    }
+
+    fn child_or_insert(
+        &mut self,
+        index: &mut HashMap<(u64, String), u64>,
+        parent: u64,
+        name: &str,
+        kind: Kind,
+    ) -> u64 {
+        if let Some(id) = index.get(&(parent, name.to_string())) {
+            return *id;
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent,
+            kind,
+            vnode: None,
+            children: Vec::new(),
+        });
+        let id = self.nodes.len() as u64;
+        self.nodes[(parent - 1) as usize].children.push(id);
+        index.insert((parent, name.to_string()), id);
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Node> {
+        self.nodes.get(id.checked_sub(1)? as usize)
+    }
+
+    pub fn lookup(&self, dir: u64, name: &str) -> Option<u64> {
+        let node = self.get(dir)?;
+        match name {
+            "." => Some(dir),
+            ".." => Some(node.parent),
+            _ => node
+                .children
+                .binary_search_by(|c| self.nodes[(*c - 1) as usize].name.as_str().cmp(name))
+                .ok()
+                .map(|i| node.children[i]),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
@@ -0,0 +1,142 @@
+//! XDR (RFC 4506) encoding for the handful of types ONC RPC and NFSv3 use:
+//! big-endian 32/64-bit integers and 4-byte-padded opaques and strings.
+
+/// Cursor over an XDR-encoded message. Reads past the end yield `None`,
+/// which the server answers with GARBAGE_ARGS.
+pub struct XdrReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> XdrReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < n {
+            return None;
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Some(head)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub fn bool(&mut self) -> Option<bool> {
+        Some(self.u32()? != 0)
+    }
+
+    /// Fixed-length opaque
+    pub fn fixed(&mut self, n: usize) -> Option<&'a [u8]> {
+        let data = self.take(n)?;
+        self.take(pad(n))?;
+        Some(data)
+    }
+
+    /// Variable-length opaque, at most `max` bytes
+    pub fn opaque(&mut self, max: usize) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        if len > max {
+            return None;
+        }
+        self.fixed(len)
+    }
+
+    pub fn string(&mut self, max: usize) -> Option<&'a str> {
+        std::str::from_utf8(self.opaque(max)?).ok()
+    }
+}
+
+/// Growable XDR output buffer
+#[derive(Default)]
+pub struct XdrWriter {
+    buf: Vec<u8>,
+}
+
+impl XdrWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn u32(&mut self, v: u32) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, v: u64) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    pub fn bool(&mut self, v: bool) -> &mut Self {
+        self.u32(v as u32)
+    }
+
+    /// Fixed-length opaque (no length prefix)
+    pub fn fixed(&mut self, data: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(data);
+        self.buf.extend(std::iter::repeat_n(0, pad(data.len())));
+        self
+    }
+
+    pub fn opaque(&mut self, data: &[u8]) -> &mut Self {
+        self.u32(data.len() as u32).fixed(data)
+    }
+
+    pub fn string(&mut self, s: &str) -> &mut Self {
+        self.opaque(s.as_bytes())
+    }
+}
+
+fn pad(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xdr_roundtrip_and_padding() {
+        let mut w = XdrWriter::new();
+        w.u32(7).u64(1 << 40).bool(true).string("abcde").opaque(&[]);
+        let bytes = w.into_bytes();
+        // 4 + 8 + 4 + (4 + 5 + 3 pad) + 4
+        assert_eq!(bytes.len(), 32);
+
+        let mut r = XdrReader::new(&bytes);
+        assert_eq!(r.u32(), Some(7));
+        assert_eq!(r.u64(), Some(1 << 40));
+        assert_eq!(r.bool(), Some(true));
+        assert_eq!(r.string(255), Some("abcde"));
+        assert_eq!(r.opaque(0), Some(&[][..]));
+        assert_eq!(r.u32(), None);
+
+        // Length over the limit is rejected, not truncated
+        let mut r = XdrReader::new(&bytes[16..]);
+        assert_eq!(r.string(4), None);
+    }
+}
@@ -0,0 +1,567 @@
+//! Single-file bundles: a manifest plus every blob it references.
+//!
+//! Bundles are for air-gapped distribution. They are read-only and can be
+//! served directly (mmap) without unpacking into a CAS directory.
+//!
+//! ## Bundle Format
+//!
+//! ```text
+//! 0   magic          b"VRIFTBDL"
+//! 8   format version u32 LE
+//! 12  flags          u32 LE (bit 0: LZ4 compression requested)
+//! 16  blob count     u64 LE
+//! 24  manifest off   u64 LE
+//! 32  manifest len   u64 LE
+//! 40  index off      u64 LE
+//! 48  index len      u64 LE
+//! 56  data off       u64 LE
+//! 64  manifest       vrift-manifest on-disk format (own header + checksum)
+//! ..  data           blobs, each raw or LZ4 block-compressed
+//! ..  index          rkyv-archived Vec<BundleIndexEntry>, 16-byte aligned
+//! ```
+//!
+//! With compression on, a blob is stored compressed only if that makes it
+//! smaller; the index records which form each blob is in.
+//!
+//! [`BundleReader::open`] checks every index entry against the data section
+//! up front, so lookups index the mmap without further bounds checks. The
+//! reader keeps the last blob it decompressed for ranged reads: a mount
+//! reading a compressed file front to back decodes it once, not once per
+//! read.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use memmap2::Mmap;
+use rkyv::Archive;
+use serde::{Deserialize, Serialize};
+
+use vrift_cas::{Blake3Hash, CasStore};
+use vrift_manifest::Manifest;
+
+use crate::{PackError, Result};
+
+/// Magic bytes for bundle identification
+pub const BUNDLE_MAGIC: &[u8; 8] = b"VRIFTBDL";
+/// Current bundle format version
+const BUNDLE_VERSION: u32 = 1;
+/// Fixed header length
+const BUNDLE_HEADER_LEN: usize = 64;
+/// Header flag: blobs may be LZ4-compressed
+const FLAG_COMPRESSED: u32 = 1;
+/// Most an LZ4 block can expand: each byte of a long match's length runs
+/// to 255 output bytes
+const LZ4_MAX_RATIO: u64 = 255;
+
+/// Index entry for a blob in a bundle
+#[derive(Debug, Clone, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+#[rkyv(derive(Debug))]
+pub struct BundleIndexEntry {
+    /// BLAKE3 hash of the uncompressed blob
+    pub hash: Blake3Hash,
+    /// Offset within the data section
+    pub offset: u64,
+    /// Bytes stored in the bundle
+    pub stored_len: u64,
+    /// Uncompressed blob length
+    pub raw_len: u64,
+    /// Whether the stored bytes are an LZ4 block
+    pub compressed: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BundleHeader {
+    flags: u32,
+    blob_count: u64,
+    manifest_offset: u64,
+    manifest_len: u64,
+    index_offset: u64,
+    index_len: u64,
+    data_offset: u64,
+}
+
+impl BundleHeader {
+    fn encode(&self) -> [u8; BUNDLE_HEADER_LEN] {
+        let mut buf = [0u8; BUNDLE_HEADER_LEN];
+        buf[0..8].copy_from_slice(BUNDLE_MAGIC);
+        buf[8..12].copy_from_slice(&BUNDLE_VERSION.to_le_bytes());
+        buf[12..16].copy_from_slice(&self.flags.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.blob_count.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.manifest_offset.to_le_bytes());
+        buf[32..40].copy_from_slice(&self.manifest_len.to_le_bytes());
+        buf[40..48].copy_from_slice(&self.index_offset.to_le_bytes());
+        buf[48..56].copy_from_slice(&self.index_len.to_le_bytes());
+        buf[56..64].copy_from_slice(&self.data_offset.to_le_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < BUNDLE_HEADER_LEN {
+            return Err(PackError::Invalid("File too small".to_string()));
+        }
+        if &buf[0..8] != BUNDLE_MAGIC {
+            return Err(PackError::Invalid("Bad magic bytes".to_string()));
+        }
+        let u32_at = |o: usize| u32::from_le_bytes(buf[o..o + 4].try_into().unwrap());
+        let u64_at = |o: usize| u64::from_le_bytes(buf[o..o + 8].try_into().unwrap());
+        let version = u32_at(8);
+        if version != BUNDLE_VERSION {
+            return Err(PackError::Invalid(format!(
+                "Unsupported version: {}",
+                version
+            )));
+        }
+        Ok(Self {
+            flags: u32_at(12),
+            blob_count: u64_at(16),
+            manifest_offset: u64_at(24),
+            manifest_len: u64_at(32),
+            index_offset: u64_at(40),
+            index_len: u64_at(48),
+            data_offset: u64_at(56),
+        })
+    }
+}
+
+/// Check whether `path` starts with the bundle magic
+pub fn is_bundle<P: AsRef<Path>>(path: P) -> bool {
+    let mut magic = [0u8; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok()
+        && &magic == BUNDLE_MAGIC
+}
+
+/// Streaming writer for bundles
+///
+/// Blobs are written as they are added; only the index is kept in memory.
+pub struct BundleWriter {
+    output_path: PathBuf,
+    writer: BufWriter<File>,
+    header: BundleHeader,
+    entries: Vec<BundleIndexEntry>,
+    seen: HashSet<Blake3Hash>,
+    data_len: u64,
+}
+
+impl BundleWriter {
+    /// Start a bundle for `manifest`, optionally compressing blobs
+    pub fn create<P: AsRef<Path>>(
+        output_path: P,
+        manifest: &Manifest,
+        compress: bool,
+    ) -> Result<Self> {
+        let output_path = output_path.as_ref().to_path_buf();
+        let manifest_bytes = manifest.to_bytes()?;
+
+        let mut writer = BufWriter::new(File::create(&output_path)?);
+        // Header is rewritten by finish() once offsets are known
+        writer.write_all(&[0u8; BUNDLE_HEADER_LEN])?;
+        writer.write_all(&manifest_bytes)?;
+
+        let manifest_offset = BUNDLE_HEADER_LEN as u64;
+        let header = BundleHeader {
+            flags: if compress { FLAG_COMPRESSED } else { 0 },
+            manifest_offset,
+            manifest_len: manifest_bytes.len() as u64,
+            data_offset: manifest_offset + manifest_bytes.len() as u64,
+            ..Default::default()
+        };
+
+        Ok(Self {
+            output_path,
+            writer,
+            header,
+            entries: Vec::new(),
+            seen: HashSet::new(),
+            data_len: 0,
+        })
+    }
+
+    /// Add a blob; duplicates are skipped
+    pub fn add(&mut self, hash: Blake3Hash, data: &[u8]) -> Result<()> {
+        if !self.seen.insert(hash) {
+            return Ok(());
+        }
+
+        let packed = if self.header.flags & FLAG_COMPRESSED != 0 {
+            Some(lz4_flex::block::compress(data)).filter(|c| c.len() < data.len())
+        } else {
+            None
+        };
+        let stored: &[u8] = packed.as_deref().unwrap_or(data);
+
+        self.writer.write_all(stored)?;
+        self.entries.push(BundleIndexEntry {
+            hash,
+            offset: self.data_len,
+            stored_len: stored.len() as u64,
+            raw_len: data.len() as u64,
+            compressed: packed.is_some(),
+        });
+        self.data_len += stored.len() as u64;
+        Ok(())
+    }
+
+    /// Number of distinct blobs added so far
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether no blobs have been added
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the index and header, returning the bundle path
+    pub fn finish(mut self) -> Result<PathBuf> {
+        let index_bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&self.entries)
+            .map_err(|e| PackError::Rkyv(e.to_string()))?;
+
+        let data_end = self.header.data_offset + self.data_len;
+        let index_offset = data_end.next_multiple_of(16);
+        self.writer
+            .write_all(&vec![0u8; (index_offset - data_end) as usize])?;
+        self.writer.write_all(&index_bytes)?;
+
+        self.header.blob_count = self.entries.len() as u64;
+        self.header.index_offset = index_offset;
+        self.header.index_len = index_bytes.len() as u64;
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&self.header.encode())?;
+        self.writer.flush()?;
+        Ok(self.output_path)
+    }
+}
+
+/// Read-only view of a bundle, served straight from an mmap
+pub struct BundleReader {
+    path: PathBuf,
+    mmap: Mmap,
+    manifest: Manifest,
+    index: HashMap<Blake3Hash, BundleIndexEntry>,
+    data_offset: u64,
+    compressed: bool,
+    /// Last blob decompressed for [`read_range`](Self::read_range)
+    decoded: Mutex<Option<(Blake3Hash, Arc<[u8]>)>>,
+}
+
+impl BundleReader {
+    /// Open a bundle and load its manifest and index
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let mmap = unsafe { Mmap::map(&file) }.map_err(io::Error::other)?;
+
+        let header = BundleHeader::decode(&mmap)?;
+        let section = |offset: u64, len: u64, what: &str| {
+            let start = offset as usize;
+            let end = start
+                .checked_add(len as usize)
+                .filter(|&e| e <= mmap.len())
+                .ok_or_else(|| PackError::Invalid(format!("{} extends past EOF", what)))?;
+            Ok::<_, PackError>(&mmap[start..end])
+        };
+
+        let manifest = Manifest::from_bytes(section(
+            header.manifest_offset,
+            header.manifest_len,
+            "Manifest",
+        )?)?;
+
+        let index_bytes = section(header.index_offset, header.index_len, "Index")?;
+        let mut aligned = rkyv::util::AlignedVec::<16>::with_capacity(index_bytes.len());
+        aligned.extend_from_slice(index_bytes);
+        let entries: Vec<BundleIndexEntry> =
+            rkyv::from_bytes::<Vec<BundleIndexEntry>, rkyv::rancor::Error>(&aligned)
+                .map_err(|e| PackError::Rkyv(e.to_string()))?;
+        if entries.len() as u64 != header.blob_count {
+            return Err(PackError::Invalid(format!(
+                "Index has {} blobs, header says {}",
+                entries.len(),
+                header.blob_count
+            )));
+        }
+
+        // Blobs sit between the data offset and the index
+        let data_len = header
+            .index_offset
+            .checked_sub(header.data_offset)
+            .ok_or_else(|| PackError::Invalid("Data section overlaps the index".to_string()))?;
+        for entry in &entries {
+            check_entry(entry, data_len)?;
+        }
+
+        let index = entries.into_iter().map(|e| (e.hash, e)).collect();
+
+        Ok(Self {
+            path,
+            mmap,
+            manifest,
+            index,
+            data_offset: header.data_offset,
+            compressed: header.flags & FLAG_COMPRESSED != 0,
+            decoded: Mutex::new(None),
+        })
+    }
+
+    /// The manifest carried by this bundle
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Get a blob by hash; zero-copy unless it is stored compressed
+    pub fn get(&self, hash: &Blake3Hash) -> Result<Cow<'_, [u8]>> {
+        let entry = self.entry(hash)?;
+        if !entry.compressed {
+            return Ok(Cow::Borrowed(self.stored(entry)));
+        }
+        self.decompress(entry).map(Cow::Owned)
+    }
+
+    /// Read `len` bytes at `offset` of a blob (short at end of blob)
+    pub fn read_range(&self, hash: &Blake3Hash, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let entry = self.entry(hash)?;
+        let range = |blob: &[u8]| {
+            let start = usize::try_from(offset)
+                .unwrap_or(usize::MAX)
+                .min(blob.len());
+            let end = start.saturating_add(len).min(blob.len());
+            blob[start..end].to_vec()
+        };
+        if !entry.compressed {
+            return Ok(range(self.stored(entry)));
+        }
+
+        let mut decoded = self.decoded.lock().unwrap();
+        let blob = match &*decoded {
+            Some((cached, blob)) if cached == hash => blob.clone(),
+            _ => {
+                let blob: Arc<[u8]> = self.decompress(entry)?.into();
+                *decoded = Some((*hash, blob.clone()));
+                blob
+            }
+        };
+        drop(decoded);
+        Ok(range(&blob))
+    }
+
+    /// Check a blob's content against its hash
+    pub fn verify(&self, hash: &Blake3Hash) -> Result<()> {
+        if CasStore::compute_hash(&self.get(hash)?) != *hash {
+            return Err(PackError::Invalid(format!(
+                "Blob hash mismatch: {}",
+                CasStore::hash_to_hex(hash)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Uncompressed size of a blob
+    pub fn blob_len(&self, hash: &Blake3Hash) -> Result<u64> {
+        Ok(self.entry(hash)?.raw_len)
+    }
+
+    /// Check if a blob exists in this bundle
+    pub fn contains(&self, hash: &Blake3Hash) -> bool {
+        self.index.contains_key(hash)
+    }
+
+    /// Whether the bundle was written with compression enabled
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Get bundle path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get number of blobs in the bundle
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Check if bundle holds no blobs
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Iterate over all blob hashes in the bundle
+    pub fn hashes(&self) -> impl Iterator<Item = &Blake3Hash> {
+        self.index.keys()
+    }
+
+    fn entry(&self, hash: &Blake3Hash) -> Result<&BundleIndexEntry> {
+        self.index.get(hash).ok_or_else(|| PackError::NotFound {
+            hash: CasStore::hash_to_hex(hash),
+        })
+    }
+
+    /// The bytes stored for `entry`, in range since `open` checked it
+    fn stored(&self, entry: &BundleIndexEntry) -> &[u8] {
+        let start = (self.data_offset + entry.offset) as usize;
+        &self.mmap[start..start + entry.stored_len as usize]
+    }
+
+    fn decompress(&self, entry: &BundleIndexEntry) -> Result<Vec<u8>> {
+        lz4_flex::block::decompress(self.stored(entry), entry.raw_len as usize)
+            .map_err(|e| PackError::Invalid(format!("Bad compressed blob: {}", e)))
+    }
+}
+
+/// Check an index entry against a data section of `data_len` bytes
+fn check_entry(entry: &BundleIndexEntry, data_len: u64) -> Result<()> {
+    let problem = if entry
+        .offset
+        .checked_add(entry.stored_len)
+        .is_none_or(|end| end > data_len)
+    {
+        "extends past the data section"
+    } else if !entry.compressed && entry.raw_len != entry.stored_len {
+        "stored and raw lengths differ"
+    } else if entry.compressed && entry.raw_len > entry.stored_len.saturating_mul(LZ4_MAX_RATIO) {
+        "decompresses past the LZ4 limit"
+    } else {
+        return Ok(());
+    };
+    Err(PackError::Invalid(format!(
+        "Index entry {} {}",
+        CasStore::hash_to_hex(&entry.hash),
+        problem
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use vrift_manifest::VnodeEntry;
+
+    fn write_bundle(path: &Path, compress: bool) -> (Blake3Hash, Blake3Hash, Vec<u8>) {
+        let small = b"hello bundle".to_vec();
+        let big = vec![b'x'; 64 * 1024];
+        let (h1, h2) = (CasStore::compute_hash(&small), CasStore::compute_hash(&big));
+
+        let mut manifest = Manifest::new();
+        manifest.insert(
+            "/a.txt",
+            VnodeEntry::new_file(h1, small.len() as u64, 0, 0o644),
+        );
+        manifest.insert(
+            "/b.bin",
+            VnodeEntry::new_file(h2, big.len() as u64, 0, 0o644),
+        );
+        manifest.insert(
+            "/dup.txt",
+            VnodeEntry::new_file(h1, small.len() as u64, 0, 0o644),
+        );
+
+        let mut writer = BundleWriter::create(path, &manifest, compress).unwrap();
+        writer.add(h1, &small).unwrap();
+        writer.add(h2, &big).unwrap();
+        writer.add(h1, &small).unwrap();
+        assert_eq!(writer.len(), 2);
+        writer.finish().unwrap();
+        (h1, h2, big)
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("test.vrift");
+        let (h1, h2, big) = write_bundle(&path, false);
+
+        assert!(is_bundle(&path));
+        let reader = BundleReader::open(&path).unwrap();
+        assert!(!reader.is_compressed());
+        assert_eq!(reader.len(), 2);
+        assert_eq!(reader.manifest().len(), 3);
+        assert!(matches!(reader.get(&h1).unwrap(), Cow::Borrowed(_)));
+        assert_eq!(&*reader.get(&h1).unwrap(), b"hello bundle");
+        assert_eq!(&*reader.get(&h2).unwrap(), &big[..]);
+        assert_eq!(reader.read_range(&h1, 6, 100).unwrap(), b"bundle");
+        reader.verify(&h2).unwrap();
+    }
+
+    #[test]
+    fn test_bundle_compressed() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("test.vrift");
+        let (h1, h2, big) = write_bundle(&path, true);
+
+        // Highly compressible blob shrinks the file well below its raw size
+        assert!(std::fs::metadata(&path).unwrap().len() < big.len() as u64 / 4);
+
+        let reader = BundleReader::open(&path).unwrap();
+        assert!(reader.is_compressed());
+        assert_eq!(reader.blob_len(&h2).unwrap(), big.len() as u64);
+        assert_eq!(&*reader.get(&h2).unwrap(), &big[..]);
+        assert_eq!(reader.read_range(&h2, 100, 10).unwrap(), vec![b'x'; 10]);
+        reader.verify(&h1).unwrap();
+        reader.verify(&h2).unwrap();
+    }
+
+    #[test]
+    fn test_sequential_reads_decode_once() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("test.vrift");
+        let (_, h2, big) = write_bundle(&path, true);
+        let reader = BundleReader::open(&path).unwrap();
+
+        let mut read = Vec::new();
+        let mut first: Option<Arc<[u8]>> = None;
+        while read.len() < big.len() {
+            read.extend(reader.read_range(&h2, read.len() as u64, 4096).unwrap());
+            let decoded = reader.decoded.lock().unwrap();
+            let (hash, blob) = decoded.as_ref().unwrap();
+            assert_eq!(hash, &h2);
+            // Still the blob the first read decoded
+            let first = first.get_or_insert_with(|| blob.clone());
+            assert!(Arc::ptr_eq(first, blob));
+        }
+        assert_eq!(read, big);
+        assert!(reader.read_range(&h2, u64::MAX, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_open_rejects_bad_index_entries() {
+        let entry = |offset, stored_len, raw_len, compressed| BundleIndexEntry {
+            hash: [1; 32],
+            offset,
+            stored_len,
+            raw_len,
+            compressed,
+        };
+        assert!(check_entry(&entry(0, 10, 10, false), 10).is_ok());
+        assert!(check_entry(&entry(0, 10, 2000, true), 10).is_ok());
+        assert!(check_entry(&entry(5, 10, 10, false), 10).is_err());
+        assert!(check_entry(&entry(u64::MAX, 2, 2, false), 10).is_err());
+        assert!(check_entry(&entry(0, 10, 11, false), 10).is_err());
+        assert!(check_entry(&entry(0, 10, u64::MAX, true), 10).is_err());
+
+        // A header whose data section ends before the blobs do
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("test.vrift");
+        write_bundle(&path, false);
+        let mut data = std::fs::read(&path).unwrap();
+        let index_offset = u64::from_le_bytes(data[40..48].try_into().unwrap());
+        data[56..64].copy_from_slice(&(index_offset - 16).to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+        assert!(BundleReader::open(&path).is_err());
+    }
+
+    #[test]
+    fn test_not_a_bundle() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("plain");
+        std::fs::write(&path, b"VELOPACK and then some").unwrap();
+        assert!(!is_bundle(&path));
+        assert!(BundleReader::open(&path).is_err());
+    }
+}
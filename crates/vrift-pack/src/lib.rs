@@ -21,6 +21,15 @@
 //! | Blob Data      |  Raw concatenated blobs
 //! +----------------+
 //! ```
+//!
+//! Single-file distribution bundles (manifest + blobs) live in [`bundle`];
+//! [`BlobSource`] abstracts over bundles and CAS directories for readers.
+
+pub mod bundle;
+pub mod source;
+
+pub use bundle::{is_bundle, BundleReader, BundleWriter};
+pub use source::BlobSource;
 
 use std::collections::HashMap;
 use std::fs::File;
@@ -51,6 +60,9 @@ pub enum PackError {
     #[error("Invalid packfile: {0}")]
     Invalid(String),
 
+    #[error("Manifest error: {0}")]
+    Manifest(#[from] vrift_manifest::ManifestError),
+
     #[error("Blob not found in pack: {hash}")]
     NotFound { hash: String },
 }
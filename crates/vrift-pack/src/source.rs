@@ -0,0 +1,33 @@
+//! Blob sources shared by the mount and NFS front ends.
+
+use vrift_cas::{Blake3Hash, CasStore};
+
+use crate::BundleReader;
+
+/// Where a mount reads file contents from
+pub trait BlobSource: Send + 'static {
+    /// Check a whole blob against its hash
+    fn verify(&self, hash: &Blake3Hash) -> anyhow::Result<()>;
+    /// Read up to `len` bytes at `offset` of a blob
+    fn read_range(&self, hash: &Blake3Hash, offset: u64, len: usize) -> anyhow::Result<Vec<u8>>;
+}
+
+impl BlobSource for CasStore {
+    fn verify(&self, hash: &Blake3Hash) -> anyhow::Result<()> {
+        Ok(CasStore::verify(self, hash)?)
+    }
+
+    fn read_range(&self, hash: &Blake3Hash, offset: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        Ok(CasStore::read_range(self, hash, offset, len)?)
+    }
+}
+
+impl BlobSource for BundleReader {
+    fn verify(&self, hash: &Blake3Hash) -> anyhow::Result<()> {
+        Ok(BundleReader::verify(self, hash)?)
+    }
+
+    fn read_range(&self, hash: &Blake3Hash, offset: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        Ok(BundleReader::read_range(self, hash, offset, len)?)
+    }
+}
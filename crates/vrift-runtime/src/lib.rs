@@ -55,7 +55,8 @@ impl LinkFarm {
     /// Populate the target directory with hard links based on one or more manifests.
     ///
     /// If multiple manifests are provided, they are applied in order. Files in later
-    /// manifests will overwrite those in earlier ones at the same path.
+    /// manifests will overwrite those in earlier ones at the same path, and
+    /// their tombstones delete it.
     pub fn populate(&self, manifests: &[Manifest], target: &Path) -> Result<()> {
         if !target.exists() {
             fs::create_dir_all(target)?;
@@ -72,6 +73,16 @@ impl LinkFarm {
                 let relative_path = path_str.trim_start_matches('/');
                 let dest_path = target.join(relative_path);
 
+                // A tombstone deletes what a lower layer put here
+                if entry.is_whiteout() {
+                    match fs::symlink_metadata(&dest_path) {
+                        Ok(meta) if meta.is_dir() => fs::remove_dir_all(&dest_path)?,
+                        Ok(_) => fs::remove_file(&dest_path)?,
+                        Err(_) => {}
+                    }
+                    continue;
+                }
+
                 if entry.is_dir() {
                     fs::create_dir_all(&dest_path)?;
                     continue;
@@ -451,4 +462,42 @@ mod tests {
             app_content
         );
     }
+
+    #[test]
+    fn test_link_farm_whiteout() {
+        let temp = TempDir::new().unwrap();
+        let link_farm_root = temp.path().join("lower");
+        let cas = CasStore::new(temp.path().join("cas")).unwrap();
+
+        let content = b"base content";
+        let hash = cas.store(content).unwrap();
+        let mut base = Manifest::new();
+        base.insert(
+            "/etc/keep",
+            VnodeEntry::new_file(hash, content.len() as u64, 0, 0o644),
+        );
+        base.insert(
+            "/etc/drop",
+            VnodeEntry::new_file(hash, content.len() as u64, 0, 0o644),
+        );
+        base.insert("/opt", VnodeEntry::new_directory(0, 0o755));
+        base.insert(
+            "/opt/tool",
+            VnodeEntry::new_file(hash, content.len() as u64, 0, 0o755),
+        );
+
+        let mut upper = Manifest::new();
+        upper.insert("/etc/drop", VnodeEntry::new_whiteout(0));
+        upper.insert("/opt", VnodeEntry::new_whiteout(0));
+        upper.insert("/never/there", VnodeEntry::new_whiteout(0));
+
+        LinkFarm::new(cas)
+            .populate(&[base, upper], &link_farm_root)
+            .unwrap();
+
+        assert!(link_farm_root.join("etc/keep").exists());
+        assert!(!link_farm_root.join("etc/drop").exists());
+        assert!(!link_farm_root.join("opt").exists());
+        assert!(!link_farm_root.join("never").exists());
+    }
 }
@@ -0,0 +1,140 @@
+//! Run a command with the Windows layer injected
+//!
+//! ```text
+//! vrift-win-run --view app.view --root C:\src\app -- cargo build
+//! ```
+//!
+//! The child is created suspended, gets `vrift_shim_win.dll` loaded, then
+//! resumes; its own children inherit the layer through the `CreateProcessW`
+//! hook.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[command(name = "vrift-win-run", version, about)]
+struct Args {
+    /// View file written by `vrift shim view`
+    #[arg(long)]
+    view: PathBuf,
+
+    /// CAS root
+    #[arg(long, env = "VR_THE_SOURCE")]
+    cas: Option<PathBuf>,
+
+    /// Project root the view is mounted at (default: current directory)
+    #[arg(long)]
+    root: Option<PathBuf>,
+
+    /// Layer DLL (default: vrift_shim_win.dll next to this executable)
+    #[arg(long)]
+    dll: Option<PathBuf>,
+
+    /// Log redirects to stderr
+    #[arg(long)]
+    debug: bool,
+
+    /// Command to run
+    #[arg(last = true, required = true)]
+    command: Vec<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+    match run(args) {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("vrift-win-run: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn run(_args: Args) -> Result<i32, String> {
+    Err("only supported on Windows".to_string())
+}
+
+#[cfg(windows)]
+fn run(args: Args) -> Result<i32, String> {
+    use std::ptr;
+
+    use vrift_shim_win::cmdline::command_line;
+    use vrift_shim_win::inject::{inject, wide};
+    use windows_sys::Win32::Foundation::{CloseHandle, FALSE, TRUE};
+    use windows_sys::Win32::System::Threading::{
+        CreateProcessW, GetExitCodeProcess, ResumeThread, TerminateProcess, WaitForSingleObject,
+        CREATE_SUSPENDED, INFINITE, PROCESS_INFORMATION, STARTUPINFOW,
+    };
+
+    let absolute = |path: PathBuf| std::path::absolute(&path).map_err(|e| e.to_string());
+    let view = absolute(args.view)?;
+    vrift_view::View::load(&view).map_err(|e| format!("{}: {}", view.display(), e))?;
+    let root = match args.root {
+        Some(root) => absolute(root)?,
+        None => std::env::current_dir().map_err(|e| e.to_string())?,
+    };
+    let dll = match args.dll {
+        Some(dll) => absolute(dll)?,
+        None => std::env::current_exe()
+            .map_err(|e| e.to_string())?
+            .with_file_name("vrift_shim_win.dll"),
+    };
+    if !dll.is_file() {
+        return Err(format!("layer DLL not found: {}", dll.display()));
+    }
+
+    // The child (and its children) inherit these
+    std::env::set_var("VRIFT_MANIFEST", &view);
+    std::env::set_var("VRIFT_PROJECT_ROOT", &root);
+    if let Some(cas) = args.cas {
+        std::env::set_var("VR_THE_SOURCE", absolute(cas)?);
+    }
+    if args.debug {
+        std::env::set_var("VRIFT_DEBUG", "1");
+    }
+
+    let mut line = wide(&command_line(&args.command));
+    unsafe {
+        let mut startup: STARTUPINFOW = std::mem::zeroed();
+        startup.cb = std::mem::size_of::<STARTUPINFOW>() as u32;
+        let mut info: PROCESS_INFORMATION = std::mem::zeroed();
+        let created = CreateProcessW(
+            ptr::null(),
+            line.as_mut_ptr(),
+            ptr::null(),
+            ptr::null(),
+            TRUE,
+            CREATE_SUSPENDED,
+            ptr::null(),
+            ptr::null(),
+            &startup,
+            &mut info,
+        );
+        if created == FALSE {
+            return Err(format!(
+                "{}: {}",
+                args.command[0],
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        let result = match inject(info.hProcess, &wide(&dll.to_string_lossy())) {
+            Ok(()) => {
+                ResumeThread(info.hThread);
+                WaitForSingleObject(info.hProcess, INFINITE);
+                let mut code = 1u32;
+                GetExitCodeProcess(info.hProcess, &mut code);
+                Ok(code as i32)
+            }
+            Err(e) => {
+                TerminateProcess(info.hProcess, 1);
+                Err(format!("injecting {}: {}", dll.display(), e))
+            }
+        };
+        CloseHandle(info.hThread);
+        CloseHandle(info.hProcess);
+        result
+    }
+}
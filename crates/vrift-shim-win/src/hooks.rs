@@ -0,0 +1,690 @@
+//! Detours on the kernel32/KernelBase file APIs
+//!
+//! Each hook maps its path to a manifest key and, if the view has an
+//! opinion about that key, answers from the view and the CAS; otherwise it
+//! calls the original. A thread-local guard makes calls made while already
+//! inside a hook (our own `std::fs` use, or KernelBase calling its own
+//! exports) go straight to the originals.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::ffi::{c_void, CStr};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use retour::GenericDetour;
+use windows_sys::core::{PCWSTR, PWSTR};
+use windows_sys::Win32::Foundation::{
+    SetLastError, BOOL, ERROR_FILE_NOT_FOUND, ERROR_NO_MORE_FILES, ERROR_PATH_NOT_FOUND, FALSE,
+    FILETIME, GENERIC_ALL, GENERIC_WRITE, HANDLE, HMODULE, INVALID_HANDLE_VALUE, TRUE,
+};
+use windows_sys::Win32::Security::SECURITY_ATTRIBUTES;
+use windows_sys::Win32::Storage::FileSystem::{
+    FindExInfoStandard, FindExSearchNameMatch, GetFileExInfoStandard, DELETE, FILE_APPEND_DATA,
+    FILE_ATTRIBUTE_ARCHIVE, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_READONLY,
+    FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_DELETE_ON_CLOSE, FILE_SHARE_READ, FILE_WRITE_ATTRIBUTES,
+    FILE_WRITE_DATA, FILE_WRITE_EA, INVALID_FILE_ATTRIBUTES, OPEN_ALWAYS, OPEN_EXISTING,
+    TRUNCATE_EXISTING, WIN32_FILE_ATTRIBUTE_DATA, WIN32_FIND_DATAW, WRITE_DAC, WRITE_OWNER,
+};
+use windows_sys::Win32::System::Environment::GetCurrentDirectoryW;
+use windows_sys::Win32::System::LibraryLoader::{
+    GetModuleFileNameW, GetModuleHandleW, GetProcAddress,
+};
+use windows_sys::Win32::System::Threading::{
+    ResumeThread, CREATE_SUSPENDED, PROCESS_INFORMATION, STARTUPINFOW,
+};
+
+use crate::inject::{inject, wide};
+use crate::winpath;
+use vrift_view::{fold_key, Entry, Kind, View};
+
+type CreateFileWFn = unsafe extern "system" fn(
+    PCWSTR,
+    u32,
+    u32,
+    *const SECURITY_ATTRIBUTES,
+    u32,
+    u32,
+    HANDLE,
+) -> HANDLE;
+type GetFileAttributesWFn = unsafe extern "system" fn(PCWSTR) -> u32;
+type GetFileAttributesExWFn = unsafe extern "system" fn(PCWSTR, i32, *mut c_void) -> BOOL;
+type FindFirstFileWFn = unsafe extern "system" fn(PCWSTR, *mut WIN32_FIND_DATAW) -> HANDLE;
+type FindFirstFileExWFn =
+    unsafe extern "system" fn(PCWSTR, i32, *mut c_void, i32, *const c_void, u32) -> HANDLE;
+type FindNextFileWFn = unsafe extern "system" fn(HANDLE, *mut WIN32_FIND_DATAW) -> BOOL;
+type FindCloseFn = unsafe extern "system" fn(HANDLE) -> BOOL;
+type CreateProcessWFn = unsafe extern "system" fn(
+    PCWSTR,
+    PWSTR,
+    *const SECURITY_ATTRIBUTES,
+    *const SECURITY_ATTRIBUTES,
+    BOOL,
+    u32,
+    *const c_void,
+    PCWSTR,
+    *const STARTUPINFOW,
+    *mut PROCESS_INFORMATION,
+) -> BOOL;
+
+struct Hooks {
+    create_file: GenericDetour<CreateFileWFn>,
+    get_attributes: GenericDetour<GetFileAttributesWFn>,
+    get_attributes_ex: GenericDetour<GetFileAttributesExWFn>,
+    find_first: GenericDetour<FindFirstFileWFn>,
+    find_first_ex: GenericDetour<FindFirstFileExWFn>,
+    find_next: GenericDetour<FindNextFileWFn>,
+    find_close: GenericDetour<FindCloseFn>,
+    create_process: GenericDetour<CreateProcessWFn>,
+}
+
+struct State {
+    view: View,
+    cas_root: PathBuf,
+    root: String,
+    /// Stand-in for directories that only exist in the view
+    empty_dir: Vec<u16>,
+    debug: bool,
+}
+
+static HOOKS: OnceLock<Hooks> = OnceLock::new();
+static STATE: OnceLock<State> = OnceLock::new();
+static MODULE: AtomicUsize = AtomicUsize::new(0);
+
+/// Open directory listings served from the view, keyed by their fake handle
+static FINDS: Mutex<BTreeMap<usize, Box<Find>>> = Mutex::new(BTreeMap::new());
+
+struct Find {
+    entries: Vec<WIN32_FIND_DATAW>,
+    next: usize,
+}
+
+thread_local! {
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+struct Guard;
+
+impl Guard {
+    /// `None` when this thread is already inside a hook (or tearing down)
+    fn enter() -> Option<Guard> {
+        IN_HOOK
+            .try_with(|flag| (!flag.replace(true)).then_some(Guard))
+            .ok()
+            .flatten()
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let _ = IN_HOOK.try_with(|flag| flag.set(false));
+    }
+}
+
+fn hooks() -> &'static Hooks {
+    HOOKS.get().expect("hooks run only after install")
+}
+
+/// What the view says about a key
+enum Virtual<'a> {
+    /// Whiteout: the path must look absent
+    Missing,
+    /// Recorded or implied directory
+    Dir(Option<&'a Entry>),
+    File(&'a Entry),
+}
+
+impl State {
+    fn key(&self, path: PCWSTR) -> Option<String> {
+        let path = unsafe { from_wide(path) }?;
+        winpath::key_for(&path, &current_dir(), &self.root)
+    }
+
+    /// `None` when the view has nothing to say (symlinks included: the
+    /// real filesystem answers for those)
+    fn classify(&self, key: &str) -> Option<Virtual<'_>> {
+        match self.view.get(key) {
+            Some(entry) => match entry.kind {
+                Kind::Whiteout => Some(Virtual::Missing),
+                Kind::Symlink => None,
+                Kind::Dir => Some(Virtual::Dir(Some(entry))),
+                Kind::File | Kind::Chunked => Some(Virtual::File(entry)),
+            },
+            None => self.view.is_dir(key).then_some(Virtual::Dir(None)),
+        }
+    }
+
+    fn debug(&self, message: std::fmt::Arguments<'_>) {
+        if self.debug {
+            eprintln!("[vrift-win] {}", message);
+        }
+    }
+}
+
+unsafe fn from_wide(ptr: PCWSTR) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let len = (0..).take_while(|&i| *ptr.add(i) != 0).count();
+    String::from_utf16(std::slice::from_raw_parts(ptr, len)).ok()
+}
+
+fn current_dir() -> String {
+    let mut buf = vec![0u16; 1024];
+    loop {
+        let len = unsafe { GetCurrentDirectoryW(buf.len() as u32, buf.as_mut_ptr()) } as usize;
+        if len == 0 {
+            return String::new();
+        }
+        if len < buf.len() {
+            return String::from_utf16_lossy(&buf[..len]);
+        }
+        buf.resize(len, 0);
+    }
+}
+
+fn filetime(secs: u64) -> FILETIME {
+    // 100ns ticks since 1601-01-01
+    let ticks = (secs + 11_644_473_600) * 10_000_000;
+    FILETIME {
+        dwLowDateTime: ticks as u32,
+        dwHighDateTime: (ticks >> 32) as u32,
+    }
+}
+
+fn attributes(entry: &Virtual<'_>) -> u32 {
+    match entry {
+        Virtual::Dir(_) => FILE_ATTRIBUTE_DIRECTORY,
+        Virtual::File(entry) if entry.mode & 0o200 == 0 => {
+            FILE_ATTRIBUTE_ARCHIVE | FILE_ATTRIBUTE_READONLY
+        }
+        _ => FILE_ATTRIBUTE_ARCHIVE,
+    }
+}
+
+fn attribute_data(entry: &Virtual<'_>) -> WIN32_FILE_ATTRIBUTE_DATA {
+    let (size, mtime) = match entry {
+        Virtual::File(entry) => (entry.size, entry.mtime),
+        Virtual::Dir(Some(entry)) => (0, entry.mtime),
+        _ => (0, 0),
+    };
+    let time = filetime(mtime);
+    WIN32_FILE_ATTRIBUTE_DATA {
+        dwFileAttributes: attributes(entry),
+        ftCreationTime: time,
+        ftLastAccessTime: time,
+        ftLastWriteTime: time,
+        nFileSizeHigh: (size >> 32) as u32,
+        nFileSizeLow: size as u32,
+    }
+}
+
+fn find_data(name: &str, entry: &Virtual<'_>) -> WIN32_FIND_DATAW {
+    let data = attribute_data(entry);
+    let mut out: WIN32_FIND_DATAW = unsafe { std::mem::zeroed() };
+    out.dwFileAttributes = data.dwFileAttributes;
+    out.ftCreationTime = data.ftCreationTime;
+    out.ftLastAccessTime = data.ftLastAccessTime;
+    out.ftLastWriteTime = data.ftLastWriteTime;
+    out.nFileSizeHigh = data.nFileSizeHigh;
+    out.nFileSizeLow = data.nFileSizeLow;
+    for (slot, unit) in out.cFileName[..259].iter_mut().zip(name.encode_utf16()) {
+        *slot = unit;
+    }
+    out
+}
+
+fn find_name(data: &WIN32_FIND_DATAW) -> String {
+    let len = data.cFileName.iter().position(|&c| c == 0).unwrap_or(260);
+    String::from_utf16_lossy(&data.cFileName[..len])
+}
+
+/// No write, delete or security access, and no create/truncate
+fn is_read_only(access: u32, disposition: u32) -> bool {
+    const WRITE_ACCESS: u32 = GENERIC_WRITE
+        | GENERIC_ALL
+        | FILE_WRITE_DATA
+        | FILE_APPEND_DATA
+        | FILE_WRITE_EA
+        | FILE_WRITE_ATTRIBUTES
+        | DELETE
+        | WRITE_DAC
+        | WRITE_OWNER;
+    access & WRITE_ACCESS == 0 && matches!(disposition, OPEN_EXISTING | OPEN_ALWAYS)
+}
+
+unsafe extern "system" fn create_file_w(
+    name: PCWSTR,
+    access: u32,
+    share: u32,
+    security: *const SECURITY_ATTRIBUTES,
+    disposition: u32,
+    flags: u32,
+    template: HANDLE,
+) -> HANDLE {
+    let original = &hooks().create_file;
+    let passthrough = || original.call(name, access, share, security, disposition, flags, template);
+    let Some(_guard) = Guard::enter() else {
+        return passthrough();
+    };
+    let Some((state, key)) = STATE.get().and_then(|s| Some((s, s.key(name)?))) else {
+        return passthrough();
+    };
+
+    match state.classify(&key) {
+        Some(Virtual::Missing) if matches!(disposition, OPEN_EXISTING | TRUNCATE_EXISTING) => {
+            SetLastError(ERROR_FILE_NOT_FOUND);
+            INVALID_HANDLE_VALUE
+        }
+        Some(Virtual::File(entry)) if is_read_only(access, disposition) => {
+            let Some(blob) = entry.blob_path(&state.cas_root).filter(|p| p.is_file()) else {
+                // Chunked content, or a blob that isn't local
+                return passthrough();
+            };
+            state.debug(format_args!("CreateFileW {} -> {}", key, blob.display()));
+            let blob = wide(&blob.to_string_lossy());
+            // Other processes may hold the same blob open
+            original.call(
+                blob.as_ptr(),
+                access,
+                share | FILE_SHARE_READ,
+                security,
+                OPEN_EXISTING,
+                flags & !FILE_FLAG_DELETE_ON_CLOSE,
+                template,
+            )
+        }
+        Some(Virtual::Dir(_))
+            if flags & FILE_FLAG_BACKUP_SEMANTICS != 0 && is_read_only(access, disposition) =>
+        {
+            let handle = passthrough();
+            if handle != INVALID_HANDLE_VALUE {
+                return handle;
+            }
+            state.debug(format_args!("CreateFileW {} -> empty directory", key));
+            original.call(
+                state.empty_dir.as_ptr(),
+                access,
+                share | FILE_SHARE_READ,
+                security,
+                OPEN_EXISTING,
+                flags,
+                template,
+            )
+        }
+        _ => passthrough(),
+    }
+}
+
+unsafe extern "system" fn get_file_attributes_w(name: PCWSTR) -> u32 {
+    let passthrough = || hooks().get_attributes.call(name);
+    let Some(_guard) = Guard::enter() else {
+        return passthrough();
+    };
+    let Some(state) = STATE.get() else {
+        return passthrough();
+    };
+    match state.key(name).and_then(|key| state.classify(&key)) {
+        Some(Virtual::Missing) => {
+            SetLastError(ERROR_FILE_NOT_FOUND);
+            INVALID_FILE_ATTRIBUTES
+        }
+        Some(entry) => attributes(&entry),
+        None => passthrough(),
+    }
+}
+
+unsafe extern "system" fn get_file_attributes_ex_w(
+    name: PCWSTR,
+    level: i32,
+    info: *mut c_void,
+) -> BOOL {
+    let passthrough = || hooks().get_attributes_ex.call(name, level, info);
+    let Some(_guard) = Guard::enter() else {
+        return passthrough();
+    };
+    let Some(state) = STATE.get().filter(|_| level == GetFileExInfoStandard) else {
+        return passthrough();
+    };
+    match state.key(name).and_then(|key| state.classify(&key)) {
+        Some(Virtual::Missing) => {
+            SetLastError(ERROR_FILE_NOT_FOUND);
+            FALSE
+        }
+        Some(entry) => {
+            info.cast::<WIN32_FILE_ATTRIBUTE_DATA>()
+                .write_unaligned(attribute_data(&entry));
+            TRUE
+        }
+        None => passthrough(),
+    }
+}
+
+/// Merged listing for `name` (`dir\pattern`), or `None` to let the
+/// original answer: the directory isn't in the view
+unsafe fn find_first(
+    state: &State,
+    name: PCWSTR,
+    level: i32,
+    search: i32,
+    filter: *const c_void,
+    flags: u32,
+    data: *mut WIN32_FIND_DATAW,
+) -> Option<HANDLE> {
+    let path = from_wide(name)?;
+    let (dir, pattern) = winpath::split_pattern(&path);
+    let dir_key = winpath::key_for(
+        if dir.is_empty() { "." } else { dir },
+        &current_dir(),
+        &state.root,
+    )?;
+    match state.classify(&dir_key)? {
+        Virtual::Dir(_) => {}
+        Virtual::Missing => {
+            SetLastError(ERROR_PATH_NOT_FOUND);
+            return Some(INVALID_HANDLE_VALUE);
+        }
+        Virtual::File(_) => return None,
+    }
+
+    // Real entries first; the view overrides them by (case-folded) name
+    let hooks = hooks();
+    let mut merged: BTreeMap<String, WIN32_FIND_DATAW> = BTreeMap::new();
+    let mut real: WIN32_FIND_DATAW = std::mem::zeroed();
+    let handle = hooks.find_first_ex.call(
+        name,
+        level,
+        (&mut real as *mut WIN32_FIND_DATAW).cast(),
+        search,
+        filter,
+        flags,
+    );
+    let real_listing = handle != INVALID_HANDLE_VALUE;
+    if real_listing {
+        loop {
+            merged.insert(fold_key(&find_name(&real)), real);
+            if hooks.find_next.call(handle, &mut real) == FALSE {
+                break;
+            }
+        }
+        hooks.find_close.call(handle);
+    }
+
+    for child in state.view.children(&dir_key) {
+        let child_name = child.name();
+        if !winpath::wildcard_match(pattern, child_name) {
+            continue;
+        }
+        match state.classify(&child.key) {
+            Some(Virtual::Missing) => {
+                merged.remove(&fold_key(child_name));
+            }
+            Some(entry) => {
+                merged.insert(fold_key(child_name), find_data(child_name, &entry));
+            }
+            None => {}
+        }
+    }
+    if !real_listing && winpath::has_wildcards(pattern) {
+        for dot in [".", ".."] {
+            if winpath::wildcard_match(pattern, dot) {
+                merged.insert(dot.to_string(), find_data(dot, &Virtual::Dir(None)));
+            }
+        }
+    }
+
+    let mut entries = merged.into_values();
+    let Some(first) = entries.next() else {
+        SetLastError(ERROR_FILE_NOT_FOUND);
+        return Some(INVALID_HANDLE_VALUE);
+    };
+    data.write_unaligned(first);
+    state.debug(format_args!("FindFirstFile {} (virtual listing)", path));
+
+    let find = Box::new(Find {
+        entries: entries.collect(),
+        next: 0,
+    });
+    let handle = &*find as *const Find as usize;
+    FINDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(handle, find);
+    Some(handle as HANDLE)
+}
+
+unsafe extern "system" fn find_first_file_w(name: PCWSTR, data: *mut WIN32_FIND_DATAW) -> HANDLE {
+    let passthrough = || hooks().find_first.call(name, data);
+    let Some(_guard) = Guard::enter() else {
+        return passthrough();
+    };
+    STATE
+        .get()
+        .and_then(|state| {
+            find_first(
+                state,
+                name,
+                FindExInfoStandard,
+                FindExSearchNameMatch,
+                ptr::null(),
+                0,
+                data,
+            )
+        })
+        .unwrap_or_else(passthrough)
+}
+
+unsafe extern "system" fn find_first_file_ex_w(
+    name: PCWSTR,
+    level: i32,
+    data: *mut c_void,
+    search: i32,
+    filter: *const c_void,
+    flags: u32,
+) -> HANDLE {
+    let passthrough = || {
+        hooks()
+            .find_first_ex
+            .call(name, level, data, search, filter, flags)
+    };
+    let Some(_guard) = Guard::enter() else {
+        return passthrough();
+    };
+    STATE
+        .get()
+        .and_then(|state| find_first(state, name, level, search, filter, flags, data.cast()))
+        .unwrap_or_else(passthrough)
+}
+
+unsafe extern "system" fn find_next_file_w(handle: HANDLE, data: *mut WIN32_FIND_DATAW) -> BOOL {
+    let mut finds = FINDS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(find) = finds.get_mut(&(handle as usize)) else {
+        drop(finds);
+        return hooks().find_next.call(handle, data);
+    };
+    match find.entries.get(find.next) {
+        Some(entry) => {
+            data.write_unaligned(*entry);
+            find.next += 1;
+            TRUE
+        }
+        None => {
+            SetLastError(ERROR_NO_MORE_FILES);
+            FALSE
+        }
+    }
+}
+
+unsafe extern "system" fn find_close(handle: HANDLE) -> BOOL {
+    let removed = FINDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&(handle as usize));
+    match removed {
+        Some(_) => TRUE,
+        None => hooks().find_close.call(handle),
+    }
+}
+
+/// Children start suspended so the DLL is in place before their first
+/// file access
+#[allow(clippy::too_many_arguments)]
+unsafe extern "system" fn create_process_w(
+    application: PCWSTR,
+    command_line: PWSTR,
+    process_attributes: *const SECURITY_ATTRIBUTES,
+    thread_attributes: *const SECURITY_ATTRIBUTES,
+    inherit_handles: BOOL,
+    creation_flags: u32,
+    environment: *const c_void,
+    current_directory: PCWSTR,
+    startup_info: *const STARTUPINFOW,
+    process_info: *mut PROCESS_INFORMATION,
+) -> BOOL {
+    let original = &hooks().create_process;
+    let call = |flags| {
+        original.call(
+            application,
+            command_line,
+            process_attributes,
+            thread_attributes,
+            inherit_handles,
+            flags,
+            environment,
+            current_directory,
+            startup_info,
+            process_info,
+        )
+    };
+    let Some(_guard) = Guard::enter() else {
+        return call(creation_flags);
+    };
+    let Some(state) = STATE.get() else {
+        return call(creation_flags);
+    };
+
+    let created = call(creation_flags | CREATE_SUSPENDED);
+    if created == FALSE || process_info.is_null() {
+        return created;
+    }
+    let info = &*process_info;
+    if let Err(e) = inject(info.hProcess, &module_path()) {
+        eprintln!(
+            "[vrift-win] child {} runs without the layer: {}",
+            info.dwProcessId, e
+        );
+    } else {
+        state.debug(format_args!("injected into child {}", info.dwProcessId));
+    }
+    if creation_flags & CREATE_SUSPENDED == 0 {
+        ResumeThread(info.hThread);
+    }
+    created
+}
+
+/// NUL-terminated path of this DLL
+fn module_path() -> Vec<u16> {
+    let module = MODULE.load(Ordering::Relaxed) as HMODULE;
+    let mut buf = vec![0u16; 32 * 1024];
+    let len = unsafe { GetModuleFileNameW(module, buf.as_mut_ptr(), buf.len() as u32) } as usize;
+    buf.truncate(len);
+    buf.push(0);
+    buf
+}
+
+/// Address of `name` in KernelBase (where the implementations live), else
+/// kernel32
+unsafe fn proc_address(name: &CStr) -> Result<unsafe extern "system" fn() -> isize, String> {
+    for module in ["kernelbase.dll", "kernel32.dll"] {
+        let handle = GetModuleHandleW(wide(module).as_ptr());
+        if handle.is_null() {
+            continue;
+        }
+        if let Some(address) = GetProcAddress(handle, name.as_ptr().cast()) {
+            return Ok(address);
+        }
+    }
+    Err(format!("{} not found", name.to_string_lossy()))
+}
+
+macro_rules! detour {
+    ($name:literal, $ty:ty, $hook:expr) => {{
+        let target: $ty = std::mem::transmute(proc_address($name)?);
+        GenericDetour::<$ty>::new(target, $hook as $ty)
+            .map_err(|e| format!("{}: {}", $name.to_string_lossy(), e))?
+    }};
+}
+
+/// Load the view named by the environment and enable the hooks. Without
+/// `VRIFT_MANIFEST` the DLL stays inert.
+pub(crate) unsafe fn install(module: HMODULE) -> Result<(), String> {
+    let Some(view_path) = std::env::var_os("VRIFT_MANIFEST") else {
+        return Ok(());
+    };
+    let view = View::load(view_path.as_ref())
+        .map_err(|e| format!("{}: {}", PathBuf::from(&view_path).display(), e))?;
+    let cas_root = match std::env::var_os("VR_THE_SOURCE") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(std::env::var_os("USERPROFILE").ok_or("USERPROFILE not set")?)
+            .join(".vrift")
+            .join("the_source"),
+    };
+    let root = std::env::var("VRIFT_PROJECT_ROOT").unwrap_or_else(|_| current_dir());
+    let empty_dir = std::env::temp_dir().join("vrift-empty-dir");
+    std::fs::create_dir_all(&empty_dir).map_err(|e| e.to_string())?;
+
+    let state = State {
+        debug: std::env::var_os("VRIFT_DEBUG").is_some(),
+        empty_dir: wide(&empty_dir.to_string_lossy()),
+        view,
+        cas_root,
+        root,
+    };
+    state.debug(format_args!(
+        "{} entries, root {}, CAS {}",
+        state.view.len(),
+        state.root,
+        state.cas_root.display()
+    ));
+    MODULE.store(module as usize, Ordering::Relaxed);
+    let _ = STATE.set(state);
+
+    let hooks = Hooks {
+        create_file: detour!(c"CreateFileW", CreateFileWFn, create_file_w),
+        get_attributes: detour!(
+            c"GetFileAttributesW",
+            GetFileAttributesWFn,
+            get_file_attributes_w
+        ),
+        get_attributes_ex: detour!(
+            c"GetFileAttributesExW",
+            GetFileAttributesExWFn,
+            get_file_attributes_ex_w
+        ),
+        find_first: detour!(c"FindFirstFileW", FindFirstFileWFn, find_first_file_w),
+        find_first_ex: detour!(
+            c"FindFirstFileExW",
+            FindFirstFileExWFn,
+            find_first_file_ex_w
+        ),
+        find_next: detour!(c"FindNextFileW", FindNextFileWFn, find_next_file_w),
+        find_close: detour!(c"FindClose", FindCloseFn, find_close),
+        create_process: detour!(c"CreateProcessW", CreateProcessWFn, create_process_w),
+    };
+    let hooks = HOOKS.get_or_init(|| hooks);
+    for enable in [
+        hooks.create_file.enable(),
+        hooks.get_attributes.enable(),
+        hooks.get_attributes_ex.enable(),
+        hooks.find_first.enable(),
+        hooks.find_first_ex.enable(),
+        hooks.find_next.enable(),
+        hooks.find_close.enable(),
+        hooks.create_process.enable(),
+    ] {
+        enable.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! DLL injection into a (suspended) child process
+//!
+//! Classic `LoadLibraryW` remote thread: copy the DLL path into the child,
+//! then start a thread there at `LoadLibraryW`. kernel32 is mapped at the
+//! same address in every process of a boot session, so our own
+//! `LoadLibraryW` address is valid in the child. Only works between
+//! processes of the same bitness.
+
+use std::ffi::c_void;
+use std::io;
+use std::ptr;
+
+use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+use windows_sys::Win32::System::Diagnostics::Debug::WriteProcessMemory;
+use windows_sys::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
+use windows_sys::Win32::System::Memory::{
+    VirtualAllocEx, VirtualFreeEx, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE,
+};
+use windows_sys::Win32::System::Threading::{
+    CreateRemoteThread, GetExitCodeThread, WaitForSingleObject, INFINITE, LPTHREAD_START_ROUTINE,
+};
+
+/// NUL-terminated UTF-16 copy of `s`
+pub fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Load the DLL at `dll` (NUL-terminated UTF-16) into `process`, waiting
+/// until its `DllMain` has run
+///
+/// # Safety
+/// `process` must be a live process handle with `PROCESS_CREATE_THREAD`,
+/// `PROCESS_VM_OPERATION` and `PROCESS_VM_WRITE` access.
+pub unsafe fn inject(process: HANDLE, dll: &[u16]) -> io::Result<()> {
+    let bytes = std::mem::size_of_val(dll);
+    let remote = VirtualAllocEx(
+        process,
+        ptr::null(),
+        bytes,
+        MEM_COMMIT | MEM_RESERVE,
+        PAGE_READWRITE,
+    );
+    if remote.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let result = load_remote(process, remote, dll, bytes);
+    VirtualFreeEx(process, remote, 0, MEM_RELEASE);
+    result
+}
+
+unsafe fn load_remote(
+    process: HANDLE,
+    remote: *mut c_void,
+    dll: &[u16],
+    bytes: usize,
+) -> io::Result<()> {
+    if WriteProcessMemory(process, remote, dll.as_ptr().cast(), bytes, ptr::null_mut()) == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let kernel32 = GetModuleHandleW(wide("kernel32.dll").as_ptr());
+    let load_library = GetProcAddress(kernel32, c"LoadLibraryW".as_ptr().cast())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "LoadLibraryW not found"))?;
+    // LoadLibraryW(LPCWSTR) -> HMODULE has the thread-start shape closely
+    // enough: one pointer argument, pointer-sized return
+    let start: LPTHREAD_START_ROUTINE = Some(std::mem::transmute::<
+        unsafe extern "system" fn() -> isize,
+        unsafe extern "system" fn(*mut c_void) -> u32,
+    >(load_library));
+
+    let thread = CreateRemoteThread(process, ptr::null(), 0, start, remote, 0, ptr::null_mut());
+    if thread.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    WaitForSingleObject(thread, INFINITE);
+    let mut module = 0u32;
+    let ok = GetExitCodeThread(thread, &mut module);
+    CloseHandle(thread);
+    // The exit code is the low half of the HMODULE: zero means it failed
+    if ok == 0 || module == 0 {
+        return Err(io::Error::other("LoadLibraryW failed in the child process"));
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! # vrift-shim-win
+//!
+//! Windows counterpart of the inception layer. There is no `LD_PRELOAD` on
+//! Windows, so the DLL is injected into the process (see `vrift-win-run`)
+//! and detours the kernel32/KernelBase file APIs in place:
+//!
+//! - `CreateFileW`: read-only opens of view files are served from the CAS
+//!   blob; view-only directories open as an empty stand-in
+//! - `GetFileAttributesW` / `GetFileAttributesExW`: answered from the view
+//! - `FindFirstFileW` / `FindFirstFileExW` / `FindNextFileW` / `FindClose`:
+//!   real listings merged with the view, whiteouts hidden
+//! - `CreateProcessW`: children get the DLL too
+//!
+//! It is a read-only subset: writes, chunked files and symlinks fall
+//! through to the real filesystem. The DLL reads a portable view
+//! (`vrift shim view`) rather than the LMDB manifest, whose crates are
+//! Unix-only.
+//!
+//! Environment (same names as the Unix layer): `VRIFT_MANIFEST` (the view
+//! file), `VR_THE_SOURCE`, `VRIFT_PROJECT_ROOT`, `VRIFT_DEBUG`.
+
+pub mod cmdline;
+pub mod winpath;
+
+#[cfg(windows)]
+mod hooks;
+#[cfg(windows)]
+pub mod inject;
+
+/// Installs the hooks when the DLL is loaded
+///
+/// # Safety
+/// Called by the Windows loader only.
+#[cfg(windows)]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "system" fn DllMain(
+    module: windows_sys::Win32::Foundation::HMODULE,
+    reason: u32,
+    _reserved: *mut std::ffi::c_void,
+) -> windows_sys::Win32::Foundation::BOOL {
+    use windows_sys::Win32::System::LibraryLoader::DisableThreadLibraryCalls;
+    use windows_sys::Win32::System::SystemServices::DLL_PROCESS_ATTACH;
+
+    if reason == DLL_PROCESS_ATTACH {
+        DisableThreadLibraryCalls(module);
+        if let Err(e) = hooks::install(module) {
+            eprintln!("[vrift-win] layer inactive: {}", e);
+        }
+    }
+    windows_sys::Win32::Foundation::TRUE
+}
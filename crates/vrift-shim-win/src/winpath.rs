@@ -0,0 +1,187 @@
+//! Windows paths → manifest keys, and `FindFirstFile` wildcards
+//!
+//! Kept free of Win32 calls so the mapping is tested on every platform.
+
+/// `path` as an absolute, normalized Windows path (`C:\a\b` or
+/// `\\server\share\a`), resolving relative forms against `cwd`
+pub fn normalize(path: &str, cwd: &str) -> Option<String> {
+    let mut path = path.replace('/', "\\");
+    if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        path = format!(r"\\{}", rest);
+    } else if let Some(rest) = path
+        .strip_prefix(r"\\?\")
+        .or_else(|| path.strip_prefix(r"\??\"))
+    {
+        path = rest.to_string();
+    }
+
+    let has_drive =
+        |p: &str| p.len() >= 2 && p.as_bytes()[1] == b':' && p.as_bytes()[0].is_ascii_alphabetic();
+    let absolute = if path.starts_with(r"\\") || (has_drive(&path) && path[2..].starts_with('\\')) {
+        path
+    } else if has_drive(&path) {
+        // Drive-relative (`C:foo`): only meaningful against the same drive
+        let cwd = normalize(cwd, "")?;
+        if !cwd[..2].eq_ignore_ascii_case(&path[..2]) {
+            format!(r"{}\{}", &path[..2], &path[2..])
+        } else {
+            format!(r"{}\{}", cwd, &path[2..])
+        }
+    } else if path.starts_with('\\') {
+        let cwd = normalize(cwd, "")?;
+        let (prefix, _) = split_prefix(&cwd)?;
+        format!("{}{}", prefix, path)
+    } else {
+        if cwd.is_empty() {
+            return None;
+        }
+        format!(r"{}\{}", normalize(cwd, "")?, path)
+    };
+
+    let (prefix, rest) = split_prefix(&absolute)?;
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(format!("{}\\{}", prefix, parts.join("\\")))
+}
+
+/// `C:` or `\\server\share`, and the rest of an absolute path
+fn split_prefix(path: &str) -> Option<(&str, &str)> {
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        let mut parts = unc.splitn(3, '\\');
+        let server = parts.next().filter(|s| !s.is_empty())?;
+        let share = parts.next().filter(|s| !s.is_empty())?;
+        let len = 2 + server.len() + 1 + share.len();
+        return Some((&path[..len], &path[len..]));
+    }
+    (path.len() >= 2 && path.as_bytes()[1] == b':').then(|| (&path[..2], &path[2..]))
+}
+
+/// Manifest key (`/src/main.rs`) for `path` if it lies under `root`
+pub fn key_for(path: &str, cwd: &str, root: &str) -> Option<String> {
+    let path = normalize(path, cwd)?;
+    let root = normalize(root, cwd)?;
+    let root = root.trim_end_matches('\\');
+    if path.len() < root.len() || !path[..root.len()].eq_ignore_ascii_case(root) {
+        return None;
+    }
+    let rest = &path[root.len()..];
+    if !rest.is_empty() && !rest.starts_with('\\') {
+        return None;
+    }
+    let key = rest.trim_end_matches('\\').replace('\\', "/");
+    Some(if key.is_empty() { "/".to_string() } else { key })
+}
+
+/// Directory and file pattern of a `FindFirstFile` argument
+pub fn split_pattern(path: &str) -> (&str, &str) {
+    match path.rfind(['\\', '/']) {
+        Some(i) => {
+            // Keep the separator of a root (`\*`, `C:\*`): `C:` alone means
+            // the drive's current directory
+            let is_root = i == 0 || (i == 2 && path.as_bytes()[1] == b':');
+            (&path[..if is_root { i + 1 } else { i }], &path[i + 1..])
+        }
+        None => ("", path),
+    }
+}
+
+/// Whether `name` matches a `FindFirstFile` pattern (`*`, `?`), ignoring
+/// case. `*.*` matches every name, as it does on Windows.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    if pattern == "*.*" {
+        return true;
+    }
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether `pattern` has wildcards (otherwise it names one entry)
+pub fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let cwd = r"C:\work\app";
+        assert_eq!(
+            normalize(r"src\main.rs", cwd).unwrap(),
+            r"C:\work\app\src\main.rs"
+        );
+        assert_eq!(
+            normalize("./src/../Cargo.toml", cwd).unwrap(),
+            r"C:\work\app\Cargo.toml"
+        );
+        assert_eq!(normalize(r"\tmp\x", cwd).unwrap(), r"C:\tmp\x");
+        assert_eq!(normalize(r"\\?\C:\work\app\", cwd).unwrap(), r"C:\work\app");
+        assert_eq!(normalize(r"C:lib.rs", cwd).unwrap(), r"C:\work\app\lib.rs");
+        assert_eq!(normalize(r"D:lib.rs", cwd).unwrap(), r"D:\lib.rs");
+        assert_eq!(
+            normalize(r"\\?\UNC\srv\share\a\..\b", cwd).unwrap(),
+            r"\\srv\share\b"
+        );
+        assert_eq!(normalize("rel", ""), None);
+    }
+
+    #[test]
+    fn test_key_for() {
+        let (cwd, root) = (r"C:\work\app", r"c:\Work\App\");
+        assert_eq!(
+            key_for(r"src\main.rs", cwd, root).as_deref(),
+            Some("/src/main.rs")
+        );
+        assert_eq!(key_for(r"C:\WORK\APP", cwd, root).as_deref(), Some("/"));
+        assert_eq!(key_for(r"C:\work\apple\x", cwd, root), None);
+        assert_eq!(key_for(r"C:\work", cwd, root), None);
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert_eq!(split_pattern(r"C:\app\src\*"), (r"C:\app\src", "*"));
+        assert_eq!(split_pattern(r"\*"), (r"\", "*"));
+        assert_eq!(split_pattern(r"C:\*.rs"), (r"C:\", "*.rs"));
+        assert_eq!(split_pattern("main.rs"), ("", "main.rs"));
+        assert!(wildcard_match("*", "Main.rs"));
+        assert!(wildcard_match("*.*", "Makefile"));
+        assert!(wildcard_match("*.RS", "main.rs"));
+        assert!(wildcard_match("m?in*", "main.rs"));
+        assert!(wildcard_match("*a*b", "xaybab"));
+        assert!(!wildcard_match("*.rs", "main.rsx"));
+        assert!(!wildcard_match("main", "main.rs"));
+        assert!(has_wildcards("*.rs") && !has_wildcards("main.rs"));
+    }
+}
@@ -0,0 +1,518 @@
+//! Just enough eBPF to load a hand-assembled tracepoint program: maps, the
+//! program, per-CPU perf attachment and a ring buffer reader. No libbpf,
+//! no BTF, no compiler toolchain.
+
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, Result};
+
+const BPF_MAP_CREATE: i32 = 0;
+const BPF_MAP_UPDATE_ELEM: i32 = 2;
+const BPF_MAP_DELETE_ELEM: i32 = 3;
+const BPF_PROG_LOAD: i32 = 5;
+
+pub(crate) const MAP_HASH: u32 = 1;
+pub(crate) const MAP_RINGBUF: u32 = 27;
+const PROG_TRACEPOINT: u32 = 5;
+
+const PERF_TYPE_TRACEPOINT: u32 = 2;
+const PERF_FLAG_FD_CLOEXEC: u64 = 8;
+const PERF_EVENT_IOC_ENABLE: u64 = 0x2400;
+const PERF_EVENT_IOC_SET_BPF: u64 = 0x4004_2408;
+
+const RINGBUF_BUSY: u32 = 1 << 31;
+const RINGBUF_DISCARD: u32 = 1 << 30;
+const RINGBUF_HDR: usize = 8;
+
+/// One eBPF instruction
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Insn {
+    code: u8,
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+/// Instruction builders, named after the kernel's `BPF_*` macros
+pub(crate) mod asm {
+    use super::Insn;
+
+    pub const R0: u8 = 0;
+    pub const R1: u8 = 1;
+    pub const R2: u8 = 2;
+    pub const R3: u8 = 3;
+    pub const R4: u8 = 4;
+    pub const R6: u8 = 6;
+    pub const R7: u8 = 7;
+    pub const R8: u8 = 8;
+    pub const R10: u8 = 10;
+
+    pub const B: u8 = 0x10;
+    pub const W: u8 = 0x00;
+    pub const DW: u8 = 0x18;
+
+    pub const JEQ: u8 = 0x10;
+
+    pub const FN_MAP_LOOKUP: i32 = 1;
+    pub const FN_MAP_UPDATE: i32 = 2;
+    pub const FN_GET_CURRENT_PID_TGID: i32 = 14;
+    pub const FN_PROBE_READ_USER_STR: i32 = 114;
+    pub const FN_RINGBUF_RESERVE: i32 = 131;
+    pub const FN_RINGBUF_SUBMIT: i32 = 132;
+
+    const fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Insn {
+        Insn {
+            code,
+            regs: (src << 4) | dst,
+            off,
+            imm,
+        }
+    }
+
+    pub const fn mov_reg(dst: u8, src: u8) -> Insn {
+        insn(0xbf, dst, src, 0, 0)
+    }
+    pub const fn mov_imm(dst: u8, imm: i32) -> Insn {
+        insn(0xb7, dst, 0, 0, imm)
+    }
+    pub const fn add_imm(dst: u8, imm: i32) -> Insn {
+        insn(0x07, dst, 0, 0, imm)
+    }
+    pub const fn rsh_imm(dst: u8, imm: i32) -> Insn {
+        insn(0x77, dst, 0, 0, imm)
+    }
+    /// `dst = *(size *)(src + off)`
+    pub const fn load(size: u8, dst: u8, src: u8, off: i16) -> Insn {
+        insn(0x61 | size, dst, src, off, 0)
+    }
+    /// `*(size *)(dst + off) = src`
+    pub const fn store(size: u8, dst: u8, off: i16, src: u8) -> Insn {
+        insn(0x63 | size, dst, src, off, 0)
+    }
+    /// `*(size *)(dst + off) = imm`
+    pub const fn store_imm(size: u8, dst: u8, off: i16, imm: i32) -> Insn {
+        insn(0x62 | size, dst, 0, off, imm)
+    }
+    /// `if dst <op> imm goto +off`
+    pub const fn jump_imm(op: u8, dst: u8, imm: i32, off: i16) -> Insn {
+        insn(0x05 | op, dst, 0, off, imm)
+    }
+    pub const fn call(helper: i32) -> Insn {
+        insn(0x85, 0, 0, 0, helper)
+    }
+    pub const fn exit() -> Insn {
+        insn(0x95, 0, 0, 0, 0)
+    }
+    /// `goto +off`
+    pub const fn ja() -> Insn {
+        insn(0x05, 0, 0, 0, 0)
+    }
+    /// `dst = map` (two instruction slots)
+    pub const fn load_map(dst: u8, map_fd: i32) -> [Insn; 2] {
+        // BPF_LD | BPF_DW | BPF_IMM, src = BPF_PSEUDO_MAP_FD
+        [insn(0x18, dst, 1, 0, map_fd), insn(0, 0, 0, 0, 0)]
+    }
+}
+
+/// Instruction list with forward jumps to named labels
+#[derive(Default)]
+pub(crate) struct Program {
+    insns: Vec<Insn>,
+    labels: Vec<(&'static str, usize)>,
+    jumps: Vec<(usize, &'static str)>,
+}
+
+impl Program {
+    pub(crate) fn push(&mut self, insns: &[Insn]) -> &mut Self {
+        self.insns.extend_from_slice(insns);
+        self
+    }
+
+    /// A jump instruction whose target is `label`
+    pub(crate) fn jump(&mut self, insn: Insn, label: &'static str) -> &mut Self {
+        self.jumps.push((self.insns.len(), label));
+        self.push(&[insn])
+    }
+
+    pub(crate) fn label(&mut self, name: &'static str) -> &mut Self {
+        self.labels.push((name, self.insns.len()));
+        self
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<Insn> {
+        for &(at, label) in &self.jumps {
+            let target = self
+                .labels
+                .iter()
+                .find(|(name, _)| *name == label)
+                .map(|&(_, pos)| pos)
+                .unwrap_or_else(|| panic!("undefined label {}", label));
+            self.insns[at].off = (target as isize - at as isize - 1) as i16;
+        }
+        self.insns
+    }
+}
+
+fn bpf<T>(cmd: i32, attr: &mut T) -> io::Result<i32> {
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *mut T,
+            std::mem::size_of::<T>() as u32,
+        )
+    };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(rc as i32)
+}
+
+pub(crate) struct Map {
+    fd: OwnedFd,
+}
+
+impl Map {
+    pub(crate) fn create(
+        map_type: u32,
+        key_size: u32,
+        value_size: u32,
+        max_entries: u32,
+    ) -> io::Result<Self> {
+        #[repr(C)]
+        #[derive(Default)]
+        struct Attr {
+            map_type: u32,
+            key_size: u32,
+            value_size: u32,
+            max_entries: u32,
+            map_flags: u32,
+        }
+        let mut attr = Attr {
+            map_type,
+            key_size,
+            value_size,
+            max_entries,
+            ..Attr::default()
+        };
+        let fd = bpf(BPF_MAP_CREATE, &mut attr)?;
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    pub(crate) fn fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    pub(crate) fn update(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        #[repr(C)]
+        struct Attr {
+            map_fd: u32,
+            _pad: u32,
+            key: u64,
+            value: u64,
+            flags: u64,
+        }
+        let mut attr = Attr {
+            map_fd: self.fd() as u32,
+            _pad: 0,
+            key: key.as_ptr() as u64,
+            value: value.as_ptr() as u64,
+            flags: 0,
+        };
+        bpf(BPF_MAP_UPDATE_ELEM, &mut attr).map(|_| ())
+    }
+
+    pub(crate) fn delete(&self, key: &[u8]) -> io::Result<()> {
+        #[repr(C)]
+        struct Attr {
+            map_fd: u32,
+            _pad: u32,
+            key: u64,
+        }
+        let mut attr = Attr {
+            map_fd: self.fd() as u32,
+            _pad: 0,
+            key: key.as_ptr() as u64,
+        };
+        bpf(BPF_MAP_DELETE_ELEM, &mut attr).map(|_| ())
+    }
+}
+
+/// Load a tracepoint program; the verifier log is part of the error
+pub(crate) fn load_program(insns: &[Insn]) -> Result<OwnedFd> {
+    #[repr(C)]
+    struct Attr {
+        prog_type: u32,
+        insn_cnt: u32,
+        insns: u64,
+        license: u64,
+        log_level: u32,
+        log_size: u32,
+        log_buf: u64,
+        kern_version: u32,
+        prog_flags: u32,
+    }
+    // bpf_probe_read_user_str is GPL-only
+    let license = c"GPL";
+    let mut log = vec![0u8; 64 * 1024];
+    let mut attr = Attr {
+        prog_type: PROG_TRACEPOINT,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: license.as_ptr() as u64,
+        log_level: 1,
+        log_size: log.len() as u32,
+        log_buf: log.as_mut_ptr() as u64,
+        kern_version: 0,
+        prog_flags: 0,
+    };
+    match bpf(BPF_PROG_LOAD, &mut attr) {
+        Ok(fd) => Ok(unsafe { OwnedFd::from_raw_fd(fd) }),
+        Err(e) => {
+            let end = log.iter().position(|&b| b == 0).unwrap_or(log.len());
+            let log = String::from_utf8_lossy(&log[..end]);
+            Err(anyhow::Error::new(e).context(format!("BPF program rejected\n{}", log.trim_end())))
+        }
+    }
+}
+
+/// tracefs, wherever it is mounted
+pub(crate) fn tracefs() -> Result<PathBuf> {
+    ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"]
+        .iter()
+        .map(PathBuf::from)
+        .find(|p| p.join("events").is_dir())
+        .context("tracefs is not mounted (try: mount -t tracefs nodev /sys/kernel/tracing)")
+}
+
+/// Read a `format` field's byte offset, e.g. `child_pid` of
+/// `sched/sched_process_fork`
+pub(crate) fn field_offset(tracefs: &Path, event: &str, field: &str) -> Result<i16> {
+    let path = tracefs.join("events").join(event).join("format");
+    let format =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_field_offset(&format, field)
+        .with_context(|| format!("No field {} in {}", field, path.display()))
+}
+
+fn parse_field_offset(format: &str, field: &str) -> Option<i16> {
+    format.lines().find_map(|line| {
+        let mut parts = line.trim().split(';');
+        let decl = parts.next()?.strip_prefix("field:")?;
+        let name = decl.rsplit([' ', '*']).next()?;
+        if name != field {
+            return None;
+        }
+        parts.next()?.trim().strip_prefix("offset:")?.parse().ok()
+    })
+}
+
+fn online_cpus() -> Vec<i32> {
+    let list = fs::read_to_string("/sys/devices/system/cpu/online").unwrap_or_else(|_| "0".into());
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        let (lo, hi) = range.split_once('-').unwrap_or((range, range));
+        if let (Ok(lo), Ok(hi)) = (lo.parse::<i32>(), hi.parse::<i32>()) {
+            cpus.extend(lo..=hi);
+        }
+    }
+    cpus
+}
+
+/// Run `prog` on every hit of tracepoint `event` (`raw_syscalls/sys_enter`)
+/// until the returned fds are dropped
+pub(crate) fn attach(tracefs: &Path, event: &str, prog: &OwnedFd) -> Result<Vec<OwnedFd>> {
+    let id_path = tracefs.join("events").join(event).join("id");
+    let id: u64 = fs::read_to_string(&id_path)
+        .with_context(|| format!("Failed to read {}", id_path.display()))?
+        .trim()
+        .parse()?;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        type_: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+    }
+    let attr = PerfEventAttr {
+        type_: PERF_TYPE_TRACEPOINT,
+        size: std::mem::size_of::<PerfEventAttr>() as u32,
+        config: id,
+        sample_period: 1,
+        wakeup_events: 1,
+        ..PerfEventAttr::default()
+    };
+
+    let mut events = Vec::new();
+    for cpu in online_cpus() {
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                -1,
+                cpu,
+                -1,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("perf_event_open({}) on CPU {}", event, cpu));
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        unsafe {
+            if libc::ioctl(
+                fd.as_raw_fd(),
+                PERF_EVENT_IOC_SET_BPF as _,
+                prog.as_raw_fd(),
+            ) != 0
+                || libc::ioctl(fd.as_raw_fd(), PERF_EVENT_IOC_ENABLE as _, 0) != 0
+            {
+                return Err(io::Error::last_os_error())
+                    .with_context(|| format!("Failed to attach to {}", event));
+            }
+        }
+        events.push(fd);
+    }
+    Ok(events)
+}
+
+/// Consumer side of a `BPF_MAP_TYPE_RINGBUF`
+pub(crate) struct RingBuf {
+    map: Map,
+    consumer: *mut u8,
+    producer: *mut u8,
+    data_len: usize,
+    page: usize,
+}
+
+impl RingBuf {
+    pub(crate) fn new(data_len: u32) -> io::Result<Self> {
+        let map = Map::create(MAP_RINGBUF, 0, 0, data_len)?;
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let data_len = data_len as usize;
+        unsafe {
+            let consumer = libc::mmap(
+                std::ptr::null_mut(),
+                page,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                map.fd(),
+                0,
+            );
+            if consumer == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            // The data pages are mapped twice so records never wrap
+            let producer = libc::mmap(
+                std::ptr::null_mut(),
+                page + 2 * data_len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                map.fd(),
+                page as libc::off_t,
+            );
+            if producer == libc::MAP_FAILED {
+                let err = io::Error::last_os_error();
+                libc::munmap(consumer, page);
+                return Err(err);
+            }
+            Ok(Self {
+                map,
+                consumer: consumer.cast(),
+                producer: producer.cast(),
+                data_len,
+                page,
+            })
+        }
+    }
+
+    pub(crate) fn fd(&self) -> RawFd {
+        self.map.fd()
+    }
+
+    /// Wait up to `timeout_ms` for records
+    pub(crate) fn wait(&self, timeout_ms: i32) {
+        let mut poll = libc::pollfd {
+            fd: self.fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut poll, 1, timeout_ms) };
+    }
+
+    /// Hand every committed record to `f`
+    pub(crate) fn drain(&mut self, mut f: impl FnMut(&[u8])) {
+        // SAFETY: both positions are u64s at the start of their pages,
+        // shared with the kernel
+        let consumer_pos = unsafe { &*(self.consumer as *const AtomicU64) };
+        let producer_pos = unsafe { &*(self.producer as *const AtomicU64) };
+        let data = unsafe { self.producer.add(self.page) };
+        let mask = self.data_len as u64 - 1;
+
+        let mut cons = consumer_pos.load(Ordering::Acquire);
+        loop {
+            let prod = producer_pos.load(Ordering::Acquire);
+            if cons >= prod {
+                break;
+            }
+            while cons < prod {
+                let hdr = unsafe { data.add((cons & mask) as usize) };
+                let len = unsafe {
+                    (*(hdr as *const std::sync::atomic::AtomicU32)).load(Ordering::Acquire)
+                };
+                if len & RINGBUF_BUSY != 0 {
+                    consumer_pos.store(cons, Ordering::Release);
+                    return;
+                }
+                let body = (len & !(RINGBUF_BUSY | RINGBUF_DISCARD)) as usize;
+                if len & RINGBUF_DISCARD == 0 {
+                    f(unsafe { std::slice::from_raw_parts(hdr.add(RINGBUF_HDR), body) });
+                }
+                cons += ((body + RINGBUF_HDR + 7) & !7) as u64;
+                consumer_pos.store(cons, Ordering::Release);
+            }
+        }
+    }
+}
+
+impl Drop for RingBuf {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.consumer.cast(), self.page);
+            libc::munmap(self.producer.cast(), self.page + 2 * self.data_len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_offset() {
+        let format = "name: sched_process_fork\n\
+            format:\n\
+            \tfield:unsigned short common_type;\toffset:0;\tsize:2;\tsigned:0;\n\
+            \tfield:char parent_comm[16];\toffset:8;\tsize:16;\tsigned:0;\n\
+            \tfield:pid_t child_pid;\toffset:44;\tsize:4;\tsigned:1;\n";
+        assert_eq!(parse_field_offset(format, "child_pid"), Some(44));
+        assert_eq!(parse_field_offset(format, "common_type"), Some(0));
+        assert_eq!(parse_field_offset(format, "parent_pid"), None);
+    }
+}
@@ -0,0 +1,492 @@
+//! `vrift trace coverage`: which filesystem syscalls reach the kernel for
+//! paths the VFS serves
+//!
+//! Two eBPF tracepoint programs follow the command's process tree:
+//! `sched_process_fork` adds each new process to a pid map, and
+//! `raw_syscalls/sys_enter` copies every watched syscall of those processes
+//! (number, first arguments, path string) into a ring buffer.
+//!
+//! The inception layer serves a VFS path by making its own syscall on the
+//! CAS blob, or by answering without one. So a watched syscall whose path
+//! (or fd) still lands on a manifest entry went past the shim: that is a
+//! gap. io_uring submissions carry their paths in shared memory, out of
+//! sight; they are only counted.
+//!
+//! Needs root (or `CAP_BPF` + `CAP_PERFMON`) and a mounted tracefs.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use vrift_manifest::Manifest;
+
+use crate::bpf::{self, asm::*, Map, Program, RingBuf};
+use crate::resolve::normalize;
+
+/// Where a watched syscall names its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// Path string in this argument; `at` calls take their dirfd in arg 0
+    Path(usize),
+    /// File descriptor in arg 0
+    Fd,
+    /// Nothing visible (io_uring)
+    Opaque,
+}
+
+struct Watched {
+    nr: i64,
+    name: &'static str,
+    target: Target,
+}
+
+const fn watch(nr: i64, name: &'static str, target: Target) -> Watched {
+    Watched { nr, name, target }
+}
+
+const WATCHED: &[Watched] = &[
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_open, "open", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_creat, "creat", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_stat, "stat", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_lstat, "lstat", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_access, "access", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_readlink, "readlink", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_mkdir, "mkdir", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_rmdir, "rmdir", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_unlink, "unlink", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_rename, "rename", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_chmod, "chmod", Target::Path(0)),
+    #[cfg(target_arch = "x86_64")]
+    watch(libc::SYS_getdents, "getdents", Target::Fd),
+    watch(libc::SYS_openat, "openat", Target::Path(1)),
+    watch(libc::SYS_openat2, "openat2", Target::Path(1)),
+    watch(libc::SYS_newfstatat, "newfstatat", Target::Path(1)),
+    watch(libc::SYS_statx, "statx", Target::Path(1)),
+    watch(libc::SYS_faccessat, "faccessat", Target::Path(1)),
+    watch(libc::SYS_faccessat2, "faccessat2", Target::Path(1)),
+    watch(libc::SYS_readlinkat, "readlinkat", Target::Path(1)),
+    watch(libc::SYS_mkdirat, "mkdirat", Target::Path(1)),
+    watch(libc::SYS_unlinkat, "unlinkat", Target::Path(1)),
+    watch(libc::SYS_renameat, "renameat", Target::Path(1)),
+    watch(libc::SYS_renameat2, "renameat2", Target::Path(1)),
+    watch(libc::SYS_fchmodat, "fchmodat", Target::Path(1)),
+    watch(libc::SYS_utimensat, "utimensat", Target::Path(1)),
+    watch(libc::SYS_truncate, "truncate", Target::Path(0)),
+    watch(libc::SYS_chdir, "chdir", Target::Path(0)),
+    watch(libc::SYS_execve, "execve", Target::Path(0)),
+    watch(libc::SYS_execveat, "execveat", Target::Path(1)),
+    watch(libc::SYS_getxattr, "getxattr", Target::Path(0)),
+    watch(libc::SYS_lgetxattr, "lgetxattr", Target::Path(0)),
+    watch(libc::SYS_listxattr, "listxattr", Target::Path(0)),
+    watch(libc::SYS_llistxattr, "llistxattr", Target::Path(0)),
+    watch(libc::SYS_getdents64, "getdents64", Target::Fd),
+    watch(libc::SYS_fchdir, "fchdir", Target::Fd),
+    watch(libc::SYS_io_uring_setup, "io_uring_setup", Target::Opaque),
+    watch(libc::SYS_io_uring_enter, "io_uring_enter", Target::Opaque),
+];
+
+/// Ring buffer record: tgid, syscall number, args 0 and 1, path
+const RECORD_LEN: i32 = 24 + PATH_LEN;
+const PATH_LEN: i32 = 256;
+const RINGBUF_LEN: u32 = 4 << 20;
+/// `watch` map value for calls without a path argument
+const NO_PATH: u32 = 6;
+
+/// `raw_syscalls/sys_enter`: record watched syscalls of tracked processes
+fn sys_enter_program(pids: &Map, watch: &Map, events: &RingBuf) -> Vec<bpf::Insn> {
+    let mut p = Program::default();
+    p.push(&[
+        mov_reg(R6, R1),
+        call(FN_GET_CURRENT_PID_TGID),
+        rsh_imm(R0, 32),
+        store(W, R10, -4, R0),
+        mov_reg(R2, R10),
+        add_imm(R2, -4),
+    ])
+    .push(&load_map(R1, pids.fd()))
+    .push(&[call(FN_MAP_LOOKUP)])
+    .jump(jump_imm(JEQ, R0, 0, 0), "exit")
+    // watch[id] is the path argument's index
+    .push(&[
+        load(DW, R1, R6, 8),
+        store(W, R10, -8, R1),
+        mov_reg(R2, R10),
+        add_imm(R2, -8),
+    ])
+    .push(&load_map(R1, watch.fd()))
+    .push(&[call(FN_MAP_LOOKUP)])
+    .jump(jump_imm(JEQ, R0, 0, 0), "exit")
+    .push(&[load(W, R7, R0, 0)])
+    .push(&load_map(R1, events.fd()))
+    .push(&[
+        mov_imm(R2, RECORD_LEN),
+        mov_imm(R3, 0),
+        call(FN_RINGBUF_RESERVE),
+    ])
+    .jump(jump_imm(JEQ, R0, 0, 0), "exit")
+    .push(&[
+        mov_reg(R8, R0),
+        call(FN_GET_CURRENT_PID_TGID),
+        rsh_imm(R0, 32),
+        store(W, R8, 0, R0),
+        load(DW, R1, R6, 8),
+        store(W, R8, 4, R1),
+        load(DW, R1, R6, 16),
+        store(DW, R8, 8, R1),
+        load(DW, R1, R6, 24),
+        store(DW, R8, 16, R1),
+        store_imm(B, R8, 24, 0),
+    ]);
+    // ctx offsets must be constant: one load per argument index
+    const ARGS: [&str; 6] = ["arg0", "arg1", "arg2", "arg3", "arg4", "arg5"];
+    for (i, label) in ARGS.iter().enumerate() {
+        p.jump(jump_imm(JEQ, R7, i as i32, 0), label);
+    }
+    p.jump(ja(), "submit");
+    for (i, label) in ARGS.iter().enumerate() {
+        p.label(label)
+            .push(&[load(DW, R3, R6, 16 + 8 * i as i16)])
+            .jump(ja(), "read");
+    }
+    p.label("read")
+        .push(&[
+            mov_reg(R1, R8),
+            add_imm(R1, 24),
+            mov_imm(R2, PATH_LEN),
+            call(FN_PROBE_READ_USER_STR),
+        ])
+        .label("submit")
+        .push(&[mov_reg(R1, R8), mov_imm(R2, 0), call(FN_RINGBUF_SUBMIT)])
+        .label("exit")
+        .push(&[mov_imm(R0, 0), exit()]);
+    p.finish()
+}
+
+/// `sched/sched_process_fork`: track children of tracked processes
+fn fork_program(pids: &Map, child_pid_offset: i16) -> Vec<bpf::Insn> {
+    let mut p = Program::default();
+    p.push(&[
+        mov_reg(R6, R1),
+        call(FN_GET_CURRENT_PID_TGID),
+        rsh_imm(R0, 32),
+        store(W, R10, -4, R0),
+        mov_reg(R2, R10),
+        add_imm(R2, -4),
+    ])
+    .push(&load_map(R1, pids.fd()))
+    .push(&[call(FN_MAP_LOOKUP)])
+    .jump(jump_imm(JEQ, R0, 0, 0), "exit")
+    .push(&[
+        load(W, R1, R6, child_pid_offset),
+        store(W, R10, -8, R1),
+        store_imm(W, R10, -12, 1),
+    ])
+    .push(&load_map(R1, pids.fd()))
+    .push(&[
+        mov_reg(R2, R10),
+        add_imm(R2, -8),
+        mov_reg(R3, R10),
+        add_imm(R3, -12),
+        mov_imm(R4, 0),
+        call(FN_MAP_UPDATE),
+    ])
+    .label("exit")
+    .push(&[mov_imm(R0, 0), exit()]);
+    p.finish()
+}
+
+/// Watched syscalls that reached the kernel for one syscall
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyscallGaps {
+    /// Calls on manifest paths
+    pub calls: u64,
+    /// Of those, on paths with nothing on disk: sure to fail or miss
+    pub virtual_only: u64,
+    /// A few of the paths, for a start
+    pub examples: Vec<String>,
+}
+
+/// What `record` saw
+#[derive(Debug, Default)]
+pub struct Coverage {
+    /// Watched syscalls made by the traced processes
+    pub syscalls_seen: u64,
+    /// Gaps per syscall name
+    pub gaps: BTreeMap<&'static str, SyscallGaps>,
+    /// Gaps per manifest path
+    pub paths: BTreeMap<String, u64>,
+    /// io_uring setups and submissions, whose paths can't be seen
+    pub io_uring: u64,
+    /// fd-based calls whose fd was gone before it could be resolved
+    pub unresolved: u64,
+}
+
+const EXAMPLES: usize = 3;
+
+/// Manifest paths, as seen from the real filesystem
+struct Classifier {
+    root: PathBuf,
+    known: HashSet<String>,
+}
+
+impl Classifier {
+    fn new(manifest: &Manifest, root: &Path) -> Self {
+        let mut known = HashSet::new();
+        for (path, vnode) in manifest.iter() {
+            if vnode.is_whiteout() {
+                known.insert(path.to_string());
+                continue;
+            }
+            let mut key = path;
+            while known.insert(key.to_string()) {
+                match key.rfind('/') {
+                    Some(0) | None => break,
+                    Some(i) => key = &key[..i],
+                }
+            }
+        }
+        Self {
+            root: normalize(root),
+            known,
+        }
+    }
+
+    /// Manifest key of an absolute path, if the manifest has it
+    fn key(&self, path: &Path) -> Option<String> {
+        let rest = normalize(path).strip_prefix(&self.root).ok()?.to_path_buf();
+        let key = format!("/{}", rest.to_str()?);
+        self.known.contains(&key).then_some(key)
+    }
+}
+
+impl Coverage {
+    fn add(&mut self, classifier: &Classifier, pid: i32, nr: i64, args: [u64; 2], path: &[u8]) {
+        let Some(watched) = WATCHED.iter().find(|w| w.nr == nr) else {
+            return;
+        };
+        self.syscalls_seen += 1;
+        let resolved = match watched.target {
+            Target::Opaque => {
+                self.io_uring += 1;
+                return;
+            }
+            // AT_EMPTY_PATH: about the dirfd itself
+            Target::Path(1) if path.is_empty() => fd_path(pid, args[0] as i32),
+            Target::Path(0) if path.is_empty() => return,
+            Target::Path(i) => {
+                let dirfd = (i == 1).then_some(args[0] as i32);
+                crate::syscalls::absolute(pid, dirfd, path)
+            }
+            Target::Fd => fd_path(pid, args[0] as i32),
+        };
+        let Some(resolved) = resolved else {
+            self.unresolved += 1;
+            return;
+        };
+        if let Some(key) = classifier.key(&resolved) {
+            self.record_gap(watched.name, key, !resolved.exists());
+        }
+    }
+
+    fn record_gap(&mut self, syscall: &'static str, key: String, virtual_only: bool) {
+        let gaps = self.gaps.entry(syscall).or_default();
+        gaps.calls += 1;
+        if virtual_only {
+            gaps.virtual_only += 1;
+        }
+        if gaps.examples.len() < EXAMPLES && !gaps.examples.contains(&key) {
+            gaps.examples.push(key.clone());
+        }
+        *self.paths.entry(key).or_default() += 1;
+    }
+
+    /// Total gaps across syscalls
+    pub fn total(&self) -> u64 {
+        self.gaps.values().map(|g| g.calls).sum()
+    }
+}
+
+fn fd_path(pid: i32, fd: i32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/fd/{}", pid, fd)).ok()
+}
+
+/// Run `cmd` (normally `vrift run ...`) with the tracepoints attached and
+/// return its exit code and what reached the kernel on paths `manifest`
+/// projects at `root`. The spawned process itself is left out unless
+/// `include_leader`: `vrift run` reads the program's binary before the
+/// inception layer is in place.
+pub fn record(
+    manifest: &Manifest,
+    root: &Path,
+    cmd: &mut Command,
+    include_leader: bool,
+) -> Result<(i32, Coverage)> {
+    let tracefs = bpf::tracefs()?;
+    let child_pid_offset = bpf::field_offset(&tracefs, "sched/sched_process_fork", "child_pid")?;
+
+    let pids = Map::create(bpf::MAP_HASH, 4, 4, 16384)
+        .context("Failed to create BPF map (needs root or CAP_BPF)")?;
+    let watch = Map::create(bpf::MAP_HASH, 4, 4, WATCHED.len() as u32)?;
+    for w in WATCHED {
+        let arg = match w.target {
+            Target::Path(i) => i as u32,
+            Target::Fd | Target::Opaque => NO_PATH,
+        };
+        watch.update(&(w.nr as u32).to_ne_bytes(), &arg.to_ne_bytes())?;
+    }
+    let mut events = RingBuf::new(RINGBUF_LEN).context("Failed to create BPF ring buffer")?;
+
+    let sys_enter = bpf::load_program(&sys_enter_program(&pids, &watch, &events))?;
+    let fork = bpf::load_program(&fork_program(&pids, child_pid_offset))?;
+    let attached = [
+        bpf::attach(&tracefs, "raw_syscalls/sys_enter", &sys_enter)?,
+        bpf::attach(&tracefs, "sched/sched_process_fork", &fork)?,
+    ];
+
+    // Tracked while spawning, so the fork program picks up the child
+    let me = std::process::id().to_ne_bytes();
+    pids.update(&me, &1u32.to_ne_bytes())?;
+    let spawned = cmd.spawn();
+    pids.delete(&me)?;
+    let mut child = spawned.context("Failed to spawn command")?;
+    let leader = child.id();
+
+    let classifier = Classifier::new(manifest, root);
+    let mut coverage = Coverage::default();
+    let consume = |coverage: &mut Coverage, record: &[u8]| {
+        if record.len() < RECORD_LEN as usize {
+            return;
+        }
+        let pid = u32::from_ne_bytes(record[0..4].try_into().unwrap());
+        if pid == std::process::id() || (pid == leader && !include_leader) {
+            return;
+        }
+        let nr = u32::from_ne_bytes(record[4..8].try_into().unwrap()) as i64;
+        let args = [
+            u64::from_ne_bytes(record[8..16].try_into().unwrap()),
+            u64::from_ne_bytes(record[16..24].try_into().unwrap()),
+        ];
+        let path = &record[24..];
+        let path = &path[..path.iter().position(|&b| b == 0).unwrap_or(path.len())];
+        coverage.add(&classifier, pid as i32, nr, args, path);
+    };
+
+    let status = loop {
+        events.wait(100);
+        events.drain(|r| consume(&mut coverage, r));
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+    };
+    events.drain(|r| consume(&mut coverage, r));
+    drop(attached);
+    Ok((crate::exit_code(status), coverage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+    use vrift_manifest::VnodeEntry;
+
+    fn manifest() -> Manifest {
+        let mut manifest = Manifest::new();
+        manifest.insert(
+            "/src/main.rs",
+            VnodeEntry::new_file(Default::default(), 1, 0, 0o644),
+        );
+        manifest.insert("/old.rs", VnodeEntry::new_whiteout(0));
+        manifest
+    }
+
+    #[test]
+    fn test_classify() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let classifier = Classifier::new(&manifest(), &root);
+        let pid = std::process::id() as i32;
+        let mut coverage = Coverage::default();
+
+        let path = root.join("src/main.rs");
+        coverage.add(
+            &classifier,
+            pid,
+            libc::SYS_openat,
+            [libc::AT_FDCWD as u64, 0],
+            path.as_os_str().as_bytes(),
+        );
+        coverage.add(
+            &classifier,
+            pid,
+            libc::SYS_statx,
+            [libc::AT_FDCWD as u64, 0],
+            root.join("src").as_os_str().as_bytes(),
+        );
+        coverage.add(
+            &classifier,
+            pid,
+            libc::SYS_statx,
+            [libc::AT_FDCWD as u64, 0],
+            root.join("unknown").as_os_str().as_bytes(),
+        );
+        coverage.add(&classifier, pid, libc::SYS_io_uring_enter, [3, 0], b"");
+        coverage.add(&classifier, pid, libc::SYS_write, [1, 0], b"");
+
+        assert_eq!(coverage.syscalls_seen, 4);
+        assert_eq!(coverage.io_uring, 1);
+        assert_eq!(coverage.total(), 2);
+        assert_eq!(
+            coverage.gaps["openat"],
+            SyscallGaps {
+                calls: 1,
+                virtual_only: 1,
+                examples: vec!["/src/main.rs".into()],
+            }
+        );
+        // /src exists on disk
+        assert_eq!(coverage.gaps["statx"].virtual_only, 0);
+        assert_eq!(
+            classifier.key(&root.join("old.rs")).as_deref(),
+            Some("/old.rs")
+        );
+    }
+
+    /// End to end, where BPF is allowed: a plain `cat` of a manifest path
+    /// has no shim in front of it, so its open is a gap
+    #[test]
+    fn test_record_cat() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let mut cmd = Command::new("cat");
+        cmd.arg(root.join("src/main.rs"))
+            .stderr(std::process::Stdio::null());
+        let (code, coverage) = match record(&manifest(), &root, &mut cmd, true) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("skipping: {:#}", e);
+                return;
+            }
+        };
+        assert_eq!(code, 1);
+        assert_eq!(
+            coverage.paths.get("/src/main.rs"),
+            Some(&1),
+            "{:#?}",
+            coverage
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! # vrift-trace
+//!
+//! Syscall-level interception for `vrift run --backend trace`: for programs
+//! `LD_PRELOAD` can't reach — static binaries (Go, musl), or anything that
+//! issues syscalls directly.
+//!
+//! A supervisor process rewrites the path syscalls (`open*`, `stat`
+//! family, `access`/`faccessat*`) of the whole child process tree so that
+//! paths under the project root are served from the manifest and CAS:
+//!
+//! - **seccomp** (Linux 5.9+): user notifications; only the path syscalls
+//!   leave the kernel
+//! - **ptrace** (x86_64): fallback where seccomp listeners are unavailable;
+//!   every syscall stops, so expect a large slowdown
+//!
+//! ## Limits
+//!
+//! - Read-only: writes, creates and truncations go to the real filesystem
+//! - Directories only in the manifest exist but list as empty
+//! - Symlinks and chunked files fall through to the real filesystem
+//! - Native ABI only; 32-bit tracees run untouched
+
+pub mod resolve;
+
+#[cfg(target_os = "linux")]
+mod bpf;
+#[cfg(target_os = "linux")]
+pub mod coverage;
+#[cfg(target_os = "linux")]
+mod mem;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod ptrace;
+#[cfg(target_os = "linux")]
+mod seccomp;
+#[cfg(target_os = "linux")]
+mod syscalls;
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Result;
+use vrift_manifest::Manifest;
+
+pub use resolve::Resolver;
+
+/// Interception mechanism
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// seccomp when the kernel supports it, otherwise ptrace
+    Auto,
+    Seccomp,
+    Ptrace,
+}
+
+/// Run `cmd` with its path syscalls served from `manifest`, projected at
+/// `root`, until it and everything it started have exited. Returns its exit
+/// code (128 + signal when killed).
+#[cfg(target_os = "linux")]
+pub fn run(
+    manifest: &Manifest,
+    root: &Path,
+    cas_root: &Path,
+    mode: Mode,
+    cmd: &mut Command,
+) -> Result<i32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = match mode {
+        Mode::Auto if seccomp_supported() => Mode::Seccomp,
+        Mode::Auto => Mode::Ptrace,
+        other => other,
+    };
+
+    let empty_dir = std::env::temp_dir().join(format!("vrift-trace-{}", std::process::id()));
+    std::fs::create_dir_all(&empty_dir)?;
+    std::fs::set_permissions(&empty_dir, std::fs::Permissions::from_mode(0o555))?;
+    let resolver = Resolver::new(manifest, root, cas_root, &empty_dir)?;
+
+    tracing::debug!(?mode, root = %resolver.root().display(), "trace: starting");
+    let result = match mode {
+        Mode::Seccomp => seccomp::run(&resolver, cmd),
+        Mode::Ptrace => run_ptrace(&resolver, cmd),
+        Mode::Auto => unreachable!(),
+    };
+    let _ = std::fs::remove_dir(&empty_dir);
+    tracing::debug!(
+        redirected = seccomp::REDIRECTED.load(std::sync::atomic::Ordering::Relaxed),
+        "trace: done"
+    );
+    result
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn run(
+    _manifest: &Manifest,
+    _root: &Path,
+    _cas_root: &Path,
+    _mode: Mode,
+    _cmd: &mut Command,
+) -> Result<i32> {
+    anyhow::bail!("the trace backend is only available on Linux")
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn run_ptrace(resolver: &Resolver, cmd: &mut Command) -> Result<i32> {
+    ptrace::run(resolver, cmd)
+}
+
+#[cfg(all(target_os = "linux", not(target_arch = "x86_64")))]
+fn run_ptrace(_resolver: &Resolver, _cmd: &mut Command) -> Result<i32> {
+    anyhow::bail!("the ptrace backend is only implemented for x86_64")
+}
+
+/// seccomp listeners need `SECCOMP_USER_NOTIF_FLAG_CONTINUE` (5.5) and
+/// `SECCOMP_IOCTL_NOTIF_ADDFD` (5.9)
+#[cfg(target_os = "linux")]
+fn seccomp_supported() -> bool {
+    let release = std::fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default();
+    kernel_at_least(&release, (5, 9)) && seccomp::available()
+}
+
+#[cfg(target_os = "linux")]
+fn kernel_at_least(release: &str, min: (u32, u32)) -> bool {
+    let mut parts = release
+        .trim()
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|p| p.parse::<u32>().unwrap_or(0));
+    let major = parts.next().unwrap_or(0);
+    let minor = parts.next().unwrap_or(0);
+    (major, minor) >= min
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn exit_code(status: std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status
+        .code()
+        .or_else(|| status.signal().map(|sig| 128 + sig))
+        .unwrap_or(1)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    /// `cat` of a file only the manifest has, under each backend
+    #[test]
+    fn test_run_cat() {
+        use vrift_cas::CasStore;
+        use vrift_manifest::VnodeEntry;
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let cas_root = temp.path().join("cas");
+        let hash = CasStore::new(&cas_root)
+            .unwrap()
+            .store(b"virtual\n")
+            .unwrap();
+        let mut manifest = Manifest::new();
+        manifest.insert("/src/a.txt", VnodeEntry::new_file(hash, 8, 7, 0o644));
+        manifest.insert("/gone.txt", VnodeEntry::new_whiteout(9));
+        let root = temp.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("gone.txt"), b"real\n").unwrap();
+
+        let mut modes = vec![Mode::Ptrace];
+        if seccomp_supported() {
+            modes.push(Mode::Seccomp);
+        }
+        for mode in modes {
+            let out = temp.path().join("out");
+            let mut cmd = Command::new("cat");
+            cmd.arg(root.join("src/a.txt"))
+                .stdout(std::fs::File::create(&out).unwrap());
+            assert_eq!(run(&manifest, &root, &cas_root, mode, &mut cmd).unwrap(), 0);
+            assert_eq!(std::fs::read(&out).unwrap(), b"virtual\n", "{:?}", mode);
+
+            let mut cmd = Command::new("cat");
+            cmd.arg(root.join("gone.txt"))
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null());
+            assert_eq!(
+                run(&manifest, &root, &cas_root, mode, &mut cmd).unwrap(),
+                1,
+                "{:?}",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn test_kernel_at_least() {
+        assert!(kernel_at_least("6.18.44-fc-v130\n", (5, 9)));
+        assert!(kernel_at_least("5.9.0", (5, 9)));
+        assert!(!kernel_at_least("5.4.0-150-generic", (5, 9)));
+        assert!(!kernel_at_least("", (5, 9)));
+    }
+}
@@ -0,0 +1,85 @@
+//! Reading and writing tracee memory (`process_vm_readv` / `writev`)
+
+use std::io;
+
+/// Longest path accepted, as the kernel's `PATH_MAX`
+const PATH_MAX: usize = 4096;
+const PAGE: u64 = 4096;
+
+pub(crate) fn read(pid: i32, addr: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    let local = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: len,
+    };
+    let remote = libc::iovec {
+        iov_base: addr as *mut libc::c_void,
+        iov_len: len,
+    };
+    let n = unsafe { libc::process_vm_readv(pid, &local, 1, &remote, 1, 0) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    buf.truncate(n as usize);
+    Ok(buf)
+}
+
+pub(crate) fn write(pid: i32, addr: u64, data: &[u8]) -> io::Result<()> {
+    let local = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let remote = libc::iovec {
+        iov_base: addr as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let n = unsafe { libc::process_vm_writev(pid, &local, 1, &remote, 1, 0) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if n as usize != data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            "short write to tracee",
+        ));
+    }
+    Ok(())
+}
+
+/// NUL-terminated string at `addr`, without the NUL. Reads a page at a
+/// time so a string ending just before an unmapped page still reads.
+pub(crate) fn read_cstring(pid: i32, addr: u64) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut at = addr;
+    while out.len() < PATH_MAX {
+        let chunk = (PAGE - at % PAGE) as usize;
+        let bytes = read(pid, at, chunk)?;
+        if bytes.is_empty() {
+            return Err(io::Error::from_raw_os_error(libc::EFAULT));
+        }
+        if let Some(end) = bytes.iter().position(|&b| b == 0) {
+            out.extend_from_slice(&bytes[..end]);
+            return Ok(out);
+        }
+        out.extend_from_slice(&bytes);
+        at += bytes.len() as u64;
+    }
+    Err(io::Error::from_raw_os_error(libc::ENAMETOOLONG))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_own_memory() {
+        let pid = std::process::id() as i32;
+        let text = c"/src/main.rs";
+        let got = read_cstring(pid, text.as_ptr() as u64).unwrap();
+        assert_eq!(got, b"/src/main.rs");
+
+        let mut target = [0u8; 4];
+        write(pid, target.as_mut_ptr() as u64, b"abcd").unwrap();
+        assert_eq!(&target, b"abcd");
+    }
+}
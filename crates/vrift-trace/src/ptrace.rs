@@ -0,0 +1,267 @@
+//! ptrace fallback backend (x86_64)
+//!
+//! For kernels or sandboxes without seccomp user notification. Every
+//! syscall of every process in the tree stops twice, so this is much slower
+//! than the seccomp backend:
+//!
+//! - entry: a redirected path is written below the tracee's stack red zone
+//!   and the argument register pointed at it; tombstoned paths and
+//!   permission checks skip the syscall (`orig_rax = -1`)
+//! - exit: the skipped syscall's result is set, and stat results get the
+//!   manifest's mode and mtime
+
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use anyhow::{Context, Result};
+
+use crate::mem;
+use crate::resolve::{Meta, Resolver};
+use crate::syscalls::{self, Action, Op, StatKind};
+
+/// x86_64 red zone: leaf functions may use these bytes below `rsp`
+const RED_ZONE: u64 = 128;
+
+/// Work left for a tracee's syscall-exit stop
+enum Pending {
+    /// The syscall was skipped; report this result
+    Return(i64),
+    /// Patch the manifest's metadata into the stat buffer on success
+    PatchStat {
+        buf: u64,
+        kind: StatKind,
+        meta: Meta,
+    },
+}
+
+#[derive(Default)]
+struct Tracee {
+    in_syscall: bool,
+    pending: Option<Pending>,
+    /// Auto-attached child whose initial SIGSTOP is still due
+    awaiting_stop: bool,
+}
+
+fn ptrace(request: libc::c_uint, pid: i32, addr: usize, data: usize) -> io::Result<libc::c_long> {
+    let rc = unsafe {
+        libc::ptrace(
+            request,
+            pid,
+            addr as *mut libc::c_void,
+            data as *mut libc::c_void,
+        )
+    };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(rc)
+}
+
+fn get_regs(pid: i32) -> io::Result<libc::user_regs_struct> {
+    let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+    ptrace(libc::PTRACE_GETREGS, pid, 0, &mut regs as *mut _ as usize)?;
+    Ok(regs)
+}
+
+fn set_regs(pid: i32, regs: &libc::user_regs_struct) -> io::Result<()> {
+    ptrace(libc::PTRACE_SETREGS, pid, 0, regs as *const _ as usize).map(|_| ())
+}
+
+fn args(regs: &libc::user_regs_struct) -> [u64; 6] {
+    [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9]
+}
+
+fn set_arg(regs: &mut libc::user_regs_struct, index: usize, value: u64) {
+    match index {
+        0 => regs.rdi = value,
+        1 => regs.rsi = value,
+        2 => regs.rdx = value,
+        3 => regs.r10 = value,
+        4 => regs.r8 = value,
+        _ => regs.r9 = value,
+    }
+}
+
+/// Spawn `cmd` traced and run it to completion, including every process
+/// it starts. Returns the child's exit code.
+pub(crate) fn run(resolver: &Resolver, cmd: &mut Command) -> Result<i32> {
+    unsafe {
+        cmd.pre_exec(|| {
+            ptrace(libc::PTRACE_TRACEME, 0, 0, 0)?;
+            Ok(())
+        });
+    }
+    let child = cmd.spawn().context("Failed to spawn traced command")?;
+    let main = child.id() as i32;
+
+    // Stopped by the SIGTRAP of its exec
+    let mut status = 0;
+    if unsafe { libc::waitpid(main, &mut status, libc::__WALL) } != main
+        || !libc::WIFSTOPPED(status)
+    {
+        anyhow::bail!("traced command did not stop after exec");
+    }
+    let options = libc::PTRACE_O_TRACESYSGOOD
+        | libc::PTRACE_O_TRACEFORK
+        | libc::PTRACE_O_TRACEVFORK
+        | libc::PTRACE_O_TRACECLONE
+        | libc::PTRACE_O_TRACEEXEC
+        | libc::PTRACE_O_EXITKILL;
+    ptrace(libc::PTRACE_SETOPTIONS, main, 0, options as usize).context("PTRACE_SETOPTIONS")?;
+
+    let mut tracees: HashMap<i32, Tracee> = HashMap::new();
+    tracees.insert(main, Tracee::default());
+    let mut exit_code = 1;
+    resume(main, 0);
+
+    loop {
+        let pid = unsafe { libc::waitpid(-1, &mut status, libc::__WALL) };
+        if pid < 0 {
+            match io::Error::last_os_error().raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // No tracees left
+                _ => break,
+            }
+        }
+        if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+            tracees.remove(&pid);
+            if pid == main {
+                exit_code = if libc::WIFEXITED(status) {
+                    libc::WEXITSTATUS(status)
+                } else {
+                    128 + libc::WTERMSIG(status)
+                };
+            }
+            continue;
+        }
+        if !libc::WIFSTOPPED(status) {
+            continue;
+        }
+
+        let signal = libc::WSTOPSIG(status);
+        let event = (status >> 16) & 0xff;
+        if signal == libc::SIGTRAP | 0x80 {
+            let tracee = tracees.entry(pid).or_default();
+            if tracee.in_syscall {
+                tracee.in_syscall = false;
+                if let Some(pending) = tracee.pending.take() {
+                    on_exit(pid, pending);
+                }
+            } else {
+                tracee.in_syscall = true;
+                tracee.pending = on_entry(resolver, pid);
+            }
+            resume(pid, 0);
+        } else if signal == libc::SIGTRAP && event != 0 {
+            if matches!(
+                event,
+                libc::PTRACE_EVENT_FORK | libc::PTRACE_EVENT_VFORK | libc::PTRACE_EVENT_CLONE
+            ) {
+                let mut new_pid: libc::c_ulong = 0;
+                if ptrace(
+                    libc::PTRACE_GETEVENTMSG,
+                    pid,
+                    0,
+                    &mut new_pid as *mut _ as usize,
+                )
+                .is_ok()
+                {
+                    tracees.entry(new_pid as i32).or_insert_with(|| Tracee {
+                        awaiting_stop: true,
+                        ..Tracee::default()
+                    });
+                }
+            }
+            resume(pid, 0);
+        } else if signal == libc::SIGSTOP && tracees.get(&pid).is_none_or(|t| t.awaiting_stop) {
+            // A new tracee's attach stop (it may beat the parent's event)
+            tracees.entry(pid).or_default().awaiting_stop = false;
+            resume(pid, 0);
+        } else {
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            let group_stop = ptrace(
+                libc::PTRACE_GETSIGINFO,
+                pid,
+                0,
+                &mut info as *mut _ as usize,
+            )
+            .is_err();
+            // Deliver real signals; swallow group-stops
+            resume(pid, if group_stop { 0 } else { signal });
+        }
+    }
+    drop(child);
+    Ok(exit_code)
+}
+
+fn resume(pid: i32, signal: i32) {
+    // ESRCH: killed meanwhile; its exit shows up in waitpid
+    let _ = ptrace(libc::PTRACE_SYSCALL, pid, 0, signal as usize);
+}
+
+fn on_entry(resolver: &Resolver, pid: i32) -> Option<Pending> {
+    let mut regs = get_regs(pid).ok()?;
+    let call = syscalls::lookup(regs.orig_rax as i64)?;
+    let args = args(&regs);
+    let raw = mem::read_cstring(pid, args[call.path]).ok()?;
+    if raw.is_empty() {
+        return None;
+    }
+    let path = syscalls::absolute(pid, call.dirfd.map(|i| args[i] as i32), &raw)?;
+
+    let result = match syscalls::decide(resolver, call, &args, &path) {
+        Action::Continue => return None,
+        Action::Fail(errno) => -(errno as i64),
+        Action::Return(val) => val,
+        Action::Redirect { path, meta } => {
+            let mut bytes = path.as_os_str().as_bytes().to_vec();
+            bytes.push(0);
+            let scratch = (regs.rsp - RED_ZONE - bytes.len() as u64) & !15;
+            if let Err(e) = mem::write(pid, scratch, &bytes) {
+                tracing::debug!(pid, error = %e, "trace: no room for redirected path");
+                return None;
+            }
+            set_arg(&mut regs, call.path, scratch);
+            set_regs(pid, &regs).ok()?;
+            crate::seccomp::REDIRECTED.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            return match (call.op, meta) {
+                (Op::Stat { buf, kind }, Some(meta)) => Some(Pending::PatchStat {
+                    buf: args[buf],
+                    kind,
+                    meta,
+                }),
+                _ => None,
+            };
+        }
+    };
+    // Skip the syscall; its result is set at the exit stop
+    regs.orig_rax = u64::MAX;
+    set_regs(pid, &regs).ok()?;
+    Some(Pending::Return(result))
+}
+
+fn on_exit(pid: i32, pending: Pending) {
+    let Ok(mut regs) = get_regs(pid) else {
+        return;
+    };
+    match pending {
+        Pending::Return(val) => {
+            regs.rax = val as u64;
+            let _ = set_regs(pid, &regs);
+        }
+        Pending::PatchStat { buf, kind, meta } => {
+            if regs.rax != 0 {
+                return;
+            }
+            if let Ok(mut bytes) = mem::read(pid, buf, kind.size()) {
+                if bytes.len() == kind.size() {
+                    syscalls::patch_stat(&mut bytes, kind, meta);
+                    let _ = mem::write(pid, buf, &bytes);
+                }
+            }
+        }
+    }
+}
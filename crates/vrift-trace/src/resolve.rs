@@ -0,0 +1,239 @@
+//! Manifest lookups for intercepted paths — shared by both backends
+//!
+//! The supervisor sees the path a tracee passed to the kernel, made absolute
+//! against its cwd or dirfd. If the manifest has an opinion about it, the
+//! syscall is pointed at a real path instead: the CAS blob for a file, an
+//! empty stand-in for a directory that only exists in the manifest.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use vrift_cas::CasStore;
+use vrift_manifest::{Manifest, VnodeEntry};
+
+/// Metadata the manifest records, patched over what the kernel reports for
+/// the redirect target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Meta {
+    pub mode: u32,
+    pub mtime: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redirect {
+    /// Serve this real path instead
+    Path { path: PathBuf, meta: Option<Meta> },
+    /// Tombstoned: fail with `ENOENT`
+    Missing,
+}
+
+pub struct Resolver {
+    root: PathBuf,
+    entries: HashMap<String, VnodeEntry>,
+    /// Ancestors of entries, which exist as directories even unrecorded
+    dirs: HashSet<String>,
+    cas: CasStore,
+    empty_dir: PathBuf,
+}
+
+impl Resolver {
+    /// `root` is where the manifest is projected; `empty_dir` stands in for
+    /// directories that only the manifest has
+    pub fn new(
+        manifest: &Manifest,
+        root: &Path,
+        cas_root: &Path,
+        empty_dir: &Path,
+    ) -> Result<Self> {
+        let cas = CasStore::new(cas_root)
+            .with_context(|| format!("Failed to open CAS: {}", cas_root.display()))?;
+        let mut entries = HashMap::new();
+        let mut dirs = HashSet::new();
+        for (path, vnode) in manifest.iter() {
+            let mut parent = parent_key(path);
+            while let Some(dir) = parent {
+                if !dirs.insert(dir.to_string()) {
+                    break;
+                }
+                parent = parent_key(dir);
+            }
+            entries.insert(path.to_string(), vnode.clone());
+        }
+        Ok(Self {
+            root: normalize(root),
+            entries,
+            dirs,
+            cas,
+            empty_dir: empty_dir.to_path_buf(),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Manifest key (`/src/main.rs`) of an absolute path under the root
+    pub fn key(&self, path: &Path) -> Option<String> {
+        let rest = normalize(path).strip_prefix(&self.root).ok()?.to_path_buf();
+        let rest = rest.to_str()?;
+        Some(format!("/{}", rest))
+    }
+
+    /// Where the syscall on `path` should go. `None` leaves it alone:
+    /// outside the root, not in the manifest, or not servable read-only
+    /// (`writes`: the caller may create, truncate or write).
+    pub fn resolve(&self, path: &Path, writes: bool) -> Option<Redirect> {
+        let key = self.key(path)?;
+        let Some(vnode) = self.entries.get(&key) else {
+            if writes || path.exists() {
+                return None;
+            }
+            // `src/..` with `src` only in the manifest: the kernel's walk
+            // fails at `src`, so take the lexical answer
+            let lexical = normalize(path);
+            if path.components().any(|c| c == Component::ParentDir) && lexical.exists() {
+                return Some(Redirect::Path {
+                    path: lexical,
+                    meta: None,
+                });
+            }
+            return self.dirs.contains(&key).then(|| self.empty_dir(None));
+        };
+        if vnode.is_whiteout() {
+            // A write may recreate it on the real filesystem
+            return (!writes).then_some(Redirect::Missing);
+        }
+        if writes || vnode.is_symlink() || vnode.is_chunked() {
+            return None;
+        }
+        let meta = Meta {
+            mode: vnode.mode,
+            mtime: vnode.mtime,
+        };
+        if vnode.is_dir() {
+            return (!path.exists()).then(|| self.empty_dir(Some(meta)));
+        }
+        let blob = self.cas.blob_path_for_hash(&vnode.content_hash)?;
+        Some(Redirect::Path {
+            path: blob,
+            meta: Some(meta),
+        })
+    }
+
+    fn empty_dir(&self, meta: Option<Meta>) -> Redirect {
+        Redirect::Path {
+            path: self.empty_dir.clone(),
+            meta,
+        }
+    }
+}
+
+fn parent_key(key: &str) -> Option<&str> {
+    match key.rfind('/') {
+        Some(0) if key.len() > 1 => Some("/"),
+        Some(0) | None => None,
+        Some(i) => Some(&key[..i]),
+    }
+}
+
+/// Lexical `.`/`..` resolution; symlinks are left to the kernel
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => {}
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn resolver(temp: &TempDir) -> (Resolver, PathBuf) {
+        let cas = CasStore::new(temp.path().join("cas")).unwrap();
+        let hash = cas.store(b"fn main() {}\n").unwrap();
+        let mut manifest = Manifest::new();
+        manifest.insert("/src", VnodeEntry::new_directory(5, 0o755));
+        manifest.insert("/src/main.rs", VnodeEntry::new_file(hash, 13, 7, 0o644));
+        manifest.insert("/gen/out/a.rs", VnodeEntry::new_file(hash, 13, 7, 0o600));
+        manifest.insert("/old.rs", VnodeEntry::new_whiteout(9));
+        let root = temp.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        let resolver = Resolver::new(
+            &manifest,
+            &root,
+            &temp.path().join("cas"),
+            &temp.path().join("empty"),
+        )
+        .unwrap();
+        (resolver, root)
+    }
+
+    #[test]
+    fn test_resolve() {
+        let temp = TempDir::new().unwrap();
+        let (resolver, root) = resolver(&temp);
+
+        assert_eq!(
+            resolver.key(&root.join("src/./x/../main.rs")).as_deref(),
+            Some("/src/main.rs")
+        );
+        assert_eq!(resolver.key(&temp.path().join("elsewhere")), None);
+
+        let Some(Redirect::Path { path, meta }) =
+            resolver.resolve(&root.join("src/main.rs"), false)
+        else {
+            panic!("file should redirect to its blob");
+        };
+        assert_eq!(std::fs::read(path).unwrap(), b"fn main() {}\n");
+        assert_eq!(
+            meta,
+            Some(Meta {
+                mode: 0o644,
+                mtime: 7
+            })
+        );
+        assert_eq!(resolver.resolve(&root.join("src/main.rs"), true), None);
+
+        assert_eq!(
+            resolver.resolve(&root.join("old.rs"), false),
+            Some(Redirect::Missing)
+        );
+        assert_eq!(resolver.resolve(&root.join("old.rs"), true), None);
+        assert_eq!(resolver.resolve(&root.join("new.rs"), false), None);
+
+        // Directories: the real one wins when present
+        let empty = |meta| Redirect::Path {
+            path: temp.path().join("empty"),
+            meta,
+        };
+        assert_eq!(
+            resolver.resolve(&root.join("src"), false),
+            Some(empty(Some(Meta {
+                mode: 0o755,
+                mtime: 5
+            })))
+        );
+        assert_eq!(
+            resolver.resolve(&root.join("gen/out"), false),
+            Some(empty(None))
+        );
+        assert_eq!(
+            resolver.resolve(&root.join("gen/../src/.."), false),
+            Some(Redirect::Path {
+                path: root.clone(),
+                meta: None
+            })
+        );
+        std::fs::create_dir_all(root.join("gen/out")).unwrap();
+        assert_eq!(resolver.resolve(&root.join("gen/out"), false), None);
+    }
+}
@@ -0,0 +1,389 @@
+//! seccomp user-notification backend (Linux 5.9+)
+//!
+//! The child installs a filter that turns the path syscalls into
+//! notifications and hands the listener fd back over a socketpair before
+//! exec. Supervisor threads then answer each notification:
+//!
+//! - not ours: `SECCOMP_USER_NOTIF_FLAG_CONTINUE`, the kernel runs it as is
+//! - opens: the supervisor opens the blob and installs the fd in the
+//!   tracee (`SECCOMP_IOCTL_NOTIF_ADDFD`)
+//! - stats: the supervisor stats the blob and writes the result into the
+//!   tracee's buffer
+//!
+//! The filter is inherited across fork and exec, so the whole process tree
+//! is covered, static binaries included. It needs `no_new_privs`: setuid
+//! programs don't gain privileges under it.
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, Result};
+
+use crate::mem;
+use crate::resolve::Resolver;
+use crate::syscalls::{self, Action, Op, StatKind};
+
+const IOC_WRITE: u32 = 1;
+const IOC_READ: u32 = 2;
+
+const fn ioc(dir: u32, nr: u32, size: usize) -> u64 {
+    ((dir << 30) | ((size as u32) << 16) | ((b'!' as u32) << 8) | nr) as u64
+}
+
+const NOTIF_RECV: u64 = ioc(
+    IOC_READ | IOC_WRITE,
+    0,
+    std::mem::size_of::<libc::seccomp_notif>(),
+);
+const NOTIF_SEND: u64 = ioc(
+    IOC_READ | IOC_WRITE,
+    1,
+    std::mem::size_of::<libc::seccomp_notif_resp>(),
+);
+const NOTIF_ID_VALID: u64 = ioc(IOC_WRITE, 2, 8);
+const NOTIF_ADDFD: u64 = ioc(
+    IOC_WRITE,
+    3,
+    std::mem::size_of::<libc::seccomp_notif_addfd>(),
+);
+
+/// Notifications answered by redirecting, for the summary
+pub(crate) static REDIRECTED: AtomicU64 = AtomicU64::new(0);
+
+fn stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jeq(k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// BPF program: native-arch path syscalls notify, everything else runs
+pub(crate) fn filter() -> Vec<libc::sock_filter> {
+    let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+    let ret = libc::BPF_RET | libc::BPF_K;
+    let mut prog = vec![
+        // seccomp_data.arch
+        stmt(load, 4),
+        jeq(syscalls::AUDIT_ARCH, 1, 0),
+        stmt(ret, libc::SECCOMP_RET_ALLOW),
+        // seccomp_data.nr
+        stmt(load, 0),
+    ];
+    for call in syscalls::CALLS {
+        prog.push(jeq(call.nr as u32, 0, 1));
+        prog.push(stmt(ret, libc::SECCOMP_RET_USER_NOTIF));
+    }
+    prog.push(stmt(ret, libc::SECCOMP_RET_ALLOW));
+    prog
+}
+
+/// Install `prog` on the calling thread; returns the listener fd. Only
+/// raw syscalls, so it is safe between fork and exec.
+fn install(prog: &[libc::sock_filter]) -> io::Result<RawFd> {
+    let fprog = libc::sock_fprog {
+        len: prog.len() as u16,
+        filter: prog.as_ptr() as *mut libc::sock_filter,
+    };
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_NEW_LISTENER,
+            &fprog as *const libc::sock_fprog,
+        );
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd as RawFd)
+    }
+}
+
+#[repr(C, align(8))]
+struct CmsgBuf([u8; 64]);
+
+/// Send `fd` over a unix socket (SCM_RIGHTS); no allocation
+fn send_fd(sock: RawFd, fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: (&mut byte as *mut u8).cast(),
+            iov_len: 1,
+        };
+        let mut cmsg_buf = CmsgBuf([0; 64]);
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.0.as_mut_ptr().cast();
+        msg.msg_controllen = libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<RawFd>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>(), fd);
+        if libc::sendmsg(sock, &msg, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn recv_fd(sock: RawFd) -> io::Result<OwnedFd> {
+    unsafe {
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: (&mut byte as *mut u8).cast(),
+            iov_len: 1,
+        };
+        let mut cmsg_buf = CmsgBuf([0; 64]);
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.0.as_mut_ptr().cast();
+        msg.msg_controllen = cmsg_buf.0.len() as _;
+        if libc::recvmsg(sock, &mut msg, libc::MSG_CMSG_CLOEXEC) <= 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        if cmsg.is_null() || (*cmsg).cmsg_type != libc::SCM_RIGHTS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no listener fd received",
+            ));
+        }
+        let fd = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>());
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+/// Whether this kernel lets an unprivileged process install a listener.
+/// Probed in a forked child, since a filter can't be removed.
+pub(crate) fn available() -> bool {
+    let prog = filter();
+    unsafe {
+        let pid = libc::fork();
+        if pid == 0 {
+            libc::_exit(if install(&prog).is_ok() { 0 } else { 1 });
+        }
+        if pid < 0 {
+            return false;
+        }
+        let mut status = 0;
+        libc::waitpid(pid, &mut status, 0) == pid
+            && libc::WIFEXITED(status)
+            && libc::WEXITSTATUS(status) == 0
+    }
+}
+
+/// Spawn `cmd` under the filter and answer its notifications until every
+/// process in the tree has exited. Returns the child's exit code.
+pub(crate) fn run(resolver: &Resolver, cmd: &mut Command) -> Result<i32> {
+    let (parent_sock, child_sock) = UnixStream::pair().context("socketpair")?;
+    let prog = filter();
+    let child_fd = child_sock.as_raw_fd();
+    unsafe {
+        cmd.pre_exec(move || {
+            let listener = install(&prog)?;
+            send_fd(child_fd, listener)?;
+            libc::close(listener);
+            Ok(())
+        });
+    }
+    let mut child = cmd.spawn().context("Failed to spawn traced command")?;
+    drop(child_sock);
+    let listener =
+        recv_fd(parent_sock.as_raw_fd()).context("Failed to receive seccomp listener")?;
+
+    let workers = std::thread::available_parallelism().map_or(2, |n| n.get().min(4));
+    let status = std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| serve(resolver, listener.as_raw_fd()));
+        }
+        child.wait()
+    })?;
+    Ok(crate::exit_code(status))
+}
+
+/// Answer notifications until no process uses the filter any more
+fn serve(resolver: &Resolver, listener: RawFd) {
+    loop {
+        let mut poll = libc::pollfd {
+            fd: listener,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let n = unsafe { libc::poll(&mut poll, 1, -1) };
+        if n < 0 {
+            if io::Error::last_os_error().raw_os_error() == Some(libc::EINTR) {
+                continue;
+            }
+            return;
+        }
+        if poll.revents & libc::POLLIN == 0 {
+            // POLLHUP: every process in the tree is gone
+            return;
+        }
+
+        let mut req: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(listener, NOTIF_RECV as _, &mut req) } != 0 {
+            match io::Error::last_os_error().raw_os_error() {
+                // Another worker took it, or the tracee died meanwhile
+                Some(libc::ENOENT | libc::EINTR | libc::EAGAIN) => continue,
+                _ => return,
+            }
+        }
+        handle(resolver, listener, &req);
+    }
+}
+
+fn id_valid(listener: RawFd, id: u64) -> bool {
+    unsafe { libc::ioctl(listener, NOTIF_ID_VALID as _, &id) == 0 }
+}
+
+fn respond(listener: RawFd, id: u64, val: i64, error: i32, flags: u32) {
+    let resp = libc::seccomp_notif_resp {
+        id,
+        val,
+        error,
+        flags,
+    };
+    // ENOENT: the tracee died; nothing to answer
+    unsafe { libc::ioctl(listener, NOTIF_SEND as _, &resp) };
+}
+
+fn handle(resolver: &Resolver, listener: RawFd, req: &libc::seccomp_notif) {
+    let action = decide(resolver, req).unwrap_or(Action::Continue);
+    // The tracee's memory was read above: make sure it still belongs to the
+    // process that made this call before acting on it
+    if !id_valid(listener, req.id) {
+        return;
+    }
+    match action {
+        Action::Continue => respond(
+            listener,
+            req.id,
+            0,
+            0,
+            libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32,
+        ),
+        Action::Fail(errno) => respond(listener, req.id, 0, -errno, 0),
+        Action::Return(val) => respond(listener, req.id, val, 0, 0),
+        Action::Redirect { path, meta } => {
+            REDIRECTED.fetch_add(1, Ordering::Relaxed);
+            let result = redirect(req, &path, meta, listener);
+            match result {
+                Ok(Some(val)) => respond(listener, req.id, val, 0, 0),
+                // ADDFD already answered
+                Ok(None) => {}
+                Err(e) => respond(
+                    listener,
+                    req.id,
+                    0,
+                    -e.raw_os_error().unwrap_or(libc::EIO),
+                    0,
+                ),
+            }
+        }
+    }
+}
+
+fn decide(resolver: &Resolver, req: &libc::seccomp_notif) -> Option<Action> {
+    let call = syscalls::lookup(req.data.nr as i64)?;
+    let pid = req.pid as i32;
+    let args = req.data.args;
+    let raw = mem::read_cstring(pid, args[call.path]).ok()?;
+    if raw.is_empty() {
+        // AT_EMPTY_PATH: about the dirfd itself
+        return None;
+    }
+    let path = syscalls::absolute(pid, call.dirfd.map(|i| args[i] as i32), &raw)?;
+    Some(syscalls::decide(resolver, call, &args, &path))
+}
+
+/// Run the call against `path` in the supervisor. `Ok(None)` when the
+/// kernel already delivered the result.
+fn redirect(
+    req: &libc::seccomp_notif,
+    path: &std::path::Path,
+    meta: Option<crate::resolve::Meta>,
+    listener: RawFd,
+) -> io::Result<Option<i64>> {
+    let call = syscalls::lookup(req.data.nr as i64).expect("decided above");
+    let args = req.data.args;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    match call.op {
+        Op::Open { flags } => {
+            let flags = args[flags] as i32;
+            let fd = unsafe { libc::open(c_path.as_ptr(), flags | libc::O_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            add_fd(listener, req.id, &fd, flags & libc::O_CLOEXEC != 0)
+        }
+        Op::Stat { buf, kind } => {
+            let mut bytes = vec![0u8; kind.size()];
+            let rc = match kind {
+                StatKind::Stat => unsafe { libc::stat(c_path.as_ptr(), bytes.as_mut_ptr().cast()) },
+                StatKind::Statx => unsafe {
+                    libc::statx(
+                        libc::AT_FDCWD,
+                        c_path.as_ptr(),
+                        args[2] as i32 & !libc::AT_EMPTY_PATH,
+                        args[3] as u32,
+                        bytes.as_mut_ptr().cast(),
+                    )
+                },
+            };
+            if rc != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if let Some(meta) = meta {
+                syscalls::patch_stat(&mut bytes, kind, meta);
+            }
+            mem::write(req.pid as i32, args[buf], &bytes)
+                .map_err(|_| io::Error::from_raw_os_error(libc::EFAULT))?;
+            Ok(Some(0))
+        }
+        Op::Access { .. } | Op::Xattr { .. } => unreachable!("answered without a redirect"),
+    }
+}
+
+/// Install `fd` in the tracee as the syscall's result
+fn add_fd(listener: RawFd, id: u64, fd: &OwnedFd, cloexec: bool) -> io::Result<Option<i64>> {
+    let mut addfd = libc::seccomp_notif_addfd {
+        id,
+        flags: libc::SECCOMP_ADDFD_FLAG_SEND as u32,
+        srcfd: fd.as_raw_fd() as u32,
+        newfd: 0,
+        newfd_flags: if cloexec { libc::O_CLOEXEC as u32 } else { 0 },
+    };
+    if unsafe { libc::ioctl(listener, NOTIF_ADDFD as _, &addfd) } >= 0 {
+        return Ok(None);
+    }
+    // Before 5.14 there is no FLAG_SEND: install, then answer separately
+    addfd.flags = 0;
+    let remote = unsafe { libc::ioctl(listener, NOTIF_ADDFD as _, &addfd) };
+    if remote < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(remote as i64))
+}
@@ -0,0 +1,238 @@
+//! Path syscalls the backends intercept, and what each one needs
+//!
+//! Only the native ABI is covered: 32-bit (and x32) tracees on x86_64 run
+//! untouched.
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::resolve::{Meta, Redirect, Resolver};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StatKind {
+    /// `struct stat` (stat, lstat, newfstatat)
+    Stat,
+    /// `struct statx`
+    Statx,
+}
+
+impl StatKind {
+    pub(crate) fn size(self) -> usize {
+        match self {
+            StatKind::Stat => std::mem::size_of::<libc::stat>(),
+            StatKind::Statx => std::mem::size_of::<libc::statx>(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Op {
+    Open {
+        flags: usize,
+    },
+    Stat {
+        buf: usize,
+        kind: StatKind,
+    },
+    Access {
+        mode: usize,
+    },
+    /// `getxattr` family (`list`: `listxattr` family)
+    Xattr {
+        list: bool,
+    },
+}
+
+/// Argument layout of one intercepted syscall
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PathCall {
+    pub nr: i64,
+    pub dirfd: Option<usize>,
+    pub path: usize,
+    pub op: Op,
+}
+
+const fn call(nr: i64, dirfd: Option<usize>, path: usize, op: Op) -> PathCall {
+    PathCall {
+        nr,
+        dirfd,
+        path,
+        op,
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+pub(crate) const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+pub(crate) const CALLS: &[PathCall] = &[
+    #[cfg(target_arch = "x86_64")]
+    call(libc::SYS_open, None, 0, Op::Open { flags: 1 }),
+    #[cfg(target_arch = "x86_64")]
+    call(
+        libc::SYS_stat,
+        None,
+        0,
+        Op::Stat {
+            buf: 1,
+            kind: StatKind::Stat,
+        },
+    ),
+    #[cfg(target_arch = "x86_64")]
+    call(
+        libc::SYS_lstat,
+        None,
+        0,
+        Op::Stat {
+            buf: 1,
+            kind: StatKind::Stat,
+        },
+    ),
+    #[cfg(target_arch = "x86_64")]
+    call(libc::SYS_access, None, 0, Op::Access { mode: 1 }),
+    call(libc::SYS_openat, Some(0), 1, Op::Open { flags: 2 }),
+    call(
+        libc::SYS_newfstatat,
+        Some(0),
+        1,
+        Op::Stat {
+            buf: 2,
+            kind: StatKind::Stat,
+        },
+    ),
+    call(
+        libc::SYS_statx,
+        Some(0),
+        1,
+        Op::Stat {
+            buf: 4,
+            kind: StatKind::Statx,
+        },
+    ),
+    call(libc::SYS_faccessat, Some(0), 1, Op::Access { mode: 2 }),
+    call(libc::SYS_faccessat2, Some(0), 1, Op::Access { mode: 2 }),
+    call(libc::SYS_getxattr, None, 0, Op::Xattr { list: false }),
+    call(libc::SYS_lgetxattr, None, 0, Op::Xattr { list: false }),
+    call(libc::SYS_listxattr, None, 0, Op::Xattr { list: true }),
+    call(libc::SYS_llistxattr, None, 0, Op::Xattr { list: true }),
+];
+
+pub(crate) fn lookup(nr: i64) -> Option<&'static PathCall> {
+    CALLS.iter().find(|c| c.nr == nr)
+}
+
+/// What the supervisor does with one intercepted call
+#[derive(Debug)]
+pub(crate) enum Action {
+    /// Not ours: let the kernel run it as issued
+    Continue,
+    /// Fail with this errno without running it
+    Fail(i32),
+    /// Succeed with this value without running it
+    Return(i64),
+    /// Run it against `path` instead; for stats, patch `meta` into the
+    /// result
+    Redirect { path: PathBuf, meta: Option<Meta> },
+}
+
+/// Decide what to do with `call`, whose path argument (made absolute) is
+/// `path`
+pub(crate) fn decide(resolver: &Resolver, call: &PathCall, args: &[u64; 6], path: &Path) -> Action {
+    let writes = match call.op {
+        Op::Open { flags } => {
+            let flags = args[flags] as i32;
+            flags & libc::O_ACCMODE != libc::O_RDONLY
+                || flags & (libc::O_CREAT | libc::O_TRUNC) != 0
+                || flags & libc::O_TMPFILE == libc::O_TMPFILE
+        }
+        Op::Access { mode } => args[mode] as i32 & libc::W_OK != 0,
+        Op::Stat { .. } | Op::Xattr { .. } => false,
+    };
+    match (resolver.resolve(path, writes), call.op) {
+        (None, _) => Action::Continue,
+        (Some(Redirect::Missing), _) => Action::Fail(libc::ENOENT),
+        // Blobs are 0444: answer permission checks from the manifest mode
+        (Some(Redirect::Path { meta, .. }), Op::Access { mode }) => {
+            let executable = meta.is_none_or(|m| m.mode & 0o111 != 0);
+            if args[mode] as i32 & libc::X_OK != 0 && !executable {
+                Action::Fail(libc::EACCES)
+            } else {
+                Action::Return(0)
+            }
+        }
+        // Manifest entries carry no extended attributes
+        (Some(Redirect::Path { .. }), Op::Xattr { list: true }) => Action::Return(0),
+        (Some(Redirect::Path { .. }), Op::Xattr { list: false }) => Action::Fail(libc::ENODATA),
+        (Some(Redirect::Path { path, meta }), _) => Action::Redirect { path, meta },
+    }
+}
+
+/// `path` made absolute against the tracee's cwd or `dirfd`
+pub(crate) fn absolute(pid: i32, dirfd: Option<i32>, path: &[u8]) -> Option<PathBuf> {
+    let path = Path::new(OsStr::from_bytes(path));
+    if path.is_absolute() {
+        return Some(path.to_path_buf());
+    }
+    let base = match dirfd {
+        None | Some(libc::AT_FDCWD) => format!("/proc/{}/cwd", pid),
+        Some(fd) => format!("/proc/{}/fd/{}", pid, fd),
+    };
+    Some(std::fs::read_link(base).ok()?.join(path))
+}
+
+/// Overwrite permissions and mtime in a stat result with the manifest's
+pub(crate) fn patch_stat(buf: &mut [u8], kind: StatKind, meta: Meta) {
+    assert_eq!(buf.len(), kind.size());
+    match kind {
+        StatKind::Stat => {
+            // SAFETY: buf holds exactly one `struct stat`
+            let mut st: libc::stat = unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+            st.st_mode = (st.st_mode & libc::S_IFMT) | (meta.mode & 0o7777);
+            st.st_mtime = meta.mtime as i64;
+            st.st_mtime_nsec = 0;
+            unsafe { std::ptr::write_unaligned(buf.as_mut_ptr().cast(), st) };
+        }
+        StatKind::Statx => {
+            // SAFETY: buf holds exactly one `struct statx`
+            let mut stx: libc::statx = unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+            stx.stx_mode = (stx.stx_mode & libc::S_IFMT as u16) | (meta.mode & 0o7777) as u16;
+            stx.stx_mtime.tv_sec = meta.mtime as i64;
+            stx.stx_mtime.tv_nsec = 0;
+            unsafe { std::ptr::write_unaligned(buf.as_mut_ptr().cast(), stx) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_stat() {
+        let mut buf = vec![0u8; StatKind::Stat.size()];
+        let mut st: libc::stat = unsafe { std::mem::zeroed() };
+        st.st_mode = libc::S_IFREG | 0o444;
+        st.st_size = 13;
+        unsafe { std::ptr::write_unaligned(buf.as_mut_ptr().cast(), st) };
+        patch_stat(
+            &mut buf,
+            StatKind::Stat,
+            Meta {
+                mode: 0o755,
+                mtime: 42,
+            },
+        );
+        let st: libc::stat = unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+        assert_eq!(st.st_mode, libc::S_IFREG | 0o755);
+        assert_eq!((st.st_size, st.st_mtime), (13, 42));
+
+        assert!(lookup(libc::SYS_openat).is_some());
+        assert!(lookup(libc::SYS_write).is_none());
+        assert!(matches!(
+            lookup(libc::SYS_lgetxattr).map(|c| c.op),
+            Some(Op::Xattr { list: false })
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+//! ManifestGet throughput with many clients querying vdir_d at once, as in
+//! a parallel build. `exclusive` takes the handler lock for every request
+//! (how vdir_d used to serve lookups); `dispatch` is what the socket does now.
+//! The handler runs with the default VDir cap, so lookups feed hot-cache
+//! admission as they do in a stock vdir_d.
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tempfile::TempDir;
+use tokio::sync::RwLock;
+use vrift_ipc::{VeloRequest, VnodeEntry};
+use vrift_manifest::lmdb::{AssetTier, LmdbManifest};
+use vrift_vdird::admission::HotCache;
+use vrift_vdird::commands::CommandHandler;
+use vrift_vdird::journal::ReingestJournal;
+use vrift_vdird::socket::dispatch;
+use vrift_vdird::state::DEFAULT_VDIR_MAX_ENTRIES;
+use vrift_vdird::vdir::VDir;
+use vrift_vdird::ProjectConfig;
+
+const FILES: usize = 10_000;
+const LOOKUPS_PER_CLIENT: usize = 256;
+
+fn handler(temp: &TempDir) -> Arc<RwLock<CommandHandler>> {
+    let config = ProjectConfig::from_project_root(temp.path().to_path_buf());
+    let vdir = VDir::create_or_open(&temp.path().join("bench.vdir")).unwrap();
+    let manifest = Arc::new(LmdbManifest::open(temp.path().join("manifest.lmdb")).unwrap());
+    for i in 0..FILES {
+        let vnode = VnodeEntry::new_file([(i % 251) as u8; 32], i as u64, 0, 0o644);
+        manifest.insert(&path(i), vnode, AssetTier::Tier2Mutable);
+    }
+    manifest.commit().unwrap();
+    let journal = ReingestJournal::open(&ReingestJournal::path_for(temp.path())).unwrap();
+    Arc::new(RwLock::new(
+        CommandHandler::new(config, vdir, manifest, journal)
+            .with_hot_cache(HotCache::new(DEFAULT_VDIR_MAX_ENTRIES)),
+    ))
+}
+
+fn path(i: usize) -> String {
+    format!("/src/mod{}/file{}.rs", i % 64, i)
+}
+
+async fn run_clients(handler: &Arc<RwLock<CommandHandler>>, clients: usize, exclusive: bool) {
+    let tasks: Vec<_> = (0..clients)
+        .map(|client| {
+            let handler = Arc::clone(handler);
+            tokio::spawn(async move {
+                for n in 0..LOOKUPS_PER_CLIENT {
+                    let request = VeloRequest::ManifestGet {
+                        path: path((client * 7919 + n * 31) % FILES),
+                    };
+                    let response = if exclusive {
+                        handler.write().await.handle_request(request).await
+                    } else {
+                        dispatch(&handler, request).await
+                    };
+                    criterion::black_box(response);
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+fn bench_lookup_contention(c: &mut Criterion) {
+    let temp = TempDir::new().unwrap();
+    let handler = handler(&temp);
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("manifest_get_contention");
+    for clients in [1, 8, 32] {
+        group.throughput(Throughput::Elements((clients * LOOKUPS_PER_CLIENT) as u64));
+        for (name, exclusive) in [("exclusive", true), ("dispatch", false)] {
+            group.bench_with_input(BenchmarkId::new(name, clients), &clients, |b, &n| {
+                b.iter(|| rt.block_on(run_clients(&handler, n, exclusive)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lookup_contention);
+criterion_main!(benches);
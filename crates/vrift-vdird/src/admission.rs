@@ -0,0 +1,369 @@
+//! Frequency-based admission to the VDir hot cache
+//!
+//! Without a cap every manifest entry is published into the VDir, which then
+//! grows with the manifest. With one, the VDir holds at most `capacity`
+//! manifest-sourced entries and vdir_d decides which by observed frequency:
+//! paths the shim keeps reporting as VDir misses (`MissReport`) are
+//! admitted on their next IPC lookup, evicting the coldest resident entry
+//! once the cache is full. Opens always go through IPC and don't count as
+//! misses; they count as hits for entries already resident.
+//!
+//! Counts are halved every few `capacity` lookups, so a burst long ago
+//! doesn't keep an entry resident forever. Entries written through the VDir
+//! (CoW reingests, upserts, renames) are the only copy of that state until
+//! the next commit and are never evicted.
+//!
+//! Lookups run under the shared handler lock, but admitting an entry
+//! writes the VDir. So a lookup only queues what it saw in [`Touches`]
+//! (sharded by path hash, like the heat counters), and the queue is
+//! applied to the [`HotCache`] whenever the handler is next held
+//! exclusively: by a write request, or right after a lookup if no one
+//! else holds the lock at that moment.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use vrift_manifest::lmdb::LmdbManifest;
+
+use crate::vdir::{fnv1a_hash, VDir};
+
+/// Once full, a candidate needs this many reported misses before it can evict
+const ADMIT_MIN_HITS: u32 = 2;
+
+/// Lookups between aging passes, as a multiple of the capacity
+const AGING_FACTOR: usize = 4;
+
+/// Smallest aging period, so tiny caches don't age on every lookup
+const MIN_AGING_PERIOD: usize = 1024;
+
+/// Queue shards (power of two)
+const TOUCH_SHARDS: usize = 64;
+
+/// Most touches queued between two applications; past that, lookups
+/// aren't counted (admission only needs rough frequencies)
+const MAX_TOUCHES: usize = 64 * 1024;
+
+/// A lookup the hot cache hasn't seen yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Touch {
+    /// Answered from the VDir
+    Hit(u64),
+    /// Answered by the manifest: a candidate for admission
+    Miss(String),
+}
+
+/// Lookups waiting to be applied to the [`HotCache`]
+pub struct Touches {
+    shards: Box<[Mutex<Vec<Touch>>]>,
+    queued: AtomicUsize,
+}
+
+impl Default for Touches {
+    fn default() -> Self {
+        Self {
+            shards: (0..TOUCH_SHARDS).map(|_| Mutex::default()).collect(),
+            queued: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Touches {
+    /// Queue a lookup of the path hashing to `hash`
+    pub fn push(&self, hash: u64, touch: Touch) {
+        if self.queued.load(Ordering::Relaxed) >= MAX_TOUCHES {
+            return;
+        }
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let shard = hash as usize & (TOUCH_SHARDS - 1);
+        self.shards[shard]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(touch);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued.load(Ordering::Relaxed) == 0
+    }
+
+    /// Everything queued so far
+    pub fn take(&self) -> Vec<Touch> {
+        let mut out = Vec::with_capacity(self.queued.swap(0, Ordering::Relaxed));
+        for shard in self.shards.iter() {
+            out.append(&mut shard.lock().unwrap_or_else(|e| e.into_inner()));
+        }
+        out
+    }
+}
+
+/// What to do after a VDir miss that the manifest answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Leave it to IPC
+    Skip,
+    /// Insert it, removing `evict` first if set
+    Admit { evict: Option<u64> },
+}
+
+/// Admission and eviction state, keyed by VDir path hash
+#[derive(Debug, Default)]
+pub struct HotCache {
+    /// Max manifest-sourced entries (0 = unlimited: the policy is off)
+    capacity: usize,
+    /// Lookup counts of paths not in the VDir
+    candidates: HashMap<u64, u32>,
+    /// Evictable VDir entries and their counts
+    resident: HashMap<u64, u32>,
+    /// (count, hash) over `resident`, coldest first
+    order: BTreeSet<(u32, u64)>,
+    /// Lookups since the last aging pass
+    ticks: usize,
+    admissions: u64,
+    evictions: u64,
+    /// Path hashes received in shim miss reports
+    reported: u64,
+}
+
+impl HotCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity != 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// (admissions, evictions) since startup
+    pub fn counters(&self) -> (u64, u64) {
+        (self.admissions, self.evictions)
+    }
+
+    /// Path hashes received in miss reports since startup
+    pub fn reported(&self) -> u64 {
+        self.reported
+    }
+
+    /// Track the VDir entries that still mirror the manifest (after warm
+    /// start). Anything else in the VDir was written through it and stays.
+    pub fn seed(&mut self, vdir: &VDir, manifest: &LmdbManifest) -> anyhow::Result<usize> {
+        let mut tracked = 0;
+        for (path, entry) in manifest.iter()? {
+            let hash = fnv1a_hash(&path);
+            let mirrors = vdir.lookup(hash).is_some_and(|v| {
+                !entry.stale
+                    && !v.is_dirty()
+                    && v.cas_hash == entry.vnode.content_hash
+                    && v.size == entry.vnode.size
+            });
+            if mirrors {
+                self.track(hash);
+                tracked += 1;
+            }
+        }
+        Ok(tracked)
+    }
+
+    /// Forget every tracked entry after the VDir was rebuilt; counters stay
+    pub fn reset(&mut self) {
+        self.candidates.clear();
+        self.resident.clear();
+        self.order.clear();
+        self.ticks = 0;
+    }
+
+    /// Register an entry already in the VDir that mirrors the manifest
+    pub fn track(&mut self, hash: u64) {
+        if self.is_enabled() && !self.resident.contains_key(&hash) {
+            self.resident.insert(hash, 0);
+            self.order.insert((0, hash));
+        }
+    }
+
+    /// The VDir entry now holds state the manifest doesn't; never evict it
+    pub fn pin(&mut self, hash: u64) {
+        self.forget(hash);
+    }
+
+    /// The entry left the VDir other than by eviction
+    pub fn forget(&mut self, hash: u64) {
+        if let Some(count) = self.resident.remove(&hash) {
+            self.order.remove(&(count, hash));
+        }
+        self.candidates.remove(&hash);
+    }
+
+    /// A resident entry was looked up over IPC
+    pub fn hit(&mut self, hash: u64) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.tick();
+        if let Some(count) = self.resident.get_mut(&hash) {
+            self.order.remove(&(*count, hash));
+            *count = count.saturating_add(1);
+            self.order.insert((*count, hash));
+        }
+    }
+
+    /// The shim reported a VDir miss for this path. Counts are kept for at
+    /// most one aging period's worth of distinct paths; past that, new
+    /// paths wait for the next aging pass.
+    pub fn report(&mut self, hash: u64) {
+        self.reported += 1;
+        if !self.is_enabled() {
+            return;
+        }
+        self.tick();
+        if let Some(count) = self.resident.get_mut(&hash) {
+            // Sent before the entry was admitted: still a use
+            self.order.remove(&(*count, hash));
+            *count = count.saturating_add(1);
+            self.order.insert((*count, hash));
+        } else if let Some(count) = self.candidates.get_mut(&hash) {
+            *count = count.saturating_add(1);
+        } else if self.candidates.len() < self.aging_period() {
+            self.candidates.insert(hash, 1);
+        }
+    }
+
+    /// A lookup missed the VDir and was answered by the manifest.
+    /// `vdir_entries` is the VDir's current entry count.
+    pub fn miss(&mut self, hash: u64, vdir_entries: usize) -> Admission {
+        if !self.is_enabled() {
+            return Admission::Skip;
+        }
+        let count = self.candidates.get(&hash).copied().unwrap_or(0);
+
+        if vdir_entries < self.capacity {
+            self.admit(hash, count);
+            return Admission::Admit { evict: None };
+        }
+        if count < ADMIT_MIN_HITS {
+            return Admission::Skip;
+        }
+        match self.order.first().copied() {
+            Some((coldest, victim)) if coldest < count => {
+                self.order.remove(&(coldest, victim));
+                self.resident.remove(&victim);
+                self.evictions += 1;
+                self.admit(hash, count);
+                Admission::Admit {
+                    evict: Some(victim),
+                }
+            }
+            _ => Admission::Skip,
+        }
+    }
+
+    fn admit(&mut self, hash: u64, count: u32) {
+        self.candidates.remove(&hash);
+        self.resident.insert(hash, count);
+        self.order.insert((count, hash));
+        self.admissions += 1;
+    }
+
+    fn aging_period(&self) -> usize {
+        (self.capacity * AGING_FACTOR).max(MIN_AGING_PERIOD)
+    }
+
+    fn tick(&mut self) {
+        self.ticks += 1;
+        if self.ticks < self.aging_period() {
+            return;
+        }
+        self.ticks = 0;
+        self.candidates.retain(|_, count| {
+            *count /= 2;
+            *count > 0
+        });
+        for count in self.resident.values_mut() {
+            *count /= 2;
+        }
+        self.order = self.resident.iter().map(|(&h, &c)| (c, h)).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admits_freely_until_full() {
+        let mut cache = HotCache::new(2);
+        assert_eq!(cache.miss(1, 0), Admission::Admit { evict: None });
+        assert_eq!(cache.miss(2, 1), Admission::Admit { evict: None });
+        // Full: a single reported miss isn't enough to displace anything
+        cache.report(3);
+        assert_eq!(cache.miss(3, 2), Admission::Skip);
+    }
+
+    #[test]
+    fn test_reports_of_resident_entries_count_as_hits() {
+        let mut cache = HotCache::new(2);
+        cache.track(10);
+        cache.track(11);
+        for _ in 0..3 {
+            cache.report(10);
+        }
+        // 30 beats 11 (0) but not 10 (3)
+        cache.report(30);
+        cache.report(30);
+        assert_eq!(cache.miss(30, 2), Admission::Admit { evict: Some(11) });
+    }
+
+    #[test]
+    fn test_frequent_candidate_evicts_coldest() {
+        let mut cache = HotCache::new(2);
+        cache.track(10);
+        cache.track(11);
+        cache.hit(10);
+        cache.hit(10);
+
+        cache.report(20);
+        assert_eq!(cache.miss(20, 2), Admission::Skip);
+        cache.report(20);
+        assert_eq!(cache.miss(20, 2), Admission::Admit { evict: Some(11) });
+        assert_eq!(cache.counters(), (1, 1));
+        assert_eq!(cache.reported(), 2);
+    }
+
+    #[test]
+    fn test_pinned_entries_are_never_evicted() {
+        let mut cache = HotCache::new(1);
+        cache.track(10);
+        cache.pin(10);
+
+        cache.report(20);
+        cache.report(20);
+        assert_eq!(cache.miss(20, 1), Admission::Skip);
+    }
+
+    #[test]
+    fn test_touches_queue_until_taken() {
+        let touches = Touches::default();
+        assert!(touches.is_empty());
+        touches.push(1, Touch::Hit(1));
+        touches.push(2, Touch::Miss("/a".to_string()));
+        assert!(!touches.is_empty());
+
+        let mut taken = touches.take();
+        taken.sort_by_key(|t| matches!(t, Touch::Miss(_)));
+        assert_eq!(taken, [Touch::Hit(1), Touch::Miss("/a".to_string())]);
+        assert!(touches.is_empty());
+        assert!(touches.take().is_empty());
+    }
+
+    #[test]
+    fn test_disabled_without_capacity() {
+        let mut cache = HotCache::new(0);
+        cache.track(1);
+        assert_eq!(cache.miss(2, 0), Admission::Skip);
+    }
+}
@@ -1,21 +1,96 @@
 //! Command handlers for vdir_d
 
-use crate::vdir::{fnv1a_hash, VDir, VDirEntry, FLAG_DIR};
+use crate::admission::{Admission, HotCache, Touch, Touches};
+use crate::heat::Heat;
+use crate::journal::{JournalEntry, ReingestJournal};
+use crate::manifest_mmap::ManifestMmap;
+use crate::protect::Protection;
+use crate::registry::ProjectRegistry;
+use crate::vdir::{flags_from_vnode, flags_to_vnode, fnv1a_hash, VDir, VDirEntry, FLAG_DIR};
+use crate::wal::ManifestWal;
 use crate::ProjectConfig;
 use anyhow::Result;
+use std::collections::VecDeque;
 use std::fs;
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use tracing::{debug, error, info, warn};
 use vrift_ipc::{
-    VeloError, VeloErrorKind, VeloRequest, VeloResponse, VnodeEntry, PROTOCOL_VERSION,
+    Capabilities, DaemonMetrics, ReingestEvent, SnapshotInfo, VeloError, VeloErrorKind,
+    VeloRequest, VeloResponse, VnodeEntry, Xattr, PROTOCOL_VERSION,
 };
+use vrift_manifest::{GenerationInfo, GenerationStore, LmdbError, ManifestEntry};
+
+/// Reingests kept for `vrift status --watch`
+const RECENT_REINGESTS: usize = 16;
+
+/// Most children one ManifestListDirPage reply carries
+const MAX_LIST_PAGE: u32 = 4096;
+
+/// What vdir_d offers in `Negotiate`: the VDir mmap, pipelined requests
+/// (see `socket::read_requests`), ManifestRename, entry xattrs and the
+/// manifest mmap
+const VDIRD_CAPABILITIES: Capabilities = Capabilities::MMAP_CACHE
+    .union(Capabilities::BATCH_REQUESTS)
+    .union(Capabilities::RENAME)
+    .union(Capabilities::XATTRS)
+    .union(Capabilities::MANIFEST_MMAP);
+
+/// Directory listings kept for ManifestListDirPage, so concurrent
+/// readdir()s of different directories don't rebuild each other's
+const LISTING_CACHE: usize = 8;
+
+/// A directory listing kept between ManifestListDirPage requests
+struct Listing {
+    path: String,
+    /// Manifest generation it was built at
+    generation: u64,
+    entries: Vec<vrift_ipc::DirEntry>,
+}
+
+/// Where a ManifestGet found its path
+enum Found {
+    /// With the inode the VDir entry carries (0: none)
+    VDir(VnodeEntry, u64),
+    Manifest(ManifestEntry),
+    Absent,
+}
 
 /// Command handler for vdir_d
 pub struct CommandHandler {
     config: ProjectConfig,
     vdir: VDir,
     manifest: std::sync::Arc<vrift_manifest::lmdb::LmdbManifest>,
+    /// Reingests between CAS store and VDir update (see `handle_reingest`)
+    journal: ReingestJournal,
+    started: std::time::Instant,
+    reingests: u64,
+    recent_reingests: VecDeque<ReingestEvent>,
+    /// IPC lookups per (path prefix, op), reported in Metrics
+    heat: Heat,
+    /// Which manifest entries the VDir holds when it is capped
+    hot: HotCache,
+    /// Lookups made under the shared lock, not yet applied to `hot`
+    touches: Touches,
+    /// Manifest generations for SnapshotCreate/SnapshotRestore
+    snapshots: GenerationStore,
+    /// Entries of protected snapshots, which mutations may not touch
+    protection: std::sync::Arc<Protection>,
+    /// Directories being paged out, most recently used first, so each page
+    /// doesn't rescan the manifest. Behind its own lock so pages are
+    /// served under the shared handler lock.
+    listings: std::sync::Mutex<VecDeque<Listing>>,
+    /// Logs IPC manifest writes until they are committed (None: write the
+    /// manifest directly)
+    wal: Option<std::sync::Arc<ManifestWal>>,
+    /// Reingests finished from the journal at startup
+    recovered: usize,
+    /// Publishes the manifest keys for shims (None: not published)
+    published: Option<std::sync::Arc<ManifestMmap>>,
+    /// Open connections to this project's socket, for Metrics
+    connections: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    /// Where `ProjectOpen` opens other projects (None: it is refused)
+    registry: Option<std::sync::Weak<ProjectRegistry>>,
 }
 
 impl CommandHandler {
@@ -23,17 +98,95 @@ impl CommandHandler {
         config: ProjectConfig,
         vdir: VDir,
         manifest: std::sync::Arc<vrift_manifest::lmdb::LmdbManifest>,
+        journal: ReingestJournal,
     ) -> Self {
+        let snapshots = GenerationStore::for_project(&config.project_root);
+        let protection = Protection::load(&snapshots).unwrap_or_else(|e| {
+            warn!(error = %e, "Failed to load protected snapshots");
+            Protection::default()
+        });
         Self {
+            snapshots,
+            protection: std::sync::Arc::new(protection),
             config,
             vdir,
             manifest,
+            journal,
+            started: std::time::Instant::now(),
+            reingests: 0,
+            recent_reingests: VecDeque::with_capacity(RECENT_REINGESTS),
+            heat: Heat::default(),
+            hot: HotCache::default(),
+            touches: Touches::default(),
+            listings: Default::default(),
+            wal: None,
+            recovered: 0,
+            published: None,
+            connections: Default::default(),
+            registry: None,
         }
     }
 
-    /// Handle incoming request
-    pub async fn handle_request(&mut self, request: VeloRequest) -> VeloResponse {
-        match request {
+    /// Cap the VDir with a frequency-based admission policy
+    pub fn with_hot_cache(mut self, hot: HotCache) -> Self {
+        self.hot = hot;
+        self
+    }
+
+    /// Share snapshot protection with the watcher's ingest handler
+    pub fn with_protection(mut self, protection: std::sync::Arc<Protection>) -> Self {
+        self.protection = protection;
+        self
+    }
+
+    /// Send manifest writes through a write-ahead log shared with the
+    /// periodic commit
+    pub fn with_wal(mut self, wal: std::sync::Arc<ManifestWal>) -> Self {
+        self.wal = Some(wal);
+        self
+    }
+
+    /// Keep the manifest mmap in step with what this handler serves
+    pub fn with_manifest_mmap(mut self, published: std::sync::Arc<ManifestMmap>) -> Self {
+        self.published = Some(published);
+        self
+    }
+
+    /// Let `ProjectOpen` open other projects in `registry`
+    pub fn with_registry(mut self, registry: std::sync::Weak<ProjectRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// The registry this project is served from, if it is still up
+    pub fn registry(&self) -> Option<std::sync::Arc<ProjectRegistry>> {
+        self.registry.as_ref()?.upgrade()
+    }
+
+    /// Counter of open connections, kept by the socket listener
+    pub fn connections(&self) -> std::sync::Arc<std::sync::atomic::AtomicU64> {
+        self.connections.clone()
+    }
+
+    /// Error response if `path` belongs to a protected snapshot
+    fn refuse_protected(&self, path: &str) -> Option<VeloResponse> {
+        let id = self.protection.protector(path)?;
+        debug!(path = %path, "Refusing change to protected entry");
+        Some(VeloResponse::Error(VeloError::with_path(
+            VeloErrorKind::PermissionDenied,
+            format!(
+                "Protected by snapshot {}",
+                &vrift_cas::CasStore::hash_to_hex(&id)[..12]
+            ),
+            path,
+        )))
+    }
+
+    /// Answer `request` if it only reads handler state, so it can run under
+    /// a shared lock alongside other lookups. `None` means it needs
+    /// [`CommandHandler::handle_request`].
+    pub fn handle_lookup(&self, request: &VeloRequest) -> Option<VeloResponse> {
+        let response = match request {
             VeloRequest::Handshake {
                 client_version,
                 protocol_version,
@@ -42,12 +195,38 @@ impl CommandHandler {
                 VeloResponse::HandshakeAck {
                     server_version: env!("CARGO_PKG_VERSION").to_string(),
                     protocol_version: PROTOCOL_VERSION,
-                    compatible: vrift_ipc::is_version_compatible(protocol_version),
+                    compatible: vrift_ipc::is_version_compatible(*protocol_version),
+                }
+            }
+            VeloRequest::Negotiate {
+                client_version,
+                protocol_version,
+                capabilities,
+            } => {
+                let (protocol_version, capabilities) =
+                    vrift_ipc::negotiate(*protocol_version, *capabilities, VDIRD_CAPABILITIES);
+                info!(
+                    client_version = %client_version,
+                    ?protocol_version,
+                    capabilities = ?capabilities.names(),
+                    "Negotiate"
+                );
+                VeloResponse::NegotiateAck {
+                    server_version: env!("CARGO_PKG_VERSION").to_string(),
+                    protocol_version,
+                    capabilities,
                 }
             }
 
             VeloRequest::Status => VeloResponse::StatusAck {
-                status: "ready".to_string(),
+                status: match self.recovered {
+                    0 => "ready".to_string(),
+                    n => format!("ready (recovered {} reingests after crash)", n),
+                },
+            },
+
+            VeloRequest::Metrics => VeloResponse::MetricsAck {
+                metrics: self.metrics(),
             },
 
             VeloRequest::RegisterWorkspace { project_root } => {
@@ -56,29 +235,111 @@ impl CommandHandler {
                     workspace_id: self.config.project_id.clone(),
                     vdird_socket: self.config.socket_path.to_string_lossy().to_string(),
                     vdir_mmap_path: self.config.vdir_path.to_string_lossy().to_string(),
+                    session_id: vrift_config::path::new_session_id(),
+                }
+            }
+
+            VeloRequest::ManifestGet { path } => {
+                self.heat.record(path, "ipc_get");
+                let path_hash = fnv1a_hash(path);
+                let found = self.find(path, path_hash);
+                // Admission writes the VDir: leave it to `apply_touches`
+                if self.hot.is_enabled() {
+                    match &found {
+                        Ok(Found::VDir(..)) => self.touches.push(path_hash, Touch::Hit(path_hash)),
+                        Ok(Found::Manifest(entry)) if !entry.stale => {
+                            self.touches.push(path_hash, Touch::Miss(path.clone()))
+                        }
+                        _ => {}
+                    }
                 }
+                self.get_response(path, found)
+            }
+
+            VeloRequest::ManifestListDir { path } => {
+                self.heat.record(path, "ipc_list");
+                self.handle_manifest_list_dir(path)
             }
 
-            VeloRequest::ManifestGet { path } => self.handle_manifest_get(&path),
+            VeloRequest::ManifestGetXattrs { path } => self.handle_manifest_get_xattrs(path),
 
-            VeloRequest::ManifestUpsert { path, entry } => {
-                self.handle_manifest_upsert(&path, entry)
+            VeloRequest::ManifestListDirPage { path, after, limit } => {
+                if after.is_empty() {
+                    self.heat.record(path, "ipc_list");
+                }
+                self.handle_manifest_list_dir_page(path, after, *limit)
             }
 
-            VeloRequest::ManifestRemove { path } => self.handle_manifest_remove(&path),
+            VeloRequest::BuildCacheKey {
+                inputs,
+                exclude,
+                command,
+            } => self.handle_build_cache_key(inputs, exclude, command),
 
-            VeloRequest::ManifestRename { old_path, new_path } => {
-                self.handle_manifest_rename(&old_path, &new_path)
+            VeloRequest::ManifestResolve { path, follow_final } => {
+                self.handle_manifest_resolve(path, *follow_final)
             }
 
-            VeloRequest::ManifestUpdateMtime { path, mtime_ns } => {
-                self.handle_manifest_update_mtime(&path, mtime_ns)
+            VeloRequest::SnapshotList => {
+                match self.snapshots.list().and_then(|list| {
+                    let protected = self.snapshots.protected()?;
+                    Ok(list
+                        .iter()
+                        .map(|info| snapshot_info(info, &protected))
+                        .collect())
+                }) {
+                    Ok(snapshots) => VeloResponse::SnapshotListAck { snapshots },
+                    Err(e) => snapshot_error("Listing snapshots failed", e),
+                }
             }
 
-            VeloRequest::ManifestListDir { path } => self.handle_manifest_list_dir(&path),
+            _ => return None,
+        };
+        Some(response)
+    }
+
+    /// Handle incoming request
+    pub async fn handle_request(&mut self, request: VeloRequest) -> VeloResponse {
+        let lookup = self.handle_lookup(&request);
+        self.apply_touches();
+        if let Some(response) = lookup {
+            return response;
+        }
+        match request {
+            VeloRequest::ManifestUpsert { path, entry } => self
+                .refuse_protected(&path)
+                .unwrap_or_else(|| self.handle_manifest_upsert(&path, entry)),
+
+            VeloRequest::ManifestRemove { path } => self
+                .refuse_protected(&path)
+                .unwrap_or_else(|| self.handle_manifest_remove(&path)),
+
+            VeloRequest::ManifestRename { old_path, new_path } => self
+                .refuse_protected(&old_path)
+                .or_else(|| self.refuse_protected(&new_path))
+                .unwrap_or_else(|| self.handle_manifest_rename(&old_path, &new_path)),
+
+            VeloRequest::ManifestUpdateMtime { path, mtime_ns } => self
+                .refuse_protected(&path)
+                .unwrap_or_else(|| self.handle_manifest_update_mtime(&path, mtime_ns)),
+
+            VeloRequest::ManifestSetXattr { path, name, value } => self
+                .refuse_protected(&path)
+                .unwrap_or_else(|| self.handle_manifest_set_xattr(&path, name, value)),
+
+            VeloRequest::MissReport { path_hashes } => {
+                for hash in path_hashes {
+                    self.hot.report(hash);
+                }
+                VeloResponse::MissReportAck
+            }
 
             VeloRequest::ManifestReingest { vpath, temp_path } => {
-                self.handle_reingest(&vpath, &temp_path).await
+                // Refused before the copy is touched, so the client keeps it
+                match self.refuse_protected(&vpath) {
+                    Some(refusal) => refusal,
+                    None => self.handle_reingest(&vpath, &temp_path).await,
+                }
             }
 
             VeloRequest::IngestFullScan {
@@ -90,6 +351,8 @@ impl CommandHandler {
                 prefix,
                 cas_root,
                 force_hash: _,
+                compress: _,
+                ignore: _,
             } => {
                 self.handle_ingest_full_scan(
                     &path,
@@ -103,6 +366,17 @@ impl CommandHandler {
                 .await
             }
 
+            VeloRequest::SnapshotCreate { label, protect } => {
+                self.handle_snapshot_create(&label, protect)
+            }
+
+            VeloRequest::SnapshotRestore { id } => self.handle_snapshot_restore(&id),
+            VeloRequest::ManifestReload => self.handle_manifest_reload(),
+
+            VeloRequest::SnapshotUnprotect { id, force } => {
+                self.handle_snapshot_unprotect(&id, force)
+            }
+
             // Not yet implemented - forward to future handlers
             _ => {
                 warn!(?request, "Unhandled request type");
@@ -111,45 +385,115 @@ impl CommandHandler {
         }
     }
 
-    /// Handle ManifestGet
-    /// First checks VDir (runtime overlay for COW), then falls back to LMDB (persistent storage)
-    fn handle_manifest_get(&self, path: &str) -> VeloResponse {
-        let path_hash = fnv1a_hash(path);
+    /// Whether lookups have queued hot-cache work for [`apply_touches`](Self::apply_touches)
+    pub fn has_touches(&self) -> bool {
+        !self.touches.is_empty()
+    }
 
-        // 1. First check VDir (runtime overlay for COW mutations)
-        if let Some(entry) = self.vdir.lookup(path_hash) {
-            let vnode = VnodeEntry {
-                content_hash: entry.cas_hash,
-                size: entry.size,
-                mtime: entry.mtime_sec as u64,
-                mode: entry.mode,
-                flags: entry.flags,
-                _pad: 0,
-            };
-            return VeloResponse::ManifestAck { entry: Some(vnode) };
+    /// Feed the lookups queued under the shared lock to the hot cache,
+    /// publishing the entries it admits
+    pub fn apply_touches(&mut self) {
+        if self.touches.is_empty() {
+            return;
+        }
+        for touch in self.touches.take() {
+            match touch {
+                Touch::Hit(path_hash) => self.hot.hit(path_hash),
+                Touch::Miss(path) => {
+                    let path_hash = fnv1a_hash(&path);
+                    // Published since, or changed by a write in between
+                    if self.vdir.lookup(path_hash).is_some() {
+                        continue;
+                    }
+                    if let Ok(Some(entry)) = self.manifest.get(&path) {
+                        if !entry.stale && !entry.vnode.is_whiteout() {
+                            self.admit(&path, path_hash, &entry);
+                        }
+                    }
+                }
+            }
         }
+    }
 
-        // 2. Fallback to LMDB (persistent storage)
-        match self.manifest.get(path) {
-            Ok(Some(entry)) => {
+    /// Look `path` up in the VDir (runtime overlay for COW mutations), then
+    /// in LMDB (persistent storage)
+    fn find(&self, path: &str, path_hash: u64) -> Result<Found, LmdbError> {
+        if let Some(entry) = self.vdir.lookup(path_hash) {
+            return Ok(Found::VDir(vdir_vnode(entry), entry.ino));
+        }
+        Ok(match self.manifest.get(path)? {
+            Some(entry) => {
                 debug!(path = %path, "ManifestGet: found in LMDB");
-                VeloResponse::ManifestAck {
-                    entry: Some(entry.vnode),
-                }
+                Found::Manifest(entry)
             }
-            Ok(None) => {
+            None => {
                 debug!(path = %path, "ManifestGet: not found in VDir or LMDB");
-                VeloResponse::ManifestAck { entry: None }
+                Found::Absent
             }
+        })
+    }
+
+    /// ManifestGet reply for a [`find`](Self::find) result. A live entry
+    /// comes with its inode from the table, allocated here if it has none
+    /// yet, so shims report the same `st_ino` whether or not the entry has
+    /// made it into the VDir.
+    fn get_response(&self, path: &str, found: Result<Found, LmdbError>) -> VeloResponse {
+        let (entry, ino) = match found {
+            Ok(Found::VDir(vnode, ino)) => (Some(vnode), ino),
+            Ok(Found::Manifest(entry)) => (Some(entry.vnode), 0),
+            Ok(Found::Absent) => (None, 0),
             Err(e) => {
+                // Not "absent": the caller must not turn this into ENOENT
                 warn!(path = %path, error = %e, "ManifestGet: LMDB lookup failed");
-                VeloResponse::ManifestAck { entry: None }
+                return VeloResponse::Error(VeloError::with_path(
+                    VeloErrorKind::IoError,
+                    format!("Manifest lookup failed: {}", e),
+                    path,
+                ));
             }
+        };
+        let ino = match &entry {
+            Some(vnode) if ino == 0 && !vnode.is_whiteout() => self.ino(path),
+            _ => ino,
+        };
+        VeloResponse::ManifestAck { entry, ino }
+    }
+
+    /// Publish a manifest entry into the VDir if the hot cache admits it
+    fn admit(&mut self, path: &str, path_hash: u64, manifest_entry: &ManifestEntry) {
+        let Admission::Admit { evict } = self.hot.miss(path_hash, self.vdir.entry_count() as usize)
+        else {
+            return;
+        };
+        if let Some(victim) = evict {
+            self.vdir.remove(victim);
+        }
+        let vnode = &manifest_entry.vnode;
+        let entry = VDirEntry {
+            path_hash,
+            cas_hash: vnode.content_hash,
+            size: vnode.size,
+            mtime_sec: vnode.mtime as i64,
+            mtime_nsec: 0,
+            mode: vnode.mode,
+            flags: flags_from_vnode(vnode.flags),
+            _pad: [0; 3],
+            btime_sec: manifest_entry.btime().unwrap_or(0) as i64,
+            ino: self.ino(path),
+        };
+        if let Err(e) = self.vdir.insert_if_absent(entry) {
+            warn!(error = %e, "Hot cache admission failed");
         }
     }
 
-    /// Handle ManifestUpsert
+    /// Handle ManifestUpsert: persist the entry to the manifest, then
+    /// publish it in the VDir
     fn handle_manifest_upsert(&mut self, path: &str, entry: VnodeEntry) -> VeloResponse {
+        if let Err(e) = self.persist_upsert(path, &entry) {
+            error!(error = %e, path = %path, "Upsert not persisted");
+            return io_error_response(&e, path);
+        }
+        self.hot.pin(fnv1a_hash(path));
         let vdir_entry = VDirEntry {
             path_hash: fnv1a_hash(path),
             cas_hash: entry.content_hash,
@@ -157,14 +501,20 @@ impl CommandHandler {
             mtime_sec: entry.mtime as i64,
             mtime_nsec: 0,
             mode: entry.mode,
-            flags: entry.flags,
+            flags: flags_from_vnode(entry.flags),
             _pad: [0; 3],
+            // Kept by the manifest across the upsert
+            btime_sec: self.recorded_btime(path),
+            ino: self.ino(path),
         };
 
         match self.vdir.upsert(vdir_entry) {
             Ok(_) => {
                 debug!(path = %path, "Upserted entry");
-                VeloResponse::ManifestAck { entry: Some(entry) }
+                VeloResponse::ManifestAck {
+                    entry: Some(entry),
+                    ino: 0,
+                }
             }
             Err(e) => {
                 error!(error = %e, path = %path, "Upsert failed");
@@ -173,19 +523,269 @@ impl CommandHandler {
         }
     }
 
-    /// Handle ManifestRemove
+    /// Handle ManifestRemove: replace the entry with a tombstone in the
+    /// manifest and the VDir, so shims answer ENOENT instead of finding it,
+    /// or falling through to the file still on disk
     fn handle_manifest_remove(&mut self, path: &str) -> VeloResponse {
+        let mtime = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let tombstone = VnodeEntry::new_whiteout(mtime);
+        if let Err(e) = self.persist_upsert(path, &tombstone) {
+            error!(error = %e, path = %path, "Remove not persisted");
+            return io_error_response(&e, path);
+        }
+        let path_hash = fnv1a_hash(path);
+        self.hot.pin(path_hash);
+        let vdir_entry = VDirEntry {
+            path_hash,
+            cas_hash: tombstone.content_hash,
+            size: 0,
+            mtime_sec: mtime as i64,
+            mtime_nsec: 0,
+            mode: 0,
+            flags: flags_from_vnode(tombstone.flags),
+            _pad: [0; 3],
+            btime_sec: 0,
+            ino: 0,
+        };
+        // Whatever gets created here next is another file
+        if let Err(e) = self.manifest.inodes().release(path) {
+            warn!(error = %e, path = %path, "Inode not released");
+        }
+        if let Err(e) = self.vdir.upsert(vdir_entry) {
+            // The manifest still has the tombstone for lookups over IPC
+            warn!(error = %e, path = %path, "VDir tombstone failed, dropping entry");
+            self.hot.forget(path_hash);
+            self.vdir.remove(path_hash);
+        }
+        debug!(path = %path, "Removed entry");
+        VeloResponse::ManifestAck {
+            entry: None,
+            ino: 0,
+        }
+    }
+
+    fn drop_entry(&mut self, path: &str) -> std::io::Result<()> {
+        match &self.wal {
+            Some(wal) => wal.remove(path)?,
+            None => self.manifest.remove(path),
+        }
         let path_hash = fnv1a_hash(path);
-        if self.vdir.mark_dirty(path_hash, false) {
-            // For now, just clear dirty bit. Full deletion would require tombstone.
-            debug!(path = %path, "Marked for removal");
-            VeloResponse::ManifestAck { entry: None }
+        self.hot.forget(path_hash);
+        self.vdir.remove(path_hash);
+        Ok(())
+    }
+
+    fn persist_upsert(&self, path: &str, entry: &VnodeEntry) -> std::io::Result<()> {
+        match &self.wal {
+            Some(wal) => wal.upsert(path, entry.clone()),
+            None => {
+                self.manifest
+                    .upsert(path, entry.clone(), vrift_manifest::AssetTier::Tier2Mutable);
+                Ok(())
+            }
+        }
+    }
+
+    fn persist_xattrs(&self, path: &str, attrs: &[(String, Vec<u8>)]) -> std::io::Result<bool> {
+        match &self.wal {
+            Some(wal) => wal.set_xattrs(path, attrs),
+            None => self
+                .manifest
+                .set_xattrs(path, attrs)
+                .map_err(|e| std::io::Error::other(e.to_string())),
+        }
+    }
+
+    fn persist_btime(&self, path: &str, btime: Option<u64>) -> std::io::Result<bool> {
+        match &self.wal {
+            Some(wal) => wal.set_btime(path, btime),
+            None => self
+                .manifest
+                .set_btime(path, btime)
+                .map_err(|e| std::io::Error::other(e.to_string())),
+        }
+    }
+
+    /// Inode allocated to `path`, in VDir form (0: none, so shims derive one
+    /// from the path hash)
+    fn ino(&self, path: &str) -> u64 {
+        self.manifest.inodes().assign(path).unwrap_or_else(|e| {
+            warn!(error = %e, path = %path, "Inode allocation failed");
+            0
+        })
+    }
+
+    /// Creation time the manifest has for `path`, in VDir form (0: none)
+    fn recorded_btime(&self, path: &str) -> i64 {
+        match self.manifest.get(path) {
+            Ok(Some(entry)) => entry.btime().unwrap_or(0) as i64,
+            _ => 0,
+        }
+    }
+
+    /// The live (not deleted) manifest entry at `path`
+    fn live_entry(&self, path: &str) -> Result<ManifestEntry, VeloError> {
+        match self.manifest.get(path) {
+            Ok(Some(entry)) if !entry.vnode.is_whiteout() => Ok(entry),
+            Ok(_) => Err(VeloError::with_path(
+                VeloErrorKind::NotFound,
+                "No manifest entry",
+                path,
+            )),
+            Err(e) => Err(VeloError::with_path(
+                VeloErrorKind::IoError,
+                format!("Manifest lookup failed: {}", e),
+                path,
+            )),
+        }
+    }
+
+    /// Handle ManifestGetXattrs
+    fn handle_manifest_get_xattrs(&self, path: &str) -> VeloResponse {
+        match self.live_entry(path) {
+            Ok(entry) => VeloResponse::ManifestXattrsAck {
+                xattrs: entry
+                    .xattrs()
+                    .into_iter()
+                    .map(|(name, value)| Xattr { name, value })
+                    .collect(),
+            },
+            Err(e) => VeloResponse::Error(e),
+        }
+    }
+
+    /// Handle ManifestResolve through the bounded resolver, so a link
+    /// cycle ends in SymlinkLoop (ELOOP) instead of spinning
+    fn handle_manifest_resolve(&self, path: &str, follow_final: bool) -> VeloResponse {
+        let mut store = None;
+        let resolved = vrift_manifest::resolve_symlinks(path, follow_final, |p| {
+            match self.manifest.get(p)? {
+                Some(entry) if entry.vnode.is_symlink() && !entry.vnode.is_whiteout() => {
+                    self.symlink_target(p, &entry, &mut store).map(Some)
+                }
+                _ => Ok(None),
+            }
+        });
+        match resolved {
+            Ok(path) => VeloResponse::ManifestResolveAck { path },
+            Err(e) => {
+                let kind = match &e {
+                    LmdbError::SymlinkLoop(_) => VeloErrorKind::SymlinkLoop,
+                    LmdbError::Io(io) => VeloErrorKind::from_io(io, VeloErrorKind::IoError),
+                    _ => VeloErrorKind::IoError,
+                };
+                VeloResponse::Error(VeloError::with_path(kind, e.to_string(), path))
+            }
+        }
+    }
+
+    /// Target of a manifest symlink: the one recorded with the entry, else
+    /// its CAS blob (live-ingested links), else the link on disk (links
+    /// made through the shim, which have no blob). `store` is opened on
+    /// first use.
+    fn symlink_target(
+        &self,
+        path: &str,
+        entry: &ManifestEntry,
+        store: &mut Option<vrift_cas::CasStore>,
+    ) -> Result<String, LmdbError> {
+        let bytes = if let Some(target) = entry.symlink_target() {
+            target.to_vec()
+        } else if entry.vnode.content_hash != [0u8; 32] {
+            let store = match store {
+                Some(store) => store,
+                None => store.insert(
+                    vrift_cas::CasStore::new(&self.config.cas_path)
+                        .map_err(|e| std::io::Error::other(e.to_string()))?,
+                ),
+            };
+            store
+                .get(&entry.vnode.content_hash)
+                .map_err(|e| std::io::Error::other(e.to_string()))?
         } else {
-            VeloResponse::ManifestAck { entry: None }
+            let on_disk = self.config.project_root.join(path.trim_start_matches('/'));
+            std::fs::read_link(on_disk)?
+                .into_os_string()
+                .into_encoded_bytes()
+        };
+        String::from_utf8(bytes)
+            .map_err(|_| LmdbError::Corrupted(format!("symlink target of {} is not UTF-8", path)))
+    }
+
+    /// Handle ManifestSetXattr: set or remove one attribute. Removing one
+    /// that isn't there is not an error here; the shim checks first.
+    fn handle_manifest_set_xattr(
+        &mut self,
+        path: &str,
+        name: String,
+        value: Option<Vec<u8>>,
+    ) -> VeloResponse {
+        let mut attrs = match self.live_entry(path) {
+            Ok(entry) => entry.xattrs(),
+            Err(e) => return VeloResponse::Error(e),
+        };
+        attrs.retain(|(n, _)| *n != name);
+        if let Some(value) = value {
+            attrs.push((name, value));
+        }
+        match self.persist_xattrs(path, &attrs) {
+            Ok(_) => {
+                debug!(path = %path, count = attrs.len(), "Set xattrs");
+                VeloResponse::ManifestAck {
+                    entry: None,
+                    ino: 0,
+                }
+            }
+            Err(e) => {
+                error!(error = %e, path = %path, "Xattrs not persisted");
+                io_error_response(&e, path)
+            }
+        }
+    }
+
+    /// Bring the VDir entry for `path` back in line with the manifest after
+    /// the manifest changed underneath it (the watcher's ingest). Shims read
+    /// the VDir without asking, so a stale entry would be served to every
+    /// process until the next restart. Paths not in the VDir are left to IPC.
+    pub fn refresh_entry(&mut self, path: &str) {
+        let path_hash = fnv1a_hash(path);
+        if self.vdir.lookup(path_hash).is_none() {
+            return;
         }
+        match self.manifest.get(path) {
+            Ok(Some(entry)) if !entry.stale => {
+                let vnode = &entry.vnode;
+                let refreshed = VDirEntry {
+                    path_hash,
+                    cas_hash: vnode.content_hash,
+                    size: vnode.size,
+                    mtime_sec: vnode.mtime as i64,
+                    mtime_nsec: 0,
+                    mode: vnode.mode,
+                    flags: flags_from_vnode(vnode.flags),
+                    _pad: [0; 3],
+                    btime_sec: entry.btime().unwrap_or(0) as i64,
+                    ino: self.ino(path),
+                };
+                if let Err(e) = self.vdir.upsert(refreshed) {
+                    warn!(path = %path, error = %e, "VDir refresh failed, dropping entry");
+                    self.hot.forget(path_hash);
+                    self.vdir.remove(path_hash);
+                }
+            }
+            _ => {
+                self.hot.forget(path_hash);
+                self.vdir.remove(path_hash);
+            }
+        }
+        debug!(path = %path, "VDir entry refreshed from manifest");
     }
 
-    /// Handle ManifestRename: remove old path, upsert under new path
+    /// Handle ManifestRename: remove old path, upsert under new path. A
+    /// directory takes everything beneath it along.
     fn handle_manifest_rename(&mut self, old_path: &str, new_path: &str) -> VeloResponse {
         let old_hash = fnv1a_hash(old_path);
         let new_hash = fnv1a_hash(new_path);
@@ -201,27 +801,73 @@ impl CommandHandler {
                 mtime_sec: lmdb_entry.vnode.mtime as i64,
                 mtime_nsec: 0,
                 mode: lmdb_entry.vnode.mode,
-                flags: lmdb_entry.vnode.flags,
+                flags: flags_from_vnode(lmdb_entry.vnode.flags),
                 _pad: [0; 3],
+                btime_sec: lmdb_entry.btime().unwrap_or(0) as i64,
+                ino: self.ino(old_path),
             })
         } else {
             None
         };
 
-        match old_entry {
+        match old_entry.filter(|entry| !entry.is_deleted()) {
             Some(entry) => {
-                // Mark old path as removed
-                self.vdir.mark_dirty(old_hash, false);
+                // New path first: a crash in between leaves both, not neither
+                let vnode = VnodeEntry {
+                    content_hash: entry.cas_hash,
+                    size: entry.size,
+                    mtime: entry.mtime_sec as u64,
+                    mode: entry.mode,
+                    flags: flags_to_vnode(entry.flags),
+                    _pad: 0,
+                };
+                // The attributes and birth time go with the file, replacing
+                // the target's
+                let (xattrs, btime) = match self.manifest.get(old_path) {
+                    Ok(Some(old)) => (old.xattrs(), old.btime()),
+                    _ => (Vec::new(), None),
+                };
+                if let Err(e) = self
+                    .persist_upsert(new_path, &vnode)
+                    .and_then(|_| self.persist_xattrs(new_path, &xattrs))
+                    .and_then(|_| self.persist_btime(new_path, btime))
+                    .and_then(|_| self.drop_entry(old_path))
+                {
+                    error!(error = %e, old = %old_path, new = %new_path, "Rename not persisted");
+                    return io_error_response(&e, new_path);
+                }
+                if entry.is_dir() {
+                    if let Err(e) = self.move_children(old_path, new_path) {
+                        error!(error = %e, old = %old_path, new = %new_path, "Rename of children not persisted");
+                        return io_error_response(&e, new_path);
+                    }
+                }
+
+                // The inode goes with the file too, so tools keying on
+                // (dev, ino) still recognize it
+                let ino = match self.manifest.inodes().rename(old_path, new_path) {
+                    Ok(Some(ino)) => ino,
+                    Ok(None) => self.ino(new_path),
+                    Err(e) => {
+                        warn!(error = %e, old = %old_path, new = %new_path, "Inode not moved");
+                        entry.ino
+                    }
+                };
 
                 // Insert under new path hash
+                self.hot.pin(new_hash);
                 let new_entry = VDirEntry {
                     path_hash: new_hash,
+                    ino,
                     ..entry
                 };
                 match self.vdir.upsert(new_entry) {
                     Ok(_) => {
                         debug!(old = %old_path, new = %new_path, "Manifest rename");
-                        VeloResponse::ManifestAck { entry: None }
+                        VeloResponse::ManifestAck {
+                            entry: None,
+                            ino: 0,
+                        }
                     }
                     Err(e) => {
                         error!(error = %e, "Rename upsert failed");
@@ -231,9 +877,46 @@ impl CommandHandler {
             }
             None => {
                 debug!(path = %old_path, "Rename: source not found, treating as no-op");
-                VeloResponse::ManifestAck { entry: None }
+                VeloResponse::ManifestAck {
+                    entry: None,
+                    ino: 0,
+                }
+            }
+        }
+    }
+
+    /// Move every entry beneath directory `old_path` to the same place under
+    /// `new_path`, as the rename on disk moves the directory's contents.
+    /// Tombstones move too, so files deleted in the directory stay deleted.
+    fn move_children(&mut self, old_path: &str, new_path: &str) -> std::io::Result<()> {
+        let prefix = format!("{}/", old_path.trim_end_matches('/'));
+        let children: Vec<_> = self
+            .manifest
+            .iter()
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+            .into_iter()
+            .filter(|(path, _)| path.starts_with(&prefix))
+            .collect();
+        for (old, entry) in &children {
+            let new = format!(
+                "{}/{}",
+                new_path.trim_end_matches('/'),
+                &old[prefix.len()..]
+            );
+            self.persist_upsert(&new, &entry.vnode)?;
+            self.persist_xattrs(&new, &entry.xattrs())?;
+            self.persist_btime(&new, entry.btime())?;
+            self.drop_entry(old)?;
+            if let Err(e) = self.manifest.inodes().rename(old, &new) {
+                warn!(error = %e, old = %old, new = %new, "Inode not moved");
             }
+            // Lookups of the new path go to the manifest until readmitted
+            let new_hash = fnv1a_hash(&new);
+            self.hot.forget(new_hash);
+            self.vdir.remove(new_hash);
         }
+        debug!(old = %old_path, new = %new_path, count = children.len(), "Moved directory contents");
+        Ok(())
     }
 
     /// Handle ManifestUpdateMtime: update mtime on existing entry
@@ -255,6 +938,8 @@ impl CommandHandler {
                 mode: lmdb_entry.vnode.mode,
                 flags: lmdb_entry.vnode.flags,
                 _pad: [0; 3],
+                btime_sec: lmdb_entry.btime().unwrap_or(0) as i64,
+                ino: self.ino(path),
             })
         } else {
             None
@@ -262,6 +947,7 @@ impl CommandHandler {
 
         match existing {
             Some(entry) => {
+                self.hot.pin(path_hash);
                 let updated = VDirEntry {
                     mtime_sec,
                     mtime_nsec,
@@ -270,7 +956,10 @@ impl CommandHandler {
                 match self.vdir.upsert(updated) {
                     Ok(_) => {
                         debug!(path = %path, mtime_sec, "Updated mtime");
-                        VeloResponse::ManifestAck { entry: None }
+                        VeloResponse::ManifestAck {
+                            entry: None,
+                            ino: 0,
+                        }
                     }
                     Err(e) => {
                         error!(error = %e, "UpdateMtime upsert failed");
@@ -280,13 +969,69 @@ impl CommandHandler {
             }
             None => {
                 debug!(path = %path, "UpdateMtime: entry not found");
-                VeloResponse::ManifestAck { entry: None }
+                VeloResponse::ManifestAck {
+                    entry: None,
+                    ino: 0,
+                }
             }
         }
     }
 
     /// Handle ManifestListDir: list direct children of a directory path
     fn handle_manifest_list_dir(&self, path: &str) -> VeloResponse {
+        let entries = self.list_children(path);
+        debug!(path = %path, count = entries.len(), "ListDir");
+        VeloResponse::ManifestListAck { entries }
+    }
+
+    /// Handle ManifestListDirPage: the children after `after`, from a
+    /// listing kept until the manifest changes or LISTING_CACHE other
+    /// directories have been paged since
+    fn handle_manifest_list_dir_page(&self, path: &str, after: &str, limit: u32) -> VeloResponse {
+        let generation = self.manifest.generation();
+        let mut listings = self.listings.lock().unwrap_or_else(|e| e.into_inner());
+        listings.retain(|l| l.generation == generation);
+        match listings.iter().position(|l| l.path == path) {
+            Some(i) => {
+                let listing = listings.remove(i).expect("position in range");
+                listings.push_front(listing);
+            }
+            None => {
+                let entries = self.list_children(path);
+                listings.truncate(LISTING_CACHE - 1);
+                listings.push_front(Listing {
+                    path: path.to_string(),
+                    generation,
+                    entries,
+                });
+            }
+        }
+        let entries = &listings[0].entries;
+
+        let start = if after.is_empty() {
+            0
+        } else {
+            entries.partition_point(|e| e.name.as_bytes() <= after.as_bytes())
+        };
+        let end = entries
+            .len()
+            .min(start + limit.clamp(1, MAX_LIST_PAGE) as usize);
+        let page = &entries[start..end];
+
+        let mut names = Vec::with_capacity(page.iter().map(|e| e.name.len() + 1).sum());
+        for entry in page {
+            names.extend_from_slice(entry.name.as_bytes());
+            names.push(0);
+        }
+        VeloResponse::ManifestListPageAck {
+            names,
+            dirs: page.iter().map(|e| e.is_dir).collect(),
+            more: end < entries.len(),
+        }
+    }
+
+    /// Direct children of a directory path, in `DirEntry` order
+    fn list_children(&self, path: &str) -> Vec<vrift_ipc::DirEntry> {
         // Build prefix for direct children lookup
         let prefix = if path.is_empty() || path == "/" {
             String::new()
@@ -305,6 +1050,10 @@ impl CommandHandler {
                 if !entry_path.starts_with(&prefix) {
                     continue;
                 }
+                // A tombstone doesn't keep its parent directories listed
+                if manifest_entry.vnode.is_whiteout() {
+                    continue;
+                }
                 // Extract direct child name (strip prefix, take first component)
                 let relative = &entry_path[prefix.len()..];
                 let child_name = if let Some(slash_pos) = relative.find('/') {
@@ -329,7 +1078,7 @@ impl CommandHandler {
                     continue;
                 }
 
-                let is_dir = manifest_entry.vnode.flags & FLAG_DIR != 0;
+                let is_dir = manifest_entry.vnode.is_dir();
                 entries.push(vrift_ipc::DirEntry {
                     name: child_name.to_string(),
                     is_dir,
@@ -337,13 +1086,63 @@ impl CommandHandler {
             }
         }
 
-        debug!(path = %path, count = entries.len(), "ListDir");
-        VeloResponse::ManifestListAck { entries }
+        // LMDB iteration interleaves base and delta layers; callers rely on a stable order
+        vrift_ipc::sort_dir_entries(&mut entries);
+        entries
+    }
+
+    /// The VDir this handler writes (read at shutdown to record its generation)
+    pub fn vdir(&self) -> &VDir {
+        &self.vdir
+    }
+
+    /// Reingests that stored their blob but never reached the VDir
+    pub fn journal(&self) -> &ReingestJournal {
+        &self.journal
+    }
+
+    /// Counters for Metrics, all in memory
+    fn metrics(&self) -> DaemonMetrics {
+        DaemonMetrics {
+            uptime_secs: self.started.elapsed().as_secs(),
+            connections: self.connections.load(std::sync::atomic::Ordering::Relaxed),
+            manifest_entries: self.vdir.entry_count() as u64,
+            pending_journal: self.journal.len() as u64,
+            reingests: self.reingests,
+            recent_reingests: self.recent_reingests.iter().cloned().collect(),
+            heat: self.heat.cells(),
+            vdir_admissions: self.hot.counters().0,
+            vdir_evictions: self.hot.counters().1,
+            vdir_misses_reported: self.hot.reported(),
+            ..Default::default()
+        }
+    }
+
+    fn record_reingest(&mut self, vpath: &str, size: u64) {
+        if self.recent_reingests.len() == RECENT_REINGESTS {
+            self.recent_reingests.pop_front();
+        }
+        self.recent_reingests.push_back(ReingestEvent {
+            vpath: vpath.to_string(),
+            size,
+            at_secs: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        });
+        self.reingests += 1;
     }
 
     /// Handle ManifestReingest (CoW commit)
+    ///
+    /// Journalled: the intent is recorded before the temp file is moved into
+    /// the CAS, the hash once it is there, and the entry cleared after the
+    /// VDir update. A daemon killed in between leaves a recoverable entry.
     async fn handle_reingest(&mut self, vpath: &str, temp_path: &str) -> VeloResponse {
         let temp = PathBuf::from(temp_path);
+        if let Err(e) = self.journal.record(vpath, temp_path) {
+            warn!(error = %e, vpath, "Failed to journal reingest");
+        }
 
         // 1. Initialize CAS store
         let store = match vrift_cas::CasStore::new(&self.config.cas_path) {
@@ -357,69 +1156,377 @@ impl CommandHandler {
             }
         };
 
+        // Rewriting a file keeps its permissions; the CAS blob is always
+        // read-only, so its mode must not leak into the entry. A file new
+        // to the VDir takes the temp file's mode, which the kernel already
+        // masked with the writer's umask.
+        let mode = self
+            .vdir
+            .lookup(fnv1a_hash(vpath))
+            .map(|e| e.mode)
+            .or_else(|| fs::symlink_metadata(&temp).ok().map(|m| m.mode()));
+
         // 2. Ingest to CAS via move (atomic & deduplicated)
         let hash_bytes = match store.store_by_move(&temp) {
             Ok(h) => h,
             Err(e) => {
                 error!(error = %e, temp = %temp_path, "CAS ingestion failed");
-                return VeloResponse::Error(VeloError::new(
-                    VeloErrorKind::IngestFailed,
+                // Nothing was committed; the client still owns the temp file
+                let _ = self.journal.complete(vpath);
+                return VeloResponse::Error(VeloError::with_path(
+                    cas_error_kind(&e, VeloErrorKind::IngestFailed),
                     format!("Ingest error: {}", e),
+                    vpath,
                 ));
             }
         };
 
+        if let Err(e) = self.journal.set_cas_hash(vpath, hash_bytes) {
+            warn!(error = %e, vpath, "Failed to journal reingest hash");
+        }
+
+        match self.publish_reingest(&store, vpath, hash_bytes, mode) {
+            Ok(entry) => {
+                info!(vpath = %vpath, hash = %hex::encode(hash_bytes), "Reingest complete");
+                self.record_reingest(vpath, entry.size);
+                VeloResponse::ManifestAck {
+                    entry: Some(entry),
+                    ino: 0,
+                }
+            }
+            Err(e) => VeloResponse::Error(e),
+        }
+    }
+
+    /// Steps 3 and 4 of a reingest: put the stored blob in the VDir and
+    /// clear the journal entry
+    fn publish_reingest(
+        &mut self,
+        store: &vrift_cas::CasStore,
+        vpath: &str,
+        hash_bytes: [u8; 32],
+        mode: Option<u32>,
+    ) -> Result<VnodeEntry, VeloError> {
         // 3. Get metadata for the committed file
         let cas_path = store.blob_path_for_hash(&hash_bytes).unwrap();
         let meta = match fs::metadata(&cas_path) {
             Ok(m) => m,
             Err(e) => {
-                return VeloResponse::Error(VeloError::io_error(format!("Metadata error: {}", e)));
+                return Err(VeloError::new(
+                    VeloErrorKind::from_io(&e, VeloErrorKind::IoError),
+                    format!("Metadata error: {}", e),
+                ));
             }
         };
 
+        let mode = mode.unwrap_or_else(|| meta.mode());
+
         // 4. Update VDir
+        self.hot.pin(fnv1a_hash(vpath));
         let entry = VDirEntry {
             path_hash: fnv1a_hash(vpath),
             cas_hash: hash_bytes,
             size: meta.len(),
             mtime_sec: meta.mtime(),
             mtime_nsec: meta.mtime_nsec() as u32,
-            mode: meta.mode(),
+            mode,
             flags: if meta.is_dir() { FLAG_DIR } else { 0 },
             _pad: [0; 3],
+            // The CAS blob's own birth means nothing; a rewrite keeps the
+            // file's
+            btime_sec: self.recorded_btime(vpath),
+            ino: self.ino(vpath),
         };
 
+        // A new file is in the VDir only, until the watcher or a commit
+        // gets it into the manifest
+        if let Some(published) = &self.published {
+            published.note_overlay(entry.path_hash);
+        }
         if let Err(e) = self.vdir.upsert(entry) {
-            return VeloResponse::Error(VeloError::io_error(format!("VDir update error: {}", e)));
+            return Err(VeloError::io_error(format!("VDir update error: {}", e)));
         }
 
-        info!(vpath = %vpath, hash = %hex::encode(hash_bytes), "Reingest complete");
-
-        VeloResponse::ManifestAck {
-            entry: Some(VnodeEntry {
-                content_hash: hash_bytes,
-                size: meta.len(),
-                mtime: meta.mtime() as u64,
-                mode: meta.mode(),
-                flags: 0,
-                _pad: 0,
-            }),
+        if let Err(e) = self.journal.complete(vpath) {
+            warn!(error = %e, vpath, "Failed to clear reingest journal entry");
         }
+
+        Ok(VnodeEntry {
+            content_hash: hash_bytes,
+            size: meta.len(),
+            mtime: meta.mtime() as u64,
+            mode,
+            flags: 0,
+            _pad: 0,
+        })
     }
 
-    /// Handle IngestFullScan - unified ingest through daemon
-    /// CLI sends this request instead of doing ingest itself
-    #[allow(clippy::too_many_arguments)]
-    async fn handle_ingest_full_scan(
-        &self,
-        path: &str,
-        manifest_path: &str,
-        threads: Option<usize>,
-        phantom: bool,
-        tier1: bool,
-        prefix: Option<&str>,
-        cas_root_override: Option<&str>,
+    /// Finish the reingests a previous run left in the journal, then clear
+    /// it. An entry whose blob reached the CAS only needs its VDir update;
+    /// one whose temp file is still there is reingested from the start.
+    /// Returns how many were recovered.
+    pub async fn recover_journal(&mut self) -> usize {
+        let pending: Vec<JournalEntry> = self
+            .journal
+            .pending_entries()
+            .into_iter()
+            .cloned()
+            .collect();
+        if pending.is_empty() {
+            return 0;
+        }
+        let store = match vrift_cas::CasStore::new(&self.config.cas_path) {
+            Ok(s) => s,
+            Err(e) => {
+                error!(error = %e, pending = pending.len(), "Journal recovery skipped: CAS unavailable");
+                return 0;
+            }
+        };
+
+        let mut recovered = 0;
+        for entry in pending {
+            let stored = entry
+                .cas_hash
+                .filter(|hash| store.blob_path_for_hash(hash).is_some());
+            let done = if let Some(hash) = stored {
+                let mode = self.vdir.lookup(fnv1a_hash(&entry.vpath)).map(|e| e.mode);
+                self.publish_reingest(&store, &entry.vpath, hash, mode)
+                    .is_ok()
+            } else if Path::new(&entry.temp_path).exists() {
+                let response = self.handle_reingest(&entry.vpath, &entry.temp_path).await;
+                matches!(response, VeloResponse::ManifestAck { .. })
+            } else {
+                false
+            };
+            if done {
+                info!(vpath = %entry.vpath, "Recovered reingest from journal");
+                recovered += 1;
+            } else {
+                warn!(vpath = %entry.vpath, "Reingest from journal not recoverable, dropped");
+            }
+        }
+
+        if let Err(e) = self.journal.clear() {
+            warn!(error = %e, "Failed to clear reingest journal after recovery");
+        }
+        self.recovered = recovered;
+        recovered
+    }
+
+    /// Handle SnapshotRestore: swap the LMDB manifest for a stored
+    /// generation and rebuild the VDir from it.
+    ///
+    /// Entries written only to the VDir (CoW reingests not yet picked up by
+    /// ingest) are discarded with it; that is the point of a rollback.
+    fn handle_snapshot_restore(&mut self, id: &str) -> VeloResponse {
+        let target = match self.snapshots.find(id) {
+            Ok(info) => info,
+            Err(e) => return snapshot_error("Snapshot lookup failed", e),
+        };
+        // Verify before touching anything so a damaged generation can't
+        // leave a half-restored workspace
+        let entries = match self.snapshots.load(&target.id) {
+            Ok(entries) => entries,
+            Err(e) => return snapshot_error("Snapshot unreadable", e),
+        };
+        if let Some(path) = self.protection.first_violation(&entries) {
+            return VeloResponse::Error(VeloError::with_path(
+                VeloErrorKind::PermissionDenied,
+                "Restore would change an entry of a protected snapshot",
+                path,
+            ));
+        }
+        let previous = match self.snapshots.create(
+            &self.manifest,
+            &format!("before restore of {}", &target.id_hex()[..12]),
+        ) {
+            Ok(info) => info,
+            Err(e) => return snapshot_error("Snapshot of current manifest failed", e),
+        };
+        if let Err(e) = self.snapshots.restore(&self.manifest, &target.id) {
+            return snapshot_error("Restore failed", e);
+        }
+        if let Some(wal) = &self.wal {
+            // Logged writes predate the restore and must not be replayed
+            if let Err(e) = wal.reset() {
+                warn!(error = %e, "Failed to clear manifest WAL after restore");
+            }
+        }
+
+        if let Err(e) = self.republish() {
+            // LMDB is already restored; lookups fall back to it
+            error!(error = %e, "VDir rebuild after restore failed");
+        }
+
+        info!(
+            id = %target.id_hex(),
+            entries = target.entries,
+            previous = %previous.id_hex(),
+            "Manifest restored from snapshot"
+        );
+        let protected = self.snapshots.protected().unwrap_or_default();
+        VeloResponse::SnapshotRestoreAck {
+            restored: snapshot_info(&target, &protected),
+            previous: snapshot_info(&previous, &protected),
+        }
+    }
+
+    /// Rebuild the VDir from the manifest as it is now. Shims see the
+    /// generation move and re-read; anything they miss while it refills
+    /// goes to IPC, which waits for this to finish.
+    fn republish(&mut self) -> Result<usize> {
+        self.listings
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        let policy = crate::state::WarmStartPolicy {
+            max_entries: self.hot.capacity(),
+            ..crate::state::WarmStartPolicy::load(&self.config.project_root)
+        };
+        let rebuilt = self.vdir.clear().and_then(|_| {
+            crate::state::warm_start_vdir_with(
+                &mut self.vdir,
+                &self.manifest,
+                &crate::state::DaemonState::default(),
+                &policy,
+            )
+        });
+        self.hot.reset();
+        if self.hot.is_enabled() {
+            if let Err(e) = self.hot.seed(&self.vdir, &self.manifest) {
+                warn!(error = %e, "Failed to reseed VDir hot cache");
+            }
+        }
+        rebuilt?;
+        Ok(self.vdir.entry_count() as usize)
+    }
+
+    /// Handle ManifestReload (also run on SIGHUP): commit what this daemon
+    /// holds, then republish the VDir from the manifest on disk, so edits
+    /// made underneath it (`vrift manifest rm --force`) reach the shims
+    /// without a restart. Where both changed a path, the daemon's
+    /// uncommitted write wins.
+    pub fn handle_manifest_reload(&mut self) -> VeloResponse {
+        let provenance =
+            vrift_manifest::Provenance::capture("vdir_d reload", Some(&self.config.project_root));
+        let committed = match &self.wal {
+            Some(wal) => wal.commit_with_provenance(&provenance),
+            None => self.manifest.commit_with_provenance(&provenance),
+        };
+        if let Err(e) = committed {
+            return snapshot_error("Commit before reload failed", e);
+        }
+        if let Some(published) = &self.published {
+            if let Err(e) = published.publish(&self.manifest) {
+                warn!(error = %e, "Manifest mmap not republished on reload");
+            }
+        }
+        match self.republish() {
+            Ok(entries) => {
+                let generation = self.vdir.generation();
+                info!(entries, generation, "Manifest reloaded");
+                VeloResponse::ManifestReloadAck {
+                    entries: entries as u64,
+                    generation,
+                }
+            }
+            Err(e) => {
+                error!(error = %e, "VDir rebuild on reload failed");
+                VeloResponse::Error(VeloError::internal(format!("Reload failed: {}", e)))
+            }
+        }
+    }
+
+    /// Handle BuildCacheKey over the manifest as shims see it now: each
+    /// entry as the VDir overlay has it if it's there (including
+    /// tombstones), else as LMDB has it
+    fn handle_build_cache_key(
+        &self,
+        inputs: &[String],
+        exclude: &[String],
+        command: &[String],
+    ) -> VeloResponse {
+        match self.manifest.iter() {
+            Ok(all) => {
+                let current: Vec<(&str, VnodeEntry)> = all
+                    .iter()
+                    .map(|(path, e)| match self.vdir.lookup(fnv1a_hash(path)) {
+                        Some(live) => (path.as_str(), vdir_vnode(live)),
+                        None => (path.as_str(), e.vnode.clone()),
+                    })
+                    .collect();
+                let (key, entries) = build_cache_key(
+                    current.iter().map(|(path, vnode)| (*path, vnode)),
+                    inputs,
+                    exclude,
+                    command,
+                );
+                debug!(entries, command = ?command, "BuildCacheKey");
+                VeloResponse::BuildCacheKeyAck { key, entries }
+            }
+            Err(e) => {
+                VeloResponse::Error(VeloError::internal(format!("Manifest read failed: {}", e)))
+            }
+        }
+    }
+
+    /// Handle SnapshotCreate, protecting the new snapshot if asked
+    fn handle_snapshot_create(&mut self, label: &str, protect: bool) -> VeloResponse {
+        let info = match self.snapshots.create(&self.manifest, label) {
+            Ok(info) => info,
+            Err(e) => return snapshot_error("Snapshot failed", e),
+        };
+        info!(id = %info.id_hex(), entries = info.entries, "Snapshot created");
+        if protect {
+            if let Err(e) = self
+                .protection
+                .protect(&self.snapshots, &self.config.cas_path, &info)
+            {
+                return snapshot_error("Protecting snapshot failed", e);
+            }
+        }
+        let protected = self.snapshots.protected().unwrap_or_default();
+        VeloResponse::SnapshotAck {
+            snapshot: snapshot_info(&info, &protected),
+        }
+    }
+
+    /// Handle SnapshotUnprotect: only with `force`, since it lets later
+    /// sessions rewrite what the snapshot released
+    fn handle_snapshot_unprotect(&mut self, id: &str, force: bool) -> VeloResponse {
+        if !force {
+            return VeloResponse::Error(VeloError::permission_denied(
+                "Unprotecting a snapshot requires force",
+            ));
+        }
+        let target = match self.snapshots.find(id) {
+            Ok(info) => info,
+            Err(e) => return snapshot_error("Snapshot lookup failed", e),
+        };
+        if let Err(e) = self
+            .protection
+            .unprotect(&self.snapshots, &self.config.cas_path, &target)
+        {
+            return snapshot_error("Unprotecting snapshot failed", e);
+        }
+        let protected = self.snapshots.protected().unwrap_or_default();
+        VeloResponse::SnapshotAck {
+            snapshot: snapshot_info(&target, &protected),
+        }
+    }
+
+    /// Handle IngestFullScan - unified ingest through daemon
+    /// CLI sends this request instead of doing ingest itself
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_ingest_full_scan(
+        &self,
+        path: &str,
+        manifest_path: &str,
+        threads: Option<usize>,
+        phantom: bool,
+        tier1: bool,
+        prefix: Option<&str>,
+        cas_root_override: Option<&str>,
     ) -> VeloResponse {
         use std::time::Instant;
         use vrift_cas::{parallel_ingest_with_progress, IngestMode};
@@ -569,11 +1676,16 @@ impl CommandHandler {
 
             let prefix_str = prefix.unwrap_or("");
             let key = if prefix_str == "/" || prefix_str.is_empty() {
-                format!("/{}", rel.display())
+                vrift_ipc::path_key_for(rel)
             } else {
-                format!("{}/{}", prefix_str.trim_end_matches('/'), rel.display())
+                format!(
+                    "{}{}",
+                    prefix_str.trim_end_matches('/'),
+                    vrift_ipc::path_key_for(rel)
+                )
             };
 
+            manifest.set_xattrs(&key, &crate::ingest::read_xattrs(&result.source_path));
             manifest.insert(&key, entry);
         }
 
@@ -585,6 +1697,102 @@ impl CommandHandler {
     }
 }
 
+fn snapshot_info(info: &GenerationInfo, protected: &[vrift_cas::Blake3Hash]) -> SnapshotInfo {
+    SnapshotInfo {
+        id: info.id_hex(),
+        created_secs: info.created_secs,
+        entries: info.entries,
+        label: info.label.clone(),
+        protected: protected.contains(&info.id),
+    }
+}
+
+fn snapshot_error(context: &str, err: LmdbError) -> VeloResponse {
+    warn!(error = %err, "{}", context);
+    let kind = match &err {
+        LmdbError::NotFound(_) => VeloErrorKind::NotFound,
+        LmdbError::Corrupted(_) => VeloErrorKind::Corrupt,
+        LmdbError::Io(e) => VeloErrorKind::from_io(e, VeloErrorKind::IoError),
+        LmdbError::Heed(_) => VeloErrorKind::IoError,
+        LmdbError::SymlinkLoop(_) => VeloErrorKind::SymlinkLoop,
+    };
+    VeloResponse::Error(VeloError::new(kind, format!("{}: {}", context, err)))
+}
+
+/// Error reply for a manifest write that could not be logged
+fn io_error_response(err: &std::io::Error, path: &str) -> VeloResponse {
+    VeloResponse::Error(VeloError::with_path(
+        VeloErrorKind::from_io(err, VeloErrorKind::IoError),
+        format!("Manifest write not persisted: {}", err),
+        path,
+    ))
+}
+
+/// Wire kind for a CAS failure
+fn cas_error_kind(err: &vrift_cas::CasError, fallback: VeloErrorKind) -> VeloErrorKind {
+    match err {
+        vrift_cas::CasError::Io(e) => VeloErrorKind::from_io(e, fallback),
+        vrift_cas::CasError::NotFound { .. } => VeloErrorKind::NotFound,
+        vrift_cas::CasError::HashMismatch { .. } => VeloErrorKind::Corrupt,
+    }
+}
+
+/// `path` is `prefix` or lies beneath it ("/" and "" cover everything)
+fn under_prefix(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    prefix.is_empty()
+        || path == prefix
+        || (path.starts_with(prefix) && path.as_bytes().get(prefix.len()) == Some(&b'/'))
+}
+
+/// The manifest entry a VDir entry stands for
+fn vdir_vnode(entry: &VDirEntry) -> VnodeEntry {
+    VnodeEntry {
+        content_hash: entry.cas_hash,
+        size: entry.size,
+        mtime: entry.mtime_sec as u64,
+        mode: entry.mode,
+        flags: flags_to_vnode(entry.flags),
+        _pad: 0,
+    }
+}
+
+/// See `VeloRequest::BuildCacheKey`. Entries are hashed in path order with
+/// each field length-delimited, so no two inputs collide by concatenation.
+pub fn build_cache_key<'a>(
+    entries: impl IntoIterator<Item = (&'a str, &'a VnodeEntry)>,
+    inputs: &[String],
+    exclude: &[String],
+    command: &[String],
+) -> ([u8; 32], u64) {
+    let mut selected: Vec<(&str, &VnodeEntry)> = entries
+        .into_iter()
+        .filter(|(path, entry)| {
+            !entry.is_whiteout()
+                && (inputs.is_empty() || inputs.iter().any(|p| under_prefix(path, p)))
+                && !exclude.iter().any(|p| under_prefix(path, p))
+        })
+        .collect();
+    selected.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"vrift-build-cache-v1\0");
+    for (path, entry) in &selected {
+        hasher.update(&(path.len() as u64).to_le_bytes());
+        hasher.update(path.as_bytes());
+        hasher.update(&entry.content_hash);
+        hasher.update(&entry.size.to_le_bytes());
+        hasher.update(&entry.mode.to_le_bytes());
+        hasher.update(&entry.flags.to_le_bytes());
+    }
+    hasher.update(&(command.len() as u64).to_le_bytes());
+    for arg in command {
+        hasher.update(&(arg.len() as u64).to_le_bytes());
+        hasher.update(arg.as_bytes());
+    }
+    (*hasher.finalize().as_bytes(), selected.len() as u64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -604,7 +1812,10 @@ mod tests {
         let manifest =
             std::sync::Arc::new(vrift_manifest::lmdb::LmdbManifest::open(&manifest_path).unwrap());
 
-        (CommandHandler::new(config, vdir, manifest), temp)
+        let journal =
+            ReingestJournal::open(&ReingestJournal::path_for(&config.project_root)).unwrap();
+
+        (CommandHandler::new(config, vdir, manifest, journal), temp)
     }
 
     // ==================== Handshake Tests ====================
@@ -633,6 +1844,32 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_negotiate_agrees_on_shared_capabilities() {
+        let (mut handler, _temp) = create_test_handler();
+
+        let response = handler
+            .handle_request(VeloRequest::Negotiate {
+                client_version: "1.0.0".to_string(),
+                protocol_version: PROTOCOL_VERSION + 1,
+                capabilities: Capabilities::RENAME.union(Capabilities::CHUNKED_CAS),
+            })
+            .await;
+
+        match response {
+            VeloResponse::NegotiateAck {
+                protocol_version,
+                capabilities,
+                ..
+            } => {
+                // A newer client is talked to in our version
+                assert_eq!(protocol_version, Some(PROTOCOL_VERSION));
+                assert_eq!(capabilities, Capabilities::RENAME);
+            }
+            other => panic!("Expected NegotiateAck, got {:?}", other),
+        }
+    }
+
     // ==================== Status Tests ====================
 
     #[tokio::test]
@@ -702,7 +1939,7 @@ mod tests {
             .await;
 
         match response {
-            VeloResponse::ManifestAck { entry: Some(e) } => {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => {
                 assert_eq!(e.size, 1000);
                 assert_eq!(e.content_hash, [42; 32]);
                 assert_eq!(e.mtime, 1234567890);
@@ -753,7 +1990,7 @@ mod tests {
             .await;
 
         match response {
-            VeloResponse::ManifestAck { entry: Some(e) } => {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => {
                 assert_eq!(e.size, 200);
             }
             _ => panic!("Expected 200"),
@@ -773,7 +2010,7 @@ mod tests {
             .await;
 
         match response {
-            VeloResponse::ManifestAck { entry: None } => {}
+            VeloResponse::ManifestAck { entry: None, .. } => {}
             _ => panic!("Expected ManifestAck with None"),
         }
     }
@@ -808,7 +2045,7 @@ mod tests {
             .await;
 
         match response {
-            VeloResponse::ManifestAck { entry: Some(e) } => {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => {
                 assert_eq!(e.content_hash, original.content_hash);
                 assert_eq!(e.size, original.size);
                 assert_eq!(e.mtime, original.mtime);
@@ -822,7 +2059,7 @@ mod tests {
     // ==================== ManifestRemove Tests ====================
 
     #[tokio::test]
-    async fn test_manifest_remove_clears_dirty() {
+    async fn test_manifest_remove_leaves_tombstone() {
         let (mut handler, _temp) = create_test_handler();
 
         // Insert with dirty flag
@@ -840,7 +2077,6 @@ mod tests {
             })
             .await;
 
-        // Remove (clears dirty in current implementation)
         let response = handler
             .handle_request(VeloRequest::ManifestRemove {
                 path: "dirty.txt".to_string(),
@@ -849,8 +2085,26 @@ mod tests {
 
         assert!(matches!(
             response,
-            VeloResponse::ManifestAck { entry: None }
+            VeloResponse::ManifestAck { entry: None, .. }
         ));
+        assert!(handler
+            .vdir
+            .lookup(fnv1a_hash("dirty.txt"))
+            .is_some_and(|e| e.is_deleted()));
+        match handler
+            .handle_request(VeloRequest::ManifestGet {
+                path: "dirty.txt".to_string(),
+            })
+            .await
+        {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => assert!(e.is_whiteout()),
+            other => panic!("Expected a tombstone, got {:?}", other),
+        }
+        assert!(handler
+            .manifest
+            .get("dirty.txt")
+            .unwrap()
+            .is_some_and(|e| e.vnode.is_whiteout()));
     }
 
     // ==================== ManifestReingest Tests ====================
@@ -874,7 +2128,7 @@ mod tests {
             .await;
 
         match response {
-            VeloResponse::ManifestAck { entry: Some(e) } => {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => {
                 // Verify expected BLAKE3 hash of "Hello, World!"
                 let expected = blake3::hash(b"Hello, World!");
                 assert_eq!(e.content_hash, *expected.as_bytes());
@@ -892,13 +2146,158 @@ mod tests {
             .await;
 
         match response {
-            VeloResponse::ManifestAck { entry: Some(e) } => {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => {
                 assert_eq!(e.size, 13);
             }
             _ => panic!("Entry not found after reingest"),
         }
     }
 
+    #[tokio::test]
+    async fn test_recover_journal_finishes_interrupted_reingests() {
+        let (mut handler, temp) = create_test_handler();
+
+        // Crashed after the CAS store, before the VDir update
+        let store = vrift_cas::CasStore::new(&handler.config.cas_path).unwrap();
+        let hash = store.store(b"stored before crash").unwrap();
+        handler
+            .journal
+            .record("stored.txt", "/gone/stored.tmp")
+            .unwrap();
+        handler.journal.set_cas_hash("stored.txt", hash).unwrap();
+
+        // Crashed before the temp file was moved
+        let staging = temp.path().join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        let temp_file = staging.join("pending.tmp");
+        std::fs::write(&temp_file, b"still staged").unwrap();
+        handler
+            .journal
+            .record("pending.txt", temp_file.to_str().unwrap())
+            .unwrap();
+
+        // Nothing left to recover from
+        handler
+            .journal
+            .record("lost.txt", "/gone/lost.tmp")
+            .unwrap();
+
+        assert_eq!(handler.recover_journal().await, 2);
+        assert!(handler.journal.is_empty());
+        assert!(!temp_file.exists());
+
+        for (path, size) in [("stored.txt", 19), ("pending.txt", 12)] {
+            match handler
+                .handle_request(VeloRequest::ManifestGet {
+                    path: path.to_string(),
+                })
+                .await
+            {
+                VeloResponse::ManifestAck { entry: Some(e), .. } => assert_eq!(e.size, size),
+                other => panic!("{} not recovered: {:?}", path, other),
+            }
+        }
+        match handler.handle_request(VeloRequest::Status).await {
+            VeloResponse::StatusAck { status } => {
+                assert_eq!(status, "ready (recovered 2 reingests after crash)")
+            }
+            other => panic!("Expected StatusAck, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reingest_keeps_file_mode_not_cas_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let (mut handler, temp) = create_test_handler();
+        let staging = temp.path().join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        let reingest = |name: &str, perm: u32| {
+            let file = staging.join(name);
+            std::fs::write(&file, name).unwrap();
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(perm)).unwrap();
+            VeloRequest::ManifestReingest {
+                vpath: format!("{}.sh", name),
+                temp_path: file.to_str().unwrap().to_string(),
+            }
+        };
+
+        // New file: the temp file's (umask-masked) mode
+        match handler.handle_request(reingest("new", 0o640)).await {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => assert_eq!(e.mode & 0o7777, 0o640),
+            other => panic!("Expected ManifestAck, got {:?}", other),
+        }
+
+        // Rewritten file: keeps the mode it already had
+        handler
+            .handle_request(VeloRequest::ManifestUpsert {
+                path: "tool.sh".to_string(),
+                entry: VnodeEntry {
+                    content_hash: [0; 32],
+                    size: 0,
+                    mtime: 0,
+                    mode: 0o100755,
+                    flags: 0,
+                    _pad: 0,
+                },
+            })
+            .await;
+        match handler.handle_request(reingest("tool", 0o600)).await {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => assert_eq!(e.mode, 0o100755),
+            other => panic!("Expected ManifestAck, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_reports_recent_reingests() {
+        let (mut handler, temp) = create_test_handler();
+
+        let temp_file = temp.path().join("staging").join("m.tmp");
+        std::fs::create_dir_all(temp_file.parent().unwrap()).unwrap();
+        std::fs::write(&temp_file, b"metrics").unwrap();
+        handler
+            .handle_request(VeloRequest::ManifestReingest {
+                vpath: "m.txt".to_string(),
+                temp_path: temp_file.to_str().unwrap().to_string(),
+            })
+            .await;
+
+        match handler.handle_request(VeloRequest::Metrics).await {
+            VeloResponse::MetricsAck { metrics } => {
+                assert_eq!(metrics.reingests, 1);
+                assert_eq!(metrics.recent_reingests[0].vpath, "m.txt");
+                assert_eq!(metrics.recent_reingests[0].size, 7);
+                assert!(metrics.manifest_entries >= 1);
+                assert_eq!(metrics.pending_journal, 0);
+            }
+            _ => panic!("Expected MetricsAck"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_reports_lookup_heat() {
+        let (mut handler, _temp) = create_test_handler();
+
+        for _ in 0..2 {
+            handler
+                .handle_request(VeloRequest::ManifestGet {
+                    path: "/src/pkg/missing.rs".to_string(),
+                })
+                .await;
+        }
+
+        match handler.handle_request(VeloRequest::Metrics).await {
+            VeloResponse::MetricsAck { metrics } => {
+                let cell = metrics
+                    .heat
+                    .iter()
+                    .find(|c| c.prefix == "/src/pkg" && c.op == "ipc_get")
+                    .expect("ipc_get heat cell");
+                assert_eq!(cell.count, 2);
+            }
+            _ => panic!("Expected MetricsAck"),
+        }
+    }
+
     #[tokio::test]
     async fn test_reingest_nonexistent_file_returns_error() {
         let (mut handler, _temp) = create_test_handler();
@@ -913,9 +2312,12 @@ mod tests {
         match response {
             VeloResponse::Error(err) => {
                 assert!(err.message.contains("Ingest error"));
+                assert_eq!(err.kind, VeloErrorKind::NotFound);
             }
             _ => panic!("Expected Error for nonexistent file"),
         }
+        // Nothing reached the CAS, so there is nothing to recover
+        assert!(handler.journal().is_empty());
     }
 
     // ==================== ManifestRename Tests ====================
@@ -956,82 +2358,454 @@ mod tests {
             })
             .await;
         match response {
-            VeloResponse::ManifestAck { entry: Some(e) } => {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => {
                 assert_eq!(e.content_hash, [42; 32]);
                 assert_eq!(e.size, 1000);
             }
             _ => panic!("Expected entry at new path"),
         }
+
+        // ...and the old one is gone for everyone reading the VDir
+        assert!(handler.vdir.lookup(fnv1a_hash("old/path.txt")).is_none());
+        assert!(matches!(
+            handler
+                .handle_request(VeloRequest::ManifestGet {
+                    path: "old/path.txt".to_string(),
+                })
+                .await,
+            VeloResponse::ManifestAck { entry: None, .. }
+        ));
+        assert!(handler.manifest.get("new/path.txt").unwrap().is_some());
+        assert!(handler.manifest.get("old/path.txt").unwrap().is_none());
     }
 
     #[tokio::test]
-    async fn test_manifest_rename_nonexistent_is_noop() {
+    async fn test_manifest_rename_moves_directory_contents() {
         let (mut handler, _temp) = create_test_handler();
+        let file = VnodeEntry::new_file([7; 32], 10, 0, 0o100644);
+        for (path, entry) in [
+            ("/dir", VnodeEntry::new_directory(0, 0o40755)),
+            ("/dir/a.txt", file.clone()),
+            ("/dir/sub", VnodeEntry::new_directory(0, 0o40755)),
+            ("/dir/sub/b.txt", file.clone()),
+            ("/dirt.txt", file.clone()),
+        ] {
+            handler
+                .handle_request(VeloRequest::ManifestUpsert {
+                    path: path.to_string(),
+                    entry,
+                })
+                .await;
+        }
+        handler
+            .handle_request(VeloRequest::ManifestRemove {
+                path: "/dir/gone.txt".to_string(),
+            })
+            .await;
 
         let response = handler
             .handle_request(VeloRequest::ManifestRename {
-                old_path: "nonexistent.txt".to_string(),
-                new_path: "new.txt".to_string(),
+                old_path: "/dir".to_string(),
+                new_path: "/moved".to_string(),
             })
             .await;
-        assert!(matches!(
-            response,
-            VeloResponse::ManifestAck { entry: None }
-        ));
+        assert!(matches!(response, VeloResponse::ManifestAck { .. }));
+
+        for path in ["/moved", "/moved/a.txt", "/moved/sub", "/moved/sub/b.txt"] {
+            assert!(
+                matches!(
+                    handler
+                        .handle_request(VeloRequest::ManifestGet {
+                            path: path.to_string(),
+                        })
+                        .await,
+                    VeloResponse::ManifestAck { entry: Some(_), .. }
+                ),
+                "{path} missing after rename"
+            );
+        }
+        for path in ["/dir", "/dir/a.txt", "/dir/sub/b.txt", "/dir/gone.txt"] {
+            assert!(
+                handler.manifest.get(path).unwrap().is_none(),
+                "{path} left behind"
+            );
+        }
+        // A deletion inside the directory moves with it; a sibling sharing
+        // the name prefix stays put
+        assert!(handler
+            .manifest
+            .get("/moved/gone.txt")
+            .unwrap()
+            .unwrap()
+            .vnode
+            .is_whiteout());
+        assert!(handler.manifest.get("/dirt.txt").unwrap().is_some());
     }
 
-    // ==================== ManifestUpdateMtime Tests ====================
+    /// The inode a ManifestGet reports for `path`
+    async fn ino_of(handler: &mut CommandHandler, path: &str) -> u64 {
+        let path = path.to_string();
+        match handler
+            .handle_request(VeloRequest::ManifestGet { path })
+            .await
+        {
+            VeloResponse::ManifestAck {
+                entry: Some(_),
+                ino,
+            } => ino,
+            other => panic!("Expected entry, got {:?}", other),
+        }
+    }
 
     #[tokio::test]
-    async fn test_manifest_update_mtime() {
+    async fn test_manifest_get_reports_table_inode() {
         let (mut handler, _temp) = create_test_handler();
-
-        // Insert a file
+        let entry = VnodeEntry::new_file([7; 32], 3, 1, 0o100644);
         handler
             .handle_request(VeloRequest::ManifestUpsert {
-                path: "test.txt".to_string(),
-                entry: VnodeEntry {
-                    content_hash: [0; 32],
-                    size: 100,
-                    mtime: 1000,
-                    mode: 0o644,
-                    flags: 0,
-                    _pad: 0,
-                },
+                path: "/a.txt".to_string(),
+                entry,
             })
             .await;
+        let ino = handler.vdir.lookup(fnv1a_hash("/a.txt")).unwrap().ino;
+        assert_ne!(ino, 0);
 
-        // Update mtime (nanoseconds)
-        let new_mtime_ns: u64 = 5_000_000_000 + 500_000_000; // 5.5 seconds
-        let response = handler
-            .handle_request(VeloRequest::ManifestUpdateMtime {
-                path: "test.txt".to_string(),
-                mtime_ns: new_mtime_ns,
-            })
-            .await;
-        assert!(matches!(response, VeloResponse::ManifestAck { .. }));
+        // Served from LMDB alone (evicted, or past the VDir cap), it keeps
+        // the inode it had in the VDir
+        handler.vdir.clear().unwrap();
+        assert_eq!(ino_of(&mut handler, "/a.txt").await, ino);
 
-        // Verify mtime was updated
-        let response = handler
-            .handle_request(VeloRequest::ManifestGet {
-                path: "test.txt".to_string(),
+        // A rename of such an entry takes its inode along
+        handler.vdir.clear().unwrap();
+        handler
+            .handle_request(VeloRequest::ManifestRename {
+                old_path: "/a.txt".to_string(),
+                new_path: "/b.txt".to_string(),
             })
             .await;
-        match response {
-            VeloResponse::ManifestAck { entry: Some(e) } => {
-                assert_eq!(e.mtime, 5); // 5 seconds
-                assert_eq!(e.size, 100); // size preserved
-            }
-            _ => panic!("Expected entry"),
-        }
+        assert_eq!(handler.vdir.lookup(fnv1a_hash("/b.txt")).unwrap().ino, ino);
+        handler.vdir.clear().unwrap();
+        assert_eq!(ino_of(&mut handler, "/b.txt").await, ino);
     }
 
-    // ==================== ManifestListDir Tests ====================
-
     #[tokio::test]
-    async fn test_manifest_list_dir_empty() {
-        let (mut handler, _temp) = create_test_handler();
-
+    async fn test_upserts_outlive_the_vdir() {
+        let (handler, temp) = create_test_handler();
+        let wal_path = crate::wal::ManifestWal::path_for(temp.path());
+        let (wal, _) = ManifestWal::open(&wal_path, handler.manifest.clone()).unwrap();
+        let wal = std::sync::Arc::new(wal);
+        let mut handler = handler.with_wal(wal.clone());
+
+        // A virtual mkdir, as the shim sends it
+        let mkdir = VnodeEntry {
+            content_hash: [0; 32],
+            size: 0,
+            mtime: 1,
+            mode: 0o755,
+            flags: 1,
+            _pad: 0,
+        };
+        handler
+            .handle_request(VeloRequest::ManifestUpsert {
+                path: "/out/gen".to_string(),
+                entry: mkdir,
+            })
+            .await;
+        assert!(!wal.is_empty());
+
+        // Cold start: the VDir is rebuilt, only the manifest remains
+        handler.vdir.clear().unwrap();
+        assert!(matches!(
+            handler
+                .handle_request(VeloRequest::ManifestGet {
+                    path: "/out/gen".to_string(),
+                })
+                .await,
+            VeloResponse::ManifestAck { entry: Some(e), .. } if e.is_dir()
+        ));
+        match handler
+            .handle_request(VeloRequest::ManifestListDir {
+                path: "/out".to_string(),
+            })
+            .await
+        {
+            VeloResponse::ManifestListAck { entries } => {
+                assert_eq!(entries.len(), 1);
+                assert_eq!(entries[0].name, "gen");
+                assert!(entries[0].is_dir);
+            }
+            other => panic!("Expected ManifestListAck, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_republishes_manifest_edits() {
+        let (mut handler, _temp) = create_test_handler();
+        let tier = vrift_manifest::lmdb::AssetTier::Tier2Mutable;
+        handler
+            .manifest
+            .insert("/keep.rs", VnodeEntry::new_file([1; 32], 1, 0, 0o644), tier);
+        handler
+            .manifest
+            .insert("/gone.rs", VnodeEntry::new_file([2; 32], 2, 0, 0o644), tier);
+        handler.manifest.commit().unwrap();
+        handler.republish().unwrap();
+        assert!(handler.vdir.lookup(fnv1a_hash("/gone.rs")).is_some());
+
+        // An edit the VDir doesn't know about, like `vrift manifest rm --force`
+        handler.manifest.remove("/gone.rs");
+        handler.manifest.commit().unwrap();
+        let before = handler.vdir.generation();
+
+        match handler.handle_request(VeloRequest::ManifestReload).await {
+            VeloResponse::ManifestReloadAck {
+                entries,
+                generation,
+            } => {
+                assert_eq!(entries, 1);
+                assert!(generation > before);
+                assert_eq!(generation % 2, 0, "seqlock left stable");
+            }
+            other => panic!("Expected ManifestReloadAck, got {:?}", other),
+        }
+        assert!(handler.vdir.lookup(fnv1a_hash("/gone.rs")).is_none());
+        assert!(handler.vdir.lookup(fnv1a_hash("/keep.rs")).is_some());
+    }
+
+    #[test]
+    fn test_refresh_entry_follows_manifest() {
+        let (mut handler, _temp) = create_test_handler();
+        let tier = vrift_manifest::lmdb::AssetTier::Tier2Mutable;
+        let vnode = |size| VnodeEntry::new_file([size as u8; 32], size, 0, 0o644);
+        let hash = fnv1a_hash("/src/lib.rs");
+
+        handler.manifest.insert("/src/lib.rs", vnode(1), tier);
+        handler
+            .vdir
+            .upsert(VDirEntry {
+                path_hash: hash,
+                size: 1,
+                ..Default::default()
+            })
+            .unwrap();
+
+        // Not published: nothing to do, IPC already reads the manifest
+        handler.refresh_entry("/src/other.rs");
+        assert!(handler.vdir.lookup(fnv1a_hash("/src/other.rs")).is_none());
+
+        // The watcher saw the file change on disk
+        handler.manifest.insert("/src/lib.rs", vnode(7), tier);
+        handler.refresh_entry("/src/lib.rs");
+        assert_eq!(handler.vdir.lookup(hash).map(|e| e.size), Some(7));
+
+        // ...and then disappear
+        handler.manifest.remove("/src/lib.rs");
+        handler.refresh_entry("/src/lib.rs");
+        assert!(handler.vdir.lookup(hash).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_manifest_rename_nonexistent_is_noop() {
+        let (mut handler, _temp) = create_test_handler();
+
+        let response = handler
+            .handle_request(VeloRequest::ManifestRename {
+                old_path: "nonexistent.txt".to_string(),
+                new_path: "new.txt".to_string(),
+            })
+            .await;
+        assert!(matches!(
+            response,
+            VeloResponse::ManifestAck { entry: None, .. }
+        ));
+    }
+
+    // ==================== ManifestUpdateMtime Tests ====================
+
+    #[tokio::test]
+    async fn test_manifest_update_mtime() {
+        let (mut handler, _temp) = create_test_handler();
+
+        // Insert a file
+        handler
+            .handle_request(VeloRequest::ManifestUpsert {
+                path: "test.txt".to_string(),
+                entry: VnodeEntry {
+                    content_hash: [0; 32],
+                    size: 100,
+                    mtime: 1000,
+                    mode: 0o644,
+                    flags: 0,
+                    _pad: 0,
+                },
+            })
+            .await;
+
+        // Update mtime (nanoseconds)
+        let new_mtime_ns: u64 = 5_000_000_000 + 500_000_000; // 5.5 seconds
+        let response = handler
+            .handle_request(VeloRequest::ManifestUpdateMtime {
+                path: "test.txt".to_string(),
+                mtime_ns: new_mtime_ns,
+            })
+            .await;
+        assert!(matches!(response, VeloResponse::ManifestAck { .. }));
+
+        // Verify mtime was updated
+        let response = handler
+            .handle_request(VeloRequest::ManifestGet {
+                path: "test.txt".to_string(),
+            })
+            .await;
+        match response {
+            VeloResponse::ManifestAck { entry: Some(e), .. } => {
+                assert_eq!(e.mtime, 5); // 5 seconds
+                assert_eq!(e.size, 100); // size preserved
+            }
+            _ => panic!("Expected entry"),
+        }
+    }
+
+    // ==================== Xattr Tests ====================
+
+    #[tokio::test]
+    async fn test_manifest_xattrs_follow_entry() {
+        let (mut handler, _temp) = create_test_handler();
+        let file = |mode| VnodeEntry {
+            content_hash: [7; 32],
+            size: 1,
+            mtime: 1,
+            mode,
+            flags: 0,
+            _pad: 0,
+        };
+        let xattrs = |handler: &CommandHandler, path: &str| match handler.handle_lookup(
+            &VeloRequest::ManifestGetXattrs {
+                path: path.to_string(),
+            },
+        ) {
+            Some(VeloResponse::ManifestXattrsAck { xattrs }) => Some(xattrs),
+            _ => None,
+        };
+        let set = |path: &str, name: &str, value: Option<&[u8]>| VeloRequest::ManifestSetXattr {
+            path: path.to_string(),
+            name: name.to_string(),
+            value: value.map(<[u8]>::to_vec),
+        };
+
+        let response = handler
+            .handle_request(set("/app", "user.x", Some(b"1")))
+            .await;
+        assert!(matches!(response, VeloResponse::Error(e) if e.kind == VeloErrorKind::NotFound));
+
+        handler
+            .handle_request(VeloRequest::ManifestUpsert {
+                path: "/app".to_string(),
+                entry: file(0o644),
+            })
+            .await;
+        for request in [
+            set("/app", "user.b", Some(b"2")),
+            set("/app", "user.a", Some(b"1")),
+            set("/app", "user.gone", Some(b"")),
+            set("/app", "user.gone", None),
+        ] {
+            let response = handler.handle_request(request).await;
+            assert!(matches!(response, VeloResponse::ManifestAck { .. }));
+        }
+
+        // A chmod (upsert) keeps them, a rename moves them
+        handler
+            .handle_request(VeloRequest::ManifestUpsert {
+                path: "/app".to_string(),
+                entry: file(0o755),
+            })
+            .await;
+        handler
+            .handle_request(VeloRequest::ManifestRename {
+                old_path: "/app".to_string(),
+                new_path: "/bin/app".to_string(),
+            })
+            .await;
+        let moved = xattrs(&handler, "/bin/app").unwrap();
+        let names: Vec<&str> = moved.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, ["user.a", "user.b"]);
+        assert_eq!(moved[1].value, b"2");
+        assert!(xattrs(&handler, "/app").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_manifest_resolve_follows_links_and_stops_loops() {
+        let (mut handler, temp) = create_test_handler();
+        handler.config.cas_path = temp.path().join("cas");
+        let store = vrift_cas::CasStore::new(&handler.config.cas_path).unwrap();
+        let link = |content_hash| VnodeEntry {
+            content_hash,
+            size: 0,
+            mtime: 1,
+            mode: 0o777,
+            flags: 2,
+            _pad: 0,
+        };
+        let tier = vrift_manifest::lmdb::AssetTier::Tier1Immutable;
+        let resolve = |handler: &CommandHandler, path: &str| {
+            handler.handle_lookup(&VeloRequest::ManifestResolve {
+                path: path.to_string(),
+                follow_final: true,
+            })
+        };
+
+        // Target recorded with the entry, in a CAS blob, and on disk
+        handler
+            .manifest
+            .insert_symlink("/lib/libz.so", b"libz.so.1", 1, tier);
+        for (path, target) in [("/lib/libz.so.1", "../real/libz"), ("/real", "/store")] {
+            let hash = store.store(target.as_bytes()).unwrap();
+            handler
+                .handle_request(VeloRequest::ManifestUpsert {
+                    path: path.to_string(),
+                    entry: link(hash),
+                })
+                .await;
+        }
+        fs::create_dir(temp.path().join("store")).unwrap();
+        std::os::unix::fs::symlink("libz.so.1.3", temp.path().join("store/libz")).unwrap();
+        handler
+            .handle_request(VeloRequest::ManifestUpsert {
+                path: "/store/libz".to_string(),
+                entry: link([0; 32]),
+            })
+            .await;
+        assert!(matches!(
+            resolve(&handler, "/lib/libz.so"),
+            Some(VeloResponse::ManifestResolveAck { path }) if path == "/store/libz.so.1.3"
+        ));
+        assert!(matches!(
+            handler.handle_lookup(&VeloRequest::ManifestResolve {
+                path: "/lib/libz.so".to_string(),
+                follow_final: false,
+            }),
+            Some(VeloResponse::ManifestResolveAck { path }) if path == "/lib/libz.so"
+        ));
+
+        // A cycle is ELOOP, not a hang
+        handler.manifest.insert_symlink("/a", b"b", 1, tier);
+        handler.manifest.insert_symlink("/b", b"/a", 1, tier);
+        match resolve(&handler, "/a") {
+            Some(VeloResponse::Error(e)) => {
+                assert_eq!(e.kind, VeloErrorKind::SymlinkLoop);
+                assert_eq!(e.kind.errno(), libc::ELOOP);
+            }
+            other => panic!("Expected SymlinkLoop, got {:?}", other),
+        }
+    }
+
+    // ==================== ManifestListDir Tests ====================
+
+    #[tokio::test]
+    async fn test_manifest_list_dir_empty() {
+        let (mut handler, _temp) = create_test_handler();
+
         let response = handler
             .handle_request(VeloRequest::ManifestListDir {
                 path: "nonexistent".to_string(),
@@ -1045,6 +2819,578 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_manifest_list_dir_is_byte_ordered() {
+        let (mut handler, _temp) = create_test_handler();
+
+        let vnode = VnodeEntry {
+            content_hash: [0; 32],
+            size: 1,
+            mtime: 0,
+            mode: 0o644,
+            flags: 0,
+            _pad: 0,
+        };
+        for path in ["/d/b.rs", "/d/a.txt", "/d/Z.rs", "/d/a/x.rs", "/d/_c.rs"] {
+            handler.manifest.insert(
+                path,
+                vnode.clone(),
+                vrift_manifest::lmdb::AssetTier::Tier2Mutable,
+            );
+        }
+
+        match handler
+            .handle_request(VeloRequest::ManifestListDir {
+                path: "/d".to_string(),
+            })
+            .await
+        {
+            VeloResponse::ManifestListAck { entries } => {
+                let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+                assert_eq!(names, ["Z.rs", "_c.rs", "a", "a.txt", "b.rs"]);
+                assert!(entries[2].is_dir);
+            }
+            _ => panic!("Expected ManifestListAck"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manifest_list_dir_hides_tombstones() {
+        let (mut handler, _temp) = create_test_handler();
+
+        let vnode = VnodeEntry::new_file([0; 32], 1, 0, 0o644);
+        for path in ["/d/a.rs", "/d/b.rs", "/d/sub/c.rs"] {
+            handler.manifest.insert(
+                path,
+                vnode.clone(),
+                vrift_manifest::lmdb::AssetTier::Tier2Mutable,
+            );
+        }
+        // A removed subtree (rm -r) takes its directory out of the listing
+        for path in ["/d/a.rs", "/d/sub/c.rs"] {
+            handler
+                .handle_request(VeloRequest::ManifestRemove {
+                    path: path.to_string(),
+                })
+                .await;
+        }
+
+        match handler
+            .handle_request(VeloRequest::ManifestListDir {
+                path: "/d".to_string(),
+            })
+            .await
+        {
+            VeloResponse::ManifestListAck { entries } => {
+                let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+                assert_eq!(names, ["b.rs"]);
+            }
+            _ => panic!("Expected ManifestListAck"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_cache_key_ignores_mtime_and_outputs() {
+        let (mut handler, _temp) = create_test_handler();
+        let tier = vrift_manifest::lmdb::AssetTier::Tier2Mutable;
+        handler.manifest.insert(
+            "/src/lib.rs",
+            VnodeEntry::new_file([1; 32], 10, 100, 0o644),
+            tier,
+        );
+        handler.manifest.insert(
+            "/target/debug/app",
+            VnodeEntry::new_file([2; 32], 20, 100, 0o755),
+            tier,
+        );
+        let command = vec!["cargo".to_string(), "build".to_string()];
+        let first = match handler
+            .handle_request(VeloRequest::BuildCacheKey {
+                inputs: vec![],
+                exclude: vec!["/target".to_string()],
+                command: command.clone(),
+            })
+            .await
+        {
+            VeloResponse::BuildCacheKeyAck { key, entries } => {
+                assert_eq!(entries, 1);
+                key
+            }
+            other => panic!("Expected BuildCacheKeyAck, got {:?}", other),
+        };
+
+        // A new checkout (other mtimes) or a rebuilt output: same key
+        let entries = [
+            ("/src/lib.rs", VnodeEntry::new_file([1; 32], 10, 999, 0o644)),
+            (
+                "/target/debug/app",
+                VnodeEntry::new_file([3; 32], 9, 999, 0o755),
+            ),
+        ];
+        let exclude = ["/target/".to_string()];
+        let refs = entries.iter().map(|(p, e)| (*p, e));
+        assert_eq!(build_cache_key(refs, &[], &exclude, &command).0, first);
+
+        // Changed source or command line: different key
+        let changed = [("/src/lib.rs", VnodeEntry::new_file([4; 32], 10, 100, 0o644))];
+        let refs = changed.iter().map(|(p, e)| (*p, e));
+        assert_ne!(build_cache_key(refs, &[], &exclude, &command).0, first);
+        let refs = entries.iter().map(|(p, e)| (*p, e));
+        let release = [command.clone(), vec!["--release".to_string()]].concat();
+        assert_ne!(build_cache_key(refs, &[], &exclude, &release).0, first);
+
+        // Inputs narrow the subset; "/srcx" is not under "/src"
+        let refs = entries.iter().map(|(p, e)| (*p, e));
+        let (_, n) = build_cache_key(refs, &["/src".to_string()], &[], &command);
+        assert_eq!(n, 1);
+        assert!(!under_prefix("/srcx/a", "/src"));
+        assert!(under_prefix("/anything", "/"));
+    }
+
+    #[tokio::test]
+    async fn test_build_cache_key_sees_live_changes() {
+        let (mut handler, _temp) = create_test_handler();
+        let tier = vrift_manifest::lmdb::AssetTier::Tier2Mutable;
+        for path in ["/src/lib.rs", "/src/main.rs"] {
+            handler
+                .manifest
+                .insert(path, VnodeEntry::new_file([1; 32], 10, 100, 0o644), tier);
+        }
+        handler.manifest.commit().unwrap();
+        async fn key(handler: &mut CommandHandler) -> ([u8; 32], u64) {
+            match handler
+                .handle_request(VeloRequest::BuildCacheKey {
+                    inputs: vec![],
+                    exclude: vec![],
+                    command: vec!["make".to_string()],
+                })
+                .await
+            {
+                VeloResponse::BuildCacheKeyAck { key, entries } => (key, entries),
+                other => panic!("Expected BuildCacheKeyAck, got {:?}", other),
+            }
+        }
+        let (before, entries) = key(&mut handler).await;
+        assert_eq!(entries, 2);
+
+        // A write through the inception layer
+        handler
+            .handle_request(VeloRequest::ManifestUpsert {
+                path: "/src/lib.rs".to_string(),
+                entry: VnodeEntry::new_file([2; 32], 12, 200, 0o644),
+            })
+            .await;
+        let (edited, _) = key(&mut handler).await;
+        assert_ne!(edited, before);
+
+        // A tombstone only the VDir has yet
+        let gone = VnodeEntry::new_whiteout(300);
+        handler
+            .vdir
+            .upsert(VDirEntry {
+                path_hash: fnv1a_hash("/src/main.rs"),
+                flags: flags_from_vnode(gone.flags),
+                ..Default::default()
+            })
+            .unwrap();
+        let (removed, entries) = key(&mut handler).await;
+        assert_eq!(entries, 1);
+        assert_ne!(removed, edited);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_list_dir_pages_resume_after_name() {
+        let (mut handler, _temp) = create_test_handler();
+
+        let vnode = VnodeEntry {
+            content_hash: [0; 32],
+            size: 1,
+            mtime: 0,
+            mode: 0o644,
+            flags: 0,
+            _pad: 0,
+        };
+        for path in ["/d/b.rs", "/d/a.txt", "/d/Z.rs", "/d/a/x.rs", "/d/_c.rs"] {
+            handler.manifest.insert(
+                path,
+                vnode.clone(),
+                vrift_manifest::lmdb::AssetTier::Tier2Mutable,
+            );
+        }
+
+        let mut after = String::new();
+        let mut pages = Vec::new();
+        loop {
+            match handler
+                .handle_request(VeloRequest::ManifestListDirPage {
+                    path: "/d".to_string(),
+                    after: after.clone(),
+                    limit: 2,
+                })
+                .await
+            {
+                VeloResponse::ManifestListPageAck { names, dirs, more } => {
+                    let page: Vec<String> = names
+                        .split(|&b| b == 0)
+                        .filter(|n| !n.is_empty())
+                        .map(|n| String::from_utf8(n.to_vec()).unwrap())
+                        .collect();
+                    assert_eq!(page.len(), dirs.len());
+                    after = page.last().cloned().unwrap_or_default();
+                    pages.push((page, dirs));
+                    if !more {
+                        break;
+                    }
+                }
+                _ => panic!("Expected ManifestListPageAck"),
+            }
+        }
+        assert_eq!(
+            pages,
+            [
+                (
+                    vec!["Z.rs".to_string(), "_c.rs".to_string()],
+                    vec![false, false]
+                ),
+                (
+                    vec!["a".to_string(), "a.txt".to_string()],
+                    vec![true, false]
+                ),
+                (vec!["b.rs".to_string()], vec![false]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manifest_list_dir_pages_keep_listings_per_directory() {
+        let (mut handler, _temp) = create_test_handler();
+        let vnode = VnodeEntry {
+            content_hash: [0; 32],
+            size: 1,
+            mtime: 0,
+            mode: 0o644,
+            flags: 0,
+            _pad: 0,
+        };
+        let dirs: Vec<String> = (0..=LISTING_CACHE).map(|i| format!("/d{}", i)).collect();
+        for dir in &dirs {
+            for name in ["a.rs", "b.rs"] {
+                handler.manifest.insert(
+                    &format!("{}/{}", dir, name),
+                    vnode.clone(),
+                    vrift_manifest::lmdb::AssetTier::Tier2Mutable,
+                );
+            }
+        }
+        let page = |dir: &str, after: &str| VeloRequest::ManifestListDirPage {
+            path: dir.to_string(),
+            after: after.to_string(),
+            limit: 1,
+        };
+        let cached = |handler: &CommandHandler| -> Vec<String> {
+            let listings = handler.listings.lock().unwrap();
+            listings.iter().map(|l| l.path.clone()).collect()
+        };
+
+        // Two readdir()s in turn: each resumes from its own listing
+        for dir in &dirs[..2] {
+            handler.handle_request(page(dir, "")).await;
+        }
+        let response = handler.handle_request(page(&dirs[0], "a.rs")).await;
+        assert!(matches!(
+            response,
+            VeloResponse::ManifestListPageAck { names, more: false, .. } if names == b"b.rs\0"
+        ));
+        assert_eq!(cached(&handler), [dirs[0].clone(), dirs[1].clone()]);
+
+        // The least recently paged directory goes first
+        for dir in &dirs[2..] {
+            handler.handle_request(page(dir, "")).await;
+        }
+        let kept = cached(&handler);
+        assert_eq!(kept.len(), LISTING_CACHE);
+        assert!(kept.contains(&dirs[0]));
+        assert!(!kept.contains(&dirs[1]));
+
+        // Any manifest change drops them all
+        handler.manifest.remove("/d0/a.rs");
+        handler.handle_request(page(&dirs[0], "")).await;
+        assert_eq!(cached(&handler), [dirs[0].clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_hot_cache_admits_reported_misses() {
+        let (handler, _temp) = create_test_handler();
+        let mut handler = handler.with_hot_cache(HotCache::new(1));
+
+        for path in ["/a.rs", "/b.rs"] {
+            let vnode = VnodeEntry {
+                content_hash: [1; 32],
+                size: 1,
+                mtime: 0,
+                mode: 0o644,
+                flags: 0,
+                _pad: 0,
+            };
+            handler
+                .manifest
+                .insert(path, vnode, vrift_manifest::lmdb::AssetTier::Tier2Mutable);
+        }
+        let get = |path: &str| VeloRequest::ManifestGet {
+            path: path.to_string(),
+        };
+
+        // Room left: admitted on first sight
+        handler.handle_request(get("/a.rs")).await;
+        assert!(handler.vdir.lookup(fnv1a_hash("/a.rs")).is_some());
+
+        // Full: lookups alone don't count, reported misses do
+        handler.handle_request(get("/b.rs")).await;
+        handler.handle_request(get("/b.rs")).await;
+        assert!(handler.vdir.lookup(fnv1a_hash("/b.rs")).is_none());
+
+        let report = VeloRequest::MissReport {
+            path_hashes: vec![fnv1a_hash("/b.rs"); 2],
+        };
+        assert!(matches!(
+            handler.handle_request(report).await,
+            VeloResponse::MissReportAck
+        ));
+        handler.handle_request(get("/b.rs")).await;
+        assert!(handler.vdir.lookup(fnv1a_hash("/b.rs")).is_some());
+        assert!(handler.vdir.lookup(fnv1a_hash("/a.rs")).is_none());
+
+        match handler.handle_request(VeloRequest::Metrics).await {
+            VeloResponse::MetricsAck { metrics } => {
+                assert_eq!(metrics.vdir_admissions, 2);
+                assert_eq!(metrics.vdir_evictions, 1);
+                assert_eq!(metrics.vdir_misses_reported, 2);
+            }
+            _ => panic!("Expected MetricsAck"),
+        }
+    }
+
+    #[test]
+    fn test_lookups_run_without_exclusive_access() {
+        let (handler, _temp) = create_test_handler();
+        let vnode = VnodeEntry {
+            content_hash: [2; 32],
+            size: 4,
+            mtime: 0,
+            mode: 0o644,
+            flags: 0,
+            _pad: 0,
+        };
+        handler.manifest.insert(
+            "/src/main.rs",
+            vnode,
+            vrift_manifest::lmdb::AssetTier::Tier2Mutable,
+        );
+        let get = VeloRequest::ManifestGet {
+            path: "/src/main.rs".to_string(),
+        };
+
+        match handler.handle_lookup(&get) {
+            Some(VeloResponse::ManifestAck {
+                entry: Some(entry), ..
+            }) => assert_eq!(entry.size, 4),
+            other => panic!("Expected ManifestAck, got {:?}", other),
+        }
+        assert!(handler
+            .handle_lookup(&VeloRequest::ManifestListDir {
+                path: "/src".to_string()
+            })
+            .is_some());
+        assert!(handler
+            .handle_lookup(&VeloRequest::ManifestRemove {
+                path: "/src/main.rs".to_string()
+            })
+            .is_none());
+
+        // A capped VDir still answers under the shared lock; admission
+        // waits until the handler is held exclusively
+        let mut handler = handler.with_hot_cache(HotCache::new(1));
+        assert!(handler.handle_lookup(&get).is_some());
+        assert!(handler.has_touches());
+        assert!(handler.vdir.lookup(fnv1a_hash("/src/main.rs")).is_none());
+        handler.apply_touches();
+        assert!(!handler.has_touches());
+        assert!(handler.vdir.lookup(fnv1a_hash("/src/main.rs")).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_restore_rolls_back_session() {
+        let (mut handler, _temp) = create_test_handler();
+        let vnode = |size| VnodeEntry {
+            content_hash: [size as u8; 32],
+            size,
+            mtime: 0,
+            mode: 0o644,
+            flags: 0,
+            _pad: 0,
+        };
+        let tier = vrift_manifest::lmdb::AssetTier::Tier2Mutable;
+        handler.manifest.insert("/lib.rs", vnode(10), tier);
+        handler.manifest.commit().unwrap();
+
+        let good = match handler
+            .handle_request(VeloRequest::SnapshotCreate {
+                label: "clean".to_string(),
+                protect: false,
+            })
+            .await
+        {
+            VeloResponse::SnapshotAck { snapshot } => snapshot,
+            other => panic!("Expected SnapshotAck, got {:?}", other),
+        };
+        assert_eq!(good.entries, 1);
+
+        // Bad session: manifest rewritten, VDir overlay clobbered
+        handler.manifest.insert("/lib.rs", vnode(99), tier);
+        handler.manifest.insert("/junk.o", vnode(5), tier);
+        handler.manifest.commit().unwrap();
+        handler
+            .handle_request(VeloRequest::ManifestUpsert {
+                path: "/lib.rs".to_string(),
+                entry: vnode(77),
+            })
+            .await;
+
+        match handler
+            .handle_request(VeloRequest::SnapshotRestore {
+                id: good.id[..10].to_string(),
+            })
+            .await
+        {
+            VeloResponse::SnapshotRestoreAck { restored, previous } => {
+                assert_eq!(restored.id, good.id);
+                assert_eq!(previous.entries, 2);
+            }
+            other => panic!("Expected SnapshotRestoreAck, got {:?}", other),
+        }
+        match handler
+            .handle_request(VeloRequest::ManifestGet {
+                path: "/lib.rs".to_string(),
+            })
+            .await
+        {
+            VeloResponse::ManifestAck {
+                entry: Some(entry), ..
+            } => assert_eq!(entry.size, 10),
+            other => panic!("Expected ManifestAck, got {:?}", other),
+        }
+        assert!(handler.manifest.get("/junk.o").unwrap().is_none());
+
+        match handler.handle_request(VeloRequest::SnapshotList).await {
+            VeloResponse::SnapshotListAck { snapshots } => assert_eq!(snapshots.len(), 2),
+            other => panic!("Expected SnapshotListAck, got {:?}", other),
+        }
+        assert!(matches!(
+            handler
+                .handle_request(VeloRequest::SnapshotRestore {
+                    id: "ffffffff".to_string(),
+                })
+                .await,
+            VeloResponse::Error(VeloError {
+                kind: VeloErrorKind::NotFound,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_protected_snapshot_refuses_changes() {
+        let (mut handler, temp) = create_test_handler();
+        handler.config.cas_path = temp.path().join("cas");
+        let vnode = |size| VnodeEntry {
+            content_hash: [size as u8; 32],
+            size,
+            mtime: 0,
+            mode: 0o644,
+            flags: 0,
+            _pad: 0,
+        };
+        let tier = vrift_manifest::lmdb::AssetTier::Tier2Mutable;
+        handler.manifest.insert("/release.bin", vnode(10), tier);
+        handler.manifest.commit().unwrap();
+        let old = handler.snapshots.create(&handler.manifest, "old").unwrap();
+        handler.manifest.insert("/release.bin", vnode(20), tier);
+        handler.manifest.commit().unwrap();
+
+        let released = match handler
+            .handle_request(VeloRequest::SnapshotCreate {
+                label: "v1".to_string(),
+                protect: true,
+            })
+            .await
+        {
+            VeloResponse::SnapshotAck { snapshot } => snapshot,
+            other => panic!("Expected SnapshotAck, got {:?}", other),
+        };
+        assert!(released.protected);
+
+        let denied = |response: VeloResponse| {
+            matches!(
+                response,
+                VeloResponse::Error(VeloError {
+                    kind: VeloErrorKind::PermissionDenied,
+                    ..
+                })
+            )
+        };
+        let upsert = || VeloRequest::ManifestUpsert {
+            path: "/release.bin".to_string(),
+            entry: vnode(30),
+        };
+        assert!(denied(handler.handle_request(upsert()).await));
+        assert!(denied(
+            handler
+                .handle_request(VeloRequest::ManifestRename {
+                    old_path: "/scratch".to_string(),
+                    new_path: "/release.bin".to_string(),
+                })
+                .await
+        ));
+        assert!(denied(
+            handler
+                .handle_request(VeloRequest::ManifestReingest {
+                    vpath: "/release.bin".to_string(),
+                    temp_path: "/nonexistent".to_string(),
+                })
+                .await
+        ));
+        // Rolling back past the release would rewrite it too
+        assert!(denied(
+            handler
+                .handle_request(VeloRequest::SnapshotRestore { id: old.id_hex() })
+                .await
+        ));
+        // Other paths are unaffected
+        assert!(matches!(
+            handler
+                .handle_request(VeloRequest::ManifestUpsert {
+                    path: "/scratch".to_string(),
+                    entry: vnode(1),
+                })
+                .await,
+            VeloResponse::ManifestAck { .. }
+        ));
+
+        let unprotect = |force| VeloRequest::SnapshotUnprotect {
+            id: released.id.clone(),
+            force,
+        };
+        assert!(denied(handler.handle_request(unprotect(false)).await));
+        match handler.handle_request(unprotect(true)).await {
+            VeloResponse::SnapshotAck { snapshot } => assert!(!snapshot.protected),
+            other => panic!("Expected SnapshotAck, got {:?}", other),
+        }
+        assert!(matches!(
+            handler.handle_request(upsert()).await,
+            VeloResponse::ManifestAck { .. }
+        ));
+    }
+
     // ==================== Unhandled Request Tests ====================
 
     #[tokio::test]
@@ -1096,7 +3442,7 @@ mod tests {
                 .await;
 
             match response {
-                VeloResponse::ManifestAck { entry: Some(e) } => {
+                VeloResponse::ManifestAck { entry: Some(e), .. } => {
                     assert_eq!(e.size, i as u64 * 100);
                 }
                 _ => panic!("File {} not found", i),
@@ -0,0 +1,328 @@
+//! fanotify backend for FS Watch (Linux)
+//!
+//! inotify needs one watch per directory, and `max_user_watches` runs out
+//! on monorepos with millions of them. fanotify with `FAN_REPORT_DFID_NAME`
+//! covers the project's whole filesystem with a single mark. Each event
+//! names a directory by file handle plus an entry name, so resolving it to
+//! a path costs an `open_by_handle_at` (cached per directory). Events from
+//! outside the project root are dropped.
+//!
+//! Filesystem marks need `CAP_SYS_ADMIN` and Linux 5.9+. Without them
+//! [`Fanotify::open`] fails and the watcher stays on inotify.
+
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+use crate::watch::Change;
+
+const EVENT_MASK: u64 = libc::FAN_CREATE
+    | libc::FAN_DELETE
+    | libc::FAN_MOVED_FROM
+    | libc::FAN_MOVED_TO
+    | libc::FAN_CLOSE_WRITE
+    | libc::FAN_ONDIR;
+
+/// `fanotify_event_metadata` is 24 bytes; its version field is 3
+const METADATA_LEN: usize = 24;
+const METADATA_VERSION: u8 = 3;
+/// Info record header (4) + `__kernel_fsid_t` (8)
+const FID_HEADER_LEN: usize = 12;
+/// Resolved directories kept before the cache is reset
+const DIR_CACHE_CAP: usize = 16 * 1024;
+
+pub(crate) struct Fanotify {
+    fd: OwnedFd,
+    /// Any fd on the marked filesystem, for `open_by_handle_at`
+    mount_fd: OwnedFd,
+    root: PathBuf,
+    /// Directory file handle → path
+    dirs: HashMap<Vec<u8>, PathBuf>,
+}
+
+impl Fanotify {
+    /// Mark the filesystem holding `root`
+    pub(crate) fn open(root: &Path) -> io::Result<Self> {
+        let root = root.canonicalize()?;
+        let root_c = CString::new(root.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let fd = unsafe {
+            libc::fanotify_init(
+                libc::FAN_CLASS_NOTIF
+                    | libc::FAN_CLOEXEC
+                    | libc::FAN_NONBLOCK
+                    | libc::FAN_REPORT_DFID_NAME,
+                (libc::O_RDONLY | libc::O_LARGEFILE | libc::O_CLOEXEC) as libc::c_uint,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let ret = unsafe {
+            libc::fanotify_mark(
+                fd.as_raw_fd(),
+                libc::FAN_MARK_ADD | libc::FAN_MARK_FILESYSTEM,
+                EVENT_MASK,
+                libc::AT_FDCWD,
+                root_c.as_ptr(),
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mount_fd = unsafe {
+            libc::open(
+                root_c.as_ptr(),
+                libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+            )
+        };
+        if mount_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mount_fd = unsafe { OwnedFd::from_raw_fd(mount_fd) };
+
+        Ok(Self {
+            fd,
+            mount_fd,
+            root,
+            dirs: HashMap::new(),
+        })
+    }
+
+    /// Drain queued events (non-blocking)
+    pub(crate) fn read_events(&mut self) -> Vec<(PathBuf, Change)> {
+        let mut out = Vec::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if n <= 0 {
+                if n < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::WouldBlock {
+                        warn!(error = %err, "fanotify read failed");
+                    }
+                }
+                break;
+            }
+            let (mount_fd, dirs) = (self.mount_fd.as_raw_fd(), &mut self.dirs);
+            let events = parse_events(&buf[..n as usize], |handle| {
+                if let Some(path) = dirs.get(handle) {
+                    return Some(path.clone());
+                }
+                let path = resolve_handle(mount_fd, handle)?;
+                if dirs.len() >= DIR_CACHE_CAP {
+                    dirs.clear();
+                }
+                dirs.insert(handle.to_vec(), path.clone());
+                Some(path)
+            });
+            for (path, change, renamed_dir) in events {
+                if renamed_dir {
+                    // Cached paths under the old name are now wrong
+                    self.dirs.clear();
+                }
+                if path.starts_with(&self.root) {
+                    out.push((path, change));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Decode a buffer of FID-mode events. `resolve` maps a directory file
+/// handle (`struct file_handle` bytes) to its path. Returns each event's
+/// path, its change, and whether it renamed or deleted a directory.
+fn parse_events(
+    buf: &[u8],
+    mut resolve: impl FnMut(&[u8]) -> Option<PathBuf>,
+) -> Vec<(PathBuf, Change, bool)> {
+    let mut out = Vec::new();
+    let mut rest = buf;
+    while rest.len() >= METADATA_LEN {
+        let event_len = u32_at(rest, 0) as usize;
+        let metadata_len = u16::from_ne_bytes([rest[6], rest[7]]) as usize;
+        if event_len < METADATA_LEN || event_len > rest.len() || metadata_len > event_len {
+            break;
+        }
+        let (event, tail) = rest.split_at(event_len);
+        rest = tail;
+        if event[4] != METADATA_VERSION {
+            continue;
+        }
+        let mask = u64::from_ne_bytes(event[8..16].try_into().unwrap());
+        if mask & libc::FAN_Q_OVERFLOW != 0 {
+            warn!("fanotify queue overflowed; some changes were not seen");
+            continue;
+        }
+
+        let Some((handle, name)) = dfid_name(&event[metadata_len..]) else {
+            continue;
+        };
+        let Some(dir) = resolve(handle) else {
+            debug!("fanotify: directory no longer resolvable");
+            continue;
+        };
+        let path = if name.is_empty() || name == b"." {
+            dir
+        } else {
+            dir.join(OsStr::from_bytes(name))
+        };
+
+        let mut gone = mask & (libc::FAN_DELETE | libc::FAN_MOVED_FROM) != 0;
+        let renamed_dir = gone && mask & libc::FAN_ONDIR != 0;
+        let came = mask & (libc::FAN_CREATE | libc::FAN_MOVED_TO) != 0;
+        if gone && mask & (libc::FAN_CREATE | libc::FAN_MOVED_TO | libc::FAN_CLOSE_WRITE) != 0 {
+            // The kernel merges queued events on the same name and their
+            // order is lost (`rm a; echo > a` is one DELETE|CREATE), so the
+            // path's current state decides
+            gone = path.symlink_metadata().is_err();
+        }
+        let change = if gone {
+            Change::Removed
+        } else if came {
+            Change::Created
+        } else {
+            Change::Modified
+        };
+        out.push((path, change, renamed_dir));
+    }
+    out
+}
+
+/// Find the DFID_NAME record among an event's info records
+fn dfid_name(mut info: &[u8]) -> Option<(&[u8], &[u8])> {
+    while info.len() >= 4 {
+        let info_type = info[0];
+        let len = u16::from_ne_bytes([info[2], info[3]]) as usize;
+        if len < 4 || len > info.len() {
+            return None;
+        }
+        let (record, tail) = info.split_at(len);
+        info = tail;
+        if info_type != libc::FAN_EVENT_INFO_TYPE_DFID_NAME || record.len() < FID_HEADER_LEN + 8 {
+            continue;
+        }
+        let fh = &record[FID_HEADER_LEN..];
+        let handle_len = 8 + u32_at(fh, 0) as usize;
+        if handle_len > fh.len() {
+            return None;
+        }
+        let (handle, name) = fh.split_at(handle_len);
+        let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        return Some((handle, &name[..end]));
+    }
+    None
+}
+
+/// Path of the directory a file handle names
+fn resolve_handle(mount_fd: libc::c_int, handle: &[u8]) -> Option<PathBuf> {
+    // struct file_handle is 4-byte aligned
+    let mut aligned = vec![0u32; handle.len().div_ceil(4)];
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            handle.as_ptr(),
+            aligned.as_mut_ptr() as *mut u8,
+            handle.len(),
+        );
+    }
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_open_by_handle_at,
+            mount_fd,
+            aligned.as_mut_ptr(),
+            libc::O_PATH | libc::O_CLOEXEC,
+        )
+    } as libc::c_int;
+    if fd < 0 {
+        return None;
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    std::fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd())).ok()
+}
+
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_ne_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One FID-mode event with a DFID_NAME record
+    fn event(mask: u64, handle: &[u8], name: &str) -> Vec<u8> {
+        let mut record = vec![libc::FAN_EVENT_INFO_TYPE_DFID_NAME, 0, 0, 0];
+        record.extend_from_slice(&[0; 8]); // fsid
+        record.extend_from_slice(&(handle.len() as u32).to_ne_bytes());
+        record.extend_from_slice(&1i32.to_ne_bytes()); // handle_type
+        record.extend_from_slice(handle);
+        record.extend_from_slice(name.as_bytes());
+        record.push(0);
+        while !record.len().is_multiple_of(4) {
+            record.push(0);
+        }
+        let len = record.len() as u16;
+        record[2..4].copy_from_slice(&len.to_ne_bytes());
+
+        let mut ev = Vec::new();
+        ev.extend_from_slice(&((METADATA_LEN + record.len()) as u32).to_ne_bytes());
+        ev.extend_from_slice(&[METADATA_VERSION, 0]);
+        ev.extend_from_slice(&(METADATA_LEN as u16).to_ne_bytes());
+        ev.extend_from_slice(&mask.to_ne_bytes());
+        ev.extend_from_slice(&(-1i32).to_ne_bytes()); // FAN_NOFD
+        ev.extend_from_slice(&0i32.to_ne_bytes());
+        ev.extend(record);
+        ev
+    }
+
+    #[test]
+    fn test_parse_fid_events() {
+        let mut buf = event(libc::FAN_CREATE, b"srcdir!!", "main.rs");
+        buf.extend(event(libc::FAN_CLOSE_WRITE, b"srcdir!!", "main.rs"));
+        buf.extend(event(
+            libc::FAN_MOVED_FROM | libc::FAN_ONDIR,
+            b"root",
+            "src",
+        ));
+        buf.extend(event(libc::FAN_CREATE, b"gone", "x"));
+        // Merged DELETE|CREATE of a path that no longer exists
+        buf.extend(event(
+            libc::FAN_DELETE | libc::FAN_CREATE,
+            b"srcdir!!",
+            "tmp.rs",
+        ));
+
+        let events = parse_events(&buf, |handle| match handle.get(8..) {
+            Some(b"srcdir!!") => Some(PathBuf::from("/proj/src")),
+            Some(b"root") => Some(PathBuf::from("/proj")),
+            _ => None,
+        });
+        assert_eq!(
+            events,
+            [
+                (PathBuf::from("/proj/src/main.rs"), Change::Created, false),
+                (PathBuf::from("/proj/src/main.rs"), Change::Modified, false),
+                (PathBuf::from("/proj/src"), Change::Removed, true),
+                (PathBuf::from("/proj/src/tmp.rs"), Change::Removed, false),
+            ]
+        );
+
+        // A truncated buffer stops cleanly
+        assert!(parse_events(&buf[..10], |_| None).is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+//! IPC lookup heat, sharded by path prefix
+//!
+//! Every ManifestGet and ManifestListDir bumps a (prefix, op) counter. Those
+//! requests are answered under a shared handler lock, so the counters take
+//! their own locks: one per shard, picked by the prefix's path hash, so
+//! concurrent lookups in different parts of the tree don't serialize here.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use vrift_ipc::heatmap::{heat_prefix, HeatCell, HEATMAP_DEFAULT_DEPTH};
+
+use crate::vdir::fnv1a_hash;
+
+/// Counter shards (power of two)
+const SHARDS: usize = 64;
+
+/// Distinct (prefix, op) cells kept; new prefixes past this are dropped
+const MAX_HEAT_CELLS: usize = 4096;
+
+type Shard = Mutex<HashMap<(String, &'static str), u64>>;
+
+/// Lookup counts per (path prefix, op)
+pub struct Heat {
+    shards: Box<[Shard]>,
+}
+
+impl Default for Heat {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+}
+
+impl Heat {
+    /// Count one `op` lookup of `path`
+    pub fn record(&self, path: &str, op: &'static str) {
+        let prefix = heat_prefix(path, HEATMAP_DEFAULT_DEPTH);
+        let shard = fnv1a_hash(prefix) as usize & (SHARDS - 1);
+        let mut cells = self.shards[shard].lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = cells.get_mut(&(prefix.to_string(), op)) {
+            *count += 1;
+        } else if cells.len() < MAX_HEAT_CELLS / SHARDS {
+            cells.insert((prefix.to_string(), op), 1);
+        }
+    }
+
+    /// Every cell, for Metrics
+    pub fn cells(&self) -> Vec<HeatCell> {
+        let mut out = Vec::new();
+        for shard in self.shards.iter() {
+            let cells = shard.lock().unwrap_or_else(|e| e.into_inner());
+            out.extend(cells.iter().map(|((prefix, op), &count)| HeatCell {
+                prefix: prefix.clone(),
+                op: op.to_string(),
+                count,
+            }));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heat_counts_across_shards() {
+        let heat = Heat::default();
+        for i in 0..200 {
+            heat.record(&format!("/src/dir{}/file.rs", i % 100), "ipc_get");
+        }
+        heat.record("/src/dir0/file.rs", "ipc_list");
+
+        let cells = heat.cells();
+        assert_eq!(cells.iter().map(|c| c.count).sum::<u64>(), 201);
+        let get = |prefix: &str, op: &str| {
+            cells
+                .iter()
+                .find(|c| c.prefix == prefix && c.op == op)
+                .map(|c| c.count)
+        };
+        assert_eq!(get("/src/dir7", "ipc_get"), Some(2));
+        assert_eq!(get("/src/dir0", "ipc_list"), Some(1));
+    }
+}
@@ -3,12 +3,10 @@
 //! All ingest events from L1 (Shim IPC), L2 (FS Watch), and L3 (Compensation Scan)
 //! flow through this single queue for serialized, conflict-free processing.
 
-use std::collections::HashSet;
-use std::path::PathBuf;
 use std::sync::atomic::{AtomicU8, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::watch::IngestEvent;
 
@@ -47,32 +45,20 @@ impl From<u8> for IngestState {
     }
 }
 
-/// Ingest Queue with deduplication
+/// Ingest Queue feeding the single consumer
 pub struct IngestQueue {
     /// Event receiver
     rx: mpsc::Receiver<IngestEvent>,
     /// State
     state: AtomicU8,
-    /// Recent paths for deduplication (path -> last_seen)
-    recent: HashSet<PathBuf>,
-    /// Dedup window
-    dedup_window: Duration,
-    /// Last cleanup time
-    last_cleanup: Instant,
 }
 
 impl IngestQueue {
     /// Create a new ingest queue
     pub fn new(rx: mpsc::Receiver<IngestEvent>) -> Self {
-        let config = vrift_config::config();
-        let dedup_window = Duration::from_millis(config.ingest.dedup_window_ms);
-
         Self {
             rx,
             state: AtomicU8::new(IngestState::Init as u8),
-            recent: HashSet::new(),
-            dedup_window,
-            last_cleanup: Instant::now(),
         }
     }
 
@@ -105,50 +91,11 @@ impl IngestQueue {
         }
     }
 
-    /// Check if path is in recent set (for deduplication)
-    fn is_recent(&self, path: &PathBuf) -> bool {
-        self.recent.contains(path)
-    }
-
-    /// Mark path as recently processed
-    fn mark_recent(&mut self, path: PathBuf) {
-        self.recent.insert(path);
-    }
-
-    /// Cleanup old entries from recent set
-    fn cleanup_recent(&mut self) {
-        if self.last_cleanup.elapsed() > self.dedup_window {
-            self.recent.clear();
-            self.last_cleanup = Instant::now();
-        }
-    }
-
-    /// Process next event (with deduplication)
+    /// Next event. Repeats for the same path are merged per batch by the
+    /// consumer (see [`coalesce`]); dropping them here could lose a change
+    /// made after the earlier event was handled.
     pub async fn next(&mut self) -> Option<IngestEvent> {
-        self.cleanup_recent();
-
-        loop {
-            match self.rx.recv().await {
-                Some(event) => {
-                    let path = match &event {
-                        IngestEvent::FileChanged { path } => path,
-                        IngestEvent::DirCreated { path } => path,
-                        IngestEvent::Removed { path } => path,
-                        IngestEvent::SymlinkCreated { path, .. } => path,
-                    };
-
-                    // Dedup check
-                    if self.is_recent(path) {
-                        debug!(?path, "Dedup: skipping recent path");
-                        continue;
-                    }
-
-                    self.mark_recent(path.clone());
-                    return Some(event);
-                }
-                None => return None,
-            }
-        }
+        self.rx.recv().await
     }
 }
 
@@ -157,6 +104,10 @@ pub struct IngestHandler {
     project_root: std::path::PathBuf,
     manifest: std::sync::Arc<vrift_manifest::lmdb::LmdbManifest>,
     cas: vrift_cas::CasStore,
+    protection: std::sync::Arc<crate::protect::Protection>,
+    /// Manifest keys changed here, for the command handler to refresh
+    /// in the VDir
+    changed: Option<mpsc::UnboundedSender<String>>,
 }
 
 impl IngestHandler {
@@ -169,11 +120,38 @@ impl IngestHandler {
             project_root,
             manifest,
             cas,
+            protection: Default::default(),
+            changed: None,
         }
     }
 
+    /// Report every manifest key this handler changes on `changed`
+    pub fn with_change_feed(mut self, changed: mpsc::UnboundedSender<String>) -> Self {
+        self.changed = Some(changed);
+        self
+    }
+
+    /// Leave entries of protected snapshots alone
+    pub fn with_protection(
+        mut self,
+        protection: std::sync::Arc<crate::protect::Protection>,
+    ) -> Self {
+        self.protection = protection;
+        self
+    }
+
     /// Process a single ingest event
     pub fn handle(&self, event: IngestEvent) {
+        let (IngestEvent::FileChanged { path }
+        | IngestEvent::DirCreated { path }
+        | IngestEvent::Removed { path }
+        | IngestEvent::SymlinkCreated { path, .. }) = &event;
+        let key = self.to_manifest_key(path);
+        if self.protection.protector(&key).is_some() {
+            warn!(path = %key, "Ingest: skipped change to a protected snapshot entry");
+            return;
+        }
+
         match event {
             IngestEvent::FileChanged { path } => {
                 self.handle_file_changed(&path);
@@ -188,6 +166,10 @@ impl IngestHandler {
                 self.handle_symlink_created(&path, &target);
             }
         }
+        if let Some(changed) = &self.changed {
+            // Closed only once the command handler is gone at shutdown
+            let _ = changed.send(key);
+        }
     }
 
     fn handle_file_changed(&self, path: &std::path::Path) {
@@ -213,8 +195,16 @@ impl IngestHandler {
                             _pad: 0,
                         };
 
-                        // Insert into manifest with classified tier
-                        self.manifest.insert(&rel_path, vnode, tier);
+                        // Insert into manifest with classified tier; the
+                        // file stays on disk, so a later scan may drop it
+                        let xattrs = read_xattrs(path);
+                        self.manifest.insert_from_disk(
+                            &rel_path,
+                            vnode,
+                            tier,
+                            &xattrs,
+                            read_btime(&meta),
+                        );
 
                         info!(
                             path = %rel_path,
@@ -274,10 +264,12 @@ impl IngestHandler {
                     _pad: 0,
                 };
 
-                self.manifest.insert(
+                self.manifest.insert_from_disk(
                     &rel_path,
                     vnode,
                     vrift_manifest::lmdb::AssetTier::Tier2Mutable,
+                    &read_xattrs(path),
+                    read_btime(&meta),
                 );
 
                 info!(path = %rel_path, "Ingest: directory registered in manifest");
@@ -321,10 +313,12 @@ impl IngestHandler {
                     _pad: 0,
                 };
 
-                self.manifest.insert(
+                self.manifest.insert_from_disk(
                     &rel_path,
                     vnode,
                     vrift_manifest::lmdb::AssetTier::Tier2Mutable,
+                    &read_xattrs(path),
+                    read_btime(&meta),
                 );
 
                 info!(
@@ -343,11 +337,31 @@ impl IngestHandler {
     /// Convert absolute path to manifest key (relative path)
     fn to_manifest_key(&self, path: &std::path::Path) -> String {
         path.strip_prefix(&self.project_root)
-            .map(|p| format!("/{}", p.display()))
+            .map(vrift_ipc::path_key_for)
             .unwrap_or_else(|_| path.display().to_string())
     }
 }
 
+/// Xattrs to record for `path`, less the configured `ingest.strip_xattrs`
+pub(crate) fn read_xattrs(path: &std::path::Path) -> vrift_manifest::Xattrs {
+    let strip = vrift_config::config().ingest.strip_xattrs.clone();
+    vrift_manifest::xattr::read_from_disk(path, &strip).unwrap_or_else(|e| {
+        debug!(path = %path.display(), error = %e, "Ingest: xattrs not readable");
+        Vec::new()
+    })
+}
+
+/// Creation time to record from `meta`, where the file system keeps one
+pub(crate) fn read_btime(meta: &std::fs::Metadata) -> Option<u64> {
+    let created = meta.created().ok()?;
+    Some(
+        created
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs(),
+    )
+}
+
 /// Consumer task that processes ingest events with batching and async CAS
 pub async fn run_consumer(mut queue: IngestQueue, handler: std::sync::Arc<IngestHandler>) {
     use tokio::time::timeout;
@@ -381,7 +395,7 @@ pub async fn run_consumer(mut queue: IngestQueue, handler: std::sync::Arc<Ingest
             match timeout(remaining, queue.next()).await {
                 Ok(Some(event)) => {
                     debug!(?event, "Queued ingest event for batch");
-                    batch.push(event);
+                    coalesce(&mut batch, event);
                 }
                 Ok(None) => {
                     // Channel closed, process remaining and exit
@@ -407,6 +421,21 @@ pub async fn run_consumer(mut queue: IngestQueue, handler: std::sync::Arc<Ingest
     }
 }
 
+/// Add `event` to `batch`, merged with an earlier event for the same path.
+/// A batch is handled in parallel, so each path may appear in it only once.
+fn coalesce(batch: &mut Vec<IngestEvent>, event: IngestEvent) {
+    match batch
+        .iter()
+        .position(|queued| queued.path() == event.path())
+    {
+        Some(i) => {
+            let older = batch.swap_remove(i);
+            batch.push(older.merge(event));
+        }
+        None => batch.push(event),
+    }
+}
+
 /// Process a batch of ingest events with async CAS storage
 async fn process_batch(handler: &std::sync::Arc<IngestHandler>, events: Vec<IngestEvent>) {
     use tokio::task::JoinSet;
@@ -36,6 +36,11 @@ pub struct ReingestJournal {
 }
 
 impl ReingestJournal {
+    /// Journal location for a project
+    pub fn path_for(project_root: &Path) -> PathBuf {
+        project_root.join(".vrift").join("reingest_journal.bin")
+    }
+
     /// Open or create journal at the given path
     pub fn open(path: &Path) -> io::Result<Self> {
         let entries = if path.exists() {
@@ -107,6 +112,15 @@ impl ReingestJournal {
         Ok(())
     }
 
+    /// Drop every entry, after recovery has dealt with them
+    pub fn clear(&mut self) -> io::Result<()> {
+        if !self.entries.is_empty() {
+            self.entries.clear();
+            self.flush()?;
+        }
+        Ok(())
+    }
+
     /// Get pending entries for recovery
     pub fn pending_entries(&self) -> Vec<&JournalEntry> {
         self.entries.values().collect()
@@ -1,13 +1,16 @@
 //! vdir_d - Per-project Virtual Directory Daemon
 //!
 //! Usage:
-//!   vdir_d /path/to/project
+//!   vdir_d /path/to/project [/path/to/other/project ...]
+//!
+//! Every project gets its own socket and state; env overrides
+//! (VRIFT_SOCKET_PATH, VRIFT_MANIFEST) apply to the first one only.
 
 use anyhow::{Context, Result};
 use std::path::PathBuf;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
-use vrift_vdird::{run_daemon, ProjectConfig};
+use vrift_vdird::{run_projects, ProjectConfig};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -19,32 +22,33 @@ async fn main() -> Result<()> {
         .init();
 
     // Parse args
-    let args: Vec<String> = std::env::args().collect();
-    let project_root = if args.len() > 1 {
-        let first_arg = &args[1];
-        if first_arg == "start" && args.len() > 2 {
-            PathBuf::from(&args[2])
-        } else if first_arg == "start" {
-            std::env::current_dir().context("Failed to get current directory")?
-        } else {
-            PathBuf::from(first_arg)
-        }
-    } else {
-        std::env::current_dir().context("Failed to get current directory")?
-    };
-
-    // Validate project root exists
-    if !project_root.exists() {
-        anyhow::bail!("Project root does not exist: {}", project_root.display());
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|first| first == "start") {
+        args.remove(0);
+    }
+    let mut project_roots: Vec<PathBuf> = args.into_iter().map(PathBuf::from).collect();
+    if project_roots.is_empty() {
+        project_roots.push(std::env::current_dir().context("Failed to get current directory")?);
     }
 
-    let project_root = project_root
-        .canonicalize()
-        .context("Failed to canonicalize project root")?;
+    let mut configs = Vec::with_capacity(project_roots.len());
+    for project_root in project_roots {
+        // Validate project root exists
+        if !project_root.exists() {
+            anyhow::bail!("Project root does not exist: {}", project_root.display());
+        }
+
+        let project_root = project_root
+            .canonicalize()
+            .context("Failed to canonicalize project root")?;
 
-    info!(path = %project_root.display(), "Starting vdir_d for project");
+        info!(path = %project_root.display(), "Starting vdir_d for project");
 
-    // Create config and run
-    let config = ProjectConfig::from_project_root(project_root);
-    run_daemon(config).await
+        configs.push(if configs.is_empty() {
+            ProjectConfig::from_project_root(project_root)
+        } else {
+            ProjectConfig::for_shared_daemon(project_root)
+        });
+    }
+    run_projects(configs).await
 }
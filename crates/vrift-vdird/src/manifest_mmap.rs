@@ -0,0 +1,347 @@
+//! Manifest mmap publishing: the writer side of `vrift_ipc::manifest_mmap`
+//!
+//! [`ManifestMmap::publish`] builds an image of every manifest key into the
+//! slot shims aren't reading and flips the generation. Between publishes,
+//! the manifest's insert hook ([`ManifestMmap::note_added`]) marks each key
+//! it is about to add in the active slot's `added` bloom, so a shim never
+//! reads "absent" for an entry vDird has acknowledged.
+//!
+//! A snapshot that outgrows its slot goes to a new file, renamed over the
+//! old one, which is marked retired: shims that mapped it fall back to IPC
+//! until they exec again.
+
+use anyhow::{Context, Result};
+use memmap2::MmapMut;
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+use vrift_ipc::manifest_mmap::{
+    bloom_insert, mmap_key_hash, ManifestMmapBuilder, ManifestMmapControl, MANIFEST_MMAP_SUFFIX,
+    MMAP_BLOOM_SIZE,
+};
+use vrift_manifest::lmdb::LmdbManifest;
+
+/// Publisher of one project's manifest mmap
+pub struct ManifestMmap {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+    /// One publish at a time: each relies on `pending` covering everything
+    /// since its own snapshot
+    publishing: Mutex<()>,
+}
+
+struct Inner {
+    /// None until the first publish
+    mmap: Option<MmapMut>,
+    /// Keys noted while a publish builds its image, for the slot it flips to
+    pending: Option<Vec<u64>>,
+    /// Keys the VDir serves that the manifest doesn't have (files created
+    /// by a reingest), carried into every image
+    overlay: HashSet<u64>,
+}
+
+impl ManifestMmap {
+    /// Manifest mmap for the VDir at `vdir_path`
+    pub fn path_for(vdir_path: &Path) -> PathBuf {
+        let mut path = vdir_path.as_os_str().to_owned();
+        path.push(MANIFEST_MMAP_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    /// A publisher for `path`. Nothing is written until [`publish`](Self::publish);
+    /// keys noted before then are kept for it.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            inner: Mutex::new(Inner {
+                mmap: None,
+                pending: Some(Vec::new()),
+                overlay: HashSet::new(),
+            }),
+            publishing: Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// `key` is about to be added to the manifest (its insert hook)
+    pub fn note_added(&self, key: &str) {
+        self.lock().note(mmap_key_hash(key));
+    }
+
+    /// The VDir now serves `path_hash` though the manifest may not have it
+    pub fn note_overlay(&self, path_hash: u64) {
+        let mut inner = self.lock();
+        inner.overlay.insert(path_hash.max(1));
+        inner.note(path_hash.max(1));
+    }
+
+    /// Flips so far, for logs and tests
+    pub fn generation(&self) -> u64 {
+        self.lock()
+            .control()
+            .map_or(0, |c| c.generation.load(Ordering::Acquire))
+    }
+
+    /// Publish every key of `manifest`, as of one snapshot, plus the
+    /// overlay. Returns the number of keys.
+    pub fn publish(&self, manifest: &LmdbManifest) -> Result<usize> {
+        let _publishing = self.publishing.lock().unwrap_or_else(|e| e.into_inner());
+        // Keys added from here on may be missing from the snapshot; the
+        // manifest can't take it while an insert is between its hook call
+        // and the delta
+        {
+            let mut inner = self.lock();
+            inner.pending.get_or_insert_with(Vec::new);
+        }
+        let entries = manifest
+            .iter()
+            .map_err(|e| anyhow::anyhow!("Failed to read manifest: {}", e))?;
+
+        let mut builder = ManifestMmapBuilder::new();
+        for (path, entry) in &entries {
+            let vnode = &entry.vnode;
+            builder.add_entry(
+                path,
+                vnode.size,
+                vnode.mtime as i64,
+                vnode.mode,
+                vnode.is_dir(),
+                vnode.is_symlink(),
+            );
+        }
+
+        let mut inner = self.lock();
+        for &key_hash in &inner.overlay {
+            builder.add_key_hash(key_hash);
+        }
+        let image = builder.build();
+        let pending = inner.pending.take().unwrap_or_default();
+        let fits = inner
+            .control()
+            .is_some_and(|c| image.len() <= c.slot_size as usize);
+        if fits {
+            inner.flip(&image, &pending);
+        } else {
+            inner.mmap = Some(self.create(&image, &pending)?);
+        }
+        debug!(keys = builder.len(), path = %self.path.display(), "Manifest mmap published");
+        Ok(builder.len())
+    }
+
+    /// A new file holding `image` in slot 0, renamed over `self.path`
+    fn create(&self, image: &[u8], pending: &[u64]) -> Result<MmapMut> {
+        self.retire_existing();
+        let slot_size = image.len() * 2;
+        let tmp = {
+            let mut tmp = self.path.as_os_str().to_owned();
+            tmp.push(".tmp");
+            PathBuf::from(tmp)
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp)
+            .context("Failed to create manifest mmap")?;
+        file.set_len(ManifestMmapControl::file_size(slot_size) as u64)?;
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        let slot = ManifestMmapControl::slot_offset(0, slot_size);
+        mmap[slot..slot + image.len()].copy_from_slice(image);
+        let added = unsafe { mmap.as_mut_ptr().add(ManifestMmapControl::added_offset(0)) };
+        for &key_hash in pending {
+            unsafe { bloom_insert(added, key_hash) };
+        }
+        unsafe {
+            std::ptr::write(
+                mmap.as_mut_ptr() as *mut ManifestMmapControl,
+                ManifestMmapControl::new(slot_size),
+            )
+        };
+        std::fs::rename(&tmp, &self.path).context("Failed to publish manifest mmap")?;
+        info!(path = %self.path.display(), slot_size, "Manifest mmap created");
+        Ok(mmap)
+    }
+
+    /// Mark the file at `self.path`, ours or an earlier run's, retired so
+    /// shims still holding it stop trusting it
+    fn retire_existing(&self) {
+        let Ok(file) = OpenOptions::new().read(true).write(true).open(&self.path) else {
+            return;
+        };
+        let Ok(mmap) = (unsafe { MmapMut::map_mut(&file) }) else {
+            return;
+        };
+        if mmap.len() < ManifestMmapControl::SIZE {
+            return;
+        }
+        let control = unsafe { &*(mmap.as_ptr() as *const ManifestMmapControl) };
+        if control.is_valid() {
+            control.retired.store(1, Ordering::Release);
+        } else {
+            warn!(path = %self.path.display(), "Replacing unrecognized manifest mmap");
+        }
+    }
+}
+
+impl Inner {
+    fn control(&self) -> Option<&ManifestMmapControl> {
+        let mmap = self.mmap.as_ref()?;
+        Some(unsafe { &*(mmap.as_ptr() as *const ManifestMmapControl) })
+    }
+
+    /// Set `key_hash` in the active slot's `added` bloom, and keep it for
+    /// the image being built, if any
+    fn note(&mut self, key_hash: u64) {
+        if let Some(pending) = &mut self.pending {
+            pending.push(key_hash);
+        }
+        if let Some(mmap) = &mut self.mmap {
+            let control = unsafe { &*(mmap.as_ptr() as *const ManifestMmapControl) };
+            let slot = (control.generation.load(Ordering::Relaxed) & 1) as usize;
+            let added = unsafe {
+                mmap.as_mut_ptr()
+                    .add(ManifestMmapControl::added_offset(slot))
+            };
+            unsafe { bloom_insert(added, key_hash) };
+        }
+    }
+
+    /// Write `image` and `pending` into the inactive slot, then make it the
+    /// active one. Readers of the old slot see the generation move and
+    /// re-read.
+    fn flip(&mut self, image: &[u8], pending: &[u64]) {
+        let Some(mmap) = &mut self.mmap else {
+            return;
+        };
+        let control = unsafe { &*(mmap.as_ptr() as *const ManifestMmapControl) };
+        let generation = control.generation.load(Ordering::Relaxed);
+        let slot_size = control.slot_size as usize;
+        let next = ((generation + 1) & 1) as usize;
+
+        let added = ManifestMmapControl::added_offset(next);
+        mmap[added..added + MMAP_BLOOM_SIZE].fill(0);
+        let added = unsafe { mmap.as_mut_ptr().add(added) };
+        for &key_hash in pending {
+            unsafe { bloom_insert(added, key_hash) };
+        }
+        let slot = ManifestMmapControl::slot_offset(next, slot_size);
+        mmap[slot..slot + image.len()].copy_from_slice(image);
+
+        let control = unsafe { &*(mmap.as_ptr() as *const ManifestMmapControl) };
+        control.generation.store(generation + 1, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use vrift_ipc::manifest_mmap::proves_absent;
+    use vrift_manifest::lmdb::AssetTier;
+    use vrift_manifest::VnodeEntry;
+
+    fn setup() -> (TempDir, LmdbManifest, std::sync::Arc<ManifestMmap>) {
+        let temp = TempDir::new().unwrap();
+        let manifest = LmdbManifest::open(temp.path().join("manifest")).unwrap();
+        let published = std::sync::Arc::new(ManifestMmap::new(ManifestMmap::path_for(
+            &temp.path().join("test.vdir"),
+        )));
+        let hook = published.clone();
+        manifest.set_insert_hook(Box::new(move |key| hook.note_added(key)));
+        (temp, manifest, published)
+    }
+
+    fn file(n: u8) -> VnodeEntry {
+        VnodeEntry::new_file([n; 32], n as u64, 0, 0o100644)
+    }
+
+    /// What a shim mapping the file now would answer
+    fn absent(published: &ManifestMmap, key: &str) -> bool {
+        let bytes = std::fs::read(published.path()).unwrap();
+        let mut aligned = vec![0u64; bytes.len().div_ceil(8)];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                aligned.as_mut_ptr() as *mut u8,
+                bytes.len(),
+            );
+            proves_absent(aligned.as_ptr() as *const u8, bytes.len(), key)
+        }
+    }
+
+    #[test]
+    fn test_publish_then_add_without_republishing() {
+        let (_temp, manifest, published) = setup();
+        let tier = AssetTier::Tier2Mutable;
+        manifest.insert("/a.rs", file(1), tier);
+        manifest.commit().unwrap();
+        assert_eq!(published.publish(&manifest).unwrap(), 1);
+
+        assert!(!absent(&published, "/a.rs"));
+        assert!(absent(&published, "/b.rs"));
+
+        // Seen through the added bloom, before any commit or publish
+        manifest.upsert("/b.rs", VnodeEntry::new_whiteout(1), tier);
+        assert!(!absent(&published, "/b.rs"));
+        assert!(absent(&published, "/c.rs"));
+    }
+
+    #[test]
+    fn test_flip_keeps_what_was_added_meanwhile() {
+        let (_temp, manifest, published) = setup();
+        let tier = AssetTier::Tier2Mutable;
+        published.publish(&manifest).unwrap();
+        assert_eq!(published.generation(), 0);
+
+        manifest.insert("/a.rs", file(1), tier);
+        published.publish(&manifest).unwrap();
+        assert_eq!(published.generation(), 1);
+        assert!(!absent(&published, "/a.rs"));
+
+        // Removed entries drop out of the next image
+        manifest.remove("/a.rs");
+        published.publish(&manifest).unwrap();
+        assert_eq!(published.generation(), 2);
+        assert!(absent(&published, "/a.rs"));
+
+        // The VDir's own entries stay in every image
+        published.note_overlay(crate::vdir::fnv1a_hash("/new.rs"));
+        published.publish(&manifest).unwrap();
+        assert!(!absent(&published, "/new.rs"));
+    }
+
+    #[test]
+    fn test_growth_retires_the_old_file() {
+        let (_temp, manifest, published) = setup();
+        published.publish(&manifest).unwrap();
+        let old = OpenOptions::new()
+            .read(true)
+            .open(published.path())
+            .unwrap();
+        let old = unsafe { memmap2::Mmap::map(&old).unwrap() };
+
+        let tier = AssetTier::Tier2Mutable;
+        for i in 0..5000 {
+            manifest.insert(&format!("/gen/{}.o", i), file(1), tier);
+        }
+        published.publish(&manifest).unwrap();
+
+        let control = unsafe { &*(old.as_ptr() as *const ManifestMmapControl) };
+        assert_eq!(control.retired.load(Ordering::Acquire), 1);
+        assert!(!unsafe { proves_absent(old.as_ptr(), old.len(), "/x") });
+        assert!(!absent(&published, "/gen/4999.o"));
+        assert!(absent(&published, "/gen/5000.o"));
+    }
+}
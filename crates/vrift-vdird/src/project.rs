@@ -0,0 +1,477 @@
+//! One project served by vdir_d
+//!
+//! Everything a project has is its own: VDir, manifest and its WAL,
+//! reingest journal, staging dir, watcher, socket and Metrics. Only the
+//! process (and the CAS on disk) is shared with the other projects in the
+//! registry, so one project stopping leaves the rest serving.
+
+use crate::registry::ProjectRegistry;
+use crate::{
+    admission, commands, ingest, journal, manifest_mmap, protect, scan, socket, state, vdir, wal,
+    watch, ProjectConfig, SHUTDOWN_DRAIN_TIMEOUT,
+};
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::info;
+
+/// Longest queued lookups wait before the hot cache sees them
+const HOT_CACHE_APPLY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// A project being served
+pub struct Project {
+    config: ProjectConfig,
+    stop: tokio::sync::watch::Sender<bool>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl Project {
+    /// Open the project's state, start its watcher, scan and commit tasks
+    /// and listen on its socket. `stopped` is notified once it stops,
+    /// whether asked to or because one of its tasks ended.
+    ///
+    /// Boxed: the listener answers ProjectOpen by starting a project, so
+    /// the future would otherwise contain itself.
+    pub fn start(
+        config: ProjectConfig,
+        registry: Weak<ProjectRegistry>,
+        stopped: Arc<Notify>,
+    ) -> Pin<Box<dyn Future<Output = Result<Self>> + Send>> {
+        Box::pin(Self::open(config, registry, stopped))
+    }
+
+    async fn open(
+        config: ProjectConfig,
+        registry: Weak<ProjectRegistry>,
+        stopped: Arc<Notify>,
+    ) -> Result<Self> {
+        info!(
+            project_root = %config.project_root.display(),
+            project_id = %config.project_id,
+            "Starting vdir_d"
+        );
+
+        // Ensure directories exist
+        std::fs::create_dir_all(config.socket_path.parent().unwrap())?;
+        std::fs::create_dir_all(&config.staging_base)?;
+        std::fs::create_dir_all(&config.cas_path)?;
+
+        // One vdir_d per project: the lock lives next to the project socket
+        let instance_lock = vrift_ipc::InstanceLock::acquire(&config.socket_path).map_err(|e| {
+            anyhow::anyhow!(
+                "vdir_d already running for {} ({})",
+                config.project_root.display(),
+                e
+            )
+        })?;
+
+        // Cleanup orphan staging files (max age: 1 hour)
+        match state::cleanup_orphan_staging(&config.staging_base, 3600) {
+            Ok(0) => {}
+            Ok(count) => info!(count, "Cleaned orphan staging files"),
+            Err(e) => tracing::warn!(error = %e, "Failed to cleanup orphan staging files"),
+        }
+
+        // Initialize VDir mmap
+        let mut vdir = vdir::VDir::create_or_open(&config.vdir_path)?;
+        info!(path = %config.vdir_path.display(), "VDir mmap initialized");
+
+        // Initialize reingest journal for crash recovery
+        let journal_path = journal::ReingestJournal::path_for(&config.project_root);
+        let reingest_journal = journal::ReingestJournal::open(&journal_path)
+            .map_err(|e| anyhow::anyhow!("Failed to open reingest journal: {}", e))?;
+        info!(path = %journal_path.display(), pending = reingest_journal.len(), "Reingest journal initialized");
+
+        // RFC-0039: Initialize LMDB manifest for Live Ingest
+        let manifest_path = &config.manifest_path;
+        std::fs::create_dir_all(manifest_path.parent().unwrap())?;
+        let manifest = Arc::new(
+            vrift_manifest::lmdb::LmdbManifest::open(manifest_path)
+                .map_err(|e| anyhow::anyhow!("Failed to open manifest: {}", e))?,
+        );
+        info!(path = %manifest_path.display(), "LMDB manifest initialized");
+
+        // Replay IPC writes the last run logged but never committed
+        let wal_path = wal::ManifestWal::path_for(&config.project_root);
+        let (manifest_wal, replayed) = wal::ManifestWal::open(&wal_path, manifest.clone())
+            .map_err(|e| anyhow::anyhow!("Failed to open manifest WAL: {}", e))?;
+        let manifest_wal = Arc::new(manifest_wal);
+        info!(path = %wal_path.display(), replayed, "Manifest WAL initialized");
+
+        // P0: Load persistent state (last_scan time)
+        let state_path = state::state_path(&config.project_root);
+        let daemon_state = state::DaemonState::load(&state_path);
+        let last_scan = daemon_state.last_scan();
+        info!(
+            last_scan_secs = daemon_state.last_scan_secs,
+            "Loaded daemon state"
+        );
+
+        // Warm start: reuse the VDir left by the previous run if its generation checks out
+        let warm_policy = state::WarmStartPolicy::load(&config.project_root);
+        match state::warm_start_vdir_with(&mut vdir, &manifest, &daemon_state, &warm_policy) {
+            Ok(state::VDirWarmStart::Reused) => {
+                info!(
+                    entries = vdir.entry_count(),
+                    generation = vdir.generation(),
+                    "VDir warm start: reused"
+                )
+            }
+            Ok(state::VDirWarmStart::Merged(count)) => {
+                info!(
+                    merged = count,
+                    "VDir warm start: kept, merged missing manifest entries"
+                )
+            }
+            Ok(state::VDirWarmStart::Rebuilt(count)) => {
+                info!(entries = count, "VDir cold start: rebuilt from manifest")
+            }
+            Err(e) => tracing::warn!(error = %e, "VDir warm start failed, serving from LMDB"),
+        }
+
+        // Past the cap, entries get into the VDir by observed lookup frequency
+        let mut hot_cache = admission::HotCache::new(warm_policy.max_entries);
+        if hot_cache.is_enabled() {
+            match hot_cache.seed(&vdir, &manifest) {
+                Ok(tracked) => info!(
+                    capacity = hot_cache.capacity(),
+                    tracked, "VDir hot cache admission enabled"
+                ),
+                Err(e) => tracing::warn!(error = %e, "Failed to seed VDir hot cache"),
+            }
+        }
+
+        // Every manifest key, so shims answer "not in the manifest" without
+        // asking; hooked before anything else writes the manifest
+        let published = Arc::new(manifest_mmap::ManifestMmap::new(
+            manifest_mmap::ManifestMmap::path_for(&config.vdir_path),
+        ));
+        let hook = published.clone();
+        manifest.set_insert_hook(Box::new(move |key| hook.note_added(key)));
+        match published.publish(&manifest) {
+            Ok(keys) => info!(keys, path = %published.path().display(), "Manifest mmap published"),
+            Err(e) => tracing::warn!(error = %e, "Failed to publish manifest mmap"),
+        }
+
+        // RFC-0039: Create ingest channel (fixed-size for backpressure)
+        let (ingest_tx, ingest_rx) = mpsc::channel::<watch::IngestEvent>(4096);
+
+        // Initialize CAS store (TheSource™)
+        let cas = vrift_cas::CasStore::default_location()
+            .map_err(|e| anyhow::anyhow!("Failed to initialize CAS: {}", e))?;
+        info!(root = %cas.root().display(), "CAS store initialized");
+
+        // Phase 1: Start consumer FIRST (consumer-first pattern)
+        let ingest_queue = ingest::IngestQueue::new(ingest_rx);
+        let protection = Arc::new(
+            protect::Protection::load(&vrift_manifest::GenerationStore::for_project(
+                &config.project_root,
+            ))
+            .unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "Failed to load protected snapshots");
+                Default::default()
+            }),
+        );
+        // Manifest keys the watcher changed, refreshed in the VDir once the
+        // command handler is up
+        let (changed_tx, mut changed_rx) = mpsc::unbounded_channel::<String>();
+        let handler = Arc::new(
+            ingest::IngestHandler::new(config.project_root.clone(), manifest.clone(), cas)
+                .with_protection(protection.clone())
+                .with_change_feed(changed_tx),
+        );
+        let consumer_handle = tokio::spawn(async move {
+            ingest::run_consumer(ingest_queue, handler).await;
+        });
+        info!("Ingest consumer started (consumer-first pattern)");
+
+        // Phase 2: Start FS Watch producer
+        let watch_handle = watch::spawn_watch_task(config.project_root.clone(), ingest_tx.clone());
+        info!("FS Watch producer started");
+
+        // Phase 3: Run compensation scan (Layer 3) for offline changes
+        let scan_tx = ingest_tx.clone();
+        let scan_root = config.project_root.clone();
+        let scan_manifest = manifest.clone();
+        let state_path_clone = state_path.clone();
+        let scan_handle = tokio::spawn(async move {
+            let count =
+                scan::run_compensation_scan(scan_root, last_scan, scan_manifest, scan_tx).await;
+
+            // P0: Update last_scan after successful scan
+            if count > 0 || last_scan == std::time::SystemTime::UNIX_EPOCH {
+                let mut state = state::DaemonState::load(&state_path_clone);
+                state.update_last_scan();
+                if let Err(e) = state.save(&state_path_clone) {
+                    tracing::warn!(error = %e, "Failed to save daemon state after scan");
+                }
+            }
+        });
+        info!("Compensation scan started");
+
+        // P1: Periodic manifest commit task (every 30 seconds)
+        let commit_manifest = manifest.clone();
+        let commit_wal = manifest_wal.clone();
+        let commit_state_path = state_path.clone();
+        let commit_vdir_path = config.vdir_path.clone();
+        let commit_project_root = config.project_root.clone();
+        let commit_published = published.clone();
+        let commit_handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                // Capturing provenance reads git and may spawn `hostname`;
+                // not worth it for an idle tick
+                if !commit_manifest.has_pending() {
+                    continue;
+                }
+
+                // Commit delta layer to base layer
+                let provenance = vrift_manifest::Provenance::capture(
+                    "vdir_d auto-commit",
+                    Some(&commit_project_root),
+                );
+                match commit_wal.commit_with_provenance(&provenance) {
+                    Ok(_) => {
+                        let mut state = state::DaemonState::load(&commit_state_path);
+                        state.update_last_commit();
+                        if let Ok(vdir) = vdir::VDir::open_readonly(&commit_vdir_path) {
+                            state.record_vdir(&vdir);
+                        }
+                        if let Err(e) = commit_manifest.len() {
+                            tracing::debug!(error = %e, "Failed to get manifest len");
+                        }
+                        if let Err(e) = state.save(&commit_state_path) {
+                            tracing::warn!(error = %e, "Failed to save state after commit");
+                        }
+                        // Starts the added bloom over
+                        if let Err(e) = commit_published.publish(&commit_manifest) {
+                            tracing::warn!(error = %e, "Failed to republish manifest mmap");
+                        }
+                        tracing::debug!("Periodic manifest commit completed");
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Periodic manifest commit failed");
+                    }
+                }
+            }
+        });
+        info!("Periodic commit task started (30s interval)");
+
+        let listener = socket::bind(&config)?;
+        let handler = Arc::new(tokio::sync::RwLock::new(
+            commands::CommandHandler::new(config.clone(), vdir, manifest.clone(), reingest_journal)
+                .with_hot_cache(hot_cache)
+                .with_protection(protection)
+                .with_wal(manifest_wal.clone())
+                .with_manifest_mmap(published)
+                .with_registry(registry),
+        ));
+        // Finish reingests a crash interrupted before anyone can look them up
+        let recovered = handler.write().await.recover_journal().await;
+        if recovered > 0 {
+            info!(recovered, "Recovered reingests from journal");
+        }
+
+        // Helpers that run for as long as the project does
+        let mut helpers = JoinSet::new();
+        let refresh_handler = handler.clone();
+        helpers.spawn(async move {
+            while let Some(path) = changed_rx.recv().await {
+                let mut handler = refresh_handler.write().await;
+                handler.refresh_entry(&path);
+                // Whatever queued up meanwhile goes under the same lock
+                while let Ok(path) = changed_rx.try_recv() {
+                    handler.refresh_entry(&path);
+                }
+            }
+        });
+        // Lookups queue hot-cache admissions and apply them when the lock is
+        // free; under a steady stream of lookups it may never be, so wait for
+        // it now and then
+        let touches_handler = handler.clone();
+        helpers.spawn(async move {
+            let mut interval = tokio::time::interval(HOT_CACHE_APPLY_INTERVAL);
+            loop {
+                interval.tick().await;
+                if touches_handler.read().await.has_touches() {
+                    touches_handler.write().await.apply_touches();
+                }
+            }
+        });
+        // SIGHUP: pick up manifest edits made underneath us, without a restart
+        let mut sighup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        let reload_handler = handler.clone();
+        helpers.spawn(async move {
+            while sighup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading manifest");
+                reload_handler.write().await.handle_manifest_reload();
+            }
+        });
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let peers = vrift_ipc::peer::PeerPolicy::for_current_user(
+            &vrift_config::config().daemon.allowed_uids,
+        );
+        let socket_handle = tokio::spawn(socket::run_listener(
+            listener,
+            handler.clone(),
+            peers,
+            shutdown_rx,
+            SHUTDOWN_DRAIN_TIMEOUT,
+        ));
+
+        let (stop, stop_rx) = tokio::sync::watch::channel(false);
+        let serving = Serving {
+            config: config.clone(),
+            handler,
+            manifest_wal,
+            daemon_state,
+            state_path,
+            ingest_tx,
+            consumer_handle,
+            watch_handle,
+            scan_handle,
+            commit_handle,
+            socket_handle,
+            shutdown_tx,
+            _helpers: helpers,
+            _instance_lock: instance_lock,
+        };
+        let task = tokio::spawn(async move {
+            let result = serving.run(stop_rx).await;
+            stopped.notify_one();
+            result
+        });
+        Ok(Self { config, stop, task })
+    }
+
+    pub fn config(&self) -> &ProjectConfig {
+        &self.config
+    }
+
+    /// Whether it still serves (it stops by itself if one of its tasks ends)
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Drain and stop it, or collect how it stopped by itself
+    pub async fn stop(self) -> Result<()> {
+        let _ = self.stop.send(true);
+        self.task.await?
+    }
+}
+
+/// What a started project runs until it stops
+struct Serving {
+    config: ProjectConfig,
+    handler: Arc<tokio::sync::RwLock<commands::CommandHandler>>,
+    manifest_wal: Arc<wal::ManifestWal>,
+    daemon_state: state::DaemonState,
+    state_path: std::path::PathBuf,
+    ingest_tx: mpsc::Sender<watch::IngestEvent>,
+    consumer_handle: JoinHandle<()>,
+    watch_handle: JoinHandle<()>,
+    scan_handle: JoinHandle<()>,
+    commit_handle: JoinHandle<()>,
+    socket_handle: JoinHandle<Result<()>>,
+    shutdown_tx: tokio::sync::watch::Sender<bool>,
+    /// Aborted when dropped, once the project has stopped
+    _helpers: JoinSet<()>,
+    _instance_lock: vrift_ipc::InstanceLock,
+}
+
+impl Serving {
+    async fn run(mut self, mut stop: tokio::sync::watch::Receiver<bool>) -> Result<()> {
+        // Wait for any task to complete, or to be stopped
+        tokio::select! {
+            _ = &mut self.consumer_handle => {
+                info!("Consumer exited");
+            }
+            _ = &mut self.watch_handle => {
+                info!("Watch exited");
+            }
+            _ = &mut self.commit_handle => {
+                info!("Commit task exited");
+            }
+            result = &mut self.socket_handle => {
+                result??;
+            }
+            _ = stop.wait_for(|stop| *stop) => {
+                info!(project_root = %self.config.project_root.display(), "Stopping project");
+            }
+        }
+        let deadline = tokio::time::Instant::now() + SHUTDOWN_DRAIN_TIMEOUT;
+
+        // 1. Stop accepting and let in-flight requests (reingests) finish
+        let _ = self.shutdown_tx.send(true);
+        if !self.socket_handle.is_finished() {
+            match tokio::time::timeout_at(deadline, &mut self.socket_handle).await {
+                Ok(Ok(Err(e))) => tracing::warn!(error = %e, "Listener failed while draining"),
+                Ok(_) => {}
+                Err(_) => {
+                    tracing::warn!("Listener did not drain in time");
+                    self.socket_handle.abort();
+                }
+            }
+        }
+
+        // 2. Stop producers and flush what the ingest queue already holds
+        self.watch_handle.abort();
+        self.scan_handle.abort();
+        self.commit_handle.abort();
+        drop(self.ingest_tx);
+        if !self.consumer_handle.is_finished()
+            && tokio::time::timeout_at(deadline, &mut self.consumer_handle)
+                .await
+                .is_err()
+        {
+            tracing::warn!("Ingest queue did not drain in time");
+            self.consumer_handle.abort();
+        }
+
+        // No request can run past this point; the handler is ours alone
+        let handler = self.handler.read().await;
+        let pending = handler.journal().len();
+        if pending > 0 {
+            tracing::warn!(
+                pending,
+                "Reingests still journalled at shutdown; they will be reported on next start"
+            );
+        }
+
+        // 3. Persist state, including the generation of the VDir we leave behind
+        if let Err(e) = handler.vdir().flush() {
+            tracing::warn!(error = %e, "Failed to flush VDir on shutdown");
+        }
+        let mut daemon_state = self.daemon_state;
+        daemon_state.update_last_scan();
+        daemon_state.record_vdir(handler.vdir());
+        let provenance = vrift_manifest::Provenance::capture(
+            "vdir_d shutdown commit",
+            Some(&self.config.project_root),
+        );
+        if let Err(e) = self.manifest_wal.commit_with_provenance(&provenance) {
+            tracing::warn!(error = %e, "Failed to commit manifest on shutdown");
+        } else {
+            daemon_state.update_last_commit();
+        }
+        if let Err(e) = daemon_state.save(&self.state_path) {
+            tracing::warn!(error = %e, "Failed to save daemon state on shutdown");
+        }
+        info!(
+            generation = daemon_state.vdir_generation,
+            "Daemon state saved on shutdown"
+        );
+
+        // 4. Only now stop advertising the socket
+        if let Err(e) = std::fs::remove_file(&self.config.socket_path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!(error = %e, "Failed to remove socket on shutdown");
+            }
+        }
+        Ok(())
+    }
+}
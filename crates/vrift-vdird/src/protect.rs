@@ -0,0 +1,250 @@
+//! Protect-on-commit for manifest snapshots
+//!
+//! A protected snapshot's entries can't be changed through vdir_d: upserts,
+//! removes, renames, mtime updates and CoW reingests of those paths are
+//! refused with `PermissionDenied`, and the watcher stops folding on-disk
+//! edits of them into the manifest. The CAS blobs they reference get the
+//! iron-law mode (0444), the immutable flag where the platform lets us set
+//! it, and a `snapshot-<id>` pin so GC keeps them.
+//!
+//! The [`GenerationStore`] records which snapshots are protected; the path
+//! set is rebuilt from their generations at startup and whenever it changes.
+//! Protection only comes off through an explicit, forced unprotect.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::RwLock;
+
+use tracing::{debug, info, warn};
+use vrift_cas::{Blake3Hash, CasStore, PinStore};
+use vrift_manifest::{GenerationInfo, GenerationStore, LmdbResult, ManifestEntry};
+
+/// Protected manifest paths, shared by the command handler and the watcher
+#[derive(Debug, Default)]
+pub struct Protection {
+    /// Path → (id of a snapshot protecting it, content hash it protects)
+    paths: RwLock<HashMap<String, (Blake3Hash, Blake3Hash)>>,
+}
+
+impl Protection {
+    /// Read the protected snapshots recorded in `store`
+    pub fn load(store: &GenerationStore) -> LmdbResult<Self> {
+        let protection = Self::default();
+        protection.rebuild(store)?;
+        Ok(protection)
+    }
+
+    /// Id of a snapshot protecting `path`, if any
+    pub fn protector(&self, path: &str) -> Option<Blake3Hash> {
+        let paths = self.paths.read().unwrap_or_else(|e| e.into_inner());
+        if paths.is_empty() {
+            return None;
+        }
+        paths.get(path).map(|(id, _)| *id)
+    }
+
+    /// First protected path that replacing the manifest with `entries`
+    /// would drop or change
+    pub fn first_violation(&self, entries: &[(String, ManifestEntry)]) -> Option<String> {
+        let paths = self.paths.read().unwrap_or_else(|e| e.into_inner());
+        if paths.is_empty() {
+            return None;
+        }
+        let incoming: HashMap<&str, &Blake3Hash> = entries
+            .iter()
+            .map(|(p, e)| (p.as_str(), &e.vnode.content_hash))
+            .collect();
+        paths
+            .iter()
+            .find(|(path, (_, content))| incoming.get(path.as_str()) != Some(&content))
+            .map(|(path, _)| path.clone())
+    }
+
+    /// Protected path count
+    pub fn len(&self) -> usize {
+        self.paths.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Protect snapshot `info`: record it, lock its blobs down and start
+    /// refusing changes to its entries
+    pub fn protect(
+        &self,
+        store: &GenerationStore,
+        cas_root: &Path,
+        info: &GenerationInfo,
+    ) -> LmdbResult<()> {
+        let blobs = blob_hashes(store, &info.id)?;
+        store.set_protected(&info.id, true)?;
+        self.rebuild(store)?;
+        lock_blobs(cas_root, &blobs, &pin_label(info));
+        info!(
+            id = %info.id_hex(),
+            paths = self.len(),
+            blobs = blobs.len(),
+            "Snapshot protected"
+        );
+        Ok(())
+    }
+
+    /// Undo [`Protection::protect`]. Blobs still referenced by another
+    /// protected snapshot stay immutable.
+    pub fn unprotect(
+        &self,
+        store: &GenerationStore,
+        cas_root: &Path,
+        info: &GenerationInfo,
+    ) -> LmdbResult<()> {
+        store.set_protected(&info.id, false)?;
+        self.rebuild(store)?;
+
+        let mut still_locked = HashSet::new();
+        for id in store.protected()? {
+            still_locked.extend(blob_hashes(store, &id)?);
+        }
+        let released: HashSet<_> = blob_hashes(store, &info.id)?
+            .difference(&still_locked)
+            .copied()
+            .collect();
+        release_blobs(cas_root, &released, &pin_label(info));
+        info!(
+            id = %info.id_hex(),
+            released = released.len(),
+            "Snapshot unprotected"
+        );
+        Ok(())
+    }
+
+    fn rebuild(&self, store: &GenerationStore) -> LmdbResult<()> {
+        let mut paths = HashMap::new();
+        for id in store.protected()? {
+            for (path, entry) in store.load(&id)? {
+                paths.entry(path).or_insert((id, entry.vnode.content_hash));
+            }
+        }
+        *self.paths.write().unwrap_or_else(|e| e.into_inner()) = paths;
+        Ok(())
+    }
+}
+
+/// Pin label holding a protected snapshot's blobs
+pub fn pin_label(info: &GenerationInfo) -> String {
+    format!("snapshot-{}", &info.id_hex()[..12])
+}
+
+/// Content blobs referenced by generation `id` (not directories or symlinks)
+fn blob_hashes(store: &GenerationStore, id: &Blake3Hash) -> LmdbResult<HashSet<Blake3Hash>> {
+    Ok(store
+        .load(id)?
+        .into_iter()
+        .filter(|(_, e)| !e.vnode.is_dir() && !e.vnode.is_symlink())
+        .map(|(_, e)| e.vnode.content_hash)
+        .filter(|h| *h != [0u8; 32])
+        .collect())
+}
+
+fn lock_blobs(cas_root: &Path, hashes: &HashSet<Blake3Hash>, label: &str) {
+    let cas = match CasStore::new(cas_root) {
+        Ok(cas) => cas,
+        Err(e) => {
+            warn!(error = %e, "Cannot open CAS to protect snapshot blobs");
+            return;
+        }
+    };
+    let mut not_immutable = 0usize;
+    for hash in hashes {
+        let Some(path) = cas.blob_path_for_hash(hash) else {
+            continue;
+        };
+        // chmod first: an immutable file can't be chmod'ed
+        if let Err(e) = vrift_cas::enforce_cas_invariant(&path) {
+            debug!(path = %path.display(), error = %e, "Blob mode not enforced");
+        }
+        if vrift_cas::set_immutable(&path, true).is_err() {
+            not_immutable += 1;
+        }
+    }
+    if not_immutable > 0 {
+        // Linux needs CAP_LINUX_IMMUTABLE; the pin and 0444 still apply
+        warn!(
+            count = not_immutable,
+            "Could not set the immutable flag on some protected blobs"
+        );
+    }
+
+    let pinned = PinStore::load(cas_root).and_then(|mut pins| {
+        pins.pin(label, hashes);
+        pins.save()
+    });
+    if let Err(e) = pinned {
+        warn!(error = %e, label, "Failed to pin protected snapshot blobs");
+    }
+}
+
+fn release_blobs(cas_root: &Path, hashes: &HashSet<Blake3Hash>, label: &str) {
+    if let Ok(cas) = CasStore::new(cas_root) {
+        for path in hashes.iter().filter_map(|h| cas.blob_path_for_hash(h)) {
+            let _ = vrift_cas::set_immutable(&path, false);
+        }
+    }
+    let unpinned = PinStore::load(cas_root).and_then(|mut pins| {
+        pins.unpin(label);
+        pins.save()
+    });
+    if let Err(e) = unpinned {
+        warn!(error = %e, label, "Failed to unpin snapshot blobs");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrift_manifest::lmdb::{AssetTier, LmdbManifest};
+    use vrift_manifest::VnodeEntry;
+
+    #[test]
+    fn test_protection_tracks_protected_snapshots() {
+        let temp = tempfile::tempdir().unwrap();
+        let cas_root = temp.path().join("cas");
+        let cas = CasStore::new(&cas_root).unwrap();
+        let hash = cas.store(b"released").unwrap();
+
+        let manifest = LmdbManifest::open(temp.path().join("manifest.lmdb")).unwrap();
+        manifest.insert(
+            "/release/app",
+            VnodeEntry::new_file(hash, 8, 0, 0o644),
+            AssetTier::Tier2Mutable,
+        );
+        manifest.commit().unwrap();
+        let store = GenerationStore::new(temp.path().join("snapshots"));
+        let info = store.create(&manifest, "v1").unwrap();
+
+        let protection = Protection::load(&store).unwrap();
+        assert!(protection.is_empty());
+        protection.protect(&store, &cas_root, &info).unwrap();
+        assert_eq!(protection.protector("/release/app"), Some(info.id));
+        assert_eq!(protection.protector("/other"), None);
+        let current = manifest.snapshot().unwrap().iter().unwrap();
+        assert_eq!(protection.first_violation(&current), None);
+        assert_eq!(
+            protection.first_violation(&[]),
+            Some("/release/app".to_string())
+        );
+
+        // Survives a restart
+        let reloaded = Protection::load(&store).unwrap();
+        assert_eq!(reloaded.protector("/release/app"), Some(info.id));
+        let pins = PinStore::load(&cas_root).unwrap();
+        assert!(pins.pinned_hashes().contains(&hash));
+
+        protection.unprotect(&store, &cas_root, &info).unwrap();
+        assert!(protection.is_empty());
+        let pins = PinStore::load(&cas_root).unwrap();
+        assert!(!pins.pinned_hashes().contains(&hash));
+        let blob = cas.blob_path_for_hash(&hash).unwrap();
+        assert!(!vrift_cas::is_immutable(&blob).unwrap_or(false));
+    }
+}
@@ -0,0 +1,227 @@
+//! Projects one vdir_d serves, keyed by canonical project root
+//!
+//! vdir_d starts with the projects on its command line and opens more on
+//! `ProjectOpen`. Each one is a [`Project`] with its own socket, so shims
+//! and the CLI reach a project exactly as they reach a vdir_d of its own.
+
+use crate::project::Project;
+use crate::ProjectConfig;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{Mutex, Notify};
+use tracing::{info, warn};
+use vrift_ipc::{VeloError, VeloResponse};
+
+#[derive(Default)]
+struct Projects {
+    by_root: HashMap<PathBuf, Project>,
+    /// Set by `stop_all`; no project opens after it
+    closed: bool,
+}
+
+#[derive(Default)]
+pub struct ProjectRegistry {
+    projects: Mutex<Projects>,
+    /// Notified when a project stops
+    stopped: Arc<Notify>,
+}
+
+impl ProjectRegistry {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Serve the project `config` is for, unless it already is; either way
+    /// the config it is served with
+    pub async fn open(self: &Arc<Self>, config: ProjectConfig) -> Result<ProjectConfig> {
+        let mut projects = self.projects.lock().await;
+        if projects.closed {
+            anyhow::bail!("vdir_d is shutting down");
+        }
+        if let Some(project) = projects.by_root.get(&config.project_root) {
+            if project.is_running() {
+                return Ok(project.config().clone());
+            }
+        }
+        // One that stopped by itself is started afresh
+        if let Some(project) = projects.by_root.remove(&config.project_root) {
+            log_stopped(&config.project_root, project.stop().await);
+        }
+        let root = config.project_root.clone();
+        let project = Project::start(config, Arc::downgrade(self), self.stopped.clone()).await?;
+        let config = project.config().clone();
+        projects.by_root.insert(root, project);
+        info!(projects = projects.by_root.len(), "Project opened");
+        Ok(config)
+    }
+
+    /// Answer a `ProjectOpen` for `project_root`
+    pub async fn handle_open(self: &Arc<Self>, project_root: &str) -> VeloResponse {
+        let root = match Path::new(project_root).canonicalize() {
+            Ok(root) if root.is_dir() => root,
+            _ => {
+                return VeloResponse::Error(VeloError::with_path(
+                    vrift_ipc::VeloErrorKind::NotFound,
+                    "Project root does not exist",
+                    project_root,
+                ))
+            }
+        };
+        match self.open(ProjectConfig::for_shared_daemon(root)).await {
+            Ok(config) => VeloResponse::ProjectAck {
+                project_id: config.project_id,
+                vdird_socket: config.socket_path.to_string_lossy().to_string(),
+                vdir_mmap_path: config.vdir_path.to_string_lossy().to_string(),
+            },
+            Err(e) => VeloResponse::Error(VeloError::internal(format!(
+                "Failed to open project: {}",
+                e
+            ))),
+        }
+    }
+
+    /// Roots of the projects being served
+    pub async fn roots(&self) -> Vec<PathBuf> {
+        let projects = self.projects.lock().await;
+        let mut roots: Vec<_> = projects
+            .by_root
+            .iter()
+            .filter(|(_, project)| project.is_running())
+            .map(|(root, _)| root.clone())
+            .collect();
+        roots.sort();
+        roots
+    }
+
+    /// Wait until a project stops
+    pub async fn stopped(&self) {
+        self.stopped.notified().await
+    }
+
+    /// Forget the projects that stopped by themselves; how many still serve
+    pub async fn reap(&self) -> usize {
+        let mut projects = self.projects.lock().await;
+        let stopped: Vec<PathBuf> = projects
+            .by_root
+            .iter()
+            .filter(|(_, project)| !project.is_running())
+            .map(|(root, _)| root.clone())
+            .collect();
+        for root in stopped {
+            if let Some(project) = projects.by_root.remove(&root) {
+                log_stopped(&root, project.stop().await);
+            }
+        }
+        projects.by_root.len()
+    }
+
+    /// Stop every project, for shutdown. The lock isn't held while they
+    /// drain: their in-flight requests may be `ProjectOpen`s waiting on it.
+    pub async fn stop_all(&self) {
+        let by_root = {
+            let mut projects = self.projects.lock().await;
+            projects.closed = true;
+            std::mem::take(&mut projects.by_root)
+        };
+        let mut stops = tokio::task::JoinSet::new();
+        for (root, project) in by_root {
+            stops.spawn(async move { (root, project.stop().await) });
+        }
+        while let Some(stopped) = stops.join_next().await {
+            if let Ok((root, result)) = stopped {
+                log_stopped(&root, result);
+            }
+        }
+    }
+}
+
+fn log_stopped(root: &Path, result: Result<()>) {
+    match result {
+        Ok(()) => info!(project_root = %root.display(), "Project stopped"),
+        Err(e) => {
+            warn!(project_root = %root.display(), error = %e, "Project stopped with an error")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use vrift_ipc::client::DaemonClient;
+    use vrift_ipc::VnodeEntry;
+
+    /// Config with every file of the project under `root`
+    fn config_in(root: &Path) -> ProjectConfig {
+        let project_root = root.join("project");
+        std::fs::create_dir_all(&project_root).unwrap();
+        ProjectConfig {
+            project_id: ProjectConfig::for_shared_daemon(project_root.clone()).project_id,
+            vdir_path: root.join("vdir.mmap"),
+            socket_path: root.join("vdird.sock"),
+            staging_base: project_root.join(".vrift").join("staging"),
+            cas_path: root.join("the_source"),
+            manifest_path: root.join("manifest.lmdb"),
+            project_root,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_projects_are_served_separately() {
+        let (a, b) = (tempdir().unwrap(), tempdir().unwrap());
+        let registry = ProjectRegistry::new();
+        let served_a = registry.open(config_in(a.path())).await.unwrap();
+        let served_b = registry.open(config_in(b.path())).await.unwrap();
+
+        // Opening a project again finds the one being served
+        let again = registry.open(config_in(a.path())).await.unwrap();
+        assert_eq!(again.socket_path, served_a.socket_path);
+        assert_eq!(registry.roots().await.len(), 2);
+
+        let entry = VnodeEntry {
+            content_hash: [7; 32],
+            size: 5,
+            mtime: 0,
+            mode: 0o100644,
+            flags: 0,
+            _pad: 0,
+        };
+        let mut client_a = DaemonClient::connect_path(&served_a.socket_path)
+            .await
+            .unwrap();
+        let _idle_a = DaemonClient::connect_path(&served_a.socket_path)
+            .await
+            .unwrap();
+        let mut client_b = DaemonClient::connect_path(&served_b.socket_path)
+            .await
+            .unwrap();
+        client_a
+            .manifest_upsert("/only/a.txt", entry)
+            .await
+            .unwrap();
+
+        // Each project has its own manifest...
+        assert!(client_a
+            .manifest_get("/only/a.txt")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(client_b
+            .manifest_get("/only/a.txt")
+            .await
+            .unwrap()
+            .is_none());
+
+        // ...and its own Metrics
+        assert_eq!(client_a.metrics().await.unwrap().connections, 2);
+        assert_eq!(client_b.metrics().await.unwrap().connections, 1);
+
+        drop((client_a, client_b));
+        registry.stop_all().await;
+        assert!(!served_a.socket_path.exists());
+        assert!(!served_b.socket_path.exists());
+        assert!(registry.open(config_in(a.path())).await.is_err());
+    }
+}
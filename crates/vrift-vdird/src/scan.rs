@@ -1,16 +1,20 @@
 //! RFC-0039: Compensation Scan for Live Ingest (Layer 3)
 //!
 //! Scans project directory for changes that occurred while daemon was stopped.
-//! Uses mtime delta detection against manifest.
+//! Uses mtime delta detection, and with a manifest also compares each path's
+//! size and mtime against its entry and reports entries whose file is gone.
 
 use std::fs;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
 
-use crate::ignore::IgnoreMatcher;
 use crate::watch::IngestEvent;
+use vrift_config::ignore::IgnoreMatcher;
+use vrift_manifest::lmdb::{LmdbManifest, ManifestEntry};
 
 /// Scan configuration
 #[derive(Debug, Clone)]
@@ -39,6 +43,8 @@ impl Default for ScanConfig {
 /// Compensation scanner for Layer 3
 pub struct CompensationScanner {
     config: ScanConfig,
+    /// Manifest to reconcile against; without it only mtimes are compared
+    manifest: Option<Arc<LmdbManifest>>,
 }
 
 impl CompensationScanner {
@@ -46,25 +52,104 @@ impl CompensationScanner {
     pub fn new(root: PathBuf, last_scan: SystemTime) -> Self {
         Self {
             config: ScanConfig {
+                ignore: IgnoreMatcher::new().with_root(&root),
                 root,
                 last_scan,
-                ..Default::default()
+                max_depth: 50,
             },
+            manifest: None,
         }
     }
 
+    /// Reconcile against `manifest` as well as `last_scan`
+    pub fn with_manifest(mut self, manifest: Arc<LmdbManifest>) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
     /// Check if path should be ignored
     fn should_ignore(&self, path: &Path) -> bool {
         self.config.ignore.should_ignore(path)
     }
 
-    /// Scan directory and emit events for changed files
+    /// Manifest key of a path under the root, as the ingest handler stores it
+    fn manifest_key(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.config.root)
+            .ok()
+            .map(vrift_ipc::path_key_for)
+    }
+
+    /// Scan directory and emit events for changed files, followed by
+    /// removals for entries whose file was deleted
     pub fn scan(&self) -> Vec<IngestEvent> {
         let mut events = Vec::new();
         self.scan_dir(&self.config.root, 0, &mut events);
+        self.scan_removed(&mut events);
         events
     }
 
+    /// Whether the path at `metadata` differs from what the manifest holds
+    fn is_changed(&self, path: &Path, metadata: &fs::Metadata) -> bool {
+        let mtime = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        if mtime > self.config.last_scan {
+            return true;
+        }
+        let Some(manifest) = &self.manifest else {
+            return false;
+        };
+        let Some(key) = self.manifest_key(path) else {
+            return false;
+        };
+        match manifest.get(&key) {
+            Ok(Some(entry)) => differs(&entry, metadata),
+            Ok(None) => true,
+            Err(e) => {
+                warn!(path = %key, error = %e, "Compensation: manifest lookup failed");
+                false
+            }
+        }
+    }
+
+    /// Emit removals for disk-ingested entries whose path no longer exists.
+    /// Entries created through the shim or by a phantom ingest have nothing
+    /// on disk behind them and are left alone.
+    fn scan_removed(&self, events: &mut Vec<IngestEvent>) {
+        use std::os::unix::ffi::OsStrExt;
+
+        let Some(manifest) = &self.manifest else {
+            return;
+        };
+        let entries = match manifest.iter() {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(error = %e, "Compensation: failed to list manifest");
+                return;
+            }
+        };
+
+        for (key, entry) in entries {
+            if !entry.is_from_disk() {
+                continue;
+            }
+            let decoded = vrift_ipc::decode_path_key(&key);
+            let rel = Path::new(std::ffi::OsStr::from_bytes(&decoded));
+            let Ok(rel) = rel.strip_prefix("/") else {
+                continue;
+            };
+            let path = self.config.root.join(rel);
+            if self.should_ignore(&path) {
+                continue;
+            }
+            match fs::symlink_metadata(&path) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    debug!(path = %path.display(), "Compensation: removed while stopped");
+                    events.push(IngestEvent::Removed { path });
+                }
+                _ => {}
+            }
+        }
+    }
+
     /// Recursive directory scan
     fn scan_dir(&self, dir: &Path, depth: usize, events: &mut Vec<IngestEvent>) {
         if depth > self.config.max_depth {
@@ -91,10 +176,9 @@ impl CompensationScanner {
                 Err(_) => continue,
             };
 
-            let mtime = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-
-            // Check if file was modified after last scan
-            if mtime > self.config.last_scan {
+            // Check if file was modified after last scan or disagrees with
+            // its manifest entry
+            if self.is_changed(&path, &metadata) {
                 if metadata.is_dir() {
                     debug!(path = %path.display(), "Compensation: new directory");
                     events.push(IngestEvent::DirCreated { path: path.clone() });
@@ -119,15 +203,30 @@ impl CompensationScanner {
     }
 }
 
+/// Whether an entry no longer describes the path at `metadata`. Only the
+/// mtime of entries ingested from disk is comparable: other writers record
+/// it in their own units.
+fn differs(entry: &ManifestEntry, metadata: &fs::Metadata) -> bool {
+    let vnode = &entry.vnode;
+    if vnode.is_dir() != metadata.is_dir() || vnode.is_symlink() != metadata.is_symlink() {
+        return true;
+    }
+    if !metadata.is_file() {
+        return false;
+    }
+    vnode.size != metadata.len() || (entry.is_from_disk() && vnode.mtime != metadata.mtime() as u64)
+}
+
 /// Run compensation scan and send events to channel
 pub async fn run_compensation_scan(
     root: PathBuf,
     last_scan: SystemTime,
+    manifest: Arc<LmdbManifest>,
     tx: mpsc::Sender<IngestEvent>,
 ) -> usize {
     info!(root = %root.display(), "Starting compensation scan");
 
-    let scanner = CompensationScanner::new(root, last_scan);
+    let scanner = CompensationScanner::new(root, last_scan).with_manifest(manifest);
     let events = scanner.scan();
     let count = events.len();
 
@@ -179,7 +278,7 @@ mod tests {
         // Scanner with custom ignore patterns
         let mut scanner = CompensationScanner::new(root, SystemTime::UNIX_EPOCH);
         scanner.config.ignore =
-            crate::ignore::IgnoreMatcher::with_patterns(&["custom".to_string()]);
+            vrift_config::ignore::IgnoreMatcher::with_patterns(&["custom".to_string()]);
         let events = scanner.scan();
 
         // Should include normal.txt but not custom/
@@ -194,4 +293,57 @@ mod tests {
             }
         }));
     }
+
+    #[test]
+    fn test_scan_reconciles_with_manifest() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let db = tempdir().unwrap();
+        let manifest = Arc::new(LmdbManifest::open(db.path().join("manifest.lmdb")).unwrap());
+        let tier = vrift_manifest::lmdb::AssetTier::Tier2Mutable;
+        let file_vnode = |meta: &fs::Metadata, size: u64| vrift_ipc::VnodeEntry {
+            content_hash: [0u8; 32],
+            size,
+            mtime: meta.mtime() as u64,
+            mode: meta.mode(),
+            flags: 0,
+            _pad: 0,
+        };
+
+        fs::write(root.join("kept.txt"), "kept").unwrap();
+        let meta = fs::metadata(root.join("kept.txt")).unwrap();
+        manifest.insert_from_disk("/kept.txt", file_vnode(&meta, meta.len()), tier, &[], None);
+
+        fs::write(root.join("resized.txt"), "grew while stopped").unwrap();
+        let meta = fs::metadata(root.join("resized.txt")).unwrap();
+        manifest.insert_from_disk("/resized.txt", file_vnode(&meta, 1), tier, &[], None);
+
+        fs::write(root.join("untracked.txt"), "new").unwrap();
+
+        // Deleted while stopped, and an entry that only ever lived in the manifest
+        manifest.insert_from_disk("/gone.txt", file_vnode(&meta, 4), tier, &[], None);
+        manifest.insert("/virtual.txt", file_vnode(&meta, 4), tier);
+
+        // Everything on disk is older than the last scan
+        let last_scan = SystemTime::now() + std::time::Duration::from_secs(60);
+        let scanner = CompensationScanner::new(root, last_scan).with_manifest(manifest);
+        let events = scanner.scan();
+
+        let changed = |name: &str| {
+            events
+                .iter()
+                .any(|e| matches!(e, IngestEvent::FileChanged { path } if path.ends_with(name)))
+        };
+        let removed = |name: &str| {
+            events
+                .iter()
+                .any(|e| matches!(e, IngestEvent::Removed { path } if path.ends_with(name)))
+        };
+        assert!(changed("resized.txt"));
+        assert!(changed("untracked.txt"));
+        assert!(!changed("kept.txt"));
+        assert!(removed("gone.txt"));
+        assert!(!removed("virtual.txt"));
+        assert_eq!(events.len(), 3);
+    }
 }
@@ -3,61 +3,173 @@
 //! Uses IpcHeader frame protocol for all IPC communication.
 
 use crate::commands::CommandHandler;
-use crate::vdir::VDir;
 use crate::ProjectConfig;
 use anyhow::Result;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, Mutex, RwLock, Semaphore};
+use tokio::task::JoinSet;
 use tracing::{debug, error, info, warn};
+use vrift_ipc::peer::{PeerCredentials, PeerPolicy};
 use vrift_ipc::{IpcHeader, VeloError, VeloRequest, VeloResponse};
 
-/// Run the UDS listener loop
-pub async fn run_listener(
-    config: ProjectConfig,
-    vdir: VDir,
-    manifest: std::sync::Arc<vrift_manifest::lmdb::LmdbManifest>,
-) -> Result<()> {
-    // Remove existing socket if present
-    if config.socket_path.exists() {
-        std::fs::remove_file(&config.socket_path)?;
-    }
+/// Requests one connection may have running at once. Past this the
+/// connection isn't read until one finishes, so a client pipelining
+/// faster than vdir_d answers is held back instead of queueing tasks.
+const MAX_IN_FLIGHT: usize = 64;
+
+/// Bind the project socket, taking over a stale one left by a crashed vdir_d
+pub fn bind(config: &ProjectConfig) -> Result<UnixListener> {
+    // Refuse if another vdir_d still answers on it
+    vrift_ipc::reclaim_socket(&config.socket_path)?;
 
     let listener = UnixListener::bind(&config.socket_path)?;
     info!(socket = %config.socket_path.display(), "Listening for connections");
+    Ok(listener)
+}
 
-    let handler = Arc::new(RwLock::new(CommandHandler::new(
-        config.clone(),
-        vdir,
-        manifest,
-    )));
+/// Accept clients until `shutdown` flips to true, then drain.
+///
+/// Clients `peers` refuses get a PermissionDenied error for every request
+/// but Handshake.
+///
+/// Draining stops accepting, lets every connection finish the request it is
+/// in the middle of (reingests included) and closes idle ones. Connections
+/// still busy after `drain_timeout` are aborted.
+pub async fn run_listener(
+    listener: UnixListener,
+    handler: Arc<RwLock<CommandHandler>>,
+    peers: PeerPolicy,
+    shutdown: watch::Receiver<bool>,
+    drain_timeout: Duration,
+) -> Result<()> {
+    let mut clients = JoinSet::new();
+    let mut stop = shutdown.clone();
+    // Reported through the project's Metrics
+    let connections = handler.read().await.connections();
 
     loop {
-        match listener.accept().await {
-            Ok((stream, _addr)) => {
-                let handler = Arc::clone(&handler);
-                tokio::spawn(async move {
-                    if let Err(e) = handle_client(stream, handler).await {
-                        warn!(error = %e, "Client handler error");
-                    }
-                });
-            }
-            Err(e) => {
-                error!(error = %e, "Accept failed");
-            }
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _addr)) => {
+                    let handler = Arc::clone(&handler);
+                    let shutdown = shutdown.clone();
+                    let access = peer_access(&stream, &peers);
+                    let connections = Arc::clone(&connections);
+                    clients.spawn(async move {
+                        connections.fetch_add(1, Ordering::Relaxed);
+                        if let Err(e) = handle_client(stream, handler, access, shutdown).await {
+                            warn!(error = %e, "Client handler error");
+                        }
+                        connections.fetch_sub(1, Ordering::Relaxed);
+                    });
+                }
+                Err(e) => {
+                    error!(error = %e, "Accept failed");
+                }
+            },
+            // Reap finished clients so the set doesn't grow without bound
+            Some(_) = clients.join_next(), if !clients.is_empty() => {}
+            _ = stop.wait_for(|stop| *stop) => break,
         }
     }
+
+    drop(listener);
+    info!(
+        clients = clients.len(),
+        "Stopped accepting, draining connections"
+    );
+
+    let drained = tokio::time::timeout(drain_timeout, async {
+        while clients.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            clients = clients.len(),
+            timeout_ms = drain_timeout.as_millis() as u64,
+            "Drain timed out, aborting remaining connections"
+        );
+        clients.shutdown().await;
+    }
+    Ok(())
+}
+
+fn peer_access(stream: &UnixStream, peers: &PeerPolicy) -> Result<(), VeloError> {
+    use std::os::unix::io::AsRawFd;
+    let creds = PeerCredentials::from_fd(stream.as_raw_fd());
+    let access = peers.check(creds.as_ref());
+    if let Err(e) = &access {
+        warn!(peer = ?creds, error = %e.message, "Refusing peer");
+    }
+    access
 }
 
 /// Handle a single client connection using IpcHeader frame protocol
-async fn handle_client(mut stream: UnixStream, handler: Arc<RwLock<CommandHandler>>) -> Result<()> {
+///
+/// Requests are pipelined: each one runs as its own task as soon as it has
+/// been read, and its response goes out tagged with its seq_id when it is
+/// done, so responses can come back in a different order than requests.
+/// At most MAX_IN_FLIGHT run at once.
+///
+/// Shutdown is only honoured between requests: once a header has been read,
+/// the request runs to completion and its response is sent.
+async fn handle_client(
+    stream: UnixStream,
+    handler: Arc<RwLock<CommandHandler>>,
+    access: Result<(), VeloError>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<()> {
     debug!("New client connected");
 
+    let (reader, writer) = stream.into_split();
+    let writer = Arc::new(Mutex::new(writer));
+    let mut in_flight = JoinSet::new();
+    let read = read_requests(reader, &writer, handler, access, shutdown, &mut in_flight).await;
+
+    // Let requests already read finish and answer
+    let mut answered = Ok(());
+    while let Some(done) = in_flight.join_next().await {
+        if let Ok(Err(e)) = done {
+            answered = Err(e);
+        }
+    }
+    read.and(answered)
+}
+
+/// Read request frames until EOF, shutdown or a bad frame, spawning a
+/// task per request into `in_flight`
+async fn read_requests(
+    mut reader: OwnedReadHalf,
+    writer: &Arc<Mutex<OwnedWriteHalf>>,
+    handler: Arc<RwLock<CommandHandler>>,
+    mut access: Result<(), VeloError>,
+    mut shutdown: watch::Receiver<bool>,
+    in_flight: &mut JoinSet<Result<()>>,
+) -> Result<()> {
+    let slots = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
     loop {
+        // A failed response write means the client is gone
+        while let Some(done) = in_flight.try_join_next() {
+            if let Ok(Err(e)) = done {
+                return Err(e);
+            }
+        }
+
         // Read IpcHeader (12 bytes)
         let mut header_buf = [0u8; IpcHeader::SIZE];
-        match stream.read_exact(&mut header_buf).await {
+        let read = tokio::select! {
+            read = reader.read_exact(&mut header_buf) => read,
+            _ = shutdown.wait_for(|stop| *stop) => {
+                debug!("Closing idle client for shutdown");
+                return Ok(());
+            }
+        };
+        match read {
             Ok(_) => {}
             Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
                 debug!("Client disconnected");
@@ -109,7 +221,7 @@ async fn handle_client(mut stream: UnixStream, handler: Arc<RwLock<CommandHandle
         // Read payload
         let mut payload = vec![0u8; header.length as usize];
         if !payload.is_empty() {
-            stream.read_exact(&mut payload).await?;
+            reader.read_exact(&mut payload).await?;
         }
 
         // Deserialize request
@@ -122,27 +234,77 @@ async fn handle_client(mut stream: UnixStream, handler: Arc<RwLock<CommandHandle
                         "Deserialize error: {}",
                         e
                     )));
-                    send_response(&mut stream, &response, header.seq_id).await?;
+                    send_response(writer, &response, header.seq_id).await?;
                     continue;
                 }
             };
 
-        debug!(?request, "Received request");
+        debug!(?request, seq_id = header.seq_id, "Received request");
 
-        // Handle request
-        let response = {
-            let mut h = handler.write().await;
-            h.handle_request(request).await
-        };
+        // Replies to an incompatible client would be misread; only the
+        // handshake (which says so) gets a real answer
+        if access.is_ok() {
+            if let Some(e) = request.handshake_error() {
+                access = Err(e);
+            }
+        }
+        if let Err(e) = &access {
+            if !request.is_handshake() {
+                send_response(writer, &VeloResponse::Error(e.clone()), header.seq_id).await?;
+                continue;
+            }
+        }
+
+        let slot = Arc::clone(&slots)
+            .acquire_owned()
+            .await
+            .expect("semaphore never closed");
+        let handler = Arc::clone(&handler);
+        let writer = Arc::clone(writer);
+        in_flight.spawn(async move {
+            let _slot = slot;
+            let response = dispatch(&handler, request).await;
+            // Send response with matching seq_id
+            send_response(&writer, &response, header.seq_id).await
+        });
+    }
+}
 
-        // Send response with matching seq_id
-        send_response(&mut stream, &response, header.seq_id).await?;
+/// Run one request: lookups share the handler lock, everything else
+/// takes it exclusively. The hot-cache work lookups queue is applied
+/// right away if the lock happens to be free, else by whoever takes it
+/// exclusively next. `ProjectOpen` starts another project and holds no
+/// lock of this one's while it does.
+pub async fn dispatch(handler: &RwLock<CommandHandler>, request: VeloRequest) -> VeloResponse {
+    if let VeloRequest::ProjectOpen { project_root } = &request {
+        let registry = handler.read().await.registry();
+        return match registry {
+            Some(registry) => registry.handle_open(project_root).await,
+            None => VeloResponse::Error(VeloError::internal(
+                "This vdir_d doesn't open other projects",
+            )),
+        };
+    }
+    let (lookup, touched) = {
+        let handler = handler.read().await;
+        (handler.handle_lookup(&request), handler.has_touches())
+    };
+    match lookup {
+        Some(response) => {
+            if touched {
+                if let Ok(mut handler) = handler.try_write() {
+                    handler.apply_touches();
+                }
+            }
+            response
+        }
+        None => handler.write().await.handle_request(request).await,
     }
 }
 
 /// Send response using IpcHeader frame protocol
 async fn send_response(
-    stream: &mut UnixStream,
+    writer: &Mutex<OwnedWriteHalf>,
     response: &VeloResponse,
     seq_id: u32,
 ) -> Result<()> {
@@ -158,6 +320,8 @@ async fn send_response(
 
     let header = IpcHeader::new_response(payload.len() as u32, seq_id);
 
+    // One frame at a time: responses finishing together mustn't interleave
+    let mut stream = writer.lock().await;
     stream.write_all(&header.to_bytes()).await?;
     stream.write_all(&payload).await?;
     Ok(())
@@ -187,4 +351,154 @@ mod tests {
         assert!(result.is_ok());
         handle.await.unwrap().unwrap();
     }
+
+    fn test_handler(root: &std::path::Path) -> (ProjectConfig, Arc<RwLock<CommandHandler>>) {
+        let mut config = ProjectConfig::from_project_root(root.to_path_buf());
+        config.socket_path = root.join("vdird.sock");
+        let vdir = crate::vdir::VDir::create_or_open(&root.join("test.vdir")).unwrap();
+        let manifest =
+            Arc::new(vrift_manifest::lmdb::LmdbManifest::open(root.join("manifest.lmdb")).unwrap());
+        let journal =
+            crate::journal::ReingestJournal::open(&crate::journal::ReingestJournal::path_for(root))
+                .unwrap();
+        let handler = Arc::new(RwLock::new(CommandHandler::new(
+            config.clone(),
+            vdir,
+            manifest,
+            journal,
+        )));
+        (config, handler)
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_requests_answered_by_seq_id() {
+        let temp = tempdir().unwrap();
+        let (config, handler) = test_handler(temp.path());
+        let listener = bind(&config).unwrap();
+        let (_tx, rx) = watch::channel(false);
+        tokio::spawn(run_listener(
+            listener,
+            handler,
+            PeerPolicy::for_current_user(&[]),
+            rx,
+            Duration::from_secs(5),
+        ));
+
+        let stream = UnixStream::connect(&config.socket_path).await.unwrap();
+        let mux = vrift_ipc::mux::AsyncMux::from_stream(stream);
+        let requests = [
+            VeloRequest::ManifestGet {
+                path: "/missing".to_string(),
+            },
+            VeloRequest::Status,
+            VeloRequest::ManifestGet {
+                path: "/also/missing".to_string(),
+            },
+        ];
+        let responses = mux.call_all(&requests).await.unwrap();
+
+        assert!(matches!(
+            responses[0],
+            VeloResponse::ManifestAck { entry: None, .. }
+        ));
+        assert!(matches!(responses[1], VeloResponse::StatusAck { .. }));
+        assert!(matches!(
+            responses[2],
+            VeloResponse::ManifestAck { entry: None, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pipelining_past_in_flight_limit() {
+        let temp = tempdir().unwrap();
+        let (config, handler) = test_handler(temp.path());
+        let listener = bind(&config).unwrap();
+        let (_tx, rx) = watch::channel(false);
+        tokio::spawn(run_listener(
+            listener,
+            handler,
+            PeerPolicy::for_current_user(&[]),
+            rx,
+            Duration::from_secs(5),
+        ));
+
+        let stream = UnixStream::connect(&config.socket_path).await.unwrap();
+        let mux = vrift_ipc::mux::AsyncMux::from_stream(stream);
+        let requests: Vec<_> = (0..MAX_IN_FLIGHT * 3)
+            .map(|i| VeloRequest::ManifestGet {
+                path: format!("/missing/{}", i),
+            })
+            .collect();
+        let responses = tokio::time::timeout(Duration::from_secs(10), mux.call_all(&requests))
+            .await
+            .expect("held-back requests still get answers")
+            .unwrap();
+        assert_eq!(responses.len(), requests.len());
+        assert!(responses
+            .iter()
+            .all(|r| matches!(r, VeloResponse::ManifestAck { entry: None, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_read_only_requests_share_the_handler_lock() {
+        let temp = tempdir().unwrap();
+        let (_config, handler) = test_handler(temp.path());
+
+        // Would wait forever for the write lock
+        let _reader = handler.read().await;
+        let requests = [
+            VeloRequest::ManifestListDirPage {
+                path: "/".to_string(),
+                after: String::new(),
+                limit: 10,
+            },
+            VeloRequest::BuildCacheKey {
+                inputs: Vec::new(),
+                exclude: Vec::new(),
+                command: vec!["cargo".to_string()],
+            },
+        ];
+        for request in requests {
+            let response =
+                tokio::time::timeout(Duration::from_secs(2), dispatch(&handler, request))
+                    .await
+                    .expect("dispatched under the shared lock");
+            assert!(
+                !matches!(response, VeloResponse::Error(_)),
+                "{:?}",
+                response
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_idle_clients_and_stops_accepting() {
+        let temp = tempdir().unwrap();
+        let (config, handler) = test_handler(temp.path());
+
+        let listener = bind(&config).unwrap();
+        let (tx, rx) = watch::channel(false);
+        let server = tokio::spawn(run_listener(
+            listener,
+            handler,
+            PeerPolicy::for_current_user(&[]),
+            rx,
+            Duration::from_secs(5),
+        ));
+
+        let mut idle = UnixStream::connect(&config.socket_path).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tx.send(true).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(2), server)
+            .await
+            .expect("listener should drain promptly")
+            .unwrap()
+            .unwrap();
+
+        // The idle connection was closed rather than waited on
+        let mut buf = [0u8; 1];
+        assert_eq!(idle.read(&mut buf).await.unwrap(), 0);
+        assert!(UnixStream::connect(&config.socket_path).await.is_err());
+    }
 }
@@ -10,6 +10,10 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use rkyv::Archive;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
+use vrift_ipc::heatmap::{heatmap_path, Heatmap};
+use vrift_manifest::lmdb::LmdbManifest;
+
+use crate::vdir::{flags_from_vnode, fnv1a_hash, VDir, VDirEntry};
 
 /// Daemon persistent state
 #[derive(
@@ -23,6 +27,10 @@ pub struct DaemonState {
     pub manifest_entry_count: u64,
     /// Last commit timestamp
     pub last_commit_secs: u64,
+    /// VDir seqlock generation observed at last save (0 = never recorded)
+    pub vdir_generation: u64,
+    /// VDir entry count observed at last save
+    pub vdir_entry_count: u64,
 }
 
 impl DaemonState {
@@ -95,9 +103,161 @@ impl DaemonState {
     }
 }
 
+/// Outcome of validating the VDir left behind by a previous run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VDirWarmStart {
+    /// Generation matches the last save: reused untouched
+    Reused,
+    /// Header valid but written after the last save: kept, missing manifest entries merged in
+    Merged(usize),
+    /// New or corrupt table: rebuilt from the manifest
+    Rebuilt(usize),
+}
+
+impl DaemonState {
+    /// Record the VDir generation handshake for the next startup
+    pub fn record_vdir(&mut self, vdir: &VDir) {
+        self.vdir_generation = vdir.generation();
+        self.vdir_entry_count = vdir.entry_count() as u64;
+    }
+}
+
+/// Which manifest entries go into the VDir first, and how many
+#[derive(Debug, Default)]
+pub struct WarmStartPolicy {
+    /// Access counts from `.vrift/heatmap.tsv`; hotter entries are inserted first
+    pub heat: Heatmap,
+    /// Stop once the VDir holds this many entries (0 = no cap). Entries left
+    /// out are still served, over IPC instead of from the mmap, and the
+    /// same cap drives [`crate::admission::HotCache`] afterwards.
+    pub max_entries: usize,
+}
+
+/// Default cap: the default table at its resize threshold, so manifest
+/// entries alone never grow the mmap
+pub const DEFAULT_VDIR_MAX_ENTRIES: usize = crate::vdir::VDIR_DEFAULT_CAPACITY * 3 / 4;
+
+impl WarmStartPolicy {
+    /// Heatmap from the project, cap from `VRIFT_VDIR_MAX_ENTRIES`
+    /// (default [`DEFAULT_VDIR_MAX_ENTRIES`], 0 = unlimited)
+    pub fn load(project_root: &Path) -> Self {
+        let path = heatmap_path(project_root);
+        let heat = Heatmap::load(&path).unwrap_or_else(|e| {
+            warn!(path = %path.display(), error = %e, "Failed to read heatmap, ignoring");
+            Heatmap::new()
+        });
+        let max_entries = std::env::var("VRIFT_VDIR_MAX_ENTRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_VDIR_MAX_ENTRIES);
+        Self { heat, max_entries }
+    }
+}
+
+/// Validate the VDir against the recorded generation and reuse it when possible.
+///
+/// The mmap file outlives the daemon, so a clean restart can serve stat hits
+/// immediately. Only a new or corrupt table is repopulated from the manifest.
+pub fn warm_start_vdir(
+    vdir: &mut VDir,
+    manifest: &LmdbManifest,
+    state: &DaemonState,
+) -> anyhow::Result<VDirWarmStart> {
+    warm_start_vdir_with(vdir, manifest, state, &WarmStartPolicy::default())
+}
+
+/// [`warm_start_vdir`], populating in `policy` order up to its cap
+pub fn warm_start_vdir_with(
+    vdir: &mut VDir,
+    manifest: &LmdbManifest,
+    state: &DaemonState,
+    policy: &WarmStartPolicy,
+) -> anyhow::Result<VDirWarmStart> {
+    let generation = vdir.generation();
+    let corrupt = !vdir.header_crc_valid() || generation & 1 != 0;
+
+    if !corrupt
+        && state.vdir_generation != 0
+        && generation == state.vdir_generation
+        && vdir.entry_count() as u64 == state.vdir_entry_count
+    {
+        return Ok(VDirWarmStart::Reused);
+    }
+
+    let rebuild = corrupt || vdir.entry_count() == 0;
+    if rebuild {
+        vdir.clear()?;
+    }
+
+    let mut entries: Vec<_> = manifest
+        .iter()?
+        .into_iter()
+        .filter(|(_, entry)| !entry.stale)
+        .collect();
+    if !policy.heat.is_empty() {
+        // Stable sort: equally hot entries keep manifest order
+        entries.sort_by_cached_key(|(path, _)| std::cmp::Reverse(policy.heat.score(path)));
+    }
+
+    // One transaction allocates every live path its inode up front
+    let live = entries
+        .iter()
+        .filter(|(_, entry)| !entry.vnode.is_whiteout())
+        .map(|(path, _)| path.as_str());
+    let mut inos = match manifest.inodes().assign_all(live) {
+        Ok(inos) => inos.into_iter(),
+        Err(e) => {
+            warn!(error = %e, "Inode allocation failed; shims derive inodes from paths");
+            Vec::new().into_iter()
+        }
+    };
+
+    let mut inserted = 0;
+    for (path, entry) in entries {
+        let ino = if entry.vnode.is_whiteout() {
+            0
+        } else {
+            inos.next().unwrap_or(0)
+        };
+        if policy.max_entries != 0 && vdir.entry_count() as usize >= policy.max_entries {
+            debug!(
+                cap = policy.max_entries,
+                "VDir entry cap reached; remaining entries served over IPC"
+            );
+            break;
+        }
+        let vnode = &entry.vnode;
+        let vdir_entry = VDirEntry {
+            path_hash: fnv1a_hash(&path),
+            cas_hash: vnode.content_hash,
+            size: vnode.size,
+            mtime_sec: vnode.mtime as i64,
+            mtime_nsec: 0,
+            mode: vnode.mode,
+            flags: flags_from_vnode(vnode.flags),
+            _pad: [0; 3],
+            btime_sec: entry.btime().unwrap_or(0) as i64,
+            ino,
+        };
+        // Entries already in the VDir may be newer COW results: never overwrite them
+        if vdir.insert_if_absent(vdir_entry)? {
+            inserted += 1;
+        }
+    }
+    vdir.flush()?;
+
+    Ok(if rebuild {
+        VDirWarmStart::Rebuilt(inserted)
+    } else {
+        VDirWarmStart::Merged(inserted)
+    })
+}
+
 /// Clean orphan temp files from staging directory
 ///
 /// Removes files older than `max_age_secs` to reclaim space after crashes.
+/// Per-session subdirectories (`staging/<session_id>/`) are swept the same
+/// way and removed once empty, covering sessions that ended without cleanup.
 /// Returns the number of files cleaned.
 pub fn cleanup_orphan_staging(staging_base: &Path, max_age_secs: u64) -> io::Result<usize> {
     use std::time::Duration;
@@ -110,9 +270,35 @@ pub fn cleanup_orphan_staging(staging_base: &Path, max_age_secs: u64) -> io::Res
         .checked_sub(Duration::from_secs(max_age_secs))
         .unwrap_or(UNIX_EPOCH);
 
+    let mut cleaned = sweep_staging_dir(staging_base, threshold)?;
+
+    for entry in fs::read_dir(staging_base)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        // Only drop session dirs that have been idle past the threshold, so a
+        // live session's freshly created dir isn't pulled out from under it.
+        // Checked before sweeping: removing files bumps the dir's mtime.
+        let idle = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .map(|t| t < threshold)
+            .unwrap_or(false);
+        cleaned += sweep_staging_dir(&path, threshold).unwrap_or(0);
+        if idle && fs::remove_dir(&path).is_ok() {
+            info!(path = %path.display(), "Removed stale session staging dir");
+        }
+    }
+
+    Ok(cleaned)
+}
+
+/// Remove files older than `threshold` directly inside `dir`
+fn sweep_staging_dir(dir: &Path, threshold: SystemTime) -> io::Result<usize> {
     let mut cleaned = 0;
 
-    for entry in fs::read_dir(staging_base)? {
+    for entry in fs::read_dir(dir)? {
         let entry = match entry {
             Ok(e) => e,
             Err(_) => continue,
@@ -182,4 +368,120 @@ mod tests {
         let state = DaemonState::load(&path);
         assert_eq!(state.last_scan_secs, 0);
     }
+
+    #[test]
+    fn test_cleanup_sweeps_session_dirs() {
+        let dir = tempdir().unwrap();
+        let staging = dir.path().join("staging");
+        let stale = staging.join("old-session");
+        let live = staging.join("live-session");
+        fs::create_dir_all(&stale).unwrap();
+        fs::create_dir_all(&live).unwrap();
+        fs::write(stale.join("vrift_cow_1.tmp"), b"x").unwrap();
+        fs::write(live.join("vrift_cow_2.tmp"), b"y").unwrap();
+
+        let old = SystemTime::now() - std::time::Duration::from_secs(7200);
+        let times = fs::FileTimes::new().set_modified(old).set_accessed(old);
+        fs::File::options()
+            .write(true)
+            .open(stale.join("vrift_cow_1.tmp"))
+            .unwrap()
+            .set_times(times)
+            .unwrap();
+        fs::File::open(&stale).unwrap().set_times(times).unwrap();
+
+        assert_eq!(cleanup_orphan_staging(&staging, 3600).unwrap(), 1);
+        assert!(!stale.exists());
+        assert!(live.join("vrift_cow_2.tmp").exists());
+    }
+
+    fn manifest_with(dir: &Path, paths: &[&str]) -> LmdbManifest {
+        let manifest = LmdbManifest::open(dir.join("manifest.lmdb")).unwrap();
+        for (i, p) in paths.iter().enumerate() {
+            let vnode = vrift_manifest::VnodeEntry::new_file([i as u8 + 1; 32], 10, 0, 0o644);
+            manifest.insert(p, vnode, vrift_manifest::AssetTier::Tier2Mutable);
+        }
+        manifest.commit().unwrap();
+        manifest
+    }
+
+    #[test]
+    fn test_warm_start_rebuilds_empty_vdir() {
+        let dir = tempdir().unwrap();
+        let manifest = manifest_with(dir.path(), &["/a.rs", "/b.rs"]);
+        let mut vdir = VDir::create_or_open(&dir.path().join("test.vdir")).unwrap();
+
+        let outcome = warm_start_vdir(&mut vdir, &manifest, &DaemonState::default()).unwrap();
+        assert_eq!(outcome, VDirWarmStart::Rebuilt(2));
+        assert!(vdir.lookup(fnv1a_hash("/a.rs")).is_some());
+    }
+
+    #[test]
+    fn test_warm_start_reuses_matching_generation() {
+        let dir = tempdir().unwrap();
+        let manifest = manifest_with(dir.path(), &["/a.rs"]);
+        let vdir_path = dir.path().join("test.vdir");
+
+        let mut state = DaemonState::default();
+        {
+            let mut vdir = VDir::create_or_open(&vdir_path).unwrap();
+            warm_start_vdir(&mut vdir, &manifest, &state).unwrap();
+            state.record_vdir(&vdir);
+        }
+
+        let mut vdir = VDir::create_or_open(&vdir_path).unwrap();
+        let outcome = warm_start_vdir(&mut vdir, &manifest, &state).unwrap();
+        assert_eq!(outcome, VDirWarmStart::Reused);
+    }
+
+    #[test]
+    fn test_warm_start_keeps_newer_overlay_entries() {
+        let dir = tempdir().unwrap();
+        let manifest = manifest_with(dir.path(), &["/a.rs", "/b.rs"]);
+        let vdir_path = dir.path().join("test.vdir");
+
+        let mut state = DaemonState::default();
+        {
+            let mut vdir = VDir::create_or_open(&vdir_path).unwrap();
+            vdir.upsert(VDirEntry {
+                path_hash: fnv1a_hash("/a.rs"),
+                size: 999,
+                ..Default::default()
+            })
+            .unwrap();
+            state.record_vdir(&vdir);
+            // Written after the last recorded save (e.g. crash before shutdown)
+            vdir.upsert(VDirEntry {
+                path_hash: fnv1a_hash("/c.rs"),
+                size: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+
+        let mut vdir = VDir::create_or_open(&vdir_path).unwrap();
+        let outcome = warm_start_vdir(&mut vdir, &manifest, &state).unwrap();
+        assert_eq!(outcome, VDirWarmStart::Merged(1));
+        assert_eq!(vdir.lookup(fnv1a_hash("/a.rs")).unwrap().size, 999);
+        assert!(vdir.lookup(fnv1a_hash("/b.rs")).is_some());
+        assert!(vdir.lookup(fnv1a_hash("/c.rs")).is_some());
+    }
+
+    #[test]
+    fn test_warm_start_capped_keeps_hottest_entries() {
+        let dir = tempdir().unwrap();
+        let manifest = manifest_with(dir.path(), &["/cold/a.rs", "/hot/b.rs", "/hot/c.rs"]);
+        let mut vdir = VDir::create_or_open(&dir.path().join("test.vdir")).unwrap();
+
+        let policy = WarmStartPolicy {
+            heat: Heatmap::parse_tsv("/hot\tstat\t50\n/cold\tstat\t1\n"),
+            max_entries: 2,
+        };
+        let outcome =
+            warm_start_vdir_with(&mut vdir, &manifest, &DaemonState::default(), &policy).unwrap();
+        assert_eq!(outcome, VDirWarmStart::Rebuilt(2));
+        assert!(vdir.lookup(fnv1a_hash("/hot/b.rs")).is_some());
+        assert!(vdir.lookup(fnv1a_hash("/hot/c.rs")).is_some());
+        assert!(vdir.lookup(fnv1a_hash("/cold/a.rs")).is_none());
+    }
 }
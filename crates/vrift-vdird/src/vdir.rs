@@ -20,7 +20,7 @@ pub struct VDir {
 impl VDir {
     /// Create or open existing VDir mmap file
     pub fn create_or_open(path: &Path) -> Result<Self> {
-        let capacity = VDIR_DEFAULT_CAPACITY;
+        let mut capacity = VDIR_DEFAULT_CAPACITY;
         let file_size = VDIR_HEADER_SIZE + (capacity * VDIR_ENTRY_SIZE);
 
         let file = OpenOptions::new()
@@ -79,6 +79,13 @@ impl VDir {
                 // Continue anyway, but log warning
             }
 
+            // Reuse a table that was resized by a previous run
+            let stored_capacity = header.table_capacity as usize;
+            let mapped_slots = (mmap.len().saturating_sub(VDIR_HEADER_SIZE)) / VDIR_ENTRY_SIZE;
+            if stored_capacity > 0 && stored_capacity <= mapped_slots {
+                capacity = stored_capacity;
+            }
+
             // Recovery: If generation is odd, previous writer crashed mid-write.
             // Reset to even (generation + 1) so seqlock readers don't spin forever.
             if header.generation & 1 != 0 {
@@ -195,8 +202,6 @@ impl VDir {
     /// Stores current_gen + 1 (even) with Release ordering to signal "data stable".
     /// Also recomputes header CRC.
     pub fn end_write(&mut self) {
-        // Recompute CRC before bumping to even (readers validate CRC after gen check)
-        self.header_mut().crc32 = Self::compute_header_crc(self.header());
         let gen_ptr = &self.header().generation as *const u64;
         let atomic = unsafe { &*(gen_ptr as *const AtomicU64) };
         let current = atomic.load(Ordering::Relaxed);
@@ -205,6 +210,11 @@ impl VDir {
             "end_write called without begin_write (gen={})",
             current
         );
+        // Recompute CRC before bumping to even (readers validate CRC after gen check).
+        // The CRC covers the generation, so hash the value readers will observe.
+        let mut committed = *self.header();
+        committed.generation = current + 1;
+        self.header_mut().crc32 = Self::compute_header_crc(&committed);
         atomic.store(current + 1, Ordering::Release);
     }
 
@@ -286,6 +296,81 @@ impl VDir {
         false
     }
 
+    /// Insert entry only if its path is not already present.
+    /// Returns true if inserted.
+    pub fn insert_if_absent(&mut self, entry: VDirEntry) -> Result<bool> {
+        if self.lookup(entry.path_hash).is_some() {
+            return Ok(false);
+        }
+        self.upsert(entry)?;
+        Ok(true)
+    }
+
+    /// Remove an entry. Returns false if it wasn't present.
+    ///
+    /// Uses backward-shift deletion so linear-probe chains stay unbroken
+    /// without tombstones: later entries of the chain move up into the hole.
+    pub fn remove(&mut self, path_hash: u64) -> bool {
+        let Some(mut hole) = self.find_slot(path_hash) else {
+            return false;
+        };
+        if self.entries()[hole].is_empty() {
+            return false;
+        }
+
+        let cap = self.capacity;
+        self.begin_write();
+        let mut next = (hole + 1) % cap;
+        loop {
+            let entry = self.entries()[next];
+            if entry.is_empty() {
+                break;
+            }
+            // Move up unless its home slot lies after the hole (cyclically)
+            let home = (entry.path_hash as usize) % cap;
+            if (hole + cap - home) % cap < (next + cap - home) % cap {
+                self.entries_mut()[hole] = entry;
+                hole = next;
+            }
+            next = (next + 1) % cap;
+        }
+        self.entries_mut()[hole] = VDirEntry::default();
+        self.header_mut().entry_count -= 1;
+        self.end_write();
+        true
+    }
+
+    /// Drop all entries, keeping the current capacity
+    pub fn clear(&mut self) -> Result<()> {
+        self.begin_write();
+        let offset = self.header().table_offset as usize;
+        unsafe {
+            std::ptr::write_bytes(
+                self.mmap.as_mut_ptr().add(offset),
+                0,
+                self.capacity * VDIR_ENTRY_SIZE,
+            );
+        }
+        self.header_mut().entry_count = 0;
+        self.end_write();
+        self.flush()
+    }
+
+    /// Current seqlock generation
+    pub fn generation(&self) -> u64 {
+        self.header().generation
+    }
+
+    /// Number of occupied slots recorded in the header
+    pub fn entry_count(&self) -> u32 {
+        self.header().entry_count
+    }
+
+    /// True if the stored header CRC matches its contents
+    pub fn header_crc_valid(&self) -> bool {
+        self.header().crc32 == Self::compute_header_crc(self.header())
+    }
+
     /// Flush mmap to disk
     pub fn flush(&self) -> Result<()> {
         self.mmap.flush()?;
@@ -447,6 +532,8 @@ mod tests {
             mode: 0o644,
             flags: 0,
             _pad: [0; 3],
+            btime_sec: 1234500000,
+            ino: 42,
         };
         vdir.upsert(entry).unwrap();
 
@@ -454,6 +541,8 @@ mod tests {
         let found = vdir.lookup(fnv1a_hash("src/main.rs"));
         assert!(found.is_some());
         assert_eq!(found.unwrap().size, 1024);
+        assert_eq!(found.unwrap().btime_sec, 1234500000);
+        assert_eq!(found.unwrap().ino, 42);
     }
 
     #[test]
@@ -556,6 +645,32 @@ mod tests {
         assert_eq!(vdir.header().entry_count, 2);
     }
 
+    #[test]
+    fn test_remove_keeps_probe_chains() {
+        let temp = tempdir().unwrap();
+        let mut vdir = VDir::create_or_open(&temp.path().join("test.vdir")).unwrap();
+        let cap = VDIR_DEFAULT_CAPACITY as u64;
+
+        // a and b share the last slot's home and wrap around; c is homed at 0
+        let (a, b, c) = (cap - 1, 2 * cap - 1, cap);
+        for hash in [a, b, c] {
+            vdir.upsert(VDirEntry {
+                path_hash: hash,
+                size: hash,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+
+        assert!(vdir.remove(a));
+        assert!(!vdir.remove(a));
+        assert!(vdir.lookup(a).is_none());
+        assert_eq!(vdir.lookup(b).unwrap().size, b);
+        assert_eq!(vdir.lookup(c).unwrap().size, c);
+        assert_eq!(vdir.entry_count(), 2);
+        assert!(vdir.header_crc_valid());
+    }
+
     // ==================== Generation Counter ====================
 
     #[test]
@@ -623,6 +738,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_header_crc_valid_after_writes() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("test.vdir");
+
+        let mut vdir = VDir::create_or_open(&path).unwrap();
+        vdir.upsert(VDirEntry {
+            path_hash: fnv1a_hash("a.txt"),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(vdir.header_crc_valid());
+
+        vdir.clear().unwrap();
+        assert!(vdir.header_crc_valid());
+        assert_eq!(vdir.entry_count(), 0);
+        assert!(vdir.lookup(fnv1a_hash("a.txt")).is_none());
+    }
+
     #[test]
     fn test_vdir_header_valid_after_reopen() {
         let temp = tempdir().unwrap();
@@ -681,6 +815,22 @@ mod tests {
         assert_eq!(e.flags & FLAG_SYMLINK, FLAG_SYMLINK);
     }
 
+    #[test]
+    fn test_flags_from_vnode() {
+        use vrift_manifest::{VnodeEntry, VnodeFlags};
+
+        let link = VnodeEntry::new_symlink([0; 32], 4, 0);
+        assert_eq!(flags_from_vnode(link.flags), FLAG_SYMLINK);
+        let dir = VnodeEntry::new_directory(0, 0o755);
+        assert_eq!(flags_from_vnode(dir.flags), FLAG_DIR);
+        let chunked = VnodeFlags::Chunked as u16;
+        assert_eq!(flags_from_vnode(chunked), FLAG_CHUNKED);
+
+        for flags in [link.flags, dir.flags, chunked, 0] {
+            assert_eq!(flags_to_vnode(flags_from_vnode(flags)), flags);
+        }
+    }
+
     // ==================== Stress Tests ====================
 
     #[test]
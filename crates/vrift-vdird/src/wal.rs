@@ -0,0 +1,302 @@
+//! Write-ahead log for manifest writes made over IPC
+//!
+//! A shim's ManifestUpsert, ManifestRemove or ManifestSetXattr (virtual
+//! mkdir, symlink, unlink, rename, setxattr) has nothing on disk behind it
+//! for the watcher to find again, and the manifest keeps it in the
+//! in-memory delta layer until the next periodic commit. Each write is appended here before it is applied,
+//! so a vdir_d that dies in between replays it on the next start. A
+//! successful commit makes the log redundant and truncates it.
+//!
+//! Records are `[len: u32 LE][rkyv WalRecord]`. They are not fsynced: the
+//! log survives a vdir_d crash, not a kernel crash. A torn tail record is
+//! dropped on replay.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tracing::{debug, info, warn};
+use vrift_manifest::lmdb::{AssetTier, LmdbManifest, LmdbResult};
+use vrift_manifest::{xattr, Provenance, VnodeEntry};
+
+/// One logged manifest write
+#[derive(Debug, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+enum WalRecord {
+    Upsert {
+        path: String,
+        entry: VnodeEntry,
+    },
+    Remove {
+        path: String,
+    },
+    /// All of the entry's xattrs, in `vrift_manifest::xattr` encoding
+    Xattrs {
+        path: String,
+        xattrs: Vec<u8>,
+    },
+    /// The entry's creation time (seconds)
+    Btime {
+        path: String,
+        btime: Option<u64>,
+    },
+}
+
+/// Manifest writer that logs each write before applying it
+pub struct ManifestWal {
+    manifest: Arc<LmdbManifest>,
+    /// Held across append + apply, and across commit + truncate, so a
+    /// truncate never drops a record whose write missed the commit
+    file: Mutex<File>,
+}
+
+impl ManifestWal {
+    /// Log location for a project
+    pub fn path_for(project_root: &Path) -> PathBuf {
+        project_root.join(".vrift").join("manifest.wal")
+    }
+
+    /// Open the log at `path`, replaying any records a previous run left
+    /// into `manifest` and committing them. Returns the writer and the
+    /// number of records replayed.
+    pub fn open(path: &Path, manifest: Arc<LmdbManifest>) -> io::Result<(Self, usize)> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let records = decode(&data);
+        for record in &records {
+            match record {
+                WalRecord::Upsert { path, entry } => {
+                    manifest.upsert(path, entry.clone(), AssetTier::Tier2Mutable)
+                }
+                WalRecord::Remove { path } => manifest.remove(path),
+                WalRecord::Xattrs { path, xattrs } => {
+                    let attrs = xattr::decode(xattrs).unwrap_or_default();
+                    manifest
+                        .set_xattrs(path, &attrs)
+                        .map_err(|e| io::Error::other(e.to_string()))?;
+                }
+                WalRecord::Btime { path, btime } => {
+                    manifest
+                        .set_btime(path, *btime)
+                        .map_err(|e| io::Error::other(e.to_string()))?;
+                }
+            }
+        }
+
+        let wal = Self {
+            manifest,
+            file: Mutex::new(file),
+        };
+        if !data.is_empty() {
+            let provenance = Provenance::capture("vdir_d WAL replay", None);
+            wal.commit_with_provenance(&provenance)
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            info!(records = records.len(), "Replayed manifest WAL");
+        }
+        Ok((wal, records.len()))
+    }
+
+    /// Log, then insert `entry` at `path` (keeping the xattrs there)
+    pub fn upsert(&self, path: &str, entry: VnodeEntry) -> io::Result<()> {
+        let mut file = self.lock();
+        append(
+            &mut file,
+            &WalRecord::Upsert {
+                path: path.to_string(),
+                entry: entry.clone(),
+            },
+        )?;
+        self.manifest.upsert(path, entry, AssetTier::Tier2Mutable);
+        Ok(())
+    }
+
+    /// Log, then replace the xattrs of the entry at `path`. False if there
+    /// is no entry.
+    pub fn set_xattrs(&self, path: &str, attrs: &[(String, Vec<u8>)]) -> io::Result<bool> {
+        let mut file = self.lock();
+        append(
+            &mut file,
+            &WalRecord::Xattrs {
+                path: path.to_string(),
+                xattrs: xattr::encode(attrs),
+            },
+        )?;
+        self.manifest
+            .set_xattrs(path, attrs)
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    /// Log, then set the creation time of the entry at `path`. False if
+    /// there is no entry.
+    pub fn set_btime(&self, path: &str, btime: Option<u64>) -> io::Result<bool> {
+        let mut file = self.lock();
+        append(
+            &mut file,
+            &WalRecord::Btime {
+                path: path.to_string(),
+                btime,
+            },
+        )?;
+        self.manifest
+            .set_btime(path, btime)
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    /// Log, then whiteout `path`
+    pub fn remove(&self, path: &str) -> io::Result<()> {
+        let mut file = self.lock();
+        append(
+            &mut file,
+            &WalRecord::Remove {
+                path: path.to_string(),
+            },
+        )?;
+        self.manifest.remove(path);
+        Ok(())
+    }
+
+    /// Commit the manifest and, if that worked, truncate the log
+    pub fn commit_with_provenance(&self, provenance: &Provenance) -> LmdbResult<bool> {
+        let file = self.lock();
+        let committed = self.manifest.commit_with_provenance(provenance)?;
+        if let Err(e) = file.set_len(0) {
+            // Harmless: replaying committed records re-applies the same writes
+            warn!(error = %e, "Failed to truncate manifest WAL");
+        }
+        debug!("Manifest WAL truncated after commit");
+        Ok(committed)
+    }
+
+    /// Drop every record, for when the manifest was replaced wholesale
+    /// (snapshot restore) and older writes must not come back
+    pub fn reset(&self) -> io::Result<()> {
+        self.lock().set_len(0)
+    }
+
+    /// Bytes currently in the log
+    pub fn len(&self) -> u64 {
+        self.lock().metadata().map(|m| m.len()).unwrap_or(0)
+    }
+
+    /// True if nothing is waiting for a commit
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, File> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn append(file: &mut File, record: &WalRecord) -> io::Result<()> {
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(record)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let mut frame = Vec::with_capacity(4 + bytes.len());
+    frame.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&bytes);
+    // One write per record, so a crash tears at most the last one
+    file.write_all(&frame)
+}
+
+fn decode(mut data: &[u8]) -> Vec<WalRecord> {
+    let mut records = Vec::new();
+    while data.len() >= 4 {
+        let len = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let Some(body) = data.get(4..4 + len) else {
+            warn!("Manifest WAL ends in a torn record, dropping it");
+            break;
+        };
+        let mut aligned = rkyv::util::AlignedVec::<16>::with_capacity(len);
+        aligned.extend_from_slice(body);
+        match rkyv::from_bytes::<WalRecord, rkyv::rancor::Error>(&aligned) {
+            Ok(record) => records.push(record),
+            Err(e) => {
+                warn!(error = %e, "Unreadable manifest WAL record, dropping the rest");
+                break;
+            }
+        }
+        data = &data[4 + len..];
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn dir_entry(mtime: u64) -> VnodeEntry {
+        VnodeEntry {
+            content_hash: [0; 32],
+            size: 0,
+            mtime,
+            mode: 0o755,
+            flags: 1,
+            _pad: 0,
+        }
+    }
+
+    #[test]
+    fn test_uncommitted_writes_replay_on_open() {
+        let temp = TempDir::new().unwrap();
+        let lmdb = temp.path().join("manifest.lmdb");
+        let log = temp.path().join("manifest.wal");
+        {
+            let manifest = Arc::new(LmdbManifest::open(&lmdb).unwrap());
+            let (wal, replayed) = ManifestWal::open(&log, manifest).unwrap();
+            assert_eq!(replayed, 0);
+            wal.upsert("/build", dir_entry(1)).unwrap();
+            let label = [("security.selinux".to_string(), b"build_t".to_vec())];
+            assert!(wal.set_xattrs("/build", &label).unwrap());
+            assert!(wal.set_btime("/build", Some(7)).unwrap());
+            wal.upsert("/tmp", dir_entry(2)).unwrap();
+            wal.remove("/tmp").unwrap();
+            assert!(!wal.is_empty());
+            // Dropped without a commit, as if vdir_d had crashed
+        }
+
+        let manifest = Arc::new(LmdbManifest::open(&lmdb).unwrap());
+        assert!(manifest.get("/build").unwrap().is_none());
+        let (wal, replayed) = ManifestWal::open(&log, manifest.clone()).unwrap();
+        assert_eq!(replayed, 5);
+        assert!(wal.is_empty(), "replay commits and truncates");
+        let build = manifest.get("/build").unwrap().unwrap();
+        assert_eq!(build.vnode.mtime, 1);
+        assert_eq!(build.xattrs()[0].1, b"build_t");
+        assert_eq!(build.btime(), Some(7));
+        assert!(manifest.get("/tmp").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_commit_truncates_and_torn_tail_is_dropped() {
+        let temp = TempDir::new().unwrap();
+        let manifest = Arc::new(LmdbManifest::open(temp.path().join("manifest.lmdb")).unwrap());
+        let log = temp.path().join("manifest.wal");
+        let (wal, _) = ManifestWal::open(&log, manifest.clone()).unwrap();
+
+        wal.upsert("/a", dir_entry(1)).unwrap();
+        wal.commit_with_provenance(&Provenance::capture("test", None))
+            .unwrap();
+        assert!(wal.is_empty());
+
+        wal.upsert("/b", dir_entry(2)).unwrap();
+        drop(wal);
+        let mut file = OpenOptions::new().append(true).open(&log).unwrap();
+        file.write_all(&[200, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let (_, replayed) = ManifestWal::open(&log, manifest.clone()).unwrap();
+        assert_eq!(replayed, 1);
+        assert!(manifest.get("/a").unwrap().is_some());
+        assert!(manifest.get("/b").unwrap().is_some());
+    }
+}
@@ -1,16 +1,24 @@
 //! RFC-0039: FS Watch for Live Ingest (Layer 2)
 //!
 //! Watches project directory for changes made outside the shim layer.
-//! Uses FSEvents on macOS, inotify on Linux.
+//! Uses FSEvents on macOS, inotify on Linux. On Linux, fanotify covers a
+//! project of any size with one mark (see [`crate::fanotify`]);
+//! `VRIFT_WATCH_BACKEND` picks the backend:
+//!
+//! - `auto` (default): inotify, or fanotify if the recursive inotify watch
+//!   cannot be set up (typically `max_user_watches` exhausted)
+//! - `fanotify`: fanotify, or inotify if it is unavailable
+//! - `inotify`: never fanotify
 
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Receiver};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc as tokio_mpsc;
 use tracing::{debug, info, warn};
 
-use crate::ignore::IgnoreMatcher;
+use vrift_config::ignore::IgnoreMatcher;
 
 /// Ingest event from any source (L1/L2/L3)
 #[derive(Debug, Clone)]
@@ -25,6 +33,29 @@ pub enum IngestEvent {
     SymlinkCreated { path: PathBuf, target: PathBuf },
 }
 
+impl IngestEvent {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::FileChanged { path }
+            | Self::DirCreated { path }
+            | Self::Removed { path }
+            | Self::SymlinkCreated { path, .. } => path,
+        }
+    }
+
+    /// The one event that stands for `self` followed by `newer` on the
+    /// same path: the newer one, except that a write right after a
+    /// directory or symlink was created doesn't change what it is
+    pub fn merge(self, newer: Self) -> Self {
+        match (&self, &newer) {
+            (Self::DirCreated { .. } | Self::SymlinkCreated { .. }, Self::FileChanged { .. }) => {
+                self
+            }
+            _ => newer,
+        }
+    }
+}
+
 /// FS Watch configuration
 #[derive(Debug, Clone)]
 pub struct WatchConfig {
@@ -40,18 +71,33 @@ impl Default for WatchConfig {
     fn default() -> Self {
         Self {
             root: PathBuf::new(),
-            debounce: Duration::from_millis(100),
+            debounce: Duration::from_millis(vrift_config::config().ingest.dedup_window_ms),
             ignore: IgnoreMatcher::new(),
         }
     }
 }
 
+/// What happened to a path, independent of the backend reporting it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Change {
+    Created,
+    Modified,
+    Removed,
+}
+
+enum Backend {
+    Notify {
+        _watcher: RecommendedWatcher,
+        event_rx: Receiver<Result<Event, notify::Error>>,
+    },
+    #[cfg(target_os = "linux")]
+    Fanotify(std::sync::Mutex<crate::fanotify::Fanotify>),
+}
+
 /// FS Watcher for Layer 2
 pub struct FsWatch {
     config: WatchConfig,
-    #[allow(dead_code)]
-    watcher: RecommendedWatcher,
-    event_rx: Receiver<Result<Event, notify::Error>>,
+    backend: Backend,
 }
 
 impl FsWatch {
@@ -59,9 +105,49 @@ impl FsWatch {
     pub fn new(root: PathBuf) -> notify::Result<Self> {
         let config = WatchConfig {
             root: root.clone(),
+            ignore: IgnoreMatcher::new().with_root(&root),
             ..Default::default()
         };
+        let choice = std::env::var("VRIFT_WATCH_BACKEND").unwrap_or_default();
+
+        #[cfg(target_os = "linux")]
+        if choice == "fanotify" {
+            match crate::fanotify::Fanotify::open(&root) {
+                Ok(fan) => return Ok(Self::with_fanotify(config, fan)),
+                Err(e) => warn!(error = %e, "fanotify unavailable, using inotify"),
+            }
+        }
 
+        match Self::notify_backend(&root) {
+            Ok(backend) => {
+                info!(path = %root.display(), "FS Watch started");
+                Ok(Self { config, backend })
+            }
+            #[cfg(target_os = "linux")]
+            Err(e) if choice != "inotify" && choice != "fanotify" => {
+                warn!(error = %e, "inotify watch failed, trying fanotify");
+                match crate::fanotify::Fanotify::open(&root) {
+                    Ok(fan) => Ok(Self::with_fanotify(config, fan)),
+                    Err(fan_err) => {
+                        warn!(error = %fan_err, "fanotify unavailable");
+                        Err(e)
+                    }
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn with_fanotify(config: WatchConfig, fan: crate::fanotify::Fanotify) -> Self {
+        info!(path = %config.root.display(), "FS Watch started (fanotify)");
+        Self {
+            config,
+            backend: Backend::Fanotify(std::sync::Mutex::new(fan)),
+        }
+    }
+
+    fn notify_backend(root: &Path) -> notify::Result<Backend> {
         let (tx, rx) = mpsc::channel();
 
         let watcher_config = Config::default()
@@ -75,13 +161,10 @@ impl FsWatch {
             watcher_config,
         )?;
 
-        watcher.watch(&root, RecursiveMode::Recursive)?;
+        watcher.watch(root, RecursiveMode::Recursive)?;
 
-        info!(path = %root.display(), "FS Watch started");
-
-        Ok(Self {
-            config,
-            watcher,
+        Ok(Backend::Notify {
+            _watcher: watcher,
             event_rx: rx,
         })
     }
@@ -95,48 +178,75 @@ impl FsWatch {
     fn to_ingest_event(&self, event: Event) -> Vec<IngestEvent> {
         use notify::EventKind;
 
-        let mut events = Vec::new();
+        let change = match event.kind {
+            EventKind::Create(_) => Change::Created,
+            EventKind::Modify(_) => Change::Modified,
+            EventKind::Remove(_) => Change::Removed,
+            _ => return Vec::new(),
+        };
+        event
+            .paths
+            .into_iter()
+            .filter_map(|path| self.classify(path, change))
+            .collect()
+    }
 
-        for path in event.paths {
-            if self.should_ignore(&path) {
-                continue;
+    /// Apply ignore rules to a changed path and describe it for ingest
+    fn classify(&self, path: PathBuf, change: Change) -> Option<IngestEvent> {
+        if path
+            .file_name()
+            .is_some_and(|n| n == vrift_config::ignore::VRIFTIGNORE_FILE)
+        {
+            if let Some(dir) = path.parent() {
+                self.config.ignore.invalidate_dir(dir);
             }
+        }
 
-            let ingest_event = match event.kind {
-                EventKind::Create(_) => {
-                    if path.is_dir() {
-                        IngestEvent::DirCreated { path }
-                    } else if path.is_symlink() {
-                        // Read symlink target
-                        let target = std::fs::read_link(&path).unwrap_or_default();
-                        IngestEvent::SymlinkCreated { path, target }
-                    } else {
-                        IngestEvent::FileChanged { path }
-                    }
-                }
-                EventKind::Modify(_) => IngestEvent::FileChanged { path },
-                EventKind::Remove(_) => IngestEvent::Removed { path },
-                _ => continue,
-            };
-
-            events.push(ingest_event);
+        if self.should_ignore(&path) {
+            return None;
         }
 
-        events
+        Some(match change {
+            Change::Created => {
+                if path.is_dir() {
+                    IngestEvent::DirCreated { path }
+                } else if path.is_symlink() {
+                    // Read symlink target
+                    let target = std::fs::read_link(&path).unwrap_or_default();
+                    IngestEvent::SymlinkCreated { path, target }
+                } else {
+                    IngestEvent::FileChanged { path }
+                }
+            }
+            Change::Modified => IngestEvent::FileChanged { path },
+            Change::Removed => IngestEvent::Removed { path },
+        })
     }
 
     /// Poll for events (non-blocking)
     pub fn poll(&self) -> Vec<IngestEvent> {
         let mut events = Vec::new();
 
-        while let Ok(result) = self.event_rx.try_recv() {
-            match result {
-                Ok(event) => {
-                    debug!(?event, "FS event received");
-                    events.extend(self.to_ingest_event(event));
+        match &self.backend {
+            Backend::Notify { event_rx, .. } => {
+                while let Ok(result) = event_rx.try_recv() {
+                    match result {
+                        Ok(event) => {
+                            debug!(?event, "FS event received");
+                            events.extend(self.to_ingest_event(event));
+                        }
+                        Err(e) => {
+                            warn!(error = %e, "FS watch error");
+                        }
+                    }
                 }
-                Err(e) => {
-                    warn!(error = %e, "FS watch error");
+            }
+            #[cfg(target_os = "linux")]
+            Backend::Fanotify(fan) => {
+                let changes = fan.lock().unwrap().read_events();
+                for (path, change) in changes {
+                    debug!(path = %path.display(), ?change, "fanotify event received");
+                    events.extend(self.classify(path, change));
                 }
             }
         }
@@ -145,6 +255,46 @@ impl FsWatch {
     }
 }
 
+/// Holds changed paths until they have been quiet for the debounce period,
+/// so a file written in many chunks is ingested once, after the last one
+pub(crate) struct Debouncer {
+    quiet: Duration,
+    pending: HashMap<PathBuf, (IngestEvent, Instant)>,
+}
+
+impl Debouncer {
+    pub(crate) fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, event: IngestEvent, now: Instant) {
+        let path = event.path().to_path_buf();
+        let merged = match self.pending.remove(&path) {
+            Some((older, _)) => older.merge(event),
+            None => event,
+        };
+        self.pending.insert(path, (merged, now));
+    }
+
+    /// Events whose path hasn't changed for the quiet period
+    pub(crate) fn ready(&mut self, now: Instant) -> Vec<IngestEvent> {
+        let quiet = self.quiet;
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, (_, last))| now.saturating_duration_since(*last) >= quiet)
+            .map(|(path, _)| path.clone())
+            .collect();
+        settled
+            .into_iter()
+            .filter_map(|path| self.pending.remove(&path).map(|(event, _)| event))
+            .collect()
+    }
+}
+
 /// Spawn async watcher task that sends events to a channel
 pub fn spawn_watch_task(
     root: PathBuf,
@@ -159,11 +309,14 @@ pub fn spawn_watch_task(
             }
         };
 
+        let mut debouncer = Debouncer::new(watcher.config.debounce);
         loop {
-            // Poll for events
-            let events = watcher.poll();
+            let now = Instant::now();
+            for event in watcher.poll() {
+                debouncer.push(event, now);
+            }
 
-            for event in events {
+            for event in debouncer.ready(now) {
                 if tx.send(event).await.is_err() {
                     // Channel closed, exit
                     return;
@@ -218,4 +371,43 @@ mod tests {
         assert!(config.ignore.should_ignore(Path::new(".vrift")));
         assert!(config.ignore.should_ignore(Path::new(".DS_Store")));
     }
+
+    #[test]
+    fn test_debouncer_waits_for_quiet_and_merges() {
+        let ms = Duration::from_millis;
+        let t0 = Instant::now();
+        let changed = |p: &str| IngestEvent::FileChanged { path: p.into() };
+        let mut debouncer = Debouncer::new(ms(100));
+
+        debouncer.push(changed("/p/a"), t0);
+        debouncer.push(changed("/p/a"), t0 + ms(50));
+        debouncer.push(
+            IngestEvent::DirCreated {
+                path: "/p/d".into(),
+            },
+            t0,
+        );
+        debouncer.push(changed("/p/d"), t0 + ms(10));
+        debouncer.push(changed("/p/gone"), t0);
+        debouncer.push(
+            IngestEvent::Removed {
+                path: "/p/gone".into(),
+            },
+            t0 + ms(20),
+        );
+
+        // /p/a was written again at 50ms, so it isn't quiet yet
+        let mut first = debouncer.ready(t0 + ms(120));
+        first.sort_by(|a, b| a.path().cmp(b.path()));
+        assert!(matches!(
+            &first[..],
+            [IngestEvent::DirCreated { .. }, IngestEvent::Removed { .. },]
+        ));
+
+        let rest = debouncer.ready(t0 + ms(150));
+        assert!(
+            matches!(&rest[..], [IngestEvent::FileChanged { path }] if path == Path::new("/p/a"))
+        );
+        assert!(debouncer.ready(t0 + ms(500)).is_empty());
+    }
 }
@@ -156,7 +156,7 @@ async fn test_daemon_manifest_operations_over_socket() {
     );
 
     match response {
-        vrift_ipc::VeloResponse::ManifestAck { entry: Some(e) } => {
+        vrift_ipc::VeloResponse::ManifestAck { entry: Some(e), .. } => {
             assert_eq!(e.size, 1000);
             assert_eq!(e.content_hash, [42; 32]);
         }
@@ -0,0 +1,368 @@
+//! # vrift-view
+//!
+//! Portable manifest view — SSOT for the CLI (writer) and the Windows
+//! inception layer, `vrift-shim-win` (reader).
+//!
+//! The DLL can't link the LMDB manifest or the CAS crate (both are
+//! Unix-only), so `vrift shim view` flattens a manifest into a text file:
+//!
+//! ```text
+//! # vrift-view 1
+//! <kind> <mode> <size> <mtime> <hash> <key>
+//! ```
+//!
+//! One entry per line, fields separated by tabs. `kind` is `d`
+//! (directory), `f` (file), `c` (chunked file), `l` (symlink) or `w`
+//! (whiteout). `mode` is octal, `mtime` is in Unix seconds, and `hash` is the
+//! BLAKE3 hex, or `-` for directories and whiteouts. `key` is the manifest
+//! key (`/src/main.rs`), with `\`, tab, CR and LF escaped as `\\`, `\t`, `\r`
+//! and `\n`.
+//!
+//! Lookups ignore case, as NTFS does.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// First line of every view file
+pub const VIEW_HEADER: &str = "# vrift-view 1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Dir,
+    File,
+    /// Content is a chunk list, not a plain blob
+    Chunked,
+    Symlink,
+    /// Deleted by an upper layer: hides the path
+    Whiteout,
+}
+
+impl Kind {
+    fn code(self) -> char {
+        match self {
+            Kind::Dir => 'd',
+            Kind::File => 'f',
+            Kind::Chunked => 'c',
+            Kind::Symlink => 'l',
+            Kind::Whiteout => 'w',
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Kind> {
+        match code {
+            "d" => Some(Kind::Dir),
+            "f" => Some(Kind::File),
+            "c" => Some(Kind::Chunked),
+            "l" => Some(Kind::Symlink),
+            "w" => Some(Kind::Whiteout),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub key: String,
+    pub kind: Kind,
+    pub mode: u32,
+    pub size: u64,
+    pub mtime: u64,
+    pub hash: Option<[u8; 32]>,
+}
+
+impl Entry {
+    /// Last component of the key
+    pub fn name(&self) -> &str {
+        self.key.rsplit('/').next().unwrap_or("")
+    }
+
+    /// Plain CAS blob holding this entry's content, if it has one
+    pub fn blob_path(&self, cas_root: &Path) -> Option<PathBuf> {
+        if self.kind != Kind::File {
+            return None;
+        }
+        let hex = to_hex(self.hash.as_ref()?);
+        Some(
+            cas_root
+                .join("blake3")
+                .join(&hex[0..2])
+                .join(&hex[2..4])
+                .join(format!("{}_{}.bin", hex, self.size)),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct ViewError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "view line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ViewError {}
+
+/// Case-folded form used for lookups
+pub fn fold_key(key: &str) -> String {
+    key.to_lowercase()
+}
+
+fn parent_key(key: &str) -> &str {
+    match key.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &key[..i],
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+fn escape(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    for c in key.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(field: &str) -> Option<String> {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'r' => '\r',
+            'n' => '\n',
+            _ => return None,
+        });
+    }
+    Some(out)
+}
+
+/// Write `entries` as a view file
+pub fn write<'a>(
+    out: &mut impl Write,
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> io::Result<()> {
+    writeln!(out, "{}", VIEW_HEADER)?;
+    for entry in entries {
+        writeln!(
+            out,
+            "{}\t{:o}\t{}\t{}\t{}\t{}",
+            entry.kind.code(),
+            entry.mode,
+            entry.size,
+            entry.mtime,
+            entry
+                .hash
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |h| to_hex(h)),
+            escape(&entry.key)
+        )?;
+    }
+    Ok(())
+}
+
+/// A parsed view, indexed for case-insensitive lookups and listings
+#[derive(Debug, Default)]
+pub struct View {
+    entries: Vec<Entry>,
+    by_key: HashMap<String, usize>,
+    children: HashMap<String, Vec<usize>>,
+    /// Every ancestor of an entry, recorded or not
+    ancestors: HashSet<String>,
+}
+
+impl View {
+    pub fn parse(text: &str) -> Result<View, ViewError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, header)) if header.trim_end() == VIEW_HEADER => {}
+            _ => {
+                return Err(ViewError {
+                    line: 1,
+                    message: format!("expected {:?}", VIEW_HEADER),
+                })
+            }
+        }
+
+        let mut view = View::default();
+        for (i, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: &str| ViewError {
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let fields: Vec<&str> = line.splitn(6, '\t').collect();
+            let [kind, mode, size, mtime, hash, key] = fields[..] else {
+                return Err(error("expected 6 fields"));
+            };
+            let entry = Entry {
+                kind: Kind::from_code(kind).ok_or_else(|| error("unknown kind"))?,
+                mode: u32::from_str_radix(mode, 8).map_err(|_| error("bad mode"))?,
+                size: size.parse().map_err(|_| error("bad size"))?,
+                mtime: mtime.parse().map_err(|_| error("bad mtime"))?,
+                hash: match hash {
+                    "-" => None,
+                    hex => Some(from_hex(hex).ok_or_else(|| error("bad hash"))?),
+                },
+                key: unescape(key).ok_or_else(|| error("bad escape in key"))?,
+            };
+            view.insert(entry);
+        }
+        Ok(view)
+    }
+
+    pub fn load(path: &Path) -> io::Result<View> {
+        let text = std::fs::read_to_string(path)?;
+        View::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn insert(&mut self, entry: Entry) {
+        let folded = fold_key(&entry.key);
+        let parent = fold_key(parent_key(&entry.key));
+        let index = self.entries.len();
+        if let Some(old) = self.by_key.insert(folded, index) {
+            // A later line wins; drop the earlier one from its listing
+            if let Some(siblings) = self.children.get_mut(&parent) {
+                siblings.retain(|&i| i != old);
+            }
+        }
+        if entry.key != "/" {
+            let mut ancestor = parent_key(&entry.key);
+            while self.ancestors.insert(fold_key(ancestor)) && ancestor != "/" {
+                ancestor = parent_key(ancestor);
+            }
+            self.children.entry(parent).or_default().push(index);
+        }
+        self.entries.push(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_key.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_key.is_empty()
+    }
+
+    /// Entry for `key`, ignoring case
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.by_key.get(&fold_key(key)).map(|&i| &self.entries[i])
+    }
+
+    /// Whether `key` is a directory: recorded as one, or implied by entries
+    /// beneath it
+    pub fn is_dir(&self, key: &str) -> bool {
+        match self.get(key) {
+            Some(entry) => entry.kind == Kind::Dir,
+            None => key == "/" || self.ancestors.contains(&fold_key(key)),
+        }
+    }
+
+    /// Entries directly under `key`, whiteouts included
+    pub fn children(&self, key: &str) -> impl Iterator<Item = &Entry> {
+        self.children
+            .get(&fold_key(key))
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.entries[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, kind: Kind, hash: Option<[u8; 32]>) -> Entry {
+        Entry {
+            key: key.to_string(),
+            kind,
+            mode: if kind == Kind::Dir { 0o755 } else { 0o644 },
+            size: 3,
+            mtime: 1_700_000_000,
+            hash,
+        }
+    }
+
+    #[test]
+    fn test_round_trip_and_lookup() {
+        let entries = vec![
+            entry("/src", Kind::Dir, None),
+            entry("/src/Main.rs", Kind::File, Some([0xab; 32])),
+            entry("/src/odd\tname\\x", Kind::File, Some([1; 32])),
+            entry("/target/debug/app", Kind::Chunked, Some([2; 32])),
+            entry("/gone", Kind::Whiteout, None),
+        ];
+        let mut out = Vec::new();
+        write(&mut out, &entries).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("# vrift-view 1\nd\t755\t3\t1700000000\t-\t/src\n"));
+
+        let view = View::parse(&text).unwrap();
+        assert_eq!(view.len(), 5);
+        assert_eq!(view.get("/SRC/main.RS"), Some(&entries[1]));
+        assert_eq!(view.get("/src/odd\tname\\x"), Some(&entries[2]));
+        assert!(view.is_dir("/src"));
+        assert!(view.is_dir("/Target"), "implied by its children");
+        assert!(view.is_dir("/"));
+        assert!(!view.is_dir("/src/main.rs"));
+        let mut names: Vec<&str> = view.children("/src").map(|e| e.name()).collect();
+        names.sort();
+        assert_eq!(names, ["Main.rs", "odd\tname\\x"]);
+        assert_eq!(view.children("/").count(), 2);
+
+        let blob = view
+            .get("/src/main.rs")
+            .unwrap()
+            .blob_path(Path::new("/cas"));
+        let expected = format!("/cas/blake3/ab/ab/{}_3.bin", "ab".repeat(32));
+        assert_eq!(blob, Some(PathBuf::from(expected)));
+        assert_eq!(
+            view.get("/target/debug/app")
+                .unwrap()
+                .blob_path(Path::new("/cas")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(View::parse("f\t644\t1\t1\t-\t/a").unwrap_err().line, 1);
+        let err = View::parse("# vrift-view 1\nx\t644\t1\t1\t-\t/a\n").unwrap_err();
+        assert_eq!((err.line, err.message.as_str()), (2, "unknown kind"));
+        assert!(View::parse("# vrift-view 1\nf\t644\t1\t1\tzz\t/a\n").is_err());
+        assert!(View::parse("# vrift-view 1\nf\t644\t1\t1\n").is_err());
+    }
+}
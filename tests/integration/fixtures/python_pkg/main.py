@@ -0,0 +1,3 @@
+import calc
+
+print(calc.add(2, 3), calc.mul(4, 5))
@@ -0,0 +1,550 @@
+//! # vrift-integration
+//!
+//! End-to-end harness: every [`Harness`] gets its own HOME, CAS root and
+//! vriftd socket under a temp dir, starts a real `vriftd` from the cargo
+//! target dir, and drives the real `vrift` CLI and inception layer against
+//! copies of the fixture projects in `fixtures/`.
+//!
+//! Backend variants (ingest mode, asset tier, extra daemon env) are picked
+//! per test through [`HarnessBuilder`] and [`IngestOptions`], so the same
+//! assertions can run against each combination.
+//!
+//! Binaries are built once per test process with `cargo build` (a no-op
+//! when `cargo test --workspace` already built them). Run just this suite
+//! with `cargo test -p vrift-integration`.
+//!
+//! With the `alloc-audit` feature the shim is built with its allocation
+//! audit, and every process run under it reports allocations made inside
+//! intercepted calls to [`Harness::alloc_audit_reports`].
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use vrift_cas::{Blake3Hash, CasStore};
+use vrift_manifest::lmdb::LmdbManifest;
+use vrift_manifest::{AssetTier, VnodeEntry};
+
+/// How long to wait for vriftd to answer a handshake after spawning it
+const DAEMON_START_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Packages whose artifacts the harness runs
+const PACKAGES: &[&str] = &[
+    "vrift-cli",
+    "vrift-daemon",
+    "vrift-vdird",
+    "vrift-inception-layer",
+];
+
+#[cfg(target_os = "macos")]
+const SHIM_NAME: &str = "libvrift_inception_layer.dylib";
+#[cfg(not(target_os = "macos"))]
+const SHIM_NAME: &str = "libvrift_inception_layer.so";
+
+/// Directory holding the built `vrift`, `vriftd`, `vdir_d` and the shim.
+///
+/// Test binaries live in `<target>/<profile>/deps/`, so the artifacts sit one
+/// level up. The first call builds them.
+pub fn bin_dir() -> Result<&'static Path> {
+    static DIR: OnceLock<std::result::Result<PathBuf, String>> = OnceLock::new();
+    DIR.get_or_init(|| build_artifacts().map_err(|e| format!("{:#}", e)))
+        .as_ref()
+        .map(PathBuf::as_path)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+fn build_artifacts() -> Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let dir = exe
+        .parent()
+        .and_then(Path::parent)
+        .context("Test binary is not inside a cargo target dir")?
+        .to_path_buf();
+
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut cmd = Command::new(cargo);
+    cmd.arg("build")
+        .arg("--manifest-path")
+        .arg(workspace.join("Cargo.toml"));
+    for pkg in PACKAGES {
+        cmd.args(["-p", pkg]);
+    }
+    if dir.file_name() == Some(OsStr::new("release")) {
+        cmd.arg("--release");
+    }
+    if cfg!(feature = "alloc-audit") {
+        cmd.args(["--features", "vrift-inception-layer/alloc-audit"]);
+    }
+    let status = cmd.status().context("Failed to run cargo build")?;
+    if !status.success() {
+        bail!("cargo build of {:?} failed", PACKAGES);
+    }
+
+    for name in ["vrift", "vriftd", "vdir_d", SHIM_NAME] {
+        if !dir.join(name).exists() {
+            bail!("{} missing from {}", name, dir.display());
+        }
+    }
+    Ok(dir)
+}
+
+/// Builder for a [`Harness`] with non-default daemon settings
+#[derive(Debug, Default)]
+pub struct HarnessBuilder {
+    env: Vec<(String, String)>,
+}
+
+impl HarnessBuilder {
+    /// Extra env for vriftd and every CLI invocation
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn start(self) -> Result<Harness> {
+        Harness::start_with(self.env)
+    }
+}
+
+/// An isolated vriftd plus the paths it owns. Dropping it stops the daemon
+/// (and the vdir_d processes it spawned) and removes the temp dir.
+pub struct Harness {
+    root: tempfile::TempDir,
+    bin_dir: &'static Path,
+    env: Vec<(String, String)>,
+    daemon: Child,
+}
+
+impl Harness {
+    /// Start a harness with default settings
+    pub fn start() -> Result<Self> {
+        Self::builder().start()
+    }
+
+    pub fn builder() -> HarnessBuilder {
+        HarnessBuilder::default()
+    }
+
+    fn start_with(extra_env: Vec<(String, String)>) -> Result<Self> {
+        let bin_dir = bin_dir()?;
+        let root = tempfile::Builder::new().prefix("vrift-it-").tempdir()?;
+        let home = root.path().join("home");
+        let cas = root.path().join("cas");
+        std::fs::create_dir_all(&home)?;
+        std::fs::create_dir_all(&cas)?;
+
+        let socket = root.path().join("vriftd.sock");
+        let mut env = vec![
+            ("HOME".to_string(), home.display().to_string()),
+            ("VR_THE_SOURCE".to_string(), cas.display().to_string()),
+            (
+                "VRIFT_SOCKET_PATH".to_string(),
+                socket.display().to_string(),
+            ),
+            (
+                "VRIFT_REGISTRY_DIR".to_string(),
+                root.path().join("registry").display().to_string(),
+            ),
+        ];
+        if cfg!(feature = "alloc-audit") {
+            let reports = root.path().join("alloc-audit");
+            std::fs::create_dir_all(&reports)?;
+            env.push((
+                "VRIFT_ALLOC_AUDIT_DIR".to_string(),
+                reports.display().to_string(),
+            ));
+        }
+        env.extend(extra_env);
+
+        let log = std::fs::File::create(root.path().join("vriftd.log"))?;
+        let daemon = Command::new(bin_dir.join("vriftd"))
+            .arg("start")
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()
+            .context("Failed to spawn vriftd")?;
+
+        let harness = Self {
+            root,
+            bin_dir,
+            env,
+            daemon,
+        };
+        harness.wait_for_daemon(&socket)?;
+        Ok(harness)
+    }
+
+    fn wait_for_daemon(&self, socket: &Path) -> Result<()> {
+        let start = Instant::now();
+        while start.elapsed() < DAEMON_START_TIMEOUT {
+            if vrift_ipc::probe_socket(socket, vrift_ipc::PROBE_TIMEOUT) {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        bail!(
+            "vriftd did not come up within {:?}; log:\n{}",
+            DAEMON_START_TIMEOUT,
+            self.daemon_log()
+        )
+    }
+
+    /// Root of this harness's temp dir
+    pub fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    pub fn cas_root(&self) -> PathBuf {
+        self.root.path().join("cas")
+    }
+
+    pub fn cas(&self) -> Result<CasStore> {
+        Ok(CasStore::new(self.cas_root())?)
+    }
+
+    /// Put `data` in this harness's CAS the way the store itself lays blobs
+    /// out, for manifest entries a test adds by hand
+    pub fn store_blob(&self, data: &[u8]) -> Result<Blake3Hash> {
+        Ok(self.cas()?.store(data)?)
+    }
+
+    /// Everything vriftd logged so far (attach to assertion messages)
+    pub fn daemon_log(&self) -> String {
+        std::fs::read_to_string(self.root.path().join("vriftd.log")).unwrap_or_default()
+    }
+
+    /// Reports written so far by shimmed processes that allocated inside
+    /// intercepted calls (only with the `alloc-audit` feature)
+    pub fn alloc_audit_reports(&self) -> Result<Vec<String>> {
+        let dir = self.root.path().join("alloc-audit");
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut reports = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            reports.push(std::fs::read_to_string(entry?.path())?);
+        }
+        Ok(reports)
+    }
+
+    /// Run the `vrift` CLI with this harness's environment
+    pub fn vrift<I, S>(&self, cwd: &Path, args: I) -> Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        Command::new(self.bin_dir.join("vrift"))
+            .args(args)
+            .current_dir(cwd)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .output()
+            .context("Failed to run vrift")
+    }
+
+    /// Copy `fixtures/<name>` into a fresh project dir
+    pub fn fixture(&self, name: &str) -> Result<Project<'_>> {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name);
+        if !src.is_dir() {
+            bail!("No such fixture: {}", name);
+        }
+        let root = self.root.path().join("projects").join(name);
+        copy_tree(&src, &root)?;
+        Ok(Project {
+            harness: self,
+            root: root.canonicalize()?,
+        })
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        // SIGINT lets vriftd reap its vdir_d children before exiting
+        let _ = kill(Pid::from_raw(self.daemon.id() as i32), Signal::SIGINT);
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Ok(Some(_)) = self.daemon.try_wait() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+
+        // Solid ingest marks blobs (and the hard-linked sources) immutable
+        clear_immutable(self.root.path());
+    }
+}
+
+/// Ingest variant: `vrift ingest --mode <mode> --tier <tier>`
+#[derive(Debug, Clone, Copy)]
+pub struct IngestOptions {
+    pub mode: &'static str,
+    pub tier: &'static str,
+}
+
+impl IngestOptions {
+    pub const SOLID_TIER1: Self = Self {
+        mode: "solid",
+        tier: "tier1",
+    };
+    pub const SOLID_TIER2: Self = Self {
+        mode: "solid",
+        tier: "tier2",
+    };
+}
+
+/// A fixture copy inside a harness
+pub struct Project<'h> {
+    harness: &'h Harness,
+    root: PathBuf,
+}
+
+impl Project<'_> {
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The manifest `ingest` writes (the CLI's per-project default)
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join(".vrift").join("manifest.lmdb")
+    }
+
+    /// `vrift init` + `vrift ingest` through the daemon
+    pub fn ingest(&self, opts: IngestOptions) -> Result<Output> {
+        let init = self.harness.vrift(&self.root, ["init"])?;
+        ensure_success("vrift init", &init)?;
+        let out = self.harness.vrift(
+            &self.root,
+            [
+                OsStr::new("ingest"),
+                self.root.as_os_str(),
+                OsStr::new("--mode"),
+                OsStr::new(opts.mode),
+                OsStr::new("--tier"),
+                OsStr::new(opts.tier),
+            ],
+        )?;
+        ensure_success("vrift ingest", &out)?;
+        if !self.manifest_path().exists() {
+            bail!(
+                "ingest reported success but wrote no manifest at {}",
+                self.manifest_path().display()
+            );
+        }
+        Ok(out)
+    }
+
+    pub fn manifest(&self) -> Result<LmdbManifest> {
+        Ok(LmdbManifest::open(self.manifest_path())?)
+    }
+
+    /// Run `cmd` under `vrift run` (shim preloaded) from the project root
+    pub fn run<I, S>(&self, cmd: I) -> Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut args: Vec<std::ffi::OsString> = vec![
+            "run".into(),
+            "--manifest".into(),
+            self.manifest_path().into(),
+            "--".into(),
+        ];
+        args.extend(cmd.into_iter().map(|s| s.as_ref().to_os_string()));
+        self.harness.vrift(&self.root, args)
+    }
+
+    /// The manifest vDird serves for this project (`~/.vrift/db/<id>.lmdb`
+    /// under the harness HOME). vDird loads it when the first shimmed
+    /// process registers, so entries must be committed before that.
+    pub fn served_manifest(&self) -> Result<LmdbManifest> {
+        let id = vrift_config::path::compute_project_id(&self.root);
+        let path = self
+            .harness
+            .root()
+            .join("home")
+            .join(".vrift")
+            .join("db")
+            .join(format!("{}.lmdb", &id[..16]));
+        std::fs::create_dir_all(&path)?;
+        Ok(LmdbManifest::open(path)?)
+    }
+
+    /// [`served_manifest`](Self::served_manifest) with `dir` (a manifest
+    /// key such as `/gen`) as a directory and `entries` inserted under
+    /// their keys. Nothing is committed, so a test can add more first.
+    pub fn manifest_with(&self, dir: &str, entries: &[(&str, VnodeEntry)]) -> Result<LmdbManifest> {
+        let manifest = self.served_manifest()?;
+        let tier = AssetTier::Tier2Mutable;
+        manifest.insert(dir, VnodeEntry::new_directory(0, 0o40755), tier);
+        for (key, vnode) in entries {
+            manifest.insert(key, vnode.clone(), tier);
+        }
+        Ok(manifest)
+    }
+
+    /// Serve `content` at `path` (project-relative, in a directory that
+    /// isn't on disk) from the manifest and the CAS only, committed
+    pub fn add_virtual_file(&self, path: &str, content: &[u8]) -> Result<Blake3Hash> {
+        let hash = self.harness.store_blob(content)?;
+        let key = format!("/{}", path);
+        let dir = key.rsplit_once('/').map_or("/", |(dir, _)| dir);
+        let vnode = VnodeEntry::new_file(hash, content.len() as u64, 0, 0o100644);
+        self.manifest_with(dir, &[(&key, vnode)])?.commit()?;
+        Ok(hash)
+    }
+
+    /// Run `cmd` from the project root with the shim preloaded directly
+    /// rather than through `vrift run`, with the whole project as the VFS
+    pub fn run_preloaded<I, S>(&self, cmd: I) -> Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.run_preloaded_with(&[], cmd)
+    }
+
+    /// [`run_preloaded`](Self::run_preloaded) with `env` set for the
+    /// command only, over the harness's
+    pub fn run_preloaded_with<I, S>(&self, env: &[(&str, &str)], cmd: I) -> Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut cmd = cmd.into_iter();
+        let program = cmd.next().context("Empty command")?;
+        #[cfg(target_os = "macos")]
+        let preload = "DYLD_INSERT_LIBRARIES";
+        #[cfg(not(target_os = "macos"))]
+        let preload = "LD_PRELOAD";
+        Command::new(program)
+            .args(cmd)
+            .current_dir(&self.root)
+            .envs(self.harness.env.iter().map(|(k, v)| (k, v)))
+            .env(preload, self.harness.bin_dir.join(SHIM_NAME))
+            .env("VRIFT_MANIFEST", self.manifest_path())
+            .env("VRIFT_VFS_PREFIX", self.root.canonicalize()?)
+            .envs(env.iter().copied())
+            .stdin(Stdio::null())
+            .output()
+            .context("Failed to run preloaded command")
+    }
+}
+
+/// Fail with both output streams when a command didn't succeed
+pub fn ensure_success(what: &str, out: &Output) -> Result<()> {
+    if !out.status.success() {
+        bail!(
+            "{} failed ({})\n--- stdout ---\n{}\n--- stderr ---\n{}",
+            what,
+            out.status,
+            String::from_utf8_lossy(&out.stdout),
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Fail unless `python3` runs: the tests that script the layer through it
+/// would otherwise pass without exercising anything
+pub fn require_python() -> Result<()> {
+    static FOUND: OnceLock<bool> = OnceLock::new();
+    let found = *FOUND.get_or_init(|| {
+        Command::new("python3")
+            .arg("--version")
+            .output()
+            .is_ok_and(|o| o.status.success())
+    });
+    if !found {
+        bail!("python3 is required by this test but was not found on PATH");
+    }
+    Ok(())
+}
+
+/// Stdout of a `vrift run`, without the banner the CLI prints first
+pub fn run_stdout(out: &Output) -> String {
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    match stdout.find("\n\n") {
+        Some(i) if stdout.starts_with("Running with Velo VFS:") => stdout[i + 2..].to_string(),
+        _ => stdout.into_owned(),
+    }
+}
+
+fn copy_tree(src: &Path, dst: &Path) -> Result<()> {
+    std::fs::create_dir_all(dst)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn clear_immutable(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => clear_immutable(&path),
+            Ok(t) if t.is_file() => {
+                let _ = vrift_cas::set_immutable(&path, false);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Project<'_> {
+    /// Check that `rel` has a manifest entry whose size and CAS blob match
+    /// the file's bytes as they were before ingest (read from the fixture)
+    pub fn assert_ingested(&self, fixture: &str, rel: &str) -> Result<()> {
+        let expected = std::fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures")
+                .join(fixture)
+                .join(rel),
+        )?;
+        let key = format!("/{}", rel);
+        let entry = self
+            .manifest()?
+            .get(&key)?
+            .with_context(|| format!("{} missing from manifest", key))?;
+        if entry.vnode.size != expected.len() as u64 {
+            bail!(
+                "{}: manifest size {} != file size {}",
+                key,
+                entry.vnode.size,
+                expected.len()
+            );
+        }
+        if entry.vnode.content_hash != CasStore::compute_hash(&expected) {
+            bail!("{}: manifest hash does not match file content", key);
+        }
+        let blob = self
+            .harness
+            .cas()?
+            .get(&entry.vnode.content_hash)
+            .with_context(|| format!("{}: blob missing from CAS", key))?;
+        if blob != expected {
+            bail!("{}: CAS blob differs from file content", key);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! access/faccessat answered from the manifest's mode
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_manifest::VnodeEntry;
+
+const FIXTURE: &str = "cargo_ws";
+
+/// `<path> <F|R|W|X>...` results, one line per path: os.access goes through
+/// faccessat, the ctypes call through access
+const PY_ACCESS: &str = "
+import ctypes, os, sys
+libc = ctypes.CDLL(None)
+modes = {'F': os.F_OK, 'R': os.R_OK, 'W': os.W_OK, 'X': os.X_OK}
+for path in sys.argv[1:]:
+    at = ''.join(m for m in 'FRWX' if os.access(path, modes[m]))
+    plain = ''.join(m for m in 'FRWX' if libc.access(path.encode(), modes[m]) == 0)
+    print(path, at, plain)
+";
+
+#[test]
+fn test_access_follows_manifest_mode() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let file = |mode| VnodeEntry::new_file([1; 32], 1, 0, mode);
+    let entries = [
+        ("/gen/tool.sh", file(0o100755)),
+        ("/gen/data.txt", file(0o100644)),
+        ("/gen/frozen.txt", file(0o100444)),
+    ];
+    let manifest = project.manifest_with("/gen", &entries).unwrap();
+    manifest.commit().unwrap();
+    assert!(!project.root().join("gen").exists());
+
+    let out = project
+        .run_preloaded([
+            "python3",
+            "-c",
+            PY_ACCESS,
+            "gen/tool.sh",
+            "gen/data.txt",
+            "gen/frozen.txt",
+            "gen",
+            "gen/missing.txt",
+        ])
+        .unwrap();
+    ensure_success("python3 access", &out).unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+
+    // Owner bits for a regular user; root skips R/W but needs an x bit
+    let root = unsafe { nix::libc::geteuid() } == 0;
+    let expected = [
+        "gen/tool.sh FRWX FRWX".to_string(),
+        "gen/data.txt FRW FRW".to_string(),
+        if root {
+            "gen/frozen.txt FRW FRW".to_string()
+        } else {
+            "gen/frozen.txt FR FR".to_string()
+        },
+        "gen FRWX FRWX".to_string(),
+        // Under the VFS prefix, but in neither the manifest nor on disk
+        "gen/missing.txt  ".to_string(),
+    ];
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), expected);
+}
@@ -0,0 +1,48 @@
+//! Hot-path allocation audit: read an ingested project under a shim built
+//! with `alloc-audit` and require no allocations inside intercepted calls.
+//!
+//! `cargo test -p vrift-integration --features alloc-audit --test alloc_audit`
+#![cfg(feature = "alloc-audit")]
+
+use vrift_integration::{ensure_success, require_python, Harness, IngestOptions};
+
+const FIXTURE: &str = "cargo_ws";
+
+const PY_WALK: &str = "
+import os
+for root, dirs, files in os.walk('.'):
+    for name in files:
+        path = os.path.join(root, name)
+        os.lstat(path)
+        os.access(path, os.R_OK)
+        with open(path, 'rb') as f:
+            f.read()
+";
+
+#[test]
+fn test_hot_path_does_not_allocate() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.ingest(IngestOptions::SOLID_TIER2).unwrap();
+
+    // stat, open/read/close and directory walks over manifest entries
+    for cmd in [
+        &["stat", "Cargo.toml", "crates/core/src/lib.rs"][..],
+        &["cat", "Cargo.toml", "crates/app/src/main.rs"],
+        &["ls", "-lR", "crates"],
+    ] {
+        let out = project.run(cmd).unwrap();
+        ensure_success(cmd[0], &out).unwrap();
+    }
+    // An interpreter issues far more calls per file than coreutils
+    require_python().unwrap();
+    let out = project.run(["python3", "-c", PY_WALK]).unwrap();
+    ensure_success("python3", &out).unwrap();
+
+    let reports = harness.alloc_audit_reports().unwrap();
+    assert!(
+        reports.is_empty(),
+        "allocations inside intercepted calls:\n{}",
+        reports.join("\n")
+    );
+}
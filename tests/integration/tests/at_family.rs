@@ -0,0 +1,107 @@
+//! unlinkat/mkdirat/renameat2/symlinkat/linkat on manifest entries, through
+//! a directory fd, including a rename of a whole manifest directory
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_manifest::VnodeEntry;
+
+const FIXTURE: &str = "cargo_ws";
+
+/// Every call goes through the gen/ directory fd; prints one line per step,
+/// "ok" or the errno name
+const PY_AT: &str = r#"
+import ctypes, errno, os
+d = os.open("gen", os.O_RDONLY | os.O_DIRECTORY)
+libc = ctypes.CDLL(None, use_errno=True)
+
+def step(name, fn):
+    try:
+        fn()
+        print(name, "ok")
+    except OSError as e:
+        print(name, errno.errorcode[e.errno])
+
+def opens(path):
+    os.close(os.open(path, os.O_RDONLY))
+
+def renameat2(old, new, flags):
+    if libc.renameat2(d, old, d, new, flags) != 0:
+        err = ctypes.get_errno()
+        raise OSError(err, os.strerror(err))
+
+step("rename", lambda: os.rename("a.txt", "b.txt", src_dir_fd=d, dst_dir_fd=d))
+step("open-old", lambda: opens("gen/a.txt"))
+step("open-new", lambda: opens("gen/b.txt"))
+step("noreplace", lambda: renameat2(b"b.txt", b"keep.txt", 1))
+step("whiteout", lambda: renameat2(b"b.txt", b"w.txt", 4))
+step("unlink", lambda: os.unlink("b.txt", dir_fd=d))
+step("open-unlinked", lambda: opens("gen/b.txt"))
+step("mkdir-existing", lambda: os.mkdir("keep.txt", dir_fd=d))
+step("mkdir", lambda: os.mkdir("sub", dir_fd=d))
+step("symlink-existing", lambda: os.symlink("x", "keep.txt", dir_fd=d))
+step("symlink", lambda: os.symlink("keep.txt", "link", dir_fd=d))
+step("link", lambda: os.link("keep.txt", "hard", src_dir_fd=d, dst_dir_fd=d))
+step("rmdir-full", lambda: os.rmdir("m", dir_fd=d))
+step("unlink-child", lambda: os.unlink("m/f.txt", dir_fd=d))
+step("rmdir", lambda: os.rmdir("m", dir_fd=d))
+step("rename-dir", lambda: os.rename("tree", "moved", src_dir_fd=d, dst_dir_fd=d))
+step("open-moved-child", lambda: opens("gen/moved/deep/d.txt"))
+step("open-old-child", lambda: opens("gen/tree/c.txt"))
+"#;
+
+#[test]
+fn test_at_calls_on_manifest_entries() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let hash = harness.store_blob(b"x").unwrap();
+
+    // gen/ is on disk; its files are only in the manifest
+    std::fs::create_dir(project.root().join("gen")).unwrap();
+    let dir = VnodeEntry::new_directory(0, 0o40755);
+    let file = VnodeEntry::new_file(hash, 1, 100, 0o100644);
+    let entries = [
+        ("/gen/m", dir.clone()),
+        ("/gen/tree", dir.clone()),
+        ("/gen/tree/deep", dir),
+        ("/gen/a.txt", file.clone()),
+        ("/gen/keep.txt", file.clone()),
+        ("/gen/m/f.txt", file.clone()),
+        ("/gen/tree/c.txt", file.clone()),
+        ("/gen/tree/deep/d.txt", file),
+    ];
+    let manifest = project.manifest_with("/gen", &entries).unwrap();
+    manifest.commit().unwrap();
+
+    let out = project.run_preloaded(["python3", "-c", PY_AT]).unwrap();
+    ensure_success("python3 at-family", &out).unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let expected = [
+        "rename ok",
+        "open-old ENOENT",
+        "open-new ok",
+        "noreplace EEXIST",
+        "whiteout EINVAL",
+        "unlink ok",
+        "open-unlinked ENOENT",
+        "mkdir-existing EEXIST",
+        "mkdir ok",
+        "symlink-existing EEXIST",
+        "symlink ok",
+        "link EXDEV",
+        "rmdir-full ENOTEMPTY",
+        "unlink-child ok",
+        "rmdir ok",
+        "rename-dir ok",
+        "open-moved-child ok",
+        "open-old-child ENOENT",
+    ];
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), expected, "{stdout}");
+
+    // New entries are real; the blob was never touched
+    let gen = project.root().join("gen");
+    assert!(gen.join("sub").is_dir());
+    assert!(gen.join("link").symlink_metadata().is_ok());
+    assert!(!gen.join("hard").exists());
+    assert_eq!(harness.cas().unwrap().get(&hash).unwrap(), b"x");
+}
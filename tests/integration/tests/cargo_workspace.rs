@@ -0,0 +1,53 @@
+//! Cargo workspace fixture: ingest, then let cargo read it under the shim
+
+use vrift_integration::{ensure_success, run_stdout, Harness, IngestOptions};
+
+const FIXTURE: &str = "cargo_ws";
+const FILES: &[&str] = &[
+    "Cargo.toml",
+    "crates/core/Cargo.toml",
+    "crates/core/src/lib.rs",
+    "crates/app/Cargo.toml",
+    "crates/app/src/main.rs",
+];
+
+#[test]
+fn test_cargo_workspace_solid_tier2() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.ingest(IngestOptions::SOLID_TIER2).unwrap();
+
+    for rel in FILES {
+        project.assert_ingested(FIXTURE, rel).unwrap();
+    }
+    // Ingest metadata is not part of the tree
+    let keys: Vec<String> = project
+        .manifest()
+        .unwrap()
+        .iter()
+        .unwrap()
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect();
+    assert!(keys.iter().all(|k| !k.starts_with("/.vrift")), "{:?}", keys);
+
+    let out = project.run(["cat", "crates/core/src/lib.rs"]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert!(run_stdout(&out).contains("hello from fixture-core"));
+
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let out = project
+        .run([
+            cargo.as_str(),
+            "metadata",
+            "--offline",
+            "--no-deps",
+            "--format-version",
+            "1",
+        ])
+        .unwrap();
+    ensure_success("cargo metadata", &out).unwrap();
+    let metadata = run_stdout(&out);
+    assert!(metadata.contains("\"fixture-core\""), "{}", metadata);
+    assert!(metadata.contains("\"fixture-app\""), "{}", metadata);
+}
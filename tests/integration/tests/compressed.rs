@@ -0,0 +1,80 @@
+//! Opening compressed CAS blobs through the layer
+
+use std::os::unix::fs::MetadataExt;
+
+use vrift_integration::{ensure_success, Harness};
+use vrift_manifest::VnodeEntry;
+
+const FIXTURE: &str = "cargo_ws";
+const FILE: &str = "data/big.txt";
+const KEY: &str = "/data/big.txt";
+
+#[test]
+fn test_compressed_blob_inflated_once() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let content: Vec<u8> = b"fn main() { println!(\"hello\"); }\n"
+        .iter()
+        .copied()
+        .cycle()
+        .take(64 * 1024)
+        .collect();
+    let cas = harness.cas().unwrap().with_compression(true);
+    let hash = cas.store(&content).unwrap();
+    assert!(cas.compressed_path(&hash).exists());
+    assert!(cas.blob_path_for_hash(&hash).is_none());
+
+    let vnode = VnodeEntry::new_file(hash, content.len() as u64, 0, 0o100644);
+    let manifest = project.manifest_with("/data", &[(KEY, vnode)]).unwrap();
+    manifest.commit().unwrap();
+
+    // The first open inflates into the cache
+    let out = project.run_preloaded(["cat", FILE]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, content);
+    let expanded = cas.expanded_path(&hash);
+    assert_eq!(std::fs::read(&expanded).unwrap(), content);
+    let ino = std::fs::metadata(&expanded).unwrap().ino();
+
+    // Later opens, in another process, reuse that copy
+    let out = project.run_preloaded(["cat", FILE]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, content);
+    assert_eq!(std::fs::metadata(&expanded).unwrap().ino(), ino);
+
+    // The store itself still holds only the compressed form
+    assert!(cas.blob_path_for_hash(&hash).is_none());
+    assert_eq!(cas.expanded_copies().unwrap(), vec![hash]);
+}
+
+#[test]
+fn test_mismatched_expansion_is_not_cached() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let content: Vec<u8> = b"pub fn lib() {}\n"
+        .iter()
+        .copied()
+        .cycle()
+        .take(64 * 1024)
+        .collect();
+    let cas = harness.cas().unwrap().with_compression(true);
+    let hash = cas.store(&content).unwrap();
+
+    // A compressed blob filed under a hash its content doesn't have
+    let claimed = vrift_cas::CasStore::compute_hash(b"something else");
+    let forged = cas.compressed_path(&claimed);
+    std::fs::create_dir_all(forged.parent().unwrap()).unwrap();
+    std::fs::copy(cas.compressed_path(&hash), &forged).unwrap();
+
+    let vnode = VnodeEntry::new_file(claimed, content.len() as u64, 0, 0o100644);
+    let manifest = project.manifest_with("/data", &[(KEY, vnode)]).unwrap();
+    manifest.commit().unwrap();
+
+    let out = project.run_preloaded(["cat", FILE]).unwrap();
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+    assert!(!cas.expanded_path(&claimed).exists());
+    assert!(cas.expanded_copies().unwrap().is_empty());
+}
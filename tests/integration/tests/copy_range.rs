@@ -0,0 +1,48 @@
+//! sendfile/copy_file_range from a CAS-served fd into the project
+
+use vrift_integration::{ensure_success, require_python, Harness};
+
+const FIXTURE: &str = "cargo_ws";
+const CONTENT: &[u8] = b"hello world";
+
+/// sendfile the whole of argv[1], copy_file_range its second word at an
+/// explicit offset, shutil.copyfile it, then try to write into the CAS fd
+const PY_COPY: &str = "
+import errno, os, shutil, sys
+src = sys.argv[1]
+fd = os.open(src, os.O_RDONLY)
+out = os.open('sent.txt', os.O_WRONLY | os.O_CREAT | os.O_TRUNC)
+print(os.sendfile(out, fd, None, 100), os.lseek(fd, 0, os.SEEK_CUR))
+os.close(out)
+out = os.open('word.txt', os.O_WRONLY | os.O_CREAT | os.O_TRUNC)
+print(os.copy_file_range(fd, out, 5, 6), os.lseek(fd, 0, os.SEEK_CUR))
+try:
+    os.copy_file_range(out, fd, 5)
+except OSError as e:
+    print(errno.errorcode[e.errno])
+os.close(out)
+os.close(fd)
+shutil.copyfile(src, 'copied.txt')
+";
+
+#[test]
+fn test_copy_from_cas_fd() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let hash = project.add_virtual_file("gen/data.txt", CONTENT).unwrap();
+
+    let out = project
+        .run_preloaded(["python3", "-c", PY_COPY, "gen/data.txt"])
+        .unwrap();
+    ensure_success("python3 copy", &out).unwrap();
+    // copy_file_range at an explicit offset leaves the fd's own alone
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "11 11\n5 11\nEBADF\n");
+
+    let root = project.root();
+    assert_eq!(std::fs::read(root.join("sent.txt")).unwrap(), CONTENT);
+    assert_eq!(std::fs::read(root.join("word.txt")).unwrap(), b"world");
+    assert_eq!(std::fs::read(root.join("copied.txt")).unwrap(), CONTENT);
+    assert_eq!(harness.cas().unwrap().get(&hash).unwrap(), CONTENT);
+}
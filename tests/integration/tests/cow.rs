@@ -0,0 +1,46 @@
+//! Writable fds of one manifest file share a single CoW copy
+
+use vrift_integration::{ensure_success, require_python, Harness};
+
+const FIXTURE: &str = "cargo_ws";
+const FILE: &str = "data/config.txt";
+const CONTENT: &[u8] = b"from the manifest\n";
+
+/// Two writable fds, each writing its part, closed in argv[2]'s order; the
+/// second keeps writing after the first is closed
+const PY_TWO_WRITERS: &str = r#"
+import os, sys
+path, order = sys.argv[1], sys.argv[2]
+a = os.open(path, os.O_RDWR)
+b = os.open(path, os.O_RDWR)
+os.pwrite(a, b"FROM", 0)
+first, second = (a, b) if order == "ab" else (b, a)
+os.close(first)
+os.pwrite(second, b"MANIFEST", 9)
+os.close(second)
+"#;
+
+#[test]
+fn test_two_writers_closed_in_either_order() {
+    require_python().unwrap();
+    for order in ["ab", "ba"] {
+        let harness = Harness::start().unwrap();
+        let project = harness.fixture(FIXTURE).unwrap();
+        project.add_virtual_file(FILE, CONTENT).unwrap();
+
+        let out = project
+            .run_preloaded(["python3", "-c", PY_TWO_WRITERS, FILE, order])
+            .unwrap();
+        ensure_success("python3 two writers", &out).unwrap();
+
+        // Both writes reach the manifest as one file
+        let out = project.run_preloaded(["cat", FILE]).unwrap();
+        ensure_success("cat", &out).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&out.stdout),
+            "FROM the MANIFEST\n",
+            "closed {}",
+            order
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Exec of manifest binaries that aren't on disk, and layer env propagation
+
+use vrift_integration::{ensure_success, require_python, Harness, Project};
+use vrift_manifest::{AssetTier, VnodeEntry};
+
+const FIXTURE: &str = "cargo_ws";
+const TOOL: &str = "bin/tool.sh";
+const TOOL_SCRIPT: &[u8] = b"#!/bin/sh\necho \"tool ran: $*\"\n";
+
+/// Serve an executable script that only the manifest and the CAS know
+fn add_virtual_tool(harness: &Harness, project: &Project<'_>) {
+    let hash = harness.store_blob(TOOL_SCRIPT).unwrap();
+
+    let manifest = project.served_manifest().unwrap();
+    let vnode = VnodeEntry::new_file(hash, TOOL_SCRIPT.len() as u64, 0, 0o100755);
+    manifest.insert(&format!("/{}", TOOL), vnode, AssetTier::Tier2Mutable);
+    manifest.commit().unwrap();
+    assert!(!project.root().join(TOOL).exists());
+}
+
+#[test]
+fn test_exec_hydrates_virtual_binary() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    add_virtual_tool(&harness, &project);
+
+    let out = project
+        .run_preloaded(["sh", "-c", &format!("./{} a b", TOOL)])
+        .unwrap();
+    ensure_success("sh ./bin/tool.sh", &out).unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "tool ran: a b");
+
+    // Served from the exec cache; the working tree is left alone
+    assert!(!project.root().join(TOOL).exists());
+    let cache = project.root().join(".vrift").join("exec");
+    let cached: Vec<_> = std::fs::read_dir(&cache).unwrap().flatten().collect();
+    assert_eq!(cached.len(), 1);
+    assert!(cached[0].path().join("tool.sh").is_file());
+}
+
+#[test]
+fn test_exec_restores_layer_env() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    add_virtual_tool(&harness, &project);
+
+    // The shell is exec'd with an empty environment: only the layer vars
+    // execve puts back let it see (and run) the virtual script
+    let script = format!(
+        "import os; os.execve('/bin/sh', ['sh', '-c', './{} env'], {{}})",
+        TOOL
+    );
+    let out = project.run_preloaded(["python3", "-c", &script]).unwrap();
+    ensure_success("python3 execve", &out).unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "tool ran: env");
+}
+
+#[test]
+fn test_exec_after_vfork_hydrates() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    add_virtual_tool(&harness, &project);
+
+    // _posixsubprocess forks with vfork() where it can and, given an env,
+    // runs the virtual script with execve() in the child
+    let script = format!(
+        "import os, subprocess; subprocess.run(['./{}', 'vforked'], env=dict(os.environ), check=True)",
+        TOOL
+    );
+    let out = project.run_preloaded(["python3", "-c", &script]).unwrap();
+    ensure_success("python3 subprocess", &out).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        "tool ran: vforked"
+    );
+}
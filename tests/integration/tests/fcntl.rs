@@ -0,0 +1,54 @@
+//! fcntl F_DUPFD/F_DUPFD_CLOEXEC on manifest fds: the new fd stays tracked
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_manifest::VnodeEntry;
+
+const FIXTURE: &str = "cargo_ws";
+const CONTENT: &[u8] = b"hello world";
+
+/// Duplicate a read-only fd on argv[1] with F_DUPFD and os.dup (which is
+/// F_DUPFD_CLOEXEC), print `<mode> <mtime> <size>` from each, then
+/// ftruncate the F_DUPFD copy to 2.
+const PY_FCNTL: &str = "
+import fcntl, os, stat, sys
+fd = os.open(sys.argv[1], os.O_RDONLY)
+dups = [fcntl.fcntl(fd, fcntl.F_DUPFD, 10), os.dup(fd)]
+os.close(fd)
+for d in dups:
+    st = os.fstat(d)
+    print(oct(stat.S_IMODE(st.st_mode)), int(st.st_mtime), st.st_size)
+os.ftruncate(dups[0], 2)
+for d in dups:
+    os.close(d)
+";
+
+#[test]
+fn test_fcntl_dupfd_keeps_tracking() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let hash = harness.store_blob(CONTENT).unwrap();
+
+    let vnode = VnodeEntry::new_file(hash, CONTENT.len() as u64, 100, 0o100640);
+    let manifest = project
+        .manifest_with("/gen", &[("/gen/data.txt", vnode)])
+        .unwrap();
+    manifest.commit().unwrap();
+
+    let out = project
+        .run_preloaded(["python3", "-c", PY_FCNTL, "gen/data.txt"])
+        .unwrap();
+    ensure_success("python3 fcntl", &out).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "0o640 100 11\n0o640 100 11\n"
+    );
+
+    // ftruncate on the duplicate went through CoW, not the blob
+    let out = project.run_preloaded(["cat", "gen/data.txt"]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, b"he");
+    assert_eq!(harness.cas().unwrap().get(&hash).unwrap(), CONTENT);
+    assert!(!project.root().join("gen").exists());
+}
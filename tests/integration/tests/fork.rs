@@ -0,0 +1,59 @@
+//! fork() while the layer has state in use on other threads
+
+use vrift_integration::{ensure_success, require_python, Harness};
+
+const FIXTURE: &str = "cargo_ws";
+const FILE: &str = "data/config.txt";
+const CONTENT: &[u8] = b"from the manifest\n";
+
+/// A thread keeps listing the virtual directory (holding the layer's
+/// directory lock and a daemon socket) while the main thread forks with a
+/// CoW copy of the file open. Every child must be able to use the layer.
+const PY_FORK: &str = r#"
+import os, signal, sys, threading
+
+signal.alarm(60)
+stop = False
+def lister():
+    while not stop:
+        os.listdir("data")
+t = threading.Thread(target=lister)
+t.start()
+
+f = open(sys.argv[1], "r+")
+for _ in range(50):
+    pid = os.fork()
+    if pid == 0:
+        ok = False
+        try:
+            with open(sys.argv[1]) as g:
+                ok = os.listdir("data") == ["config.txt"] and g.read() != ""
+            f.close()
+        finally:
+            os._exit(0 if ok else 1)
+    _, status = os.waitpid(pid, 0)
+    if status != 0:
+        stop = True
+        sys.exit("child failed: %d" % status)
+
+stop = True
+t.join()
+f.write("from the parent\n")
+f.close()
+print("ok")
+"#;
+
+#[test]
+fn test_fork_child_uses_layer_while_parent_threads_busy() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.add_virtual_file(FILE, CONTENT).unwrap();
+    assert!(!project.root().join(FILE).exists());
+
+    let out = project
+        .run_preloaded(["python3", "-c", PY_FORK, FILE])
+        .unwrap();
+    ensure_success("python3 fork", &out).unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "ok");
+}
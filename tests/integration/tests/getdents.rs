@@ -0,0 +1,63 @@
+//! getdents64 on directory fds: manifest children merged into real listings
+#![cfg(target_os = "linux")]
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_manifest::VnodeEntry;
+
+const FIXTURE: &str = "cargo_ws";
+
+/// Names from raw getdents64 calls on an O_DIRECTORY fd, sorted, one per
+/// line. The small buffer forces several calls per listing.
+const PY_GETDENTS: &str = "
+import ctypes, os, struct, sys
+libc = ctypes.CDLL(None, use_errno=True)
+fd = os.open(sys.argv[1], os.O_RDONLY | os.O_DIRECTORY)
+buf = ctypes.create_string_buffer(96)
+names = []
+while True:
+    n = libc.getdents64(fd, buf, len(buf))
+    if n < 0:
+        raise OSError(ctypes.get_errno(), 'getdents64')
+    if n == 0:
+        break
+    off = 0
+    while off < n:
+        reclen = struct.unpack_from('H', buf.raw, off + 16)[0]
+        names.append(buf.raw[off + 19:off + reclen].split(b'\\0')[0].decode())
+        off += reclen
+os.close(fd)
+for name in sorted(names):
+    print(name)
+";
+
+#[test]
+fn test_getdents64_lists_manifest_children() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let vnode = VnodeEntry::new_file([1; 32], 1, 0, 0o100644);
+    let entries = [
+        ("/gen/a.rs", vnode.clone()),
+        ("/gen/b.rs", vnode.clone()),
+        ("/crates/app/src/extra.rs", vnode.clone()),
+        // Listed by the kernel already; must not show up twice
+        ("/crates/app/src/main.rs", vnode),
+    ];
+    let manifest = project.manifest_with("/gen", &entries).unwrap();
+    manifest.commit().unwrap();
+    assert!(!project.root().join("gen").exists());
+
+    let list = |dir: &str| {
+        let out = project
+            .run_preloaded(["python3", "-c", PY_GETDENTS, dir])
+            .unwrap();
+        ensure_success("python3 getdents64", &out).unwrap();
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+
+    // Manifest-only directory
+    assert_eq!(list("gen"), ".\n..\na.rs\nb.rs\n");
+    // Real directory with a manifest-only child
+    assert_eq!(list("crates/app/src"), ".\n..\nextra.rs\nmain.rs\n");
+}
@@ -0,0 +1,56 @@
+//! Listing a manifest-only directory larger than one readdir batch
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_manifest::VnodeEntry;
+
+const FIXTURE: &str = "cargo_ws";
+const DIR: &str = "big";
+const FILES: usize = 1000;
+
+/// readdir order, one name per line. Coreutils `ls` would statx the
+/// directory first, which manifest-only paths don't answer yet.
+const PY_LIST: &str = "
+import os, sys
+for name in os.listdir(sys.argv[1]):
+    print(name)
+";
+
+/// Every other name is NAME_MAX long, so a full batch doesn't fit the
+/// stream's arena
+fn name(i: usize) -> String {
+    if i.is_multiple_of(2) {
+        format!("f{:04}_{}", i, "x".repeat(249))
+    } else {
+        format!("f{:04}.rs", i)
+    }
+}
+
+#[test]
+fn test_readdir_streams_large_virtual_dir() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let vnode = VnodeEntry::new_file([1; 32], 1, 0, 0o100644);
+    let keys: Vec<String> = (0..FILES)
+        .map(|i| format!("/{}/{}", DIR, name(i)))
+        .collect();
+    let entries: Vec<_> = keys.iter().map(|k| (k.as_str(), vnode.clone())).collect();
+    let manifest = project
+        .manifest_with(&format!("/{}", DIR), &entries)
+        .unwrap();
+    manifest.commit().unwrap();
+    assert!(!project.root().join(DIR).exists());
+
+    let out = project
+        .run_preloaded(["python3", "-c", PY_LIST, DIR])
+        .unwrap();
+    ensure_success("python3 listdir", &out).unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let listed: Vec<&str> = stdout.lines().collect();
+
+    // Byte order, each child once, across every batch
+    let mut expected: Vec<String> = (0..FILES).map(name).collect();
+    expected.sort();
+    assert_eq!(listed, expected);
+}
@@ -0,0 +1,61 @@
+//! vDird publishes the manifest keys next to the VDir; shims answer lookups
+//! of paths the manifest lacks from it, and still find entries added since
+
+use vrift_integration::{ensure_success, Harness};
+
+const FIXTURE: &str = "cargo_ws";
+const FILE: &str = "gen/data.txt";
+const CONTENT: &[u8] = b"from the manifest\n";
+
+#[test]
+fn test_missing_paths_are_answered_locally() {
+    let harness = Harness::builder()
+        .env("VRIFT_SUMMARY", "1")
+        .start()
+        .unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.add_virtual_file(FILE, CONTENT).unwrap();
+
+    // The first shimmed process starts vDird, so it maps nothing yet
+    let out = project.run_preloaded(["cat", FILE]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, CONTENT);
+
+    let missing: Vec<String> = (0..50).map(|i| format!("gen/missing{}.txt", i)).collect();
+    let out = project
+        .run_preloaded(std::iter::once("stat").chain(missing.iter().map(String::as_str)))
+        .unwrap();
+    assert!(!out.status.success());
+
+    // None of them needed vDird
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let line = stderr
+        .lines()
+        .find(|l| l.starts_with("vrift: ") && l.contains(" from VFS, "))
+        .unwrap_or_else(|| panic!("no summary line in stderr:\n{}", stderr));
+    assert!(
+        line.ends_with(" 0 IPC calls, 100.0% cache hits"),
+        "{}",
+        line
+    );
+}
+
+#[test]
+fn test_entries_added_after_publishing_are_found() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.add_virtual_file(FILE, CONTENT).unwrap();
+
+    // The first shimmed process starts vDird, which publishes without
+    // gen/moved.txt; the rename adds it
+    let out = project
+        .run_preloaded(["mv", FILE, "gen/moved.txt"])
+        .unwrap();
+    ensure_success("mv", &out).unwrap();
+
+    let out = project.run_preloaded(["cat", "gen/moved.txt"]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, CONTENT);
+    let out = project.run_preloaded(["cat", FILE]).unwrap();
+    assert!(!out.status.success());
+}
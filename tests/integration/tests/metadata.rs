@@ -0,0 +1,71 @@
+//! chmod/chown/utimensat on manifest entries: metadata goes to the manifest
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_manifest::VnodeEntry;
+
+const FIXTURE: &str = "cargo_ws";
+
+/// chmod/fchmod/utime/chown on argv[1..4], then `<path> <mode> <mtime>` for
+/// each, from fstat on a fresh fd. argv[4] is a real file the manifest
+/// doesn't have.
+const PY_META: &str = "
+import os, stat, sys
+tool, data, opened, output = sys.argv[1:5]
+os.chmod(tool, 0o755)
+os.utime(data, (1000, 1234567890))
+os.chown(data, -1, -1)
+fd = os.open(opened, os.O_RDONLY)
+os.fchmod(fd, 0o600)
+os.close(fd)
+open(output, 'w').close()
+os.chmod(output, 0o700)
+for path in sys.argv[1:5]:
+    fd = os.open(path, os.O_RDONLY)
+    st = os.fstat(fd)
+    os.close(fd)
+    print(path, oct(stat.S_IMODE(st.st_mode)), int(st.st_mtime))
+";
+
+#[test]
+fn test_metadata_changes_update_manifest() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    // opened.txt gets opened, so its content has to be in the CAS
+    let hash = harness.store_blob(b"x").unwrap();
+
+    let vnode = VnodeEntry::new_file(hash, 1, 100, 0o100644);
+    let entries = ["/gen/tool.sh", "/gen/data.txt", "/gen/opened.txt"].map(|p| (p, vnode.clone()));
+    let manifest = project.manifest_with("/gen", &entries).unwrap();
+    manifest.commit().unwrap();
+    assert!(!project.root().join("gen").exists());
+
+    let out = project
+        .run_preloaded([
+            "python3",
+            "-c",
+            PY_META,
+            "gen/tool.sh",
+            "gen/data.txt",
+            "gen/opened.txt",
+            "out.bin",
+        ])
+        .unwrap();
+    ensure_success("python3 metadata", &out).unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines[0], "gen/tool.sh 0o755 100");
+    assert_eq!(lines[1], "gen/data.txt 0o644 1234567890");
+    assert_eq!(lines[2], "gen/opened.txt 0o600 100");
+    assert!(lines[3].starts_with("out.bin 0o700 "), "{stdout}");
+
+    // Nothing materialized, and the CAS blob kept its mode
+    assert!(!project.root().join("gen").exists());
+    let blob = harness.cas().unwrap().blob_path_for_hash(&hash).unwrap();
+    let blob_mode = std::fs::metadata(&blob).unwrap().permissions();
+    assert_ne!(
+        std::os::unix::fs::PermissionsExt::mode(&blob_mode) & 0o777,
+        0o600
+    );
+}
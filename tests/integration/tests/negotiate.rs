@@ -0,0 +1,112 @@
+//! The shim negotiates capabilities with vDird and holds back what an older
+//! vDird didn't agree to
+
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_ipc::{frame_sync, VeloError, VeloErrorKind, VeloRequest, VeloResponse, VnodeEntry};
+
+const FIXTURE: &str = "cargo_ws";
+const FILE: &str = "virtual.txt";
+
+const PY_RENAME: &str = "
+import errno, os, sys
+try:
+    os.rename(sys.argv[1], 'moved.txt')
+    print('renamed')
+except OSError as e:
+    print(errno.errorcode[e.errno])
+";
+
+/// Requests a fake daemon was sent, by variant name
+type Seen = Arc<Mutex<Vec<String>>>;
+
+type Respond = Arc<dyn Fn(&VeloRequest) -> VeloResponse + Send + Sync>;
+
+/// Answer every connection to `socket` with `respond`, on its own thread
+fn serve(socket: &Path, seen: Seen, respond: Respond) {
+    let listener = UnixListener::bind(socket).unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let (seen, respond) = (Arc::clone(&seen), Arc::clone(&respond));
+            std::thread::spawn(move || answer(stream.unwrap(), &seen, &*respond));
+        }
+    });
+}
+
+fn answer(
+    mut stream: UnixStream,
+    seen: &Seen,
+    respond: &(dyn Fn(&VeloRequest) -> VeloResponse + Send + Sync),
+) {
+    while let Ok((header, request)) = frame_sync::read_request(&mut stream) {
+        let name = format!("{:?}", request);
+        let name = name.split([' ', '{', '(']).next().unwrap_or("").to_string();
+        seen.lock().unwrap().push(name);
+        if frame_sync::send_response(&mut stream, &respond(&request), header.seq_id).is_err() {
+            return;
+        }
+    }
+}
+
+/// A vDird from before Negotiate: it can't decode the request, and serves
+/// FILE as a manifest entry
+fn old_vdird(request: &VeloRequest) -> VeloResponse {
+    match request {
+        VeloRequest::ManifestGet { path } => {
+            let entry = (path.trim_start_matches('/') == FILE).then_some(VnodeEntry {
+                content_hash: [7; 32],
+                size: 5,
+                mtime: 0,
+                mode: 0o100644,
+                flags: 0,
+                _pad: 0,
+            });
+            VeloResponse::ManifestAck { entry, ino: 0 }
+        }
+        _ => VeloResponse::Error(VeloError::new(
+            VeloErrorKind::Internal,
+            "Failed to deserialize request",
+        )),
+    }
+}
+
+#[test]
+fn test_rename_needs_vdird_to_agree() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    // A vriftd that hands out the old vDird
+    let vriftd_socket = harness.root().join("old-vriftd.sock");
+    let vdird_socket = harness.root().join("old-vdird.sock");
+    let seen = Seen::default();
+    serve(&vdird_socket, Arc::clone(&seen), Arc::new(old_vdird));
+    let vdird = vdird_socket.display().to_string();
+    let old_vriftd = move |request: &VeloRequest| match request {
+        VeloRequest::RegisterWorkspace { .. } => VeloResponse::RegisterAck {
+            workspace_id: "old".to_string(),
+            vdird_socket: vdird.clone(),
+            vdir_mmap_path: String::new(),
+            session_id: String::new(),
+        },
+        _ => VeloResponse::Error(VeloError::new(VeloErrorKind::Internal, "old vriftd")),
+    };
+    serve(&vriftd_socket, Seen::default(), Arc::new(old_vriftd));
+
+    let out = project
+        .run_preloaded_with(
+            &[("VRIFT_SOCKET_PATH", &vriftd_socket.display().to_string())],
+            ["python3", "-c", PY_RENAME, FILE],
+        )
+        .unwrap();
+    ensure_success("python3 rename", &out).unwrap();
+
+    // Like a rename across devices, so mv falls back to copy and unlink
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "EXDEV");
+    let seen = seen.lock().unwrap();
+    assert!(seen.iter().any(|r| r == "Negotiate"), "{:?}", seen);
+    assert!(!seen.iter().any(|r| r == "ManifestRename"), "{:?}", seen);
+}
@@ -0,0 +1,116 @@
+//! `vrift pack`: bundle an ingested project and read it back without a CAS
+
+use vrift_integration::{ensure_success, run_stdout, Harness, IngestOptions};
+use vrift_pack::BundleReader;
+
+const FIXTURE: &str = "python_pkg";
+
+#[test]
+fn test_pack_bundle_matches_cas() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.ingest(IngestOptions::SOLID_TIER2).unwrap();
+
+    let bundle_path = harness.root().join("python_pkg.vrift");
+    let out = harness
+        .vrift(
+            project.root(),
+            [
+                "pack".as_ref(),
+                project.manifest_path().as_os_str(),
+                "-o".as_ref(),
+                bundle_path.as_os_str(),
+                "--compress".as_ref(),
+            ],
+        )
+        .unwrap();
+    ensure_success("vrift pack", &out).unwrap();
+    assert!(run_stdout(&out).contains("Bundle:"));
+    assert!(vrift_pack::is_bundle(&bundle_path));
+
+    let manifest = project.manifest().unwrap();
+    let cas = harness.cas().unwrap();
+    let bundle = BundleReader::open(&bundle_path).unwrap();
+    assert_eq!(bundle.manifest().len(), manifest.len().unwrap());
+
+    let entry = manifest.get("/calc/ops.py").unwrap().unwrap().vnode;
+    assert_eq!(bundle.manifest().get("/calc/ops.py"), Some(&entry));
+    bundle.verify(&entry.content_hash).unwrap();
+    assert_eq!(
+        &*bundle.get(&entry.content_hash).unwrap(),
+        &cas.get(&entry.content_hash).unwrap()[..]
+    );
+
+    // Every blob the manifest references is carried, intact
+    for (path, entry) in manifest.iter().unwrap() {
+        if !entry.vnode.is_dir() {
+            assert!(bundle.contains(&entry.vnode.content_hash), "{}", path);
+        }
+    }
+    for hash in bundle.hashes() {
+        bundle.verify(hash).unwrap();
+    }
+}
+
+#[test]
+fn test_bundle_run_and_unpack() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.ingest(IngestOptions::SOLID_TIER2).unwrap();
+
+    let bundle_path = harness.root().join("python_pkg.vrift");
+    let out = harness
+        .vrift(
+            project.root(),
+            [
+                "pack".as_ref(),
+                project.manifest_path().as_os_str(),
+                "-o".as_ref(),
+                bundle_path.as_os_str(),
+            ],
+        )
+        .unwrap();
+    ensure_success("vrift pack", &out).unwrap();
+
+    // Run from somewhere unrelated: the bundle is the whole environment
+    let elsewhere = harness.root().join("elsewhere");
+    std::fs::create_dir_all(&elsewhere).unwrap();
+    let out = harness
+        .vrift(
+            &elsewhere,
+            [
+                "run".as_ref(),
+                "--bundle".as_ref(),
+                bundle_path.as_os_str(),
+                "--".as_ref(),
+                "cat".as_ref(),
+                "calc/ops.py".as_ref(),
+            ],
+        )
+        .unwrap();
+    ensure_success("vrift run --bundle", &out).unwrap();
+    let expected = std::fs::read_to_string(project.root().join("calc/ops.py")).unwrap();
+    assert!(run_stdout(&out).ends_with(&expected));
+
+    let manifest_out = elsewhere.join("imported.manifest");
+    let out = harness
+        .vrift(
+            &elsewhere,
+            [
+                "unpack".as_ref(),
+                bundle_path.as_os_str(),
+                "-o".as_ref(),
+                manifest_out.as_os_str(),
+            ],
+        )
+        .unwrap();
+    ensure_success("vrift unpack", &out).unwrap();
+    let imported = vrift_manifest::Manifest::load(&manifest_out).unwrap();
+    assert_eq!(imported.len(), project.manifest().unwrap().len().unwrap());
+    let cas = harness.cas().unwrap();
+    for (_, entry) in imported.iter() {
+        if !entry.is_dir() {
+            assert!(cas.exists(&entry.content_hash));
+        }
+    }
+}
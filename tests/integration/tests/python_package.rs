@@ -0,0 +1,56 @@
+//! Python package fixture: ingest, then import and run it under the shim
+
+use vrift_integration::{ensure_success, require_python, run_stdout, Harness, IngestOptions};
+
+const FIXTURE: &str = "python_pkg";
+const FILES: &[&str] = &[
+    "main.py",
+    "pyproject.toml",
+    "calc/__init__.py",
+    "calc/ops.py",
+];
+
+#[test]
+fn test_python_package_solid_tier2() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.ingest(IngestOptions::SOLID_TIER2).unwrap();
+
+    for rel in FILES {
+        project.assert_ingested(FIXTURE, rel).unwrap();
+    }
+
+    // stat through the shim reports the manifest size
+    let out = project.run(["stat", "-c", "%s", "calc/ops.py"]).unwrap();
+    ensure_success("stat", &out).unwrap();
+    let size = project
+        .manifest()
+        .unwrap()
+        .get("/calc/ops.py")
+        .unwrap()
+        .unwrap()
+        .vnode
+        .size;
+    assert_eq!(run_stdout(&out).trim(), size.to_string());
+
+    require_python().unwrap();
+    let out = project.run(["python3", "-B", "main.py"]).unwrap();
+    ensure_success("python3 main.py", &out).unwrap();
+    assert_eq!(run_stdout(&out).trim(), "5 20");
+}
+
+#[test]
+fn test_python_package_solid_tier1_blobs_survive_writes() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.ingest(IngestOptions::SOLID_TIER1).unwrap();
+
+    for rel in FILES {
+        project.assert_ingested(FIXTURE, rel).unwrap();
+    }
+
+    // Tier1 sources are hard links into the CAS: a write under the shim
+    // must fail or break the link, never change the shared blob
+    let _ = project.run(["sh", "-c", "echo clobbered > calc/ops.py"]);
+    project.assert_ingested(FIXTURE, "calc/ops.py").unwrap();
+}
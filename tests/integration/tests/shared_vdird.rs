@@ -0,0 +1,31 @@
+//! `[daemon] shared_vdird`: the vDird started for the first project serves
+//! the next one too, each from its own manifest
+
+use vrift_integration::{ensure_success, Harness};
+
+#[test]
+fn test_second_project_is_served_by_the_first_vdird() {
+    let harness = Harness::builder()
+        .env("VRIFT_SHARED_VDIRD", "1")
+        .start()
+        .unwrap();
+    let ws = harness.fixture("cargo_ws").unwrap();
+    let pkg = harness.fixture("python_pkg").unwrap();
+    ws.add_virtual_file("gen/ws.txt", b"workspace\n").unwrap();
+    pkg.add_virtual_file("gen/pkg.txt", b"package\n").unwrap();
+
+    let out = ws.run_preloaded(["cat", "gen/ws.txt"]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, b"workspace\n");
+    let out = pkg.run_preloaded(["cat", "gen/pkg.txt"]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, b"package\n");
+
+    // Neither project sees the other's files
+    let out = pkg.run_preloaded(["cat", "gen/ws.txt"]).unwrap();
+    assert!(!out.status.success());
+
+    let log = harness.daemon_log();
+    assert_eq!(log.matches("vDird spawned").count(), 1, "{}", log);
+    assert!(log.contains("now also serves"), "{}", log);
+}
@@ -0,0 +1,166 @@
+//! Synthesized stat for manifest entries: inodes from vDird's table,
+//! st_blocks/st_blksize from the size and the birth time recorded at ingest
+#![cfg(target_os = "linux")]
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_manifest::{AssetTier, VnodeEntry};
+
+const FIXTURE: &str = "cargo_ws";
+
+#[test]
+fn test_stat_reports_blocks_and_btime() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let hash = *blake3::hash(b"sized").as_bytes();
+    let vnode = VnodeEntry::new_file(hash, 10_000, 1_700_000_000, 0o100644);
+    let entries = [
+        ("/gen/sized.bin", vnode.clone()),
+        ("/gen/unborn.bin", vnode),
+    ];
+    let manifest = project.manifest_with("/gen", &entries).unwrap();
+    assert!(manifest
+        .set_btime("/gen/sized.bin", Some(1_600_000_000))
+        .unwrap());
+    manifest.commit().unwrap();
+
+    // The first shimmed process brings vDird up; only later ones map its
+    // VDir, the one place the shim finds a btime
+    let out = project.run_preloaded(["stat", "gen"]).unwrap();
+    ensure_success("stat", &out).unwrap();
+
+    // coreutils stat goes through statx, the only Linux call carrying btime;
+    // %W is 0 when the birth time is unknown
+    let out = project
+        .run_preloaded([
+            "stat",
+            "-c",
+            "%n %s %b %B %o %W",
+            "gen/sized.bin",
+            "gen/unborn.bin",
+        ])
+        .unwrap();
+    ensure_success("stat", &out).unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "gen/sized.bin 10000 20 512 4096 1600000000",
+            "gen/unborn.bin 10000 20 512 4096 0",
+        ],
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_inode_survives_rename() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let hash = *blake3::hash(b"moved").as_bytes();
+    let vnode = VnodeEntry::new_file(hash, 5, 1_700_000_000, 0o100644);
+    let entries = [("/gen/old.bin", vnode.clone()), ("/gen/other.bin", vnode)];
+    let manifest = project.manifest_with("/gen", &entries).unwrap();
+    manifest.commit().unwrap();
+
+    let out = project.run_preloaded(["stat", "gen"]).unwrap();
+    ensure_success("stat", &out).unwrap();
+
+    let ino = |path: &str| {
+        let out = project.run_preloaded(["stat", "-c", "%i", path]).unwrap();
+        ensure_success("stat", &out).unwrap();
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    };
+    let before = ino("gen/old.bin");
+    assert_ne!(before, ino("gen/other.bin"));
+
+    // The inode moves with the file and replaces the target's. Not mv: it
+    // lstat()s the source, and the Linux shim leaves path stats to the kernel
+    let out = project
+        .run_preloaded([
+            "python3",
+            "-c",
+            "import os; os.rename('gen/old.bin', 'gen/other.bin')",
+        ])
+        .unwrap();
+    ensure_success("python3 rename", &out).unwrap();
+    assert_eq!(ino("gen/other.bin"), before);
+}
+
+#[test]
+fn test_inode_same_in_and_out_of_vdir() {
+    // Room for one entry: the rest are served over IPC until admitted
+    let harness = Harness::builder()
+        .env("VRIFT_VDIR_MAX_ENTRIES", "1")
+        .start()
+        .unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let hash = *blake3::hash(b"cold").as_bytes();
+    let vnode = VnodeEntry::new_file(hash, 4, 1_700_000_000, 0o100644);
+    let entries = ["/gen/a.bin", "/gen/b.bin", "/gen/c.bin"].map(|p| (p, vnode.clone()));
+    let manifest = project.manifest_with("/gen", &entries).unwrap();
+    manifest.commit().unwrap();
+
+    let ino = |path: &str| {
+        let out = project.run_preloaded(["stat", "-c", "%i", path]).unwrap();
+        ensure_success("stat", &out).unwrap();
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    };
+    // Lookups get entries admitted to the VDir, evicting the others
+    let paths = ["gen/a.bin", "gen/b.bin", "gen/c.bin"];
+    let first: Vec<_> = paths.iter().map(|p| ino(p)).collect();
+    for _ in 0..3 {
+        for (path, first) in paths.iter().zip(&first) {
+            assert_eq!(&ino(path), first, "{path}");
+        }
+    }
+}
+
+#[test]
+fn test_stat_follows_manifest_symlinks() {
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    // None of these are on disk, so the kernel can't follow them
+    let hash = *blake3::hash(b"target").as_bytes();
+    let vnode = VnodeEntry::new_file(hash, 6, 1_700_000_000, 0o100644);
+    let manifest = project
+        .manifest_with("/gen", &[("/gen/real.bin", vnode)])
+        .unwrap();
+    let tier = AssetTier::Tier2Mutable;
+    manifest.insert_symlink("/gen/hop", b"real.bin", 1, tier);
+    manifest.insert_symlink("/gen/link", b"/gen/hop", 1, tier);
+    manifest.insert_symlink("/gen/loop_a", b"loop_b", 1, tier);
+    manifest.insert_symlink("/gen/loop_b", b"loop_a", 1, tier);
+    manifest.commit().unwrap();
+
+    let out = project.run_preloaded(["stat", "gen"]).unwrap();
+    ensure_success("stat", &out).unwrap();
+
+    let out = project
+        .run_preloaded(["stat", "-L", "-c", "%n %s %F", "gen/link"])
+        .unwrap();
+    ensure_success("stat -L", &out).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        "gen/link 6 regular file"
+    );
+
+    let out = project
+        .run_preloaded(["stat", "-c", "%F", "gen/link"])
+        .unwrap();
+    ensure_success("stat", &out).unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "symbolic link");
+
+    // A cycle is ELOOP, as the kernel reports for one on disk
+    let out = project.run_preloaded(["stat", "-L", "gen/loop_a"]).unwrap();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("Too many levels of symbolic links"),
+        "{stderr}"
+    );
+}
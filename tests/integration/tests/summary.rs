@@ -0,0 +1,33 @@
+//! VRIFT_SUMMARY=1 exit line
+
+use vrift_integration::{ensure_success, Harness};
+
+const FIXTURE: &str = "cargo_ws";
+const FILE: &str = "data/config.txt";
+const CONTENT: &[u8] = b"from the manifest\n";
+
+#[test]
+fn test_summary_counts_vfs_reads() {
+    let harness = Harness::builder()
+        .env("VRIFT_SUMMARY", "1")
+        .start()
+        .unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+    project.add_virtual_file(FILE, CONTENT).unwrap();
+
+    let out = project.run_preloaded(["cat", FILE]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, CONTENT);
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let line = stderr
+        .lines()
+        .find(|l| l.starts_with("vrift: ") && l.contains(" from VFS, "))
+        .unwrap_or_else(|| panic!("no summary line in stderr:\n{}", stderr));
+    assert!(
+        line.starts_with("vrift: 1 file from VFS, 18 B from CAS, 0 reingested, "),
+        "{}",
+        line
+    );
+    assert!(!line.contains(" 0 IPC calls"), "{}", line);
+}
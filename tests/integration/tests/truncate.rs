@@ -0,0 +1,45 @@
+//! truncate/ftruncate on manifest files: CoW copy, then reingest
+
+use vrift_integration::{ensure_success, require_python, Harness};
+
+const FIXTURE: &str = "cargo_ws";
+const CONTENT: &[u8] = b"hello world";
+
+/// truncate(argv[1]) to 5 and print the content, then ftruncate a read-only
+/// fd to 2. The copy behind that fd is reingested once it's closed.
+const PY_TRUNCATE: &str = "
+import os, sys
+path = sys.argv[1]
+def content():
+    with open(path, 'rb') as f:
+        return f.read().decode()
+os.truncate(path, 5)
+print(content())
+fd = os.open(path, os.O_RDONLY)
+os.ftruncate(fd, 2)
+os.close(fd)
+";
+
+#[test]
+fn test_truncate_copies_on_write() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let hash = project.add_virtual_file("gen/data.txt", CONTENT).unwrap();
+
+    let out = project
+        .run_preloaded(["python3", "-c", PY_TRUNCATE, "gen/data.txt"])
+        .unwrap();
+    ensure_success("python3 truncate", &out).unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "hello\n");
+
+    // A later process sees the reingested content
+    let out = project.run_preloaded(["cat", "gen/data.txt"]).unwrap();
+    ensure_success("cat", &out).unwrap();
+    assert_eq!(out.stdout, b"he");
+
+    // The blob is untouched and nothing was materialized
+    assert_eq!(harness.cas().unwrap().get(&hash).unwrap(), CONTENT);
+    assert!(!project.root().join("gen").exists());
+}
@@ -0,0 +1,92 @@
+//! VRIFT_IO_URING: io_uring_setup through syscall() and around the shim
+#![cfg(target_os = "linux")]
+
+use vrift_integration::{ensure_success, require_python, Harness};
+
+const FIXTURE: &str = "cargo_ws";
+
+/// io_uring_setup(0, NULL) three times through the interposed syscall()
+/// and once through libc's own, which bypasses the shim the way inline
+/// assembly does; then getpid and close(-1) through syscall(). Prints
+/// the errno name of each io_uring_setup failure.
+const PY_URING: &str = r#"
+import ctypes, errno, os, platform
+
+SYS = {"x86_64": (425, 39, 3), "aarch64": (425, 172, 57)}[platform.machine()]
+setup, getpid, close = SYS
+
+def call(lib, *args):
+    lib.syscall.restype = ctypes.c_long
+    ret = lib.syscall(*[ctypes.c_long(a) for a in args])
+    return ret, ctypes.get_errno()
+
+shim = ctypes.CDLL(None, use_errno=True)
+libc = ctypes.CDLL("libc.so.6", use_errno=True)
+for _ in range(3):
+    ret, err = call(shim, setup, 0, 0)
+    print("shim", ret, errno.errorcode.get(err, err))
+ret, err = call(libc, setup, 0, 0)
+print("libc", ret, errno.errorcode.get(err, err))
+print("getpid", call(shim, getpid)[0] == os.getpid())
+ret, err = call(shim, close, -1)
+print("close", ret, errno.errorcode[err])
+"#;
+
+fn run(mode: &str) -> (Vec<String>, String) {
+    require_python().unwrap();
+    let harness = Harness::builder()
+        .env("VRIFT_IO_URING", mode)
+        .start()
+        .unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    let out = project.run_preloaded(["python3", "-c", PY_URING]).unwrap();
+    ensure_success("python3 io_uring", &out).unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    (
+        stdout.lines().map(str::to_string).collect(),
+        String::from_utf8_lossy(&out.stderr).into_owned(),
+    )
+}
+
+/// Lines announcing the io_uring warning on its first occurrence
+fn first_warnings(stderr: &str) -> usize {
+    stderr
+        .lines()
+        .filter(|l| l.starts_with("vrift: warning: io_uring in use"))
+        .count()
+}
+
+#[test]
+fn test_deny_fails_setup_with_enosys() {
+    let (lines, stderr) = run("deny");
+    assert_eq!(
+        lines,
+        [
+            "shim -1 ENOSYS",
+            "shim -1 ENOSYS",
+            "shim -1 ENOSYS",
+            "libc -1 ENOSYS",
+            "getpid True",
+            "close -1 EBADF",
+        ],
+        "{}",
+        stderr
+    );
+    assert_eq!(first_warnings(&stderr), 0, "{}", stderr);
+}
+
+#[test]
+fn test_warn_reports_setup_once() {
+    let (lines, stderr) = run("warn");
+    // NULL params is EFAULT, or ENOSYS where the kernel has io_uring off;
+    // either way the call reached the kernel
+    assert_eq!(lines.len(), 6, "{}", stderr);
+    assert_eq!(lines[4..], ["getpid True", "close -1 EBADF"]);
+    assert_eq!(first_warnings(&stderr), 1, "{}", stderr);
+    assert!(
+        stderr.contains("vrift: warning repeated 2 more times: io_uring in use"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,78 @@
+//! getxattr/setxattr/listxattr/removexattr on manifest entries: served from
+//! the xattrs the manifest keeps with the entry
+
+use vrift_integration::{ensure_success, require_python, Harness};
+use vrift_manifest::VnodeEntry;
+
+const FIXTURE: &str = "cargo_ws";
+
+/// xattr calls on argv[1] (a manifest entry recorded with user.origin),
+/// printing one result per line; errors print their errno name
+const PY_XATTR: &str = "
+import errno, os, sys
+path = sys.argv[1]
+def attempt(f, *args):
+    try:
+        r = f(*args)
+        print(r if r is not None else 'ok')
+    except OSError as e:
+        print(errno.errorcode[e.errno])
+attempt(os.getxattr, path, 'user.origin')
+attempt(os.setxattr, path, 'user.new', b'v1')
+attempt(os.setxattr, path, 'user.new', b'v2', os.XATTR_CREATE)
+attempt(os.setxattr, path, 'user.other', b'x', os.XATTR_REPLACE)
+attempt(os.setxattr, path, 'bogus', b'x')
+attempt(os.listxattr, path)
+fd = os.open(path, os.O_RDONLY)
+attempt(os.getxattr, fd, 'user.new')
+os.close(fd)
+attempt(os.removexattr, path, 'user.new')
+attempt(os.getxattr, path, 'user.new')
+attempt(os.removexattr, path, 'user.new')
+attempt(os.listxattr, path)
+";
+
+#[test]
+fn test_xattrs_served_from_manifest() {
+    require_python().unwrap();
+    let harness = Harness::start().unwrap();
+    let project = harness.fixture(FIXTURE).unwrap();
+
+    // The file gets opened, so its content has to be in the CAS
+    let hash = harness.store_blob(b"x").unwrap();
+
+    let vnode = VnodeEntry::new_file(hash, 1, 100, 0o100644);
+    let manifest = project
+        .manifest_with("/gen", &[("/gen/labeled.bin", vnode)])
+        .unwrap();
+    let origin = [("user.origin".to_string(), b"ingest".to_vec())];
+    assert!(manifest.set_xattrs("/gen/labeled.bin", &origin).unwrap());
+    manifest.commit().unwrap();
+
+    let out = project
+        .run_preloaded(["python3", "-c", PY_XATTR, "gen/labeled.bin"])
+        .unwrap();
+    ensure_success("python3 xattr", &out).unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "b'ingest'",
+            "ok",
+            "EEXIST",
+            "ENODATA",
+            "ENOTSUP",
+            "['user.new', 'user.origin']",
+            "b'v1'",
+            "ok",
+            "ENODATA",
+            "ENODATA",
+            "['user.origin']",
+        ],
+        "{stdout}"
+    );
+
+    // Nothing materialized: the attributes live in the manifest
+    assert!(!project.root().join("gen").exists());
+}
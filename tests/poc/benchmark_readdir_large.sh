@@ -0,0 +1,140 @@
+#!/bin/bash
+# Large-directory readdir benchmark
+#
+# Lists a directory of N entries (default 100000) that exists only in the
+# manifest, so the inception layer synthesizes every dirent from batches
+# paged in from vDird, and compares entries/sec against native readdir of
+# the same directory on disk.
+#
+# Usage: benchmark_readdir_large.sh [ENTRIES] [ROUNDS]
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "$0")" && pwd)"
+PROJECT_ROOT="$(cd "$SCRIPT_DIR/../.." && pwd)"
+ENTRIES="${1:-100000}"
+ROUNDS="${2:-5}"
+
+# Find binaries (release preferred)
+BUILD_DIR="${PROJECT_ROOT}/target/release"
+[ -x "$BUILD_DIR/vriftd" ] || BUILD_DIR="${PROJECT_ROOT}/target/debug"
+VRIFT_BIN="$BUILD_DIR/vrift"
+VRIFTD_BIN="$BUILD_DIR/vriftd"
+if [ "$(uname -s)" = "Darwin" ]; then
+    SHIM_LIB="$BUILD_DIR/libvrift_inception_layer.dylib"
+    PRELOAD=(DYLD_INSERT_LIBRARIES="$SHIM_LIB" DYLD_FORCE_FLAT_NAMESPACE=1)
+else
+    SHIM_LIB="$BUILD_DIR/libvrift_inception_layer.so"
+    PRELOAD=(LD_PRELOAD="$SHIM_LIB")
+fi
+for bin in "$VRIFT_BIN" "$VRIFTD_BIN" "$SHIM_LIB"; do
+    if [ ! -e "$bin" ]; then
+        echo "❌ ERROR: $bin not found. Run: cargo build --release"
+        exit 1
+    fi
+done
+
+echo "=== Large-Directory readdir Benchmark ($ENTRIES entries, $ROUNDS rounds) ==="
+echo "Binaries: $BUILD_DIR"
+
+WORK_DIR=$(mktemp -d)
+WORK_DIR="$(cd "$WORK_DIR" && pwd -P)"
+export HOME="$WORK_DIR/home"
+export VR_THE_SOURCE="$WORK_DIR/cas"
+export VRIFT_SOCKET_PATH="$WORK_DIR/vriftd.sock"
+export VRIFT_REGISTRY_DIR="$WORK_DIR/registry"
+mkdir -p "$HOME" "$VR_THE_SOURCE"
+
+PROJECT="$WORK_DIR/project"
+DAEMON_PID=""
+cleanup() {
+    [ -n "$DAEMON_PID" ] && kill "$DAEMON_PID" 2>/dev/null || true
+    # vDird outlives vriftd
+    pkill -f "vdir_d $PROJECT" 2>/dev/null || true
+    # CAS blobs are made immutable
+    chattr -R -i "$WORK_DIR" 2>/dev/null || true
+    chflags -R nouchg "$WORK_DIR" 2>/dev/null || true
+    chmod -R u+w "$WORK_DIR" 2>/dev/null || true
+    rm -rf "$WORK_DIR"
+}
+trap cleanup EXIT
+
+# 1. The directory, on disk for the native run and in the project to ingest
+echo "📦 Creating $ENTRIES files..."
+NATIVE_DIR="$WORK_DIR/native/big"
+mkdir -p "$NATIVE_DIR" "$PROJECT/big"
+(cd "$NATIVE_DIR" && seq -f "entry_%08g.rs" 1 "$ENTRIES" | xargs touch)
+(cd "$PROJECT/big" && seq -f "entry_%08g.rs" 1 "$ENTRIES" | xargs touch)
+
+cat > "$WORK_DIR/bench.c" << 'EOF'
+#include <dirent.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <time.h>
+
+static double now_s(void) {
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return ts.tv_sec + ts.tv_nsec / 1e9;
+}
+
+int main(int argc, char **argv) {
+    if (argc < 3) return 2;
+    int rounds = atoi(argv[2]);
+    long total = 0, per_round = -1;
+    double start = now_s();
+    for (int r = 0; r < rounds; r++) {
+        DIR *dir = opendir(argv[1]);
+        if (!dir) { perror("opendir"); return 1; }
+        long n = 0;
+        while (readdir(dir)) n++;
+        closedir(dir);
+        if (per_round >= 0 && n != per_round) {
+            fprintf(stderr, "round %d listed %ld entries, expected %ld\n", r, n, per_round);
+            return 1;
+        }
+        per_round = n;
+        total += n;
+    }
+    double secs = now_s() - start;
+    printf("%ld entries/round, %.0f entries/sec\n", per_round, total / secs);
+    return 0;
+}
+EOF
+cc -O2 -o "$WORK_DIR/bench" "$WORK_DIR/bench.c"
+codesign -f -s - "$WORK_DIR/bench" 2>/dev/null || true
+
+# 2. Daemon, then ingest into the manifest vDird serves for the project
+echo "🔧 Starting vriftd..."
+"$VRIFTD_BIN" start > "$WORK_DIR/vriftd.log" 2>&1 &
+DAEMON_PID=$!
+for _ in $(seq 1 50); do
+    [ -S "$VRIFT_SOCKET_PATH" ] && break
+    sleep 0.1
+done
+if [ ! -S "$VRIFT_SOCKET_PATH" ]; then
+    echo "❌ ERROR: vriftd failed to start"
+    cat "$WORK_DIR/vriftd.log"
+    exit 1
+fi
+
+echo "🚀 Ingesting..."
+(cd "$PROJECT" && "$VRIFT_BIN" init > /dev/null 2>&1)
+MANIFEST=$(ls -d "$HOME"/.vrift/db/*.lmdb)
+"$VRIFT_BIN" ingest "$PROJECT" --output "$MANIFEST" --prefix "" --mode phantom > /dev/null
+
+# Only the manifest knows the directory from here on (phantom ingest moved
+# the files into the CAS)
+rm -rf "$PROJECT/big"
+
+# 3. Measure
+echo ""
+echo "Native readdir:"
+"$WORK_DIR/bench" "$NATIVE_DIR" "$ROUNDS"
+
+echo "Synthetic readdir (inception layer):"
+# VRIFT_MANIFEST under the project is what tells the layer its project root
+(cd "$PROJECT" && env "${PRELOAD[@]}" \
+    VRIFT_MANIFEST="$PROJECT/.vrift/manifest.lmdb" \
+    VRIFT_VFS_PREFIX="$PROJECT" \
+    "$WORK_DIR/bench" big "$ROUNDS")
@@ -24,7 +24,7 @@ int main(int argc, char *argv[]) {
     if (fstat(fd, &sb) != 0) { perror("fstat"); close(fd); return 1; }
     close(fd);
     printf("dev=0x%llx size=%lld\n", (unsigned long long)sb.st_dev, (long long)sb.st_size);
-    // Note: fstat currently passthrough - checking if file exists and basic functionality
+    // fstat on a VFS fd reports the virtual identity (dev=0x52494654) like stat()
     printf("✅ PASS: fstat returned valid metadata\n");
     return 0;
 }
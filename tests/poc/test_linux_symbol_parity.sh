@@ -17,6 +17,7 @@ fi
 REQUIRED_SYMBOLS=(
     "openat"
     "faccessat"
+    "fstat"
     "fstatat"
     "opendir"
     "readdir"